@@ -192,13 +192,28 @@ fn generate_rpc_openrpc(out_dir: &Path) -> Result<(), Box<dyn Error>> {
             _ => None,
         })
         .expect("present");
+    let wallet_management_rpc_trait = methods_ast
+        .items
+        .iter()
+        .find_map(|item| match item {
+            syn::Item::Trait(item_trait) if item_trait.ident == "WalletManagementRpc" => {
+                Some(item_trait)
+            }
+            _ => None,
+        })
+        .expect("present");
 
     let mut contents = "#[allow(unused_qualifications)]
 pub(super) static METHODS: ::phf::Map<&str, RpcMethod> = ::phf::phf_map! {
 "
     .to_string();
 
-    for item in rpc_trait.items.iter().chain(&wallet_rpc_trait.items) {
+    for item in rpc_trait
+        .items
+        .iter()
+        .chain(&wallet_rpc_trait.items)
+        .chain(&wallet_management_rpc_trait.items)
+    {
         if let syn::TraitItem::Fn(method) = item {
             // Find methods via their `#[method(name = "command")]` attribute.
             let mut command = None;
@@ -303,6 +318,12 @@ pub(super) static METHODS: ::phf::Map<&str, RpcMethod> = ::phf::phf_map! {
                 }
                 contents.push_str("\",\n");
 
+                // Require every method to be categorized, so that `rpc.read_only` cannot
+                // silently allow a new method that changes the wallet.
+                contents.push_str("    category: super::");
+                contents.push_str(&module);
+                contents.push_str("::CATEGORY,\n");
+
                 contents.push_str("    params: |_g| vec![\n");
                 for (parameter, schema_ty, required) in params {
                     let param_upper = parameter.to_uppercase();
@@ -18,11 +18,15 @@
 -systemd = systemd
 
 -zallet-add-rpc-user = {-zallet} add-rpc-user
+-zallet-doctor-fix = {-zallet} doctor --fix
 
 -allow-warnings = --allow-warnings
+-dry-run = --dry-run
+-force = --force
 -allow-alpha-example = --this-is-alpha-code-and-you-will-need-to-recreate-the-example-later
 -allow-alpha-migration = --this-is-alpha-code-and-you-will-need-to-redo-the-migration-later
 -allow-multiple-wallet-imports = --allow-multiple-wallet-imports
+-wallet = --wallet
 -datadir = --datadir
 -db_dump = db_dump
 -zcashd_install_dir = --zcashd_install_dir
@@ -30,9 +34,30 @@
 -legacy_pool_seed_fingerprint = legacy_pool_seed_fingerprint
 -zallet_toml = zallet.toml
 
+-cfg-builder-trusted-confirmations = builder.trusted_confirmations
+-cfg-builder-untrusted-confirmations = builder.untrusted_confirmations
+-cfg-database-memo-index = database.memo_index
+-cfg-database-wallets = database.wallets
+-cfg-external-audit-log = external.audit_log
+-cfg-external-export-dir = external.export_dir
+-cfg-external-force-shutdown-timeout = external.force_shutdown_timeout
+-cfg-indexer-onion-only = indexer.onion_only
+-cfg-indexer-proxy = indexer.proxy
+-cfg-keystore-allow-unlock = keystore.allow_unlock
+-cfg-keystore-passphrase-file = keystore.passphrase_file
+-cfg-keystore-require-encryption = keystore.require_encryption
+-cfg-logging-file = logging.file
+-cfg-logging-filter = logging.filter
+-cfg-rpc-allow-ip = rpc.allow_ip
 -cfg-rpc-auth = rpc.auth
+-cfg-rpc-auth-method = rpc.auth_method
 -cfg-rpc-auth-password = rpc.auth.password
 -cfg-rpc-auth-pwhash = rpc.auth.pwhash
+-cfg-rpc-bind = rpc.bind
+-cfg-rpc-shutdown-timeout = rpc.shutdown_timeout
+-cfg-rpc-timeout = rpc.timeout
+-cfg-rpc-tls-cert-path = rpc.tls_cert_path
+-cfg-rpc-tls-key-path = rpc.tls_key_path
 
 ## Usage
 
@@ -46,10 +71,126 @@ cmd-add-rpc-user-prompt = Enter password:
 cmd-add-rpc-user-instructions = Add this to your {-zallet_toml} file:
 cmd-seed-fingerprint = Seed fingerprint: {$seedfp}
 cmd-import-mnemonic-prompt = Enter mnemonic:
+cmd-import-mnemonic-passphrase-prompt = Enter the wallet passphrase:
+cmd-change-wallet-encryption-current-prompt = Enter the current wallet passphrase:
+cmd-change-wallet-encryption-new-prompt = Enter the new wallet passphrase:
+cmd-change-wallet-encryption-repeat-prompt = Repeat the new wallet passphrase:
+cmd-change-wallet-encryption-changed = The wallet passphrase has been changed.
+cmd-change-wallet-encryption-added = The age identity file at {$path} is now encrypted with the passphrase.
+cmd-change-wallet-encryption-remove-warning =
+    WARNING: This decrypts the age identity file at {$path}, and leaves it unprotected
+    on disk. Anyone who can read that file will be able to decrypt the wallet's keys and
+    spend its funds, and the wallet will be permanently unlocked whenever {-zallet} runs.
+cmd-change-wallet-encryption-remove-confirm = Type '{$phrase}' to continue:
+cmd-change-wallet-encryption-removed = The age identity file at {$path} is no longer encrypted.
+cmd-start-passphrase-prompt = Enter the wallet passphrase (or press Enter to start locked):
+cmd-import-mnemonic-already-present =
+    This mnemonic is already in the wallet, with seed fingerprint {$seedfp}. Nothing
+    was imported.
+cmd-rpc-schema-written = OpenRPC document written to {$path}
+validate-config-valid = {$path} is valid ({$warnings} warnings).
+validate-config-invalid = {$path} is not valid ({$errors} errors, {$warnings} warnings).
+
+cmd-audit-not-enabled = The audit log is not enabled; set '{-cfg-external-audit-log}' or use --path.
+cmd-audit-intact = The audit log {$path} is intact.
+cmd-audit-entries = Entries: {$entries}
+cmd-audit-last-hash = Hash of the last entry: {$hash}
+cmd-audit-broken =
+    The audit log {$path} is broken at entry {$line}: {$reason}.
+    Entries before it are intact; it and any later entries cannot be trusted.
+cmd-audit-unreadable = Failed to read the audit log {$path}: {$error}
+
+check-balances-missing-trigger = Trigger {$trigger} is not installed
+check-balances-discrepancy = Account {$account} has {$expected_count} {$pool} outputs worth {$expected_value} zatoshis, but the aggregates record {$stored_count} outputs worth {$stored_value} zatoshis
+
+cmd-doctor-checking-balances = Checking balance aggregates...
+cmd-doctor-rebuilt-balances = Rebuilt the balance aggregates.
+cmd-doctor-rebuilding-memo-index = Rebuilding the memo index...
+cmd-doctor-indexed-memos = Indexed the memos of {$notes} note(s).
+cmd-doctor-memo-index-disabled =
+    The memo index is disabled ('{-cfg-database-memo-index}' is false); not rebuilding it.
+cmd-doctor-no-problems = No problems found.
+cmd-doctor-repaired = Repaired {$fixed} problem(s).
+err-doctor-problems = Found {$problems} problem(s); run '{-zallet-doctor-fix}' to repair them
+
+cmd-rebuild-moved-aside = Moved the damaged database to {$path}
+cmd-rebuild-unopenable = Could not open the damaged database: {$error}
+cmd-rebuild-creating = Creating a new wallet database...
+cmd-rebuild-restoring-keystore = Restoring the keystore...
+cmd-rebuild-restored-table = Restored {$rows} row(s) of {$table}
+cmd-rebuild-table-failed = Could not restore {$table}: {$error}
+cmd-rebuild-recreating-accounts = Recreating accounts...
+cmd-rebuild-account-failed = Could not recreate account {$account}: {$error}
+cmd-rebuild-account-unreadable = Could not read an account: {$error}
+cmd-rebuild-accounts-unreadable = Could not read the accounts of the damaged database: {$error}
+cmd-rebuild-unnamed-account = (unnamed)
+cmd-rebuild-recreated-account = Recreated account {$account} with birthday height {$height}
+cmd-rebuild-recreated-seed-account = Recreated ZIP 32 account 0 of seed {$seedfp}
+cmd-rebuild-recover-other-accounts =
+    Recreate any other accounts of these seeds with the z_recoveraccounts RPC method.
+cmd-rebuild-recreated = Recreated {$accounts} account(s).
+cmd-rebuild-not-restored =
+    The following cannot be recovered from the wallet's keys, and are not restored:
+    - Address book labels
+    - The history and results of async operations
+    - Transactions that were created but not broadcast
+    - The fees of transactions that the wallet did not fund
+    - Sprout notes found when the wallet was migrated from {-zcashd}
+    - Addresses and standalone keys imported into accounts, which must be imported again
+    They may still be readable from the damaged database at {$path}.
+cmd-rebuild-rescan =
+    Start {-zallet} to rescan the chain from each account's birthday, which restores the
+    wallet's notes, UTXOs, and transaction history.
+cmd-rebuild-passphrase-prompt = Enter the wallet passphrase:
+cmd-rebuild-birthday-prompt =
+    Enter the birthday height of seed {$seedfp} (or leave empty to scan from Sapling activation):
+cmd-rebuild-not-a-height = '{$input}' is not a block height.
+err-rebuild-no-database = No wallet database found at {$path}
+err-rebuild-problems = Rebuilt the wallet, but {$problems} problem(s) occurred; see above
+
+cmd-status-running = {-zallet} is running (PID {$pid}).
+cmd-status-no-pid-file = The datadir is in use, but there is no PID file at {$path}.
+cmd-status-stale = {-zallet} is not running, but its PID file {$path} records process {$pid}.
+cmd-status-stopped = {-zallet} is not running.
+
+cmd-stop-waiting = Asked {-zallet} (PID {$pid}) to shut down; waiting for it to stop...
+cmd-stop-stopped = {-zallet} has stopped.
+err-stop-no-pid-file = The datadir is in use, but there is no PID file at {$path}
+err-stop-timeout = {-zallet} did not stop within {$timeout} seconds
+
+cmd-wallet-check-passed = passed
+cmd-wallet-check-repaired = repaired
+cmd-wallet-check-failed = FAILED
+cmd-wallet-check-backup = The database was backed up to {$path} before repairing it.
+err-wallet-check-failed = {$failed} check(s) failed
+err-wallet-check-unrepaired = {$failed} check(s) failed and could not be repaired
 
 ## Startup messages
 
 warn-config-unused = Config option '{$option}' is not yet implemented in {-zallet}; ignoring its value.
+warn-config-rpc-public-no-auth =
+    '{-cfg-rpc-bind}' contains {$addr}, which is not a loopback address, and
+    '{-cfg-rpc-auth-method}' is 'none'. Anyone who can connect to it can spend your
+    funds.
+warn-config-rpc-public-no-tls =
+    '{-cfg-rpc-bind}' contains {$addr}, which is not a loopback address, but TLS is
+    not enabled. Credentials are sent unencrypted, so anyone who can observe traffic
+    to this address can use them to spend your funds.
+warn-config-rpc-public-no-allow-ip =
+    '{-cfg-rpc-bind}' contains {$addr}, which is not a loopback address, but
+    '{-cfg-rpc-allow-ip}' is empty, so only clients on this machine can connect.
+warn-config-force-shutdown-timeout-short =
+    '{-cfg-external-force-shutdown-timeout}' is not longer than '{-cfg-rpc-shutdown-timeout}',
+    so {-zallet} may exit before in-flight JSON-RPC requests and async operations finish.
+warn-sprout-funds =
+    This wallet holds {$count} unspent Sprout notes worth {$value} zatoshis, which
+    were migrated from {-zcashd}. {-zallet} cannot spend Sprout funds; to recover
+    them, migrate them to a Sapling address using {-zcashd} before decommissioning it.
+datadir-lock-waiting =
+    Waiting for process {$pid} to release the lock on data directory {$datadir}...
+datadir-lock-stale =
+    Process {$pid} did not release the lock on data directory {$datadir} cleanly; it may
+    have crashed.
 
 rpc-bare-password-auth-info = Using '{-cfg-rpc-auth-password}' authorization
 rpc-bare-password-auth-warn =
@@ -58,6 +199,14 @@ rpc-bare-password-auth-warn =
     cookie-based auth, or otherwise to use '{-cfg-rpc-auth-pwhash}' credentials generated with
     '{-zallet-add-rpc-user}'.
 rpc-pwhash-auth-info = Using '{-cfg-rpc-auth-pwhash}' authorization
+rpc-non-loopback-auth-warn =
+    The JSON-RPC server is listening on {$addr}, which is not a loopback address.
+    Credentials are sent unencrypted over HTTP, so anyone who can observe traffic to
+    this address can use them to spend your funds. Consider binding to a loopback
+    address, and forwarding the port over an encrypted connection.
+rpc-no-auth-warn =
+    '{-cfg-rpc-auth-method}' is 'none', so JSON-RPC requests are not authorized.
+    Anyone who can connect to the JSON-RPC port can spend your funds.
 
 ## zallet.toml example messages
 
@@ -86,6 +235,14 @@ migrate-warn-rpcport =
 migrate-warn-sprout-migration =
     {-zallet} does not support Sprout, so the Sprout-to-Sapling migration option
     '{$option}' will not be migrated over.
+migrate-warn-sprout-funds =
+    The {-zcashd} wallet holds {$count} unspent Sprout notes worth {$value} zatoshis.
+    {-zallet} cannot spend Sprout funds; to recover them, migrate them to a Sapling
+    address using {-zcashd} before decommissioning it.
+migrate-warn-txexpirydelta =
+    {-zallet} does not yet support custom expiry deltas, so '{$option}' is not being
+    migrated. Transactions created by {-zallet} expire {$default} blocks after
+    creation.
 migrate-warn-cli-only =
     {-zcashd} supported configuring '{$option}' via both a CLI flag and a config
     file entry. {-zallet} does not support it as a config file entry; you will
@@ -103,11 +260,59 @@ migrate-alpha-code =
     this, use '{-allow-alpha-migration}'.
 
 migrate-config-written = {-zallet} config written to {$conf}
-
+migrate-dry-run-unchanged = {-zallet} config at {$conf} would not be changed.
+
+migrate-wallet-address-book = Imported {$count} address book labels.
+migrate-wallet-address-book-skipped =
+    Skipped the label '{$label}' for '{$address}', which is not an address on this
+    network that {-zallet} supports.
+migrate-wallet-address-book-conflict =
+    Kept the existing label '{$existing_label}' for '{$address}' instead of the
+    {-zcashd} label '{$label}'.
 migrate-wallet-legacy-seed-fp =
     Importing zcashd legacy account for seed fingerprint '{$seed_fp}'. If you wish to
     enable legacy zcashd semantics for wallet RPC methods, you should set
     '{-legacy_pool_seed_fingerprint}' to this value in '{-zallet_toml}'.",
+migrate-wallet-report = Migrating the following from the {-zcashd} wallet:
+migrate-wallet-report-dry-run =
+    This is a dry run, and the {-zallet} wallet will not be changed. The following
+    would be migrated from the {-zcashd} wallet:
+migrate-wallet-report-mnemonic = The mnemonic seed phrase.
+migrate-wallet-report-legacy-seed =
+    The legacy HD seed used by {-zcashd} before v4.7.0.
+migrate-wallet-report-unified-accounts =
+    {$count} unified accounts, derived from the mnemonic seed phrase.
+migrate-wallet-report-sapling-keys =
+    {$count} Sapling spending keys ({$mnemonic} derived from the mnemonic seed phrase,
+    {$legacy} from the legacy HD seed, and {$standalone} imported with z_importkey).
+migrate-wallet-report-transparent-keys =
+    {$count} standalone transparent keys.
+migrate-wallet-report-address-book = {$count} address book labels.
+migrate-wallet-report-transactions = {$count} transactions in the main chain.
+migrate-wallet-report-birthday =
+    The wallet birthday is height {$height}, based on the earliest of the wallet's
+    transactions.
+migrate-wallet-report-birthday-no-txs =
+    The wallet birthday is height {$height} (Sapling activation), because none of
+    the wallet's transactions are in the main chain.
+migrate-wallet-report-birthday-txs-not-fetched =
+    The wallet birthday is height {$height} (Sapling activation), because some of
+    the wallet's transactions could not be fetched.
+migrate-wallet-report-skipped = Not migrated:
+migrate-wallet-report-skipped-sprout-keys =
+    {$count} Sprout spending keys, which {-zallet} does not support. They are only
+    used to find the wallet's Sprout notes.
+migrate-wallet-report-skipped-unlabelled =
+    {$count} address book entries without a label.
+migrate-wallet-report-skipped-txs-not-found =
+    {$count} transactions that are not in the main chain.
+migrate-wallet-report-skipped-txs-not-fetched =
+    {$count} transactions that could not be fetched from the chain source. Any that
+    are in the main chain will be found when the wallet is scanned.
+migrate-wallet-report-skipped-unrecognized =
+    {$count} records of the wallet file that {-zallet} does not recognize. These
+    include viewing keys imported with z_importviewingkey, which you can import
+    into {-zallet} with the z_importviewingkey RPC method.
 
 ## General errors
 
@@ -118,10 +323,17 @@ err-kind-sync = Failed to synchronize {-zallet}
 err-init-cannot-find-home-dir =
     Cannot find home directory for the default datadir. Use '{-datadir}' to set
     the datadir directly.
+err-init-datadir-tilde =
+    The datadir {$path} starts with '~', which {-zallet} does not expand. Use an
+    absolute path, such as one starting with $HOME.
+err-init-datadir-relative = The datadir {$path} must be an absolute path.
 err-init-failed-to-create-lockfile = Failed to create a lockfile at {$path}: {$error}
 err-init-failed-to-read-lockfile = Failed to read lockfile at {$path}: {$error}
 err-init-zallet-already-running =
     Cannot obtain a lock on data directory {$datadir}. {-zallet} is probably already running.
+err-init-zallet-already-running-pid =
+    Cannot obtain a lock on data directory {$datadir}, because it is in use by process {$pid}.
+    Wait for that process to finish, or use --wait to wait for it.
 
 err-init-config-db-mismatch =
     The wallet database was created for network type {$db_network_type}, but the
@@ -131,7 +343,70 @@ err-init-identity-not-found = Encryption identity file could not be located at {
 err-init-identity-not-passphrase-encrypted = {$path} is not encrypted with a passphrase
 err-init-path-not-utf8 = {$path} is not currently supported (not UTF-8)
 err-init-identity-not-usable = Identity file at {$path} is not usable: {$error}
+err-init-metrics-bind = Failed to open the metrics endpoint at {$addr}: {$error}
+err-init-metrics-recorder = Failed to set up metrics: {$error}
 err-init-rpc-auth-invalid = Invalid '{-cfg-rpc-auth}' configuration
+err-init-rpc-auth-none-with-users =
+    '{-cfg-rpc-auth-method}' is 'none', but users are configured in '{-cfg-rpc-auth}'.
+err-init-indexer-proxy-unreachable =
+    Failed to use the SOCKS5 proxy at {$proxy} ('{-cfg-indexer-proxy}'), so no
+    connection was made to the full node: {$error}
+err-init-rpc-cookie-write = Failed to write the JSON-RPC cookie file {$path}: {$error}
+err-init-rpc-tls-incomplete =
+    '{-cfg-rpc-tls-cert-path}' and '{-cfg-rpc-tls-key-path}' must both be set to enable TLS.
+err-init-rpc-tls-invalid = Invalid JSON-RPC TLS configuration: {$error}
+err-init-log-file-open = Failed to open the log file {$path} ('{-cfg-logging-file}'): {$error}
+err-config-trusted-confirmations-zero =
+    '{-cfg-builder-trusted-confirmations}' is set to 0, but must be at least 1, as a
+    shielded note cannot be spent until it is mined.
+err-config-untrusted-confirmations-too-small =
+    '{-cfg-builder-untrusted-confirmations}' is set to {$untrusted}, but must be at least
+    '{-cfg-builder-trusted-confirmations}' ({$trusted}).
+err-config-file-exists = {$path} already exists. Use '{-force}' to overwrite it.
+err-config-export-dir-missing =
+    '{-cfg-external-export-dir}' is set to {$path}, which is not an existing directory.
+err-config-export-dir-unwritable =
+    '{-cfg-external-export-dir}' is set to {$path}, which cannot be written to: {$error}
+err-config-path-tilde =
+    '{$option}' is set to {$path}, but {-zallet} does not expand '~'. Use an absolute
+    path, or a path relative to the data directory.
+err-config-rpc-bind-duplicate = '{-cfg-rpc-bind}' contains {$addr} more than once.
+err-config-rpc-timeout-zero = '{-cfg-rpc-timeout}' must be greater than zero.
+err-config-indexer-proxy-invalid =
+    '{-cfg-indexer-proxy}' is set to "{$proxy}", which is not a host and port.
+err-config-indexer-onion-only-without-proxy =
+    '{-cfg-indexer-onion-only}' is enabled, but '{-cfg-indexer-proxy}' is not set.
+err-config-indexer-onion-without-proxy =
+    '{$option}' contains the onion address {$addr}, which can only be reached if
+    '{-cfg-indexer-proxy}' is set to a Tor SOCKS5 proxy.
+err-config-indexer-not-onion =
+    '{$option}' contains {$addr}, which is not an onion address, but
+    '{-cfg-indexer-onion-only}' is enabled.
+err-config-logging-filter-invalid =
+    '{-cfg-logging-filter}' is set to "{$filter}", which is not a valid filter: {$error}
+err-validate-config-unusable = Failed to load the config file {$path}: {$error}
+err-config-wallet-name-invalid =
+    '{-cfg-database-wallets}' contains a wallet named "{$name}". Wallet names must be
+    non-empty, may only contain ASCII letters, digits, '-', '_', and '.', and must not
+    start with '.'.
+err-config-wallet-name-duplicate =
+    '{-cfg-database-wallets}' contains more than one wallet named "{$name}".
+err-config-wallet-not-found =
+    No wallet named "{$name}" is configured in '{-cfg-database-wallets}', or has been
+    created with 'createwallet'.
+err-init-wallet-not-selected =
+    Several wallets are configured in '{-cfg-database-wallets}'. Select one with
+    '{-wallet} <NAME>'.
+err-init-wallet-db-not-found = No wallet database exists at {$path}.
+err-init-encryption-required-uninitialized =
+    '{-cfg-keystore-require-encryption}' is enabled, but wallet encryption has not
+    been initialized. Run '{$init_cmd}' first.
+err-init-encryption-required-plaintext-identity =
+    '{-cfg-keystore-require-encryption}' is enabled, but the identity file at {$path}
+    is not encrypted with a passphrase.
+err-init-unlock-disabled-plaintext-identity =
+    '{-cfg-keystore-allow-unlock}' is disabled, but the identity file at {$path}
+    is not encrypted with a passphrase, so the wallet cannot be locked.
 
 ## Keystore errors
 
@@ -139,6 +414,23 @@ err-keystore-missing-recipients = The wallet has not been set up to store key ma
 rec-keystore-missing-recipients = Have you run '{$init_cmd}'?
 err-keystore-already-initialized = Keystore age recipients already initialized
 err-wallet-locked = Wallet is locked
+err-keystore-wrong-passphrase = The wallet passphrase is incorrect
+err-keystore-not-passphrase-encrypted = The age identity file at {$path} is not encrypted with a passphrase
+err-keystore-already-passphrase-encrypted = The age identity file at {$path} is already encrypted with a passphrase
+err-keystore-passphrases-differ = The passphrases do not match
+err-keystore-empty-passphrase = The passphrase cannot be empty
+err-keystore-remove-encryption-required =
+    Cannot remove the passphrase from the age identity file, because {$option} requires
+    it to be encrypted.
+err-keystore-remove-encryption-not-confirmed = The passphrase was not removed.
+err-keystore-invalid-mnemonic = The mnemonic is not a valid BIP 39 phrase in {$language}: {$error}
+err-keystore-passphrase-file-unreadable = Cannot read the wallet passphrase from {$path}: {$error}
+err-keystore-passphrase-file-world-readable =
+    The wallet passphrase file {$path} is readable by other users. Restrict its
+    permissions with 'chmod 600 {$path}', or change '{-cfg-keystore-passphrase-file}'.
+err-keystore-passphrase-file-too-long =
+    The wallet passphrase file {$path} is longer than {$limit} bytes.
+err-keystore-passphrase-env-unreadable = The {$var} environment variable does not contain valid UTF-8
 
 ## Account errors
 
@@ -148,6 +440,10 @@ err-account-no-payment-source = Account has no payment source.
 # errors in migration of configuration data from the zcashd `zcash.conf` config file format
 
 err-migrate-allow-warnings = To allow a migration with warnings, use '{-allow-warnings}'
+err-migrate-dry-run-force = '{-dry-run}' and '{-force}' cannot be used together
+err-migrate-zcashd-datadir-tilde =
+    The {-zcashd} datadir {$path} starts with '~', which {-zallet} does not expand. Use
+    an absolute path, such as one starting with $HOME.
 err-migrate-duplicate-zcashd-option =
     {-zcashd} option '{$option}' does not support multiple values,
     but appears multiple times in {$conf}
@@ -159,6 +455,9 @@ err-migrate-multiple-related-zcashd-options =
     {-zcashd} option '{$option}' collides with '{$prev}', but both appear in
     {$conf}
     Remove one of the conflicting options, then re-run this command.
+err-migrate-rpc-credentials-unpaired =
+    {-zcashd} options 'rpcuser' and 'rpcpassword' must be set together, but only
+    one of them appears in {$conf}
 err-migrate-unknown-zcashd-option = Unknown {-zcashd} option '{$option}'
 
 # errors in migration of wallet data from the zcashd `wallet.dat` database format
@@ -277,6 +576,10 @@ err-rpc-cli-conn-failed = Failed to connect to the Zallet wallet's JSON-RPC port
 err-rpc-cli-invalid-param = Invalid parameter '{$parameter}'
 err-rpc-cli-no-server = No JSON-RPC port is available.
 err-rpc-cli-request-failed = JSON-RPC request failed: {$error}
+err-rpc-cli-tls-unsupported =
+    The JSON-RPC port uses TLS, which '{-zallet} rpc' does not support yet.
+err-rpc-cli-unix-socket-unsupported =
+    The JSON-RPC server only listens on Unix domain sockets, which '{-zallet} rpc' does not support yet.
 
 ## zallet manpage
 
@@ -1,18 +1,25 @@
 //! Zallet Abscissa Application
 
+use std::process;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 
 use abscissa_core::{
     Application, Component, FrameworkError, StandardPaths,
     application::{self, AppCell},
-    config::{self, CfgCell},
+    config::{self, CfgCell, Configurable},
     terminal::component::Terminal,
 };
 use abscissa_tokio::TokioComponent;
 use i18n_embed::unic_langid::LanguageIdentifier;
 
-use crate::{cli::EntryPoint, components::tracing::Tracing, config::ZalletConfig, i18n};
+use crate::{
+    cli::EntryPoint,
+    components::tracing::{self, Tracing},
+    config::ZalletConfig,
+    error::ExitCode,
+    i18n,
+};
 
 /// Application state
 pub static APP: AppCell<ZalletApp> = AppCell::new();
@@ -56,6 +63,25 @@ impl Application for ZalletApp {
         &self.state
     }
 
+    // Overridden so that config errors exit with a distinct exit code.
+    fn init(&mut self, command: &Self::Cmd) -> Result<(), FrameworkError> {
+        self.register_components(command)?;
+
+        let config = command
+            .config_path()
+            .map(|path| self.load_config(&path))
+            .transpose()
+            .and_then(|config| command.process_config(config.unwrap_or_default()));
+
+        match config {
+            Ok(config) => self.after_config(config),
+            Err(e) => {
+                eprintln!("{} fatal error: {e}", self.name());
+                process::exit(ExitCode::Config.code())
+            }
+        }
+    }
+
     // Overridden to leave out framework components.
     fn framework_components(
         &mut self,
@@ -91,6 +117,8 @@ impl Application for ZalletApp {
     }
 
     fn after_config(&mut self, config: Self::Cfg) -> Result<(), FrameworkError> {
+        tracing::configure(&config)?;
+
         // Configure components
         let mut components = self.state.components_mut();
         components.after_config(&config)?;
@@ -105,6 +133,11 @@ pub fn boot(requested_languages: Vec<LanguageIdentifier>) {
     // We load languages here so that the app's CLI usage text can be localized.
     i18n::load_languages(&requested_languages);
 
+    // The wallet passphrase is taken out of the environment before Abscissa starts the
+    // Tokio runtime's threads.
+    #[cfg(zallet_build = "wallet")]
+    crate::components::keystore::take_env_passphrase();
+
     // Now do the normal Abscissa boot.
     abscissa_core::boot(&APP);
 }
@@ -3,13 +3,70 @@ use std::path::PathBuf;
 use clap::{Parser, builder::Styles};
 
 #[cfg(zallet_build = "wallet")]
-use uuid::Uuid;
+use {clap::ValueEnum, uuid::Uuid};
 
 #[cfg(outside_buildscript)]
 use abscissa_core::{Command, Runnable};
 
 use crate::fl;
 
+/// The exit codes of `zallet start`, which are shown in its `--help` output.
+///
+/// These must match `crate::error::ExitCode`.
+pub(crate) const START_EXIT_CODES: &str = "\
+Exit codes:
+  0  Zallet shut down cleanly.
+  1  Zallet failed for a reason that does not have a more specific exit code.
+  2  The command-line arguments were invalid.
+  3  The config file could not be read or parsed, or contains invalid settings.
+  4  A wallet database could not be opened.
+  5  A wallet's keystore could not be opened, or does not satisfy the config.
+  6  The JSON-RPC server could not listen on `rpc.bind`.
+  7  The chain indexer could not connect to its backend.
+  8  The wallet passphrase supplied at startup was incorrect.";
+
+/// The exit codes of `zallet change-wallet-encryption`, which are shown in its `--help`
+/// output.
+///
+/// These must match `crate::error::ExitCode`.
+#[cfg(zallet_build = "wallet")]
+pub(crate) const CHANGE_WALLET_ENCRYPTION_EXIT_CODES: &str = "\
+Exit codes:
+  0  The passphrase was changed, added, or removed.
+  1  The identity file could not be read or written, or another failure occurred.
+  2  The command-line arguments were invalid.
+  8  The current wallet passphrase is incorrect.";
+
+/// The exit codes of `zallet validate-config`, which are shown in its `--help` output.
+///
+/// These must match `crate::error::ExitCode`.
+pub(crate) const VALIDATE_CONFIG_EXIT_CODES: &str = "\
+Exit codes:
+  0  The config file is valid. There may be warnings, unless `--strict` is set.
+  2  The command-line arguments were invalid.
+  3  The config file could not be read or parsed, or contains invalid settings (or,
+     with `--strict`, risky settings).";
+
+/// The exit codes of `zallet wallet-check`, which are shown in its `--help` output.
+///
+/// These must match `crate::error::ExitCode`.
+pub(crate) const WALLET_CHECK_EXIT_CODES: &str = "\
+Exit codes:
+  0  Every check passed (or, with `--repair`, its problems were repaired).
+  1  A check failed, or the checks could not be run.
+  2  The command-line arguments were invalid.
+  4  The wallet database could not be opened.";
+
+/// The exit codes of `zallet status`, which are shown in its `--help` output.
+///
+/// These follow the LSB conventions for the `status` action of init scripts.
+pub(crate) const STATUS_EXIT_CODES: &str = "\
+Exit codes:
+  0  Zallet is running.
+  1  Zallet is not running, but its PID file exists.
+  3  Zallet is not running.
+  4  The status of Zallet could not be determined.";
+
 #[derive(Debug, Parser)]
 #[cfg_attr(outside_buildscript, derive(Command))]
 #[command(author, about, version)]
@@ -27,14 +84,16 @@ pub struct EntryPoint {
     #[command(subcommand)]
     pub(crate) cmd: ZalletCmd,
 
-    /// Enable verbose logging
+    /// Enable verbose logging.
+    ///
+    /// This logs debug events, overriding the `logging.filter` config option.
     #[arg(short, long)]
     pub(crate) verbose: bool,
 
     /// Specify the data directory for the Zallet wallet.
     ///
-    /// This must be an absolute path.
-    #[arg(short, long)]
+    /// This must be an absolute path. Defaults to `~/.zallet`.
+    #[arg(short, long, env = "ZALLET_DATADIR")]
     pub(crate) datadir: Option<PathBuf>,
 
     /// Use the specified configuration file.
@@ -42,17 +101,55 @@ pub struct EntryPoint {
     /// Relative paths will be prefixed by the datadir.
     #[arg(short, long)]
     pub(crate) config: Option<PathBuf>,
+
+    /// Use the specified Zcash network, overriding `consensus.network` in the config
+    /// file.
+    ///
+    /// A wallet database can only be opened on the network it was created for.
+    #[arg(long, value_parser = ["main", "test", "regtest"])]
+    pub(crate) network: Option<String>,
+
+    /// Operate on the named wallet from `database.wallets`.
+    ///
+    /// This is required by commands that access a wallet when several wallets are
+    /// configured. It is ignored by `zallet start`, which loads every wallet.
+    #[arg(long)]
+    pub(crate) wallet: Option<String>,
+
+    /// If another Zallet process is using the data directory, wait for it to finish
+    /// instead of failing.
+    #[arg(long)]
+    pub(crate) wait: bool,
 }
 
 #[derive(Debug, Parser)]
 #[cfg_attr(outside_buildscript, derive(Command, Runnable))]
 pub(crate) enum ZalletCmd {
     /// The `start` subcommand
+    #[command(after_long_help = START_EXIT_CODES)]
     Start(StartCmd),
 
+    /// Stop a running Zallet process.
+    Stop(StopCmd),
+
+    /// Check whether Zallet is running.
+    #[command(after_long_help = STATUS_EXIT_CODES)]
+    Status(StatusCmd),
+
+    /// List the accounts in the wallet.
+    #[cfg(zallet_build = "wallet")]
+    ListAccounts(ListAccountsCmd),
+
+    /// Write a `zallet.toml` config with every option commented out.
+    InitConfig(InitConfigCmd),
+
     /// Generate an example `zallet.toml` config.
     ExampleConfig(ExampleConfigCmd),
 
+    /// Check a `zallet.toml` config without starting Zallet.
+    #[command(after_long_help = VALIDATE_CONFIG_EXIT_CODES)]
+    ValidateConfig(ValidateConfigCmd),
+
     /// Generate a `zallet.toml` config from an existing `zcash.conf` file.
     #[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
     MigrateZcashConf(MigrateZcashConfCmd),
@@ -65,6 +162,11 @@ pub(crate) enum ZalletCmd {
     #[cfg(zallet_build = "wallet")]
     InitWalletEncryption(InitWalletEncryptionCmd),
 
+    /// Change, add, or remove the passphrase of the wallet's age identity file.
+    #[cfg(zallet_build = "wallet")]
+    #[command(after_long_help = CHANGE_WALLET_ENCRYPTION_EXIT_CODES)]
+    ChangeWalletEncryption(ChangeWalletEncryptionCmd),
+
     /// Generate a BIP 39 mnemonic phrase and store it in the wallet.
     #[cfg(zallet_build = "wallet")]
     GenerateMnemonic(GenerateMnemonicCmd),
@@ -84,6 +186,25 @@ pub(crate) enum ZalletCmd {
     #[cfg(feature = "rpc-cli")]
     Rpc(RpcCliCmd),
 
+    /// Write an OpenRPC document describing the JSON-RPC interface.
+    #[cfg(zallet_build = "wallet")]
+    RpcSchema(RpcSchemaCmd),
+
+    /// Check the wallet database for inconsistencies.
+    Doctor(DoctorCmd),
+
+    /// Check the integrity and consistency of the wallet database.
+    #[command(after_long_help = WALLET_CHECK_EXIT_CODES)]
+    WalletCheck(WalletCheckCmd),
+
+    /// Rebuild the wallet database from the wallet's keys.
+    #[cfg(zallet_build = "wallet")]
+    Rebuild(RebuildCmd),
+
+    /// Commands for working with the audit log.
+    #[command(subcommand)]
+    Audit(AuditCmd),
+
     /// Commands for repairing broken wallet states.
     #[command(subcommand)]
     Repair(RepairCmd),
@@ -96,7 +217,72 @@ pub(crate) enum ZalletCmd {
 /// `start` subcommand
 #[derive(Debug, Parser)]
 #[cfg_attr(outside_buildscript, derive(Command))]
-pub(crate) struct StartCmd {}
+pub(crate) struct StartCmd {
+    /// Refuse to start unless the wallet's key material is passphrase-encrypted.
+    ///
+    /// This overrides `keystore.require_encryption` in the config file.
+    #[cfg(zallet_build = "wallet")]
+    #[arg(long)]
+    pub(crate) require_encryption: bool,
+
+    /// Read the passphrase that unlocks encrypted wallets from this file.
+    ///
+    /// This can be a named pipe, or a file descriptor such as `/dev/fd/3`. It overrides
+    /// `keystore.passphrase_file` in the config file.
+    #[cfg(zallet_build = "wallet")]
+    #[arg(long, value_name = "PATH")]
+    pub(crate) passphrase_file: Option<PathBuf>,
+
+    /// Rescan each wallet's blocks from this height once it is loaded.
+    ///
+    /// Heights below a wallet's birthday are not rescanned.
+    #[arg(long, value_name = "HEIGHT")]
+    pub(crate) rescan_from: Option<u32>,
+
+    /// Use the full node / validator at this address as the chain source.
+    ///
+    /// This can be given more than once; the first address is used, and the others are
+    /// failed over to in order. It overrides `indexer.validator_address` and
+    /// `indexer.fallback_validator_addresses` in the config file.
+    #[arg(long = "validator-address", value_name = "ADDRESS")]
+    pub(crate) validator_addresses: Vec<String>,
+}
+
+/// `stop` subcommand
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command))]
+pub(crate) struct StopCmd {
+    /// How long to wait (in seconds) for Zallet to shut down.
+    #[arg(long, default_value_t = 60)]
+    pub(crate) timeout: u64,
+}
+
+/// `status` subcommand
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command))]
+pub(crate) struct StatusCmd {}
+
+/// `list-accounts` subcommand
+#[cfg(zallet_build = "wallet")]
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command))]
+pub(crate) struct ListAccountsCmd {}
+
+/// `init-config` subcommand
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command))]
+pub(crate) struct InitConfigCmd {
+    /// Where to write the Zallet config file.
+    ///
+    /// - By default, the config file that Zallet loads is written (see `--config`).
+    /// - The value `-` will write the config to stdout.
+    #[arg(short, long)]
+    pub(crate) output: Option<String>,
+
+    /// Force an existing Zallet config file to be overwritten.
+    #[arg(short, long)]
+    pub(crate) force: bool,
+}
 
 /// `example-config` subcommand
 #[derive(Debug, Parser)]
@@ -104,7 +290,7 @@ pub(crate) struct StartCmd {}
 pub(crate) struct ExampleConfigCmd {
     /// Where to write the Zallet config file.
     ///
-    /// - By default, the default Zallet config file path is used.
+    /// - By default, the config file that Zallet loads is written (see `--config`).
     /// - The value `-` will write the config to stdout.
     #[arg(short, long)]
     pub(crate) output: Option<String>,
@@ -118,6 +304,19 @@ pub(crate) struct ExampleConfigCmd {
     pub(crate) this_is_alpha_code_and_you_will_need_to_recreate_the_example_later: bool,
 }
 
+/// `validate-config` subcommand
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command))]
+pub(crate) struct ValidateConfigCmd {
+    /// Print the result as JSON.
+    #[arg(long)]
+    pub(crate) json: bool,
+
+    /// Treat warnings as errors.
+    #[arg(long)]
+    pub(crate) strict: bool,
+}
+
 /// `migrate-zcash-conf` subcommand
 #[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
 #[derive(Debug, Parser)]
@@ -134,6 +333,8 @@ pub(crate) struct MigrateZcashConfCmd {
     pub(crate) zcashd_datadir: Option<PathBuf>,
 
     /// Allow a migration when warnings are present.
+    ///
+    /// Warnings are raised for the options listed as `unsupported` in the report.
     #[arg(long)]
     pub(crate) allow_warnings: bool,
 
@@ -148,6 +349,21 @@ pub(crate) struct MigrateZcashConfCmd {
     #[arg(short, long)]
     pub(crate) force: bool,
 
+    /// Perform the migration without writing the Zallet config file.
+    ///
+    /// Prints a unified diff between the existing Zallet config file (if any) and the
+    /// migrated config, and exits with code 2 if they differ. With `--output -`, the
+    /// migrated config is printed instead. Cannot be combined with `--force`.
+    #[arg(long)]
+    pub(crate) dry_run: bool,
+
+    /// Write a JSON report of how each `zcashd` option was handled to this path.
+    ///
+    /// Each option is listed as `migrated` (with the Zallet config option it was
+    /// migrated to), `ignored` (with the reason), or `unsupported`.
+    #[arg(long)]
+    pub(crate) report: Option<PathBuf>,
+
     /// Temporary flag ensuring any alpha users are aware the migration is not stable.
     #[arg(long)]
     pub(crate) this_is_alpha_code_and_you_will_need_to_redo_the_migration_later: bool,
@@ -196,6 +412,13 @@ pub(crate) struct MigrateZcashdWalletCmd {
     #[arg(long)]
     pub(crate) allow_warnings: bool,
 
+    /// Report what would be migrated without changing the Zallet wallet.
+    ///
+    /// The chain source is still queried for the wallet's transactions, in order to
+    /// report them and the wallet birthday.
+    #[arg(long)]
+    pub(crate) dry_run: bool,
+
     /// Temporary flag ensuring any alpha users are aware the migration is not stable.
     #[arg(long)]
     pub(crate) this_is_alpha_code_and_you_will_need_to_redo_the_migration_later: bool,
@@ -207,6 +430,16 @@ pub(crate) struct MigrateZcashdWalletCmd {
 #[cfg_attr(outside_buildscript, derive(Command))]
 pub(crate) struct InitWalletEncryptionCmd {}
 
+/// `change-wallet-encryption` subcommand
+#[cfg(zallet_build = "wallet")]
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command))]
+pub(crate) struct ChangeWalletEncryptionCmd {
+    /// Decrypt the age identity file, leaving it unprotected by a passphrase.
+    #[arg(long)]
+    pub(crate) remove: bool,
+}
+
 /// `generate-mnemonic` subcommand
 #[cfg(zallet_build = "wallet")]
 #[derive(Debug, Parser)]
@@ -217,7 +450,36 @@ pub(crate) struct GenerateMnemonicCmd {}
 #[cfg(zallet_build = "wallet")]
 #[derive(Debug, Parser)]
 #[cfg_attr(outside_buildscript, derive(Command))]
-pub(crate) struct ImportMnemonicCmd {}
+pub(crate) struct ImportMnemonicCmd {
+    /// The height of the first block that may contain transactions involving the
+    /// mnemonic.
+    ///
+    /// Accounts that are later derived from the mnemonic with `z_getnewaccount` are
+    /// scanned from this height. Defaults to the chain tip at the time each account is
+    /// derived.
+    #[arg(long, value_name = "HEIGHT")]
+    pub(crate) birthday: Option<u32>,
+
+    /// The language of the BIP 39 wordlist that the mnemonic is written in.
+    #[arg(long, value_enum, default_value_t = MnemonicLanguage::English)]
+    pub(crate) language: MnemonicLanguage,
+}
+
+/// The languages of the BIP 39 wordlists.
+#[cfg(zallet_build = "wallet")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum MnemonicLanguage {
+    English,
+    ChineseSimplified,
+    ChineseTraditional,
+    Czech,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Portuguese,
+    Spanish,
+}
 
 /// `export-mnemonic` subcommand
 #[cfg(zallet_build = "wallet")]
@@ -263,12 +525,92 @@ pub(crate) struct RpcCliCmd {
     pub(crate) params: Vec<String>,
 }
 
+/// `rpc-schema` subcommand
+#[cfg(zallet_build = "wallet")]
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command))]
+pub(crate) struct RpcSchemaCmd {
+    /// Where to write the OpenRPC document.
+    ///
+    /// The value `-` (the default) will write the document to stdout.
+    #[arg(short, long, default_value = "-")]
+    pub(crate) output: String,
+
+    /// Force an existing file to be overwritten.
+    #[arg(short, long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command, Runnable))]
+pub(crate) enum AuditCmd {
+    Verify(AuditVerifyCmd),
+}
+
+/// Checks that the audit log has not been altered.
+///
+/// Each entry in the log is checked against the hash of the entry before it, and the
+/// first entry that does not match is reported. If the log is intact, the hash of its
+/// last entry is printed; keep a copy of it elsewhere to detect the removal of entries
+/// from the end of the log.
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command))]
+pub(crate) struct AuditVerifyCmd {
+    /// The audit log to check.
+    ///
+    /// By default, the log configured in `external.audit_log` is checked.
+    #[arg(long)]
+    pub(crate) path: Option<PathBuf>,
+}
+
 #[derive(Debug, Parser)]
 #[cfg_attr(outside_buildscript, derive(Command, Runnable))]
 pub(crate) enum RepairCmd {
     TruncateWallet(TruncateWalletCmd),
 }
 
+/// `doctor` subcommand
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command))]
+pub(crate) struct DoctorCmd {
+    /// Repair the inconsistencies that are found.
+    #[arg(long)]
+    pub(crate) fix: bool,
+
+    /// Discard the memo index and rebuild it from the wallet's received notes.
+    #[arg(long)]
+    pub(crate) rebuild_memo_index: bool,
+}
+
+/// `wallet-check` subcommand
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command))]
+pub(crate) struct WalletCheckCmd {
+    /// Print the result as JSON.
+    #[arg(long)]
+    pub(crate) json: bool,
+
+    /// Repair the problems that can be fixed without changing the wallet's state.
+    ///
+    /// A copy of the database is written next to it before anything is repaired. Key
+    /// material is never modified.
+    #[arg(long)]
+    pub(crate) repair: bool,
+}
+
+/// `rebuild` subcommand
+#[cfg(zallet_build = "wallet")]
+#[derive(Debug, Parser)]
+#[cfg_attr(outside_buildscript, derive(Command))]
+pub(crate) struct RebuildCmd {
+    /// The birthday height to use for seeds whose accounts cannot be read from the
+    /// damaged database.
+    ///
+    /// If not set, you will be prompted for the birthday height of each seed.
+    #[arg(long)]
+    pub(crate) birthday: Option<u32>,
+}
+
 /// Truncates the wallet database to at most the specified height.
 ///
 /// Upon successful truncation, this method returns the height to which the data store was
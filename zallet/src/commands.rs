@@ -10,22 +10,31 @@ use abscissa_core::{
     config::Override,
 };
 use home::home_dir;
-use tracing::info;
+use tracing::{error, info, warn};
 
 use crate::{
     cli::{EntryPoint, ZalletCmd},
-    config::ZalletConfig,
+    config::{ZalletConfig, starts_with_tilde},
     error::{Error, ErrorKind},
     fl,
     prelude::APP,
 };
 
 mod add_rpc_user;
+mod audit;
+mod doctor;
 mod example_config;
+mod init_config;
 mod regtest;
 mod repair;
 mod start;
+mod status;
+mod stop;
+mod validate_config;
+mod wallet_check;
 
+#[cfg(zallet_build = "wallet")]
+mod change_wallet_encryption;
 #[cfg(zallet_build = "wallet")]
 mod export_mnemonic;
 #[cfg(zallet_build = "wallet")]
@@ -34,10 +43,16 @@ mod generate_mnemonic;
 mod import_mnemonic;
 #[cfg(zallet_build = "wallet")]
 mod init_wallet_encryption;
+#[cfg(zallet_build = "wallet")]
+mod list_accounts;
 #[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
 mod migrate_zcash_conf;
 #[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
 mod migrate_zcashd_wallet;
+#[cfg(zallet_build = "wallet")]
+mod rebuild;
+#[cfg(zallet_build = "wallet")]
+mod rpc_schema;
 
 #[cfg(feature = "rpc-cli")]
 pub(crate) mod rpc_cli;
@@ -45,9 +60,43 @@ pub(crate) mod rpc_cli;
 /// Zallet Configuration Filename
 pub const CONFIG_FILE: &str = "zallet.toml";
 
+/// Returns the path to the lockfile of the data directory.
+fn lockfile_path(datadir: &Path) -> PathBuf {
+    resolve_datadir_path(datadir, Path::new(".lock"))
+}
+
+/// Returns the path to the file recording which process holds the datadir lock.
+fn lock_holder_path(datadir: &Path) -> PathBuf {
+    resolve_datadir_path(datadir, Path::new(".lock.pid"))
+}
+
+/// Returns the ID of the process that most recently acquired the datadir lock, if it
+/// did not release it cleanly.
+fn lock_holder(datadir: &Path) -> Option<u32> {
+    fs::read_to_string(lock_holder_path(datadir))
+        .ok()
+        .and_then(|pid| pid.trim().parse().ok())
+}
+
+/// An exclusive lock on the data directory, which is released when this is dropped.
+pub(crate) struct DatadirLock {
+    holder_path: PathBuf,
+    _guard: fmutex::Guard<'static>,
+}
+
+impl Drop for DatadirLock {
+    fn drop(&mut self) {
+        // This runs before the lock itself is released.
+        let _ = fs::remove_file(&self.holder_path);
+    }
+}
+
 /// Ensures only a single Zallet process is using the data directory.
-pub(crate) fn lock_datadir(datadir: &Path) -> Result<fmutex::Guard<'static>, Error> {
-    let lockfile_path = resolve_datadir_path(datadir, Path::new(".lock"));
+///
+/// If `wait` is `true` and another process holds the lock, this waits for it to be
+/// released instead of failing.
+pub(crate) fn lock_datadir(datadir: &Path, wait: bool) -> Result<DatadirLock, Error> {
+    let lockfile_path = lockfile_path(datadir);
 
     {
         // Ensure that the lockfile exists on disk.
@@ -60,22 +109,93 @@ pub(crate) fn lock_datadir(datadir: &Path) -> Result<fmutex::Guard<'static>, Err
         })?;
     }
 
-    let guard = fmutex::try_lock_exclusive_path(&lockfile_path)
-        .map_err(|e| {
-            ErrorKind::Init.context(fl!(
-                "err-init-failed-to-read-lockfile",
-                path = lockfile_path.display().to_string(),
-                error = e.to_string(),
-            ))
-        })?
-        .ok_or_else(|| {
-            ErrorKind::Init.context(fl!(
-                "err-init-zallet-already-running",
+    let read_err = |e: std::io::Error| {
+        ErrorKind::Init.context(fl!(
+            "err-init-failed-to-read-lockfile",
+            path = lockfile_path.display().to_string(),
+            error = e.to_string(),
+        ))
+    };
+
+    let guard = match fmutex::try_lock_exclusive_path(&lockfile_path).map_err(read_err)? {
+        Some(guard) => guard,
+        None => match (lock_holder(datadir), wait) {
+            (Some(pid), false) => {
+                return Err(ErrorKind::Init
+                    .context(fl!(
+                        "err-init-zallet-already-running-pid",
+                        datadir = datadir.display().to_string(),
+                        pid = pid,
+                    ))
+                    .into());
+            }
+            (None, false) => {
+                return Err(ErrorKind::Init
+                    .context(fl!(
+                        "err-init-zallet-already-running",
+                        datadir = datadir.display().to_string(),
+                    ))
+                    .into());
+            }
+            (pid, true) => {
+                info!(
+                    "{}",
+                    fl!(
+                        "datadir-lock-waiting",
+                        datadir = datadir.display().to_string(),
+                        pid = pid.map_or_else(|| "?".into(), |pid| pid.to_string()),
+                    )
+                );
+                tokio::task::block_in_place(|| fmutex::lock_exclusive_path(&lockfile_path))
+                    .map_err(read_err)?
+            }
+        },
+    };
+
+    // The lock is released when its holder exits, so if the holder did not remove its
+    // record, it exited without shutting down cleanly.
+    if let Some(pid) = lock_holder(datadir) {
+        warn!(
+            "{}",
+            fl!(
+                "datadir-lock-stale",
                 datadir = datadir.display().to_string(),
-            ))
-        })?;
+                pid = pid,
+            )
+        );
+    }
+
+    let holder_path = lock_holder_path(datadir);
+    fs::write(&holder_path, format!("{}\n", std::process::id())).map_err(|e| {
+        ErrorKind::Init.context(fl!(
+            "err-init-failed-to-create-lockfile",
+            path = holder_path.display().to_string(),
+            error = e.to_string(),
+        ))
+    })?;
+
+    Ok(DatadirLock {
+        holder_path,
+        _guard: guard,
+    })
+}
+
+/// Returns whether a Zallet process is currently using the data directory.
+pub(crate) fn datadir_locked(datadir: &Path) -> Result<bool, Error> {
+    let lockfile_path = lockfile_path(datadir);
+    if !lockfile_path.exists() {
+        return Ok(false);
+    }
+
+    let guard = fmutex::try_lock_exclusive_path(&lockfile_path).map_err(|e| {
+        ErrorKind::Init.context(fl!(
+            "err-init-failed-to-read-lockfile",
+            path = lockfile_path.display().to_string(),
+            error = e.to_string(),
+        ))
+    })?;
 
-    Ok(guard)
+    Ok(guard.is_none())
 }
 
 /// Resolves the requested path relative to the Zallet data directory.
@@ -93,7 +213,21 @@ impl EntryPoint {
         //       chain-specific.
         //       https://github.com/zcash/wallet/issues/250
         if let Some(datadir) = &self.datadir {
-            Ok(datadir.clone())
+            // Shells don't expand `~` in every position (such as `--datadir=~/zallet`),
+            // and a relative datadir would depend on the working directory, so reject
+            // both instead of silently using an unexpected directory.
+            let path = datadir.display().to_string();
+            if starts_with_tilde(datadir) {
+                Err(FrameworkErrorKind::ConfigError
+                    .context(fl!("err-init-datadir-tilde", path = path))
+                    .into())
+            } else if datadir.is_relative() {
+                Err(FrameworkErrorKind::ConfigError
+                    .context(fl!("err-init-datadir-relative", path = path))
+                    .into())
+            } else {
+                Ok(datadir.clone())
+            }
         } else {
             // The XDG Base Directory Specification is widely misread as saying that
             // `$XDG_DATA_HOME` should be used for storing mutable user-generated data.
@@ -130,17 +264,33 @@ impl Runnable for EntryPoint {
     }
 }
 
+impl EntryPoint {
+    /// Returns the path of the config file to use, whether or not it exists.
+    fn config_file(&self) -> Result<PathBuf, FrameworkError> {
+        Ok(resolve_datadir_path(
+            &self.datadir()?,
+            self.config
+                .as_deref()
+                .unwrap_or_else(|| Path::new(CONFIG_FILE)),
+        ))
+    }
+}
+
 impl Configurable<ZalletConfig> for EntryPoint {
     fn config_path(&self) -> Option<PathBuf> {
+        // `zallet validate-config` loads the config file itself, so that it can report
+        // any errors in it, and `zallet init-config` may be overwriting a broken one.
+        if matches!(
+            self.cmd,
+            ZalletCmd::ValidateConfig(_) | ZalletCmd::InitConfig(_)
+        ) {
+            return None;
+        }
+
         // Check if the config file exists, and if it does not, ignore it.
         // If you'd like for a missing configuration file to be a hard error
         // instead, always return `Some(CONFIG_FILE)` here.
-        let filename = resolve_datadir_path(
-            &self.datadir().ok()?,
-            self.config
-                .as_deref()
-                .unwrap_or_else(|| Path::new(CONFIG_FILE)),
-        );
+        let filename = self.config_file().ok()?;
 
         if filename.exists() {
             Some(filename)
@@ -153,16 +303,47 @@ impl Configurable<ZalletConfig> for EntryPoint {
         // Components access top-level CLI settings solely through `ZalletConfig`.
         // Load them in here.
         config.datadir = Some(self.datadir()?);
+        config.config_file = Some(self.config_file()?);
+        config.wait_for_datadir_lock = self.wait;
+        config.verbose = self.verbose;
+        if let Some(network) = &self.network {
+            config.consensus.network =
+                crate::network::kind::str_to_type(network).expect("checked by clap");
+        }
+
+        config
+            .builder
+            .check_confirmations()
+            .map_err(|(_, e)| FrameworkError::from(FrameworkErrorKind::ConfigError.context(e)))?;
 
-        match &self.cmd {
-            ZalletCmd::Start(cmd) => cmd.override_config(config),
-            _ => Ok(config),
+        config
+            .check_wallet_names()
+            .map_err(|e| FrameworkError::from(FrameworkErrorKind::ConfigError.context(e)))?;
+
+        match (&self.cmd, &self.wallet) {
+            (ZalletCmd::Start(cmd), _) => cmd.override_config(config),
+            // These commands don't load the config file.
+            (ZalletCmd::ValidateConfig(_) | ZalletCmd::InitConfig(_), _) => Ok(config),
+            (_, None) => Ok(config),
+            // Other commands operate on the selected wallet.
+            (_, Some(name)) => config.for_wallet(name).ok_or_else(|| {
+                FrameworkErrorKind::ConfigError
+                    .context(fl!("err-config-wallet-not-found", name = name.clone()))
+                    .into()
+            }),
         }
     }
 }
 
 /// An async version of the [`Runnable`] trait.
 pub(crate) trait AsyncRunnable {
+    /// Whether [`AsyncRunnable::run`] handles shutdown requests itself.
+    ///
+    /// If `true`, the first interrupt or termination signal requests a graceful shutdown
+    /// (see [`crate::shutdown`]) instead of cancelling [`AsyncRunnable::run`], and only a
+    /// second signal forces Zallet to exit immediately.
+    const GRACEFUL_SHUTDOWN: bool = false;
+
     /// Runs this `AsyncRunnable`.
     async fn run(&self) -> Result<(), Error>;
 
@@ -170,22 +351,46 @@ pub(crate) trait AsyncRunnable {
     ///
     /// Signal detection is included for handling both interrupts (Ctrl-C on most
     /// platforms, corresponding to `SIGINT` on Unix), and programmatic termination
-    /// (`SIGTERM` on Unix). Both of these will cause [`AsyncRunnable::run`] to be
-    /// cancelled (ending execution at an `.await` boundary).
+    /// (`SIGTERM` on Unix). Unless [`AsyncRunnable::GRACEFUL_SHUTDOWN`] is set, both of
+    /// these will cause [`AsyncRunnable::run`] to be cancelled (ending execution at an
+    /// `.await` boundary).
     ///
     /// This should be called from [`Runnable::run`].
     fn run_on_runtime(&self) {
         match abscissa_tokio::run(&APP, async move {
+            if !Self::GRACEFUL_SHUTDOWN {
+                return tokio::select! {
+                    biased;
+                    _ = shutdown() => Ok(()),
+                    result = self.run() => result,
+                };
+            }
+
+            let run = self.run();
+            tokio::pin!(run);
+
             tokio::select! {
                 biased;
-                _ = shutdown() => Ok(()),
-                result = self.run() => result,
+                _ = shutdown() => crate::shutdown::request(),
+                result = &mut run => return result,
+            }
+
+            tokio::select! {
+                biased;
+                _ = shutdown() => {
+                    error!(
+                        "Received a second shutdown signal; exiting immediately. \
+                        In-progress operations may not have been recorded."
+                    );
+                    Err(ErrorKind::Generic.context("Shutdown was forced").into())
+                }
+                result = run => result,
             }
         }) {
             Ok(Ok(())) => (),
             Ok(Err(e)) => {
                 eprintln!("{e}");
-                APP.shutdown_with_exitcode(Shutdown::Forced, 1);
+                APP.shutdown_with_exitcode(Shutdown::Forced, e.exit_code().code());
             }
             Err(e) => {
                 eprintln!("{e}");
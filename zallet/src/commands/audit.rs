@@ -0,0 +1,57 @@
+//! `audit` subcommands
+
+use abscissa_core::{Application, Runnable, Shutdown};
+
+use crate::{
+    cli::AuditVerifyCmd,
+    components::audit::{self, Verification},
+    fl,
+    prelude::APP,
+};
+
+impl Runnable for AuditVerifyCmd {
+    fn run(&self) {
+        let config = APP.config();
+
+        let Some(path) = self.path.clone().or_else(|| config.audit_log_path()) else {
+            eprintln!("{}", fl!("cmd-audit-not-enabled"));
+            APP.shutdown_with_exitcode(Shutdown::Graceful, 1);
+        };
+
+        match audit::verify(&path) {
+            Ok(Verification::Valid { entries, last_hash }) => {
+                println!(
+                    "{}",
+                    fl!("cmd-audit-intact", path = path.display().to_string())
+                );
+                println!("{}", fl!("cmd-audit-entries", entries = entries));
+                if let Some(hash) = last_hash {
+                    println!("{}", fl!("cmd-audit-last-hash", hash = hash));
+                }
+            }
+            Ok(Verification::Broken { line, reason }) => {
+                println!(
+                    "{}",
+                    fl!(
+                        "cmd-audit-broken",
+                        path = path.display().to_string(),
+                        line = line,
+                        reason = reason,
+                    )
+                );
+                APP.shutdown_with_exitcode(Shutdown::Graceful, 1);
+            }
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    fl!(
+                        "cmd-audit-unreadable",
+                        path = path.display().to_string(),
+                        error = e.to_string(),
+                    )
+                );
+                APP.shutdown_with_exitcode(Shutdown::Graceful, 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::io::{self, Write};
+
+use abscissa_core::Runnable;
+use age::secrecy::{ExposeSecret, SecretString};
+
+use crate::{
+    cli::ChangeWalletEncryptionCmd,
+    commands::AsyncRunnable,
+    components::{
+        audit::{self, Event},
+        database::Database,
+        keystore::KeyStore,
+    },
+    error::{Error, ErrorKind, ExitCode},
+    fl,
+    prelude::*,
+};
+
+/// The phrase that must be typed to confirm `--remove`.
+const REMOVE_CONFIRMATION: &str = "remove my wallet passphrase";
+
+impl AsyncRunnable for ChangeWalletEncryptionCmd {
+    async fn run(&self) -> Result<(), Error> {
+        let config = APP.config();
+        // This refuses to run while `zallet start` is using the wallet, which would
+        // otherwise keep using the old identity file.
+        let _lock = config.lock_datadir()?;
+
+        let db = Database::open(&config).await?;
+        let keystore = KeyStore::new(&config, db)?;
+        let path = config.encryption_identity().display().to_string();
+
+        if !keystore.uses_encrypted_identities() {
+            if self.remove {
+                return Err(ErrorKind::Generic
+                    .context(fl!("err-keystore-not-passphrase-encrypted", path = path))
+                    .into());
+            }
+
+            keystore.add_passphrase(prompt_new_passphrase()?).await?;
+            audit::record(Event::WalletPassphraseAdded);
+            println!("{}", fl!("cmd-change-wallet-encryption-added", path = path));
+            return Ok(());
+        }
+
+        if self.remove {
+            // A plaintext identity file would stop Zallet from starting.
+            for (forbidden, option) in [
+                (
+                    config.keystore.require_encryption(),
+                    "keystore.require_encryption",
+                ),
+                (!config.keystore.allow_unlock(), "keystore.allow_unlock"),
+            ] {
+                if forbidden {
+                    return Err(ErrorKind::Generic
+                        .context(fl!(
+                            "err-keystore-remove-encryption-required",
+                            option = option,
+                        ))
+                        .into());
+                }
+            }
+
+            let current = prompt_current_passphrase(&keystore).await?;
+            confirm_removal(&path)?;
+            if !keystore.remove_passphrase(current).await? {
+                return Err(wrong_passphrase());
+            }
+            audit::record(Event::WalletPassphraseRemoved);
+            println!(
+                "{}",
+                fl!("cmd-change-wallet-encryption-removed", path = path)
+            );
+        } else {
+            let current = prompt_current_passphrase(&keystore).await?;
+            let new = prompt_new_passphrase()?;
+            if !keystore.change_passphrase(current, new).await? {
+                return Err(wrong_passphrase());
+            }
+            audit::record(Event::WalletPassphraseChanged);
+            println!("{}", fl!("cmd-change-wallet-encryption-changed"));
+        }
+
+        Ok(())
+    }
+}
+
+impl Runnable for ChangeWalletEncryptionCmd {
+    fn run(&self) {
+        self.run_on_runtime();
+    }
+}
+
+fn prompt_passphrase(prompt: String) -> Result<SecretString, Error> {
+    rpassword::prompt_password(prompt)
+        .map(SecretString::from)
+        .map_err(|e| ErrorKind::Generic.context(e).into())
+}
+
+/// Prompts for the current passphrase, and checks it before anything else is asked.
+async fn prompt_current_passphrase(keystore: &KeyStore) -> Result<SecretString, Error> {
+    let current = prompt_passphrase(fl!("cmd-change-wallet-encryption-current-prompt"))?;
+    if !keystore.check_passphrase(current.clone()).await? {
+        return Err(wrong_passphrase());
+    }
+    Ok(current)
+}
+
+/// Prompts twice for a new passphrase, and checks that the two entries match.
+fn prompt_new_passphrase() -> Result<SecretString, Error> {
+    let new = prompt_passphrase(fl!("cmd-change-wallet-encryption-new-prompt"))?;
+    if new.expose_secret().is_empty() {
+        return Err(ErrorKind::Generic
+            .context(fl!("err-keystore-empty-passphrase"))
+            .into());
+    }
+    let repeated = prompt_passphrase(fl!("cmd-change-wallet-encryption-repeat-prompt"))?;
+    if new.expose_secret() != repeated.expose_secret() {
+        return Err(ErrorKind::Generic
+            .context(fl!("err-keystore-passphrases-differ"))
+            .into());
+    }
+    Ok(new)
+}
+
+fn confirm_removal(path: &str) -> Result<(), Error> {
+    println!(
+        "{}",
+        fl!("cmd-change-wallet-encryption-remove-warning", path = path)
+    );
+    print!(
+        "{} ",
+        fl!(
+            "cmd-change-wallet-encryption-remove-confirm",
+            phrase = REMOVE_CONFIRMATION
+        )
+    );
+    io::stdout()
+        .flush()
+        .map_err(|e| ErrorKind::Generic.context(e))?;
+
+    let mut line = String::new();
+    io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| ErrorKind::Generic.context(e))?;
+    if line.trim() != REMOVE_CONFIRMATION {
+        return Err(ErrorKind::Generic
+            .context(fl!("err-keystore-remove-encryption-not-confirmed"))
+            .into());
+    }
+    Ok(())
+}
+
+fn wrong_passphrase() -> Error {
+    Error::from(ErrorKind::Generic.context(fl!("err-keystore-wrong-passphrase")))
+        .with_exit_code(ExitCode::WrongPassphrase)
+}
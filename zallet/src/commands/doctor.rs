@@ -0,0 +1,75 @@
+//! `doctor` subcommand
+
+use abscissa_core::Runnable;
+
+use crate::{
+    cli::DoctorCmd,
+    commands::AsyncRunnable,
+    components::database::Database,
+    error::{Error, ErrorKind},
+    fl,
+    prelude::*,
+};
+
+impl AsyncRunnable for DoctorCmd {
+    async fn run(&self) -> Result<(), Error> {
+        let config = APP.config();
+        let _lock = config.lock_datadir()?;
+
+        let db = Database::open(&config).await?;
+        let wallet = db.handle().await?;
+
+        let mut problems = 0;
+        let mut fixed = 0;
+
+        println!("{}", fl!("cmd-doctor-checking-balances"));
+        let balances = wallet
+            .check_balance_aggregates()
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+        for problem in balances.problems() {
+            println!("- {problem}");
+        }
+        if !balances.is_consistent() {
+            if self.fix {
+                wallet
+                    .rebuild_balance_aggregates()
+                    .map_err(|e| ErrorKind::Generic.context(e))?;
+                println!("  {}", fl!("cmd-doctor-rebuilt-balances"));
+                fixed += 1;
+            } else {
+                problems += 1;
+            }
+        }
+
+        if self.rebuild_memo_index {
+            if config.database.memo_index() {
+                println!("{}", fl!("cmd-doctor-rebuilding-memo-index"));
+                let indexed = wallet
+                    .rebuild_memo_index()
+                    .map_err(|e| ErrorKind::Generic.context(e))?;
+                println!("  {}", fl!("cmd-doctor-indexed-memos", notes = indexed));
+            } else {
+                println!("{}", fl!("cmd-doctor-memo-index-disabled"));
+            }
+        }
+
+        if problems == 0 {
+            if fixed == 0 {
+                println!("{}", fl!("cmd-doctor-no-problems"));
+            } else {
+                println!("{}", fl!("cmd-doctor-repaired", fixed = fixed));
+            }
+            Ok(())
+        } else {
+            Err(ErrorKind::Generic
+                .context(fl!("err-doctor-problems", problems = problems))
+                .into())
+        }
+    }
+}
+
+impl Runnable for DoctorCmd {
+    fn run(&self) {
+        self.run_on_runtime();
+    }
+}
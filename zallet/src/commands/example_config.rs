@@ -1,5 +1,7 @@
 //! `example-config` subcommand
 
+use std::path::PathBuf;
+
 use abscissa_core::Runnable;
 use tokio::{fs::File, io::AsyncWriteExt};
 
@@ -9,6 +11,7 @@ use crate::{
     config::ZalletConfig,
     error::{Error, ErrorKind},
     fl,
+    prelude::*,
 };
 
 impl AsyncRunnable for ExampleConfigCmd {
@@ -17,31 +20,7 @@ impl AsyncRunnable for ExampleConfigCmd {
             return Err(ErrorKind::Generic.context(fl!("example-alpha-code")).into());
         }
 
-        // Serialize the example config.
-        let output = ZalletConfig::generate_example();
-
-        // Write the Zallet config file.
-        let output_path = match self.output.as_deref() {
-            None => todo!("No default Zallet config path yet, use -o/--output"),
-            Some("-") => None,
-            Some(path) => Some(path),
-        };
-        if let Some(path) = output_path {
-            let mut f = if self.force {
-                File::create(path).await
-            } else {
-                File::create_new(path).await
-            }
-            .map_err(|e| ErrorKind::Generic.context(e))?;
-            f.write_all(output.as_bytes())
-                .await
-                .map_err(|e| ErrorKind::Generic.context(e))?;
-            println!("{}", fl!("migrate-config-written", conf = path));
-        } else {
-            println!("{output}")
-        }
-
-        Ok(())
+        write_example_config(self.output.as_deref(), self.force).await
     }
 }
 
@@ -50,3 +29,59 @@ impl Runnable for ExampleConfigCmd {
         self.run_on_runtime();
     }
 }
+
+/// Writes the example config to `output`.
+///
+/// - If `output` is `None`, the config file that Zallet loads is written.
+/// - If `output` is `-`, the config is written to stdout.
+///
+/// An existing file is only overwritten if `force` is set.
+pub(super) async fn write_example_config(output: Option<&str>, force: bool) -> Result<(), Error> {
+    // Serialize the example config.
+    let output = match output {
+        None => Some(
+            APP.config()
+                .config_file
+                .clone()
+                .expect("set by EntryPoint::process_config"),
+        ),
+        Some("-") => None,
+        Some(path) => Some(PathBuf::from(path)),
+    };
+    let config = ZalletConfig::generate_example();
+
+    // Write the Zallet config file.
+    if let Some(path) = output {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| ErrorKind::Generic.context(e))?;
+        }
+        let mut f = if force {
+            File::create(&path).await
+        } else {
+            File::create_new(&path).await
+        }
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::AlreadyExists {
+                ErrorKind::Generic.context(fl!(
+                    "err-config-file-exists",
+                    path = path.display().to_string(),
+                ))
+            } else {
+                ErrorKind::Generic.context(e)
+            }
+        })?;
+        f.write_all(config.as_bytes())
+            .await
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+        println!(
+            "{}",
+            fl!("migrate-config-written", conf = path.display().to_string())
+        );
+    } else {
+        println!("{config}")
+    }
+
+    Ok(())
+}
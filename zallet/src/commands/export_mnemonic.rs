@@ -6,7 +6,11 @@ use zcash_client_sqlite::AccountUuid;
 use crate::{
     cli::ExportMnemonicCmd,
     commands::AsyncRunnable,
-    components::{database::Database, keystore::KeyStore},
+    components::{
+        audit::{self, Event},
+        database::Database,
+        keystore::KeyStore,
+    },
     error::{Error, ErrorKind},
     fl,
     prelude::*,
@@ -15,9 +19,9 @@ use crate::{
 impl AsyncRunnable for ExportMnemonicCmd {
     async fn run(&self) -> Result<(), Error> {
         let config = APP.config();
-        let _lock = config.lock_datadir()?;
 
-        let db = Database::open(&config).await?;
+        // This only reads the wallet, so it can run alongside `zallet start`.
+        let db = Database::open_read_only(&config).await?;
         let wallet = db.handle().await?;
         let keystore = KeyStore::new(&config, db)?;
 
@@ -34,6 +38,10 @@ impl AsyncRunnable for ExportMnemonicCmd {
         let encrypted_mnemonic = keystore
             .export_mnemonic(derivation.seed_fingerprint(), self.armor)
             .await?;
+        audit::record(Event::MnemonicExported {
+            seed_fingerprint: derivation.seed_fingerprint().to_string(),
+            account_uuid: self.account_uuid.to_string(),
+        });
 
         let mut stdout = io::stdout();
         stdout
@@ -1,11 +1,13 @@
 use abscissa_core::Runnable;
-use bip0039::{Count, English, Mnemonic};
-use rand::{RngCore, rngs::OsRng};
 
 use crate::{
     cli::GenerateMnemonicCmd,
     commands::AsyncRunnable,
-    components::{database::Database, keystore::KeyStore},
+    components::{
+        audit::{self, Event},
+        database::Database,
+        keystore::{self, KeyStore},
+    },
     error::Error,
     fl,
     prelude::*,
@@ -18,19 +20,14 @@ impl AsyncRunnable for GenerateMnemonicCmd {
 
         let db = Database::open(&config).await?;
         let keystore = KeyStore::new(&config, db)?;
+        keystore.enforce_required_encryption(&config).await?;
 
-        // Adapted from `Mnemonic::generate` so we can use `OsRng` directly.
-        const BITS_PER_BYTE: usize = 8;
-        const MAX_ENTROPY_BITS: usize = Count::Words24.entropy_bits();
-        const ENTROPY_BYTES: usize = MAX_ENTROPY_BITS / BITS_PER_BYTE;
-
-        let mut entropy = [0u8; ENTROPY_BYTES];
-        OsRng.fill_bytes(&mut entropy);
-
-        let mnemonic = Mnemonic::<English>::from_entropy(entropy)
-            .expect("valid entropy length won't fail to generate the mnemonic");
+        let mnemonic = keystore::generate_mnemonic();
 
         let seedfp = keystore.encrypt_and_store_mnemonic(mnemonic).await?;
+        audit::record(Event::MnemonicGenerated {
+            seed_fingerprint: seedfp.to_string(),
+        });
 
         println!(
             "{}",
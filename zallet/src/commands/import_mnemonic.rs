@@ -1,11 +1,20 @@
 use abscissa_core::Runnable;
-use bip0039::{English, Mnemonic};
+use bip0039::{
+    ChineseSimplified, ChineseTraditional, Czech, English, French, Italian, Japanese, Korean,
+    Language, Mnemonic, Portuguese, Spanish,
+};
 use secrecy::{ExposeSecret, SecretString};
+use zcash_protocol::consensus::BlockHeight;
+use zip32::fingerprint::SeedFingerprint;
 
 use crate::{
-    cli::ImportMnemonicCmd,
+    cli::{ImportMnemonicCmd, MnemonicLanguage},
     commands::AsyncRunnable,
-    components::{database::Database, keystore::KeyStore},
+    components::{
+        audit::{self, Event},
+        database::Database,
+        keystore::KeyStore,
+    },
     error::{Error, ErrorKind},
     fl,
     prelude::*,
@@ -18,16 +27,82 @@ impl AsyncRunnable for ImportMnemonicCmd {
 
         let db = Database::open(&config).await?;
         let keystore = KeyStore::new(&config, db)?;
+        keystore.enforce_required_encryption(&config).await?;
 
         let phrase = SecretString::new(
             rpassword::prompt_password(fl!("cmd-import-mnemonic-prompt"))
                 .map_err(|e| ErrorKind::Generic.context(e))?,
         );
 
-        let mnemonic = Mnemonic::<English>::from_phrase(phrase.expose_secret())
-            .map_err(|e| ErrorKind::Generic.context(e))?;
+        match self.language {
+            MnemonicLanguage::English => self.import::<English>(&keystore, phrase).await,
+            MnemonicLanguage::ChineseSimplified => {
+                self.import::<ChineseSimplified>(&keystore, phrase).await
+            }
+            MnemonicLanguage::ChineseTraditional => {
+                self.import::<ChineseTraditional>(&keystore, phrase).await
+            }
+            MnemonicLanguage::Czech => self.import::<Czech>(&keystore, phrase).await,
+            MnemonicLanguage::French => self.import::<French>(&keystore, phrase).await,
+            MnemonicLanguage::Italian => self.import::<Italian>(&keystore, phrase).await,
+            MnemonicLanguage::Japanese => self.import::<Japanese>(&keystore, phrase).await,
+            MnemonicLanguage::Korean => self.import::<Korean>(&keystore, phrase).await,
+            MnemonicLanguage::Portuguese => self.import::<Portuguese>(&keystore, phrase).await,
+            MnemonicLanguage::Spanish => self.import::<Spanish>(&keystore, phrase).await,
+        }
+    }
+}
+
+impl ImportMnemonicCmd {
+    async fn import<L: Language>(
+        &self,
+        keystore: &KeyStore,
+        phrase: SecretString,
+    ) -> Result<(), Error> {
+        let mnemonic = Mnemonic::<L>::from_phrase(phrase.expose_secret()).map_err(|e| {
+            ErrorKind::Generic.context(fl!(
+                "err-keystore-invalid-mnemonic",
+                language = format!("{:?}", self.language),
+                error = e.to_string(),
+            ))
+        })?;
+
+        let seedfp = SeedFingerprint::from_seed(&mnemonic.to_seed("")).expect("valid length");
+        if keystore.list_seed_fingerprints().await?.contains(&seedfp) {
+            println!(
+                "{}",
+                fl!(
+                    "cmd-import-mnemonic-already-present",
+                    seedfp = seedfp.to_string()
+                )
+            );
+            return Ok(());
+        }
+
+        // The mnemonic is encrypted at rest to the wallet's age recipients. If the wallet's
+        // identity is passphrase-encrypted, check that the operator can decrypt it.
+        if keystore.uses_encrypted_identities() {
+            let passphrase =
+                rpassword::prompt_password(fl!("cmd-import-mnemonic-passphrase-prompt"))
+                    .map_err(|e| ErrorKind::Generic.context(e))?;
+            if !keystore.unlock(passphrase.into(), 60).await {
+                return Err(ErrorKind::Generic
+                    .context(fl!("err-keystore-wrong-passphrase"))
+                    .into());
+            }
+        }
 
         let seedfp = keystore.encrypt_and_store_mnemonic(mnemonic).await?;
+        if let Some(birthday) = self.birthday {
+            keystore
+                .set_mnemonic_birthday(&seedfp, BlockHeight::from_u32(birthday))
+                .await?;
+        }
+        // The user already has the mnemonic, as they just entered it.
+        keystore.confirm_backup(&seedfp).await?;
+        audit::record(Event::MnemonicImported {
+            seed_fingerprint: seedfp.to_string(),
+        });
 
         println!(
             "{}",
@@ -0,0 +1,19 @@
+//! `init-config` subcommand
+
+use abscissa_core::Runnable;
+
+use crate::{cli::InitConfigCmd, commands::AsyncRunnable, error::Error};
+
+use super::example_config::write_example_config;
+
+impl AsyncRunnable for InitConfigCmd {
+    async fn run(&self) -> Result<(), Error> {
+        write_example_config(self.output.as_deref(), self.force).await
+    }
+}
+
+impl Runnable for InitConfigCmd {
+    fn run(&self) {
+        self.run_on_runtime();
+    }
+}
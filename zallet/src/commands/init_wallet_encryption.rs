@@ -3,7 +3,11 @@ use abscissa_core::Runnable;
 use crate::{
     cli::InitWalletEncryptionCmd,
     commands::AsyncRunnable,
-    components::{database::Database, keystore::KeyStore},
+    components::{
+        audit::{self, Event},
+        database::Database,
+        keystore::KeyStore,
+    },
     error::{Error, ErrorKind},
     fl,
     prelude::*,
@@ -58,7 +62,10 @@ impl AsyncRunnable for InitWalletEncryptionCmd {
             .collect();
 
         // Store the recipients in the keystore.
-        keystore.initialize_recipients(recipient_strings).await
+        keystore.initialize_recipients(recipient_strings).await?;
+        audit::record(Event::EncryptionInitialized);
+
+        Ok(())
     }
 }
 
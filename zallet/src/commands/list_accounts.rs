@@ -0,0 +1,53 @@
+//! `list-accounts` subcommand
+
+use abscissa_core::Runnable;
+use zcash_client_backend::data_api::{Account as _, WalletRead};
+
+use crate::{
+    cli::ListAccountsCmd,
+    commands::AsyncRunnable,
+    components::database::Database,
+    error::{Error, ErrorKind},
+    prelude::*,
+};
+
+impl AsyncRunnable for ListAccountsCmd {
+    async fn run(&self) -> Result<(), Error> {
+        let config = APP.config();
+
+        // This only reads the wallet, so it can run alongside `zallet start`.
+        let db = Database::open_read_only(&config).await?;
+        let wallet = db.handle().await?;
+
+        for account_id in wallet
+            .get_account_ids()
+            .map_err(|e| ErrorKind::Generic.context(e))?
+        {
+            let account = wallet
+                .get_account(account_id)
+                .map_err(|e| ErrorKind::Generic.context(e))?
+                .ok_or_else(|| ErrorKind::Generic.context("Account was deleted"))?;
+
+            let mut line = account_id.expose_uuid().to_string();
+            if let Some(name) = account.name() {
+                line.push_str(&format!(" {name:?}"));
+            }
+            if let Some(derivation) = account.source().key_derivation() {
+                line.push_str(&format!(
+                    " seed {} account {}",
+                    derivation.seed_fingerprint(),
+                    u32::from(derivation.account_index()),
+                ));
+            }
+            println!("{line}");
+        }
+
+        Ok(())
+    }
+}
+
+impl Runnable for ListAccountsCmd {
+    fn run(&self) {
+        self.run_on_runtime();
+    }
+}
@@ -1,34 +1,51 @@
 //! `migrate-zcash-conf` subcommand
 
 use std::collections::{HashMap, HashSet};
+use std::io;
 use std::iter;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use abscissa_core::Runnable;
+use abscissa_core::{Application, Runnable, Shutdown};
+use ipnet::IpNet;
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use serde::Serialize;
+use similar::{DiffTag, TextDiff};
 use tokio::{
     fs::File,
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
 };
+use zcash_primitives::transaction::builder::DEFAULT_TX_EXPIRY_DELTA;
+use zcash_protocol::value::COIN;
 
 use crate::{
     cli::MigrateZcashConfCmd,
     commands::AsyncRunnable,
-    config::{RpcAuthSection, ZalletConfig},
+    components::json_rpc::server::authorization::PasswordHash,
+    config::{AllowedIp, RpcAuthSection, RpcBind, ZalletConfig, starts_with_tilde},
     error::{Error, ErrorKind},
     fl,
     network::RegTestNuParam,
+    prelude::APP,
 };
 
+/// The exit code of a dry run that found differences, distinct from the exit code of a
+/// failed migration.
+const DRY_RUN_DIFFERENCES_EXIT_CODE: i32 = 2;
+
+/// Set by a dry run that found differences.
+static DRY_RUN_FOUND_DIFFERENCES: AtomicBool = AtomicBool::new(false);
+
 impl AsyncRunnable for MigrateZcashConfCmd {
     async fn run(&self) -> Result<(), Error> {
+        if self.dry_run && self.force {
+            return Err(ErrorKind::Generic
+                .context(fl!("err-migrate-dry-run-force"))
+                .into());
+        }
+
         let conf = if self.conf.is_relative() {
-            if let Some(datadir) = self.zcashd_datadir.as_ref() {
-                datadir.join(&self.conf)
-            } else {
-                zcashd_default_data_dir()
-                    .ok_or(ErrorKind::Generic)?
-                    .join(&self.conf)
-            }
+            zcashd_data_dir(self.zcashd_datadir.as_deref())?.join(&self.conf)
         } else {
             self.conf.to_path_buf()
         };
@@ -44,7 +61,10 @@ impl AsyncRunnable for MigrateZcashConfCmd {
         let mut config = ZalletConfig::default();
         let mut observed = HashSet::new();
         let mut related = HashMap::<String, String>::new();
-        let mut warnings = vec![];
+        let mut report = MigrationReport {
+            conf: conf.display().to_string(),
+            options: vec![],
+        };
 
         while let Some(line) = lines
             .next_line()
@@ -71,8 +91,8 @@ impl AsyncRunnable for MigrateZcashConfCmd {
                 .split_once('#')
                 .map_or_else(|| rest.trim_end(), |(value, _)| value.trim_end());
 
-            match actions.get(option) {
-                Some(Action::MapTo { f, target }) => {
+            let disposition = match actions.get(option) {
+                Some(Action::MapTo { f, key, target }) => {
                     if let Some(prev) = target.and_then(|target| related.get(target)) {
                         return Err(ErrorKind::Generic
                             .context(fl!(
@@ -95,26 +115,66 @@ impl AsyncRunnable for MigrateZcashConfCmd {
                         if let Some(target) = target {
                             related.insert(target.to_string(), option.to_owned());
                         }
-                        f(&mut config, value)?
+                        f(&mut config, value)?;
+                        Disposition::Migrated { key: *key }
                     }
                 }
-                Some(Action::MapMulti(f)) => f(&mut config, value)?,
-                Some(Action::Ignore) => (),
-                Some(Action::Warn(f)) => {
-                    if let Some(warning) = f(value) {
-                        warnings.push(warning);
-                    }
+                Some(Action::MapMulti { f, key }) => {
+                    f(&mut config, value)?;
+                    Disposition::Migrated { key: *key }
                 }
+                Some(Action::Ignore(reason)) => Disposition::Ignored {
+                    reason: (*reason).into(),
+                },
+                Some(Action::Warn(f)) => match f(value) {
+                    Some(warning) => Disposition::Unsupported { reason: warning },
+                    None => Disposition::Ignored {
+                        reason: "The configured value matches Zallet's behaviour.".into(),
+                    },
+                },
                 None => {
                     return Err(ErrorKind::Generic
                         .context(fl!("err-migrate-unknown-zcashd-option", option = option))
                         .into());
                 }
-            }
+            };
+            report.options.push(OptionReport {
+                option: option.to_owned(),
+                disposition,
+            });
+        }
+
+        // `rpcuser` and `rpcpassword` only describe a user together.
+        if config
+            .rpc
+            .auth
+            .iter()
+            .any(|auth| auth.user.is_empty() || auth.pwhash.is_none())
+        {
+            return Err(ErrorKind::Generic
+                .context(fl!(
+                    "err-migrate-rpc-credentials-unpaired",
+                    conf = conf.display().to_string(),
+                ))
+                .into());
+        }
+
+        // Write the report before deciding whether to proceed, so that automation can
+        // inspect it either way.
+        if let Some(path) = &self.report {
+            let json =
+                serde_json::to_string_pretty(&report).map_err(|e| ErrorKind::Generic.context(e))?;
+            let mut f = File::create(path)
+                .await
+                .map_err(|e| ErrorKind::Generic.context(e))?;
+            f.write_all(json.as_bytes())
+                .await
+                .map_err(|e| ErrorKind::Generic.context(e))?;
         }
 
         // Inform the user of any warnings.
-        if !warnings.is_empty() {
+        let mut warnings = report.warnings().peekable();
+        if warnings.peek().is_some() {
             println!("{}", fl!("migrate-warnings"));
             println!();
             for warning in warnings {
@@ -153,7 +213,26 @@ impl AsyncRunnable for MigrateZcashConfCmd {
             Some("-") => None,
             Some(path) => Some(path),
         };
-        if let Some(path) = output_path {
+        if let Some(path) = output_path.filter(|_| self.dry_run) {
+            let existing = match tokio::fs::read_to_string(path).await {
+                Ok(existing) => Some(existing),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(ErrorKind::Generic.context(e).into()),
+            };
+
+            let diff = TextDiff::from_lines(existing.as_deref().unwrap_or(""), &output);
+            if diff.ops().iter().all(|op| op.tag() == DiffTag::Equal) {
+                println!("{}", fl!("migrate-dry-run-unchanged", conf = path));
+            } else {
+                let old_path = if existing.is_some() {
+                    path
+                } else {
+                    "/dev/null"
+                };
+                print!("{}", diff.unified_diff().header(old_path, path));
+                DRY_RUN_FOUND_DIFFERENCES.store(true, Ordering::Relaxed);
+            }
+        } else if let Some(path) = output_path {
             let mut f = if self.force {
                 File::create(path).await
             } else {
@@ -175,6 +254,26 @@ impl AsyncRunnable for MigrateZcashConfCmd {
 impl Runnable for MigrateZcashConfCmd {
     fn run(&self) {
         self.run_on_runtime();
+        if DRY_RUN_FOUND_DIFFERENCES.load(Ordering::Relaxed) {
+            APP.shutdown_with_exitcode(Shutdown::Graceful, DRY_RUN_DIFFERENCES_EXIT_CODE);
+        }
+    }
+}
+
+/// Returns the `zcashd` data directory given by `--zcashd-datadir`, or the default one.
+///
+/// As with Zallet's own `--datadir`, paths starting with `~` are rejected rather than
+/// being used literally.
+pub(crate) fn zcashd_data_dir(zcashd_datadir: Option<&Path>) -> Result<PathBuf, Error> {
+    match zcashd_datadir {
+        Some(datadir) if starts_with_tilde(datadir) => Err(ErrorKind::Generic
+            .context(fl!(
+                "err-migrate-zcashd-datadir-tilde",
+                path = datadir.display().to_string(),
+            ))
+            .into()),
+        Some(datadir) => Ok(datadir.to_path_buf()),
+        None => Ok(zcashd_default_data_dir().ok_or(ErrorKind::Generic)?),
     }
 }
 
@@ -198,6 +297,48 @@ pub(crate) fn zcashd_default_data_dir() -> Option<PathBuf> {
     }
 }
 
+/// A machine-readable report of how each option in a `zcashd` config file was handled.
+#[derive(Debug, Serialize)]
+struct MigrationReport {
+    /// The path to the `zcashd` config file.
+    conf: String,
+    /// Every option in the config file, in the order they appear.
+    options: Vec<OptionReport>,
+}
+
+impl MigrationReport {
+    /// Returns the warnings for the options that could not be migrated.
+    ///
+    /// A migration with warnings only proceeds if `--allow-warnings` is set.
+    fn warnings(&self) -> impl Iterator<Item = &str> {
+        self.options
+            .iter()
+            .filter_map(|option| match &option.disposition {
+                Disposition::Unsupported { reason } => Some(reason.as_str()),
+                _ => None,
+            })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct OptionReport {
+    option: String,
+    #[serde(flatten)]
+    disposition: Disposition,
+}
+
+/// How a `zcashd` option was handled by the migration.
+#[derive(Debug, Serialize)]
+#[serde(tag = "disposition", rename_all = "snake_case")]
+enum Disposition {
+    /// The option was migrated to the given Zallet config option.
+    Migrated { key: &'static str },
+    /// The option has no effect on Zallet, for the given reason.
+    Ignored { reason: String },
+    /// The option is not supported by Zallet; the reason is shown as a warning.
+    Unsupported { reason: String },
+}
+
 type MapAction = Box<dyn Fn(&mut ZalletConfig, &str) -> Result<(), Error>>;
 type WarnMessage = Box<dyn Fn(&str) -> Option<String>>;
 
@@ -206,13 +347,19 @@ enum Action {
     /// Maps the option to its equivalent Zallet config option.
     MapTo {
         f: MapAction,
+        /// The Zallet config option that the option is migrated to.
+        key: &'static str,
         /// The target Zallet config option, if this is one of a set of related `zcashd` options.
         target: Option<&'static str>,
     },
     /// Maps the multi-valued option to its equivalent Zallet config option.
-    MapMulti(MapAction),
-    /// Silently ignores the option.
-    Ignore,
+    MapMulti {
+        f: MapAction,
+        /// The Zallet config option that the option is migrated to.
+        key: &'static str,
+    },
+    /// Ignores the option, for the given reason.
+    Ignore(&'static str),
     /// Warns the user that the option is not supported in Zallet.
     ///
     /// The warning might be conditional on the configured value of the option.
@@ -222,6 +369,7 @@ enum Action {
 impl Action {
     fn map<T>(
         option: &'static str,
+        key: &'static str,
         f: impl for<'a> Fn(&'a mut ZalletConfig) -> &'a mut Option<T> + 'static,
         v: impl Fn(&str) -> Result<T, ()> + 'static,
     ) -> Option<(&'static str, Self)> {
@@ -236,6 +384,26 @@ impl Action {
                     *f(config) = Some(value);
                     Ok(())
                 }),
+                key,
+                target: None,
+            },
+        ))
+    }
+
+    /// Maps the option onto the config with a custom function.
+    fn map_with(
+        option: &'static str,
+        key: &'static str,
+        f: impl Fn(&mut ZalletConfig, &str) -> Result<(), ()> + 'static,
+    ) -> Option<(&'static str, Self)> {
+        Some((
+            option,
+            Self::MapTo {
+                f: Box::new(move |config, value| match f(config, value) {
+                    Ok(()) => Ok(()),
+                    Err(()) => invalid_option_value(option, value),
+                }),
+                key,
                 target: None,
             },
         ))
@@ -243,9 +411,10 @@ impl Action {
 
     fn map_bool(
         option: &'static str,
+        key: &'static str,
         f: impl for<'a> Fn(&'a mut ZalletConfig) -> &'a mut Option<bool> + 'static,
     ) -> Option<(&'static str, Self)> {
-        Self::map(option, f, |value| match value {
+        Self::map(option, key, f, |value| match value {
             "0" => Ok(false),
             "1" => Ok(true),
             _ => Err(()),
@@ -255,6 +424,7 @@ impl Action {
     /// Maps multiple related boolean flags onto the same config option.
     fn map_related<T>(
         option: &'static str,
+        key: &'static str,
         target: &'static str,
         f: impl for<'a> Fn(&'a mut ZalletConfig) -> &'a mut T + 'static,
         v: impl Fn(&str) -> Result<Option<T>, ()> + 'static,
@@ -271,6 +441,7 @@ impl Action {
                     }
                     Ok(())
                 }),
+                key,
                 target: Some(target),
             },
         ))
@@ -278,24 +449,28 @@ impl Action {
 
     fn map_multi<T>(
         option: &'static str,
+        key: &'static str,
         f: impl for<'a> Fn(&'a mut ZalletConfig) -> &'a mut Vec<T> + 'static,
         v: impl Fn(&str) -> Result<T, ()> + 'static,
     ) -> Option<(&'static str, Self)> {
         Some((
             option,
-            Self::MapMulti(Box::new(move |config, value| {
-                let value = match v(value) {
-                    Ok(v) => Ok(v),
-                    Err(()) => invalid_option_value(option, value),
-                }?;
-                f(config).push(value);
-                Ok(())
-            })),
+            Self::MapMulti {
+                f: Box::new(move |config, value| {
+                    let value = match v(value) {
+                        Ok(v) => Ok(v),
+                        Err(()) => invalid_option_value(option, value),
+                    }?;
+                    f(config).push(value);
+                    Ok(())
+                }),
+                key,
+            },
         ))
     }
 
-    fn ignore(option: &'static str) -> Option<(&'static str, Self)> {
-        Some((option, Action::Ignore))
+    fn ignore(option: &'static str, reason: &'static str) -> Option<(&'static str, Self)> {
+        Some((option, Action::Ignore(reason)))
     }
 
     fn warn(f: impl Fn(&str) -> Option<String> + 'static) -> Self {
@@ -303,6 +478,29 @@ impl Action {
     }
 }
 
+/// Returns the `rpc.auth` entry that `rpcuser` and `rpcpassword` are migrated to.
+///
+/// Whichever of the two options appears first creates the entry, and the other completes
+/// it. Entries migrated from `rpcauth` are always complete.
+fn legacy_rpc_user(config: &mut ZalletConfig) -> &mut RpcAuthSection {
+    let auth = &mut config.rpc.auth;
+    let index = match auth
+        .iter()
+        .position(|auth| auth.user.is_empty() || auth.pwhash.is_none())
+    {
+        Some(index) => index,
+        None => {
+            auth.push(RpcAuthSection {
+                user: String::new(),
+                password: None,
+                pwhash: None,
+            });
+            auth.len() - 1
+        }
+    };
+    &mut auth[index]
+}
+
 fn invalid_option_value<T>(option: &str, value: &str) -> Result<T, Error> {
     Err(ErrorKind::Generic
         .context(fl!(
@@ -322,9 +520,11 @@ fn build_actions() -> HashMap<&'static str, Action> {
                 (value != "0").then(|| fl!("migrate-warn-disablewallet", option = "disablewallet"))
             }),
         )))
-        // Zallet does not support "bare" transparent keys, so there is no need to
-        // maintain a keypool for performance.
-        .chain(Action::ignore("keypool"))
+        .chain(Action::ignore(
+            "keypool",
+            "Zallet does not support \"bare\" transparent keys, so there is no need to \
+             maintain a keypool for performance.",
+        ))
         .chain(Some((
             "migration",
             Action::warn(|_| Some(fl!("migrate-warn-sprout-migration", option = "migration"))),
@@ -340,6 +540,7 @@ fn build_actions() -> HashMap<&'static str, Action> {
         )))
         .chain(Action::map(
             "orchardactionlimit",
+            "builder.limits.orchard_actions",
             |config| &mut config.builder.limits.orchard_actions,
             |value| value.parse().map_err(|_| ()),
         ))
@@ -367,37 +568,50 @@ fn build_actions() -> HashMap<&'static str, Action> {
                 ))
             }),
         )))
-        .chain(Action::map_bool("spendzeroconfchange", |config| {
-            &mut config.builder.spend_zeroconf_change
-        }))
-        .chain(Action::map(
-            "txexpirydelta",
-            |config| &mut config.builder.tx_expiry_delta,
-            |value| match value.parse() {
-                // Minimum is `TX_EXPIRING_SOON_THRESHOLD + 1`.
-                Ok(0..=3) => Err(()),
-                Ok(n @ 4..) => Ok(n),
-                Err(_) => Err(()),
-            },
+        .chain(Action::map_bool(
+            "spendzeroconfchange",
+            "builder.spend_zeroconf_change",
+            |config| &mut config.builder.spend_zeroconf_change,
         ))
+        .chain(Some((
+            "txexpirydelta",
+            Action::warn(|_| {
+                Some(fl!(
+                    "migrate-warn-txexpirydelta",
+                    option = "txexpirydelta",
+                    default = DEFAULT_TX_EXPIRY_DELTA,
+                ))
+            }),
+        )))
         // TODO: Decide if we want to distinguish between database migrations (which we
         // currently require) and wallet format upgrades.
-        .chain(Action::ignore("upgradewallet"))
+        .chain(Action::ignore(
+            "upgradewallet",
+            "Zallet upgrades its wallet database automatically.",
+        ))
         // TODO: Decide whether we want to allow renaming the `WalletDb` backing file.
-        .chain(Action::ignore("wallet"))
-        .chain(Action::map_bool("walletbroadcast", |config| {
-            &mut config.external.broadcast
-        }))
+        .chain(Action::ignore(
+            "wallet",
+            "Zallet stores its wallet in its own database, configured with `database.wallet`.",
+        ))
+        .chain(Action::map_bool(
+            "walletbroadcast",
+            "external.broadcast",
+            |config| &mut config.external.broadcast,
+        ))
         // TODO: Decide if we want to change how this is configured, or do anything to
         // improve security.
         .chain(Action::map(
             "walletnotify",
+            "external.notify",
             |config| &mut config.external.notify,
             |value| Ok(value.into()),
         ))
-        .chain(Action::map_bool("walletrequirebackup", |config| {
-            &mut config.keystore.require_backup
-        }))
+        .chain(Action::map_bool(
+            "walletrequirebackup",
+            "keystore.require_backup",
+            |config| &mut config.keystore.require_backup,
+        ))
         .chain(Some((
             "zapwallettxes",
             Action::warn(|_| {
@@ -411,8 +625,10 @@ fn build_actions() -> HashMap<&'static str, Action> {
 
     // Documented wallet debugging/testing options.
     let documented_wallet_debug_options = iter::empty()
-        // `dblogsize` doesn't have an SQLite analogue.
-        .chain(Action::ignore("dblogsize"))
+        .chain(Action::ignore(
+            "dblogsize",
+            "Zallet's SQLite database has no analogue of this option.",
+        ))
         // `flushwallet` does have SQLite analogues, but they have different semantics, so
         // we aren't migrating this across by default.
         .chain(Some((
@@ -428,36 +644,74 @@ fn build_actions() -> HashMap<&'static str, Action> {
             }),
         )))
         // TODO: Figure out if SQLite has an analogue for BDB's `DB_PRIVATE`.
-        .chain(Action::ignore("privdb"));
+        .chain(Action::ignore(
+            "privdb",
+            "Zallet's SQLite database has no analogue of this option.",
+        ));
 
     // Undocumented options used only by wallet code.
     let undocumented_wallet_options = iter::empty()
         // TODO: Decide if we want to map this to the eventual "untrusted confirmations"
         // setting for the improved note selection logic.
-        .chain(Action::ignore("anchorconfirmations"))
-        // Unsupported in `zcashd` since 5.5.0.
-        .chain(Action::ignore("mintxfee"))
+        .chain(Action::ignore(
+            "anchorconfirmations",
+            "Zallet selects anchors based on `builder.trusted_confirmations` and \
+             `builder.untrusted_confirmations`.",
+        ))
+        .chain(Action::ignore(
+            "mintxfee",
+            "Unsupported in `zcashd` since 5.5.0.",
+        ))
         // TODO: Determine whether we need this for regtest testing of Zallet.
-        .chain(Action::ignore("regtestwalletsetbestchaineveryblock"))
-        // Unsupported in `zcashd` since 5.5.0.
-        .chain(Action::ignore("sendfreetransactions"))
-        // Unsupported in `zcashd` since 5.5.0.
-        .chain(Action::ignore("txconfirmtarget"));
+        .chain(Action::ignore(
+            "regtestwalletsetbestchaineveryblock",
+            "Testing option that Zallet does not need.",
+        ))
+        .chain(Action::ignore(
+            "sendfreetransactions",
+            "Unsupported in `zcashd` since 5.5.0.",
+        ))
+        .chain(Action::ignore(
+            "txconfirmtarget",
+            "Unsupported in `zcashd` since 5.5.0.",
+        ));
 
     // Node options used directly by wallet code.
     let node_options_direct_wallet = iter::empty()
-        // Experimental feature we aren't migrating.
-        .chain(Action::ignore("developerencryptwallet"))
-        // Used to re-enable CPU mining after disabling it during proving.
-        // Irrelevant for Zallet which doesn't include mining.
-        .chain(Action::ignore("genproclimit"))
-        // TODO: Figure out where this was used, and if we want to keep it.
-        .chain(Action::ignore("maxtxfee"))
-        // Used to check whether the configured miner address exists in the wallet.
-        // Irrelevant for Zallet which doesn't include mining.
-        .chain(Action::ignore("mineraddress"))
-        // Experimental feature we aren't migrating.
-        .chain(Action::ignore("paymentdisclosure"))
+        .chain(Action::ignore(
+            "developerencryptwallet",
+            "Experimental `zcashd` feature that is not migrated; see `zallet init-wallet-encryption`.",
+        ))
+        .chain(Action::ignore(
+            "genproclimit",
+            "Only used to re-enable CPU mining, which Zallet does not include.",
+        ))
+        .chain(Action::map(
+            "maxtxfee",
+            "builder.max_fee",
+            |config| &mut config.builder.max_fee,
+            // `zcashd` takes the fee in ZEC.
+            |value| {
+                let fee = value
+                    .parse::<Decimal>()
+                    .ok()
+                    .and_then(|fee| fee.checked_mul(Decimal::from(COIN)))
+                    .ok_or(())?;
+                if fee.is_sign_negative() || !fee.fract().is_zero() {
+                    return Err(());
+                }
+                fee.to_u64().ok_or(())
+            },
+        ))
+        .chain(Action::ignore(
+            "mineraddress",
+            "Only used to check the miner address against the wallet; Zallet does not \
+             include mining.",
+        ))
+        .chain(Action::ignore(
+            "paymentdisclosure",
+            "Experimental `zcashd` feature that is not migrated.",
+        ))
         .chain(Some((
             "preferredtxversion",
             Action::warn(|value| {
@@ -472,45 +726,71 @@ fn build_actions() -> HashMap<&'static str, Action> {
     // Node options used indirectly by the `zcashd` wallet (such as in common ambient
     // infrastructure that is being replicated in Zallet).
     let node_options_indirect_wallet = iter::empty()
-        // This is likely the file we're migrating from; we don't want its name or path.
-        .chain(Action::ignore("conf"))
+        .chain(Action::ignore(
+            "conf",
+            "This is likely the file being migrated from.",
+        ))
         .chain(Some((
             "daemon",
             Action::warn(|value| {
                 (value != "0").then(|| fl!("migrate-warn-daemon", option = "daemon"))
             }),
         )))
-        // We don't want to store Zallet data in the same folder as `zcashd` data.
-        .chain(Action::ignore("datadir"))
-        // The logging systems of `zcashd` and Zallet differ sufficiently that we don't
-        // try to map existing log targets across.
-        .chain(Action::ignore("debug"))
-        // We aren't going to migrate over the `zcashd` wallet's experimental features
-        // compatibly. If we add a similar framework to Zallet it will be for from-scratch
-        // features.
-        .chain(Action::ignore("experimentalfeatures"))
+        .chain(Action::ignore(
+            "datadir",
+            "Zallet data is not stored in the same folder as `zcashd` data.",
+        ))
+        .chain(Action::ignore(
+            "debug",
+            "The logging systems of `zcashd` and Zallet differ sufficiently that log \
+             targets are not migrated.",
+        ))
+        .chain(Action::ignore(
+            "experimentalfeatures",
+            "Experimental `zcashd` features are not migrated.",
+        ))
         .chain(Action::map(
             "exportdir",
+            "external.export_dir",
             |config| &mut config.external.export_dir,
             |value| Ok(value.into()),
         ))
         .chain(Action::map_multi(
             "nuparams",
+            "consensus.regtest_nuparams",
             |config| &mut config.consensus.regtest_nuparams,
             |value| RegTestNuParam::try_from(value.to_string()).map_err(|_| ()),
         ))
         .chain(Action::map_related(
             "regtest",
+            "consensus.network",
             "network",
             |config| &mut config.consensus.network,
             |value| Ok((value == "1").then_some(zcash_protocol::consensus::NetworkType::Regtest)),
         ))
-        // TODO: Support mapping multi-arg options.
-        .chain(Action::ignore("rpcallowip"))
-        // Unsupported in `zcashd` since 1.0.0-beta1.
-        .chain(Action::ignore("rpcasyncthreads"))
+        // `zcashd` accepts subnets with either a prefix length or a netmask.
+        .chain(Action::map_multi(
+            "rpcallowip",
+            "rpc.allow_ip",
+            |config| &mut config.rpc.allow_ip,
+            |value| match value.split_once('/') {
+                Some((addr, netmask)) if netmask.contains(['.', ':']) => {
+                    let addr = addr.parse().map_err(|_| ())?;
+                    let netmask = netmask.parse().map_err(|_| ())?;
+                    IpNet::with_netmask(addr, netmask)
+                        .map(AllowedIp::from)
+                        .map_err(|_| ())
+                }
+                _ => AllowedIp::try_from(value.to_string()).map_err(|_| ()),
+            },
+        ))
+        .chain(Action::ignore(
+            "rpcasyncthreads",
+            "Unsupported in `zcashd` since 1.0.0-beta1.",
+        ))
         .chain(Action::map_multi(
             "rpcauth",
+            "rpc.auth",
             |config| &mut config.rpc.auth,
             |value| {
                 let (username, pwhash) = value.split_once(':').ok_or(())?;
@@ -521,16 +801,46 @@ fn build_actions() -> HashMap<&'static str, Action> {
                 })
             },
         ))
-        // TODO: Need to check how this interacts with `rpcport`; we may instead need a
-        // warning here and no migration.
+        // `zcashd` accepts `rpcbind` values with or without a port.
         .chain(Action::map_multi(
             "rpcbind",
+            "rpc.bind",
             |config| &mut config.rpc.bind,
             // TODO: Decide on a default Zallet JSON-RPC port.
-            |value| format!("{}:{}", value, 8234).parse().map_err(|_| ()),
+            |value| {
+                value
+                    .parse()
+                    .or_else(|_| format!("{}:{}", value, 8234).parse())
+                    .map(RpcBind::Tcp)
+                    .map_err(|_| ())
+            },
+        ))
+        // The password is migrated as a salted hash, so that it does not appear in the
+        // Zallet config in plaintext.
+        .chain(Action::map_with(
+            "rpcpassword",
+            "rpc.auth",
+            |config, value| {
+                if value.is_empty() {
+                    return Err(());
+                }
+                legacy_rpc_user(config).pwhash = Some(PasswordHash::from_bare(value).to_string());
+                Ok(())
+            },
+        ))
+        .chain(Action::map_with("rpcuser", "rpc.auth", |config, value| {
+            if value.is_empty() || value.contains(':') {
+                return Err(());
+            }
+            legacy_rpc_user(config).user = value.into();
+            Ok(())
+        }))
+        .chain(Action::map(
+            "rpccookiefile",
+            "rpc.cookie_file",
+            |config| &mut config.rpc.cookie_file,
+            |value| Ok(value.into()),
         ))
-        // TODO
-        .chain(Action::ignore("rpccookiefile"))
         .chain(Some((
             "rpcport",
             Action::warn(|_| {
@@ -544,39 +854,114 @@ fn build_actions() -> HashMap<&'static str, Action> {
         )))
         .chain(Action::map(
             "rpcservertimeout",
+            "rpc.timeout",
             |config| &mut config.rpc.timeout,
             |value| value.parse().map_err(|_| ()),
         ))
-        // Unsupported in `zcashd` since 1.0.8.
-        .chain(Action::ignore("rpcssl"))
-        // `jsonrpsee` is an async JSON-RPC implementation, so it doesn't have dedicated
-        // threads; instead it shares the Tokio worker thread pool.
-        .chain(Action::ignore("rpcthreads"))
+        .chain(Action::ignore(
+            "rpcssl",
+            "Unsupported in `zcashd` since 1.0.8.",
+        ))
+        .chain(Action::ignore(
+            "rpcthreads",
+            "Zallet's JSON-RPC server shares the Tokio worker thread pool instead of \
+             having dedicated threads.",
+        ))
         // TODO: Not quite the same thing as `ServerBuilder::set_message_buffer_capacity` I think?
-        .chain(Action::ignore("rpcworkqueue"))
+        .chain(Action::ignore(
+            "rpcworkqueue",
+            "Zallet's JSON-RPC server has no equivalent work queue.",
+        ))
         .chain(Action::map_related(
             "testnet",
+            "consensus.network",
             "network",
             |config| &mut config.consensus.network,
             |value| Ok((value == "1").then_some(zcash_protocol::consensus::NetworkType::Test)),
         ));
 
+    // Options that configure clients of the `zcashd` JSON-RPC server.
+    let rpc_client_options = [
+        (
+            "rpcclienttimeout",
+            "Only used by `zcash-cli`; see `zallet rpc --timeout`.",
+        ),
+        ("rpcconnect", "Only used by `zcash-cli`."),
+        ("rpcwait", "Only used by `zcash-cli`."),
+    ];
+
+    // Node network options. Zallet makes no P2P connections of its own; it reaches the
+    // network through its validator, which should be configured instead.
+    let node_network_options = [
+        "addnode",
+        "bind",
+        "connect",
+        "discover",
+        "dns",
+        "dnsseed",
+        "externalip",
+        "forcednsseed",
+        "listen",
+        "listenonion",
+        "maxconnections",
+        "maxreceivebuffer",
+        "maxsendbuffer",
+        "maxuploadtarget",
+        "onion",
+        "onlynet",
+        "port",
+        "proxy",
+        "proxyrandomize",
+        "seednode",
+        "timeout",
+        "tor",
+        "torcontrol",
+        "torpassword",
+        "whitebind",
+        "whitelist",
+        "whitelistforcerelay",
+        "whitelistrelay",
+    ]
+    .map(|option| {
+        (
+            option,
+            "Network option for the node; Zallet makes no P2P connections of its own.",
+        )
+    });
+
+    // Node mempool and relay policy options. Zallet relies on its validator's mempool.
+    let node_mempool_options = [
+        "blocksonly",
+        "limitancestorcount",
+        "limitancestorsize",
+        "limitdescendantcount",
+        "limitdescendantsize",
+        "maxorphantx",
+        "mempoolevictionmemoryminutes",
+        "mempooltxcostlimit",
+        "minrelaytxfee",
+        "txunpaidactionlimit",
+    ]
+    .map(|option| {
+        (
+            option,
+            "Mempool option for the node; Zallet relies on its validator's mempool.",
+        )
+    });
+
     // Node options never used by the `zcashd` wallet. These can be safely ignored if
     // encountered in a `zcashd` config file.
     let node_options_unused_wallet = [
-        "addnode",
         "alertnotify",
         "alerts",
         "allowdeprecated",
         "banscore",
         "bantime",
         "benchmark",
-        "bind",
         "blockmaxsize",
         "blockminsize",
         "blocknotify",
         "blockprioritysize",
-        "blocksonly",
         "blockunpaidactionlimit",
         "blockversion",
         "checkblockindex",
@@ -585,7 +970,6 @@ fn build_actions() -> HashMap<&'static str, Action> {
         "checkmempool",
         "checkpoints",
         "clockoffset",
-        "connect",
         "create",
         "datacarrier",
         "datacarriersize",
@@ -595,14 +979,9 @@ fn build_actions() -> HashMap<&'static str, Action> {
         "debugnet",
         "developersetpoolsizezero",
         "disablesafemode",
-        "discover",
-        "dns",
-        "dnsseed",
         "dropmessagestest",
         "enforcenodebloom",
         "equihashsolver",
-        "externalip",
-        "forcednsseed",
         "fundingstream",
         "fuzzmessagestest",
         "gen",
@@ -613,59 +992,34 @@ fn build_actions() -> HashMap<&'static str, Action> {
         "insightexplorer",
         "json",
         "lightwalletd",
-        "limitancestorcount",
-        "limitancestorsize",
-        "limitdescendantcount",
-        "limitdescendantsize",
-        "listen",
-        "listenonion",
         "loadblock",
         "logips",
         "logtimestamps",
-        "maxconnections",
-        "maxorphantx",
-        "maxreceivebuffer",
-        "maxsendbuffer",
         "maxsigcachesize",
         "maxtipage",
-        "maxuploadtarget",
-        "mempoolevictionmemoryminutes",
-        "mempooltxcostlimit",
         "metricsallowip",
         "metricsbind",
         "metricsrefreshtime",
         "metricsui",
         "minetolocalwallet",
-        "minrelaytxfee",
         "mocktime",
         "nodebug",
         "nurejectoldversions",
-        "onion",
-        "onlynet",
         "optimize-getheaders",
         "par",
         "paramsdir",
         "peerbloomfilters",
         "permitbaremultisig",
         "pid",
-        "port",
         "printalert",
         "printpriority",
         "printtoconsole",
         "prometheusport",
-        "proxy",
-        "proxyrandomize",
         "prune",
         "regtestshieldcoinbase",
         "reindex",
         "reindex-chainstate",
         "rest",
-        "rpcclienttimeout",
-        "rpcconnect",
-        "rpcpassword",
-        "rpcuser",
-        "rpcwait",
-        "seednode",
         "sendalert",
         "server",
         "showmetrics",
@@ -675,20 +1029,12 @@ fn build_actions() -> HashMap<&'static str, Action> {
         "stopafterblockimport",
         "sysperms",
         "testsafemode",
-        "timeout",
-        "tor",
-        "torcontrol",
-        "torpassword",
         "txexpirynotify",
         "txindex",
-        "txunpaidactionlimit",
         "uacomment",
         "version",
-        "whitebind",
-        "whitelist",
-        "whitelistforcerelay",
-        "whitelistrelay",
-    ];
+    ]
+    .map(|option| (option, "Not used by the `zcashd` wallet."));
 
     // Compose in parts to avoid type system recursion limits.
     iter::empty()
@@ -698,9 +1044,12 @@ fn build_actions() -> HashMap<&'static str, Action> {
         .chain(node_options_direct_wallet)
         .chain(node_options_indirect_wallet)
         .chain(
-            node_options_unused_wallet
+            rpc_client_options
                 .into_iter()
-                .filter_map(Action::ignore),
+                .chain(node_network_options)
+                .chain(node_mempool_options)
+                .chain(node_options_unused_wallet)
+                .filter_map(|(option, reason)| Action::ignore(option, reason)),
         )
         .collect()
 }
@@ -20,6 +20,7 @@ use zcash_client_backend::data_api::{
 };
 use zcash_client_sqlite::error::SqliteClientError;
 use zcash_keys::{
+    address::Address,
     encoding::AddressCodec,
     keys::{
         DerivationError, UnifiedFullViewingKey,
@@ -27,17 +28,26 @@ use zcash_keys::{
     },
 };
 use zcash_primitives::transaction::Transaction;
-use zcash_protocol::consensus::{BlockHeight, BranchId, NetworkType, Parameters};
+use zcash_protocol::{
+    TxId,
+    consensus::{BlockHeight, BranchId, NetworkType, Parameters},
+};
 use zewif_zcashd::{BDBDump, ZcashdDump, ZcashdParser, ZcashdWallet};
 use zip32::{AccountId, fingerprint::SeedFingerprint};
 
 use crate::{
     cli::MigrateZcashdWalletCmd,
-    components::{chain::Chain, database::Database, keystore::KeyStore},
+    components::{
+        audit::{self, Event},
+        chain::Chain,
+        database::{AddressLabelOutcome, AddressPurpose, Database},
+        keystore::KeyStore,
+    },
     error::{Error, ErrorKind},
     fl,
     prelude::*,
     rosetta::to_chainstate,
+    sprout,
 };
 
 use super::{AsyncRunnable, migrate_zcash_conf};
@@ -61,37 +71,44 @@ impl AsyncRunnable for MigrateZcashdWalletCmd {
             return Err(ErrorKind::Generic.context(fl!("migrate-alpha-code")).into());
         }
 
+        let _lock = config.lock_datadir()?;
+
         // Start monitoring the chain.
         let (chain, _chain_indexer_task_handle) = Chain::new(&config).await?;
         let db = Database::open(&config).await?;
         let keystore = KeyStore::new(&config, db.clone())?;
 
-        let wallet = self.dump_wallet()?;
+        let (wallet, unrecognized_records) = self.dump_wallet()?;
 
         Self::migrate_zcashd_wallet(
             db,
             keystore,
             chain,
             wallet,
+            unrecognized_records,
             self.buffer_wallet_transactions,
             self.allow_multiple_wallet_imports,
+            self.dry_run,
         )
         .await?;
+        if !self.dry_run {
+            audit::record(Event::WalletKeysImported {
+                source: self.path.clone(),
+            });
+        }
 
         Ok(())
     }
 }
 
 impl MigrateZcashdWalletCmd {
-    fn dump_wallet(&self) -> Result<ZcashdWallet, MigrateError> {
+    /// Parses the `zcashd` wallet file.
+    ///
+    /// Returns the parsed wallet, and the number of records in the wallet file that were
+    /// not recognized.
+    fn dump_wallet(&self) -> Result<(ZcashdWallet, usize), MigrateError> {
         let wallet_path = if self.path.is_relative() {
-            if let Some(datadir) = self.zcashd_datadir.as_ref() {
-                datadir.join(&self.path)
-            } else {
-                migrate_zcash_conf::zcashd_default_data_dir()
-                    .ok_or(MigrateError::Wrapped(ErrorKind::Generic.into()))?
-                    .join(&self.path)
-            }
+            migrate_zcash_conf::zcashd_data_dir(self.zcashd_datadir.as_deref())?.join(&self.path)
         } else {
             self.path.to_path_buf()
         };
@@ -124,7 +141,7 @@ impl MigrateZcashdWalletCmd {
                     }
                 })?;
 
-            let (zcashd_wallet, _unparsed_keys) =
+            let (zcashd_wallet, unparsed_keys) =
                 ZcashdParser::parse_dump(&zcashd_dump, !self.allow_warnings).map_err(|e| {
                     MigrateError::Zewif {
                         error_type: ZewifError::ZcashdDump,
@@ -133,7 +150,7 @@ impl MigrateZcashdWalletCmd {
                     }
                 })?;
 
-            Ok(zcashd_wallet)
+            Ok((zcashd_wallet, unparsed_keys.len()))
         } else {
             Err(MigrateError::Wrapped(
                 ErrorKind::Generic
@@ -210,13 +227,16 @@ impl MigrateZcashdWalletCmd {
             .transpose()
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn migrate_zcashd_wallet(
         db: Database,
         keystore: KeyStore,
         chain: Chain,
         wallet: ZcashdWallet,
+        unrecognized_records: usize,
         buffer_wallet_transactions: bool,
         allow_multiple_wallet_imports: bool,
+        dry_run: bool,
     ) -> Result<(), MigrateError> {
         let mut db_data = db.handle().await?;
         let network_params = *db_data.params();
@@ -243,21 +263,19 @@ impl MigrateZcashdWalletCmd {
             },
         )?;
 
-        let mnemonic_seed_data = match Self::parse_mnemonic(wallet.bip39_mnemonic().mnemonic())? {
-            Some(m) => Some((
-                SecretVec::new(m.to_seed("").to_vec()),
-                keystore.encrypt_and_store_mnemonic(m).await?,
-            )),
-            None => None,
-        };
+        // The mnemonic is only stored once we know that the migration will go ahead.
+        let mnemonic = Self::parse_mnemonic(wallet.bip39_mnemonic().mnemonic())?;
+        let mnemonic_seed_data = mnemonic.as_ref().map(|m| {
+            let seed = SecretVec::new(m.to_seed("").to_vec());
+            let seed_fp = SeedFingerprint::from_seed(seed.expose_secret()).expect("valid length");
+            (seed, seed_fp)
+        });
 
         if !existing_zcash_sourced_accounts.is_empty() {
             if allow_multiple_wallet_imports {
-                if let Some((seed, _)) = mnemonic_seed_data.as_ref() {
-                    let seed_fp =
-                        SeedFingerprint::from_seed(seed.expose_secret()).expect("valid length");
-                    if existing_zcash_sourced_accounts.contains(&seed_fp) {
-                        return Err(MigrateError::DuplicateImport(seed_fp));
+                if let Some((_, seed_fp)) = mnemonic_seed_data.as_ref() {
+                    if existing_zcash_sourced_accounts.contains(seed_fp) {
+                        return Err(MigrateError::DuplicateImport(*seed_fp));
                     }
                 }
             } else {
@@ -273,9 +291,27 @@ impl MigrateZcashdWalletCmd {
             .activation_height(zcash_protocol::consensus::NetworkUpgrade::Sapling)
             .expect("Sapling activation height is defined.");
 
+        // Zallet cannot spend Sprout notes, but we find the notes received by the wallet's
+        // Sprout keys so that their value remains visible.
+        // TODO: Move this into zewif-zcashd once we're out of dependency version hell.
+        let sprout_keys = wallet
+            .sprout_keys()
+            .map(|keys| {
+                keys.keypairs()
+                    .filter_map(|key| {
+                        sprout::SpendingKey::from_bytes(*key.spending_key().as_bytes())
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let mut sprout_notes = vec![];
+        let mut sprout_spends = vec![];
+
         // Collect an index from txid to block height for all transactions known to the wallet that
         // appear in the main chain.
         let mut tx_heights = HashMap::new();
+        let mut txs_not_found = 0;
+        let mut txs_not_fetched = 0;
         for (txid, _) in wallet.transactions().iter() {
             let tx_filter = TxFilter {
                 hash: txid.as_ref().to_vec(),
@@ -292,6 +328,16 @@ impl MigrateZcashdWalletCmd {
                                 err = e.to_string()
                             ))
                         })?);
+                    if !sprout_keys.is_empty() {
+                        let txid =
+                            TxId::from_bytes(txid.as_ref().try_into().expect("txids are 32 bytes"));
+                        sprout_notes.extend(sprout::received_notes(
+                            txid,
+                            &raw_tx.data,
+                            &sprout_keys,
+                        )?);
+                        sprout_spends.push((txid, sprout::nullifiers(&raw_tx.data)?));
+                    }
                     tx_heights.insert(
                         txid,
                         (tx_height, buffer_wallet_transactions.then_some(raw_tx)),
@@ -301,11 +347,13 @@ impl MigrateZcashdWalletCmd {
                     if (status.code() as isize) == (tonic::Code::NotFound as isize) =>
                 {
                     // Ignore any transactions that are not in the main chain.
+                    txs_not_found += 1;
                 }
                 other => {
                     // FIXME: we should be able to propagate this error, but at present Zaino is
                     // returning all sorts of errors as 500s.
                     dbg!(other);
+                    txs_not_fetched += 1;
                 }
             }
         }
@@ -316,27 +364,114 @@ impl MigrateZcashdWalletCmd {
         // range. We don't have a good source of individual per-account birthday information at
         // this point; once we've imported all of the transaction data into the wallet then we'll
         // be able to choose per-account birthdays without difficulty.
-        let wallet_birthday = Self::get_birthday(
-            &chain_subscriber,
-            // Fall back to the chain tip height, and then Sapling activation as a last resort. If
-            // we have a birthday height, max() that with sapling activation; that will be the
-            // minimum possible wallet birthday that is relevant to future recovery scenarios.
-            tx_heights
-                .values()
-                .map(|(h, _)| h)
-                .min()
-                .copied()
-                .or(chain_tip)
-                .map_or(sapling_activation, |h| std::cmp::max(h, sapling_activation)),
-            chain_tip,
-        )
-        .await?;
+        //
+        // If we can't tell when the wallet was first used, we fall back to Sapling activation,
+        // which is the minimum possible wallet birthday that is relevant to future recovery
+        // scenarios. In particular, we don't fall back to the chain tip: the keys may have
+        // received funds after `zcashd` last synced the wallet file.
+        let earliest_tx_height = tx_heights.values().map(|(h, _)| h).min().copied();
+        let (birthday_height, birthday_source) = match earliest_tx_height {
+            _ if txs_not_fetched > 0 => {
+                (sapling_activation, BirthdaySource::TransactionsNotFetched)
+            }
+            Some(h) => (
+                std::cmp::max(h, sapling_activation),
+                BirthdaySource::EarliestTransaction,
+            ),
+            None => (sapling_activation, BirthdaySource::NoTransactions),
+        };
+
+        let mnemonic_seed_fp = mnemonic_seed_data.as_ref().map(|(_, fp)| *fp);
+        let legacy_seed_fp = wallet
+            .legacy_hd_seed()
+            .map(|d| {
+                SeedFingerprint::from_seed(d.seed_data()).ok_or_else(|| {
+                    ErrorKind::Generic.context(fl!("err-failed-seed-fingerprinting"))
+                })
+            })
+            .transpose()?;
+
+        // Migrate the address book. `zcashd` labels both the addresses of counterparties
+        // (with the "send" purpose) and its own receiving addresses (with the "receive"
+        // purpose); other purposes are treated as "send". Entries without a label carry
+        // no information, and are skipped.
+        let mut address_book = wallet
+            .address_names()
+            .iter()
+            .filter(|(_, label)| !label.is_empty())
+            .map(|(address, label)| {
+                let purpose = match wallet.address_purposes().get(address) {
+                    Some(purpose) if purpose.as_str() == "receive" => AddressPurpose::Receive,
+                    _ => AddressPurpose::Send,
+                };
+                (address.to_string(), label.as_str(), purpose)
+            })
+            .collect::<Vec<_>>();
+        address_book.sort();
+        let unlabelled_addresses = wallet.address_names().len() - address_book.len();
+        let mut skipped_addresses = vec![];
+        address_book.retain(|(address, label, _)| {
+            let valid = Address::decode(&network_params, address).is_some();
+            if !valid {
+                skipped_addresses.push(fl!(
+                    "migrate-wallet-address-book-skipped",
+                    address = address.as_str(),
+                    label = *label,
+                ));
+            }
+            valid
+        });
+
+        let mut report = MigrationReport {
+            mnemonic: mnemonic.is_some(),
+            legacy_seed: legacy_seed_fp.is_some(),
+            unified_accounts: wallet.unified_accounts().account_metadata.len(),
+            sapling_keys: [0; 3],
+            transparent_keys: wallet.keys().keypairs().count(),
+            address_book_labels: address_book.len(),
+            transactions: tx_heights.len(),
+            birthday_height,
+            birthday_source,
+            sprout_keys: sprout_keys.len(),
+            unlabelled_addresses,
+            skipped_addresses,
+            txs_not_found,
+            txs_not_fetched,
+            unrecognized_records,
+        };
+        for key in wallet.sapling_keys().keypairs() {
+            let key_seed_fp = key
+                .metadata()
+                .seed_fp()
+                .map(|seed_fp_bytes| SeedFingerprint::from_bytes(*seed_fp_bytes.as_bytes()));
+            let category = match key_seed_fp {
+                Some(fp) if Some(fp) == mnemonic_seed_fp => 0,
+                Some(fp) if Some(fp) == legacy_seed_fp => 1,
+                _ => 2,
+            };
+            report.sapling_keys[category] += 1;
+        }
+        report.print(dry_run);
+
+        if dry_run {
+            return Ok(());
+        }
+
+        if let Some(m) = mnemonic {
+            keystore.encrypt_and_store_mnemonic(m).await?;
+            // The `zcashd` wallet file is itself a backup of the mnemonic.
+            keystore
+                .confirm_backup(&mnemonic_seed_fp.expect("present for mnemonic"))
+                .await?;
+        }
+
+        let wallet_birthday =
+            Self::get_birthday(&chain_subscriber, birthday_height, chain_tip).await?;
         info!(
             "Setting the wallet birthday to height {}",
             wallet_birthday.height(),
         );
 
-        let mnemonic_seed_fp = mnemonic_seed_data.as_ref().map(|(_, fp)| *fp);
         let legacy_transparent_account_uuid = if let Some((seed, _)) = mnemonic_seed_data.as_ref() {
             // If there are any legacy transparent keys, create the legacy account.
             if !wallet.keys().is_empty() {
@@ -370,12 +505,11 @@ impl MigrateZcashdWalletCmd {
         };
 
         let legacy_seed_data = match wallet.legacy_hd_seed() {
-            Some(d) => Some((
-                SecretVec::new(d.seed_data().to_vec()),
-                keystore
-                    .encrypt_and_store_legacy_seed(&SecretVec::new(d.seed_data().to_vec()))
-                    .await?,
-            )),
+            Some(d) => {
+                let seed = SecretVec::new(d.seed_data().to_vec());
+                keystore.encrypt_and_store_legacy_seed(&seed).await?;
+                Some(seed)
+            }
             None => None,
         };
         let legacy_transparent_account_uuid =
@@ -385,7 +519,7 @@ impl MigrateZcashdWalletCmd {
                     // account, so we don't need to do anything.
                     Some(uuid)
                 }
-                (None, Some((seed, _))) if !wallet.keys().is_empty() => {
+                (None, Some(seed)) if !wallet.keys().is_empty() => {
                     // In this case, we have the legacy seed, but no mnemonic seed was ever derived
                     // from it, so this is a pre-v4.7.0 wallet. We construct the mnemonic in the same
                     // fashion as zcashd, by using the legacy seed as entropy in the generation of the
@@ -396,7 +530,9 @@ impl MigrateZcashdWalletCmd {
                         .ok_or(ErrorKind::Generic.context(fl!("err-failed-seed-fingerprinting")))?;
 
                     let seed = SecretVec::new(mnemonic.to_seed("").to_vec());
-                    keystore.encrypt_and_store_mnemonic(mnemonic).await?;
+                    let seed_fp = keystore.encrypt_and_store_mnemonic(mnemonic).await?;
+                    // The mnemonic can be re-derived from the `zcashd` wallet file.
+                    keystore.confirm_backup(&seed_fp).await?;
                     let (account, _) = db_data.import_account_hd(
                         &format!(
                             "zcashd post-v4.7.0 legacy transparent account {}",
@@ -413,8 +549,6 @@ impl MigrateZcashdWalletCmd {
                 _ => None,
             };
 
-        let legacy_seed_fp = legacy_seed_data.map(|(_, fp)| fp);
-
         // Add unified accounts. The only source of unified accounts in zcashd is derivation from
         // the mnemonic seed.
         if wallet.unified_accounts().account_metadata.is_empty() {
@@ -560,7 +694,7 @@ impl MigrateZcashdWalletCmd {
             );
 
             keystore
-                .encrypt_and_store_standalone_transparent_key(&key)
+                .encrypt_and_store_standalone_transparent_key(key.secret())
                 .await?;
 
             db_data.import_standalone_transparent_pubkey(
@@ -569,6 +703,58 @@ impl MigrateZcashdWalletCmd {
             )?;
         }
 
+        info!("Importing {} address book entries", address_book.len());
+        let mut labels_added = 0;
+        let mut address_book_notes = vec![];
+        for (address, label, purpose) in address_book {
+            match db_data
+                .add_address_label(&address, label, purpose)
+                .map_err(SqliteClientError::from)?
+            {
+                AddressLabelOutcome::Added => labels_added += 1,
+                AddressLabelOutcome::Unchanged => (),
+                AddressLabelOutcome::Conflict { existing_label } => address_book_notes.push(fl!(
+                    "migrate-wallet-address-book-conflict",
+                    address = address,
+                    label = label,
+                    existing_label = existing_label,
+                )),
+            }
+        }
+        println!(
+            "{}",
+            fl!("migrate-wallet-address-book", count = labels_added)
+        );
+        for note in address_book_notes {
+            println!("- {note}");
+        }
+
+        if !sprout_keys.is_empty() {
+            info!("Importing Sprout notes");
+            db_data
+                .record_sprout_notes(&sprout_notes)
+                .map_err(SqliteClientError::from)?;
+            for (txid, nullifiers) in &sprout_spends {
+                db_data
+                    .record_sprout_spends(txid, nullifiers)
+                    .map_err(SqliteClientError::from)?;
+            }
+            if let Some(balance) = db_data
+                .sprout_balance()
+                .map_err(SqliteClientError::from)?
+                .filter(|balance| balance.notes > 0)
+            {
+                println!(
+                    "{}",
+                    fl!(
+                        "migrate-warn-sprout-funds",
+                        count = balance.notes,
+                        value = balance.value.into_u64(),
+                    )
+                );
+            }
+        }
+
         // Since we've retrieved the raw transaction data anyway, preemptively store it for faster
         // access to balance & to set priorities in the scan queue.
         if buffer_wallet_transactions {
@@ -590,6 +776,143 @@ impl MigrateZcashdWalletCmd {
     }
 }
 
+/// How the wallet birthday was chosen.
+#[derive(Clone, Copy, Debug)]
+enum BirthdaySource {
+    /// The height at which the earliest of the wallet's transactions was mined.
+    EarliestTransaction,
+    /// None of the wallet's transactions are in the main chain.
+    NoTransactions,
+    /// Some of the wallet's transactions could not be fetched from the chain source.
+    TransactionsNotFetched,
+}
+
+/// A summary of the contents of a `zcashd` wallet file, and of what will be migrated.
+#[derive(Debug)]
+struct MigrationReport {
+    mnemonic: bool,
+    legacy_seed: bool,
+    unified_accounts: usize,
+    /// The number of Sapling keys derived from the mnemonic seed, derived from the legacy
+    /// HD seed, and imported into `zcashd` with `z_importkey`.
+    sapling_keys: [usize; 3],
+    transparent_keys: usize,
+    address_book_labels: usize,
+    transactions: usize,
+    birthday_height: BlockHeight,
+    birthday_source: BirthdaySource,
+    sprout_keys: usize,
+    unlabelled_addresses: usize,
+    /// Descriptions of the address book entries that will not be migrated.
+    skipped_addresses: Vec<String>,
+    txs_not_found: usize,
+    txs_not_fetched: usize,
+    unrecognized_records: usize,
+}
+
+impl MigrationReport {
+    fn print(&self, dry_run: bool) {
+        if dry_run {
+            println!("{}", fl!("migrate-wallet-report-dry-run"));
+        } else {
+            println!("{}", fl!("migrate-wallet-report"));
+        }
+
+        let mut migrated = vec![];
+        if self.mnemonic {
+            migrated.push(fl!("migrate-wallet-report-mnemonic"));
+        }
+        if self.legacy_seed {
+            migrated.push(fl!("migrate-wallet-report-legacy-seed"));
+        }
+        migrated.push(fl!(
+            "migrate-wallet-report-unified-accounts",
+            count = self.unified_accounts,
+        ));
+        let [mnemonic, legacy, standalone] = self.sapling_keys;
+        migrated.push(fl!(
+            "migrate-wallet-report-sapling-keys",
+            count = mnemonic + legacy + standalone,
+            mnemonic = mnemonic,
+            legacy = legacy,
+            standalone = standalone,
+        ));
+        migrated.push(fl!(
+            "migrate-wallet-report-transparent-keys",
+            count = self.transparent_keys,
+        ));
+        migrated.push(fl!(
+            "migrate-wallet-report-address-book",
+            count = self.address_book_labels,
+        ));
+        migrated.push(fl!(
+            "migrate-wallet-report-transactions",
+            count = self.transactions,
+        ));
+        for line in migrated {
+            println!("- {line}");
+        }
+
+        let height = u32::from(self.birthday_height);
+        println!(
+            "{}",
+            match self.birthday_source {
+                BirthdaySource::EarliestTransaction => {
+                    fl!("migrate-wallet-report-birthday", height = height)
+                }
+                BirthdaySource::NoTransactions => {
+                    fl!("migrate-wallet-report-birthday-no-txs", height = height)
+                }
+                BirthdaySource::TransactionsNotFetched => {
+                    fl!(
+                        "migrate-wallet-report-birthday-txs-not-fetched",
+                        height = height
+                    )
+                }
+            }
+        );
+
+        let mut skipped = vec![];
+        if self.sprout_keys > 0 {
+            skipped.push(fl!(
+                "migrate-wallet-report-skipped-sprout-keys",
+                count = self.sprout_keys,
+            ));
+        }
+        if self.unlabelled_addresses > 0 {
+            skipped.push(fl!(
+                "migrate-wallet-report-skipped-unlabelled",
+                count = self.unlabelled_addresses,
+            ));
+        }
+        skipped.extend(self.skipped_addresses.iter().cloned());
+        if self.txs_not_found > 0 {
+            skipped.push(fl!(
+                "migrate-wallet-report-skipped-txs-not-found",
+                count = self.txs_not_found,
+            ));
+        }
+        if self.txs_not_fetched > 0 {
+            skipped.push(fl!(
+                "migrate-wallet-report-skipped-txs-not-fetched",
+                count = self.txs_not_fetched,
+            ));
+        }
+        if self.unrecognized_records > 0 {
+            skipped.push(fl!(
+                "migrate-wallet-report-skipped-unrecognized",
+                count = self.unrecognized_records,
+            ));
+        }
+        if !skipped.is_empty() {
+            println!("{}", fl!("migrate-wallet-report-skipped"));
+            for line in skipped {
+                println!("- {line}");
+            }
+        }
+    }
+}
+
 impl Runnable for MigrateZcashdWalletCmd {
     fn run(&self) {
         self.run_on_runtime();
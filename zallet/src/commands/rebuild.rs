@@ -0,0 +1,509 @@
+//! `rebuild` subcommand
+
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use abscissa_core::Runnable;
+use rusqlite::{OpenFlags, named_params, types::Value};
+use zaino_state::FetchServiceSubscriber;
+use zcash_client_backend::{
+    data_api::{Account as _, AccountBirthday, AccountPurpose, WalletWrite, Zip32Derivation},
+    proto::service::TreeState,
+};
+use zcash_keys::keys::UnifiedFullViewingKey;
+use zcash_protocol::consensus::{BlockHeight, NetworkType, NetworkUpgrade, Parameters};
+use zip32::fingerprint::SeedFingerprint;
+
+use crate::{
+    cli::RebuildCmd,
+    commands::AsyncRunnable,
+    components::{
+        chain::Chain,
+        database::{Database, DbHandle},
+        keystore::KeyStore,
+    },
+    error::{Error, ErrorKind},
+    fl,
+    prelude::*,
+};
+
+/// The keystore tables, which are copied as-is from the damaged database.
+///
+/// Their contents are encrypted, so they are copied without needing to access the
+/// wallet's age identities.
+const KEYSTORE_TABLES: &[&str] = &[
+    "ext_zallet_keystore_age_recipients",
+    "ext_zallet_keystore_mnemonics",
+    "ext_zallet_keystore_legacy_seeds",
+    "ext_zallet_keystore_standalone_sapling_keys",
+    "ext_zallet_keystore_standalone_transparent_keys",
+];
+
+/// An account recorded in the damaged database.
+struct RecordedAccount {
+    uuid: Vec<u8>,
+    name: Option<String>,
+    ufvk: Option<String>,
+    seed_fingerprint: Option<[u8; 32]>,
+    account_index: Option<u32>,
+    birthday_height: u32,
+    has_spend_key: bool,
+    key_source: Option<String>,
+}
+
+impl RecordedAccount {
+    /// Returns the name of the account for display to the user.
+    fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => fl!("cmd-rebuild-unnamed-account"),
+        }
+    }
+}
+
+impl AsyncRunnable for RebuildCmd {
+    async fn run(&self) -> Result<(), Error> {
+        let config = APP.config();
+        let _lock = config.lock_datadir()?;
+
+        let db_path = config.wallet_db_path();
+        if !db_path.exists() {
+            return Err(ErrorKind::Generic
+                .context(fl!(
+                    "err-rebuild-no-database",
+                    path = db_path.display().to_string(),
+                ))
+                .into());
+        }
+
+        // Move the damaged database aside, so that nothing in it is lost.
+        let damaged_path = move_aside(&db_path)?;
+        println!(
+            "{}",
+            fl!(
+                "cmd-rebuild-moved-aside",
+                path = damaged_path.display().to_string(),
+            )
+        );
+
+        let damaged = rusqlite::Connection::open_with_flags(
+            &damaged_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        );
+        if let Err(e) = &damaged {
+            println!("- {}", fl!("cmd-rebuild-unopenable", error = e.to_string()));
+        }
+
+        let (chain, _chain_indexer_task_handle) = Chain::new(&config).await?;
+        let chain = chain.subscribe().await?.inner();
+
+        println!("{}", fl!("cmd-rebuild-creating"));
+        let db = Database::open(&config).await?;
+        let mut wallet = db.handle().await?;
+
+        println!("{}", fl!("cmd-rebuild-restoring-keystore"));
+        let mut problems = 0;
+        for table in KEYSTORE_TABLES {
+            let copied = damaged
+                .as_ref()
+                .map_err(|e| e.to_string())
+                .and_then(|damaged| copy_table(damaged, &wallet, table).map_err(|e| e.to_string()));
+            match copied {
+                Ok(rows) => println!(
+                    "- {}",
+                    fl!("cmd-rebuild-restored-table", rows = rows, table = *table)
+                ),
+                Err(e) => {
+                    println!(
+                        "- {}",
+                        fl!(
+                            "cmd-rebuild-table-failed",
+                            table = *table,
+                            error = e.to_string(),
+                        )
+                    );
+                    problems += 1;
+                }
+            }
+        }
+
+        println!("{}", fl!("cmd-rebuild-recreating-accounts"));
+        let accounts = damaged
+            .as_ref()
+            .map_err(|e| e.to_string())
+            .and_then(|damaged| recorded_accounts(damaged).map_err(|e| e.to_string()));
+        let recreated = match accounts {
+            Ok(accounts) => {
+                let mut recreated = 0;
+                for account in accounts {
+                    match account {
+                        Ok(account) => {
+                            match recreate_account(&mut wallet, &chain, &account).await {
+                                Ok(()) => recreated += 1,
+                                Err(e) => {
+                                    println!(
+                                        "- {}",
+                                        fl!(
+                                            "cmd-rebuild-account-failed",
+                                            account = account.display_name(),
+                                            error = e.to_string(),
+                                        )
+                                    );
+                                    problems += 1;
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            println!(
+                                "- {}",
+                                fl!("cmd-rebuild-account-unreadable", error = e.to_string())
+                            );
+                            problems += 1;
+                        }
+                    }
+                }
+                recreated
+            }
+            Err(e) => {
+                println!(
+                    "- {}",
+                    fl!("cmd-rebuild-accounts-unreadable", error = e.to_string())
+                );
+                self.recreate_from_seeds(&config, db.clone(), &mut wallet, &chain)
+                    .await?
+            }
+        };
+        println!("{}", fl!("cmd-rebuild-recreated", accounts = recreated));
+
+        println!();
+        println!(
+            "{}",
+            fl!(
+                "cmd-rebuild-not-restored",
+                path = damaged_path.display().to_string(),
+            )
+        );
+        println!();
+        println!("{}", fl!("cmd-rebuild-rescan"));
+
+        if problems > 0 {
+            Err(ErrorKind::Generic
+                .context(fl!("err-rebuild-problems", problems = problems))
+                .into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl RebuildCmd {
+    /// Recreates the first ZIP 32 account of each of the keystore's mnemonic seeds.
+    ///
+    /// This is used when the accounts of the damaged database cannot be read.
+    async fn recreate_from_seeds(
+        &self,
+        config: &crate::config::ZalletConfig,
+        db: Database,
+        wallet: &mut DbHandle,
+        chain: &FetchServiceSubscriber,
+    ) -> Result<usize, Error> {
+        let keystore = KeyStore::new(config, db)?;
+        let seed_fps = keystore.list_seed_fingerprints().await?;
+        if seed_fps.is_empty() {
+            return Ok(0);
+        }
+
+        if keystore.uses_encrypted_identities() {
+            let passphrase = rpassword::prompt_password(fl!("cmd-rebuild-passphrase-prompt"))
+                .map_err(|e| ErrorKind::Generic.context(e))?;
+            if !keystore.unlock(passphrase.into(), 600).await {
+                return Err(ErrorKind::Generic
+                    .context(fl!("err-keystore-wrong-passphrase"))
+                    .into());
+            }
+        }
+
+        let sapling_activation = wallet
+            .params()
+            .activation_height(NetworkUpgrade::Sapling)
+            .expect("Sapling activation height is defined");
+
+        let mut recreated = 0;
+        for seed_fp in seed_fps {
+            let birthday_height = match self.birthday {
+                Some(height) => BlockHeight::from_u32(height),
+                None => prompt_birthday(&seed_fp)?.unwrap_or(sapling_activation),
+            };
+            let birthday =
+                account_birthday(chain, wallet.params().network_type(), birthday_height).await?;
+            let seed = keystore.decrypt_seed(&seed_fp).await?;
+            wallet
+                .import_account_hd("", &seed, zip32::AccountId::ZERO, &birthday, None)
+                .map_err(|e| ErrorKind::Generic.context(e))?;
+            println!(
+                "- {}",
+                fl!(
+                    "cmd-rebuild-recreated-seed-account",
+                    seedfp = seed_fp.to_string(),
+                )
+            );
+            recreated += 1;
+        }
+        println!("{}", fl!("cmd-rebuild-recover-other-accounts"));
+
+        Ok(recreated)
+    }
+}
+
+impl Runnable for RebuildCmd {
+    fn run(&self) {
+        self.run_on_runtime();
+    }
+}
+
+/// Moves the database at `path` (and its SQLite journal files) aside.
+///
+/// Returns the new path of the database.
+fn move_aside(path: &Path) -> Result<PathBuf, Error> {
+    let suffix = format!(
+        "damaged-{}",
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    );
+    let with_suffix = |path: &Path, extra: &str| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{suffix}{extra}"));
+        PathBuf::from(name)
+    };
+
+    // The journal files are named after the database, so they are renamed to match it.
+    for journal in ["-wal", "-shm", "-journal"] {
+        let mut journal_path = path.as_os_str().to_owned();
+        journal_path.push(journal);
+        let journal_path = PathBuf::from(journal_path);
+        if journal_path.exists() {
+            fs::rename(&journal_path, with_suffix(path, journal))
+                .map_err(|e| ErrorKind::Generic.context(e))?;
+        }
+    }
+
+    let damaged_path = with_suffix(path, "");
+    fs::rename(path, &damaged_path).map_err(|e| ErrorKind::Generic.context(e))?;
+    Ok(damaged_path)
+}
+
+/// Copies the rows of `table` from the damaged database into the new wallet.
+///
+/// Returns the number of rows that were copied.
+fn copy_table(
+    damaged: &rusqlite::Connection,
+    wallet: &DbHandle,
+    table: &str,
+) -> Result<usize, rusqlite::Error> {
+    let mut stmt = damaged.prepare(&format!("SELECT * FROM {table}"))?;
+    let columns = stmt
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let rows = stmt
+        .query_map([], |row| {
+            (0..columns.len())
+                .map(|i| row.get::<_, Value>(i))
+                .collect::<Result<Vec<_>, _>>()
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    wallet.with_raw_mut(|conn, _| {
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare(&format!(
+                "INSERT OR IGNORE INTO {table} ({}) VALUES ({})",
+                columns.join(", "),
+                vec!["?"; columns.len()].join(", "),
+            ))?;
+            for row in &rows {
+                insert.execute(rusqlite::params_from_iter(row))?;
+            }
+        }
+        tx.commit()
+    })?;
+
+    Ok(rows.len())
+}
+
+/// Reads the accounts recorded in the damaged database.
+///
+/// Accounts that cannot be read are returned as errors, so that the others can still be
+/// recreated.
+fn recorded_accounts(
+    damaged: &rusqlite::Connection,
+) -> Result<Vec<Result<RecordedAccount, rusqlite::Error>>, rusqlite::Error> {
+    let mut stmt = damaged.prepare(
+        "SELECT uuid, name, ufvk, hd_seed_fingerprint, hd_account_index, birthday_height,
+            has_spend_key, key_source
+        FROM accounts
+        ORDER BY id",
+    )?;
+    let accounts = stmt
+        .query_map([], |row| {
+            Ok(RecordedAccount {
+                uuid: row.get("uuid")?,
+                name: row.get("name")?,
+                ufvk: row.get("ufvk")?,
+                seed_fingerprint: row.get("hd_seed_fingerprint")?,
+                account_index: row.get("hd_account_index")?,
+                birthday_height: row.get("birthday_height")?,
+                has_spend_key: row.get("has_spend_key")?,
+                key_source: row.get("key_source")?,
+            })
+        })?
+        .collect();
+    Ok(accounts)
+}
+
+/// Recreates a recorded account in the new wallet, with its original UUID.
+async fn recreate_account(
+    wallet: &mut DbHandle,
+    chain: &FetchServiceSubscriber,
+    account: &RecordedAccount,
+) -> Result<(), Error> {
+    let ufvk = account
+        .ufvk
+        .as_deref()
+        .ok_or_else(|| ErrorKind::Generic.context("the account has no full viewing key"))?;
+    let ufvk = UnifiedFullViewingKey::decode(wallet.params(), ufvk)
+        .map_err(|e| ErrorKind::Generic.context(e))?;
+
+    let derivation = match (account.seed_fingerprint, account.account_index) {
+        (Some(seed_fp), Some(account_index)) => Some(Zip32Derivation::new(
+            SeedFingerprint::from_bytes(seed_fp),
+            zip32::AccountId::try_from(account_index).map_err(|e| ErrorKind::Generic.context(e))?,
+            None,
+        )),
+        _ => None,
+    };
+    let purpose = if account.has_spend_key {
+        AccountPurpose::Spending { derivation }
+    } else {
+        AccountPurpose::ViewOnly
+    };
+
+    let birthday = account_birthday(
+        chain,
+        wallet.params().network_type(),
+        BlockHeight::from_u32(account.birthday_height),
+    )
+    .await?;
+
+    let recreated = wallet
+        .import_account_ufvk(
+            account.name.as_deref().unwrap_or(""),
+            &ufvk,
+            &birthday,
+            purpose,
+            account.key_source.as_deref(),
+        )
+        .map_err(|e| ErrorKind::Generic.context(e))?;
+
+    // Keep the account's UUID, so that clients can continue to refer to it.
+    wallet
+        .with_raw_mut(|conn, _| {
+            conn.execute(
+                "UPDATE accounts SET uuid = :uuid WHERE uuid = :new_uuid",
+                named_params! {
+                    ":uuid": account.uuid,
+                    ":new_uuid": recreated.id().expose_uuid(),
+                },
+            )
+        })
+        .map_err(|e| ErrorKind::Generic.context(e))?;
+
+    println!(
+        "- {}",
+        fl!(
+            "cmd-rebuild-recreated-account",
+            account = account.display_name(),
+            height = account.birthday_height,
+        )
+    );
+    Ok(())
+}
+
+/// Asks the user for the birthday height of the accounts of the given seed.
+///
+/// Returns `None` if the user does not know it.
+fn prompt_birthday(seed_fp: &SeedFingerprint) -> Result<Option<BlockHeight>, Error> {
+    loop {
+        print!(
+            "{} ",
+            fl!("cmd-rebuild-birthday-prompt", seedfp = seed_fp.to_string())
+        );
+        io::stdout()
+            .flush()
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+
+        let mut line = String::new();
+        io::stdin()
+            .read_line(&mut line)
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        match line.parse::<u32>() {
+            Ok(height) => return Ok(Some(BlockHeight::from_u32(height))),
+            Err(_) => println!("{}", fl!("cmd-rebuild-not-a-height", input = line)),
+        }
+    }
+}
+
+/// Returns the birthday of an account that was created at `birthday_height`.
+async fn account_birthday(
+    chain: &FetchServiceSubscriber,
+    network_type: NetworkType,
+    birthday_height: BlockHeight,
+) -> Result<AccountBirthday, Error> {
+    let treestate_height = birthday_height.saturating_sub(1);
+    let treestate = chain
+        .fetcher
+        .get_treestate(treestate_height.to_string())
+        .await
+        .map_err(|e| {
+            ErrorKind::Generic.context(format!(
+                "Failed to get treestate at height {treestate_height}: {e}"
+            ))
+        })?;
+
+    let treestate = TreeState {
+        network: match network_type {
+            NetworkType::Main => "main".into(),
+            NetworkType::Test => "test".into(),
+            NetworkType::Regtest => "regtest".into(),
+        },
+        height: u64::try_from(treestate.height)
+            .map_err(|_| ErrorKind::Generic.context("Invalid treestate height"))?,
+        hash: treestate.hash,
+        time: treestate.time,
+        sapling_tree: treestate
+            .sapling
+            .commitments()
+            .final_state()
+            .as_ref()
+            .map(hex::encode)
+            .unwrap_or_default(),
+        orchard_tree: treestate
+            .orchard
+            .commitments()
+            .final_state()
+            .as_ref()
+            .map(hex::encode)
+            .unwrap_or_default(),
+    };
+
+    AccountBirthday::from_treestate(treestate, None)
+        .map_err(|_| ErrorKind::Generic.context("Invalid treestate").into())
+}
@@ -8,7 +8,14 @@ use jsonrpsee::core::{client::ClientT, params::ArrayParams};
 use jsonrpsee_http_client::HttpClientBuilder;
 use secrecy::{ExposeSecret, SecretString};
 
-use crate::{cli::RpcCliCmd, commands::AsyncRunnable, error::Error, prelude::*};
+use crate::{
+    cli::RpcCliCmd,
+    commands::AsyncRunnable,
+    components::json_rpc::server::cookie,
+    config::{RpcAuthMethod, RpcBind},
+    error::Error,
+    prelude::*,
+};
 
 const DEFAULT_HTTP_CLIENT_TIMEOUT: u64 = 900;
 
@@ -43,7 +50,8 @@ impl AsyncRunnable for RpcCliCmd {
             None => DEFAULT_HTTP_CLIENT_TIMEOUT,
         });
 
-        // Find a password we can use. If none are configured, we assume none is needed.
+        // Find a password we can use, falling back to the cookie file. If neither is
+        // available, we assume none is needed.
         let auth_prefix = config
             .rpc
             .auth
@@ -53,21 +61,63 @@ impl AsyncRunnable for RpcCliCmd {
                     .as_ref()
                     .map(|pw| SecretString::new(format!("{}:{}@", auth.user, pw.expose_secret())))
             })
+            .or_else(|| match config.rpc.auth_method() {
+                RpcAuthMethod::Cookie => {
+                    cookie::read(&config.rpc_cookie_path())
+                        .ok()
+                        .map(|credentials| {
+                            SecretString::new(format!("{}@", credentials.expose_secret()))
+                        })
+                }
+                RpcAuthMethod::None => None,
+            })
             .unwrap_or_else(|| SecretString::new(String::new()));
 
+        // Select the wallet, if one was named with `--wallet`.
+        let path = config
+            .wallet_name
+            .as_ref()
+            .map(|name| format!("/wallet/{name}"))
+            .unwrap_or_default();
+
+        // The client does not support connecting over TLS.
+        if config.rpc.tls_cert_path.is_some() {
+            return Err(RpcCliError::TlsUnsupported.into());
+        }
+
+        // The client can only connect over TCP.
+        let binds = config
+            .rpc
+            .bind
+            .iter()
+            .filter_map(|bind| match bind {
+                RpcBind::Tcp(addr) => Some(*addr),
+                RpcBind::Unix(_) => None,
+            })
+            .collect::<Vec<_>>();
+        if binds.is_empty() && !config.rpc.bind.is_empty() {
+            return Err(RpcCliError::UnixSocketUnsupported.into());
+        }
+
         // Connect to the Zallet wallet.
-        let client = match config.rpc.bind.as_slice() {
+        let client = match binds.as_slice() {
             &[] => Err(RpcCliError::WalletHasNoRpcServer),
             &[bind] => HttpClientBuilder::default()
                 .request_timeout(timeout)
-                .build(format!("http://{}{bind}", auth_prefix.expose_secret()))
+                .build(format!(
+                    "http://{}{bind}{path}",
+                    auth_prefix.expose_secret()
+                ))
                 .map_err(|_| RpcCliError::FailedToConnect),
             addrs => addrs
                 .iter()
                 .find_map(|bind| {
                     HttpClientBuilder::default()
                         .request_timeout(timeout)
-                        .build(format!("http://{}{bind}", auth_prefix.expose_secret()))
+                        .build(format!(
+                            "http://{}{bind}{path}",
+                            auth_prefix.expose_secret()
+                        ))
                         .ok()
                 })
                 .ok_or(RpcCliError::FailedToConnect),
@@ -111,6 +161,8 @@ pub(crate) enum RpcCliError {
     FailedToConnect,
     InvalidParameter(String),
     RequestFailed(String),
+    TlsUnsupported,
+    UnixSocketUnsupported,
     WalletHasNoRpcServer,
 }
 
@@ -124,6 +176,8 @@ impl fmt::Display for RpcCliError {
             Self::RequestFailed(e) => {
                 wfl!(f, "err-rpc-cli-request-failed", error = e)
             }
+            Self::TlsUnsupported => wfl!(f, "err-rpc-cli-tls-unsupported"),
+            Self::UnixSocketUnsupported => wfl!(f, "err-rpc-cli-unix-socket-unsupported"),
             Self::WalletHasNoRpcServer => wfl!(f, "err-rpc-cli-no-server"),
         }
     }
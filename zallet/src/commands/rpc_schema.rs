@@ -0,0 +1,47 @@
+//! `rpc-schema` subcommand
+
+use abscissa_core::Runnable;
+use tokio::{fs::File, io::AsyncWriteExt};
+
+use crate::{
+    cli::RpcSchemaCmd,
+    commands::AsyncRunnable,
+    components::json_rpc::methods::openrpc,
+    error::{Error, ErrorKind},
+    fl,
+};
+
+impl AsyncRunnable for RpcSchemaCmd {
+    async fn run(&self) -> Result<(), Error> {
+        // This is the same document that the `rpc.discover` method returns.
+        let document = openrpc::call().map_err(|e| ErrorKind::Generic.context(e))?;
+        let output =
+            serde_json::to_string_pretty(&document).map_err(|e| ErrorKind::Generic.context(e))?;
+
+        if self.output == "-" {
+            println!("{output}");
+        } else {
+            let mut f = if self.force {
+                File::create(&self.output).await
+            } else {
+                File::create_new(&self.output).await
+            }
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+            f.write_all(format!("{output}\n").as_bytes())
+                .await
+                .map_err(|e| ErrorKind::Generic.context(e))?;
+            println!(
+                "{}",
+                fl!("cmd-rpc-schema-written", path = self.output.as_str())
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Runnable for RpcSchemaCmd {
+    fn run(&self) {
+        self.run_on_runtime();
+    }
+}
@@ -1,88 +1,159 @@
 //! `start` subcommand
 
+use std::pin::Pin;
+
 use abscissa_core::{FrameworkError, Runnable, config};
-use tokio::{pin, select};
+use tokio::{pin, select, task::JoinHandle};
+use zcash_protocol::consensus::BlockHeight;
 
 use crate::{
     cli::StartCmd,
     commands::AsyncRunnable,
-    components::{chain::Chain, database::Database, json_rpc::JsonRpc, sync::WalletSync},
-    config::ZalletConfig,
-    error::Error,
+    components::{
+        audit::{self, Event},
+        chain::Chain,
+        json_rpc::JsonRpc,
+        metrics::Metrics,
+        sync::configure_scan_workers,
+        tracing::log_to_file,
+        wallets::{OpenedWallet, Wallets},
+    },
+    config::{Severity, ZalletConfig},
+    error::{Error, ErrorKind, ExitCode},
     fl,
+    pid_file::PidFile,
     prelude::*,
+    shutdown,
 };
 
-#[cfg(zallet_build = "wallet")]
-use crate::components::keystore::KeyStore;
-
 impl AsyncRunnable for StartCmd {
+    const GRACEFUL_SHUTDOWN: bool = true;
+
     async fn run(&self) -> Result<(), Error> {
         let config = APP.config();
+
+        // Refuse to start with a config that `zallet validate-config` rejects. The
+        // JSON-RPC server warns about risky settings itself once it is listening.
+        if let Some(issue) = config
+            .validate()
+            .into_iter()
+            .find(|issue| issue.severity == Severity::Error)
+        {
+            return Err(Error::from(ErrorKind::Init.context(issue.message))
+                .with_exit_code(ExitCode::Config));
+        }
+
         let _lock = config.lock_datadir()?;
+        log_to_file(&config)?;
+        let _pid_file = PidFile::create(&config)?;
+        audit::record(Event::Started {
+            version: crate::build::PKG_VERSION,
+        });
 
         // ALPHA: Warn when currently-unused config options are set.
         let warn_unused =
             |option: &str| warn!("{}", fl!("warn-config-unused", option = option.to_string()));
-        // TODO: https://github.com/zcash/wallet/issues/199
-        if config.builder.spend_zeroconf_change.is_some() {
-            warn_unused("builder.spend_zeroconf_change");
-        }
-        // TODO: https://github.com/zcash/wallet/issues/200
-        if config.builder.tx_expiry_delta.is_some() {
-            warn_unused("builder.tx_expiry_delta");
-        }
         // TODO: https://github.com/zcash/wallet/issues/138
         #[cfg(zallet_build = "wallet")]
         if config.features.legacy_pool_seed_fingerprint.is_some() {
             warn_unused("features.legacy_pool_seed_fingerprint");
         }
-        // TODO: https://github.com/zcash/wallet/issues/201
-        #[cfg(zallet_build = "wallet")]
-        if config.keystore.require_backup.is_some() {
-            warn_unused("keystore.require_backup");
+
+        // Open each of the wallets that Zallet serves.
+        let mut opened = vec![];
+        for wallet_config in config.wallet_configs() {
+            if let Some(name) = &wallet_config.wallet_name {
+                info!("Opening wallet \"{name}\"");
+            }
+            opened.push(OpenedWallet::open(wallet_config).await?);
         }
 
-        let db = Database::open(&config).await?;
+        // Unlock the encrypted wallets, if a passphrase is supplied. A passphrase from the
+        // environment is not needed afterwards.
         #[cfg(zallet_build = "wallet")]
-        let keystore = KeyStore::new(&config, db.clone())?;
+        {
+            let unlocked = unlock_at_startup(&config, &opened).await;
+            crate::components::keystore::forget_env_passphrase();
+            unlocked?;
+        }
 
         // Start monitoring the chain.
-        let (chain, chain_indexer_task_handle) = Chain::new(&config).await?;
+        let (chain, chain_indexer_task_handle) = Chain::new(&config)
+            .await
+            .map_err(|e| e.with_exit_code(ExitCode::Backend))?;
+
+        // Start the sync process for each wallet. The wallets share the connection to
+        // the chain indexer, and the scan workers.
+        configure_scan_workers(&config.sync);
+        let (wallets, mut wallet_task_exits) = Wallets::new(chain);
+        for wallet in opened {
+            if let Err(e) = wallets.attach(wallet).await {
+                wallets.close().await;
+                return Err(e);
+            }
+        }
+
+        if let Some(height) = self.rescan_from {
+            for wallet in wallets.loaded() {
+                let db_data = wallet.db.handle().await?;
+                if let Err(e) = wallet.sync_status.schedule_rescan(
+                    &db_data,
+                    Some(BlockHeight::from_u32(height)),
+                    None,
+                ) {
+                    warn!("Not rescanning from height {height}: {e}");
+                }
+            }
+        }
 
         // Launch RPC server.
-        let rpc_task_handle = JsonRpc::spawn(
-            &config,
-            db.clone(),
-            #[cfg(zallet_build = "wallet")]
-            keystore,
-            chain.clone(),
-        )
-        .await?;
-
-        // Start the wallet sync process.
-        let (
-            wallet_sync_steady_state_task_handle,
-            wallet_sync_recover_history_task_handle,
-            wallet_sync_poll_transparent_task_handle,
-            wallet_sync_data_requests_task_handle,
-        ) = WalletSync::spawn(&config, db, chain).await?;
+        let rpc_task_handle = JsonRpc::spawn(&config, wallets.clone()).await?;
+
+        // Launch the metrics endpoint.
+        let metrics_task_handle = Metrics::spawn(&config, wallets.clone()).await?;
 
         info!("Spawned Zallet tasks");
 
+        // The wallets are open and the RPC server is listening, so tell systemd (if it
+        // started us) that we are ready.
+        #[cfg(all(target_os = "linux", feature = "systemd"))]
+        let systemd = crate::systemd::Notifier::from_env();
+        #[cfg(all(target_os = "linux", feature = "systemd"))]
+        let mut systemd_ticks = systemd.as_ref().map(|systemd| {
+            systemd.ready(&wallets);
+            tokio::time::interval(systemd.tick_interval())
+        });
+        #[cfg(not(all(target_os = "linux", feature = "systemd")))]
+        let mut systemd_ticks = None::<tokio::time::Interval>;
+
         // ongoing tasks.
         pin!(chain_indexer_task_handle);
         pin!(rpc_task_handle);
-        pin!(wallet_sync_steady_state_task_handle);
-        pin!(wallet_sync_recover_history_task_handle);
-        pin!(wallet_sync_poll_transparent_task_handle);
-        pin!(wallet_sync_data_requests_task_handle);
+        pin!(metrics_task_handle);
 
-        // Wait for tasks to finish.
+        // Wait for tasks to finish, or for a shutdown to be requested.
+        let mut stopping = false;
         let res = loop {
             let exit_when_task_finishes = true;
 
             let result = select! {
+                _ = shutdown::requested() => {
+                    stopping = true;
+                    Ok(())
+                }
+
+                // Petting the watchdog from this loop means that systemd restarts Zallet
+                // if the loop gets stuck.
+                _ = async { systemd_ticks.as_mut().expect("checked").tick().await },
+                    if systemd_ticks.is_some() =>
+                {
+                    #[cfg(all(target_os = "linux", feature = "systemd"))]
+                    if let Some(systemd) = &systemd {
+                        systemd.tick(&wallets);
+                    }
+                    continue;
+                }
+
                 chain_indexer_join_result = &mut chain_indexer_task_handle => {
                     let chain_indexer_result = chain_indexer_join_result
                         .expect("unexpected panic in the chain indexer task");
@@ -97,31 +168,17 @@ impl AsyncRunnable for StartCmd {
                     Ok(())
                 }
 
-                wallet_sync_join_result = &mut wallet_sync_steady_state_task_handle => {
-                    let wallet_sync_result = wallet_sync_join_result
-                        .expect("unexpected panic in the wallet steady-state sync task");
-                    info!(?wallet_sync_result, "Wallet steady-state sync task exited");
-                    Ok(())
-                }
-
-                wallet_sync_join_result = &mut wallet_sync_recover_history_task_handle => {
-                    let wallet_sync_result = wallet_sync_join_result
-                        .expect("unexpected panic in the wallet recover-history sync task");
-                    info!(?wallet_sync_result, "Wallet recover-history sync task exited");
-                    Ok(())
-                }
-
-                wallet_sync_join_result = &mut wallet_sync_poll_transparent_task_handle => {
-                    let wallet_sync_result = wallet_sync_join_result
-                        .expect("unexpected panic in the wallet poll-transparent sync task");
-                    info!(?wallet_sync_result, "Wallet poll-transparent sync task exited");
+                metrics_join_result = &mut metrics_task_handle => {
+                    let metrics_result = metrics_join_result
+                        .expect("unexpected panic in the metrics task");
+                    info!(?metrics_result, "Metrics task exited");
                     Ok(())
                 }
 
-                wallet_sync_join_result = &mut wallet_sync_data_requests_task_handle => {
+                Some((task, wallet_sync_join_result)) = wallet_task_exits.recv() => {
                     let wallet_sync_result = wallet_sync_join_result
-                        .expect("unexpected panic in the wallet data-requests sync task");
-                    info!(?wallet_sync_result, "Wallet data-requests sync task exited");
+                        .unwrap_or_else(|_| panic!("unexpected panic in the {task} task"));
+                    info!(?wallet_sync_result, "{task} task exited");
                     Ok(())
                 }
             };
@@ -135,22 +192,126 @@ impl AsyncRunnable for StartCmd {
             }
         };
 
-        info!("Exiting Zallet because an ongoing task exited; asking other tasks to stop");
+        #[cfg(all(target_os = "linux", feature = "systemd"))]
+        if let Some(systemd) = &systemd {
+            systemd.stopping();
+        }
 
-        // ongoing tasks
-        chain_indexer_task_handle.abort();
-        rpc_task_handle.abort();
-        wallet_sync_steady_state_task_handle.abort();
-        wallet_sync_recover_history_task_handle.abort();
-        wallet_sync_poll_transparent_task_handle.abort();
-        wallet_sync_data_requests_task_handle.abort();
+        // A component that does not stop must not keep Zallet running forever.
+        let force_shutdown_timeout = config.external.force_shutdown_timeout();
+        let stopped = tokio::time::timeout(force_shutdown_timeout, async {
+            if stopping {
+                // The RPC server stops accepting requests, and waits for in-flight requests
+                // and async operations to finish, before it exits.
+                info!("Shutting down; waiting for RPC requests and async operations to finish");
+                let rpc_server_result = (&mut rpc_task_handle)
+                    .await
+                    .expect("unexpected panic in the RPC task");
+                info!(?rpc_server_result, "RPC task exited");
+            } else {
+                info!("Exiting Zallet because an ongoing task exited; asking other tasks to stop");
+            }
 
-        info!("All tasks have been asked to stop, waiting for remaining tasks to finish");
+            // ongoing tasks
+            chain_indexer_task_handle.abort();
+            rpc_task_handle.abort();
+            metrics_task_handle.abort();
+
+            info!("All tasks have been asked to stop, waiting for remaining tasks to finish");
+
+            // Wait for the tasks to release their database connections, so that the wallet
+            // databases are closed cleanly.
+            join_stopped(chain_indexer_task_handle).await;
+            join_stopped(rpc_task_handle).await;
+            join_stopped(metrics_task_handle).await;
+            wallets.close().await;
+            info!("Closed the wallet databases");
+        })
+        .await;
+        if stopped.is_err() {
+            error!(
+                "Zallet did not stop within {} seconds (external.force_shutdown_timeout); exiting anyway",
+                force_shutdown_timeout.as_secs(),
+            );
+            return Err(ErrorKind::Generic.context("Shutdown timed out").into());
+        }
 
         res
     }
 }
 
+/// Unlocks the encrypted wallets with the passphrase supplied when Zallet starts.
+///
+/// A wallet with its own `passphrase_file` in `database.wallets` is unlocked with that
+/// passphrase, and the other wallets share the passphrase from the global sources.
+/// Wallets for which `keystore.allow_unlock` is disabled stay locked. If a supplied
+/// passphrase does not unlock its wallet, Zallet exits with a distinct exit code, so that
+/// service managers can avoid restarting it with the same passphrase.
+#[cfg(zallet_build = "wallet")]
+async fn unlock_at_startup(config: &ZalletConfig, opened: &[OpenedWallet]) -> Result<(), Error> {
+    use secrecy::ExposeSecret;
+
+    let encrypted = opened
+        .iter()
+        .filter(|wallet| {
+            wallet.keystore.uses_encrypted_identities() && wallet.keystore.allows_unlock()
+        })
+        .collect::<Vec<_>>();
+    if encrypted.is_empty() {
+        return Ok(());
+    }
+
+    // The shared passphrase is only obtained if a wallet needs it, so that the user is
+    // not prompted for a passphrase that would not be used. Passphrases are zeroized
+    // when they (and each of their clones) are dropped.
+    let mut shared = None;
+    let mut unlocked = 0;
+    for wallet in &encrypted {
+        let passphrase =
+            if wallet.config.keystore.passphrase_file != config.keystore.passphrase_file {
+                crate::components::keystore::startup_passphrase(&wallet.config)?
+            } else {
+                if shared.is_none() {
+                    shared = Some(crate::components::keystore::startup_passphrase(config)?);
+                }
+                shared.clone().flatten()
+            };
+        let Some(passphrase) = passphrase else {
+            continue;
+        };
+
+        // The keystore takes the passphrase as age's `SecretString` type.
+        let passphrase = passphrase.expose_secret().as_str().into();
+        if !wallet.keystore.unlock_until_locked(passphrase).await {
+            return Err(
+                Error::from(ErrorKind::Init.context(fl!("err-keystore-wrong-passphrase")))
+                    .with_exit_code(ExitCode::WrongPassphrase),
+            );
+        }
+        unlocked += 1;
+    }
+
+    if unlocked == 0 {
+        info!("No wallet passphrase was supplied, so encrypted wallets start locked");
+    } else if unlocked < encrypted.len() {
+        info!(
+            "Unlocked {unlocked} of {} encrypted wallets with the supplied passphrases; the others start locked",
+            encrypted.len(),
+        );
+    } else {
+        info!("Unlocked the encrypted wallets with the supplied passphrase");
+    }
+
+    Ok(())
+}
+
+/// Waits for a task that has been asked to stop, unless its result was already taken.
+async fn join_stopped<T>(handle: Pin<&mut JoinHandle<T>>) {
+    if !handle.is_finished() {
+        let _ = handle.await;
+    }
+}
+
 impl Runnable for StartCmd {
     fn run(&self) {
         self.run_on_runtime();
@@ -159,7 +320,22 @@ impl Runnable for StartCmd {
 }
 
 impl config::Override<ZalletConfig> for StartCmd {
-    fn override_config(&self, config: ZalletConfig) -> Result<ZalletConfig, FrameworkError> {
+    fn override_config(&self, mut config: ZalletConfig) -> Result<ZalletConfig, FrameworkError> {
+        if let Some((address, fallbacks)) = self.validator_addresses.split_first() {
+            config.indexer.validator_address = Some(address.clone());
+            config.indexer.fallback_validator_addresses = fallbacks.to_vec();
+        }
+
+        #[cfg(zallet_build = "wallet")]
+        {
+            if self.require_encryption {
+                config.keystore.require_encryption = Some(true);
+            }
+            if let Some(path) = &self.passphrase_file {
+                config.keystore.passphrase_file = Some(path.clone());
+            }
+        }
+
         Ok(config)
     }
 }
@@ -0,0 +1,68 @@
+//! `status` subcommand
+
+use abscissa_core::{Application, Runnable, Shutdown};
+
+use crate::{
+    cli::StatusCmd,
+    fl,
+    pid_file::{self, Status},
+    prelude::APP,
+};
+
+/// The exit code when Zallet is running.
+const RUNNING_EXIT_CODE: i32 = 0;
+
+/// The exit code when Zallet is not running, but left behind a PID file.
+const STALE_EXIT_CODE: i32 = 1;
+
+/// The exit code when Zallet is not running.
+const STOPPED_EXIT_CODE: i32 = 3;
+
+/// The exit code when the status of Zallet could not be determined.
+const UNKNOWN_EXIT_CODE: i32 = 4;
+
+impl Runnable for StatusCmd {
+    fn run(&self) {
+        let config = APP.config();
+
+        let exit_code = match pid_file::status(&config) {
+            Ok(Status::Running(Some(pid))) => {
+                println!("{}", fl!("cmd-status-running", pid = pid));
+                RUNNING_EXIT_CODE
+            }
+            Ok(Status::Running(None)) => {
+                println!(
+                    "{}",
+                    fl!(
+                        "cmd-status-no-pid-file",
+                        path = config.pid_file_path().display().to_string(),
+                    )
+                );
+                RUNNING_EXIT_CODE
+            }
+            Ok(Status::Stale(pid)) => {
+                println!(
+                    "{}",
+                    fl!(
+                        "cmd-status-stale",
+                        path = config.pid_file_path().display().to_string(),
+                        pid = pid,
+                    )
+                );
+                STALE_EXIT_CODE
+            }
+            Ok(Status::Stopped) => {
+                println!("{}", fl!("cmd-status-stopped"));
+                STOPPED_EXIT_CODE
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                UNKNOWN_EXIT_CODE
+            }
+        };
+
+        if exit_code != RUNNING_EXIT_CODE {
+            APP.shutdown_with_exitcode(Shutdown::Graceful, exit_code);
+        }
+    }
+}
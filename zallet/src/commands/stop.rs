@@ -0,0 +1,85 @@
+//! `stop` subcommand
+
+use std::time::Duration;
+
+use abscissa_core::Runnable;
+use tokio::time::Instant;
+
+use crate::{
+    cli::StopCmd,
+    commands::AsyncRunnable,
+    error::{Error, ErrorKind},
+    fl,
+    pid_file::{self, Status},
+    prelude::*,
+};
+
+impl AsyncRunnable for StopCmd {
+    async fn run(&self) -> Result<(), Error> {
+        let config = APP.config();
+
+        let pid = match pid_file::status(&config)? {
+            Status::Running(Some(pid)) => pid,
+            Status::Running(None) => {
+                return Err(ErrorKind::Generic
+                    .context(fl!(
+                        "err-stop-no-pid-file",
+                        path = config.pid_file_path().display().to_string(),
+                    ))
+                    .into());
+            }
+            Status::Stale(_) | Status::Stopped => {
+                println!("{}", fl!("cmd-status-stopped"));
+                return Ok(());
+            }
+        };
+
+        terminate(pid)?;
+        println!("{}", fl!("cmd-stop-waiting", pid = pid));
+
+        // Zallet releases the datadir lock once it has shut down.
+        let deadline = Instant::now() + Duration::from_secs(self.timeout);
+        while config.datadir_locked()? {
+            if Instant::now() >= deadline {
+                return Err(ErrorKind::Generic
+                    .context(fl!("err-stop-timeout", timeout = self.timeout))
+                    .into());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        println!("{}", fl!("cmd-stop-stopped"));
+        Ok(())
+    }
+}
+
+impl Runnable for StopCmd {
+    fn run(&self) {
+        self.run_on_runtime();
+    }
+}
+
+/// Asks the process with the given ID to shut down gracefully.
+#[cfg(unix)]
+fn terminate(pid: u32) -> Result<(), Error> {
+    use nix::{
+        sys::signal::{Signal, kill},
+        unistd::Pid,
+    };
+
+    let raw_pid = i32::try_from(pid)
+        .map_err(|_| ErrorKind::Generic.context(format!("Invalid process ID {pid}")))?;
+    kill(Pid::from_raw(raw_pid), Signal::SIGTERM).map_err(|e| {
+        ErrorKind::Generic
+            .context(format!("Failed to signal process {pid}: {e}"))
+            .into()
+    })
+}
+
+/// Asks the process with the given ID to shut down gracefully.
+#[cfg(not(unix))]
+fn terminate(_pid: u32) -> Result<(), Error> {
+    Err(ErrorKind::Generic
+        .context("`zallet stop` is not supported on this platform")
+        .into())
+}
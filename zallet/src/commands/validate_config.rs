@@ -0,0 +1,104 @@
+//! `validate-config` subcommand
+
+use std::fs;
+use std::path::Path;
+
+use abscissa_core::{Application, Runnable, Shutdown};
+use serde::Serialize;
+
+use crate::{
+    cli::ValidateConfigCmd,
+    config::{ConfigIssue, Severity, ZalletConfig},
+    error::ExitCode,
+    fl,
+    prelude::APP,
+};
+
+/// The result of `zallet validate-config --json`.
+#[derive(Debug, Serialize)]
+struct Report {
+    /// The path of the config file that was checked.
+    path: String,
+    /// Whether Zallet would start with the config file.
+    ///
+    /// With `--strict`, this is `false` if there are any warnings.
+    valid: bool,
+    issues: Vec<ConfigIssue>,
+}
+
+impl Runnable for ValidateConfigCmd {
+    fn run(&self) {
+        let config = APP.config();
+        let path = config
+            .config_file
+            .clone()
+            .expect("set by EntryPoint::process_config");
+
+        let issues = match load(&path) {
+            Ok(mut loaded) => {
+                loaded.datadir = config.datadir.clone();
+                loaded.validate()
+            }
+            Err(issue) => vec![issue],
+        };
+
+        let errors = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count();
+        let warnings = issues.len() - errors;
+        let report = Report {
+            path: path.display().to_string(),
+            valid: errors == 0 && (warnings == 0 || !self.strict),
+            issues,
+        };
+
+        if self.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).expect("report is serializable")
+            );
+        } else {
+            for issue in &report.issues {
+                println!("{issue}");
+            }
+            println!(
+                "{}",
+                if report.valid {
+                    fl!(
+                        "validate-config-valid",
+                        path = report.path.as_str(),
+                        warnings = warnings,
+                    )
+                } else {
+                    fl!(
+                        "validate-config-invalid",
+                        path = report.path.as_str(),
+                        errors = errors,
+                        warnings = warnings,
+                    )
+                }
+            );
+        }
+
+        if !report.valid {
+            APP.shutdown_with_exitcode(Shutdown::Graceful, ExitCode::Config.code());
+        }
+    }
+}
+
+/// Reads and parses the config file at `path`, in the same way as when Zallet starts.
+fn load(path: &Path) -> Result<ZalletConfig, ConfigIssue> {
+    let unusable = |error: String| ConfigIssue {
+        severity: Severity::Error,
+        option: None,
+        message: fl!(
+            "err-validate-config-unusable",
+            path = path.display().to_string(),
+            error = error,
+        ),
+    };
+
+    let contents = fs::read_to_string(path).map_err(|e| unusable(e.to_string()))?;
+    toml::from_str(&contents).map_err(|e| unusable(e.to_string()))
+}
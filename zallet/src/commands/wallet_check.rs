@@ -0,0 +1,190 @@
+//! `wallet-check` subcommand
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use abscissa_core::Runnable;
+use serde::Serialize;
+
+use crate::{
+    cli::WalletCheckCmd,
+    commands::AsyncRunnable,
+    components::database::Database,
+    error::{Error, ErrorKind, ExitCode},
+    fl,
+    prelude::*,
+};
+
+/// The result of `zallet wallet-check`.
+#[derive(Debug, Serialize)]
+struct Report {
+    /// Whether every check passed, or was repaired.
+    passed: bool,
+    checks: Vec<Check>,
+    /// The path of the copy of the database that was taken before repairing it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup: Option<String>,
+}
+
+/// The result of a single check.
+#[derive(Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    passed: bool,
+    /// Whether the problems that were found have been repaired.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    repaired: bool,
+    problems: Vec<String>,
+}
+
+impl Report {
+    /// Records the outcome of a check, failing if the check could not be run.
+    fn run<E>(&mut self, name: &'static str, problems: Result<Vec<String>, E>) -> Result<(), Error>
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let problems = problems.map_err(|e| ErrorKind::Generic.context(e))?;
+        self.add(name, problems);
+        Ok(())
+    }
+
+    fn add(&mut self, name: &'static str, problems: Vec<String>) {
+        self.checks.push(Check {
+            name,
+            passed: problems.is_empty(),
+            repaired: false,
+            problems,
+        });
+    }
+
+    fn check_mut(&mut self, name: &str) -> &mut Check {
+        self.checks
+            .iter_mut()
+            .find(|check| check.name == name)
+            .expect("check was run")
+    }
+}
+
+/// The check of the database file itself. Nothing is repaired if it fails.
+const SQLITE_INTEGRITY: &str = "sqlite_integrity";
+
+/// The check whose failures can be safely repaired, by rebuilding the balance
+/// aggregates from the wallet's outputs.
+const BALANCE_AGGREGATES: &str = "balance_aggregates";
+
+impl AsyncRunnable for WalletCheckCmd {
+    async fn run(&self) -> Result<(), Error> {
+        let config = APP.config();
+
+        // `zallet start` holds this lock while it has the wallet database open.
+        let _lock = config.lock_datadir()?;
+
+        let db = Database::open_read_only(&config)
+            .await
+            .map_err(|e| e.with_exit_code(ExitCode::Database))?;
+        let wallet = db.handle().await?;
+
+        let mut report = Report {
+            passed: false,
+            checks: vec![],
+            backup: None,
+        };
+        report.run(SQLITE_INTEGRITY, wallet.check_sqlite_integrity())?;
+        report.run("references", wallet.check_references())?;
+        report.run("unrecorded_spends", wallet.check_unrecorded_spends())?;
+        report.run("scan_ranges", wallet.check_scan_ranges())?;
+        report.run("address_derivation", wallet.check_address_derivation())?;
+
+        let balances = wallet
+            .check_balance_aggregates()
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+        report.add(BALANCE_AGGREGATES, balances.problems());
+
+        // Repairs only rebuild data that is derived from the rest of the wallet, and are
+        // not attempted if the database file itself is damaged.
+        if self.repair
+            && report.check_mut(SQLITE_INTEGRITY).passed
+            && !report.check_mut(BALANCE_AGGREGATES).passed
+        {
+            let backup_path = backup_path(&config.wallet_db_path());
+            wallet
+                .backup_to(&backup_path)
+                .map_err(|e| ErrorKind::Generic.context(e))?;
+            info!("Backed up the wallet database to {}", backup_path.display());
+            report.backup = Some(backup_path.display().to_string());
+
+            drop(wallet);
+            db.close();
+
+            let db = Database::open(&config)
+                .await
+                .map_err(|e| e.with_exit_code(ExitCode::Database))?;
+            db.handle()
+                .await?
+                .rebuild_balance_aggregates()
+                .map_err(|e| ErrorKind::Generic.context(e))?;
+
+            let check = report.check_mut(BALANCE_AGGREGATES);
+            check.passed = true;
+            check.repaired = true;
+        }
+
+        report.passed = report.checks.iter().all(|check| check.passed);
+
+        if self.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).expect("report is serializable")
+            );
+        } else {
+            for check in &report.checks {
+                println!(
+                    "{}: {}",
+                    check.name,
+                    match (check.passed, check.repaired) {
+                        (true, false) => fl!("cmd-wallet-check-passed"),
+                        (true, true) => fl!("cmd-wallet-check-repaired"),
+                        (false, _) => fl!("cmd-wallet-check-failed"),
+                    },
+                );
+                for problem in &check.problems {
+                    println!("- {problem}");
+                }
+            }
+            if let Some(backup) = &report.backup {
+                println!("{}", fl!("cmd-wallet-check-backup", path = backup.as_str()));
+            }
+        }
+
+        if report.passed {
+            Ok(())
+        } else {
+            let failed = report.checks.iter().filter(|check| !check.passed).count();
+            Err(ErrorKind::Generic
+                .context(if self.repair {
+                    fl!("err-wallet-check-unrepaired", failed = failed)
+                } else {
+                    fl!("err-wallet-check-failed", failed = failed)
+                })
+                .into())
+        }
+    }
+}
+
+impl Runnable for WalletCheckCmd {
+    fn run(&self) {
+        self.run_on_runtime();
+    }
+}
+
+/// Returns the path to back the database at `path` up to before repairing it.
+fn backup_path(path: &std::path::Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(
+        ".backup-{}",
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    ));
+    PathBuf::from(name)
+}
@@ -7,11 +7,15 @@ use tokio::task::JoinHandle;
 
 use crate::error::Error;
 
+pub(crate) mod audit;
 pub(crate) mod chain;
 pub(crate) mod database;
 pub(crate) mod json_rpc;
+pub(crate) mod metrics;
+pub(crate) mod notifications;
 pub(crate) mod sync;
 pub(crate) mod tracing;
+pub(crate) mod wallets;
 
 #[cfg(zallet_build = "wallet")]
 pub(crate) mod keystore;
@@ -0,0 +1,418 @@
+//! An append-only log of spending and key operations.
+//!
+//! If `external.audit_log` is set, Zallet appends an entry to it for each operation that
+//! spends funds or changes the wallet's keys. Each entry is a single line of JSON that
+//! includes the SHA-256 hash of the previous entry, along with its own hash. Altering,
+//! removing, or reordering entries therefore breaks the chain of hashes, which
+//! `zallet audit verify` detects. Truncation of the most recent entries can only be
+//! detected by comparing against a previously-recorded hash of the last entry, so
+//! operators should periodically record it somewhere else.
+//!
+//! Entries never contain secrets: there are no spending keys, mnemonics, passphrases, or
+//! memo contents in the log.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::prelude::*;
+
+/// The `prev_hash` of the first entry in a log.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// The key under which each entry's hash is recorded.
+///
+/// The hash is always the last field of an entry, and covers the preceding fields.
+const HASH_FIELD: &str = ",\"hash\":\"";
+
+/// The length of the hex encoding of a hash.
+const HASH_LEN: usize = 64;
+
+/// How much of the log to read at a time when looking for the last entry.
+const TAIL_CHUNK: u64 = 4096;
+
+/// An operation recorded in the audit log.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum Event {
+    /// `zallet start` started with the configuration in effect.
+    ///
+    /// Zallet only reads its configuration at startup, so this also records any changes
+    /// to the configuration.
+    Started { version: &'static str },
+    /// An async operation that spends funds was submitted.
+    SendSubmitted {
+        method: String,
+        operation_id: String,
+        params: Value,
+    },
+    /// An async operation that spends funds succeeded.
+    SendCompleted {
+        operation_id: String,
+        txids: Vec<String>,
+    },
+    /// An async operation that spends funds failed.
+    ///
+    /// `txids` lists any transactions that were created before it failed.
+    SendFailed {
+        operation_id: String,
+        error: String,
+        txids: Vec<String>,
+    },
+    /// A new mnemonic phrase was generated and added to the wallet.
+    MnemonicGenerated { seed_fingerprint: String },
+    /// A mnemonic phrase was imported into the wallet.
+    MnemonicImported { seed_fingerprint: String },
+    /// A mnemonic phrase was exported from the wallet.
+    MnemonicExported {
+        seed_fingerprint: String,
+        account_uuid: String,
+    },
+    /// The user confirmed that they backed up one of the wallet's mnemonic phrases.
+    BackupConfirmed { seed_fingerprint: String },
+    /// A new account was derived from one of the wallet's mnemonic phrases.
+    AccountCreated {
+        account_uuid: String,
+        seed_fingerprint: String,
+    },
+    /// The wallet's keys were exported to a file in `external.export_dir`.
+    WalletExported {
+        method: String,
+        path: PathBuf,
+        /// Whether the export was encrypted with age.
+        encrypted: bool,
+    },
+    /// A wallet dump was imported with `z_importwallet`.
+    WalletImported { path: PathBuf },
+    /// A spending key was imported with `z_importkey`.
+    SpendingKeyImported { account_uuid: String },
+    /// The spending key for an address was exported with `z_exportkey`.
+    SpendingKeyExported { address: String },
+    /// The keys of a `zcashd` wallet were imported into the wallet.
+    WalletKeysImported { source: PathBuf },
+    /// The wallet was unlocked with its passphrase.
+    WalletUnlocked { timeout: u64 },
+    /// The wallet was locked.
+    WalletLocked,
+    /// The passphrase of the wallet's age identity file was changed.
+    WalletPassphraseChanged,
+    /// The wallet's plaintext age identity file was encrypted with a passphrase.
+    WalletPassphraseAdded,
+    /// The wallet's age identity file was decrypted, and is no longer protected by a
+    /// passphrase.
+    WalletPassphraseRemoved,
+    /// The recipients that the wallet's secrets are encrypted to were initialized.
+    EncryptionInitialized,
+}
+
+/// An entry in the audit log, without its hash.
+#[derive(Serialize)]
+struct Entry<'a> {
+    seq: u64,
+    timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<&'a str>,
+    #[serde(flatten)]
+    event: &'a Event,
+    prev_hash: &'a str,
+}
+
+/// Records an event in the audit log, if it is enabled.
+///
+/// If the event is being recorded while handling a JSON-RPC call, the call's correlation
+/// ID is included in the entry.
+pub(crate) fn record(event: Event) {
+    #[cfg(zallet_build = "wallet")]
+    let request_id = super::json_rpc::server::RequestId::current().map(|id| id.to_string());
+    #[cfg(not(zallet_build = "wallet"))]
+    let request_id = None;
+
+    record_for(request_id.as_deref(), event);
+}
+
+/// Records an event in the audit log on behalf of the JSON-RPC call with the given
+/// correlation ID, if the log is enabled.
+///
+/// A failure to write to the log is reported as an error, but does not otherwise affect
+/// the operation being recorded.
+pub(crate) fn record_for(request_id: Option<&str>, event: Event) {
+    record_to(APP.config().audit_log_path().as_deref(), request_id, event);
+}
+
+/// Records an event in the audit log at `path`, if given, on behalf of the JSON-RPC call
+/// with the given correlation ID.
+pub(crate) fn record_to(path: Option<&Path>, request_id: Option<&str>, event: Event) {
+    let Some(path) = path else {
+        return;
+    };
+
+    if let Err(e) = append(path, request_id, &event, SystemTime::now()) {
+        error!(
+            "Failed to write to audit log {}: {e}; the event was {event:?}",
+            path.display(),
+        );
+    }
+}
+
+/// Returns the parameters of a spending operation with the contents of any memos
+/// replaced by `true`, so that the log records whether each output has a memo.
+pub(crate) fn redact_memos(mut params: Value) -> Value {
+    if let Some(amounts) = params.get_mut("amounts").and_then(Value::as_array_mut) {
+        for memo in amounts
+            .iter_mut()
+            .filter_map(|amount| amount.get_mut("memo"))
+        {
+            *memo = Value::Bool(true);
+        }
+    }
+    if let Some(memo) = params.get_mut("memo").filter(|memo| !memo.is_null()) {
+        *memo = Value::Bool(true);
+    }
+    params
+}
+
+/// Appends an entry for `event` to the log at `path`.
+fn append(
+    path: &Path,
+    request_id: Option<&str>,
+    event: &Event,
+    time: SystemTime,
+) -> io::Result<()> {
+    // Several Zallet processes may record events at the same time (for example,
+    // `zallet start` and `zallet export-mnemonic`).
+    let lock_path = sidecar_path(path, "lock");
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&lock_path)?;
+    let _guard = fmutex::lock_exclusive_path(&lock_path)?;
+
+    let (seq, prev_hash) = match last_line(path)? {
+        None => (0, GENESIS_HASH.to_string()),
+        Some(line) => {
+            let (entry, hash) = split_entry(&line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the last entry is malformed; run `zallet audit verify`",
+                )
+            })?;
+            let seq = serde_json::from_str::<Value>(&entry)
+                .ok()
+                .and_then(|entry| entry.get("seq").and_then(Value::as_u64))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "the last entry is malformed; run `zallet audit verify`",
+                    )
+                })?;
+            (seq + 1, hash.to_string())
+        }
+    };
+
+    let entry = serde_json::to_string(&Entry {
+        seq,
+        timestamp: time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        request_id,
+        event,
+        prev_hash: &prev_hash,
+    })
+    .map_err(io::Error::other)?;
+    let hash = hex::encode(Sha256::digest(entry.as_bytes()));
+    let line = format!(
+        "{}{HASH_FIELD}{hash}\"}}\n",
+        entry.strip_suffix('}').expect("entry is a JSON object"),
+    );
+
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.write_all(line.as_bytes())?;
+    file.sync_data()
+}
+
+/// Returns the path of a file stored alongside the log at `path`.
+fn sidecar_path(path: &Path, extension: &str) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".");
+    sidecar.push(extension);
+    sidecar.into()
+}
+
+/// Returns the last line of the log at `path`, or `None` if the log is empty or does not
+/// exist.
+fn last_line(path: &Path) -> io::Result<Option<String>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    // Read backwards from the end of the file until we find the start of the last line.
+    let mut end = file.metadata()?.len();
+    let mut tail = vec![];
+    let line = loop {
+        let start = end.saturating_sub(TAIL_CHUNK);
+        let mut chunk = vec![0; usize::try_from(end - start).expect("bounded by TAIL_CHUNK")];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+        end = start;
+
+        let content = tail.strip_suffix(b"\n").unwrap_or(&tail);
+        if let Some(pos) = content.iter().rposition(|&b| b == b'\n') {
+            break &content[pos + 1..];
+        } else if end == 0 {
+            break content;
+        }
+    };
+
+    if line.is_empty() {
+        Ok(None)
+    } else {
+        String::from_utf8(line.to_vec())
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Splits a line of the log into the entry that was hashed, and its hash.
+fn split_entry(line: &str) -> Option<(String, &str)> {
+    let rest = line.strip_suffix("\"}")?;
+    let (entry, hash) = rest.split_at_checked(rest.len().checked_sub(HASH_LEN)?)?;
+    let entry = entry.strip_suffix(HASH_FIELD)?;
+    hash.bytes()
+        .all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase())
+        .then(|| (format!("{entry}}}"), hash))
+}
+
+/// The result of verifying an audit log.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Verification {
+    /// Every entry in the log is intact.
+    Valid {
+        /// The number of entries in the log.
+        entries: u64,
+        /// The hash of the last entry, or `None` if the log is empty.
+        last_hash: Option<String>,
+    },
+    /// The given entry (counting from 1) does not match the entries before it.
+    Broken { line: u64, reason: String },
+}
+
+/// Checks the chain of hashes in the audit log at `path`.
+pub(crate) fn verify(path: &Path) -> io::Result<Verification> {
+    let reader = BufReader::new(File::open(path)?);
+
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut entries = 0;
+    for line in reader.lines() {
+        let line = line?;
+        let broken = |reason: &str| Verification::Broken {
+            line: entries + 1,
+            reason: reason.into(),
+        };
+
+        let Some((entry, hash)) = split_entry(&line) else {
+            return Ok(broken("the entry is malformed"));
+        };
+        if hex::encode(Sha256::digest(entry.as_bytes())) != hash {
+            return Ok(broken("the entry does not match its hash"));
+        }
+        let Ok(entry) = serde_json::from_str::<Value>(&entry) else {
+            return Ok(broken("the entry is malformed"));
+        };
+        if entry.get("prev_hash").and_then(Value::as_str) != Some(prev_hash.as_str()) {
+            return Ok(broken("the hash of the previous entry does not match"));
+        }
+        if entry.get("seq").and_then(Value::as_u64) != Some(entries) {
+            return Ok(broken("the entry is out of sequence"));
+        }
+
+        prev_hash = hash.to_string();
+        entries += 1;
+    }
+
+    Ok(Verification::Valid {
+        entries,
+        last_hash: (entries > 0).then_some(prev_hash),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde_json::json;
+
+    use super::{Event, Verification, append, redact_memos, verify};
+
+    #[test]
+    fn memos_are_redacted() {
+        assert_eq!(
+            redact_memos(json!({
+                "amounts": [{ "address": "a", "memo": "ff" }, { "address": "b" }],
+            })),
+            json!({ "amounts": [{ "address": "a", "memo": true }, { "address": "b" }] }),
+        );
+        assert_eq!(
+            redact_memos(json!({ "toaddress": "a", "memo": "ff" })),
+            json!({ "toaddress": "a", "memo": true }),
+        );
+        assert_eq!(
+            redact_memos(json!({ "toaddress": "a", "memo": null })),
+            json!({ "toaddress": "a", "memo": null }),
+        );
+    }
+
+    #[test]
+    fn tampering_breaks_the_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        for timeout in [60, 120, 180] {
+            append(
+                &path,
+                Some("req-1"),
+                &Event::WalletUnlocked { timeout },
+                time,
+            )
+            .unwrap();
+        }
+        append(&path, None, &Event::WalletLocked, SystemTime::now()).unwrap();
+
+        let Verification::Valid { entries, last_hash } = verify(&path).unwrap() else {
+            panic!("log should be valid");
+        };
+        assert_eq!(entries, 4);
+        assert!(last_hash.is_some());
+
+        // Altering an entry is detected at that entry.
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, log.replacen("\"timeout\":120", "\"timeout\":999", 1)).unwrap();
+        assert!(matches!(
+            verify(&path).unwrap(),
+            Verification::Broken { line: 2, .. },
+        ));
+
+        // Removing an entry is detected at the entry after it.
+        let mut lines = log.lines().collect::<Vec<_>>();
+        lines.remove(1);
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+        assert!(matches!(
+            verify(&path).unwrap(),
+            Verification::Broken { line: 2, .. },
+        ));
+    }
+}
@@ -1,28 +1,143 @@
 #![allow(deprecated)] // For zaino
 
+use std::collections::HashMap;
 use std::fmt;
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use jsonrpsee::tracing::{error, info};
+use jsonrpsee::tracing::{debug, error, info, warn};
 use tokio::net::lookup_host;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, watch};
 use zaino_common::{CacheConfig, DatabaseConfig, ServiceConfig, StorageConfig};
+use zaino_proto::proto::service::BlockId;
 use zaino_state::{
     FetchService, FetchServiceConfig, FetchServiceSubscriber, IndexerService, IndexerSubscriber,
-    StatusType, ZcashService,
+    LightWalletIndexer as _, StatusType, ZcashService,
 };
+use zcash_primitives::block::BlockHash;
+use zcash_protocol::consensus::{BlockHeight, NetworkType, Parameters};
 
 use crate::{
     config::ZalletConfig,
     error::{Error, ErrorKind},
+    fl,
+    network::Network,
 };
 
 use super::TaskHandle;
 
+mod failover;
+use failover::{CandidateView, Checkpoints, Health, Reason};
+
+mod proxy;
+use proxy::Proxy;
+
+/// How often the backend is asked for its chain tip, to measure its responsiveness.
+const PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long the backend may take to report its chain tip before the probe fails.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a backend that Zallet is failing over to may take to connect, and to answer
+/// the checks made before it is used.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to wait for Zallet to notice that the backend is unusable, after a request to
+/// it fails.
+///
+/// This is long enough for several probes to fail.
+const RECONNECT_GRACE: Duration = Duration::from_secs(60);
+
 #[derive(Clone)]
 pub(crate) struct Chain {
     // TODO: Migrate to `StateService`.
     indexer: Arc<RwLock<Option<IndexerService<FetchService>>>>,
+    /// Whether the indexer was ready to serve requests when its status was last polled.
+    reachable: Arc<AtomicBool>,
+    /// What was last observed about the backends, for `getbackendinfo`.
+    backends: Arc<std::sync::RwLock<Backends>>,
+    /// What the probe has observed about the backend in use, for deciding when to fail
+    /// over.
+    monitor: Arc<Mutex<Monitor>>,
+    connection: Arc<watch::Sender<Connection>>,
+    /// The local ports that relay connections to each backend through
+    /// `indexer.proxy`, if it is set.
+    relays: Arc<HashMap<String, SocketAddr>>,
+}
+
+/// Whether the chain indexer is connected to a backend.
+///
+/// Subscribers to the indexer keep using the connection that they were created with, so
+/// they need to be replaced when Zallet reconnects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Connection {
+    /// The indexer is connected. The count increases each time Zallet reconnects.
+    Connected(u64),
+    /// Zallet is looking for a backend to reconnect to.
+    Reconnecting,
+}
+
+/// The full nodes that Zallet can use as its chain source.
+///
+/// Zallet uses one backend at a time, both for scanning and for broadcasting
+/// transactions. The others are failed over to if it becomes unusable.
+#[derive(Clone, Debug)]
+pub(crate) struct Backends {
+    /// The backends, in order of preference.
+    pub(crate) list: Vec<BackendInfo>,
+    /// The index in [`Backends::list`] of the backend in use.
+    pub(crate) active: usize,
+    /// When Zallet last switched to a different backend.
+    pub(crate) last_failover: Option<SystemTime>,
+}
+
+/// What Zallet last observed about a full node that it can use as its chain source.
+#[derive(Clone, Debug)]
+pub(crate) struct BackendInfo {
+    /// The address of the backend, as configured in `indexer.validator_address` or
+    /// `indexer.fallback_validator_addresses`.
+    pub(crate) address: String,
+    /// The socket address that [`BackendInfo::address`] resolved to, when Zallet last
+    /// connected to it.
+    ///
+    /// If `indexer.proxy` is set, this is instead the local port that relays connections
+    /// to the backend through the proxy.
+    pub(crate) resolved_address: Option<SocketAddr>,
+    /// The status of the indexer connected to the backend, when it was last polled.
+    pub(crate) status: Option<StatusType>,
+    /// The chain tip that the backend last reported.
+    pub(crate) tip: Option<(BlockHeight, BlockHash)>,
+    /// How long the backend took to report its chain tip, when last probed.
+    pub(crate) latency: Option<Duration>,
+    /// When the backend last reported its chain tip.
+    pub(crate) last_success: Option<Instant>,
+    /// Why the backend could not be used, if the most recent attempt failed.
+    pub(crate) last_error: Option<String>,
+}
+
+impl Backends {
+    /// Returns the backend in use.
+    pub(crate) fn active(&self) -> &BackendInfo {
+        &self.list[self.active]
+    }
+
+    /// Returns when Zallet last switched to a different backend, in seconds since the
+    /// Unix epoch.
+    pub(crate) fn last_failover_time(&self) -> Option<u64> {
+        self.last_failover.map(|time| {
+            time.duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs())
+        })
+    }
+}
+
+struct Monitor {
+    health: Health,
+    checkpoints: Checkpoints,
 }
 
 impl fmt::Debug for Chain {
@@ -33,94 +148,114 @@ impl fmt::Debug for Chain {
 
 impl Chain {
     pub(crate) async fn new(config: &ZalletConfig) -> Result<(Self, TaskHandle), Error> {
-        let resolved_validator_address = match config.indexer.validator_address.as_deref() {
-            Some(addr_str) => match lookup_host(addr_str).await {
-                Ok(mut addrs) => match addrs.next() {
-                    Some(socket_addr) => {
-                        info!(
-                            "Resolved validator_address '{}' to {}",
-                            addr_str, socket_addr
-                        );
-                        Ok(socket_addr)
-                    }
-                    None => {
-                        error!(
-                            "validator_address '{}' resolved to no IP addresses",
-                            addr_str
-                        );
-                        Err(ErrorKind::Init.context(format!(
-                            "validator_address '{addr_str}' resolved to no IP addresses"
-                        )))
-                    }
-                },
-                Err(e) => {
-                    error!("Failed to resolve validator_address '{}': {}", addr_str, e);
-                    Err(ErrorKind::Init.context(format!(
-                        "Failed to resolve validator_address '{addr_str}': {e}"
-                    )))
+        let addresses = config.validator_addresses();
+        if config.indexer.validator_address.is_none() {
+            info!("validator_address not set, defaulting to {}", addresses[0]);
+        }
+        let mut backends = Backends {
+            list: addresses
+                .iter()
+                .map(|address| BackendInfo {
+                    address: address.clone(),
+                    resolved_address: None,
+                    status: None,
+                    tip: None,
+                    latency: None,
+                    last_success: None,
+                    last_error: None,
+                })
+                .collect(),
+            active: 0,
+            last_failover: None,
+        };
+
+        // If a proxy is configured, every connection to a backend goes through it.
+        let mut relays = HashMap::new();
+        if let Some(proxy_addr) = &config.indexer.proxy {
+            let proxy = Proxy::check(proxy_addr).await.map_err(|e| {
+                ErrorKind::Init.context(fl!(
+                    "err-init-indexer-proxy-unreachable",
+                    proxy = proxy_addr.as_str(),
+                    error = e.to_string(),
+                ))
+            })?;
+            for address in &addresses {
+                let relay = proxy
+                    .relay(address)
+                    .await
+                    .map_err(|e| ErrorKind::Init.context(e))?;
+                relays.insert(address.clone(), relay);
+            }
+        }
+
+        // Use the first backend that works. If there are no others to fall back to,
+        // connect as before without checking which chain it is on.
+        let mut connected = None;
+        for (index, address) in addresses.iter().enumerate() {
+            let relay = relays.get(address).copied();
+            let res = if addresses.len() == 1 {
+                spawn_indexer(config, address, relay).await
+            } else {
+                connect_to(config, address, relay, None, None)
+                    .await
+                    .map_err(|e| ErrorKind::Init.context(e).into())
+            };
+            match res {
+                Ok((resolved_address, service)) => {
+                    backends.active = index;
+                    backends.list[index].resolved_address = Some(resolved_address);
+                    connected = Some(service);
+                    break;
                 }
-            },
-            None => {
-                // Default to localhost and standard port based on network
-                let default_port = match config.consensus.network() {
-                    crate::network::Network::Consensus(
-                        zcash_protocol::consensus::Network::MainNetwork,
-                    ) => 8232, // Mainnet default RPC port for Zebra/zcashd
-                    _ => 18232, // Testnet/Regtest default RPC port for Zebra/zcashd
-                };
-                let default_addr_str = format!("127.0.0.1:{default_port}");
-                info!(
-                    "validator_address not set, defaulting to {}",
-                    default_addr_str
-                );
-                match default_addr_str.parse::<std::net::SocketAddr>() {
-                    Ok(socket_addr) => Ok(socket_addr),
-                    Err(e) => {
-                        // This should ideally not happen with a hardcoded IP and port
-                        error!(
-                            "Failed to parse default validator_address '{}': {}",
-                            default_addr_str, e
-                        );
-                        Err(ErrorKind::Init.context(format!(
-                            "Failed to parse default validator_address '{default_addr_str}': {e}"
-                        )))
-                    }
+                Err(e) if index + 1 < addresses.len() => {
+                    warn!("Not using chain backend {address}: {e}");
+                    backends.list[index].last_error = Some(e.to_string());
                 }
+                Err(e) => return Err(e),
             }
-        }?;
-
-        let config = FetchServiceConfig::new(
-            resolved_validator_address,
-            config.indexer.validator_cookie_path.clone(),
-            config.indexer.validator_user.clone(),
-            config.indexer.validator_password.clone(),
-            ServiceConfig::default(),
-            StorageConfig {
-                cache: CacheConfig::default(),
-                database: DatabaseConfig {
-                    path: config.indexer_db_path().to_path_buf(),
-                    // Setting this to as non-zero value causes start-up to block on
-                    // completely filling the cache. Zaino's DB currently only contains a
-                    // cache of CompactBlocks, so we make do for now with uncached queries.
-                    // TODO: https://github.com/zingolabs/zaino/issues/249
-                    size: zaino_common::DatabaseSize::Gb(0),
-                },
-            },
-            config.consensus.network().to_zaino(),
-        );
-
-        info!("Starting Zaino indexer");
+        }
         let indexer = Arc::new(RwLock::new(Some(
-            IndexerService::<FetchService>::spawn(config)
-                .await
-                .map_err(|e| ErrorKind::Init.context(e))?,
+            connected.expect("returned early if none"),
         )));
 
         let chain = Self {
-            indexer: indexer.clone(),
+            indexer,
+            reachable: Arc::new(AtomicBool::new(false)),
+            backends: Arc::new(std::sync::RwLock::new(backends)),
+            monitor: Arc::new(Mutex::new(Monitor {
+                health: Health::new(),
+                checkpoints: Checkpoints::default(),
+            })),
+            connection: Arc::new(watch::Sender::new(Connection::Connected(0))),
+            relays: Arc::new(relays),
+        };
+
+        // Spawn a task that periodically measures how quickly the backend responds.
+        let probed = chain.clone();
+        crate::spawn!("Backend probe", async move {
+            let mut interval = tokio::time::interval(PROBE_INTERVAL);
+            loop {
+                interval.tick().await;
+                if *probed.connection.borrow() == Connection::Reconnecting {
+                    continue;
+                }
+                if probed.indexer.read().await.is_none() {
+                    return;
+                }
+                probed.probe().await;
+            }
+        });
+
+        // Regtest blocks are only mined on request, so a stalled tip is expected there.
+        let stall_timeout = match config.consensus.network().network_type() {
+            NetworkType::Regtest => None,
+            _ => Some(failover::STALL_TIMEOUT),
         };
 
-        // Spawn a task that stops the indexer when appropriate internal signals occur.
+        // Spawn a task that stops the indexer when appropriate internal signals occur,
+        // and fails over to another backend if the one in use becomes unusable.
+        let supervised = chain.clone();
+        let config = config.clone();
         let task = crate::spawn!("Indexer shutdown", async move {
             let mut server_interval =
                 tokio::time::interval(tokio::time::Duration::from_millis(100));
@@ -128,31 +263,242 @@ impl Chain {
             loop {
                 server_interval.tick().await;
 
-                let service = indexer.read().await;
-                let status = match service.as_ref() {
+                let status = match supervised.indexer.read().await.as_ref() {
                     Some(service) => service.inner_ref().status().await,
                     None => StatusType::CriticalError,
                 };
-
-                // Check for errors.
-                if matches!(status, StatusType::Offline | StatusType::CriticalError) {
-                    let service = indexer.write().await.take().expect("only happens once");
-                    service.inner().close();
-                    return Err(ErrorKind::Generic.into());
-                }
+                supervised.reachable.store(
+                    matches!(status, StatusType::Ready | StatusType::Busy),
+                    Ordering::Relaxed,
+                );
+                supervised.update_active(|backend| backend.status = Some(status));
 
                 // Check for shutdown signals.
                 if status == StatusType::Closing {
-                    let service = indexer.write().await.take().expect("only happens once");
-                    service.inner().close();
+                    supervised.close().await;
                     return Ok(());
                 }
+
+                // Check for errors.
+                let reason = if matches!(status, StatusType::Offline | StatusType::CriticalError) {
+                    Some(Reason::Down(status))
+                } else {
+                    let monitor = supervised.monitor.lock().expect("not poisoned");
+                    monitor.health.check(Instant::now(), stall_timeout)
+                };
+                match reason {
+                    Some(reason) if supervised.has_fallbacks() => {
+                        supervised.fail_over(&config, reason).await;
+                    }
+                    // With no other backend to switch to, Zallet stops.
+                    Some(Reason::Down(_)) => {
+                        supervised.close().await;
+                        return Err(ErrorKind::Generic.into());
+                    }
+                    _ => (),
+                }
             }
         });
 
         Ok((chain, task))
     }
 
+    /// Returns `true` if the chain indexer was ready to serve requests when its status
+    /// was last checked.
+    ///
+    /// This never waits on the indexer.
+    pub(crate) fn is_reachable(&self) -> bool {
+        self.reachable.load(Ordering::Relaxed)
+    }
+
+    /// Returns what was last observed about each of the configured chain backends.
+    ///
+    /// This never waits on the indexer.
+    pub(crate) fn backends(&self) -> Backends {
+        self.backends.read().expect("not poisoned").clone()
+    }
+
+    /// Returns `true` if Zallet can fail over to another chain backend.
+    pub(crate) fn has_fallbacks(&self) -> bool {
+        self.backends.read().expect("not poisoned").list.len() > 1
+    }
+
+    /// Returns a receiver that is notified when the indexer reconnects.
+    pub(crate) fn connection(&self) -> watch::Receiver<Connection> {
+        self.connection.subscribe()
+    }
+
+    /// Waits for the indexer to reconnect, after a request through connection `since`
+    /// failed.
+    ///
+    /// Returns `false` if Zallet did not start reconnecting soon after `since` failed,
+    /// meaning that the failure was not caused by the backend becoming unusable.
+    pub(crate) async fn wait_for_reconnect(&self, since: Connection) -> bool {
+        let mut connection = self.connection();
+        let reconnecting = matches!(
+            tokio::time::timeout(RECONNECT_GRACE, connection.wait_for(|c| *c != since)).await,
+            Ok(Ok(_)),
+        );
+        if !reconnecting {
+            return false;
+        }
+        connection
+            .wait_for(|c| matches!(c, Connection::Connected(_)))
+            .await
+            .is_ok()
+    }
+
+    fn update_active(&self, f: impl FnOnce(&mut BackendInfo)) {
+        let mut backends = self.backends.write().expect("not poisoned");
+        let active = backends.active;
+        f(&mut backends.list[active]);
+    }
+
+    /// Stops the indexer, if it is running.
+    async fn close(&self) {
+        if let Some(service) = self.indexer.write().await.take() {
+            service.inner().close();
+        }
+    }
+
+    /// Replaces the backend in use, which has become unusable for the given reason.
+    ///
+    /// This keeps trying the configured backends in turn, with exponential backoff,
+    /// until one of them can be used.
+    async fn fail_over(&self, config: &ZalletConfig, reason: Reason) {
+        let (from, addresses) = {
+            let backends = self.backends.read().expect("not poisoned");
+            let addresses = backends
+                .list
+                .iter()
+                .map(|backend| backend.address.clone())
+                .collect::<Vec<_>>();
+            (backends.active, addresses)
+        };
+        info!(
+            "Chain backend {} {reason}; looking for another backend",
+            addresses[from],
+        );
+
+        let generation = match *self.connection.borrow() {
+            Connection::Connected(generation) => generation,
+            Connection::Reconnecting => unreachable!("only the supervisor reconnects"),
+        };
+        self.connection.send_replace(Connection::Reconnecting);
+        self.close().await;
+        self.reachable.store(false, Ordering::Relaxed);
+        self.update_active(|backend| {
+            backend.status = None;
+            backend.last_error = Some(format!("Stopped using this backend: it {reason}"));
+        });
+
+        // The replacement must agree with the chain that the old backend reported.
+        let checkpoint = self
+            .monitor
+            .lock()
+            .expect("not poisoned")
+            .checkpoints
+            .settled();
+
+        for round in 0u32.. {
+            for candidate in failover::candidates(addresses.len(), from) {
+                // At first, a stalled backend is only replaced by one that is ahead of it.
+                let ahead_of = match reason {
+                    Reason::Stalled(height) if round == 0 && candidate != from => Some(height),
+                    _ => None,
+                };
+                let address = &addresses[candidate];
+                let relay = self.relays.get(address).copied();
+                match connect_to(config, address, relay, checkpoint, ahead_of).await {
+                    Ok((resolved_address, service)) => {
+                        *self.indexer.write().await = Some(service);
+                        self.monitor.lock().expect("not poisoned").health = Health::new();
+                        {
+                            let mut backends = self.backends.write().expect("not poisoned");
+                            backends.active = candidate;
+                            let backend = &mut backends.list[candidate];
+                            backend.resolved_address = Some(resolved_address);
+                            backend.last_error = None;
+                            if candidate != from {
+                                backends.last_failover = Some(SystemTime::now());
+                            }
+                        }
+                        if candidate == from {
+                            info!("Reconnected to chain backend {address}");
+                        } else {
+                            info!(
+                                "Switched chain backend from {} to {address}",
+                                addresses[from],
+                            );
+                        }
+                        self.connection
+                            .send_replace(Connection::Connected(generation + 1));
+                        return;
+                    }
+                    Err(e) => {
+                        warn!("Not using chain backend {address}: {e}");
+                        self.backends.write().expect("not poisoned").list[candidate].last_error =
+                            Some(e);
+                    }
+                }
+            }
+
+            let delay = failover::backoff(round);
+            warn!(
+                "No chain backend is usable; trying again in {} seconds",
+                delay.as_secs(),
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Asks the backend for its chain tip, and records how long it took to respond.
+    async fn probe(&self) {
+        let connection = *self.connection.borrow();
+        let start = Instant::now();
+        let res = match self.subscribe().await {
+            Ok(subscriber) => {
+                tokio::time::timeout(PROBE_TIMEOUT, subscriber.inner().get_latest_block())
+                    .await
+                    .map_err(|_| format!("No response within {} seconds", PROBE_TIMEOUT.as_secs()))
+                    .and_then(|res| res.map_err(|e| e.to_string()))
+                    .and_then(|block_id| {
+                        let height = u32::try_from(block_id.height)
+                            .map_err(|_| "Invalid tip height".to_string())?;
+                        let hash = BlockHash::try_from_slice(&block_id.hash)
+                            .ok_or_else(|| "Invalid tip hash".to_string())?;
+                        Ok((BlockHeight::from_u32(height), hash))
+                    })
+            }
+            Err(e) => Err(e.to_string()),
+        };
+
+        // Ignore the result if Zallet reconnected while the backend was being probed.
+        if *self.connection.borrow() != connection {
+            return;
+        }
+
+        let mut monitor = self.monitor.lock().expect("not poisoned");
+        let mut backends = self.backends.write().expect("not poisoned");
+        let active = backends.active;
+        let backend = &mut backends.list[active];
+        match res {
+            Ok(tip) => {
+                monitor.health.succeeded(tip.0, Instant::now());
+                monitor.checkpoints.record(tip.0, tip.1);
+                backend.tip = Some(tip);
+                backend.latency = Some(start.elapsed());
+                backend.last_success = Some(Instant::now());
+                backend.last_error = None;
+            }
+            Err(e) => {
+                debug!("Failed to probe the chain backend: {e}");
+                monitor.health.failed();
+                backend.last_error = Some(e);
+            }
+        }
+    }
+
     pub(crate) async fn subscribe(
         &self,
     ) -> Result<IndexerSubscriber<FetchServiceSubscriber>, Error> {
@@ -166,3 +512,159 @@ impl Chain {
             .get_subscriber())
     }
 }
+
+/// Resolves the address of a backend.
+async fn resolve(addr_str: &str) -> Result<SocketAddr, Error> {
+    match lookup_host(addr_str).await {
+        Ok(mut addrs) => match addrs.next() {
+            Some(socket_addr) => {
+                info!(
+                    "Resolved validator_address '{}' to {}",
+                    addr_str, socket_addr
+                );
+                Ok(socket_addr)
+            }
+            None => {
+                error!(
+                    "validator_address '{}' resolved to no IP addresses",
+                    addr_str
+                );
+                Err(ErrorKind::Init
+                    .context(format!(
+                        "validator_address '{addr_str}' resolved to no IP addresses"
+                    ))
+                    .into())
+            }
+        },
+        Err(e) => {
+            error!("Failed to resolve validator_address '{}': {}", addr_str, e);
+            Err(ErrorKind::Init
+                .context(format!(
+                    "Failed to resolve validator_address '{addr_str}': {e}"
+                ))
+                .into())
+        }
+    }
+}
+
+/// Starts an indexer that uses the backend at the given address.
+///
+/// If `relay` is set, the indexer connects to it instead of resolving `address`.
+async fn spawn_indexer(
+    config: &ZalletConfig,
+    address: &str,
+    relay: Option<SocketAddr>,
+) -> Result<(SocketAddr, IndexerService<FetchService>), Error> {
+    let resolved_validator_address = match relay {
+        Some(relay) => relay,
+        None => resolve(address).await?,
+    };
+
+    let config = FetchServiceConfig::new(
+        resolved_validator_address,
+        config.indexer.validator_cookie_path.clone(),
+        config.indexer.validator_user.clone(),
+        config.indexer.validator_password.clone(),
+        ServiceConfig::default(),
+        StorageConfig {
+            cache: CacheConfig::default(),
+            database: DatabaseConfig {
+                path: config.indexer_db_path().to_path_buf(),
+                // Setting this to as non-zero value causes start-up to block on
+                // completely filling the cache. Zaino's DB currently only contains a
+                // cache of CompactBlocks, so we make do for now with uncached queries.
+                // TODO: https://github.com/zingolabs/zaino/issues/249
+                size: zaino_common::DatabaseSize::Gb(0),
+            },
+        },
+        config.consensus.network().to_zaino(),
+    );
+
+    info!("Starting Zaino indexer");
+    let service = IndexerService::<FetchService>::spawn(config)
+        .await
+        .map_err(|e| ErrorKind::Init.context(e))?;
+
+    Ok((resolved_validator_address, service))
+}
+
+/// Starts an indexer that uses the backend at the given address, once the backend is
+/// found to be on the expected chain.
+///
+/// The indexers of all backends share a database, so this must only be called while no
+/// other indexer is running.
+async fn connect_to(
+    config: &ZalletConfig,
+    address: &str,
+    relay: Option<SocketAddr>,
+    checkpoint: Option<(BlockHeight, BlockHash)>,
+    ahead_of: Option<BlockHeight>,
+) -> Result<(SocketAddr, IndexerService<FetchService>), String> {
+    let (resolved_address, service) =
+        tokio::time::timeout(CONNECT_TIMEOUT, spawn_indexer(config, address, relay))
+            .await
+            .map_err(|_| format!("Not connected within {} seconds", CONNECT_TIMEOUT.as_secs()))?
+            .map_err(|e| e.to_string())?;
+
+    let subscriber = service.inner_ref().get_subscriber().inner();
+    let params = config.consensus.network();
+    let checked = tokio::time::timeout(CONNECT_TIMEOUT, async {
+        verify(&subscriber, &params, checkpoint, ahead_of).await
+    })
+    .await
+    .unwrap_or_else(|_| {
+        Err(format!(
+            "No response within {} seconds",
+            CONNECT_TIMEOUT.as_secs()
+        ))
+    });
+
+    match checked {
+        Ok(()) => Ok((resolved_address, service)),
+        Err(e) => {
+            service.inner().close();
+            Err(e)
+        }
+    }
+}
+
+/// Checks that a backend is on the same chain as the `checkpoint` block, and (if
+/// `ahead_of` is set) that its chain tip is above that height.
+async fn verify(
+    chain: &FetchServiceSubscriber,
+    params: &Network,
+    checkpoint: Option<(BlockHeight, BlockHash)>,
+    ahead_of: Option<BlockHeight>,
+) -> Result<(), String> {
+    let tip = chain
+        .get_latest_block()
+        .await
+        .map_err(|e| e.to_string())?
+        .height;
+    let tip = BlockHeight::from_u32(u32::try_from(tip).map_err(|_| "Invalid tip height")?);
+
+    let info = chain.get_lightd_info().await.map_err(|e| e.to_string())?;
+    let branch_id = u32::from_str_radix(&info.consensus_branch_id, 16)
+        .map_err(|_| format!("Invalid consensus branch ID {}", info.consensus_branch_id))?;
+
+    let checkpoint_hash = match checkpoint {
+        Some((height, _)) if height <= tip => {
+            let block = chain
+                .get_block(BlockId {
+                    height: u32::from(height).into(),
+                    hash: vec![],
+                })
+                .await
+                .map_err(|e| e.to_string())?;
+            BlockHash::try_from_slice(&block.hash)
+        }
+        _ => None,
+    };
+
+    CandidateView {
+        tip,
+        branch_id,
+        checkpoint_hash,
+    }
+    .check(params, checkpoint, ahead_of)
+}
@@ -0,0 +1,349 @@
+//! Deciding when to switch to another full node, and whether to trust it.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use zaino_state::StatusType;
+use zcash_primitives::block::BlockHash;
+use zcash_protocol::consensus::{BlockHeight, BranchId, Parameters};
+
+/// How many probes in a row must fail before Zallet switches to another backend.
+const MAX_PROBE_FAILURES: u32 = 3;
+
+/// How long the backend's chain tip may go without advancing before Zallet looks for a
+/// backend that is further ahead.
+///
+/// The target block interval is 75 seconds, so a gap this long is very unlikely unless
+/// the backend has stopped following the chain.
+pub(super) const STALL_TIMEOUT: Duration = Duration::from_secs(20 * 60);
+
+/// How long to wait before trying the backends again, after none of them could be used.
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+
+/// The longest that Zallet waits between attempts to find a usable backend.
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// How many blocks below the backend's chain tip a block must be before it is used to
+/// check that another backend is on the same chain.
+///
+/// This keeps a reorg of the most recent blocks from making a candidate look like it is
+/// on a different chain.
+const SETTLED_DEPTH: u32 = 10;
+
+/// How many of the backend's recent chain tips are remembered.
+const MAX_CHECKPOINTS: usize = 100;
+
+/// Why Zallet stopped using a backend.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum Reason {
+    /// The indexer lost its connection to the backend.
+    Down(StatusType),
+    /// The backend failed to report its chain tip this many times in a row.
+    Unresponsive(u32),
+    /// The backend's chain tip has not advanced past this height for too long.
+    Stalled(BlockHeight),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Down(status) => write!(f, "is unavailable (indexer status {status:?})"),
+            Reason::Unresponsive(failures) => {
+                write!(
+                    f,
+                    "failed to report its chain tip {failures} times in a row"
+                )
+            }
+            Reason::Stalled(height) => write!(
+                f,
+                "has not advanced past height {height} in {} minutes",
+                STALL_TIMEOUT.as_secs() / 60,
+            ),
+        }
+    }
+}
+
+/// The health of the backend in use, as observed by the probe.
+#[derive(Debug)]
+pub(super) struct Health {
+    failures: u32,
+    /// The most recent chain tip height, and when it was first reported.
+    tip: Option<(BlockHeight, Instant)>,
+}
+
+impl Health {
+    pub(super) fn new() -> Self {
+        Self {
+            failures: 0,
+            tip: None,
+        }
+    }
+
+    pub(super) fn succeeded(&mut self, height: BlockHeight, now: Instant) {
+        self.failures = 0;
+        if self.tip.is_none_or(|(tip, _)| tip != height) {
+            self.tip = Some((height, now));
+        }
+    }
+
+    pub(super) fn failed(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+
+    /// Returns why the backend should no longer be used, if it should not.
+    ///
+    /// Stalls are not detected if `stall_timeout` is `None`.
+    pub(super) fn check(&self, now: Instant, stall_timeout: Option<Duration>) -> Option<Reason> {
+        if self.failures >= MAX_PROBE_FAILURES {
+            return Some(Reason::Unresponsive(self.failures));
+        }
+        match (self.tip, stall_timeout) {
+            (Some((height, since)), Some(timeout)) if now.duration_since(since) >= timeout => {
+                Some(Reason::Stalled(height))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Recent chain tips reported by the backend in use.
+#[derive(Debug, Default)]
+pub(super) struct Checkpoints(VecDeque<(BlockHeight, BlockHash)>);
+
+impl Checkpoints {
+    pub(super) fn record(&mut self, height: BlockHeight, hash: BlockHash) {
+        if self.0.back() == Some(&(height, hash)) {
+            return;
+        }
+        // Forget any tips that were reorged away.
+        while self.0.back().is_some_and(|(h, _)| *h >= height) {
+            self.0.pop_back();
+        }
+        self.0.push_back((height, hash));
+        if self.0.len() > MAX_CHECKPOINTS {
+            self.0.pop_front();
+        }
+    }
+
+    /// Returns the most recent checkpoint that is unlikely to be reorged away.
+    pub(super) fn settled(&self) -> Option<(BlockHeight, BlockHash)> {
+        let (tip, _) = self.0.back()?;
+        self.0
+            .iter()
+            .rev()
+            .find(|(height, _)| *height + SETTLED_DEPTH <= *tip)
+            .copied()
+    }
+}
+
+/// What a candidate backend reported about its view of the chain.
+#[derive(Debug)]
+pub(super) struct CandidateView {
+    pub(super) tip: BlockHeight,
+    pub(super) branch_id: u32,
+    /// The hash of the candidate's block at the height of the checkpoint, if asked for.
+    pub(super) checkpoint_hash: Option<BlockHash>,
+}
+
+impl CandidateView {
+    /// Checks that the candidate is on the same chain as the backend it would replace.
+    ///
+    /// If `ahead_of` is set, the candidate must also have a chain tip above that height.
+    pub(super) fn check(
+        &self,
+        params: &impl Parameters,
+        checkpoint: Option<(BlockHeight, BlockHash)>,
+        ahead_of: Option<BlockHeight>,
+    ) -> Result<(), String> {
+        // Backends may report the branch ID of either their tip or the next block.
+        let expected =
+            [self.tip, self.tip + 1].map(|height| u32::from(BranchId::for_height(params, height)));
+        if !expected.contains(&self.branch_id) {
+            return Err(format!(
+                "it reports consensus branch ID {:08x} at height {}, expected {:08x}",
+                self.branch_id, self.tip, expected[0],
+            ));
+        }
+
+        if let Some((height, hash)) = checkpoint {
+            match self.checkpoint_hash {
+                _ if self.tip < height => {
+                    return Err(format!(
+                        "its chain tip {} is behind the settled block at height {height}",
+                        self.tip,
+                    ));
+                }
+                Some(candidate_hash) if candidate_hash == hash => (),
+                _ => {
+                    return Err(format!(
+                        "it does not have block {hash} at height {height}, so it is on a different chain",
+                    ));
+                }
+            }
+        }
+
+        match ahead_of {
+            Some(height) if self.tip <= height => Err(format!(
+                "its chain tip {} is not ahead of height {height}",
+                self.tip,
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the order in which to try the configured backends, when replacing the one at
+/// index `active`.
+///
+/// The other backends are tried in order of preference, wrapping around, and the
+/// backend being replaced is tried last.
+pub(super) fn candidates(count: usize, active: usize) -> impl Iterator<Item = usize> {
+    (1..=count).map(move |offset| (active + offset) % count)
+}
+
+/// Returns how long to wait before trying the backends again, after `round` rounds in
+/// which none of them could be used.
+pub(super) fn backoff(round: u32) -> Duration {
+    MIN_BACKOFF
+        .saturating_mul(1u32 << round.min(16))
+        .min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use zcash_primitives::block::BlockHash;
+    use zcash_protocol::consensus::{BlockHeight, BranchId, MAIN_NETWORK};
+
+    use super::{
+        CandidateView, Checkpoints, Health, MAX_BACKOFF, MIN_BACKOFF, Reason, backoff, candidates,
+    };
+
+    fn hash(n: u8) -> BlockHash {
+        BlockHash([n; 32])
+    }
+
+    #[test]
+    fn unresponsive_and_stalled_backends() {
+        let start = Instant::now();
+        let timeout = Some(Duration::from_secs(60));
+        let mut health = Health::new();
+        assert_eq!(health.check(start, timeout), None);
+
+        // A few failed probes are tolerated, and a success resets the count.
+        health.failed();
+        health.failed();
+        assert_eq!(health.check(start, timeout), None);
+        health.succeeded(BlockHeight::from_u32(100), start);
+        health.failed();
+        health.failed();
+        assert_eq!(health.check(start, timeout), None);
+        health.failed();
+        assert_eq!(health.check(start, timeout), Some(Reason::Unresponsive(3)));
+
+        // A tip that stops advancing is a stall, unless stalls are not detected.
+        let mut health = Health::new();
+        health.succeeded(BlockHeight::from_u32(100), start);
+        let later = start + Duration::from_secs(59);
+        health.succeeded(BlockHeight::from_u32(100), later);
+        assert_eq!(health.check(later, timeout), None);
+        let later = start + Duration::from_secs(60);
+        assert_eq!(
+            health.check(later, timeout),
+            Some(Reason::Stalled(BlockHeight::from_u32(100)))
+        );
+        assert_eq!(health.check(later, None), None);
+
+        // An advancing tip resets the stall timer.
+        health.succeeded(BlockHeight::from_u32(101), later);
+        assert_eq!(health.check(later + Duration::from_secs(59), timeout), None);
+    }
+
+    #[test]
+    fn settled_checkpoints() {
+        let mut checkpoints = Checkpoints::default();
+        assert_eq!(checkpoints.settled(), None);
+
+        for height in 100..110 {
+            checkpoints.record(BlockHeight::from_u32(height), hash(height as u8));
+        }
+        assert_eq!(checkpoints.settled(), None);
+        checkpoints.record(BlockHeight::from_u32(110), hash(110));
+        assert_eq!(
+            checkpoints.settled(),
+            Some((BlockHeight::from_u32(100), hash(100)))
+        );
+
+        // Tips that are reorged away are forgotten.
+        checkpoints.record(BlockHeight::from_u32(105), hash(205));
+        assert_eq!(checkpoints.settled(), None);
+        checkpoints.record(BlockHeight::from_u32(115), hash(215));
+        assert_eq!(
+            checkpoints.settled(),
+            Some((BlockHeight::from_u32(105), hash(205)))
+        );
+    }
+
+    #[test]
+    fn candidates_must_be_on_the_same_chain() {
+        let tip = BlockHeight::from_u32(3_000_000);
+        let branch_id = u32::from(BranchId::for_height(&MAIN_NETWORK, tip));
+        let checkpoint = Some((BlockHeight::from_u32(2_999_000), hash(1)));
+        let view = |branch_id, checkpoint_hash| CandidateView {
+            tip,
+            branch_id,
+            checkpoint_hash,
+        };
+
+        assert_eq!(
+            view(branch_id, None).check(&MAIN_NETWORK, None, None),
+            Ok(())
+        );
+        assert_eq!(
+            view(branch_id, Some(hash(1))).check(&MAIN_NETWORK, checkpoint, None),
+            Ok(())
+        );
+
+        // A backend for another network, or on another chain, is rejected.
+        assert!(view(0, None).check(&MAIN_NETWORK, None, None).is_err());
+        assert!(
+            view(branch_id, Some(hash(2)))
+                .check(&MAIN_NETWORK, checkpoint, None)
+                .is_err()
+        );
+
+        // A backend that is behind the checkpoint cannot vouch for it.
+        let behind = CandidateView {
+            tip: BlockHeight::from_u32(2_998_999),
+            branch_id,
+            checkpoint_hash: None,
+        };
+        assert!(behind.check(&MAIN_NETWORK, checkpoint, None).is_err());
+
+        // Replacing a stalled backend requires a candidate that is further ahead.
+        assert!(
+            view(branch_id, None)
+                .check(&MAIN_NETWORK, None, Some(tip))
+                .is_err()
+        );
+        assert_eq!(
+            view(branch_id, None).check(&MAIN_NETWORK, None, Some(tip - 1)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn failover_order_and_backoff() {
+        assert_eq!(candidates(1, 0).collect::<Vec<_>>(), [0]);
+        assert_eq!(candidates(3, 0).collect::<Vec<_>>(), [1, 2, 0]);
+        assert_eq!(candidates(3, 1).collect::<Vec<_>>(), [2, 0, 1]);
+
+        assert_eq!(backoff(0), MIN_BACKOFF);
+        assert_eq!(backoff(1), MIN_BACKOFF * 2);
+        assert_eq!(backoff(2), MIN_BACKOFF * 4);
+        assert_eq!(backoff(10), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,358 @@
+//! Connecting to backends through a SOCKS5 proxy, such as Tor.
+//!
+//! Zaino connects to a backend by socket address, so for each backend Zallet listens on
+//! a local port, and relays every connection made to it through the proxy. The backend's
+//! hostname is sent to the proxy to resolve, so it is never looked up locally.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use jsonrpsee::tracing::{debug, info, warn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, copy_bidirectional};
+use tokio::net::{TcpListener, TcpStream};
+
+const SOCKS_VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const CONNECT: u8 = 1;
+const ADDRESS_IPV4: u8 = 1;
+const ADDRESS_DOMAIN: u8 = 3;
+const ADDRESS_IPV6: u8 = 4;
+
+/// How long the proxy may take to complete a handshake.
+///
+/// Tor may need to build a circuit before it can connect to a backend.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A SOCKS5 proxy that does not require authentication.
+#[derive(Debug)]
+pub(super) struct Proxy {
+    addr: String,
+}
+
+impl Proxy {
+    /// Checks that a SOCKS5 proxy is listening at `addr`.
+    pub(super) async fn check(addr: &str) -> io::Result<Arc<Self>> {
+        let proxy = Self {
+            addr: addr.to_string(),
+        };
+        proxy.greet().await?;
+        Ok(Arc::new(proxy))
+    }
+
+    /// Opens a connection to the proxy, and agrees on how to authenticate.
+    async fn greet(&self) -> io::Result<TcpStream> {
+        with_timeout(async {
+            let mut stream = TcpStream::connect(&self.addr).await?;
+            stream
+                .write_all(&[SOCKS_VERSION, 1, NO_AUTHENTICATION])
+                .await?;
+            let mut reply = [0; 2];
+            stream.read_exact(&mut reply).await?;
+            match reply {
+                [SOCKS_VERSION, NO_AUTHENTICATION] => Ok(stream),
+                [SOCKS_VERSION, _] => Err(io::Error::other("the proxy requires authentication")),
+                _ => Err(io::Error::other("it is not a SOCKS5 proxy")),
+            }
+        })
+        .await
+    }
+
+    /// Opens a connection to `target` (a `host:port` string) through the proxy.
+    pub(super) async fn connect(&self, target: &str) -> io::Result<TcpStream> {
+        let request = connect_request(target)?;
+        let mut stream = self.greet().await?;
+
+        with_timeout(async {
+            stream.write_all(&request).await?;
+            let mut reply = [0; 4];
+            stream.read_exact(&mut reply).await?;
+            if reply[0] != SOCKS_VERSION {
+                return Err(io::Error::other("it is not a SOCKS5 proxy"));
+            }
+            if reply[1] != 0 {
+                return Err(io::Error::other(format!(
+                    "the proxy could not connect to {target}: {}",
+                    reply_error(reply[1]),
+                )));
+            }
+
+            // Skip the address that the proxy bound to.
+            let len = match reply[3] {
+                ADDRESS_IPV4 => 4,
+                ADDRESS_IPV6 => 16,
+                ADDRESS_DOMAIN => usize::from(stream.read_u8().await?),
+                _ => return Err(io::Error::other("the proxy sent an invalid reply")),
+            };
+            stream.read_exact(&mut vec![0; len + 2]).await?;
+            Ok(())
+        })
+        .await?;
+
+        Ok(stream)
+    }
+
+    /// Starts relaying the connections made to a local port to `target` (a `host:port`
+    /// string) through the proxy, and returns the address of the local port.
+    pub(super) async fn relay(self: &Arc<Self>, target: &str) -> io::Result<SocketAddr> {
+        // Reject an invalid target now, rather than on every connection.
+        connect_request(target)?;
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let local_addr = listener.local_addr()?;
+        info!(
+            "Connecting to {target} through the SOCKS5 proxy at {}, relayed from {local_addr}",
+            self.addr,
+        );
+
+        let proxy = self.clone();
+        let target = target.to_string();
+        crate::spawn!("SOCKS5 relay", async move {
+            loop {
+                let mut inbound = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        debug!("Failed to accept relayed connection: {e}");
+                        continue;
+                    }
+                };
+                let proxy = proxy.clone();
+                let target = target.clone();
+                crate::spawn!("SOCKS5 relay connection", async move {
+                    match proxy.connect(&target).await {
+                        Ok(mut outbound) => {
+                            let _ = copy_bidirectional(&mut inbound, &mut outbound).await;
+                        }
+                        // The inbound connection is closed, so the request fails rather
+                        // than being made directly.
+                        Err(e) => warn!("Failed to connect to {target} through the proxy: {e}"),
+                    }
+                });
+            }
+        });
+
+        Ok(local_addr)
+    }
+}
+
+async fn with_timeout<T>(f: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, f)
+        .await
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "the proxy did not respond within {} seconds",
+                    HANDSHAKE_TIMEOUT.as_secs()
+                ),
+            )
+        })?
+}
+
+/// Splits a `host:port` string, removing the brackets around an IPv6 address.
+fn split_host_port(addr: &str) -> Option<(&str, u16)> {
+    let (host, port) = addr.rsplit_once(':')?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    let port = port.parse().ok()?;
+    (!host.is_empty()).then_some((host, port))
+}
+
+/// Returns the request asking the proxy to connect to `target`.
+///
+/// Hostnames are sent to the proxy as they are, for the proxy to resolve.
+fn connect_request(target: &str) -> io::Result<Vec<u8>> {
+    let (host, port) = split_host_port(target).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{target} is not a host and port"),
+        )
+    })?;
+
+    let mut request = vec![SOCKS_VERSION, CONNECT, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(ADDRESS_IPV4);
+            request.extend(ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(ADDRESS_IPV6);
+            request.extend(ip.octets());
+        }
+        Err(_) => {
+            let len = u8::try_from(host.len()).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{host} is too long to send to a SOCKS5 proxy"),
+                )
+            })?;
+            request.push(ADDRESS_DOMAIN);
+            request.push(len);
+            request.extend(host.as_bytes());
+        }
+    }
+    request.extend(port.to_be_bytes());
+
+    Ok(request)
+}
+
+fn reply_error(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::{Proxy, connect_request, split_host_port};
+
+    /// Starts a SOCKS5 proxy that records the connection requests it receives, and
+    /// answers each connection with an echo of what it is sent.
+    ///
+    /// Requests for port 1 are refused.
+    async fn fake_proxy() -> (String, Arc<Mutex<Vec<Vec<u8>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let recorded = recorded.clone();
+                tokio::spawn(async move {
+                    let mut greeting = [0; 3];
+                    stream.read_exact(&mut greeting).await.unwrap();
+                    assert_eq!(greeting, [5, 1, 0]);
+                    stream.write_all(&[5, 0]).await.unwrap();
+
+                    // Only the proxy's reachability was checked.
+                    let mut request = [0; 5];
+                    if stream.read_exact(&mut request).await.is_err() {
+                        return;
+                    }
+                    let mut request = request.to_vec();
+                    // The rest of the address (a domain name, or an IPv4 address), and
+                    // the port.
+                    let rest = if request[3] == 3 {
+                        usize::from(request[4]) + 2
+                    } else {
+                        5
+                    };
+                    let mut buf = vec![0; rest];
+                    stream.read_exact(&mut buf).await.unwrap();
+                    request.extend(buf);
+                    let refused = request.ends_with(&[0, 1]);
+                    recorded.lock().unwrap().push(request);
+
+                    let status = if refused { 5 } else { 0 };
+                    stream
+                        .write_all(&[5, status, 0, 1, 127, 0, 0, 1, 0, 80])
+                        .await
+                        .unwrap();
+                    if refused {
+                        return;
+                    }
+
+                    let (mut read, mut write) = stream.split();
+                    let _ = tokio::io::copy(&mut read, &mut write).await;
+                });
+            }
+        });
+
+        (addr, requests)
+    }
+
+    #[test]
+    fn requests() {
+        assert_eq!(
+            split_host_port("example.onion:8232"),
+            Some(("example.onion", 8232))
+        );
+        assert_eq!(split_host_port("[::1]:8232"), Some(("::1", 8232)));
+        assert_eq!(split_host_port("example.onion"), None);
+        assert_eq!(split_host_port(":8232"), None);
+
+        // Hostnames are resolved by the proxy.
+        assert_eq!(
+            connect_request("node.onion:8232").unwrap(),
+            [&[5, 1, 0, 3, 10][..], &b"node.onion"[..], &[0x20, 0x28][..]].concat(),
+        );
+        assert_eq!(
+            connect_request("10.0.0.1:8232").unwrap(),
+            [5, 1, 0, 1, 10, 0, 0, 1, 0x20, 0x28],
+        );
+        assert_eq!(connect_request("[::1]:80").unwrap()[3], 4);
+        assert!(connect_request(&format!("{}:80", "a".repeat(256))).is_err());
+    }
+
+    #[tokio::test]
+    async fn connections_are_relayed_through_the_proxy() {
+        let (addr, requests) = fake_proxy().await;
+        let proxy = Proxy::check(&addr).await.unwrap();
+
+        let local_addr = proxy.relay("node.onion:8232").await.unwrap();
+        assert!(local_addr.ip().is_loopback());
+        let mut stream = TcpStream::connect(local_addr).await.unwrap();
+        stream.write_all(b"getinfo").await.unwrap();
+        let mut echoed = [0; 7];
+        stream.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"getinfo");
+
+        // The hostname was sent to the proxy.
+        assert_eq!(
+            requests.lock().unwrap().as_slice(),
+            [[&[5, 1, 0, 3, 10][..], &b"node.onion"[..], &[0x20, 0x28][..]].concat()],
+        );
+
+        // Errors from the proxy are reported.
+        let err = proxy.connect("node.onion:1").await.unwrap_err();
+        assert!(err.to_string().contains("connection refused"), "{err}");
+
+        // A relayed connection that the proxy refuses is closed.
+        let local_addr = proxy.relay("node.onion:1").await.unwrap();
+        let mut stream = TcpStream::connect(local_addr).await.unwrap();
+        let mut buf = [0; 1];
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn unreachable_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        assert!(Proxy::check(&addr).await.is_err());
+
+        // Something that is not a SOCKS5 proxy is rejected.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 3];
+            stream.read_exact(&mut request).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n")
+                .await
+                .unwrap();
+            let _ = stream.read(&mut request).await;
+        });
+        let err = Proxy::check(&addr).await.unwrap_err();
+        assert_eq!(err.to_string(), "it is not a SOCKS5 proxy");
+    }
+}
@@ -1,4 +1,6 @@
 use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use abscissa_core::tracing::info;
 use rusqlite::{OptionalExtension, named_params};
@@ -17,14 +19,77 @@ use crate::{
 #[cfg(zallet_build = "wallet")]
 use super::keystore;
 
+mod account_index;
+pub(crate) use account_index::IndexedAccounts;
+
+mod address_reuse;
+
+mod as_of;
+pub(crate) use as_of::QueryHeight;
+
+mod balances;
+
+#[cfg(zallet_build = "wallet")]
+pub(crate) use balances::{account_balances, confirmed_balances};
+
+#[cfg(zallet_build = "wallet")]
+pub(crate) use as_of::{COINBASE_MATURITY, UnspentOutput, unspent_outputs};
+
+mod conflicts;
+pub(crate) use conflicts::wallet_conflicts;
+
 mod connection;
 pub(crate) use connection::DbConnection;
 
+#[cfg(zallet_build = "wallet")]
+pub(crate) use connection::{StoredOperation, StoredOperationError};
+
+#[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+pub(crate) use connection::{AddressLabelOutcome, AddressPurpose};
+
 mod ext;
+mod integrity;
+mod memos;
+mod next_address;
+mod note_selection;
+mod received_by_address;
+pub(crate) use received_by_address::ReceivedOutput;
+mod reorgs;
+pub(crate) use reorgs::ReferenceBlock;
+mod reservations;
+mod scan_queue;
+#[cfg(zallet_build = "wallet")]
+mod sprout;
+#[cfg(zallet_build = "wallet")]
+mod transparent_addresses;
+mod tx_effects;
+pub(crate) use tx_effects::TxEffects;
+mod tx_values;
+
+#[cfg(zallet_build = "wallet")]
+pub(crate) use memos::MemoMatch;
+
+#[cfg(zallet_build = "wallet")]
+pub(crate) use reservations::{Reservation, ReservedInput};
+
+#[cfg(all(test, zallet_build = "wallet"))]
+pub(crate) use reservations::InputReservations;
+
+#[cfg(zallet_build = "wallet")]
+pub(crate) use sprout::SproutBalance;
+
+#[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+pub(crate) use sprout::SproutNote;
+
+#[cfg(zallet_build = "wallet")]
+pub(crate) use tx_values::TxValues;
 
 #[cfg(test)]
 mod tests;
 
+#[cfg(test)]
+pub(crate) mod testing;
+
 pub(crate) type DbHandle = deadpool::managed::Object<connection::WalletManager>;
 
 /// Returns the full list of migrations defined in Zallet, to be applied alongside the
@@ -40,6 +105,49 @@ fn all_external_migrations(
     migrations.collect()
 }
 
+/// Checks that the wallet database matches the configured network type.
+fn check_network(handle: &DbHandle, config: &ZalletConfig) -> Result<(), Error> {
+    // We can assume the presence of this table, as it's added by the initial migrations.
+    handle.with_raw(|conn, _| {
+        let wallet_network_type = conn
+            .query_row(
+                "SELECT network_type FROM ext_zallet_db_wallet_metadata",
+                [],
+                |row| row.get::<_, crate::network::kind::Sql>("network_type"),
+            )
+            .map_err(|e| ErrorKind::Init.context(e))?;
+
+        if wallet_network_type.0 == config.consensus.network {
+            Ok(())
+        } else {
+            Err(ErrorKind::Init
+                .context(fl!(
+                    "err-init-config-db-mismatch",
+                    db_network_type = crate::network::kind::type_to_str(&wallet_network_type.0),
+                    config_network_type =
+                        crate::network::kind::type_to_str(&config.consensus.network),
+                ))
+                .into())
+        }
+    })
+}
+
+/// Returns an SQLite URI that opens the database at `path` in read-only mode.
+fn read_only_uri(path: &Path) -> PathBuf {
+    let mut uri = String::from("file:");
+    for c in path.to_string_lossy().chars() {
+        // These characters have special meanings in URIs.
+        match c {
+            '?' => uri.push_str("%3f"),
+            '#' => uri.push_str("%23"),
+            '%' => uri.push_str("%25"),
+            _ => uri.push(c),
+        }
+    }
+    uri.push_str("?mode=ro");
+    PathBuf::from(uri)
+}
+
 #[derive(Clone)]
 pub(crate) struct Database {
     db_data_pool: connection::WalletPool,
@@ -53,13 +161,25 @@ impl fmt::Debug for Database {
 
 impl Database {
     pub(crate) async fn open(config: &ZalletConfig) -> Result<Self, Error> {
+        // A config listing several wallets must first be narrowed to one of them.
+        if !config.database.wallets.is_empty() {
+            return Err(ErrorKind::Init
+                .context(fl!("err-init-wallet-not-selected"))
+                .into());
+        }
+
         let path = config.wallet_db_path();
 
         let db_exists = fs::try_exists(&path)
             .await
             .map_err(|e| ErrorKind::Init.context(e))?;
 
-        let db_data_pool = connection::pool(&path, config.consensus.network())?;
+        let db_data_pool = connection::pool(
+            &path,
+            config.consensus.network(),
+            config.builder.spend_zeroconf_change(),
+            config.builder.input_selection(),
+        )?;
 
         let database = Self { db_data_pool };
 
@@ -68,34 +188,23 @@ impl Database {
         if db_exists {
             // Verify that the database matches the configured network type before we make
             // any changes (including migrations, some of which make use of the network
-            // params), to avoid leaving the database in an inconsistent state. We can
-            // assume the presence of this table, as it's added by the initial migrations.
-            handle.with_raw(|conn, _| {
-                let wallet_network_type = conn
-                    .query_row(
-                        "SELECT network_type FROM ext_zallet_db_wallet_metadata",
-                        [],
-                        |row| row.get::<_, crate::network::kind::Sql>("network_type"),
-                    )
-                    .map_err(|e| ErrorKind::Init.context(e))?;
-
-                if wallet_network_type.0 == config.consensus.network {
-                    Ok(())
-                } else {
-                    Err(ErrorKind::Init.context(fl!(
-                        "err-init-config-db-mismatch",
-                        db_network_type = crate::network::kind::type_to_str(&wallet_network_type.0),
-                        config_network_type =
-                            crate::network::kind::type_to_str(&config.consensus.network),
-                    )))
-                }
-            })?;
+            // params), to avoid leaving the database in an inconsistent state.
+            check_network(&handle, config)?;
 
             info!("Applying latest database migrations");
         } else {
             info!("Creating empty database");
         }
 
+        // The triggers that maintain the balance aggregates are removed while migrations
+        // run, so that they cannot get in the way of changes to the tables they watch.
+        let schema_version = handle
+            .with_raw(|conn, _| {
+                balances::uninstall(conn)?;
+                balances::schema_version(conn)
+            })
+            .map_err(|e| ErrorKind::Init.context(e))?;
+
         // Initialize the database before we go any further.
         handle.with_mut(|mut db_data| {
             match WalletMigrator::new()
@@ -121,6 +230,22 @@ impl Database {
             Ok::<(), Error>(())
         })?;
 
+        // If the migrations changed the database, the aggregates may be out of date.
+        handle
+            .with_raw_mut(|conn, _| {
+                let migrated = balances::schema_version(conn)? != schema_version;
+                balances::install(conn, migrated)
+            })
+            .map_err(|e| ErrorKind::Init.context(e))?;
+
+        // Bring the memo index up to date, or discard it if it has been disabled.
+        if config.database.memo_index() {
+            handle.index_memos()
+        } else {
+            handle.clear_memo_index()
+        }
+        .map_err(|e| ErrorKind::Init.context(e))?;
+
         let now = ::time::OffsetDateTime::now_utc();
 
         // Record that we migrated the database using this Zallet version. We don't have
@@ -174,10 +299,64 @@ impl Database {
         Ok(database)
     }
 
-    pub(crate) async fn handle(&self) -> Result<DbHandle, Error> {
-        self.db_data_pool
-            .get()
+    /// Opens an existing wallet database without modifying it.
+    ///
+    /// Unlike [`Database::open`], this neither creates the database nor applies
+    /// migrations, and its connections cannot write to the database. Commands that only
+    /// read the wallet use this so that they don't need the datadir lock, and can run
+    /// while another Zallet process is using the wallet.
+    pub(crate) async fn open_read_only(config: &ZalletConfig) -> Result<Self, Error> {
+        // A config listing several wallets must first be narrowed to one of them.
+        if !config.database.wallets.is_empty() {
+            return Err(ErrorKind::Init
+                .context(fl!("err-init-wallet-not-selected"))
+                .into());
+        }
+
+        let path = config.wallet_db_path();
+        if !fs::try_exists(&path)
             .await
-            .map_err(|e| ErrorKind::Generic.context(e).into())
+            .map_err(|e| ErrorKind::Init.context(e))?
+        {
+            return Err(ErrorKind::Init
+                .context(fl!(
+                    "err-init-wallet-db-not-found",
+                    path = path.display().to_string(),
+                ))
+                .into());
+        }
+
+        let db_data_pool = connection::pool(
+            read_only_uri(&path),
+            config.consensus.network(),
+            config.builder.spend_zeroconf_change(),
+            config.builder.input_selection(),
+        )?;
+
+        let database = Self { db_data_pool };
+        check_network(&database.handle().await?, config)?;
+
+        Ok(database)
+    }
+
+    pub(crate) async fn handle(&self) -> Result<DbHandle, Error> {
+        let start = Instant::now();
+        let handle = self.db_data_pool.get().await;
+        metrics::histogram!("zallet.database.handle_wait_seconds")
+            .record(start.elapsed().as_secs_f64());
+        handle.map_err(|e| ErrorKind::Generic.context(e).into())
+    }
+
+    /// Closes the database.
+    ///
+    /// Idle connections are closed immediately, and connections that are in use are
+    /// closed when they are released. No new connections can be obtained afterwards.
+    pub(crate) fn close(&self) {
+        self.db_data_pool.close();
+    }
+
+    /// Returns `true` if the database has not been closed.
+    pub(crate) fn is_open(&self) -> bool {
+        !self.db_data_pool.is_closed()
     }
 }
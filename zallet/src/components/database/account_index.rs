@@ -0,0 +1,214 @@
+//! A cache of the metadata used to resolve the wallet's accounts.
+//!
+//! JSON-RPC methods can identify accounts by name, or (in legacy methods) by ZIP 32
+//! account index, both of which require knowing the metadata of every account in the
+//! wallet. Rather than loading each account on every request, the metadata is indexed
+//! once and shared by every connection to the wallet database. The index is rebuilt on
+//! next use after an account is created, imported, or deleted.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use zcash_client_sqlite::AccountUuid;
+
+#[cfg(zallet_build = "wallet")]
+use zip32::fingerprint::SeedFingerprint;
+
+/// The wallet's accounts, indexed by the metadata that JSON-RPC methods use to identify
+/// them.
+#[derive(Debug, Default)]
+pub(crate) struct IndexedAccounts {
+    /// Accounts that are derived from a seed, by seed fingerprint and ZIP 32 account
+    /// index.
+    #[cfg(zallet_build = "wallet")]
+    by_seed: HashMap<SeedFingerprint, HashMap<u32, AccountUuid>>,
+    /// Accounts that have a name. Names are not required to be unique.
+    by_name: HashMap<String, Vec<AccountUuid>>,
+}
+
+impl IndexedAccounts {
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn insert_derived(
+        &mut self,
+        seed_fp: SeedFingerprint,
+        account_index: zip32::AccountId,
+        account_uuid: AccountUuid,
+    ) {
+        self.by_seed
+            .entry(seed_fp)
+            .or_default()
+            .insert(account_index.into(), account_uuid);
+    }
+
+    pub(crate) fn insert_named(&mut self, name: &str, account_uuid: AccountUuid) {
+        self.by_name
+            .entry(name.into())
+            .or_default()
+            .push(account_uuid);
+    }
+
+    /// Returns the fingerprints of the seeds that accounts are derived from.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn seeds(&self) -> impl Iterator<Item = &SeedFingerprint> {
+        self.by_seed.keys()
+    }
+
+    /// Returns the account derived from the given seed at the given ZIP 32 account index.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn derived(
+        &self,
+        seed_fp: &SeedFingerprint,
+        account_index: u32,
+    ) -> Option<AccountUuid> {
+        self.by_seed.get(seed_fp)?.get(&account_index).copied()
+    }
+
+    /// Returns the accounts with exactly the given name.
+    pub(crate) fn named(&self, name: &str) -> &[AccountUuid] {
+        self.by_name.get(name).map_or(&[][..], Vec::as_slice)
+    }
+}
+
+/// The cached [`IndexedAccounts`], shared by every connection to the wallet database.
+#[derive(Clone, Default)]
+pub(crate) struct AccountIndex {
+    cached: Arc<Mutex<Option<Arc<IndexedAccounts>>>>,
+}
+
+impl AccountIndex {
+    /// Returns the cached index, calling `build` to populate the cache if it is empty.
+    ///
+    /// The cache is locked while `build` runs, so that an invalidation that races with it
+    /// is applied after the stale index has been stored. `build` must therefore not wait
+    /// on a connection that may be invalidating the cache.
+    pub(crate) fn get_or_build<E>(
+        &self,
+        build: impl FnOnce() -> Result<IndexedAccounts, E>,
+    ) -> Result<Arc<IndexedAccounts>, E> {
+        let mut cached = self.cached.lock().expect("not poisoned");
+        match cached.as_ref() {
+            Some(accounts) => Ok(accounts.clone()),
+            None => {
+                let accounts = Arc::new(build()?);
+                *cached = Some(accounts.clone());
+                Ok(accounts)
+            }
+        }
+    }
+
+    /// Discards the cached index, so it is rebuilt on next use.
+    pub(crate) fn invalidate(&self) {
+        *self.cached.lock().expect("not poisoned") = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use uuid::Uuid;
+    use zcash_client_sqlite::AccountUuid;
+
+    use super::{AccountIndex, IndexedAccounts};
+
+    #[cfg(zallet_build = "wallet")]
+    use zip32::fingerprint::SeedFingerprint;
+
+    fn account(i: u32) -> AccountUuid {
+        AccountUuid::from_uuid(Uuid::from_u128(i.into()))
+    }
+
+    fn indexed_accounts(count: u32) -> IndexedAccounts {
+        let mut accounts = IndexedAccounts::default();
+        for i in 0..count {
+            #[cfg(zallet_build = "wallet")]
+            accounts.insert_derived(
+                SeedFingerprint::from_bytes([1; 32]),
+                zip32::AccountId::try_from(i).unwrap(),
+                account(i),
+            );
+            accounts.insert_named(&format!("Account {i}"), account(i));
+        }
+        accounts
+    }
+
+    #[test]
+    fn lookups_do_not_scan_accounts() {
+        for count in [1, 10, 1000] {
+            let index = AccountIndex::default();
+            let mut builds = 0;
+
+            // The accounts are only scanned on the first lookup, however many there are.
+            for i in 0..count {
+                let accounts = index
+                    .get_or_build(|| {
+                        builds += 1;
+                        Ok::<_, Infallible>(indexed_accounts(count))
+                    })
+                    .unwrap();
+                #[cfg(zallet_build = "wallet")]
+                assert_eq!(
+                    accounts.derived(&SeedFingerprint::from_bytes([1; 32]), i),
+                    Some(account(i)),
+                );
+                assert_eq!(accounts.named(&format!("Account {i}")), [account(i)]);
+            }
+            assert_eq!(builds, 1);
+
+            // The accounts are scanned again after the index is invalidated.
+            index.invalidate();
+            let accounts = index
+                .get_or_build(|| {
+                    builds += 1;
+                    Ok::<_, Infallible>(indexed_accounts(count + 1))
+                })
+                .unwrap();
+            assert_eq!(builds, 2);
+            assert_eq!(
+                accounts.named(&format!("Account {count}")),
+                [account(count)]
+            );
+            #[cfg(zallet_build = "wallet")]
+            assert_eq!(accounts.seeds().count(), 1);
+        }
+    }
+
+    #[cfg(zallet_build = "wallet")]
+    #[test]
+    fn second_seed() {
+        let index = AccountIndex::default();
+        let build = |seeds: &[u8]| {
+            let mut accounts = IndexedAccounts::default();
+            for (i, seed) in seeds.iter().enumerate() {
+                accounts.insert_derived(
+                    SeedFingerprint::from_bytes([*seed; 32]),
+                    zip32::AccountId::ZERO,
+                    account(i as u32),
+                );
+            }
+            Ok::<_, Infallible>(accounts)
+        };
+
+        let accounts = index.get_or_build(|| build(&[1])).unwrap();
+        assert_eq!(accounts.seeds().count(), 1);
+
+        // An account derived from a second seed is seen once the index is invalidated,
+        // which makes account numbers ambiguous.
+        index.invalidate();
+        let accounts = index.get_or_build(|| build(&[1, 2])).unwrap();
+        assert_eq!(accounts.seeds().count(), 2);
+    }
+
+    #[test]
+    fn names_are_exact() {
+        let mut accounts = IndexedAccounts::default();
+        accounts.insert_named("Savings", account(0));
+        accounts.insert_named("Shared", account(1));
+        accounts.insert_named("Shared", account(2));
+
+        assert_eq!(accounts.named("Savings"), [account(0)]);
+        assert!(accounts.named("savings").is_empty());
+        assert!(accounts.named("Savings ").is_empty());
+        assert_eq!(accounts.named("Shared"), [account(1), account(2)]);
+    }
+}
@@ -0,0 +1,86 @@
+//! Detection of shielded address reuse.
+//!
+//! Zallet can derive a fresh diversified address for every counterparty, and addresses
+//! derived from the same key cannot be linked to each other. If the same address is given
+//! to several counterparties instead, they can tell that they paid the same recipient.
+//!
+//! An address is counted as reused once it has received payments in more than one
+//! transaction from outside the wallet. Change, and any transaction that spends the
+//! wallet's own funds (such as a transfer between the wallet's accounts), does not count
+//! towards an address's reuse.
+
+#[cfg(zallet_build = "wallet")]
+use std::collections::HashMap;
+
+use rusqlite::named_params;
+use zcash_protocol::TxId;
+
+/// The `(address_id, tx)` pairs of the payments that addresses received from outside the
+/// wallet.
+const EXTERNAL_PAYMENTS: &str = "
+    SELECT n.address_id, n.tx
+    FROM (
+        SELECT address_id, tx FROM sapling_received_notes WHERE is_change = 0
+        UNION
+        SELECT address_id, tx FROM orchard_received_notes WHERE is_change = 0
+    ) n
+    WHERE n.address_id IS NOT NULL
+    AND n.tx NOT IN (
+        SELECT transaction_id FROM sapling_received_note_spends
+        UNION
+        SELECT transaction_id FROM orchard_received_note_spends
+        UNION
+        SELECT transaction_id FROM transparent_received_output_spends
+    )";
+
+/// A shielded address that has been paid by more than one transaction from outside the
+/// wallet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ReusedAddress {
+    /// The encoded address.
+    pub(crate) address: String,
+    /// The number of distinct transactions from outside the wallet that paid it.
+    pub(crate) payments: u32,
+}
+
+/// Returns every reused address in the wallet, keyed by its encoding, along with the
+/// number of transactions that paid it.
+#[cfg(zallet_build = "wallet")]
+pub(super) fn all(conn: &rusqlite::Connection) -> Result<HashMap<String, u32>, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT addresses.address, COUNT(*)
+        FROM ({EXTERNAL_PAYMENTS}) p
+        JOIN addresses ON addresses.id = p.address_id
+        GROUP BY addresses.id
+        HAVING COUNT(*) > 1"
+    ))?;
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+/// Returns the reused addresses that the given transaction paid.
+pub(super) fn paid_by(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+) -> Result<Vec<ReusedAddress>, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT addresses.address, COUNT(*)
+        FROM ({EXTERNAL_PAYMENTS}) p
+        JOIN addresses ON addresses.id = p.address_id
+        WHERE p.address_id IN (
+            SELECT q.address_id
+            FROM ({EXTERNAL_PAYMENTS}) q
+            JOIN transactions t ON t.id_tx = q.tx
+            WHERE t.txid = :txid
+        )
+        GROUP BY addresses.id
+        HAVING COUNT(*) > 1"
+    ))?;
+    stmt.query_map(named_params! { ":txid": txid.as_ref() }, |row| {
+        Ok(ReusedAddress {
+            address: row.get(0)?,
+            payments: row.get(1)?,
+        })
+    })?
+    .collect()
+}
@@ -0,0 +1,204 @@
+//! Queries of the wallet's state at a point in its history.
+//!
+//! Several JSON-RPC methods take an `asOfHeight` parameter, which asks what the wallet
+//! held when the chain was at a given height. Every such query follows the same rules:
+//! - Only transactions mined at or below the height are taken into account.
+//! - Spends mined above the height are ignored, so the outputs they spend are treated as
+//!   unspent.
+//! - Unmined transactions are excluded entirely.
+//!
+//! Queries of the wallet's current state additionally take unmined transactions into
+//! account, as `zcashd` did.
+
+use zcash_protocol::consensus::BlockHeight;
+
+#[cfg(zallet_build = "wallet")]
+use {
+    rusqlite::named_params,
+    uuid::Uuid,
+    zcash_protocol::{PoolType, ShieldedProtocol, TxId, value::Zatoshis},
+};
+
+/// The point in the wallet's history that a query is evaluated at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum QueryHeight {
+    /// The wallet's current state at the given chain tip, including the effects of
+    /// unexpired unmined transactions.
+    Tip(BlockHeight),
+    /// The wallet's state when the chain was at the given height.
+    AsOf(BlockHeight),
+}
+
+impl QueryHeight {
+    /// Returns the chain height that the query is evaluated at.
+    pub(crate) fn height(self) -> BlockHeight {
+        match self {
+            QueryHeight::Tip(height) | QueryHeight::AsOf(height) => height,
+        }
+    }
+}
+
+/// The number of confirmations a coinbase output needs before it can be spent.
+#[cfg(zallet_build = "wallet")]
+pub(crate) const COINBASE_MATURITY: u32 = 100;
+
+/// An output received by the wallet that was unspent at a [`QueryHeight`].
+#[cfg(zallet_build = "wallet")]
+#[derive(Clone, Debug)]
+pub(crate) struct UnspentOutput {
+    pub(crate) account_uuid: Uuid,
+    pub(crate) txid: TxId,
+    pub(crate) output_index: u32,
+    pub(crate) value: Zatoshis,
+    /// The height at which the output was mined, or `None` if it is unmined.
+    pub(crate) mined_height: Option<BlockHeight>,
+    /// The index of the output's transaction within its block, if known.
+    pub(crate) tx_index: Option<u32>,
+    /// The wallet's row ID for the output's transaction.
+    pub(crate) tx_row: i64,
+    /// For transparent outputs, the address and script that received the output.
+    pub(crate) transparent: Option<(String, Vec<u8>)>,
+}
+
+#[cfg(zallet_build = "wallet")]
+impl UnspentOutput {
+    /// Returns the number of confirmations the output has at `height`.
+    pub(crate) fn confirmations(&self, height: BlockHeight) -> u32 {
+        self.mined_height.map_or(0, |mined| {
+            (u32::from(height) + 1).saturating_sub(u32::from(mined))
+        })
+    }
+
+    /// Returns `true` if the output was received in a coinbase transaction.
+    ///
+    /// The coinbase transaction is always the first transaction in its block.
+    pub(crate) fn is_coinbase(&self) -> bool {
+        self.mined_height.is_some() && self.tx_index == Some(0)
+    }
+
+    /// Returns `true` if the output is a coinbase output that cannot yet be spent at
+    /// `height`.
+    pub(crate) fn is_immature(&self, height: BlockHeight) -> bool {
+        self.is_coinbase() && self.confirmations(height) < COINBASE_MATURITY
+    }
+}
+
+/// Returns the wallet's outputs in the given pool that were unspent at `at`.
+///
+/// An output is returned if it was received in a block with at least `minconf`
+/// confirmations at `at`, and it was not spent in a block at or below `at`. When `at` is
+/// the chain tip, outputs spent by unexpired unmined transactions are also excluded, and
+/// outputs received by them are included if `minconf` is 0.
+///
+/// If `account_uuid` is set, only outputs received by that account are returned.
+#[cfg(zallet_build = "wallet")]
+pub(crate) fn unspent_outputs(
+    conn: &rusqlite::Connection,
+    pool: PoolType,
+    at: QueryHeight,
+    minconf: u32,
+    account_uuid: Option<Uuid>,
+) -> Result<Vec<UnspentOutput>, rusqlite::Error> {
+    let (table, tx_col, index_col, value_col, transparent_cols) = match pool {
+        PoolType::Transparent => (
+            "transparent_received_outputs",
+            "transaction_id",
+            "output_index",
+            "value_zat",
+            "o.address, o.script",
+        ),
+        PoolType::Shielded(ShieldedProtocol::Sapling) => (
+            "sapling_received_notes",
+            "tx",
+            "output_index",
+            "value",
+            "NULL, NULL",
+        ),
+        PoolType::Shielded(ShieldedProtocol::Orchard) => (
+            "orchard_received_notes",
+            "tx",
+            "action_index",
+            "value",
+            "NULL, NULL",
+        ),
+    };
+    let (spends_table, spends_col) = match pool {
+        PoolType::Transparent => (
+            "transparent_received_output_spends",
+            "transparent_received_output_id",
+        ),
+        PoolType::Shielded(ShieldedProtocol::Sapling) => {
+            ("sapling_received_note_spends", "sapling_received_note_id")
+        }
+        PoolType::Shielded(ShieldedProtocol::Orchard) => {
+            ("orchard_received_note_spends", "orchard_received_note_id")
+        }
+    };
+
+    let height = at.height();
+    let at_tip = matches!(at, QueryHeight::Tip(_));
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT a.uuid, t.txid, o.{index_col}, o.{value_col}, t.mined_height, {transparent_cols},
+            t.tx_index, t.id_tx
+        FROM {table} o
+        JOIN transactions t ON t.id_tx = o.{tx_col}
+        JOIN accounts a ON a.id = o.account_id
+        WHERE o.{value_col} > 0
+        AND (:account_uuid IS NULL OR a.uuid = :account_uuid)
+        AND (
+            t.mined_height <= :max_mined_height
+            OR (
+                :include_unmined
+                AND t.mined_height IS NULL
+                AND (t.expiry_height = 0 OR t.expiry_height > :height)
+            )
+        )
+        AND NOT EXISTS (
+            SELECT 1
+            FROM {spends_table} s
+            JOIN transactions st ON st.id_tx = s.transaction_id
+            WHERE s.{spends_col} = o.id
+            AND (
+                st.mined_height <= :height
+                OR (
+                    :at_tip
+                    AND st.mined_height IS NULL
+                    AND (st.expiry_height = 0 OR st.expiry_height > :height)
+                )
+            )
+        )
+        ORDER BY t.mined_height, t.id_tx, o.{index_col}"
+    ))?;
+
+    stmt.query_and_then(
+        named_params! {
+            ":account_uuid": account_uuid,
+            // An output received at height `h` has `height - h + 1` confirmations.
+            ":max_mined_height": (u32::from(height) + 1).saturating_sub(minconf),
+            ":include_unmined": at_tip && minconf == 0,
+            ":height": u32::from(height),
+            ":at_tip": at_tip,
+        },
+        |row| {
+            let value = row.get::<_, i64>(3)?;
+            let transparent = match row.get::<_, Option<String>>(5)? {
+                Some(address) => Some((address, row.get(6)?)),
+                None => None,
+            };
+
+            Ok(UnspentOutput {
+                account_uuid: row.get(0)?,
+                txid: TxId::from_bytes(row.get(1)?),
+                output_index: row.get(2)?,
+                value: Zatoshis::from_nonnegative_i64(value)
+                    .map_err(|_| rusqlite::Error::IntegralValueOutOfRange(3, value))?,
+                mined_height: row.get::<_, Option<u32>>(4)?.map(BlockHeight::from),
+                tx_index: row.get(7)?,
+                tx_row: row.get(8)?,
+                transparent,
+            })
+        },
+    )?
+    .collect()
+}
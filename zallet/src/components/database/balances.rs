@@ -0,0 +1,745 @@
+//! Incrementally maintained balance aggregates.
+//!
+//! Summing every unspent output each time a balance is requested gets slow for large
+//! wallets. Instead, the wallet's unspent outputs are grouped into buckets that share
+//! everything a balance query depends on (account, pool, mined height, expiry heights,
+//! and whether the output is from a coinbase transaction), and each bucket's total value
+//! is kept in `ext_zallet_db_balance_aggregates`. A balance query then only needs to
+//! classify each bucket against the chain tip and the requested number of confirmations.
+//!
+//! The buckets are maintained by SQLite triggers on the tables that `zcash_client_sqlite`
+//! writes received outputs, spends, and transactions to, so they are updated in the same
+//! database transactions as the wallet state they summarize. Each trigger refreshes the
+//! outputs affected by the change: the bucket each output was last counted in is recorded
+//! in `ext_zallet_db_balance_entries`, so the output is removed from that bucket and then
+//! added to the bucket it now belongs in (if it is still unspent).
+//!
+//! Triggers are used instead of updating the buckets when Zallet commits scanned blocks,
+//! because scanning is only one of the ways the wallet's outputs change. Transactions
+//! fetched from the mempool or created by the wallet, transparent UTXOs found by address
+//! queries, and rewinds on reorgs all write to the same tables from inside
+//! `zcash_client_sqlite`, where Zallet has no hook. A trigger sees every one of these
+//! changes, including any added by future versions of that crate, and is rolled back
+//! along with the change if its database transaction fails.
+//!
+//! The triggers are removed while database migrations run, so that they cannot interfere
+//! with changes to the tables they are attached to, and are reinstalled afterwards. If the
+//! migrations changed the database, the aggregates are rebuilt from scratch.
+//!
+//! Within the tables, heights use sentinel values instead of `NULL` so that buckets can
+//! be matched by their primary key:
+//! - `mined_height` is -1 for unmined outputs.
+//! - `expiry_height` is the expiry height of an unmined output's transaction (0 if it
+//!   never expires), -1 if the expiry height is unknown, and 0 for mined outputs.
+//! - `spend_expiry_height` is -1 if the output is not spent by any unmined transaction,
+//!   0 if it is spent by one that never expires, and otherwise the height after which
+//!   every unmined transaction spending it has expired. Outputs spent by a mined
+//!   transaction are not counted at all.
+
+use std::collections::{BTreeMap, HashMap};
+
+use uuid::Uuid;
+use zcash_protocol::PoolType;
+
+use crate::fl;
+
+#[cfg(zallet_build = "wallet")]
+use {
+    super::as_of::{COINBASE_MATURITY, QueryHeight, unspent_outputs},
+    abscissa_core::tracing::warn,
+    zcash_protocol::{consensus::BlockHeight, value::Zatoshis},
+};
+
+/// The `zcash_client_sqlite` tables that hold a pool's received outputs and their spends.
+struct PoolTables {
+    code: i64,
+    name: &'static str,
+    outputs: &'static str,
+    tx_col: &'static str,
+    value_col: &'static str,
+    spends: &'static str,
+    spends_col: &'static str,
+}
+
+const POOLS: [PoolTables; 3] = [
+    PoolTables {
+        code: 0,
+        name: "transparent",
+        outputs: "transparent_received_outputs",
+        tx_col: "transaction_id",
+        value_col: "value_zat",
+        spends: "transparent_received_output_spends",
+        spends_col: "transparent_received_output_id",
+    },
+    PoolTables {
+        code: 2,
+        name: "sapling",
+        outputs: "sapling_received_notes",
+        tx_col: "tx",
+        value_col: "value",
+        spends: "sapling_received_note_spends",
+        spends_col: "sapling_received_note_id",
+    },
+    PoolTables {
+        code: 3,
+        name: "orchard",
+        outputs: "orchard_received_notes",
+        tx_col: "tx",
+        value_col: "value",
+        spends: "orchard_received_note_spends",
+        spends_col: "orchard_received_note_id",
+    },
+];
+
+const BUCKET_COLS: &str =
+    "account_id, pool, mined_height, expiry_height, spend_expiry_height, is_coinbase";
+
+/// Returns the pool with the given code, as used by `zcash_client_sqlite`.
+pub(super) fn pool_type(code: i64) -> Option<PoolType> {
+    match code {
+        0 => Some(PoolType::Transparent),
+        2 => Some(PoolType::SAPLING),
+        3 => Some(PoolType::ORCHARD),
+        _ => None,
+    }
+}
+
+impl PoolTables {
+    /// Returns a query for the bucket that each unspent output matching `filter` belongs
+    /// in, with the columns of `ext_zallet_db_balance_entries`.
+    fn entries(&self, filter: &str) -> String {
+        let PoolTables {
+            code,
+            outputs,
+            tx_col,
+            value_col,
+            spends,
+            spends_col,
+            ..
+        } = self;
+
+        format!(
+            "SELECT {code} AS pool, o.id AS output_id, o.account_id AS account_id,
+                o.{value_col} AS value,
+                IFNULL(t.mined_height, -1) AS mined_height,
+                CASE
+                    WHEN t.mined_height IS NULL THEN IFNULL(t.expiry_height, -1)
+                    ELSE 0
+                END AS expiry_height,
+                IFNULL(
+                    (
+                        SELECT CASE
+                            WHEN MIN(st.expiry_height) = 0 THEN 0
+                            ELSE MAX(st.expiry_height)
+                        END
+                        FROM {spends} s
+                        JOIN transactions st ON st.id_tx = s.transaction_id
+                        WHERE s.{spends_col} = o.id
+                        AND st.mined_height IS NULL
+                    ),
+                    -1
+                ) AS spend_expiry_height,
+                IFNULL(t.mined_height IS NOT NULL AND t.tx_index = 0, 0) AS is_coinbase
+            FROM {outputs} o
+            JOIN transactions t ON t.id_tx = o.{tx_col}
+            WHERE ({filter})
+            AND NOT EXISTS (
+                SELECT 1
+                FROM {spends} s
+                JOIN transactions st ON st.id_tx = s.transaction_id
+                WHERE s.{spends_col} = o.id
+                AND st.mined_height IS NOT NULL
+            )"
+        )
+    }
+
+    /// Returns a query for the number and total value of the pool's unspent outputs, as
+    /// they would be counted in the balance aggregates.
+    #[cfg(zallet_build = "wallet")]
+    fn unspent_totals(&self) -> String {
+        let PoolTables {
+            outputs,
+            value_col,
+            spends,
+            spends_col,
+            ..
+        } = self;
+
+        format!(
+            "SELECT COUNT(*), IFNULL(SUM(o.{value_col}), 0)
+            FROM {outputs} o
+            WHERE NOT EXISTS (
+                SELECT 1
+                FROM {spends} s
+                JOIN transactions st ON st.id_tx = s.transaction_id
+                WHERE s.{spends_col} = o.id
+                AND st.mined_height IS NOT NULL
+            )"
+        )
+    }
+
+    /// Returns the statements that move the outputs with the IDs returned by `affected`
+    /// into the buckets they currently belong in.
+    fn refresh(&self, affected: &str) -> String {
+        let code = self.code;
+        let entries = self.entries(&format!("o.id IN ({affected})"));
+
+        format!(
+            "INSERT INTO ext_zallet_db_balance_aggregates ({BUCKET_COLS}, value, output_count)
+            SELECT {BUCKET_COLS}, -value, -1
+            FROM ext_zallet_db_balance_entries
+            WHERE pool = {code} AND output_id IN ({affected})
+            ON CONFLICT ({BUCKET_COLS}) DO UPDATE SET
+                value = value + excluded.value,
+                output_count = output_count + excluded.output_count;
+            DELETE FROM ext_zallet_db_balance_entries
+            WHERE pool = {code} AND output_id IN ({affected});
+            INSERT INTO ext_zallet_db_balance_entries {entries};
+            INSERT INTO ext_zallet_db_balance_aggregates ({BUCKET_COLS}, value, output_count)
+            SELECT {BUCKET_COLS}, value, 1
+            FROM ext_zallet_db_balance_entries
+            WHERE pool = {code} AND output_id IN ({affected})
+            ON CONFLICT ({BUCKET_COLS}) DO UPDATE SET
+                value = value + excluded.value,
+                output_count = output_count + excluded.output_count;
+            DELETE FROM ext_zallet_db_balance_aggregates WHERE output_count = 0;"
+        )
+    }
+
+    /// Returns the names and definitions of the triggers that maintain this pool's
+    /// buckets.
+    fn triggers(&self) -> Vec<(String, String)> {
+        let PoolTables {
+            name,
+            outputs,
+            tx_col,
+            value_col,
+            spends,
+            spends_col,
+            ..
+        } = self;
+        let affected_by_tx = |tx| {
+            format!(
+                "SELECT id FROM {outputs} WHERE {tx_col} = {tx}
+                UNION SELECT {spends_col} FROM {spends} WHERE transaction_id = {tx}"
+            )
+        };
+
+        [
+            (
+                "output_insert",
+                format!("AFTER INSERT ON {outputs}"),
+                "SELECT NEW.id".to_owned(),
+            ),
+            (
+                "output_update",
+                format!("AFTER UPDATE OF id, account_id, {value_col}, {tx_col} ON {outputs}"),
+                "SELECT OLD.id UNION SELECT NEW.id".to_owned(),
+            ),
+            (
+                "output_delete",
+                format!("AFTER DELETE ON {outputs}"),
+                "SELECT OLD.id".to_owned(),
+            ),
+            (
+                "spend_insert",
+                format!("AFTER INSERT ON {spends}"),
+                format!("SELECT NEW.{spends_col}"),
+            ),
+            (
+                "spend_update",
+                format!("AFTER UPDATE ON {spends}"),
+                format!("SELECT OLD.{spends_col} UNION SELECT NEW.{spends_col}"),
+            ),
+            (
+                "spend_delete",
+                format!("AFTER DELETE ON {spends}"),
+                format!("SELECT OLD.{spends_col}"),
+            ),
+            (
+                "tx_update",
+                "AFTER UPDATE OF mined_height, expiry_height, tx_index ON transactions".to_owned(),
+                affected_by_tx("NEW.id_tx"),
+            ),
+            (
+                "tx_delete",
+                "AFTER DELETE ON transactions".to_owned(),
+                affected_by_tx("OLD.id_tx"),
+            ),
+        ]
+        .into_iter()
+        .map(|(event, on, affected)| {
+            let trigger = format!("ext_zallet_balances_{name}_{event}");
+            let sql = format!(
+                "CREATE TRIGGER IF NOT EXISTS {trigger} {on}
+                BEGIN
+                    {}
+                END;",
+                self.refresh(&affected)
+            );
+            (trigger, sql)
+        })
+        .collect()
+    }
+}
+
+fn all_triggers() -> impl Iterator<Item = (String, String)> {
+    POOLS.iter().flat_map(|pool| pool.triggers())
+}
+
+/// Returns the database's schema version, which changes whenever its schema does.
+pub(super) fn schema_version(conn: &rusqlite::Connection) -> Result<i64, rusqlite::Error> {
+    conn.query_row("PRAGMA schema_version", [], |row| row.get(0))
+}
+
+/// Removes the triggers that maintain the balance aggregates.
+pub(super) fn uninstall(conn: &rusqlite::Connection) -> Result<(), rusqlite::Error> {
+    for (trigger, _) in all_triggers() {
+        conn.execute_batch(&format!("DROP TRIGGER IF EXISTS {trigger};"))?;
+    }
+    Ok(())
+}
+
+/// Installs any missing triggers that maintain the balance aggregates, and if
+/// `recompute` is set, recomputes the aggregates from the wallet's outputs.
+pub(super) fn install(
+    conn: &mut rusqlite::Connection,
+    recompute: bool,
+) -> Result<(), rusqlite::Error> {
+    let transaction = conn.transaction()?;
+    for (_, sql) in all_triggers() {
+        transaction.execute_batch(&sql)?;
+    }
+    if recompute {
+        rebuild(&transaction)?;
+    }
+    transaction.commit()
+}
+
+/// Recomputes the balance aggregates from the wallet's outputs.
+fn rebuild(conn: &rusqlite::Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "DELETE FROM ext_zallet_db_balance_entries;
+        DELETE FROM ext_zallet_db_balance_aggregates;",
+    )?;
+    for pool in &POOLS {
+        conn.execute(
+            &format!(
+                "INSERT INTO ext_zallet_db_balance_entries {}",
+                pool.entries("1")
+            ),
+            [],
+        )?;
+    }
+    conn.execute(
+        &format!(
+            "INSERT INTO ext_zallet_db_balance_aggregates ({BUCKET_COLS}, value, output_count)
+            SELECT {BUCKET_COLS}, SUM(value), COUNT(*)
+            FROM ext_zallet_db_balance_entries
+            GROUP BY {BUCKET_COLS}"
+        ),
+        [],
+    )?;
+    Ok(())
+}
+
+/// A difference between the stored balance aggregates for an account's outputs in a
+/// pool and the aggregates recomputed from the wallet's outputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Discrepancy {
+    pub(crate) account_uuid: Uuid,
+    pub(crate) pool: PoolType,
+    /// The number of outputs and their total value in the stored aggregates.
+    pub(crate) stored: (i64, i64),
+    /// The number of outputs and their total value in the recomputed aggregates.
+    pub(crate) expected: (i64, i64),
+}
+
+/// The result of checking the balance aggregates.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CheckResult {
+    /// The triggers that maintain the aggregates which are not installed.
+    pub(crate) missing_triggers: Vec<String>,
+    /// The accounts and pools for which the stored aggregates are wrong.
+    pub(crate) discrepancies: Vec<Discrepancy>,
+}
+
+impl CheckResult {
+    pub(crate) fn is_consistent(&self) -> bool {
+        self.missing_triggers.is_empty() && self.discrepancies.is_empty()
+    }
+
+    /// Returns a description of each problem that was found.
+    pub(crate) fn problems(&self) -> Vec<String> {
+        self.missing_triggers
+            .iter()
+            .map(|trigger| fl!("check-balances-missing-trigger", trigger = trigger.as_str()))
+            .chain(self.discrepancies.iter().map(|discrepancy| {
+                fl!(
+                    "check-balances-discrepancy",
+                    account = discrepancy.account_uuid.to_string(),
+                    pool = discrepancy.pool.to_string(),
+                    expected_count = discrepancy.expected.0,
+                    expected_value = discrepancy.expected.1,
+                    stored_count = discrepancy.stored.0,
+                    stored_value = discrepancy.stored.1,
+                )
+            }))
+            .collect()
+    }
+}
+
+type Buckets = BTreeMap<(i64, i64, i64, i64, i64, bool), (i64, i64)>;
+
+fn read_buckets(conn: &rusqlite::Connection, query: &str) -> Result<Buckets, rusqlite::Error> {
+    let mut stmt = conn.prepare(query)?;
+    let mut rows = stmt.query([])?;
+    let mut buckets = BTreeMap::new();
+    while let Some(row) = rows.next()? {
+        buckets.insert(
+            (
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ),
+            (row.get(6)?, row.get(7)?),
+        );
+    }
+    Ok(buckets)
+}
+
+/// Recomputes the balance aggregates from the wallet's outputs, and compares them with
+/// the stored aggregates.
+pub(crate) fn check(conn: &rusqlite::Connection) -> Result<CheckResult, rusqlite::Error> {
+    let installed = conn
+        .prepare(
+            "SELECT name FROM sqlite_schema
+            WHERE type = 'trigger' AND name LIKE 'ext_zallet_balances_%'",
+        )?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let missing_triggers = all_triggers()
+        .map(|(trigger, _)| trigger)
+        .filter(|trigger| !installed.contains(trigger))
+        .collect();
+
+    let stored = read_buckets(
+        conn,
+        &format!("SELECT {BUCKET_COLS}, value, output_count FROM ext_zallet_db_balance_aggregates"),
+    )?;
+    let expected = read_buckets(
+        conn,
+        &format!(
+            "SELECT {BUCKET_COLS}, SUM(value), COUNT(*)
+            FROM ({})
+            GROUP BY {BUCKET_COLS}",
+            POOLS
+                .iter()
+                .map(|pool| pool.entries("1"))
+                .collect::<Vec<_>>()
+                .join(" UNION ALL "),
+        ),
+    )?;
+
+    // Compare the buckets of each account and pool.
+    let mut totals = BTreeMap::<(i64, i64), ((i64, i64), (i64, i64), bool)>::new();
+    for (key, value, is_stored) in stored
+        .iter()
+        .map(|(k, v)| (k, v, true))
+        .chain(expected.iter().map(|(k, v)| (k, v, false)))
+    {
+        let entry = totals.entry((key.0, key.1)).or_default();
+        let total = if is_stored {
+            &mut entry.0
+        } else {
+            &mut entry.1
+        };
+        total.0 += value.1;
+        total.1 += value.0;
+        if stored.get(key) != expected.get(key) {
+            entry.2 = true;
+        }
+    }
+
+    let uuids = conn
+        .prepare("SELECT id, uuid FROM accounts")?
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Uuid>(1)?))
+        })?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
+    let discrepancies = totals
+        .into_iter()
+        .filter(|(_, (_, _, differs))| *differs)
+        .filter_map(|((account_id, pool), (stored, expected, _))| {
+            Some(Discrepancy {
+                account_uuid: uuids.get(&account_id).copied().unwrap_or(Uuid::nil()),
+                pool: pool_type(pool)?,
+                stored,
+                expected,
+            })
+        })
+        .collect();
+
+    Ok(CheckResult {
+        missing_triggers,
+        discrepancies,
+    })
+}
+
+/// The balance of an account's outputs in a pool.
+#[cfg(zallet_build = "wallet")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Balance {
+    /// Outputs with at least the requested number of confirmations (including unmined
+    /// outputs if zero confirmations were requested).
+    pub(crate) confirmed: Zatoshis,
+    /// Outputs that do not yet have the requested number of confirmations.
+    pub(crate) pending: Zatoshis,
+    /// Coinbase outputs that have not yet reached maturity.
+    pub(crate) immature: Zatoshis,
+}
+
+/// The category that a bucket of outputs falls into for a balance query.
+#[cfg(zallet_build = "wallet")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Category {
+    Confirmed,
+    Pending,
+    Immature,
+}
+
+/// A bucket of the wallet's unspent outputs, as stored in the aggregates table.
+#[cfg(zallet_build = "wallet")]
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    mined_height: i64,
+    expiry_height: i64,
+    spend_expiry_height: i64,
+    is_coinbase: bool,
+}
+
+#[cfg(zallet_build = "wallet")]
+impl Bucket {
+    /// Returns the category of the bucket's outputs at the chain tip `tip`, or `None` if
+    /// they are spent or were received in an expired transaction.
+    fn classify(&self, tip: BlockHeight, minconf: u32) -> Option<Category> {
+        let tip = i64::from(u32::from(tip));
+
+        // Outputs are spent by unexpired unmined transactions.
+        if self.spend_expiry_height == 0 || self.spend_expiry_height > tip {
+            return None;
+        }
+
+        if self.mined_height < 0 {
+            let expired = !(self.expiry_height == 0 || self.expiry_height > tip);
+            return match (expired, minconf) {
+                (true, _) => None,
+                (false, 0) => Some(Category::Confirmed),
+                (false, _) => Some(Category::Pending),
+            };
+        }
+
+        let confirmations = tip + 1 - self.mined_height;
+        Some(
+            if self.is_coinbase && confirmations < i64::from(COINBASE_MATURITY) {
+                Category::Immature
+            } else if confirmations >= i64::from(minconf) {
+                Category::Confirmed
+            } else {
+                Category::Pending
+            },
+        )
+    }
+}
+
+/// Compares the number and total value of the outputs in each pool's balance aggregates
+/// with those of the pool's unspent outputs, logging a warning for each pool where they
+/// differ. Returns `true` if they match in every pool.
+///
+/// This is much cheaper than [`check`], as it does not work out which bucket each output
+/// belongs in. It catches outputs that are missing from (or left behind in) the
+/// aggregates, but not outputs that are counted in the wrong bucket.
+#[cfg(zallet_build = "wallet")]
+pub(super) fn totals_match(conn: &rusqlite::Connection) -> Result<bool, rusqlite::Error> {
+    let totals = |row: &rusqlite::Row<'_>| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?));
+
+    let mut matched = true;
+    for pool in &POOLS {
+        let stored = conn.query_row(
+            "SELECT IFNULL(SUM(output_count), 0), IFNULL(SUM(value), 0)
+            FROM ext_zallet_db_balance_aggregates
+            WHERE pool = ?1",
+            [pool.code],
+            totals,
+        )?;
+        let expected = conn.query_row(&pool.unspent_totals(), [], totals)?;
+
+        if stored != expected {
+            warn!(
+                "Balance aggregates count {} {} outputs worth {} zatoshis, but the wallet has {} worth {} zatoshis; run `zallet wallet-check --repair` to rebuild them",
+                stored.0, pool.name, stored.1, expected.0, expected.1,
+            );
+            matched = false;
+        }
+    }
+    Ok(matched)
+}
+
+/// Returns the balance of each account's outputs in each pool at the chain tip `tip`,
+/// read from the balance aggregates.
+#[cfg(zallet_build = "wallet")]
+pub(crate) fn account_balances(
+    conn: &rusqlite::Connection,
+    tip: BlockHeight,
+    minconf: u32,
+) -> Result<Vec<(Uuid, PoolType, Balance)>, rusqlite::Error> {
+    totals_match(conn)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT a.uuid, g.pool, g.mined_height, g.expiry_height, g.spend_expiry_height,
+            g.is_coinbase, g.value
+        FROM ext_zallet_db_balance_aggregates g
+        JOIN accounts a ON a.id = g.account_id",
+    )?;
+    let mut rows = stmt.query([])?;
+
+    let mut totals = BTreeMap::<(Uuid, i64), [i64; 3]>::new();
+    while let Some(row) = rows.next()? {
+        let bucket = Bucket {
+            mined_height: row.get(2)?,
+            expiry_height: row.get(3)?,
+            spend_expiry_height: row.get(4)?,
+            is_coinbase: row.get(5)?,
+        };
+        if let Some(category) = bucket.classify(tip, minconf) {
+            let total =
+                &mut totals.entry((row.get(0)?, row.get(1)?)).or_default()[category as usize];
+            *total = total
+                .checked_add(row.get(6)?)
+                .ok_or(rusqlite::Error::IntegralValueOutOfRange(6, i64::MAX))?;
+        }
+    }
+
+    let zatoshis = |value| {
+        Zatoshis::from_nonnegative_i64(value)
+            .map_err(|_| rusqlite::Error::IntegralValueOutOfRange(6, value))
+    };
+    let mut balances = vec![];
+    for ((account_uuid, pool), [confirmed, pending, immature]) in totals {
+        if let Some(pool) = pool_type(pool) {
+            balances.push((
+                account_uuid,
+                pool,
+                Balance {
+                    confirmed: zatoshis(confirmed)?,
+                    pending: zatoshis(pending)?,
+                    immature: zatoshis(immature)?,
+                },
+            ));
+        }
+    }
+    Ok(balances)
+}
+
+/// Returns the value of each account's outputs in `pool` that have at least `minconf`
+/// confirmations at `at`, excluding immature coinbase outputs.
+///
+/// Queries of the chain tip are answered from the balance aggregates; queries of an
+/// earlier height sum the wallet's outputs.
+#[cfg(zallet_build = "wallet")]
+pub(crate) fn confirmed_balances(
+    conn: &rusqlite::Connection,
+    pool: PoolType,
+    at: QueryHeight,
+    minconf: u32,
+) -> Result<HashMap<Uuid, Zatoshis>, rusqlite::Error> {
+    match at {
+        QueryHeight::Tip(tip) => Ok(account_balances(conn, tip, minconf)?
+            .into_iter()
+            .filter(|(_, p, balance)| *p == pool && !balance.confirmed.is_zero())
+            .map(|(account_uuid, _, balance)| (account_uuid, balance.confirmed))
+            .collect()),
+        QueryHeight::AsOf(_) => sum_unspent_outputs(conn, pool, at, minconf),
+    }
+}
+
+#[cfg(zallet_build = "wallet")]
+fn sum_unspent_outputs(
+    conn: &rusqlite::Connection,
+    pool: PoolType,
+    at: QueryHeight,
+    minconf: u32,
+) -> Result<HashMap<Uuid, Zatoshis>, rusqlite::Error> {
+    let mut totals = HashMap::new();
+    for output in unspent_outputs(conn, pool, at, minconf, None)? {
+        if !output.is_immature(at.height()) {
+            let total = totals.entry(output.account_uuid).or_insert(Zatoshis::ZERO);
+            *total = (*total + output.value)
+                .ok_or(rusqlite::Error::IntegralValueOutOfRange(3, i64::MAX))?;
+        }
+    }
+    Ok(totals)
+}
+
+#[cfg(test)]
+#[cfg(zallet_build = "wallet")]
+mod tests {
+    use zcash_protocol::consensus::BlockHeight;
+
+    use super::{Bucket, Category};
+
+    #[test]
+    fn classify_buckets() {
+        let tip = BlockHeight::from_u32(1000);
+        let bucket = |mined_height, expiry_height, spend_expiry_height, is_coinbase| Bucket {
+            mined_height,
+            expiry_height,
+            spend_expiry_height,
+            is_coinbase,
+        };
+
+        // Mined outputs are confirmed once they have `minconf` confirmations.
+        assert_eq!(
+            bucket(991, 0, -1, false).classify(tip, 10),
+            Some(Category::Confirmed)
+        );
+        assert_eq!(
+            bucket(992, 0, -1, false).classify(tip, 10),
+            Some(Category::Pending)
+        );
+
+        // Unmined outputs count only while their transaction has not expired.
+        assert_eq!(
+            bucket(-1, 0, -1, false).classify(tip, 0),
+            Some(Category::Confirmed)
+        );
+        assert_eq!(
+            bucket(-1, 1001, -1, false).classify(tip, 1),
+            Some(Category::Pending)
+        );
+        assert_eq!(bucket(-1, 1000, -1, false).classify(tip, 0), None);
+        assert_eq!(bucket(-1, -1, -1, false).classify(tip, 0), None);
+
+        // Outputs spent by unexpired unmined transactions are excluded.
+        assert_eq!(bucket(900, 0, 0, false).classify(tip, 1), None);
+        assert_eq!(bucket(900, 0, 1001, false).classify(tip, 1), None);
+        assert_eq!(
+            bucket(900, 0, 1000, false).classify(tip, 1),
+            Some(Category::Confirmed)
+        );
+
+        // Coinbase outputs are immature until they have 100 confirmations.
+        assert_eq!(
+            bucket(902, 0, -1, true).classify(tip, 1),
+            Some(Category::Immature)
+        );
+        assert_eq!(
+            bucket(901, 0, -1, true).classify(tip, 1),
+            Some(Category::Confirmed)
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Conflicts between the wallet's transactions.
+//!
+//! Two transactions conflict if they spend the same wallet input. Finding conflicts
+//! joins the wallet's spends against each other, so methods that report the conflicts of
+//! many transactions find them all with a single query, and the set of transactions that
+//! conflict with a mined transaction is cached until the wallet next changes.
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::{named_params, types::Value};
+use zcash_protocol::TxId;
+
+/// A common table expression listing the wallet inputs spent by each transaction.
+const TX_SPENDS_CTE: &str = "
+    tx_spends AS (
+        SELECT 0 AS pool, sapling_received_note_id AS input_id, transaction_id
+        FROM sapling_received_note_spends
+        UNION ALL
+        SELECT 1 AS pool, orchard_received_note_id AS input_id, transaction_id
+        FROM orchard_received_note_spends
+        UNION ALL
+        SELECT 2 AS pool, transparent_received_output_id AS input_id, transaction_id
+        FROM transparent_received_output_spends
+    )";
+
+/// Returns the IDs of the transactions in the wallet that spend any of the same wallet
+/// inputs as each of the given transactions.
+///
+/// Transactions without conflicts are omitted from the returned map.
+pub(crate) fn wallet_conflicts(
+    conn: &rusqlite::Connection,
+    txids: &[TxId],
+) -> Result<HashMap<TxId, Vec<TxId>>, rusqlite::Error> {
+    if txids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        "WITH {TX_SPENDS_CTE},
+        requested AS (
+            SELECT id_tx, txid FROM transactions WHERE txid IN rarray(:txids)
+        )
+        SELECT DISTINCT r.txid, other_tx.txid, r.id_tx, other_tx.id_tx
+        FROM requested r
+        JOIN tx_spends s ON s.transaction_id = r.id_tx
+        JOIN tx_spends o
+            ON o.pool = s.pool
+            AND o.input_id = s.input_id
+            AND o.transaction_id != s.transaction_id
+        JOIN transactions other_tx ON other_tx.id_tx = o.transaction_id
+        ORDER BY r.id_tx, other_tx.id_tx"
+    ))?;
+
+    let txids = Rc::new(
+        txids
+            .iter()
+            .map(|txid| Value::Blob(txid.as_ref().to_vec()))
+            .collect::<Vec<_>>(),
+    );
+    let mut conflicts = HashMap::<_, Vec<_>>::new();
+    let mut rows = stmt.query(named_params! { ":txids": txids })?;
+    while let Some(row) = rows.next()? {
+        conflicts
+            .entry(TxId::from_bytes(row.get(0)?))
+            .or_default()
+            .push(TxId::from_bytes(row.get(1)?));
+    }
+    Ok(conflicts)
+}
+
+/// Returns the IDs of the wallet's unmined transactions that conflict with a mined
+/// transaction, and so can never be mined themselves.
+pub(super) fn conflicted_transactions(
+    conn: &rusqlite::Connection,
+) -> Result<HashSet<TxId>, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "WITH {TX_SPENDS_CTE}
+        SELECT DISTINCT t.txid
+        FROM transactions t
+        JOIN tx_spends s ON s.transaction_id = t.id_tx
+        JOIN tx_spends o
+            ON o.pool = s.pool
+            AND o.input_id = s.input_id
+            AND o.transaction_id != s.transaction_id
+        JOIN transactions other_tx ON other_tx.id_tx = o.transaction_id
+        WHERE t.mined_height IS NULL
+        AND other_tx.mined_height IS NOT NULL"
+    ))?;
+    stmt.query_map([], |row| row.get::<_, [u8; 32]>(0).map(TxId::from_bytes))?
+        .collect()
+}
+
+/// The cached result of [`conflicted_transactions`], shared by every connection to the
+/// wallet database.
+#[derive(Clone, Default)]
+pub(super) struct ConflictCache {
+    cached: Arc<Mutex<Option<Arc<HashSet<TxId>>>>>,
+}
+
+impl ConflictCache {
+    /// Returns the cached conflicted transactions, calling `build` to populate the cache
+    /// if it is empty.
+    ///
+    /// As with [`AccountIndex::get_or_build`], the cache is locked while `build` runs, so
+    /// it must only be invalidated once the wallet database is no longer locked for the
+    /// write that made the cached set stale.
+    ///
+    /// [`AccountIndex::get_or_build`]: super::account_index::AccountIndex::get_or_build
+    pub(super) fn get_or_build<E>(
+        &self,
+        build: impl FnOnce() -> Result<HashSet<TxId>, E>,
+    ) -> Result<Arc<HashSet<TxId>>, E> {
+        let mut cached = self.cached.lock().expect("not poisoned");
+        match cached.as_ref() {
+            Some(conflicted) => Ok(conflicted.clone()),
+            None => {
+                let conflicted = Arc::new(build()?);
+                *cached = Some(conflicted.clone());
+                Ok(conflicted)
+            }
+        }
+    }
+
+    /// Discards the cached set, so it is recomputed on next use.
+    pub(super) fn invalidate(&self) {
+        *self.cached.lock().expect("not poisoned") = None;
+    }
+}
@@ -1,36 +1,65 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroU32;
+use std::ops::Range;
 use std::path::Path;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::SystemTime;
 
 use rand::rngs::OsRng;
+use rusqlite::{OptionalExtension, named_params};
 use secrecy::SecretVec;
 use shardtree::{ShardTree, error::ShardTreeError};
 use transparent::{address::TransparentAddress, bundle::OutPoint, keys::TransparentKeyScope};
 use zcash_client_backend::{
     address::UnifiedAddress,
     data_api::{
-        AccountBirthday, AccountMeta, AddressInfo, Balance, InputSource, NoteFilter,
-        ORCHARD_SHARD_HEIGHT, ReceivedNotes, SAPLING_SHARD_HEIGHT, TargetValue,
+        Account, AccountBirthday, AccountMeta, AddressInfo, Balance, InputSource, MaxSpendMode,
+        NoteFilter, ORCHARD_SHARD_HEIGHT, ReceivedNotes, SAPLING_SHARD_HEIGHT, TargetValue,
         WalletCommitmentTrees, WalletRead, WalletUtxo, WalletWrite, Zip32Derivation,
         wallet::{ConfirmationsPolicy, TargetHeight},
     },
     keys::{UnifiedAddressRequest, UnifiedFullViewingKey, UnifiedSpendingKey},
     wallet::{Note, ReceivedNote, TransparentAddressMetadata, WalletTransparentOutput},
 };
-use zcash_client_sqlite::{WalletDb, util::SystemClock};
+use zcash_client_sqlite::{AccountUuid, WalletDb, error::SqliteClientError, util::SystemClock};
+use zcash_keys::address::Receiver;
 use zcash_primitives::{block::BlockHash, transaction::Transaction};
-use zcash_protocol::{ShieldedProtocol, consensus::BlockHeight};
+use zcash_protocol::{ShieldedProtocol, TxId, consensus::BlockHeight, value::Zatoshis};
 use zip32::DiversifierIndex;
 
+use super::{
+    account_index::{AccountIndex, IndexedAccounts},
+    address_reuse::{self, ReusedAddress},
+    balances::{self, CheckResult},
+    conflicts::{self, ConflictCache},
+    integrity, memos, next_address,
+    note_selection::Candidate,
+    received_by_address::{self, ReceivedOutput},
+    reorgs::{self, ReferenceBlock},
+    reservations::{InputReservations, ReservedInput},
+    scan_queue,
+    tx_effects::{self, TxEffects},
+    tx_values,
+};
+
 use crate::{
+    config::{InputSelection, TX_EXPIRING_SOON_THRESHOLD},
     error::{Error, ErrorKind},
     network::Network,
 };
 
-pub(super) fn pool(path: impl AsRef<Path>, params: Network) -> Result<WalletPool, Error> {
+#[cfg(zallet_build = "wallet")]
+use super::{sprout, transparent_addresses};
+
+pub(super) fn pool(
+    path: impl AsRef<Path>,
+    params: Network,
+    spend_zeroconf_change: bool,
+    input_selection: InputSelection,
+) -> Result<WalletPool, Error> {
     let config = deadpool_sqlite::Config::new(path.as_ref());
-    let manager = WalletManager::from_config(&config, params);
+    let manager =
+        WalletManager::from_config(&config, params, spend_zeroconf_change, input_selection);
     WalletPool::builder(manager)
         .config(deadpool::managed::PoolConfig::default())
         .build()
@@ -46,17 +75,37 @@ pub(crate) struct WalletManager {
     /// against SQLite `DatabaseBusy` errors.
     lock: Arc<RwLock<()>>,
     params: Network,
+    /// Whether unmined transparent change created by the wallet may be spent.
+    spend_zeroconf_change: bool,
+    /// The default strategy for selecting notes to spend.
+    input_selection: InputSelection,
+    /// The inputs reserved by in-progress operations, which input selection skips.
+    reservations: InputReservations,
+    /// The cached index of the wallet's accounts.
+    account_index: AccountIndex,
+    /// The cached set of transactions that conflict with a mined transaction.
+    conflicts: ConflictCache,
 }
 
 impl WalletManager {
     /// Creates a new [`WalletManager`] using the given [`deadpool_sqlite::Config`] backed
     /// by the specified [`deadpool_sqlite::Runtime`].
     #[must_use]
-    pub fn from_config(config: &deadpool_sqlite::Config, params: Network) -> Self {
+    pub fn from_config(
+        config: &deadpool_sqlite::Config,
+        params: Network,
+        spend_zeroconf_change: bool,
+        input_selection: InputSelection,
+    ) -> Self {
         Self {
             inner: deadpool_sqlite::Manager::from_config(config, deadpool_sqlite::Runtime::Tokio1),
             lock: Arc::new(RwLock::new(())),
             params,
+            spend_zeroconf_change,
+            input_selection,
+            reservations: InputReservations::default(),
+            account_index: AccountIndex::default(),
+            conflicts: ConflictCache::default(),
         }
     }
 }
@@ -75,6 +124,12 @@ impl deadpool::managed::Manager for WalletManager {
             inner,
             lock: self.lock.clone(),
             params: self.params,
+            spend_zeroconf_change: self.spend_zeroconf_change,
+            input_selection: Mutex::new(self.input_selection),
+            allowed_inputs: Mutex::new(None),
+            reservations: self.reservations.clone(),
+            account_index: self.account_index.clone(),
+            conflicts: self.conflicts.clone(),
         })
     }
 
@@ -83,6 +138,9 @@ impl deadpool::managed::Manager for WalletManager {
         obj: &mut Self::Type,
         metrics: &deadpool_sqlite::Metrics,
     ) -> deadpool::managed::RecycleResult<Self::Error> {
+        // Discard any per-request override of the note selection strategy.
+        *obj.input_selection.lock().unwrap() = self.input_selection;
+        *obj.allowed_inputs.lock().unwrap() = None;
         self.inner.recycle(&mut obj.inner, metrics).await
     }
 }
@@ -91,6 +149,13 @@ pub(crate) struct DbConnection {
     inner: deadpool_sync::SyncWrapper<rusqlite::Connection>,
     lock: Arc<RwLock<()>>,
     params: Network,
+    spend_zeroconf_change: bool,
+    input_selection: Mutex<InputSelection>,
+    /// If set, the only inputs that input selection may spend.
+    allowed_inputs: Mutex<Option<HashSet<ReservedInput>>>,
+    reservations: InputReservations,
+    account_index: AccountIndex,
+    conflicts: ConflictCache,
 }
 
 impl DbConnection {
@@ -98,6 +163,572 @@ impl DbConnection {
         &self.params
     }
 
+    /// Returns the inputs reserved by in-progress operations.
+    pub(crate) fn reservations(&self) -> &InputReservations {
+        &self.reservations
+    }
+
+    /// Returns the wallet's accounts, indexed by name and (for accounts derived from a
+    /// seed) by seed fingerprint and ZIP 32 account index.
+    ///
+    /// The index is cached, and only rebuilt after the wallet's accounts change.
+    pub(crate) fn indexed_accounts(&self) -> Result<Arc<IndexedAccounts>, SqliteClientError> {
+        self.account_index.get_or_build(|| {
+            self.with(|db_data| {
+                let mut accounts = IndexedAccounts::default();
+                for account_id in db_data.get_account_ids()? {
+                    let Some(account) = db_data.get_account(account_id)? else {
+                        continue;
+                    };
+
+                    if let Some(name) = account.name() {
+                        accounts.insert_named(name, account_id);
+                    }
+
+                    // Accounts from imported keys have no derivation.
+                    #[cfg(zallet_build = "wallet")]
+                    if let Some(derivation) = account.source().key_derivation() {
+                        accounts.insert_derived(
+                            *derivation.seed_fingerprint(),
+                            derivation.account_index(),
+                            account_id,
+                        );
+                    }
+                }
+                Ok(accounts)
+            })
+        })
+    }
+
+    /// Sets the strategy used to select notes when proposing transactions with this
+    /// connection.
+    ///
+    /// The configured `builder.input_selection` strategy is restored when the connection
+    /// is returned to the pool.
+    pub(crate) fn set_input_selection(&self, input_selection: InputSelection) {
+        *self.input_selection.lock().unwrap() = input_selection;
+    }
+
+    /// Restricts the inputs that input selection may spend with this connection to the
+    /// given inputs.
+    ///
+    /// The restriction is lifted when the connection is returned to the pool.
+    pub(crate) fn restrict_inputs(&self, inputs: HashSet<ReservedInput>) {
+        *self.allowed_inputs.lock().unwrap() = Some(inputs);
+    }
+
+    /// Lifts any restriction set by [`Self::restrict_inputs`].
+    pub(crate) fn allow_all_inputs(&self) {
+        *self.allowed_inputs.lock().unwrap() = None;
+    }
+
+    /// Returns whether input selection may spend the given input with this connection.
+    fn is_selectable(&self, input: &ReservedInput) -> bool {
+        self.allowed_inputs
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(input))
+            && !self.reservations.is_reserved(input)
+    }
+
+    /// Removes the notes that are reserved by in-progress operations, or that are
+    /// excluded by [`Self::restrict_inputs`].
+    fn unreserved_notes<NoteRef: Clone>(
+        &self,
+        notes: ReceivedNotes<NoteRef>,
+    ) -> ReceivedNotes<NoteRef> {
+        let unreserved = |txid: &TxId, protocol, index: u16| {
+            self.is_selectable(&ReservedInput::Note {
+                txid: *txid,
+                protocol,
+                index: index.into(),
+            })
+        };
+        ReceivedNotes::new(
+            notes
+                .sapling()
+                .iter()
+                .filter(|note| {
+                    unreserved(note.txid(), ShieldedProtocol::Sapling, note.output_index())
+                })
+                .cloned()
+                .collect(),
+            notes
+                .orchard()
+                .iter()
+                .filter(|note| {
+                    unreserved(note.txid(), ShieldedProtocol::Orchard, note.output_index())
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Returns `true` if the given unmined transparent output may be spent in a
+    /// transaction targeting `target_height`.
+    ///
+    /// Unmined outputs are only spendable if `builder.spend_zeroconf_change` is enabled
+    /// and the output is from a trusted transaction (see [`Self::is_trusted_tx`]).
+    /// Unmined outputs received from third parties are never spendable, and neither are
+    /// outputs of transactions that are expiring soon (matching `zcashd`).
+    pub(crate) fn is_unmined_output_spendable(
+        &self,
+        outpoint: &OutPoint,
+        target_height: TargetHeight,
+    ) -> Result<bool, SqliteClientError> {
+        if !self.spend_zeroconf_change {
+            return Ok(false);
+        }
+
+        let txid = TxId::from_bytes(*outpoint.hash());
+        if !self.is_trusted_tx(&txid)? {
+            return Ok(false);
+        }
+
+        Ok(self
+            .with(|db_data| db_data.get_transaction(txid))?
+            .is_some_and(|tx| !is_expiring_soon(tx.expiry_height(), target_height.into())))
+    }
+
+    /// Returns `true` if the outputs of the transaction with the given ID can be trusted.
+    ///
+    /// Mined transactions are always trusted. Following Bitcoin Core, an unmined
+    /// transaction is trusted only if it was created by this wallet, all of its
+    /// transparent inputs belong to the wallet, and every wallet output that it spends
+    /// is itself trusted.
+    pub(crate) fn is_trusted_tx(&self, txid: &TxId) -> Result<bool, SqliteClientError> {
+        is_trusted(*txid, |txid| {
+            let Some(tx) = self.with(|db_data| db_data.get_transaction(*txid))? else {
+                return Ok(None);
+            };
+            let transparent_inputs = tx.transparent_bundle().map_or(0, |bundle| bundle.vin.len());
+            Ok(self.with_raw(|conn, _| tx_trust(conn, txid, transparent_inputs))?)
+        })
+    }
+
+    /// Changes the name of the given account.
+    ///
+    /// Returns `false` if the wallet does not contain the account.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn rename_account(
+        &self,
+        account: AccountUuid,
+        name: &str,
+    ) -> Result<bool, rusqlite::Error> {
+        let res = self.with_raw_mut(|conn, _| rename_account(conn, account, name));
+        self.account_index.invalidate();
+        res
+    }
+
+    /// Records that the given transactions were created without being broadcast.
+    pub(crate) fn mark_unbroadcast(&self, txids: &[TxId]) -> Result<(), rusqlite::Error> {
+        self.with_raw_mut(|conn, _| {
+            let tx = conn.transaction()?;
+            for txid in txids {
+                mark_unbroadcast(&tx, txid)?;
+            }
+            tx.commit()
+        })
+    }
+
+    /// Records that the given transaction has been broadcast.
+    pub(crate) fn mark_broadcast(&self, txid: &TxId) -> Result<(), rusqlite::Error> {
+        self.with_raw_mut(|conn, _| mark_broadcast(conn, txid))
+    }
+
+    /// Returns the IDs of the transactions that the wallet created without broadcasting
+    /// them.
+    pub(crate) fn unbroadcast_transactions(&self) -> Result<Vec<TxId>, rusqlite::Error> {
+        self.with_raw(|conn, _| unbroadcast_transactions(conn))
+    }
+
+    /// Returns the key that authenticates the proposals returned by `z_proposetransfer`.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn proposal_key(&self) -> Result<SecretVec<u8>, rusqlite::Error> {
+        self.with_raw(|conn, _| {
+            conn.query_row("SELECT key FROM ext_zallet_db_proposal_key", [], |row| {
+                row.get(0)
+            })
+            .map(SecretVec::new)
+        })
+    }
+
+    /// Records the Sprout notes received by the Sprout keys of a migrated `zcashd`
+    /// wallet.
+    #[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+    pub(crate) fn record_sprout_notes(
+        &self,
+        notes: &[sprout::SproutNote],
+    ) -> Result<(), rusqlite::Error> {
+        self.with_raw_mut(|conn, _| {
+            let tx = conn.transaction()?;
+            sprout::record_notes(&tx, notes)?;
+            tx.commit()
+        })
+    }
+
+    /// Marks the Sprout notes with the given nullifiers as spent by the transaction
+    /// `txid`.
+    ///
+    /// Returns the number of notes that were newly marked as spent.
+    #[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+    pub(crate) fn record_sprout_spends(
+        &self,
+        txid: &TxId,
+        nullifiers: &[[u8; 32]],
+    ) -> Result<usize, rusqlite::Error> {
+        self.with_raw(|conn, _| sprout::record_spends(conn, txid, nullifiers))
+    }
+
+    /// Records the wallet's blocks above `fork_height`, and the wallet transactions mined
+    /// in them, as removed by a chain reorg.
+    ///
+    /// This must be called before the wallet is rewound to `fork_height`. See [`reorgs`]
+    /// for how the records are used.
+    pub(crate) fn record_reorg(&self, fork_height: BlockHeight) -> Result<(), rusqlite::Error> {
+        self.with_raw_mut(|conn, _| {
+            let tx = conn.transaction()?;
+            reorgs::record_orphaned_blocks(&tx, fork_height)?;
+            tx.commit()
+        })
+    }
+
+    /// Finds the block with the given hash, in either the wallet's current chain or the
+    /// blocks that chain reorgs have removed from it.
+    ///
+    /// Returns `None` if the wallet has never seen the block.
+    pub(crate) fn find_reference_block(
+        &self,
+        hash: &BlockHash,
+    ) -> Result<Option<ReferenceBlock>, rusqlite::Error> {
+        self.with_raw(|conn, _| reorgs::find_reference_block(conn, hash))
+    }
+
+    /// Returns the wallet's unspent Sprout notes, or `None` if the wallet has never held
+    /// any Sprout notes.
+    ///
+    /// See [`sprout`] for how Sprout notes are tracked.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn sprout_balance(&self) -> Result<Option<sprout::SproutBalance>, rusqlite::Error> {
+        self.with_raw(|conn, _| sprout::balance(conn))
+    }
+
+    /// Records the total values of the inputs and outputs of transactions created by the
+    /// wallet.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn record_tx_values(
+        &self,
+        values: impl IntoIterator<Item = (TxId, tx_values::TxValues)>,
+    ) -> Result<(), rusqlite::Error> {
+        self.with_raw_mut(|conn, _| {
+            let tx = conn.transaction()?;
+            for (txid, values) in values {
+                tx_values::record(&tx, &txid, &values)?;
+            }
+            tx.commit()
+        })
+    }
+
+    /// Returns the fee paid by the given transaction, or `None` if it is not known.
+    ///
+    /// See [`tx_values`] for how fees are determined.
+    pub(crate) fn tx_fee(&self, txid: &TxId) -> Result<Option<Zatoshis>, SqliteClientError> {
+        if let Some(fee) = self.with_raw(|conn, _| tx_values::stored_fee(conn, txid))? {
+            return Ok(Some(fee));
+        }
+
+        let Some(tx) = self.with(|db_data| db_data.get_transaction(*txid))? else {
+            return Ok(None);
+        };
+        self.with_raw(|conn, _| {
+            tx.fee_paid(|prevout| {
+                tx_values::wallet_output_value(conn, prevout).map_err(SqliteClientError::from)
+            })
+        })
+    }
+
+    /// Records the current state of an async operation.
+    pub(crate) fn save_operation(&self, op: &StoredOperation) -> Result<(), rusqlite::Error> {
+        self.with_raw_mut(|conn, _| {
+            let tx = conn.transaction()?;
+            save_operation(&tx, op)?;
+            tx.commit()
+        })
+    }
+
+    /// Returns the async operations that have been recorded, in order of creation.
+    pub(crate) fn operations(&self) -> Result<Vec<StoredOperation>, rusqlite::Error> {
+        self.with_raw(|conn, _| operations(conn))
+    }
+
+    /// Removes the async operation with the given ID.
+    pub(crate) fn delete_operation(&self, operation_id: &str) -> Result<(), rusqlite::Error> {
+        self.with_raw_mut(|conn, _| {
+            let tx = conn.transaction()?;
+            delete_operation(&tx, operation_id)?;
+            tx.commit()
+        })
+    }
+
+    /// Labels the given address, unless it already has a label.
+    #[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+    pub(crate) fn add_address_label(
+        &self,
+        address: &str,
+        label: &str,
+        purpose: AddressPurpose,
+    ) -> Result<AddressLabelOutcome, rusqlite::Error> {
+        self.with_raw_mut(|conn, _| {
+            let tx = conn.transaction()?;
+            let outcome = add_address_label(&tx, address, label, purpose)?;
+            tx.commit()?;
+            Ok(outcome)
+        })
+    }
+
+    /// Recomputes the balance aggregates from the wallet's outputs, and compares them
+    /// with the stored aggregates.
+    pub(crate) fn check_balance_aggregates(&self) -> Result<CheckResult, rusqlite::Error> {
+        self.with_raw(|conn, _| balances::check(conn))
+    }
+
+    /// Reinstalls the triggers that maintain the balance aggregates, and recomputes the
+    /// aggregates from the wallet's outputs.
+    pub(crate) fn rebuild_balance_aggregates(&self) -> Result<(), rusqlite::Error> {
+        self.with_raw_mut(|conn, _| balances::install(conn, true))
+    }
+
+    /// Runs SQLite's integrity check of the database file, and returns any problems
+    /// that it reports.
+    pub(crate) fn check_sqlite_integrity(&self) -> Result<Vec<String>, rusqlite::Error> {
+        self.with_raw(|conn, _| integrity::sqlite_integrity(conn))
+    }
+
+    /// Returns the rows of the wallet's tables that refer to missing or mismatched rows.
+    pub(crate) fn check_references(&self) -> Result<Vec<String>, rusqlite::Error> {
+        self.with_raw(|conn, _| integrity::references(conn))
+    }
+
+    /// Returns the wallet outputs that a mined wallet transaction spends, but that are
+    /// recorded as unspent.
+    pub(crate) fn check_unrecorded_spends(&self) -> Result<Vec<String>, SqliteClientError> {
+        integrity::unrecorded_spends(self)
+    }
+
+    /// Returns the overlaps and gaps between the ranges of blocks in the scan queue.
+    pub(crate) fn check_scan_ranges(&self) -> Result<Vec<String>, rusqlite::Error> {
+        self.with_raw(|conn, _| integrity::scan_ranges(conn))
+    }
+
+    /// Returns the addresses that do not match their recorded derivation.
+    pub(crate) fn check_address_derivation(&self) -> Result<Vec<String>, SqliteClientError> {
+        integrity::address_derivation(self)
+    }
+
+    /// Writes a consistent copy of the database to `path`, which must not exist.
+    pub(crate) fn backup_to(&self, path: &Path) -> Result<(), rusqlite::Error> {
+        self.with_raw(|conn, _| integrity::backup(conn, path))
+    }
+
+    /// Adds the memos of any newly-received notes to the memo index.
+    pub(crate) fn index_memos(&self) -> Result<usize, rusqlite::Error> {
+        self.with_raw_mut(|conn, _| {
+            let tx = conn.transaction()?;
+            let indexed = memos::index(&tx)?;
+            tx.commit()?;
+            Ok(indexed)
+        })
+    }
+
+    /// Discards the memo index and rebuilds it from the wallet's received notes.
+    ///
+    /// Returns the number of notes that were indexed.
+    pub(crate) fn rebuild_memo_index(&self) -> Result<usize, rusqlite::Error> {
+        self.with_raw_mut(|conn, _| {
+            let tx = conn.transaction()?;
+            memos::clear(&tx)?;
+            let indexed = memos::index(&tx)?;
+            tx.commit()?;
+            Ok(indexed)
+        })
+    }
+
+    /// Discards the memo index.
+    pub(crate) fn clear_memo_index(&self) -> Result<(), rusqlite::Error> {
+        self.with_raw(|conn, _| memos::clear(conn))
+    }
+
+    /// Returns the received notes whose text memos contain `query`.
+    ///
+    /// See [`memos::search`] for the meaning of the parameters.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn search_memos(
+        &self,
+        query: &str,
+        account_uuid: Option<uuid::Uuid>,
+        from_height: Option<BlockHeight>,
+        to_height: Option<BlockHeight>,
+        limit: u32,
+    ) -> Result<Vec<memos::MemoMatch>, rusqlite::Error> {
+        self.with_raw(|conn, _| {
+            memos::search(conn, query, account_uuid, from_height, to_height, limit)
+        })
+    }
+
+    /// Returns the IDs of the wallet's unmined transactions that conflict with a mined
+    /// transaction, and so can never be mined themselves.
+    ///
+    /// The set is only recomputed after the wallet has changed.
+    pub(crate) fn conflicted_transactions(&self) -> Result<Arc<HashSet<TxId>>, rusqlite::Error> {
+        self.conflicts
+            .get_or_build(|| self.with_raw(|conn, _| conflicts::conflicted_transactions(conn)))
+    }
+
+    /// Returns whether the wallet contains the given transaction.
+    pub(crate) fn contains_transaction(&self, txid: &TxId) -> Result<bool, rusqlite::Error> {
+        self.with_raw(|conn, _| contains_transaction(conn, txid))
+    }
+
+    /// Returns how the given transaction affects the wallet, or `None` if it is not a
+    /// wallet transaction.
+    pub(crate) fn transaction_effects(
+        &self,
+        txid: &TxId,
+    ) -> Result<Option<TxEffects>, rusqlite::Error> {
+        self.with_raw(|conn, _| tx_effects::for_transaction(conn, txid))
+    }
+
+    /// Schedules the blocks in the given range that have already been scanned to be
+    /// scanned again. See [`scan_queue`] for how rescans are scheduled.
+    pub(crate) fn reschedule_scan(
+        &self,
+        heights: Range<BlockHeight>,
+    ) -> Result<(), rusqlite::Error> {
+        self.with_raw_mut(|conn, _| {
+            let tx = conn.transaction()?;
+            scan_queue::reschedule(&tx, heights)?;
+            tx.commit()
+        })
+    }
+
+    /// Returns the number of blocks in the given range that are waiting to be scanned.
+    pub(crate) fn unscanned_blocks_in(
+        &self,
+        heights: Range<BlockHeight>,
+    ) -> Result<u32, rusqlite::Error> {
+        self.with_raw(|conn, _| scan_queue::unscanned_blocks_in(conn, heights))
+    }
+
+    /// Returns the wallet transactions mined within the given range of heights, along
+    /// with the height at which each was mined.
+    pub(crate) fn transactions_mined_in(
+        &self,
+        heights: Range<BlockHeight>,
+    ) -> Result<Vec<(TxId, BlockHeight)>, rusqlite::Error> {
+        self.with_raw(|conn, _| transactions_mined_in(conn, heights))
+    }
+
+    /// Returns the reused shielded addresses that the given transaction paid.
+    ///
+    /// See [`address_reuse`] for when an address counts as reused.
+    pub(crate) fn reused_addresses_paid_by(
+        &self,
+        txid: &TxId,
+    ) -> Result<Vec<ReusedAddress>, rusqlite::Error> {
+        self.with_raw(|conn, _| address_reuse::paid_by(conn, txid))
+    }
+
+    /// Returns the outputs received on any of the given receivers with at least `minconf`
+    /// confirmations, or `None` if none of the receivers belong to the wallet.
+    ///
+    /// See [`received_by_address`] for how outputs are matched to receivers.
+    pub(crate) fn received_on(
+        &self,
+        receivers: &[Receiver],
+        minconf: u32,
+        chain_height: BlockHeight,
+    ) -> Result<Option<Vec<ReceivedOutput>>, rusqlite::Error> {
+        self.with_raw(|conn, params| {
+            received_by_address::received_on(conn, params, receivers, minconf, chain_height)
+        })
+    }
+
+    /// Exposes the next unused transparent address of the given account (or change
+    /// address, if `change` is `true`), and returns it along with its address index.
+    ///
+    /// Returns `None` if the account has reached the transparent gap limit. See
+    /// [`transparent_addresses`] for how addresses are allocated.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn expose_next_transparent_address(
+        &self,
+        account: AccountUuid,
+        change: bool,
+        exposed_at: BlockHeight,
+    ) -> Result<Option<(String, u32)>, rusqlite::Error> {
+        self.with_raw_mut(|conn, _| {
+            let tx = conn.transaction()?;
+            let address = transparent_addresses::expose_next(&tx, account, change, exposed_at)?;
+            tx.commit()?;
+            Ok(address)
+        })
+    }
+
+    /// Returns the transparent address indices of the account's external addresses that
+    /// have been neither exposed nor used, in increasing order.
+    ///
+    /// See [`next_address`] for how the account's next address is found.
+    pub(crate) fn unexposed_transparent_indices(
+        &self,
+        account: AccountUuid,
+    ) -> Result<Vec<u32>, rusqlite::Error> {
+        self.with_raw(|conn, _| next_address::unexposed_transparent_indices(conn, account))
+    }
+
+    /// Returns the diversifier index above the highest index at which the account has
+    /// exposed an external address, or `None` if there is no such index.
+    ///
+    /// See [`next_address`] for how the account's next address is found.
+    pub(crate) fn first_unexposed_diversifier_index(
+        &self,
+        account: AccountUuid,
+    ) -> Result<Option<DiversifierIndex>, rusqlite::Error> {
+        self.with_raw(|conn, _| next_address::first_unexposed_diversifier_index(conn, account))
+    }
+
+    /// Returns the number of payments received by each reused shielded address in the
+    /// wallet, keyed by the encoded address.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn reused_addresses(&self) -> Result<HashMap<String, u32>, rusqlite::Error> {
+        self.with_raw(|conn, _| address_reuse::all(conn))
+    }
+
+    /// Returns the unmined wallet transactions whose expiry height is within the given
+    /// range of heights, along with each transaction's expiry height.
+    pub(crate) fn transactions_expiring_in(
+        &self,
+        heights: Range<BlockHeight>,
+    ) -> Result<Vec<(TxId, BlockHeight)>, rusqlite::Error> {
+        self.with_raw(|conn, _| transactions_expiring_in(conn, heights))
+    }
+
+    /// Returns the index of the given transaction within its block (if known), and the
+    /// wallet's row ID for the transaction, or `None` if the wallet does not contain it.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn transaction_position(
+        &self,
+        txid: &TxId,
+    ) -> Result<Option<(Option<u32>, i64)>, rusqlite::Error> {
+        self.with_raw(|conn, _| {
+            conn.query_row(
+                "SELECT tx_index, id_tx FROM transactions WHERE txid = :txid",
+                named_params! { ":txid": txid.as_ref() },
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+        })
+    }
+
     pub(crate) fn with<T>(
         &self,
         f: impl FnOnce(WalletDb<&rusqlite::Connection, Network, SystemClock, OsRng>) -> T,
@@ -117,7 +748,7 @@ impl DbConnection {
         &self,
         f: impl FnOnce(WalletDb<&mut rusqlite::Connection, Network, SystemClock, OsRng>) -> T,
     ) -> T {
-        tokio::task::block_in_place(|| {
+        let res = tokio::task::block_in_place(|| {
             let _guard = self.lock.write().unwrap();
             f(WalletDb::from_connection(
                 self.inner.lock().unwrap().as_mut(),
@@ -125,7 +756,10 @@ impl DbConnection {
                 SystemClock,
                 OsRng,
             ))
-        })
+        });
+        // Any write may have changed which transactions conflict.
+        self.conflicts.invalidate();
+        res
     }
 
     pub(crate) fn with_raw<T>(&self, f: impl FnOnce(&rusqlite::Connection, &Network) -> T) -> T {
@@ -139,10 +773,12 @@ impl DbConnection {
         &self,
         f: impl FnOnce(&mut rusqlite::Connection, &Network) -> T,
     ) -> T {
-        tokio::task::block_in_place(|| {
+        let res = tokio::task::block_in_place(|| {
             let _guard = self.lock.write().unwrap();
             f(self.inner.lock().unwrap().as_mut(), &self.params)
-        })
+        });
+        self.conflicts.invalidate();
+        res
     }
 }
 
@@ -385,16 +1021,66 @@ impl InputSource for DbConnection {
         confirmations_policy: ConfirmationsPolicy,
         exclude: &[Self::NoteRef],
     ) -> Result<ReceivedNotes<Self::NoteRef>, Self::Error> {
-        self.with(|db_data| {
+        let target = match target_value {
+            TargetValue::AtLeast(target) => target,
+            TargetValue::AllFunds(_) => {
+                return self
+                    .with(|db_data| {
+                        db_data.select_spendable_notes(
+                            account,
+                            target_value,
+                            sources,
+                            target_height,
+                            confirmations_policy,
+                            exclude,
+                        )
+                    })
+                    .map(|notes| self.unreserved_notes(notes));
+            }
+        };
+
+        // Fetch every note that may be spent under the confirmations policy (which also
+        // omits spent and excluded notes), and let the strategy choose among them.
+        let notes = self.unreserved_notes(self.with(|db_data| {
             db_data.select_spendable_notes(
                 account,
-                target_value,
+                TargetValue::AllFunds(MaxSpendMode::MaxSpendable),
                 sources,
                 target_height,
                 confirmations_policy,
                 exclude,
             )
-        })
+        })?);
+
+        let candidates = notes
+            .sapling()
+            .iter()
+            .map(|note| {
+                Ok(Candidate {
+                    pool: ShieldedProtocol::Sapling,
+                    value: note.note_value()?,
+                    mined_height: note.mined_height(),
+                })
+            })
+            .chain(notes.orchard().iter().map(|note| {
+                Ok(Candidate {
+                    pool: ShieldedProtocol::Orchard,
+                    value: note.note_value()?,
+                    mined_height: note.mined_height(),
+                })
+            }))
+            .collect::<Result<Vec<_>, SqliteClientError>>()?;
+
+        let strategy = self.input_selection.lock().unwrap().strategy();
+        match strategy.select(&candidates, target) {
+            Some(selected) => {
+                let (sapling, orchard) = split_selection(&notes, selected);
+                Ok(ReceivedNotes::new(sapling, orchard))
+            }
+            // The notes are insufficient; return all of them so that the input selector
+            // can report by how much.
+            None => Ok(notes),
+        }
     }
 
     fn select_unspent_notes(
@@ -421,9 +1107,42 @@ impl InputSource for DbConnection {
         target_height: TargetHeight,
         confirmations_policy: ConfirmationsPolicy,
     ) -> Result<Vec<WalletUtxo>, Self::Error> {
-        self.with(|db_data| {
-            db_data.get_spendable_transparent_outputs(address, target_height, confirmations_policy)
-        })
+        // Whether an unmined output is spendable depends on `builder.spend_zeroconf_change`
+        // rather than on the caller's confirmations policy, so we fetch mined and unmined
+        // outputs separately.
+        let mined = self
+            .with(|db_data| {
+                db_data.get_spendable_transparent_outputs(
+                    address,
+                    target_height,
+                    confirmations_policy,
+                )
+            })?
+            .into_iter()
+            .filter(|utxo| utxo.mined_height().is_some());
+
+        let unmined = if self.spend_zeroconf_change {
+            self.with(|db_data| {
+                db_data.get_spendable_transparent_outputs(
+                    address,
+                    target_height,
+                    ConfirmationsPolicy::new_symmetrical(NonZeroU32::MIN, true),
+                )
+            })?
+            .into_iter()
+            .filter(|utxo| utxo.mined_height().is_none())
+            .collect()
+        } else {
+            vec![]
+        };
+
+        filter_zero_conf_outputs(
+            mined.chain(unmined).filter(|utxo| {
+                self.is_selectable(&ReservedInput::Transparent(utxo.outpoint().clone()))
+            }),
+            |utxo| utxo.mined_height().is_some(),
+            |utxo| self.is_unmined_output_spendable(utxo.outpoint(), target_height),
+        )
     }
 
     fn get_account_metadata(
@@ -448,9 +1167,11 @@ impl WalletWrite for DbConnection {
         birthday: &AccountBirthday,
         key_source: Option<&str>,
     ) -> Result<(Self::AccountId, UnifiedSpendingKey), Self::Error> {
-        self.with_mut(|mut db_data| {
+        let res = self.with_mut(|mut db_data| {
             db_data.create_account(account_name, seed, birthday, key_source)
-        })
+        });
+        self.account_index.invalidate();
+        res
     }
 
     fn import_account_hd(
@@ -461,9 +1182,11 @@ impl WalletWrite for DbConnection {
         birthday: &AccountBirthday,
         key_source: Option<&str>,
     ) -> Result<(Self::Account, UnifiedSpendingKey), Self::Error> {
-        self.with_mut(|mut db_data| {
+        let res = self.with_mut(|mut db_data| {
             db_data.import_account_hd(account_name, seed, account_index, birthday, key_source)
-        })
+        });
+        self.account_index.invalidate();
+        res
     }
 
     fn import_account_ufvk(
@@ -474,13 +1197,17 @@ impl WalletWrite for DbConnection {
         purpose: zcash_client_backend::data_api::AccountPurpose,
         key_source: Option<&str>,
     ) -> Result<Self::Account, Self::Error> {
-        self.with_mut(|mut db_data| {
+        let res = self.with_mut(|mut db_data| {
             db_data.import_account_ufvk(account_name, unified_key, birthday, purpose, key_source)
-        })
+        });
+        self.account_index.invalidate();
+        res
     }
 
     fn delete_account(&mut self, account: Self::AccountId) -> Result<(), Self::Error> {
-        self.with_mut(|mut db_data| db_data.delete_account(account))
+        let res = self.with_mut(|mut db_data| db_data.delete_account(account));
+        self.account_index.invalidate();
+        res
     }
 
     #[cfg(feature = "zcashd-import")]
@@ -644,3 +1371,526 @@ impl WalletCommitmentTrees for DbConnection {
         self.with_mut(|mut db_data| db_data.put_orchard_subtree_roots(start_index, roots))
     }
 }
+
+/// Returns `true` if a transaction with the given expiry height will expire within
+/// [`TX_EXPIRING_SOON_THRESHOLD`] blocks of `next_height`.
+///
+/// Transactions with an expiry height of zero never expire.
+pub(super) fn is_expiring_soon(expiry_height: BlockHeight, next_height: BlockHeight) -> bool {
+    expiry_height != BlockHeight::from_u32(0)
+        && next_height + TX_EXPIRING_SOON_THRESHOLD > expiry_height
+}
+
+/// Returns `true` if the transaction with the given ID spends funds belonging to the
+/// wallet, meaning that it was created by the wallet.
+pub(super) fn spends_wallet_funds(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+) -> Result<bool, rusqlite::Error> {
+    conn.query_row(
+        "SELECT EXISTS (
+            SELECT 1
+            FROM v_transactions
+            WHERE txid = :txid
+            AND spent_note_count > 0
+        )",
+        named_params! { ":txid": txid.as_ref() },
+        |row| row.get(0),
+    )
+}
+
+/// Changes the name that `zcash_client_sqlite` records for the given account, which it
+/// does not provide a way to do.
+#[cfg(zallet_build = "wallet")]
+fn rename_account(
+    conn: &rusqlite::Connection,
+    account: AccountUuid,
+    name: &str,
+) -> Result<bool, rusqlite::Error> {
+    let updated = conn.execute(
+        "UPDATE accounts SET name = :name WHERE uuid = :account_uuid",
+        named_params! {
+            ":name": name,
+            ":account_uuid": account.expose_uuid(),
+        },
+    )?;
+    Ok(updated > 0)
+}
+
+pub(super) fn mark_unbroadcast(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "INSERT OR IGNORE INTO ext_zallet_db_unbroadcast_transactions (txid)
+        VALUES (:txid)",
+        named_params! { ":txid": txid.as_ref() },
+    )?;
+    Ok(())
+}
+
+pub(super) fn mark_broadcast(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "DELETE FROM ext_zallet_db_unbroadcast_transactions
+        WHERE txid = :txid",
+        named_params! { ":txid": txid.as_ref() },
+    )?;
+    Ok(())
+}
+
+pub(super) fn unbroadcast_transactions(
+    conn: &rusqlite::Connection,
+) -> Result<Vec<TxId>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT txid
+        FROM ext_zallet_db_unbroadcast_transactions
+        ORDER BY rowid",
+    )?;
+    stmt.query_map([], |row| row.get::<_, [u8; 32]>(0).map(TxId::from_bytes))?
+        .collect()
+}
+
+/// The purpose of a labelled address.
+#[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AddressPurpose {
+    /// The address of a counterparty that the wallet sends to.
+    Send,
+    /// An address at which the wallet receives funds.
+    Receive,
+}
+
+#[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+impl AddressPurpose {
+    fn as_str(self) -> &'static str {
+        match self {
+            AddressPurpose::Send => "send",
+            AddressPurpose::Receive => "receive",
+        }
+    }
+}
+
+/// The result of labelling an address.
+#[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum AddressLabelOutcome {
+    /// The label was added.
+    Added,
+    /// The address already had the same label and purpose.
+    Unchanged,
+    /// The address already had a different label or purpose, which was kept.
+    Conflict { existing_label: String },
+}
+
+#[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+pub(super) fn add_address_label(
+    conn: &rusqlite::Connection,
+    address: &str,
+    label: &str,
+    purpose: AddressPurpose,
+) -> Result<AddressLabelOutcome, rusqlite::Error> {
+    let existing = conn
+        .query_row(
+            "SELECT label, purpose
+            FROM ext_zallet_db_address_book
+            WHERE address = :address",
+            named_params! { ":address": address },
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()?;
+
+    match existing {
+        None => {
+            conn.execute(
+                "INSERT INTO ext_zallet_db_address_book (address, label, purpose)
+                VALUES (:address, :label, :purpose)",
+                named_params! {
+                    ":address": address,
+                    ":label": label,
+                    ":purpose": purpose.as_str(),
+                },
+            )?;
+            Ok(AddressLabelOutcome::Added)
+        }
+        Some((existing_label, existing_purpose))
+            if existing_label == label && existing_purpose == purpose.as_str() =>
+        {
+            Ok(AddressLabelOutcome::Unchanged)
+        }
+        Some((existing_label, _)) => Ok(AddressLabelOutcome::Conflict { existing_label }),
+    }
+}
+
+/// The persisted state of an async operation.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StoredOperation {
+    pub(crate) operation_id: String,
+    pub(crate) method: Option<String>,
+    /// The operation's context parameters, as JSON.
+    pub(crate) params: Option<String>,
+    pub(crate) state: String,
+    /// The times at which the operation was created, started, and finished, in seconds
+    /// since the Unix epoch.
+    pub(crate) creation_time: u64,
+    pub(crate) start_time: Option<u64>,
+    pub(crate) end_time: Option<u64>,
+    /// The IDs of the transactions that the operation has created.
+    pub(crate) txids: Vec<TxId>,
+    /// The result of a successful operation, as JSON.
+    pub(crate) result: Option<String>,
+    pub(crate) error: Option<StoredOperationError>,
+    /// The correlation ID of the RPC request that launched the operation.
+    pub(crate) request_id: Option<String>,
+}
+
+/// The error returned by a failed async operation.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StoredOperationError {
+    pub(crate) code: i32,
+    pub(crate) message: String,
+    pub(crate) data: Option<String>,
+}
+
+pub(super) fn save_operation(
+    conn: &rusqlite::Connection,
+    op: &StoredOperation,
+) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "INSERT INTO ext_zallet_db_async_operations (
+            operation_id, method, params, state, creation_time, start_time, end_time,
+            result, error_code, error_message, error_data, request_id
+        )
+        VALUES (
+            :operation_id, :method, :params, :state, :creation_time, :start_time, :end_time,
+            :result, :error_code, :error_message, :error_data, :request_id
+        )
+        ON CONFLICT (operation_id) DO UPDATE
+        SET state = :state,
+            start_time = :start_time,
+            end_time = :end_time,
+            result = :result,
+            error_code = :error_code,
+            error_message = :error_message,
+            error_data = :error_data",
+        named_params! {
+            ":operation_id": op.operation_id,
+            ":method": op.method,
+            ":params": op.params,
+            ":state": op.state,
+            ":creation_time": op.creation_time,
+            ":start_time": op.start_time,
+            ":end_time": op.end_time,
+            ":result": op.result,
+            ":error_code": op.error.as_ref().map(|e| e.code),
+            ":error_message": op.error.as_ref().map(|e| &e.message),
+            ":error_data": op.error.as_ref().and_then(|e| e.data.as_ref()),
+            ":request_id": op.request_id,
+        },
+    )?;
+
+    for txid in &op.txids {
+        conn.execute(
+            "INSERT OR IGNORE INTO ext_zallet_db_async_operation_txids (operation_id, txid)
+            VALUES (:operation_id, :txid)",
+            named_params! {
+                ":operation_id": op.operation_id,
+                ":txid": txid.as_ref(),
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn operations(
+    conn: &rusqlite::Connection,
+) -> Result<Vec<StoredOperation>, rusqlite::Error> {
+    let mut txids_stmt = conn.prepare(
+        "SELECT txid
+        FROM ext_zallet_db_async_operation_txids
+        WHERE operation_id = :operation_id
+        ORDER BY rowid",
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT operation_id, method, params, state, creation_time, start_time, end_time,
+            result, error_code, error_message, error_data, request_id
+        FROM ext_zallet_db_async_operations
+        ORDER BY creation_time, rowid",
+    )?;
+
+    stmt.query_and_then([], |row| {
+        let operation_id = row.get::<_, String>("operation_id")?;
+        let txids = txids_stmt
+            .query_map(named_params! { ":operation_id": operation_id }, |row| {
+                row.get::<_, [u8; 32]>(0).map(TxId::from_bytes)
+            })?
+            .collect::<Result<_, _>>()?;
+
+        let error = row
+            .get::<_, Option<i32>>("error_code")?
+            .zip(row.get::<_, Option<String>>("error_message")?)
+            .map(|(code, message)| {
+                Ok::<_, rusqlite::Error>(StoredOperationError {
+                    code,
+                    message,
+                    data: row.get("error_data")?,
+                })
+            })
+            .transpose()?;
+
+        Ok(StoredOperation {
+            operation_id,
+            method: row.get("method")?,
+            params: row.get("params")?,
+            state: row.get("state")?,
+            creation_time: row.get("creation_time")?,
+            start_time: row.get("start_time")?,
+            end_time: row.get("end_time")?,
+            txids,
+            result: row.get("result")?,
+            error,
+            request_id: row.get("request_id")?,
+        })
+    })?
+    .collect()
+}
+
+pub(super) fn delete_operation(
+    conn: &rusqlite::Connection,
+    operation_id: &str,
+) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "DELETE FROM ext_zallet_db_async_operation_txids
+        WHERE operation_id = :operation_id",
+        named_params! { ":operation_id": operation_id },
+    )?;
+    conn.execute(
+        "DELETE FROM ext_zallet_db_async_operations
+        WHERE operation_id = :operation_id",
+        named_params! { ":operation_id": operation_id },
+    )?;
+    Ok(())
+}
+
+/// What the wallet knows about whether a transaction can be trusted.
+#[derive(Clone)]
+pub(super) struct TxTrust {
+    /// Whether the transaction has been mined.
+    pub(super) mined: bool,
+    /// Whether the transaction was created by this wallet, and all of its transparent
+    /// inputs belong to the wallet.
+    pub(super) from_wallet: bool,
+    /// The unmined transactions that created the wallet outputs spent by this
+    /// transaction.
+    pub(super) unmined_sources: Vec<TxId>,
+}
+
+/// Returns `true` if the transaction with the given ID is trusted, using `lookup` to
+/// fetch what the wallet knows about each transaction.
+///
+/// A transaction is trusted if it is mined, or if it was created by this wallet and
+/// each of its unmined sources is trusted. Transactions unknown to the wallet are not
+/// trusted.
+pub(super) fn is_trusted<E>(
+    txid: TxId,
+    lookup: impl Fn(&TxId) -> Result<Option<TxTrust>, E>,
+) -> Result<bool, E> {
+    let mut visited = HashSet::new();
+    let mut pending = vec![txid];
+
+    while let Some(txid) = pending.pop() {
+        if !visited.insert(txid) {
+            continue;
+        }
+        match lookup(&txid)? {
+            Some(tx) if tx.mined => (),
+            Some(tx) if tx.from_wallet => pending.extend(tx.unmined_sources),
+            _ => return Ok(false),
+        }
+    }
+
+    Ok(true)
+}
+
+/// Fetches what the wallet knows about whether the given transaction can be trusted.
+///
+/// `transparent_inputs` is the number of transparent inputs of the transaction.
+pub(super) fn tx_trust(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+    transparent_inputs: usize,
+) -> Result<Option<TxTrust>, rusqlite::Error> {
+    let Some((id_tx, mined)) = conn
+        .query_row(
+            "SELECT id_tx, mined_height IS NOT NULL
+            FROM transactions
+            WHERE txid = :txid",
+            named_params! { ":txid": txid.as_ref() },
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?)),
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    if mined {
+        return Ok(Some(TxTrust {
+            mined,
+            from_wallet: false,
+            unmined_sources: vec![],
+        }));
+    }
+
+    let wallet_transparent_inputs = conn.query_row(
+        "SELECT COUNT(*)
+        FROM transparent_received_output_spends
+        WHERE transaction_id = :id_tx",
+        named_params! { ":id_tx": id_tx },
+        |row| row.get::<_, usize>(0),
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT src.txid
+        FROM transactions src
+        WHERE src.mined_height IS NULL
+        AND src.id_tx IN (
+            SELECT n.tx
+            FROM sapling_received_note_spends s
+            JOIN sapling_received_notes n ON n.id = s.sapling_received_note_id
+            WHERE s.transaction_id = :id_tx
+            UNION
+            SELECT n.tx
+            FROM orchard_received_note_spends s
+            JOIN orchard_received_notes n ON n.id = s.orchard_received_note_id
+            WHERE s.transaction_id = :id_tx
+            UNION
+            SELECT o.transaction_id
+            FROM transparent_received_output_spends s
+            JOIN transparent_received_outputs o ON o.id = s.transparent_received_output_id
+            WHERE s.transaction_id = :id_tx
+        )",
+    )?;
+    let unmined_sources = stmt
+        .query_map(named_params! { ":id_tx": id_tx }, |row| {
+            row.get::<_, [u8; 32]>(0).map(TxId::from_bytes)
+        })?
+        .collect::<Result<_, _>>()?;
+
+    Ok(Some(TxTrust {
+        mined,
+        from_wallet: spends_wallet_funds(conn, txid)?
+            && wallet_transparent_inputs == transparent_inputs,
+        unmined_sources,
+    }))
+}
+
+pub(super) fn contains_transaction(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+) -> Result<bool, rusqlite::Error> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM transactions WHERE txid = :txid)",
+        named_params! { ":txid": txid.as_ref() },
+        |row| row.get(0),
+    )
+}
+
+pub(super) fn transactions_mined_in(
+    conn: &rusqlite::Connection,
+    heights: Range<BlockHeight>,
+) -> Result<Vec<(TxId, BlockHeight)>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT txid, mined_height
+        FROM transactions
+        WHERE mined_height >= :start AND mined_height < :end
+        ORDER BY mined_height, id_tx",
+    )?;
+    stmt.query_map(
+        named_params! {
+            ":start": u32::from(heights.start),
+            ":end": u32::from(heights.end),
+        },
+        |row| {
+            Ok((
+                TxId::from_bytes(row.get(0)?),
+                BlockHeight::from_u32(row.get(1)?),
+            ))
+        },
+    )?
+    .collect()
+}
+
+pub(super) fn transactions_expiring_in(
+    conn: &rusqlite::Connection,
+    heights: Range<BlockHeight>,
+) -> Result<Vec<(TxId, BlockHeight)>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT txid, expiry_height
+        FROM transactions
+        WHERE mined_height IS NULL
+        AND expiry_height > 0
+        AND expiry_height >= :start AND expiry_height < :end
+        ORDER BY expiry_height, id_tx",
+    )?;
+    stmt.query_map(
+        named_params! {
+            ":start": u32::from(heights.start),
+            ":end": u32::from(heights.end),
+        },
+        |row| {
+            Ok((
+                TxId::from_bytes(row.get(0)?),
+                BlockHeight::from_u32(row.get(1)?),
+            ))
+        },
+    )?
+    .collect()
+}
+
+/// Filters `outputs` down to those that are spendable, given a predicate for whether an
+/// unmined output is spendable.
+///
+/// Mined outputs are assumed to have already been checked against the confirmations
+/// policy.
+fn filter_zero_conf_outputs<T, E>(
+    outputs: impl IntoIterator<Item = T>,
+    is_mined: impl Fn(&T) -> bool,
+    is_unmined_spendable: impl Fn(&T) -> Result<bool, E>,
+) -> Result<Vec<T>, E> {
+    let mut spendable = vec![];
+    for output in outputs {
+        if is_mined(&output) || is_unmined_spendable(&output)? {
+            spendable.push(output);
+        }
+    }
+    Ok(spendable)
+}
+
+/// The notes selected to fund a transaction, split by pool.
+type SelectedNotes<NoteRef> = (
+    Vec<ReceivedNote<NoteRef, sapling::Note>>,
+    Vec<ReceivedNote<NoteRef, orchard::Note>>,
+);
+
+/// Maps indices into the concatenation of the Sapling and Orchard notes in `notes` back
+/// to the notes they select.
+fn split_selection<NoteRef: Clone>(
+    notes: &ReceivedNotes<NoteRef>,
+    selected: Vec<usize>,
+) -> SelectedNotes<NoteRef> {
+    let sapling_count = notes.sapling().len();
+    let mut sapling = vec![];
+    let mut orchard = vec![];
+    for i in selected {
+        if i < sapling_count {
+            sapling.push(notes.sapling()[i].clone());
+        } else {
+            orchard.push(notes.orchard()[i - sapling_count].clone());
+        }
+    }
+    (sapling, orchard)
+}
@@ -47,3 +47,267 @@ CREATE TABLE ext_zallet_db_version_metadata (
     migrated TEXT NOT NULL
 )
 "#;
+
+/// Stores labels for addresses, such as those migrated from a `zcashd` address book.
+///
+/// ### Columns
+///
+/// - `address`: The encoded address.
+/// - `label`: The label for the address.
+/// - `purpose`: `send` for the addresses of counterparties that the wallet sends to, or
+///   `receive` for addresses at which the wallet receives funds.
+pub(crate) const TABLE_ADDRESS_BOOK: &str = r#"
+CREATE TABLE ext_zallet_db_address_book (
+    address TEXT NOT NULL PRIMARY KEY,
+    label TEXT NOT NULL,
+    purpose TEXT NOT NULL,
+    CHECK (purpose IN ('send', 'receive'))
+)
+"#;
+
+/// Stores the IDs of transactions that the wallet created without broadcasting them.
+///
+/// A transaction is removed from this table once it has been broadcast.
+///
+/// ### Columns
+///
+/// - `txid`: The ID of the transaction.
+pub(crate) const TABLE_UNBROADCAST_TRANSACTIONS: &str = r#"
+CREATE TABLE ext_zallet_db_unbroadcast_transactions (
+    txid BLOB NOT NULL PRIMARY KEY
+)
+"#;
+
+/// Stores the state of the wallet's async operations, so that they can be reported
+/// (and, if they had not started, resumed) after Zallet restarts.
+///
+/// ### Columns
+///
+/// - `operation_id`: The ID of the operation, in the form `opid-<uuid>`.
+/// - `method`: The RPC method that created the operation, or `NULL` if unknown.
+/// - `params`: The operation's context parameters as a JSON object, or `NULL` if the
+///   operation has no context.
+/// - `state`: The state of the operation (`queued`, `executing`, `cancelled`, `failed`,
+///   or `success`).
+/// - `creation_time`, `start_time`, `end_time`: The times at which the operation was
+///   created, started executing, and finished, in seconds since the Unix epoch.
+/// - `result`: The result of a successful operation, as JSON.
+/// - `error_code`, `error_message`, `error_data`: The error returned by a failed
+///   operation.
+pub(crate) const TABLE_ASYNC_OPERATIONS: &str = r#"
+CREATE TABLE ext_zallet_db_async_operations (
+    operation_id TEXT NOT NULL PRIMARY KEY,
+    method TEXT,
+    params TEXT,
+    state TEXT NOT NULL,
+    creation_time INTEGER NOT NULL,
+    start_time INTEGER,
+    end_time INTEGER,
+    result TEXT,
+    error_code INTEGER,
+    error_message TEXT,
+    error_data TEXT,
+    request_id TEXT,
+    CHECK ((error_code IS NULL) = (error_message IS NULL))
+)
+"#;
+
+/// Stores the IDs of the transactions created by each async operation.
+///
+/// Transactions are recorded as soon as they are created, before they are broadcast,
+/// so that an operation interrupted by a restart can be checked for whether its
+/// transactions reached the network.
+///
+/// ### Columns
+///
+/// - `operation_id`: The ID of the operation that created the transaction.
+/// - `txid`: The ID of the transaction.
+pub(crate) const TABLE_ASYNC_OPERATION_TXIDS: &str = r#"
+CREATE TABLE ext_zallet_db_async_operation_txids (
+    operation_id TEXT NOT NULL
+        REFERENCES ext_zallet_db_async_operations(operation_id),
+    txid BLOB NOT NULL,
+    PRIMARY KEY (operation_id, txid)
+)
+"#;
+
+/// Stores the total value of the wallet's unspent outputs, grouped into buckets that
+/// are classified together by balance queries.
+///
+/// Rows are maintained by triggers on the `zcash_client_sqlite` tables; see
+/// `components::database::balances` for the details.
+///
+/// ### Columns
+///
+/// - `account_id`: The account that received the outputs.
+/// - `pool`: The pool containing the outputs (0 for transparent, 2 for Sapling, and 3 for
+///   Orchard).
+/// - `mined_height`: The height at which the outputs were mined, or -1 if they are
+///   unmined.
+/// - `expiry_height`: For unmined outputs, the expiry height of their transaction (0 if
+///   it never expires, or -1 if it is unknown). 0 for mined outputs.
+/// - `spend_expiry_height`: -1 if the outputs are not spent by an unmined transaction, 0
+///   if they are spent by one that never expires, and otherwise the height after which
+///   every unmined transaction spending them has expired.
+/// - `is_coinbase`: Whether the outputs were received in a coinbase transaction.
+/// - `value`: The total value of the outputs, in zatoshis.
+/// - `output_count`: The number of outputs.
+pub(crate) const TABLE_BALANCE_AGGREGATES: &str = r#"
+CREATE TABLE ext_zallet_db_balance_aggregates (
+    account_id INTEGER NOT NULL,
+    pool INTEGER NOT NULL,
+    mined_height INTEGER NOT NULL,
+    expiry_height INTEGER NOT NULL,
+    spend_expiry_height INTEGER NOT NULL,
+    is_coinbase INTEGER NOT NULL,
+    value INTEGER NOT NULL,
+    output_count INTEGER NOT NULL,
+    PRIMARY KEY (
+        account_id, pool, mined_height, expiry_height, spend_expiry_height, is_coinbase
+    )
+)
+"#;
+
+/// Records the bucket of `ext_zallet_db_balance_aggregates` that each of the wallet's
+/// unspent outputs is counted in, so that the output can be removed from that bucket
+/// when it changes.
+///
+/// ### Columns
+///
+/// - `pool`: The pool containing the output.
+/// - `output_id`: The row ID of the output in the pool's received outputs table.
+/// - `value`: The value of the output, in zatoshis.
+/// - The remaining columns identify the output's bucket, as in
+///   `ext_zallet_db_balance_aggregates`.
+pub(crate) const TABLE_BALANCE_ENTRIES: &str = r#"
+CREATE TABLE ext_zallet_db_balance_entries (
+    pool INTEGER NOT NULL,
+    output_id INTEGER NOT NULL,
+    account_id INTEGER NOT NULL,
+    value INTEGER NOT NULL,
+    mined_height INTEGER NOT NULL,
+    expiry_height INTEGER NOT NULL,
+    spend_expiry_height INTEGER NOT NULL,
+    is_coinbase INTEGER NOT NULL,
+    PRIMARY KEY (pool, output_id)
+)
+"#;
+
+/// Stores the decoded text of the memos of the wallet's received notes, so that they can
+/// be searched.
+///
+/// Rows are added once the memo of a note is known; see
+/// `components::database::memos` for the details.
+///
+/// ### Columns
+///
+/// - `pool`: The pool containing the note (2 for Sapling, and 3 for Orchard).
+/// - `note_id`: The row ID of the note in the pool's received notes table.
+/// - `memo`: The text of the note's memo, or `NULL` if the memo is not a text memo.
+pub(crate) const TABLE_MEMO_INDEX: &str = r#"
+CREATE TABLE ext_zallet_db_memo_index (
+    pool INTEGER NOT NULL,
+    note_id INTEGER NOT NULL,
+    memo TEXT,
+    PRIMARY KEY (pool, note_id)
+)
+"#;
+
+/// Stores the blocks that chain reorgs have removed from the wallet's view of the chain.
+///
+/// See `components::database::reorgs` for how these are used by `listsinceblock`.
+///
+/// ### Columns
+///
+/// - `hash`: The hash of the block.
+/// - `height`: The height of the block.
+/// - `reorg_id`: Identifies the reorg that removed the block. Blocks removed by the same
+///   reorg were part of the same chain.
+/// - `fork_height`: The height that the reorg rewound the wallet to.
+/// - `fork_hash`: The hash of the block at `fork_height` when the reorg happened, or
+///   `NULL` if the wallet had not scanned it.
+pub(crate) const TABLE_ORPHANED_BLOCKS: &str = r#"
+CREATE TABLE ext_zallet_db_orphaned_blocks (
+    hash BLOB NOT NULL PRIMARY KEY,
+    height INTEGER NOT NULL,
+    reorg_id INTEGER NOT NULL,
+    fork_height INTEGER NOT NULL,
+    fork_hash BLOB
+)
+"#;
+
+/// Stores the wallet transactions that were mined in the blocks in
+/// `ext_zallet_db_orphaned_blocks`.
+///
+/// ### Columns
+///
+/// - `block_hash`: The hash of the orphaned block.
+/// - `txid`: The ID of the wallet transaction that was mined in the block.
+pub(crate) const TABLE_ORPHANED_TRANSACTIONS: &str = r#"
+CREATE TABLE ext_zallet_db_orphaned_transactions (
+    block_hash BLOB NOT NULL,
+    txid BLOB NOT NULL,
+    PRIMARY KEY (block_hash, txid)
+)
+"#;
+
+/// Stores the key with which Zallet authenticates the proposals returned by
+/// `z_proposetransfer`, so that `z_executeproposal` only executes proposals that this
+/// wallet created and that have not been modified.
+///
+/// This table should only ever contain exactly one row.
+///
+/// ### Columns
+///
+/// - `key`: A random 32-byte HMAC-SHA256 key, generated when the table was created.
+pub(crate) const TABLE_PROPOSAL_KEY: &str = r#"
+CREATE TABLE ext_zallet_db_proposal_key (
+    key BLOB NOT NULL
+)
+"#;
+
+/// Stores the Sprout notes received by the Sprout keys of a `zcashd` wallet that was
+/// migrated into this wallet.
+///
+/// Zallet cannot spend these notes; they are recorded so that their value remains
+/// visible. See `components::database::sprout` for how they are tracked.
+///
+/// ### Columns
+///
+/// - `txid`: The ID of the transaction that received the note.
+/// - `js_index`: The index of the JoinSplit description within the transaction.
+/// - `note_index`: The index of the note within the JoinSplit description.
+/// - `value`: The value of the note, in zatoshis.
+/// - `nullifier`: The nullifier of the note.
+/// - `spent_in_txid`: The ID of the transaction that spent the note, or `NULL` if the
+///   wallet has not seen it be spent.
+pub(crate) const TABLE_SPROUT_NOTES: &str = r#"
+CREATE TABLE ext_zallet_db_sprout_notes (
+    txid BLOB NOT NULL,
+    js_index INTEGER NOT NULL,
+    note_index INTEGER NOT NULL,
+    value INTEGER NOT NULL,
+    nullifier BLOB NOT NULL UNIQUE,
+    spent_in_txid BLOB,
+    PRIMARY KEY (txid, js_index, note_index)
+)
+"#;
+
+/// Stores the total values of the inputs and outputs of each transaction created by the
+/// wallet, from which the transaction's fee is computed.
+///
+/// See `components::database::tx_values` for how the fees of other transactions are
+/// determined.
+///
+/// ### Columns
+///
+/// - `txid`: The ID of the transaction.
+/// - `total_input`: The total value of the transaction's inputs, in zatoshis.
+/// - `total_output`: The total value of the transaction's outputs, in zatoshis.
+pub(crate) const TABLE_TX_VALUES: &str = r#"
+CREATE TABLE ext_zallet_db_tx_values (
+    txid BLOB NOT NULL PRIMARY KEY,
+    total_input INTEGER NOT NULL,
+    total_output INTEGER NOT NULL
+)
+"#;
@@ -3,7 +3,17 @@ use schemerz_rusqlite::RusqliteMigration;
 use zcash_client_sqlite::wallet::init::WalletMigrationError;
 use zcash_protocol::consensus::NetworkType;
 
+mod address_book;
+mod async_operation_request_ids;
+mod async_operations;
+mod balance_aggregates;
 mod initial_setup;
+mod memo_index;
+mod orphaned_blocks;
+mod proposal_key;
+mod sprout_notes;
+mod tx_values;
+mod unbroadcast_transactions;
 
 pub(in crate::components) fn all(
     network_type: NetworkType,
@@ -11,6 +21,26 @@ pub(in crate::components) fn all(
     [
         // initial_setup
         Box::new(initial_setup::Migration { network_type }) as _,
+        // unbroadcast_transactions
+        Box::new(unbroadcast_transactions::Migration) as _,
+        // async_operations
+        Box::new(async_operations::Migration) as _,
+        // async_operation_request_ids
+        Box::new(async_operation_request_ids::Migration) as _,
+        // address_book
+        Box::new(address_book::Migration) as _,
+        // balance_aggregates
+        Box::new(balance_aggregates::Migration) as _,
+        // memo_index
+        Box::new(memo_index::Migration) as _,
+        // tx_values
+        Box::new(tx_values::Migration) as _,
+        // sprout_notes
+        Box::new(sprout_notes::Migration) as _,
+        // orphaned_blocks
+        Box::new(orphaned_blocks::Migration) as _,
+        // proposal_key
+        Box::new(proposal_key::Migration) as _,
     ]
     .into_iter()
 }
@@ -0,0 +1,48 @@
+use std::collections::HashSet;
+
+use schemerz_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_client_sqlite::wallet::init::WalletMigrationError;
+
+use super::async_operation_request_ids;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0xc2f7a946_51d3_4e8b_8a0c_e49b3f6d1725);
+
+pub(super) struct Migration;
+
+impl schemerz::Migration<Uuid> for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [async_operation_request_ids::MIGRATION_ID]
+            .into_iter()
+            .collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Stores labels for the addresses that the wallet sends to and receives at."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch(
+            "CREATE TABLE ext_zallet_db_address_book (
+                address TEXT NOT NULL PRIMARY KEY,
+                label TEXT NOT NULL,
+                purpose TEXT NOT NULL,
+                CHECK (purpose IN ('send', 'receive'))
+            );",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+use std::collections::HashSet;
+
+use schemerz_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_client_sqlite::wallet::init::WalletMigrationError;
+
+use super::async_operations;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x3b8e5f21_c94a_4d7e_a613_0f52d9c7e4b8);
+
+pub(super) struct Migration;
+
+impl schemerz::Migration<Uuid> for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [async_operations::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Records the ID of the RPC request that launched each async operation."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch(
+            "ALTER TABLE ext_zallet_db_async_operations ADD COLUMN request_id TEXT;",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+
+use schemerz_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_client_sqlite::wallet::init::WalletMigrationError;
+
+use super::unbroadcast_transactions;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x9e47b1c3_2a6d_4f08_b5e2_6c13d8a0f974);
+
+pub(super) struct Migration;
+
+impl schemerz::Migration<Uuid> for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [unbroadcast_transactions::MIGRATION_ID]
+            .into_iter()
+            .collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Persists async operations across restarts."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch(
+            "CREATE TABLE ext_zallet_db_async_operations (
+                operation_id TEXT NOT NULL PRIMARY KEY,
+                method TEXT,
+                params TEXT,
+                state TEXT NOT NULL,
+                creation_time INTEGER NOT NULL,
+                start_time INTEGER,
+                end_time INTEGER,
+                result TEXT,
+                error_code INTEGER,
+                error_message TEXT,
+                error_data TEXT,
+                CHECK ((error_code IS NULL) = (error_message IS NULL))
+            );
+            CREATE TABLE ext_zallet_db_async_operation_txids (
+                operation_id TEXT NOT NULL
+                    REFERENCES ext_zallet_db_async_operations(operation_id),
+                txid BLOB NOT NULL,
+                PRIMARY KEY (operation_id, txid)
+            );",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
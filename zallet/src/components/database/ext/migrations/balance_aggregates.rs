@@ -0,0 +1,67 @@
+use std::collections::HashSet;
+
+use schemerz_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_client_sqlite::wallet::init::WalletMigrationError;
+
+use super::address_book;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x5e0d8b41_7c2a_4f96_b3e8_1a9c64d2f073);
+
+pub(super) struct Migration;
+
+impl schemerz::Migration<Uuid> for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [address_book::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Stores aggregates of the wallet's unspent outputs for balance queries."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // The tables are backfilled, and the triggers that maintain them installed, once
+        // every migration has been applied (see `Database::open`). This migration may run
+        // before later `zcash_client_sqlite` migrations that the backfill query relies on.
+        transaction.execute_batch(
+            "CREATE TABLE ext_zallet_db_balance_aggregates (
+                account_id INTEGER NOT NULL,
+                pool INTEGER NOT NULL,
+                mined_height INTEGER NOT NULL,
+                expiry_height INTEGER NOT NULL,
+                spend_expiry_height INTEGER NOT NULL,
+                is_coinbase INTEGER NOT NULL,
+                value INTEGER NOT NULL,
+                output_count INTEGER NOT NULL,
+                PRIMARY KEY (
+                    account_id, pool, mined_height, expiry_height, spend_expiry_height, is_coinbase
+                )
+            );
+            CREATE TABLE ext_zallet_db_balance_entries (
+                pool INTEGER NOT NULL,
+                output_id INTEGER NOT NULL,
+                account_id INTEGER NOT NULL,
+                value INTEGER NOT NULL,
+                mined_height INTEGER NOT NULL,
+                expiry_height INTEGER NOT NULL,
+                spend_expiry_height INTEGER NOT NULL,
+                is_coinbase INTEGER NOT NULL,
+                PRIMARY KEY (pool, output_id)
+            );",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
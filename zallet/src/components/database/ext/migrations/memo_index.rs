@@ -0,0 +1,47 @@
+use std::collections::HashSet;
+
+use schemerz_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_client_sqlite::wallet::init::WalletMigrationError;
+
+use super::balance_aggregates;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x2b7f4c19_d6e3_4a58_9f01_8c3e5a6d7b42);
+
+pub(super) struct Migration;
+
+impl schemerz::Migration<Uuid> for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [balance_aggregates::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Stores the decoded text of received memos for searching."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // The index is populated as the wallet syncs (see `Database::open`).
+        transaction.execute_batch(
+            "CREATE TABLE ext_zallet_db_memo_index (
+                pool INTEGER NOT NULL,
+                note_id INTEGER NOT NULL,
+                memo TEXT,
+                PRIMARY KEY (pool, note_id)
+            );",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
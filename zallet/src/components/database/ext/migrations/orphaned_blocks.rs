@@ -0,0 +1,53 @@
+use std::collections::HashSet;
+
+use schemerz_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_client_sqlite::wallet::init::WalletMigrationError;
+
+use super::sprout_notes;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x64b4869a_ee25_495b_a365_5ed617801359);
+
+pub(super) struct Migration;
+
+impl schemerz::Migration<Uuid> for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [sprout_notes::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Records the blocks and transactions that chain reorgs remove from the wallet."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // Reorgs that happened before this migration were not recorded.
+        transaction.execute_batch(
+            "CREATE TABLE ext_zallet_db_orphaned_blocks (
+                hash BLOB NOT NULL PRIMARY KEY,
+                height INTEGER NOT NULL,
+                reorg_id INTEGER NOT NULL,
+                fork_height INTEGER NOT NULL,
+                fork_hash BLOB
+            );
+            CREATE TABLE ext_zallet_db_orphaned_transactions (
+                block_hash BLOB NOT NULL,
+                txid BLOB NOT NULL,
+                PRIMARY KEY (block_hash, txid)
+            );",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
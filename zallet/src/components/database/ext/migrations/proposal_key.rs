@@ -0,0 +1,52 @@
+use std::collections::HashSet;
+
+use rand::{RngCore, rngs::OsRng};
+use rusqlite::named_params;
+use schemerz_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_client_sqlite::wallet::init::WalletMigrationError;
+
+use super::orphaned_blocks;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x3f0c9b52_71d4_4e8a_b6a9_c2e85d17f403);
+
+pub(super) struct Migration;
+
+impl schemerz::Migration<Uuid> for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [orphaned_blocks::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Generates the key that authenticates proposals created by z_proposetransfer."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch(
+            "CREATE TABLE ext_zallet_db_proposal_key (
+                key BLOB NOT NULL
+            );",
+        )?;
+
+        let mut key = [0; 32];
+        OsRng.fill_bytes(&mut key);
+        transaction.execute(
+            "INSERT INTO ext_zallet_db_proposal_key VALUES (:key)",
+            named_params! { ":key": &key[..] },
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashSet;
+
+use schemerz_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_client_sqlite::wallet::init::WalletMigrationError;
+
+use super::tx_values;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x49bb993d_74d6_48dd_a5f0_3b342b7a9db5);
+
+pub(super) struct Migration;
+
+impl schemerz::Migration<Uuid> for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [tx_values::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Stores the Sprout notes of wallets migrated from zcashd."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch(
+            "CREATE TABLE ext_zallet_db_sprout_notes (
+                txid BLOB NOT NULL,
+                js_index INTEGER NOT NULL,
+                note_index INTEGER NOT NULL,
+                value INTEGER NOT NULL,
+                nullifier BLOB NOT NULL UNIQUE,
+                spent_in_txid BLOB,
+                PRIMARY KEY (txid, js_index, note_index)
+            );",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
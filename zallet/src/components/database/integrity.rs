@@ -0,0 +1,353 @@
+//! Consistency checks of the wallet database, for `zallet wallet-check`.
+//!
+//! Each check only reads the database, and returns a description of every problem that
+//! it finds; a check passes if it finds no problems. The checks complement the balance
+//! aggregates check in [`super::balances`], which covers data that Zallet derives from
+//! the wallet's state rather than the state itself.
+
+use std::path::Path;
+
+use rusqlite::named_params;
+use transparent::keys::{IncomingViewingKey, NonHardenedChildIndex, TransparentKeyScope};
+use zcash_client_backend::{
+    data_api::{Account as _, AddressSource, WalletRead},
+    keys::{ReceiverRequirement, UnifiedAddressRequest},
+};
+use zcash_client_sqlite::error::SqliteClientError;
+use zcash_keys::address::Address;
+use zcash_protocol::TxId;
+
+use super::DbConnection;
+
+/// The `zcash_client_sqlite` tables that record a shielded pool's received notes.
+const SHIELDED_POOLS: [(&str, &str, &str); 2] = [
+    ("Sapling", "sapling", "output_index"),
+    ("Orchard", "orchard", "action_index"),
+];
+
+/// Returns the problems found by SQLite's own integrity check of the database file.
+pub(super) fn sqlite_integrity(
+    conn: &rusqlite::Connection,
+) -> Result<Vec<String>, rusqlite::Error> {
+    // A database without problems produces a single "ok" row.
+    conn.prepare("PRAGMA integrity_check")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .filter(|row| !matches!(row.as_deref(), Ok("ok")))
+        .collect()
+}
+
+/// Returns the rows that refer to missing rows of another table, and the received
+/// outputs that belong to a different account from the address they were received at.
+pub(super) fn references(conn: &rusqlite::Connection) -> Result<Vec<String>, rusqlite::Error> {
+    let mut problems = conn
+        .prepare("PRAGMA foreign_key_check")?
+        .query_map([], |row| {
+            Ok(format!(
+                "Row {} of {} refers to a missing row of {}",
+                row.get::<_, Option<i64>>(1)?
+                    .map_or_else(|| "?".into(), |rowid| rowid.to_string()),
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut query = SHIELDED_POOLS
+        .iter()
+        .map(|(name, prefix, index_col)| {
+            format!(
+                "SELECT '{name}', t.txid, n.{index_col}
+                FROM {prefix}_received_notes n
+                JOIN addresses a ON a.id = n.address_id
+                JOIN transactions t ON t.id_tx = n.tx
+                WHERE a.account_id != n.account_id"
+            )
+        })
+        .collect::<Vec<_>>();
+    query.push(
+        "SELECT 'Transparent', t.txid, o.output_index
+        FROM transparent_received_outputs o
+        JOIN addresses a ON a.cached_transparent_receiver_address = o.address
+        JOIN transactions t ON t.id_tx = o.transaction_id
+        WHERE a.account_id != o.account_id"
+            .into(),
+    );
+    problems.extend(
+        conn.prepare(&query.join(" UNION ALL "))?
+            .query_map([], |row| {
+                Ok(format!(
+                    "{} output {}:{} belongs to a different account from the address it was received at",
+                    row.get::<_, String>(0)?,
+                    TxId::from_bytes(row.get(1)?),
+                    row.get::<_, u32>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?,
+    );
+
+    Ok(problems)
+}
+
+/// Returns the wallet outputs that are spent by a mined wallet transaction, but that the
+/// wallet does not record as spent by that transaction.
+///
+/// Such outputs are counted as unspent in the wallet's balances.
+pub(super) fn unrecorded_spends(wallet: &DbConnection) -> Result<Vec<String>, SqliteClientError> {
+    let mined = wallet.with_raw(|conn, _| {
+        conn.prepare(
+            "SELECT txid FROM transactions
+            WHERE mined_height IS NOT NULL
+            ORDER BY mined_height, id_tx",
+        )?
+        .query_map([], |row| row.get::<_, [u8; 32]>(0).map(TxId::from_bytes))?
+        .collect::<Result<Vec<_>, _>>()
+    })?;
+
+    let mut problems = vec![];
+    for txid in mined {
+        // Transactions whose data the wallet has not fetched cannot be checked.
+        let Some(tx) = wallet.get_transaction(txid)? else {
+            continue;
+        };
+
+        let mut spent = vec![];
+        if let Some(bundle) = tx.transparent_bundle() {
+            if !bundle.is_coinbase() {
+                for input in &bundle.vin {
+                    spent.extend(wallet.with_raw(|conn, _| {
+                        unrecorded_transparent_spend(
+                            conn,
+                            &txid,
+                            &TxId::from_bytes(*input.prevout().hash()),
+                            input.prevout().n(),
+                        )
+                    })?);
+                }
+            }
+        }
+        if let Some(bundle) = tx.sapling_bundle() {
+            for spend in bundle.shielded_spends() {
+                spent.extend(wallet.with_raw(|conn, _| {
+                    unrecorded_shielded_spend(conn, &SHIELDED_POOLS[0], &txid, spend.nullifier().0)
+                })?);
+            }
+        }
+        if let Some(bundle) = tx.orchard_bundle() {
+            for action in bundle.actions() {
+                spent.extend(wallet.with_raw(|conn, _| {
+                    unrecorded_shielded_spend(
+                        conn,
+                        &SHIELDED_POOLS[1],
+                        &txid,
+                        action.nullifier().to_bytes(),
+                    )
+                })?);
+            }
+        }
+
+        problems.extend(spent.into_iter().map(|output| {
+            format!("{output} is spent by mined transaction {txid}, but is recorded as unspent")
+        }));
+    }
+
+    Ok(problems)
+}
+
+/// Returns the wallet's output at `prev_txid:n` if the wallet does not record it as
+/// spent by the transaction `txid`.
+fn unrecorded_transparent_spend(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+    prev_txid: &TxId,
+    n: u32,
+) -> Result<Option<String>, rusqlite::Error> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT 1
+        FROM transparent_received_outputs o
+        JOIN transactions t ON t.id_tx = o.transaction_id
+        WHERE t.txid = :prev_txid
+        AND o.output_index = :n
+        AND NOT EXISTS (
+            SELECT 1
+            FROM transparent_received_output_spends s
+            JOIN transactions st ON st.id_tx = s.transaction_id
+            WHERE s.transparent_received_output_id = o.id
+            AND st.txid = :txid
+        )",
+    )?;
+    let unrecorded = stmt.exists(named_params! {
+        ":prev_txid": prev_txid.as_ref(),
+        ":n": n,
+        ":txid": txid.as_ref(),
+    })?;
+    Ok(unrecorded.then(|| format!("Transparent output {prev_txid}:{n}")))
+}
+
+/// Returns the wallet's note with the nullifier `nf` if the wallet does not record it as
+/// spent by the transaction `txid`.
+fn unrecorded_shielded_spend(
+    conn: &rusqlite::Connection,
+    (name, prefix, index_col): &(&str, &str, &str),
+    txid: &TxId,
+    nf: [u8; 32],
+) -> Result<Option<String>, rusqlite::Error> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT t.txid, n.{index_col}
+        FROM {prefix}_received_notes n
+        JOIN transactions t ON t.id_tx = n.tx
+        WHERE n.nf = :nf
+        AND NOT EXISTS (
+            SELECT 1
+            FROM {prefix}_received_note_spends s
+            JOIN transactions st ON st.id_tx = s.transaction_id
+            WHERE s.{prefix}_received_note_id = n.id
+            AND st.txid = :txid
+        )"
+    ))?;
+    let mut rows = stmt.query(named_params! { ":nf": nf, ":txid": txid.as_ref() })?;
+    rows.next()?
+        .map(|row| {
+            Ok(format!(
+                "{name} output {}:{}",
+                TxId::from_bytes(row.get(0)?),
+                row.get::<_, u32>(1)?,
+            ))
+        })
+        .transpose()
+}
+
+/// Returns the problems with the ranges of blocks in the scan queue.
+///
+/// `zcash_client_sqlite` records every block between the wallet's birthday and its view
+/// of the chain tip in exactly one range, so the ranges must not be empty, overlap, or
+/// leave gaps between them.
+pub(super) fn scan_ranges(conn: &rusqlite::Connection) -> Result<Vec<String>, rusqlite::Error> {
+    let ranges = conn
+        .prepare(
+            "SELECT block_range_start, block_range_end
+            FROM scan_queue
+            ORDER BY block_range_start, block_range_end",
+        )?
+        .query_map([], |row| Ok((row.get::<_, u32>(0)?, row.get::<_, u32>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut problems = vec![];
+    for (start, end) in &ranges {
+        if start >= end {
+            problems.push(format!("Scan range {start}..{end} is empty"));
+        }
+    }
+    for pair in ranges.windows(2) {
+        let ((prev_start, prev_end), (start, end)) = (pair[0], pair[1]);
+        if start < prev_end {
+            problems.push(format!(
+                "Scan ranges {prev_start}..{prev_end} and {start}..{end} overlap"
+            ));
+        } else if start > prev_end {
+            problems.push(format!(
+                "Blocks {prev_end}..{start} are missing from the scan ranges"
+            ));
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Returns the addresses that do not match the address derived from their account's
+/// viewing key at their recorded diversifier index (or transparent address index).
+///
+/// Addresses without derivation information, and ephemeral transparent addresses, are
+/// not checked.
+pub(super) fn address_derivation(wallet: &DbConnection) -> Result<Vec<String>, SqliteClientError> {
+    let mut problems = vec![];
+
+    for account_id in wallet.get_account_ids()? {
+        let Some(account) = wallet.get_account(account_id)? else {
+            continue;
+        };
+        let uivk = account.uivk();
+
+        for address_info in wallet.list_addresses(account_id)? {
+            #[allow(irrefutable_let_patterns)]
+            let AddressSource::Derived {
+                diversifier_index, ..
+            } = address_info.source()
+            else {
+                continue;
+            };
+            let diversifier_index = *diversifier_index;
+
+            let matches = match address_info.address() {
+                Address::Unified(ua) => {
+                    let requirement = |present| {
+                        if present {
+                            ReceiverRequirement::Require
+                        } else {
+                            ReceiverRequirement::Omit
+                        }
+                    };
+                    let request = UnifiedAddressRequest::unsafe_custom(
+                        requirement(ua.has_orchard()),
+                        requirement(ua.has_sapling()),
+                        requirement(ua.has_transparent()),
+                    );
+                    uivk.address(diversifier_index, request)
+                        .is_ok_and(|derived| {
+                            derived.encode(wallet.params()) == ua.encode(wallet.params())
+                        })
+                }
+                Address::Sapling(addr) => {
+                    let request = UnifiedAddressRequest::unsafe_custom(
+                        ReceiverRequirement::Omit,
+                        ReceiverRequirement::Require,
+                        ReceiverRequirement::Omit,
+                    );
+                    uivk.address(diversifier_index, request)
+                        .is_ok_and(|derived| derived.sapling() == Some(addr))
+                }
+                Address::Transparent(addr) => {
+                    let index = u32::try_from(u128::from(diversifier_index))
+                        .ok()
+                        .and_then(NonHardenedChildIndex::from_index);
+                    let Some((index, account_pubkey)) =
+                        index.zip(account.ufvk().and_then(|ufvk| ufvk.transparent()))
+                    else {
+                        // Standalone transparent keys have no viewing key to derive from.
+                        continue;
+                    };
+                    let derived = match address_info.source().transparent_key_scope() {
+                        Some(&TransparentKeyScope::EXTERNAL) => account_pubkey
+                            .derive_external_ivk()
+                            .and_then(|ivk| ivk.derive_address(index)),
+                        Some(&TransparentKeyScope::INTERNAL) => account_pubkey
+                            .derive_internal_ivk()
+                            .and_then(|ivk| ivk.derive_address(index)),
+                        _ => continue,
+                    };
+                    derived.is_ok_and(|derived| derived == *addr)
+                }
+                Address::Tex(_) => continue,
+            };
+
+            if !matches {
+                problems.push(format!(
+                    "Address {} of account {} does not match its derivation at index {}",
+                    address_info.address().encode(wallet.params()),
+                    account_id.expose_uuid(),
+                    u128::from(diversifier_index),
+                ));
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Writes a consistent copy of the database to `path`, which must not exist.
+pub(super) fn backup(conn: &rusqlite::Connection, path: &Path) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "VACUUM INTO :path",
+        named_params! { ":path": path.to_string_lossy() },
+    )?;
+    Ok(())
+}
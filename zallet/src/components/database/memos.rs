@@ -0,0 +1,203 @@
+//! An index of the text memos of the notes received by the wallet.
+//!
+//! `zcash_client_sqlite` stores the raw memo of each received note once the transaction
+//! containing it has been fully fetched. The index records the decoded text of each such
+//! memo in `ext_zallet_db_memo_index`, so that memos can be searched without decoding
+//! every one of them. Notes are added to the index as their memos become available; memos
+//! that are not text are recorded as such, so that they are only decoded once.
+
+use zcash_protocol::{
+    ShieldedProtocol,
+    memo::{Memo, MemoBytes},
+};
+
+#[cfg(zallet_build = "wallet")]
+use {
+    rusqlite::named_params,
+    uuid::Uuid,
+    zcash_protocol::{TxId, consensus::BlockHeight, value::Zatoshis},
+};
+
+/// Returns the table holding the received notes of the given pool, and the name of the
+/// column holding each note's index within its transaction.
+fn notes_table(pool: ShieldedProtocol) -> (&'static str, &'static str) {
+    match pool {
+        ShieldedProtocol::Sapling => ("sapling_received_notes", "output_index"),
+        ShieldedProtocol::Orchard => ("orchard_received_notes", "action_index"),
+    }
+}
+
+fn pool_code(pool: ShieldedProtocol) -> i64 {
+    match pool {
+        ShieldedProtocol::Sapling => 2,
+        ShieldedProtocol::Orchard => 3,
+    }
+}
+
+/// Returns the text of the given memo, or `None` if it is not a text memo.
+fn decode(memo: &[u8]) -> Option<String> {
+    match MemoBytes::from_bytes(memo).ok().map(Memo::try_from)? {
+        Ok(Memo::Text(text)) => Some(text.into()),
+        _ => None,
+    }
+}
+
+/// Adds the memos of received notes that are not yet in the index.
+///
+/// Returns the number of notes that were added. This should be called within a
+/// transaction.
+pub(super) fn index(conn: &rusqlite::Connection) -> Result<usize, rusqlite::Error> {
+    let mut indexed = 0;
+    for pool in [ShieldedProtocol::Sapling, ShieldedProtocol::Orchard] {
+        let (table, _) = notes_table(pool);
+        let code = pool_code(pool);
+
+        // Remove entries for notes that no longer exist, so that their IDs can be reused.
+        conn.execute(
+            &format!(
+                "DELETE FROM ext_zallet_db_memo_index
+                WHERE pool = :pool
+                AND note_id NOT IN (SELECT id FROM {table} WHERE memo IS NOT NULL)"
+            ),
+            &[(":pool", &code)],
+        )?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT n.id, n.memo
+            FROM {table} n
+            WHERE n.memo IS NOT NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM ext_zallet_db_memo_index m
+                WHERE m.pool = :pool AND m.note_id = n.id
+            )"
+        ))?;
+        let mut insert = conn.prepare(
+            "INSERT INTO ext_zallet_db_memo_index (pool, note_id, memo)
+            VALUES (:pool, :note_id, :memo)",
+        )?;
+
+        let mut rows = stmt.query(&[(":pool", &code)])?;
+        while let Some(row) = rows.next()? {
+            let note_id: i64 = row.get(0)?;
+            let memo = decode(&row.get::<_, Vec<u8>>(1)?);
+            insert.execute(rusqlite::named_params! {
+                ":pool": code,
+                ":note_id": note_id,
+                ":memo": memo,
+            })?;
+            indexed += 1;
+        }
+    }
+    Ok(indexed)
+}
+
+/// Removes every entry from the index.
+pub(super) fn clear(conn: &rusqlite::Connection) -> Result<(), rusqlite::Error> {
+    conn.execute("DELETE FROM ext_zallet_db_memo_index", [])?;
+    Ok(())
+}
+
+/// A received note whose memo matched a search.
+#[cfg(zallet_build = "wallet")]
+#[derive(Clone, Debug)]
+pub(crate) struct MemoMatch {
+    pub(crate) account_uuid: Uuid,
+    pub(crate) pool: ShieldedProtocol,
+    pub(crate) txid: TxId,
+    pub(crate) output_index: u32,
+    /// The height at which the note was mined, or `None` if it is unmined.
+    pub(crate) mined_height: Option<BlockHeight>,
+    /// The index of the note's transaction within its block, if known.
+    pub(crate) tx_index: Option<u32>,
+    /// The wallet's row ID for the note's transaction.
+    pub(crate) tx_row: i64,
+    pub(crate) value: Zatoshis,
+    /// The address that received the note, if known.
+    pub(crate) address: Option<String>,
+    pub(crate) memo: String,
+}
+
+/// Returns the received notes whose text memos contain `query`.
+///
+/// If `account_uuid` is set, only notes received by that account are returned. If either
+/// height bound is set, only notes mined within the (inclusive) bounds are returned. At
+/// most `limit` notes are returned, ordered by the height of their transactions.
+#[cfg(zallet_build = "wallet")]
+pub(crate) fn search(
+    conn: &rusqlite::Connection,
+    query: &str,
+    account_uuid: Option<Uuid>,
+    from_height: Option<BlockHeight>,
+    to_height: Option<BlockHeight>,
+    limit: u32,
+) -> Result<Vec<MemoMatch>, rusqlite::Error> {
+    let mut matches = vec![];
+    for pool in [ShieldedProtocol::Sapling, ShieldedProtocol::Orchard] {
+        let (table, index_col) = notes_table(pool);
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT a.uuid, t.txid, n.{index_col}, t.mined_height, t.tx_index, t.id_tx,
+                n.value, addresses.address, m.memo
+            FROM ext_zallet_db_memo_index m
+            JOIN {table} n ON n.id = m.note_id
+            JOIN transactions t ON t.id_tx = n.tx
+            JOIN accounts a ON a.id = n.account_id
+            LEFT OUTER JOIN addresses ON addresses.id = n.address_id
+            WHERE m.pool = :pool
+            AND instr(m.memo, :query) > 0
+            AND (:account_uuid IS NULL OR a.uuid = :account_uuid)
+            AND (:from_height IS NULL OR t.mined_height >= :from_height)
+            AND (:to_height IS NULL OR t.mined_height <= :to_height)
+            ORDER BY t.mined_height IS NULL, t.mined_height, t.tx_index, t.id_tx, n.{index_col}
+            LIMIT :limit"
+        ))?;
+
+        let rows = stmt.query_and_then(
+            named_params! {
+                ":pool": pool_code(pool),
+                ":query": query,
+                ":account_uuid": account_uuid,
+                ":from_height": from_height.map(u32::from),
+                ":to_height": to_height.map(u32::from),
+                ":limit": limit,
+            },
+            |row| {
+                let value = row.get::<_, i64>(6)?;
+                Ok::<_, rusqlite::Error>(MemoMatch {
+                    account_uuid: row.get(0)?,
+                    pool,
+                    txid: TxId::from_bytes(row.get(1)?),
+                    output_index: row.get(2)?,
+                    mined_height: row.get::<_, Option<u32>>(3)?.map(BlockHeight::from),
+                    tx_index: row.get(4)?,
+                    tx_row: row.get(5)?,
+                    value: Zatoshis::from_nonnegative_i64(value)
+                        .map_err(|_| rusqlite::Error::IntegralValueOutOfRange(6, value))?,
+                    address: row.get(7)?,
+                    memo: row.get(8)?,
+                })
+            },
+        )?;
+        for row in rows {
+            matches.push(row?);
+        }
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::memo::{Memo, MemoBytes};
+
+    use super::decode;
+
+    #[test]
+    fn decodes_text_memos() {
+        let text = MemoBytes::from(Memo::from_bytes(b"order-1234").unwrap());
+        assert_eq!(decode(text.as_slice()).as_deref(), Some("order-1234"));
+
+        assert_eq!(decode(MemoBytes::empty().as_slice()), None);
+        assert_eq!(decode(&[0xff; 512]), None);
+        assert_eq!(decode(&[0; 513]), None);
+    }
+}
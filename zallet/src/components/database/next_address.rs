@@ -0,0 +1,85 @@
+//! Where an account's next address would be derived, for `z_previewaddressforaccount`.
+//!
+//! Addresses are exposed (recorded with an `exposed_at_height`) when they are handed out,
+//! so the next address of an account is found among the indices that have not been
+//! exposed:
+//!
+//! - An address with a transparent receiver is derived at a transparent address index, of
+//!   which the wallet generates addresses ahead of use up to the transparent gap limit. The
+//!   next such address is the lowest-index address that has been neither exposed nor used.
+//! - A shielded-only address can be derived at any diversifier index, so the next such
+//!   address is the first one above the highest index that the account has exposed.
+//!
+//! Nothing here modifies the database.
+
+use rusqlite::named_params;
+use zcash_client_sqlite::AccountUuid;
+use zip32::DiversifierIndex;
+
+/// The `key_scope` that `zcash_client_sqlite` records for external addresses.
+const KEY_SCOPE_EXTERNAL: i64 = 0;
+
+/// Returns the transparent address indices of the account's external addresses that have
+/// been neither exposed nor used, in increasing order.
+///
+/// This is empty if every address up to the transparent gap limit has been exposed.
+pub(super) fn unexposed_transparent_indices(
+    conn: &rusqlite::Connection,
+    account: AccountUuid,
+) -> Result<Vec<u32>, rusqlite::Error> {
+    conn.prepare(
+        "SELECT a.transparent_child_index
+        FROM addresses a
+        JOIN accounts ON accounts.id = a.account_id
+        WHERE accounts.uuid = :account_uuid
+        AND a.key_scope = :key_scope
+        AND a.exposed_at_height IS NULL
+        AND a.cached_transparent_receiver_address IS NOT NULL
+        AND a.transparent_child_index IS NOT NULL
+        AND NOT EXISTS (
+            SELECT 1 FROM transparent_received_outputs o
+            WHERE o.address = a.cached_transparent_receiver_address
+        )
+        ORDER BY a.transparent_child_index",
+    )?
+    .query_map(
+        named_params! {
+            ":account_uuid": account.expose_uuid(),
+            ":key_scope": KEY_SCOPE_EXTERNAL,
+        },
+        |row| row.get(0),
+    )?
+    .collect()
+}
+
+/// Returns the diversifier index above the highest index at which the account has
+/// exposed an external address, or zero if it has exposed none.
+///
+/// Returns `None` if the account has exposed an address at the highest diversifier index.
+pub(super) fn first_unexposed_diversifier_index(
+    conn: &rusqlite::Connection,
+    account: AccountUuid,
+) -> Result<Option<DiversifierIndex>, rusqlite::Error> {
+    let highest = conn.query_row(
+        "SELECT MAX(a.diversifier_index_be)
+        FROM addresses a
+        JOIN accounts ON accounts.id = a.account_id
+        WHERE accounts.uuid = :account_uuid
+        AND a.key_scope = :key_scope
+        AND a.exposed_at_height IS NOT NULL",
+        named_params! {
+            ":account_uuid": account.expose_uuid(),
+            ":key_scope": KEY_SCOPE_EXTERNAL,
+        },
+        |row| row.get::<_, Option<[u8; 11]>>(0),
+    )?;
+
+    Ok(match highest {
+        None => Some(DiversifierIndex::new()),
+        Some(mut index_be) => {
+            index_be.reverse();
+            let mut index = DiversifierIndex::from(index_be);
+            index.increment().ok().map(|()| index)
+        }
+    })
+}
@@ -0,0 +1,233 @@
+//! Strategies for selecting the notes spent by a transaction.
+
+use zcash_protocol::{ShieldedProtocol, consensus::BlockHeight, value::Zatoshis};
+
+use crate::config::InputSelection;
+
+/// A spendable note that may be selected to fund a transaction.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Candidate {
+    pub(super) pool: ShieldedProtocol,
+    pub(super) value: Zatoshis,
+    pub(super) mined_height: Option<BlockHeight>,
+}
+
+/// A strategy for choosing which spendable notes to spend.
+pub(super) trait NoteSelectionStrategy {
+    /// Returns the indices of the candidates that should be spent to fund `target`, or
+    /// `None` if the candidates cannot fund it.
+    fn select(&self, candidates: &[Candidate], target: Zatoshis) -> Option<Vec<usize>>;
+}
+
+impl InputSelection {
+    pub(super) fn strategy(self) -> &'static dyn NoteSelectionStrategy {
+        match self {
+            InputSelection::FewestInputs => &FewestInputs,
+            InputSelection::LargestFirst => &LargestFirst,
+            InputSelection::OldestFirst => &OldestFirst,
+            InputSelection::SinglePool => &SinglePool,
+        }
+    }
+}
+
+/// Takes candidates in the given order until their total value reaches `target`.
+fn take_until(
+    candidates: &[Candidate],
+    order: impl IntoIterator<Item = usize>,
+    target: Zatoshis,
+) -> Option<Vec<usize>> {
+    let mut selected = vec![];
+    let mut total = Zatoshis::ZERO;
+    for i in order {
+        if total >= target {
+            break;
+        }
+        total = (total + candidates[i].value)?;
+        selected.push(i);
+    }
+    (total >= target).then_some(selected)
+}
+
+/// Returns the candidate indices ordered by decreasing value.
+fn by_value_descending(candidates: &[Candidate]) -> Vec<usize> {
+    let mut order = (0..candidates.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| std::cmp::Reverse(candidates[i].value));
+    order
+}
+
+/// Spends as few notes as possible, which minimizes the fee.
+///
+/// If a single note can fund the transaction, the smallest such note is used;
+/// otherwise notes are spent largest-first.
+struct FewestInputs;
+
+impl NoteSelectionStrategy for FewestInputs {
+    fn select(&self, candidates: &[Candidate], target: Zatoshis) -> Option<Vec<usize>> {
+        if target == Zatoshis::ZERO {
+            return Some(vec![]);
+        }
+
+        (0..candidates.len())
+            .filter(|&i| candidates[i].value >= target)
+            .min_by_key(|&i| candidates[i].value)
+            .map(|i| vec![i])
+            .or_else(|| take_until(candidates, by_value_descending(candidates), target))
+    }
+}
+
+/// Spends the largest notes first.
+struct LargestFirst;
+
+impl NoteSelectionStrategy for LargestFirst {
+    fn select(&self, candidates: &[Candidate], target: Zatoshis) -> Option<Vec<usize>> {
+        take_until(candidates, by_value_descending(candidates), target)
+    }
+}
+
+/// Spends the oldest notes first, consolidating the wallet over time.
+struct OldestFirst;
+
+impl NoteSelectionStrategy for OldestFirst {
+    fn select(&self, candidates: &[Candidate], target: Zatoshis) -> Option<Vec<usize>> {
+        let mut order = (0..candidates.len()).collect::<Vec<_>>();
+        // Unmined notes sort last, and are only used if nothing else suffices.
+        order.sort_by_key(|&i| {
+            (
+                candidates[i].mined_height.is_none(),
+                candidates[i].mined_height,
+                std::cmp::Reverse(candidates[i].value),
+            )
+        });
+        take_until(candidates, order, target)
+    }
+}
+
+/// Funds the transaction from a single shielded pool where possible, to avoid
+/// revealing the amount that crosses between pools.
+///
+/// Orchard is preferred over Sapling. Within a pool, notes are selected as for
+/// [`FewestInputs`].
+struct SinglePool;
+
+impl NoteSelectionStrategy for SinglePool {
+    fn select(&self, candidates: &[Candidate], target: Zatoshis) -> Option<Vec<usize>> {
+        [ShieldedProtocol::Orchard, ShieldedProtocol::Sapling]
+            .into_iter()
+            .find_map(|pool| {
+                let indices = (0..candidates.len())
+                    .filter(|&i| candidates[i].pool == pool)
+                    .collect::<Vec<_>>();
+                let pool_candidates = indices.iter().map(|&i| candidates[i]).collect::<Vec<_>>();
+                FewestInputs
+                    .select(&pool_candidates, target)
+                    .map(|selected| selected.into_iter().map(|i| indices[i]).collect())
+            })
+            .or_else(|| FewestInputs.select(candidates, target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+    use zcash_protocol::{ShieldedProtocol, consensus::BlockHeight, value::Zatoshis};
+
+    use super::Candidate;
+    use crate::config::InputSelection;
+
+    const STRATEGIES: [InputSelection; 4] = [
+        InputSelection::FewestInputs,
+        InputSelection::LargestFirst,
+        InputSelection::OldestFirst,
+        InputSelection::SinglePool,
+    ];
+
+    fn arbitrary_candidates(rng: &mut StdRng) -> Vec<Candidate> {
+        (0..rng.gen_range(0..20))
+            .map(|_| Candidate {
+                pool: if rng.gen_bool(0.5) {
+                    ShieldedProtocol::Sapling
+                } else {
+                    ShieldedProtocol::Orchard
+                },
+                value: Zatoshis::const_from_u64(rng.gen_range(1..1_000_000)),
+                mined_height: rng
+                    .gen_bool(0.9)
+                    .then(|| BlockHeight::from_u32(rng.gen_range(1..1000))),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn strategies_fund_target_or_fail() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..1000 {
+            let candidates = arbitrary_candidates(&mut rng);
+            let available = candidates.iter().map(|c| c.value.into_u64()).sum::<u64>();
+            let target = Zatoshis::const_from_u64(rng.gen_range(0..=available + 100_000));
+
+            for strategy in STRATEGIES {
+                match strategy.strategy().select(&candidates, target) {
+                    Some(selected) => {
+                        // Only the given candidates are selected, each at most once.
+                        let mut unique = selected.clone();
+                        unique.sort();
+                        unique.dedup();
+                        assert_eq!(unique.len(), selected.len(), "{strategy:?}");
+                        assert!(selected.iter().all(|&i| i < candidates.len()));
+
+                        // The selection funds the target.
+                        let total = selected
+                            .iter()
+                            .map(|&i| candidates[i].value.into_u64())
+                            .sum::<u64>();
+                        assert!(total >= target.into_u64(), "{strategy:?}");
+                    }
+                    // A strategy may only fail if the target cannot be funded at all.
+                    None => assert!(available < target.into_u64(), "{strategy:?}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_pool_avoids_crossing_pools() {
+        let candidates = [
+            Candidate {
+                pool: ShieldedProtocol::Sapling,
+                value: Zatoshis::const_from_u64(100_000),
+                mined_height: Some(BlockHeight::from_u32(1)),
+            },
+            Candidate {
+                pool: ShieldedProtocol::Orchard,
+                value: Zatoshis::const_from_u64(30_000),
+                mined_height: Some(BlockHeight::from_u32(2)),
+            },
+            Candidate {
+                pool: ShieldedProtocol::Orchard,
+                value: Zatoshis::const_from_u64(30_000),
+                mined_height: Some(BlockHeight::from_u32(3)),
+            },
+        ];
+        let target = Zatoshis::const_from_u64(50_000);
+
+        assert_eq!(
+            InputSelection::SinglePool
+                .strategy()
+                .select(&candidates, target),
+            Some(vec![1, 2]),
+        );
+        assert_eq!(
+            InputSelection::FewestInputs
+                .strategy()
+                .select(&candidates, target),
+            Some(vec![0]),
+        );
+        assert_eq!(
+            InputSelection::OldestFirst
+                .strategy()
+                .select(&candidates, target),
+            Some(vec![0]),
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Outputs received on a particular address.
+//!
+//! An address matches a wallet address if they share a receiver. Each shielded note
+//! records the wallet address that it was received on, so a note is counted as received
+//! on the given address if its wallet address shares the receiver for the note's pool.
+//! Transparent outputs record the transparent address that they were received on.
+
+use std::rc::Rc;
+
+use rusqlite::{named_params, types::Value};
+use zcash_keys::address::{Address, Receiver};
+use zcash_protocol::{
+    PoolType, TxId,
+    consensus::{BlockHeight, Parameters},
+};
+
+/// An output that the wallet received on a particular address.
+#[derive(Clone, Debug)]
+pub(crate) struct ReceivedOutput {
+    pub(crate) pool: PoolType,
+    pub(crate) txid: TxId,
+    /// The index of the output within the transaction's outputs in its pool.
+    pub(crate) output_index: u32,
+    pub(crate) value: u64,
+    pub(crate) memo: Option<Vec<u8>>,
+    pub(crate) is_change: bool,
+    pub(crate) mined_height: Option<BlockHeight>,
+}
+
+/// Returns the outputs received on any of `receivers` that have at least `minconf`
+/// confirmations at `chain_height`, ordered by when they were mined.
+///
+/// Returns `None` if none of `receivers` belong to the wallet.
+pub(super) fn received_on<P: Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    receivers: &[Receiver],
+    minconf: u32,
+    chain_height: BlockHeight,
+) -> Result<Option<Vec<ReceivedOutput>>, rusqlite::Error> {
+    let mut transparent = vec![];
+    let mut sapling = vec![];
+    let mut orchard = vec![];
+
+    let mut stmt_addresses = conn.prepare("SELECT id, address FROM addresses")?;
+    let mut rows = stmt_addresses.query([])?;
+    while let Some(row) = rows.next()? {
+        let address_id = row.get::<_, i64>(0)?;
+        let Some(address) = row
+            .get::<_, Option<String>>(1)?
+            .and_then(|address| Address::decode(params, &address))
+        else {
+            continue;
+        };
+
+        for wallet_receiver in address.as_understood_unified_receivers() {
+            for receiver in receivers {
+                match (receiver, &wallet_receiver) {
+                    (Receiver::Transparent(a), Receiver::Transparent(b)) if a == b => {
+                        transparent.push(Value::Text(Address::Transparent(*a).encode(params)))
+                    }
+                    (Receiver::Sapling(a), Receiver::Sapling(b)) if a == b => {
+                        sapling.push(Value::Integer(address_id))
+                    }
+                    (Receiver::Orchard(a), Receiver::Orchard(b)) if a == b => {
+                        orchard.push(Value::Integer(address_id))
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    if transparent.is_empty() && sapling.is_empty() && orchard.is_empty() {
+        return Ok(None);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT * FROM (
+            SELECT 0 AS pool, t.txid, o.output_index, o.value_zat AS value, NULL AS memo,
+                0 AS is_change, t.mined_height, t.expiry_height, t.tx_index, t.id_tx
+            FROM transparent_received_outputs o
+            JOIN transactions t ON t.id_tx = o.transaction_id
+            WHERE o.address IN rarray(:transparent)
+            UNION ALL
+            SELECT 2, t.txid, o.output_index, o.value, o.memo, o.is_change,
+                t.mined_height, t.expiry_height, t.tx_index, t.id_tx
+            FROM sapling_received_notes o
+            JOIN transactions t ON t.id_tx = o.tx
+            WHERE o.address_id IN rarray(:sapling)
+            UNION ALL
+            SELECT 3, t.txid, o.action_index, o.value, o.memo, o.is_change,
+                t.mined_height, t.expiry_height, t.tx_index, t.id_tx
+            FROM orchard_received_notes o
+            JOIN transactions t ON t.id_tx = o.tx
+            WHERE o.address_id IN rarray(:orchard)
+        )
+        WHERE mined_height <= :max_mined_height
+        OR (
+            -- Unexpired unmined transactions have no confirmations.
+            :minconf = 0
+            AND mined_height IS NULL
+            AND (expiry_height = 0 OR expiry_height > :chain_height)
+        )
+        ORDER BY mined_height ASC NULLS LAST, tx_index, id_tx, pool, output_index",
+    )?;
+
+    let chain_height = u32::from(chain_height);
+    stmt.query_map(
+        named_params! {
+            ":transparent": Rc::new(transparent),
+            ":sapling": Rc::new(sapling),
+            ":orchard": Rc::new(orchard),
+            ":minconf": minconf,
+            ":chain_height": chain_height,
+            ":max_mined_height": (chain_height + 1).saturating_sub(minconf.max(1)),
+        },
+        |row| {
+            Ok(ReceivedOutput {
+                pool: match row.get::<_, i64>("pool")? {
+                    0 => PoolType::Transparent,
+                    2 => PoolType::SAPLING,
+                    _ => PoolType::ORCHARD,
+                },
+                txid: TxId::from_bytes(row.get("txid")?),
+                output_index: row.get("output_index")?,
+                value: row.get("value")?,
+                memo: row.get("memo")?,
+                is_change: row.get("is_change")?,
+                mined_height: row
+                    .get::<_, Option<u32>>("mined_height")?
+                    .map(BlockHeight::from),
+            })
+        },
+    )?
+    .collect::<Result<_, _>>()
+    .map(Some)
+}
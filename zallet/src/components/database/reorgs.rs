@@ -0,0 +1,162 @@
+//! The blocks that chain reorgs have removed from the wallet's view of the chain.
+//!
+//! When a reorg rewinds the wallet, `zcash_client_sqlite` forgets the blocks above the
+//! fork point, and the transactions that were mined in them become unmined (until they
+//! are mined again in the new chain). Callers of `listsinceblock` may have already seen
+//! those transactions as mined, and need to be told that they were removed. So before the
+//! wallet is rewound, the blocks being removed, and the wallet transactions mined in
+//! them, are recorded in `ext_zallet_db_orphaned_blocks` and
+//! `ext_zallet_db_orphaned_transactions`.
+//!
+//! Each reorg is recorded with its own `reorg_id`, along with the fork point that the
+//! wallet was rewound to. The fork point may itself be removed by a later reorg, in which
+//! case it is found among that reorg's orphaned blocks.
+
+use rusqlite::{OptionalExtension, named_params};
+use zcash_primitives::block::BlockHash;
+use zcash_protocol::{TxId, consensus::BlockHeight};
+
+/// Records the wallet's blocks above `fork_height`, and the wallet transactions mined in
+/// them, as orphaned.
+///
+/// This must be called before the wallet is rewound to `fork_height`.
+pub(super) fn record_orphaned_blocks(
+    conn: &rusqlite::Connection,
+    fork_height: BlockHeight,
+) -> Result<(), rusqlite::Error> {
+    let fork_hash = conn
+        .query_row(
+            "SELECT hash FROM blocks WHERE height = :height",
+            named_params! {":height": u32::from(fork_height)},
+            |row| row.get::<_, Vec<u8>>(0),
+        )
+        .optional()?;
+    let reorg_id = conn.query_row(
+        "SELECT IFNULL(MAX(reorg_id), 0) + 1 FROM ext_zallet_db_orphaned_blocks",
+        [],
+        |row| row.get::<_, i64>(0),
+    )?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO ext_zallet_db_orphaned_blocks
+            (hash, height, reorg_id, fork_height, fork_hash)
+        SELECT hash, height, :reorg_id, :fork_height, :fork_hash
+        FROM blocks
+        WHERE height > :fork_height",
+        named_params! {
+            ":reorg_id": reorg_id,
+            ":fork_height": u32::from(fork_height),
+            ":fork_hash": fork_hash,
+        },
+    )?;
+    conn.execute(
+        "INSERT OR IGNORE INTO ext_zallet_db_orphaned_transactions (block_hash, txid)
+        SELECT b.hash, t.txid
+        FROM transactions t
+        JOIN blocks b ON b.height = t.mined_height
+        WHERE t.mined_height > :fork_height",
+        named_params! {":fork_height": u32::from(fork_height)},
+    )?;
+
+    Ok(())
+}
+
+/// A block that a caller of `listsinceblock` has already seen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ReferenceBlock {
+    /// The height in the wallet's current chain from which the caller has not seen any
+    /// transactions.
+    ///
+    /// This is the height of the block if it is in the current chain, or otherwise the
+    /// height of the fork point of the reorg that removed it.
+    pub(crate) height: BlockHeight,
+    /// The wallet transactions that were mined in blocks that have since been removed by
+    /// reorgs, between the current chain and the block.
+    pub(crate) removed: Vec<TxId>,
+}
+
+/// Finds the block with the given hash, in either the wallet's current chain or the
+/// blocks that reorgs have removed from it.
+///
+/// Returns `None` if the wallet has never seen the block.
+pub(super) fn find_reference_block(
+    conn: &rusqlite::Connection,
+    hash: &BlockHash,
+) -> Result<Option<ReferenceBlock>, rusqlite::Error> {
+    let current_height = |hash: &[u8]| {
+        conn.query_row(
+            "SELECT height FROM blocks WHERE hash = :hash",
+            named_params! {":hash": hash},
+            |row| row.get::<_, u32>(0),
+        )
+        .optional()
+    };
+
+    if let Some(height) = current_height(&hash.0)? {
+        return Ok(Some(ReferenceBlock {
+            height: BlockHeight::from_u32(height),
+            removed: vec![],
+        }));
+    }
+
+    let mut orphaned_block = conn.prepare(
+        "SELECT height, reorg_id, fork_height, fork_hash
+        FROM ext_zallet_db_orphaned_blocks
+        WHERE hash = :hash",
+    )?;
+    let mut removed_txs = conn.prepare(
+        "SELECT DISTINCT o.txid
+        FROM ext_zallet_db_orphaned_transactions o
+        JOIN ext_zallet_db_orphaned_blocks b ON b.hash = o.block_hash
+        WHERE b.reorg_id = :reorg_id
+        AND b.height <= :height",
+    )?;
+
+    let mut removed = vec![];
+    let mut hash = hash.0.to_vec();
+    let mut reached_height = None;
+    loop {
+        let Some((height, reorg_id, fork_height, fork_hash)) = orphaned_block
+            .query_row(named_params! {":hash": hash}, |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, u32>(2)?,
+                    row.get::<_, Option<Vec<u8>>>(3)?,
+                ))
+            })
+            .optional()?
+        else {
+            // Either the block is unknown, or we have reached a fork point that has since
+            // been removed without a reorg (such as by a rescan); its height is the best
+            // we know.
+            return Ok(reached_height.map(|height| ReferenceBlock {
+                height: BlockHeight::from_u32(height),
+                removed,
+            }));
+        };
+
+        for txid in removed_txs.query_map(
+            named_params! {":reorg_id": reorg_id, ":height": height},
+            |row| row.get::<_, [u8; 32]>(0).map(TxId::from_bytes),
+        )? {
+            let txid = txid?;
+            if !removed.contains(&txid) {
+                removed.push(txid);
+            }
+        }
+        reached_height = Some(fork_height);
+
+        // Continue from the fork point, unless it is still in the current chain. If the
+        // wallet had not scanned the fork point, its height is the best we know.
+        match fork_hash {
+            Some(fork_hash) if current_height(&fork_hash)?.is_none() => hash = fork_hash,
+            _ => {
+                return Ok(Some(ReferenceBlock {
+                    height: BlockHeight::from_u32(fork_height),
+                    removed,
+                }));
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Reservations of the inputs that the wallet intends to spend.
+//!
+//! An input is reserved from when an operation selects it until the operation finishes,
+//! so that concurrent operations never build transactions that spend the same inputs.
+//! Reserved inputs are skipped by input selection. A manual lock on an input is a
+//! reservation that is held until the input is unlocked.
+
+use std::collections::HashMap;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+
+use tokio::sync::{Notify, futures::Notified};
+use transparent::bundle::OutPoint;
+use zcash_protocol::{ShieldedProtocol, TxId};
+
+/// An input that can be reserved.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum ReservedInput {
+    Note {
+        txid: TxId,
+        protocol: ShieldedProtocol,
+        index: u32,
+    },
+    Transparent(OutPoint),
+}
+
+/// The set of reserved inputs, shared by every connection to the wallet database.
+#[derive(Clone, Default)]
+pub(crate) struct InputReservations {
+    inner: Arc<ReservationsInner>,
+}
+
+#[derive(Default)]
+struct ReservationsInner {
+    /// The reserved inputs, mapped to the ID of the reservation that holds them.
+    reserved: Mutex<HashMap<ReservedInput, u64>>,
+    next_id: AtomicU64,
+    /// Notified whenever a reservation is released.
+    released: Notify,
+}
+
+impl InputReservations {
+    /// Returns `true` if the given input is reserved.
+    pub(crate) fn is_reserved(&self, input: &ReservedInput) -> bool {
+        self.inner
+            .reserved
+            .lock()
+            .expect("not poisoned")
+            .contains_key(input)
+    }
+
+    /// Reserves the given inputs, returning `None` if any of them are already reserved.
+    ///
+    /// The inputs are released when the returned [`Reservation`] is dropped.
+    pub(crate) fn try_reserve(
+        &self,
+        inputs: impl IntoIterator<Item = ReservedInput>,
+    ) -> Option<Reservation> {
+        let inputs = inputs.into_iter().collect::<Vec<_>>();
+        let mut reserved = self.inner.reserved.lock().expect("not poisoned");
+        if inputs.iter().any(|input| reserved.contains_key(input)) {
+            return None;
+        }
+
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        reserved.extend(inputs.into_iter().map(|input| (input, id)));
+        Some(Reservation {
+            id,
+            reservations: self.clone(),
+        })
+    }
+
+    /// Returns a future that completes the next time a reservation is released.
+    ///
+    /// The future observes every release after this method is called, even if it is not
+    /// polled until later.
+    pub(crate) fn released(&self) -> Notified<'_> {
+        self.inner.released.notified()
+    }
+}
+
+/// A set of inputs reserved by [`InputReservations::try_reserve`].
+pub(crate) struct Reservation {
+    id: u64,
+    reservations: InputReservations,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        let inner = &self.reservations.inner;
+        inner
+            .reserved
+            .lock()
+            .expect("not poisoned")
+            .retain(|_, id| *id != self.id);
+        inner.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use transparent::bundle::OutPoint;
+    use zcash_protocol::{ShieldedProtocol, TxId};
+
+    use super::{InputReservations, ReservedInput};
+
+    fn note(index: u32) -> ReservedInput {
+        ReservedInput::Note {
+            txid: TxId::from_bytes([1; 32]),
+            protocol: ShieldedProtocol::Orchard,
+            index,
+        }
+    }
+
+    #[test]
+    fn conflicting_reservations() {
+        let reservations = InputReservations::default();
+        let outpoint = ReservedInput::Transparent(OutPoint::new([2; 32], 0));
+
+        let first = reservations
+            .try_reserve([note(0), outpoint.clone()])
+            .unwrap();
+        assert!(reservations.is_reserved(&note(0)));
+        assert!(!reservations.is_reserved(&note(1)));
+
+        // Overlapping inputs cannot be reserved, and a failed reservation holds nothing.
+        assert!(
+            reservations
+                .try_reserve([note(1), outpoint.clone()])
+                .is_none()
+        );
+        assert!(!reservations.is_reserved(&note(1)));
+
+        // Disjoint inputs can be reserved concurrently.
+        let second = reservations.try_reserve([note(1)]).unwrap();
+
+        drop(first);
+        assert!(!reservations.is_reserved(&outpoint));
+        assert!(reservations.is_reserved(&note(1)));
+        assert!(reservations.try_reserve([outpoint]).is_some());
+
+        drop(second);
+        assert!(!reservations.is_reserved(&note(1)));
+    }
+}
@@ -0,0 +1,99 @@
+//! Rescanning of blocks that the wallet has already scanned.
+//!
+//! `zcash_client_sqlite` records the ranges of blocks between the wallet's birthday and
+//! its view of the chain tip in its `scan_queue` table, along with the priority with which
+//! each range should be scanned. A rescan is scheduled by giving ranges that have already
+//! been scanned the `Historic` priority again, which is also how `zcash_client_sqlite`
+//! schedules a rescan when an account is imported with an earlier birthday.
+
+use std::ops::Range;
+
+use rusqlite::named_params;
+use zcash_protocol::consensus::BlockHeight;
+
+/// The priority that `zcash_client_sqlite` records for ranges that have been scanned.
+const PRIORITY_SCANNED: i64 = 10;
+
+/// The priority that `zcash_client_sqlite` records for ranges of historic blocks that
+/// have not been scanned.
+const PRIORITY_HISTORIC: i64 = 20;
+
+/// Schedules the blocks in `range` that have already been scanned to be scanned again.
+///
+/// Blocks that are below the wallet's birthday, or that are already waiting to be
+/// scanned, are left as they are.
+pub(super) fn reschedule(
+    conn: &rusqlite::Transaction<'_>,
+    range: Range<BlockHeight>,
+) -> Result<(), rusqlite::Error> {
+    let (start, end) = (u32::from(range.start), u32::from(range.end));
+
+    let scanned = conn
+        .prepare(
+            "SELECT block_range_start, block_range_end
+            FROM scan_queue
+            WHERE priority = :scanned
+            AND block_range_start < :end
+            AND block_range_end > :start",
+        )?
+        .query_map(
+            named_params! { ":scanned": PRIORITY_SCANNED, ":start": start, ":end": end },
+            |row| Ok((row.get::<_, u32>(0)?, row.get::<_, u32>(1)?)),
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    conn.execute(
+        "DELETE FROM scan_queue
+        WHERE priority = :scanned
+        AND block_range_start < :end
+        AND block_range_end > :start",
+        named_params! { ":scanned": PRIORITY_SCANNED, ":start": start, ":end": end },
+    )?;
+
+    // The parts of each scanned range that are outside of `range` stay scanned.
+    let mut insert = conn.prepare(
+        "INSERT INTO scan_queue (block_range_start, block_range_end, priority)
+        VALUES (:start, :end, :priority)",
+    )?;
+    for (range_start, range_end) in scanned {
+        for (part_start, part_end, priority) in [
+            (range_start, start, PRIORITY_SCANNED),
+            (
+                range_start.max(start),
+                range_end.min(end),
+                PRIORITY_HISTORIC,
+            ),
+            (end, range_end, PRIORITY_SCANNED),
+        ] {
+            if part_start < part_end {
+                insert.execute(named_params! {
+                    ":start": part_start,
+                    ":end": part_end,
+                    ":priority": priority,
+                })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the number of blocks in `range` that are waiting to be scanned.
+pub(super) fn unscanned_blocks_in(
+    conn: &rusqlite::Connection,
+    range: Range<BlockHeight>,
+) -> Result<u32, rusqlite::Error> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(MIN(block_range_end, :end) - MAX(block_range_start, :start)), 0)
+        FROM scan_queue
+        WHERE priority >= :historic
+        AND block_range_start < :end
+        AND block_range_end > :start",
+        named_params! {
+            ":historic": PRIORITY_HISTORIC,
+            ":start": u32::from(range.start),
+            ":end": u32::from(range.end),
+        },
+        |row| row.get(0),
+    )
+}
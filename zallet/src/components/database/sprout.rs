@@ -0,0 +1,114 @@
+//! The Sprout notes of wallets migrated from `zcashd`.
+//!
+//! Zallet cannot spend Sprout notes, and does not scan the chain for them. Instead,
+//! `zallet migrate-zcashd-wallet` records the Sprout notes received by the `zcashd`
+//! wallet's Sprout keys in `ext_zallet_db_sprout_notes`, along with their nullifiers. A
+//! note is marked as spent when the wallet sees a transaction that reveals its nullifier:
+//! either a transaction in the `zcashd` wallet, or one that the wallet fetches later
+//! because it involves one of the wallet's addresses (such as a transaction migrating the
+//! funds to a Sapling address of the wallet).
+
+use zcash_protocol::value::Zatoshis;
+
+#[cfg(feature = "zcashd-import")]
+use {rusqlite::named_params, zcash_protocol::TxId};
+
+/// A Sprout note received by a key in a migrated `zcashd` wallet.
+#[cfg(feature = "zcashd-import")]
+#[derive(Clone, Debug)]
+pub(crate) struct SproutNote {
+    pub(crate) txid: TxId,
+    pub(crate) js_index: u32,
+    pub(crate) note_index: u8,
+    pub(crate) value: Zatoshis,
+    pub(crate) nullifier: [u8; 32],
+}
+
+/// The unspent Sprout notes of a wallet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SproutBalance {
+    /// The total value of the unspent notes.
+    pub(crate) value: Zatoshis,
+    /// The number of unspent notes.
+    pub(crate) notes: u32,
+}
+
+/// Records Sprout notes received by the wallet.
+///
+/// This should be called within a transaction.
+#[cfg(feature = "zcashd-import")]
+pub(super) fn record_notes(
+    conn: &rusqlite::Connection,
+    notes: &[SproutNote],
+) -> Result<(), rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "INSERT OR IGNORE INTO ext_zallet_db_sprout_notes
+        (txid, js_index, note_index, value, nullifier)
+        VALUES (:txid, :js_index, :note_index, :value, :nullifier)",
+    )?;
+    for note in notes {
+        stmt.execute(named_params! {
+            ":txid": note.txid.as_ref(),
+            ":js_index": note.js_index,
+            ":note_index": note.note_index,
+            ":value": note.value.into_u64(),
+            ":nullifier": note.nullifier,
+        })?;
+    }
+    Ok(())
+}
+
+/// Marks the Sprout notes with the given nullifiers as spent by the transaction `txid`.
+///
+/// Returns the number of notes that were newly marked as spent.
+#[cfg(feature = "zcashd-import")]
+pub(super) fn record_spends(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+    nullifiers: &[[u8; 32]],
+) -> Result<usize, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "UPDATE ext_zallet_db_sprout_notes
+        SET spent_in_txid = :txid
+        WHERE nullifier = :nullifier
+        AND spent_in_txid IS NULL",
+    )?;
+    nullifiers.iter().try_fold(0, |marked, nullifier| {
+        Ok(marked
+            + stmt.execute(named_params! {
+                ":txid": txid.as_ref(),
+                ":nullifier": nullifier,
+            })?)
+    })
+}
+
+/// Returns the unspent Sprout notes of the wallet, or `None` if the wallet has never held
+/// any Sprout notes.
+pub(super) fn balance(
+    conn: &rusqlite::Connection,
+) -> Result<Option<SproutBalance>, rusqlite::Error> {
+    let (total_notes, unspent_notes, value) = conn.query_row(
+        "SELECT COUNT(*),
+            COUNT(*) FILTER (WHERE spent_in_txid IS NULL),
+            COALESCE(SUM(value) FILTER (WHERE spent_in_txid IS NULL), 0)
+        FROM ext_zallet_db_sprout_notes",
+        [],
+        |row| {
+            Ok((
+                row.get::<_, u32>(0)?,
+                row.get::<_, u32>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        },
+    )?;
+
+    if total_notes == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(SproutBalance {
+            value: Zatoshis::from_nonnegative_i64(value)
+                .map_err(|_| rusqlite::Error::IntegralValueOutOfRange(2, value))?,
+            notes: unspent_notes,
+        }))
+    }
+}
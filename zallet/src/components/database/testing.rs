@@ -0,0 +1,99 @@
+//! Helpers for tests that need a wallet holding transactions.
+
+use transparent::{address::TransparentAddress, bundle::OutPoint};
+use zcash_primitives::transaction::Transaction;
+use zcash_protocol::consensus::BlockHeight;
+
+use super::DbHandle;
+
+/// Creates an account in the given wallet, born at height 999,999, and returns its
+/// default external and internal transparent addresses.
+pub(crate) fn create_account(wallet: &mut DbHandle) -> (TransparentAddress, TransparentAddress) {
+    use secrecy::SecretVec;
+    use zcash_client_backend::data_api::{AccountBirthday, WalletWrite, chain::ChainState};
+    use zcash_primitives::block::BlockHash;
+
+    let birthday = AccountBirthday::from_parts(
+        ChainState::empty(BlockHeight::from_u32(999_999), BlockHash([0; 32])),
+        None,
+    );
+    let (_, usk) = wallet
+        .create_account("Test", &SecretVec::new(vec![7; 32]), &birthday, None)
+        .unwrap();
+    let account_pubkey = usk.transparent().to_account_pubkey();
+    let (external, _) = account_pubkey
+        .derive_external_ivk()
+        .unwrap()
+        .default_address();
+    let (internal, _) = account_pubkey
+        .derive_internal_ivk()
+        .unwrap()
+        .default_address();
+    (external, internal)
+}
+
+/// Stores a 100,000 zatoshi UTXO paying `address`, mined at `mined_height`.
+pub(crate) fn receive_utxo(
+    wallet: &mut DbHandle,
+    outpoint: &OutPoint,
+    address: &TransparentAddress,
+    mined_height: u32,
+) {
+    use transparent::{address::Script, bundle::TxOut};
+    use zcash_client_backend::{data_api::WalletWrite, wallet::WalletTransparentOutput};
+    use zcash_protocol::value::Zatoshis;
+    use zcash_script::script;
+
+    let output = WalletTransparentOutput::from_parts(
+        outpoint.clone(),
+        TxOut::new(
+            Zatoshis::const_from_u64(100_000),
+            Script(script::Code(p2pkh(address))),
+        ),
+        Some(BlockHeight::from_u32(mined_height)),
+    )
+    .unwrap();
+    wallet.put_received_transparent_utxo(&output).unwrap();
+}
+
+/// Returns the script that pays the given P2PKH address.
+fn p2pkh(address: &TransparentAddress) -> Vec<u8> {
+    match address {
+        TransparentAddress::PublicKeyHash(hash) => {
+            [&[0x76, 0xa9, 0x14][..], &hash[..], &[0x88, 0xac][..]].concat()
+        }
+        TransparentAddress::ScriptHash(_) => unreachable!("addresses are P2PKH"),
+    }
+}
+
+/// Returns a v4 transaction that spends `spent`, and pays `value` zatoshis to
+/// `recipient`.
+pub(crate) fn spend_to(
+    spent: &OutPoint,
+    recipient: &TransparentAddress,
+    value: u64,
+    expiry_height: u32,
+) -> Transaction {
+    use zcash_protocol::consensus::BranchId;
+
+    let script = p2pkh(recipient);
+    let mut raw_tx = vec![];
+    raw_tx.extend_from_slice(&0x8000_0004u32.to_le_bytes());
+    raw_tx.extend_from_slice(&0x892f_2085u32.to_le_bytes());
+    raw_tx.push(1);
+    raw_tx.extend_from_slice(spent.hash());
+    raw_tx.extend_from_slice(&spent.n().to_le_bytes());
+    raw_tx.push(0);
+    raw_tx.extend_from_slice(&u32::MAX.to_le_bytes());
+    raw_tx.push(1);
+    raw_tx.extend_from_slice(&value.to_le_bytes());
+    raw_tx.push(script.len() as u8);
+    raw_tx.extend_from_slice(&script);
+    // nLockTime and nExpiryHeight.
+    raw_tx.extend_from_slice(&0u32.to_le_bytes());
+    raw_tx.extend_from_slice(&expiry_height.to_le_bytes());
+    // No Sapling value balance, spends, or outputs, and no JoinSplits.
+    raw_tx.extend_from_slice(&0i64.to_le_bytes());
+    raw_tx.extend_from_slice(&[0, 0, 0]);
+    Transaction::read(&raw_tx[..], BranchId::Nu5).unwrap()
+}
@@ -1,13 +1,38 @@
 use rand::rngs::OsRng;
 use rusqlite::Connection;
+use transparent::bundle::OutPoint;
 use zcash_client_sqlite::{WalletDb, util::SystemClock, wallet::init::WalletMigrator};
-use zcash_protocol::consensus::{self, Parameters};
+use zcash_protocol::{
+    TxId,
+    consensus::{self, BlockHeight, Parameters},
+};
 
-use crate::{components::database, network::Network};
+use super::testing::{create_account, receive_utxo, spend_to};
+use crate::{components::database, config::ZalletConfig, network::Network};
 
 #[cfg(zallet_build = "wallet")]
 use crate::components::keystore;
 
+fn migrated_db() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    rusqlite::vtab::array::load_module(&conn).unwrap();
+    let mut db_data = WalletDb::from_connection(
+        &mut conn,
+        Network::Consensus(consensus::Network::MainNetwork),
+        SystemClock,
+        OsRng,
+    );
+
+    WalletMigrator::new()
+        .with_external_migrations(database::all_external_migrations(
+            db_data.params().network_type(),
+        ))
+        .init_or_migrate(&mut db_data)
+        .unwrap();
+
+    conn
+}
+
 #[test]
 fn verify_schema() {
     let mut conn = Connection::open_in_memory().unwrap();
@@ -49,6 +74,18 @@ fn verify_schema() {
         WHERE type = 'table' AND tbl_name LIKE 'ext_zallet_%'
         ORDER BY tbl_name",
         &[
+            database::ext::TABLE_ADDRESS_BOOK,
+            database::ext::TABLE_ASYNC_OPERATION_TXIDS,
+            database::ext::TABLE_ASYNC_OPERATIONS,
+            database::ext::TABLE_BALANCE_AGGREGATES,
+            database::ext::TABLE_BALANCE_ENTRIES,
+            database::ext::TABLE_MEMO_INDEX,
+            database::ext::TABLE_ORPHANED_BLOCKS,
+            database::ext::TABLE_ORPHANED_TRANSACTIONS,
+            database::ext::TABLE_PROPOSAL_KEY,
+            database::ext::TABLE_SPROUT_NOTES,
+            database::ext::TABLE_TX_VALUES,
+            database::ext::TABLE_UNBROADCAST_TRANSACTIONS,
             database::ext::TABLE_VERSION_METADATA,
             database::ext::TABLE_WALLET_METADATA,
             #[cfg(zallet_build = "wallet")]
@@ -56,11 +93,17 @@ fn verify_schema() {
             #[cfg(zallet_build = "wallet")]
             keystore::db::TABLE_LEGACY_SEEDS,
             #[cfg(zallet_build = "wallet")]
+            keystore::db::TABLE_MNEMONIC_BACKUPS,
+            #[cfg(zallet_build = "wallet")]
+            keystore::db::TABLE_MNEMONIC_BIRTHDAYS,
+            #[cfg(zallet_build = "wallet")]
             keystore::db::TABLE_MNEMONICS,
             #[cfg(zallet_build = "wallet")]
             keystore::db::TABLE_STANDALONE_SAPLING_KEYS,
             #[cfg(zallet_build = "wallet")]
             keystore::db::TABLE_STANDALONE_TRANSPARENT_KEYS,
+            #[cfg(zallet_build = "wallet")]
+            keystore::db::TABLE_STANDALONE_UNIFIED_KEYS,
         ],
     );
 
@@ -80,3 +123,617 @@ fn verify_schema() {
         &[],
     );
 }
+
+/// Creates a wallet holding two mined UTXOs, one of which has been spent by an unmined
+/// transaction that sends its change back to the wallet, and returns the outpoints that
+/// [`InputSource::get_spendable_transparent_outputs`] selects for the wallet's external
+/// address and its change address, along with the unspent mined UTXO and the change.
+///
+/// [`InputSource::get_spendable_transparent_outputs`]: zcash_client_backend::data_api::InputSource::get_spendable_transparent_outputs
+async fn spendable_outputs_with_fresh_change(
+    spend_zeroconf_change: bool,
+) -> (Vec<OutPoint>, OutPoint, OutPoint) {
+    use std::num::NonZeroU32;
+
+    use zcash_client_backend::data_api::{
+        InputSource, WalletWrite,
+        wallet::{ConfirmationsPolicy, TargetHeight, decrypt_and_store_transaction},
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = ZalletConfig::default();
+    config.datadir = Some(dir.path().to_path_buf());
+    config.builder.spend_zeroconf_change = Some(spend_zeroconf_change);
+
+    let db = database::Database::open(&config).await.unwrap();
+    let mut wallet = db.handle().await.unwrap();
+    let params = *wallet.params();
+
+    let (external, internal) = create_account(&mut wallet);
+
+    let tip = BlockHeight::from_u32(1_000_099);
+    wallet.update_chain_tip(tip).unwrap();
+
+    let spent = OutPoint::new([1; 32], 0);
+    let unspent = OutPoint::new([2; 32], 0);
+    for outpoint in [&spent, &unspent] {
+        receive_utxo(&mut wallet, outpoint, &external, 1_000_000);
+    }
+
+    // A transaction spending `spent`, and sending its change to the wallet.
+    let tx = spend_to(&spent, &internal, 90_000, 2_000_000);
+    decrypt_and_store_transaction(&params, &mut *wallet, &tx, None).unwrap();
+    let change = OutPoint::new(*tx.txid().as_ref(), 0);
+
+    let target_height = TargetHeight::from(tip + 1);
+    let policy = ConfirmationsPolicy::new_symmetrical(NonZeroU32::MIN, false);
+    let mut selected = vec![];
+    for address in [&external, &internal] {
+        selected.extend(
+            wallet
+                .get_spendable_transparent_outputs(address, target_height, policy)
+                .unwrap()
+                .into_iter()
+                .map(|utxo| utxo.outpoint().clone()),
+        );
+    }
+
+    (selected, unspent, change)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn zero_conf_change_selection() {
+    // Fresh change is only selected if the policy permits it.
+    let (selected, unspent, change) = spendable_outputs_with_fresh_change(true).await;
+    assert_eq!(selected, vec![unspent, change]);
+
+    let (selected, unspent, _) = spendable_outputs_with_fresh_change(false).await;
+    assert_eq!(selected, vec![unspent]);
+}
+
+#[test]
+fn unknown_transaction_does_not_spend_wallet_funds() {
+    let conn = migrated_db();
+    let txid = TxId::from_bytes([0; 32]);
+    assert!(!database::connection::spends_wallet_funds(&conn, &txid).unwrap());
+}
+
+#[test]
+fn unbroadcast_transactions() {
+    let conn = migrated_db();
+    let txid_a = TxId::from_bytes([1; 32]);
+    let txid_b = TxId::from_bytes([2; 32]);
+
+    database::connection::mark_unbroadcast(&conn, &txid_a).unwrap();
+    database::connection::mark_unbroadcast(&conn, &txid_b).unwrap();
+    // Marking a transaction again is a no-op.
+    database::connection::mark_unbroadcast(&conn, &txid_a).unwrap();
+    assert_eq!(
+        database::connection::unbroadcast_transactions(&conn).unwrap(),
+        vec![txid_a, txid_b],
+    );
+
+    database::connection::mark_broadcast(&conn, &txid_a).unwrap();
+    assert_eq!(
+        database::connection::unbroadcast_transactions(&conn).unwrap(),
+        vec![txid_b],
+    );
+}
+
+#[test]
+fn async_operations() {
+    use database::connection::{
+        StoredOperation, StoredOperationError, delete_operation, operations, save_operation,
+    };
+
+    let conn = migrated_db();
+    let mut op = StoredOperation {
+        operation_id: "opid-00000000-0000-0000-0000-000000000001".into(),
+        method: Some("z_sendmany".into()),
+        params: Some(r#"{"minconf":1}"#.into()),
+        state: "queued".into(),
+        creation_time: 1_700_000_000,
+        start_time: None,
+        end_time: None,
+        txids: vec![],
+        result: None,
+        error: None,
+        request_id: Some("req-00000000-0000-0000-0000-000000000001".into()),
+    };
+    let other = StoredOperation {
+        operation_id: "opid-00000000-0000-0000-0000-000000000002".into(),
+        method: None,
+        params: None,
+        state: "failed".into(),
+        creation_time: 1_700_000_001,
+        start_time: Some(1_700_000_001),
+        end_time: Some(1_700_000_002),
+        txids: vec![],
+        result: None,
+        error: Some(StoredOperationError {
+            code: -4,
+            message: "Failed".into(),
+            data: None,
+        }),
+        request_id: None,
+    };
+
+    save_operation(&conn, &op).unwrap();
+    save_operation(&conn, &other).unwrap();
+    assert_eq!(operations(&conn).unwrap(), vec![op.clone(), other.clone()]);
+
+    // State transitions update the existing record, and accumulate transactions.
+    op.state = "executing".into();
+    op.start_time = Some(1_700_000_005);
+    op.txids = vec![TxId::from_bytes([1; 32])];
+    save_operation(&conn, &op).unwrap();
+    op.txids.push(TxId::from_bytes([2; 32]));
+    op.state = "success".into();
+    op.end_time = Some(1_700_000_010);
+    op.result = Some(r#"{"txids":[]}"#.into());
+    save_operation(&conn, &op).unwrap();
+    assert_eq!(operations(&conn).unwrap(), vec![op.clone(), other.clone()]);
+
+    delete_operation(&conn, &op.operation_id).unwrap();
+    assert_eq!(operations(&conn).unwrap(), vec![other]);
+}
+
+#[test]
+fn no_conflicts_in_empty_wallet() {
+    let conn = migrated_db();
+    let txid = TxId::from_bytes([0; 32]);
+    assert!(
+        database::conflicts::wallet_conflicts(&conn, &[txid])
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        database::conflicts::conflicted_transactions(&conn)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn conflicts_are_found_in_one_query() {
+    use std::collections::{HashMap, HashSet};
+
+    use database::conflicts::{conflicted_transactions, wallet_conflicts};
+
+    let conn = migrated_db();
+    // The spent outputs are not needed to find conflicts.
+    conn.execute_batch("PRAGMA foreign_keys = OFF").unwrap();
+
+    let txid = |n| TxId::from_bytes([n; 32]);
+    for (id_tx, mined_height) in [(1, None), (2, Some(100)), (3, None), (4, None)] {
+        conn.execute(
+            "INSERT INTO transactions (id_tx, txid, mined_height, min_observed_height)
+            VALUES (:id_tx, :txid, :mined_height, 100)",
+            rusqlite::named_params! {
+                ":id_tx": id_tx,
+                ":txid": txid(id_tx).as_ref(),
+                ":mined_height": mined_height,
+            },
+        )
+        .unwrap();
+    }
+    // Transactions 1 and 2 spend the same transparent output and the same Orchard note.
+    // Transaction 3 spends a Sapling note with the same row ID as the Orchard note, which
+    // is not a conflict, and transaction 4 spends the same Sapling note.
+    conn.execute_batch(
+        "INSERT INTO transparent_received_output_spends
+            (transparent_received_output_id, transaction_id)
+        VALUES (1, 1), (1, 2);
+        INSERT INTO orchard_received_note_spends (orchard_received_note_id, transaction_id)
+        VALUES (7, 1), (7, 2);
+        INSERT INTO sapling_received_note_spends (sapling_received_note_id, transaction_id)
+        VALUES (7, 3), (7, 4);",
+    )
+    .unwrap();
+
+    assert_eq!(
+        wallet_conflicts(&conn, &[txid(1), txid(2), txid(3), txid(5)]).unwrap(),
+        HashMap::from([
+            (txid(1), vec![txid(2)]),
+            (txid(2), vec![txid(1)]),
+            (txid(3), vec![txid(4)]),
+        ]),
+    );
+    assert!(wallet_conflicts(&conn, &[]).unwrap().is_empty());
+
+    // Only unmined transactions that conflict with a mined transaction are conflicted.
+    assert_eq!(
+        conflicted_transactions(&conn).unwrap(),
+        HashSet::from([txid(1)])
+    );
+}
+
+#[test]
+fn no_transaction_events_in_empty_wallet() {
+    let conn = migrated_db();
+    let heights = BlockHeight::from_u32(1)..BlockHeight::from_u32(1_000_000);
+    assert!(
+        !database::connection::contains_transaction(&conn, &TxId::from_bytes([0; 32])).unwrap()
+    );
+    assert!(
+        database::connection::transactions_mined_in(&conn, heights.clone())
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        database::connection::transactions_expiring_in(&conn, heights)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn trusted_transactions() {
+    use std::collections::HashMap;
+
+    use database::connection::{TxTrust, is_trusted};
+
+    let txid = |n| TxId::from_bytes([n; 32]);
+    let tx = |mined, from_wallet, sources: &[u8]| TxTrust {
+        mined,
+        from_wallet,
+        unmined_sources: sources.iter().map(|n| txid(*n)).collect(),
+    };
+
+    let mut wallet = HashMap::new();
+    // A mined transaction received from a third party.
+    wallet.insert(txid(1), tx(true, false, &[]));
+    // Our unmined transaction spending mined funds.
+    wallet.insert(txid(2), tx(false, true, &[]));
+    // Our unmined transaction spending its unmined change.
+    wallet.insert(txid(3), tx(false, true, &[2]));
+    // An unmined transaction received from a third party.
+    wallet.insert(txid(4), tx(false, false, &[]));
+    // Our unmined transaction spending an untrusted output.
+    wallet.insert(txid(5), tx(false, true, &[3, 4]));
+
+    let lookup = |txid: &TxId| Ok::<_, ()>(wallet.get(txid).cloned());
+
+    assert!(is_trusted(txid(1), lookup).unwrap());
+    assert!(is_trusted(txid(2), lookup).unwrap());
+    assert!(is_trusted(txid(3), lookup).unwrap());
+    assert!(!is_trusted(txid(4), lookup).unwrap());
+    assert!(!is_trusted(txid(5), lookup).unwrap());
+    // Transactions unknown to the wallet are not trusted.
+    assert!(!is_trusted(txid(6), lookup).unwrap());
+}
+
+#[test]
+fn trust_of_stored_transactions() {
+    use database::connection::tx_trust;
+
+    let conn = migrated_db();
+    let insert = |txid: &TxId, mined_height: Option<u32>| {
+        conn.execute(
+            "INSERT INTO transactions (txid, mined_height, min_observed_height)
+            VALUES (:txid, :mined_height, 100)",
+            rusqlite::named_params! {
+                ":txid": txid.as_ref(),
+                ":mined_height": mined_height,
+            },
+        )
+        .unwrap();
+    };
+
+    let mined = TxId::from_bytes([1; 32]);
+    let unmined = TxId::from_bytes([2; 32]);
+    insert(&mined, Some(100));
+    insert(&unmined, None);
+
+    let trust = tx_trust(&conn, &mined, 0).unwrap().unwrap();
+    assert!(trust.mined);
+    assert!(trust.unmined_sources.is_empty());
+
+    // An unmined transaction that spends none of the wallet's funds was not created by
+    // the wallet, and so is not trusted.
+    let trust = tx_trust(&conn, &unmined, 0).unwrap().unwrap();
+    assert!(!trust.mined);
+    assert!(!trust.from_wallet);
+    assert!(trust.unmined_sources.is_empty());
+    assert!(!database::connection::is_trusted(unmined, |txid| tx_trust(&conn, txid, 0)).unwrap());
+
+    assert!(
+        tx_trust(&conn, &TxId::from_bytes([3; 32]), 0)
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn expiring_soon() {
+    let next_height = BlockHeight::from_u32(100);
+    let is_expiring_soon =
+        |expiry| database::connection::is_expiring_soon(BlockHeight::from_u32(expiry), next_height);
+
+    // Transactions without an expiry height never expire.
+    assert!(!is_expiring_soon(0));
+
+    assert!(is_expiring_soon(99));
+    assert!(is_expiring_soon(102));
+    assert!(!is_expiring_soon(103));
+    assert!(!is_expiring_soon(140));
+}
+
+#[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+#[test]
+fn address_labels_are_not_overwritten() {
+    use database::{AddressLabelOutcome, AddressPurpose};
+
+    let conn = migrated_db();
+    let add_for = |address, label, purpose| {
+        database::connection::add_address_label(&conn, address, label, purpose).unwrap()
+    };
+    let add = |label, purpose| add_for("t1VmmGiyjVNeCjxDZzg7vZmd99WyzVby9yC", label, purpose);
+
+    assert_eq!(
+        add("Exchange", AddressPurpose::Send),
+        AddressLabelOutcome::Added
+    );
+    assert_eq!(
+        add("Exchange", AddressPurpose::Send),
+        AddressLabelOutcome::Unchanged,
+    );
+    assert_eq!(
+        add("Café ☕", AddressPurpose::Send),
+        AddressLabelOutcome::Conflict {
+            existing_label: "Exchange".into()
+        },
+    );
+    assert_eq!(
+        add("Exchange", AddressPurpose::Receive),
+        AddressLabelOutcome::Conflict {
+            existing_label: "Exchange".into()
+        },
+    );
+
+    // Labels are stored exactly, including non-ASCII characters.
+    let other = "t1KzZ5n2TPEGYXTZ3WYGL1AYEumEQaRoHaL";
+    assert_eq!(
+        add_for(other, "Café ☕", AddressPurpose::Receive),
+        AddressLabelOutcome::Added,
+    );
+    assert_eq!(
+        add_for(other, "Café ☕", AddressPurpose::Receive),
+        AddressLabelOutcome::Unchanged,
+    );
+}
+
+#[test]
+fn balance_aggregates_in_empty_wallet() {
+    let mut conn = migrated_db();
+    database::balances::install(&mut conn, true).unwrap();
+    assert!(database::balances::check(&conn).unwrap().is_consistent());
+
+    database::balances::uninstall(&conn).unwrap();
+    let result = database::balances::check(&conn).unwrap();
+    assert_eq!(result.missing_triggers.len(), 24);
+    assert!(result.discrepancies.is_empty());
+}
+
+/// Asserts that the balance aggregates maintained by the triggers match the wallet's
+/// unspent outputs at `tip`, and returns the account's confirmed transparent balance
+/// with zero and one confirmations.
+#[cfg(zallet_build = "wallet")]
+fn aggregates_match_outputs(conn: &Connection, tip: BlockHeight) -> [u64; 2] {
+    use std::collections::HashMap;
+
+    use zcash_protocol::PoolType;
+
+    use database::{
+        as_of::{QueryHeight, unspent_outputs},
+        balances::{account_balances, check, totals_match},
+    };
+
+    let result = check(conn).unwrap();
+    assert!(result.is_consistent(), "{:?}", result.problems());
+    assert!(totals_match(conn).unwrap());
+
+    [0, 1].map(|minconf| {
+        let aggregated = account_balances(conn, tip, minconf)
+            .unwrap()
+            .into_iter()
+            .filter(|(_, _, balance)| !balance.confirmed.is_zero())
+            .map(|(account_uuid, pool, balance)| {
+                ((account_uuid, pool), balance.confirmed.into_u64())
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut summed = HashMap::new();
+        for pool in [PoolType::Transparent, PoolType::SAPLING, PoolType::ORCHARD] {
+            for output in unspent_outputs(conn, pool, QueryHeight::Tip(tip), minconf, None).unwrap()
+            {
+                if !output.is_immature(tip) {
+                    *summed.entry((output.account_uuid, pool)).or_insert(0) +=
+                        output.value.into_u64();
+                }
+            }
+        }
+        assert_eq!(aggregated, summed, "at height {tip} with minconf {minconf}");
+
+        aggregated
+            .into_iter()
+            .filter(|((_, pool), _)| *pool == PoolType::Transparent)
+            .map(|(_, value)| value)
+            .sum()
+    })
+}
+
+#[cfg(zallet_build = "wallet")]
+#[tokio::test(flavor = "multi_thread")]
+async fn balance_aggregates_follow_reorgs_and_expiry() {
+    use zcash_client_backend::data_api::{WalletWrite, wallet::decrypt_and_store_transaction};
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = ZalletConfig::default();
+    config.datadir = Some(dir.path().to_path_buf());
+
+    let db = database::Database::open(&config).await.unwrap();
+    let mut wallet = db.handle().await.unwrap();
+    let params = *wallet.params();
+    let (external, internal) = create_account(&mut wallet);
+    wallet
+        .update_chain_tip(BlockHeight::from_u32(1_000_099))
+        .unwrap();
+
+    let spent = OutPoint::new([1; 32], 0);
+    let unspent = OutPoint::new([2; 32], 0);
+    receive_utxo(&mut wallet, &spent, &external, 1_000_000);
+    receive_utxo(&mut wallet, &unspent, &external, 1_000_090);
+
+    // An unmined transaction spends one UTXO, sending its change back to the wallet
+    // until it expires.
+    let tx = spend_to(&spent, &internal, 90_000, 1_000_105);
+    decrypt_and_store_transaction(&params, &mut *wallet, &tx, None).unwrap();
+
+    let aggregates_at = |height| {
+        wallet.with_raw(|conn, _| aggregates_match_outputs(conn, BlockHeight::from_u32(height)))
+    };
+    assert_eq!(aggregates_at(1_000_099), [190_000, 100_000]);
+
+    // Once the spending transaction expires, the UTXO it spent is spendable again and
+    // its change is gone. This is decided when balances are read, so the aggregates
+    // are not changed.
+    assert_eq!(aggregates_at(1_000_104), [190_000, 100_000]);
+    assert_eq!(aggregates_at(1_000_105), [200_000, 200_000]);
+
+    // Roll back the blocks above height 1,000,050, as `WalletWrite::truncate_to_height`
+    // does. The UTXO mined at 1,000,090 becomes unmined, and is not counted until it
+    // is mined again.
+    wallet
+        .with_raw_mut(|conn, _| {
+            conn.execute(
+                "UPDATE transactions SET mined_height = NULL, tx_index = NULL
+                WHERE mined_height > 1000050",
+                [],
+            )
+        })
+        .unwrap();
+    assert_eq!(aggregates_at(1_000_050), [90_000, 0]);
+
+    // The UTXO is mined again at a different height, and the spending transaction is
+    // mined after it, so the UTXO it spent is no longer counted even once the
+    // transaction's expiry height has passed.
+    let mine = |txid: [u8; 32], height: u32| {
+        wallet
+            .with_raw_mut(|conn, _| {
+                conn.execute(
+                    "UPDATE transactions SET mined_height = ?2, tx_index = 1 WHERE txid = ?1",
+                    rusqlite::params![&txid[..], height],
+                )
+            })
+            .unwrap()
+    };
+    assert_eq!(mine(*unspent.hash(), 1_000_060), 1);
+    assert_eq!(mine(*tx.txid().as_ref(), 1_000_070), 1);
+    assert_eq!(aggregates_at(1_000_070), [190_000, 190_000]);
+    assert_eq!(aggregates_at(1_000_200), [190_000, 190_000]);
+
+    // If the aggregates drift from the wallet's outputs, balance queries log a warning
+    // instead of failing.
+    wallet
+        .with_raw_mut(|conn, _| {
+            conn.execute(
+                "UPDATE ext_zallet_db_balance_aggregates SET output_count = output_count + 1",
+                [],
+            )
+        })
+        .unwrap();
+    wallet.with_raw(|conn, _| {
+        assert!(!database::balances::totals_match(conn).unwrap());
+        assert!(
+            database::balances::account_balances(conn, BlockHeight::from_u32(1_000_200), 1).is_ok()
+        );
+    });
+}
+
+#[test]
+fn no_reused_addresses_in_empty_wallet() {
+    let conn = migrated_db();
+    assert!(
+        database::address_reuse::paid_by(&conn, &TxId::from_bytes([0; 32]))
+            .unwrap()
+            .is_empty()
+    );
+    #[cfg(zallet_build = "wallet")]
+    assert!(database::address_reuse::all(&conn).unwrap().is_empty());
+}
+
+#[test]
+fn rescans_are_scheduled_over_scanned_ranges() {
+    let mut conn = migrated_db();
+    let height = BlockHeight::from_u32;
+    // Blocks below 100 are below the wallet's birthday, blocks 100..500 have been
+    // scanned, and blocks 500..600 are waiting to be scanned.
+    conn.execute_batch(
+        "INSERT INTO scan_queue (block_range_start, block_range_end, priority)
+        VALUES (0, 100, 0), (100, 500, 10), (500, 600, 20)",
+    )
+    .unwrap();
+    assert_eq!(
+        database::scan_queue::unscanned_blocks_in(&conn, height(0)..height(600)).unwrap(),
+        100,
+    );
+
+    let tx = conn.transaction().unwrap();
+    database::scan_queue::reschedule(&tx, height(50)..height(300)).unwrap();
+    tx.commit().unwrap();
+
+    let queue = conn
+        .prepare(
+            "SELECT block_range_start, block_range_end, priority
+            FROM scan_queue ORDER BY block_range_start",
+        )
+        .unwrap()
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, u32>(0)?,
+                row.get::<_, u32>(1)?,
+                row.get::<_, u32>(2)?,
+            ))
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        queue,
+        [(0, 100, 0), (100, 300, 20), (300, 500, 10), (500, 600, 20)],
+    );
+    assert_eq!(
+        database::scan_queue::unscanned_blocks_in(&conn, height(200)..height(550)).unwrap(),
+        150,
+    );
+}
+
+#[test]
+fn integrity_checks_pass_in_empty_wallet() {
+    let conn = migrated_db();
+    assert!(
+        database::integrity::sqlite_integrity(&conn)
+            .unwrap()
+            .is_empty()
+    );
+    assert!(database::integrity::references(&conn).unwrap().is_empty());
+    assert!(database::integrity::scan_ranges(&conn).unwrap().is_empty());
+}
+
+#[test]
+fn scan_range_overlaps_and_gaps_are_found() {
+    let conn = migrated_db();
+    conn.execute_batch(
+        "INSERT INTO scan_queue (block_range_start, block_range_end, priority)
+        VALUES (0, 100, 0), (100, 300, 10), (250, 400, 10), (450, 600, 20)",
+    )
+    .unwrap();
+    assert_eq!(
+        database::integrity::scan_ranges(&conn).unwrap(),
+        [
+            "Scan ranges 100..300 and 250..400 overlap",
+            "Blocks 400..450 are missing from the scan ranges",
+        ],
+    );
+}
@@ -0,0 +1,68 @@
+//! Allocation of plain transparent addresses for the legacy `getnewaddress` and
+//! `getrawchangeaddress` methods.
+//!
+//! The wallet derives transparent addresses ahead of use, up to the transparent gap limit
+//! past the last address that has received funds. These addresses are not shown to the
+//! user until they have been exposed, so we allocate a new address by exposing the
+//! lowest-index address that has been neither exposed nor used. Exposure is recorded in
+//! the same way as for addresses returned by `z_getaddressforaccount`, so an address is
+//! never handed out twice and shows up in `listaddresses`.
+
+use rusqlite::{OptionalExtension, named_params};
+use zcash_client_sqlite::AccountUuid;
+use zcash_protocol::consensus::BlockHeight;
+
+/// The `key_scope` that `zcash_client_sqlite` records for external addresses.
+const KEY_SCOPE_EXTERNAL: i64 = 0;
+
+/// The `key_scope` that `zcash_client_sqlite` records for internal (change) addresses.
+const KEY_SCOPE_INTERNAL: i64 = 1;
+
+/// Exposes the next unused transparent address of the given account, and returns it
+/// along with its BIP 44 address index.
+///
+/// Returns `None` if every address up to the transparent gap limit has been exposed.
+pub(super) fn expose_next(
+    conn: &rusqlite::Connection,
+    account: AccountUuid,
+    change: bool,
+    exposed_at: BlockHeight,
+) -> Result<Option<(String, u32)>, rusqlite::Error> {
+    let next = conn
+        .query_row(
+            "SELECT a.id, a.cached_transparent_receiver_address, a.transparent_child_index
+            FROM addresses a
+            JOIN accounts ON accounts.id = a.account_id
+            WHERE accounts.uuid = :account_uuid
+            AND a.key_scope = :key_scope
+            AND a.exposed_at_height IS NULL
+            AND a.cached_transparent_receiver_address IS NOT NULL
+            AND a.transparent_child_index IS NOT NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM transparent_received_outputs o
+                WHERE o.address = a.cached_transparent_receiver_address
+            )
+            ORDER BY a.transparent_child_index
+            LIMIT 1",
+            named_params! {
+                ":account_uuid": account.expose_uuid(),
+                ":key_scope": if change { KEY_SCOPE_INTERNAL } else { KEY_SCOPE_EXTERNAL },
+            },
+            |row| Ok((row.get::<_, i64>(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+
+    let Some((address_id, address, index)) = next else {
+        return Ok(None);
+    };
+
+    conn.execute(
+        "UPDATE addresses SET exposed_at_height = :exposed_at WHERE id = :address_id",
+        named_params! {
+            ":exposed_at": u32::from(exposed_at),
+            ":address_id": address_id,
+        },
+    )?;
+
+    Ok(Some((address, index)))
+}
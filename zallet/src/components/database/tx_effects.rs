@@ -0,0 +1,83 @@
+//! The effects of wallet transactions on the wallet's accounts.
+
+use rusqlite::named_params;
+use uuid::Uuid;
+use zcash_protocol::{PoolType, TxId, consensus::BlockHeight};
+
+use super::balances::pool_type;
+
+/// How a wallet transaction affects the wallet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TxEffects {
+    /// The height at which the transaction was mined, if it has been.
+    pub(crate) mined_height: Option<BlockHeight>,
+    /// The change in zatoshis to the balance of each account that the transaction
+    /// involves.
+    pub(crate) accounts: Vec<(Uuid, i64)>,
+    /// The pools of the wallet outputs that the transaction creates or spends, along
+    /// with the pools of any outputs that the wallet sent.
+    pub(crate) pools: Vec<PoolType>,
+}
+
+/// Returns how the given transaction affects the wallet, or `None` if it is not a wallet
+/// transaction.
+pub(super) fn for_transaction(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+) -> Result<Option<TxEffects>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT account_uuid, account_balance_delta, mined_height
+        FROM v_transactions
+        WHERE txid = :txid
+        ORDER BY account_uuid",
+    )?;
+    let rows = stmt
+        .query_map(named_params! { ":txid": txid.as_ref() }, |row| {
+            Ok((
+                Uuid::from_bytes(row.get(0)?),
+                row.get::<_, i64>(1)?,
+                row.get::<_, Option<u32>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let Some(mined_height) = rows.first().map(|(_, _, height)| *height) else {
+        return Ok(None);
+    };
+
+    // The pool codes are those of `v_tx_outputs`.
+    let mut stmt = conn.prepare(
+        "SELECT output_pool FROM v_tx_outputs WHERE txid = :txid
+        UNION
+        SELECT 0
+        FROM transparent_received_output_spends s
+        JOIN transactions t ON t.id_tx = s.transaction_id
+        WHERE t.txid = :txid
+        UNION
+        SELECT 2
+        FROM sapling_received_note_spends s
+        JOIN transactions t ON t.id_tx = s.transaction_id
+        WHERE t.txid = :txid
+        UNION
+        SELECT 3
+        FROM orchard_received_note_spends s
+        JOIN transactions t ON t.id_tx = s.transaction_id
+        WHERE t.txid = :txid
+        ORDER BY 1",
+    )?;
+    let pools = stmt
+        .query_map(named_params! { ":txid": txid.as_ref() }, |row| {
+            let code = row.get(0)?;
+            pool_type(code).ok_or(rusqlite::Error::IntegralValueOutOfRange(0, code))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Some(TxEffects {
+        mined_height: mined_height.map(BlockHeight::from_u32),
+        accounts: rows
+            .into_iter()
+            .map(|(account, delta, _)| (account, delta))
+            .collect(),
+        pools,
+    }))
+}
@@ -0,0 +1,110 @@
+//! The fees paid by the wallet's transactions.
+//!
+//! The fee of a transaction is the total value of its inputs minus the total value of its
+//! outputs. The net value of each shielded bundle is public, so the fee can be computed
+//! from the transaction itself once the value of every transparent output that it spends
+//! is known.
+//!
+//! When Zallet creates a transaction, it records the total values of the transaction's
+//! inputs and outputs in `ext_zallet_db_tx_values`. The fees of other transactions are
+//! computed from the values of the wallet outputs that they spend; if a transaction
+//! spends a transparent output that does not belong to the wallet, its fee is unknown.
+
+use rusqlite::{OptionalExtension, named_params};
+use transparent::bundle::OutPoint;
+use zcash_protocol::{TxId, value::Zatoshis};
+
+/// The total values of the inputs and outputs of a transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct TxValues {
+    pub(crate) total_input: Zatoshis,
+    pub(crate) total_output: Zatoshis,
+}
+
+impl TxValues {
+    /// Returns the fee paid by the transaction.
+    pub(crate) fn fee(&self) -> Option<Zatoshis> {
+        self.total_input - self.total_output
+    }
+}
+
+fn zatoshis(value: i64, column: usize) -> Result<Zatoshis, rusqlite::Error> {
+    Zatoshis::from_nonnegative_i64(value)
+        .map_err(|_| rusqlite::Error::IntegralValueOutOfRange(column, value))
+}
+
+/// Records the total values of the inputs and outputs of a transaction created by the
+/// wallet.
+#[cfg(zallet_build = "wallet")]
+pub(super) fn record(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+    values: &TxValues,
+) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "INSERT OR REPLACE INTO ext_zallet_db_tx_values (txid, total_input, total_output)
+        VALUES (:txid, :total_input, :total_output)",
+        named_params! {
+            ":txid": txid.as_ref(),
+            ":total_input": values.total_input.into_u64(),
+            ":total_output": values.total_output.into_u64(),
+        },
+    )?;
+    Ok(())
+}
+
+/// Returns the fee of the given transaction, if it was recorded when the transaction was
+/// created or stored.
+pub(super) fn stored_fee(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+) -> Result<Option<Zatoshis>, rusqlite::Error> {
+    let recorded = conn
+        .query_row(
+            "SELECT total_input, total_output
+            FROM ext_zallet_db_tx_values
+            WHERE txid = :txid",
+            named_params! { ":txid": txid.as_ref() },
+            |row| {
+                Ok(TxValues {
+                    total_input: zatoshis(row.get(0)?, 0)?,
+                    total_output: zatoshis(row.get(1)?, 1)?,
+                })
+            },
+        )
+        .optional()?;
+    if let Some(values) = recorded {
+        return Ok(values.fee());
+    }
+
+    // `zcash_client_sqlite` records the fee of a transaction that it stores, if the value
+    // of every input was known at the time.
+    conn.query_row(
+        "SELECT fee FROM transactions WHERE txid = :txid",
+        named_params! { ":txid": txid.as_ref() },
+        |row| row.get::<_, Option<i64>>(0),
+    )
+    .optional()
+    .map(Option::flatten)?
+    .map(|fee| zatoshis(fee, 0))
+    .transpose()
+}
+
+/// Returns the value of the given transparent output, if it belongs to the wallet.
+pub(super) fn wallet_output_value(
+    conn: &rusqlite::Connection,
+    outpoint: &OutPoint,
+) -> Result<Option<Zatoshis>, rusqlite::Error> {
+    conn.query_row(
+        "SELECT o.value_zat
+        FROM transparent_received_outputs o
+        JOIN transactions t ON t.id_tx = o.transaction_id
+        WHERE t.txid = :txid AND o.output_index = :output_index",
+        named_params! {
+            ":txid": outpoint.hash(),
+            ":output_index": outpoint.n(),
+        },
+        |row| zatoshis(row.get(0)?, 0),
+    )
+    .optional()
+}
@@ -7,52 +7,175 @@
 //! - Some methods from the `zcashd` wallet are unsupported.
 
 use abscissa_core::tracing::{info, warn};
-use jsonrpsee::tracing::Instrument;
+use jsonrpsee::{server::Methods, tracing::Instrument};
 
 use crate::{
     config::ZalletConfig,
     error::{Error, ErrorKind},
+    shutdown,
 };
 
-use super::{TaskHandle, chain::Chain, database::Database};
+use super::{
+    TaskHandle,
+    chain::Chain,
+    database::Database,
+    sync::{SyncStatus, TransactionEvents},
+    wallets::Wallets,
+};
+use methods::{RpcImpl, RpcServer as _};
 
 #[cfg(zallet_build = "wallet")]
-use super::keystore::KeyStore;
+use {
+    super::{keystore::KeyStore, wallets::WeakWallets},
+    methods::{
+        WalletManagementRpcImpl, WalletManagementRpcServer as _, WalletRpcImpl,
+        WalletRpcServer as _,
+    },
+    std::{sync::Arc, time::Duration},
+};
 
 #[cfg(zallet_build = "wallet")]
 mod asyncop;
+#[cfg(zallet_build = "wallet")]
+mod batching;
+#[cfg(zallet_build = "wallet")]
+mod fees;
 pub(crate) mod methods;
+mod pagination;
 #[cfg(zallet_build = "wallet")]
 mod payments;
+#[cfg(zallet_build = "wallet")]
+mod pczts;
+#[cfg(zallet_build = "wallet")]
+mod send_error;
 pub(crate) mod server;
 pub(crate) mod utils;
 
+/// The JSON-RPC methods served by a single wallet.
+pub(crate) struct WalletMethods {
+    methods: Methods,
+    /// The registry of async operations launched by the wallet's methods.
+    #[cfg(zallet_build = "wallet")]
+    operations: Arc<asyncop::OperationRegistry>,
+}
+
+impl WalletMethods {
+    /// Creates the methods served by the given wallet, and restores the async operations
+    /// that were recorded before Zallet last stopped.
+    pub(crate) async fn new(
+        db: &Database,
+        #[cfg(zallet_build = "wallet")] keystore: KeyStore,
+        chain: &Chain,
+        events: TransactionEvents,
+        sync_status: SyncStatus,
+        #[cfg(zallet_build = "wallet")] wallets: WeakWallets,
+    ) -> Result<Self, Error> {
+        #[cfg(zallet_build = "wallet")]
+        let wallet_rpc_impl = WalletRpcImpl::new(
+            db.clone(),
+            keystore.clone(),
+            chain.clone(),
+            events.clone(),
+            sync_status.clone(),
+        );
+        #[cfg(zallet_build = "wallet")]
+        wallet_rpc_impl.restore_operations().await?;
+        #[cfg(zallet_build = "wallet")]
+        let operations = wallet_rpc_impl.operations();
+
+        let rpc_impl = RpcImpl::new(
+            db.clone(),
+            #[cfg(zallet_build = "wallet")]
+            keystore,
+            chain.clone(),
+            events,
+            sync_status,
+        );
+
+        #[allow(unused_mut)]
+        let mut rpc_module = rpc_impl.into_rpc();
+        #[cfg(zallet_build = "wallet")]
+        {
+            rpc_module
+                .merge(wallet_rpc_impl.into_rpc())
+                .map_err(|e| ErrorKind::Init.context(e))?;
+            rpc_module
+                .merge(WalletManagementRpcImpl::new(wallets).into_rpc())
+                .map_err(|e| ErrorKind::Init.context(e))?;
+        }
+
+        Ok(Self {
+            methods: rpc_module.into(),
+            #[cfg(zallet_build = "wallet")]
+            operations,
+        })
+    }
+
+    pub(crate) fn methods(&self) -> Methods {
+        self.methods.clone()
+    }
+
+    /// Waits for the wallet's executing async operations to finish, for up to `timeout`.
+    ///
+    /// Returns `false` if any operation was still executing when `timeout` elapsed.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) async fn drain(&self, timeout: Duration) -> bool {
+        self.operations.drain(timeout).await
+    }
+
+    /// Returns the number of the wallet's async operations in each state, including the
+    /// states that no operation is in.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) async fn operation_counts(&self) -> Vec<(&'static str, usize)> {
+        use asyncop::OperationState;
+
+        let mut counts = [
+            OperationState::Ready,
+            OperationState::Executing,
+            OperationState::Cancelled,
+            OperationState::Failed,
+            OperationState::Success,
+        ]
+        .map(|state| (state, 0));
+        for operation in self.operations.read().await.iter() {
+            let state = operation.state().await;
+            if let Some((_, count)) = counts.iter_mut().find(|(s, _)| *s == state) {
+                *count += 1;
+            }
+        }
+
+        counts
+            .into_iter()
+            .map(|(state, count)| (state.into(), count))
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct JsonRpc {}
 
 impl JsonRpc {
     pub(crate) async fn spawn(
         config: &ZalletConfig,
-        db: Database,
-        #[cfg(zallet_build = "wallet")] keystore: KeyStore,
-        chain: Chain,
+        wallets: Wallets,
     ) -> Result<TaskHandle, Error> {
         let rpc = config.rpc.clone();
+        let bind = config.rpc_bind();
 
-        if !rpc.bind.is_empty() {
-            if rpc.bind.len() > 1 {
+        if !bind.is_empty() {
+            if bind.len() > 1 {
                 return Err(ErrorKind::Init
                     .context("Only one RPC bind address is supported (for now)")
                     .into());
             }
             info!("Spawning RPC server");
-            info!("Trying to open RPC endpoint at {}...", rpc.bind[0]);
+            info!("Trying to open RPC endpoint at {}...", bind[0]);
             server::spawn(
                 rpc,
-                db,
-                #[cfg(zallet_build = "wallet")]
-                keystore,
-                chain,
+                bind[0].clone(),
+                config.rpc_cookie_path(),
+                server::TlsPaths::from_config(config)?,
+                wallets,
             )
             .await
         } else {
@@ -60,7 +183,11 @@ impl JsonRpc {
             // Emulate a normally-operating ongoing task to simplify subsequent logic.
             Ok(crate::spawn!(
                 "No JSON-RPC",
-                std::future::pending().in_current_span()
+                async {
+                    shutdown::requested().await;
+                    Ok(())
+                }
+                .in_current_span()
             ))
         }
     }
@@ -1,7 +1,13 @@
+use std::collections::HashSet;
 use std::future::Future;
-use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::PathBuf;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use abscissa_core::tracing::{debug, warn};
 use documented::Documented;
 use jsonrpsee::{
     core::{JsonValue, RpcResult},
@@ -9,11 +15,22 @@ use jsonrpsee::{
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use tokio::sync::RwLock;
+use serde_json::{Value, json};
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use uuid::Uuid;
+use zcash_protocol::TxId;
 
-use super::server::LegacyCode;
+use super::server::{LegacyCode, RequestId};
+use crate::{
+    components::{
+        audit,
+        database::{Database, StoredOperation, StoredOperationError},
+    },
+    config::{BuilderLimitsSection, ZalletConfig},
+    error::{Error, ErrorKind},
+    prelude::*,
+    shutdown,
+};
 
 /// An async operation ID.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize, Documented, JsonSchema)]
@@ -42,11 +59,39 @@ impl TryFrom<String> for OperationId {
 pub(super) struct ContextInfo {
     method: &'static str,
     params: JsonValue,
+    progress: Option<OperationProgress>,
 }
 
 impl ContextInfo {
     pub(super) fn new(method: &'static str, params: JsonValue) -> Self {
-        Self { method, params }
+        Self {
+            method,
+            params,
+            progress: None,
+        }
+    }
+
+    /// Reports the given progress handle's contents in the operation's status.
+    pub(super) fn with_progress(mut self, progress: OperationProgress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+}
+
+/// A handle that an async operation can use to report its progress while executing.
+#[derive(Clone, Default)]
+pub(super) struct OperationProgress(Arc<std::sync::RwLock<Option<Value>>>);
+
+impl OperationProgress {
+    /// Replaces the operation's current progress.
+    pub(super) fn set<T: Serialize>(&self, progress: &T) {
+        let progress =
+            serde_json::to_value(progress).expect("progress values should be serializable");
+        *self.0.write().expect("not poisoned") = Some(progress);
+    }
+
+    fn get(&self) -> Option<Value> {
+        self.0.read().expect("not poisoned").clone()
     }
 }
 
@@ -91,108 +136,712 @@ pub(super) struct OperationData {
     state: OperationState,
     start_time: Option<SystemTime>,
     end_time: Option<SystemTime>,
+    txids: Vec<TxId>,
+    /// The transactions that the operation has broadcast.
+    broadcast: Vec<TxId>,
     result: Option<RpcResult<Value>>,
 }
 
-/// An async operation launched by an RPC call.
-pub(super) struct AsyncOperation {
+tokio::task_local! {
+    /// The async operations on whose behalf the current task is running.
+    static CURRENT_OPERATIONS: Vec<AsyncOperation>;
+}
+
+/// Returns the async operations on whose behalf the current task is running.
+pub(super) fn current_operations() -> Vec<AsyncOperation> {
+    CURRENT_OPERATIONS
+        .try_with(|ops| ops.clone())
+        .unwrap_or_default()
+}
+
+/// Runs `f` on behalf of the given async operations, so that any transactions it
+/// creates are recorded against them.
+pub(super) async fn on_behalf_of<F: Future>(ops: Vec<AsyncOperation>, f: F) -> F::Output {
+    CURRENT_OPERATIONS.scope(ops, f).await
+}
+
+/// Records that the async operations on whose behalf the current task is running have
+/// created the given transactions.
+///
+/// This should be called as soon as the transactions exist, before they are broadcast,
+/// so that an operation interrupted by a restart can be checked for whether its
+/// transactions reached the network.
+pub(super) async fn record_txids(txids: &[TxId]) {
+    for op in current_operations() {
+        {
+            let mut data = op.inner.data.write().await;
+            for txid in txids {
+                if !data.txids.contains(txid) {
+                    data.txids.push(*txid);
+                }
+            }
+        }
+        op.persist().await;
+    }
+}
+
+/// Records that the async operations on whose behalf the current task is running have
+/// broadcast the given transactions.
+///
+/// An operation that timed out is changed from failed to successful once it is known to
+/// have broadcast a transaction, as its effects can no longer be undone.
+pub(super) async fn record_broadcast(txids: &[TxId]) {
+    for op in current_operations() {
+        let timed_out = {
+            let mut data = op.inner.data.write().await;
+            data.broadcast.extend_from_slice(txids);
+            op.is_cancelled() && data.state == OperationState::Failed
+        };
+        if timed_out {
+            op.finish(Ok(op.timed_out_result().await)).await;
+        }
+    }
+}
+
+/// Returns an error if Zallet is shutting down.
+///
+/// Async operations call this at the points where they can safely stop early, such as
+/// before creating a transaction. It must never be called between broadcasting a
+/// transaction and recording that it was broadcast.
+pub(super) fn checkpoint() -> RpcResult<()> {
+    if shutdown::is_requested() {
+        Err(LegacyCode::Misc.with_static("Operation interrupted because Zallet is shutting down"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the JSON result describing the given transactions.
+pub(super) fn txids_result(txids: &[TxId]) -> Value {
+    let txids = txids
+        .iter()
+        .map(|txid| txid.to_string())
+        .collect::<Vec<_>>();
+    match txids.as_slice() {
+        [txid] => json!({ "txid": txid, "txids": txids }),
+        _ => json!({ "txids": txids }),
+    }
+}
+
+/// Limits on the async operations retained by an [`OperationRegistry`].
+#[derive(Clone, Copy, Debug)]
+pub(super) struct RetentionLimits {
+    /// The maximum number of finished operations to retain.
+    pub(super) max_finished: usize,
+    /// The maximum time for which a finished operation is retained.
+    pub(super) max_age: Duration,
+    /// The maximum size of an operation's serialized result, in bytes.
+    pub(super) max_result_size: usize,
+}
+
+impl RetentionLimits {
+    /// Returns the limits configured for the wallet.
+    pub(super) fn from_config(limits: &BuilderLimitsSection) -> Self {
+        Self {
+            max_finished: usize::try_from(limits.retained_operations()).unwrap_or(usize::MAX),
+            max_age: limits.retained_operation_age(),
+            max_result_size: usize::try_from(limits.operation_result_size()).unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Returns the indices of the finished operations that should be dropped.
+    ///
+    /// `finished` contains the time at which each finished operation ended, along with
+    /// its index.
+    fn expired(&self, mut finished: Vec<(SystemTime, usize)>, now: SystemTime) -> HashSet<usize> {
+        finished.sort();
+        let excess = finished.len().saturating_sub(self.max_finished);
+        finished
+            .into_iter()
+            .enumerate()
+            .filter(|(n, (end_time, _))| {
+                *n < excess
+                    || now
+                        .duration_since(*end_time)
+                        .is_ok_and(|age| age > self.max_age)
+            })
+            .map(|(_, (_, index))| index)
+            .collect()
+    }
+}
+
+/// The async operations launched by RPC calls.
+///
+/// The registry enforces its [`RetentionLimits`] whenever it is accessed, by dropping
+/// the operations that finished earliest.
+pub(super) struct OperationRegistry {
+    ops: RwLock<Vec<AsyncOperation>>,
+    store: OperationStore,
+    limits: RetentionLimits,
+}
+
+impl OperationRegistry {
+    pub(super) fn new(wallet: Database, config: &ZalletConfig) -> Self {
+        let limits = RetentionLimits::from_config(&config.builder.limits);
+        Self {
+            ops: RwLock::new(vec![]),
+            store: OperationStore {
+                wallet,
+                max_result_size: limits.max_result_size,
+                limits: Arc::new(config.builder.limits.clone()),
+                audit_log: config.audit_log_path(),
+            },
+            limits,
+        }
+    }
+
+    /// Returns the store in which the registry's operations are persisted.
+    pub(super) fn store(&self) -> &OperationStore {
+        &self.store
+    }
+
+    /// Launches a new async operation, and returns its ID.
+    pub(super) async fn start<T: Serialize + Send + 'static>(
+        &self,
+        context: Option<ContextInfo>,
+        f: impl Future<Output = RpcResult<T>> + Send + 'static,
+    ) -> OperationId {
+        let op = AsyncOperation::new(self.store.clone(), context, f).await;
+        let op_id = op.operation_id().clone();
+        self.write().await.push(op);
+        op_id
+    }
+
+    /// Adds the given operations to the registry.
+    pub(super) async fn extend(&self, ops: impl IntoIterator<Item = AsyncOperation>) {
+        self.write().await.extend(ops);
+    }
+
+    /// Returns the operations in the registry.
+    pub(super) async fn read(&self) -> RwLockReadGuard<'_, Vec<AsyncOperation>> {
+        self.prune(&mut self.ops.write().await).await;
+        self.ops.read().await
+    }
+
+    /// Returns the operations in the registry, for modification.
+    pub(super) async fn write(&self) -> RwLockWriteGuard<'_, Vec<AsyncOperation>> {
+        let mut ops = self.ops.write().await;
+        self.prune(&mut ops).await;
+        ops
+    }
+
+    /// Waits for the executing operations to finish, for up to `timeout`.
+    ///
+    /// This is used during shutdown, after which no new operations start executing.
+    /// Returns `false` if any operation was still executing when `timeout` elapsed, in
+    /// which case those operations are [interrupted](Self::interrupt).
+    pub(super) async fn drain(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let mut executing = 0;
+            for op in self.ops.read().await.iter() {
+                if op.state().await == OperationState::Executing {
+                    executing += 1;
+                }
+            }
+
+            if executing == 0 {
+                return true;
+            } else if tokio::time::Instant::now() >= deadline {
+                warn!(
+                    "{executing} async operations were still executing after {} seconds",
+                    timeout.as_secs(),
+                );
+                self.interrupt().await;
+                return false;
+            }
+
+            debug!("Waiting for {executing} async operations to finish");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Fails the executing operations that have not created any transactions, as they
+    /// will not finish before Zallet stops.
+    ///
+    /// Operations that have created transactions are left executing, so that their
+    /// outcome is determined from the chain when Zallet next starts.
+    async fn interrupt(&self) {
+        for op in self.ops.read().await.iter() {
+            if op.state().await == OperationState::Executing && op.txids().await.is_empty() {
+                // If the operation broadcasts a transaction after all, it succeeds.
+                op.inner.cancelled.store(true, Ordering::Release);
+                op.finish(Err(
+                    LegacyCode::Misc.with_static("Operation interrupted by shutdown")
+                ))
+                .await;
+            }
+        }
+    }
+
+    /// Drops the finished operations that exceed the registry's retention limits.
+    async fn prune(&self, ops: &mut Vec<AsyncOperation>) {
+        let mut finished = vec![];
+        for (index, op) in ops.iter().enumerate() {
+            if let Some(end_time) = op.finished_at().await {
+                finished.push((end_time, index));
+            }
+        }
+
+        let expired = self.limits.expired(finished, SystemTime::now());
+        if expired.is_empty() {
+            return;
+        }
+
+        let mut index = 0;
+        let mut dropped = vec![];
+        ops.retain(|op| {
+            let keep = !expired.contains(&index);
+            if !keep {
+                dropped.push(op.clone());
+            }
+            index += 1;
+            keep
+        });
+
+        for op in dropped {
+            debug!(
+                "Dropping async operation {} that exceeds the retention limits",
+                op.operation_id().0,
+            );
+            op.forget().await;
+        }
+    }
+}
+
+/// Stores the state of async operations in the wallet database, so that they survive
+/// restarts.
+#[derive(Clone)]
+pub(super) struct OperationStore {
+    wallet: Database,
+    /// The maximum size of an operation's serialized result, in bytes.
+    max_result_size: usize,
+    /// The limits that determine how long each operation may spend executing.
+    limits: Arc<BuilderLimitsSection>,
+    /// The audit log in which operations are recorded, if it is enabled.
+    audit_log: Option<PathBuf>,
+}
+
+impl OperationStore {
+    /// Returns the async operations that were recorded before the most recent restart.
+    pub(super) async fn load(&self) -> Result<Vec<StoredOperation>, Error> {
+        let handle = self.wallet.handle().await?;
+        handle
+            .operations()
+            .map_err(|e| ErrorKind::Generic.context(e).into())
+    }
+
+    async fn save(&self, op: &StoredOperation) {
+        let res = match self.wallet.handle().await {
+            Ok(handle) => handle.save_operation(op).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = res {
+            warn!("Failed to persist async operation {}: {e}", op.operation_id);
+        }
+    }
+
+    async fn delete(&self, operation_id: &OperationId) {
+        let res = match self.wallet.handle().await {
+            Ok(handle) => handle
+                .delete_operation(&operation_id.0)
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = res {
+            warn!("Failed to remove async operation {}: {e}", operation_id.0);
+        }
+    }
+}
+
+struct OperationInner {
     operation_id: OperationId,
-    context: Option<ContextInfo>,
+    method: Option<String>,
+    params: Option<JsonValue>,
+    progress: Option<OperationProgress>,
     creation_time: SystemTime,
-    data: Arc<RwLock<OperationData>>,
+    /// The correlation ID of the RPC request that launched this operation.
+    request_id: Option<String>,
+    data: RwLock<OperationData>,
+    /// The operation's cooperative cancellation signal.
+    cancelled: AtomicBool,
+    store: OperationStore,
+}
+
+/// An async operation launched by an RPC call.
+#[derive(Clone)]
+pub(super) struct AsyncOperation {
+    inner: Arc<OperationInner>,
 }
 
 impl AsyncOperation {
     /// Launches a new async operation.
-    pub(super) async fn new<T: Serialize + Send + 'static>(
+    async fn new<T: Serialize + Send + 'static>(
+        store: OperationStore,
         context: Option<ContextInfo>,
         f: impl Future<Output = RpcResult<T>> + Send + 'static,
     ) -> Self {
-        let creation_time = SystemTime::now();
+        let operation_id = OperationId::new();
+        let request_id = RequestId::current().map(|id| id.to_string());
 
-        let data = Arc::new(RwLock::new(OperationData {
-            state: OperationState::Ready,
-            start_time: None,
-            end_time: None,
-            result: None,
-        }));
-
-        let handle = data.clone();
-
-        crate::spawn!(
-            context
-                .as_ref()
-                .map(|context| context.method)
-                .unwrap_or("AsyncOp"),
-            async move {
-                // Record that the task has started.
-                {
-                    let mut data = handle.write().await;
-                    if matches!(data.state, OperationState::Cancelled) {
-                        return;
-                    }
-                    data.state = OperationState::Executing;
-                    data.start_time = Some(SystemTime::now());
-                }
+        // Every async operation spends funds.
+        if let Some(context) = &context {
+            audit::record_to(
+                store.audit_log.as_deref(),
+                request_id.as_deref(),
+                audit::Event::SendSubmitted {
+                    method: context.method.into(),
+                    operation_id: operation_id.0.clone(),
+                    params: audit::redact_memos(context.params.clone()),
+                },
+            );
+        }
+
+        Self::launch(
+            store,
+            operation_id,
+            SystemTime::now(),
+            request_id,
+            context,
+            f,
+        )
+        .await
+    }
+
+    /// Relaunches an operation that was queued but had not started executing when
+    /// Zallet last stopped.
+    ///
+    /// `f` is the result of re-issuing the operation's original request; if the request
+    /// is no longer valid, the operation fails with its error.
+    pub(super) async fn resume<T: Serialize + Send + 'static>(
+        store: OperationStore,
+        stored: StoredOperation,
+        f: RpcResult<(
+            Option<ContextInfo>,
+            impl Future<Output = RpcResult<T>> + Send + 'static,
+        )>,
+    ) -> Option<Self> {
+        let operation_id = OperationId::try_from(stored.operation_id.clone()).ok()?;
+        let creation_time = from_unix_secs(stored.creation_time);
+        Some(match f {
+            Ok((context, f)) => {
+                Self::launch(
+                    store,
+                    operation_id,
+                    creation_time,
+                    stored.request_id,
+                    context,
+                    f,
+                )
+                .await
+            }
+            Err(e) => {
+                let op = Self::restore(store, stored)?;
+                op.finish(Err(e)).await;
+                op
+            }
+        })
+    }
+
+    /// Restores an operation from its persisted state, without executing anything.
+    pub(super) fn restore(store: OperationStore, stored: StoredOperation) -> Option<Self> {
+        let operation_id = OperationId::try_from(stored.operation_id).ok()?;
+        let result = match (stored.result, stored.error) {
+            (_, Some(e)) => Some(Err(ErrorObjectOwned::owned(
+                e.code,
+                e.message,
+                e.data
+                    .and_then(|data| serde_json::value::RawValue::from_string(data).ok()),
+            ))),
+            (Some(result), None) => Some(Ok(serde_json::from_str(&result).ok()?)),
+            (None, None) => None,
+        };
+
+        Some(Self {
+            inner: Arc::new(OperationInner {
+                operation_id,
+                method: stored.method,
+                params: stored
+                    .params
+                    .and_then(|params| serde_json::from_str(&params).ok()),
+                progress: None,
+                creation_time: from_unix_secs(stored.creation_time),
+                request_id: stored.request_id,
+                data: RwLock::new(OperationData {
+                    state: OperationState::parse(&stored.state)?,
+                    start_time: stored.start_time.map(from_unix_secs),
+                    end_time: stored.end_time.map(from_unix_secs),
+                    txids: stored.txids,
+                    broadcast: vec![],
+                    result,
+                }),
+                cancelled: AtomicBool::new(false),
+                store,
+            }),
+        })
+    }
+
+    async fn launch<T: Serialize + Send + 'static>(
+        store: OperationStore,
+        operation_id: OperationId,
+        creation_time: SystemTime,
+        request_id: Option<String>,
+        context: Option<ContextInfo>,
+        f: impl Future<Output = RpcResult<T>> + Send + 'static,
+    ) -> Self {
+        let (method, params, progress) = match context {
+            Some(context) => (Some(context.method), Some(context.params), context.progress),
+            None => (None, None, None),
+        };
+        let timeout = store
+            .limits
+            .operation_timeout_for(method.unwrap_or_default());
+
+        let op = Self {
+            inner: Arc::new(OperationInner {
+                operation_id,
+                method: method.map(String::from),
+                params,
+                progress,
+                creation_time,
+                request_id,
+                data: RwLock::new(OperationData {
+                    state: OperationState::Ready,
+                    start_time: None,
+                    end_time: None,
+                    txids: vec![],
+                    broadcast: vec![],
+                    result: None,
+                }),
+                cancelled: AtomicBool::new(false),
+                store,
+            }),
+        };
 
-                // Run the async task.
-                let res = f.await;
-                let end_time = SystemTime::now();
-
-                // Map the concrete task result into a generic JSON blob.
-                let res = res.map(|ret| {
-                    serde_json::from_str(
-                        &serde_json::to_string(&ret)
-                            .expect("async return values should be serializable to JSON"),
-                    )
-                    .expect("round trip should succeed")
-                });
-
-                // Record the result.
-                let mut data = handle.write().await;
-                data.state = if res.is_ok() {
-                    OperationState::Success
-                } else {
-                    OperationState::Failed
-                };
-                data.end_time = Some(end_time);
-                data.result = Some(res);
+        // Record the operation before it can do anything.
+        op.persist().await;
+
+        let handle = op.clone();
+        crate::spawn!(method.unwrap_or("AsyncOp"), async move {
+            // Record that the task has started.
+            {
+                let mut data = handle.inner.data.write().await;
+                if matches!(data.state, OperationState::Cancelled) {
+                    return;
+                }
+                // Leave the operation queued, so that it is resumed after the restart.
+                if shutdown::is_requested() {
+                    return;
+                }
+                data.state = OperationState::Executing;
+                data.start_time = Some(SystemTime::now());
             }
+            handle.persist().await;
+
+            // Run the async task, measuring the timeout from when it starts executing.
+            let Ok(res) =
+                tokio::time::timeout(timeout, on_behalf_of(vec![handle.clone()], f)).await
+            else {
+                handle.time_out(timeout).await;
+                return;
+            };
+
+            // Map the concrete task result into a generic JSON blob.
+            let res = res.map(|ret| {
+                serde_json::from_str(
+                    &serde_json::to_string(&ret)
+                        .expect("async return values should be serializable to JSON"),
+                )
+                .expect("round trip should succeed")
+            });
+
+            handle.finish(res).await;
+        });
+
+        op
+    }
+
+    /// Cancels this operation after it exceeded its timeout.
+    ///
+    /// The operation fails with a timeout error, unless it has already broadcast a
+    /// transaction, in which case it succeeds with a warning.
+    async fn time_out(&self, timeout: Duration) {
+        self.inner.cancelled.store(true, Ordering::Release);
+
+        let (txids, broadcast) = {
+            let data = self.inner.data.read().await;
+            (data.txids.clone(), !data.broadcast.is_empty())
+        };
+
+        warn!(
+            "Async operation {} timed out after {} seconds",
+            self.inner.operation_id.0,
+            timeout.as_secs(),
         );
 
-        Self {
-            operation_id: OperationId::new(),
-            context,
-            creation_time,
-            data,
+        let res = if broadcast {
+            Ok(self.timed_out_result().await)
+        } else {
+            let message = format!("Operation timed out after {} seconds", timeout.as_secs());
+            Err(if txids.is_empty() {
+                LegacyCode::Misc.with_message(message)
+            } else {
+                // The transactions were created but never broadcast.
+                ErrorObjectOwned::owned(
+                    LegacyCode::Misc.into(),
+                    message,
+                    Some(txids_result(&txids)),
+                )
+            })
+        };
+
+        self.finish(res).await;
+    }
+
+    /// Returns the result of an operation that timed out after broadcasting
+    /// transactions.
+    async fn timed_out_result(&self) -> Value {
+        let mut result = txids_result(&self.inner.data.read().await.broadcast);
+        result["warning"] = "Operation timed out after broadcasting these transactions; \
+            any later steps were not completed."
+            .into();
+        result
+    }
+
+    /// Returns `true` if this operation has been cancelled.
+    fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Records the final result of this operation.
+    pub(super) async fn finish(&self, res: RpcResult<Value>) {
+        // Bound the memory and storage used by the result.
+        let max_result_size = self.inner.store.max_result_size;
+        let res = match res {
+            Ok(v) if v.to_string().len() > max_result_size => {
+                debug!(
+                    "Result of async operation {} exceeds the size limit",
+                    self.inner.operation_id.0,
+                );
+                let txids = self.txids().await;
+                Ok(json!({
+                    "truncated": true,
+                    "txids": txids.iter().map(|txid| txid.to_string()).collect::<Vec<_>>(),
+                }))
+            }
+            Err(e)
+                if e.data()
+                    .is_some_and(|data| data.get().len() > max_result_size) =>
+            {
+                Err(ErrorObjectOwned::owned(
+                    e.code(),
+                    e.message().to_string(),
+                    None::<()>,
+                ))
+            }
+            res => res,
+        };
+
+        let error = res.as_ref().err().map(|e| e.message().to_string());
+
+        {
+            let mut data = self.inner.data.write().await;
+            data.state = if res.is_ok() {
+                OperationState::Success
+            } else {
+                OperationState::Failed
+            };
+            data.end_time = Some(SystemTime::now());
+            data.result = Some(res);
         }
+        self.persist().await;
+
+        let operation_id = self.inner.operation_id.0.clone();
+        let txids = self
+            .txids()
+            .await
+            .iter()
+            .map(|txid| txid.to_string())
+            .collect();
+        audit::record_to(
+            self.inner.store.audit_log.as_deref(),
+            self.inner.request_id.as_deref(),
+            match error {
+                None => audit::Event::SendCompleted {
+                    operation_id,
+                    txids,
+                },
+                Some(error) => audit::Event::SendFailed {
+                    operation_id,
+                    error,
+                    txids,
+                },
+            },
+        );
+    }
+
+    /// Writes the current state of this operation to the wallet database.
+    async fn persist(&self) {
+        let stored = {
+            let data = self.inner.data.read().await;
+
+            let (result, error) = match &data.result {
+                None => (None, None),
+                Some(Ok(v)) => (Some(v.to_string()), None),
+                Some(Err(e)) => (
+                    None,
+                    Some(StoredOperationError {
+                        code: e.code(),
+                        message: e.message().to_string(),
+                        data: e.data().map(|data| data.get().to_string()),
+                    }),
+                ),
+            };
+
+            StoredOperation {
+                operation_id: self.inner.operation_id.0.clone(),
+                method: self.inner.method.clone(),
+                params: self.inner.params.as_ref().map(|params| params.to_string()),
+                state: <&'static str>::from(data.state).into(),
+                creation_time: to_unix_secs(self.inner.creation_time),
+                start_time: data.start_time.map(to_unix_secs),
+                end_time: data.end_time.map(to_unix_secs),
+                txids: data.txids.clone(),
+                result,
+                error,
+                request_id: self.inner.request_id.clone(),
+            }
+        };
+
+        self.inner.store.save(&stored).await;
+    }
+
+    /// Removes this operation from the wallet database.
+    pub(super) async fn forget(&self) {
+        self.inner.store.delete(&self.inner.operation_id).await;
     }
 
     /// Returns the ID of this operation.
     pub(super) fn operation_id(&self) -> &OperationId {
-        &self.operation_id
+        &self.inner.operation_id
     }
 
     /// Returns the current state of this operation.
     pub(super) async fn state(&self) -> OperationState {
-        self.data.read().await.state
+        self.inner.data.read().await.state
     }
 
     /// Builds the current status of this operation.
     pub(super) async fn to_status(&self) -> OperationStatus {
-        let data = self.data.read().await;
+        let data = self.inner.data.read().await;
 
-        let (method, params) = self
-            .context
+        let progress = self
+            .inner
+            .progress
             .as_ref()
-            .map(|context| (context.method, context.params.clone()))
-            .unzip();
+            .and_then(|progress| progress.get());
 
-        let creation_time = self
-            .creation_time
-            .duration_since(UNIX_EPOCH)
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
+        let creation_time = to_unix_secs(self.inner.creation_time);
 
         let (error, result, execution_secs) = match &data.result {
             None => (None, None, None),
@@ -218,16 +867,44 @@ impl AsyncOperation {
         };
 
         OperationStatus {
-            id: self.operation_id.clone(),
-            method,
-            params,
+            id: self.inner.operation_id.clone(),
+            method: self.inner.method.clone(),
+            params: self.inner.params.clone(),
             status: data.state,
             creation_time,
+            request_id: self.inner.request_id.clone(),
+            progress,
             error,
             result,
             execution_secs,
         }
     }
+
+    /// Returns the time at which this operation finished, or `None` if it has not.
+    async fn finished_at(&self) -> Option<SystemTime> {
+        let data = self.inner.data.read().await;
+        match data.state {
+            OperationState::Ready | OperationState::Executing => None,
+            OperationState::Cancelled | OperationState::Failed | OperationState::Success => {
+                Some(data.end_time.unwrap_or(self.inner.creation_time))
+            }
+        }
+    }
+
+    /// Returns the IDs of the transactions that this operation has created.
+    pub(super) async fn txids(&self) -> Vec<TxId> {
+        self.inner.data.read().await.txids.clone()
+    }
+}
+
+fn to_unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn from_unix_secs(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
 }
 
 /// The status of an async operation.
@@ -236,7 +913,7 @@ pub(crate) struct OperationStatus {
     id: OperationId,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    method: Option<&'static str>,
+    method: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     params: Option<JsonValue>,
@@ -246,6 +923,15 @@ pub(crate) struct OperationStatus {
     // The creation time, in seconds since the Unix epoch.
     creation_time: u64,
 
+    /// The correlation ID of the RPC request that launched the operation, as recorded in
+    /// Zallet's request logs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+
+    /// Progress reported by the operation, if it supports progress reporting.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress: Option<Value>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<OperationError>,
 
@@ -269,3 +955,304 @@ struct OperationError {
     #[serde(skip_serializing_if = "Option::is_none")]
     data: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use serde_json::Value;
+    use tempfile::TempDir;
+    use zcash_protocol::TxId;
+
+    use crate::{
+        components::database::{Database, InputReservations, ReservedInput, StoredOperation},
+        config::ZalletConfig,
+        shutdown,
+    };
+
+    use super::{
+        AsyncOperation, OperationId, OperationRegistry, OperationState, RetentionLimits,
+        on_behalf_of, record_broadcast, record_txids, to_unix_secs,
+    };
+
+    /// Returns a registry whose operations time out after `timeout_secs` seconds, along
+    /// with the directory holding its wallet.
+    ///
+    /// Tests that use it must run on a multi-threaded runtime, as database accesses block.
+    async fn registry(timeout_secs: u64) -> (TempDir, OperationRegistry) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = ZalletConfig::default();
+        config.datadir = Some(dir.path().to_path_buf());
+        config.builder.limits.operation_timeout = Some(timeout_secs);
+
+        let wallet = Database::open(&config).await.unwrap();
+        (dir, OperationRegistry::new(wallet, &config))
+    }
+
+    async fn operation(registry: &OperationRegistry, id: &OperationId) -> AsyncOperation {
+        registry
+            .read()
+            .await
+            .iter()
+            .find(|op| op.operation_id() == id)
+            .expect("operation is retained")
+            .clone()
+    }
+
+    /// Waits for the given operation to leave `state`, and returns its status.
+    async fn wait_while(op: &AsyncOperation, state: OperationState) -> Value {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while op.state().await == state {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("operation should change state");
+        serde_json::to_value(op.to_status().await).unwrap()
+    }
+
+    /// Waits for the given operation to finish, and returns its status.
+    async fn wait_for(op: &AsyncOperation) -> Value {
+        wait_while(op, OperationState::Ready).await;
+        wait_while(op, OperationState::Executing).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn timeout_is_measured_from_execution_start() {
+        let _running = shutdown::testing::without_shutdown().await;
+        let (_dir, registry) = registry(1).await;
+
+        // An operation that was queued long before it started executing still has the
+        // whole timeout to run.
+        let stored = StoredOperation {
+            operation_id: OperationId::new().0,
+            method: None,
+            params: None,
+            state: "queued".into(),
+            creation_time: to_unix_secs(SystemTime::now() - Duration::from_secs(60 * 60)),
+            start_time: None,
+            end_time: None,
+            txids: vec![],
+            result: None,
+            error: None,
+            request_id: None,
+        };
+        let op = AsyncOperation::resume(
+            registry.store().clone(),
+            stored,
+            Ok((None, async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                Ok(())
+            })),
+        )
+        .await
+        .unwrap();
+        let status = wait_for(&op).await;
+        assert_eq!(status["status"], "success");
+
+        // An operation that runs for longer than the timeout fails.
+        let id = registry
+            .start(None, async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+        let status = wait_for(&operation(&registry, &id).await).await;
+        assert_eq!(status["status"], "failed");
+        assert_eq!(status["error"]["code"], -1);
+        assert_eq!(
+            status["error"]["message"],
+            "Operation timed out after 1 seconds"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn operations_that_broadcast_succeed_after_timing_out() {
+        let _running = shutdown::testing::without_shutdown().await;
+        let (_dir, registry) = registry(1).await;
+        let txid = |n| TxId::from_bytes([n; 32]);
+
+        let broadcast = registry
+            .start(None, async move {
+                record_txids(&[txid(1)]).await;
+                record_broadcast(&[txid(1)]).await;
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+        let created = registry
+            .start(None, async move {
+                record_txids(&[txid(2)]).await;
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+        let pending = registry
+            .start(None, async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+
+        // An operation that broadcast a transaction succeeds with a warning.
+        let status = wait_for(&operation(&registry, &broadcast).await).await;
+        assert_eq!(status["status"], "success");
+        assert_eq!(status["result"]["txid"], txid(1).to_string());
+        assert!(status["result"]["warning"].is_string());
+
+        // An operation whose transactions were never broadcast fails, and reports them.
+        let status = wait_for(&operation(&registry, &created).await).await;
+        assert_eq!(status["status"], "failed");
+        let data =
+            serde_json::from_str::<Value>(status["error"]["data"].as_str().unwrap()).unwrap();
+        assert_eq!(data["txid"], txid(2).to_string());
+
+        // A transaction broadcast on behalf of an operation that already timed out, as
+        // a shared batch transaction can be, turns its failure into success.
+        let pending = operation(&registry, &pending).await;
+        assert_eq!(wait_for(&pending).await["status"], "failed");
+        on_behalf_of(vec![pending.clone()], record_broadcast(&[txid(3)])).await;
+        let status = serde_json::to_value(pending.to_status().await).unwrap();
+        assert_eq!(status["status"], "success");
+        assert_eq!(status["result"]["txid"], txid(3).to_string());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn timed_out_operations_release_reserved_inputs() {
+        let _running = shutdown::testing::without_shutdown().await;
+        let (_dir, registry) = registry(1).await;
+
+        let reservations = InputReservations::default();
+        let input = ReservedInput::Transparent(transparent::bundle::OutPoint::new([1; 32], 0));
+        let reservation = reservations.try_reserve([input.clone()]).unwrap();
+
+        let id = registry
+            .start(None, async move {
+                let _reservation = reservation;
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+        assert!(reservations.is_reserved(&input));
+
+        let status = wait_for(&operation(&registry, &id).await).await;
+        assert_eq!(status["status"], "failed");
+        assert!(!reservations.is_reserved(&input));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn drain_waits_for_executing_operations() {
+        let _running = shutdown::testing::without_shutdown().await;
+        let (_dir, registry) = registry(60).await;
+
+        let id = registry
+            .start(None, async {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                Ok(())
+            })
+            .await;
+        let op = operation(&registry, &id).await;
+        wait_while(&op, OperationState::Ready).await;
+
+        assert!(registry.drain(Duration::from_secs(10)).await);
+        assert_eq!(op.state().await, OperationState::Success);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn drain_interrupts_operations_that_outlast_it() {
+        let _running = shutdown::testing::without_shutdown().await;
+        let (_dir, registry) = registry(60).await;
+
+        let (created_tx, created_rx) = tokio::sync::oneshot::channel();
+        let idle = registry
+            .start(None, async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+        let sending = registry
+            .start(None, async move {
+                record_txids(&[TxId::from_bytes([1; 32])]).await;
+                let _ = created_tx.send(());
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+        let idle = operation(&registry, &idle).await;
+        wait_while(&idle, OperationState::Ready).await;
+        created_rx.await.unwrap();
+
+        assert!(!registry.drain(Duration::from_millis(300)).await);
+
+        // The operation that had not created a transaction is failed, so that it is not
+        // left executing forever.
+        let status = serde_json::to_value(idle.to_status().await).unwrap();
+        assert_eq!(status["status"], "failed");
+        assert_eq!(
+            status["error"]["message"],
+            "Operation interrupted by shutdown"
+        );
+
+        // The operation that created a transaction is left executing, so that its outcome
+        // is determined from the chain when Zallet next starts.
+        let sending = operation(&registry, &sending).await;
+        assert_eq!(sending.state().await, OperationState::Executing);
+    }
+
+    #[test]
+    fn operation_state_filters() {
+        for state in [
+            OperationState::Ready,
+            OperationState::Executing,
+            OperationState::Cancelled,
+            OperationState::Failed,
+            OperationState::Success,
+        ] {
+            assert_eq!(OperationState::parse(state.into()), Some(state));
+        }
+
+        // As in `zcashd`, unrecognised states match no operations rather than erroring.
+        assert_eq!(OperationState::parse("ready"), None);
+        assert_eq!(OperationState::parse("Success"), None);
+        assert_eq!(OperationState::parse(""), None);
+    }
+
+    #[test]
+    fn retention_limits() {
+        let limits = RetentionLimits {
+            max_finished: 2,
+            max_age: Duration::from_secs(100),
+            max_result_size: 1000,
+        };
+        let now = SystemTime::now();
+        let ago = |secs| now - Duration::from_secs(secs);
+
+        // Within the limits, nothing is dropped.
+        assert!(
+            limits
+                .expired(vec![(ago(10), 0), (ago(20), 1)], now)
+                .is_empty()
+        );
+
+        // The operations that finished earliest are dropped first, regardless of the
+        // order in which they were created.
+        let mut expired = limits
+            .expired(
+                vec![(ago(10), 0), (ago(30), 1), (ago(20), 2), (ago(5), 3)],
+                now,
+            )
+            .into_iter()
+            .collect::<Vec<_>>();
+        expired.sort();
+        assert_eq!(expired, vec![1, 2]);
+
+        // Operations older than the maximum age are dropped even when under the count.
+        assert_eq!(
+            limits
+                .expired(vec![(ago(101), 4), (ago(99), 5)], now)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![4],
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Planning for operations that spend many inputs or pay many recipients.
+//!
+//! Merge and sweep operations may need to spend more inputs than fit in a single
+//! transaction, and a split `z_sendmany` payment may pay more recipients than fit in
+//! one. The planner in this module partitions the inputs or recipients into batches that
+//! each satisfy the wallet's builder limits, so that they can be handled by a sequence
+//! of transactions.
+
+use zcash_protocol::{PoolType, ShieldedProtocol};
+
+/// The maximum size of a transaction, in bytes.
+pub(super) const MAX_TX_SIZE: usize = 100_000;
+
+/// The space reserved in each transaction for everything other than its inputs
+/// (headers, outputs, and bundle-level proofs and signatures), in bytes.
+const TX_OVERHEAD: usize = 10_000;
+
+/// The estimated serialized size of a P2PKH transparent input, in bytes.
+const P2PKH_INPUT_SIZE: usize = 150;
+
+/// The serialized size of a Sapling spend, in bytes.
+const SAPLING_SPEND_SIZE: usize = 384;
+
+/// The serialized size of an Orchard action, including its share of the aggregated
+/// Halo 2 proof, in bytes.
+const ORCHARD_ACTION_SIZE: usize = 820 + 2272;
+
+/// The serialized size of a P2PKH transparent output, in bytes.
+const P2PKH_OUTPUT_SIZE: usize = 34;
+
+/// The serialized size of a Sapling output, in bytes.
+const SAPLING_OUTPUT_SIZE: usize = 948;
+
+/// The kind of an input to be spent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum InputKind {
+    Transparent,
+    Sapling,
+    Orchard,
+}
+
+impl InputKind {
+    fn size(self) -> usize {
+        match self {
+            InputKind::Transparent => P2PKH_INPUT_SIZE,
+            InputKind::Sapling => SAPLING_SPEND_SIZE,
+            InputKind::Orchard => ORCHARD_ACTION_SIZE,
+        }
+    }
+}
+
+/// Limits on the inputs spent by each transaction in a batched operation.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct BatchLimits {
+    pub(super) transparent_inputs: usize,
+    pub(super) sapling_spends: usize,
+    pub(super) orchard_actions: usize,
+}
+
+impl BatchLimits {
+    /// Returns the limits configured for the wallet's transaction builder.
+    pub(super) fn from_config(limits: &crate::config::BuilderLimitsSection) -> Self {
+        Self {
+            transparent_inputs: limits.transparent_inputs().into(),
+            sapling_spends: limits.sapling_spends().into(),
+            orchard_actions: limits.orchard_actions().into(),
+        }
+    }
+
+    fn limit(&self, kind: InputKind) -> usize {
+        match kind {
+            InputKind::Transparent => self.transparent_inputs,
+            InputKind::Sapling => self.sapling_spends,
+            // Reserve an action for the output to the destination.
+            InputKind::Orchard => self.orchard_actions.saturating_sub(1),
+        }
+    }
+}
+
+/// Partitions `inputs` into batches that can each be spent by a single transaction.
+///
+/// Inputs are kept in their original order. Each batch contains at least one input, and
+/// respects both the per-pool limits and the maximum transaction size.
+pub(super) fn plan_batches<T>(
+    inputs: impl IntoIterator<Item = T>,
+    kind: impl Fn(&T) -> InputKind,
+    limits: &BatchLimits,
+) -> Vec<Vec<T>> {
+    let limits = [
+        InputKind::Transparent,
+        InputKind::Sapling,
+        InputKind::Orchard,
+    ]
+    .map(|kind| limits.limit(kind));
+
+    partition(
+        inputs,
+        |input| {
+            let kind = kind(input);
+            (Some(kind as usize), kind.size())
+        },
+        limits,
+    )
+}
+
+/// Partitions the recipients of a payment into groups that can each be paid by a single
+/// transaction, given the pool that pays each recipient.
+///
+/// Recipients are kept in their original order. Only Orchard recipients count towards
+/// the Orchard action limit, which reserves an action for change; every recipient counts
+/// towards the maximum transaction size.
+pub(super) fn plan_recipients<T>(
+    recipients: impl IntoIterator<Item = T>,
+    pool: impl Fn(&T) -> PoolType,
+    limits: &BatchLimits,
+) -> Vec<Vec<T>> {
+    partition(
+        recipients,
+        |recipient| match pool(recipient) {
+            PoolType::Transparent => (None, P2PKH_OUTPUT_SIZE),
+            PoolType::Shielded(ShieldedProtocol::Sapling) => (None, SAPLING_OUTPUT_SIZE),
+            PoolType::Shielded(ShieldedProtocol::Orchard) => (Some(0), ORCHARD_ACTION_SIZE),
+        },
+        [limits.limit(InputKind::Orchard)],
+    )
+}
+
+/// Partitions `items` into batches, where each item takes up one of the `limits[i]`
+/// places available in a batch for index `i` (if any), along with the given number of
+/// bytes of the maximum transaction size.
+///
+/// Items are kept in their original order, and each batch contains at least one item.
+fn partition<T, const N: usize>(
+    items: impl IntoIterator<Item = T>,
+    weight: impl Fn(&T) -> (Option<usize>, usize),
+    limits: [usize; N],
+) -> Vec<Vec<T>> {
+    let mut batches = vec![];
+    let mut batch = vec![];
+    let mut counts = [0; N];
+    let mut size = TX_OVERHEAD;
+
+    for item in items {
+        let (index, item_size) = weight(&item);
+        let is_full = index.is_some_and(|index| counts[index] >= limits[index]);
+
+        if !batch.is_empty() && (is_full || size + item_size > MAX_TX_SIZE) {
+            batches.push(std::mem::take(&mut batch));
+            counts = [0; N];
+            size = TX_OVERHEAD;
+        }
+
+        if let Some(index) = index {
+            counts[index] += 1;
+        }
+        size += item_size;
+        batch.push(item);
+    }
+
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::{PoolType, ShieldedProtocol};
+
+    use super::{BatchLimits, InputKind, plan_batches, plan_recipients};
+
+    const LIMITS: BatchLimits = BatchLimits {
+        transparent_inputs: 50,
+        sapling_spends: 200,
+        orchard_actions: 50,
+    };
+
+    #[test]
+    fn per_pool_limits() {
+        let batches = plan_batches(0..120, |_| InputKind::Transparent, &LIMITS);
+        assert_eq!(
+            batches.iter().map(|b| b.len()).collect::<Vec<_>>(),
+            vec![50, 50, 20],
+        );
+
+        // One action is reserved for the output.
+        let batches = plan_batches(0..100, |_| InputKind::Orchard, &LIMITS);
+        assert_eq!(
+            batches.iter().map(|b| b.len()).collect::<Vec<_>>(),
+            vec![29, 29, 29, 13],
+        );
+    }
+
+    #[test]
+    fn transaction_size() {
+        let batches = plan_batches(0..500, |_| InputKind::Sapling, &LIMITS);
+        assert_eq!(
+            batches.iter().map(|b| b.len()).collect::<Vec<_>>(),
+            vec![200, 200, 100],
+        );
+
+        let limits = BatchLimits {
+            sapling_spends: 1000,
+            ..LIMITS
+        };
+        let batches = plan_batches(0..500, |_| InputKind::Sapling, &limits);
+        assert_eq!(
+            batches.iter().map(|b| b.len()).collect::<Vec<_>>(),
+            vec![234, 234, 32],
+        );
+    }
+
+    #[test]
+    fn recipients() {
+        // One action is reserved for change.
+        let batches = plan_recipients(0..100, |_| PoolType::ORCHARD, &LIMITS);
+        assert_eq!(
+            batches.iter().map(|b| b.len()).collect::<Vec<_>>(),
+            vec![29, 29, 29, 13],
+        );
+
+        // Only Orchard recipients count towards the action limit.
+        let limits = BatchLimits {
+            orchard_actions: 5,
+            ..LIMITS
+        };
+        let batches = plan_recipients(
+            0..20,
+            |i| {
+                if i % 2 == 0 {
+                    PoolType::ORCHARD
+                } else {
+                    PoolType::Shielded(ShieldedProtocol::Sapling)
+                }
+            },
+            &limits,
+        );
+        assert_eq!(
+            batches.iter().map(|b| b.len()).collect::<Vec<_>>(),
+            vec![8, 8, 4],
+        );
+
+        let batches = plan_recipients(0..1000, |_| PoolType::Transparent, &LIMITS);
+        assert_eq!(batches.len(), 1);
+    }
+}
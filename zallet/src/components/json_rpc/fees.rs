@@ -0,0 +1,369 @@
+//! ZIP 317 fee calculation.
+//!
+//! Every JSON-RPC method that creates transactions uses the helpers in this module to
+//! determine the fee it will pay, so that the wallet's fee behaviour is defined in a
+//! single place.
+//!
+//! See [ZIP 317](https://zips.z.cash/zip-0317) for the specification of the conventional
+//! fee.
+
+use jsonrpsee::core::{JsonValue, RpcResult};
+use serde_json::json;
+use zcash_address::ZcashAddress;
+use zcash_client_backend::fees::StandardFeeRule;
+use zcash_primitives::transaction::fees::zip317;
+use zcash_protocol::{PoolType, ShieldedProtocol, value::Zatoshis};
+
+use super::{
+    server::LegacyCode,
+    utils::{value_from_zatoshis, zatoshis_from_value},
+};
+use crate::config::{BuilderSection, NoteManagementSection};
+
+/// The maximum number of unpaid actions that a block producer will include in a block,
+/// per ZIP 317.
+///
+/// Transactions that pay less than the conventional fee are only relayed and mined if
+/// their number of unpaid actions does not exceed this limit.
+pub(super) const BLOCK_UNPAID_ACTION_LIMIT: usize = 50;
+
+/// The minimum number of actions in a non-empty Sapling or Orchard bundle, after padding
+/// by the transaction builder.
+const MIN_SHIELDED_ACTIONS: usize = 2;
+
+/// The fee rule used by the wallet when proposing transactions.
+pub(super) fn fee_rule() -> StandardFeeRule {
+    StandardFeeRule::Zip317
+}
+
+/// The fee rule used by the wallet when directly constructing transactions.
+pub(super) fn builder_fee_rule() -> zip317::FeeRule {
+    zip317::FeeRule::standard()
+}
+
+/// The fee paid by the wallet for each logical action beyond the grace actions.
+pub(super) fn marginal_fee() -> Zatoshis {
+    zip317::MARGINAL_FEE
+}
+
+/// The values below which outputs are considered dust.
+///
+/// Payments of dust are rejected. Dust change is added to the fee instead (see
+/// [`NoteManagementSection::dust_policy`]).
+#[derive(Clone, Copy, Debug)]
+pub(super) struct DustThresholds {
+    transparent: Zatoshis,
+    shielded: Zatoshis,
+}
+
+impl DustThresholds {
+    /// Returns the thresholds configured for the wallet.
+    pub(super) fn from_config(config: &NoteManagementSection) -> Self {
+        Self {
+            transparent: config.transparent_dust_threshold(),
+            shielded: config.dust_threshold(),
+        }
+    }
+
+    /// Returns the threshold for payments to `recipient`.
+    ///
+    /// Recipients that can receive shielded funds are paid in a shielded pool, so are
+    /// held to the shielded threshold.
+    pub(super) fn threshold(&self, recipient: &ZcashAddress) -> Zatoshis {
+        let shielded = [ShieldedProtocol::Orchard, ShieldedProtocol::Sapling]
+            .into_iter()
+            .any(|protocol| recipient.can_receive_as(PoolType::Shielded(protocol)));
+
+        if shielded {
+            self.shielded
+        } else {
+            self.transparent
+        }
+    }
+
+    /// Returns `true` if a payment of `value` to `recipient` would be dust.
+    pub(super) fn is_dust(&self, recipient: &ZcashAddress, value: Zatoshis) -> bool {
+        value < self.threshold(recipient)
+    }
+}
+
+/// The most that a transaction may pay in fees.
+///
+/// This is `builder.max_fee`, unless the caller of a method that sends funds set a lower
+/// limit with its `fee` parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct FeeLimit {
+    value: Zatoshis,
+    /// Whether the limit was set by the caller.
+    requested: bool,
+}
+
+impl FeeLimit {
+    /// Returns the limit configured for the wallet.
+    pub(super) fn from_config(config: &BuilderSection) -> Self {
+        Self {
+            value: config.max_fee(),
+            requested: false,
+        }
+    }
+
+    /// Parses the `fee` parameter of a method that sends funds.
+    ///
+    /// If the parameter is omitted or null, the configured limit applies.
+    pub(super) fn from_param(fee: Option<&JsonValue>, config: &BuilderSection) -> RpcResult<Self> {
+        let Some(fee) = fee.filter(|fee| !fee.is_null()) else {
+            return Ok(Self::from_config(config));
+        };
+
+        let value = zatoshis_from_value(fee)?;
+        if value > config.max_fee() {
+            return Err(LegacyCode::InvalidParameter.with_data(
+                format!(
+                    "Invalid parameter, fee of {} ZEC exceeds builder.max_fee of {} ZEC",
+                    value_from_zatoshis(value),
+                    value_from_zatoshis(config.max_fee()),
+                ),
+                json!({ "fee": value.into_u64(), "limit": config.max_fee().into_u64() }),
+            ));
+        }
+
+        Ok(Self {
+            value,
+            requested: true,
+        })
+    }
+
+    /// Checks that a transaction with the given actions, paying the given fee, is within
+    /// this limit.
+    ///
+    /// A limit set by the caller must also cover the transaction's conventional fee.
+    pub(super) fn check(&self, counts: &ActionCounts, fee: Zatoshis) -> RpcResult<()> {
+        let conventional_fee = counts.conventional_fee();
+        if self.requested && self.value < conventional_fee {
+            return Err(LegacyCode::InvalidParameter.with_data(
+                format!(
+                    "Invalid parameter, fee of {} ZEC is below the ZIP 317 conventional fee of {} ZEC for this transaction",
+                    value_from_zatoshis(self.value),
+                    value_from_zatoshis(conventional_fee),
+                ),
+                json!({ "fee": self.value.into_u64(), "required": conventional_fee.into_u64() }),
+            ));
+        }
+
+        if fee > self.value {
+            let (code, limit) = if self.requested {
+                (LegacyCode::InvalidParameter, "the requested fee")
+            } else {
+                (LegacyCode::Wallet, "builder.max_fee")
+            };
+            return Err(code.with_data(
+                format!(
+                    "Transaction would pay a fee of {} ZEC, which exceeds {limit} of {} ZEC",
+                    value_from_zatoshis(fee),
+                    value_from_zatoshis(self.value),
+                ),
+                json!({ "fee": fee.into_u64(), "limit": self.value.into_u64() }),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// The number of logical actions in a transaction, broken down by kind.
+///
+/// All counts are of the elements requested by the caller; padding added by the
+/// transaction builder to shielded bundles is accounted for by [`Self::logical_actions`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(super) struct ActionCounts {
+    /// The number of P2PKH transparent inputs.
+    pub(super) transparent_inputs: usize,
+    /// The number of transparent outputs.
+    pub(super) transparent_outputs: usize,
+    /// The number of Sapling spends.
+    pub(super) sapling_spends: usize,
+    /// The number of Sapling outputs.
+    pub(super) sapling_outputs: usize,
+    /// The number of Orchard spends.
+    pub(super) orchard_spends: usize,
+    /// The number of Orchard outputs.
+    pub(super) orchard_outputs: usize,
+}
+
+impl ActionCounts {
+    /// Returns the number of logical actions in the transaction, as defined in ZIP 317.
+    pub(super) fn logical_actions(&self) -> usize {
+        // Every input and output is assumed to have the standard P2PKH size.
+        let transparent = self.transparent_inputs.max(self.transparent_outputs);
+
+        let sapling = match self.sapling_spends.max(self.sapling_outputs) {
+            0 => 0,
+            // The builder pads Sapling outputs up to the minimum.
+            _ => self
+                .sapling_spends
+                .max(self.sapling_outputs.max(MIN_SHIELDED_ACTIONS)),
+        };
+
+        let orchard = match self.orchard_spends.max(self.orchard_outputs) {
+            0 => 0,
+            // The builder pads Orchard actions up to the minimum.
+            n => n.max(MIN_SHIELDED_ACTIONS),
+        };
+
+        transparent + sapling + orchard
+    }
+
+    /// Returns the ZIP 317 conventional fee for the transaction.
+    pub(super) fn conventional_fee(&self) -> Zatoshis {
+        let actions = self.logical_actions().max(zip317::GRACE_ACTIONS);
+        Zatoshis::from_u64(marginal_fee().into_u64() * u64::try_from(actions).expect("fits"))
+            .expect("number of actions is bounded by transaction size")
+    }
+
+    /// Returns the number of unpaid actions in a transaction paying the given fee.
+    ///
+    /// This is zero if `fee` is at least the conventional fee.
+    pub(super) fn unpaid_actions(&self, fee: Zatoshis) -> usize {
+        let actions = self.logical_actions().max(zip317::GRACE_ACTIONS);
+        let paid_actions =
+            usize::try_from(fee.into_u64() / marginal_fee().into_u64()).unwrap_or(usize::MAX);
+        actions.saturating_sub(paid_actions)
+    }
+
+    /// Returns `true` if a transaction paying the given fee would be accepted for relay
+    /// and mining by nodes following ZIP 317.
+    pub(super) fn is_fee_acceptable(&self, fee: Zatoshis) -> bool {
+        self.unpaid_actions(fee) <= BLOCK_UNPAID_ACTION_LIMIT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpsee::core::JsonValue;
+    use serde_json::json;
+    use zcash_address::ZcashAddress;
+    use zcash_protocol::{consensus::NetworkType, value::Zatoshis};
+
+    use super::{ActionCounts, BLOCK_UNPAID_ACTION_LIMIT, DustThresholds, FeeLimit};
+    use crate::config::{BuilderSection, NoteManagementSection};
+
+    #[test]
+    fn grace_actions() {
+        // An empty transaction still pays for the grace actions.
+        assert_eq!(
+            ActionCounts::default().conventional_fee(),
+            Zatoshis::const_from_u64(10_000),
+        );
+
+        // A single transparent input and output fits within the grace actions.
+        let counts = ActionCounts {
+            transparent_inputs: 1,
+            transparent_outputs: 1,
+            ..Default::default()
+        };
+        assert_eq!(counts.logical_actions(), 1);
+        assert_eq!(counts.conventional_fee(), Zatoshis::const_from_u64(10_000));
+    }
+
+    #[test]
+    fn shielded_padding() {
+        // A single Orchard output is padded to two actions.
+        let counts = ActionCounts {
+            transparent_inputs: 3,
+            orchard_outputs: 1,
+            ..Default::default()
+        };
+        assert_eq!(counts.logical_actions(), 5);
+        assert_eq!(counts.conventional_fee(), Zatoshis::const_from_u64(25_000));
+
+        // Sapling spends are not padded, but outputs are.
+        let counts = ActionCounts {
+            sapling_spends: 4,
+            sapling_outputs: 1,
+            ..Default::default()
+        };
+        assert_eq!(counts.logical_actions(), 4);
+    }
+
+    #[test]
+    fn unpaid_actions() {
+        let counts = ActionCounts {
+            orchard_spends: 60,
+            orchard_outputs: 2,
+            ..Default::default()
+        };
+        assert_eq!(counts.unpaid_actions(counts.conventional_fee()), 0);
+        assert_eq!(counts.unpaid_actions(Zatoshis::ZERO), 60);
+        assert!(!counts.is_fee_acceptable(Zatoshis::ZERO));
+        assert!(counts.is_fee_acceptable(Zatoshis::const_from_u64(
+            5_000 * (60 - BLOCK_UNPAID_ACTION_LIMIT as u64)
+        )));
+    }
+
+    #[test]
+    fn fee_limit() {
+        let config = BuilderSection {
+            max_fee: Some(20_000),
+            ..Default::default()
+        };
+        let counts = ActionCounts {
+            orchard_spends: 1,
+            orchard_outputs: 2,
+            ..Default::default()
+        };
+
+        // Without a `fee` parameter, only the configured limit applies.
+        let limit = FeeLimit::from_param(Some(&JsonValue::Null), &config).unwrap();
+        assert_eq!(limit, FeeLimit::from_config(&config));
+        assert!(
+            limit
+                .check(&counts, Zatoshis::const_from_u64(20_000))
+                .is_ok()
+        );
+        assert!(
+            limit
+                .check(&counts, Zatoshis::const_from_u64(20_001))
+                .is_err()
+        );
+
+        // The requested fee cannot exceed the configured limit.
+        assert!(FeeLimit::from_param(Some(&json!("0.0003")), &config).is_err());
+
+        // The requested fee must cover the conventional fee.
+        let limit = FeeLimit::from_param(Some(&json!("0.00005")), &config).unwrap();
+        assert!(
+            limit
+                .check(&ActionCounts::default(), Zatoshis::ZERO)
+                .is_err()
+        );
+        let limit = FeeLimit::from_param(Some(&json!("0.0001")), &config).unwrap();
+        assert!(limit.check(&counts, counts.conventional_fee()).is_ok());
+        assert!(
+            limit
+                .check(&counts, Zatoshis::const_from_u64(15_000))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn dust_thresholds() {
+        let thresholds = DustThresholds::from_config(&NoteManagementSection::default());
+        let transparent = ZcashAddress::from_transparent_p2pkh(NetworkType::Main, [7; 20]);
+        let sapling = ZcashAddress::from_sapling(NetworkType::Main, [7; 43]);
+
+        // The thresholds are exclusive.
+        assert!(thresholds.is_dust(&transparent, Zatoshis::const_from_u64(53)));
+        assert!(!thresholds.is_dust(&transparent, Zatoshis::const_from_u64(54)));
+        assert!(thresholds.is_dust(&sapling, Zatoshis::const_from_u64(4_999)));
+        assert!(!thresholds.is_dust(&sapling, Zatoshis::const_from_u64(5_000)));
+
+        let thresholds = DustThresholds::from_config(&NoteManagementSection {
+            dust_threshold: Some(0),
+            transparent_dust_threshold: Some(1_000),
+            ..Default::default()
+        });
+        assert!(!thresholds.is_dust(&sapling, Zatoshis::ZERO));
+        assert!(thresholds.is_dust(&transparent, Zatoshis::const_from_u64(999)));
+        assert!(!thresholds.is_dust(&transparent, Zatoshis::const_from_u64(1_000)));
+    }
+}
@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 use jsonrpsee::{
-    core::{JsonValue, RpcResult},
+    PendingSubscriptionSink,
+    core::{JsonValue, RpcResult, SubscriptionResult},
     proc_macros::rpc,
 };
 use zaino_state::FetchServiceSubscriber;
@@ -8,58 +9,183 @@ use zaino_state::FetchServiceSubscriber;
 use crate::components::{
     chain::Chain,
     database::{Database, DbHandle},
+    sync::{SyncStatus, TransactionEvents},
 };
 
+use super::server::ComponentFailure;
+
 #[cfg(zallet_build = "wallet")]
 use {
-    super::asyncop::{AsyncOperation, ContextInfo, OperationId},
-    crate::components::keystore::KeyStore,
+    super::{
+        asyncop::{
+            self, AsyncOperation, ContextInfo, OperationId, OperationRegistry, OperationState,
+        },
+        pczts::PendingPczts,
+        server::LegacyCode,
+    },
+    crate::{
+        components::{
+            database::StoredOperation,
+            keystore::KeyStore,
+            wallets::{Wallets, WeakWallets},
+        },
+        error::Error,
+        prelude::*,
+    },
+    abscissa_core::Application,
+    jsonrpsee::{tracing::warn, types::ErrorObjectOwned},
     serde::Serialize,
-    tokio::sync::RwLock,
+    std::sync::Arc,
+    zaino_state::ZcashIndexer,
+    zcash_client_backend::data_api::WalletRead,
 };
 
+#[cfg(zallet_build = "wallet")]
+mod change_wallet_passphrase;
+#[cfg(zallet_build = "wallet")]
+mod confirm_backup;
 mod convert_tex;
+#[cfg(zallet_build = "wallet")]
+mod create_wallet;
+#[cfg(zallet_build = "wallet")]
+mod export_history;
+#[cfg(zallet_build = "wallet")]
+mod export_key;
+mod export_viewing_key;
+#[cfg(zallet_build = "wallet")]
+mod export_wallet;
 mod get_account;
 mod get_address_for_account;
+mod get_backend_info;
+#[cfg(zallet_build = "wallet")]
+mod get_balance;
+#[cfg(zallet_build = "wallet")]
+mod get_balance_for_account;
+#[cfg(zallet_build = "wallet")]
+mod get_migration_status;
 #[cfg(zallet_build = "wallet")]
 mod get_new_account;
 #[cfg(zallet_build = "wallet")]
+mod get_new_address;
+#[cfg(zallet_build = "wallet")]
 mod get_notes_count;
 #[cfg(zallet_build = "wallet")]
 mod get_operation;
 mod get_raw_transaction;
+mod get_transaction;
 #[cfg(zallet_build = "wallet")]
 mod get_wallet_info;
 #[cfg(zallet_build = "wallet")]
 mod help;
+#[cfg(zallet_build = "wallet")]
+mod import_key;
+mod import_viewing_key;
+#[cfg(zallet_build = "wallet")]
+mod import_wallet;
 mod list_accounts;
 mod list_addresses;
 #[cfg(zallet_build = "wallet")]
 mod list_operation_ids;
+mod list_received_by_address;
+mod list_since_block;
 mod list_transactions;
+#[cfg(zallet_build = "wallet")]
+mod list_transparent_unspent;
 mod list_unified_receivers;
 #[cfg(zallet_build = "wallet")]
 mod list_unspent;
+mod list_wallet_history;
+#[cfg(zallet_build = "wallet")]
+mod list_wallets;
+#[cfg(zallet_build = "wallet")]
+mod load_wallet;
 #[cfg(zallet_build = "wallet")]
 mod lock_wallet;
 #[cfg(zallet_build = "wallet")]
-mod openrpc;
+pub(crate) mod openrpc;
+mod preview_address_for_account;
 #[cfg(zallet_build = "wallet")]
 mod recover_accounts;
+#[cfg(zallet_build = "wallet")]
+mod rename_account;
+mod reopen_logs;
+mod rescan_blockchain;
+#[cfg(zallet_build = "wallet")]
+mod resend_wallet_transactions;
+#[cfg(zallet_build = "wallet")]
+mod search_memos;
+#[cfg(zallet_build = "wallet")]
+mod sign_message;
 mod stop;
+mod subscribe_transactions;
+#[cfg(zallet_build = "wallet")]
+mod sweep_priv_key;
+#[cfg(test)]
+mod testing;
+#[cfg(zallet_build = "wallet")]
+mod unload_wallet;
 #[cfg(zallet_build = "wallet")]
 mod unlock_wallet;
+mod validate_address;
 mod verify_message;
 mod view_transaction;
 #[cfg(zallet_build = "wallet")]
+pub(super) mod z_combine_pczts;
+#[cfg(zallet_build = "wallet")]
+mod z_create_pczt;
+#[cfg(zallet_build = "wallet")]
+mod z_execute_proposal;
+#[cfg(zallet_build = "wallet")]
+mod z_finalize_pczt;
+mod z_get_sync_status;
+#[cfg(zallet_build = "wallet")]
 mod z_get_total_balance;
 #[cfg(zallet_build = "wallet")]
+mod z_merge_to_address;
+#[cfg(zallet_build = "wallet")]
+mod z_payment_uri_decode;
+#[cfg(zallet_build = "wallet")]
+mod z_payment_uri_encode;
+#[cfg(zallet_build = "wallet")]
+mod z_propose_transfer;
+#[cfg(zallet_build = "wallet")]
+pub(super) mod z_prove_pczt;
+#[cfg(zallet_build = "wallet")]
 mod z_send_many;
+#[cfg(zallet_build = "wallet")]
+mod z_send_payment_uri;
+#[cfg(zallet_build = "wallet")]
+mod z_shield_coinbase;
+
+/// Whether a JSON-RPC method can change the wallet.
+///
+/// Each method's module declares the method's category in a `CATEGORY` constant, which
+/// `build.rs` requires to be present.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MethodCategory {
+    /// The method only queries the wallet or the chain.
+    Query,
+    /// The method can move funds, import, export, or sign with keys, lock or unlock the
+    /// wallet, change the loaded wallets, or otherwise change the state of Zallet.
+    Mutating,
+}
+
+/// The methods that manage subscriptions, which are only available over WebSocket
+/// connections.
+pub(crate) const SUBSCRIPTION_METHODS: &[&str] =
+    &["subscribe_transactions", "unsubscribe_transactions"];
+
+/// Returns the category of the given method, or `None` if Zallet does not provide it.
+#[cfg(zallet_build = "wallet")]
+pub(crate) fn category(method: &str) -> Option<MethodCategory> {
+    openrpc::METHODS.get(method).map(|method| method.category)
+}
 
 /// The general JSON-RPC interface, containing the methods provided in all Zallet builds.
 #[rpc(server)]
 pub(crate) trait Rpc {
-    /// Returns the list of accounts created with `z_getnewaccount` or `z_recoveraccounts`.
+    /// Returns the list of accounts created with `z_getnewaccount`, `z_recoveraccounts`,
+    /// or `z_importviewingkey`.
     ///
     /// # Arguments
     /// - `include_addresses` (bool, optional, default=true) Also include the addresses
@@ -80,6 +206,8 @@ pub(crate) trait Rpc {
     /// - If no list of receiver types is given (or the empty list `[]`), the best and
     ///   second-best shielded receiver types, along with the "p2pkh" (i.e. transparent)
     ///   receiver type, will be used.
+    /// - A diversifier index can be given as a JSON number, a decimal string, or a
+    ///   `0x`-prefixed hex string encoding the index in little-endian byte order.
     /// - If no diversifier index is given, then:
     ///   - If a transparent receiver would be included (either because no list of
     ///     receiver types is given, or the provided list includes "p2pkh"), the next
@@ -87,9 +215,11 @@ pub(crate) trait Rpc {
     ///   - If only shielded receivers would be included (because a list of receiver types
     ///     is given that does not include "p2pkh"), a time-based index will be selected.
     ///
-    /// The account parameter must be a UUID or account number that was previously
-    /// generated by a call to the `z_getnewaccount` RPC method. The legacy account number
-    /// is only supported for wallets containing a single seed phrase.
+    /// The account parameter must be a UUID, name, or account number of an account that
+    /// was previously generated by a call to the `z_getnewaccount` RPC method. A name is
+    /// only accepted if exactly one account has it. The legacy account number is resolved
+    /// against the seed configured by `keystore.default_seed_fingerprint`, and is otherwise
+    /// only supported for wallets containing a single seed phrase.
     ///
     /// Once a Unified Address has been derived at a specific diversifier index,
     /// re-deriving it (via a subsequent call to `z_getaddressforaccount` with the same
@@ -97,14 +227,45 @@ pub(crate) trait Rpc {
     /// types. An error will be returned if a different list of receiver types is
     /// requested, including when the empty list `[]` is provided (if the default receiver
     /// types don't match).
+    ///
+    /// The response includes the diversifier index of the address, and the receiver types
+    /// that it contains. Passing these back to `z_getaddressforaccount` re-derives the same
+    /// address.
     #[method(name = "z_getaddressforaccount")]
     async fn get_address_for_account(
         &self,
         account: JsonValue,
         receiver_types: Option<Vec<String>>,
-        diversifier_index: Option<u128>,
+        diversifier_index: Option<JsonValue>,
     ) -> get_address_for_account::Response;
 
+    /// Returns the next address of the given account that has not been handed out,
+    /// without recording it.
+    ///
+    /// The response has the same form as that of `z_getaddressforaccount`. To hand the
+    /// address out, pass its diversifier index and receiver types to
+    /// `z_getaddressforaccount`; until then, this method may return the same address
+    /// again.
+    ///
+    /// - If a transparent receiver would be included, the address is derived at the
+    ///   lowest transparent address index that has been neither handed out nor used.
+    /// - If only shielded receivers would be included, the address is derived at the
+    ///   first valid diversifier index above those that have been handed out. This is not
+    ///   the time-based index that `z_getaddressforaccount` selects when no diversifier
+    ///   index is given.
+    ///
+    /// # Arguments
+    /// - `account` (string or numeric, required) The UUID, name, or ZIP 32 account index
+    ///   of the account, as for `z_getaddressforaccount`.
+    /// - `receiver_types` (array, optional) The receiver types to include in the address,
+    ///   as for `z_getaddressforaccount`.
+    #[method(name = "z_previewaddressforaccount")]
+    async fn preview_address_for_account(
+        &self,
+        account: JsonValue,
+        receiver_types: Option<Vec<String>>,
+    ) -> preview_address_for_account::Response;
+
     /// Lists the addresses managed by this wallet by source.
     ///
     /// Sources include:
@@ -115,17 +276,72 @@ pub(crate) trait Rpc {
     /// - Addresses derived from mnemonic seed phrases.
     ///
     /// In the case that a source does not have addresses for a value pool, the key
-    /// associated with that pool will be absent.
+    /// associated with that pool will be absent. Sources without any addresses are
+    /// omitted, so filters that match nothing return an empty list.
     ///
     /// REMINDER: It is recommended that you back up your wallet files regularly. If you
     /// have not imported externally-produced keys, it only necessary to have backed up
     /// the wallet's key storage file.
+    ///
+    /// # Arguments
+    /// - `account` (string or numeric, optional) The UUID, name, or ZIP 32 account index
+    ///   of the account whose addresses to list. If omitted, every account is listed.
+    /// - `source` (string, optional) Only list addresses from this source. One of
+    ///   "legacy_random", "imported", "imported_watchonly", "legacy_hdseed", or
+    ///   "mnemonic_seed".
+    /// - `include_diversifier_indices` (boolean, optional, default=false) Include the
+    ///   diversifier index of each Unified Address, in the form accepted by the
+    ///   `diversifier_index` parameter of `z_getaddressforaccount`.
+    /// - `offset` (numeric, optional, default=0) The number of addresses to skip within
+    ///   each account.
+    /// - `limit` (numeric, optional) The maximum number of addresses to list for each
+    ///   account. If omitted, all remaining addresses are listed.
     #[method(name = "listaddresses")]
-    async fn list_addresses(&self) -> list_addresses::Response;
+    async fn list_addresses(
+        &self,
+        account: Option<JsonValue>,
+        source: Option<String>,
+        include_diversifier_indices: Option<bool>,
+        offset: Option<u32>,
+        limit: Option<u32>,
+    ) -> list_addresses::Response;
+
+    /// Imports a viewing key as a new watch-only account.
+    ///
+    /// The key can be a unified full viewing key, or a legacy Sapling extended full
+    /// viewing key. If the key is already in the wallet, the UUID of its existing account
+    /// is returned instead.
+    ///
+    /// The wallet scans the chain for the account's transactions from its birthday
+    /// height. A rescan only happens if `rescan` is `"yes"`, or if `start_height` is below
+    /// the wallet's chain tip; otherwise the birthday is set to the chain tip.
+    ///
+    /// # Arguments
+    /// - `vkey` (string, required) The viewing key to import.
+    /// - `rescan` (string, optional, default="whenkeyisnew") Whether to rescan the chain
+    ///   for transactions: "yes", "no", or "whenkeyisnew". With "yes", the rescan starts
+    ///   from `start_height` if given, or otherwise from Sapling activation.
+    /// - `start_height` (numeric, optional) The birthday height of the viewing key.
+    #[method(name = "z_importviewingkey")]
+    async fn import_viewing_key(
+        &self,
+        vkey: &str,
+        rescan: Option<&str>,
+        start_height: Option<u32>,
+    ) -> import_viewing_key::Response;
+
+    /// Returns the unified full viewing key of the given account.
+    ///
+    /// # Arguments
+    /// - `account` (string or numeric, required) The UUID, name, or ZIP 32 account index
+    ///   of the account.
+    #[method(name = "z_exportviewingkey")]
+    async fn export_viewing_key(&self, account: JsonValue) -> export_viewing_key::Response;
 
     /// Returns a record of the individual receivers contained within the provided UA,
     /// keyed by receiver type. The UA may not have receivers for some receiver types,
-    /// in which case those keys will be absent.
+    /// in which case those keys will be absent. Items with typecodes that Zallet does
+    /// not recognise are listed under the `unknown` key, with their typecode and data.
     ///
     /// Transactions that send funds to any of the receivers returned by this RPC
     /// method will be detected by the wallet as having been sent to the unified
@@ -139,6 +355,38 @@ pub(crate) trait Rpc {
         unified_address: &str,
     ) -> list_unified_receivers::Response;
 
+    /// Returns information about the given address.
+    ///
+    /// Any Zcash address can be given. If it is not a valid address for the wallet's
+    /// network, `isvalid` is `false` and `reason` explains why, rather than the method
+    /// returning an error. For a valid address, the result identifies its type, lists
+    /// the receivers of a unified address (as `z_listunifiedreceivers` does), and
+    /// reports whether any of its receivers belong to an account in the wallet.
+    ///
+    /// # Arguments
+    /// - `address` (string, required) The address to validate.
+    #[method(name = "z_validateaddress")]
+    async fn validate_address(&self, address: &str) -> validate_address::Response;
+
+    /// Returns the outputs that the wallet received on the given address.
+    ///
+    /// If `address` is a unified address, outputs received on any of its receivers are
+    /// returned, with the `pool` of each output identifying the receiver. Outputs that
+    /// were received on a different address sharing one of these receivers (such as the
+    /// same Sapling receiver in another unified address) are included.
+    ///
+    /// # Arguments
+    /// - `address` (string, required) An address belonging to the wallet, including
+    ///   addresses of watch-only accounts.
+    /// - `minconf` (numeric, optional, default=1) Only include outputs confirmed at least
+    ///   this many times.
+    #[method(name = "z_listreceivedbyaddress")]
+    async fn list_received_by_address(
+        &self,
+        address: &str,
+        minconf: Option<u32>,
+    ) -> list_received_by_address::Response;
+
     /// Returns a list of the wallet's transactions, optionally filtered by account and block
     /// range.
     ///
@@ -153,9 +401,26 @@ pub(crate) trait Rpc {
     ///   in those blocks should be returned. If omitted, return all transactions mined or created
     ///   above the start height.
     /// - `offset`: An optional number of transactions to skip over before a page of results is
-    ///   returned. Defaults to zero.
+    ///   returned. Defaults to zero. Cannot be combined with `cursor`.
     /// - `limit`: An optional upper bound on the number of results that should be returned in a
-    ///   page.  
+    ///   page. When `cursor` is provided, this is the page size (default = 1000).
+    /// - `as_of_height`: Execute the query as if it were run when the blockchain was at the height
+    ///   specified by this argument. Only transactions mined at or below that height are
+    ///   returned. The default is to use the entire blockchain that the wallet is aware of,
+    ///   including the mempool. -1 can be used as in other RPC calls to indicate the current
+    ///   height, and other negative values are offsets from the current height (-2 is the block
+    ///   below it). Heights above the current height are rejected.
+    /// - `include_watchonly`: Also include transactions that only affect watch-only accounts.
+    ///   Defaults to `false`, except in the merchant terminal build where every account is
+    ///   watch-only. Entries for watch-only accounts have `involvesWatchonly` set.
+    /// - `cursor`: If provided, return a page of results as an object with `results` and
+    ///   `next_cursor` fields, instead of an array. Pass the empty string to fetch the first
+    ///   page, and the returned `next_cursor` to fetch each following page; `next_cursor` is
+    ///   `null` on the last page. Pages remain consistent if the wallet gains transactions
+    ///   while they are being fetched.
+    ///
+    /// Without a cursor, the request fails if there are more than `rpc.max_list_results`
+    /// results.
     ///
     /// WARNING: This is currently an experimental feature; arguments and result data may change at
     /// any time.
@@ -167,8 +432,60 @@ pub(crate) trait Rpc {
         end_height: Option<u32>,
         offset: Option<u32>,
         limit: Option<u32>,
+        as_of_height: Option<i64>,
+        include_watchonly: Option<bool>,
+        cursor: Option<String>,
     ) -> list_transactions::Response;
 
+    /// Returns up to `count` of the most recent wallet history entries, skipping the
+    /// `from` most recent entries. Entries are ordered from oldest to newest, as in
+    /// `zcashd`.
+    ///
+    /// Each output that an account in the wallet sent or received (other than change) is
+    /// a separate entry. Unlike `zcashd`, this includes shielded outputs; their memos and
+    /// other details are available from `z_viewtransaction`.
+    ///
+    /// # Arguments
+    /// - `dummy` (string, optional) Remains for backward compatibility. Must be excluded
+    ///   or set to "*" or "".
+    /// - `count` (numeric, optional, default=10) The number of entries to return.
+    /// - `from` (numeric, optional, default=0) The number of most recent entries to skip.
+    ///   If fewer entries remain than `count`, only those are returned.
+    /// - `include_watchonly` (bool, optional, default=false) Also include entries for
+    ///   watch-only accounts, whose spending keys the wallet cannot derive.
+    #[method(name = "listtransactions")]
+    async fn list_wallet_history(
+        &self,
+        dummy: Option<String>,
+        count: Option<u32>,
+        from: Option<u32>,
+        include_watchonly: Option<bool>,
+    ) -> list_wallet_history::Response;
+
+    /// Returns the wallet history entries for transactions that are unmined, or were mined
+    /// after the block `blockhash`, ordered from oldest to newest.
+    ///
+    /// If `blockhash` has been removed from the chain by a reorg, `transactions` contains
+    /// the entries since the point where its chain forked from the current chain, and
+    /// `removed` contains the entries for wallet transactions that were mined in the
+    /// removed blocks. Callers that only track `lastblock` between calls will therefore
+    /// learn about transactions that a reorg has unmined.
+    ///
+    /// # Arguments
+    /// - `blockhash` (string, optional) The block hash to list transactions since. If
+    ///   omitted or empty, all transactions are listed.
+    /// - `target_confirmations` (numeric, optional, default=1) The number of
+    ///   confirmations of the block returned as `lastblock`. Must be at least 1.
+    /// - `include_watchonly` (bool, optional, default=false) Also include entries for
+    ///   watch-only accounts, whose spending keys the wallet cannot derive.
+    #[method(name = "listsinceblock")]
+    async fn list_since_block(
+        &self,
+        blockhash: Option<String>,
+        target_confirmations: Option<u32>,
+        include_watchonly: Option<bool>,
+    ) -> list_since_block::Response;
+
     /// Returns the raw transaction data for the given transaction ID.
     ///
     /// NOTE: If `blockhash` is provided, only that block will be searched, and if the
@@ -199,15 +516,100 @@ pub(crate) trait Rpc {
     #[method(name = "z_viewtransaction")]
     async fn view_transaction(&self, txid: &str) -> view_transaction::Response;
 
-    /// Stop the running zallet process.
+    /// Returns detailed information about in-wallet transaction `txid`, in the format
+    /// used by `zcashd`.
+    ///
+    /// The `details` field has an entry for each output that an account in the wallet
+    /// sent or received (other than change), in the same format as `listtransactions`.
+    /// For information about spends and memos, use `z_viewtransaction`.
+    ///
+    /// # Arguments
+    /// - `txid` (string, required) The transaction ID.
+    /// - `include_watchonly` (bool, optional, default=false) Also include the effects of
+    ///   the transaction on watch-only accounts, whose spending keys the wallet cannot
+    ///   derive.
+    #[method(name = "gettransaction")]
+    async fn get_transaction(
+        &self,
+        txid: &str,
+        include_watchonly: Option<bool>,
+    ) -> get_transaction::Response;
+
+    /// Returns information about the chain sources that Zallet uses to scan the chain and
+    /// to broadcast transactions.
+    ///
+    /// For each chain source, this reports its address, the status of the connection, the
+    /// chain tip it last reported, how quickly it responded, when it last responded
+    /// successfully, the most recent error, and whether it is currently used for scanning
+    /// or broadcast (and why).
+    ///
+    /// Zallet currently connects to a single full node, so exactly one chain source is
+    /// returned.
+    #[method(name = "getbackendinfo")]
+    async fn get_backend_info(&self) -> get_backend_info::Response;
+
+    /// Returns how far the wallet has synced with the chain.
+    ///
+    /// This reports the height below which the wallet has scanned every block, the latest
+    /// chain tip reported by the chain source, the estimated percentage of the wallet's
+    /// history that has been scanned, the number of blocks left to scan, the number of
+    /// blocks scanned per second over the last minute, and the chain source in use.
+    ///
+    /// The `state` field is one of:
+    /// - `connecting`: the chain source has not been reached yet.
+    /// - `initializing`: the wallet is preparing to sync, for example by downloading the
+    ///   note commitment tree state.
+    /// - `scanning`: the wallet is scanning blocks.
+    /// - `rescanning`: the wallet is rescanning blocks, as requested with
+    ///   `rescanblockchain`. The progress of the rescan is reported in the `rescan` field.
+    /// - `synced`: the wallet has scanned every block up to the chain tip.
+    #[method(name = "z_getsyncstatus")]
+    async fn get_sync_status(&self) -> z_get_sync_status::Response;
+
+    /// Rescans the chain for transactions involving the wallet, from `start_height` to
+    /// `stop_height` (inclusive).
+    ///
+    /// `start_height` defaults to the wallet's birthday, and heights below it are not
+    /// rescanned. `stop_height` defaults to 100 blocks below the chain tip; the wallet
+    /// already rescans the blocks above that whenever the chain reorganizes.
+    ///
+    /// This returns the heights of the first and last blocks that will be rescanned as
+    /// soon as the rescan is scheduled, and the blocks are then rescanned in the
+    /// background. Its progress is reported by `z_getsyncstatus`. Only one rescan can be
+    /// in progress at a time; calling this while the wallet is rescanning returns an
+    /// error.
+    ///
+    /// # Arguments
+    /// - `start_height` (numeric, optional) The height of the first block to rescan.
+    /// - `stop_height` (numeric, optional) The height of the last block to rescan.
+    #[method(name = "rescanblockchain")]
+    async fn rescan_blockchain(
+        &self,
+        start_height: Option<u32>,
+        stop_height: Option<u32>,
+    ) -> rescan_blockchain::Response;
+
+    /// Stops Zallet.
     ///
-    /// # Notes
+    /// This starts the same orderly shutdown as an interrupt or termination signal: the
+    /// JSON-RPC server stops accepting connections, in-flight requests and async
+    /// operations are given up to `rpc.shutdown_timeout` to finish, the RPC cookie file
+    /// is removed, and the wallet databases are closed. If Zallet has not stopped within
+    /// `external.force_shutdown_timeout`, it exits anyway.
     ///
-    /// - Works for non windows targets only.
-    /// - Works only if the network of the running zallet process is `Regtest`.
+    /// Returns an acknowledgement once the shutdown has been requested.
     #[method(name = "stop")]
     async fn stop(&self) -> stop::Response;
 
+    /// Reopens the log file.
+    ///
+    /// This is used after the log file has been renamed by an external tool such as
+    /// `logrotate`, so that Zallet continues logging to a new file at the configured
+    /// path. It has the same effect as sending `SIGHUP` to Zallet, and does nothing if
+    /// Zallet is not logging to a file.
+    #[method(name = "reopenlogs")]
+    async fn reopen_logs(&self) -> reopen_logs::Response;
+
     /// Verify a signed message.
     ///
     /// # Arguments
@@ -232,6 +634,22 @@ pub(crate) trait Rpc {
     /// - `transparent_address` (string, required): The transparent P2PKH address to convert.
     #[method(name = "z_converttex")]
     async fn convert_tex(&self, transparent_address: &str) -> convert_tex::Response;
+
+    /// Subscribes to changes to the wallet's transactions.
+    ///
+    /// A `transaction` notification is sent when a wallet transaction is first detected,
+    /// when it is mined, and when it becomes conflicted (because a transaction spending
+    /// the same inputs was mined). Cancel the subscription with
+    /// `unsubscribe_transactions`.
+    ///
+    /// Subscriptions are only available over WebSocket connections. A subscriber that
+    /// cannot keep up misses the oldest notifications, rather than delaying wallet sync.
+    #[subscription(
+        name = "subscribe_transactions" => "transaction",
+        unsubscribe = "unsubscribe_transactions",
+        item = subscribe_transactions::Notification
+    )]
+    async fn subscribe_transactions(&self) -> SubscriptionResult;
 }
 
 /// The wallet-specific JSON-RPC interface, containing the methods only provided in the
@@ -270,6 +688,7 @@ pub(crate) trait WalletRpc {
     /// # Arguments
     /// - `operationid` (array, optional) A list of operation ids we are interested in.
     ///   If not provided, examine all operations known to the node.
+    ///   Operation ids that are not known to the wallet are omitted from the result.
     #[method(name = "z_getoperationstatus")]
     async fn get_operation_status(&self, operationid: Vec<OperationId>) -> get_operation::Response;
 
@@ -283,6 +702,8 @@ pub(crate) trait WalletRpc {
     /// # Arguments
     /// - `operationid` (array, optional) A list of operation ids we are interested in.
     ///   If not provided, retrieve all finished operations known to the node.
+    ///   Operation ids that are not known to the wallet, or whose operations have not
+    ///   yet finished, are omitted from the result.
     #[method(name = "z_getoperationresult")]
     async fn get_operation_result(&self, operationid: Vec<OperationId>) -> get_operation::Response;
 
@@ -297,6 +718,13 @@ pub(crate) trait WalletRpc {
     ///
     /// Issuing the `walletpassphrase` command while the wallet is already unlocked will
     /// set a new unlock time that overrides the old one.
+    ///
+    /// `timeout` is limited to the `keystore.max_unlock_duration` config option. If the
+    /// `keystore.allow_unlock` config option is disabled, this method always fails.
+    ///
+    /// If the unlock time passes while an async operation that was started with the
+    /// wallet unlocked is still running, the wallet is relocked once the operation
+    /// finishes.
     #[method(name = "walletpassphrase")]
     async fn unlock_wallet(
         &self,
@@ -308,11 +736,207 @@ pub(crate) trait WalletRpc {
     ///
     /// After calling this method, you will need to call `walletpassphrase` again before
     /// being able to call any methods which require the wallet to be unlocked.
+    ///
+    /// The wallet is locked immediately, even if async operations are running. Those
+    /// operations fail if they need to decrypt key material after this.
     #[method(name = "walletlock")]
     async fn lock_wallet(&self) -> lock_wallet::Response;
 
+    /// Changes the wallet encryption passphrase from `oldpassphrase` to `newpassphrase`.
+    ///
+    /// The passphrase encrypts the wallet's age identity file, which is re-encrypted and
+    /// replaced on disk. Key material in the wallet database is encrypted to the
+    /// identity's recipients, and does not need to be re-encrypted. This does not change
+    /// whether the wallet is currently unlocked.
+    ///
+    /// # Arguments
+    /// - `oldpassphrase` (string, required) The current passphrase.
+    /// - `newpassphrase` (string, required) The new passphrase.
+    #[method(name = "walletpassphrasechange")]
+    async fn change_wallet_passphrase(
+        &self,
+        oldpassphrase: age::secrecy::SecretString,
+        newpassphrase: age::secrecy::SecretString,
+    ) -> change_wallet_passphrase::Response;
+
+    /// Confirms that the user has backed up one of the wallet's mnemonic seed phrases.
+    ///
+    /// While `keystore.require_backup` is enabled (the default), `z_getnewaccount` will
+    /// not derive accounts from a mnemonic until its backup has been confirmed. Mnemonics
+    /// added with `zallet import-mnemonic` or `zallet migrate-zcashd-wallet` are already
+    /// backed up; use `zallet export-mnemonic` to back up a mnemonic that Zallet
+    /// generated, and then call this method with the mnemonic phrase to confirm that the
+    /// backup is correct.
+    ///
+    /// This does not require the wallet to be unlocked.
+    ///
+    /// # Arguments
+    /// - `mnemonic` (string, required) The mnemonic seed phrase, as written down by the
+    ///   user.
+    #[method(name = "walletconfirmbackup")]
+    async fn confirm_backup(
+        &self,
+        mnemonic: age::secrecy::SecretString,
+    ) -> confirm_backup::Response;
+
+    /// Exports the wallet's keys and account metadata to a file.
+    ///
+    /// The file is written to the directory configured by `external.export_dir`, which
+    /// must be set. The export is a versioned text dump containing the wallet's mnemonic
+    /// seed phrases, its accounts (with their viewing keys and birthday heights), the
+    /// addresses that have been generated for them, and any imported spending keys. It is
+    /// the same as the export written by `z_exportwallet`.
+    ///
+    /// The export is encrypted with age to the wallet's own recipients, or to the given
+    /// recipients, unless `allow_plaintext` is true. The wallet must be unlocked, so
+    /// that its secrets can be exported. An existing file is never overwritten.
+    ///
+    /// Returns the absolute path of the written file.
+    ///
+    /// # Arguments
+    /// - `filename` (string, required) The name of the file to write, relative to
+    ///   `external.export_dir`. It must not be an absolute path, or contain `..`
+    ///   components.
+    /// - `recipients` (array of strings, optional) The age recipients to encrypt the
+    ///   export to. Defaults to the wallet's own recipients, so that only this wallet's
+    ///   age identity can decrypt it.
+    /// - `allow_plaintext` (boolean, optional, default=false) If true, write the export
+    ///   in plaintext instead. This cannot be combined with `recipients`.
+    #[method(name = "backupwallet")]
+    async fn backup_wallet(
+        &self,
+        filename: &str,
+        recipients: Option<Vec<String>>,
+        allow_plaintext: Option<bool>,
+    ) -> export_wallet::Response;
+
+    /// Exports the wallet's keys and account metadata to a file.
+    ///
+    /// This is the same as `backupwallet`; see its documentation for details.
+    ///
+    /// # Arguments
+    /// - `filename` (string, required) The name of the file to write, relative to
+    ///   `external.export_dir`. It must not be an absolute path, or contain `..`
+    ///   components.
+    /// - `recipients` (array of strings, optional) The age recipients to encrypt the
+    ///   export to. Defaults to the wallet's own recipients.
+    /// - `allow_plaintext` (boolean, optional, default=false) If true, write the export
+    ///   in plaintext instead.
+    #[method(name = "z_exportwallet")]
+    async fn export_wallet(
+        &self,
+        filename: &str,
+        recipients: Option<Vec<String>>,
+        allow_plaintext: Option<bool>,
+    ) -> export_wallet::Response;
+
+    /// Exports the wallet's transaction history to a CSV file.
+    ///
+    /// The file is written to the directory configured by `external.export_dir`, which
+    /// must be set. It has a row for each output that an account in the wallet sent or
+    /// received (other than change), and a row for each fee that an account paid, with
+    /// the columns `timestamp`, `block_height`, `txid`, `account_uuid`, `address`,
+    /// `pool`, `direction` (`send`, `receive`, or `fee`), `amount_zat`, `amount_zec`,
+    /// and `memo`. Text memos are written as text, and other memos are hex-encoded. An
+    /// existing file is never overwritten.
+    ///
+    /// Returns the number of rows written and the absolute path of the file.
+    ///
+    /// # Arguments
+    /// - `filename` (string, required) The name of the file to write, relative to
+    ///   `external.export_dir`. It must not be an absolute path, or contain `..`
+    ///   components.
+    /// - `start_height` (numeric, optional) The inclusive lower bound of block heights
+    ///   for which transactions mined at those heights are exported.
+    /// - `end_height` (numeric, optional) The exclusive upper bound of block heights for
+    ///   which transactions mined at those heights are exported. If omitted, unmined
+    ///   transactions are also exported.
+    /// - `account` (string or numeric, optional) The UUID, name, or ZIP 32 account index
+    ///   of the account to export the history of. If omitted, the history of every
+    ///   account is exported.
+    #[method(name = "z_exporthistory")]
+    async fn export_history(
+        &self,
+        filename: &str,
+        start_height: Option<u32>,
+        end_height: Option<u32>,
+        account: Option<JsonValue>,
+    ) -> export_history::Response;
+
+    /// Imports a wallet dump written by `backupwallet` or `z_exportwallet`.
+    ///
+    /// Seeds, accounts, addresses, and imported spending keys that are already in the
+    /// wallet are skipped, so importing the same dump twice has no further effect. Seeds
+    /// imported from the dump are treated as backed up. The wallet scans the chain for
+    /// newly imported accounts from their birthday heights.
+    ///
+    /// A dump that was encrypted with age is decrypted with the wallet's age identity.
+    ///
+    /// The dump is rejected if it is for a different network, or if any of its mnemonic
+    /// phrases is invalid. Records that this version of Zallet does not recognise are
+    /// ignored.
+    ///
+    /// Returns the number of seeds, accounts, and standalone keys that were imported or
+    /// skipped.
+    ///
+    /// # Arguments
+    /// - `path` (string, required) The wallet dump file. Relative paths are resolved
+    ///   against `external.export_dir`.
+    #[method(name = "z_importwallet")]
+    async fn import_wallet(&self, path: &str) -> import_wallet::Response;
+
+    /// Imports a spending key as a new account.
+    ///
+    /// The key can be a legacy Sapling extended spending key, or a hex-encoded unified
+    /// spending key as returned by `z_exportkey`. If the key is already held by the
+    /// wallet, including as part of an account derived from one of its seeds, no account
+    /// is created and the existing account is returned with a `status` saying why.
+    ///
+    /// The wallet must be unlocked. Funds received by an imported Sapling key are
+    /// watch-only, because transactions can only be created with unified spending keys.
+    ///
+    /// # Arguments
+    /// - `key` (string, required) The spending key to import.
+    /// - `rescan` (string, optional, default="whenkeyisnew") Whether to rescan the chain
+    ///   for transactions: "yes", "no", or "whenkeyisnew". With "yes", the rescan starts
+    ///   from `start_height` if given, or otherwise from Sapling activation.
+    /// - `start_height` (numeric, optional) The birthday height of the spending key.
+    #[method(name = "z_importkey")]
+    async fn import_key(
+        &self,
+        key: &str,
+        rescan: Option<&str>,
+        start_height: Option<u32>,
+    ) -> import_key::Response;
+
+    /// Returns the spending key for an address held by the wallet.
+    ///
+    /// For a unified address, this is the hex-encoded unified spending key of the
+    /// address's account. For a Sapling address, this is a Sapling extended spending key.
+    /// Keys are never exported for watch-only accounts, and the wallet must be unlocked.
+    ///
+    /// # Arguments
+    /// - `address` (string, required) The Sapling or unified address.
+    #[method(name = "z_exportkey")]
+    async fn export_key(&self, address: &str) -> export_key::Response;
+
+    /// Signs a message with the private key of a transparent address.
+    ///
+    /// The signature uses the same scheme as `zcashd`, and can be checked with
+    /// `verifymessage`. The address must be a P2PKH address held by a non-watch-only
+    /// account, and the wallet must be unlocked.
+    ///
+    /// # Arguments
+    /// - `t_addr` (string, required) The transparent address to sign with.
+    /// - `message` (string, required) The message to sign.
+    #[method(name = "signmessage")]
+    async fn sign_message(&self, t_addr: &str, message: &str) -> sign_message::Response;
+
     /// Prepares and returns a new account.
     ///
+    /// If `keystore.require_backup` is enabled (the default), the backup of the mnemonic
+    /// seed phrase must first have been confirmed with `walletconfirmbackup`.
+    ///
     /// If the wallet contains more than one UA-compatible HD seed phrase, the `seedfp`
     /// argument must be provided. Available seed fingerprints can be found in the output
     /// of the `listaddresses` RPC method.
@@ -320,17 +944,80 @@ pub(crate) trait WalletRpc {
     /// Within a UA-compatible HD seed phrase, accounts are numbered starting from zero;
     /// this RPC method selects the next available sequential account number.
     ///
+    /// The wallet scans the chain for the account's transactions from the birthday given
+    /// to `zallet import-mnemonic --birthday` for its seed phrase, or otherwise from the
+    /// current chain tip.
+    ///
     /// Each new account is a separate group of funds within the wallet, and adds an
     /// additional performance cost to wallet scanning.
     ///
     /// Use the `z_getaddressforaccount` RPC method to obtain addresses for an account.
+    ///
+    /// Account names need not be unique, unless `strict_unique` is `true`. They are at
+    /// most 100 characters long, and can be changed with `z_renameaccount`.
     #[method(name = "z_getnewaccount")]
     async fn get_new_account(
         &self,
         account_name: &str,
         seedfp: Option<&str>,
+        strict_unique: Option<bool>,
     ) -> get_new_account::Response;
 
+    /// Changes the name of an account.
+    ///
+    /// Account names are for display, and need not be unique unless `strict_unique` is
+    /// `true`. They are at most 100 characters long, and must not contain control
+    /// characters. The account's UUID is unaffected.
+    ///
+    /// # Arguments
+    /// - `account` (string or numeric, required) The UUID, name, or ZIP 32 account index
+    ///   of the account.
+    /// - `name` (string, required) The new name for the account.
+    /// - `strict_unique` (boolean, optional, default=false) If true, fail if another
+    ///   account already has the name.
+    #[method(name = "z_renameaccount")]
+    async fn rename_account(
+        &self,
+        account: JsonValue,
+        name: &str,
+        strict_unique: Option<bool>,
+    ) -> rename_account::Response;
+
+    /// Returns a new transparent address for receiving payments.
+    ///
+    /// The address is the next unused address of the legacy transparent account (BIP 44
+    /// account 0x7fffffff) of the wallet's default seed, from which `zcashd` derived the
+    /// addresses returned by this method. The legacy account is created if the wallet does
+    /// not already contain it. Alternatively, the address can be derived from a specific
+    /// account.
+    ///
+    /// The address is recorded in the wallet, and will be listed by `listaddresses`. An
+    /// address is never returned twice, but only a limited number of addresses beyond the
+    /// last address to receive funds can be generated (the transparent gap limit), to
+    /// ensure that the wallet can be recovered from its seed phrase.
+    ///
+    /// If `keystore.require_backup` is enabled (the default), the backup of the mnemonic
+    /// seed phrase must first have been confirmed with `walletconfirmbackup`.
+    ///
+    /// # Arguments
+    /// - `account` (string, optional) The UUID or name of the account to derive the
+    ///   address from. If omitted or empty (`""`, as `zcashd` required), the legacy
+    ///   transparent account is used.
+    #[method(name = "getnewaddress")]
+    async fn get_new_address(&self, account: Option<String>) -> get_new_address::Response;
+
+    /// Returns a new transparent change address.
+    ///
+    /// This is intended for use with raw transactions, and is otherwise the same as
+    /// `getnewaddress`, except that the address is derived from the account's internal
+    /// (change) key.
+    ///
+    /// # Arguments
+    /// - `account` (string, optional) The UUID or name of the account to derive the
+    ///   address from. If omitted or empty, the legacy transparent account is used.
+    #[method(name = "getrawchangeaddress")]
+    async fn get_raw_change_address(&self, account: Option<String>) -> get_new_address::Response;
+
     /// Tells the wallet to track specific accounts.
     ///
     /// Returns the UUIDs within this Zallet instance of the newly-tracked accounts.
@@ -363,15 +1050,21 @@ pub(crate) trait WalletRpc {
 
     /// Returns the total value of funds stored in the node's wallet.
     ///
-    /// TODO: Currently watchonly addresses cannot be omitted; `include_watchonly` must be
-    /// set to true.
+    /// The value is summed across every account in the wallet, and reported for the
+    /// transparent pool, for each shielded pool (`sapling` and `orchard`), for the
+    /// shielded pools together (`private`), and in total. Amounts are decimal strings
+    /// in ZEC, with the equivalent values in zatoshis in the `*Zat` fields. Immature
+    /// coinbase outputs, and funds that are being spent by unmined transactions, are not
+    /// counted.
     ///
     /// # Arguments
     ///
     /// - `minconf` (numeric, optional, default=1) Only include private and transparent
-    ///   transactions confirmed at least this many times.
+    ///   transactions confirmed at least this many times. Funds in unmined transactions
+    ///   (including change from transactions that the wallet created) are only included
+    ///   if this is 0.
     /// - `include_watchonly` (bool, optional, default=false) Also include balance in
-    ///   watchonly addresses (see 'importaddress' and 'z_importviewingkey').
+    ///   watch-only accounts, whose spending keys the wallet cannot derive.
     #[method(name = "z_gettotalbalance")]
     async fn z_get_total_balance(
         &self,
@@ -379,6 +1072,109 @@ pub(crate) trait WalletRpc {
         include_watchonly: Option<bool>,
     ) -> z_get_total_balance::Response;
 
+    /// Returns the total transparent balance of the wallet.
+    ///
+    /// As in `zcashd`, only transparent funds are counted: shielded funds are never
+    /// included, so scripts written against `zcashd` see the same semantics. Use
+    /// `z_gettotalbalance` for the wallet's balance across all pools. Unlike `zcashd`,
+    /// this includes the transparent funds of every account in the wallet. Immature
+    /// coinbase outputs, and outputs that are being spent by unmined transactions, are
+    /// not counted.
+    ///
+    /// # Arguments
+    ///
+    /// - `dummy` (string, optional) Remains for backward compatibility. Must be excluded
+    ///   or set to "*".
+    /// - `minconf` (numeric, optional, default=1) Only include transactions confirmed at
+    ///   least this many times. Must be at least 1 when `as_of_height` is provided.
+    /// - `include_watchonly` (bool, optional, default=false) Also include balance in
+    ///   watch-only accounts, whose spending keys the wallet cannot derive.
+    /// - `in_zat` (bool, optional, default=false) Get the result amount in zatoshis.
+    /// - `as_of_height`: Execute the query as if it were run when the blockchain was at the height
+    ///   specified by this argument. The default is to use the entire blockchain that the wallet
+    ///   is aware of. -1 can be used as in other RPC calls to indicate the current height
+    ///   (including the mempool), and other negative values are offsets from the current height
+    ///   (-2 is the block below it). Heights above the current height are rejected.
+    #[method(name = "getbalance")]
+    async fn get_balance(
+        &self,
+        dummy: Option<String>,
+        minconf: Option<u32>,
+        include_watchonly: Option<bool>,
+        in_zat: Option<bool>,
+        as_of_height: Option<i64>,
+    ) -> get_balance::Response;
+
+    /// Returns the balance of an account in each value pool.
+    ///
+    /// The balance in each pool is split into funds that have at least `minconf`
+    /// confirmations, and funds that are still pending. Amounts are reported both in ZEC
+    /// and in zatoshis. Pools in which the account holds no funds are omitted.
+    ///
+    /// Balances are also reported for watch-only accounts (such as accounts imported
+    /// from a viewing key), which are flagged with `is_watch_only`.
+    ///
+    /// # Arguments
+    ///
+    /// - `account` (string or numeric, required) The UUID, name, or ZIP 32 account index
+    ///   of the account.
+    /// - `minconf` (numeric, optional, default=1) Only include funds in transactions
+    ///   confirmed at least this many times. If 0, funds in unmined transactions
+    ///   (including change from transactions that the wallet created) are included. Must
+    ///   be at least 1 when `as_of_height` is provided.
+    /// - `as_of_height`: Execute the query as if it were run when the blockchain was at the height
+    ///   specified by this argument. The default is to use the entire blockchain that the wallet
+    ///   is aware of. -1 can be used as in other RPC calls to indicate the current height
+    ///   (including the mempool), and other negative values are offsets from the current height
+    ///   (-2 is the block below it). Heights above the current height are rejected.
+    #[method(name = "z_getbalanceforaccount")]
+    async fn get_balance_for_account(
+        &self,
+        account: JsonValue,
+        minconf: Option<u32>,
+        as_of_height: Option<i64>,
+    ) -> get_balance_for_account::Response;
+
+    /// Returns an array of unspent transparent outputs with between minconf and maxconf
+    /// (inclusive) confirmations.
+    ///
+    /// Use `z_listunspent` to also list unspent shielded notes.
+    ///
+    /// Outputs that are reserved as inputs by an in-progress operation (such as an
+    /// asynchronous `z_sendmany`) are flagged as `reserved`, and coinbase outputs are
+    /// flagged as `generated`. Neither reserved outputs nor coinbase outputs with fewer
+    /// than 100 confirmations are `spendable`.
+    ///
+    /// # Arguments
+    /// - `minconf`: Select outputs with at least this many confirmations (default = 1). Must be at
+    ///   least 1 when `as_of_height` is provided.
+    /// - `maxconf`: Select outputs with at most this many confirmations (default = 9999999).
+    /// - `addresses`: A list of transparent addresses. If provided and non-empty, only outputs
+    ///   received by these addresses are returned.
+    /// - `as_of_height`: Execute the query as if it were run when the blockchain was at the height
+    ///   specified by this argument. The default is to use the entire blockchain that the wallet
+    ///   is aware of. -1 can be used as in other RPC calls to indicate the current height
+    ///   (including the mempool), and other negative values are offsets from the current height
+    ///   (-2 is the block below it). Heights above the current height are rejected.
+    /// - `limit`: The page size when `cursor` is provided (default = 1000).
+    /// - `cursor`: If provided, return a page of results as an object with `results` and
+    ///   `next_cursor` fields, instead of an array. Pass the empty string to fetch the first
+    ///   page, and the returned `next_cursor` to fetch each following page; `next_cursor` is
+    ///   `null` on the last page.
+    ///
+    /// Without a cursor, the request fails if there are more than `rpc.max_list_results`
+    /// results.
+    #[method(name = "listunspent")]
+    async fn list_transparent_unspent(
+        &self,
+        minconf: Option<u32>,
+        maxconf: Option<u32>,
+        addresses: Option<Vec<String>>,
+        as_of_height: Option<i64>,
+        limit: Option<u32>,
+        cursor: Option<String>,
+    ) -> list_transparent_unspent::Response;
+
     /// Returns an array of unspent shielded notes with between minconf and maxconf
     /// (inclusive) confirmations.
     ///
@@ -392,16 +1188,23 @@ pub(crate) trait WalletRpc {
     /// - `maxconf`: Select outputs with at most this many confirmations (default = unlimited).
     /// - `include_watchonly`: Include notes/utxos for which the wallet does not provide spending
     ///   capability (default = false).
-    /// - `addresses`: A list of addresses for which to retrieve UTXOs. For shielded addresses that
-    ///   correspond to a unified account, unspent notes belonging to that account are returned
-    ///   irrespective of whether the provided address's diversifier corresponds to the diversifier
-    ///   of the address that received the funds. If this parameter is omitted or empty, all notes
-    ///   are returned, irrespective of account. (default = None)
+    /// - `addresses`: A list of transparent, Sapling, or unified addresses for which to retrieve
+    ///   outputs. Outputs received by any of the addresses are returned; for a unified address,
+    ///   this includes outputs received by any of its receivers. If this parameter is omitted or
+    ///   empty, all outputs are returned, irrespective of account. (default = None)
     /// - `as_of_height`: Execute the query as if it were run when the blockchain was at the height
-    ///   specified by this argument. The default is to use the entire blockchain that the node is
-    ///   aware of. -1 can be used as in other RPC calls to indicate the current height (including
-    ///   the mempool), but this does not support negative values in general. A “future” height will
-    ///   fall back to the current height.
+    ///   specified by this argument. The default is to use the entire blockchain that the wallet
+    ///   is aware of. -1 can be used as in other RPC calls to indicate the current height
+    ///   (including the mempool), and other negative values are offsets from the current height
+    ///   (-2 is the block below it). Heights above the current height are rejected.
+    /// - `limit`: The page size when `cursor` is provided (default = 1000).
+    /// - `cursor`: If provided, return a page of results as an object with `results` and
+    ///   `next_cursor` fields, instead of an array. Pass the empty string to fetch the first
+    ///   page, and the returned `next_cursor` to fetch each following page; `next_cursor` is
+    ///   `null` on the last page.
+    ///
+    /// Without a cursor, the request fails if there are more than `rpc.max_list_results`
+    /// results.
     #[method(name = "z_listunspent")]
     async fn list_unspent(
         &self,
@@ -410,18 +1213,47 @@ pub(crate) trait WalletRpc {
         include_watchonly: Option<bool>,
         addresses: Option<Vec<String>>,
         as_of_height: Option<i64>,
+        limit: Option<u32>,
+        cursor: Option<String>,
     ) -> list_unspent::Response;
 
+    /// Returns the received notes whose text memos contain the given text.
+    ///
+    /// Matching is by exact substring, and is case-sensitive. Memos that are not text
+    /// are never matched. Results are ordered by the height of the transaction that
+    /// received each note, with unmined transactions last.
+    ///
+    /// This requires the memo index, which is enabled by `database.memo_index`.
+    ///
+    /// # Arguments
+    /// - `query` (string, required) The text to search for.
+    /// - `account` (string or numeric, optional) The UUID, name, or ZIP 32 account index
+    ///   of the account whose memos to search. If omitted, every account is searched.
+    /// - `from_height` (numeric, optional) Only search the memos of transactions mined at
+    ///   or above this height. Unmined transactions are excluded if this is set.
+    /// - `to_height` (numeric, optional) Only search the memos of transactions mined at
+    ///   or below this height. Unmined transactions are excluded if this is set.
+    ///
+    /// The request fails if there are more than `rpc.max_list_results` results.
+    #[method(name = "z_searchmemos")]
+    async fn search_memos(
+        &self,
+        query: String,
+        account: Option<JsonValue>,
+        from_height: Option<u32>,
+        to_height: Option<u32>,
+    ) -> search_memos::Response;
+
     /// Returns the number of notes available in the wallet for each shielded value pool.
     ///
     /// # Arguments
     /// - `minconf`: Only include notes in transactions confirmed at least this many times
     ///   (default = 1). Must be at least 1 when `as_of_height` is provided.
     /// - `as_of_height`: Execute the query as if it were run when the blockchain was at the height
-    ///   specified by this argument. The default is to use the entire blockchain that the node is
-    ///   aware of. -1 can be used as in other RPC calls to indicate the current height (including
-    ///   the mempool), but this does not support negative values in general. A “future” height will
-    ///   fall back to the current height.
+    ///   specified by this argument. The default is to use the entire blockchain that the wallet
+    ///   is aware of. -1 can be used as in other RPC calls to indicate the current height
+    ///   (including the mempool), and other negative values are offsets from the current height
+    ///   (-2 is the block below it). Heights above the current height are rejected.
     #[method(name = "z_getnotescount")]
     async fn get_notes_count(
         &self,
@@ -429,6 +1261,14 @@ pub(crate) trait WalletRpc {
         as_of_height: Option<i64>,
     ) -> get_notes_count::Response;
 
+    /// Returns the status of the Sprout to Sapling migration.
+    ///
+    /// Zallet cannot spend Sprout notes, and so does not perform the migration. This
+    /// reports the value of any Sprout notes that were migrated from a `zcashd` wallet,
+    /// which should be migrated to Sapling using `zcashd`.
+    #[method(name = "z_getmigrationstatus")]
+    async fn get_migration_status(&self) -> get_migration_status::Response;
+
     /// Send a transaction with multiple recipients.
     ///
     /// This is an async operation; it returns an operation ID string that you can pass to
@@ -457,6 +1297,8 @@ pub(crate) trait WalletRpc {
     ///   If a unified address is provided for this argument, the TXOs to be spent will be
     ///   selected from those associated with the account corresponding to that unified
     ///   address, from value pools corresponding to the receivers included in the UA.
+    ///   The UUID or exact name of an account may also be provided, in which case the
+    ///   TXOs to be spent will be selected from those associated with that account.
     /// - `amounts` (array, required) An array of JSON objects representing the amounts to
     ///   send, with the following fields:
     ///   - `address` (string, required) A taddr, zaddr, or Unified Address.
@@ -465,8 +1307,10 @@ pub(crate) trait WalletRpc {
     ///     hexadecimal string format. If the output is being sent to a transparent
     ///     address, it’s an error to include this field.
     /// - `minconf` (numeric, optional) Only use funds confirmed at least this many times.
-    /// - `fee` (numeric, optional) If set, it must be null. Zallet always uses a fee
-    ///   calculated according to ZIP 317.
+    /// - `fee` (numeric, optional, default=`builder.max_fee`) The most, in ZEC, that each
+    ///   transaction may pay in fees. Zallet pays the fee calculated according to ZIP
+    ///   317; the request is rejected if `fee` is below that fee, or above
+    ///   `builder.max_fee`. Batchable payments cannot set a fee.
     /// - `privacy_policy` (string, optional, default=`"FullPrivacy"`) Policy for what
     ///   information leakage is acceptable. One of the following strings:
     ///   - `"FullPrivacy"`: Only allow fully-shielded transactions (involving a single
@@ -488,6 +1332,36 @@ pub(crate) trait WalletRpc {
     ///   - `"NoPrivacy"`: Allow the transaction to reveal any information necessary to
     ///     create it. This implies revealing information described under
     ///     `"AllowFullyTransparent"` and `"AllowLinkingAccountAddresses"`.
+    /// - `allow_split` (bool, optional, default=false) If the payment would require a
+    ///   transaction with more Orchard actions than `builder.limits.orchard_actions`
+    ///   permits, send it in a chain of transactions instead of failing. Too many
+    ///   recipients are split across transactions. If the payment must spend too many
+    ///   notes, earlier transactions in the chain merge some of them into a single
+    ///   note at an internal address of the account, and the operation waits for each
+    ///   of these to be confirmed so that later transactions can spend its output. The
+    ///   transactions are created one after another, each spending funds left over by
+    ///   its predecessors, and the operation's progress reports those sent so far. The
+    ///   operation result lists the recipients and txids of each transaction in its
+    ///   `split` field, where transactions that only merged notes have no recipients.
+    ///   A payment whose notes must be merged cannot be combined with `no_broadcast`.
+    /// - `input_selection` (string, optional) The strategy used to select the notes to
+    ///   spend, overriding the `builder.input_selection` config option. One of
+    ///   `"fewest_inputs"`, `"largest_first"`, `"oldest_first"`, or `"single_pool"`.
+    ///   Every strategy only selects notes that satisfy `minconf`.
+    /// - `batchable` (bool, optional, default=false) If true, the payment is queued for
+    ///   up to `builder.batching.window` seconds and then sent in a single transaction
+    ///   along with other batchable payments from the same account that use the same
+    ///   `minconf`, `privacy_policy`, and `input_selection`. The operation result
+    ///   contains the shared transaction's txid, and an `outputs` field giving the pool
+    ///   and output index that pays each recipient. If the shared transaction cannot be
+    ///   created, every payment in the batch fails with the same error. Cannot be
+    ///   combined with `allow_split`.
+    /// - `no_broadcast` (bool, optional, default=false) If true, the transaction is
+    ///   created and stored in the wallet without being broadcast, as if the
+    ///   `external.broadcast` config option were disabled. When the transaction is not
+    ///   broadcast, the operation result includes the raw transaction in its `hex`
+    ///   field, and the transaction can later be broadcast with
+    ///   `resendwallettransactions`.
     #[method(name = "z_sendmany")]
     async fn z_send_many(
         &self,
@@ -496,28 +1370,423 @@ pub(crate) trait WalletRpc {
         minconf: Option<u32>,
         fee: Option<JsonValue>,
         privacy_policy: Option<String>,
+        allow_split: Option<bool>,
+        input_selection: Option<String>,
+        batchable: Option<bool>,
+        no_broadcast: Option<bool>,
     ) -> z_send_many::Response;
-}
-
-pub(crate) struct RpcImpl {
-    wallet: Database,
-    #[cfg(zallet_build = "wallet")]
-    keystore: KeyStore,
-    chain: Chain,
-}
 
-impl RpcImpl {
-    /// Creates a new instance of the general RPC handler.
-    pub(crate) fn new(
-        wallet: Database,
-        #[cfg(zallet_build = "wallet")] keystore: KeyStore,
-        chain: Chain,
-    ) -> Self {
+    /// Sends the payments requested by a ZIP 321 payment URI from the given account.
+    ///
+    /// Each payment in the URI is sent to its recipient in a single transaction, in
+    /// the same way as with `z_sendmany`. The URI is checked before the operation
+    /// starts; in particular, this fails immediately if the account's spendable funds
+    /// do not cover the payments.
+    ///
+    /// This is an async operation; it returns an operation ID string that you can pass to
+    /// `z_getoperationstatus` or `z_getoperationresult`. The operation is reported as a
+    /// `z_sendmany` operation.
+    ///
+    /// # Arguments
+    /// - `account` (string or numeric, required) The UUID, name, or ZIP 32 account index
+    ///   of the account to send funds from.
+    /// - `uri` (string, required) A ZIP 321 payment URI.
+    /// - `minconf` (numeric, optional) Only use funds confirmed at least this many
+    ///   times.
+    /// - `fee` (numeric or string, optional) The most that the transaction may pay in
+    ///   fees, in ZEC. Defaults to `builder.max_fee`.
+    /// - `privacy_policy` (string, optional, default=`"FullPrivacy"`) Policy for what
+    ///   information leakage is acceptable. See `z_sendmany` for the possible values.
+    /// - `no_broadcast` (boolean, optional, default=false) If true, create the
+    ///   transaction without broadcasting it, and return it in the result.
+    #[method(name = "z_sendpaymenturi")]
+    async fn z_send_payment_uri(
+        &self,
+        account: JsonValue,
+        uri: String,
+        minconf: Option<u32>,
+        fee: Option<JsonValue>,
+        privacy_policy: Option<String>,
+        no_broadcast: Option<bool>,
+    ) -> z_send_payment_uri::Response;
+
+    /// Parses a ZIP 321 payment URI into the payments that it requests.
+    ///
+    /// Fails if the URI is malformed, has a required parameter that Zallet does not
+    /// support, or requests a payment to an address for a different network.
+    ///
+    /// # Arguments
+    /// - `uri` (string, required) A ZIP 321 payment URI.
+    #[method(name = "z_paymenturi_decode")]
+    async fn z_payment_uri_decode(&self, uri: String) -> z_payment_uri_decode::Response;
+
+    /// Creates a ZIP 321 payment URI requesting a payment to the given address.
+    ///
+    /// # Arguments
+    /// - `address` (string, required) The address to request payment to.
+    /// - `amount` (numeric or string, required) The amount to request, in ZEC.
+    /// - `memo` (string, optional) A memo for the payment, in hexadecimal string format.
+    ///   Only permitted for shielded addresses.
+    /// - `label` (string, optional) A label for the recipient.
+    /// - `message` (string, optional) A message describing the payment.
+    #[method(name = "z_paymenturi_encode")]
+    async fn z_payment_uri_encode(
+        &self,
+        address: String,
+        amount: JsonValue,
+        memo: Option<String>,
+        label: Option<String>,
+        message: Option<String>,
+    ) -> z_payment_uri_encode::Response;
+
+    /// Sends all funds controlled by the given private key to the given destination,
+    /// without importing the key into the wallet.
+    ///
+    /// The funds controlled by a transparent key are discovered by querying the backing
+    /// full node for UTXOs received by the address corresponding to the key. Coinbase
+    /// UTXOs with fewer than 100 confirmations cannot yet be spent, and are listed in
+    /// the operation result without being swept.
+    ///
+    /// The funds controlled by a Sapling key are discovered by scanning the chain from
+    /// `start_height` for notes that the key can spend. The key is only held in memory
+    /// for the duration of the operation, and is never written to the wallet.
+    ///
+    /// This is an async operation; it returns an operation ID string that you can pass to
+    /// `z_getoperationstatus` or `z_getoperationresult`. The operation result lists the
+    /// UTXOs or notes that were found and swept, along with the fee paid.
+    ///
+    /// If more funds are found than fit in a single transaction (as configured by the
+    /// `builder.limits` options), they are swept in batches by a sequence of
+    /// transactions, and `z_getoperationstatus` reports the progress of the sweep. If a
+    /// batch fails, the sweep stops and the error lists the funds that remain unswept.
+    ///
+    /// # Arguments
+    /// - `privkey` (string, required) The private key: a transparent key in WIF format,
+    ///   or a Sapling extended spending key.
+    /// - `destination` (string, required) Either the UUID of a wallet account (in which
+    ///   case the funds are sent to the default shielded address of that account), or an
+    ///   address.
+    /// - `start_height` (numeric, optional) The height from which to scan the chain for
+    ///   notes spendable by a Sapling key. Defaults to 100,000 blocks before the chain
+    ///   tip. Must be omitted for transparent keys.
+    #[method(name = "sweepprivkey")]
+    async fn sweep_priv_key(
+        &self,
+        privkey: age::secrecy::SecretString,
+        destination: String,
+        start_height: Option<u32>,
+    ) -> sweep_priv_key::Response;
+
+    /// Shields the transparent funds of an account into one of its shielded pools.
+    ///
+    /// Unlike in `zcashd`, all spendable transparent UTXOs are shielded, not only
+    /// coinbase outputs. The funds are sent to an internal address of the destination
+    /// account, in the Orchard pool unless the destination is an address without an
+    /// Orchard receiver.
+    ///
+    /// This is an async operation; the result contains an operation ID string that you
+    /// can pass to `z_getoperationstatus` or `z_getoperationresult`, along with the
+    /// number and value of the UTXOs being shielded and of those left unshielded because
+    /// of `limit`. Fails if there are no UTXOs to shield.
+    ///
+    /// # Arguments
+    /// - `fromaddress` (string, required) A transparent address of the destination
+    ///   account to shield funds from, or `"*"` (or `"ANY_TADDR"`) to shield funds from
+    ///   all of the account's transparent addresses.
+    /// - `toaddress` (string, required) The UUID or name of the account to shield funds
+    ///   into, or a shielded address of that account.
+    /// - `fee` (numeric, optional, default=`builder.max_fee`) The most, in ZEC, that the
+    ///   transaction may pay in fees. Zallet pays the fee calculated according to ZIP
+    ///   317; the request is rejected if `fee` is below that fee, or above
+    ///   `builder.max_fee`.
+    /// - `limit` (numeric, optional, default=0) The maximum number of UTXOs to shield.
+    ///   If 0 or omitted, the `builder.limits.transparent_inputs` config option is used,
+    ///   which also caps larger limits. The oldest UTXOs are shielded first.
+    #[method(name = "z_shieldcoinbase")]
+    async fn z_shield_coinbase(
+        &self,
+        fromaddress: String,
+        toaddress: String,
+        fee: Option<JsonValue>,
+        limit: Option<u32>,
+    ) -> z_shield_coinbase::Response;
+
+    /// Merges many UTXOs or notes into a single output, to consolidate the wallet's
+    /// funds.
+    ///
+    /// Notes are merged by sending all of them, less the fee, to `toaddress`. UTXOs are
+    /// merged by shielding them into the account of `toaddress` (see
+    /// `z_shieldcoinbase`); transparent and shielded funds cannot be merged by the same
+    /// call. The oldest inputs are merged first.
+    ///
+    /// This is an async operation; the result contains an operation ID string that you
+    /// can pass to `z_getoperationstatus` or `z_getoperationresult`, along with the
+    /// number and value of the UTXOs and notes being merged, and of those that remain
+    /// because of the limits. Call this method again until nothing remains to merge
+    /// everything. Fails if there is nothing to merge.
+    ///
+    /// # Arguments
+    /// - `fromaddresses` (array, required) The addresses to merge funds from. Each entry
+    ///   is one of:
+    ///   - `"ANY_TADDR"`: Merge UTXOs from the transparent addresses of the destination
+    ///     account.
+    ///   - `"ANY_SAPLING"` or `"ANY_ORCHARD"`: Merge notes from that pool.
+    ///   - A transparent address of the destination account.
+    ///   - A Sapling or unified address, to merge notes from its account in the pools
+    ///     of its receivers.
+    ///
+    ///   All shielded addresses must belong to the same account. If only wildcards are
+    ///   given, notes are merged from the account of `toaddress` if it belongs to the
+    ///   wallet, or otherwise from the wallet's only account.
+    /// - `toaddress` (string, required) The address to send the merged funds to.
+    /// - `fee` (numeric, optional, default=`builder.max_fee`) The most, in ZEC, that the
+    ///   transaction may pay in fees. Zallet pays the fee calculated according to ZIP
+    ///   317; the request is rejected if `fee` is below that fee, or above
+    ///   `builder.max_fee`.
+    /// - `transparent_limit` (numeric, optional, default=0) The maximum number of UTXOs
+    ///   to merge. If 0 or omitted, the `builder.limits.transparent_inputs` config
+    ///   option is used, which also caps larger limits.
+    /// - `shielded_limit` (numeric, optional, default=200) The maximum number of notes
+    ///   to merge, or 0 for no limit. Unless `allow_split` is true, only as many notes
+    ///   as fit in a single transaction are merged, given `builder.limits` and the
+    ///   maximum transaction size.
+    /// - `memo` (string, optional) A memo for the merged output, represented in
+    ///   hexadecimal string format. Only permitted when merging notes to a shielded
+    ///   address.
+    /// - `allow_split` (bool, optional, default=false) If true, the limits on a single
+    ///   transaction do not cap the number of notes merged. Instead, the notes are
+    ///   merged by a chain of transactions, each within `builder.limits`, that are
+    ///   created one after another. The operation's progress reports the transactions sent so far,
+    ///   and its result lists them in its `split` field. If a transaction in the chain
+    ///   cannot be created, the operation fails with an error listing the notes that
+    ///   were not merged. Only permitted when merging notes.
+    #[method(name = "z_mergetoaddress")]
+    async fn z_merge_to_address(
+        &self,
+        fromaddresses: Vec<String>,
+        toaddress: String,
+        fee: Option<JsonValue>,
+        transparent_limit: Option<u32>,
+        shielded_limit: Option<u32>,
+        memo: Option<String>,
+        allow_split: Option<bool>,
+    ) -> z_merge_to_address::Response;
+
+    /// Proposes a transfer of funds from the given account, without creating any
+    /// transactions.
+    ///
+    /// The result contains an opaque serialized proposal, along with a summary of the
+    /// transactions that it would create. The proposal can be reviewed, and then sent
+    /// with `z_executeproposal`.
+    ///
+    /// A proposal expires once a transaction created from it would be within 3 blocks of
+    /// its expiry height; the summary's `expiryheight` field gives the last next-block
+    /// height at which it can be executed.
+    ///
+    /// # Arguments
+    /// - `account` (string or numeric, required) The UUID, name, or ZIP 32 account index
+    ///   of the account to send funds from.
+    /// - `recipients` (array, required) An array of JSON objects representing the
+    ///   amounts to send, in the same format as the `amounts` argument of `z_sendmany`.
+    /// - `minconf` (numeric, optional) Only use funds confirmed at least this many
+    ///   times.
+    /// - `privacy_policy` (string, optional, default=`"FullPrivacy"`) Policy for what
+    ///   information leakage is acceptable. See `z_sendmany` for the possible values.
+    #[method(name = "z_proposetransfer")]
+    async fn propose_transfer(
+        &self,
+        account: JsonValue,
+        recipients: Vec<z_send_many::AmountParameter>,
+        minconf: Option<u32>,
+        privacy_policy: Option<String>,
+    ) -> z_propose_transfer::Response;
+
+    /// Executes a proposal created by `z_proposetransfer`, creating and broadcasting its
+    /// transactions.
+    ///
+    /// The proposal is executed exactly as it was proposed. Execution fails, without
+    /// selecting different inputs, if the wallet state has changed such that the
+    /// proposal can no longer be executed:
+    /// - the proposal has expired;
+    /// - a block that the proposal's shielded inputs are anchored to has been
+    ///   reorganized out of the chain;
+    /// - an input has since been spent, or is otherwise no longer spendable.
+    ///
+    /// Proposals are authenticated with a key that is stored in the wallet, so only
+    /// unmodified proposals created by this wallet can be executed.
+    ///
+    /// This is an async operation; it returns an operation ID string that you can pass to
+    /// `z_getoperationstatus` or `z_getoperationresult`.
+    ///
+    /// # Arguments
+    /// - `proposal` (string, required) A proposal returned by `z_proposetransfer`.
+    #[method(name = "z_executeproposal")]
+    async fn execute_proposal(&self, proposal: String) -> z_execute_proposal::Response;
+
+    /// Creates a partially created Zcash transaction (PCZT) that sends funds from the
+    /// given account, so that it can be proven and signed elsewhere.
+    ///
+    /// The PCZT has no proofs or signatures. The account does not need to have a
+    /// spending key in the wallet, so this can be used with accounts whose keys are held
+    /// on an air-gapped machine or a hardware wallet. The PCZT can be proven with
+    /// `z_provepczt`, the signed copies can be merged with `z_combinepczts`, and the
+    /// finished PCZT can then be sent with `z_finalizepczt`.
+    ///
+    /// The inputs that the PCZT spends are reserved, so that other sends do not select
+    /// them, until it is finalized or its transaction expires.
+    ///
+    /// Fails if sending the funds requires more than one transaction.
+    ///
+    /// # Arguments
+    /// - `account` (string or numeric, required) The UUID, name, or ZIP 32 account index
+    ///   of the account to send funds from.
+    /// - `recipients` (array, required) An array of JSON objects representing the
+    ///   amounts to send, in the same format as the `amounts` argument of `z_sendmany`.
+    /// - `minconf` (numeric, optional) Only use funds confirmed at least this many
+    ///   times.
+    /// - `privacy_policy` (string, optional, default=`"FullPrivacy"`) Policy for what
+    ///   information leakage is acceptable. See `z_sendmany` for the possible values.
+    #[method(name = "z_createpczt")]
+    async fn create_pczt(
+        &self,
+        account: JsonValue,
+        recipients: Vec<z_send_many::AmountParameter>,
+        minconf: Option<u32>,
+        privacy_policy: Option<String>,
+    ) -> z_create_pczt::Response;
+
+    /// Creates the Sapling and Orchard proofs for a PCZT.
+    ///
+    /// Returns the PCZT with its proofs, base64-encoded.
+    ///
+    /// # Arguments
+    /// - `pczt` (string, required) A base64-encoded PCZT.
+    #[method(name = "z_provepczt")]
+    async fn prove_pczt(&self, pczt: String) -> z_prove_pczt::Response;
+
+    /// Combines copies of a PCZT that have been updated separately, for example with
+    /// proofs and with signatures, into a single PCZT.
+    ///
+    /// Returns the combined PCZT, base64-encoded.
+    ///
+    /// # Arguments
+    /// - `pczts` (array, required) An array of base64-encoded PCZTs to combine.
+    #[method(name = "z_combinepczts")]
+    async fn combine_pczts(&self, pczts: Vec<String>) -> z_combine_pczts::Response;
+
+    /// Extracts the transaction from a PCZT that has all of its proofs and signatures,
+    /// and stores it in the wallet.
+    ///
+    /// The transaction is broadcast unless the `external.broadcast` config option is
+    /// disabled, in which case the result includes the raw transaction.
+    ///
+    /// # Arguments
+    /// - `pczt` (string, required) A base64-encoded PCZT with its proofs and signatures.
+    /// - `pczt_id` (numeric, optional) The `pczt_id` returned by `z_createpczt`, to
+    ///   release the reservation of the PCZT's inputs.
+    #[method(name = "z_finalizepczt")]
+    async fn finalize_pczt(&self, pczt: String, pczt_id: Option<u64>) -> z_finalize_pczt::Response;
+
+    /// Broadcasts the transactions that the wallet created without broadcasting them,
+    /// either because the `external.broadcast` config option was disabled or because
+    /// they were sent with `no_broadcast`.
+    ///
+    /// Transactions that have since been mined, or that have expired, are not
+    /// broadcast. Fails if `external.broadcast` is disabled.
+    ///
+    /// Returns an array of the IDs of the broadcast transactions.
+    #[method(name = "resendwallettransactions")]
+    async fn resend_wallet_transactions(&self) -> resend_wallet_transactions::Response;
+}
+
+/// The JSON-RPC interface for managing the wallets that Zallet serves.
+///
+/// These methods are served whether or not a request selects a wallet.
+#[cfg(zallet_build = "wallet")]
+#[rpc(server)]
+pub(crate) trait WalletManagementRpc {
+    /// Creates and loads a new wallet.
+    ///
+    /// The wallet is stored in the `wallets/<wallet_name>` subdirectory of the data
+    /// directory. Unless it is watch-only, it is given a new age identity (stored there
+    /// unencrypted), a new BIP 39 mnemonic phrase, and an account derived from that
+    /// phrase. This fails if `keystore.require_encryption` is enabled.
+    ///
+    /// The wallet is not loaded when Zallet restarts, unless it is loaded again with
+    /// `loadwallet`.
+    ///
+    /// # Arguments
+    /// - `wallet_name` (string, required) The name of the new wallet. This may only
+    ///   contain ASCII letters, digits, `-`, `_`, and `.`, and must not start with `.`.
+    /// - `watch_only` (boolean, optional, default=false) Create a wallet without
+    ///   spending keys, which cannot store mnemonic phrases or create accounts from them.
+    /// - `birthday` (numeric, optional) The height at which the wallet's account is
+    ///   created. The wallet does not scan the chain below this height. Defaults to the
+    ///   current chain tip. Not allowed for watch-only wallets.
+    #[method(name = "createwallet")]
+    async fn create_wallet(
+        &self,
+        wallet_name: String,
+        watch_only: Option<bool>,
+        birthday: Option<u32>,
+    ) -> create_wallet::Response;
+
+    /// Loads a wallet, and starts scanning the chain for it.
+    ///
+    /// The wallet is either listed in `database.wallets`, or was created with
+    /// `createwallet`. The empty name loads the wallet configured by `database.wallet`,
+    /// if `database.wallets` is empty.
+    ///
+    /// # Arguments
+    /// - `wallet_name` (string, required) The name of the wallet.
+    #[method(name = "loadwallet")]
+    async fn load_wallet(&self, wallet_name: String) -> load_wallet::Response;
+
+    /// Unloads a wallet.
+    ///
+    /// New requests can no longer select the wallet. Requests that were already sent to
+    /// it (including this one, if it was) finish first, and its executing async
+    /// operations are given up to `rpc.shutdown_timeout` to finish. The wallet's files
+    /// are not deleted, so it can be loaded again with `loadwallet`.
+    ///
+    /// # Arguments
+    /// - `wallet_name` (string, required) The name of the wallet. Unlike Bitcoin Core,
+    ///   this is not inferred from the RPC endpoint.
+    #[method(name = "unloadwallet")]
+    async fn unload_wallet(&self, wallet_name: String) -> unload_wallet::Response;
+
+    /// Returns the names of the loaded wallets.
+    ///
+    /// The wallet configured by `database.wallet` has the empty name.
+    #[method(name = "listwallets")]
+    async fn list_wallets(&self) -> list_wallets::Response;
+}
+
+pub(crate) struct RpcImpl {
+    wallet: Database,
+    #[cfg(zallet_build = "wallet")]
+    keystore: KeyStore,
+    chain: Chain,
+    events: TransactionEvents,
+    sync_status: SyncStatus,
+}
+
+impl RpcImpl {
+    /// Creates a new instance of the general RPC handler.
+    pub(crate) fn new(
+        wallet: Database,
+        #[cfg(zallet_build = "wallet")] keystore: KeyStore,
+        chain: Chain,
+        events: TransactionEvents,
+        sync_status: SyncStatus,
+    ) -> Self {
         Self {
             wallet,
             #[cfg(zallet_build = "wallet")]
             keystore,
             chain,
+            events,
+            sync_status,
         }
     }
 
@@ -525,7 +1794,7 @@ impl RpcImpl {
         self.wallet
             .handle()
             .await
-            .map_err(|_| jsonrpsee::types::ErrorCode::InternalError.into())
+            .map_err(|e| ComponentFailure::database(e.message()).into())
     }
 
     async fn chain(&self) -> RpcResult<FetchServiceSubscriber> {
@@ -533,7 +1802,7 @@ impl RpcImpl {
             .subscribe()
             .await
             .map(|s| s.inner())
-            .map_err(|_| jsonrpsee::types::ErrorCode::InternalError.into())
+            .map_err(|_| ComponentFailure::ChainUnavailable.into())
     }
 }
 
@@ -541,20 +1810,157 @@ impl RpcImpl {
 pub(crate) struct WalletRpcImpl {
     general: RpcImpl,
     keystore: KeyStore,
-    async_ops: RwLock<Vec<AsyncOperation>>,
+    operations: Arc<OperationRegistry>,
+    batcher: z_send_many::PaymentBatcher,
+    pczts: PendingPczts,
 }
 
 #[cfg(zallet_build = "wallet")]
 impl WalletRpcImpl {
     /// Creates a new instance of the wallet-specific RPC handler.
-    pub(crate) fn new(wallet: Database, keystore: KeyStore, chain_view: Chain) -> Self {
+    pub(crate) fn new(
+        wallet: Database,
+        keystore: KeyStore,
+        chain_view: Chain,
+        events: TransactionEvents,
+        sync_status: SyncStatus,
+    ) -> Self {
         Self {
-            general: RpcImpl::new(wallet, keystore.clone(), chain_view),
+            batcher: z_send_many::PaymentBatcher::new(
+                wallet.clone(),
+                keystore.clone(),
+                chain_view.clone(),
+            ),
+            operations: Arc::new(OperationRegistry::new(wallet.clone(), &APP.config())),
+            pczts: PendingPczts::new(wallet.clone()),
+            general: RpcImpl::new(wallet, keystore.clone(), chain_view, events, sync_status),
             keystore,
-            async_ops: RwLock::new(Vec::new()),
         }
     }
 
+    /// Returns the registry of async operations launched by this handler.
+    pub(super) fn operations(&self) -> Arc<OperationRegistry> {
+        self.operations.clone()
+    }
+
+    /// Restores the async operations that were recorded before Zallet last stopped.
+    ///
+    /// Operations that had not started executing are resumed. Operations that were
+    /// executing are marked as failed, unless every transaction they created is known
+    /// to have reached the network.
+    pub(crate) async fn restore_operations(&self) -> Result<(), Error> {
+        let mut restored = vec![];
+
+        for stored in self.operations.store().load().await? {
+            let op = match OperationState::parse(&stored.state) {
+                Some(OperationState::Ready) => self.resume_operation(stored).await,
+                Some(OperationState::Executing) => self.recover_operation(stored).await,
+                _ => AsyncOperation::restore(self.operations.store().clone(), stored),
+            };
+            restored.extend(op);
+        }
+
+        self.operations.extend(restored).await;
+        Ok(())
+    }
+
+    /// Re-issues the request for an operation that had not started executing.
+    async fn resume_operation(&self, stored: StoredOperation) -> Option<AsyncOperation> {
+        let store = self.operations.store().clone();
+
+        match stored.method.as_deref() {
+            Some("z_sendmany") => {
+                let request = async {
+                    z_send_many::resume(
+                        self.wallet().await?,
+                        self.keystore.clone(),
+                        self.chain().await?,
+                        self.batcher.clone(),
+                        stored.params.as_deref().unwrap_or("{}"),
+                    )
+                    .await
+                }
+                .await;
+                AsyncOperation::resume(store, stored, request).await
+            }
+            Some("z_shieldcoinbase") => {
+                let request = async {
+                    z_shield_coinbase::resume(
+                        self.wallet().await?,
+                        self.keystore.clone(),
+                        self.chain().await?,
+                        stored.params.as_deref().unwrap_or("{}"),
+                    )
+                    .await
+                }
+                .await;
+                AsyncOperation::resume(store, stored, request).await
+            }
+            _ => {
+                let op = AsyncOperation::restore(store, stored)?;
+                op.finish(Err(LegacyCode::Misc.with_static(
+                    "Operation was interrupted by a restart before it started, and cannot be resumed.",
+                )))
+                .await;
+                Some(op)
+            }
+        }
+    }
+
+    /// Determines the outcome of an operation that was executing when Zallet stopped.
+    async fn recover_operation(&self, stored: StoredOperation) -> Option<AsyncOperation> {
+        let op = AsyncOperation::restore(self.operations.store().clone(), stored)?;
+        let txids = op.txids().await;
+
+        if txids.is_empty() {
+            op.finish(Err(LegacyCode::Wallet.with_static(
+                "Operation was interrupted by a restart before it created any transactions.",
+            )))
+            .await;
+            return Some(op);
+        }
+
+        let mempool = match self.chain().await {
+            Ok(chain) => chain.get_raw_mempool().await.unwrap_or_else(|e| {
+                warn!("Failed to fetch the mempool: {e}");
+                vec![]
+            }),
+            Err(_) => vec![],
+        };
+
+        let mut unknown = vec![];
+        match self.wallet().await {
+            Ok(wallet) => {
+                for txid in &txids {
+                    let mined = wallet.get_tx_height(*txid).ok().flatten().is_some();
+                    if !mined && !mempool.contains(&txid.to_string()) {
+                        unknown.push(*txid);
+                    }
+                }
+                // Let `resendwallettransactions` broadcast any that never made it.
+                if let Err(e) = wallet.mark_unbroadcast(&unknown) {
+                    warn!("Failed to record unbroadcast transactions: {e}");
+                }
+            }
+            Err(_) => unknown.clone_from(&txids),
+        }
+
+        let res = if unknown.is_empty() {
+            Ok(asyncop::txids_result(&txids))
+        } else {
+            Err(ErrorObjectOwned::owned(
+                LegacyCode::Wallet.into(),
+                "Operation was interrupted by a restart, and it is unknown whether all of its \
+                transactions were broadcast. Use resendwallettransactions to broadcast any \
+                that were not.",
+                Some(asyncop::txids_result(&txids)),
+            ))
+        };
+        op.finish(res).await;
+
+        Some(op)
+    }
+
     async fn wallet(&self) -> RpcResult<DbHandle> {
         self.general.wallet().await
     }
@@ -568,11 +1974,17 @@ impl WalletRpcImpl {
         F: Future<Output = RpcResult<T>> + Send + 'static,
         T: Serialize + Send + 'static,
     {
-        let mut async_ops = self.async_ops.write().await;
-        let op = AsyncOperation::new(context, f).await;
-        let op_id = op.operation_id().clone();
-        async_ops.push(op);
-        op_id
+        // Operations may decrypt key material while they execute (for example, to sign
+        // with standalone transparent keys), so the wallet is kept unlocked until they
+        // finish if it is unlocked when they start executing. The guard is not taken
+        // earlier, so that operations which never start cannot defer the relock.
+        let keystore = self.keystore.clone();
+        self.operations
+            .start(context, async move {
+                let _unlocked = keystore.unlock_guard().await;
+                f.await
+            })
+            .await
     }
 }
 
@@ -590,7 +2002,7 @@ impl RpcServer for RpcImpl {
         &self,
         account: JsonValue,
         receiver_types: Option<Vec<String>>,
-        diversifier_index: Option<u128>,
+        diversifier_index: Option<JsonValue>,
     ) -> get_address_for_account::Response {
         get_address_for_account::call(
             self.wallet().await?.as_mut(),
@@ -603,15 +2015,85 @@ impl RpcServer for RpcImpl {
         .await
     }
 
-    async fn list_addresses(&self) -> list_addresses::Response {
-        list_addresses::call(self.wallet().await?.as_ref())
+    async fn preview_address_for_account(
+        &self,
+        account: JsonValue,
+        receiver_types: Option<Vec<String>>,
+    ) -> preview_address_for_account::Response {
+        preview_address_for_account::call(
+            self.wallet().await?.as_ref(),
+            #[cfg(zallet_build = "wallet")]
+            self.keystore.clone(),
+            account,
+            receiver_types,
+        )
+        .await
+    }
+
+    async fn list_addresses(
+        &self,
+        account: Option<JsonValue>,
+        source: Option<String>,
+        include_diversifier_indices: Option<bool>,
+        offset: Option<u32>,
+        limit: Option<u32>,
+    ) -> list_addresses::Response {
+        list_addresses::call(
+            self.wallet().await?.as_ref(),
+            #[cfg(zallet_build = "wallet")]
+            &self.keystore,
+            account,
+            source,
+            include_diversifier_indices,
+            offset,
+            limit,
+        )
+        .await
+    }
+
+    async fn import_viewing_key(
+        &self,
+        vkey: &str,
+        rescan: Option<&str>,
+        start_height: Option<u32>,
+    ) -> import_viewing_key::Response {
+        import_viewing_key::call(
+            self.wallet().await?.as_mut(),
+            self.chain().await?,
+            vkey,
+            rescan,
+            start_height,
+        )
+        .await
+    }
+
+    async fn export_viewing_key(&self, account: JsonValue) -> export_viewing_key::Response {
+        export_viewing_key::call(
+            self.wallet().await?.as_ref(),
+            #[cfg(zallet_build = "wallet")]
+            &self.keystore,
+            account,
+        )
+        .await
     }
 
     async fn list_unified_receivers(
         &self,
         unified_address: &str,
     ) -> list_unified_receivers::Response {
-        list_unified_receivers::call(self.wallet().await?.as_ref(), unified_address)
+        list_unified_receivers::call(self.wallet().await?.params(), unified_address)
+    }
+
+    async fn validate_address(&self, address: &str) -> validate_address::Response {
+        validate_address::call(self.wallet().await?.as_ref(), address)
+    }
+
+    async fn list_received_by_address(
+        &self,
+        address: &str,
+        minconf: Option<u32>,
+    ) -> list_received_by_address::Response {
+        list_received_by_address::call(self.wallet().await?.as_ref(), address, minconf)
     }
 
     async fn list_transactions(
@@ -621,6 +2103,9 @@ impl RpcServer for RpcImpl {
         end_height: Option<u32>,
         offset: Option<u32>,
         limit: Option<u32>,
+        as_of_height: Option<i64>,
+        include_watchonly: Option<bool>,
+        cursor: Option<String>,
     ) -> list_transactions::Response {
         list_transactions::call(
             self.wallet().await?.as_ref(),
@@ -629,10 +2114,43 @@ impl RpcServer for RpcImpl {
             end_height,
             offset,
             limit,
+            as_of_height,
+            include_watchonly,
+            cursor,
         )
         .await
     }
 
+    async fn list_wallet_history(
+        &self,
+        dummy: Option<String>,
+        count: Option<u32>,
+        from: Option<u32>,
+        include_watchonly: Option<bool>,
+    ) -> list_wallet_history::Response {
+        list_wallet_history::call(
+            self.wallet().await?.as_ref(),
+            dummy,
+            count,
+            from,
+            include_watchonly,
+        )
+    }
+
+    async fn list_since_block(
+        &self,
+        blockhash: Option<String>,
+        target_confirmations: Option<u32>,
+        include_watchonly: Option<bool>,
+    ) -> list_since_block::Response {
+        list_since_block::call(
+            self.wallet().await?.as_ref(),
+            blockhash,
+            target_confirmations,
+            include_watchonly,
+        )
+    }
+
     async fn get_raw_transaction(
         &self,
         txid: &str,
@@ -653,8 +2171,45 @@ impl RpcServer for RpcImpl {
         view_transaction::call(self.wallet().await?.as_ref(), self.chain().await?, txid).await
     }
 
+    async fn get_transaction(
+        &self,
+        txid: &str,
+        include_watchonly: Option<bool>,
+    ) -> get_transaction::Response {
+        get_transaction::call(self.wallet().await?.as_ref(), txid, include_watchonly)
+    }
+
+    async fn get_backend_info(&self) -> get_backend_info::Response {
+        get_backend_info::call(&self.chain)
+    }
+
+    async fn get_sync_status(&self) -> z_get_sync_status::Response {
+        z_get_sync_status::call(
+            self.wallet().await?.as_ref(),
+            &self.chain,
+            &self.sync_status,
+        )
+    }
+
+    async fn rescan_blockchain(
+        &self,
+        start_height: Option<u32>,
+        stop_height: Option<u32>,
+    ) -> rescan_blockchain::Response {
+        rescan_blockchain::call(
+            self.wallet().await?.as_ref(),
+            &self.sync_status,
+            start_height,
+            stop_height,
+        )
+    }
+
     async fn stop(&self) -> stop::Response {
-        stop::call(self.wallet().await?)
+        stop::call()
+    }
+
+    async fn reopen_logs(&self) -> reopen_logs::Response {
+        reopen_logs::call()
     }
 
     async fn verify_message(
@@ -674,6 +2229,10 @@ impl RpcServer for RpcImpl {
     async fn convert_tex(&self, transparent_address: &str) -> convert_tex::Response {
         convert_tex::call(self.wallet().await?.params(), transparent_address)
     }
+
+    async fn subscribe_transactions(&self, pending: PendingSubscriptionSink) -> SubscriptionResult {
+        subscribe_transactions::call(pending, self.events.subscribe()).await
+    }
 }
 
 #[cfg(zallet_build = "wallet")]
@@ -688,19 +2247,25 @@ impl WalletRpcServer for WalletRpcImpl {
     }
 
     async fn list_operation_ids(&self, status: Option<&str>) -> list_operation_ids::Response {
-        list_operation_ids::call(&self.async_ops.read().await, status).await
+        list_operation_ids::call(&self.operations.read().await, status).await
     }
 
     async fn get_operation_status(&self, operationid: Vec<OperationId>) -> get_operation::Response {
-        get_operation::status(&self.async_ops.read().await, operationid).await
+        get_operation::status(&self.operations.read().await, operationid).await
     }
 
     async fn get_operation_result(&self, operationid: Vec<OperationId>) -> get_operation::Response {
-        get_operation::result(self.async_ops.write().await.as_mut(), operationid).await
+        get_operation::result(self.operations.write().await.as_mut(), operationid).await
     }
 
     async fn get_wallet_info(&self) -> get_wallet_info::Response {
-        get_wallet_info::call(&self.keystore).await
+        get_wallet_info::call(
+            self.wallet().await?.as_ref(),
+            &self.keystore,
+            &self.general.chain,
+            &self.general.sync_status,
+        )
+        .await
     }
 
     async fn unlock_wallet(
@@ -715,10 +2280,119 @@ impl WalletRpcServer for WalletRpcImpl {
         lock_wallet::call(&self.keystore).await
     }
 
+    async fn change_wallet_passphrase(
+        &self,
+        oldpassphrase: age::secrecy::SecretString,
+        newpassphrase: age::secrecy::SecretString,
+    ) -> change_wallet_passphrase::Response {
+        change_wallet_passphrase::call(&self.keystore, oldpassphrase, newpassphrase).await
+    }
+
+    async fn confirm_backup(
+        &self,
+        mnemonic: age::secrecy::SecretString,
+    ) -> confirm_backup::Response {
+        confirm_backup::call(&self.keystore, mnemonic).await
+    }
+
+    async fn backup_wallet(
+        &self,
+        filename: &str,
+        recipients: Option<Vec<String>>,
+        allow_plaintext: Option<bool>,
+    ) -> export_wallet::Response {
+        export_wallet::call(
+            self.wallet().await?.as_ref(),
+            &self.keystore,
+            "backupwallet",
+            filename,
+            recipients,
+            allow_plaintext,
+        )
+        .await
+    }
+
+    async fn export_wallet(
+        &self,
+        filename: &str,
+        recipients: Option<Vec<String>>,
+        allow_plaintext: Option<bool>,
+    ) -> export_wallet::Response {
+        export_wallet::call(
+            self.wallet().await?.as_ref(),
+            &self.keystore,
+            "z_exportwallet",
+            filename,
+            recipients,
+            allow_plaintext,
+        )
+        .await
+    }
+
+    async fn export_history(
+        &self,
+        filename: &str,
+        start_height: Option<u32>,
+        end_height: Option<u32>,
+        account: Option<JsonValue>,
+    ) -> export_history::Response {
+        export_history::call(
+            self.wallet().await?.as_ref(),
+            &self.keystore,
+            filename,
+            start_height,
+            end_height,
+            account,
+        )
+        .await
+    }
+
+    async fn import_wallet(&self, path: &str) -> import_wallet::Response {
+        import_wallet::call(
+            self.wallet().await?.as_mut(),
+            &self.keystore,
+            self.chain().await?,
+            path,
+        )
+        .await
+    }
+
+    async fn import_key(
+        &self,
+        key: &str,
+        rescan: Option<&str>,
+        start_height: Option<u32>,
+    ) -> import_key::Response {
+        import_key::call(
+            self.wallet().await?.as_mut(),
+            &self.keystore,
+            self.chain().await?,
+            key,
+            rescan,
+            start_height,
+        )
+        .await
+    }
+
+    async fn export_key(&self, address: &str) -> export_key::Response {
+        export_key::call(self.wallet().await?.as_ref(), &self.keystore, address).await
+    }
+
+    async fn sign_message(&self, t_addr: &str, message: &str) -> sign_message::Response {
+        sign_message::call(
+            self.wallet().await?.as_ref(),
+            &self.keystore,
+            t_addr,
+            message,
+        )
+        .await
+    }
+
     async fn get_new_account(
         &self,
         account_name: &str,
         seedfp: Option<&str>,
+        strict_unique: Option<bool>,
     ) -> get_new_account::Response {
         get_new_account::call(
             self.wallet().await?.as_mut(),
@@ -726,6 +2400,45 @@ impl WalletRpcServer for WalletRpcImpl {
             self.chain().await?,
             account_name,
             seedfp,
+            strict_unique,
+        )
+        .await
+    }
+
+    async fn rename_account(
+        &self,
+        account: JsonValue,
+        name: &str,
+        strict_unique: Option<bool>,
+    ) -> rename_account::Response {
+        rename_account::call(
+            self.wallet().await?.as_ref(),
+            &self.keystore,
+            account,
+            name,
+            strict_unique,
+        )
+        .await
+    }
+
+    async fn get_new_address(&self, account: Option<String>) -> get_new_address::Response {
+        get_new_address::call(
+            self.wallet().await?.as_mut(),
+            &self.keystore,
+            self.chain().await?,
+            account,
+            false,
+        )
+        .await
+    }
+
+    async fn get_raw_change_address(&self, account: Option<String>) -> get_new_address::Response {
+        get_new_address::call(
+            self.wallet().await?.as_mut(),
+            &self.keystore,
+            self.chain().await?,
+            account,
+            true,
         )
         .await
     }
@@ -751,6 +2464,60 @@ impl WalletRpcServer for WalletRpcImpl {
         z_get_total_balance::call(self.wallet().await?.as_ref(), minconf, include_watchonly)
     }
 
+    async fn get_balance(
+        &self,
+        dummy: Option<String>,
+        minconf: Option<u32>,
+        include_watchonly: Option<bool>,
+        in_zat: Option<bool>,
+        as_of_height: Option<i64>,
+    ) -> get_balance::Response {
+        get_balance::call(
+            self.wallet().await?.as_ref(),
+            dummy,
+            minconf,
+            include_watchonly,
+            in_zat,
+            as_of_height,
+        )
+    }
+
+    async fn get_balance_for_account(
+        &self,
+        account: JsonValue,
+        minconf: Option<u32>,
+        as_of_height: Option<i64>,
+    ) -> get_balance_for_account::Response {
+        get_balance_for_account::call(
+            self.wallet().await?.as_ref(),
+            &self.keystore,
+            account,
+            minconf,
+            as_of_height,
+        )
+        .await
+    }
+
+    async fn list_transparent_unspent(
+        &self,
+        minconf: Option<u32>,
+        maxconf: Option<u32>,
+        addresses: Option<Vec<String>>,
+        as_of_height: Option<i64>,
+        limit: Option<u32>,
+        cursor: Option<String>,
+    ) -> list_transparent_unspent::Response {
+        list_transparent_unspent::call(
+            self.wallet().await?.as_ref(),
+            minconf,
+            maxconf,
+            addresses,
+            as_of_height,
+            limit,
+            cursor,
+        )
+    }
+
     async fn list_unspent(
         &self,
         minconf: Option<u32>,
@@ -758,6 +2525,8 @@ impl WalletRpcServer for WalletRpcImpl {
         include_watchonly: Option<bool>,
         addresses: Option<Vec<String>>,
         as_of_height: Option<i64>,
+        limit: Option<u32>,
+        cursor: Option<String>,
     ) -> list_unspent::Response {
         list_unspent::call(
             self.wallet().await?.as_ref(),
@@ -766,9 +2535,29 @@ impl WalletRpcServer for WalletRpcImpl {
             include_watchonly,
             addresses,
             as_of_height,
+            limit,
+            cursor,
         )
     }
 
+    async fn search_memos(
+        &self,
+        query: String,
+        account: Option<JsonValue>,
+        from_height: Option<u32>,
+        to_height: Option<u32>,
+    ) -> search_memos::Response {
+        search_memos::call(
+            self.wallet().await?.as_ref(),
+            &self.keystore,
+            query,
+            account,
+            from_height,
+            to_height,
+        )
+        .await
+    }
+
     async fn get_notes_count(
         &self,
         minconf: Option<u32>,
@@ -777,6 +2566,10 @@ impl WalletRpcServer for WalletRpcImpl {
         get_notes_count::call(self.wallet().await?.as_ref(), minconf, as_of_height)
     }
 
+    async fn get_migration_status(&self) -> get_migration_status::Response {
+        get_migration_status::call(self.wallet().await?.as_ref())
+    }
+
     async fn z_send_many(
         &self,
         fromaddress: String,
@@ -784,6 +2577,10 @@ impl WalletRpcServer for WalletRpcImpl {
         minconf: Option<u32>,
         fee: Option<JsonValue>,
         privacy_policy: Option<String>,
+        allow_split: Option<bool>,
+        input_selection: Option<String>,
+        batchable: Option<bool>,
+        no_broadcast: Option<bool>,
     ) -> z_send_many::Response {
         Ok(self
             .start_async(
@@ -791,14 +2588,294 @@ impl WalletRpcServer for WalletRpcImpl {
                     self.wallet().await?,
                     self.keystore.clone(),
                     self.chain().await?,
+                    self.batcher.clone(),
                     fromaddress,
                     amounts,
                     minconf,
                     fee,
                     privacy_policy,
+                    allow_split,
+                    input_selection,
+                    batchable,
+                    no_broadcast,
                 )
                 .await?,
             )
             .await)
     }
+
+    async fn z_send_payment_uri(
+        &self,
+        account: JsonValue,
+        uri: String,
+        minconf: Option<u32>,
+        fee: Option<JsonValue>,
+        privacy_policy: Option<String>,
+        no_broadcast: Option<bool>,
+    ) -> z_send_payment_uri::Response {
+        Ok(self
+            .start_async(
+                z_send_payment_uri::call(
+                    self.wallet().await?,
+                    self.keystore.clone(),
+                    self.chain().await?,
+                    self.batcher.clone(),
+                    account,
+                    uri,
+                    minconf,
+                    fee,
+                    privacy_policy,
+                    no_broadcast,
+                )
+                .await?,
+            )
+            .await)
+    }
+
+    async fn z_payment_uri_decode(&self, uri: String) -> z_payment_uri_decode::Response {
+        z_payment_uri_decode::call(self.wallet().await?.params(), &uri)
+    }
+
+    async fn z_payment_uri_encode(
+        &self,
+        address: String,
+        amount: JsonValue,
+        memo: Option<String>,
+        label: Option<String>,
+        message: Option<String>,
+    ) -> z_payment_uri_encode::Response {
+        z_payment_uri_encode::call(
+            self.wallet().await?.params(),
+            &address,
+            amount,
+            memo,
+            label,
+            message,
+        )
+    }
+
+    async fn sweep_priv_key(
+        &self,
+        privkey: age::secrecy::SecretString,
+        destination: String,
+        start_height: Option<u32>,
+    ) -> sweep_priv_key::Response {
+        Ok(self
+            .start_async(
+                sweep_priv_key::call(
+                    self.wallet().await?,
+                    self.chain().await?,
+                    privkey,
+                    destination,
+                    start_height,
+                )
+                .await?,
+            )
+            .await)
+    }
+
+    async fn z_shield_coinbase(
+        &self,
+        fromaddress: String,
+        toaddress: String,
+        fee: Option<JsonValue>,
+        limit: Option<u32>,
+    ) -> z_shield_coinbase::Response {
+        let (selection, operation) = z_shield_coinbase::call(
+            self.wallet().await?,
+            self.keystore.clone(),
+            self.chain().await?,
+            fromaddress,
+            toaddress,
+            fee,
+            limit,
+        )
+        .await?;
+        Ok(selection.started(self.start_async(operation).await))
+    }
+
+    async fn z_merge_to_address(
+        &self,
+        fromaddresses: Vec<String>,
+        toaddress: String,
+        fee: Option<JsonValue>,
+        transparent_limit: Option<u32>,
+        shielded_limit: Option<u32>,
+        memo: Option<String>,
+        allow_split: Option<bool>,
+    ) -> z_merge_to_address::Response {
+        let (selection, operation) = z_merge_to_address::call(
+            self.wallet().await?,
+            self.keystore.clone(),
+            self.chain().await?,
+            fromaddresses,
+            toaddress,
+            fee,
+            transparent_limit,
+            shielded_limit,
+            memo,
+            allow_split,
+        )
+        .await?;
+        Ok(selection.started(self.start_async(operation).await))
+    }
+
+    async fn propose_transfer(
+        &self,
+        account: JsonValue,
+        recipients: Vec<z_send_many::AmountParameter>,
+        minconf: Option<u32>,
+        privacy_policy: Option<String>,
+    ) -> z_propose_transfer::Response {
+        z_propose_transfer::call(
+            self.wallet().await?,
+            self.keystore.clone(),
+            account,
+            recipients,
+            minconf,
+            privacy_policy,
+        )
+        .await
+    }
+
+    async fn execute_proposal(&self, proposal: String) -> z_execute_proposal::Response {
+        Ok(self
+            .start_async(
+                z_execute_proposal::call(
+                    self.wallet().await?,
+                    self.keystore.clone(),
+                    self.chain().await?,
+                    proposal,
+                )
+                .await?,
+            )
+            .await)
+    }
+
+    async fn create_pczt(
+        &self,
+        account: JsonValue,
+        recipients: Vec<z_send_many::AmountParameter>,
+        minconf: Option<u32>,
+        privacy_policy: Option<String>,
+    ) -> z_create_pczt::Response {
+        z_create_pczt::call(
+            self.wallet().await?,
+            self.keystore.clone(),
+            self.pczts.clone(),
+            account,
+            recipients,
+            minconf,
+            privacy_policy,
+        )
+        .await
+    }
+
+    async fn prove_pczt(&self, pczt: String) -> z_prove_pczt::Response {
+        z_prove_pczt::call(pczt).await
+    }
+
+    async fn combine_pczts(&self, pczts: Vec<String>) -> z_combine_pczts::Response {
+        z_combine_pczts::call(pczts)
+    }
+
+    async fn finalize_pczt(&self, pczt: String, pczt_id: Option<u64>) -> z_finalize_pczt::Response {
+        z_finalize_pczt::call(
+            self.wallet().await?,
+            self.chain().await?,
+            self.pczts.clone(),
+            pczt,
+            pczt_id,
+        )
+        .await
+    }
+
+    async fn resend_wallet_transactions(&self) -> resend_wallet_transactions::Response {
+        resend_wallet_transactions::call(self.wallet().await?.as_ref(), self.chain().await?).await
+    }
+}
+
+#[cfg(zallet_build = "wallet")]
+pub(crate) struct WalletManagementRpcImpl {
+    wallets: WeakWallets,
+}
+
+#[cfg(zallet_build = "wallet")]
+impl WalletManagementRpcImpl {
+    /// Creates a new instance of the wallet management RPC handler.
+    pub(crate) fn new(wallets: WeakWallets) -> Self {
+        Self { wallets }
+    }
+
+    fn wallets(&self) -> RpcResult<Wallets> {
+        self.wallets
+            .upgrade()
+            .ok_or_else(|| LegacyCode::Misc.with_static("Zallet is shutting down"))
+    }
+}
+
+#[cfg(zallet_build = "wallet")]
+#[async_trait]
+impl WalletManagementRpcServer for WalletManagementRpcImpl {
+    async fn create_wallet(
+        &self,
+        wallet_name: String,
+        watch_only: Option<bool>,
+        birthday: Option<u32>,
+    ) -> create_wallet::Response {
+        create_wallet::call(&self.wallets()?, &wallet_name, watch_only, birthday).await
+    }
+
+    async fn load_wallet(&self, wallet_name: String) -> load_wallet::Response {
+        load_wallet::call(&self.wallets()?, &wallet_name).await
+    }
+
+    async fn unload_wallet(&self, wallet_name: String) -> unload_wallet::Response {
+        unload_wallet::call(&self.wallets()?, &wallet_name).await
+    }
+
+    async fn list_wallets(&self) -> list_wallets::Response {
+        list_wallets::call(&self.wallets()?)
+    }
+}
+
+#[cfg(all(test, zallet_build = "wallet"))]
+mod tests {
+    use super::{
+        get_balance, get_transaction, list_transactions, list_unspent, list_wallet_history,
+        z_get_total_balance,
+    };
+
+    #[test]
+    fn include_watchonly_defaults() {
+        // Every RPC that takes `include_watchonly` excludes watch-only accounts by
+        // default, as `zcashd` did.
+        assert_eq!(
+            [
+                ("getbalance", get_balance::INCLUDE_WATCHONLY_DEFAULT),
+                (
+                    "z_gettotalbalance",
+                    z_get_total_balance::INCLUDE_WATCHONLY_DEFAULT
+                ),
+                (
+                    "z_listtransactions",
+                    list_transactions::INCLUDE_WATCHONLY_DEFAULT
+                ),
+                ("z_listunspent", list_unspent::INCLUDE_WATCHONLY_DEFAULT),
+                (
+                    "listtransactions",
+                    list_wallet_history::INCLUDE_WATCHONLY_DEFAULT
+                ),
+                ("gettransaction", get_transaction::INCLUDE_WATCHONLY_DEFAULT),
+            ],
+            [
+                ("getbalance", false),
+                ("z_gettotalbalance", false),
+                ("z_listtransactions", false),
+                ("z_listunspent", false),
+                ("listtransactions", false),
+                ("gettransaction", false),
+            ],
+        );
+    }
 }
@@ -0,0 +1,54 @@
+use age::secrecy::{ExposeSecret, SecretString};
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::components::{
+    audit::{self, Event},
+    json_rpc::server::{ComponentFailure, LegacyCode},
+    keystore::KeyStore,
+};
+
+use super::MethodCategory;
+
+/// Response to a `walletpassphrasechange` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// Empty result indicating success.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(());
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_OLDPASSPHRASE_DESC: &str = "The current passphrase.";
+pub(super) const PARAM_NEWPASSPHRASE_DESC: &str = "The new passphrase.";
+
+pub(crate) async fn call(
+    keystore: &KeyStore,
+    oldpassphrase: SecretString,
+    newpassphrase: SecretString,
+) -> Response {
+    if !keystore.uses_encrypted_identities() {
+        return Err(LegacyCode::WalletWrongEncState.with_static(
+            "Error: running with an unencrypted wallet, but walletpassphrasechange was called.",
+        ));
+    }
+
+    if newpassphrase.expose_secret().is_empty() {
+        return Err(LegacyCode::InvalidParameter.with_static("The new passphrase cannot be empty."));
+    }
+
+    if !keystore
+        .change_passphrase(oldpassphrase, newpassphrase)
+        .await
+        .map_err(ComponentFailure::keystore)?
+    {
+        return Err(LegacyCode::WalletPassphraseIncorrect
+            .with_static("Error: The wallet passphrase entered was incorrect."));
+    }
+    audit::record(Event::WalletPassphraseChanged);
+
+    Ok(ResultType(()))
+}
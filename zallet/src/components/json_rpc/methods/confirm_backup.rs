@@ -0,0 +1,57 @@
+use age::secrecy::{ExposeSecret, SecretString};
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use secrecy::ExposeSecret as _;
+use serde::Serialize;
+use zip32::fingerprint::SeedFingerprint;
+
+use crate::components::{
+    audit::{self, Event},
+    json_rpc::server::{ComponentFailure, LegacyCode},
+    keystore::{KeyStore, mnemonic_to_seed},
+};
+
+use super::MethodCategory;
+
+/// Response to a `walletconfirmbackup` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// Empty result indicating success.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(());
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_MNEMONIC_DESC: &str =
+    "The mnemonic seed phrase, as written down by the user.";
+
+pub(crate) async fn call(keystore: &KeyStore, mnemonic: SecretString) -> Response {
+    // We identify the mnemonic by its seed fingerprint, so that the wallet does not need
+    // to be unlocked to confirm the backup.
+    let seed = mnemonic_to_seed(mnemonic.expose_secret())
+        .map_err(|_| LegacyCode::InvalidParameter.with_static("Invalid mnemonic phrase"))?;
+    let seed_fp = SeedFingerprint::from_seed(seed.expose_secret()).expect("valid length");
+
+    if !keystore
+        .list_seed_fingerprints()
+        .await
+        .map_err(ComponentFailure::keystore)?
+        .contains(&seed_fp)
+    {
+        return Err(LegacyCode::Wallet.with_static(
+            "Error: The mnemonic phrase does not match any seed in the wallet. Please check that you wrote it down correctly.",
+        ));
+    }
+
+    keystore
+        .confirm_backup(&seed_fp)
+        .await
+        .map_err(ComponentFailure::keystore)?;
+    audit::record(Event::BackupConfirmed {
+        seed_fingerprint: seed_fp.to_string(),
+    });
+
+    Ok(ResultType(()))
+}
@@ -9,6 +9,8 @@ use zcash_protocol::consensus::Parameters;
 
 use crate::{components::json_rpc::server::LegacyCode, fl, network::Network};
 
+use super::MethodCategory;
+
 pub(crate) type Response = RpcResult<ResultType>;
 
 /// The TEX address encoding of the input transparent P2PKH address.
@@ -16,6 +18,8 @@ pub(crate) type Response = RpcResult<ResultType>;
 #[serde(transparent)]
 pub(crate) struct ResultType(String);
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_TRANSPARENT_ADDRESS_DESC: &str = "The transparent P2PKH address to convert.";
 
 /// Converts a transparent P2PKH Zcash address to a TEX address.
@@ -0,0 +1,135 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use zaino_state::ZcashIndexer;
+use zcash_client_backend::data_api::WalletWrite;
+use zcash_protocol::consensus::BlockHeight;
+
+use crate::components::{
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::account_birthday,
+    },
+    keystore,
+    wallets::Wallets,
+};
+
+use super::MethodCategory;
+
+/// Response to a `createwallet` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = CreatedWallet;
+
+/// The wallet that was created.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct CreatedWallet {
+    /// The name of the wallet.
+    name: String,
+
+    /// Warning messages about creating the wallet, if any.
+    warning: String,
+
+    /// The UUID of the wallet's account.
+    ///
+    /// Omitted for watch-only wallets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_uuid: Option<String>,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_WALLET_NAME_DESC: &str = "The name of the new wallet.";
+pub(super) const PARAM_WATCH_ONLY_DESC: &str = "Create a wallet without spending keys.";
+pub(super) const PARAM_BIRTHDAY_DESC: &str =
+    "The height at which the wallet's account is created. Defaults to the chain tip.";
+
+pub(crate) async fn call(
+    wallets: &Wallets,
+    wallet_name: &str,
+    watch_only: Option<bool>,
+    birthday: Option<u32>,
+) -> Response {
+    let watch_only = watch_only.unwrap_or(false);
+    if watch_only && birthday.is_some() {
+        return Err(LegacyCode::InvalidParameter
+            .with_static("birthday is not allowed for watch-only wallets"));
+    }
+
+    // Wallets with spending keys need the chain to create their account, so check that
+    // it is available before creating anything.
+    let chain = match watch_only {
+        true => None,
+        false => Some(
+            wallets
+                .chain()
+                .subscribe()
+                .await
+                .map_err(|_| ComponentFailure::ChainUnavailable)?
+                .inner(),
+        ),
+    };
+
+    let new_wallet = wallets.create(wallet_name, watch_only).await?;
+
+    let account_uuid = match chain {
+        None => None,
+        Some(chain) => {
+            let chain_tip = BlockHeight::from(
+                chain
+                    .chain_height()
+                    .await
+                    .map_err(ComponentFailure::chain)?
+                    .0,
+            );
+            let birthday_height = match birthday.map(BlockHeight::from_u32) {
+                Some(height) if height > chain_tip => {
+                    return Err(LegacyCode::InvalidParameter.with_message(format!(
+                        "birthday must not be above the chain tip at height {chain_tip}"
+                    )));
+                }
+                Some(height) => height,
+                None => chain_tip,
+            };
+
+            let wallet = new_wallet.wallet();
+            let mut handle = wallet
+                .db
+                .handle()
+                .await
+                .map_err(|e| ComponentFailure::database(e.message()))?;
+            let db = handle.as_mut();
+            let birthday = account_birthday(&chain, db.params(), birthday_height).await?;
+
+            let seed_fp = wallet
+                .keystore
+                .encrypt_and_store_mnemonic(keystore::generate_mnemonic())
+                .await
+                .map_err(ComponentFailure::keystore)?;
+            let seed = wallet
+                .keystore
+                .decrypt_seed(&seed_fp)
+                .await
+                .map_err(ComponentFailure::keystore)?;
+
+            let (account_id, _usk) = db
+                .create_account(wallet_name, &seed, &birthday, None)
+                .map_err(ComponentFailure::database)?;
+            Some(account_id.expose_uuid().to_string())
+        }
+    };
+
+    new_wallet
+        .finish()
+        .await
+        .map_err(|e| LegacyCode::Wallet.with_message(e.message()))?;
+
+    Ok(CreatedWallet {
+        name: wallet_name.into(),
+        warning: match watch_only {
+            true => String::new(),
+            false => "The wallet's age identity is not encrypted with a passphrase".into(),
+        },
+        account_uuid,
+    })
+}
@@ -0,0 +1,439 @@
+//! The CSV transaction history written by `z_exporthistory`.
+//!
+//! The file follows RFC 4180: records are separated by CRLF, and fields that contain
+//! commas, double quotes, or line breaks are enclosed in double quotes, with any double
+//! quotes inside them doubled. The first record is a header with the column names:
+//!
+//! - `timestamp`: The time of the block that the transaction is mined in, in RFC 3339
+//!   format. Empty for unmined transactions.
+//! - `block_height`: The height of the block that the transaction is mined in. Empty for
+//!   unmined transactions.
+//! - `txid`: The transaction ID.
+//! - `account_uuid`: The UUID of the account that the row affects.
+//! - `address`: The address that the output was sent to, if known.
+//! - `pool`: The value pool that the output is in (`transparent`, `sapling`, or
+//!   `orchard`). Empty for `fee` rows.
+//! - `direction`: `send` if the account sent the output, `receive` if the account
+//!   received it, or `fee` for the fee paid by the account.
+//! - `amount_zat`: The change to the account's balance in zatoshis, which is negative for
+//!   `send` and `fee` rows. Empty if the fee is not known to the wallet.
+//! - `amount_zec`: The same amount in ZEC, with 8 decimal places.
+//! - `memo`: The memo of a shielded output, as text if it is a text memo, or otherwise
+//!   hex-encoded. Empty for transparent outputs and empty memos.
+//!
+//! Change outputs are omitted, so the amounts of an account's rows sum to the change in
+//! its balance.
+
+use std::borrow::Cow;
+use std::io;
+
+use abscissa_core::Application;
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use rusqlite::named_params;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use time::format_description::well_known::Rfc3339;
+use uuid::Uuid;
+use zcash_client_sqlite::error::SqliteClientError;
+use zcash_protocol::{TxId, memo::Memo};
+
+use crate::{
+    components::{
+        database::DbConnection,
+        json_rpc::{
+            server::{ComponentFailure, LegacyCode},
+            utils::parse_account_parameter,
+        },
+        keystore::KeyStore,
+    },
+    prelude::*,
+};
+
+use super::{
+    MethodCategory,
+    export_wallet::{export_path, write_new_file},
+    list_wallet_history::pool_name,
+};
+
+/// Response to a `z_exporthistory` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The history file that was written.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct ResultType {
+    /// The number of rows written, excluding the header.
+    rows: usize,
+
+    /// The absolute path of the written file.
+    path: String,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_FILENAME_DESC: &str =
+    "The name of the file to write, relative to the directory configured by `external.export_dir`.";
+pub(super) const PARAM_START_HEIGHT_DESC: &str =
+    "The (inclusive) lower bound on block heights for which transactions should be exported.";
+pub(super) const PARAM_END_HEIGHT_DESC: &str =
+    "The (exclusive) upper bound on block heights for which transactions should be exported.";
+pub(super) const PARAM_ACCOUNT_DESC: &str =
+    "The UUID, name, or ZIP 32 account index of the account to export the history of.";
+
+const HEADER: &[&str] = &[
+    "timestamp",
+    "block_height",
+    "txid",
+    "account_uuid",
+    "address",
+    "pool",
+    "direction",
+    "amount_zat",
+    "amount_zec",
+    "memo",
+];
+
+pub(crate) async fn call(
+    wallet: &DbConnection,
+    keystore: &KeyStore,
+    filename: &str,
+    start_height: Option<u32>,
+    end_height: Option<u32>,
+    account: Option<JsonValue>,
+) -> Response {
+    let path = export_path(APP.config().export_dir().as_deref(), filename)?;
+
+    let account_id = match account {
+        Some(account) => Some(parse_account_parameter(wallet, keystore, &account).await?),
+        None => None,
+    };
+
+    let mut rows = wallet
+        .with_raw(|conn, _| {
+            query_rows(
+                conn,
+                account_id.map(|account_id| *account_id.expose_uuid()),
+                start_height,
+                end_height,
+            )
+        })
+        .map_err(ComponentFailure::database)?;
+
+    // Fees are looked up once the query is complete, because the wallet may need to
+    // parse the transaction to determine them.
+    for row in rows
+        .iter_mut()
+        .filter(|row| row.direction == Direction::Fee)
+    {
+        row.amount_zat = wallet
+            .tx_fee(&row.txid)
+            .map_err(ComponentFailure::database)?
+            .map(|fee| -(u64::from(fee) as i64));
+    }
+
+    let mut csv = String::new();
+    push_record(&mut csv, HEADER.iter().copied());
+    for row in &rows {
+        row.push_to(&mut csv);
+    }
+
+    write_new_file(&path, csv.as_bytes()).map_err(|e| match e.kind() {
+        io::ErrorKind::AlreadyExists => LegacyCode::Wallet
+            .with_message(format!("Cannot overwrite existing file {}", path.display())),
+        _ => LegacyCode::Wallet
+            .with_message(format!("Cannot write history file {}: {e}", path.display())),
+    })?;
+
+    Ok(ResultType {
+        rows: rows.len(),
+        path: path.display().to_string(),
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Send,
+    Fee,
+    Receive,
+}
+
+impl Direction {
+    fn from_code(code: i64) -> Result<Self, SqliteClientError> {
+        match code {
+            0 => Ok(Self::Send),
+            1 => Ok(Self::Fee),
+            2 => Ok(Self::Receive),
+            _ => Err(SqliteClientError::CorruptedData(format!(
+                "Invalid direction code: {code}"
+            ))),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Send => "send",
+            Self::Fee => "fee",
+            Self::Receive => "receive",
+        }
+    }
+}
+
+/// A row of the history file.
+#[derive(Debug)]
+struct Row {
+    block_time: Option<i64>,
+    mined_height: Option<u32>,
+    txid: TxId,
+    account_uuid: Uuid,
+    address: Option<String>,
+    pool: Option<&'static str>,
+    direction: Direction,
+    amount_zat: Option<i64>,
+    memo: Option<Vec<u8>>,
+}
+
+impl Row {
+    fn push_to(&self, csv: &mut String) {
+        let timestamp = self
+            .block_time
+            .and_then(|t| time::OffsetDateTime::from_unix_timestamp(t).ok())
+            .map(|datetime| {
+                datetime
+                    .format(&Rfc3339)
+                    .expect("datetime can be formatted")
+            })
+            .unwrap_or_default();
+        let height = self
+            .mined_height
+            .map(|height| height.to_string())
+            .unwrap_or_default();
+        let amount_zat = self
+            .amount_zat
+            .map(|amount| amount.to_string())
+            .unwrap_or_default();
+        let amount_zec = self.amount_zat.map(format_zec).unwrap_or_default();
+        let memo = self.memo.as_deref().map(memo_field).unwrap_or_default();
+        let txid = self.txid.to_string();
+        let account_uuid = self.account_uuid.to_string();
+
+        push_record(
+            csv,
+            [
+                timestamp.as_str(),
+                height.as_str(),
+                txid.as_str(),
+                account_uuid.as_str(),
+                self.address.as_deref().unwrap_or(""),
+                self.pool.unwrap_or(""),
+                self.direction.as_str(),
+                amount_zat.as_str(),
+                amount_zec.as_str(),
+                memo.as_str(),
+            ],
+        );
+    }
+}
+
+/// Returns the rows of the history, ordered from oldest to newest, with the amounts of
+/// `fee` rows left to be filled in.
+fn query_rows(
+    conn: &rusqlite::Connection,
+    account_uuid: Option<Uuid>,
+    start_height: Option<u32>,
+    end_height: Option<u32>,
+) -> Result<Vec<Row>, SqliteClientError> {
+    let mut stmt = conn.prepare(
+        "WITH entries AS (
+            SELECT txid, output_pool, output_index, to_address, value, memo,
+                from_account_uuid AS account_uuid, 0 AS direction
+            FROM v_tx_outputs
+            WHERE from_account_uuid IS NOT NULL AND NOT is_change
+            UNION ALL
+            -- The fee is attributed to the first account that sent any of the
+            -- transaction's outputs, including change.
+            SELECT o.txid, NULL, NULL, NULL, NULL, NULL,
+                (
+                    SELECT a.uuid
+                    FROM v_tx_outputs s
+                    JOIN accounts a ON a.uuid = s.from_account_uuid
+                    WHERE s.txid = o.txid
+                    ORDER BY a.id
+                    LIMIT 1
+                ),
+                1
+            FROM (
+                SELECT DISTINCT txid FROM v_tx_outputs WHERE from_account_uuid IS NOT NULL
+            ) o
+            UNION ALL
+            SELECT txid, output_pool, output_index, to_address, value, memo,
+                to_account_uuid AS account_uuid, 2 AS direction
+            FROM v_tx_outputs
+            WHERE to_account_uuid IS NOT NULL AND NOT is_change
+        )
+        SELECT e.txid,
+            e.account_uuid,
+            e.to_address,
+            e.direction,
+            e.value,
+            e.output_pool,
+            e.memo,
+            v.mined_height,
+            v.block_time
+        FROM entries e
+        JOIN transactions t ON t.txid = e.txid
+        JOIN v_transactions v ON v.txid = e.txid AND v.account_uuid = e.account_uuid
+        JOIN accounts a ON a.uuid = e.account_uuid
+        WHERE NOT v.expired_unmined
+          AND (:account_uuid IS NULL OR e.account_uuid = :account_uuid)
+          -- Unmined transactions are only exported if no end height is given, as in
+          -- `z_listtransactions`.
+          AND (
+            :start_height IS NULL
+            OR v.mined_height >= :start_height
+            OR (v.mined_height IS NULL AND :end_height IS NULL)
+          )
+          AND (:end_height IS NULL OR v.mined_height < :end_height)
+        ORDER BY v.mined_height ASC NULLS LAST,
+            t.tx_index ASC NULLS LAST,
+            t.id_tx,
+            a.id,
+            e.direction,
+            e.output_pool,
+            e.output_index",
+    )?;
+
+    stmt.query_and_then::<_, SqliteClientError, _, _>(
+        named_params! {
+            ":account_uuid": account_uuid.map(|uuid| uuid.as_bytes().to_vec()),
+            ":start_height": start_height,
+            ":end_height": end_height,
+        },
+        |row| {
+            let direction = Direction::from_code(row.get("direction")?)?;
+            let value = row.get::<_, Option<i64>>("value")?;
+
+            Ok(Row {
+                block_time: row.get("block_time")?,
+                mined_height: row.get("mined_height")?,
+                txid: TxId::from_bytes(row.get("txid")?),
+                account_uuid: Uuid::from_bytes(row.get("account_uuid")?),
+                address: row.get("to_address")?,
+                pool: row
+                    .get::<_, Option<i64>>("output_pool")?
+                    .map(|pool_code| pool_name(pool_code).map(|(pool, _)| pool))
+                    .transpose()?,
+                direction,
+                amount_zat: match direction {
+                    Direction::Send => value.map(|value| -value),
+                    // Filled in by `call`.
+                    Direction::Fee => None,
+                    Direction::Receive => value,
+                },
+                memo: row.get("memo")?,
+            })
+        },
+    )?
+    .collect()
+}
+
+/// Appends a record with the given fields to `csv`.
+fn push_record<'a>(csv: &mut String, fields: impl IntoIterator<Item = &'a str>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            csv.push(',');
+        }
+        csv.push_str(&escape_field(field));
+    }
+    csv.push_str("\r\n");
+}
+
+/// Escapes `field` for use in a CSV record.
+fn escape_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Renders a memo for the `memo` column.
+///
+/// Spreadsheet applications evaluate fields that start with `=`, `+`, `-`, or `@` as
+/// formulas, and anyone can send a memo to the wallet. Text memos that start with one of
+/// these characters are therefore prefixed with `'`, which spreadsheets hide.
+fn memo_field(memo: &[u8]) -> String {
+    match Memo::from_bytes(memo) {
+        Ok(Memo::Empty) => String::new(),
+        Ok(Memo::Text(text)) => {
+            let text = String::from(text);
+            if text.starts_with(['=', '+', '-', '@']) {
+                format!("'{text}")
+            } else {
+                text
+            }
+        }
+        _ => hex::encode(memo),
+    }
+}
+
+/// Formats an amount of zatoshis as ZEC, with 8 decimal places.
+fn format_zec(zat: i64) -> String {
+    let sign = if zat < 0 { "-" } else { "" };
+    let zat = zat.unsigned_abs();
+    format!("{sign}{}.{:08}", zat / 100_000_000, zat % 100_000_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_field, format_zec, memo_field, push_record};
+
+    #[test]
+    fn fields_are_escaped() {
+        assert_eq!(escape_field("plain memo"), "plain memo");
+        assert_eq!(escape_field(""), "");
+        assert_eq!(escape_field("a,b"), "\"a,b\"");
+        assert_eq!(escape_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_field("line 1\nline 2"), "\"line 1\nline 2\"");
+        assert_eq!(escape_field("line 1\r\nline 2"), "\"line 1\r\nline 2\"");
+        assert_eq!(escape_field("\""), "\"\"\"\"");
+    }
+
+    #[test]
+    fn records_keep_awkward_memos_in_one_field() {
+        let mut csv = String::new();
+        push_record(&mut csv, ["a", "Invoice 12, \"paid\"\nThanks", "c"]);
+        push_record(&mut csv, ["", "", ""]);
+        assert_eq!(csv, "a,\"Invoice 12, \"\"paid\"\"\nThanks\",c\r\n,,\r\n",);
+    }
+
+    #[test]
+    fn memos_are_rendered() {
+        let memo = |bytes: &[u8]| {
+            let mut memo = [0; 512];
+            memo[..bytes.len()].copy_from_slice(bytes);
+            memo_field(&memo)
+        };
+
+        assert_eq!(memo(&[0xf6]), "");
+        assert_eq!(memo(b"Thanks, \"Bob\""), "Thanks, \"Bob\"");
+        assert_eq!(memo("caf\u{e9}".as_bytes()), "caf\u{e9}");
+        assert_eq!(memo(b"=1+1"), "'=1+1");
+        assert_eq!(memo(b"-5"), "'-5");
+
+        // Invalid UTF-8 and arbitrary data are hex-encoded.
+        let invalid = memo(&[0xc3, 0x28]);
+        assert_eq!(invalid.len(), 1024);
+        assert!(invalid.starts_with("c328000000"));
+        assert!(memo(&[0xff, 0x01]).starts_with("ff01"));
+    }
+
+    #[test]
+    fn zec_amounts() {
+        assert_eq!(format_zec(0), "0.00000000");
+        assert_eq!(format_zec(1), "0.00000001");
+        assert_eq!(format_zec(123_456_789), "1.23456789");
+        assert_eq!(format_zec(-100_000_000), "-1.00000000");
+        assert_eq!(format_zec(-50), "-0.00000050");
+    }
+}
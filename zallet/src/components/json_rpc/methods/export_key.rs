@@ -0,0 +1,168 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use sapling::zip32::ExtendedSpendingKey;
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_client_backend::data_api::{Account as _, AccountPurpose, AccountSource, WalletRead};
+use zcash_client_sqlite::AccountUuid;
+use zcash_keys::{
+    address::Address,
+    encoding::encode_extended_spending_key,
+    keys::{Era, UnifiedFullViewingKey, UnifiedSpendingKey},
+};
+use zcash_protocol::consensus::NetworkConstants;
+
+use crate::{
+    components::{
+        audit::{self, Event},
+        database::DbConnection,
+        json_rpc::{
+            server::{ComponentFailure, LegacyCode},
+            utils::ensure_wallet_is_unlocked,
+        },
+        keystore::KeyStore,
+    },
+    network::Network,
+};
+
+use super::{MethodCategory, z_send_many::account_spending_key};
+
+/// Response to a `z_exportkey` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The spending key for the address.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(String);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_ADDRESS_DESC: &str = "The Sapling or unified address for the spending key.";
+
+pub(crate) async fn call(wallet: &DbConnection, keystore: &KeyStore, address: &str) -> Response {
+    let _unlocked = ensure_wallet_is_unlocked(keystore).await?;
+
+    let params = wallet.params();
+    let address = Address::decode(params, address)
+        .ok_or_else(|| LegacyCode::InvalidAddressOrKey.with_static("Invalid address"))?;
+
+    let (sapling, orchard) = match &address {
+        Address::Sapling(addr) => (Some(addr), None),
+        Address::Unified(ua) => (ua.sapling(), ua.orchard()),
+        Address::Transparent(_) | Address::Tex(_) => {
+            return Err(LegacyCode::InvalidAddressOrKey
+                .with_static("Only Sapling and unified addresses are supported"));
+        }
+    };
+
+    let not_held = || {
+        LegacyCode::InvalidAddressOrKey
+            .with_static("Wallet does not hold the spending key for this address")
+    };
+
+    let account_id = find_account(wallet, sapling, orchard)?.ok_or_else(not_held)?;
+    let account = wallet
+        .get_account(account_id)
+        .map_err(ComponentFailure::database)?
+        // This would be a race condition between this and account deletion.
+        .ok_or_else(|| ComponentFailure::internal("Account was deleted during the request"))?;
+    let ufvk = account.ufvk().ok_or_else(not_held)?;
+
+    if let AccountSource::Imported {
+        purpose: AccountPurpose::ViewOnly,
+        ..
+    } = account.source()
+    {
+        return Err(not_held());
+    }
+
+    let usk = account_spending_key(keystore, params, &account).await?;
+
+    // Keys derived from legacy `zcashd` HD paths do not match the account's UFVK.
+    let usk =
+        usk.filter(|usk| usk.to_unified_full_viewing_key().encode(params) == ufvk.encode(params));
+
+    let key = match (&address, usk) {
+        (Address::Unified(_), Some(usk)) => encode_unified_spending_key(&usk),
+        (_, Some(usk)) => encode_sapling_spending_key(params, usk.sapling()),
+        (Address::Sapling(_), None) => {
+            let dfvk = ufvk.sapling().ok_or_else(not_held)?;
+            let extsk = keystore
+                .decrypt_standalone_sapling_keys()
+                .await
+                .map_err(ComponentFailure::keystore)?
+                .into_iter()
+                .find(|extsk| {
+                    extsk.to_diversifiable_full_viewing_key().to_bytes() == dfvk.to_bytes()
+                })
+                .ok_or_else(not_held)?;
+
+            encode_sapling_spending_key(params, &extsk)
+        }
+        (_, None) => return Err(not_held()),
+    };
+
+    audit::record(Event::SpendingKeyExported {
+        address: address.encode(params),
+    });
+
+    Ok(ResultType(key))
+}
+
+/// Encodes a unified spending key in the format accepted by `z_importkey`.
+///
+/// Unified spending keys have no standard string encoding, so they are exported as the
+/// hex encoding of their binary serialization. Unlike Bech32 keys, this does not record
+/// the network that the key is for.
+pub(super) fn encode_unified_spending_key(usk: &UnifiedSpendingKey) -> String {
+    hex::encode(usk.to_bytes(Era::Orchard))
+}
+
+/// Encodes a Sapling extended spending key for the given network.
+pub(super) fn encode_sapling_spending_key(params: &Network, extsk: &ExtendedSpendingKey) -> String {
+    encode_extended_spending_key(params.hrp_sapling_extended_spending_key(), extsk)
+}
+
+/// Returns the account whose viewing key derives the given receivers, if any.
+pub(super) fn find_account(
+    wallet: &DbConnection,
+    sapling: Option<&sapling::PaymentAddress>,
+    orchard: Option<&orchard::Address>,
+) -> RpcResult<Option<AccountUuid>> {
+    for account_id in wallet
+        .get_account_ids()
+        .map_err(ComponentFailure::database)?
+    {
+        let Some(account) = wallet
+            .get_account(account_id)
+            .map_err(ComponentFailure::database)?
+        else {
+            continue;
+        };
+
+        if account
+            .ufvk()
+            .is_some_and(|ufvk| derives(ufvk, sapling, orchard))
+        {
+            return Ok(Some(account_id));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns whether `ufvk` derives any of the given receivers.
+fn derives(
+    ufvk: &UnifiedFullViewingKey,
+    sapling: Option<&sapling::PaymentAddress>,
+    orchard: Option<&orchard::Address>,
+) -> bool {
+    let sapling = sapling
+        .zip(ufvk.sapling())
+        .is_some_and(|(addr, dfvk)| dfvk.decrypt_diversifier(addr).is_some());
+    let orchard = orchard
+        .zip(ufvk.orchard())
+        .is_some_and(|(addr, fvk)| fvk.scope_for_address(addr).is_some());
+
+    sapling || orchard
+}
@@ -0,0 +1,57 @@
+use documented::Documented;
+use jsonrpsee::core::{JsonValue, RpcResult};
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_client_backend::data_api::{Account as _, WalletRead};
+
+use crate::components::{
+    database::DbConnection,
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::parse_account_parameter,
+    },
+};
+
+use super::MethodCategory;
+
+#[cfg(zallet_build = "wallet")]
+use crate::components::keystore::KeyStore;
+
+/// Response to a `z_exportviewingkey` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The account's unified full viewing key.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(String);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_ACCOUNT_DESC: &str =
+    "The UUID, name, or ZIP 32 account index of the account.";
+
+pub(crate) async fn call(
+    wallet: &DbConnection,
+    #[cfg(zallet_build = "wallet")] keystore: &KeyStore,
+    account: JsonValue,
+) -> Response {
+    let account_id = parse_account_parameter(
+        wallet,
+        #[cfg(zallet_build = "wallet")]
+        keystore,
+        &account,
+    )
+    .await?;
+
+    let account = wallet
+        .get_account(account_id)
+        .map_err(ComponentFailure::database)?
+        // This would be a race condition between this and account deletion.
+        .ok_or_else(|| ComponentFailure::internal("Account was deleted during the request"))?;
+
+    let ufvk = account.ufvk().ok_or_else(|| {
+        LegacyCode::Wallet.with_static("Account only has an incoming viewing key")
+    })?;
+
+    Ok(ResultType(ufvk.encode(wallet.params())))
+}
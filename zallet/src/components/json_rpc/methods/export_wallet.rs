@@ -0,0 +1,387 @@
+//! The wallet export format written by `backupwallet` and `z_exportwallet`.
+//!
+//! An export is a UTF-8 text file. Lines starting with `#` are comments, and every other
+//! line is a record of tab-separated fields, the first of which is the record type:
+//!
+//! - `mnemonic <seed fingerprint> <mnemonic phrase>`
+//! - `account <UUID> <birthday height> <purpose> <seed fingerprint> <ZIP 32 account
+//!   index> <viewing key> <name>`
+//!   - `purpose` is `spending` or `view_only`.
+//!   - `seed fingerprint` and `ZIP 32 account index` are `-` if the account was imported
+//!     without derivation information.
+//!   - `viewing key` is the account's UFVK, or its UIVK if it has no UFVK.
+//!   - `name` is hex-encoded, or `-` if the account has no name.
+//! - `address <account UUID> <address> <diversifier index>`
+//!   - `diversifier index` is `-` if the address was not derived from the account's
+//!     viewing key.
+//! - `transparent_key <hex-encoded secret key>`
+//! - `sapling_key <encoded extended spending key>`
+//! - `unified_key <hex-encoded unified spending key>`
+//!
+//! By default the export is encrypted with age, and ASCII-armored. The plaintext
+//! described here is then the content of the age file.
+//!
+//! The header comments record the format version, which is incremented whenever records
+//! or fields are added. Importers ignore record types and trailing fields that they do
+//! not know, so that an export with a newer format version can still be imported.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Component, Path, PathBuf};
+
+use abscissa_core::Application;
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use secrecy::{ExposeSecret, Zeroize};
+use serde::Serialize;
+use zcash_client_backend::data_api::{
+    Account as _, AccountPurpose, AccountSource, AddressSource, WalletRead,
+};
+use zcash_protocol::consensus::{NetworkType, Parameters};
+
+use crate::{
+    components::{
+        audit::{self, Event},
+        database::DbConnection,
+        json_rpc::{
+            server::{ComponentFailure, LegacyCode},
+            utils::ensure_wallet_is_unlocked,
+        },
+        keystore::{KeyStore, parse_recipients},
+    },
+    prelude::*,
+};
+
+use super::{
+    MethodCategory,
+    export_key::{encode_sapling_spending_key, encode_unified_spending_key},
+};
+
+/// Response to a `backupwallet` or `z_exportwallet` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The absolute path of the file that the wallet was exported to.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(String);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_FILENAME_DESC: &str =
+    "The name of the file to write, relative to the directory configured by `external.export_dir`.";
+pub(super) const PARAM_RECIPIENTS_DESC: &str =
+    "The age recipients to encrypt the export to. Defaults to the wallet's own recipients.";
+pub(super) const PARAM_ALLOW_PLAINTEXT_DESC: &str =
+    "If true, write the export in plaintext instead of encrypting it.";
+
+/// The version of the export format.
+pub(super) const FORMAT_VERSION: u32 = 1;
+
+pub(crate) async fn call(
+    wallet: &DbConnection,
+    keystore: &KeyStore,
+    method: &'static str,
+    filename: &str,
+    recipients: Option<Vec<String>>,
+    allow_plaintext: Option<bool>,
+) -> Response {
+    let path = export_path(APP.config().export_dir().as_deref(), filename)?;
+
+    let allow_plaintext = allow_plaintext.unwrap_or(false);
+    let recipients = match recipients {
+        Some(_) if allow_plaintext => {
+            return Err(LegacyCode::InvalidParameter
+                .with_static("Cannot set recipients for an export that is not encrypted"));
+        }
+        Some(recipients) if recipients.is_empty() => {
+            return Err(LegacyCode::InvalidParameter.with_static("No recipients were given"));
+        }
+        Some(recipients) => Some(parse_recipients(recipients).map_err(|e| {
+            LegacyCode::InvalidParameter.with_message(format!("Invalid age recipient: {e}"))
+        })?),
+        None => None,
+    };
+
+    // Exporting from a locked wallet fails rather than omitting its secrets.
+    let _unlocked = ensure_wallet_is_unlocked(keystore).await?;
+
+    let mut dump = dump(wallet, keystore).await?;
+    let written = if allow_plaintext {
+        Ok(write_new_file(&path, dump.as_bytes()))
+    } else {
+        keystore
+            .encrypt_export(recipients, &dump)
+            .await
+            .map(|export| write_new_file(&path, &export))
+    };
+    dump.zeroize();
+
+    written
+        .map_err(ComponentFailure::keystore)?
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => LegacyCode::Wallet
+                .with_message(format!("Cannot overwrite existing file {}", path.display())),
+            _ => LegacyCode::Wallet.with_message(format!(
+                "Cannot write wallet dump file {}: {e}",
+                path.display()
+            )),
+        })?;
+
+    audit::record(Event::WalletExported {
+        method: method.into(),
+        path: path.clone(),
+        encrypted: !allow_plaintext,
+    });
+
+    Ok(ResultType(path.display().to_string()))
+}
+
+/// Returns the path within `export_dir` that `filename` refers to.
+///
+/// `filename` must be a relative path that stays within `export_dir`.
+pub(super) fn export_path(export_dir: Option<&Path>, filename: &str) -> RpcResult<PathBuf> {
+    let export_dir = export_dir.ok_or_else(|| {
+        LegacyCode::Wallet
+            .with_static("Cannot export wallet until the external.export_dir option has been set")
+    })?;
+
+    // Relative paths would otherwise be resolved against Zallet's working directory.
+    if !export_dir.is_absolute() {
+        return Err(LegacyCode::Wallet.with_static("external.export_dir must be an absolute path"));
+    }
+
+    let filename = Path::new(filename);
+    if filename.as_os_str().is_empty()
+        || !filename
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(LegacyCode::InvalidParameter.with_static(
+            "Filename must be a relative path within external.export_dir, without \"..\" components",
+        ));
+    }
+
+    Ok(export_dir.join(filename))
+}
+
+/// Writes `contents` to a new file at `path` that is only readable by its owner.
+pub(super) fn write_new_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Returns the name of the network that is recorded in the export header.
+pub(super) fn network_name(network: NetworkType) -> &'static str {
+    match network {
+        NetworkType::Main => "main",
+        NetworkType::Test => "test",
+        NetworkType::Regtest => "regtest",
+    }
+}
+
+/// Renders the wallet in the export format.
+pub(super) async fn dump(wallet: &DbConnection, keystore: &KeyStore) -> RpcResult<String> {
+    let params = wallet.params();
+    let mut dump = String::new();
+
+    // Writing to a `String` cannot fail.
+    let mut line = |args: std::fmt::Arguments<'_>| {
+        dump.write_fmt(args).expect("infallible");
+        dump.push('\n');
+    };
+
+    line(format_args!(
+        "# Wallet dump created by Zallet {}",
+        crate::build::PKG_VERSION
+    ));
+    line(format_args!("# Format version: {FORMAT_VERSION}"));
+    line(format_args!(
+        "# Network: {}",
+        network_name(params.network_type())
+    ));
+    if let Some(height) = wallet.chain_height().map_err(ComponentFailure::database)? {
+        line(format_args!("# Best block at time of backup: {height}"));
+    }
+    line(format_args!(
+        "# Record formats are documented in the Zallet book."
+    ));
+
+    line(format_args!(""));
+    line(format_args!("# Mnemonic seed phrases"));
+    for (seed_fp, mnemonic) in keystore
+        .decrypt_mnemonics()
+        .await
+        .map_err(ComponentFailure::keystore)?
+    {
+        line(format_args!(
+            "mnemonic\t{seed_fp}\t{}",
+            mnemonic.expose_secret()
+        ));
+    }
+
+    line(format_args!(""));
+    line(format_args!("# Accounts"));
+    let mut addresses = vec![];
+    for account_id in wallet
+        .get_account_ids()
+        .map_err(ComponentFailure::database)?
+    {
+        let account = wallet
+            .get_account(account_id)
+            .map_err(ComponentFailure::database)?
+            // This would be a race condition between this and account deletion.
+            .ok_or_else(|| ComponentFailure::internal("Account was deleted during the request"))?;
+
+        let birthday = wallet
+            .get_account_birthday(account_id)
+            .map_err(ComponentFailure::database)?;
+
+        let purpose = match account.source() {
+            AccountSource::Derived { .. }
+            | AccountSource::Imported {
+                purpose: AccountPurpose::Spending { .. },
+                ..
+            } => "spending",
+            AccountSource::Imported {
+                purpose: AccountPurpose::ViewOnly,
+                ..
+            } => "view_only",
+        };
+
+        let (seed_fp, account_index) = match account.source().key_derivation() {
+            Some(derivation) => (
+                derivation.seed_fingerprint().to_string(),
+                u32::from(derivation.account_index()).to_string(),
+            ),
+            None => ("-".into(), "-".into()),
+        };
+
+        let viewing_key = match account.ufvk() {
+            Some(ufvk) => ufvk.encode(params),
+            None => account.uivk().encode(params),
+        };
+
+        line(format_args!(
+            "account\t{}\t{birthday}\t{purpose}\t{seed_fp}\t{account_index}\t{viewing_key}\t{}",
+            account_id.expose_uuid(),
+            account.name().map_or("-".into(), hex::encode),
+        ));
+
+        for address in wallet
+            .list_addresses(account_id)
+            .map_err(ComponentFailure::database)?
+        {
+            let diversifier_index = match address.source() {
+                AddressSource::Derived {
+                    diversifier_index, ..
+                } => u128::from(diversifier_index).to_string(),
+                #[cfg(feature = "transparent-key-import")]
+                AddressSource::Standalone => "-".into(),
+            };
+            addresses.push((
+                account_id,
+                address.address().encode(params),
+                diversifier_index,
+            ));
+        }
+    }
+
+    line(format_args!(""));
+    line(format_args!("# Addresses"));
+    for (account_id, address, diversifier_index) in addresses {
+        line(format_args!(
+            "address\t{}\t{address}\t{diversifier_index}",
+            account_id.expose_uuid()
+        ));
+    }
+
+    #[cfg(feature = "transparent-key-import")]
+    {
+        line(format_args!(""));
+        line(format_args!("# Imported transparent keys"));
+        for key in keystore
+            .decrypt_standalone_transparent_keys()
+            .await
+            .map_err(ComponentFailure::keystore)?
+        {
+            line(format_args!(
+                "transparent_key\t{}",
+                hex::encode(key.secret_bytes())
+            ));
+        }
+    }
+
+    line(format_args!(""));
+    line(format_args!("# Imported Sapling keys"));
+    for key in keystore
+        .decrypt_standalone_sapling_keys()
+        .await
+        .map_err(ComponentFailure::keystore)?
+    {
+        line(format_args!(
+            "sapling_key\t{}",
+            encode_sapling_spending_key(params, &key)
+        ));
+    }
+
+    line(format_args!(""));
+    line(format_args!("# Imported unified keys"));
+    for key in keystore
+        .decrypt_standalone_unified_keys()
+        .await
+        .map_err(ComponentFailure::keystore)?
+    {
+        line(format_args!(
+            "unified_key\t{}",
+            encode_unified_spending_key(&key)
+        ));
+    }
+
+    line(format_args!(""));
+    line(format_args!("# End of dump"));
+
+    Ok(dump)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::export_path;
+
+    #[test]
+    fn export_paths() {
+        let export_dir = if cfg!(windows) {
+            Path::new("C:\\exports")
+        } else {
+            Path::new("/exports")
+        };
+        let path =
+            |filename| export_path(Some(export_dir), filename).map_err(|e| e.message().to_owned());
+
+        assert_eq!(path("dump"), Ok(export_dir.join("dump")));
+        assert_eq!(
+            path("daily/dump"),
+            Ok(export_dir.join("daily").join("dump"))
+        );
+
+        for filename in ["", "..", "../dump", "daily/../../dump", "./dump"] {
+            assert!(path(filename).is_err(), "{filename:?} should be rejected");
+        }
+        assert!(path(if cfg!(windows) { "C:\\dump" } else { "/dump" }).is_err());
+
+        assert_eq!(
+            export_path(None, "dump").map_err(|e| e.message().to_owned()),
+            Err("Cannot export wallet until the external.export_dir option has been set".into()),
+        );
+        assert!(export_path(Some(Path::new("exports")), "dump").is_err());
+    }
+}
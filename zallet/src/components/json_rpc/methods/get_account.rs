@@ -12,6 +12,8 @@ use crate::components::{
     },
 };
 
+use super::MethodCategory;
+
 /// Response to a `z_getaccount` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 pub(crate) type ResultType = Account;
@@ -44,6 +46,8 @@ pub(crate) struct Account {
     addresses: Vec<Address>,
 }
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_ACCOUNT_UUID_DESC: &str = "The UUID of the account.";
 
 pub(crate) fn call(wallet: &DbConnection, account_uuid: String) -> Response {
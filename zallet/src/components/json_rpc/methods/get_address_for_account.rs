@@ -1,25 +1,29 @@
 use documented::Documented;
 use jsonrpsee::{
     core::{JsonValue, RpcResult},
-    types::{ErrorCode as RpcErrorCode, ErrorObjectOwned},
+    types::ErrorObjectOwned,
 };
 use schemars::JsonSchema;
 use serde::Serialize;
 use zcash_address::unified;
 use zcash_client_backend::{
+    address::UnifiedAddress,
     data_api::WalletWrite,
     keys::{AddressGenerationError, ReceiverRequirement, UnifiedAddressRequest},
 };
-use zcash_client_sqlite::error::SqliteClientError;
+use zcash_client_sqlite::{AccountUuid, error::SqliteClientError};
+use zip32::DiversifierIndex;
 
 use crate::components::{
     database::DbConnection,
     json_rpc::{
-        server::LegacyCode,
+        server::{ComponentFailure, LegacyCode},
         utils::{parse_account_parameter, parse_diversifier_index},
     },
 };
 
+use super::MethodCategory;
+
 #[cfg(zallet_build = "wallet")]
 use crate::components::keystore::KeyStore;
 
@@ -47,21 +51,22 @@ pub(crate) struct Address {
     address: String,
 }
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
 pub(super) const PARAM_ACCOUNT_DESC: &str =
-    "Either the UUID or ZIP 32 account index of the account to derive from.";
+    "The UUID, name, or ZIP 32 account index of the account to derive from.";
 pub(super) const PARAM_RECEIVER_TYPES_DESC: &str =
     "Receiver types to include in the derived address.";
-pub(super) const PARAM_DIVERSIFIER_INDEX_DESC: &str = "A specific diversifier index to derive at.";
+pub(super) const PARAM_DIVERSIFIER_INDEX_DESC: &str = "A specific diversifier index to derive at, as a number, a decimal string, or a 0x-prefixed little-endian hex string.";
 
 pub(crate) async fn call(
     wallet: &mut DbConnection,
     #[cfg(zallet_build = "wallet")] keystore: KeyStore,
     account: JsonValue,
     receiver_types: Option<Vec<String>>,
-    diversifier_index: Option<u128>,
+    diversifier_index: Option<JsonValue>,
 ) -> Response {
     let account_id = parse_account_parameter(
-        #[cfg(zallet_build = "wallet")]
         wallet,
         #[cfg(zallet_build = "wallet")]
         &keystore,
@@ -69,64 +74,12 @@ pub(crate) async fn call(
     )
     .await?;
 
-    let (receiver_types, request) = match receiver_types {
-        Some(receiver_types) if !receiver_types.is_empty() => {
-            let mut orchard = ReceiverRequirement::Omit;
-            let mut sapling = ReceiverRequirement::Omit;
-            let mut p2pkh = ReceiverRequirement::Omit;
-            let mut invalid_receivers = vec![];
-            for receiver_type in &receiver_types {
-                match receiver_type.as_str() {
-                    "orchard" => orchard = ReceiverRequirement::Require,
-                    "sapling" => sapling = ReceiverRequirement::Require,
-                    "p2sh" => {
-                        return Err(LegacyCode::Wallet.with_static(
-                            "Error: P2SH addresses can not be created using this RPC method.",
-                        ));
-                    }
-                    "p2pkh" => p2pkh = ReceiverRequirement::Require,
-                    _ => invalid_receivers.push(receiver_type),
-                }
-            }
-
-            if invalid_receivers.is_empty() {
-                UnifiedAddressRequest::custom(orchard, sapling, p2pkh)
-                    .map(|request| (receiver_types, request))
-                    .map_err(|_| {
-                        LegacyCode::InvalidParameter.with_static(
-                            "Error: cannot generate an address containing no shielded receivers.",
-                        )
-                    })
-            } else {
-                Err(LegacyCode::InvalidParameter.with_message(format!(
-                    "{:?} {}. Arguments must be “p2pkh”, “sapling”, or “orchard”",
-                    // TODO: Format nicely (matching zcashd).
-                    invalid_receivers,
-                    if invalid_receivers.len() == 1 {
-                        "is an invalid receiver type"
-                    } else {
-                        "are invalid receiver types"
-                    }
-                )))
-            }
-        }
-        _ => {
-            // zcashd default is the best and second-best shielded receiver types, and the
-            // transparent (P2PKH) receiver type. That currently corresponds to all possible
-            // receiver types.
-            let request = UnifiedAddressRequest::unsafe_custom(
-                ReceiverRequirement::Require,
-                ReceiverRequirement::Require,
-                ReceiverRequirement::Require,
-            );
-            Ok((
-                vec!["orchard".into(), "sapling".into(), "p2pkh".into()],
-                request,
-            ))
-        }
-    }?;
+    let (_, request) = parse_request(receiver_types.as_deref())?;
 
-    let diversifier_index = diversifier_index.map(parse_diversifier_index).transpose()?;
+    let diversifier_index = diversifier_index
+        .as_ref()
+        .map(parse_diversifier_index)
+        .transpose()?;
 
     let (address, diversifier_index) = if let Some(diversifier_index) = diversifier_index {
         match wallet
@@ -146,26 +99,155 @@ pub(crate) async fn call(
             .get_next_available_address(account_id, request)
             .map_err(|e| map_sqlite_error(e, &account))?
             // This would be a race condition between this and account deletion.
-            .ok_or(RpcErrorCode::InternalError.into())
+            .ok_or_else(|| {
+                ComponentFailure::internal("Account was deleted during the request").into()
+            })
     }?;
 
-    // Only include `account` in the response if it was provided in the request. We rely
-    // on the checks performed in `parse_account_parameter`.
-    let account = match account {
-        JsonValue::Number(n) => n.as_u64(),
-        _ => None,
-    };
+    Ok(Address::new(
+        wallet,
+        account_id,
+        &account,
+        &address,
+        diversifier_index,
+    ))
+}
+
+impl Address {
+    /// Describes an address derived from the given account at `diversifier_index`.
+    ///
+    /// The receiver types are read from the address itself, so that they select the same
+    /// address when passed back to `z_getaddressforaccount` with the same index.
+    pub(super) fn new(
+        wallet: &DbConnection,
+        account_id: AccountUuid,
+        account: &JsonValue,
+        address: &UnifiedAddress,
+        diversifier_index: DiversifierIndex,
+    ) -> Self {
+        // Only include `account` in the response if it was provided in the request. We
+        // rely on the checks performed in `parse_account_parameter`.
+        let account = match account {
+            JsonValue::Number(n) => n.as_u64(),
+            _ => None,
+        };
+
+        Address {
+            account_uuid: account_id.expose_uuid().to_string(),
+            account,
+            diversifier_index: diversifier_index.into(),
+            receiver_types: receiver_types(address),
+            address: address.encode(wallet.params()),
+        }
+    }
+}
+
+/// Parses the optional `receiver_types` parameter, returning the canonical names of the
+/// requested receiver types along with the corresponding request.
+pub(super) fn parse_request(
+    receiver_types: Option<&[String]>,
+) -> RpcResult<(Vec<String>, UnifiedAddressRequest)> {
+    match receiver_types {
+        Some(receiver_types) if !receiver_types.is_empty() => parse_receiver_types(receiver_types),
+        _ => {
+            // zcashd default is the best and second-best shielded receiver types, and the
+            // transparent (P2PKH) receiver type. That currently corresponds to all possible
+            // receiver types.
+            let request = UnifiedAddressRequest::unsafe_custom(
+                ReceiverRequirement::Require,
+                ReceiverRequirement::Require,
+                ReceiverRequirement::Require,
+            );
+            Ok((
+                vec!["orchard".into(), "sapling".into(), "p2pkh".into()],
+                request,
+            ))
+        }
+    }
+}
 
-    Ok(Address {
-        account_uuid: account_id.expose_uuid().to_string(),
-        account,
-        diversifier_index: diversifier_index.into(),
-        receiver_types,
-        address: address.encode(wallet.params()),
-    })
+/// Returns the names of the receiver types that `address` contains, in the order that
+/// `zcashd` lists them.
+fn receiver_types(address: &UnifiedAddress) -> Vec<String> {
+    [
+        ("orchard", address.has_orchard()),
+        ("sapling", address.has_sapling()),
+        ("p2pkh", address.has_transparent()),
+    ]
+    .into_iter()
+    .filter(|(_, present)| *present)
+    .map(|(name, _)| name.into())
+    .collect()
 }
 
-fn map_address_generation_error(
+/// Parses the `receiver_types` parameter.
+///
+/// Receiver types are matched case-insensitively. Returns the canonical names of the
+/// requested receiver types (in the order that `zcashd` lists them), so that equivalent
+/// lists produce the same request and response.
+fn parse_receiver_types(
+    receiver_types: &[String],
+) -> RpcResult<(Vec<String>, UnifiedAddressRequest)> {
+    let mut orchard = ReceiverRequirement::Omit;
+    let mut sapling = ReceiverRequirement::Omit;
+    let mut p2pkh = ReceiverRequirement::Omit;
+    let mut invalid_receivers = vec![];
+
+    for receiver_type in receiver_types {
+        let requirement = match receiver_type.to_ascii_lowercase().as_str() {
+            "orchard" => &mut orchard,
+            "sapling" => &mut sapling,
+            "p2pkh" => &mut p2pkh,
+            "p2sh" => {
+                return Err(LegacyCode::Wallet.with_static(
+                    "Error: P2SH addresses can not be created using this RPC method.",
+                ));
+            }
+            _ => {
+                invalid_receivers.push(format!("“{receiver_type}”"));
+                continue;
+            }
+        };
+
+        if *requirement == ReceiverRequirement::Require {
+            return Err(LegacyCode::InvalidParameter.with_message(format!(
+                "Error: receiver type “{receiver_type}” is listed more than once.",
+            )));
+        }
+        *requirement = ReceiverRequirement::Require;
+    }
+
+    if let Some((last, rest)) = invalid_receivers.split_last() {
+        return Err(LegacyCode::InvalidParameter.with_message(format!(
+            "{} {}. Arguments must be “p2pkh”, “sapling”, or “orchard”",
+            match rest {
+                [] => last.clone(),
+                [first] => format!("{first} and {last}"),
+                _ => format!("{}, and {last}", rest.join(", ")),
+            },
+            if rest.is_empty() {
+                "is an invalid receiver type"
+            } else {
+                "are invalid receiver types"
+            }
+        )));
+    }
+
+    let request = UnifiedAddressRequest::custom(orchard, sapling, p2pkh).map_err(|_| {
+        LegacyCode::InvalidParameter
+            .with_static("Error: cannot generate an address containing no shielded receivers.")
+    })?;
+
+    let receiver_types = [("orchard", orchard), ("sapling", sapling), ("p2pkh", p2pkh)]
+        .into_iter()
+        .filter(|(_, requirement)| *requirement == ReceiverRequirement::Require)
+        .map(|(name, _)| name.into())
+        .collect();
+
+    Ok((receiver_types, request))
+}
+
+pub(super) fn map_address_generation_error(
     e: AddressGenerationError,
     account: &JsonValue,
 ) -> ErrorObjectOwned {
@@ -237,6 +319,154 @@ fn map_sqlite_error(e: SqliteClientError, account: &JsonValue) -> ErrorObjectOwn
                 u128::from(diversifier_index),
             ))
         }
-        _ => LegacyCode::Database.with_message(e.to_string()),
+        _ => ComponentFailure::database(e).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpsee::core::JsonValue;
+    use zcash_client_sqlite::error::SqliteClientError;
+    use zcash_keys::keys::UnifiedSpendingKey;
+    use zcash_protocol::consensus;
+    use zip32::DiversifierIndex;
+
+    use crate::{
+        components::json_rpc::methods::testing::{TestWallet, error_code},
+        network::Network,
+    };
+
+    use super::{call, map_sqlite_error, parse_receiver_types, parse_request, receiver_types};
+
+    fn parse(receiver_types: &[&str]) -> Result<Vec<String>, String> {
+        let receiver_types = receiver_types
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        parse_receiver_types(&receiver_types)
+            .map(|(receiver_types, _)| receiver_types)
+            .map_err(|e| e.message().to_owned())
+    }
+
+    #[test]
+    fn receiver_types() {
+        // Equivalent lists are canonicalized to the same receiver types.
+        assert_eq!(
+            parse(&["P2PKH", "Orchard"]),
+            Ok(vec!["orchard".into(), "p2pkh".into()])
+        );
+        assert_eq!(parse(&["orchard", "p2pkh"]), parse(&["p2pkh", "ORCHARD"]));
+
+        assert_eq!(
+            parse(&["P2PKH", "p2pkh", "orchard"]),
+            Err("Error: receiver type “p2pkh” is listed more than once.".into()),
+        );
+        assert_eq!(
+            parse(&["orchard", "P2SH"]),
+            Err("Error: P2SH addresses can not be created using this RPC method.".into()),
+        );
+        assert_eq!(
+            parse(&["orchard", "sprout"]),
+            Err("“sprout” is an invalid receiver type. Arguments must be “p2pkh”, “sapling”, or “orchard”".into()),
+        );
+        assert_eq!(
+            parse(&["foo", "orchard", "bar"]),
+            Err("“foo” and “bar” are invalid receiver types. Arguments must be “p2pkh”, “sapling”, or “orchard”".into()),
+        );
+        assert_eq!(
+            parse(&["p2pkh"]),
+            Err("Error: cannot generate an address containing no shielded receivers.".into()),
+        );
+    }
+
+    #[test]
+    fn receiver_types_rederive_the_address() {
+        let params = Network::Consensus(consensus::Network::MainNetwork);
+        let ufvk = UnifiedSpendingKey::from_seed(&params, &[7; 32], zip32::AccountId::ZERO)
+            .unwrap()
+            .to_unified_full_viewing_key();
+
+        for requested in [
+            vec![],
+            vec!["orchard"],
+            vec!["sapling", "p2pkh"],
+            vec!["orchard", "sapling"],
+        ] {
+            let requested = requested.into_iter().map(String::from).collect::<Vec<_>>();
+            let (expected, request) = parse_request(Some(&requested)).unwrap();
+            let (address, diversifier_index) = ufvk
+                .find_address(DiversifierIndex::from(5u32), request)
+                .unwrap();
+
+            // The receiver types read from the address are those requested, and select
+            // the same address at the same index.
+            let reported = receiver_types(&address);
+            assert_eq!(reported, expected);
+            let (_, rederived) = parse_request(Some(&reported)).unwrap();
+            assert_eq!(
+                ufvk.address(diversifier_index, rederived)
+                    .unwrap()
+                    .encode(&params),
+                address.encode(&params),
+            );
+        }
+    }
+
+    #[test]
+    fn wallet_errors() {
+        let account = JsonValue::from(0);
+        assert_eq!(
+            map_sqlite_error(SqliteClientError::AccountUnknown, &account).code(),
+            -4
+        );
+        assert_eq!(
+            map_sqlite_error(SqliteClientError::ChainHeightUnknown, &account).code(),
+            -4
+        );
+        assert_eq!(
+            map_sqlite_error(
+                SqliteClientError::DbError(rusqlite::Error::QueryReturnedNoRows),
+                &account,
+            )
+            .code(),
+            -20
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failures() {
+        let wallet = TestWallet::new().await;
+        let get = async |account: JsonValue, receiver_types: Option<Vec<String>>| {
+            call(
+                &mut wallet.handle().await,
+                #[cfg(zallet_build = "wallet")]
+                wallet.keystore.clone(),
+                account,
+                receiver_types,
+                None,
+            )
+            .await
+        };
+
+        // Invalid parameters.
+        assert_eq!(error_code(get("Savings".into(), None).await), -8);
+        assert_eq!(
+            error_code(
+                get(
+                    "7b1b4ab3-0a46-4c7e-bb8c-3a1cf93b27e8".into(),
+                    Some(vec!["sprout".into()]),
+                )
+                .await
+            ),
+            -8
+        );
+
+        // The account does not exist.
+        assert_eq!(
+            error_code(get("7b1b4ab3-0a46-4c7e-bb8c-3a1cf93b27e8".into(), None).await),
+            -4
+        );
+        #[cfg(zallet_build = "wallet")]
+        assert_eq!(error_code(get(0.into(), None).await), -4);
     }
 }
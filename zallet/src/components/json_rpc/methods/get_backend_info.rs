@@ -0,0 +1,117 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::components::chain::Chain;
+
+use super::MethodCategory;
+
+/// Response to a `getbackendinfo` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The chain sources that the wallet is configured to use.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(Vec<Backend>);
+
+/// A chain source, and what the wallet last observed about it.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct Backend {
+    /// The kind of chain source.
+    ///
+    /// This is always `full_node`: a `zebrad` or `zcashd` node, accessed over JSON-RPC
+    /// through Zallet's embedded indexer.
+    kind: &'static str,
+
+    /// The address of the chain source, as configured in `indexer.validator_address` or
+    /// `indexer.fallback_validator_addresses`.
+    address: String,
+
+    /// The socket address that `address` resolved to when Zallet last connected to it.
+    /// If `indexer.proxy` is set, this is the local port that relays connections to the
+    /// chain source through the proxy.
+    ///
+    /// Omitted if Zallet has not connected to this chain source.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolved_address: Option<String>,
+
+    /// The status of the connection to the chain source, as reported by the indexer
+    /// (for example `Ready`, `Syncing`, or `Offline`).
+    ///
+    /// Omitted if the status has not yet been checked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<String>,
+
+    /// The height of the chain tip that the chain source last reported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tip_height: Option<u32>,
+
+    /// The hash of the chain tip that the chain source last reported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tip_hash: Option<String>,
+
+    /// How long (in milliseconds) the chain source took to report its chain tip, when it
+    /// last responded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u64>,
+
+    /// How long ago (in seconds) the chain source last responded successfully.
+    ///
+    /// Omitted if it has not responded since Zallet started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_success_secs_ago: Option<u64>,
+
+    /// Why the most recent request to (or attempt to use) the chain source failed, if it
+    /// did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_error: Option<String>,
+
+    /// Whether the wallet scans the chain using this chain source.
+    active_for_scanning: bool,
+
+    /// Whether the wallet broadcasts transactions through this chain source.
+    active_for_broadcast: bool,
+
+    /// Why this chain source is (or is not) active.
+    selection_reason: &'static str,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(crate) fn call(chain: &Chain) -> Response {
+    let backends = chain.backends();
+    let fallbacks = backends.list.len() > 1;
+
+    Ok(ResultType(
+        backends
+            .list
+            .into_iter()
+            .enumerate()
+            .map(|(index, info)| {
+                // Zallet uses a single chain source at a time for everything.
+                let active = index == backends.active;
+                Backend {
+                    kind: "full_node",
+                    address: info.address,
+                    resolved_address: info.resolved_address.map(|addr| addr.to_string()),
+                    state: info.status.map(|status| format!("{status:?}")),
+                    tip_height: info.tip.map(|(height, _)| height.into()),
+                    tip_hash: info.tip.map(|(_, hash)| hash.to_string()),
+                    latency_ms: info
+                        .latency
+                        .map(|latency| latency.as_millis().try_into().unwrap_or(u64::MAX)),
+                    last_success_secs_ago: info.last_success.map(|time| time.elapsed().as_secs()),
+                    last_error: info.last_error,
+                    active_for_scanning: active,
+                    active_for_broadcast: active,
+                    selection_reason: match (active, fallbacks) {
+                        (true, false) => "This is the only configured chain source.",
+                        (true, true) => "This is the chain source in use.",
+                        (false, _) => "This is a fallback, used if the chain source in use fails.",
+                    },
+                }
+            })
+            .collect(),
+    ))
+}
@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_client_sqlite::AccountUuid;
+use zcash_protocol::{PoolType, value::Zatoshis};
+
+use crate::components::{
+    database::{DbConnection, confirmed_balances},
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::{
+            JsonZec, parse_as_of_height, parse_minconf, query_height, value_from_zatoshis,
+            watch_only_accounts,
+        },
+    },
+};
+
+use super::MethodCategory;
+
+/// Response to a `getbalance` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The total transparent balance of the wallet, in ZEC or (if `in_zat` is `true`) in
+/// zatoshis.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(untagged)]
+pub(crate) enum ResultType {
+    Zec(JsonZec),
+    Zat(u64),
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_DUMMY_DESC: &str =
+    "Remains for backward compatibility. Must be excluded or set to \"*\".";
+pub(super) const PARAM_MINCONF_DESC: &str =
+    "Only include transactions confirmed at least this many times.";
+pub(super) const PARAM_INCLUDE_WATCHONLY_DESC: &str =
+    "Also include balance in watchonly addresses.";
+pub(super) const PARAM_IN_ZAT_DESC: &str = "Get the result amount in zatoshis.";
+pub(super) const PARAM_AS_OF_HEIGHT_DESC: &str = "Execute the query as if it were run when the blockchain was at the height specified by this argument.";
+
+/// Whether `include_watchonly` is `true` if omitted, as in `zcashd`.
+pub(super) const INCLUDE_WATCHONLY_DEFAULT: bool = false;
+
+pub(crate) fn call(
+    wallet: &DbConnection,
+    dummy: Option<String>,
+    minconf: Option<u32>,
+    include_watchonly: Option<bool>,
+    in_zat: Option<bool>,
+    as_of_height: Option<i64>,
+) -> Response {
+    if dummy.is_some_and(|dummy| dummy != "*") {
+        return Err(LegacyCode::InvalidParameter
+            .with_static("dummy first argument must be excluded or set to \"*\"."));
+    }
+
+    let as_of_height = parse_as_of_height(as_of_height)?;
+    let minconf = parse_minconf(minconf, 1, as_of_height)?;
+    let at = query_height(wallet, as_of_height)?;
+    let watch_only = match include_watchonly.unwrap_or(INCLUDE_WATCHONLY_DEFAULT) {
+        true => HashSet::new(),
+        false => watch_only_accounts(wallet)?,
+    };
+
+    let balance = wallet
+        .with_raw(|conn, _| confirmed_balances(conn, PoolType::Transparent, at, minconf))
+        .map_err(ComponentFailure::database)?
+        .into_iter()
+        .filter(|(account_uuid, _)| !watch_only.contains(&AccountUuid::from_uuid(*account_uuid)))
+        .try_fold(Zatoshis::ZERO, |acc, (_, value)| acc + value)
+        .ok_or_else(|| LegacyCode::Misc.with_static("Value out of range"))?;
+
+    Ok(if in_zat.unwrap_or(false) {
+        ResultType::Zat(balance.into_u64())
+    } else {
+        ResultType::Zec(value_from_zatoshis(balance))
+    })
+}
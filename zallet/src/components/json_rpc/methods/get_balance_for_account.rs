@@ -0,0 +1,144 @@
+use documented::Documented;
+use jsonrpsee::core::{JsonValue, RpcResult};
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_client_backend::data_api::WalletRead;
+use zcash_protocol::{PoolType, value::Zatoshis};
+
+use crate::components::{
+    database::{DbConnection, QueryHeight, confirmed_balances},
+    json_rpc::{
+        server::ComponentFailure,
+        utils::{
+            JsonZec, is_watch_only, parse_account_parameter, parse_as_of_height, parse_minconf,
+            query_height, zec_and_zat,
+        },
+    },
+    keystore::KeyStore,
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_getbalanceforaccount` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = AccountBalance;
+
+/// The balance of an account.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct AccountBalance {
+    /// The balance held in each value pool.
+    pools: Pools,
+
+    /// The minimum number of confirmations that funds needed to be counted.
+    minimum_confirmations: u32,
+
+    /// `true` if the account is watch-only (for example, it was imported from a viewing
+    /// key), in which case the wallet cannot spend its funds.
+    is_watch_only: bool,
+}
+
+/// The balance of an account in each value pool.
+///
+/// Pools in which the account holds no funds are omitted.
+#[derive(Clone, Debug, Default, Serialize, JsonSchema)]
+struct Pools {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transparent: Option<PoolBalance>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sapling: Option<PoolBalance>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    orchard: Option<PoolBalance>,
+}
+
+/// The balance of an account in a single value pool.
+///
+/// Immature coinbase outputs are not included in either `value` or `pending`.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+struct PoolBalance {
+    /// The value of funds that have at least `minimum_confirmations` confirmations, in
+    /// ZEC.
+    ///
+    /// If `minimum_confirmations` is 0, this includes funds in unmined transactions,
+    /// such as change from transactions that the wallet created.
+    value: JsonZec,
+
+    /// The value of `value` in zatoshis.
+    #[serde(rename = "valueZat")]
+    value_zat: u64,
+
+    /// The value of funds that do not yet have `minimum_confirmations` confirmations,
+    /// in ZEC.
+    pending: JsonZec,
+
+    /// The value of `pending` in zatoshis.
+    #[serde(rename = "pendingZat")]
+    pending_zat: u64,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_ACCOUNT_DESC: &str =
+    "The UUID, name, or ZIP 32 account index of the account.";
+pub(super) const PARAM_MINCONF_DESC: &str =
+    "Only include funds in transactions confirmed at least this many times.";
+pub(super) const PARAM_AS_OF_HEIGHT_DESC: &str = "Execute the query as if it were run when the blockchain was at the height specified by this argument.";
+
+pub(crate) async fn call(
+    wallet: &DbConnection,
+    keystore: &KeyStore,
+    account: JsonValue,
+    minconf: Option<u32>,
+    as_of_height: Option<i64>,
+) -> Response {
+    let account_id = parse_account_parameter(wallet, keystore, &account).await?;
+    let as_of_height = parse_as_of_height(as_of_height)?;
+    let minconf = parse_minconf(minconf, 1, as_of_height)?;
+    let at = query_height(wallet, as_of_height)?;
+
+    let account = wallet
+        .get_account(account_id)
+        .map_err(ComponentFailure::database)?
+        // This would be a race condition between this and account deletion.
+        .ok_or_else(|| ComponentFailure::internal("Account was deleted during the request"))?;
+
+    // Funds are pending if they would be counted with the smallest `minconf` allowed.
+    let min_minconf = match at {
+        QueryHeight::Tip(_) => 0,
+        QueryHeight::AsOf(_) => 1,
+    };
+
+    let balance = |pool| {
+        let total = |minconf| {
+            RpcResult::Ok(
+                wallet
+                    .with_raw(|conn, _| confirmed_balances(conn, pool, at, minconf))
+                    .map_err(ComponentFailure::database)?
+                    .remove(&account_id.expose_uuid())
+                    .unwrap_or(Zatoshis::ZERO),
+            )
+        };
+        let confirmed = total(minconf)?;
+        let pending = (total(min_minconf.min(minconf))? - confirmed).unwrap_or(Zatoshis::ZERO);
+
+        RpcResult::Ok((!(confirmed.is_zero() && pending.is_zero())).then(|| {
+            let (value, value_zat) = zec_and_zat(confirmed);
+            let (pending, pending_zat) = zec_and_zat(pending);
+            PoolBalance {
+                value,
+                value_zat,
+                pending,
+                pending_zat,
+            }
+        }))
+    };
+
+    Ok(AccountBalance {
+        pools: Pools {
+            transparent: balance(PoolType::Transparent)?,
+            sapling: balance(PoolType::SAPLING)?,
+            orchard: balance(PoolType::ORCHARD)?,
+        },
+        minimum_confirmations: minconf,
+        is_watch_only: is_watch_only(&account),
+    })
+}
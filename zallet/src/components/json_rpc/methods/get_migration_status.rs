@@ -0,0 +1,76 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_protocol::value::Zatoshis;
+
+use crate::components::{
+    database::DbConnection,
+    json_rpc::{server::ComponentFailure, utils::zec_and_zat},
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_getmigrationstatus` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = MigrationStatus;
+
+/// The status of the Sprout to Sapling migration.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct MigrationStatus {
+    /// Whether the migration is enabled.
+    ///
+    /// Always `false`, because Zallet cannot spend Sprout notes.
+    enabled: bool,
+
+    /// The total value of unspent Sprout notes, in ZEC.
+    ///
+    /// These are the notes of a `zcashd` wallet that was migrated into this wallet. To
+    /// recover them, migrate them to a Sapling address using `zcashd`.
+    unmigrated_amount: String,
+
+    /// The value of `unmigrated_amount` in zatoshis.
+    #[serde(rename = "unmigrated_amountZat")]
+    unmigrated_amount_zat: u64,
+
+    /// The total value of migration transactions that have not yet been mined, in ZEC.
+    ///
+    /// Always zero, because Zallet does not create migration transactions.
+    unfinalized_migrated_amount: String,
+
+    /// The total value of migration transactions that have been mined, in ZEC.
+    ///
+    /// Always zero, because Zallet does not create migration transactions.
+    finalized_migrated_amount: String,
+
+    /// The number of migration transactions that have been mined.
+    ///
+    /// Always zero, because Zallet does not create migration transactions.
+    finalized_migration_transactions: u32,
+
+    /// The IDs of the migration transactions.
+    ///
+    /// Always empty, because Zallet does not create migration transactions.
+    migration_txids: Vec<String>,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(crate) fn call(wallet: &DbConnection) -> Response {
+    let unmigrated = wallet
+        .sprout_balance()
+        .map_err(ComponentFailure::database)?
+        .map_or(Zatoshis::ZERO, |balance| balance.value);
+    let (unmigrated_amount, unmigrated_amount_zat) = zec_and_zat(unmigrated);
+    let (zero, _) = zec_and_zat(Zatoshis::ZERO);
+
+    Ok(MigrationStatus {
+        enabled: false,
+        unmigrated_amount: unmigrated_amount.to_string(),
+        unmigrated_amount_zat,
+        unfinalized_migrated_amount: zero.to_string(),
+        finalized_migrated_amount: zero.to_string(),
+        finalized_migration_transactions: 0,
+        migration_txids: vec![],
+    })
+}
@@ -1,23 +1,24 @@
 use documented::Documented;
-use jsonrpsee::{core::RpcResult, types::ErrorCode as RpcErrorCode};
+use jsonrpsee::core::RpcResult;
 use schemars::JsonSchema;
 use serde::Serialize;
 use zaino_state::FetchServiceSubscriber;
-use zcash_client_backend::{
-    data_api::{AccountBirthday, WalletRead, WalletWrite},
-    proto::service::TreeState,
-};
-use zcash_protocol::consensus::{NetworkType, Parameters};
+use zcash_client_backend::data_api::{Account as _, WalletRead, WalletWrite};
 
 use crate::components::{
+    audit::{self, Event},
     database::DbConnection,
     json_rpc::{
-        server::LegacyCode,
-        utils::{ensure_wallet_is_unlocked, parse_seedfp_parameter},
+        server::{ComponentFailure, LegacyCode},
+        utils::{
+            account_birthday, check_account_name, ensure_wallet_is_unlocked, parse_seedfp_parameter,
+        },
     },
     keystore::KeyStore,
 };
 
+use super::MethodCategory;
+
 /// Response to a `z_getnewaccount` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 pub(crate) type ResultType = Account;
@@ -29,13 +30,21 @@ pub(crate) struct Account {
     account_uuid: String,
 
     /// The new account's ZIP 32 account index.
+    ///
+    /// This is only present if legacy account numbers are resolved against the seed that
+    /// the account was derived from; that is, if the wallet contains a single seed, or
+    /// the seed is configured by `keystore.default_seed_fingerprint`.
     #[serde(skip_serializing_if = "Option::is_none")]
     account: Option<u64>,
 }
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
 pub(super) const PARAM_ACCOUNT_NAME_DESC: &str = "A human-readable name for the account.";
 pub(super) const PARAM_SEEDFP_DESC: &str =
     "ZIP 32 seed fingerprint for the BIP 39 mnemonic phrase from which to derive the account.";
+pub(super) const PARAM_STRICT_UNIQUE_DESC: &str =
+    "If true, fail if another account already has the name.";
 
 pub(crate) async fn call(
     wallet: &mut DbConnection,
@@ -43,59 +52,29 @@ pub(crate) async fn call(
     chain: FetchServiceSubscriber,
     account_name: &str,
     seedfp: Option<&str>,
+    strict_unique: Option<bool>,
 ) -> Response {
+    check_account_name(account_name, None, strict_unique.unwrap_or(false), || {
+        wallet
+            .indexed_accounts()
+            .map_err(|e| ComponentFailure::database(e).into())
+    })?;
+
     ensure_wallet_is_unlocked(keystore).await?;
-    // TODO: Ensure wallet is backed up.
-    //       https://github.com/zcash/wallet/issues/201
 
     let seedfp = seedfp.map(parse_seedfp_parameter).transpose()?;
 
-    let birthday_height = wallet
+    let chain_height = wallet
         .chain_height()
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+        .map_err(ComponentFailure::database)?
         .ok_or(LegacyCode::InWarmup.with_static("Wallet sync required"))?;
 
-    let treestate = {
-        let treestate = chain
-            .fetcher
-            .get_treestate(birthday_height.saturating_sub(1).to_string())
-            .await
-            .map_err(|_| RpcErrorCode::InternalError)?;
-
-        TreeState {
-            network: match wallet.params().network_type() {
-                NetworkType::Main => "main".into(),
-                NetworkType::Test => "test".into(),
-                NetworkType::Regtest => "regtest".into(),
-            },
-            height: u64::try_from(treestate.height).map_err(|_| RpcErrorCode::InternalError)?,
-            hash: treestate.hash,
-            time: treestate.time,
-            sapling_tree: treestate
-                .sapling
-                .commitments()
-                .final_state()
-                .as_ref()
-                .map(hex::encode)
-                .unwrap_or_default(),
-            orchard_tree: treestate
-                .orchard
-                .commitments()
-                .final_state()
-                .as_ref()
-                .map(hex::encode)
-                .unwrap_or_default(),
-        }
-    };
-
-    let birthday = AccountBirthday::from_treestate(treestate, None)
-        .map_err(|_| RpcErrorCode::InternalError)?;
-
     let seed_fps = keystore
         .list_seed_fingerprints()
         .await
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
+        .map_err(ComponentFailure::keystore)?;
 
+    let single_seed = seed_fps.len() == 1;
     let seed_fp = match (seed_fps.len(), seedfp) {
         (0, _) => Err(LegacyCode::Wallet
             .with_static("Wallet does not contain any seeds to generate accounts with")),
@@ -107,18 +86,58 @@ pub(crate) async fn call(
         }),
     }?;
 
+    if keystore.require_backup()
+        && !keystore
+            .is_backup_confirmed(&seed_fp)
+            .await
+            .map_err(ComponentFailure::keystore)?
+    {
+        return Err(LegacyCode::WalletBackupRequired.with_static(
+            "Error: Please acknowledge that you have backed up the wallet's emergency recovery phrase by calling walletconfirmbackup first.",
+        ));
+    }
+
+    // Accounts derived from an imported mnemonic may have existing transactions, so they
+    // start from the birthday that was given for it.
+    let birthday_height = keystore
+        .mnemonic_birthday(&seed_fp)
+        .await
+        .map_err(ComponentFailure::keystore)?
+        .map_or(chain_height, |height| height.min(chain_height));
+    let birthday = account_birthday(&chain, wallet.params(), birthday_height).await?;
+
     let seed = keystore
         .decrypt_seed(&seed_fp)
         .await
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
+        .map_err(ComponentFailure::keystore)?;
 
     let (account_id, _usk) = wallet
         .create_account(account_name, &seed, &birthday, None)
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
+        .map_err(ComponentFailure::database)?;
+
+    audit::record(Event::AccountCreated {
+        account_uuid: account_id.expose_uuid().to_string(),
+        seed_fingerprint: seed_fp.to_string(),
+    });
+
+    // Only report the account number if it can be used to refer to the account.
+    let account = match keystore.default_seed_fingerprint() {
+        Some(default_seed_fp) => default_seed_fp == &seed_fp,
+        None => single_seed,
+    }
+    .then(|| wallet.get_account(account_id))
+    .transpose()
+    .map_err(ComponentFailure::database)?
+    .flatten()
+    .and_then(|account| {
+        account
+            .source()
+            .key_derivation()
+            .map(|derivation| u64::from(u32::from(derivation.account_index())))
+    });
 
     Ok(Account {
         account_uuid: account_id.expose_uuid().to_string(),
-        // TODO: Should we ever set this in Zallet?
-        account: None,
+        account,
     })
 }
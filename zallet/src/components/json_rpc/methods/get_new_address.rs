@@ -0,0 +1,180 @@
+use documented::Documented;
+use jsonrpsee::core::{JsonValue, RpcResult};
+use schemars::JsonSchema;
+use serde::Serialize;
+use zaino_state::FetchServiceSubscriber;
+use zcash_client_backend::data_api::{Account as _, WalletRead, WalletWrite};
+use zcash_client_sqlite::AccountUuid;
+use zcash_protocol::consensus::BlockHeight;
+use zip32::fingerprint::SeedFingerprint;
+
+use crate::components::{
+    audit::{self, Event},
+    database::DbConnection,
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::{
+            ZCASH_LEGACY_ACCOUNT, account_birthday, ensure_wallet_is_unlocked,
+            parse_account_parameter,
+        },
+    },
+    keystore::KeyStore,
+};
+
+use super::MethodCategory;
+
+/// Response to a `getnewaddress` or `getrawchangeaddress` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The new transparent address.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(String);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_ACCOUNT_DESC: &str = "The UUID or name of the account to derive the address from. If omitted or empty, the legacy transparent account is used.";
+
+/// The name given to the legacy transparent account if this method creates it.
+const LEGACY_ACCOUNT_NAME: &str = "Legacy transparent account";
+
+pub(crate) async fn call(
+    wallet: &mut DbConnection,
+    keystore: &KeyStore,
+    chain: FetchServiceSubscriber,
+    account: Option<String>,
+    change: bool,
+) -> Response {
+    let chain_height = wallet
+        .chain_height()
+        .map_err(ComponentFailure::database)?
+        .ok_or(LegacyCode::InWarmup.with_static("Wallet sync required"))?;
+
+    let account_id = match account.filter(|account| !account.is_empty()) {
+        Some(account) => {
+            let account_id =
+                parse_account_parameter(wallet, keystore, &JsonValue::String(account)).await?;
+
+            let seed_fp = wallet
+                .get_account(account_id)
+                .map_err(ComponentFailure::database)?
+                // This would be a race condition between this and account deletion.
+                .ok_or_else(|| {
+                    ComponentFailure::internal("Account was deleted during the request")
+                })?
+                .source()
+                .key_derivation()
+                .map(|derivation| *derivation.seed_fingerprint());
+
+            // Accounts imported from a viewing key have no seed to back up.
+            if let Some(seed_fp) = seed_fp {
+                require_backup(keystore, &seed_fp).await?;
+            }
+
+            account_id
+        }
+        None => legacy_account(wallet, keystore, chain, chain_height).await?,
+    };
+
+    let (address, _) = wallet
+        .expose_next_transparent_address(account_id, change, chain_height)
+        .map_err(ComponentFailure::database)?
+        .ok_or_else(|| {
+            LegacyCode::WalletKeypoolRanOut.with_static(
+                "Error: reached the transparent gap limit. Wait for a previously generated address to receive funds and then try again.",
+            )
+        })?;
+
+    Ok(ResultType(address))
+}
+
+/// Returns the legacy transparent account of the wallet's default seed, creating it if
+/// necessary.
+///
+/// `zcashd` derived the addresses returned by `getnewaddress` and `getrawchangeaddress`
+/// from the BIP 44 account [`ZCASH_LEGACY_ACCOUNT`] of its mnemonic seed. A migrated
+/// `zcashd` wallet already contains this account.
+async fn legacy_account(
+    wallet: &mut DbConnection,
+    keystore: &KeyStore,
+    chain: FetchServiceSubscriber,
+    chain_height: BlockHeight,
+) -> RpcResult<AccountUuid> {
+    let seed_fp = match keystore.default_seed_fingerprint() {
+        Some(seed_fp) => *seed_fp,
+        None => {
+            let seed_fps = keystore
+                .list_seed_fingerprints()
+                .await
+                .map_err(ComponentFailure::keystore)?;
+
+            let mut seed_fps = seed_fps.into_iter();
+            match (seed_fps.next(), seed_fps.next()) {
+                (Some(seed_fp), None) => seed_fp,
+                (None, _) => {
+                    return Err(LegacyCode::Wallet.with_static(
+                        "Wallet does not contain any seeds to generate addresses with",
+                    ));
+                }
+                _ => {
+                    return Err(LegacyCode::Wallet.with_static(
+                        "The legacy transparent account is not supported in wallets with multiple seeds. Provide an account UUID instead.",
+                    ));
+                }
+            }
+        }
+    };
+
+    require_backup(keystore, &seed_fp).await?;
+
+    if let Some(account_id) = wallet
+        .indexed_accounts()
+        .map_err(ComponentFailure::database)?
+        .derived(&seed_fp, ZCASH_LEGACY_ACCOUNT)
+    {
+        return Ok(account_id);
+    }
+
+    // The account needs to be derived from the seed.
+    ensure_wallet_is_unlocked(keystore).await?;
+
+    let birthday = account_birthday(&chain, wallet.params(), chain_height).await?;
+
+    let seed = keystore
+        .decrypt_seed(&seed_fp)
+        .await
+        .map_err(ComponentFailure::keystore)?;
+
+    let (account, _usk) = wallet
+        .import_account_hd(
+            LEGACY_ACCOUNT_NAME,
+            &seed,
+            zip32::AccountId::try_from(ZCASH_LEGACY_ACCOUNT).expect("valid"),
+            &birthday,
+            None,
+        )
+        .map_err(ComponentFailure::database)?;
+
+    audit::record(Event::AccountCreated {
+        account_uuid: account.id().expose_uuid().to_string(),
+        seed_fingerprint: seed_fp.to_string(),
+    });
+
+    Ok(account.id())
+}
+
+/// Refuses to hand out addresses derived from a seed whose backup has not been confirmed.
+async fn require_backup(keystore: &KeyStore, seed_fp: &SeedFingerprint) -> RpcResult<()> {
+    if keystore.require_backup()
+        && !keystore
+            .is_backup_confirmed(seed_fp)
+            .await
+            .map_err(ComponentFailure::keystore)?
+    {
+        Err(LegacyCode::WalletBackupRequired.with_static(
+            "Error: Please acknowledge that you have backed up the wallet's emergency recovery phrase by calling walletconfirmbackup first.",
+        ))
+    } else {
+        Ok(())
+    }
+}
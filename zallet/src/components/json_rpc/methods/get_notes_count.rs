@@ -2,17 +2,18 @@ use documented::Documented;
 use jsonrpsee::core::RpcResult;
 use schemars::JsonSchema;
 use serde::Serialize;
-use zcash_client_backend::data_api::{InputSource, NoteFilter, WalletRead, wallet::TargetHeight};
-use zcash_protocol::{ShieldedProtocol, value::Zatoshis};
+use zcash_protocol::PoolType;
 
 use crate::components::{
-    database::DbConnection,
+    database::{DbConnection, unspent_outputs},
     json_rpc::{
-        server::LegacyCode,
-        utils::{parse_as_of_height, parse_minconf},
+        server::ComponentFailure,
+        utils::{parse_as_of_height, parse_minconf, query_height},
     },
 };
 
+use super::MethodCategory;
+
 /// Response to a `z_getnotescount` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 pub(crate) type ResultType = GetNotesCount;
@@ -22,7 +23,9 @@ pub(crate) type ResultType = GetNotesCount;
 pub(crate) struct GetNotesCount {
     /// The number of Sprout notes in the wallet.
     ///
-    /// Always zero, because Sprout is not supported.
+    /// Zallet does not scan for Sprout notes; this only counts the unspent notes of a
+    /// `zcashd` wallet that was migrated into this wallet, regardless of `minconf` and
+    /// `as_of_height`.
     sprout: u32,
 
     /// The number of Sapling notes in the wallet.
@@ -32,6 +35,8 @@ pub(crate) struct GetNotesCount {
     orchard: u32,
 }
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_MINCONF_DESC: &str =
     "Only include notes in transactions confirmed at least this many times.";
 pub(super) const PARAM_AS_OF_HEIGHT_DESC: &str = "Execute the query as if it were run when the blockchain was at the height specified by this argument.";
@@ -43,37 +48,43 @@ pub(crate) fn call(
 ) -> Response {
     let as_of_height = parse_as_of_height(as_of_height)?;
     let minconf = parse_minconf(minconf, 1, as_of_height)?;
+    let at = query_height(wallet, as_of_height)?;
 
-    let chain_height = wallet
-        .chain_height()
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
-        .ok_or(LegacyCode::InWarmup.with_static("Wallet sync required"))?;
-
-    let target_height = TargetHeight::from(as_of_height.unwrap_or(chain_height) + 1 - minconf);
-
-    let selector = NoteFilter::ExceedsMinValue(Zatoshis::ZERO);
-
-    let mut sapling = 0;
-    let mut orchard = 0;
-    for account_id in wallet
-        .get_account_ids()
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
-    {
-        let account_metadata = wallet
-            .get_account_metadata(account_id, &selector, target_height, &[])
-            .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
-
-        if let Some(note_count) = account_metadata.note_count(ShieldedProtocol::Sapling) {
-            sapling += note_count as u32;
-        }
-        if let Some(note_count) = account_metadata.note_count(ShieldedProtocol::Orchard) {
-            orchard += note_count as u32;
-        }
-    }
+    let count = |pool| {
+        wallet
+            .with_raw(|conn, _| unspent_outputs(conn, pool, at, minconf, None))
+            .map(|outputs| u32::try_from(outputs.len()).unwrap_or(u32::MAX))
+            .map_err(ComponentFailure::database)
+    };
+
+    let sprout = wallet
+        .sprout_balance()
+        .map_err(ComponentFailure::database)?
+        .map_or(0, |balance| balance.notes);
 
     Ok(GetNotesCount {
-        sprout: 0,
-        sapling,
-        orchard,
+        sprout,
+        sapling: count(PoolType::SAPLING)?,
+        orchard: count(PoolType::ORCHARD)?,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::components::json_rpc::methods::testing::{TestWallet, error_code};
+
+    use super::call;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failures() {
+        let wallet = TestWallet::new().await;
+        let handle = wallet.handle().await;
+
+        // Invalid parameters.
+        assert_eq!(error_code(call(&handle, None, Some(0))), -8);
+        assert_eq!(error_code(call(&handle, Some(0), Some(10))), -8);
+
+        // The wallet has not synced yet.
+        assert_eq!(error_code(call(&handle, None, None)), -28);
+    }
+}
@@ -9,6 +9,8 @@ use crate::components::json_rpc::asyncop::{
     AsyncOperation, OperationId, OperationState, OperationStatus,
 };
 
+use super::MethodCategory;
+
 /// Response to a `z_getoperationstatus` or `z_getoperationresult` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 
@@ -17,6 +19,8 @@ pub(crate) type Response = RpcResult<ResultType>;
 #[serde(transparent)]
 pub(crate) struct ResultType(Vec<OperationStatus>);
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_OPERATIONID_DESC: &str = "A list of operation ids we are interested in.";
 pub(super) const PARAM_OPERATIONID_REQUIRED: bool = false;
 
@@ -50,6 +54,7 @@ pub(crate) async fn result(
             OperationState::Success | OperationState::Failed | OperationState::Cancelled
         ) {
             ret.push(op.to_status().await);
+            op.forget().await;
             remove.insert(op.operation_id().clone());
         }
     }
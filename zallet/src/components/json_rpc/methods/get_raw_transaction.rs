@@ -16,11 +16,13 @@ use zcash_protocol::{
 use crate::components::{
     database::DbConnection,
     json_rpc::{
-        server::LegacyCode,
-        utils::{JsonZec, JsonZecBalance, parse_txid, value_from_zat_balance, value_from_zatoshis},
+        server::{ComponentFailure, LegacyCode},
+        utils::{JsonZec, JsonZecBalance, parse_txid, zec_and_zat, zec_and_zat_balance},
     },
 };
 
+use super::MethodCategory;
+
 /// Response to a `getrawtransaction` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 
@@ -466,6 +468,8 @@ struct OrchardFlags {
     enable_outputs: bool,
 }
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_TXID_DESC: &str = "The ID of the transaction to fetch.";
 pub(super) const PARAM_VERBOSE_DESC: &str = "If 0, return a string of hex-encoded data. If non-zero, return a JSON object with information about `txid`";
 pub(super) const PARAM_BLOCKHASH_DESC: &str = "The block in which to look for the transaction.";
@@ -505,7 +509,7 @@ pub(crate) async fn call(
             Err(LegacyCode::InvalidAddressOrKey
                 .with_static("No such mempool or blockchain transaction"))
         }
-        Err(e) => Err(LegacyCode::Database.with_message(e.to_string())),
+        Err(e) => Err(ComponentFailure::chain(e).into()),
     }?;
 
     // TODO: Once we migrate to `ChainIndex`, fetch these via the snapshot.
@@ -533,7 +537,7 @@ pub(crate) async fn call(
         chain
             .get_latest_block()
             .await
-            .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+            .map_err(ComponentFailure::chain)?
             .height
             .try_into()
             .expect("not our problem"),
@@ -580,11 +584,14 @@ pub(crate) async fn call(
         _ => (vec![], None, None),
     };
 
-    let (value_balance, value_balance_zat, v_shielded_spend, v_shielded_output, binding_sig) =
+    let (value_balance, value_balance_zat) = tx
+        .sapling_bundle()
+        .map(|bundle| zec_and_zat_balance(*bundle.value_balance()))
+        .unzip();
+
+    let (v_shielded_spend, v_shielded_output, binding_sig) =
         if let Some(bundle) = tx.sapling_bundle() {
             (
-                Some(value_from_zat_balance(*bundle.value_balance())),
-                Some(bundle.value_balance().into()),
                 Some(
                     bundle
                         .shielded_spends()
@@ -604,7 +611,7 @@ pub(crate) async fn call(
                 ))),
             )
         } else {
-            (None, None, None, None, None)
+            (None, None, None)
         };
 
     let orchard = tx
@@ -690,10 +697,12 @@ impl TransparentOutput {
             addresses: vec![],
         };
 
+        let (value, value_zat) = zec_and_zat(tx_out.value());
+
         Self {
-            value: value_from_zatoshis(tx_out.value()),
-            value_zat: tx_out.value().into_u64(),
-            value_sat: tx_out.value().into_u64(),
+            value,
+            value_zat,
+            value_sat: value_zat,
             n,
             script_pub_key,
         }
@@ -736,11 +745,15 @@ impl SaplingOutput {
 
 impl Orchard {
     fn encode(bundle: Option<&orchard::Bundle<orchard::bundle::Authorized, ZatBalance>>) -> Self {
+        let (value_balance, value_balance_zat) = zec_and_zat_balance(
+            bundle.map_or(ZatBalance::zero(), |bundle| *bundle.value_balance()),
+        );
+
         match bundle {
             None => Self {
                 actions: vec![],
-                value_balance: value_from_zat_balance(ZatBalance::zero()),
-                value_balance_zat: 0,
+                value_balance,
+                value_balance_zat,
                 flags: None,
                 anchor: None,
                 proof: None,
@@ -748,8 +761,8 @@ impl Orchard {
             },
             Some(bundle) => Self {
                 actions: bundle.actions().iter().map(OrchardAction::encode).collect(),
-                value_balance: value_from_zat_balance(*bundle.value_balance()),
-                value_balance_zat: bundle.value_balance().into(),
+                value_balance,
+                value_balance_zat,
                 flags: Some(OrchardFlags {
                     enable_spends: bundle.flags().spends_enabled(),
                     enable_outputs: bundle.flags().outputs_enabled(),
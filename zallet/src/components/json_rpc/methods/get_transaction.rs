@@ -0,0 +1,242 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use rusqlite::named_params;
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_client_backend::data_api::WalletRead;
+use zcash_client_sqlite::error::SqliteClientError;
+use zcash_primitives::block::BlockHash;
+use zcash_protocol::value::ZatBalance;
+
+use crate::components::{
+    database::{DbConnection, wallet_conflicts},
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::{JsonZecBalance, parse_txid, watch_only_accounts, zec_and_zat_balance},
+    },
+};
+
+use super::{
+    MethodCategory,
+    list_wallet_history::{self, HistoryDetail},
+};
+
+/// Response to a `gettransaction` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// Information about an in-wallet transaction.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct ResultType {
+    /// The net effect of the transaction on the wallet in ZEC, excluding the fee.
+    ///
+    /// This is the sum of the `amount` fields in `details`.
+    amount: JsonZecBalance,
+
+    /// The net effect of the transaction on the wallet in zatoshis, excluding the fee.
+    #[serde(rename = "amountZat")]
+    amount_zat: i64,
+
+    /// The fee paid by the transaction in ZEC, as a negative number.
+    ///
+    /// Omitted if the wallet did not fund the transaction, or if it spends transparent
+    /// inputs whose values are not known to the wallet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee: Option<JsonZecBalance>,
+
+    /// The fee paid by the transaction in zatoshis, as a negative number.
+    #[serde(rename = "feeZat")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee_zat: Option<i64>,
+
+    /// The number of confirmations of the transaction.
+    ///
+    /// This is 0 if the transaction is unmined, and -1 if it conflicts with a mined
+    /// transaction.
+    confirmations: i64,
+
+    /// Whether the transaction is a coinbase transaction.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generated: Option<bool>,
+
+    /// The hash of the block that the transaction is mined in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blockhash: Option<String>,
+
+    /// The index of the transaction within the block that it is mined in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blockindex: Option<u32>,
+
+    /// The time of the block that the transaction is mined in, in seconds since the
+    /// Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocktime: Option<i64>,
+
+    /// The block height after which the transaction expires, or 0 if it does not expire.
+    expiryheight: u32,
+
+    /// The transaction ID.
+    txid: String,
+
+    /// The IDs of wallet transactions that spend any of the same inputs as this
+    /// transaction.
+    walletconflicts: Vec<String>,
+
+    /// The time of the block that the transaction is mined in.
+    ///
+    /// Omitted if the transaction is unmined, as the wallet does not record when it
+    /// first observed a transaction.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<i64>,
+
+    /// The same as `time`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timereceived: Option<i64>,
+
+    /// The effects of the transaction's outputs on the accounts in the wallet, in the
+    /// same format as `listtransactions` entries.
+    details: Vec<HistoryDetail>,
+
+    /// The serialized, hex-encoded transaction.
+    hex: String,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_TXID_DESC: &str = "The ID of the transaction to view.";
+pub(super) const PARAM_INCLUDE_WATCHONLY_DESC: &str =
+    "Also include the effects of the transaction on watch-only accounts.";
+
+/// Whether `include_watchonly` is `true` if omitted, as in `listtransactions`.
+pub(super) const INCLUDE_WATCHONLY_DEFAULT: bool = list_wallet_history::INCLUDE_WATCHONLY_DEFAULT;
+
+/// Where the transaction is mined, if it is.
+struct MinedInfo {
+    height: Option<u32>,
+    index: Option<u32>,
+    hash: Option<BlockHash>,
+    time: Option<i64>,
+}
+
+pub(crate) fn call(
+    wallet: &DbConnection,
+    txid_str: &str,
+    include_watchonly: Option<bool>,
+) -> Response {
+    let txid = parse_txid(txid_str)?;
+
+    let chain_height = wallet
+        .chain_height()
+        .map_err(ComponentFailure::database)?
+        .ok_or_else(|| LegacyCode::InWarmup.with_static("Wait for the wallet to start up"))?;
+
+    let tx = wallet
+        .get_transaction(txid)
+        .map_err(ComponentFailure::database)?
+        .ok_or(
+            LegacyCode::InvalidAddressOrKey.with_static("Invalid or non-wallet transaction id"),
+        )?;
+
+    let include_watchonly = include_watchonly.unwrap_or(INCLUDE_WATCHONLY_DEFAULT);
+    let watch_only = watch_only_accounts(wallet)?;
+
+    let (details, mined, funded_by_wallet, walletconflicts) = wallet
+        .with_raw(|conn, _| {
+            let details =
+                list_wallet_history::query_details(conn, &txid, include_watchonly, &watch_only)?;
+
+            let mined = conn.query_row(
+                "SELECT t.mined_height, t.tx_index, b.hash, b.time
+                FROM transactions t
+                LEFT JOIN blocks b ON b.height = t.mined_height
+                WHERE t.txid = :txid",
+                named_params! { ":txid": txid.as_ref() },
+                |row| {
+                    Ok(MinedInfo {
+                        height: row.get(0)?,
+                        index: row.get(1)?,
+                        hash: row.get::<_, Option<[u8; 32]>>(2)?.map(BlockHash),
+                        time: row.get(3)?,
+                    })
+                },
+            )?;
+
+            // The wallet funded the transaction if it reduced the balance of one of the
+            // wallet's accounts.
+            let funded_by_wallet = conn.query_row(
+                "SELECT EXISTS (
+                    SELECT 1 FROM v_transactions
+                    WHERE txid = :txid AND account_balance_delta < 0
+                )",
+                named_params! { ":txid": txid.as_ref() },
+                |row| row.get::<_, bool>(0),
+            )?;
+
+            let walletconflicts = wallet_conflicts(conn, &[txid])?
+                .remove(&txid)
+                .unwrap_or_default();
+
+            Ok::<_, SqliteClientError>((details, mined, funded_by_wallet, walletconflicts))
+        })
+        .map_err(ComponentFailure::database)?;
+
+    let confirmations = match mined.height {
+        Some(height) => i64::from(u32::from(chain_height + 1).saturating_sub(height)),
+        None => {
+            if wallet
+                .conflicted_transactions()
+                .map_err(ComponentFailure::database)?
+                .contains(&txid)
+            {
+                -1
+            } else {
+                0
+            }
+        }
+    };
+
+    let (amount, amount_zat) = zec_and_zat_balance(
+        details
+            .iter()
+            .map(|detail| ZatBalance::from_i64(detail.amount_zat()).ok())
+            .try_fold(ZatBalance::zero(), |acc, value| acc + value?)
+            .ok_or_else(|| LegacyCode::Misc.with_static("Value out of range"))?,
+    );
+
+    let (fee, fee_zat) = if funded_by_wallet {
+        wallet
+            .tx_fee(&txid)
+            .map_err(ComponentFailure::database)?
+            .map(|fee| zec_and_zat_balance(-ZatBalance::from(fee)))
+            .unzip()
+    } else {
+        (None, None)
+    };
+
+    let mut hex = vec![];
+    tx.write(&mut hex).map_err(ComponentFailure::internal)?;
+
+    Ok(ResultType {
+        amount,
+        amount_zat,
+        fee,
+        fee_zat,
+        confirmations,
+        generated: tx
+            .transparent_bundle()
+            .is_some_and(|bundle| bundle.is_coinbase())
+            .then_some(true),
+        blockhash: mined.hash.map(|hash| hash.to_string()),
+        blockindex: mined.index,
+        blocktime: mined.time,
+        expiryheight: tx.expiry_height().into(),
+        txid: txid.to_string(),
+        walletconflicts: walletconflicts
+            .into_iter()
+            .map(|txid| txid.to_string())
+            .collect(),
+        time: mined.time,
+        timereceived: mined.time,
+        details,
+        hex: hex::encode(hex),
+    })
+}
@@ -1,16 +1,31 @@
-use std::time::UNIX_EPOCH;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use abscissa_core::Application;
 use documented::Documented;
 use jsonrpsee::{core::RpcResult, tracing::warn};
 use schemars::JsonSchema;
 use serde::Serialize;
-use zcash_protocol::value::Zatoshis;
+use zcash_client_backend::data_api::{Account as _, WalletRead};
+use zcash_client_sqlite::AccountUuid;
+use zcash_protocol::{PoolType, value::Zatoshis};
 
-use crate::components::{
-    json_rpc::utils::{JsonZec, value_from_zatoshis},
-    keystore::KeyStore,
+use crate::{
+    components::{
+        chain::Chain,
+        database::{DbConnection, account_balances},
+        json_rpc::{
+            fees,
+            server::{ComponentFailure, LegacyCode},
+            utils::{JsonZec, watch_only_accounts, zec_and_zat},
+        },
+        keystore::KeyStore,
+        sync::SyncStatus,
+    },
+    prelude::APP,
 };
 
+use super::MethodCategory;
+
 /// Response to a `getwalletinfo` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 pub(crate) type ResultType = GetWalletInfo;
@@ -24,22 +39,46 @@ pub(crate) struct GetWalletInfo {
     /// The total confirmed transparent balance of the wallet in ZEC.
     balance: JsonZec,
 
+    /// The value of `balance` in zatoshis.
+    #[serde(rename = "balanceZat")]
+    balance_zat: u64,
+
     /// The total unconfirmed transparent balance of the wallet in ZEC.
     ///
     /// Not included if `asOfHeight` is specified.
     unconfirmed_balance: Option<JsonZec>,
 
+    /// The value of `unconfirmed_balance` in zatoshis.
+    ///
+    /// Not included if `asOfHeight` is specified.
+    #[serde(rename = "unconfirmed_balanceZat")]
+    unconfirmed_balance_zat: Option<u64>,
+
     /// The total immature transparent balance of the wallet in ZEC.
     immature_balance: JsonZec,
 
+    /// The value of `immature_balance` in zatoshis.
+    #[serde(rename = "immature_balanceZat")]
+    immature_balance_zat: u64,
+
     /// The total confirmed shielded balance of the wallet in ZEC.
     shielded_balance: String,
 
+    /// The value of `shielded_balance` in zatoshis.
+    #[serde(rename = "shielded_balanceZat")]
+    shielded_balance_zat: u64,
+
     /// The total unconfirmed shielded balance of the wallet in ZEC.
     ///
     /// Not included if `asOfHeight` is specified.
     shielded_unconfirmed_balance: Option<String>,
 
+    /// The value of `shielded_unconfirmed_balance` in zatoshis.
+    ///
+    /// Not included if `asOfHeight` is specified.
+    #[serde(rename = "shielded_unconfirmed_balanceZat")]
+    shielded_unconfirmed_balance_zat: Option<u64>,
+
     /// The total number of transactions in the wallet
     txcount: u64,
 
@@ -52,40 +91,266 @@ pub(crate) struct GetWalletInfo {
 
     /// The timestamp in seconds since epoch (midnight Jan 1 1970 GMT) that the wallet is
     /// unlocked for transfers, or 0 if the wallet is locked.
+    ///
+    /// Omitted if the wallet is not encrypted, or was unlocked with a passphrase supplied
+    /// when Zallet started (in which case it stays unlocked until `walletlock` is called).
     #[serde(skip_serializing_if = "Option::is_none")]
     unlocked_until: Option<u64>,
 
+    /// Whether the wallet's spending keys are currently available.
+    ///
+    /// - `"unencrypted"`: the wallet is not encrypted with a passphrase.
+    /// - `"locked"`: the wallet is encrypted and locked.
+    /// - `"unlocked"`: the wallet has been unlocked with `walletpassphrase` until
+    ///   `unlocked_until`, or with a passphrase supplied when Zallet started.
+    lock_state: &'static str,
+
+    /// The fee in ZEC that the wallet pays per logical action, as defined in ZIP 317.
+    ///
+    /// Transactions created by the wallet pay this fee for each logical action, with a
+    /// minimum of two actions.
+    paytxfee: JsonZec,
+
+    /// The value of `paytxfee` in zatoshis.
+    #[serde(rename = "paytxfeeZat")]
+    paytxfee_zat: u64,
+
+    /// The number of confirmations that a trusted output, such as the wallet's own
+    /// change, needs before the wallet spends it.
+    ///
+    /// This is set by the `builder.trusted_confirmations` config option, and is used
+    /// unless a method's `minconf` parameter is set.
+    trusted_confirmations: u32,
+
+    /// The number of confirmations that an untrusted output needs before the wallet
+    /// spends it.
+    ///
+    /// This is set by the `builder.untrusted_confirmations` config option, and is used
+    /// unless a method's `minconf` parameter is set.
+    untrusted_confirmations: u32,
+
     /// The BLAKE2b-256 hash of the HD seed derived from the wallet's emergency recovery phrase.
     mnemonic_seedfp: String,
+
+    /// The fingerprint of the seed that legacy ZIP 32 account numbers are resolved
+    /// against, as configured by `keystore.default_seed_fingerprint`.
+    ///
+    /// Omitted if no default seed is configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_seed_fingerprint: Option<String>,
+
+    /// The wallet's accounts, with their names.
+    accounts: Vec<AccountSummary>,
+
+    /// `false` if the wallet is not rescanning blocks, or the progress of the rescan
+    /// requested with `rescanblockchain`.
+    scanning: Scanning,
+
+    /// The address of the chain source that the wallet syncs from, as configured in
+    /// `indexer.validator_address` or `indexer.fallback_validator_addresses`.
+    backend: String,
+
+    /// The time (in seconds since the Unix epoch) at which Zallet last switched to a
+    /// different chain source.
+    ///
+    /// Omitted if Zallet has not switched chain sources since it started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_failover: Option<u64>,
+}
+
+/// An account in the wallet.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct AccountSummary {
+    /// The account's UUID.
+    account_uuid: String,
+
+    /// The account's name.
+    ///
+    /// Omitted if the account has no name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
 }
 
-pub(crate) async fn call(keystore: &KeyStore) -> Response {
+/// Whether the wallet is rescanning blocks.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub(crate) enum Scanning {
+    /// The wallet is not rescanning blocks.
+    NotScanning(bool),
+    /// The wallet is rescanning blocks.
+    Rescan {
+        /// The number of seconds since the rescan started.
+        duration: u64,
+        /// The fraction of the blocks being rescanned that have been scanned, between 0
+        /// and 1.
+        progress: f64,
+    },
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(crate) async fn call(
+    wallet: &DbConnection,
+    keystore: &KeyStore,
+    chain: &Chain,
+    sync_status: &SyncStatus,
+) -> Response {
     // https://github.com/zcash/wallet/issues/55
     warn!("TODO: Implement getwalletinfo");
 
     let unlocked_until = if keystore.uses_encrypted_identities() {
-        Some(
-            keystore
-                .unlocked_until()
-                .await
-                .map(|i| i.duration_since(UNIX_EPOCH).expect("valid").as_secs())
-                .unwrap_or(0),
-        )
+        Some(if keystore.is_locked().await {
+            Unlocked::No
+        } else {
+            Unlocked::Until(keystore.unlocked_until().await)
+        })
     } else {
         None
     };
+    let (lock_state, unlocked_until) = lock_state(unlocked_until);
+
+    // Balances are those of the wallet's spending accounts, with outputs counted as
+    // confirmed once they have a single confirmation.
+    let watch_only = watch_only_accounts(wallet)?;
+    let account_balances = match wallet.chain_height().map_err(ComponentFailure::database)? {
+        Some(tip) => wallet
+            .with_raw(|conn, _| account_balances(conn, tip, 1))
+            .map_err(ComponentFailure::database)?,
+        None => vec![],
+    };
+
+    let add = |a: Zatoshis, b: Zatoshis| {
+        (a + b).ok_or_else(|| LegacyCode::Wallet.with_static("balance overflow"))
+    };
+    let mut transparent = [Zatoshis::ZERO; 3];
+    let mut shielded = [Zatoshis::ZERO; 2];
+    for (account_uuid, pool, balance) in account_balances {
+        if watch_only.contains(&AccountUuid::from_uuid(account_uuid)) {
+            continue;
+        }
+        match pool {
+            PoolType::Transparent => {
+                transparent[0] = add(transparent[0], balance.confirmed)?;
+                transparent[1] = add(transparent[1], balance.pending)?;
+                transparent[2] = add(transparent[2], balance.immature)?;
+            }
+            // Immature shielded coinbase outputs are reported as unconfirmed.
+            PoolType::Shielded(_) => {
+                shielded[0] = add(shielded[0], balance.confirmed)?;
+                shielded[1] = add(add(shielded[1], balance.pending)?, balance.immature)?;
+            }
+        }
+    }
+
+    let (balance, balance_zat) = zec_and_zat(transparent[0]);
+    let (unconfirmed_balance, unconfirmed_balance_zat) = zec_and_zat(transparent[1]);
+    let (immature_balance, immature_balance_zat) = zec_and_zat(transparent[2]);
+    let (shielded_balance, shielded_balance_zat) = zec_and_zat(shielded[0]);
+    let (shielded_unconfirmed_balance, shielded_unconfirmed_balance_zat) = zec_and_zat(shielded[1]);
+    let (paytxfee, paytxfee_zat) = zec_and_zat(fees::marginal_fee());
+    let config = APP.config();
+
+    let mut accounts = vec![];
+    for account_id in wallet
+        .get_account_ids()
+        .map_err(ComponentFailure::database)?
+    {
+        // An account that has just been deleted is skipped.
+        if let Some(account) = wallet
+            .get_account(account_id)
+            .map_err(ComponentFailure::database)?
+        {
+            accounts.push(AccountSummary {
+                account_uuid: account_id.expose_uuid().to_string(),
+                name: account.name().map(String::from),
+            });
+        }
+    }
+
+    let scanning = match sync_status.rescan() {
+        Some(rescan) => Scanning::Rescan {
+            duration: rescan.started.elapsed().as_secs(),
+            progress: rescan
+                .progress(wallet)
+                .map_err(ComponentFailure::database)?,
+        },
+        None => Scanning::NotScanning(false),
+    };
+    let backends = chain.backends();
 
     Ok(GetWalletInfo {
         walletversion: 0,
-        balance: value_from_zatoshis(Zatoshis::ZERO),
-        unconfirmed_balance: Some(value_from_zatoshis(Zatoshis::ZERO)),
-        immature_balance: value_from_zatoshis(Zatoshis::ZERO),
-        shielded_balance: "0.00".into(),
-        shielded_unconfirmed_balance: Some("0.00".into()),
+        balance,
+        balance_zat,
+        unconfirmed_balance: Some(unconfirmed_balance),
+        unconfirmed_balance_zat: Some(unconfirmed_balance_zat),
+        immature_balance,
+        immature_balance_zat,
+        // `zcashd` returns the shielded amounts as strings.
+        shielded_balance: shielded_balance.to_string(),
+        shielded_balance_zat,
+        shielded_unconfirmed_balance: Some(shielded_unconfirmed_balance.to_string()),
+        shielded_unconfirmed_balance_zat: Some(shielded_unconfirmed_balance_zat),
         txcount: 0,
         keypoololdest: 0,
         keypoolsize: 0,
         unlocked_until,
+        lock_state,
+        paytxfee,
+        paytxfee_zat,
+        trusted_confirmations: config.builder.trusted_confirmations(),
+        untrusted_confirmations: config.builder.untrusted_confirmations(),
         mnemonic_seedfp: "TODO".into(),
+        default_seed_fingerprint: keystore.default_seed_fingerprint().map(ToString::to_string),
+        accounts,
+        scanning,
+        backend: backends.active().address.clone(),
+        last_failover: backends.last_failover_time(),
     })
 }
+
+/// Whether an encrypted wallet is unlocked.
+enum Unlocked {
+    No,
+    /// Unlocked until the given time, or until it is locked with `walletlock` if `None`.
+    Until(Option<SystemTime>),
+}
+
+/// Returns the `lock_state` and `unlocked_until` fields for an encrypted wallet, or for
+/// an unencrypted wallet if `None`.
+fn lock_state(unlocked: Option<Unlocked>) -> (&'static str, Option<u64>) {
+    match unlocked {
+        None => ("unencrypted", None),
+        Some(Unlocked::No) => ("locked", Some(0)),
+        Some(Unlocked::Until(None)) => ("unlocked", None),
+        Some(Unlocked::Until(Some(deadline))) => (
+            "unlocked",
+            Some(
+                deadline
+                    .duration_since(UNIX_EPOCH)
+                    .expect("valid")
+                    .as_secs(),
+            ),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{Unlocked, lock_state};
+
+    #[test]
+    fn lock_state_reporting() {
+        assert_eq!(lock_state(None), ("unencrypted", None));
+        assert_eq!(lock_state(Some(Unlocked::No)), ("locked", Some(0)));
+        assert_eq!(lock_state(Some(Unlocked::Until(None))), ("unlocked", None));
+
+        let deadline = UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        assert_eq!(
+            lock_state(Some(Unlocked::Until(Some(deadline)))),
+            ("unlocked", Some(1_700_000_000))
+        );
+    }
+}
@@ -3,7 +3,7 @@ use jsonrpsee::core::RpcResult;
 use schemars::JsonSchema;
 use serde::Serialize;
 
-use super::openrpc::METHODS;
+use super::{MethodCategory, openrpc::METHODS};
 
 /// Response to a `help` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
@@ -13,6 +13,8 @@ pub(crate) type Response = RpcResult<ResultType>;
 #[serde(transparent)]
 pub(crate) struct ResultType(String);
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_COMMAND_DESC: &str = "The command to get help on.";
 
 pub(crate) fn call(command: Option<&str>) -> Response {
@@ -0,0 +1,284 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use sapling::zip32::ExtendedSpendingKey;
+use schemars::JsonSchema;
+use secrecy::Zeroize;
+use serde::Serialize;
+use zaino_state::FetchServiceSubscriber;
+use zcash_client_backend::data_api::{
+    Account as _, AccountPurpose, AccountSource, WalletRead, WalletWrite,
+};
+use zcash_keys::{
+    encoding::decode_extended_spending_key,
+    keys::{Era, UnifiedFullViewingKey, UnifiedSpendingKey},
+};
+use zcash_protocol::consensus::NetworkConstants;
+
+use crate::{
+    components::{
+        audit::{self, Event},
+        database::DbConnection,
+        json_rpc::{
+            server::{ComponentFailure, LegacyCode},
+            utils::{
+                IMPORTED_SAPLING_KEY_SOURCE, IMPORTED_UNIFIED_KEY_SOURCE, account_birthday,
+                ensure_wallet_is_unlocked,
+            },
+        },
+        keystore::KeyStore,
+    },
+    network::Network,
+};
+
+use super::{MethodCategory, export_key::find_account, import_viewing_key::birthday_height};
+
+/// Response to a `z_importkey` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = ImportedKey;
+
+/// The account that holds an imported spending key.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct ImportedKey {
+    /// The type of the imported spending key ("unified" or "sapling").
+    #[serde(rename = "type")]
+    key_type: &'static str,
+
+    /// The UUID of the account that holds the spending key.
+    account_uuid: String,
+
+    /// Whether the key was added to the wallet.
+    ///
+    /// - `"imported"`: a new account was created for the key.
+    /// - `"already_imported"`: the key was imported previously.
+    /// - `"derived_from_seed"`: the key belongs to an account that the wallet derives from
+    ///   one of its seeds, and was not imported again.
+    status: &'static str,
+
+    /// The height from which the wallet will scan the chain for the account's
+    /// transactions.
+    ///
+    /// Omitted if the key was already in the wallet, or if no rescan is needed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rescan_from_height: Option<u32>,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_KEY_DESC: &str = "The Sapling extended spending key, or hex-encoded unified spending key (as returned by `z_exportkey`), to import.";
+pub(super) const PARAM_RESCAN_DESC: &str =
+    "Whether to rescan the chain for transactions: \"yes\", \"no\", or \"whenkeyisnew\".";
+pub(super) const PARAM_START_HEIGHT_DESC: &str =
+    "The birthday height of the spending key, from which a rescan starts.";
+
+/// The name given to accounts created by this method.
+const ACCOUNT_NAME: &str = "Imported spending key";
+
+/// A spending key accepted by `z_importkey`.
+enum SpendingKey {
+    Sapling(ExtendedSpendingKey),
+    Unified(UnifiedSpendingKey),
+}
+
+impl SpendingKey {
+    fn key_type(&self) -> &'static str {
+        match self {
+            SpendingKey::Sapling(_) => "sapling",
+            SpendingKey::Unified(_) => "unified",
+        }
+    }
+
+    fn to_unified_full_viewing_key(&self) -> RpcResult<UnifiedFullViewingKey> {
+        match self {
+            SpendingKey::Sapling(extsk) => sapling_key_ufvk(extsk),
+            SpendingKey::Unified(usk) => Ok(usk.to_unified_full_viewing_key()),
+        }
+    }
+}
+
+pub(crate) async fn call(
+    wallet: &mut DbConnection,
+    keystore: &KeyStore,
+    chain: FetchServiceSubscriber,
+    key: &str,
+    rescan: Option<&str>,
+    start_height: Option<u32>,
+) -> Response {
+    let _unlocked = ensure_wallet_is_unlocked(keystore).await?;
+
+    let params = *wallet.params();
+    let key = parse_spending_key(&params, key)?;
+    let key_type = key.key_type();
+    let ufvk = key.to_unified_full_viewing_key()?;
+
+    // A key whose addresses the wallet already derives is reported rather than imported
+    // again, which would create a second account for the same funds.
+    let existing = match wallet
+        .get_account_for_ufvk(&ufvk)
+        .map_err(ComponentFailure::database)?
+    {
+        Some(account) => Some(account),
+        None => {
+            let sapling = ufvk.sapling().map(|dfvk| dfvk.default_address().1);
+            let orchard = ufvk
+                .orchard()
+                .map(|fvk| fvk.address_at(0u32, orchard::keys::Scope::External));
+            match find_account(wallet, sapling.as_ref(), orchard.as_ref())? {
+                Some(account_id) => wallet
+                    .get_account(account_id)
+                    .map_err(ComponentFailure::database)?,
+                None => None,
+            }
+        }
+    };
+
+    if let Some(account) = existing {
+        let status = match account.source() {
+            AccountSource::Imported {
+                purpose: AccountPurpose::ViewOnly,
+                ..
+            } => {
+                return Err(LegacyCode::Wallet
+                    .with_static("The wallet already watches this key with a view-only account"));
+            }
+            source if source.key_derivation().is_some() => "derived_from_seed",
+            _ => "already_imported",
+        };
+
+        return Ok(ImportedKey {
+            key_type,
+            account_uuid: account.id().expose_uuid().to_string(),
+            status,
+            rescan_from_height: None,
+        });
+    }
+
+    let (birthday_height, chain_height) = birthday_height(wallet, rescan, start_height)?;
+    let birthday = account_birthday(&chain, &params, birthday_height).await?;
+
+    // Store the key before creating its account, so that the account is never left
+    // without its spending key.
+    let key_source = match &key {
+        SpendingKey::Sapling(extsk) => {
+            keystore
+                .encrypt_and_store_standalone_sapling_key(extsk)
+                .await
+                .map_err(ComponentFailure::keystore)?;
+            IMPORTED_SAPLING_KEY_SOURCE
+        }
+        SpendingKey::Unified(usk) => {
+            keystore
+                .encrypt_and_store_standalone_unified_key(usk)
+                .await
+                .map_err(ComponentFailure::keystore)?;
+            IMPORTED_UNIFIED_KEY_SOURCE
+        }
+    };
+
+    let account = wallet
+        .import_account_ufvk(
+            ACCOUNT_NAME,
+            &ufvk,
+            &birthday,
+            AccountPurpose::Spending { derivation: None },
+            Some(key_source),
+        )
+        .map_err(ComponentFailure::database)?;
+
+    audit::record(Event::SpendingKeyImported {
+        account_uuid: account.id().expose_uuid().to_string(),
+    });
+
+    Ok(ImportedKey {
+        key_type,
+        account_uuid: account.id().expose_uuid().to_string(),
+        status: "imported",
+        rescan_from_height: (birthday_height < chain_height).then(|| birthday_height.into()),
+    })
+}
+
+/// Returns the UFVK of the account for an imported Sapling extended spending key.
+pub(super) fn sapling_key_ufvk(extsk: &ExtendedSpendingKey) -> RpcResult<UnifiedFullViewingKey> {
+    #[allow(deprecated)]
+    let extfvk = extsk.to_extended_full_viewing_key();
+    UnifiedFullViewingKey::from_sapling_extended_full_viewing_key(extfvk)
+        .map_err(|e| ComponentFailure::internal(e).into())
+}
+
+/// Parses a Sapling extended spending key, or a hex-encoded unified spending key.
+fn parse_spending_key(params: &Network, key: &str) -> RpcResult<SpendingKey> {
+    if let Ok(extsk) = decode_extended_spending_key(params.hrp_sapling_extended_spending_key(), key)
+    {
+        return Ok(SpendingKey::Sapling(extsk));
+    }
+
+    let usk = hex::decode(key).ok().and_then(|mut bytes| {
+        let usk = UnifiedSpendingKey::from_bytes(Era::Orchard, &bytes).ok();
+        bytes.zeroize();
+        usk
+    });
+
+    usk.map(SpendingKey::Unified).ok_or_else(|| {
+        LegacyCode::InvalidAddressOrKey
+            .with_static("Invalid spending key, or key is for a different network")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sapling::zip32::ExtendedSpendingKey;
+    use zcash_keys::keys::UnifiedSpendingKey;
+    use zcash_protocol::consensus;
+
+    use crate::{
+        components::json_rpc::methods::export_key::{
+            encode_sapling_spending_key, encode_unified_spending_key,
+        },
+        network::Network,
+    };
+
+    use super::{SpendingKey, parse_spending_key};
+
+    #[test]
+    fn spending_key_encodings() {
+        let mainnet = Network::Consensus(consensus::Network::MainNetwork);
+        let testnet = Network::Consensus(consensus::Network::TestNetwork);
+        let seed = [7; 32];
+
+        let extsk = ExtendedSpendingKey::master(&seed);
+        let mainnet_key = encode_sapling_spending_key(&mainnet, &extsk);
+        let testnet_key = encode_sapling_spending_key(&testnet, &extsk);
+        assert!(mainnet_key.starts_with("secret-extended-key-main1"));
+        assert!(testnet_key.starts_with("secret-extended-key-test1"));
+
+        for (params, key) in [(&mainnet, &mainnet_key), (&testnet, &testnet_key)] {
+            match parse_spending_key(params, key) {
+                Ok(SpendingKey::Sapling(parsed)) => assert_eq!(parsed.to_bytes(), extsk.to_bytes()),
+                _ => panic!("{key} should parse as a Sapling key"),
+            }
+        }
+
+        // Sapling keys are bound to their network.
+        assert!(parse_spending_key(&mainnet, &testnet_key).is_err());
+        assert!(parse_spending_key(&testnet, &mainnet_key).is_err());
+
+        // Unified spending keys are not, and roundtrip on both networks.
+        for params in [&mainnet, &testnet] {
+            let usk = UnifiedSpendingKey::from_seed(params, &seed, zip32::AccountId::ZERO).unwrap();
+            let key = encode_unified_spending_key(&usk);
+            match parse_spending_key(params, &key) {
+                Ok(SpendingKey::Unified(parsed)) => assert_eq!(
+                    parsed.to_unified_full_viewing_key().encode(params),
+                    usk.to_unified_full_viewing_key().encode(params),
+                ),
+                _ => panic!("unified spending key should parse"),
+            }
+        }
+
+        for key in ["", "deadbeef", "secret-extended-key-main1qqqq", "not a key"] {
+            assert!(
+                parse_spending_key(&mainnet, key).is_err(),
+                "{key:?} should be rejected"
+            );
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use zaino_state::FetchServiceSubscriber;
+use zcash_client_backend::data_api::{Account as _, AccountPurpose, WalletRead, WalletWrite};
+use zcash_keys::{encoding::decode_extended_full_viewing_key, keys::UnifiedFullViewingKey};
+use zcash_protocol::consensus::{BlockHeight, NetworkConstants, NetworkUpgrade, Parameters};
+
+use crate::{
+    components::{
+        database::DbConnection,
+        json_rpc::{
+            server::{ComponentFailure, LegacyCode},
+            utils::account_birthday,
+        },
+    },
+    network::Network,
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_importviewingkey` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = ImportedViewingKey;
+
+/// The watch-only account for an imported viewing key.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct ImportedViewingKey {
+    /// The type of the imported viewing key ("unified" or "sapling").
+    #[serde(rename = "type")]
+    key_type: &'static str,
+
+    /// The UUID of the account for the viewing key.
+    account_uuid: String,
+
+    /// The height from which the wallet will scan the chain for the account's
+    /// transactions.
+    ///
+    /// Omitted if the viewing key was already in the wallet, or if no rescan is needed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rescan_from_height: Option<u32>,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_VKEY_DESC: &str =
+    "The unified full viewing key, or legacy Sapling extended full viewing key, to import.";
+pub(super) const PARAM_RESCAN_DESC: &str =
+    "Whether to rescan the chain for transactions: \"yes\", \"no\", or \"whenkeyisnew\".";
+pub(super) const PARAM_START_HEIGHT_DESC: &str =
+    "The birthday height of the viewing key, from which a rescan starts.";
+
+/// The name given to accounts created by this method.
+const ACCOUNT_NAME: &str = "Imported viewing key";
+
+pub(crate) async fn call(
+    wallet: &mut DbConnection,
+    chain: FetchServiceSubscriber,
+    vkey: &str,
+    rescan: Option<&str>,
+    start_height: Option<u32>,
+) -> Response {
+    let params = *wallet.params();
+    let (key_type, ufvk) = parse_viewing_key(&params, vkey)?;
+
+    // Importing a key that is already in the wallet is not an error.
+    if let Some(account) = wallet
+        .get_account_for_ufvk(&ufvk)
+        .map_err(ComponentFailure::database)?
+    {
+        return Ok(ImportedViewingKey {
+            key_type,
+            account_uuid: account.id().expose_uuid().to_string(),
+            rescan_from_height: None,
+        });
+    }
+
+    let (birthday_height, chain_height) = birthday_height(wallet, rescan, start_height)?;
+
+    let birthday = account_birthday(&chain, &params, birthday_height).await?;
+
+    let account = wallet
+        .import_account_ufvk(
+            ACCOUNT_NAME,
+            &ufvk,
+            &birthday,
+            AccountPurpose::ViewOnly,
+            None,
+        )
+        .map_err(ComponentFailure::database)?;
+
+    Ok(ImportedViewingKey {
+        key_type,
+        account_uuid: account.id().expose_uuid().to_string(),
+        rescan_from_height: (birthday_height < chain_height).then(|| birthday_height.into()),
+    })
+}
+
+/// Returns the birthday height for an account imported with the given `rescan` and
+/// `start_height` parameters, along with the wallet's chain tip.
+///
+/// The account's birthday determines where the wallet starts scanning for it, so a
+/// birthday at the chain tip means that no rescan happens.
+pub(super) fn birthday_height(
+    wallet: &DbConnection,
+    rescan: Option<&str>,
+    start_height: Option<u32>,
+) -> RpcResult<(BlockHeight, BlockHeight)> {
+    let chain_height = wallet
+        .chain_height()
+        .map_err(ComponentFailure::database)?
+        .ok_or(LegacyCode::InWarmup.with_static("Wallet sync required"))?;
+
+    let start_height = start_height.map(BlockHeight::from_u32);
+    if start_height.is_some_and(|height| height > chain_height) {
+        return Err(LegacyCode::InvalidParameter.with_static("Block height out of range"));
+    }
+
+    let requested_height = match rescan.unwrap_or("whenkeyisnew") {
+        "yes" => start_height.unwrap_or(BlockHeight::from_u32(0)),
+        "whenkeyisnew" => start_height.unwrap_or(chain_height),
+        "no" => chain_height,
+        _ => {
+            return Err(LegacyCode::InvalidParameter
+                .with_static("rescan must be \"yes\", \"no\", or \"whenkeyisnew\""));
+        }
+    };
+
+    // Keys cannot have received funds before Sapling activated.
+    let birthday_height = requested_height.max(
+        wallet
+            .params()
+            .activation_height(NetworkUpgrade::Sapling)
+            .expect("Sapling activation height is known"),
+    );
+
+    Ok((birthday_height, chain_height))
+}
+
+/// Parses a unified or legacy Sapling full viewing key.
+fn parse_viewing_key(
+    params: &Network,
+    vkey: &str,
+) -> RpcResult<(&'static str, UnifiedFullViewingKey)> {
+    if let Ok(ufvk) = UnifiedFullViewingKey::decode(params, vkey) {
+        return Ok(("unified", ufvk));
+    }
+
+    decode_extended_full_viewing_key(params.hrp_sapling_extended_full_viewing_key(), vkey)
+        .ok()
+        .and_then(|extfvk| {
+            UnifiedFullViewingKey::from_sapling_extended_full_viewing_key(extfvk).ok()
+        })
+        .map(|ufvk| ("sapling", ufvk))
+        .ok_or_else(|| LegacyCode::InvalidAddressOrKey.with_static("Invalid viewing key"))
+}
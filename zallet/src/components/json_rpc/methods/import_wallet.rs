@@ -0,0 +1,790 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use abscissa_core::Application;
+use bip0039::{English, Mnemonic};
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use sapling::zip32::ExtendedSpendingKey;
+use schemars::JsonSchema;
+use secrecy::{ExposeSecret, SecretVec, Zeroize};
+use serde::Serialize;
+use zaino_state::FetchServiceSubscriber;
+use zcash_address::unified;
+use zcash_client_backend::{
+    data_api::{Account as _, AccountPurpose, WalletRead, WalletWrite, Zip32Derivation},
+    keys::{ReceiverRequirement, UnifiedAddressRequest},
+};
+use zcash_client_sqlite::AccountUuid;
+use zcash_keys::{
+    address::Address,
+    keys::{Era, UnifiedFullViewingKey, UnifiedSpendingKey},
+};
+use zcash_protocol::consensus::{BlockHeight, NetworkConstants, Parameters};
+use zip32::{DiversifierIndex, fingerprint::SeedFingerprint};
+
+use crate::{
+    components::{
+        audit::{self, Event},
+        database::DbConnection,
+        json_rpc::{
+            server::{ComponentFailure, LegacyCode},
+            utils::{
+                IMPORTED_SAPLING_KEY_SOURCE, IMPORTED_UNIFIED_KEY_SOURCE, account_birthday,
+                ensure_wallet_is_unlocked, parse_seedfp,
+            },
+        },
+        keystore::KeyStore,
+    },
+    network::Network,
+    prelude::*,
+};
+
+#[cfg(feature = "zcashd-import")]
+use crate::components::json_rpc::utils::ZCASH_LEGACY_ACCOUNT;
+
+use super::{
+    MethodCategory,
+    export_wallet::{FORMAT_VERSION, network_name},
+    import_key::sapling_key_ufvk,
+};
+
+/// Response to a `z_importwallet` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = ImportSummary;
+
+/// A summary of what was imported from the wallet dump.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct ImportSummary {
+    /// The mnemonic seed phrases in the dump.
+    seeds: Counts,
+
+    /// The accounts in the dump.
+    accounts: Counts,
+
+    /// The imported transparent, Sapling, and unified spending keys in the dump.
+    standalone_keys: Counts,
+
+    /// The height from which the wallet will scan the chain for the imported accounts.
+    ///
+    /// Omitted if no accounts were imported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rescan_from_height: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, JsonSchema)]
+struct Counts {
+    /// The number that were added to the wallet.
+    imported: usize,
+
+    /// The number that were already in the wallet, or could not be imported.
+    skipped: usize,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_PATH_DESC: &str =
+    "The wallet dump file. Relative paths are resolved against `external.export_dir`.";
+
+pub(crate) async fn call(
+    wallet: &mut DbConnection,
+    keystore: &KeyStore,
+    chain: FetchServiceSubscriber,
+    path: &str,
+) -> Response {
+    let path = import_path(APP.config().export_dir().as_deref(), path)?;
+
+    let _unlocked = ensure_wallet_is_unlocked(keystore).await?;
+
+    let contents = fs::read(&path).map_err(|e| {
+        LegacyCode::InvalidParameter.with_message(format!(
+            "Cannot open wallet dump file {}: {e}",
+            path.display()
+        ))
+    })?;
+    let dump = if is_age_encrypted(&contents) {
+        let contents = keystore.decrypt_export(&contents).await.map_err(|e| {
+            LegacyCode::Wallet.with_message(format!(
+                "Cannot decrypt wallet dump file {} with the wallet's age identity: {e}",
+                path.display()
+            ))
+        })?;
+        parse(contents.expose_secret(), wallet.params())
+    } else {
+        let mut contents = String::from_utf8(contents).map_err(|e| {
+            e.into_bytes().zeroize();
+            LegacyCode::InvalidParameter.with_static("Wallet dump file is not valid UTF-8")
+        })?;
+        let dump = parse(&contents, wallet.params());
+        contents.zeroize();
+        dump
+    }?;
+
+    let mut summary = ImportSummary {
+        seeds: Counts::default(),
+        accounts: Counts::default(),
+        standalone_keys: Counts::default(),
+        rescan_from_height: None,
+    };
+
+    // Import the seeds first, so that accounts can be re-derived from them.
+    let existing_seeds = keystore
+        .list_seed_fingerprints()
+        .await
+        .map_err(ComponentFailure::keystore)?;
+    let mut seeds = HashMap::new();
+    for (seed_fp, mnemonic) in dump.mnemonics {
+        let seed = SecretVec::new(mnemonic.to_seed("").to_vec());
+
+        if existing_seeds.contains(&seed_fp) {
+            summary.seeds.skipped += 1;
+        } else {
+            keystore
+                .encrypt_and_store_mnemonic(mnemonic)
+                .await
+                .map_err(ComponentFailure::keystore)?;
+            audit::record(Event::MnemonicImported {
+                seed_fingerprint: seed_fp.to_string(),
+            });
+            summary.seeds.imported += 1;
+        }
+
+        // The dump is itself a backup of the seed.
+        if !keystore
+            .is_backup_confirmed(&seed_fp)
+            .await
+            .map_err(ComponentFailure::keystore)?
+        {
+            keystore
+                .confirm_backup(&seed_fp)
+                .await
+                .map_err(ComponentFailure::keystore)?;
+            audit::record(Event::BackupConfirmed {
+                seed_fingerprint: seed_fp.to_string(),
+            });
+        }
+
+        seeds.insert(seed_fp, seed);
+    }
+
+    // Accounts that `z_importkey` created are restored with their key source, so that
+    // they hold the same funds as before.
+    let mut key_sources = HashMap::new();
+    for key in &dump.sapling_keys {
+        key_sources.insert(
+            sapling_key_ufvk(key)?.encode(wallet.params()),
+            IMPORTED_SAPLING_KEY_SOURCE,
+        );
+    }
+    for key in &dump.unified_keys {
+        key_sources.insert(
+            key.to_unified_full_viewing_key().encode(wallet.params()),
+            IMPORTED_UNIFIED_KEY_SOURCE,
+        );
+    }
+
+    // Maps the account UUIDs in the dump to the corresponding accounts in the wallet.
+    let mut accounts = HashMap::new();
+    for record in &dump.accounts {
+        match import_account(wallet, &chain, &seeds, &key_sources, record).await? {
+            Some((account_id, true)) => {
+                let birthday = u32::from(record.birthday);
+                summary.accounts.imported += 1;
+                summary.rescan_from_height = Some(
+                    summary
+                        .rescan_from_height
+                        .map_or(birthday, |height| height.min(birthday)),
+                );
+                accounts.insert(record.uuid.as_str(), account_id);
+            }
+            Some((account_id, false)) => {
+                summary.accounts.skipped += 1;
+                accounts.insert(record.uuid.as_str(), account_id);
+            }
+            None => summary.accounts.skipped += 1,
+        }
+    }
+
+    import_addresses(wallet, &accounts, &dump.addresses)?;
+
+    #[cfg(feature = "zcashd-import")]
+    {
+        let secp = secp256k1::Secp256k1::signing_only();
+
+        // Standalone transparent keys belong to the legacy transparent account, as they
+        // do in a migrated `zcashd` wallet.
+        let indexed_accounts = wallet
+            .indexed_accounts()
+            .map_err(ComponentFailure::database)?;
+        let legacy_account = keystore
+            .default_seed_fingerprint()
+            .into_iter()
+            .chain(seeds.keys())
+            .find_map(|seed_fp| indexed_accounts.derived(seed_fp, ZCASH_LEGACY_ACCOUNT));
+
+        let existing_keys = keystore
+            .decrypt_standalone_transparent_keys()
+            .await
+            .map_err(ComponentFailure::keystore)?
+            .iter()
+            .map(|key| secp256k1::PublicKey::from_secret_key(&secp, key))
+            .collect::<HashSet<_>>();
+        for key in &dump.transparent_keys {
+            let pubkey = secp256k1::PublicKey::from_secret_key(&secp, key);
+            match legacy_account {
+                Some(account_id) if !existing_keys.contains(&pubkey) => {
+                    keystore
+                        .encrypt_and_store_standalone_transparent_key(key)
+                        .await
+                        .map_err(ComponentFailure::keystore)?;
+                    wallet
+                        .import_standalone_transparent_pubkey(account_id, pubkey)
+                        .map_err(ComponentFailure::database)?;
+                    summary.standalone_keys.imported += 1;
+                }
+                _ => summary.standalone_keys.skipped += 1,
+            }
+        }
+    }
+
+    #[cfg(not(feature = "zcashd-import"))]
+    {
+        summary.standalone_keys.skipped += dump.transparent_keys.len();
+    }
+
+    // The viewing keys of standalone Sapling and unified keys were imported as accounts
+    // above.
+    let existing_keys = keystore
+        .decrypt_standalone_sapling_keys()
+        .await
+        .map_err(ComponentFailure::keystore)?
+        .iter()
+        .map(|key| key.to_diversifiable_full_viewing_key().to_bytes())
+        .collect::<HashSet<_>>();
+    for key in &dump.sapling_keys {
+        if existing_keys.contains(&key.to_diversifiable_full_viewing_key().to_bytes()) {
+            summary.standalone_keys.skipped += 1;
+        } else {
+            keystore
+                .encrypt_and_store_standalone_sapling_key(key)
+                .await
+                .map_err(ComponentFailure::keystore)?;
+            summary.standalone_keys.imported += 1;
+        }
+    }
+
+    for key in &dump.unified_keys {
+        if keystore
+            .decrypt_standalone_unified_key(&key.to_unified_full_viewing_key())
+            .await
+            .map_err(ComponentFailure::keystore)?
+            .is_some()
+        {
+            summary.standalone_keys.skipped += 1;
+        } else {
+            keystore
+                .encrypt_and_store_standalone_unified_key(key)
+                .await
+                .map_err(ComponentFailure::keystore)?;
+            summary.standalone_keys.imported += 1;
+        }
+    }
+
+    audit::record(Event::WalletImported { path });
+
+    Ok(summary)
+}
+
+/// Returns `true` if `contents` is an age-encrypted file, either binary or
+/// ASCII-armored.
+fn is_age_encrypted(contents: &[u8]) -> bool {
+    const BINARY_HEADER: &[u8] = b"age-encryption.org/";
+    const ARMORED_HEADER: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+
+    let contents = contents.trim_ascii_start();
+    contents.starts_with(BINARY_HEADER) || contents.starts_with(ARMORED_HEADER)
+}
+
+/// Returns the path of the wallet dump file that `path` refers to.
+fn import_path(export_dir: Option<&Path>, path: &str) -> RpcResult<PathBuf> {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return Ok(path.into());
+    }
+
+    match export_dir {
+        Some(export_dir) if export_dir.is_absolute() && !path.as_os_str().is_empty() => {
+            Ok(export_dir.join(path))
+        }
+        _ => Err(LegacyCode::InvalidParameter.with_static(
+            "Path must be absolute, or relative to the directory configured by external.export_dir",
+        )),
+    }
+}
+
+/// Imports an account from the dump if it is not already in the wallet.
+///
+/// Returns the account's UUID within the wallet, and whether it was imported. Returns
+/// `None` if the account cannot be imported.
+async fn import_account(
+    wallet: &mut DbConnection,
+    chain: &FetchServiceSubscriber,
+    seeds: &HashMap<SeedFingerprint, SecretVec<u8>>,
+    key_sources: &HashMap<String, &'static str>,
+    record: &AccountRecord,
+) -> RpcResult<Option<(AccountUuid, bool)>> {
+    let params = *wallet.params();
+
+    // The wallet cannot import accounts that only have an incoming viewing key.
+    let Ok(ufvk) = UnifiedFullViewingKey::decode(&params, &record.viewing_key) else {
+        return Ok(None);
+    };
+
+    if let Some(account) = wallet
+        .get_account_for_ufvk(&ufvk)
+        .map_err(ComponentFailure::database)?
+    {
+        return Ok(Some((account.id(), false)));
+    }
+
+    let birthday = account_birthday(chain, &params, record.birthday).await?;
+    let name = record.name.as_deref().unwrap_or_default();
+
+    if let Some((seed_fp, account_index)) = record.derivation {
+        // Re-derive the account from its seed if we can, so that it is restored as a
+        // derived account.
+        if let Some(seed) = seeds.get(&seed_fp) {
+            let derived =
+                UnifiedSpendingKey::from_seed(&params, seed.expose_secret(), account_index)
+                    .map_err(ComponentFailure::internal)?
+                    .to_unified_full_viewing_key();
+
+            if derived.encode(&params) == ufvk.encode(&params) {
+                let (account, _usk) = wallet
+                    .import_account_hd(name, seed, account_index, &birthday, None)
+                    .map_err(ComponentFailure::database)?;
+
+                audit::record(Event::AccountCreated {
+                    account_uuid: account.id().expose_uuid().to_string(),
+                    seed_fingerprint: seed_fp.to_string(),
+                });
+
+                return Ok(Some((account.id(), true)));
+            }
+        }
+    }
+
+    let purpose = if record.view_only {
+        AccountPurpose::ViewOnly
+    } else {
+        AccountPurpose::Spending {
+            derivation: record
+                .derivation
+                .map(|(seed_fp, account_index)| Zip32Derivation::new(seed_fp, account_index, None)),
+        }
+    };
+
+    let key_source = match &purpose {
+        AccountPurpose::Spending { derivation: None } => {
+            key_sources.get(&record.viewing_key).copied()
+        }
+        _ => None,
+    };
+
+    let account = wallet
+        .import_account_ufvk(name, &ufvk, &birthday, purpose, key_source)
+        .map_err(ComponentFailure::database)?;
+
+    Ok(Some((account.id(), true)))
+}
+
+/// Regenerates the unified addresses in the dump that are not already in the wallet.
+///
+/// Other addresses are regenerated by the wallet as they are needed.
+fn import_addresses(
+    wallet: &mut DbConnection,
+    accounts: &HashMap<&str, AccountUuid>,
+    addresses: &[AddressRecord],
+) -> RpcResult<()> {
+    let params = *wallet.params();
+
+    let mut existing = HashSet::new();
+    for account_id in accounts.values() {
+        for address in wallet
+            .list_addresses(*account_id)
+            .map_err(ComponentFailure::database)?
+        {
+            existing.insert(address.address().encode(&params));
+        }
+    }
+
+    for record in addresses {
+        let (Some(account_id), Some(diversifier_index)) = (
+            accounts.get(record.account_uuid.as_str()),
+            record.diversifier_index,
+        ) else {
+            continue;
+        };
+        if existing.contains(&record.address) {
+            continue;
+        }
+        let Some(Address::Unified(address)) = Address::decode(&params, &record.address) else {
+            continue;
+        };
+
+        let receiver_types = address.receiver_types();
+        let requirement = |typecode| {
+            if receiver_types.contains(&typecode) {
+                ReceiverRequirement::Require
+            } else {
+                ReceiverRequirement::Omit
+            }
+        };
+        let Ok(request) = UnifiedAddressRequest::custom(
+            requirement(unified::Typecode::Orchard),
+            requirement(unified::Typecode::Sapling),
+            requirement(unified::Typecode::P2pkh),
+        ) else {
+            continue;
+        };
+
+        wallet
+            .get_address_for_index(*account_id, diversifier_index, request)
+            .map_err(ComponentFailure::database)?;
+    }
+
+    Ok(())
+}
+
+/// The records of a wallet dump.
+#[derive(Default)]
+struct Dump {
+    mnemonics: Vec<(SeedFingerprint, Mnemonic<English>)>,
+    accounts: Vec<AccountRecord>,
+    addresses: Vec<AddressRecord>,
+    transparent_keys: Vec<secp256k1::SecretKey>,
+    sapling_keys: Vec<ExtendedSpendingKey>,
+    unified_keys: Vec<UnifiedSpendingKey>,
+}
+
+struct AccountRecord {
+    uuid: String,
+    birthday: BlockHeight,
+    view_only: bool,
+    derivation: Option<(SeedFingerprint, zip32::AccountId)>,
+    viewing_key: String,
+    name: Option<String>,
+}
+
+struct AddressRecord {
+    account_uuid: String,
+    address: String,
+    diversifier_index: Option<DiversifierIndex>,
+}
+
+/// Parses a wallet dump in the format written by `z_exportwallet`.
+///
+/// Record types and trailing fields that this version of Zallet does not know are
+/// ignored, so that dumps written by newer versions can still be imported.
+fn parse(contents: &str, params: &Network) -> RpcResult<Dump> {
+    let mut dump = Dump::default();
+    let mut format_version = None;
+
+    for (i, line) in contents.lines().enumerate() {
+        // Error messages must not include the line, which may contain secrets.
+        let invalid = |reason: &str| {
+            LegacyCode::Deserialization
+                .with_message(format!("Invalid wallet dump at line {}: {reason}", i + 1))
+        };
+
+        if let Some(comment) = line.strip_prefix('#') {
+            let comment = comment.trim();
+            if let Some(version) = comment.strip_prefix("Format version:") {
+                let version = version
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| invalid("invalid format version"))?;
+                if version > FORMAT_VERSION {
+                    warn!(
+                        "Wallet dump has format version {version}, which is newer than {FORMAT_VERSION}; ignoring unknown records and fields"
+                    );
+                }
+                format_version = Some(version);
+            } else if let Some(network) = comment.strip_prefix("Network:") {
+                let network = network.trim();
+                if network != network_name(params.network_type()) {
+                    return Err(LegacyCode::InvalidParameter
+                        .with_message(format!("Wallet dump is for the {network} network")));
+                }
+            }
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match format_version {
+            Some(version) if version >= 1 => (),
+            _ => {
+                return Err(LegacyCode::Deserialization
+                    .with_static("Not a Zallet wallet dump: the format version is missing"));
+            }
+        }
+
+        let mut fields = line.split('\t');
+        let mut field = |name: &str| {
+            fields
+                .next()
+                .ok_or_else(|| invalid(&format!("missing {name}")))
+        };
+
+        match field("record type")? {
+            "mnemonic" => {
+                let seed_fp = parse_seedfp(field("seed fingerprint")?)
+                    .map_err(|_| invalid("invalid seed fingerprint"))?;
+                let mnemonic = Mnemonic::<English>::from_phrase(field("mnemonic phrase")?)
+                    .map_err(|_| invalid("invalid mnemonic phrase (checksum mismatch?)"))?;
+
+                let seed = SecretVec::new(mnemonic.to_seed("").to_vec());
+                if SeedFingerprint::from_seed(seed.expose_secret()) != Some(seed_fp) {
+                    return Err(invalid(
+                        "mnemonic phrase does not match its seed fingerprint",
+                    ));
+                }
+
+                dump.mnemonics.push((seed_fp, mnemonic));
+            }
+            "account" => {
+                let uuid = field("account UUID")?.to_owned();
+                let birthday = field("birthday height")?
+                    .parse()
+                    .map(BlockHeight::from_u32)
+                    .map_err(|_| invalid("invalid birthday height"))?;
+                let view_only = match field("purpose")? {
+                    "spending" => false,
+                    "view_only" => true,
+                    _ => return Err(invalid("invalid account purpose")),
+                };
+                let derivation = match (field("seed fingerprint")?, field("account index")?) {
+                    ("-", "-") => None,
+                    (seed_fp, account_index) => Some((
+                        parse_seedfp(seed_fp).map_err(|_| invalid("invalid seed fingerprint"))?,
+                        account_index
+                            .parse::<u32>()
+                            .ok()
+                            .and_then(|index| zip32::AccountId::try_from(index).ok())
+                            .ok_or_else(|| invalid("invalid ZIP 32 account index"))?,
+                    )),
+                };
+                let viewing_key = field("viewing key")?.to_owned();
+                let name = match field("account name")? {
+                    "-" => None,
+                    name => Some(
+                        hex::decode(name)
+                            .ok()
+                            .and_then(|name| String::from_utf8(name).ok())
+                            .ok_or_else(|| invalid("invalid account name"))?,
+                    ),
+                };
+
+                dump.accounts.push(AccountRecord {
+                    uuid,
+                    birthday,
+                    view_only,
+                    derivation,
+                    viewing_key,
+                    name,
+                });
+            }
+            "address" => {
+                let account_uuid = field("account UUID")?.to_owned();
+                let address = field("address")?.to_owned();
+                let diversifier_index = match field("diversifier index")? {
+                    "-" => None,
+                    index => Some(
+                        index
+                            .parse::<u128>()
+                            .ok()
+                            .and_then(|index| DiversifierIndex::try_from(index).ok())
+                            .ok_or_else(|| invalid("invalid diversifier index"))?,
+                    ),
+                };
+
+                dump.addresses.push(AddressRecord {
+                    account_uuid,
+                    address,
+                    diversifier_index,
+                });
+            }
+            "transparent_key" => {
+                let key = SecretVec::new(
+                    hex::decode(field("transparent key")?)
+                        .map_err(|_| invalid("invalid transparent key"))?,
+                );
+                dump.transparent_keys.push(
+                    secp256k1::SecretKey::from_slice(key.expose_secret())
+                        .map_err(|_| invalid("invalid transparent key"))?,
+                );
+            }
+            "sapling_key" => {
+                dump.sapling_keys.push(
+                    zcash_keys::encoding::decode_extended_spending_key(
+                        params.hrp_sapling_extended_spending_key(),
+                        field("Sapling key")?,
+                    )
+                    .map_err(|_| invalid("invalid Sapling key"))?,
+                );
+            }
+            "unified_key" => {
+                let key = SecretVec::new(
+                    hex::decode(field("unified key")?)
+                        .map_err(|_| invalid("invalid unified key"))?,
+                );
+                dump.unified_keys.push(
+                    UnifiedSpendingKey::from_bytes(Era::Orchard, key.expose_secret())
+                        .map_err(|_| invalid("invalid unified key"))?,
+                );
+            }
+            // Added in a newer format version.
+            _ => (),
+        }
+    }
+
+    if format_version.is_none() {
+        return Err(LegacyCode::Deserialization
+            .with_static("Not a Zallet wallet dump: the format version is missing"));
+    }
+
+    Ok(dump)
+}
+
+#[cfg(test)]
+mod tests {
+    use age::secrecy::ExposeSecret as _;
+    use bip0039::{English, Mnemonic};
+    use secrecy::ExposeSecret;
+    use zcash_protocol::consensus;
+    use zip32::fingerprint::SeedFingerprint;
+
+    use crate::{
+        components::{
+            database::Database,
+            keystore::{KeyStore, parse_recipients},
+        },
+        config::ZalletConfig,
+        network::Network,
+    };
+
+    use super::{super::export_wallet, is_age_encrypted, parse};
+
+    const PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn seed_fp(phrase: &str) -> SeedFingerprint {
+        let seed = Mnemonic::<English>::from_phrase(phrase)
+            .unwrap()
+            .to_seed("");
+        SeedFingerprint::from_seed(&seed).unwrap()
+    }
+
+    #[test]
+    fn parse_dumps() {
+        let params = Network::Consensus(consensus::Network::MainNetwork);
+        let error = |contents: &str| {
+            parse(contents, &params)
+                .err()
+                .map(|e| e.message().to_owned())
+        };
+
+        let dump = parse(
+            &format!(
+                "# Wallet dump created by Zallet 0.1.0\n\
+                 # Format version: 2\n\
+                 # Network: main\n\
+                 \n\
+                 mnemonic\t{}\t{PHRASE}\tfuture field\n\
+                 future_record\tfoo\tbar\n\
+                 # End of dump\n",
+                seed_fp(PHRASE),
+            ),
+            &params,
+        )
+        .unwrap();
+        assert_eq!(dump.mnemonics.len(), 1);
+        assert_eq!(dump.mnemonics[0].0, seed_fp(PHRASE));
+
+        // The last word of the phrase is a checksum.
+        let bad_checksum = PHRASE.replace("about", "abandon");
+        assert_eq!(
+            error(&format!(
+                "# Format version: 1\nmnemonic\t{}\t{bad_checksum}\n",
+                seed_fp(PHRASE),
+            )),
+            Some(
+                "Invalid wallet dump at line 2: invalid mnemonic phrase (checksum mismatch?)"
+                    .into()
+            ),
+        );
+
+        assert_eq!(
+            error(&format!("mnemonic\t{}\t{PHRASE}\n", seed_fp(PHRASE))),
+            Some("Not a Zallet wallet dump: the format version is missing".into()),
+        );
+        assert_eq!(
+            error("# Format version: 1\n# Network: test\n"),
+            Some("Wallet dump is for the test network".into()),
+        );
+        assert_eq!(
+            error("# Format version: 1\naccount\tuuid\n"),
+            Some("Invalid wallet dump at line 2: missing birthday height".into()),
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn encrypted_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = ZalletConfig::default();
+        config.datadir = Some(dir.path().to_path_buf());
+        let identity = age::x25519::Identity::generate();
+        std::fs::write(
+            config.encryption_identity(),
+            format!("{}\n", identity.to_string().expose_secret()),
+        )
+        .unwrap();
+
+        let db = Database::open(&config).await.unwrap();
+        let keystore = KeyStore::new(&config, db.clone()).unwrap();
+        keystore
+            .initialize_recipients(vec![identity.to_public().to_string()])
+            .await
+            .unwrap();
+        keystore
+            .encrypt_and_store_mnemonic(Mnemonic::<English>::from_phrase(PHRASE).unwrap())
+            .await
+            .unwrap();
+
+        let wallet = db.handle().await.unwrap();
+        let dump = export_wallet::dump(&wallet, &keystore).await.unwrap();
+        assert!(!is_age_encrypted(dump.as_bytes()));
+
+        // By default, the export is encrypted to the wallet's own recipients.
+        let export = keystore.encrypt_export(None, &dump).await.unwrap();
+        assert!(is_age_encrypted(&export));
+        assert!(!String::from_utf8_lossy(&export).contains(PHRASE));
+
+        let decrypted = keystore.decrypt_export(&export).await.unwrap();
+        let imported = parse(decrypted.expose_secret(), wallet.params()).unwrap();
+        assert_eq!(imported.mnemonics.len(), 1);
+        assert_eq!(imported.mnemonics[0].0, seed_fp(PHRASE));
+
+        // An export encrypted to other recipients cannot be decrypted by the wallet.
+        let other = age::x25519::Identity::generate().to_public().to_string();
+        let export = keystore
+            .encrypt_export(Some(parse_recipients(vec![other]).unwrap()), &dump)
+            .await
+            .unwrap();
+        assert!(is_age_encrypted(&export));
+        assert!(keystore.decrypt_export(&export).await.is_err());
+    }
+}
@@ -1,11 +1,13 @@
 use documented::Documented;
-use jsonrpsee::{core::RpcResult, types::ErrorCode as RpcErrorCode};
+use jsonrpsee::core::RpcResult;
 use schemars::JsonSchema;
 use serde::Serialize;
 use zcash_client_backend::data_api::{Account as _, AddressSource, WalletRead};
 use zcash_client_sqlite::AccountUuid;
 
-use crate::components::{database::DbConnection, json_rpc::server::LegacyCode};
+use crate::components::{database::DbConnection, json_rpc::server::ComponentFailure};
+
+use super::MethodCategory;
 
 /// Response to a `z_listaccounts` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
@@ -76,6 +78,8 @@ pub(super) struct Address {
     transparent: Option<String>,
 }
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_INCLUDE_ADDRESSES_DESC: &str =
     "Also include the addresses known to the wallet for this account.";
 
@@ -84,7 +88,7 @@ pub(crate) fn call(wallet: &DbConnection, include_addresses: Option<bool>) -> Re
 
     let accounts = wallet
         .get_account_ids()
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+        .map_err(ComponentFailure::database)?
         .into_iter()
         .map(|account_id| {
             account_details(
@@ -114,9 +118,9 @@ pub(super) fn account_details<T>(
 ) -> RpcResult<T> {
     let account = wallet
         .get_account(account_id)
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+        .map_err(ComponentFailure::database)?
         // This would be a race condition between this and account deletion.
-        .ok_or(RpcErrorCode::InternalError)?;
+        .ok_or_else(|| ComponentFailure::internal("Account was deleted during the request"))?;
 
     let name = account.name().map(String::from);
 
@@ -126,7 +130,7 @@ pub(super) fn account_details<T>(
 
     let addresses = wallet
         .list_addresses(account_id)
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
+        .map_err(ComponentFailure::database)?;
 
     let addresses = include_addresses.then(|| {
         addresses
@@ -160,3 +164,19 @@ pub(super) fn account_details<T>(
 
     Ok(f(name, seedfp, account, addresses))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::components::json_rpc::methods::testing::{TestWallet, error_code};
+
+    use super::call;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failures() {
+        let wallet = TestWallet::new().await;
+        assert!(call(&wallet.handle().await, None).is_ok());
+
+        wallet.drop_table("accounts").await;
+        assert_eq!(error_code(call(&wallet.handle().await, None)), -20);
+    }
+}
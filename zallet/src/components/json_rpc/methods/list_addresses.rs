@@ -1,8 +1,9 @@
+use std::collections::BTreeMap;
+
 use documented::Documented;
 use jsonrpsee::{
-    core::RpcResult,
+    core::{JsonValue, RpcResult},
     tracing::{error, warn},
-    types::ErrorCode as RpcErrorCode,
 };
 use schemars::JsonSchema;
 use serde::Serialize;
@@ -14,7 +15,20 @@ use zcash_client_backend::data_api::{
 use zcash_keys::address::Address;
 use zcash_protocol::consensus::NetworkConstants;
 
-use crate::components::{database::DbConnection, json_rpc::server::LegacyCode};
+use crate::components::{
+    database::DbConnection,
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::{
+            IMPORTED_SAPLING_KEY_SOURCE, IMPORTED_UNIFIED_KEY_SOURCE, parse_account_parameter,
+        },
+    },
+};
+
+use super::MethodCategory;
+
+#[cfg(zallet_build = "wallet")]
+use crate::components::keystore::KeyStore;
 
 /// Response to a `listaddresses` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
@@ -104,6 +118,12 @@ struct SaplingAddresses {
     zip32_key_path: Option<String>,
 
     addresses: Vec<String>,
+
+    /// The number of transactions from outside the wallet that paid each of the
+    /// addresses that has been reused.
+    #[serde(rename = "reuseCounts")]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    reuse_counts: BTreeMap<String, u32>,
 }
 
 #[derive(Clone, Debug, Serialize, JsonSchema)]
@@ -121,32 +141,123 @@ struct UnifiedAddresses {
 #[derive(Clone, Debug, Serialize, JsonSchema)]
 pub(crate) struct UnifiedAddress {
     /// The diversifier index that the UA was derived at.
-    diversifier_index: u128,
+    ///
+    /// Only present if `include_diversifier_indices` was set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diversifier_index: Option<u128>,
 
     /// The receiver types that the UA contains (valid values are "p2pkh", "sapling", "orchard").
     receiver_types: Vec<String>,
 
     /// The unified address corresponding to the diversifier.
     address: String,
+
+    /// The number of transactions from outside the wallet that have paid this address,
+    /// if it has been reused.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reuse_count: Option<u32>,
 }
 
-pub(crate) fn call(wallet: &DbConnection) -> Response {
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_ACCOUNT_DESC: &str =
+    "The UUID, name, or ZIP 32 account index of the account whose addresses to list.";
+pub(super) const PARAM_SOURCE_DESC: &str = "Only list addresses from this source.";
+pub(super) const PARAM_INCLUDE_DIVERSIFIER_INDICES_DESC: &str =
+    "Include the diversifier index of each Unified Address.";
+pub(super) const PARAM_OFFSET_DESC: &str = "The number of addresses to skip within each account.";
+pub(super) const PARAM_LIMIT_DESC: &str =
+    "The maximum number of addresses to list for each account.";
+
+/// The address sources that `listaddresses` can report, in the order they are listed.
+///
+/// `legacy_random` and `legacy_hdseed` are the `zcashd` sources for keys that Zallet does
+/// not track separately; they are accepted as filters but never contain addresses.
+const SOURCES: &[&str] = &[
+    "legacy_random",
+    "imported",
+    "imported_watchonly",
+    "legacy_hdseed",
+    "mnemonic_seed",
+];
+
+pub(crate) async fn call(
+    wallet: &DbConnection,
+    #[cfg(zallet_build = "wallet")] keystore: &KeyStore,
+    account: Option<JsonValue>,
+    source: Option<String>,
+    include_diversifier_indices: Option<bool>,
+    offset: Option<u32>,
+    limit: Option<u32>,
+) -> Response {
+    let source = source.as_deref().map(parse_source).transpose()?;
+    let include_diversifier_indices = include_diversifier_indices.unwrap_or(false);
+    let offset = offset.unwrap_or(0) as usize;
+    let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+
+    let account_ids = match &account {
+        Some(account) => vec![
+            parse_account_parameter(
+                wallet,
+                #[cfg(zallet_build = "wallet")]
+                keystore,
+                account,
+            )
+            .await?,
+        ],
+        None => wallet
+            .get_account_ids()
+            .map_err(ComponentFailure::database)?,
+    };
+
+    let mut imported = AddressSource::empty("imported");
     let mut imported_watchonly = AddressSource::empty("imported_watchonly");
     let mut mnemonic_seed = AddressSource::empty("mnemonic_seed");
+    let reused = wallet
+        .reused_addresses()
+        .map_err(ComponentFailure::database)?;
 
-    for account_id in wallet
-        .get_account_ids()
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
-    {
+    for account_id in account_ids {
         let account = wallet
             .get_account(account_id)
-            .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+            .map_err(ComponentFailure::database)?
             // This would be a race condition between this and account deletion.
-            .ok_or(RpcErrorCode::InternalError)?;
+            .ok_or_else(|| ComponentFailure::internal("Account was deleted during the request"))?;
+
+        let (account_source, derivation) = match account.source() {
+            AccountSource::Derived { derivation, .. } => ("mnemonic_seed", Some(derivation)),
+            AccountSource::Imported {
+                purpose: AccountPurpose::Spending { derivation: None },
+                key_source: Some(key_source),
+            } if key_source == IMPORTED_UNIFIED_KEY_SOURCE
+                || key_source == IMPORTED_SAPLING_KEY_SOURCE =>
+            {
+                ("imported", None)
+            }
+            AccountSource::Imported { purpose, .. } => {
+                let derivation = match purpose {
+                    // Imported UFVKs marked for spending are still counted as watch-only
+                    // because their corresponding spending key has never been observed by the
+                    // wallet; the distinction only affects whether Zallet tracks additional
+                    // metadata about the UFVK's notes. The `imported` category is used for
+                    // individual spending keys imported with `z_importkey`, as in `zcashd`.
+                    AccountPurpose::Spending { derivation } => derivation.as_ref(),
+                    AccountPurpose::ViewOnly => None,
+                };
+                ("imported_watchonly", derivation)
+            }
+        };
+
+        if source.is_some_and(|source| source != account_source) {
+            continue;
+        }
 
         let addresses = wallet
             .list_addresses(account.id())
-            .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
+            .map_err(ComponentFailure::database)?
+            .into_iter()
+            .skip(offset)
+            .take(limit);
 
         let mut transparent_addresses = vec![];
         let mut transparent_change_addresses = vec![];
@@ -174,28 +285,35 @@ pub(crate) fn call(wallet: &DbConnection) -> Response {
                                 address_info.source().transparent_key_scope(),
                                 addr.encode(wallet.params()),
                             );
-                            return Err(RpcErrorCode::InternalError.into());
+                            return Err(ComponentFailure::internal(
+                                "Wallet contains an address of unknown origin",
+                            )
+                            .into());
                         }
                     }
                 }
                 Address::Sapling(_) => sapling_addresses.push(addr.encode(wallet.params())),
                 Address::Unified(addr) => {
                     let address = addr.encode(wallet.params());
+                    let diversifier_index: u128 = match address_info.source() {
+                        zcash_client_backend::data_api::AddressSource::Derived {
+                            diversifier_index,
+                            ..
+                        } => diversifier_index.into(),
+                        #[cfg(feature = "transparent-key-import")]
+                        zcash_client_backend::data_api::AddressSource::Standalone => {
+                            error!(
+                                "Unified address {} lacks HD derivation information.",
+                                address
+                            );
+                            return Err(ComponentFailure::internal(
+                                "Wallet contains an address of unknown origin",
+                            )
+                            .into());
+                        }
+                    };
                     unified_addresses.push(UnifiedAddress {
-                        diversifier_index: match address_info.source() {
-                            zcash_client_backend::data_api::AddressSource::Derived {
-                                diversifier_index,
-                                ..
-                            } => diversifier_index.into(),
-                            #[cfg(feature = "transparent-key-import")]
-                            zcash_client_backend::data_api::AddressSource::Standalone => {
-                                error!(
-                                    "Unified address {} lacks HD derivation information.",
-                                    address
-                                );
-                                return Err(RpcErrorCode::InternalError.into());
-                            }
-                        },
+                        diversifier_index: include_diversifier_indices.then_some(diversifier_index),
                         receiver_types: addr
                             .receiver_types()
                             .into_iter()
@@ -209,6 +327,7 @@ pub(crate) fn call(wallet: &DbConnection) -> Response {
                                 }
                             })
                             .collect(),
+                        reuse_count: reused.get(&address).copied(),
                         address,
                     })
                 }
@@ -259,6 +378,10 @@ pub(crate) fn call(wallet: &DbConnection) -> Response {
                     zip32_key_path: account.map(|account_index| {
                         format!("m/32'/{}'/{}'", wallet.params().coin_type(), account_index)
                     }),
+                    reuse_counts: sapling_addresses
+                        .iter()
+                        .filter_map(|addr| Some((addr.clone(), *reused.get(addr)?)))
+                        .collect(),
                     addresses: sapling_addresses,
                 });
             }
@@ -270,28 +393,16 @@ pub(crate) fn call(wallet: &DbConnection) -> Response {
             });
         };
 
-        match account.source() {
-            AccountSource::Derived { derivation, .. } => {
-                add_addrs(&mut mnemonic_seed, Some(derivation));
-            }
-            AccountSource::Imported { purpose, .. } => {
-                let derivation = match purpose {
-                    // Imported UFVKs marked for spending are still counted as watch-only
-                    // because their corresponding spending key has never been observed by the
-                    // wallet; the distinction only affects whether Zallet tracks additional
-                    // metadata about the UFVK's notes. The `imported` category was used by
-                    // `zcashd` where individual spending keys were imported into the wallet.
-                    AccountPurpose::Spending { derivation } => derivation.as_ref(),
-                    AccountPurpose::ViewOnly => None,
-                };
-
-                add_addrs(&mut imported_watchonly, derivation);
-            }
+        match account_source {
+            "mnemonic_seed" => add_addrs(&mut mnemonic_seed, derivation),
+            "imported" => add_addrs(&mut imported, derivation),
+            _ => add_addrs(&mut imported_watchonly, derivation),
         }
     }
 
     Ok(ResultType(
         [
+            imported.has_data().then_some(imported),
             imported_watchonly.has_data().then_some(imported_watchonly),
             mnemonic_seed.has_data().then_some(mnemonic_seed),
         ]
@@ -300,3 +411,65 @@ pub(crate) fn call(wallet: &DbConnection) -> Response {
         .collect(),
     ))
 }
+
+/// Parses the `source` parameter of `listaddresses`.
+fn parse_source(source: &str) -> RpcResult<&'static str> {
+    SOURCES
+        .iter()
+        .find(|s| **s == source)
+        .copied()
+        .ok_or_else(|| {
+            LegacyCode::InvalidParameter.with_message(format!(
+                "Invalid address source \"{source}\", must be one of: {}",
+                SOURCES.join(", ")
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpsee::core::JsonValue;
+
+    use crate::components::json_rpc::methods::testing::{TestWallet, error_code};
+
+    use super::{call, parse_source};
+
+    #[test]
+    fn parse_source_accepts_known_sources() {
+        for source in super::SOURCES {
+            assert_eq!(parse_source(source).unwrap(), *source);
+        }
+    }
+
+    #[test]
+    fn parse_source_rejects_unknown_sources() {
+        assert!(parse_source("imported_spending").is_err());
+        assert!(parse_source("MNEMONIC_SEED").is_err());
+        assert!(parse_source("").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failures() {
+        let wallet = TestWallet::new().await;
+        let list = async |account: Option<&str>, source: Option<&str>| {
+            call(
+                &wallet.handle().await,
+                #[cfg(zallet_build = "wallet")]
+                &wallet.keystore,
+                account.map(|account| JsonValue::String(account.into())),
+                source.map(String::from),
+                None,
+                None,
+                None,
+            )
+            .await
+        };
+
+        // Invalid parameters.
+        assert_eq!(error_code(list(None, Some("imported_spending")).await), -8);
+        assert_eq!(error_code(list(Some("Savings"), None).await), -8);
+
+        wallet.drop_table("accounts").await;
+        assert_eq!(error_code(list(None, None).await), -20);
+    }
+}
@@ -5,6 +5,8 @@ use serde::Serialize;
 
 use crate::components::json_rpc::asyncop::{AsyncOperation, OperationId, OperationState};
 
+use super::MethodCategory;
+
 /// Response to a `z_listoperationids` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 
@@ -13,6 +15,8 @@ pub(crate) type Response = RpcResult<ResultType>;
 #[serde(transparent)]
 pub(crate) struct ResultType(Vec<OperationId>);
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_STATUS_DESC: &str =
     "Filter result by the operation's state e.g. \"success\".";
 
@@ -38,3 +42,91 @@ pub(crate) async fn call(async_ops: &[AsyncOperation], status: Option<&str>) ->
 
     Ok(ResultType(operation_ids))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::{Value, json};
+    use tokio::sync::oneshot;
+
+    use crate::{
+        components::json_rpc::{
+            asyncop::{OperationId, OperationRegistry, OperationState},
+            server::LegacyCode,
+        },
+        shutdown,
+    };
+
+    use super::{super::testing::TestWallet, call};
+
+    /// Waits for the given operation to reach `state`.
+    async fn wait_for(operations: &OperationRegistry, id: &OperationId, state: OperationState) {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let ops = operations.read().await;
+                let op = ops.iter().find(|op| op.operation_id() == id).unwrap();
+                if op.state().await == state {
+                    break;
+                }
+                drop(ops);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("operation should reach the state");
+    }
+
+    async fn list(operations: &OperationRegistry, status: Option<&str>) -> Value {
+        let ops = operations.read().await;
+        serde_json::to_value(call(&ops, status).await.unwrap()).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn filters_operations_by_state() {
+        let _running = shutdown::testing::without_shutdown().await;
+        let wallet = TestWallet::new().await;
+        let operations = wallet.operations();
+
+        let succeeded = operations.start(None, async { Ok(()) }).await;
+        let failed = operations
+            .start(None, async {
+                Err::<(), _>(LegacyCode::Misc.with_static("Operation failed"))
+            })
+            .await;
+        let (finish_tx, finish_rx) = oneshot::channel::<()>();
+        let executing = operations
+            .start(None, async move {
+                let _ = finish_rx.await;
+                Ok(())
+            })
+            .await;
+
+        wait_for(&operations, &succeeded, OperationState::Success).await;
+        wait_for(&operations, &failed, OperationState::Failed).await;
+        wait_for(&operations, &executing, OperationState::Executing).await;
+
+        assert_eq!(
+            list(&operations, None).await,
+            json!([succeeded, failed, executing]),
+        );
+        assert_eq!(
+            list(&operations, Some("executing")).await,
+            json!([executing]),
+        );
+        assert_eq!(list(&operations, Some("success")).await, json!([succeeded]));
+        assert_eq!(list(&operations, Some("failed")).await, json!([failed]));
+        assert_eq!(list(&operations, Some("queued")).await, json!([]));
+        // Like `zcashd`, unrecognised states match no operations.
+        assert_eq!(list(&operations, Some("running")).await, json!([]));
+
+        // Once the executing operation finishes, it is listed as a success.
+        finish_tx.send(()).unwrap();
+        wait_for(&operations, &executing, OperationState::Success).await;
+        assert_eq!(list(&operations, Some("executing")).await, json!([]));
+        assert_eq!(
+            list(&operations, Some("success")).await,
+            json!([succeeded, executing]),
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_client_backend::data_api::WalletRead;
+use zcash_keys::address::Address;
+use zcash_protocol::{PoolType, ShieldedProtocol, value::Zatoshis};
+
+use crate::components::{
+    database::DbConnection,
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::{JsonZec, zec_and_zat},
+    },
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_listreceivedbyaddress` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The outputs received on an address, ordered by when they were mined.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(Vec<ReceivedOutput>);
+
+/// An output received on the address.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct ReceivedOutput {
+    /// The ID of the transaction that created the output.
+    txid: String,
+
+    /// The value pool that the output is in (`transparent`, `sapling`, or `orchard`).
+    pool: &'static str,
+
+    /// The value of the output in ZEC.
+    amount: JsonZec,
+
+    /// The value of the output in zatoshis.
+    #[serde(rename = "amountZat")]
+    amount_zat: u64,
+
+    /// The output's memo, encoded as a hex string.
+    ///
+    /// Omitted for transparent outputs, and for shielded outputs whose memo the wallet
+    /// has not yet retrieved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+
+    /// The index of the output within the transaction's transparent outputs.
+    ///
+    /// Omitted for shielded outputs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vout: Option<u32>,
+
+    /// The index of the output within its shielded pool's bundle.
+    ///
+    /// Omitted for transparent outputs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outindex: Option<u32>,
+
+    /// The number of confirmations of the transaction, or 0 if it is unmined.
+    confirmations: u32,
+
+    /// The height of the block that the transaction is mined in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blockheight: Option<u32>,
+
+    /// Whether the output was received as change from the wallet's own transaction.
+    change: bool,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_ADDRESS_DESC: &str = "The address to list received outputs for.";
+pub(super) const PARAM_MINCONF_DESC: &str =
+    "Only include outputs confirmed at least this many times.";
+
+pub(crate) fn call(wallet: &DbConnection, address: &str, minconf: Option<u32>) -> Response {
+    let minconf = minconf.unwrap_or(1);
+
+    // A unified address is split into its receivers, as in `z_listunspent`.
+    let receivers = Address::decode(wallet.params(), address)
+        .ok_or_else(|| LegacyCode::InvalidAddressOrKey.with_static("Invalid address."))?
+        .as_understood_unified_receivers();
+
+    let chain_height = wallet
+        .chain_height()
+        .map_err(ComponentFailure::database)?
+        .ok_or_else(|| LegacyCode::InWarmup.with_static("Wait for the wallet to start up"))?;
+
+    let outputs = wallet
+        .received_on(&receivers, minconf, chain_height)
+        .map_err(ComponentFailure::database)?
+        // An empty list would not distinguish an unused address from a foreign one.
+        .ok_or_else(|| {
+            LegacyCode::InvalidAddressOrKey
+                .with_static("From address does not belong to this wallet.")
+        })?;
+
+    outputs
+        .into_iter()
+        .map(|output| {
+            let (amount, amount_zat) = zec_and_zat(
+                Zatoshis::from_u64(output.value)
+                    .map_err(|_| ComponentFailure::database("Invalid output value"))?,
+            );
+            let is_transparent = output.pool == PoolType::Transparent;
+
+            Ok(ReceivedOutput {
+                txid: output.txid.to_string(),
+                pool: match output.pool {
+                    PoolType::Transparent => "transparent",
+                    PoolType::Shielded(ShieldedProtocol::Sapling) => "sapling",
+                    PoolType::Shielded(ShieldedProtocol::Orchard) => "orchard",
+                },
+                amount,
+                amount_zat,
+                memo: output.memo.map(hex::encode),
+                vout: is_transparent.then_some(output.output_index),
+                outindex: (!is_transparent).then_some(output.output_index),
+                confirmations: output
+                    .mined_height
+                    .map_or(0, |h| u32::from(chain_height + 1).saturating_sub(h.into())),
+                blockheight: output.mined_height.map(u32::from),
+                change: output.is_change,
+            })
+        })
+        .collect::<RpcResult<_>>()
+        .map(ResultType)
+}
@@ -0,0 +1,177 @@
+use abscissa_core::Application;
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use rusqlite::{OptionalExtension, named_params};
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_client_backend::data_api::WalletRead;
+use zcash_primitives::block::BlockHash;
+
+use crate::{
+    components::{
+        database::DbConnection,
+        json_rpc::{
+            server::{ComponentFailure, LegacyCode},
+            utils::watch_only_accounts,
+        },
+    },
+    prelude::APP,
+};
+
+use super::{
+    MethodCategory,
+    list_wallet_history::{HistoryEntry, INCLUDE_WATCHONLY_DEFAULT, Selection, query_entries},
+};
+
+/// Response to a `listsinceblock` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = SinceBlock;
+
+/// The wallet history since a block.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct SinceBlock {
+    /// The wallet history entries for transactions that are unmined, or were mined after
+    /// the given block, ordered from oldest to newest.
+    transactions: Vec<HistoryEntry>,
+
+    /// The wallet history entries for transactions that were mined in blocks which chain
+    /// reorgs have since removed, if the given block was itself removed by a reorg.
+    ///
+    /// These transactions may have been mined again in the current chain, in which case
+    /// they are also in `transactions`.
+    removed: Vec<HistoryEntry>,
+
+    /// The hash of the block `target_confirmations - 1` blocks below the chain tip, to
+    /// pass as `blockhash` in the next call.
+    lastblock: String,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_BLOCKHASH_DESC: &str =
+    "The block hash to list transactions since. If omitted, all transactions are listed.";
+pub(super) const PARAM_TARGET_CONFIRMATIONS_DESC: &str =
+    "The number of confirmations of the block returned as lastblock.";
+pub(super) const PARAM_INCLUDE_WATCHONLY_DESC: &str =
+    "Also include entries for watch-only accounts.";
+
+pub(crate) fn call(
+    wallet: &DbConnection,
+    blockhash: Option<String>,
+    target_confirmations: Option<u32>,
+    include_watchonly: Option<bool>,
+) -> Response {
+    let target_confirmations = target_confirmations.unwrap_or(1);
+    if target_confirmations < 1 {
+        return Err(LegacyCode::InvalidParameter.with_static("Invalid parameter"));
+    }
+
+    let chain_height = wallet
+        .chain_height()
+        .map_err(ComponentFailure::database)?
+        .ok_or_else(|| LegacyCode::InWarmup.with_static("Wait for the wallet to start up"))?;
+
+    let reference = match blockhash.filter(|blockhash| !blockhash.is_empty()) {
+        Some(blockhash) => Some(
+            wallet
+                .find_reference_block(&parse_block_hash(&blockhash)?)
+                .map_err(ComponentFailure::database)?
+                .ok_or_else(|| LegacyCode::InvalidAddressOrKey.with_static("Block not found"))?,
+        ),
+        None => None,
+    };
+
+    // The checkpoint is the block at the target depth. If the wallet has not scanned
+    // that block, the closest block below it that the wallet has scanned is used, so
+    // that no transactions are skipped.
+    let lastblock_height = u32::from(chain_height + 1).saturating_sub(target_confirmations);
+    let lastblock = wallet
+        .with_raw(|conn, _| {
+            conn.query_row(
+                "SELECT hash FROM blocks
+                WHERE height <= :height
+                ORDER BY height DESC
+                LIMIT 1",
+                named_params! {":height": lastblock_height},
+                |row| row.get::<_, [u8; 32]>(0),
+            )
+            .optional()
+        })
+        .map_err(ComponentFailure::database)?
+        .ok_or_else(|| LegacyCode::InWarmup.with_static("Wait for the wallet to scan a block"))?;
+
+    let include_watchonly = include_watchonly.unwrap_or(INCLUDE_WATCHONLY_DEFAULT);
+    let watch_only = watch_only_accounts(wallet)?;
+    let max = APP.config().rpc.max_list_results();
+
+    let (transactions, removed) = wallet.with_raw_mut(|conn, _| {
+        let db_tx = conn.transaction().map_err(ComponentFailure::database)?;
+
+        let transactions = query_entries(
+            &db_tx,
+            Selection::Since {
+                height: reference.as_ref().map(|reference| reference.height),
+                count: max.saturating_add(1),
+            },
+            chain_height,
+            include_watchonly,
+            &watch_only,
+        )
+        .map_err(ComponentFailure::database)?;
+
+        let removed = match &reference {
+            Some(reference) if !reference.removed.is_empty() => query_entries(
+                &db_tx,
+                Selection::Transactions(&reference.removed),
+                chain_height,
+                include_watchonly,
+                &watch_only,
+            )
+            .map_err(ComponentFailure::database)?,
+            _ => vec![],
+        };
+
+        Ok::<_, ComponentFailure>((transactions, removed))
+    })?;
+
+    if transactions.len() > max as usize {
+        return Err(LegacyCode::InvalidParameter.with_message(format!(
+            "More than {max} results (rpc.max_list_results); pass a more recent blockhash"
+        )));
+    }
+
+    Ok(SinceBlock {
+        transactions,
+        removed,
+        lastblock: BlockHash(lastblock).to_string(),
+    })
+}
+
+fn parse_block_hash(blockhash: &str) -> RpcResult<BlockHash> {
+    let mut bytes = [0; 32];
+    hex::decode_to_slice(blockhash, &mut bytes).map_err(|_| {
+        LegacyCode::InvalidParameter
+            .with_static("blockhash must be a hexadecimal string of length 64")
+    })?;
+    bytes.reverse();
+    Ok(BlockHash(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_primitives::block::BlockHash;
+
+    use super::parse_block_hash;
+
+    #[test]
+    fn block_hashes_are_parsed_in_display_order() {
+        let hash = "00000000015b8a0e1d7fae6ea84b0a1e0b3b8d4e7c5d55b1f7a1ec6b1d1b6f2a";
+        assert_eq!(parse_block_hash(hash).unwrap().to_string(), hash);
+        assert!(parse_block_hash("00").is_err());
+        assert!(parse_block_hash(&"zz".repeat(32)).is_err());
+        assert_eq!(
+            parse_block_hash(&"00".repeat(32)).unwrap(),
+            BlockHash([0; 32])
+        );
+    }
+}
@@ -1,18 +1,33 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
 use documented::Documented;
 use jsonrpsee::core::RpcResult;
-use rusqlite::named_params;
+use rusqlite::{named_params, types::Value};
 use schemars::JsonSchema;
 use serde::Serialize;
 use time::format_description::well_known::Rfc3339;
 use uuid::Uuid;
-use zcash_client_sqlite::error::SqliteClientError;
+use zcash_client_sqlite::{AccountUuid, error::SqliteClientError};
 use zcash_protocol::{
     PoolType, ShieldedProtocol, TxId,
     memo::{Memo, MemoBytes},
     value::{ZatBalance, Zatoshis},
 };
 
-use crate::components::{database::DbConnection, json_rpc::server::LegacyCode};
+use crate::components::{
+    database::{DbConnection, wallet_conflicts},
+    json_rpc::{
+        pagination::{Listing, Pagination, Position},
+        server::{ComponentFailure, LegacyCode},
+        utils::{
+            JsonZec, JsonZecBalance, parse_as_of_height, query_height, watch_only_accounts,
+            zec_and_zat, zec_and_zat_balance,
+        },
+    },
+};
+
+use super::MethodCategory;
 
 const POOL_TRANSPARENT: &str = "transparent";
 const POOL_SAPLING: &str = "sapling";
@@ -21,10 +36,12 @@ const POOL_ORCHARD: &str = "orchard";
 /// Response to a `z_viewtransaction` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 
-/// A list of transactions involving the wallet.
+/// A list of transactions involving the wallet, or a page of them if a cursor was given.
 #[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
 #[serde(transparent)]
-pub(crate) struct ResultType(Vec<WalletTx>);
+pub(crate) struct ResultType(Listing<WalletTx>);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
 
 pub(super) const PARAM_ACCOUNT_UUID_DESC: &str =
     "The UUID of the account to list transactions for.";
@@ -35,7 +52,18 @@ pub(super) const PARAM_END_HEIGHT_DESC: &str =
 pub(super) const PARAM_OFFSET_DESC: &str =
     "The number of results to skip before returning a page of results.";
 pub(super) const PARAM_LIMIT_DESC: &str =
-    "The maximum number of results to return from a single call.";
+    "The maximum number of results to return from a single call, or in a page.";
+pub(super) const PARAM_AS_OF_HEIGHT_DESC: &str = "Execute the query as if it were run when the blockchain was at the height specified by this argument.";
+pub(super) const PARAM_INCLUDE_WATCHONLY_DESC: &str =
+    "Also include transactions that only affect watch-only accounts.";
+pub(super) const PARAM_CURSOR_DESC: &str =
+    "Return a page of results after this cursor, or the first page if empty.";
+
+/// Whether `include_watchonly` is `true` if omitted.
+///
+/// The merchant terminal build holds no spending keys, so its accounts are all
+/// watch-only and are listed by default.
+pub(super) const INCLUDE_WATCHONLY_DEFAULT: bool = cfg!(not(zallet_build = "wallet"));
 
 /// Basic information about a transaction output that was either created or received by this
 /// wallet.
@@ -46,7 +74,11 @@ struct WalletTxOutput {
     from_account: Option<String>,
     to_account: Option<String>,
     to_address: Option<String>,
-    value: u64,
+    /// The value of the output in ZEC.
+    value: JsonZec,
+    /// The value of the output in zatoshis.
+    #[serde(rename = "valueZat")]
+    value_zat: u64,
     is_change: bool,
     memo: Option<String>,
 }
@@ -72,6 +104,11 @@ impl WalletTxOutput {
         is_change: bool,
         memo: Option<Vec<u8>>,
     ) -> Result<Self, SqliteClientError> {
+        let (value, value_zat) =
+            zec_and_zat(Zatoshis::from_nonnegative_i64(value).map_err(|e| {
+                SqliteClientError::CorruptedData(format!("Invalid output value {value}: {e:?}"))
+            })?);
+
         Ok(Self {
             pool: match Self::parse_pool_code(pool_code).ok_or(SqliteClientError::CorruptedData(
                 format!("Invalid pool code: {pool_code}"),
@@ -85,9 +122,8 @@ impl WalletTxOutput {
             from_account: from_account.map(|u| u.to_string()),
             to_account: to_account.map(|u| u.to_string()),
             to_address,
-            value: u64::from(Zatoshis::from_nonnegative_i64(value).map_err(|e| {
-                SqliteClientError::CorruptedData(format!("Invalid output value {value}: {e:?}"))
-            })?),
+            value,
+            value_zat,
             is_change,
             memo: memo
                 .as_ref()
@@ -117,16 +153,28 @@ pub(crate) struct WalletTx {
     ///
     /// A transaction that has effects on multiple accounts will have multiple rows in the result.
     account_uuid: String,
+    /// Whether the account that this transaction entry is acting on is watch-only.
+    #[serde(rename = "involvesWatchonly")]
+    involves_watchonly: bool,
     /// The height at which the transaction was mined
     mined_height: Option<u32>,
     /// The transaction identifier
     txid: String,
     /// The expiry height of the transaction
     expiry_height: Option<u32>,
-    /// The delta to the account produced by the transaction
-    account_balance_delta: i64,
-    /// The fee paid by the transaction, if known.
-    fee_paid: Option<u64>,
+    /// The delta to the account produced by the transaction, in ZEC.
+    account_balance_delta: JsonZecBalance,
+    /// The delta to the account produced by the transaction, in zatoshis.
+    #[serde(rename = "account_balance_deltaZat")]
+    account_balance_delta_zat: i64,
+    /// The fee paid by the transaction in ZEC, if known.
+    ///
+    /// This is null if the transaction spends transparent inputs whose values are not
+    /// known to the wallet.
+    fee_paid: Option<JsonZec>,
+    /// The fee paid by the transaction in zatoshis, if known.
+    #[serde(rename = "fee_paidZat")]
+    fee_paid_zat: Option<u64>,
     /// The number of outputs produced by the transaction.
     sent_note_count: usize,
     /// The number of outputs received by the transaction.
@@ -137,54 +185,56 @@ pub(crate) struct WalletTx {
     block_datetime: Option<String>,
     /// Whether or not the transaction expired without having been mined.
     expired_unmined: bool,
+    /// Whether the transaction is mined, or is unmined but can be trusted.
+    ///
+    /// An unmined transaction is trusted only if it was created by this wallet, and all
+    /// of its inputs belong to the wallet and are themselves mined or trusted.
+    trusted: bool,
     /// The outputs of the transaction received by the wallet.
     outputs: Vec<WalletTxOutput>,
+    /// The IDs of wallet transactions that spend any of the same inputs as this
+    /// transaction.
+    walletconflicts: Vec<String>,
+    #[serde(skip)]
+    parsed_txid: TxId,
 }
 
 impl WalletTx {
     #[allow(clippy::too_many_arguments)]
     fn from_parts(
         account_uuid: Vec<u8>,
+        involves_watchonly: bool,
         mined_height: Option<u32>,
-        txid: Vec<u8>,
+        txid: TxId,
         expiry_height: Option<u32>,
         account_balance_delta: i64,
-        fee_paid: Option<u64>,
         sent_note_count: usize,
         received_note_count: usize,
         block_time: Option<i64>,
         expired_unmined: bool,
         outputs: Vec<WalletTxOutput>,
     ) -> Result<Self, SqliteClientError> {
+        let (account_balance_delta, account_balance_delta_zat) =
+            zec_and_zat_balance(ZatBalance::from_i64(account_balance_delta).map_err(|e| {
+                SqliteClientError::CorruptedData(format!(
+                    "Invalid balance delta {account_balance_delta}: {e:?}"
+                ))
+            })?);
         Ok(WalletTx {
             account_uuid: Uuid::from_bytes(<[u8; 16]>::try_from(account_uuid).map_err(|e| {
                 SqliteClientError::CorruptedData(format!("Invalid account uuid: {}", e.len()))
             })?)
             .to_string(),
+            involves_watchonly,
             mined_height,
-            txid: TxId::from_bytes(<[u8; 32]>::try_from(txid).map_err(|e| {
-                SqliteClientError::CorruptedData(format!("Invalid txid: {}", e.len()))
-            })?)
-            .to_string(),
+            txid: txid.to_string(),
+            parsed_txid: txid,
             expiry_height,
-            account_balance_delta: i64::from(ZatBalance::from_i64(account_balance_delta).map_err(
-                |e| {
-                    SqliteClientError::CorruptedData(format!(
-                        "Invalid balance delta {account_balance_delta}: {e:?}"
-                    ))
-                },
-            )?),
-            fee_paid: fee_paid
-                .map(|v| {
-                    Zatoshis::from_u64(v)
-                        .map_err(|e| {
-                            SqliteClientError::CorruptedData(format!(
-                                "Invalid fee value {v}: {e:?}"
-                            ))
-                        })
-                        .map(u64::from)
-                })
-                .transpose()?,
+            account_balance_delta,
+            account_balance_delta_zat,
+            // Filled in by `call`.
+            fee_paid: None,
+            fee_paid_zat: None,
             sent_note_count,
             received_note_count,
             block_time,
@@ -201,11 +251,16 @@ impl WalletTx {
                 })
                 .transpose()?,
             expired_unmined,
+            // Unmined transactions are checked once the query is complete.
+            trusted: mined_height.is_some(),
             outputs,
+            // Filled in once the query is complete.
+            walletconflicts: vec![],
         })
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn query_transactions(
     conn: &rusqlite::Transaction<'_>,
     account_uuid: Option<Uuid>,
@@ -213,47 +268,79 @@ fn query_transactions(
     end_height: Option<u32>,
     offset: Option<u32>,
     limit: Option<u32>,
-) -> Result<Vec<WalletTx>, SqliteClientError> {
+    pagination: Pagination,
+    as_of_height: Option<u32>,
+    include_watchonly: bool,
+    watch_only: &HashSet<AccountUuid>,
+) -> Result<Vec<(Position, WalletTx)>, SqliteClientError> {
+    let watch_only = Rc::new(
+        watch_only
+            .iter()
+            .map(|account_id| Value::Blob(account_id.expose_uuid().as_bytes().to_vec()))
+            .collect::<Vec<_>>(),
+    );
+
     let mut stmt_txs = conn.prepare(
-        "SELECT account_uuid,
-                mined_height,
-                txid,
-                expiry_height,
-                account_balance_delta,
-                fee_paid,
-                sent_note_count,
-                received_note_count,
-                block_time,
-                expired_unmined,
+        "SELECT * FROM (
+            SELECT v.account_uuid,
+                v.account_uuid IN rarray(:watch_only_accounts) AS involves_watchonly,
+                v.mined_height,
+                v.txid,
+                v.expiry_height,
+                v.account_balance_delta,
+                v.sent_note_count,
+                v.received_note_count,
+                v.block_time,
+                v.expired_unmined,
                 -- Fallback order for transaction history ordering:
                 COALESCE(
                     -- Block height the transaction was mined at (if mined and known).
-                    mined_height,
+                    v.mined_height,
                     -- Expiry height for the transaction (if non-zero, which is always the
                     -- case for transactions we create).
-                    CASE WHEN expiry_height == 0 THEN NULL ELSE expiry_height END
+                    CASE WHEN v.expiry_height == 0 THEN NULL ELSE v.expiry_height END
                     -- Mempool height (i.e. chain height + 1, so it appears most recently
                     -- in history). We represent this with NULL.
-                ) AS sort_height
-            FROM v_transactions
-            WHERE (:account_uuid IS NULL OR account_uuid = :account_uuid)
+                ) AS sort_height,
+                -- Tie-breakers that give each row a stable position for pagination.
+                t.tx_index,
+                t.id_tx,
+                a.id AS account_id
+            FROM v_transactions v
+            JOIN transactions t ON t.txid = v.txid
+            JOIN accounts a ON a.uuid = v.account_uuid
+            WHERE (:account_uuid IS NULL OR v.account_uuid = :account_uuid)
               AND (
                 -- ignore the start height if the provided value is None
                 :start_height IS NULL OR
                 -- the transaction is mined in the desired range
-                mined_height >= :start_height OR
+                v.mined_height >= :start_height OR
                 -- the start height is non-null, but we permit mempool transactions
-                (mined_height IS NULL AND :end_height IS NULL)
+                (v.mined_height IS NULL AND :end_height IS NULL)
               )
               AND (
                 -- ignore the end height & allow mempool txs if the provided value is None
-                :end_height IS NULL OR 
+                :end_height IS NULL OR
                 -- if an end height is provided, then the tx is required to be mined
-                mined_height < :end_height
+                v.mined_height < :end_height
+              )
+              AND (
+                -- as of a past height, only transactions mined at or below it are included
+                :as_of_height IS NULL OR
+                v.mined_height <= :as_of_height
               )
-            ORDER BY sort_height ASC NULLS LAST
-            LIMIT :limit
-            OFFSET :offset",
+              AND (:include_watchonly OR v.account_uuid NOT IN rarray(:watch_only_accounts))
+          )
+          WHERE :after_height IS NULL
+             OR (
+                COALESCE(sort_height, 4294967295),
+                COALESCE(tx_index, 4294967295),
+                id_tx,
+                account_id
+             ) > (:after_height, :after_tx_index, :after_tx_row, :after_entry)
+          ORDER BY sort_height ASC NULLS LAST, tx_index ASC NULLS LAST, id_tx, account_id
+          LIMIT :limit
+          OFFSET :offset",
     )?;
 
     let mut stmt_outputs = conn.prepare(
@@ -270,19 +357,37 @@ fn query_transactions(
              WHERE txid = :txid",
     )?;
 
-    stmt_txs
+    let after = pagination.after();
+    // When paginating, `limit` is the page size and is accounted for by `pagination`.
+    let limit = match (pagination, limit) {
+        (Pagination::All, Some(limit)) => limit.min(pagination.fetch_limit()),
+        _ => pagination.fetch_limit(),
+    };
+
+    let mut txs = stmt_txs
         .query_and_then::<_, SqliteClientError, _, _>(
             named_params! {
                 ":account_uuid": account_uuid,
                 ":start_height": start_height,
                 ":end_height": end_height,
-                ":limit": limit.map_or(-1, i64::from),
+                ":as_of_height": as_of_height,
+                ":include_watchonly": include_watchonly,
+                ":watch_only_accounts": watch_only,
+                ":after_height": after.map(|p| p.height()),
+                ":after_tx_index": after.map(|p| p.tx_index()),
+                ":after_tx_row": after.map(|p| p.tx_row()),
+                ":after_entry": after.map(|p| p.entry() as i64),
+                ":limit": limit,
                 ":offset": offset.unwrap_or(0)
             },
             |row| {
                 let txid = row
                     .get::<_, Vec<u8>>("txid")
                     .map_err(|e| SqliteClientError::CorruptedData(format!("{e}")))?;
+                let parsed_txid =
+                    TxId::from_bytes(<[u8; 32]>::try_from(txid.as_slice()).map_err(|_| {
+                        SqliteClientError::CorruptedData(format!("Invalid txid: {}", txid.len()))
+                    })?);
 
                 let tx_outputs = stmt_outputs
                     .query_and_then::<_, SqliteClientError, _, _>(
@@ -302,24 +407,45 @@ fn query_transactions(
                     )?
                     .collect::<Result<Vec<_>, _>>()?;
 
-                WalletTx::from_parts(
+                let position = Position::new(
+                    row.get("sort_height")?,
+                    row.get("tx_index")?,
+                    row.get("id_tx")?,
+                    row.get::<_, i64>("account_id")? as u64,
+                );
+
+                let tx = WalletTx::from_parts(
                     row.get("account_uuid")?,
+                    row.get("involves_watchonly")?,
                     row.get("mined_height")?,
-                    txid,
+                    parsed_txid,
                     row.get("expiry_height")?,
                     row.get("account_balance_delta")?,
-                    row.get("fee_paid")?,
                     row.get("sent_note_count")?,
                     row.get("received_note_count")?,
                     row.get("block_time")?,
                     row.get("expired_unmined")?,
                     tx_outputs,
-                )
+                )?;
+
+                Ok((position, tx))
             },
         )?
-        .collect::<Result<Vec<WalletTx>, _>>()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Find the conflicts of every listed transaction at once, rather than per row.
+    let txids = txs.iter().map(|(_, tx)| tx.parsed_txid).collect::<Vec<_>>();
+    let conflicts = wallet_conflicts(conn, &txids)?;
+    for (_, tx) in txs.iter_mut() {
+        if let Some(conflicts) = conflicts.get(&tx.parsed_txid) {
+            tx.walletconflicts = conflicts.iter().map(|txid| txid.to_string()).collect();
+        }
+    }
+
+    Ok(txs)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn call(
     wallet: &DbConnection,
     account_uuid: Option<String>,
@@ -327,7 +453,15 @@ pub(crate) async fn call(
     end_height: Option<u32>,
     offset: Option<u32>,
     limit: Option<u32>,
+    as_of_height: Option<i64>,
+    include_watchonly: Option<bool>,
+    cursor: Option<String>,
 ) -> Response {
+    let pagination = Pagination::parse(cursor.as_deref(), limit)?;
+    if matches!(pagination, Pagination::Page { .. }) && offset.is_some() {
+        return Err(LegacyCode::InvalidParameter.with_static("Cannot use offset with a cursor"));
+    }
+
     let account_uuid = account_uuid
         .map(|s| {
             Uuid::try_parse(&s).map_err(|_| {
@@ -336,21 +470,45 @@ pub(crate) async fn call(
         })
         .transpose()?;
 
-    wallet.with_raw_mut(|conn, _| {
-        let db_tx = conn
-            .transaction()
-            .map_err(|e| LegacyCode::Database.with_message(format!("{e}")))?;
-
-        Ok(ResultType(
-            query_transactions(
-                &db_tx,
-                account_uuid,
-                start_height,
-                end_height,
-                offset,
-                limit,
-            )
-            .map_err(|e| LegacyCode::Database.with_message(format!("{e}")))?,
-        ))
-    })
+    let as_of_height = parse_as_of_height(as_of_height)?
+        .map(|as_of_height| query_height(wallet, Some(as_of_height)))
+        .transpose()?
+        .map(|at| u32::from(at.height()));
+
+    let include_watchonly = include_watchonly.unwrap_or(INCLUDE_WATCHONLY_DEFAULT);
+    let watch_only = watch_only_accounts(wallet)?;
+
+    let mut txs = wallet.with_raw_mut(|conn, _| {
+        let db_tx = conn.transaction().map_err(ComponentFailure::database)?;
+
+        query_transactions(
+            &db_tx,
+            account_uuid,
+            start_height,
+            end_height,
+            offset,
+            limit,
+            pagination,
+            as_of_height,
+            include_watchonly,
+            &watch_only,
+        )
+        .map_err(ComponentFailure::database)
+    })?;
+
+    for (_, tx) in txs.iter_mut().filter(|(_, tx)| !tx.trusted) {
+        tx.trusted = wallet
+            .is_trusted_tx(&tx.parsed_txid)
+            .map_err(ComponentFailure::database)?;
+    }
+
+    for (_, tx) in txs.iter_mut() {
+        (tx.fee_paid, tx.fee_paid_zat) = wallet
+            .tx_fee(&tx.parsed_txid)
+            .map_err(ComponentFailure::database)?
+            .map(zec_and_zat)
+            .unzip();
+    }
+
+    Ok(ResultType(pagination.finish(txs)?))
 }
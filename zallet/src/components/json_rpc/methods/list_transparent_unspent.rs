@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use transparent::bundle::OutPoint;
+use zcash_client_backend::data_api::wallet::TargetHeight;
+use zcash_client_sqlite::AccountUuid;
+use zcash_keys::address::Address;
+use zcash_protocol::PoolType;
+
+use crate::components::{
+    database::{DbConnection, ReservedInput, unspent_outputs},
+    json_rpc::{
+        pagination::{Listing, Pagination, Position},
+        server::{ComponentFailure, LegacyCode},
+        utils::{
+            JsonZec, parse_as_of_height, parse_minconf, query_height, watch_only_accounts,
+            zec_and_zat,
+        },
+    },
+};
+
+use super::MethodCategory;
+
+/// Response to a `listunspent` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// A list of unspent transparent outputs, or a page of them if a cursor was given.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(Listing<UnspentTransparentOutput>);
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct UnspentTransparentOutput {
+    /// The ID of the transaction that created this output.
+    txid: String,
+
+    /// The index of the output within the transaction.
+    vout: u32,
+
+    /// The transparent address that received the output.
+    address: String,
+
+    /// The UUID of the wallet account that received this output.
+    account_uuid: String,
+
+    /// `true` if the account that received the output is watch-only.
+    ///
+    /// As in `zcashd`, outputs received by watch-only accounts are always listed.
+    is_watch_only: bool,
+
+    /// The output's script, as a hex string.
+    #[serde(rename = "scriptPubKey")]
+    script_pub_key: String,
+
+    /// The value of the output in ZEC.
+    amount: JsonZec,
+
+    /// The value of the output in zatoshis.
+    #[serde(rename = "amountZat")]
+    amount_zat: u64,
+
+    /// The number of confirmations.
+    confirmations: u32,
+
+    /// `true` if the output is from a coinbase transaction.
+    ///
+    /// Coinbase outputs cannot be spent until they have 100 confirmations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generated: Option<bool>,
+
+    /// `true` if the output is reserved as an input by an in-progress operation, such as
+    /// an asynchronous `z_sendmany`.
+    ///
+    /// Reserved outputs are not selected by other operations until the reservation is
+    /// released.
+    reserved: bool,
+
+    /// `true` if the wallet could spend the output in a new transaction.
+    ///
+    /// Outputs received by watch-only accounts, immature coinbase outputs, and reserved
+    /// outputs are not spendable. Unconfirmed outputs are only spendable if they are
+    /// change that the wallet may spend under `builder.spend_zeroconf_change`.
+    spendable: bool,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_MINCONF_DESC: &str =
+    "Only include outputs of transactions confirmed at least this many times.";
+pub(super) const PARAM_MAXCONF_DESC: &str =
+    "Only include outputs of transactions confirmed at most this many times.";
+pub(super) const PARAM_ADDRESSES_DESC: &str =
+    "If non-empty, only outputs received by the provided transparent addresses will be returned.";
+pub(super) const PARAM_AS_OF_HEIGHT_DESC: &str = "Execute the query as if it were run when the blockchain was at the height specified by this argument.";
+pub(super) const PARAM_LIMIT_DESC: &str =
+    "The maximum number of results to return in a page, when a cursor is given.";
+pub(super) const PARAM_CURSOR_DESC: &str =
+    "Return a page of results after this cursor, or the first page if empty.";
+
+pub(crate) fn call(
+    wallet: &DbConnection,
+    minconf: Option<u32>,
+    maxconf: Option<u32>,
+    addresses: Option<Vec<String>>,
+    as_of_height: Option<i64>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Response {
+    let pagination = Pagination::parse(cursor.as_deref(), limit)?;
+    let as_of_height = parse_as_of_height(as_of_height)?;
+    let minconf = parse_minconf(minconf, 1, as_of_height)?;
+    let maxconf = maxconf.unwrap_or(9_999_999);
+
+    // Addresses are compared in their canonical encoding.
+    let addresses = addresses
+        .unwrap_or_default()
+        .iter()
+        .map(|addr| match Address::decode(wallet.params(), addr) {
+            Some(address @ Address::Transparent(_)) => Ok(address.encode(wallet.params())),
+            _ => Err(LegacyCode::InvalidAddressOrKey
+                .with_message(format!("Invalid Zcash transparent address: {addr}"))),
+        })
+        .collect::<RpcResult<HashSet<_>>>()?;
+
+    let at = query_height(wallet, as_of_height)?;
+    let watch_only = watch_only_accounts(wallet)?;
+
+    let target_height = TargetHeight::from(at.height() + 1);
+
+    let outputs = wallet
+        .with_raw(|conn, _| unspent_outputs(conn, PoolType::Transparent, at, minconf, None))
+        .map_err(ComponentFailure::database)?;
+
+    let outputs = outputs
+        .into_iter()
+        .filter(|output| output.confirmations(at.height()) <= maxconf)
+        .filter_map(|output| {
+            let (address, script) = output.transparent.clone()?;
+            (addresses.is_empty() || addresses.contains(&address))
+                .then_some((output, address, script))
+        })
+        .map(|(output, address, script)| {
+            let confirmations = output.confirmations(at.height());
+            let is_watch_only = watch_only.contains(&AccountUuid::from_uuid(output.account_uuid));
+
+            // Flag the outputs that an in-progress operation intends to spend, so that
+            // they are not selected again by the caller.
+            let outpoint = OutPoint::new(*output.txid.as_ref(), output.output_index);
+            let reserved = wallet
+                .reservations()
+                .is_reserved(&ReservedInput::Transparent(outpoint.clone()));
+
+            let spendable = !(is_watch_only || reserved || output.is_immature(at.height()))
+                && (output.mined_height.is_some()
+                    || wallet
+                        .is_unmined_output_spendable(&outpoint, target_height)
+                        .map_err(ComponentFailure::database)?);
+
+            let position = Position::new(
+                output.mined_height.map(u32::from),
+                output.tx_index,
+                output.tx_row,
+                output.output_index.into(),
+            );
+
+            let (amount, amount_zat) = zec_and_zat(output.value);
+            Ok((
+                position,
+                UnspentTransparentOutput {
+                    txid: output.txid.to_string(),
+                    vout: output.output_index,
+                    address,
+                    account_uuid: output.account_uuid.to_string(),
+                    is_watch_only,
+                    script_pub_key: hex::encode(script),
+                    amount,
+                    amount_zat,
+                    confirmations,
+                    generated: output.is_coinbase().then_some(true),
+                    reserved,
+                    spendable,
+                },
+            ))
+        })
+        .collect::<RpcResult<Vec<_>>>()?;
+
+    Ok(ResultType(pagination.apply(outputs)?))
+}
@@ -1,19 +1,28 @@
+use std::convert::Infallible;
+
 use documented::Documented;
 use jsonrpsee::core::RpcResult;
 use schemars::JsonSchema;
 use serde::Serialize;
-use transparent::address::TransparentAddress;
-use zcash_address::ZcashAddress;
-use zcash_keys::{address::UnifiedAddress, encoding::AddressCodec};
+use zcash_address::{
+    ConversionError, TryFromAddress, ZcashAddress,
+    unified::{self, Container, Encoding},
+};
+use zcash_protocol::consensus::{NetworkType, Parameters};
+
+use crate::{
+    components::json_rpc::server::LegacyCode,
+    network::{Network, kind::type_to_str},
+};
 
-use crate::components::{database::DbConnection, json_rpc::server::LegacyCode};
+use super::MethodCategory;
 
 /// Response to a `z_listunifiedreceivers` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 pub(crate) type ResultType = ListUnifiedReceivers;
 
 /// The receivers within a unified address.
-#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[derive(Clone, Debug, Default, Serialize, Documented, JsonSchema)]
 pub(crate) struct ListUnifiedReceivers {
     /// The legacy P2PKH transparent address.
     ///
@@ -34,39 +43,189 @@ pub(crate) struct ListUnifiedReceivers {
     /// A single-receiver Unified Address containing the Orchard receiver.
     #[serde(skip_serializing_if = "Option::is_none")]
     orchard: Option<String>,
+
+    /// The items of the unified address with typecodes that Zallet does not recognise.
+    ///
+    /// Omitted if there are none.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unknown: Vec<UnknownReceiver>,
+}
+
+/// An item of a unified address with an unrecognised typecode.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+pub(crate) struct UnknownReceiver {
+    /// The item's typecode.
+    typecode: u32,
+
+    /// The item's encoding, as a hex string.
+    data: String,
 }
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_UNIFIED_ADDRESS_DESC: &str = "The unified address to inspect.";
 
-pub(crate) fn call(wallet: &DbConnection, unified_address: &str) -> Response {
-    ZcashAddress::try_from_encoded(unified_address)
-        .map_err(|_| LegacyCode::InvalidAddressOrKey.with_message("Invalid address".to_string()))?;
+pub(crate) fn call(params: &Network, unified_address: &str) -> Response {
+    let (network, address) =
+        unified::Address::decode(unified_address).map_err(
+            |e| match ZcashAddress::try_from_encoded(unified_address)
+                .ok()
+                .and_then(|addr| addr.convert::<AddressKind>().ok())
+            {
+                Some(AddressKind(kind)) => LegacyCode::InvalidParameter.with_message(format!(
+                    "Address is a {kind} address, not a unified address"
+                )),
+                None => LegacyCode::InvalidParameter
+                    .with_message(format!("Invalid unified address: {e}")),
+            },
+        )?;
+
+    if network != params.network_type() {
+        return Err(LegacyCode::InvalidParameter.with_message(format!(
+            "Unified address is for the {} network, but the wallet is on the {} network",
+            type_to_str(&network),
+            type_to_str(&params.network_type()),
+        )));
+    }
+
+    Ok(list_receivers(network, address))
+}
+
+pub(super) fn list_receivers(
+    network: NetworkType,
+    address: unified::Address,
+) -> ListUnifiedReceivers {
+    let mut receivers = ListUnifiedReceivers::default();
 
-    let address = match UnifiedAddress::decode(wallet.params(), unified_address) {
-        Ok(addr) => addr,
-        Err(_) => {
-            return Err(LegacyCode::InvalidParameter
-                .with_message("Address is not a unified address".to_string()));
+    for item in address.items() {
+        match item {
+            unified::Receiver::P2pkh(data) => {
+                receivers.p2pkh =
+                    Some(ZcashAddress::from_transparent_p2pkh(network, data).encode());
+            }
+            unified::Receiver::P2sh(data) => {
+                receivers.p2sh = Some(ZcashAddress::from_transparent_p2sh(network, data).encode());
+            }
+            unified::Receiver::Sapling(data) => {
+                receivers.sapling = Some(ZcashAddress::from_sapling(network, data).encode());
+            }
+            unified::Receiver::Orchard(data) => {
+                receivers.orchard = Some(
+                    ZcashAddress::from_unified(
+                        network,
+                        unified::Address::try_from_items(vec![unified::Receiver::Orchard(data)])
+                            .expect("valid"),
+                    )
+                    .encode(),
+                );
+            }
+            unified::Receiver::Unknown { typecode, data } => {
+                receivers.unknown.push(UnknownReceiver {
+                    typecode,
+                    data: hex::encode(data),
+                });
+            }
         }
-    };
+    }
+
+    receivers
+}
+
+/// The kind of an address that is not a unified address, for error messages.
+struct AddressKind(&'static str);
+
+impl TryFromAddress for AddressKind {
+    type Error = Infallible;
+
+    fn try_from_sprout(_: NetworkType, _: [u8; 64]) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(AddressKind("Sprout"))
+    }
+
+    fn try_from_sapling(_: NetworkType, _: [u8; 43]) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(AddressKind("Sapling"))
+    }
+
+    fn try_from_transparent_p2pkh(
+        _: NetworkType,
+        _: [u8; 20],
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(AddressKind("transparent P2PKH"))
+    }
+
+    fn try_from_transparent_p2sh(
+        _: NetworkType,
+        _: [u8; 20],
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(AddressKind("transparent P2SH"))
+    }
+
+    fn try_from_tex(_: NetworkType, _: [u8; 20]) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(AddressKind("TEX"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::consensus;
+
+    use super::*;
+
+    fn encode_ua(network: NetworkType, receivers: Vec<unified::Receiver>) -> String {
+        unified::Address::try_from_items(receivers)
+            .unwrap()
+            .encode(&network)
+    }
 
-    let transparent = address.transparent().map(|taddr| match taddr {
-        TransparentAddress::PublicKeyHash(_) => (Some(taddr.encode(wallet.params())), None),
-        TransparentAddress::ScriptHash(_) => (None, Some(taddr.encode(wallet.params()))),
-    });
+    #[test]
+    fn unknown_receivers() {
+        let params = Network::Consensus(consensus::Network::MainNetwork);
+        let ua = encode_ua(
+            NetworkType::Main,
+            vec![
+                unified::Receiver::Orchard([1; 43]),
+                unified::Receiver::Sapling([2; 43]),
+                unified::Receiver::Unknown {
+                    typecode: 0x30,
+                    data: vec![0xab, 0xcd],
+                },
+            ],
+        );
 
-    let (p2pkh, p2sh) = transparent.unwrap_or((None, None));
+        let receivers = call(&params, &ua).unwrap();
+        assert_eq!(
+            receivers.sapling,
+            Some(ZcashAddress::from_sapling(NetworkType::Main, [2; 43]).encode()),
+        );
+        assert!(receivers.orchard.is_some());
+        assert_eq!(
+            receivers.unknown,
+            vec![UnknownReceiver {
+                typecode: 0x30,
+                data: "abcd".into(),
+            }],
+        );
+    }
 
-    let sapling = address.sapling().map(|s| s.encode(wallet.params()));
+    #[test]
+    fn not_unified_addresses() {
+        let params = Network::Consensus(consensus::Network::MainNetwork);
+        let message = |address: &str| call(&params, address).unwrap_err().message().to_owned();
 
-    let orchard = address.orchard().and_then(|orch| {
-        UnifiedAddress::from_receivers(Some(*orch), None, None).map(|ua| ua.encode(wallet.params()))
-    });
+        assert_eq!(
+            message(&ZcashAddress::from_sapling(NetworkType::Main, [2; 43]).encode()),
+            "Address is a Sapling address, not a unified address",
+        );
+        assert_eq!(
+            message("t1V9mnyk5Z5cTNMCkLbaDwSskgJZucTLdgW"),
+            "Address is a transparent P2PKH address, not a unified address",
+        );
+        assert!(message("not an address").starts_with("Invalid unified address: "));
 
-    Ok(ListUnifiedReceivers {
-        p2pkh,
-        p2sh,
-        sapling,
-        orchard,
-    })
+        // Unified addresses for a different network are rejected.
+        let ua = encode_ua(NetworkType::Test, vec![unified::Receiver::Orchard([1; 43])]);
+        assert_eq!(
+            message(&ua),
+            "Unified address is for the test network, but the wallet is on the main network",
+        );
+    }
 }
@@ -1,10 +1,7 @@
 use std::num::NonZeroU32;
 
 use documented::Documented;
-use jsonrpsee::{
-    core::RpcResult,
-    types::{ErrorCode as RpcErrorCode, ErrorObjectOwned as RpcError},
-};
+use jsonrpsee::{core::RpcResult, types::ErrorObjectOwned as RpcError};
 use schemars::JsonSchema;
 use serde::Serialize;
 
@@ -12,32 +9,38 @@ use transparent::keys::TransparentKeyScope;
 use zcash_client_backend::{
     address::UnifiedAddress,
     data_api::{
-        Account, AccountPurpose, InputSource, WalletRead,
+        InputSource, WalletRead,
         wallet::{ConfirmationsPolicy, TargetHeight},
     },
     encoding::AddressCodec,
     fees::{orchard::InputView as _, sapling::InputView as _},
     wallet::NoteId,
 };
-use zcash_keys::address::Address;
-use zcash_protocol::ShieldedProtocol;
+use zcash_keys::address::{Address, Receiver};
+use zcash_protocol::{ShieldedProtocol, TxId, consensus::BlockHeight};
 use zip32::Scope;
 
 use crate::components::{
     database::DbConnection,
     json_rpc::{
-        server::LegacyCode,
-        utils::{JsonZec, parse_as_of_height, parse_minconf, value_from_zatoshis},
+        pagination::{Listing, Pagination, Position},
+        server::{ComponentFailure, LegacyCode},
+        utils::{
+            JsonZec, ensure_scanned, is_watch_only, parse_as_of_height, parse_minconf,
+            resolve_as_of_height, zec_and_zat,
+        },
     },
 };
 
+use super::MethodCategory;
+
 /// Response to a `z_listunspent` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 
-/// A list of unspent notes.
+/// A list of unspent notes, or a page of them if a cursor was given.
 #[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
 #[serde(transparent)]
-pub(crate) struct ResultType(Vec<UnspentOutput>);
+pub(crate) struct ResultType(Listing<UnspentOutput>);
 
 #[derive(Clone, Debug, Serialize, JsonSchema)]
 pub(crate) struct UnspentOutput {
@@ -58,6 +61,20 @@ pub(crate) struct UnspentOutput {
     /// `true` if the account that received the output is watch-only
     is_watch_only: bool,
 
+    /// `true` if the output can currently be spent by the wallet.
+    ///
+    /// Outputs received by watch-only accounts are never spendable. Unconfirmed outputs
+    /// are only spendable if they are transparent and `trusted`, and
+    /// `builder.spend_zeroconf_change` is enabled.
+    spendable: bool,
+
+    /// `true` if the output is confirmed, or is unconfirmed but can be trusted.
+    ///
+    /// An unconfirmed output is trusted only if the transaction that created it was
+    /// created by this wallet, and all of that transaction's inputs belong to the wallet
+    /// and are themselves confirmed or trusted.
+    trusted: bool,
+
     /// The Zcash address that received the output.
     ///
     /// Omitted if this output was received on an account-internal address (for example, change
@@ -96,6 +113,8 @@ pub(crate) struct UnspentOutput {
     memo_str: Option<String>,
 }
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_MINCONF_DESC: &str =
     "Only include outputs of transactions confirmed at least this many times.";
 pub(super) const PARAM_MAXCONF_DESC: &str =
@@ -105,17 +124,26 @@ pub(super) const PARAM_INCLUDE_WATCHONLY_DESC: &str =
 pub(super) const PARAM_ADDRESSES_DESC: &str =
     "If non-empty, only outputs received by the provided addresses will be returned.";
 pub(super) const PARAM_AS_OF_HEIGHT_DESC: &str = "Execute the query as if it were run when the blockchain was at the height specified by this argument.";
+pub(super) const PARAM_LIMIT_DESC: &str =
+    "The maximum number of results to return in a page, when a cursor is given.";
+pub(super) const PARAM_CURSOR_DESC: &str =
+    "Return a page of results after this cursor, or the first page if empty.";
 
-// FIXME: the following parameters are not yet properly supported
-// * include_watchonly
+/// Whether `include_watchonly` is `true` if omitted, as in `zcashd`.
+pub(super) const INCLUDE_WATCHONLY_DEFAULT: bool = false;
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn call(
     wallet: &DbConnection,
     minconf: Option<u32>,
     maxconf: Option<u32>,
-    _include_watchonly: Option<bool>,
+    include_watchonly: Option<bool>,
     addresses: Option<Vec<String>>,
     as_of_height: Option<i64>,
+    limit: Option<u32>,
+    cursor: Option<String>,
 ) -> Response {
+    let pagination = Pagination::parse(cursor.as_deref(), limit)?;
     let as_of_height = parse_as_of_height(as_of_height)?;
     let minconf = parse_minconf(minconf, 1, as_of_height)?;
 
@@ -124,7 +152,7 @@ pub(crate) fn call(
         None => ConfirmationsPolicy::new_symmetrical(NonZeroU32::new(1).unwrap(), true),
     };
 
-    //let include_watchonly = include_watchonly.unwrap_or(false);
+    let include_watchonly = include_watchonly.unwrap_or(INCLUDE_WATCHONLY_DEFAULT);
     let addresses = addresses
         .unwrap_or_default()
         .iter()
@@ -139,103 +167,190 @@ pub(crate) fn call(
         })
         .collect::<Result<Vec<Address>, _>>()?;
 
-    let target_height = match as_of_height.map_or_else(
-        || {
-            wallet.chain_height().map_err(|e| {
-                RpcError::owned(
-                    LegacyCode::Database.into(),
-                    "WalletDb::chain_height failed",
-                    Some(format!("{e}")),
-                )
-            })
-        },
-        |h| Ok(Some(h)),
-    )? {
-        Some(h) => TargetHeight::from(h + 1),
+    // If addresses are given, only outputs received by one of them (or by any receiver
+    // of a given unified address) are listed.
+    let filtered = !addresses.is_empty();
+    let receivers = addresses
+        .iter()
+        .flat_map(|addr| addr.as_understood_unified_receivers())
+        .collect::<Vec<_>>();
+    let transparent_receivers = receivers
+        .iter()
+        .filter_map(|r| match r {
+            Receiver::Transparent(addr) => Some(*addr),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let sapling_receivers = receivers
+        .iter()
+        .filter_map(|r| match r {
+            Receiver::Sapling(addr) => Some(*addr),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let orchard_receivers = receivers
+        .iter()
+        .filter_map(|r| match r {
+            Receiver::Orchard(addr) => Some(*addr),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let chain_tip = match wallet
+        .chain_height()
+        .map_err(|e| ComponentFailure::database(format!("WalletDb::chain_height failed: {e}")))?
+    {
+        Some(h) => h,
         None => {
-            return Ok(ResultType(vec![]));
+            return Ok(ResultType(pagination.apply(vec![])?));
         }
     };
+    let height = resolve_as_of_height(as_of_height, chain_tip)?;
+    if as_of_height.is_some() {
+        ensure_scanned(wallet, height)?;
+    }
+    let target_height = TargetHeight::from(height + 1);
 
     let mut unspent_outputs = vec![];
 
-    for account_id in wallet.get_account_ids().map_err(|e| {
-        RpcError::owned(
-            LegacyCode::Database.into(),
-            "WalletDb::get_account_ids failed",
-            Some(format!("{e}")),
-        )
-    })? {
-        let account = wallet
-            .get_account(account_id)
+    // Outputs are ordered by their transaction's position in the chain, then by pool and
+    // output index (using the same pool codes as `z_listtransactions`).
+    let position_of = |txid: &TxId,
+                       mined_height: Option<BlockHeight>,
+                       pool_code: u64,
+                       index: u32|
+     -> RpcResult<Position> {
+        let (tx_index, tx_row) = wallet
+            .transaction_position(txid)
             .map_err(|e| {
-                RpcError::owned(
-                    LegacyCode::Database.into(),
-                    "WalletDb::get_account failed",
-                    Some(format!("{e}")),
-                )
+                ComponentFailure::database(format!(
+                    "DbConnection::transaction_position failed: {e}"
+                ))
             })?
+            .ok_or_else(|| {
+                ComponentFailure::internal("Output's transaction is not in the wallet")
+            })?;
+        Ok(Position::new(
+            mined_height.map(u32::from),
+            tx_index,
+            tx_row,
+            (pool_code << 32) | u64::from(index),
+        ))
+    };
+
+    for account_id in wallet
+        .get_account_ids()
+        .map_err(|e| ComponentFailure::database(format!("WalletDb::get_account_ids failed: {e}")))?
+    {
+        let account = wallet
+            .get_account(account_id)
+            .map_err(|e| ComponentFailure::database(format!("WalletDb::get_account failed: {e}")))?
             // This would be a race condition between this and account deletion.
-            .ok_or(RpcErrorCode::InternalError)?;
+            .ok_or_else(|| ComponentFailure::internal("Account was deleted during the request"))?;
 
-        let is_watch_only = !matches!(account.purpose(), AccountPurpose::Spending { .. });
+        let is_watch_only = is_watch_only(&account);
+        if is_watch_only && !include_watchonly {
+            continue;
+        }
 
         let utxos = wallet
             .get_transparent_receivers(account_id, true, true)
             .map_err(|e| {
-                RpcError::owned(
-                    LegacyCode::Database.into(),
-                    "WalletDb::get_transparent_receivers failed",
-                    Some(format!("{e}")),
-                )
+                ComponentFailure::database(format!(
+                    "WalletDb::get_transparent_receivers failed: {e}"
+                ))
             })?
             .iter()
             .try_fold(vec![], |mut acc, (addr, _)| {
+                // Query the backend directly so that unconfirmed outputs are listed
+                // regardless of whether the wallet's policy permits spending them.
                 let mut outputs = wallet
-                    .get_spendable_transparent_outputs(addr, target_height, confirmations_policy)
-                    .map_err(|e| {
-                        RpcError::owned(
-                            LegacyCode::Database.into(),
-                            "WalletDb::get_spendable_transparent_outputs failed",
-                            Some(format!("{e}")),
+                    .with(|db_data| {
+                        db_data.get_spendable_transparent_outputs(
+                            addr,
+                            target_height,
+                            confirmations_policy,
                         )
+                    })
+                    .map_err(|e| {
+                        ComponentFailure::database(format!(
+                            "WalletDb::get_spendable_transparent_outputs failed: {e}"
+                        ))
                     })?;
 
                 acc.append(&mut outputs);
                 Ok::<_, RpcError>(acc)
             })?;
 
-        for utxo in utxos {
+        let is_trusted = |txid: &TxId| {
+            wallet.is_trusted_tx(txid).map_err(|e| {
+                ComponentFailure::database(format!("DbConnection::is_trusted_tx failed: {e}"))
+            })
+        };
+
+        for utxo in utxos
+            .into_iter()
+            .filter(|utxo| !filtered || transparent_receivers.contains(utxo.recipient_address()))
+        {
             let confirmations = utxo.mined_height().map(|h| target_height - h).unwrap_or(0);
 
+            // skip outputs that have too many confirmations according to maxconf
+            if maxconf.iter().any(|c| confirmations > *c) {
+                continue;
+            }
+
             let wallet_internal = wallet
                 .get_transparent_address_metadata(account_id, utxo.recipient_address())
                 .map_err(|e| {
-                    RpcError::owned(
-                        LegacyCode::Database.into(),
-                        "WalletDb::get_transparent_address_metadata failed",
-                        Some(format!("{e}")),
-                    )
+                    ComponentFailure::database(format!(
+                        "WalletDb::get_transparent_address_metadata failed: {e}"
+                    ))
                 })?
                 .is_some_and(|m| m.scope() == Some(TransparentKeyScope::INTERNAL));
 
-            unspent_outputs.push(UnspentOutput {
-                txid: utxo.outpoint().txid().to_string(),
-                pool: "transparent".into(),
-                outindex: utxo.outpoint().n(),
-                confirmations,
-                is_watch_only,
-                account_uuid: account_id.expose_uuid().to_string(),
-                address: utxo
-                    .txout()
-                    .recipient_address()
-                    .map(|addr| addr.encode(wallet.params())),
-                value: value_from_zatoshis(utxo.value()),
-                value_zat: u64::from(utxo.value()),
-                memo: None,
-                memo_str: None,
-                wallet_internal,
-            })
+            let trusted = utxo.mined_height().is_some()
+                || is_trusted(&TxId::from_bytes(*utxo.outpoint().hash()))?;
+
+            let spendable = !is_watch_only
+                && (utxo.mined_height().is_some()
+                    || wallet
+                        .is_unmined_output_spendable(utxo.outpoint(), target_height)
+                        .map_err(|e| {
+                            ComponentFailure::database(format!(
+                                "DbConnection::is_unmined_output_spendable failed: {e}"
+                            ))
+                        })?);
+
+            let (value, value_zat) = zec_and_zat(utxo.value());
+            let position = position_of(
+                &TxId::from_bytes(*utxo.outpoint().hash()),
+                utxo.mined_height(),
+                0,
+                utxo.outpoint().n(),
+            )?;
+
+            unspent_outputs.push((
+                position,
+                UnspentOutput {
+                    txid: utxo.outpoint().txid().to_string(),
+                    pool: "transparent".into(),
+                    outindex: utxo.outpoint().n(),
+                    confirmations,
+                    is_watch_only,
+                    spendable,
+                    trusted,
+                    account_uuid: account_id.expose_uuid().to_string(),
+                    address: utxo
+                        .txout()
+                        .recipient_address()
+                        .map(|addr| addr.encode(wallet.params())),
+                    value,
+                    value_zat,
+                    memo: None,
+                    memo_str: None,
+                    wallet_internal,
+                },
+            ))
         }
 
         let notes = wallet
@@ -246,23 +361,13 @@ pub(crate) fn call(
                 &[],
             )
             .map_err(|e| {
-                RpcError::owned(
-                    LegacyCode::Database.into(),
-                    "WalletDb::select_unspent_notes failed",
-                    Some(format!("{e}")),
-                )
+                ComponentFailure::database(format!("WalletDb::select_unspent_notes failed: {e}"))
             })?;
 
         let get_memo = |txid, protocol, output_index| -> RpcResult<_> {
             Ok(wallet
                 .get_memo(NoteId::new(txid, protocol, output_index))
-                .map_err(|e| {
-                    RpcError::owned(
-                        LegacyCode::Database.into(),
-                        "WalletDb::get_memo failed",
-                        Some(format!("{e}")),
-                    )
-                })?
+                .map_err(|e| ComponentFailure::database(format!("WalletDb::get_memo failed: {e}")))?
                 .map(|memo| {
                     (
                         hex::encode(memo.encode().as_array()),
@@ -277,19 +382,15 @@ pub(crate) fn call(
 
         let get_mined_height = |txid| {
             wallet.get_tx_height(txid).map_err(|e| {
-                RpcError::owned(
-                    LegacyCode::Database.into(),
-                    "WalletDb::get_tx_height failed",
-                    Some(format!("{e}")),
-                )
+                ComponentFailure::database(format!("WalletDb::get_tx_height failed: {e}"))
             })
         };
 
-        for note in notes.sapling().iter().filter(|n| {
-            addresses
-                .iter()
-                .all(|addr| addr.to_sapling_address() == Some(n.note().recipient()))
-        }) {
+        for note in notes
+            .sapling()
+            .iter()
+            .filter(|n| !filtered || sapling_receivers.contains(&n.note().recipient()))
+        {
             let tx_mined_height = get_mined_height(*note.txid())?;
             let confirmations = tx_mined_height
                 .map_or(0, |h| u32::from(target_height.saturating_sub(u32::from(h))));
@@ -309,35 +410,39 @@ pub(crate) fn call(
             let (memo, memo_str) =
                 get_memo(*note.txid(), ShieldedProtocol::Sapling, note.output_index())?;
 
-            unspent_outputs.push(UnspentOutput {
-                txid: note.txid().to_string(),
-                pool: "sapling".into(),
-                outindex: note.output_index().into(),
-                confirmations,
-                is_watch_only,
-                account_uuid: account_id.expose_uuid().to_string(),
-                // TODO: Ensure we generate the same kind of shielded address as `zcashd`.
-                address: (!is_internal).then(|| note.note().recipient().encode(wallet.params())),
-                value: value_from_zatoshis(note.value()),
-                value_zat: u64::from(note.value()),
-                memo: Some(memo),
-                memo_str,
-                wallet_internal: is_internal,
-            })
+            let (value, value_zat) = zec_and_zat(note.value());
+            let position =
+                position_of(note.txid(), tx_mined_height, 2, note.output_index().into())?;
+
+            unspent_outputs.push((
+                position,
+                UnspentOutput {
+                    txid: note.txid().to_string(),
+                    pool: "sapling".into(),
+                    outindex: note.output_index().into(),
+                    confirmations,
+                    is_watch_only,
+                    // Unconfirmed shielded change cannot be spent.
+                    spendable: !is_watch_only && tx_mined_height.is_some(),
+                    trusted: tx_mined_height.is_some() || is_trusted(note.txid())?,
+                    account_uuid: account_id.expose_uuid().to_string(),
+                    // TODO: Ensure we generate the same kind of shielded address as `zcashd`.
+                    address: (!is_internal)
+                        .then(|| note.note().recipient().encode(wallet.params())),
+                    value,
+                    value_zat,
+                    memo: Some(memo),
+                    memo_str,
+                    wallet_internal: is_internal,
+                },
+            ))
         }
 
-        for note in notes.orchard().iter().filter(|n| {
-            addresses.iter().all(|addr| {
-                addr.as_understood_unified_receivers()
-                    .iter()
-                    .any(|r| match r {
-                        zcash_keys::address::Receiver::Orchard(address) => {
-                            address == &n.note().recipient()
-                        }
-                        _ => false,
-                    })
-            })
-        }) {
+        for note in notes
+            .orchard()
+            .iter()
+            .filter(|n| !filtered || orchard_receivers.contains(&n.note().recipient()))
+        {
             let tx_mined_height = get_mined_height(*note.txid())?;
             let confirmations = tx_mined_height
                 .map_or(0, |h| u32::from(target_height.saturating_sub(u32::from(h))));
@@ -357,27 +462,37 @@ pub(crate) fn call(
             let (memo, memo_str) =
                 get_memo(*note.txid(), ShieldedProtocol::Orchard, note.output_index())?;
 
-            unspent_outputs.push(UnspentOutput {
-                txid: note.txid().to_string(),
-                pool: "orchard".into(),
-                outindex: note.output_index().into(),
-                confirmations,
-                is_watch_only,
-                account_uuid: account_id.expose_uuid().to_string(),
-                // TODO: Ensure we generate the same kind of shielded address as `zcashd`.
-                address: (!wallet_internal).then(|| {
-                    UnifiedAddress::from_receivers(Some(note.note().recipient()), None, None)
-                        .expect("valid")
-                        .encode(wallet.params())
-                }),
-                value: value_from_zatoshis(note.value()),
-                value_zat: u64::from(note.value()),
-                memo: Some(memo),
-                memo_str,
-                wallet_internal,
-            })
+            let (value, value_zat) = zec_and_zat(note.value());
+            let position =
+                position_of(note.txid(), tx_mined_height, 3, note.output_index().into())?;
+
+            unspent_outputs.push((
+                position,
+                UnspentOutput {
+                    txid: note.txid().to_string(),
+                    pool: "orchard".into(),
+                    outindex: note.output_index().into(),
+                    confirmations,
+                    is_watch_only,
+                    // Unconfirmed shielded change cannot be spent.
+                    spendable: !is_watch_only && tx_mined_height.is_some(),
+                    trusted: tx_mined_height.is_some() || is_trusted(note.txid())?,
+                    account_uuid: account_id.expose_uuid().to_string(),
+                    // TODO: Ensure we generate the same kind of shielded address as `zcashd`.
+                    address: (!wallet_internal).then(|| {
+                        UnifiedAddress::from_receivers(Some(note.note().recipient()), None, None)
+                            .expect("valid")
+                            .encode(wallet.params())
+                    }),
+                    value,
+                    value_zat,
+                    memo: Some(memo),
+                    memo_str,
+                    wallet_internal,
+                },
+            ))
         }
     }
 
-    Ok(ResultType(unspent_outputs))
+    Ok(ResultType(pagination.apply(unspent_outputs)?))
 }
@@ -0,0 +1,401 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use rusqlite::{named_params, types::Value};
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+use zcash_client_backend::data_api::WalletRead;
+use zcash_client_sqlite::{AccountUuid, error::SqliteClientError};
+use zcash_primitives::block::BlockHash;
+use zcash_protocol::{TxId, consensus::BlockHeight, value::ZatBalance};
+
+use crate::components::{
+    database::{DbConnection, wallet_conflicts},
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::{JsonZecBalance, watch_only_accounts, zec_and_zat_balance},
+    },
+};
+
+use super::MethodCategory;
+
+/// Response to a `listtransactions` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// A list of wallet history entries, ordered from oldest to newest.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(Vec<HistoryEntry>);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_DUMMY_DESC: &str =
+    "Remains for backward compatibility. Must be excluded or set to \"*\" or \"\".";
+pub(super) const PARAM_COUNT_DESC: &str = "The number of entries to return.";
+pub(super) const PARAM_FROM_DESC: &str = "The number of most recent entries to skip.";
+pub(super) const PARAM_INCLUDE_WATCHONLY_DESC: &str =
+    "Also include entries for watch-only accounts.";
+
+/// The number of entries returned if `count` is omitted, as in `zcashd`.
+const DEFAULT_COUNT: u32 = 10;
+
+/// Whether `include_watchonly` is `true` if omitted.
+///
+/// As with `z_listtransactions`, the merchant terminal build lists its watch-only
+/// accounts by default.
+pub(super) const INCLUDE_WATCHONLY_DEFAULT: bool = cfg!(not(zallet_build = "wallet"));
+
+/// The effect of a single transaction output on an account in the wallet.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct HistoryDetail {
+    /// The UUID of the account that this entry is acting on.
+    account_uuid: String,
+
+    /// Whether the account that this entry is acting on is watch-only.
+    #[serde(rename = "involvesWatchonly")]
+    involves_watchonly: bool,
+
+    /// The address that the output was sent to, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+
+    /// The kind of entry:
+    /// - `send`: The account sent funds to this output.
+    /// - `receive`: The account received funds in this output.
+    /// - `generate`: The account received funds in this output of a coinbase
+    ///   transaction.
+    category: &'static str,
+
+    /// The value of the output in ZEC. This is negative for `send` entries.
+    amount: JsonZecBalance,
+
+    /// The value of the output in zatoshis. This is negative for `send` entries.
+    #[serde(rename = "amountZat")]
+    amount_zat: i64,
+
+    /// The value pool that the output is in (`transparent`, `sapling`, or `orchard`).
+    ///
+    /// Shielded outputs have no `vout`; their memos and other details are available from
+    /// `z_viewtransaction`.
+    pool: &'static str,
+
+    /// The index of the output within the transaction's transparent outputs.
+    ///
+    /// Omitted for shielded outputs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vout: Option<u32>,
+
+    /// The index of the output within its shielded pool's bundle.
+    ///
+    /// Omitted for transparent outputs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outindex: Option<u32>,
+}
+
+impl HistoryDetail {
+    /// The value of the output in zatoshis, negated for `send` entries.
+    pub(super) fn amount_zat(&self) -> i64 {
+        self.amount_zat
+    }
+
+    fn from_row(row: &rusqlite::Row<'_>) -> Result<Self, SqliteClientError> {
+        let is_receive = row.get::<_, bool>("is_receive")?;
+        let value = row.get::<_, i64>("value")?;
+        let (amount, amount_zat) = zec_and_zat_balance(
+            ZatBalance::from_i64(if is_receive { value } else { -value }).map_err(|e| {
+                SqliteClientError::CorruptedData(format!("Invalid output value {value}: {e:?}"))
+            })?,
+        );
+        let (pool, is_transparent) = pool_name(row.get("output_pool")?)?;
+        let output_index = row.get::<_, u32>("output_index")?;
+
+        // The coinbase transaction is always the first transaction in its block.
+        let category = match (is_receive, row.get::<_, Option<u32>>("tx_index")?) {
+            (false, _) => "send",
+            (true, Some(0)) => "generate",
+            (true, _) => "receive",
+        };
+
+        Ok(Self {
+            account_uuid: Uuid::from_bytes(row.get("account_uuid")?).to_string(),
+            involves_watchonly: row.get("involves_watchonly")?,
+            address: row.get("to_address")?,
+            category,
+            amount,
+            amount_zat,
+            pool,
+            vout: is_transparent.then_some(output_index),
+            outindex: (!is_transparent).then_some(output_index),
+        })
+    }
+}
+
+/// A wallet history entry.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct HistoryEntry {
+    #[serde(flatten)]
+    detail: HistoryDetail,
+
+    /// The number of confirmations of the transaction, or 0 if it is unmined.
+    confirmations: u32,
+
+    /// The hash of the block that the transaction is mined in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blockhash: Option<String>,
+
+    /// The height of the block that the transaction is mined in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blockheight: Option<u32>,
+
+    /// The time of the block that the transaction is mined in, in seconds since the
+    /// Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocktime: Option<i64>,
+
+    /// The transaction ID.
+    txid: String,
+
+    /// The IDs of wallet transactions that spend any of the same inputs as this
+    /// transaction.
+    walletconflicts: Vec<String>,
+}
+
+pub(super) fn pool_name(pool_code: i64) -> Result<(&'static str, bool), SqliteClientError> {
+    match pool_code {
+        0 => Ok(("transparent", true)),
+        2 => Ok(("sapling", false)),
+        3 => Ok(("orchard", false)),
+        _ => Err(SqliteClientError::CorruptedData(format!(
+            "Invalid pool code: {pool_code}"
+        ))),
+    }
+}
+
+/// Each non-change output produces a `send` entry for the account that sent it, and a
+/// `receive` entry for the account that received it.
+const ENTRIES_CTE: &str = "entries AS (
+    SELECT txid, output_pool, output_index, to_address, value,
+        from_account_uuid AS account_uuid, 0 AS is_receive
+    FROM v_tx_outputs
+    WHERE from_account_uuid IS NOT NULL AND NOT is_change
+    UNION ALL
+    SELECT txid, output_pool, output_index, to_address, value,
+        to_account_uuid AS account_uuid, 1 AS is_receive
+    FROM v_tx_outputs
+    WHERE to_account_uuid IS NOT NULL AND NOT is_change
+)";
+
+fn watch_only_param(watch_only: &HashSet<AccountUuid>) -> Rc<Vec<Value>> {
+    Rc::new(
+        watch_only
+            .iter()
+            .map(|account_id| Value::Blob(account_id.expose_uuid().as_bytes().to_vec()))
+            .collect(),
+    )
+}
+
+/// Returns the entries for the given transaction, without their transaction-level
+/// fields.
+pub(super) fn query_details(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+    include_watchonly: bool,
+    watch_only: &HashSet<AccountUuid>,
+) -> Result<Vec<HistoryDetail>, SqliteClientError> {
+    let mut stmt = conn.prepare(&format!(
+        "WITH {ENTRIES_CTE}
+        SELECT e.account_uuid,
+            e.account_uuid IN rarray(:watch_only_accounts) AS involves_watchonly,
+            e.to_address,
+            e.is_receive,
+            e.value,
+            e.output_pool,
+            e.output_index,
+            t.tx_index
+        FROM entries e
+        JOIN transactions t ON t.txid = e.txid
+        JOIN accounts a ON a.uuid = e.account_uuid
+        WHERE e.txid = :txid
+          AND (:include_watchonly OR e.account_uuid NOT IN rarray(:watch_only_accounts))
+        ORDER BY a.id, e.is_receive, e.output_pool, e.output_index"
+    ))?;
+
+    stmt.query_and_then(
+        named_params! {
+            ":txid": txid.as_ref(),
+            ":include_watchonly": include_watchonly,
+            ":watch_only_accounts": watch_only_param(watch_only),
+        },
+        HistoryDetail::from_row,
+    )?
+    .collect()
+}
+
+/// The wallet history entries to select.
+pub(super) enum Selection<'a> {
+    /// Up to `count` of the most recent entries, skipping the `from` most recent.
+    Recent { count: u32, from: u32 },
+    /// Up to `count` of the most recent entries for transactions that are unmined, or
+    /// mined above `height`.
+    Since {
+        height: Option<BlockHeight>,
+        count: u32,
+    },
+    /// The entries for the given transactions.
+    Transactions(&'a [TxId]),
+}
+
+pub(super) fn query_entries(
+    conn: &rusqlite::Transaction<'_>,
+    selection: Selection<'_>,
+    chain_height: BlockHeight,
+    include_watchonly: bool,
+    watch_only: &HashSet<AccountUuid>,
+) -> Result<Vec<HistoryEntry>, SqliteClientError> {
+    let (count, from, since_height, txids) = match selection {
+        Selection::Recent { count, from } => (i64::from(count), from, None, None),
+        Selection::Since { height, count } => (i64::from(count), 0, height, None),
+        // A negative limit means that there is no limit.
+        Selection::Transactions(txids) => (-1, 0, None, Some(txids)),
+    };
+
+    // Entries are selected from the newest end of the history, so that `from` and
+    // `count` slice it as `zcashd` does.
+    let mut stmt = conn.prepare(&format!(
+        "WITH {ENTRIES_CTE}
+        SELECT e.account_uuid,
+            e.account_uuid IN rarray(:watch_only_accounts) AS involves_watchonly,
+            e.to_address,
+            e.is_receive,
+            e.value,
+            e.output_pool,
+            e.output_index,
+            e.txid,
+            t.tx_index,
+            v.mined_height,
+            v.block_time,
+            b.hash AS block_hash
+        FROM entries e
+        JOIN transactions t ON t.txid = e.txid
+        JOIN v_transactions v ON v.txid = e.txid AND v.account_uuid = e.account_uuid
+        JOIN accounts a ON a.uuid = e.account_uuid
+        LEFT JOIN blocks b ON b.height = v.mined_height
+        WHERE (:include_watchonly OR e.account_uuid NOT IN rarray(:watch_only_accounts))
+          AND (
+            :since_height IS NULL
+            OR v.mined_height IS NULL
+            OR v.mined_height > :since_height
+          )
+          AND (:filter_txids = 0 OR e.txid IN rarray(:txids))
+        -- The same fallback order as `z_listtransactions`, reversed.
+        ORDER BY COALESCE(
+                v.mined_height,
+                CASE WHEN v.expiry_height == 0 THEN NULL ELSE v.expiry_height END
+            ) DESC NULLS FIRST,
+            t.tx_index DESC NULLS FIRST,
+            t.id_tx DESC,
+            a.id DESC,
+            e.is_receive DESC,
+            e.output_pool DESC,
+            e.output_index DESC
+        LIMIT :count
+        OFFSET :from"
+    ))?;
+
+    let entries = stmt
+        .query_and_then::<_, SqliteClientError, _, _>(
+            named_params! {
+                ":include_watchonly": include_watchonly,
+                ":watch_only_accounts": watch_only_param(watch_only),
+                ":since_height": since_height.map(u32::from),
+                ":filter_txids": txids.is_some(),
+                ":txids": Rc::new(
+                    txids
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|txid| Value::Blob(txid.as_ref().to_vec()))
+                        .collect::<Vec<_>>(),
+                ),
+                ":count": count,
+                ":from": from,
+            },
+            |row| {
+                let txid = TxId::from_bytes(row.get("txid")?);
+                let mined_height = row.get::<_, Option<u32>>("mined_height")?;
+
+                let entry = HistoryEntry {
+                    detail: HistoryDetail::from_row(row)?,
+                    confirmations: mined_height
+                        .map_or(0, |h| u32::from(chain_height + 1).saturating_sub(h)),
+                    blockhash: row
+                        .get::<_, Option<[u8; 32]>>("block_hash")?
+                        .map(|hash| BlockHash(hash).to_string()),
+                    blockheight: mined_height,
+                    blocktime: row.get("block_time")?,
+                    txid: txid.to_string(),
+                    // Filled in once the query is complete.
+                    walletconflicts: vec![],
+                };
+                Ok((txid, entry))
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Find the conflicts of every listed transaction at once, rather than per entry.
+    let txids = entries.iter().map(|(txid, _)| *txid).collect::<Vec<_>>();
+    let conflicts = wallet_conflicts(conn, &txids)?;
+
+    // Return the entries newest-last, as `zcashd` does.
+    Ok(entries
+        .into_iter()
+        .rev()
+        .map(|(txid, mut entry)| {
+            if let Some(conflicts) = conflicts.get(&txid) {
+                entry.walletconflicts = conflicts.iter().map(|txid| txid.to_string()).collect();
+            }
+            entry
+        })
+        .collect())
+}
+
+pub(crate) fn call(
+    wallet: &DbConnection,
+    dummy: Option<String>,
+    count: Option<u32>,
+    from: Option<u32>,
+    include_watchonly: Option<bool>,
+) -> Response {
+    if dummy.is_some_and(|dummy| dummy != "*" && !dummy.is_empty()) {
+        return Err(LegacyCode::InvalidParameter
+            .with_static("dummy first argument must be excluded or set to \"*\" or \"\"."));
+    }
+
+    let chain_height = wallet
+        .chain_height()
+        .map_err(ComponentFailure::database)?
+        .ok_or_else(|| LegacyCode::InWarmup.with_static("Wait for the wallet to start up"))?;
+
+    let include_watchonly = include_watchonly.unwrap_or(INCLUDE_WATCHONLY_DEFAULT);
+    let watch_only = watch_only_accounts(wallet)?;
+
+    let entries = wallet.with_raw_mut(|conn, _| {
+        let db_tx = conn.transaction().map_err(ComponentFailure::database)?;
+
+        query_entries(
+            &db_tx,
+            Selection::Recent {
+                count: count.unwrap_or(DEFAULT_COUNT),
+                from: from.unwrap_or(0),
+            },
+            chain_height,
+            include_watchonly,
+            &watch_only,
+        )
+        .map_err(ComponentFailure::database)
+    })?;
+
+    Ok(ResultType(entries))
+}
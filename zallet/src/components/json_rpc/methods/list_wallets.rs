@@ -0,0 +1,28 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::components::wallets::Wallets;
+
+use super::MethodCategory;
+
+/// Response to a `listwallets` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The names of the loaded wallets, in the order they were loaded.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(Vec<String>);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(crate) fn call(wallets: &Wallets) -> Response {
+    Ok(ResultType(
+        wallets
+            .loaded()
+            .iter()
+            .map(|wallet| wallet.name().into())
+            .collect(),
+    ))
+}
@@ -0,0 +1,35 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::components::wallets::Wallets;
+
+use super::MethodCategory;
+
+/// Response to a `loadwallet` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = LoadedWallet;
+
+/// The wallet that was loaded.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct LoadedWallet {
+    /// The name of the wallet.
+    name: String,
+
+    /// Warning messages about loading the wallet, if any.
+    warning: String,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_WALLET_NAME_DESC: &str = "The name of the wallet.";
+
+pub(crate) async fn call(wallets: &Wallets, wallet_name: &str) -> Response {
+    wallets.load(wallet_name).await?;
+
+    Ok(LoadedWallet {
+        name: wallet_name.into(),
+        warning: String::new(),
+    })
+}
@@ -3,7 +3,13 @@ use jsonrpsee::core::RpcResult;
 use schemars::JsonSchema;
 use serde::Serialize;
 
-use crate::components::{json_rpc::server::LegacyCode, keystore::KeyStore};
+use crate::components::{
+    audit::{self, Event},
+    json_rpc::server::LegacyCode,
+    keystore::KeyStore,
+};
+
+use super::MethodCategory;
 
 /// Response to a `walletlock` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
@@ -13,6 +19,8 @@ pub(crate) type Response = RpcResult<ResultType>;
 #[serde(transparent)]
 pub(crate) struct ResultType(());
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
 pub(crate) async fn call(keystore: &KeyStore) -> Response {
     if !keystore.uses_encrypted_identities() {
         return Err(LegacyCode::WalletWrongEncState
@@ -20,6 +28,7 @@ pub(crate) async fn call(keystore: &KeyStore) -> Response {
     }
 
     keystore.lock().await;
+    audit::record(Event::WalletLocked);
 
     Ok(ResultType(()))
 }
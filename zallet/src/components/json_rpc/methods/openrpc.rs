@@ -5,6 +5,8 @@ use jsonrpsee::core::{JsonValue, RpcResult};
 use schemars::{JsonSchema, Schema, SchemaGenerator, generate::SchemaSettings};
 use serde::Serialize;
 
+use super::MethodCategory;
+
 // Imports to work around deficiencies in the build script.
 #[cfg(zallet_build = "wallet")]
 use super::{super::asyncop::OperationId, recover_accounts, z_send_many};
@@ -16,6 +18,8 @@ include!(concat!(env!("OUT_DIR"), "/rpc_openrpc.rs"));
 pub(crate) type Response = RpcResult<ResultType>;
 pub(crate) type ResultType = OpenRpc;
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(crate) fn call() -> Response {
     let mut generator = Generator::new();
 
@@ -39,6 +43,7 @@ pub(crate) fn call() -> Response {
 /// Static information about a Zallet JSON-RPC method.
 pub(super) struct RpcMethod {
     pub(super) description: &'static str,
+    pub(super) category: MethodCategory,
     params: fn(&mut Generator) -> Vec<ContentDescriptor>,
     result: fn(&mut Generator) -> ContentDescriptor,
     deprecated: bool,
@@ -182,3 +187,32 @@ struct Components {
 fn is_false(b: &bool) -> bool {
     !b
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{METHODS, call};
+
+    #[test]
+    fn every_method_is_documented() {
+        // The server registers every method declared with a `#[method]` attribute in the
+        // RPC traits, so check that none were skipped when the document was generated.
+        let registered = include_str!("../methods.rs")
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("#[method(name = \""))
+            .map(|rest| rest.split_once('"').expect("valid attribute").0)
+            .collect::<BTreeSet<_>>();
+        assert!(registered.contains("rpc.discover"));
+
+        let document = call().unwrap();
+        let documented = document
+            .methods
+            .iter()
+            .map(|method| method.name)
+            .collect::<BTreeSet<_>>();
+
+        assert_eq!(documented, registered);
+        assert_eq!(METHODS.len(), registered.len());
+    }
+}
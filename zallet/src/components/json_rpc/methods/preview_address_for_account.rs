@@ -0,0 +1,107 @@
+use jsonrpsee::core::{JsonValue, RpcResult};
+use zcash_client_backend::{
+    data_api::{Account as _, WalletRead},
+    keys::AddressGenerationError,
+};
+use zip32::DiversifierIndex;
+
+use crate::components::{
+    database::DbConnection,
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::parse_account_parameter,
+    },
+};
+
+use super::{
+    MethodCategory,
+    get_address_for_account::{Address, map_address_generation_error, parse_request},
+};
+
+#[cfg(zallet_build = "wallet")]
+use crate::components::keystore::KeyStore;
+
+/// Response to a `z_previewaddressforaccount` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = Address;
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_ACCOUNT_DESC: &str =
+    "The UUID, name, or ZIP 32 account index of the account to preview an address of.";
+pub(super) const PARAM_RECEIVER_TYPES_DESC: &str =
+    "Receiver types to include in the previewed address.";
+
+pub(crate) async fn call(
+    wallet: &DbConnection,
+    #[cfg(zallet_build = "wallet")] keystore: KeyStore,
+    account: JsonValue,
+    receiver_types: Option<Vec<String>>,
+) -> Response {
+    let account_id = parse_account_parameter(
+        wallet,
+        #[cfg(zallet_build = "wallet")]
+        &keystore,
+        &account,
+    )
+    .await?;
+
+    let (receiver_types, request) = parse_request(receiver_types.as_deref())?;
+    let transparent = receiver_types
+        .iter()
+        .any(|receiver_type| receiver_type == "p2pkh");
+
+    let uivk = wallet
+        .get_account(account_id)
+        .map_err(ComponentFailure::database)?
+        // This would be a race condition between this and account deletion.
+        .ok_or_else(|| ComponentFailure::internal("Account was deleted during the request"))?
+        .uivk();
+
+    let (address, diversifier_index) = if transparent {
+        // The address is derived at a transparent address index, which may not be a valid
+        // Sapling diversifier index; such indices are skipped.
+        let mut found = None;
+        for index in wallet
+            .unexposed_transparent_indices(account_id)
+            .map_err(ComponentFailure::database)?
+        {
+            let diversifier_index = DiversifierIndex::from(index);
+            match uivk.address(diversifier_index, request) {
+                Ok(address) => {
+                    found = Some((address, diversifier_index));
+                    break;
+                }
+                Err(AddressGenerationError::InvalidSaplingDiversifierIndex(_)) => (),
+                Err(e) => return Err(map_address_generation_error(e, &account)),
+            }
+        }
+
+        match found {
+            Some(found) => found,
+            None => {
+                // Report a missing key in preference to the gap limit.
+                uivk.find_address(DiversifierIndex::new(), request)
+                    .map_err(|e| map_address_generation_error(e, &account))?;
+                return Err(LegacyCode::Wallet.with_static(
+                    "Error: reached the transparent gap limit. Wait for an address of this account to receive funds, or omit the \"p2pkh\" receiver type.",
+                ));
+            }
+        }
+    } else {
+        wallet
+            .first_unexposed_diversifier_index(account_id)
+            .map_err(ComponentFailure::database)?
+            .ok_or(AddressGenerationError::DiversifierSpaceExhausted)
+            .and_then(|start| uivk.find_address(start, request))
+            .map_err(|e| map_address_generation_error(e, &account))?
+    };
+
+    Ok(Address::new(
+        wallet,
+        account_id,
+        &account,
+        &address,
+        diversifier_index,
+    ))
+}
@@ -1,10 +1,7 @@
 use std::collections::HashMap;
 
 use documented::Documented;
-use jsonrpsee::{
-    core::RpcResult,
-    types::{ErrorCode as RpcErrorCode, ErrorObjectOwned},
-};
+use jsonrpsee::{core::RpcResult, types::ErrorObjectOwned};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use zaino_state::FetchServiceSubscriber;
@@ -15,14 +12,17 @@ use zcash_client_backend::{
 use zcash_protocol::consensus::{BlockHeight, NetworkType, Parameters};
 
 use crate::components::{
+    audit::{self, Event},
     database::DbConnection,
     json_rpc::{
-        server::LegacyCode,
+        server::{ComponentFailure, LegacyCode},
         utils::{ensure_wallet_is_unlocked, parse_seedfp_parameter},
     },
     keystore::KeyStore,
 };
 
+use super::MethodCategory;
+
 /// Response to a `z_recoveraccounts` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 pub(crate) type ResultType = Accounts;
@@ -52,6 +52,8 @@ struct Account {
     zip32_account_index: u32,
 }
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
 pub(super) const PARAM_ACCOUNTS_DESC: &str =
     "An array of JSON objects representing the accounts to recover.";
 pub(super) const PARAM_ACCOUNTS_REQUIRED: bool = true;
@@ -62,13 +64,13 @@ pub(crate) async fn call(
     chain: FetchServiceSubscriber,
     accounts: Vec<AccountParameter<'_>>,
 ) -> Response {
-    ensure_wallet_is_unlocked(keystore).await?;
+    let _unlocked = ensure_wallet_is_unlocked(keystore).await?;
     // TODO: Ensure wallet is backed up.
     //       https://github.com/zcash/wallet/issues/201
 
     let recover_until = wallet
         .chain_height()
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+        .map_err(ComponentFailure::database)?
         .ok_or(LegacyCode::InWarmup.with_static("Wallet sync required"))?;
 
     // Prepare arguments for the wallet.
@@ -102,7 +104,8 @@ pub(crate) async fn call(
                     NetworkType::Test => "test".into(),
                     NetworkType::Regtest => "regtest".into(),
                 },
-                height: u64::try_from(treestate.height).map_err(|_| RpcErrorCode::InternalError)?,
+                height: u64::try_from(treestate.height)
+                    .map_err(|_| ComponentFailure::chain("Invalid treestate height"))?,
                 hash: treestate.hash,
                 time: treestate.time,
                 sapling_tree: treestate
@@ -123,7 +126,7 @@ pub(crate) async fn call(
         };
 
         let birthday = AccountBirthday::from_treestate(treestate, Some(recover_until))
-            .map_err(|_| RpcErrorCode::InternalError)?;
+            .map_err(|_| ComponentFailure::chain("Invalid treestate"))?;
 
         account_args.push((account.name, seed_fp, account_index, birthday));
     }
@@ -135,7 +138,7 @@ pub(crate) async fn call(
             let seed = keystore
                 .decrypt_seed(seed_fp)
                 .await
-                .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
+                .map_err(ComponentFailure::keystore)?;
 
             seeds.insert(*seed_fp, seed);
         }
@@ -149,7 +152,12 @@ pub(crate) async fn call(
 
             let (account, _usk) = wallet
                 .import_account_hd(account_name, seed, account_index, &birthday, None)
-                .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
+                .map_err(ComponentFailure::database)?;
+
+            audit::record(Event::AccountCreated {
+                account_uuid: account.id().expose_uuid().to_string(),
+                seed_fingerprint: seed_fp.to_string(),
+            });
 
             Ok::<_, ErrorObjectOwned>(Account {
                 account_uuid: account.id().expose_uuid().to_string(),
@@ -0,0 +1,64 @@
+use documented::Documented;
+use jsonrpsee::core::{JsonValue, RpcResult};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::components::{
+    database::DbConnection,
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::{check_account_name, parse_account_parameter},
+    },
+    keystore::KeyStore,
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_renameaccount` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// Empty result indicating success.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(());
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_ACCOUNT_DESC: &str =
+    "The UUID, name, or ZIP 32 account index of the account to rename.";
+pub(super) const PARAM_NAME_DESC: &str = "The new name for the account.";
+pub(super) const PARAM_STRICT_UNIQUE_DESC: &str =
+    "If true, fail if another account already has the name.";
+
+pub(crate) async fn call(
+    wallet: &DbConnection,
+    keystore: &KeyStore,
+    account: JsonValue,
+    name: &str,
+    strict_unique: Option<bool>,
+) -> Response {
+    let account_id = parse_account_parameter(wallet, keystore, &account).await?;
+
+    check_account_name(
+        name,
+        Some(account_id),
+        strict_unique.unwrap_or(false),
+        || {
+            wallet
+                .indexed_accounts()
+                .map_err(|e| ComponentFailure::database(e).into())
+        },
+    )?;
+
+    if wallet
+        .rename_account(account_id, name)
+        .map_err(ComponentFailure::database)?
+    {
+        Ok(ResultType(()))
+    } else {
+        Err(LegacyCode::InvalidParameter.with_message(format!(
+            "Invalid account: no account has UUID {}.",
+            account_id.expose_uuid(),
+        )))
+    }
+}
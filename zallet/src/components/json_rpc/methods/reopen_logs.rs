@@ -0,0 +1,25 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::components::{json_rpc::server::LegacyCode, tracing::reopen_log_file};
+
+use super::MethodCategory;
+
+/// Response to a `reopenlogs` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// Empty result indicating success.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(());
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(crate) fn call() -> Response {
+    match reopen_log_file() {
+        Ok(_) => Ok(ResultType(())),
+        Err(e) => Err(LegacyCode::Misc.with_message(format!("Failed to reopen log file: {e}"))),
+    }
+}
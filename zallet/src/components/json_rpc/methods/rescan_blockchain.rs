@@ -0,0 +1,62 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_protocol::consensus::BlockHeight;
+
+use crate::components::{
+    database::DbConnection,
+    json_rpc::server::{ComponentFailure, LegacyCode},
+    sync::{RescanError, SyncStatus},
+};
+
+use super::MethodCategory;
+
+/// Response to a `rescanblockchain` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = RescanRange;
+
+/// The blocks that the wallet will rescan.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct RescanRange {
+    /// The height of the first block that will be rescanned.
+    start_height: u32,
+
+    /// The height of the last block that will be rescanned.
+    stop_height: u32,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_START_HEIGHT_DESC: &str =
+    "The height of the first block to rescan. Defaults to the wallet's birthday.";
+pub(super) const PARAM_STOP_HEIGHT_DESC: &str =
+    "The height of the last block to rescan. Defaults to 100 blocks below the chain tip.";
+
+pub(crate) fn call(
+    wallet: &DbConnection,
+    sync_status: &SyncStatus,
+    start_height: Option<u32>,
+    stop_height: Option<u32>,
+) -> Response {
+    let heights = sync_status
+        .schedule_rescan(
+            wallet,
+            start_height.map(BlockHeight::from_u32),
+            stop_height.map(BlockHeight::from_u32),
+        )
+        .map_err(|e| match e {
+            RescanError::InProgress(_) | RescanError::NoAccounts => {
+                LegacyCode::Wallet.with_message(e.to_string())
+            }
+            RescanError::EmptyRange { .. } => {
+                LegacyCode::InvalidParameter.with_message(e.to_string())
+            }
+            RescanError::Database(e) => ComponentFailure::database(e).into(),
+        })?;
+
+    Ok(RescanRange {
+        start_height: (*heights.start()).into(),
+        stop_height: (*heights.end()).into(),
+    })
+}
@@ -0,0 +1,166 @@
+use abscissa_core::Application;
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use zaino_state::FetchServiceSubscriber;
+use zcash_client_backend::data_api::WalletRead;
+
+use crate::{
+    components::{
+        database::DbConnection,
+        json_rpc::{
+            payments::Broadcaster,
+            server::{ComponentFailure, LegacyCode},
+        },
+    },
+    prelude::*,
+};
+
+use super::MethodCategory;
+
+/// Response to a `resendwallettransactions` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The IDs of the transactions that were broadcast.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(Vec<String>);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(crate) async fn call(wallet: &DbConnection, chain: FetchServiceSubscriber) -> Response {
+    if !APP.config().external.broadcast() {
+        return Err(LegacyCode::Wallet.with_static(
+            "Error: Wallet transaction broadcasting is disabled with external.broadcast",
+        ));
+    }
+
+    resend(wallet, chain).await
+}
+
+/// Broadcasts the transactions that the wallet created without broadcasting, unless
+/// they can no longer be mined.
+async fn resend(wallet: &DbConnection, chain: impl Broadcaster) -> Response {
+    let next_height = wallet
+        .chain_height()
+        .map_err(ComponentFailure::database)?
+        .ok_or_else(|| LegacyCode::InWarmup.with_static("Wallet sync required"))?
+        + 1;
+
+    let mut txids = vec![];
+
+    for txid in wallet
+        .unbroadcast_transactions()
+        .map_err(ComponentFailure::database)?
+    {
+        let tx = wallet
+            .get_transaction(txid)
+            .map_err(ComponentFailure::database)?;
+        let mined = wallet
+            .get_tx_height(txid)
+            .map_err(ComponentFailure::database)?
+            .is_some();
+
+        // Skip transactions that were mined after being submitted by other means, or
+        // that can no longer be mined.
+        let pending = tx.filter(|tx| {
+            !mined && (u32::from(tx.expiry_height()) == 0 || tx.expiry_height() >= next_height)
+        });
+
+        if let Some(tx) = pending {
+            let mut tx_bytes = vec![];
+            tx.write(&mut tx_bytes)
+                .map_err(|e| LegacyCode::OutOfMemory.with_message(e.to_string()))?;
+
+            chain.broadcast(hex::encode(&tx_bytes)).await?;
+
+            txids.push(txid.to_string());
+        }
+
+        wallet
+            .mark_broadcast(&txid)
+            .map_err(ComponentFailure::database)?;
+    }
+
+    Ok(ResultType(txids))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use jsonrpsee::core::RpcResult;
+    use serde_json::json;
+    use transparent::bundle::OutPoint;
+    use zcash_client_backend::data_api::{WalletWrite, wallet::decrypt_and_store_transaction};
+    use zcash_protocol::consensus::BlockHeight;
+
+    use crate::components::{
+        database::testing::{create_account, receive_utxo, spend_to},
+        json_rpc::payments::{Broadcaster, broadcast_transactions},
+    };
+
+    use super::{super::testing::TestWallet, resend};
+
+    /// A network that records the transactions broadcast to it.
+    #[derive(Clone, Default)]
+    struct TestNetwork(Arc<Mutex<Vec<String>>>);
+
+    impl TestNetwork {
+        fn sent(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl Broadcaster for TestNetwork {
+        async fn broadcast(&self, raw_transaction_hex: String) -> RpcResult<()> {
+            self.0.lock().unwrap().push(raw_transaction_hex);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unbroadcast_transactions_are_resent() {
+        let wallet = TestWallet::new().await;
+        let mut handle = wallet.handle().await;
+        let params = *handle.params();
+        let (external, internal) = create_account(&mut handle);
+        handle
+            .update_chain_tip(BlockHeight::from_u32(1_000_099))
+            .unwrap();
+
+        let spent = OutPoint::new([1; 32], 0);
+        receive_utxo(&mut handle, &spent, &external, 1_000_000);
+        let tx = spend_to(&spent, &internal, 90_000, 2_000_000);
+        decrypt_and_store_transaction(&params, &mut *handle, &tx, None).unwrap();
+        let txid = tx.txid();
+        let mut raw_tx = vec![];
+        tx.write(&mut raw_tx).unwrap();
+        let raw_tx = hex::encode(raw_tx);
+
+        // With broadcasting disabled, the transaction is returned instead of being sent.
+        let network = TestNetwork::default();
+        let result = broadcast_transactions(&handle, network.clone(), vec![txid], false)
+            .await
+            .unwrap();
+        let result = serde_json::to_value(result).unwrap();
+        assert_eq!(result["txid"], txid.to_string());
+        assert_eq!(result["hex"], json!([raw_tx]));
+        assert!(network.sent().is_empty());
+        assert_eq!(handle.unbroadcast_transactions().unwrap(), vec![txid]);
+
+        // `resendwallettransactions` broadcasts it later, and only once.
+        let resent = resend(&handle, network.clone()).await.unwrap();
+        assert_eq!(
+            serde_json::to_value(resent).unwrap(),
+            json!([txid.to_string()])
+        );
+        assert_eq!(network.sent(), vec![raw_tx]);
+        assert!(handle.unbroadcast_transactions().unwrap().is_empty());
+
+        let resent = resend(&handle, network.clone()).await.unwrap();
+        assert_eq!(serde_json::to_value(resent).unwrap(), json!([]));
+        assert_eq!(network.sent().len(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+use documented::Documented;
+use jsonrpsee::core::{JsonValue, RpcResult};
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_protocol::{ShieldedProtocol, consensus::BlockHeight};
+
+use crate::{
+    components::{
+        database::DbConnection,
+        json_rpc::{
+            pagination::Position,
+            server::{ComponentFailure, LegacyCode},
+            utils::{JsonZec, parse_account_parameter, zec_and_zat},
+        },
+        keystore::KeyStore,
+    },
+    prelude::*,
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_searchmemos` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The received notes whose memos contain the query.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(Vec<MemoMatch>);
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct MemoMatch {
+    /// The ID of the transaction that received the note.
+    txid: String,
+
+    /// The shielded value pool.
+    ///
+    /// One of `["sapling", "orchard"]`.
+    pool: String,
+
+    /// The Sapling output or Orchard action index.
+    outindex: u32,
+
+    /// The height at which the transaction was mined.
+    ///
+    /// Omitted if the transaction is unmined.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+
+    /// The UUID of the wallet account that received the note.
+    account_uuid: String,
+
+    /// The Zcash address that received the note.
+    ///
+    /// Omitted if the note was received on an account-internal address.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+
+    /// The value of the note in ZEC.
+    value: JsonZec,
+
+    /// The value of the note in zatoshis.
+    #[serde(rename = "valueZat")]
+    value_zat: u64,
+
+    /// The text of the note's memo.
+    #[serde(rename = "memoStr")]
+    memo_str: String,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_QUERY_DESC: &str = "The text to search for.";
+pub(super) const PARAM_ACCOUNT_DESC: &str =
+    "Only search the memos received by this account (UUID, name, or ZIP 32 account index).";
+pub(super) const PARAM_FROM_HEIGHT_DESC: &str =
+    "Only search the memos of transactions mined at or above this height.";
+pub(super) const PARAM_TO_HEIGHT_DESC: &str =
+    "Only search the memos of transactions mined at or below this height.";
+
+pub(crate) async fn call(
+    wallet: &DbConnection,
+    keystore: &KeyStore,
+    query: String,
+    account: Option<JsonValue>,
+    from_height: Option<u32>,
+    to_height: Option<u32>,
+) -> Response {
+    if !APP.config().database.memo_index() {
+        return Err(LegacyCode::Misc
+            .with_static("The memo index is disabled (database.memo_index = false)"));
+    }
+    if query.is_empty() {
+        return Err(LegacyCode::InvalidParameter.with_static("query must not be empty"));
+    }
+    if from_height
+        .zip(to_height)
+        .is_some_and(|(from, to)| from > to)
+    {
+        return Err(LegacyCode::InvalidParameter
+            .with_static("from_height must not be greater than to_height"));
+    }
+
+    let account_uuid = match &account {
+        Some(account) => Some(
+            parse_account_parameter(wallet, keystore, account)
+                .await?
+                .expose_uuid(),
+        ),
+        None => None,
+    };
+
+    // Pick up the memos of any notes that the sync tasks have not yet indexed.
+    wallet.index_memos().map_err(ComponentFailure::database)?;
+
+    let max = APP.config().rpc.max_list_results();
+    let mut matches = wallet
+        .search_memos(
+            &query,
+            account_uuid,
+            from_height.map(BlockHeight::from_u32),
+            to_height.map(BlockHeight::from_u32),
+            max.saturating_add(1),
+        )
+        .map_err(ComponentFailure::database)?;
+    if matches.len() > max as usize {
+        return Err(LegacyCode::InvalidParameter.with_message(format!(
+            "More than {max} results (rpc.max_list_results); narrow the search with the account or height parameters"
+        )));
+    }
+
+    // Each pool's matches are ordered; interleave them in the order of the wallet's
+    // history.
+    matches.sort_by_key(|m| {
+        Position::new(
+            m.mined_height.map(u32::from),
+            m.tx_index,
+            m.tx_row,
+            u64::from(m.output_index),
+        )
+    });
+
+    Ok(ResultType(
+        matches
+            .into_iter()
+            .map(|m| {
+                let (value, value_zat) = zec_and_zat(m.value);
+                MemoMatch {
+                    txid: m.txid.to_string(),
+                    pool: match m.pool {
+                        ShieldedProtocol::Sapling => "sapling",
+                        ShieldedProtocol::Orchard => "orchard",
+                    }
+                    .into(),
+                    outindex: m.output_index,
+                    height: m.mined_height.map(u32::from),
+                    account_uuid: m.account_uuid.to_string(),
+                    address: m.address,
+                    value,
+                    value_zat,
+                    memo_str: m.memo,
+                }
+            })
+            .collect(),
+    ))
+}
@@ -0,0 +1,183 @@
+use base64ct::{Base64, Encoding};
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use serde::Serialize;
+use transparent::address::TransparentAddress;
+use zcash_client_backend::{data_api::WalletRead, wallet::TransparentAddressMetadata};
+use zcash_client_sqlite::wallet::Account;
+use zcash_keys::encoding::AddressCodec;
+
+use crate::components::{
+    database::DbConnection,
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::{ensure_wallet_is_unlocked, is_watch_only},
+    },
+    keystore::KeyStore,
+};
+
+use super::{MethodCategory, verify_message::message_hash, z_send_many::account_spending_key};
+
+/// Response to a `signmessage` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The signature of the message, in base64 encoding.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(String);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_T_ADDR_DESC: &str =
+    "The transparent P2PKH address whose private key signs the message.";
+pub(super) const PARAM_MESSAGE_DESC: &str = "The message to sign.";
+
+/// Signs a message with the private key of a transparent address held by the wallet.
+///
+/// The signature can be checked with `verifymessage`.
+pub(crate) async fn call(
+    wallet: &DbConnection,
+    keystore: &KeyStore,
+    t_addr: &str,
+    message: &str,
+) -> Response {
+    let params = wallet.params();
+    let address = TransparentAddress::decode(params, t_addr)
+        .map_err(|_| LegacyCode::Type.with_static("Invalid address"))?;
+
+    if matches!(address, TransparentAddress::ScriptHash(_)) {
+        return Err(LegacyCode::Type.with_static("Address does not refer to key"));
+    }
+
+    let not_held = || LegacyCode::Wallet.with_static("Private key not available");
+
+    let (account, metadata) = find_address(wallet, &address)?.ok_or_else(not_held)?;
+
+    if is_watch_only(&account) {
+        return Err(LegacyCode::Wallet
+            .with_static("Private key not available: address belongs to a watch-only account"));
+    }
+
+    let _unlocked = ensure_wallet_is_unlocked(keystore).await?;
+
+    let secret_key = match metadata.scope().zip(metadata.address_index()) {
+        Some((scope, address_index)) => account_spending_key(keystore, params, &account)
+            .await?
+            .and_then(|usk| {
+                usk.transparent()
+                    .derive_secret_key(scope, address_index)
+                    .ok()
+            }),
+        #[cfg(feature = "transparent-key-import")]
+        None => {
+            let secp = Secp256k1::signing_only();
+            keystore
+                .decrypt_standalone_transparent_keys()
+                .await
+                .map_err(ComponentFailure::keystore)?
+                .into_iter()
+                .find(|key| {
+                    TransparentAddress::from_pubkey(&PublicKey::from_secret_key(&secp, key))
+                        == address
+                })
+        }
+        #[cfg(not(feature = "transparent-key-import"))]
+        None => None,
+    }
+    .ok_or_else(not_held)?;
+
+    // Keys derived from legacy `zcashd` HD paths do not match the wallet's addresses.
+    let pubkey = PublicKey::from_secret_key(&Secp256k1::signing_only(), &secret_key);
+    if TransparentAddress::from_pubkey(&pubkey) != address {
+        return Err(not_held());
+    }
+
+    Ok(ResultType(sign(&secret_key, message)))
+}
+
+/// Returns the account that holds the given address, along with the address's
+/// metadata.
+fn find_address(
+    wallet: &DbConnection,
+    address: &TransparentAddress,
+) -> RpcResult<Option<(Account, TransparentAddressMetadata)>> {
+    for account_id in wallet
+        .get_account_ids()
+        .map_err(ComponentFailure::database)?
+    {
+        if let Some(metadata) = wallet
+            .get_transparent_address_metadata(account_id, address)
+            .map_err(ComponentFailure::database)?
+        {
+            let account = wallet
+                .get_account(account_id)
+                .map_err(ComponentFailure::database)?
+                // This would be a race condition between this and account deletion.
+                .ok_or_else(|| {
+                    ComponentFailure::internal("Account was deleted during the request")
+                })?;
+            return Ok(Some((account, metadata)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Signs `message` in the format checked by `verifymessage`, matching `zcashd`.
+///
+/// The signature is a compact recoverable signature, with a header byte of 31 to 34 to
+/// indicate a compressed public key and the recovery ID.
+fn sign(secret_key: &SecretKey, message: &str) -> String {
+    let msg = Message::from_digest(message_hash(message));
+    let (recovery_id, signature) = Secp256k1::signing_only()
+        .sign_ecdsa_recoverable(&msg, secret_key)
+        .serialize_compact();
+
+    let mut sig_bytes = Vec::with_capacity(65);
+    sig_bytes.push(27 + 4 + recovery_id.to_i32() as u8);
+    sig_bytes.extend_from_slice(&signature);
+    Base64::encode_string(&sig_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+    use serde_json::json;
+    use transparent::address::TransparentAddress;
+    use zcash_keys::encoding::AddressCodec;
+    use zcash_protocol::consensus;
+
+    use super::{super::verify_message, sign};
+    use crate::network::Network;
+
+    #[test]
+    fn sign_and_verify() {
+        let params = Network::Consensus(consensus::Network::MainNetwork);
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let address = TransparentAddress::from_pubkey(&PublicKey::from_secret_key(
+            &Secp256k1::signing_only(),
+            &secret_key,
+        ))
+        .encode(&params);
+        let message = "Zallet test message";
+
+        // Signatures are deterministic, so this must not change unless the signing
+        // scheme does, which would break verification by other tools.
+        let signature = sign(&secret_key, message);
+        assert_eq!(
+            signature,
+            "H3PNW3SSi81vxPRIsKj5uhBJogeqd0yzrmnMuN9i80MCC3n/aZPbiivpuv9KT77iZQUZR+GMsJ2l+3/UIOwfuEo=",
+        );
+
+        let verify = |message| {
+            serde_json::to_value(
+                verify_message::call(&params, &address, &signature, message).unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(verify(message), json!(true));
+        assert_eq!(verify("Another message"), json!(false));
+    }
+}
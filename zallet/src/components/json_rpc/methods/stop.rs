@@ -1,31 +1,100 @@
 use documented::Documented;
-use jsonrpsee::{core::RpcResult, types::ErrorCode as RpcErrorCode};
+use jsonrpsee::core::RpcResult;
 use schemars::JsonSchema;
 use serde::Serialize;
-use zcash_protocol::consensus::{NetworkType, Parameters};
 
-use crate::components::database::DbHandle;
+use crate::shutdown;
+
+use super::MethodCategory;
 
 /// Response to a `stop` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 
-/// The stop response.
+/// An acknowledgement that Zallet is stopping.
 #[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
 #[serde(transparent)]
-pub(crate) struct ResultType(());
-
-pub(crate) fn call(wallet: DbHandle) -> Response {
-    #[cfg(not(target_os = "windows"))]
-    match wallet.params().network_type() {
-        NetworkType::Regtest => match nix::sys::signal::raise(nix::sys::signal::SIGINT) {
-            Ok(_) => Ok(ResultType(())),
-            Err(_) => Err(RpcErrorCode::InternalError.into()),
-        },
-        _ => Err(RpcErrorCode::MethodNotFound.into()),
+pub(crate) struct ResultType(&'static str);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(crate) fn call() -> Response {
+    // This is the same shutdown that an interrupt or termination signal requests. The
+    // RPC server lets in-flight requests finish, so this response is still sent.
+    shutdown::request();
+    Ok(ResultType("Zallet server stopping"))
+}
+
+#[cfg(test)]
+#[cfg(zallet_build = "wallet")]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::Value;
+    use tokio::sync::oneshot;
+
+    use crate::{
+        components::json_rpc::asyncop::{self, OperationId, OperationRegistry, OperationState},
+        shutdown,
+    };
+
+    use super::{super::testing::TestWallet, call};
+
+    async fn operation_status(
+        operations: &OperationRegistry,
+        id: &OperationId,
+    ) -> (OperationState, Value) {
+        let ops = operations.read().await;
+        let op = ops
+            .iter()
+            .find(|op| op.operation_id() == id)
+            .expect("operation is retained");
+        (
+            op.state().await,
+            serde_json::to_value(op.to_status().await).unwrap(),
+        )
     }
-    #[cfg(target_os = "windows")]
-    {
-        let _ = wallet;
-        Err(RpcErrorCode::MethodNotFound.into())
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stop_shuts_down_gracefully() {
+        let _stopping = shutdown::testing::allow_shutdown().await;
+        let wallet = TestWallet::new().await;
+        let operations = wallet.operations();
+
+        // An operation that runs until it reaches a checkpoint during a shutdown.
+        let (started_tx, started_rx) = oneshot::channel();
+        let running = operations
+            .start(None, async move {
+                let _ = started_tx.send(());
+                while asyncop::checkpoint().is_ok() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                asyncop::checkpoint()
+            })
+            .await;
+        started_rx.await.unwrap();
+
+        assert_eq!(
+            serde_json::to_value(call().unwrap()).unwrap(),
+            "Zallet server stopping",
+        );
+        assert!(shutdown::is_requested());
+
+        // Operations submitted once the shutdown has been requested are not started, so
+        // that they are resumed when Zallet next starts.
+        let queued = operations.start(None, async { Ok(()) }).await;
+
+        assert!(operations.drain(Duration::from_secs(10)).await);
+        let (state, status) = operation_status(&operations, &running).await;
+        assert_eq!(state, OperationState::Failed);
+        assert_eq!(
+            status["error"]["message"],
+            "Operation interrupted because Zallet is shutting down",
+        );
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            operation_status(&operations, &queued).await.0,
+            OperationState::Ready
+        );
     }
 }
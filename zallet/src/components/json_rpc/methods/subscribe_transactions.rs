@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use jsonrpsee::{
+    PendingSubscriptionSink, SubscriptionMessage,
+    core::SubscriptionResult,
+    tracing::{debug, warn},
+};
+use serde::Serialize;
+use tokio::{
+    select,
+    sync::broadcast::{Receiver, error::RecvError},
+};
+use zcash_protocol::{PoolType, ShieldedProtocol, value::ZatBalance};
+
+use crate::components::{
+    json_rpc::utils::{JsonZecBalance, zec_and_zat_balance},
+    sync::{TransactionEvent, TransactionEventKind},
+};
+
+/// A `transaction` notification, sent when a wallet transaction changes.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Notification {
+    /// What happened to the transaction: `detected`, `mined`, or `conflicted`.
+    event: &'static str,
+    /// The transaction ID.
+    txid: String,
+    /// The height of the block that the transaction was mined in, if it is mined.
+    blockheight: Option<u32>,
+    /// The number of confirmations that the transaction has, or 0 if it is unmined.
+    confirmations: u32,
+    /// The accounts that the transaction involves.
+    accounts: Vec<AccountChange>,
+    /// The pools of the wallet outputs that the transaction creates or spends, along
+    /// with the pools of any outputs that the wallet sent.
+    pools: Vec<&'static str>,
+}
+
+/// The effect of a transaction on one of the wallet's accounts.
+#[derive(Clone, Debug, Serialize)]
+struct AccountChange {
+    /// The UUID of the account.
+    account_uuid: String,
+    /// The change to the account's balance, in ZEC.
+    account_balance_delta: JsonZecBalance,
+    /// The change to the account's balance, in zatoshis.
+    #[serde(rename = "account_balance_deltaZat")]
+    account_balance_delta_zat: i64,
+}
+
+impl Notification {
+    fn from_event(event: &TransactionEvent) -> Result<Self, String> {
+        Ok(Self {
+            event: match event.kind {
+                TransactionEventKind::Detected => "detected",
+                TransactionEventKind::Mined => "mined",
+                TransactionEventKind::Conflicted => "conflicted",
+            },
+            txid: event.txid.to_string(),
+            blockheight: event.effects.mined_height.map(u32::from),
+            confirmations: event.confirmations,
+            accounts: event
+                .effects
+                .accounts
+                .iter()
+                .map(|(account_uuid, delta)| {
+                    let (account_balance_delta, account_balance_delta_zat) = zec_and_zat_balance(
+                        ZatBalance::from_i64(*delta)
+                            .map_err(|e| format!("Invalid balance delta {delta}: {e:?}"))?,
+                    );
+                    Ok(AccountChange {
+                        account_uuid: account_uuid.to_string(),
+                        account_balance_delta,
+                        account_balance_delta_zat,
+                    })
+                })
+                .collect::<Result<_, String>>()?,
+            pools: event
+                .effects
+                .pools
+                .iter()
+                .map(|pool| match pool {
+                    PoolType::Transparent => "transparent",
+                    PoolType::Shielded(ShieldedProtocol::Sapling) => "sapling",
+                    PoolType::Shielded(ShieldedProtocol::Orchard) => "orchard",
+                })
+                .collect(),
+        })
+    }
+}
+
+pub(crate) async fn call(
+    pending: PendingSubscriptionSink,
+    mut events: Receiver<Arc<TransactionEvent>>,
+) -> SubscriptionResult {
+    let sink = pending.accept().await?;
+
+    loop {
+        let event = select! {
+            event = events.recv() => event,
+            _ = sink.closed() => break,
+        };
+
+        match event {
+            Ok(event) => match Notification::from_event(&event) {
+                Ok(notification) => {
+                    if sink
+                        .send(SubscriptionMessage::from_json(&notification)?)
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Err(e) => warn!("Not notifying transaction {}: {e}", event.txid),
+            },
+            // The subscriber fell too far behind, and the oldest events were dropped
+            // rather than holding up wallet sync.
+            Err(RecvError::Lagged(missed)) => {
+                debug!("Transaction subscriber missed {missed} events");
+                metrics::counter!("zallet.rpc.subscriptions.lagged").increment(missed);
+            }
+            // The wallet was unloaded.
+            Err(RecvError::Closed) => break,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+    use zcash_protocol::{PoolType, TxId, consensus::BlockHeight};
+
+    use super::Notification;
+    use crate::components::{
+        database::TxEffects,
+        sync::{TransactionEvent, TransactionEventKind},
+    };
+
+    #[test]
+    fn notification_format() {
+        let account = Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+        let event = TransactionEvent {
+            kind: TransactionEventKind::Mined,
+            txid: TxId::from_bytes([7; 32]),
+            confirmations: 3,
+            effects: TxEffects {
+                mined_height: Some(BlockHeight::from_u32(3_000_000)),
+                accounts: vec![(account, -150_010_000)],
+                pools: vec![PoolType::Transparent, PoolType::ORCHARD],
+            },
+        };
+
+        // ZEC values keep their 8 decimal places, so the expected JSON is parsed rather
+        // than constructed with `json!`.
+        let expected: serde_json::Value = serde_json::from_str(&format!(
+            r#"{{
+                "event": "mined",
+                "txid": "{}",
+                "blockheight": 3000000,
+                "confirmations": 3,
+                "accounts": [{{
+                    "account_uuid": "01234567-89ab-cdef-0123-456789abcdef",
+                    "account_balance_delta": -1.50010000,
+                    "account_balance_deltaZat": -150010000
+                }}],
+                "pools": ["transparent", "orchard"]
+            }}"#,
+            TxId::from_bytes([7; 32]),
+        ))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(Notification::from_event(&event).unwrap()).unwrap(),
+            expected,
+        );
+
+        // Balance deltas outside of the valid range are rejected.
+        let mut event = event;
+        event.effects.accounts[0].1 = i64::MAX;
+        assert!(Notification::from_event(&event).is_err());
+    }
+}
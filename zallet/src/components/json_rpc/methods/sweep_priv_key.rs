@@ -0,0 +1,1211 @@
+use std::collections::HashSet;
+use std::convert::Infallible;
+
+use abscissa_core::Application;
+use age::secrecy::{ExposeSecret, SecretString};
+use jsonrpsee::{core::RpcResult, types::ErrorObjectOwned};
+use rand::rngs::OsRng;
+use sapling::{
+    keys::{NullifierDerivingKey, PreparedIncomingViewingKey},
+    note_encryption::{CompactOutputDescription, try_sapling_compact_note_decryption},
+    zip32::ExtendedSpendingKey,
+};
+use serde::Serialize;
+use serde_json::json;
+use transparent::{
+    address::{Script, TransparentAddress},
+    builder::TransparentSigningSet,
+    bundle::{OutPoint, TxOut},
+};
+use zaino_state::{FetchServiceSubscriber, ZcashIndexer};
+use zcash_client_backend::{
+    data_api::{
+        Account as _, WalletRead,
+        scanning::{ScanPriority, ScanRange},
+    },
+    proto::compact_formats::CompactBlock,
+};
+use zcash_client_sqlite::AccountUuid;
+use zcash_keys::{
+    address::Address,
+    encoding::{AddressCodec, decode_extended_spending_key},
+    keys::UnifiedAddressRequest,
+    keys::transparent::Key,
+};
+use zcash_primitives::transaction::{
+    Transaction,
+    builder::{BuildConfig, Builder},
+    components::sapling::zip212_enforcement,
+};
+use zcash_proofs::prover::LocalTxProver;
+use zcash_protocol::{
+    PoolType, ShieldedProtocol,
+    consensus::{BlockHeight, BranchId, NetworkConstants, NetworkUpgrade, Parameters},
+    memo::MemoBytes,
+    value::Zatoshis,
+};
+use zcash_script::script;
+use zebra_rpc::methods::{AddressStrings, GetRawTransaction};
+
+use crate::{
+    components::{
+        database::{COINBASE_MATURITY, DbHandle},
+        json_rpc::{
+            asyncop::{self, ContextInfo, OperationId, OperationProgress},
+            batching::{BatchLimits, InputKind, MAX_TX_SIZE, plan_batches},
+            fees::{self, ActionCounts, DustThresholds, FeeLimit},
+            payments::pool_name,
+            send_error::SendFailure,
+            server::{ComponentFailure, LegacyCode},
+            utils::{JsonZec, value_from_zatoshis, zec_and_zat},
+        },
+        sync::{fetch_blocks, fetch_chain_state},
+    },
+    network::Network,
+    prelude::*,
+};
+
+use super::MethodCategory;
+
+/// Response to a `sweepprivkey` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = OperationId;
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_PRIVKEY_DESC: &str =
+    "A transparent private key in WIF format, or a Sapling extended spending key.";
+pub(super) const PARAM_DESTINATION_DESC: &str =
+    "The UUID of a wallet account, or an address, to which the funds will be sent.";
+pub(super) const PARAM_START_HEIGHT_DESC: &str =
+    "The height from which to scan the chain for notes spendable by a Sapling key.";
+
+/// The number of blocks, ending at the chain tip, that are scanned for notes spendable by
+/// a Sapling key if the caller does not provide a `start_height`.
+///
+/// This is around three months of blocks.
+const DEFAULT_SAPLING_SCAN_DEPTH: u32 = 100_000;
+
+/// The number of blocks that are fetched at a time while scanning for Sapling notes.
+const SAPLING_SCAN_BATCH_SIZE: u32 = 1_000;
+
+/// The result of sweeping a private key.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct SweepResult {
+    /// The IDs of the sweep transactions, in the order they were sent.
+    txids: Vec<String>,
+
+    /// The address controlled by the swept key.
+    ///
+    /// For a Sapling key, this is the key's default address, although the funds of
+    /// every address derived from the key are swept.
+    address: String,
+
+    /// The address that the swept funds were sent to.
+    destination: String,
+
+    /// The transactions that were sent.
+    ///
+    /// If more funds were found than fit in a single transaction (per the
+    /// `builder.limits` config options), they are swept in several batches.
+    batches: Vec<SweptBatch>,
+
+    /// Coinbase outputs that were found but not swept, because they cannot be spent
+    /// until they have 100 confirmations.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    immature: Vec<SweptOutput>,
+
+    /// The total value of the funds that were swept, in ZEC.
+    total: JsonZec,
+
+    /// The value of `total` in zatoshis.
+    #[serde(rename = "totalZat")]
+    total_zat: u64,
+
+    /// The total fee paid by the sweep transactions, in ZEC.
+    fee: JsonZec,
+
+    /// The value of `fee` in zatoshis.
+    #[serde(rename = "feeZat")]
+    fee_zat: u64,
+
+    /// The total value received by the destination, in ZEC.
+    swept: JsonZec,
+
+    /// The value of `swept` in zatoshis.
+    #[serde(rename = "sweptZat")]
+    swept_zat: u64,
+}
+
+/// A single sweep transaction.
+#[derive(Clone, Debug, Serialize)]
+struct SweptBatch {
+    /// The ID of the sweep transaction.
+    txid: String,
+
+    /// The UTXOs and notes spent by the sweep transaction.
+    found: Vec<SweptOutput>,
+
+    /// The total value of the funds spent by the sweep transaction, in ZEC.
+    total: JsonZec,
+
+    /// The value of `total` in zatoshis.
+    #[serde(rename = "totalZat")]
+    total_zat: u64,
+
+    /// The fee paid by the sweep transaction, in ZEC.
+    fee: JsonZec,
+
+    /// The value of `fee` in zatoshis.
+    #[serde(rename = "feeZat")]
+    fee_zat: u64,
+
+    /// The value received by the destination, in ZEC.
+    swept: JsonZec,
+
+    /// The value of `swept` in zatoshis.
+    #[serde(rename = "sweptZat")]
+    swept_zat: u64,
+
+    /// The height after which the sweep transaction will expire if it has not been mined.
+    expiryheight: u32,
+
+    /// The raw sweep transaction, hex-encoded, if it was not broadcast.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hex: Option<String>,
+}
+
+/// A UTXO or note controlled by the swept key.
+#[derive(Clone, Debug, Serialize)]
+struct SweptOutput {
+    /// The value pool containing the output.
+    ///
+    /// One of `["transparent", "sapling"]`.
+    pool: &'static str,
+
+    /// The ID of the transaction containing the output.
+    txid: String,
+
+    /// The index of the output within the transaction's outputs in its pool.
+    vout: u32,
+
+    /// The value of the output in ZEC.
+    amount: JsonZec,
+
+    /// The value of the output in zatoshis.
+    #[serde(rename = "amountZat")]
+    amount_zat: u64,
+
+    /// The height at which the output was mined.
+    height: u32,
+}
+
+impl SweptOutput {
+    fn new(pool: PoolType, txid: String, vout: u32, value: Zatoshis, height: u32) -> Self {
+        let (amount, amount_zat) = zec_and_zat(value);
+        Self {
+            pool: pool_name(pool),
+            txid,
+            vout,
+            amount,
+            amount_zat,
+            height,
+        }
+    }
+}
+
+/// The progress of a sweep operation.
+#[derive(Clone, Debug, Serialize)]
+struct SweepProgress {
+    /// The number of batches that have been sent.
+    batches_sent: usize,
+
+    /// The total number of batches in the sweep.
+    batches_total: usize,
+
+    /// The IDs of the transactions that have been sent so far.
+    txids: Vec<String>,
+}
+
+/// The progress of scanning the chain for notes spendable by a Sapling key.
+#[derive(Clone, Debug, Serialize)]
+struct ScanProgress {
+    /// The height of the last block that has been scanned.
+    scanned_height: u32,
+
+    /// The height of the last block that will be scanned.
+    end_height: u32,
+}
+
+/// A key whose funds can be swept.
+enum SweepKey {
+    Transparent(secp256k1::SecretKey),
+    Sapling(Box<ExtendedSpendingKey>),
+}
+
+impl SweepKey {
+    /// Parses a transparent key in WIF format, or an encoded Sapling extended spending
+    /// key.
+    fn parse(params: &Network, encoded: &str) -> Option<Self> {
+        if let Ok(key) = Key::decode_base58(params, encoded) {
+            return Some(Self::Transparent(*key.secret()));
+        }
+        decode_extended_spending_key(
+            params.network_type().hrp_sapling_extended_spending_key(),
+            encoded,
+        )
+        .ok()
+        .map(|extsk| Self::Sapling(Box::new(extsk)))
+    }
+}
+
+/// The key material needed to spend the funds found for a [`SweepKey`].
+#[derive(Clone)]
+enum SweepSigner {
+    Transparent {
+        secret_key: secp256k1::SecretKey,
+        pubkey: secp256k1::PublicKey,
+    },
+    Sapling {
+        extsk: Box<ExtendedSpendingKey>,
+        /// The anchor that the witnesses of all of the found notes are for.
+        anchor: sapling::Anchor,
+    },
+}
+
+/// A UTXO or note controlled by the swept key.
+struct SweepInput {
+    spend: SweepSpend,
+    value: Zatoshis,
+    output: SweptOutput,
+}
+
+enum SweepSpend {
+    Transparent {
+        outpoint: OutPoint,
+        txout: TxOut,
+    },
+    Sapling {
+        note: sapling::Note,
+        merkle_path: sapling::MerklePath,
+    },
+}
+
+impl SweepInput {
+    fn kind(&self) -> InputKind {
+        match self.spend {
+            SweepSpend::Transparent { .. } => InputKind::Transparent,
+            SweepSpend::Sapling { .. } => InputKind::Sapling,
+        }
+    }
+}
+
+pub(crate) async fn call(
+    wallet: DbHandle,
+    chain: FetchServiceSubscriber,
+    privkey: SecretString,
+    destination: String,
+    start_height: Option<u32>,
+) -> RpcResult<(
+    Option<ContextInfo>,
+    impl Future<Output = RpcResult<SweepResult>>,
+)> {
+    let params = *wallet.params();
+
+    // The key is only ever held in memory; it is never written to the keystore or to the
+    // wallet database.
+    let key = SweepKey::parse(&params, privkey.expose_secret()).ok_or_else(|| {
+        LegacyCode::InvalidAddressOrKey.with_static("Invalid private key encoding")
+    })?;
+
+    let destination_addr = match destination.parse() {
+        // This might be an account UUID; send to the default shielded address of that
+        // account.
+        Ok(uuid) => {
+            let account = wallet
+                .get_account(AccountUuid::from_uuid(uuid))
+                .map_err(ComponentFailure::database)?
+                .ok_or_else(|| LegacyCode::InvalidParameter.with_static("Account not found."))?;
+
+            let ua = account
+                .ufvk()
+                .ok_or_else(|| {
+                    LegacyCode::Wallet.with_static("Account has no unified full viewing key.")
+                })?
+                .default_address(UnifiedAddressRequest::SHIELDED)
+                .map_err(|e| LegacyCode::Wallet.with_message(e.to_string()))?
+                .0;
+
+            Address::Unified(ua)
+        }
+        Err(_) => Address::decode(&params, &destination).ok_or_else(|| {
+            LegacyCode::InvalidAddressOrKey.with_data(
+                "Invalid destination: should be an account UUID or an address.",
+                json!({ "address": destination, "reason": "not an account UUID or an address for this network" }),
+            )
+        })?,
+    };
+
+    let chain_height = wallet
+        .chain_height()
+        .map_err(ComponentFailure::database)?
+        .ok_or(LegacyCode::InWarmup.with_static("Wallet sync required"))?;
+
+    let start_height = match (&key, start_height) {
+        (SweepKey::Transparent(_), None) => None,
+        (SweepKey::Transparent(_), Some(_)) => {
+            return Err(LegacyCode::InvalidParameter
+                .with_static("Invalid parameter, start_height can only be set for Sapling keys."));
+        }
+        (SweepKey::Sapling(_), Some(height)) if height > u32::from(chain_height) => {
+            return Err(LegacyCode::InvalidParameter.with_message(format!(
+                "Invalid parameter, start_height is above the chain height {chain_height}."
+            )));
+        }
+        (SweepKey::Sapling(_), height) => {
+            let sapling_activation = params
+                .activation_height(NetworkUpgrade::Sapling)
+                .expect("Sapling is active on all supported networks");
+            let default = chain_height.saturating_sub(DEFAULT_SAPLING_SCAN_DEPTH - 1);
+            Some(
+                height
+                    .map_or(default, BlockHeight::from_u32)
+                    .max(sapling_activation),
+            )
+        }
+    };
+
+    let progress = OperationProgress::default();
+
+    Ok((
+        Some(
+            ContextInfo::new(
+                "sweepprivkey",
+                // Never include the private key in the operation context.
+                json!({
+                    "destination": destination,
+                    "start_height": start_height.map(u32::from),
+                }),
+            )
+            .with_progress(progress.clone()),
+        ),
+        run(
+            chain,
+            params,
+            key,
+            destination_addr,
+            chain_height,
+            start_height,
+            progress,
+        ),
+    ))
+}
+
+async fn run(
+    chain: FetchServiceSubscriber,
+    params: Network,
+    key: SweepKey,
+    destination: Address,
+    chain_height: BlockHeight,
+    start_height: Option<BlockHeight>,
+    progress: OperationProgress,
+) -> RpcResult<SweepResult> {
+    let target_height = chain_height + 1;
+
+    let (source_str, signer, inputs, immature) = match key {
+        SweepKey::Transparent(secret_key) => {
+            let pubkey = TransparentSigningSet::new().add_key(secret_key);
+            let source_str = TransparentAddress::from_pubkey(&pubkey).encode(&params);
+            let (inputs, immature) = find_utxos(&chain, params, &source_str, target_height).await?;
+            (
+                source_str,
+                SweepSigner::Transparent { secret_key, pubkey },
+                inputs,
+                immature,
+            )
+        }
+        SweepKey::Sapling(extsk) => {
+            let source_str = Address::Sapling(extsk.default_address().1).encode(&params);
+            let (inputs, anchor) = find_notes(
+                &chain,
+                params,
+                &extsk,
+                start_height.expect("set for Sapling keys"),
+                chain_height,
+                &progress,
+            )
+            .await?;
+            (
+                source_str,
+                SweepSigner::Sapling { extsk, anchor },
+                inputs,
+                vec![],
+            )
+        }
+    };
+
+    if inputs.is_empty() {
+        return Err(if immature.is_empty() {
+            LegacyCode::Wallet.with_message(format!("No funds found for address {source_str}"))
+        } else {
+            LegacyCode::Wallet.with_data(
+                format!(
+                    "Only immature coinbase funds found for address {source_str}; they can be swept once they have {COINBASE_MATURITY} confirmations"
+                ),
+                json!({ "immature": immature }),
+            )
+        });
+    }
+
+    let batches = plan_batches(
+        inputs,
+        SweepInput::kind,
+        &BatchLimits::from_config(&APP.config().builder.limits),
+    );
+    let batches_total = batches.len();
+
+    let mut sent = vec![];
+    let mut total = Zatoshis::ZERO;
+    let mut fee = Zatoshis::ZERO;
+    let mut swept = Zatoshis::ZERO;
+    let mut batches = batches.into_iter();
+    while let Some(batch) = batches.next() {
+        match sweep_batch(&chain, params, target_height, &signer, &destination, &batch).await {
+            Ok((result, values)) => {
+                total = (total + values.total)
+                    .ok_or_else(|| LegacyCode::Misc.with_static("Value out of range"))?;
+                fee = (fee + values.fee)
+                    .ok_or_else(|| LegacyCode::Misc.with_static("Value out of range"))?;
+                swept = (swept + values.swept)
+                    .ok_or_else(|| LegacyCode::Misc.with_static("Value out of range"))?;
+                sent.push(result);
+                progress.set(&SweepProgress {
+                    batches_sent: sent.len(),
+                    batches_total,
+                    txids: sent.iter().map(|batch| batch.txid.clone()).collect(),
+                });
+            }
+            Err(e) => {
+                // Report exactly which funds have not been swept, so that the caller can
+                // retry without double-counting funds that were already moved.
+                let remaining = batch
+                    .iter()
+                    .chain(batches.as_slice().iter().flatten())
+                    .map(|input| format!("{}:{}", input.output.txid, input.output.vout))
+                    .collect::<Vec<_>>();
+
+                return Err(ErrorObjectOwned::owned(
+                    e.code(),
+                    format!(
+                        "Sweep stopped after {} of {batches_total} batches: {}",
+                        sent.len(),
+                        e.message(),
+                    ),
+                    Some(json!({
+                        "txids": sent.iter().map(|batch| &batch.txid).collect::<Vec<_>>(),
+                        "remaining": remaining,
+                    })),
+                ));
+            }
+        }
+    }
+
+    let ((total, total_zat), (fee, fee_zat), (swept, swept_zat)) =
+        (zec_and_zat(total), zec_and_zat(fee), zec_and_zat(swept));
+
+    Ok(SweepResult {
+        txids: sent.iter().map(|batch| batch.txid.clone()).collect(),
+        address: source_str,
+        destination: destination.encode(&params),
+        batches: sent,
+        immature,
+        total,
+        total_zat,
+        fee,
+        fee_zat,
+        swept,
+        swept_zat,
+    })
+}
+
+/// Queries the backing full node for the UTXOs received by the given transparent
+/// address.
+///
+/// Returns the UTXOs that can be spent in a transaction mined at `target_height`, and the
+/// coinbase outputs that cannot be spent until later.
+async fn find_utxos(
+    chain: &FetchServiceSubscriber,
+    params: Network,
+    address: &str,
+    target_height: BlockHeight,
+) -> RpcResult<(Vec<SweepInput>, Vec<SweptOutput>)> {
+    let utxos = chain
+        .z_get_address_utxos(AddressStrings::new(vec![address.to_string()]))
+        .await
+        .map_err(ComponentFailure::chain)?;
+
+    let mut inputs = vec![];
+    let mut immature = vec![];
+    for utxo in utxos {
+        let (_, txid, index, script, value_zat, mined_height) = utxo.into_parts();
+        let value = Zatoshis::from_u64(value_zat)
+            .map_err(|_| LegacyCode::Misc.with_static("Invalid UTXO value"))?;
+        let mined_height = BlockHeight::from_u32(mined_height.0);
+        let output = SweptOutput::new(
+            PoolType::Transparent,
+            txid.to_string(),
+            index.index(),
+            value,
+            mined_height.into(),
+        );
+
+        // Only recently-mined outputs can be immature, so we avoid fetching every
+        // transaction.
+        if is_immature(mined_height, target_height)
+            && is_coinbase(chain, params, &output.txid, mined_height).await?
+        {
+            immature.push(output);
+            continue;
+        }
+
+        inputs.push(SweepInput {
+            spend: SweepSpend::Transparent {
+                outpoint: OutPoint::new(txid.0, index.index()),
+                txout: TxOut::new(value, Script(script::Code(script.as_raw_bytes().to_vec()))),
+            },
+            value,
+            output,
+        });
+    }
+
+    Ok((inputs, immature))
+}
+
+/// Returns `true` if a coinbase output mined at `mined_height` cannot be spent in a
+/// transaction mined at `target_height`.
+fn is_immature(mined_height: BlockHeight, target_height: BlockHeight) -> bool {
+    u32::from(target_height).saturating_sub(mined_height.into()) < COINBASE_MATURITY
+}
+
+/// Returns `true` if the given mined transaction is a coinbase transaction.
+async fn is_coinbase(
+    chain: &FetchServiceSubscriber,
+    params: Network,
+    txid: &str,
+    mined_height: BlockHeight,
+) -> RpcResult<bool> {
+    let tx = match chain
+        .get_raw_transaction(txid.to_string(), Some(1))
+        .await
+        .map_err(ComponentFailure::chain)?
+    {
+        // TODO: Zaino should have a Rust API for fetching tx details, instead of
+        // requiring us to specify a verbosity and then deal with an enum variant that
+        // should never occur.
+        GetRawTransaction::Raw(_) => unreachable!("We requested verbosity 1"),
+        GetRawTransaction::Object(tx) => tx,
+    };
+    let tx = Transaction::read(
+        tx.hex().as_ref(),
+        BranchId::for_height(&params, mined_height),
+    )
+    .map_err(ComponentFailure::chain)?;
+
+    Ok(tx
+        .transparent_bundle()
+        .is_some_and(|bundle| bundle.is_coinbase()))
+}
+
+/// Scans the chain from `start_height` to `end_height` for the unspent notes that the
+/// given Sapling key can spend.
+///
+/// Returns the notes along with the anchor at `end_height`, which their witnesses are
+/// for.
+async fn find_notes(
+    chain: &FetchServiceSubscriber,
+    params: Network,
+    extsk: &ExtendedSpendingKey,
+    start_height: BlockHeight,
+    end_height: BlockHeight,
+    progress: &OperationProgress,
+) -> RpcResult<(Vec<SweepInput>, sapling::Anchor)> {
+    let chain_state = fetch_chain_state(chain, &params, start_height - 1)
+        .await
+        .map_err(ComponentFailure::chain)?;
+    let mut scanner = SaplingScanner::new(
+        extsk,
+        sapling::CommitmentTree::from_frontier(chain_state.final_sapling_tree()),
+    );
+
+    let mut height = start_height;
+    while height <= end_height {
+        let batch_end = (height + SAPLING_SCAN_BATCH_SIZE).min(end_height + 1);
+        let range = ScanRange::from_parts(height..batch_end, ScanPriority::Historic);
+        for block in fetch_blocks(chain, &range)
+            .await
+            .map_err(ComponentFailure::chain)?
+        {
+            scanner.scan_block(&params, block)?;
+        }
+        height = batch_end;
+
+        progress.set(&ScanProgress {
+            scanned_height: u32::from(height) - 1,
+            end_height: end_height.into(),
+        });
+
+        // Stop scanning if Zallet is shutting down.
+        asyncop::checkpoint()?;
+    }
+
+    Ok(scanner.finish())
+}
+
+/// Finds the unspent notes that a Sapling key can spend, by trial-decrypting the outputs
+/// of consecutive compact blocks with the key's external and internal viewing keys.
+struct SaplingScanner {
+    keys: Vec<(PreparedIncomingViewingKey, NullifierDerivingKey)>,
+    /// The note commitment tree as of the last scanned block.
+    tree: sapling::CommitmentTree,
+    notes: Vec<ScannedNote>,
+    /// The nullifiers revealed by the scanned blocks.
+    spent: HashSet<sapling::Nullifier>,
+}
+
+struct ScannedNote {
+    note: sapling::Note,
+    nf: sapling::Nullifier,
+    witness: sapling::IncrementalWitness,
+    output: SweptOutput,
+}
+
+impl SaplingScanner {
+    /// Starts a scan with the note commitment tree as of the block before the first
+    /// block to be scanned.
+    fn new(extsk: &ExtendedSpendingKey, tree: sapling::CommitmentTree) -> Self {
+        let dfvk = extsk.to_diversifiable_full_viewing_key();
+        let keys = [zip32::Scope::External, zip32::Scope::Internal]
+            .into_iter()
+            .map(|scope| {
+                (
+                    PreparedIncomingViewingKey::new(&dfvk.to_ivk(scope)),
+                    dfvk.to_nk(scope),
+                )
+            })
+            .collect();
+
+        Self {
+            keys,
+            tree,
+            notes: vec![],
+            spent: HashSet::new(),
+        }
+    }
+
+    /// Scans the block after the last scanned block.
+    fn scan_block(&mut self, params: &Network, block: CompactBlock) -> RpcResult<()> {
+        let height = block.height();
+        let zip212_enforcement = zip212_enforcement(params, height);
+        let tree_full = || ComponentFailure::internal("Sapling note commitment tree is full");
+
+        for tx in block.vtx {
+            let txid = tx.txid();
+            for spend in &tx.spends {
+                let nf = spend.nf().map_err(|_| {
+                    ComponentFailure::chain(format!("Invalid Sapling nullifier in {txid}"))
+                })?;
+                self.spent.insert(nf);
+            }
+
+            for (output, index) in tx.outputs.into_iter().zip(0..) {
+                let output = CompactOutputDescription::try_from(output).map_err(|_| {
+                    ComponentFailure::chain(format!("Invalid Sapling output in {txid}"))
+                })?;
+                let node = sapling::Node::from_cmu(&output.cmu);
+
+                self.tree.append(node).map_err(|_| tree_full())?;
+                for note in &mut self.notes {
+                    note.witness.append(node).map_err(|_| tree_full())?;
+                }
+
+                let Some((note, nk)) = self.keys.iter().find_map(|(ivk, nk)| {
+                    try_sapling_compact_note_decryption(ivk, &output, zip212_enforcement)
+                        .map(|(note, _)| (note, nk))
+                }) else {
+                    continue;
+                };
+
+                let witness = sapling::IncrementalWitness::from_tree(self.tree.clone())
+                    .expect("the tree contains the note");
+                let nf = note.nf(nk, u64::from(witness.witnessed_position()));
+                let value = Zatoshis::from_u64(note.value().inner())
+                    .map_err(|_| ComponentFailure::chain("Invalid Sapling note value"))?;
+                self.notes.push(ScannedNote {
+                    note,
+                    nf,
+                    witness,
+                    output: SweptOutput::new(
+                        PoolType::Shielded(ShieldedProtocol::Sapling),
+                        txid.to_string(),
+                        index,
+                        value,
+                        height.into(),
+                    ),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the notes that have not been spent in any scanned block, along with the
+    /// anchor that their witnesses are for.
+    fn finish(self) -> (Vec<SweepInput>, sapling::Anchor) {
+        let anchor = sapling::Anchor::from(self.tree.root());
+        let inputs = self
+            .notes
+            .into_iter()
+            .filter(|note| !self.spent.contains(&note.nf))
+            .map(|note| SweepInput {
+                value: Zatoshis::from_u64(note.note.value().inner()).expect("checked when found"),
+                spend: SweepSpend::Sapling {
+                    merkle_path: note.witness.path().expect("the tree contains the note"),
+                    note: note.note,
+                },
+                output: note.output,
+            })
+            .collect();
+        (inputs, anchor)
+    }
+}
+
+/// The values moved by a single sweep transaction.
+struct BatchValues {
+    total: Zatoshis,
+    fee: Zatoshis,
+    swept: Zatoshis,
+}
+
+/// Builds and sends a transaction sweeping the given funds to `destination`.
+async fn sweep_batch(
+    chain: &FetchServiceSubscriber,
+    params: Network,
+    target_height: BlockHeight,
+    signer: &SweepSigner,
+    destination: &Address,
+    batch: &[SweepInput],
+) -> RpcResult<(SweptBatch, BatchValues)> {
+    let total = batch
+        .iter()
+        .try_fold(Zatoshis::ZERO, |acc, input| acc + input.value)
+        .ok_or_else(|| LegacyCode::Misc.with_static("Value out of range"))?;
+
+    let counts = sweep_action_counts(batch, destination);
+    let fee = counts.conventional_fee();
+    FeeLimit::from_config(&APP.config().builder).check(&counts, fee)?;
+
+    let swept = (total - fee).filter(|v| !v.is_zero()).ok_or_else(|| {
+        LegacyCode::WalletInsufficientFunds.with_data(
+            format!(
+                "Found {} ZEC, which is not enough to pay the {} ZEC fee",
+                value_from_zatoshis(total),
+                value_from_zatoshis(fee),
+            ),
+            json!({ "required": fee.into_u64(), "available": total.into_u64() }),
+        )
+    })?;
+
+    let dust = DustThresholds::from_config(&APP.config().note_management)
+        .threshold(&destination.to_zcash_address(&params));
+    if swept < dust {
+        return Err(LegacyCode::WalletInsufficientFunds.with_data(
+            format!(
+                "Found {} ZEC, which after the {} ZEC fee is below the dust threshold of {} ZEC",
+                value_from_zatoshis(total),
+                value_from_zatoshis(fee),
+                value_from_zatoshis(dust),
+            ),
+            json!({
+                "required": (fee + dust).map_or(u64::MAX, Zatoshis::into_u64),
+                "available": total.into_u64(),
+            }),
+        ));
+    }
+
+    let builder = sweep_builder(params, target_height, signer, batch, destination, swept)?;
+
+    // Stop before creating the transaction if Zallet is shutting down; the inputs that
+    // have not been swept are reported to the caller.
+    asyncop::checkpoint()?;
+
+    let prover = LocalTxProver::bundled();
+    let signer = signer.clone();
+    let tx = crate::spawn_blocking!("sweepprivkey prover", move || {
+        let mut signing_set = TransparentSigningSet::new();
+        let sapling_extsks = match signer {
+            SweepSigner::Transparent { secret_key, .. } => {
+                signing_set.add_key(secret_key);
+                vec![]
+            }
+            SweepSigner::Sapling { extsk, .. } => vec![*extsk],
+        };
+        builder
+            .build(
+                &signing_set,
+                &sapling_extsks,
+                &[],
+                OsRng,
+                &prover,
+                &prover,
+                &fees::builder_fee_rule(),
+            )
+            .map(|res| res.transaction().clone())
+    })
+    .await
+    .map_err(|e| SendFailure::Internal(e.to_string()))?
+    .map_err(|e| SendFailure::Build(e.to_string()))?;
+
+    let mut tx_bytes = vec![];
+    tx.write(&mut tx_bytes)
+        .map_err(|e| LegacyCode::OutOfMemory.with_message(e.to_string()))?;
+    if tx_bytes.len() > MAX_TX_SIZE {
+        return Err(SendFailure::TooLarge {
+            size: tx_bytes.len(),
+        }
+        .into());
+    }
+    let raw_transaction_hex = hex::encode(&tx_bytes);
+    asyncop::record_txids(&[tx.txid()]).await;
+
+    // The sweep transaction is not stored in the wallet, so if we don't broadcast it
+    // the caller must submit it themselves.
+    let hex = if APP.config().external.broadcast() {
+        chain
+            .send_raw_transaction(raw_transaction_hex)
+            .await
+            .map_err(SendFailure::from_broadcast_error)?;
+        asyncop::record_broadcast(&[tx.txid()]).await;
+        None
+    } else {
+        Some(raw_transaction_hex)
+    };
+
+    let values = BatchValues { total, fee, swept };
+    let ((total, total_zat), (fee, fee_zat), (swept, swept_zat)) =
+        (zec_and_zat(total), zec_and_zat(fee), zec_and_zat(swept));
+
+    Ok((
+        SweptBatch {
+            txid: tx.txid().to_string(),
+            found: batch.iter().map(|input| input.output.clone()).collect(),
+            total,
+            total_zat,
+            fee,
+            fee_zat,
+            swept,
+            swept_zat,
+            expiryheight: tx.expiry_height().into(),
+            hex,
+        },
+        values,
+    ))
+}
+
+/// Returns the logical actions of a transaction spending `inputs` to the given
+/// destination.
+fn sweep_action_counts(inputs: &[SweepInput], destination: &Address) -> ActionCounts {
+    let mut counts = ActionCounts::default();
+    for input in inputs {
+        match input.spend {
+            SweepSpend::Transparent { .. } => counts.transparent_inputs += 1,
+            SweepSpend::Sapling { .. } => counts.sapling_spends += 1,
+        }
+    }
+    match destination {
+        Address::Transparent(_) | Address::Tex(_) => counts.transparent_outputs = 1,
+        Address::Sapling(_) => counts.sapling_outputs = 1,
+        Address::Unified(ua) => match (ua.orchard(), ua.sapling()) {
+            (Some(_), _) => counts.orchard_outputs = 1,
+            (None, Some(_)) => counts.sapling_outputs = 1,
+            (None, None) => counts.transparent_outputs = 1,
+        },
+    }
+    counts
+}
+
+/// Constructs a transaction builder that spends all of the given inputs to `destination`.
+fn sweep_builder(
+    params: Network,
+    target_height: BlockHeight,
+    signer: &SweepSigner,
+    inputs: &[SweepInput],
+    destination: &Address,
+    value: Zatoshis,
+) -> RpcResult<Builder<'static, Network, ()>> {
+    let sapling_anchor = match signer {
+        SweepSigner::Transparent { .. } => None,
+        SweepSigner::Sapling { anchor, .. } => Some(*anchor),
+    };
+    let mut builder = Builder::new(
+        params,
+        target_height,
+        BuildConfig::Standard {
+            sapling_anchor,
+            orchard_anchor: None,
+        },
+    );
+
+    for input in inputs {
+        let res = match (&input.spend, signer) {
+            (
+                SweepSpend::Transparent { outpoint, txout },
+                SweepSigner::Transparent { pubkey, .. },
+            ) => builder
+                .add_transparent_input(*pubkey, outpoint.clone(), txout.clone())
+                .map_err(|e| e.to_string()),
+            (SweepSpend::Sapling { note, merkle_path }, SweepSigner::Sapling { extsk, .. }) => {
+                builder
+                    .add_sapling_spend::<Infallible>(
+                        extsk.to_diversifiable_full_viewing_key().fvk().clone(),
+                        note.clone(),
+                        merkle_path.clone(),
+                    )
+                    .map_err(|e| e.to_string())
+            }
+            _ => unreachable!("inputs are found with the signer's key"),
+        };
+        res.map_err(|e| LegacyCode::Wallet.with_message(e))?;
+    }
+
+    let res = match destination {
+        Address::Transparent(addr) => builder
+            .add_transparent_output(addr, value)
+            .map_err(|e| e.to_string()),
+        Address::Tex(data) => builder
+            .add_transparent_output(&TransparentAddress::PublicKeyHash(*data), value)
+            .map_err(|e| e.to_string()),
+        Address::Sapling(addr) => builder
+            .add_sapling_output::<Infallible>(None, *addr, value, MemoBytes::empty())
+            .map_err(|e| e.to_string()),
+        Address::Unified(ua) => match (ua.orchard(), ua.sapling(), ua.transparent()) {
+            (Some(addr), _, _) => builder
+                .add_orchard_output::<Infallible>(None, *addr, value.into_u64(), MemoBytes::empty())
+                .map_err(|e| e.to_string()),
+            (None, Some(addr), _) => builder
+                .add_sapling_output::<Infallible>(None, *addr, value, MemoBytes::empty())
+                .map_err(|e| e.to_string()),
+            (None, None, Some(addr)) => builder
+                .add_transparent_output(addr, value)
+                .map_err(|e| e.to_string()),
+            (None, None, None) => Err("Unified address has no usable receivers".into()),
+        },
+    };
+    res.map_err(|e| LegacyCode::InvalidParameter.with_message(e))?;
+
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{RngCore, rngs::OsRng};
+    use sapling::{
+        Note, PaymentAddress, Rseed,
+        note_encryption::{SaplingDomain, sapling_note_encryption},
+        value::NoteValue,
+        zip32::ExtendedSpendingKey,
+    };
+    use zcash_client_backend::proto::compact_formats::{
+        CompactBlock, CompactSaplingOutput, CompactSaplingSpend, CompactTx,
+    };
+    use zcash_keys::{address::Address, encoding::encode_extended_spending_key};
+    use zcash_note_encryption::Domain;
+    use zcash_protocol::{
+        TxId,
+        consensus::{self, BlockHeight, NetworkConstants, NetworkType, Parameters},
+        memo::MemoBytes,
+    };
+
+    use super::{SaplingScanner, SweepKey, SweepSpend, is_immature, sweep_action_counts};
+    use crate::network::Network;
+
+    /// A height after Canopy, so that ZIP 212 is enforced.
+    const HEIGHT: u32 = 2_000_000;
+
+    fn params() -> Network {
+        Network::Consensus(consensus::Network::MainNetwork)
+    }
+
+    fn note(recipient: PaymentAddress, value: u64) -> Note {
+        let mut rseed = [0; 32];
+        OsRng.fill_bytes(&mut rseed);
+        Note::from_parts(
+            recipient,
+            NoteValue::from_raw(value),
+            Rseed::AfterZip212(rseed),
+        )
+    }
+
+    fn compact_output(note: &Note) -> CompactSaplingOutput {
+        let encryptor = sapling_note_encryption(
+            None,
+            note.clone(),
+            MemoBytes::empty().into_bytes(),
+            &mut OsRng,
+        );
+        CompactSaplingOutput {
+            cmu: note.cmu().to_bytes().to_vec(),
+            ephemeral_key: SaplingDomain::epk_bytes(encryptor.epk()).0.to_vec(),
+            ciphertext: encryptor.encrypt_note_plaintext().as_ref()[..52].to_vec(),
+        }
+    }
+
+    fn compact_tx(id: u8, spends: Vec<CompactSaplingSpend>, outputs: &[Note]) -> CompactTx {
+        CompactTx {
+            hash: vec![id; 32],
+            spends,
+            outputs: outputs.iter().map(compact_output).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn compact_block(height: u32, vtx: Vec<CompactTx>) -> CompactBlock {
+        CompactBlock {
+            height: height.into(),
+            vtx,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_keys() {
+        let params = params();
+
+        // The WIF encoding of the compressed secret key 1.
+        assert!(matches!(
+            SweepKey::parse(
+                &params,
+                "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn"
+            ),
+            Some(SweepKey::Transparent(_)),
+        ));
+
+        let extsk = ExtendedSpendingKey::master(&[7; 32]);
+        let encoded = encode_extended_spending_key(
+            params.network_type().hrp_sapling_extended_spending_key(),
+            &extsk,
+        );
+        match SweepKey::parse(&params, &encoded) {
+            Some(SweepKey::Sapling(parsed)) => assert_eq!(parsed.to_bytes(), extsk.to_bytes()),
+            _ => panic!("Sapling key was not parsed"),
+        }
+
+        // Keys for other networks, and other strings, are rejected.
+        let testnet = encode_extended_spending_key(
+            NetworkType::Test.hrp_sapling_extended_spending_key(),
+            &extsk,
+        );
+        assert!(SweepKey::parse(&params, &testnet).is_none());
+        assert!(SweepKey::parse(&params, "not a key").is_none());
+    }
+
+    #[test]
+    fn coinbase_maturity() {
+        let mined = BlockHeight::from_u32(1_000);
+        assert!(is_immature(mined, BlockHeight::from_u32(1_001)));
+        assert!(is_immature(mined, BlockHeight::from_u32(1_099)));
+        assert!(!is_immature(mined, BlockHeight::from_u32(1_100)));
+    }
+
+    #[test]
+    fn scanner_finds_unspent_notes() {
+        let params = params();
+        let extsk = ExtendedSpendingKey::master(&[7; 32]);
+        let dfvk = extsk.to_diversifiable_full_viewing_key();
+        let external = dfvk.default_address().1;
+        let internal = dfvk.change_address().1;
+        let other = ExtendedSpendingKey::master(&[8; 32]).default_address().1;
+
+        let received = note(external, 50_000);
+        let spent = note(external, 20_000);
+        let change = note(internal, 7_000);
+
+        let mut scanner = SaplingScanner::new(&extsk, sapling::CommitmentTree::empty());
+        scanner
+            .scan_block(
+                &params,
+                compact_block(
+                    HEIGHT,
+                    vec![
+                        compact_tx(1, vec![], &[note(other, 1_000), received.clone()]),
+                        compact_tx(2, vec![], &[spent.clone()]),
+                    ],
+                ),
+            )
+            .unwrap();
+
+        // `spent` is the third note commitment in the tree.
+        let spent_nf = spent.nf(&dfvk.to_nk(zip32::Scope::External), 2);
+        scanner
+            .scan_block(
+                &params,
+                compact_block(
+                    HEIGHT + 1,
+                    vec![compact_tx(
+                        3,
+                        vec![CompactSaplingSpend {
+                            nf: spent_nf.0.to_vec(),
+                        }],
+                        &[note(other, 5_000), change.clone()],
+                    )],
+                ),
+            )
+            .unwrap();
+
+        let (inputs, anchor) = scanner.finish();
+
+        // Notes received by both the external and internal addresses are found, and
+        // spent notes are not.
+        let found = inputs
+            .iter()
+            .map(|input| {
+                (
+                    input.output.txid.clone(),
+                    input.output.vout,
+                    input.output.height,
+                    input.value.into_u64(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (TxId::from_bytes([1; 32]).to_string(), 1, HEIGHT, 50_000),
+                (TxId::from_bytes([3; 32]).to_string(), 1, HEIGHT + 1, 7_000),
+            ],
+        );
+
+        // Each note's witness is for the anchor that the notes are spent with.
+        for (input, note) in inputs.iter().zip([&received, &change]) {
+            match &input.spend {
+                SweepSpend::Sapling {
+                    note: found,
+                    merkle_path,
+                } => {
+                    assert_eq!(found.cmu(), note.cmu());
+                    assert_eq!(
+                        sapling::Anchor::from(
+                            merkle_path.root(sapling::Node::from_cmu(&note.cmu()))
+                        ),
+                        anchor,
+                    );
+                }
+                SweepSpend::Transparent { .. } => panic!("expected a Sapling note"),
+            }
+        }
+
+        // Sweeping both notes to a Sapling address needs two logical actions.
+        let counts = sweep_action_counts(&inputs, &Address::Sapling(other));
+        assert_eq!(counts.sapling_spends, 2);
+        assert_eq!(counts.logical_actions(), 2);
+    }
+}
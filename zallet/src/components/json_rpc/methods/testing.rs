@@ -0,0 +1,82 @@
+//! Helpers for testing RPC methods against a wallet database.
+
+use jsonrpsee::core::RpcResult;
+use tempfile::TempDir;
+
+use crate::{
+    components::database::{Database, DbHandle},
+    config::ZalletConfig,
+};
+
+#[cfg(zallet_build = "wallet")]
+use crate::components::{json_rpc::asyncop::OperationRegistry, keystore::KeyStore};
+
+/// An empty wallet in a temporary directory.
+///
+/// Tests that use it must run on a multi-threaded runtime, as database accesses block.
+pub(super) struct TestWallet {
+    /// Deletes the wallet when the test finishes.
+    _dir: TempDir,
+    db: Database,
+    #[cfg(zallet_build = "wallet")]
+    pub(super) keystore: KeyStore,
+}
+
+impl TestWallet {
+    pub(super) async fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = ZalletConfig::default();
+        config.datadir = Some(dir.path().to_path_buf());
+
+        #[cfg(zallet_build = "wallet")]
+        {
+            use age::secrecy::ExposeSecret;
+
+            let identity = age::x25519::Identity::generate();
+            std::fs::write(
+                config.encryption_identity(),
+                format!("{}\n", identity.to_string().expose_secret()),
+            )
+            .unwrap();
+        }
+
+        let db = Database::open(&config).await.unwrap();
+        #[cfg(zallet_build = "wallet")]
+        let keystore = KeyStore::new(&config, db.clone()).unwrap();
+
+        Self {
+            _dir: dir,
+            db,
+            #[cfg(zallet_build = "wallet")]
+            keystore,
+        }
+    }
+
+    pub(super) async fn handle(&self) -> DbHandle {
+        self.db.handle().await.unwrap()
+    }
+
+    /// Returns a registry of async operations that are persisted in this wallet.
+    #[cfg(zallet_build = "wallet")]
+    pub(super) fn operations(&self) -> OperationRegistry {
+        OperationRegistry::new(self.db.clone(), &ZalletConfig::default())
+    }
+
+    /// Drops a table from the wallet database, so that queries that use it fail.
+    pub(super) async fn drop_table(&self, table: &str) {
+        self.handle()
+            .await
+            .with_raw_mut(|conn, _| {
+                conn.execute_batch(&format!("PRAGMA foreign_keys = OFF; DROP TABLE {table};"))
+            })
+            .unwrap();
+    }
+}
+
+/// Returns the legacy error code of a failed RPC call.
+pub(super) fn error_code<T>(res: RpcResult<T>) -> i32 {
+    match res {
+        Ok(_) => panic!("RPC call unexpectedly succeeded"),
+        Err(e) => e.code(),
+    }
+}
@@ -0,0 +1,35 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::components::wallets::Wallets;
+
+use super::MethodCategory;
+
+/// Response to an `unloadwallet` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = UnloadedWallet;
+
+/// The result of unloading the wallet.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct UnloadedWallet {
+    /// Warning messages about unloading the wallet, if any.
+    warning: String,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_WALLET_NAME_DESC: &str = "The name of the wallet.";
+
+pub(crate) async fn call(wallets: &Wallets, wallet_name: &str) -> Response {
+    let drained = wallets.unload(wallet_name).await?;
+
+    Ok(UnloadedWallet {
+        warning: if drained {
+            String::new()
+        } else {
+            "Some async operations were still executing when the wallet was unloaded".into()
+        },
+    })
+}
@@ -4,7 +4,13 @@ use jsonrpsee::core::RpcResult;
 use schemars::JsonSchema;
 use serde::Serialize;
 
-use crate::components::{json_rpc::server::LegacyCode, keystore::KeyStore};
+use crate::components::{
+    audit::{self, Event},
+    json_rpc::server::LegacyCode,
+    keystore::KeyStore,
+};
+
+use super::MethodCategory;
 
 /// Response to a `walletpassphrase` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
@@ -14,21 +20,72 @@ pub(crate) type Response = RpcResult<ResultType>;
 #[serde(transparent)]
 pub(crate) struct ResultType(());
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
 pub(super) const PARAM_PASSPHRASE_DESC: &str =
     "The passphrase for decrypting the wallet's age identity.";
-pub(super) const PARAM_TIMEOUT_DESC: &str = "Time in seconds after which the wallet will relock.";
+pub(super) const PARAM_TIMEOUT_DESC: &str =
+    "Time in seconds after which the wallet will relock, at most `keystore.max_unlock_duration`.";
 
 pub(crate) async fn call(keystore: &KeyStore, passphrase: SecretString, timeout: u64) -> Response {
-    if !keystore.uses_encrypted_identities() {
-        return Err(LegacyCode::WalletWrongEncState.with_static(
-            "Error: running with an unencrypted wallet, but walletpassphrase was called.",
-        ));
-    }
+    ensure_unlockable(
+        keystore.uses_encrypted_identities(),
+        keystore.allows_unlock(),
+    )?;
 
     if !keystore.unlock(passphrase, timeout).await {
         return Err(LegacyCode::WalletPassphraseIncorrect
             .with_static("Error: The wallet passphrase entered was incorrect."));
     }
+    audit::record(Event::WalletUnlocked {
+        timeout: keystore.unlock_duration(timeout).as_secs(),
+    });
 
     Ok(ResultType(()))
 }
+
+/// Checks that the wallet can be unlocked with `walletpassphrase`.
+fn ensure_unlockable(uses_encrypted_identities: bool, allows_unlock: bool) -> RpcResult<()> {
+    if !uses_encrypted_identities {
+        return Err(LegacyCode::WalletWrongEncState.with_static(
+            "Error: running with an unencrypted wallet, but walletpassphrase was called.",
+        ));
+    }
+
+    // In view-only operation, the passphrase alone is not enough to spend funds.
+    if !allows_unlock {
+        return Err(LegacyCode::ForbiddenBySafeMode.with_static(
+            "Error: unlocking the wallet is disabled by the keystore.allow_unlock option.",
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::json_rpc::server::LegacyCode;
+
+    use super::ensure_unlockable;
+
+    #[test]
+    fn unlock_can_be_disabled() {
+        let code = |uses_encrypted_identities, allows_unlock| {
+            ensure_unlockable(uses_encrypted_identities, allows_unlock).map_err(|e| e.code())
+        };
+
+        assert_eq!(code(true, true), Ok(()));
+        assert_eq!(
+            code(true, false),
+            Err(LegacyCode::ForbiddenBySafeMode as i32)
+        );
+        assert_eq!(
+            code(false, true),
+            Err(LegacyCode::WalletWrongEncState as i32)
+        );
+        assert_eq!(
+            code(false, false),
+            Err(LegacyCode::WalletWrongEncState as i32)
+        );
+    }
+}
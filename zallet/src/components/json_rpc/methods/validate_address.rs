@@ -0,0 +1,381 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use transparent::address::TransparentAddress;
+use zcash_address::{ConversionError, TryFromAddress, ZcashAddress, unified};
+use zcash_client_backend::data_api::{Account as _, WalletRead};
+use zcash_client_sqlite::AccountUuid;
+use zcash_keys::{address::Address, keys::UnifiedFullViewingKey};
+use zcash_protocol::consensus::{NetworkType, Parameters};
+use zip32::DiversifierIndex;
+
+use crate::{
+    components::{
+        database::DbConnection,
+        json_rpc::{server::ComponentFailure, utils::is_watch_only},
+    },
+    network::{Network, kind::type_to_str},
+};
+
+use super::{
+    MethodCategory,
+    list_unified_receivers::{ListUnifiedReceivers, list_receivers},
+};
+
+/// Response to a `z_validateaddress` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = ValidateAddress;
+
+/// Information about the address.
+#[derive(Clone, Debug, Default, Serialize, Documented, JsonSchema)]
+pub(crate) struct ValidateAddress {
+    /// Whether the address is a valid Zcash address for the wallet's network.
+    isvalid: bool,
+
+    /// Why the address is not valid.
+    ///
+    /// Omitted if the address is valid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+
+    /// The address.
+    ///
+    /// Omitted if the address is not valid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+
+    /// The type of the address: `p2pkh`, `p2sh`, `sprout`, `sapling`, `unified`, or
+    /// `tex`.
+    ///
+    /// Omitted if the address is not valid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address_type: Option<&'static str>,
+
+    /// The diversifier of a Sapling address, as a hex string.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diversifier: Option<String>,
+
+    /// The diversified transmission key of a Sapling address, as a hex string.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diversifiedtransmissionkey: Option<String>,
+
+    /// The receivers within a unified address, as returned by `z_listunifiedreceivers`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    receivers: Option<ListUnifiedReceivers>,
+
+    /// Whether any receiver of the address belongs to an account in the wallet.
+    ///
+    /// Omitted if the address is not valid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ismine: Option<bool>,
+
+    /// Whether the account that the address belongs to is watch-only, so that the
+    /// wallet cannot spend funds received on it.
+    ///
+    /// Omitted unless `ismine` is `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iswatchonly: Option<bool>,
+
+    /// The UUID of the account that the address belongs to.
+    ///
+    /// Omitted unless `ismine` is `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_uuid: Option<String>,
+
+    /// The diversifier index of the address within its account.
+    ///
+    /// Omitted unless `ismine` is `true` and the address has a shielded receiver.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diversifier_index: Option<u128>,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_ADDRESS_DESC: &str = "The address to validate.";
+
+pub(crate) fn call(wallet: &DbConnection, address: &str) -> Response {
+    let params = wallet.params();
+    let mut result = classify(params, address);
+
+    // Sprout addresses are valid, but never belong to the wallet.
+    let Some(address) = result
+        .isvalid
+        .then(|| ZcashAddress::try_from_encoded(address).ok())
+        .flatten()
+        .and_then(|addr| Address::try_from_zcash_address(params, addr).ok())
+    else {
+        result.ismine = result.isvalid.then_some(false);
+        return Ok(result);
+    };
+
+    let owner = find_owner(wallet, &address)?;
+    result.ismine = Some(owner.is_some());
+    if let Some(owner) = owner {
+        result.iswatchonly = Some(owner.watch_only);
+        result.account_uuid = Some(owner.account_id.expose_uuid().to_string());
+        result.diversifier_index = owner.diversifier_index.map(u128::from);
+    }
+
+    Ok(result)
+}
+
+/// Parses `address` without consulting the wallet.
+///
+/// Addresses that cannot be parsed, or are for a different network, are reported as
+/// invalid rather than as an error, as `zcashd` did.
+fn classify(params: &Network, address: &str) -> ValidateAddress {
+    let invalid = |reason: String| ValidateAddress {
+        reason: Some(reason),
+        ..Default::default()
+    };
+
+    let decoded = match ZcashAddress::try_from_encoded(address)
+        .map_err(|e| e.to_string())
+        .and_then(|addr| addr.convert::<Decoded>().map_err(|e| e.to_string()))
+    {
+        Ok(decoded) => decoded,
+        Err(e) => return invalid(format!("Invalid address: {e}")),
+    };
+
+    if decoded.network != params.network_type() {
+        return invalid(format!(
+            "Address is for the {} network, but the wallet is on the {} network",
+            type_to_str(&decoded.network),
+            type_to_str(&params.network_type()),
+        ));
+    }
+
+    let mut result = ValidateAddress {
+        isvalid: true,
+        address: Some(address.to_owned()),
+        ..Default::default()
+    };
+    result.address_type = Some(match decoded.kind {
+        Kind::P2pkh => "p2pkh",
+        Kind::P2sh => "p2sh",
+        Kind::Sprout => "sprout",
+        Kind::Sapling(data) => {
+            // `zcashd` encoded the transmission key as a `uint256`, which is reversed.
+            let mut pk_d = data[11..].to_vec();
+            pk_d.reverse();
+            result.diversifier = Some(hex::encode(&data[..11]));
+            result.diversifiedtransmissionkey = Some(hex::encode(pk_d));
+            "sapling"
+        }
+        Kind::Unified(ua) => {
+            result.receivers = Some(list_receivers(decoded.network, ua));
+            "unified"
+        }
+        Kind::Tex => "tex",
+    });
+    result
+}
+
+/// The account that an address belongs to.
+struct Owner {
+    account_id: AccountUuid,
+    watch_only: bool,
+    /// The diversifier index of the address's shielded receivers, if it has any.
+    diversifier_index: Option<DiversifierIndex>,
+}
+
+/// Returns the account that any receiver of `address` belongs to.
+fn find_owner(wallet: &DbConnection, address: &Address) -> RpcResult<Option<Owner>> {
+    let (sapling, orchard, transparent) = match address {
+        Address::Sapling(addr) => (Some(addr), None, None),
+        Address::Transparent(addr) => (None, None, Some(*addr)),
+        Address::Tex(data) => (None, None, Some(TransparentAddress::PublicKeyHash(*data))),
+        Address::Unified(ua) => (ua.sapling(), ua.orchard(), ua.transparent().copied()),
+    };
+
+    for account_id in wallet
+        .get_account_ids()
+        .map_err(ComponentFailure::database)?
+    {
+        let Some(account) = wallet
+            .get_account(account_id)
+            .map_err(ComponentFailure::database)?
+        else {
+            continue;
+        };
+
+        let owner = |diversifier_index| Owner {
+            account_id,
+            watch_only: is_watch_only(&account),
+            diversifier_index,
+        };
+
+        if let Some(index) = account
+            .ufvk()
+            .and_then(|ufvk| shielded_index(ufvk, sapling, orchard))
+        {
+            return Ok(Some(owner(Some(index))));
+        }
+
+        if let Some(taddr) = &transparent {
+            if wallet
+                .get_transparent_address_metadata(account_id, taddr)
+                .map_err(ComponentFailure::database)?
+                .is_some()
+            {
+                return Ok(Some(owner(None)));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the diversifier index at which `ufvk` derives any of the given receivers.
+fn shielded_index(
+    ufvk: &UnifiedFullViewingKey,
+    sapling: Option<&sapling::PaymentAddress>,
+    orchard: Option<&orchard::Address>,
+) -> Option<DiversifierIndex> {
+    let sapling = sapling
+        .zip(ufvk.sapling())
+        .and_then(|(addr, dfvk)| dfvk.decrypt_diversifier(addr))
+        .map(|(index, _)| index);
+    let orchard = || {
+        orchard.zip(ufvk.orchard()).and_then(|(addr, fvk)| {
+            fvk.scope_for_address(addr)
+                .and_then(|scope| fvk.to_ivk(scope).diversifier_index(addr))
+        })
+    };
+
+    sapling.or_else(orchard)
+}
+
+/// A decoded address, before checking its network.
+struct Decoded {
+    network: NetworkType,
+    kind: Kind,
+}
+
+enum Kind {
+    P2pkh,
+    P2sh,
+    Sprout,
+    Sapling([u8; 43]),
+    Unified(unified::Address),
+    Tex,
+}
+
+impl TryFromAddress for Decoded {
+    type Error = std::convert::Infallible;
+
+    fn try_from_sprout(
+        network: NetworkType,
+        _: [u8; 64],
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(Decoded {
+            network,
+            kind: Kind::Sprout,
+        })
+    }
+
+    fn try_from_sapling(
+        network: NetworkType,
+        data: [u8; 43],
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(Decoded {
+            network,
+            kind: Kind::Sapling(data),
+        })
+    }
+
+    fn try_from_unified(
+        network: NetworkType,
+        data: unified::Address,
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(Decoded {
+            network,
+            kind: Kind::Unified(data),
+        })
+    }
+
+    fn try_from_transparent_p2pkh(
+        network: NetworkType,
+        _: [u8; 20],
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(Decoded {
+            network,
+            kind: Kind::P2pkh,
+        })
+    }
+
+    fn try_from_transparent_p2sh(
+        network: NetworkType,
+        _: [u8; 20],
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(Decoded {
+            network,
+            kind: Kind::P2sh,
+        })
+    }
+
+    fn try_from_tex(
+        network: NetworkType,
+        _: [u8; 20],
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(Decoded {
+            network,
+            kind: Kind::Tex,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_address::ZcashAddress;
+    use zcash_protocol::consensus::{self, NetworkType};
+
+    use super::classify;
+    use crate::network::Network;
+
+    #[test]
+    fn classify_addresses() {
+        let params = Network::Consensus(consensus::Network::MainNetwork);
+
+        let taddr = "t1V9mnyk5Z5cTNMCkLbaDwSskgJZucTLdgW";
+        let result = classify(&params, taddr);
+        assert!(result.isvalid);
+        assert_eq!(result.address_type, Some("p2pkh"));
+
+        let mut data = [0; 43];
+        data[..11].copy_from_slice(&[1; 11]);
+        data[11] = 2;
+        let result = classify(
+            &params,
+            &ZcashAddress::from_sapling(NetworkType::Main, data).encode(),
+        );
+        assert_eq!(result.address_type, Some("sapling"));
+        assert_eq!(
+            result.diversifier.as_deref(),
+            Some("0101010101010101010101")
+        );
+        assert!(
+            result
+                .diversifiedtransmissionkey
+                .as_deref()
+                .is_some_and(|pk_d| pk_d.ends_with("02"))
+        );
+
+        // Addresses for another network are invalid, rather than an error.
+        let result = classify(
+            &params,
+            &ZcashAddress::from_sapling(NetworkType::Test, data).encode(),
+        );
+        assert!(!result.isvalid);
+        assert_eq!(
+            result.reason.as_deref(),
+            Some("Address is for the test network, but the wallet is on the main network"),
+        );
+        assert_eq!(result.address, None);
+
+        let result = classify(&params, "not an address");
+        assert!(!result.isvalid);
+        assert!(result.reason.unwrap().starts_with("Invalid address: "));
+    }
+}
@@ -15,6 +15,8 @@ use zcash_primitives::transaction::util::sha256d::HashWriter;
 
 use crate::{components::json_rpc::server::LegacyCode, network::Network};
 
+use super::MethodCategory;
+
 const MESSAGE_MAGIC: &str = "Zcash Signed Message:\n";
 
 pub(crate) type Response = RpcResult<ResultType>;
@@ -24,6 +26,8 @@ pub(crate) type Response = RpcResult<ResultType>;
 #[serde(transparent)]
 pub(crate) struct ResultType(bool);
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_ZCASHADDRESS_DESC: &str =
     "The zcash transparent address to use for the signature.";
 pub(super) const PARAM_SIGNATURE_DESC: &str =
@@ -35,7 +39,7 @@ pub(super) const PARAM_MESSAGE_DESC: &str = "The message that was signed.";
 /// This matches zcashd's `src/rpc/misc.cpp:493-495`.
 ///
 /// Each string is prefixed with CompactSize length, then the result is double SHA-256 hashed.
-fn message_hash(message: &str) -> [u8; 32] {
+pub(super) fn message_hash(message: &str) -> [u8; 32] {
     let mut writer = HashWriter::default();
 
     CompactSize::write(&mut writer, MESSAGE_MAGIC.len()).expect("write to HashWriter");
@@ -26,17 +26,22 @@ use zcash_protocol::{
 use zcash_script::script;
 use zebra_rpc::methods::GetRawTransaction;
 
-use crate::components::{
-    database::DbConnection,
-    json_rpc::{
-        server::LegacyCode,
-        utils::{JsonZec, parse_txid, value_from_zatoshis},
+use crate::{
+    components::{
+        database::{DbConnection, wallet_conflicts},
+        json_rpc::{
+            server::{ComponentFailure, LegacyCode},
+            utils::{JsonZec, parse_txid, zec_and_zat},
+        },
     },
+    config::TX_EXPIRING_SOON_THRESHOLD,
 };
 
+use super::MethodCategory;
+
 #[cfg(zallet_build = "wallet")]
 use {
-    crate::components::json_rpc::utils::{JsonZecBalance, value_from_zat_balance},
+    crate::components::json_rpc::utils::{JsonZecBalance, zec_and_zat_balance},
     zcash_protocol::value::ZatBalance,
 };
 
@@ -44,9 +49,6 @@ const POOL_TRANSPARENT: &str = "transparent";
 const POOL_SAPLING: &str = "sapling";
 const POOL_ORCHARD: &str = "orchard";
 
-/// The number of blocks within expiry height when a tx is considered to be expiring soon.
-const TX_EXPIRING_SOON_THRESHOLD: u32 = 3;
-
 /// Response to a `z_viewtransaction` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 pub(crate) type ResultType = Transaction;
@@ -105,6 +107,13 @@ pub(crate) struct Transaction {
     #[serde(skip_serializing_if = "Option::is_none")]
     fee: Option<JsonZec>,
 
+    /// The fee paid by the transaction, in zatoshis.
+    ///
+    /// Omitted if `fee` is omitted.
+    #[serde(rename = "feeZat")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee_zat: Option<u64>,
+
     /// Set to `true` if this is a coinbase transaction, omitted otherwise.
     #[serde(skip_serializing_if = "Option::is_none")]
     generated: Option<bool>,
@@ -115,6 +124,12 @@ pub(crate) struct Transaction {
     /// The outputs of the transaction that the wallet is capable of viewing.
     outputs: Vec<Output>,
 
+    /// The IDs of wallet transactions that spend any of the same inputs as this
+    /// transaction.
+    ///
+    /// If one of them is mined, this transaction can never be mined.
+    walletconflicts: Vec<String>,
+
     /// A map from an involved account's UUID to the effects of this transaction on it.
     #[cfg(zallet_build = "wallet")]
     accounts: BTreeMap<String, AccountEffect>,
@@ -275,6 +290,8 @@ struct AccountEffect {
     delta_zat: i64,
 }
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
 pub(super) const PARAM_TXID_DESC: &str = "The ID of the transaction to view.";
 
 pub(crate) async fn call(
@@ -289,12 +306,12 @@ pub(crate) async fn call(
     //       https://github.com/zcash/wallet/issues/237
     let chain_height = wallet
         .chain_height()
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+        .map_err(ComponentFailure::database)?
         .ok_or_else(|| LegacyCode::InWarmup.with_static("Wait for the wallet to start up"))?;
 
     let tx = wallet
         .get_transaction(txid)
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+        .map_err(ComponentFailure::database)?
         .ok_or(
             LegacyCode::InvalidAddressOrKey.with_static("Invalid or non-wallet transaction id"),
         )?;
@@ -312,7 +329,7 @@ pub(crate) async fn call(
     // Collect account IDs for transparent coin relevance detection.
     let account_ids = wallet
         .get_account_ids()
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
+        .map_err(ComponentFailure::database)?;
 
     // Collect viewing keys for recovering output information.
     // - OVKs are used cross-protocol and thus are collected as byte arrays.
@@ -321,7 +338,7 @@ pub(crate) async fn call(
     let mut ovks = vec![];
     for (account_id, ufvk) in wallet
         .get_unified_full_viewing_keys()
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+        .map_err(ComponentFailure::database)?
     {
         if let Some(t) = ufvk.transparent() {
             let (internal_ovk, external_ovk) = t.ovks_for_shielding();
@@ -387,7 +404,7 @@ pub(crate) async fn call(
             })
             .optional()
             .map_err(|e| {
-                LegacyCode::Database.with_message(format!("Failed to fetch spent note: {e:?}"))
+                ComponentFailure::database(format!("Failed to fetch spent note: {e}")).into()
             })
     }
 
@@ -423,7 +440,7 @@ pub(crate) async fn call(
             // Allow the `sent_notes` table to not be populated.
             .optional()
             .map_err(|e| {
-                LegacyCode::Database.with_message(format!("Failed to fetch sent-to address: {e}"))
+                ComponentFailure::database(format!("Failed to fetch sent-to address: {e}"))
             })?
             // If we don't have a cached recipient, fall back on an address that
             // corresponds to the actual receiver.
@@ -478,6 +495,8 @@ pub(crate) async fn call(
 
                 transparent_input_values.insert(input.prevout(), value);
 
+                let (value, value_zat) = zec_and_zat(value);
+
                 spends.push(Spend {
                     pool: POOL_TRANSPARENT,
                     t_in: Some(idx),
@@ -489,8 +508,8 @@ pub(crate) async fn call(
                     action_prev: None,
                     account_uuid,
                     address,
-                    value: value_from_zatoshis(value),
-                    value_zat: value.into_u64(),
+                    value,
+                    value_zat,
                 });
             }
         }
@@ -503,6 +522,8 @@ pub(crate) async fn call(
                 output_with_nullifier(wallet, ShieldedProtocol::Sapling, spend.nullifier().0)?;
 
             if let Some((txid_prev, output_prev, account_id, address, value)) = spent_note {
+                let (value, value_zat) = zec_and_zat(value);
+
                 spends.push(Spend {
                     pool: POOL_SAPLING,
                     t_in: None,
@@ -514,8 +535,8 @@ pub(crate) async fn call(
                     action_prev: None,
                     account_uuid: Some(account_id.expose_uuid().to_string()),
                     address,
-                    value: value_from_zatoshis(value),
-                    value_zat: value.into_u64(),
+                    value,
+                    value_zat,
                 });
             }
         }
@@ -530,6 +551,8 @@ pub(crate) async fn call(
             )?;
 
             if let Some((txid_prev, action_prev, account_id, address, value)) = spent_note {
+                let (value, value_zat) = zec_and_zat(value);
+
                 spends.push(Spend {
                     pool: POOL_ORCHARD,
                     t_in: None,
@@ -541,8 +564,8 @@ pub(crate) async fn call(
                     action_prev: Some(action_prev),
                     account_uuid: Some(account_id.expose_uuid().to_string()),
                     address,
-                    value: value_from_zatoshis(value),
-                    value_zat: value.into_u64(),
+                    value,
+                    value_zat,
                 });
             }
         }
@@ -587,6 +610,8 @@ pub(crate) async fn call(
                     }
                 };
 
+            let (value, value_zat) = zec_and_zat(output.value());
+
             outputs.push(Output {
                 pool: POOL_TRANSPARENT,
                 t_out: Some(idx),
@@ -596,8 +621,8 @@ pub(crate) async fn call(
                 address,
                 outgoing,
                 wallet_internal,
-                value: value_from_zatoshis(output.value()),
-                value_zat: output.value().into_u64(),
+                value,
+                value_zat,
                 memo: None,
                 memo_str: None,
             });
@@ -699,6 +724,8 @@ pub(crate) async fn call(
                 };
                 let memo = Some(hex::encode(memo));
 
+                let (value, value_zat) = zec_and_zat(value);
+
                 outputs.push(Output {
                     pool: POOL_SAPLING,
                     t_out: None,
@@ -708,8 +735,8 @@ pub(crate) async fn call(
                     address,
                     outgoing,
                     wallet_internal,
-                    value: value_from_zatoshis(value),
-                    value_zat: value.into_u64(),
+                    value,
+                    value_zat,
                     memo,
                     memo_str,
                 });
@@ -812,6 +839,8 @@ pub(crate) async fn call(
                 };
                 let memo = Some(hex::encode(memo));
 
+                let (value, value_zat) = zec_and_zat(value);
+
                 outputs.push(Output {
                     pool: POOL_ORCHARD,
                     t_out: None,
@@ -821,8 +850,8 @@ pub(crate) async fn call(
                     address,
                     outgoing,
                     wallet_internal,
-                    value: value_from_zatoshis(value),
-                    value_zat: value.into_u64(),
+                    value,
+                    value_zat,
                     memo,
                     memo_str,
                 });
@@ -832,13 +861,31 @@ pub(crate) async fn call(
 
     let wallet_tx_info = WalletTxInfo::fetch(wallet, &chain, &tx, chain_height)
         .await
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
-
-    let fee = tx
-        .fee_paid(|prevout| Ok::<_, BalanceError>(transparent_input_values.get(prevout).copied()))
-        // This should never occur, as a transaction that violated balance would be
-        // rejected by the backing full node.
-        .map_err(|e| LegacyCode::Database.with_message(format!("Failed to compute fee: {e}")))?;
+        .map_err(ComponentFailure::database)?;
+
+    let walletconflicts = wallet
+        .with_raw(|conn, _| wallet_conflicts(conn, &[txid]))
+        .map_err(ComponentFailure::database)?
+        .remove(&txid)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|txid| txid.to_string())
+        .collect();
+
+    // Prefer the fee that the wallet reports for the transaction, so that it matches the
+    // fee reported by `z_listtransactions`. If the wallet does not know it, fall back to
+    // the transparent input values that we looked up above.
+    let fee = match wallet.tx_fee(&txid).map_err(ComponentFailure::database)? {
+        Some(fee) => Some(fee),
+        None => tx
+            .fee_paid(|prevout| {
+                Ok::<_, BalanceError>(transparent_input_values.get(prevout).copied())
+            })
+            // This should never occur, as a transaction that violated balance would be
+            // rejected by the backing full node.
+            .map_err(|e| ComponentFailure::internal(format!("Failed to compute fee: {e}")))?,
+    };
+    let (fee, fee_zat) = fee.map(zec_and_zat).unzip();
 
     #[cfg(zallet_build = "wallet")]
     let accounts = wallet.with_raw(|conn, _| {
@@ -848,7 +895,7 @@ pub(crate) async fn call(
                 FROM v_transactions
                 WHERE txid = :txid",
             )
-            .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
+            .map_err(ComponentFailure::database)?;
         stmt.query_map(
             named_params! {
                 ":txid": txid.as_ref(),
@@ -860,18 +907,16 @@ pub(crate) async fn call(
                 ))
             },
         )
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+        .map_err(ComponentFailure::database)?
         .map(|res| {
-            res.map_err(|e| LegacyCode::Database.with_message(e.to_string()))
-                .and_then(|(account_id, delta_zat)| {
-                    let delta = ZatBalance::from_i64(delta_zat)
-                        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
+            res.map_err(ComponentFailure::database)
+                .and_then(|(account_id, delta)| {
+                    let (delta, delta_zat) = zec_and_zat_balance(
+                        ZatBalance::from_i64(delta).map_err(ComponentFailure::database)?,
+                    );
                     Ok((
                         account_id.expose_uuid().to_string(),
-                        AccountEffect {
-                            delta: value_from_zat_balance(delta),
-                            delta_zat,
-                        },
+                        AccountEffect { delta, delta_zat },
                     ))
                 })
         })
@@ -887,10 +932,12 @@ pub(crate) async fn call(
         blocktime: wallet_tx_info.blocktime,
         version: tx.version().header() & 0x7FFFFFFF,
         expiryheight: wallet_tx_info.expiryheight,
-        fee: fee.map(value_from_zatoshis),
+        fee,
+        fee_zat,
         generated: wallet_tx_info.generated,
         spends,
         outputs,
+        walletconflicts,
         #[cfg(zallet_build = "wallet")]
         accounts,
     })
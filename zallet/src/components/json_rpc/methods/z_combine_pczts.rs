@@ -0,0 +1,42 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use pczt::roles::combiner::Combiner;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::components::json_rpc::{
+    pczts::{encode, parse},
+    server::LegacyCode,
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_combinepczts` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The combined PCZT, base64-encoded.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(String);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_PCZTS_DESC: &str = "An array of base64-encoded PCZTs to combine.";
+pub(super) const PARAM_PCZTS_REQUIRED: bool = true;
+
+pub(crate) fn call(pczts: Vec<String>) -> Response {
+    if pczts.is_empty() {
+        return Err(LegacyCode::InvalidParameter.with_static("Missing PCZTs to combine"));
+    }
+
+    let pczts = pczts
+        .iter()
+        .map(|pczt| parse(pczt))
+        .collect::<RpcResult<Vec<_>>>()?;
+
+    let combined = Combiner::new(pczts).combine().map_err(|e| {
+        LegacyCode::InvalidParameter.with_message(format!("Failed to combine PCZTs: {e:?}"))
+    })?;
+
+    Ok(ResultType(encode(combined)))
+}
@@ -0,0 +1,139 @@
+use std::convert::Infallible;
+
+use abscissa_core::Application;
+use documented::Documented;
+use jsonrpsee::core::{JsonValue, RpcResult};
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_client_backend::{data_api::wallet::create_pczt_from_proposal, wallet::OvkPolicy};
+use zcash_protocol::{consensus::BlockHeight, value::Zatoshis};
+
+use crate::{
+    components::{
+        database::DbHandle,
+        json_rpc::{
+            fees::FeeLimit,
+            payments::{enforce_privacy_policy, reserve_inputs},
+            pczts::{self, PendingPczts},
+            send_error::SendFailure,
+            server::LegacyCode,
+            utils::{JsonZec, parse_account_parameter, zec_and_zat},
+        },
+        keystore::KeyStore,
+    },
+    prelude::*,
+};
+
+use super::{
+    MethodCategory,
+    z_send_many::{
+        self, check_fees, check_orchard_actions, parse_confirmations_policy, parse_payments,
+        parse_privacy_policy, propose, transaction_request,
+    },
+};
+
+/// Response to a `z_createpczt` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = CreatedPczt;
+
+/// An unproven and unsigned PCZT.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct CreatedPczt {
+    /// The PCZT, base64-encoded.
+    pczt: String,
+
+    /// The ID of the reservation of the PCZT's inputs, which can be passed to
+    /// `z_finalizepczt` to release them.
+    pczt_id: u64,
+
+    /// The height after which the PCZT's transaction will expire if it has not been
+    /// mined.
+    expiryheight: u32,
+
+    /// The fee that the transaction pays, in ZEC.
+    fee: JsonZec,
+
+    /// The value of `fee` in zatoshis.
+    #[serde(rename = "feeZat")]
+    fee_zat: u64,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_ACCOUNT_DESC: &str =
+    "The UUID, name, or ZIP 32 account index of the account to send funds from.";
+pub(super) const PARAM_RECIPIENTS_DESC: &str =
+    "An array of JSON objects representing the amounts to send.";
+pub(super) const PARAM_RECIPIENTS_REQUIRED: bool = true;
+pub(super) const PARAM_MINCONF_DESC: &str = "Only use funds confirmed at least this many times.";
+pub(super) const PARAM_PRIVACY_POLICY_DESC: &str =
+    "Policy for what information leakage is acceptable.";
+
+pub(crate) async fn call(
+    mut wallet: DbHandle,
+    keystore: KeyStore,
+    pending: PendingPczts,
+    account: JsonValue,
+    recipients: Vec<z_send_many::AmountParameter>,
+    minconf: Option<u32>,
+    privacy_policy: Option<String>,
+) -> Response {
+    let account_id = parse_account_parameter(wallet.as_ref(), &keystore, &account).await?;
+
+    let payments = parse_payments(&recipients)?;
+    let privacy_policy = parse_privacy_policy(privacy_policy.as_deref())?;
+    let confirmations_policy = parse_confirmations_policy(minconf);
+
+    let request = transaction_request(payments)?;
+    let repropose = move |wallet: &mut DbHandle| -> RpcResult<_> {
+        let proposal = propose(wallet, account_id, request.clone(), confirmations_policy)?;
+        enforce_privacy_policy(&proposal, privacy_policy)?;
+        check_orchard_actions(
+            &proposal,
+            APP.config().builder.limits.orchard_actions().into(),
+        )?;
+        check_fees(&proposal, FeeLimit::from_config(&APP.config().builder))?;
+        Ok(proposal)
+    };
+    let proposal = repropose(&mut wallet)?;
+
+    // A PCZT contains a single transaction, so we can't create one for a proposal that
+    // needs to first shield transparent funds.
+    if proposal.steps().len() > 1 {
+        return Err(LegacyCode::Wallet.with_static(
+            "Error: Sending these funds requires more than one transaction, which a single PCZT cannot contain.",
+        ));
+    }
+
+    let (proposal, reservation) = reserve_inputs(&mut wallet, proposal, repropose).await?;
+
+    let params = *wallet.params();
+    let pczt = create_pczt_from_proposal::<_, _, Infallible, _, Infallible, _>(
+        wallet.as_mut(),
+        &params,
+        account_id,
+        OvkPolicy::Sender,
+        &proposal,
+    )
+    .map_err(|e| SendFailure::from_wallet_error(&e))?;
+
+    let (fee, fee_zat) = zec_and_zat(
+        proposal
+            .steps()
+            .iter()
+            .try_fold(Zatoshis::ZERO, |acc, step| {
+                acc + step.balance().fee_required()
+            })
+            .ok_or_else(|| LegacyCode::Wallet.with_static("Proposal fee overflowed"))?,
+    );
+
+    let expiry_height = *pczt.global().expiry_height();
+
+    Ok(CreatedPczt {
+        pczt: pczts::encode(pczt),
+        pczt_id: pending.hold(reservation, BlockHeight::from_u32(expiry_height)),
+        expiryheight: expiry_height,
+        fee,
+        fee_zat,
+    })
+}
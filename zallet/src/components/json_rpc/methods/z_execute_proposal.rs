@@ -0,0 +1,132 @@
+use abscissa_core::Application;
+use jsonrpsee::core::RpcResult;
+use secrecy::ExposeSecret;
+use serde_json::json;
+use zaino_state::FetchServiceSubscriber;
+use zcash_client_backend::data_api::{Account, WalletRead};
+
+use crate::{
+    components::{
+        database::DbHandle,
+        json_rpc::{
+            asyncop::{ContextInfo, OperationId},
+            fees::FeeLimit,
+            payments::{SendResult, reserve_inputs},
+            server::{ComponentFailure, LegacyCode},
+        },
+        keystore::KeyStore,
+    },
+    prelude::*,
+};
+
+use super::{
+    MethodCategory,
+    z_propose_transfer::SerializedProposal,
+    z_send_many::{check_fees, check_orchard_actions, send_proposal, spending_key},
+};
+
+/// Response to a `z_executeproposal` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = OperationId;
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_PROPOSAL_DESC: &str = "A proposal returned by z_proposetransfer.";
+
+pub(crate) async fn call(
+    mut wallet: DbHandle,
+    keystore: KeyStore,
+    chain: FetchServiceSubscriber,
+    proposal: String,
+) -> RpcResult<(
+    Option<ContextInfo>,
+    impl Future<Output = RpcResult<SendResult>>,
+)> {
+    let key = wallet.proposal_key().map_err(ComponentFailure::database)?;
+    let stored = SerializedProposal::decode(&proposal, key.expose_secret()).ok_or_else(|| {
+        LegacyCode::Deserialization.with_static(
+            "Invalid parameter, proposal is malformed, was modified, or was not created by this wallet.",
+        )
+    })?;
+
+    let chain_height = wallet
+        .chain_height()
+        .map_err(ComponentFailure::database)?
+        .ok_or_else(|| LegacyCode::InWarmup.with_static("Wallet sync required"))?;
+
+    if chain_height + 1 > stored.expiry_height {
+        return Err(LegacyCode::Wallet.with_message(format!(
+            "Proposal expired: it could only be executed up to height {}, and the chain is at height {chain_height}.",
+            stored.expiry_height,
+        )));
+    }
+
+    // The proposal's shielded inputs can only be spent with the anchors it selected if
+    // those blocks are still in the main chain.
+    for (height, hash) in &stored.anchors {
+        let block = wallet
+            .block_metadata(*height)
+            .map_err(ComponentFailure::database)?;
+        if block.is_none_or(|block| block.block_hash() != *hash) {
+            return Err(LegacyCode::Wallet.with_message(format!(
+                "Proposal is no longer valid: the block at its anchor height {height} has been reorganized out of the chain.",
+            )));
+        }
+    }
+
+    let (account_id, expiry_height) = (stored.account_id, stored.expiry_height);
+
+    // This fails if any input has since been spent or is otherwise no longer spendable.
+    // We never select replacement inputs, as that would change what was approved.
+    let revalidate = move |wallet: &mut DbHandle| {
+        stored
+            .proposal
+            .try_into_standard_proposal(wallet.as_ref())
+            .map_err(|e| {
+                LegacyCode::Wallet.with_message(format!("Proposal is no longer valid: {e}"))
+            })
+    };
+    let proposal = revalidate(&mut wallet)?;
+
+    check_orchard_actions(
+        &proposal,
+        APP.config().builder.limits.orchard_actions().into(),
+    )?;
+    check_fees(&proposal, FeeLimit::from_config(&APP.config().builder))?;
+
+    let account = wallet
+        .get_account(account_id)
+        .map_err(ComponentFailure::database)?
+        .ok_or_else(|| {
+            LegacyCode::Wallet
+                .with_static("Proposal is no longer valid: its account does not exist.")
+        })?;
+
+    let derivation = account.source().key_derivation().ok_or_else(|| {
+        LegacyCode::InvalidAddressOrKey
+            .with_static("Invalid proposal, account has no spending key.")
+    })?;
+
+    let usk = spending_key(&keystore, wallet.params(), derivation).await?;
+
+    let broadcast = APP.config().external.broadcast();
+
+    Ok((
+        Some(ContextInfo::new(
+            "z_executeproposal",
+            json!({
+                "account_uuid": account_id.expose_uuid().to_string(),
+                "expiryheight": u32::from(expiry_height),
+            }),
+        )),
+        async move {
+            // If another operation is spending any of the proposal's inputs, this waits
+            // for it to finish and then checks that the proposal is still valid.
+            let (proposal, _reservation) =
+                reserve_inputs(&mut wallet, proposal, revalidate).await?;
+            send_proposal(wallet, &keystore, chain, &usk, proposal, broadcast)
+                .await
+                .map(|(_, _, result)| result)
+        },
+    ))
+}
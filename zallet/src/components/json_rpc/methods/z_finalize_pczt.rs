@@ -0,0 +1,69 @@
+use abscissa_core::Application;
+use jsonrpsee::core::RpcResult;
+use zaino_state::FetchServiceSubscriber;
+use zcash_client_backend::data_api::wallet::extract_and_store_transaction_from_pczt;
+use zcash_client_sqlite::ReceivedNoteId;
+use zcash_proofs::prover::LocalTxProver;
+
+use crate::{
+    components::{
+        database::DbHandle,
+        json_rpc::{
+            payments::{SendResult, broadcast_transactions},
+            pczts::{self, ORCHARD_VERIFYING_KEY, PendingPczts},
+            server::LegacyCode,
+        },
+    },
+    prelude::*,
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_finalizepczt` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = SendResult;
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_PCZT_DESC: &str = "A base64-encoded PCZT with its proofs and signatures.";
+pub(super) const PARAM_PCZT_ID_DESC: &str =
+    "The ID returned by z_createpczt, to release the reservation of the PCZT's inputs.";
+
+pub(crate) async fn call(
+    mut wallet: DbHandle,
+    chain: FetchServiceSubscriber,
+    pending: PendingPczts,
+    pczt: String,
+    pczt_id: Option<u64>,
+) -> Response {
+    let pczt = pczts::parse(&pczt)?;
+
+    let (wallet, txid) = crate::spawn_blocking!("z_finalizepczt verifier", move || {
+        let (spend_vk, output_vk) = LocalTxProver::bundled().verifying_keys();
+        extract_and_store_transaction_from_pczt::<_, ReceivedNoteId>(
+            wallet.as_mut(),
+            pczt,
+            Some((&spend_vk.prepare(), &output_vk.prepare())),
+            Some(&ORCHARD_VERIFYING_KEY),
+        )
+        .map(|txid| (wallet, txid))
+        .map_err(|e| format!("Failed to finalize PCZT: {e}"))
+    })
+    .await
+    .map_err(|e| LegacyCode::Misc.with_message(e.to_string()))?
+    .map_err(|e| LegacyCode::Verify.with_message(e))?;
+
+    // The transaction is now stored in the wallet, so its inputs will not be selected
+    // again.
+    if let Some(id) = pczt_id {
+        pending.release(id);
+    }
+
+    broadcast_transactions(
+        &wallet,
+        chain,
+        vec![txid],
+        APP.config().external.broadcast(),
+    )
+    .await
+}
@@ -0,0 +1,241 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_client_backend::data_api::WalletRead;
+use zcash_protocol::consensus::BlockHeight;
+
+use crate::components::{
+    chain::Chain,
+    database::DbConnection,
+    json_rpc::server::ComponentFailure,
+    sync::{SyncProgress, SyncStatus},
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_getsyncstatus` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = WalletSyncStatus;
+
+/// How far the wallet has synced with the chain.
+#[derive(Clone, Debug, PartialEq, Serialize, Documented, JsonSchema)]
+pub(crate) struct WalletSyncStatus {
+    /// The state of wallet sync: `connecting`, `initializing`, `scanning`, `rescanning`,
+    /// or `synced`.
+    state: &'static str,
+
+    /// The height below which the wallet has scanned every block.
+    ///
+    /// Omitted if the wallet has not fully scanned any blocks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fully_scanned_height: Option<u32>,
+
+    /// The height of the latest chain tip known to the wallet.
+    ///
+    /// Omitted while the state is `connecting` or `initializing`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chain_tip_height: Option<u32>,
+
+    /// The estimated percentage of the wallet's history, from its birthday to the chain
+    /// tip, that has been scanned.
+    ///
+    /// Omitted while the state is `connecting` or `initializing`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress: Option<f64>,
+
+    /// The number of blocks that are left to scan.
+    ///
+    /// Omitted while the state is `connecting` or `initializing`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocks_remaining: Option<u32>,
+
+    /// The number of blocks scanned per second, over the last minute.
+    scan_rate: f64,
+
+    /// The address of the chain source that the wallet syncs from, as configured in
+    /// `indexer.validator_address` or `indexer.fallback_validator_addresses`.
+    backend: String,
+
+    /// The time (in seconds since the Unix epoch) at which Zallet last switched to a
+    /// different chain source.
+    ///
+    /// Omitted if Zallet has not switched chain sources since it started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_failover: Option<u64>,
+
+    /// The rescan requested with `rescanblockchain` that is in progress.
+    ///
+    /// Omitted if the wallet is not rescanning.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rescan: Option<RescanStatus>,
+}
+
+/// The progress of a rescan.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+pub(crate) struct RescanStatus {
+    /// The height of the first block being rescanned.
+    start_height: u32,
+
+    /// The height of the last block being rescanned.
+    stop_height: u32,
+
+    /// The number of seconds since the rescan started.
+    duration: u64,
+
+    /// The percentage of the blocks being rescanned that have been scanned.
+    progress: f64,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(crate) fn call(wallet: &DbConnection, chain: &Chain, sync_status: &SyncStatus) -> Response {
+    let birthday = wallet
+        .get_wallet_birthday()
+        .map_err(ComponentFailure::database)?;
+    let backends = chain.backends();
+    let backend = backends.active().clone();
+
+    let mut status = match sync_status.progress() {
+        Some(progress) => {
+            // The chain source may have reported a newer chain tip than the wallet has
+            // seen so far.
+            let backend_tip = backend.tip.map(|(height, _)| height);
+            scanning(progress, backend_tip, birthday)
+        }
+        None => WalletSyncStatus::starting(chain.is_reachable()),
+    };
+    status.scan_rate = (sync_status.scan_rate() * 100.0).round() / 100.0;
+    status.backend = backend.address;
+    status.last_failover = backends.last_failover_time();
+
+    if let Some(rescan) = sync_status.rescan() {
+        let progress = rescan
+            .progress(wallet)
+            .map_err(ComponentFailure::database)?;
+        if status.state == "scanning" || status.state == "synced" {
+            status.state = "rescanning";
+        }
+        status.rescan = Some(RescanStatus {
+            start_height: (*rescan.heights.start()).into(),
+            stop_height: (*rescan.heights.end()).into(),
+            duration: rescan.started.elapsed().as_secs(),
+            progress: (progress * 10_000.0).floor() / 100.0,
+        });
+    }
+
+    Ok(status)
+}
+
+impl WalletSyncStatus {
+    /// The status of a wallet that has not yet recorded any sync progress.
+    fn starting(chain_reachable: bool) -> Self {
+        Self {
+            state: if chain_reachable {
+                "initializing"
+            } else {
+                "connecting"
+            },
+            fully_scanned_height: None,
+            chain_tip_height: None,
+            progress: None,
+            blocks_remaining: None,
+            scan_rate: 0.0,
+            backend: String::new(),
+            last_failover: None,
+            rescan: None,
+        }
+    }
+}
+
+/// Returns the status of a wallet that has recorded its sync progress.
+fn scanning(
+    progress: SyncProgress,
+    backend_tip: Option<BlockHeight>,
+    birthday: Option<BlockHeight>,
+) -> WalletSyncStatus {
+    let chain_tip = backend_tip.map_or(progress.chain_tip, |tip| tip.max(progress.chain_tip));
+    // The wallet scans from its birthday, so nothing below it needs scanning.
+    let start = birthday.map_or(BlockHeight::from_u32(0), |birthday| {
+        birthday.saturating_sub(1)
+    });
+    let scanned_to = progress
+        .fully_scanned
+        .map_or(start, |height| height.max(start));
+
+    let total = u32::from(chain_tip).saturating_sub(start.into());
+    let blocks_remaining = u32::from(chain_tip).saturating_sub(scanned_to.into());
+    let percentage = if total == 0 {
+        100.0
+    } else {
+        let scanned = f64::from(total - blocks_remaining) / f64::from(total);
+        (scanned * 10_000.0).floor() / 100.0
+    };
+
+    WalletSyncStatus {
+        state: if blocks_remaining == 0 {
+            "synced"
+        } else {
+            "scanning"
+        },
+        fully_scanned_height: progress.fully_scanned.map(u32::from),
+        chain_tip_height: Some(chain_tip.into()),
+        progress: Some(percentage),
+        blocks_remaining: Some(blocks_remaining),
+        scan_rate: 0.0,
+        backend: String::new(),
+        last_failover: None,
+        rescan: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::consensus::BlockHeight;
+
+    use super::{WalletSyncStatus, scanning};
+    use crate::components::sync::SyncProgress;
+
+    fn progress(chain_tip: u32, fully_scanned: Option<u32>) -> SyncProgress {
+        SyncProgress {
+            chain_tip: BlockHeight::from_u32(chain_tip),
+            fully_scanned: fully_scanned.map(BlockHeight::from_u32),
+        }
+    }
+
+    #[test]
+    fn states() {
+        assert_eq!(WalletSyncStatus::starting(false).state, "connecting");
+        assert_eq!(WalletSyncStatus::starting(true).state, "initializing");
+
+        // Progress is measured from the wallet's birthday.
+        let birthday = Some(BlockHeight::from_u32(1001));
+        let status = scanning(progress(2000, Some(1250)), None, birthday);
+        assert_eq!(status.state, "scanning");
+        assert_eq!(status.fully_scanned_height, Some(1250));
+        assert_eq!(status.blocks_remaining, Some(750));
+        assert_eq!(status.progress, Some(25.0));
+
+        // Nothing has been scanned yet.
+        let status = scanning(progress(2000, None), None, birthday);
+        assert_eq!(status.fully_scanned_height, None);
+        assert_eq!(status.blocks_remaining, Some(1000));
+        assert_eq!(status.progress, Some(0.0));
+
+        // A newer chain tip reported by the chain source is not yet scanned.
+        let status = scanning(
+            progress(2000, Some(2000)),
+            Some(BlockHeight::from_u32(2001)),
+            birthday,
+        );
+        assert_eq!(status.state, "scanning");
+        assert_eq!(status.chain_tip_height, Some(2001));
+        assert_eq!(status.blocks_remaining, Some(1));
+        assert_eq!(status.progress, Some(99.9));
+
+        let status = scanning(progress(2000, Some(2000)), None, birthday);
+        assert_eq!(status.state, "synced");
+        assert_eq!(status.blocks_remaining, Some(0));
+        assert_eq!(status.progress, Some(100.0));
+    }
+}
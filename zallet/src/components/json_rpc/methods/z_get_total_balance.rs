@@ -1,17 +1,23 @@
-use std::num::NonZeroU32;
+use std::collections::HashSet;
 
 use documented::Documented;
 use jsonrpsee::core::RpcResult;
 use schemars::JsonSchema;
 use serde::Serialize;
-use zcash_client_backend::data_api::{WalletRead, wallet::ConfirmationsPolicy};
-use zcash_protocol::value::Zatoshis;
+use zcash_client_backend::data_api::WalletRead;
+use zcash_client_sqlite::AccountUuid;
+use zcash_protocol::{PoolType, ShieldedProtocol, value::Zatoshis};
 
 use crate::components::{
-    database::DbConnection,
-    json_rpc::{server::LegacyCode, utils::value_from_zatoshis},
+    database::{DbConnection, account_balances},
+    json_rpc::{
+        server::{ComponentFailure, LegacyCode},
+        utils::{watch_only_accounts, zec_and_zat},
+    },
 };
 
+use super::MethodCategory;
+
 /// Response to a `z_gettotalbalance` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 pub(crate) type ResultType = TotalBalance;
@@ -22,63 +28,146 @@ pub(crate) struct TotalBalance {
     /// The total value of unspent transparent outputs, in ZEC
     transparent: String,
 
+    /// The value of `transparent` in zatoshis.
+    #[serde(rename = "transparentZat")]
+    transparent_zat: u64,
+
     /// The total value of unspent Sapling and Orchard outputs, in ZEC
     private: String,
 
+    /// The value of `private` in zatoshis.
+    #[serde(rename = "privateZat")]
+    private_zat: u64,
+
+    /// The total value of unspent Sapling outputs, in ZEC
+    sapling: String,
+
+    /// The value of `sapling` in zatoshis.
+    #[serde(rename = "saplingZat")]
+    sapling_zat: u64,
+
+    /// The total value of unspent Orchard outputs, in ZEC
+    orchard: String,
+
+    /// The value of `orchard` in zatoshis.
+    #[serde(rename = "orchardZat")]
+    orchard_zat: u64,
+
+    /// The total value of unspent Sprout notes, in ZEC
+    ///
+    /// Zallet cannot spend Sprout notes, so they are not included in `private` or `total`.
+    /// Omitted unless the wallet was migrated from a `zcashd` wallet that held Sprout
+    /// notes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sprout: Option<String>,
+
+    /// The value of `sprout` in zatoshis.
+    #[serde(rename = "sproutZat", skip_serializing_if = "Option::is_none")]
+    sprout_zat: Option<u64>,
+
     /// The total value of unspent shielded and transparent outputs, in ZEC
     total: String,
+
+    /// The value of `total` in zatoshis.
+    #[serde(rename = "totalZat")]
+    total_zat: u64,
 }
 
-pub(super) const PARAM_MINCONF_DESC: &str =
-    "Only include notes in transactions confirmed at least this many times.";
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_MINCONF_DESC: &str = "Only include notes in transactions confirmed at least this many times. If 0, funds in unmined transactions are included.";
 pub(super) const PARAM_INCLUDE_WATCHONLY_DESC: &str =
     "Also include balance in watchonly addresses.";
 
+/// Whether `include_watchonly` is `true` if omitted, as in `zcashd`.
+pub(super) const INCLUDE_WATCHONLY_DEFAULT: bool = false;
+
 pub(crate) fn call(
     wallet: &DbConnection,
     minconf: Option<u32>,
     include_watchonly: Option<bool>,
 ) -> Response {
-    match include_watchonly {
-        Some(true) => Ok(()),
-        None | Some(false) => Err(LegacyCode::Misc
-            .with_message("include_watchonly argument must be set to true (for now)")),
-    }?;
-
-    let confirmations_policy = match minconf {
-        Some(minconf) => match NonZeroU32::new(minconf) {
-            Some(c) => ConfirmationsPolicy::new_symmetrical(c, false),
-            None => ConfirmationsPolicy::new_symmetrical(NonZeroU32::MIN, true),
-        },
-        None => ConfirmationsPolicy::new_symmetrical(NonZeroU32::MIN, false),
+    let minconf = minconf.unwrap_or(1);
+
+    let watch_only = match include_watchonly.unwrap_or(INCLUDE_WATCHONLY_DEFAULT) {
+        true => HashSet::new(),
+        false => watch_only_accounts(wallet)?,
     };
 
-    let (transparent, private) = if let Some(summary) = wallet
-        .get_wallet_summary(confirmations_policy)
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
-    {
-        // TODO: support `include_watch_only = false`
-        summary.account_balances().iter().fold(
-            (Some(Zatoshis::ZERO), Some(Zatoshis::ZERO)),
-            |(transparent, private), (_, balance)| {
-                (
-                    transparent + balance.unshielded_balance().total(),
-                    private + balance.sapling_balance().total() + balance.orchard_balance().total(),
-                )
-            },
-        )
-    } else {
-        (Some(Zatoshis::ZERO), Some(Zatoshis::ZERO))
+    // The balances are read from the balance aggregates, rather than by summing every
+    // unspent output. Funds without `minconf` confirmations, and immature coinbase
+    // outputs, are not counted.
+    let account_balances = match wallet.chain_height().map_err(ComponentFailure::database)? {
+        Some(tip) => wallet
+            .with_raw(|conn, _| account_balances(conn, tip, minconf))
+            .map_err(ComponentFailure::database)?,
+        None => vec![],
     };
 
-    transparent
-        .zip(private)
-        .and_then(|(transparent, private)| {
-            (transparent + private).map(|total| TotalBalance {
-                transparent: value_from_zatoshis(transparent).to_string(),
-                private: value_from_zatoshis(private).to_string(),
-                total: value_from_zatoshis(total).to_string(),
-            })
+    let overflow = || LegacyCode::Wallet.with_static("balance overflow");
+    let mut transparent = Zatoshis::ZERO;
+    let mut sapling = Zatoshis::ZERO;
+    let mut orchard = Zatoshis::ZERO;
+    for (account_uuid, pool, balance) in account_balances {
+        if watch_only.contains(&AccountUuid::from_uuid(account_uuid)) {
+            continue;
+        }
+        let total = match pool {
+            PoolType::Transparent => &mut transparent,
+            PoolType::Shielded(ShieldedProtocol::Sapling) => &mut sapling,
+            PoolType::Shielded(ShieldedProtocol::Orchard) => &mut orchard,
+        };
+        *total = (*total + balance.confirmed).ok_or_else(overflow)?;
+    }
+    let private = (sapling + orchard).ok_or_else(overflow)?;
+    let total = (transparent + private).ok_or_else(overflow)?;
+
+    let (sprout, sprout_zat) = wallet
+        .sprout_balance()
+        .map_err(ComponentFailure::database)?
+        .map(|balance| {
+            let (sprout, sprout_zat) = zec_and_zat(balance.value);
+            (sprout.to_string(), sprout_zat)
         })
-        .ok_or_else(|| LegacyCode::Wallet.with_static("balance overflow"))
+        .unzip();
+
+    // `zcashd` returns these amounts as strings.
+    let (transparent, transparent_zat) = zec_and_zat(transparent);
+    let (private, private_zat) = zec_and_zat(private);
+    let (sapling, sapling_zat) = zec_and_zat(sapling);
+    let (orchard, orchard_zat) = zec_and_zat(orchard);
+    let (total, total_zat) = zec_and_zat(total);
+    Ok(TotalBalance {
+        transparent: transparent.to_string(),
+        transparent_zat,
+        private: private.to_string(),
+        private_zat,
+        sapling: sapling.to_string(),
+        sapling_zat,
+        orchard: orchard.to_string(),
+        orchard_zat,
+        sprout,
+        sprout_zat,
+        total: total.to_string(),
+        total_zat,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::json_rpc::methods::testing::{TestWallet, error_code};
+
+    use super::call;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failures() {
+        let wallet = TestWallet::new().await;
+        assert!(call(&wallet.handle().await, None, Some(false)).is_ok());
+
+        wallet.drop_table("accounts").await;
+        assert_eq!(
+            error_code(call(&wallet.handle().await, None, Some(false))),
+            -20
+        );
+    }
 }
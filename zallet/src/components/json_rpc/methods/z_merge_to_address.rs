@@ -0,0 +1,830 @@
+use std::collections::HashSet;
+use std::convert::Infallible;
+
+use abscissa_core::Application;
+use documented::Documented;
+use jsonrpsee::{
+    core::{JsonValue, RpcResult},
+    types::ErrorObjectOwned,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::json;
+use transparent::address::TransparentAddress;
+use zaino_state::FetchServiceSubscriber;
+use zcash_address::ZcashAddress;
+use zcash_client_backend::{
+    data_api::{
+        Account as _, InputSource, MaxSpendMode, TargetValue, WalletRead,
+        wallet::{ConfirmationsPolicy, TargetHeight, propose_send_max_transfer},
+    },
+    fees::StandardFeeRule,
+    proposal::Proposal,
+};
+use zcash_client_sqlite::{AccountUuid, ReceivedNoteId};
+use zcash_keys::{address::Address, keys::UnifiedSpendingKey};
+use zcash_primitives::transaction::builder::DEFAULT_TX_EXPIRY_DELTA;
+use zcash_protocol::{
+    PoolType, ShieldedProtocol, TxId, consensus::BlockHeight, memo::MemoBytes, value::Zatoshis,
+};
+
+use crate::{
+    components::{
+        database::{DbConnection, DbHandle, ReservedInput},
+        json_rpc::{
+            asyncop::{ContextInfo, OperationId, OperationProgress},
+            batching::{BatchLimits, InputKind, plan_batches},
+            fees::{self, FeeLimit},
+            payments::{
+                SendResult, get_account_for_address, parse_memo, pool_name, reserve_inputs,
+            },
+            send_error::SendFailure,
+            server::{ComponentFailure, LegacyCode},
+            utils::{JsonZec, value_from_zatoshis},
+        },
+        keystore::KeyStore,
+    },
+    config::TX_EXPIRING_SOON_THRESHOLD,
+    prelude::*,
+};
+
+use super::{
+    MethodCategory,
+    z_send_many::{
+        check_fees, check_orchard_actions, parse_confirmations_policy, send_proposal, spending_key,
+    },
+    z_shield_coinbase::{
+        ShieldRequest, max_utxos, select_utxos, shielding_destination, total_value,
+    },
+};
+
+/// Response to a `z_mergetoaddress` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The inputs selected for merging, and the ID of the operation merging them.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct ResultType {
+    #[serde(flatten)]
+    selection: Selection,
+
+    /// An operation ID to pass to `z_getoperationstatus` or `z_getoperationresult`.
+    opid: OperationId,
+}
+
+/// The inputs selected for merging.
+///
+/// Callers can merge all of the funds by calling `z_mergetoaddress` again until
+/// `remainingUTXOs` and `remainingNotes` are both 0.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct Selection {
+    /// The number of spendable UTXOs that were not selected because of
+    /// `transparent_limit`.
+    #[serde(rename = "remainingUTXOs")]
+    remaining_utxos: usize,
+
+    /// The total value of the UTXOs that were not selected, in ZEC.
+    #[serde(rename = "remainingTransparentValue")]
+    remaining_transparent_value: JsonZec,
+
+    /// The number of spendable notes that were not selected because of `shielded_limit`
+    /// or the limits on a single transaction.
+    #[serde(rename = "remainingNotes")]
+    remaining_notes: usize,
+
+    /// The total value of the notes that were not selected, in ZEC.
+    #[serde(rename = "remainingShieldedValue")]
+    remaining_shielded_value: JsonZec,
+
+    /// The number of UTXOs being merged.
+    #[serde(rename = "mergingUTXOs")]
+    merging_utxos: usize,
+
+    /// The total value of the UTXOs being merged, in ZEC.
+    #[serde(rename = "mergingTransparentValue")]
+    merging_transparent_value: JsonZec,
+
+    /// The number of notes being merged.
+    #[serde(rename = "mergingNotes")]
+    merging_notes: usize,
+
+    /// The total value of the notes being merged, in ZEC.
+    #[serde(rename = "mergingShieldedValue")]
+    merging_shielded_value: JsonZec,
+}
+
+impl Selection {
+    /// Returns the result of a `z_mergetoaddress` call that started the given operation.
+    pub(crate) fn started(self, opid: OperationId) -> ResultType {
+        ResultType {
+            selection: self,
+            opid,
+        }
+    }
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_FROMADDRESSES_DESC: &str =
+    "The addresses to merge funds from, or the strings ANY_TADDR, ANY_SAPLING, or ANY_ORCHARD.";
+pub(super) const PARAM_FROMADDRESSES_REQUIRED: bool = true;
+pub(super) const PARAM_TOADDRESS_DESC: &str = "The address to send the merged funds to.";
+pub(super) const PARAM_FEE_DESC: &str =
+    "The most that the transaction may pay in fees, in ZEC. Defaults to builder.max_fee.";
+pub(super) const PARAM_TRANSPARENT_LIMIT_DESC: &str =
+    "The maximum number of UTXOs to merge, or 0 for builder.limits.transparent_inputs.";
+pub(super) const PARAM_SHIELDED_LIMIT_DESC: &str =
+    "The maximum number of notes to merge, or 0 for as many as fit in a transaction.";
+pub(super) const PARAM_MEMO_DESC: &str =
+    "A memo for the merged output, represented in hexadecimal string format.";
+pub(super) const PARAM_ALLOW_SPLIT_DESC: &str =
+    "If true, merge notes beyond the Orchard action limit in a chain of transactions.";
+
+/// The default value of `shielded_limit`, matching `zcashd`'s limit for Sapling notes.
+const DEFAULT_SHIELDED_LIMIT: usize = 200;
+
+/// A source of funds to merge.
+enum Source {
+    AnyTaddr,
+    Taddr(TransparentAddress),
+    AnyPool(ShieldedProtocol),
+    Account(AccountUuid, Vec<ShieldedProtocol>),
+}
+
+/// A spendable note that can be merged.
+struct Candidate {
+    input: ReservedInput,
+    pool: ShieldedProtocol,
+    value: Zatoshis,
+    /// Orders candidates by age, then by pool and outpoint.
+    key: (u32, u8, [u8; 32], u16),
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn call(
+    mut wallet: DbHandle,
+    keystore: KeyStore,
+    chain: FetchServiceSubscriber,
+    fromaddresses: Vec<String>,
+    toaddress: String,
+    fee: Option<JsonValue>,
+    transparent_limit: Option<u32>,
+    shielded_limit: Option<u32>,
+    memo: Option<String>,
+    allow_split: Option<bool>,
+) -> RpcResult<(
+    Selection,
+    (
+        Option<ContextInfo>,
+        impl Future<Output = RpcResult<SendResult>>,
+    ),
+)> {
+    let fee_limit = FeeLimit::from_param(fee.as_ref(), &APP.config().builder)?;
+    let allow_split = allow_split.unwrap_or(false);
+
+    if fromaddresses.is_empty() {
+        return Err(LegacyCode::InvalidParameter
+            .with_static("Invalid parameter, fromaddresses array is empty."));
+    }
+
+    let broadcast = APP.config().external.broadcast();
+    let sources = parse_sources(wallet.as_ref(), &fromaddresses)?;
+    let confirmations_policy = parse_confirmations_policy(None);
+
+    let context = ContextInfo::new(
+        "z_mergetoaddress",
+        json!({
+            "fromaddresses": fromaddresses,
+            "toaddress": toaddress,
+            "fee": fee,
+            "transparent_limit": transparent_limit,
+            "shielded_limit": shielded_limit,
+            "memo": memo,
+            "allow_split": allow_split,
+        }),
+    );
+    let memo = memo.as_deref().map(parse_memo).transpose()?;
+
+    // Transparent funds can only be spent by a shielding transaction, which cannot also
+    // spend notes.
+    let is_transparent = sources
+        .iter()
+        .all(|source| matches!(source, Source::AnyTaddr | Source::Taddr(_)));
+    if !is_transparent
+        && sources
+            .iter()
+            .any(|source| matches!(source, Source::AnyTaddr | Source::Taddr(_)))
+    {
+        return Err(LegacyCode::InvalidParameter.with_static(
+            "Cannot merge transparent and shielded funds in the same transaction; merge them separately.",
+        ));
+    }
+
+    if is_transparent {
+        if allow_split {
+            return Err(LegacyCode::InvalidParameter.with_static(
+                "Invalid parameter, allow_split is only supported when merging notes.",
+            ));
+        }
+        if memo.is_some() {
+            return Err(LegacyCode::InvalidParameter
+                .with_static("Memos are not supported when merging transparent funds."));
+        }
+
+        let (account, pool) = shielding_destination(wallet.as_ref(), &keystore, &toaddress)
+            .await
+            .map_err(|e| {
+                LegacyCode::InvalidParameter.with_message(format!(
+                    "{} Transparent funds can only be merged into a shielded address of the same account.",
+                    e.message(),
+                ))
+            })?;
+
+        let receivers = wallet
+            .get_transparent_receivers(account.id(), true, true)
+            .map_err(ComponentFailure::database)?;
+        let mut from_addrs = vec![];
+        for source in &sources {
+            match source {
+                Source::AnyTaddr => from_addrs.extend(receivers.keys().copied()),
+                Source::Taddr(addr) if receivers.contains_key(addr) => from_addrs.push(*addr),
+                _ => {
+                    return Err(LegacyCode::InvalidAddressOrKey.with_static(
+                        "Invalid from address, it does not belong to the destination account.",
+                    ));
+                }
+            }
+        }
+        let from_addrs = from_addrs
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let (utxos, remaining) = select_utxos(
+            wallet.as_ref(),
+            &from_addrs,
+            confirmations_policy,
+            max_utxos(transparent_limit),
+        )?;
+        if utxos.is_empty() {
+            return Err(LegacyCode::WalletInsufficientFunds
+                .with_static("Could not find any funds to merge."));
+        }
+
+        let selection = Selection {
+            remaining_utxos: remaining.len(),
+            remaining_transparent_value: value_from_zatoshis(total_value(&remaining)?),
+            remaining_notes: 0,
+            remaining_shielded_value: value_from_zatoshis(Zatoshis::ZERO),
+            merging_utxos: utxos.len(),
+            merging_transparent_value: value_from_zatoshis(total_value(&utxos)?),
+            merging_notes: 0,
+            merging_shielded_value: value_from_zatoshis(Zatoshis::ZERO),
+        };
+
+        let request = ShieldRequest::new(
+            &wallet,
+            account.id(),
+            pool,
+            &utxos,
+            confirmations_policy,
+            fee_limit,
+        );
+        let proposal = request.propose(&mut wallet)?;
+        let usk = account_spending_key(&wallet, &keystore, account.id()).await?;
+
+        return Ok((
+            selection,
+            (
+                Some(context),
+                MergeOperation::Transparent { request, proposal }
+                    .run(wallet, keystore, chain, usk, broadcast),
+            ),
+        ));
+    }
+
+    let params = *wallet.params();
+    let recipient = Address::decode(&params, &toaddress).ok_or_else(|| {
+        LegacyCode::InvalidAddressOrKey.with_data(
+            format!("Invalid parameter, unknown address format: {toaddress}"),
+            json!({ "address": toaddress, "reason": "not an address for this network" }),
+        )
+    })?;
+    if memo.is_some() && matches!(recipient, Address::Transparent(_) | Address::Tex(_)) {
+        return Err(
+            LegacyCode::InvalidParameter.with_static("Cannot send memo to transparent recipient")
+        );
+    }
+
+    let mut pools = vec![];
+    let mut accounts = vec![];
+    for source in &sources {
+        let source_pools = match source {
+            Source::AnyPool(pool) => std::slice::from_ref(pool),
+            Source::Account(account_id, account_pools) => {
+                if !accounts.contains(account_id) {
+                    accounts.push(*account_id);
+                }
+                account_pools.as_slice()
+            }
+            Source::AnyTaddr | Source::Taddr(_) => unreachable!("checked above"),
+        };
+        for pool in source_pools {
+            if !pools.contains(pool) {
+                pools.push(*pool);
+            }
+        }
+    }
+
+    // Each merge transaction is signed with the keys of a single account.
+    let account_id = match accounts.as_slice() {
+        [] => wildcard_account(wallet.as_ref(), &recipient)?,
+        [account_id] => *account_id,
+        _ => {
+            return Err(LegacyCode::InvalidParameter.with_static(
+                "Invalid parameter, all from addresses must belong to the same account.",
+            ));
+        }
+    };
+
+    let (notes, mut remaining) = select_notes(
+        wallet.as_ref(),
+        account_id,
+        &pools,
+        confirmations_policy,
+        shielded_limit,
+    )?;
+    if notes.is_empty() {
+        return Err(
+            LegacyCode::WalletInsufficientFunds.with_static("Could not find any funds to merge.")
+        );
+    }
+
+    // Without `allow_split`, only the notes that fit in a single transaction are merged.
+    let mut batches = plan_batches(
+        notes,
+        |note| match note.pool {
+            ShieldedProtocol::Sapling => InputKind::Sapling,
+            ShieldedProtocol::Orchard => InputKind::Orchard,
+        },
+        &BatchLimits::from_config(&APP.config().builder.limits),
+    );
+    if !allow_split {
+        remaining.extend(batches.drain(1..).flatten());
+    }
+
+    let notes_value = |notes: &mut dyn Iterator<Item = &Candidate>| {
+        notes
+            .map(|note| note.value)
+            .try_fold(Zatoshis::ZERO, |acc, value| acc + value)
+            .ok_or_else(|| LegacyCode::Misc.with_static("Value out of range"))
+    };
+    let selection = Selection {
+        remaining_utxos: 0,
+        remaining_transparent_value: value_from_zatoshis(Zatoshis::ZERO),
+        remaining_notes: remaining.len(),
+        remaining_shielded_value: value_from_zatoshis(notes_value(&mut remaining.iter())?),
+        merging_utxos: 0,
+        merging_transparent_value: value_from_zatoshis(Zatoshis::ZERO),
+        merging_notes: batches.iter().map(|batch| batch.len()).sum(),
+        merging_shielded_value: value_from_zatoshis(notes_value(&mut batches.iter().flatten())?),
+    };
+
+    let batches = batches
+        .into_iter()
+        .map(|batch| batch.into_iter().map(|note| note.input).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    // Input selection may only spend the notes of the current batch.
+    wallet.restrict_inputs(batches[0].iter().cloned().collect());
+
+    let request = MergeRequest {
+        account_id,
+        pools,
+        recipient: recipient.to_zcash_address(&params),
+        memo,
+        confirmations_policy,
+        orchard_actions_limit: APP.config().builder.limits.orchard_actions().into(),
+        fee_limit,
+    };
+    let proposal = request.propose(&mut wallet)?;
+    let usk = account_spending_key(&wallet, &keystore, account_id).await?;
+
+    let progress = OperationProgress::default();
+    Ok((
+        selection,
+        (
+            Some(context.with_progress(progress.clone())),
+            MergeOperation::Shielded {
+                request,
+                batches,
+                proposal,
+                progress,
+            }
+            .run(wallet, keystore, chain, usk, broadcast),
+        ),
+    ))
+}
+
+/// Parses the `fromaddresses` parameter.
+fn parse_sources(wallet: &DbConnection, fromaddresses: &[String]) -> RpcResult<Vec<Source>> {
+    let mut seen = HashSet::new();
+    fromaddresses
+        .iter()
+        .map(|fromaddress| {
+            if !seen.insert(fromaddress) {
+                return Err(LegacyCode::InvalidParameter.with_message(format!(
+                    "Invalid parameter, duplicated address: {fromaddress}",
+                )));
+            }
+
+            match fromaddress.as_str() {
+                "ANY_TADDR" => return Ok(Source::AnyTaddr),
+                "ANY_SAPLING" => return Ok(Source::AnyPool(ShieldedProtocol::Sapling)),
+                "ANY_ORCHARD" => return Ok(Source::AnyPool(ShieldedProtocol::Orchard)),
+                _ => (),
+            }
+
+            let address = Address::decode(wallet.params(), fromaddress).ok_or_else(|| {
+                let reason = match fromaddress.parse::<ZcashAddress>() {
+                    Ok(_) => {
+                        "address is for a different network, or of an unsupported kind".to_string()
+                    }
+                    Err(e) => e.to_string(),
+                };
+                LegacyCode::InvalidAddressOrKey.with_data(
+                    format!("Invalid parameter, unknown address format: {fromaddress}"),
+                    json!({ "address": fromaddress, "reason": reason }),
+                )
+            })?;
+
+            let pools = match &address {
+                Address::Transparent(addr) => return Ok(Source::Taddr(*addr)),
+                Address::Tex(_) => {
+                    return Err(LegacyCode::InvalidAddressOrKey.with_message(format!(
+                        "Invalid parameter, cannot merge funds from a TEX address: {fromaddress}",
+                    )));
+                }
+                Address::Sapling(_) => vec![ShieldedProtocol::Sapling],
+                Address::Unified(ua) => [
+                    (ua.has_sapling(), ShieldedProtocol::Sapling),
+                    (ua.has_orchard(), ShieldedProtocol::Orchard),
+                ]
+                .into_iter()
+                .filter_map(|(has, pool)| has.then_some(pool))
+                .collect(),
+            };
+
+            let account = get_account_for_address(wallet, &address).map_err(|_| {
+                LegacyCode::InvalidAddressOrKey.with_message(format!(
+                    "Invalid parameter, address does not belong to this wallet: {fromaddress}",
+                ))
+            })?;
+            Ok(Source::Account(account.id(), pools))
+        })
+        .collect()
+}
+
+/// Returns the account to merge funds from when only `ANY_SAPLING` or `ANY_ORCHARD` is
+/// given: the recipient's account if it belongs to the wallet, or otherwise the wallet's
+/// only account.
+fn wildcard_account(wallet: &DbConnection, recipient: &Address) -> RpcResult<AccountUuid> {
+    if let Ok(account) = get_account_for_address(wallet, recipient) {
+        return Ok(account.id());
+    }
+
+    match wallet
+        .get_account_ids()
+        .map_err(ComponentFailure::database)?
+        .as_slice()
+    {
+        [account_id] => Ok(*account_id),
+        _ => Err(LegacyCode::InvalidParameter.with_static(
+            "Invalid parameter, ANY_SAPLING and ANY_ORCHARD require the destination or another from address to belong to an account of this wallet.",
+        )),
+    }
+}
+
+/// Returns the spendable notes of the account in the given pools that should be merged,
+/// along with the spendable notes that were not selected.
+///
+/// Notes are selected oldest first, up to `shielded_limit` notes. The caller plans the
+/// transactions that merge them with [`plan_batches`].
+fn select_notes(
+    wallet: &DbConnection,
+    account_id: AccountUuid,
+    pools: &[ShieldedProtocol],
+    confirmations_policy: ConfirmationsPolicy,
+    shielded_limit: Option<u32>,
+) -> RpcResult<(Vec<Candidate>, Vec<Candidate>)> {
+    let target_height = TargetHeight::from(
+        wallet
+            .chain_height()
+            .map_err(ComponentFailure::database)?
+            .ok_or_else(|| LegacyCode::InWarmup.with_static("Wait for the wallet to start up"))?
+            + 1,
+    );
+
+    let notes = wallet
+        .select_spendable_notes(
+            account_id,
+            TargetValue::AllFunds(MaxSpendMode::MaxSpendable),
+            pools,
+            target_height,
+            confirmations_policy,
+            &[],
+        )
+        .map_err(ComponentFailure::database)?;
+
+    let candidate =
+        |pool, txid: &TxId, index: u16, mined_height: Option<BlockHeight>, value| Candidate {
+            input: ReservedInput::Note {
+                txid: *txid,
+                protocol: pool,
+                index: index.into(),
+            },
+            pool,
+            value,
+            key: (
+                mined_height.map_or(u32::MAX, u32::from),
+                match pool {
+                    ShieldedProtocol::Sapling => 0,
+                    ShieldedProtocol::Orchard => 1,
+                },
+                *txid.as_ref(),
+                index,
+            ),
+        };
+    let invalid_value = |_| ComponentFailure::database("Invalid note value");
+    let mut candidates = notes
+        .sapling()
+        .iter()
+        .map(|note| {
+            Ok(candidate(
+                ShieldedProtocol::Sapling,
+                note.txid(),
+                note.output_index(),
+                note.mined_height(),
+                note.note_value().map_err(invalid_value)?,
+            ))
+        })
+        .chain(notes.orchard().iter().map(|note| {
+            Ok(candidate(
+                ShieldedProtocol::Orchard,
+                note.txid(),
+                note.output_index(),
+                note.mined_height(),
+                note.note_value().map_err(invalid_value)?,
+            ))
+        }))
+        .collect::<RpcResult<Vec<_>>>()?;
+    candidates.sort_by_key(|candidate| candidate.key);
+
+    let max_notes = match shielded_limit.and_then(|limit| usize::try_from(limit).ok()) {
+        Some(0) => usize::MAX,
+        Some(limit) => limit,
+        None => DEFAULT_SHIELDED_LIMIT,
+    };
+    let remaining = candidates.split_off(max_notes.min(candidates.len()));
+    Ok((candidates, remaining))
+}
+
+/// Derives the spending key for the given account.
+async fn account_spending_key(
+    wallet: &DbConnection,
+    keystore: &KeyStore,
+    account_id: AccountUuid,
+) -> RpcResult<UnifiedSpendingKey> {
+    let account = wallet
+        .get_account(account_id)
+        .map_err(ComponentFailure::database)?
+        .ok_or_else(|| LegacyCode::InvalidParameter.with_static("Account not found."))?;
+
+    let derivation = account.source().key_derivation().ok_or_else(|| {
+        LegacyCode::InvalidAddressOrKey
+            .with_static("Invalid from address, no payment source found for address.")
+    })?;
+
+    spending_key(keystore, wallet.params(), derivation).await
+}
+
+/// The parameters of a transaction merging notes, from which it is proposed again if
+/// its inputs were reserved by another operation.
+pub(super) struct MergeRequest {
+    pub(super) account_id: AccountUuid,
+    pub(super) pools: Vec<ShieldedProtocol>,
+    pub(super) recipient: ZcashAddress,
+    pub(super) memo: Option<MemoBytes>,
+    pub(super) confirmations_policy: ConfirmationsPolicy,
+    pub(super) orchard_actions_limit: usize,
+    pub(super) fee_limit: FeeLimit,
+}
+
+impl MergeRequest {
+    /// Proposes a transaction sending every note that the wallet connection may spend to
+    /// the recipient.
+    pub(super) fn propose(
+        &self,
+        wallet: &mut DbHandle,
+    ) -> RpcResult<Proposal<StandardFeeRule, ReceivedNoteId>> {
+        let params = *wallet.params();
+
+        let proposal = propose_send_max_transfer::<_, _, _, Infallible>(
+            wallet.as_mut(),
+            &params,
+            self.account_id,
+            &self.pools,
+            &fees::fee_rule(),
+            self.recipient.clone(),
+            self.memo.clone(),
+            MaxSpendMode::MaxSpendable,
+            self.confirmations_policy,
+        )
+        .map_err(|e| SendFailure::from_wallet_error(&e))?;
+
+        check_orchard_actions(&proposal, self.orchard_actions_limit)?;
+        check_fees(&proposal, self.fee_limit)?;
+
+        Ok(proposal)
+    }
+}
+
+/// A merge transaction that is ready to be created.
+enum MergeOperation {
+    Transparent {
+        request: ShieldRequest,
+        proposal: Proposal<StandardFeeRule, Infallible>,
+    },
+    Shielded {
+        request: MergeRequest,
+        /// The notes merged by each transaction, in order.
+        batches: Vec<Vec<ReservedInput>>,
+        /// The proposal for the first batch.
+        proposal: Proposal<StandardFeeRule, ReceivedNoteId>,
+        progress: OperationProgress,
+    },
+}
+
+/// The progress of a `z_mergetoaddress` operation that merges its notes in a chain of
+/// transactions.
+#[derive(Serialize)]
+struct MergeProgress {
+    batches_sent: usize,
+    batches_total: usize,
+    txids: Vec<String>,
+}
+
+impl MergeOperation {
+    /// Creates the merge transactions, and broadcasts them if `broadcast` is `true`.
+    ///
+    /// Batches of notes are merged one after another. If a batch cannot be merged, the
+    /// chain stops, and the error lists the notes that were not merged.
+    async fn run(
+        self,
+        mut wallet: DbHandle,
+        keystore: KeyStore,
+        chain: FetchServiceSubscriber,
+        usk: UnifiedSpendingKey,
+        broadcast: bool,
+    ) -> RpcResult<SendResult> {
+        match self {
+            MergeOperation::Transparent { request, proposal } => {
+                super::z_shield_coinbase::run(
+                    wallet, keystore, chain, usk, request, proposal, broadcast,
+                )
+                .await
+            }
+            MergeOperation::Shielded {
+                request,
+                batches,
+                mut proposal,
+                progress,
+            } => {
+                // If the operation was queued for long enough, a transaction created from
+                // the original proposal would expire soon after being broadcast. The
+                // transaction builder always uses the default expiry delta.
+                let expiry_height = BlockHeight::from(proposal.min_target_height())
+                    + (DEFAULT_TX_EXPIRY_DELTA - TX_EXPIRING_SOON_THRESHOLD);
+                if wallet
+                    .chain_height()
+                    .map_err(ComponentFailure::database)?
+                    .is_some_and(|height| height + 1 > expiry_height)
+                {
+                    proposal = request.propose(&mut wallet)?;
+                }
+
+                let batches_total = batches.len();
+                let mut results = vec![];
+                // The reserved inputs are held until the operation finishes.
+                let mut reservations = vec![];
+                let mut first = Some(proposal);
+                let mut batches = batches.into_iter();
+                while let Some(batch) = batches.next() {
+                    let proposal = match first.take() {
+                        Some(proposal) => Ok(proposal),
+                        None => {
+                            wallet.restrict_inputs(batch.iter().cloned().collect());
+                            request.propose(&mut wallet)
+                        }
+                    };
+                    let reserved = match proposal {
+                        Ok(proposal) => {
+                            reserve_inputs(&mut wallet, proposal, |wallet| request.propose(wallet))
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    let sent = match reserved {
+                        Ok((proposal, reservation)) => {
+                            reservations.push(reservation);
+                            send_proposal(
+                                wallet,
+                                &keystore,
+                                chain.clone(),
+                                &usk,
+                                proposal,
+                                broadcast,
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    };
+
+                    match sent {
+                        Ok((returned_wallet, _, result)) => {
+                            wallet = returned_wallet;
+                            results.push((vec![request.recipient.encode()], result));
+                            progress.set(&MergeProgress {
+                                batches_sent: results.len(),
+                                batches_total,
+                                txids: results
+                                    .iter()
+                                    .flat_map(|(_, result)| result.txids())
+                                    .cloned()
+                                    .collect(),
+                            });
+                        }
+                        Err(e) if batches_total == 1 => return Err(e),
+                        Err(e) => {
+                            return Err(chain_stopped(
+                                e,
+                                &results,
+                                batches_total,
+                                batch.iter().chain(batches.as_slice().iter().flatten()),
+                            ));
+                        }
+                    }
+                }
+
+                Ok(if batches_total > 1 {
+                    SendResult::from_split(results)
+                } else {
+                    results.pop().expect("merged at least one batch").1
+                })
+            }
+        }
+    }
+}
+
+/// Returns the error for a chain of merge transactions that stopped because of `e`,
+/// listing the transactions that were sent and the notes that remain unmerged.
+fn chain_stopped<'a>(
+    e: ErrorObjectOwned,
+    results: &[(Vec<String>, SendResult)],
+    batches_total: usize,
+    remaining: impl Iterator<Item = &'a ReservedInput>,
+) -> ErrorObjectOwned {
+    let remaining = remaining
+        .map(|input| match input {
+            ReservedInput::Note {
+                txid,
+                protocol,
+                index,
+            } => json!({
+                "txid": txid.to_string(),
+                "pool": pool_name(PoolType::Shielded(*protocol)),
+                "outindex": index,
+            }),
+            ReservedInput::Transparent(outpoint) => json!({
+                "txid": TxId::from_bytes(*outpoint.hash()).to_string(),
+                "vout": outpoint.n(),
+            }),
+        })
+        .collect::<Vec<_>>();
+
+    ErrorObjectOwned::owned(
+        e.code(),
+        format!(
+            "Merge stopped after {} of {batches_total} transactions: {}",
+            results.len(),
+            e.message(),
+        ),
+        Some(json!({
+            "txids": results
+                .iter()
+                .flat_map(|(_, result)| result.txids())
+                .collect::<Vec<_>>(),
+            "remaining": remaining,
+        })),
+    )
+}
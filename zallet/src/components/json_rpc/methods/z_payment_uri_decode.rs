@@ -0,0 +1,215 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::json;
+use zcash_client_backend::zip321::TransactionRequest;
+use zcash_keys::address::Address;
+use zcash_protocol::{
+    memo::{Memo, MemoBytes},
+    value::Zatoshis,
+};
+
+use crate::{
+    components::json_rpc::{
+        server::LegacyCode,
+        utils::{JsonZec, zec_and_zat},
+    },
+    network::Network,
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_paymenturi_decode` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = DecodedPaymentUri;
+
+/// The payments requested by a ZIP 321 payment URI.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct DecodedPaymentUri {
+    /// The requested payments, in the order of their indices in the URI.
+    payments: Vec<RequestedPayment>,
+
+    /// The total amount requested, in ZEC.
+    total: JsonZec,
+
+    /// The value of `total` in zatoshis.
+    #[serde(rename = "totalZat")]
+    total_zat: u64,
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+struct RequestedPayment {
+    /// The recipient's address.
+    address: String,
+
+    /// The requested amount, in ZEC.
+    amount: JsonZec,
+
+    /// The value of `amount` in zatoshis.
+    #[serde(rename = "amountZat")]
+    amount_zat: u64,
+
+    /// The memo to send to the recipient, in hexadecimal string format.
+    ///
+    /// Omitted if the URI does not include a memo for this payment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+
+    /// UTF-8 string representation of the memo (if it contains valid UTF-8).
+    #[serde(rename = "memoStr")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo_str: Option<String>,
+
+    /// The label for the recipient, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+
+    /// The message describing the payment, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_URI_DESC: &str = "A ZIP 321 payment URI.";
+
+pub(crate) fn call(params: &Network, uri: &str) -> Response {
+    let request = parse_payment_uri(params, uri)?;
+
+    let mut total = Zatoshis::ZERO;
+    let mut payments = vec![];
+    for payment in request.payments().values() {
+        total = (total + payment.amount())
+            .ok_or_else(|| LegacyCode::InvalidParameter.with_static("Value too large"))?;
+
+        let (amount, amount_zat) = zec_and_zat(payment.amount());
+        payments.push(RequestedPayment {
+            address: payment.recipient_address().encode(),
+            amount,
+            amount_zat,
+            memo: payment.memo().map(|memo| hex::encode(memo.as_slice())),
+            memo_str: payment.memo().and_then(memo_text),
+            label: payment.label().cloned(),
+            message: payment.message().cloned(),
+        });
+    }
+
+    let (total, total_zat) = zec_and_zat(total);
+    Ok(DecodedPaymentUri {
+        payments,
+        total,
+        total_zat,
+    })
+}
+
+/// Parses a ZIP 321 payment URI, checking that its recipients are valid for the given
+/// network.
+pub(super) fn parse_payment_uri(params: &Network, uri: &str) -> RpcResult<TransactionRequest> {
+    // ZIP 321 requires URIs with required parameters that we don't understand to be
+    // rejected. The parser does that too, but without saying which parameter it was.
+    if let Some(param) = unknown_required_param(uri) {
+        return Err(LegacyCode::InvalidParameter.with_data(
+            format!("Invalid payment URI: unsupported required parameter {param}"),
+            json!({ "uri": uri, "parameter": param }),
+        ));
+    }
+
+    let request = TransactionRequest::from_uri(uri).map_err(|e| {
+        LegacyCode::InvalidParameter.with_data(
+            format!("Invalid payment URI: {e}"),
+            json!({ "uri": uri, "reason": e.to_string() }),
+        )
+    })?;
+
+    for payment in request.payments().values() {
+        Address::try_from_zcash_address(params, payment.recipient_address().clone()).map_err(
+            |e| {
+                LegacyCode::InvalidAddressOrKey.with_data(
+                    format!(
+                        "Invalid payment URI: address {} is not valid for this network",
+                        payment.recipient_address().encode(),
+                    ),
+                    json!({
+                        "address": payment.recipient_address().encode(),
+                        "reason": e.to_string(),
+                    }),
+                )
+            },
+        )?;
+    }
+
+    Ok(request)
+}
+
+/// Returns the first `req-` parameter of the URI, which ZIP 321 does not define any of.
+fn unknown_required_param(uri: &str) -> Option<&str> {
+    let (_, query) = uri.split_once('?')?;
+    query
+        .split('&')
+        .map(|param| param.split_once('=').map_or(param, |(key, _)| key))
+        .find(|key| key.starts_with("req-"))
+}
+
+/// Returns the memo as a string, if it is a text memo.
+fn memo_text(memo: &MemoBytes) -> Option<String> {
+    match Memo::try_from(memo) {
+        Ok(Memo::Text(text_memo)) => Some(text_memo.into()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::consensus;
+
+    use super::{call, unknown_required_param};
+    use crate::{components::json_rpc::server::LegacyCode, network::Network};
+
+    /// The multiple-payment example from ZIP 321.
+    const URI: &str = "zcash:?address=tmEZhbWHTpdKMw5it8YDspUXSMGQyFwovpU&amount=123.456&address.1=ztestsapling10yy2ex5dcqkclhc7z7yrnjq2z6feyjad56ptwlfgmy77dmaqqrl9gyhprdx59qgmsnyfska2kez&amount.1=0.789&memo.1=VGhpcyBpcyBhIHVuaWNvZGUgbWVtbyDinKjwn6aE8J-PhvCfjok";
+
+    #[test]
+    fn decode() {
+        let params = Network::Consensus(consensus::Network::TestNetwork);
+        let decoded = call(&params, URI).unwrap();
+
+        assert_eq!(decoded.payments.len(), 2);
+        assert_eq!(decoded.payments[0].amount_zat, 12_345_600_000);
+        assert_eq!(decoded.payments[0].memo, None);
+        assert_eq!(decoded.payments[1].amount_zat, 78_900_000);
+        assert_eq!(
+            decoded.payments[1].memo_str.as_deref(),
+            Some("This is a unicode memo ✨🦄🏆🎉"),
+        );
+        assert_eq!(
+            decoded.payments[1].memo.as_deref(),
+            Some(hex::encode("This is a unicode memo ✨🦄🏆🎉").as_str()),
+        );
+        assert_eq!(decoded.total_zat, 12_345_600_000 + 78_900_000);
+
+        // The recipients must be for the wallet's network.
+        let params = Network::Consensus(consensus::Network::MainNetwork);
+        assert_eq!(
+            call(&params, URI).unwrap_err().code(),
+            LegacyCode::InvalidAddressOrKey as i32,
+        );
+    }
+
+    #[test]
+    fn required_params() {
+        assert_eq!(
+            unknown_required_param("zcash:ztestsapling1abc?amount=1&req-foo=bar"),
+            Some("req-foo"),
+        );
+        assert_eq!(
+            unknown_required_param("zcash:?address.1=ztestsapling1abc&req-foo.1"),
+            Some("req-foo.1"),
+        );
+        assert_eq!(
+            unknown_required_param("zcash:ztestsapling1abc?amount=1&message=req-foo"),
+            None,
+        );
+        assert_eq!(unknown_required_param("zcash:ztestsapling1abc"), None);
+    }
+}
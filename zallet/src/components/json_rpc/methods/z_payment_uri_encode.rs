@@ -0,0 +1,60 @@
+use documented::Documented;
+use jsonrpsee::core::{JsonValue, RpcResult};
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_address::ZcashAddress;
+use zcash_client_backend::zip321::{Payment, TransactionRequest};
+use zcash_keys::address::Address;
+
+use crate::{
+    components::json_rpc::{payments::parse_memo, server::LegacyCode, utils::zatoshis_from_value},
+    network::Network,
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_paymenturi_encode` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The ZIP 321 payment URI.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(String);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_ADDRESS_DESC: &str = "The address to request payment to.";
+pub(super) const PARAM_AMOUNT_DESC: &str = "The amount to request, in ZEC.";
+pub(super) const PARAM_MEMO_DESC: &str =
+    "A memo for the payment, in hexadecimal string format. Only permitted for shielded addresses.";
+pub(super) const PARAM_LABEL_DESC: &str = "A label for the recipient.";
+pub(super) const PARAM_MESSAGE_DESC: &str = "A message describing the payment.";
+
+pub(crate) fn call(
+    params: &Network,
+    address: &str,
+    amount: JsonValue,
+    memo: Option<String>,
+    label: Option<String>,
+    message: Option<String>,
+) -> Response {
+    let recipient = address
+        .parse::<ZcashAddress>()
+        .ok()
+        .filter(|addr| Address::try_from_zcash_address(params, addr.clone()).is_ok())
+        .ok_or_else(|| {
+            LegacyCode::InvalidAddressOrKey.with_message(format!("Invalid address: {address}"))
+        })?;
+
+    let amount = zatoshis_from_value(&amount)?;
+    let memo = memo.as_deref().map(parse_memo).transpose()?;
+
+    let payment =
+        Payment::new(recipient, amount, memo, label, message, vec![]).ok_or_else(|| {
+            LegacyCode::InvalidParameter.with_static("Cannot send memo to transparent recipient")
+        })?;
+    let request = TransactionRequest::new(vec![payment])
+        .map_err(|e| LegacyCode::InvalidParameter.with_message(e.to_string()))?;
+
+    Ok(ResultType(request.to_uri()))
+}
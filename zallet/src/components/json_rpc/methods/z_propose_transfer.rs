@@ -0,0 +1,429 @@
+use abscissa_core::Application;
+use documented::Documented;
+use hmac::{Hmac, Mac};
+use jsonrpsee::core::{JsonValue, RpcResult};
+use prost::Message;
+use schemars::JsonSchema;
+use secrecy::ExposeSecret;
+use serde::Serialize;
+use sha2::Sha256;
+use uuid::Uuid;
+use zcash_client_backend::{
+    data_api::WalletRead, fees::StandardFeeRule, proposal::Proposal, proto,
+};
+use zcash_client_sqlite::{AccountUuid, ReceivedNoteId};
+use zcash_primitives::{block::BlockHash, transaction::builder::DEFAULT_TX_EXPIRY_DELTA};
+use zcash_protocol::{ShieldedProtocol, consensus::BlockHeight, value::Zatoshis};
+
+use crate::{
+    components::{
+        database::DbHandle,
+        json_rpc::{
+            fees::FeeLimit,
+            payments::{enforce_privacy_policy, pool_name},
+            server::{ComponentFailure, LegacyCode},
+            utils::{JsonZec, parse_account_parameter, zec_and_zat},
+        },
+        keystore::KeyStore,
+    },
+    config::TX_EXPIRING_SOON_THRESHOLD,
+    prelude::*,
+};
+
+use super::{
+    MethodCategory,
+    z_send_many::{
+        self, check_fees, check_orchard_actions, parse_confirmations_policy, parse_payments,
+        parse_privacy_policy, propose, transaction_request,
+    },
+};
+
+/// Response to a `z_proposetransfer` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = ProposedTransfer;
+
+/// A proposed transfer of funds, which can be sent with `z_executeproposal`.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct ProposedTransfer {
+    /// The serialized proposal, to be passed to `z_executeproposal`.
+    proposal: String,
+
+    /// A summary of the transactions that executing the proposal will create.
+    summary: ProposalSummary,
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+struct ProposalSummary {
+    /// The UUID of the account that the funds will be sent from.
+    account_uuid: String,
+
+    /// The payments that the proposal makes.
+    payments: Vec<ProposedPayment>,
+
+    /// The number of transactions that executing the proposal will create.
+    transactions: usize,
+
+    /// The number of inputs that the proposal spends from each value pool.
+    inputs: PoolCounts,
+
+    /// The change outputs that the proposal creates.
+    change: Vec<ProposedChange>,
+
+    /// The total fee that the proposal pays, in ZEC.
+    fee: JsonZec,
+
+    /// The value of `fee` in zatoshis.
+    #[serde(rename = "feeZat")]
+    fee_zat: u64,
+
+    /// The proposal can only be executed while the height of the next block is at most
+    /// this height.
+    expiryheight: u32,
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+struct ProposedPayment {
+    /// The recipient's address.
+    address: String,
+
+    /// The amount paid to the recipient, in ZEC.
+    amount: JsonZec,
+
+    /// The value of `amount` in zatoshis.
+    #[serde(rename = "amountZat")]
+    amount_zat: u64,
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+struct PoolCounts {
+    transparent: usize,
+    sapling: usize,
+    orchard: usize,
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+struct ProposedChange {
+    /// The value pool that receives the change.
+    ///
+    /// One of `["transparent", "sapling", "orchard"]`.
+    pool: &'static str,
+
+    /// The value of the change output, in ZEC.
+    amount: JsonZec,
+
+    /// The value of `amount` in zatoshis.
+    #[serde(rename = "amountZat")]
+    amount_zat: u64,
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_ACCOUNT_DESC: &str =
+    "The UUID, name, or ZIP 32 account index of the account to send funds from.";
+pub(super) const PARAM_RECIPIENTS_DESC: &str =
+    "An array of JSON objects representing the amounts to send.";
+pub(super) const PARAM_RECIPIENTS_REQUIRED: bool = true;
+pub(super) const PARAM_MINCONF_DESC: &str = "Only use funds confirmed at least this many times.";
+pub(super) const PARAM_PRIVACY_POLICY_DESC: &str =
+    "Policy for what information leakage is acceptable.";
+
+pub(crate) async fn call(
+    mut wallet: DbHandle,
+    keystore: KeyStore,
+    account: JsonValue,
+    recipients: Vec<z_send_many::AmountParameter>,
+    minconf: Option<u32>,
+    privacy_policy: Option<String>,
+) -> Response {
+    let account_id = parse_account_parameter(wallet.as_ref(), &keystore, &account).await?;
+
+    let payments = parse_payments(&recipients)?;
+    let privacy_policy = parse_privacy_policy(privacy_policy.as_deref())?;
+    let confirmations_policy = parse_confirmations_policy(minconf);
+
+    let proposal = propose(
+        &mut wallet,
+        account_id,
+        transaction_request(payments.clone())?,
+        confirmations_policy,
+    )?;
+    enforce_privacy_policy(&proposal, privacy_policy)?;
+    check_orchard_actions(
+        &proposal,
+        APP.config().builder.limits.orchard_actions().into(),
+    )?;
+    check_fees(&proposal, FeeLimit::from_config(&APP.config().builder))?;
+
+    // Once a transaction created from the proposal would be expiring soon, it is no
+    // longer worth creating. Transactions always use the default expiry delta.
+    let expiry_height = BlockHeight::from(proposal.min_target_height())
+        + (DEFAULT_TX_EXPIRY_DELTA - TX_EXPIRING_SOON_THRESHOLD);
+
+    // Record the blocks that the shielded inputs are anchored to, so that we can detect
+    // chain reorgs that would invalidate them.
+    let mut anchors = vec![];
+    for anchor_height in proposal
+        .steps()
+        .iter()
+        .filter_map(|step| step.shielded_inputs())
+        .map(|inputs| inputs.anchor_height())
+    {
+        if anchors.iter().any(|(height, _)| *height == anchor_height) {
+            continue;
+        }
+        let block = wallet
+            .block_metadata(anchor_height)
+            .map_err(ComponentFailure::database)?
+            .ok_or_else(|| {
+                LegacyCode::Wallet.with_message(format!(
+                    "Wallet is missing the anchor block at height {anchor_height}"
+                ))
+            })?;
+        anchors.push((anchor_height, block.block_hash()));
+    }
+
+    let (fee, fee_zat) = zec_and_zat(
+        proposal
+            .steps()
+            .iter()
+            .try_fold(Zatoshis::ZERO, |acc, step| {
+                acc + step.balance().fee_required()
+            })
+            .ok_or_else(|| LegacyCode::Wallet.with_static("Proposal fee overflowed"))?,
+    );
+
+    let summary = ProposalSummary {
+        account_uuid: account_id.expose_uuid().to_string(),
+        payments: payments
+            .iter()
+            .map(|payment| {
+                let (amount, amount_zat) = zec_and_zat(payment.amount());
+                ProposedPayment {
+                    address: payment.recipient_address().encode(),
+                    amount,
+                    amount_zat,
+                }
+            })
+            .collect(),
+        transactions: proposal.steps().len(),
+        inputs: input_counts(&proposal),
+        change: proposal
+            .steps()
+            .iter()
+            .flat_map(|step| step.balance().proposed_change())
+            .map(|change| {
+                let (amount, amount_zat) = zec_and_zat(change.value());
+                ProposedChange {
+                    pool: pool_name(change.output_pool()),
+                    amount,
+                    amount_zat,
+                }
+            })
+            .collect(),
+        fee,
+        fee_zat,
+        expiryheight: expiry_height.into(),
+    };
+
+    let proposal = SerializedProposal {
+        account_id,
+        expiry_height,
+        anchors,
+        proposal: proto::proposal::Proposal::from_standard_proposal(&proposal),
+    };
+
+    let key = wallet.proposal_key().map_err(ComponentFailure::database)?;
+
+    Ok(ProposedTransfer {
+        proposal: proposal.encode(key.expose_secret()),
+        summary,
+    })
+}
+
+/// Counts the inputs spent by each step of the proposal.
+fn input_counts(proposal: &Proposal<StandardFeeRule, ReceivedNoteId>) -> PoolCounts {
+    let mut counts = PoolCounts {
+        transparent: 0,
+        sapling: 0,
+        orchard: 0,
+    };
+
+    for step in proposal.steps() {
+        counts.transparent += step.transparent_inputs().len();
+        for note in step
+            .shielded_inputs()
+            .iter()
+            .flat_map(|inputs| inputs.notes())
+        {
+            match note.note().protocol() {
+                ShieldedProtocol::Sapling => counts.sapling += 1,
+                ShieldedProtocol::Orchard => counts.orchard += 1,
+            }
+        }
+    }
+
+    counts
+}
+
+/// The version of the serialized proposal format.
+const SERIALIZED_PROPOSAL_VERSION: u8 = 2;
+
+/// The length of the tag that authenticates a serialized proposal.
+const PROPOSAL_TAG_LEN: usize = 32;
+
+/// A transaction proposal, along with the wallet state that it was created against.
+///
+/// This is serialized as a hex string containing:
+/// - A version byte.
+/// - The account UUID (16 bytes).
+/// - The expiry height (4 bytes, little-endian).
+/// - The number of anchors (1 byte), followed by each anchor's height (4 bytes,
+///   little-endian) and block hash (32 bytes).
+/// - The proposal, in its protobuf encoding.
+/// - An HMAC-SHA256 tag over all of the above (32 bytes), keyed with the wallet's
+///   proposal key.
+///
+/// The tag ensures that `z_executeproposal` only executes proposals that this wallet
+/// created, exactly as they were summarized to the caller.
+pub(super) struct SerializedProposal {
+    pub(super) account_id: AccountUuid,
+    /// The maximum next block height at which the proposal can be executed.
+    pub(super) expiry_height: BlockHeight,
+    /// The blocks that the proposal's shielded inputs are anchored to.
+    pub(super) anchors: Vec<(BlockHeight, BlockHash)>,
+    pub(super) proposal: proto::proposal::Proposal,
+}
+
+impl SerializedProposal {
+    fn encode(&self, key: &[u8]) -> String {
+        let mut bytes = vec![SERIALIZED_PROPOSAL_VERSION];
+        bytes.extend_from_slice(self.account_id.expose_uuid().as_bytes());
+        bytes.extend_from_slice(&u32::from(self.expiry_height).to_le_bytes());
+        bytes.push(
+            u8::try_from(self.anchors.len()).expect("proposals have a small number of steps"),
+        );
+        for (height, hash) in &self.anchors {
+            bytes.extend_from_slice(&u32::from(*height).to_le_bytes());
+            bytes.extend_from_slice(&hash.0);
+        }
+        self.proposal.encode(&mut bytes).expect("Vec can grow");
+        let tag = proposal_mac(key, &bytes).finalize().into_bytes();
+        bytes.extend_from_slice(&tag);
+        hex::encode(bytes)
+    }
+
+    /// Parses a serialized proposal, returning `None` if it is malformed or was not
+    /// created with the given key.
+    pub(super) fn decode(encoded: &str, key: &[u8]) -> Option<Self> {
+        let bytes = hex::decode(encoded).ok()?;
+
+        let (bytes, tag) = bytes.split_last_chunk::<PROPOSAL_TAG_LEN>()?;
+        proposal_mac(key, bytes).verify_slice(tag).ok()?;
+
+        let (&version, rest) = bytes.split_first()?;
+        if version != SERIALIZED_PROPOSAL_VERSION {
+            return None;
+        }
+        let (account_id, rest) = rest.split_first_chunk::<16>()?;
+        let (expiry_height, rest) = rest.split_first_chunk::<4>()?;
+        let (&anchor_count, mut rest) = rest.split_first()?;
+
+        let mut anchors = vec![];
+        for _ in 0..anchor_count {
+            let (height, tail) = rest.split_first_chunk::<4>()?;
+            let (hash, tail) = tail.split_first_chunk::<32>()?;
+            anchors.push((
+                BlockHeight::from_u32(u32::from_le_bytes(*height)),
+                BlockHash(*hash),
+            ));
+            rest = tail;
+        }
+
+        Some(Self {
+            account_id: AccountUuid::from_uuid(Uuid::from_bytes(*account_id)),
+            expiry_height: BlockHeight::from_u32(u32::from_le_bytes(*expiry_height)),
+            anchors,
+            proposal: proto::proposal::Proposal::decode(rest).ok()?,
+        })
+    }
+}
+
+/// Returns the MAC that authenticates the given serialized proposal.
+fn proposal_mac(key: &[u8], bytes: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(bytes);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use hmac::Mac;
+    use uuid::Uuid;
+    use zcash_client_backend::proto;
+    use zcash_client_sqlite::AccountUuid;
+    use zcash_primitives::block::BlockHash;
+    use zcash_protocol::consensus::BlockHeight;
+
+    use super::{PROPOSAL_TAG_LEN, SerializedProposal, proposal_mac};
+
+    const KEY: &[u8] = &[0x42; 32];
+
+    fn proposal() -> SerializedProposal {
+        SerializedProposal {
+            account_id: AccountUuid::from_uuid(Uuid::from_u128(7)),
+            expiry_height: BlockHeight::from_u32(1_000_037),
+            anchors: vec![(BlockHeight::from_u32(999_990), BlockHash([5; 32]))],
+            proposal: proto::proposal::Proposal::default(),
+        }
+    }
+
+    /// Re-encodes the given serialized proposal bytes with a valid tag.
+    fn tagged(mut bytes: Vec<u8>) -> String {
+        let tag = proposal_mac(KEY, &bytes).finalize().into_bytes();
+        bytes.extend_from_slice(&tag);
+        hex::encode(bytes)
+    }
+
+    #[test]
+    fn serialized_proposal_round_trip() {
+        let proposal = proposal();
+
+        let encoded = proposal.encode(KEY);
+        let decoded = SerializedProposal::decode(&encoded, KEY).unwrap();
+        assert_eq!(decoded.account_id, proposal.account_id);
+        assert_eq!(decoded.expiry_height, proposal.expiry_height);
+        assert_eq!(decoded.anchors, proposal.anchors);
+        assert_eq!(decoded.proposal, proposal.proposal);
+
+        // Truncated and unknown-version encodings are rejected, even when they are
+        // correctly tagged.
+        let bytes = hex::decode(&encoded).unwrap();
+        let body = bytes[..bytes.len() - PROPOSAL_TAG_LEN].to_vec();
+        assert!(SerializedProposal::decode(&encoded[..40], KEY).is_none());
+        assert!(SerializedProposal::decode(&tagged(body[..20].to_vec()), KEY).is_none());
+        let mut unknown_version = body;
+        unknown_version[0] = 0xff;
+        assert!(SerializedProposal::decode(&tagged(unknown_version), KEY).is_none());
+        assert!(SerializedProposal::decode("not hex", KEY).is_none());
+    }
+
+    #[test]
+    fn tampered_proposals_are_rejected() {
+        let encoded = proposal().encode(KEY);
+        let bytes = hex::decode(&encoded).unwrap();
+
+        // Extending the expiry height invalidates the tag.
+        let mut extended = bytes.clone();
+        extended[1 + 16 + 3] = 0x7f;
+        assert!(SerializedProposal::decode(&hex::encode(&extended), KEY).is_none());
+
+        // So does modifying the tag itself.
+        let mut retagged = bytes.clone();
+        *retagged.last_mut().unwrap() ^= 1;
+        assert!(SerializedProposal::decode(&hex::encode(&retagged), KEY).is_none());
+
+        // A proposal created by another wallet is rejected.
+        assert!(SerializedProposal::decode(&encoded, &[0x43; 32]).is_none());
+        assert!(SerializedProposal::decode(&proposal().encode(&[0x43; 32]), KEY).is_none());
+    }
+}
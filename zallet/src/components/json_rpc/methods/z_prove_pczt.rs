@@ -0,0 +1,53 @@
+use documented::Documented;
+use jsonrpsee::core::RpcResult;
+use pczt::roles::prover::Prover;
+use schemars::JsonSchema;
+use serde::Serialize;
+use zcash_proofs::prover::LocalTxProver;
+
+use crate::components::json_rpc::{
+    pczts::{self, ORCHARD_PROVING_KEY},
+    server::LegacyCode,
+};
+
+use super::MethodCategory;
+
+/// Response to a `z_provepczt` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The PCZT with its proofs, base64-encoded.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct ResultType(String);
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Query;
+
+pub(super) const PARAM_PCZT_DESC: &str = "A base64-encoded PCZT.";
+
+pub(crate) async fn call(pczt: String) -> Response {
+    let pczt = pczts::parse(&pczt)?;
+
+    let proven = crate::spawn_blocking!("z_provepczt prover", move || {
+        let mut prover = Prover::new(pczt);
+
+        if prover.requires_orchard_proof() {
+            prover = prover
+                .create_orchard_proof(&ORCHARD_PROVING_KEY)
+                .map_err(|e| format!("Failed to create Orchard proof: {e:?}"))?;
+        }
+
+        if prover.requires_sapling_proofs() {
+            let sapling_prover = LocalTxProver::bundled();
+            prover = prover
+                .create_sapling_proofs(&sapling_prover, &sapling_prover)
+                .map_err(|e| format!("Failed to create Sapling proofs: {e:?}"))?;
+        }
+
+        Ok::<_, String>(prover.finish())
+    })
+    .await
+    .map_err(|e| LegacyCode::Misc.with_message(e.to_string()))?
+    .map_err(|e| LegacyCode::Verify.with_message(e))?;
+
+    Ok(ResultType(pczts::encode(proven)))
+}
@@ -1,58 +1,80 @@
 use std::collections::HashSet;
 use std::convert::Infallible;
 use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use abscissa_core::Application;
-use jsonrpsee::core::{JsonValue, RpcResult};
+use jsonrpsee::{
+    core::{JsonValue, RpcResult},
+    types::ErrorObjectOwned,
+};
+use rusqlite::named_params;
 use schemars::JsonSchema;
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use tokio::sync::oneshot;
 use zaino_state::FetchServiceSubscriber;
 use zcash_address::{ZcashAddress, unified};
 use zcash_client_backend::data_api::wallet::SpendingKeys;
-use zcash_client_backend::proposal::Proposal;
+use zcash_client_backend::proposal::{Proposal, Step};
 use zcash_client_backend::{
     data_api::{
-        Account,
+        Account, WalletRead, Zip32Derivation,
         wallet::{
             ConfirmationsPolicy, create_proposed_transactions,
             input_selection::GreedyInputSelector, propose_transfer,
         },
     },
-    fees::{DustOutputPolicy, StandardFeeRule, standard::MultiOutputChangeStrategy},
+    fees::{StandardFeeRule, standard::MultiOutputChangeStrategy},
     wallet::OvkPolicy,
     zip321::{Payment, TransactionRequest},
 };
-use zcash_client_sqlite::ReceivedNoteId;
-use zcash_keys::{address::Address, keys::UnifiedSpendingKey};
+use zcash_client_sqlite::{AccountUuid, ReceivedNoteId};
+use zcash_keys::{
+    address::{Address, UnifiedAddress},
+    keys::UnifiedSpendingKey,
+};
 use zcash_proofs::prover::LocalTxProver;
 use zcash_protocol::{
-    PoolType, ShieldedProtocol,
+    PoolType, ShieldedProtocol, TxId,
+    consensus::BlockHeight,
     value::{MAX_MONEY, Zatoshis},
 };
 
 use crate::{
     components::{
-        database::DbHandle,
+        chain::Chain,
+        database::{Database, DbHandle, Reservation, ReservedInput, TxValues},
         json_rpc::{
-            asyncop::{ContextInfo, OperationId},
+            asyncop::{self, AsyncOperation, ContextInfo, OperationId, OperationProgress},
+            batching::{BatchLimits, InputKind, plan_batches, plan_recipients},
+            fees::{self, ActionCounts, DustThresholds, FeeLimit},
             payments::{
-                IncompatiblePrivacyPolicy, PrivacyPolicy, SendResult, broadcast_transactions,
-                enforce_privacy_policy, get_account_for_address, parse_memo,
+                BatchedOutput, IncompatiblePrivacyPolicy, PrivacyPolicy, SendResult,
+                broadcast_transactions, enforce_privacy_policy, get_account_for_address,
+                parse_memo, proposal_inputs, reserve_inputs,
+            },
+            send_error::{PoolBreakdown, SendFailure},
+            server::{ComponentFailure, LegacyCode},
+            utils::{
+                is_watch_only, parse_account_parameter, value_from_zatoshis, zatoshis_from_value,
             },
-            server::LegacyCode,
-            utils::zatoshis_from_value,
         },
         keystore::KeyStore,
     },
+    config::InputSelection,
     fl,
+    network::Network,
     prelude::*,
 };
 
 #[cfg(feature = "transparent-key-import")]
 use {transparent::address::TransparentAddress, zcash_script::script};
 
+use super::{MethodCategory, z_merge_to_address::MergeRequest};
+
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub(crate) struct AmountParameter {
     /// A taddr, zaddr, or Unified Address.
@@ -68,30 +90,56 @@ pub(crate) struct AmountParameter {
     memo: Option<String>,
 }
 
+impl AmountParameter {
+    /// Returns the parameter for a payment requested by a ZIP 321 payment URI.
+    pub(super) fn from_payment(payment: &Payment) -> Self {
+        Self {
+            address: payment.recipient_address().encode(),
+            amount: json!(value_from_zatoshis(payment.amount())),
+            memo: payment.memo().map(|memo| hex::encode(memo.as_slice())),
+        }
+    }
+}
+
 /// Response to a `z_sendmany` RPC request.
 pub(crate) type Response = RpcResult<ResultType>;
 pub(crate) type ResultType = OperationId;
 
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
 pub(super) const PARAM_FROMADDRESS_DESC: &str =
     "The transparent or shielded address to send the funds from.";
 pub(super) const PARAM_AMOUNTS_DESC: &str =
     "An array of JSON objects representing the amounts to send.";
 pub(super) const PARAM_AMOUNTS_REQUIRED: bool = true;
 pub(super) const PARAM_MINCONF_DESC: &str = "Only use funds confirmed at least this many times.";
-pub(super) const PARAM_FEE_DESC: &str = "If set, it must be null.";
+pub(super) const PARAM_FEE_DESC: &str =
+    "The most that each transaction may pay in fees, in ZEC. Defaults to builder.max_fee.";
 pub(super) const PARAM_PRIVACY_POLICY_DESC: &str =
     "Policy for what information leakage is acceptable.";
+pub(super) const PARAM_ALLOW_SPLIT_DESC: &str = "If true, split the payment across multiple transactions if it would exceed the Orchard action limit.";
+pub(super) const PARAM_INPUT_SELECTION_DESC: &str =
+    "The strategy used to select the notes to spend, overriding builder.input_selection.";
+pub(super) const PARAM_BATCHABLE_DESC: &str =
+    "If true, the payment may be combined with other batchable payments into a single transaction.";
+pub(super) const PARAM_NO_BROADCAST_DESC: &str =
+    "If true, create the transaction without broadcasting it, and return it in the result.";
 
 #[allow(clippy::too_many_arguments)]
 pub(crate) async fn call(
     mut wallet: DbHandle,
     keystore: KeyStore,
     chain: FetchServiceSubscriber,
+    batcher: PaymentBatcher,
     fromaddress: String,
     amounts: Vec<AmountParameter>,
     minconf: Option<u32>,
     fee: Option<JsonValue>,
     privacy_policy: Option<String>,
+    allow_split: Option<bool>,
+    input_selection: Option<String>,
+    batchable: Option<bool>,
+    no_broadcast: Option<bool>,
 ) -> RpcResult<(
     Option<ContextInfo>,
     impl Future<Output = RpcResult<SendResult>>,
@@ -105,50 +153,37 @@ pub(crate) async fn call(
         );
     }
 
-    if fee.is_some() {
+    let fee_limit = FeeLimit::from_param(fee.as_ref(), &APP.config().builder)?;
+
+    let is_batchable = batchable.unwrap_or(false);
+    if is_batchable && allow_split.unwrap_or(false) {
         return Err(LegacyCode::InvalidParameter
-            .with_static("Zallet always calculates fees internally; the fee field must be null."));
+            .with_static("Invalid parameter, batchable payments cannot also be split."));
     }
-
-    let mut recipient_addrs = HashSet::new();
-    let mut payments = vec![];
-    let mut total_out = Zatoshis::ZERO;
-
-    for amount in &amounts {
-        let addr: ZcashAddress = amount.address.parse().map_err(|_| {
-            LegacyCode::InvalidParameter.with_message(format!(
-                "Invalid parameter, unknown address format: {}",
-                amount.address,
-            ))
-        })?;
-
-        if !recipient_addrs.insert(addr.clone()) {
-            return Err(LegacyCode::InvalidParameter.with_message(format!(
-                "Invalid parameter, duplicated recipient address: {}",
-                amount.address,
-            )));
-        }
-
-        let memo = amount.memo.as_deref().map(parse_memo).transpose()?;
-        let value = zatoshis_from_value(&amount.amount)?;
-
-        let payment = Payment::new(addr, value, memo, None, None, vec![]).ok_or_else(|| {
-            LegacyCode::InvalidParameter.with_static("Cannot send memo to transparent recipient")
-        })?;
-
-        payments.push(payment);
-        total_out = (total_out + value)
-            .ok_or_else(|| LegacyCode::InvalidParameter.with_static("Value too large"))?;
+    if is_batchable && fee_limit != FeeLimit::from_config(&APP.config().builder) {
+        return Err(LegacyCode::InvalidParameter
+            .with_static("Invalid parameter, batchable payments cannot set a fee."));
     }
 
-    if payments.is_empty() {
-        return Err(LegacyCode::InvalidParameter.with_static("No recipients"));
+    let broadcast = APP.config().external.broadcast() && !no_broadcast.unwrap_or(false);
+
+    let strategy = input_selection
+        .as_deref()
+        .map(|name| {
+            InputSelection::from_name(name).ok_or_else(|| {
+                LegacyCode::InvalidParameter.with_message(format!(
+                    "Invalid parameter, unknown input selection strategy: {name}",
+                ))
+            })
+        })
+        .transpose()?;
+    if let Some(strategy) = strategy {
+        // This applies to every transaction proposed by this operation.
+        wallet.set_input_selection(strategy);
     }
 
-    let request = TransactionRequest::new(payments).map_err(|e| {
-        // TODO: Map errors to `zcashd` shape.
-        LegacyCode::InvalidParameter.with_message(format!("Invalid payment request: {e}"))
-    })?;
+    let payments = parse_payments(&amounts)?;
+    let request = transaction_request(payments.clone())?;
 
     let account = match fromaddress.as_str() {
         // Select from the legacy transparent address pool.
@@ -156,41 +191,46 @@ pub(crate) async fn call(
         "ANY_TADDR" => Err(LegacyCode::WalletAccountsUnsupported
             .with_static("The legacy account is currently unsupported for spending from")),
         // Select the account corresponding to the given address.
-        _ => {
-            let address = Address::decode(wallet.params(), &fromaddress).ok_or_else(|| {
-                LegacyCode::InvalidAddressOrKey.with_static(
-                "Invalid from address: should be a taddr, zaddr, UA, or the string 'ANY_TADDR'.",
-            )
-            })?;
+        _ => match Address::decode(wallet.params(), &fromaddress) {
+            Some(address) => get_account_for_address(wallet.as_ref(), &address),
+            None => {
+                let address_error = || {
+                    let reason = match fromaddress.parse::<ZcashAddress>() {
+                        Ok(_) => "address is for a different network, or of an unsupported kind"
+                            .to_string(),
+                        Err(e) => e.to_string(),
+                    };
+                    LegacyCode::InvalidAddressOrKey.with_data(
+                        "Invalid from address: should be a taddr, zaddr, UA, account UUID or name, or the string 'ANY_TADDR'.",
+                        json!({ "address": fromaddress, "reason": reason }),
+                    )
+                };
 
-            get_account_for_address(wallet.as_ref(), &address)
-        }
-    }?;
+                // Otherwise, this might be an account UUID or name.
+                let account_id = parse_account_parameter(
+                    wallet.as_ref(),
+                    &keystore,
+                    &JsonValue::String(fromaddress.clone()),
+                )
+                .await
+                .map_err(|_| address_error())?;
 
-    let privacy_policy = match privacy_policy.as_deref() {
-        Some("LegacyCompat") => Err(LegacyCode::InvalidParameter
-            .with_static("LegacyCompat privacy policy is unsupported in Zallet")),
-        Some(s) => PrivacyPolicy::from_str(s).ok_or_else(|| {
-            LegacyCode::InvalidParameter.with_message(format!("Unknown privacy policy {s}"))
-        }),
-        None => Ok(PrivacyPolicy::FullPrivacy),
+                wallet
+                    .as_ref()
+                    .get_account(account_id)
+                    .map_err(ComponentFailure::database)?
+                    .ok_or_else(address_error)
+            }
+        },
     }?;
 
+    let privacy_policy_param = privacy_policy;
+    let privacy_policy = parse_privacy_policy(privacy_policy_param.as_deref())?;
+
     // Sanity check for transaction size
     // TODO: https://github.com/zcash/wallet/issues/255
 
-    let confirmations_policy = match minconf {
-        Some(minconf) => NonZeroU32::new(minconf).map_or(
-            ConfirmationsPolicy::new_symmetrical(NonZeroU32::MIN, true),
-            |c| ConfirmationsPolicy::new_symmetrical(c, false),
-        ),
-        None => {
-            APP.config().builder.confirmations_policy().map_err(|_| {
-                LegacyCode::Wallet.with_message(
-                    "Configuration error: minimum confirmations for spending trusted TXOs cannot exceed that for untrusted TXOs.")
-            })?
-        }
-    };
+    let confirmations_policy = parse_confirmations_policy(minconf);
 
     let params = *wallet.params();
 
@@ -256,11 +296,333 @@ pub(crate) async fn call(
         }
     }
 
+    let proposal = propose(&mut wallet, account.id(), request, confirmations_policy)?;
+
+    enforce_privacy_policy(&proposal, privacy_policy)?;
+
+    let limits = BatchLimits::from_config(&APP.config().builder.limits);
+    let (proposal, groups) = match check_orchard_actions(&proposal, limits.orchard_actions) {
+        Ok(()) => (proposal, vec![payments.clone()]),
+        Err(_) if allow_split.unwrap_or(false) => {
+            let mut plan = plan_split(
+                &mut wallet,
+                account.id(),
+                payments.clone(),
+                confirmations_policy,
+                privacy_policy,
+                &limits,
+            )?
+            .into_iter();
+            let (first, proposal) = plan.next().expect("plan is non-empty");
+            (
+                proposal,
+                std::iter::once(first)
+                    .chain(plan.map(|(payments, _)| payments))
+                    .collect(),
+            )
+        }
+        Err(e) => return Err(e),
+    };
+    check_fees(&proposal, fee_limit)?;
+
+    // Fetch spending key last, to avoid a keystore decryption if unnecessary.
+    let usk = account_spending_key(&keystore, wallet.params(), &account)
+        .await?
+        .ok_or_else(|| {
+            LegacyCode::InvalidAddressOrKey
+                .with_static("Invalid from address, no payment source found for address.")
+        })?;
+
+    // TODO: verify that the proposal satisfies the requested privacy policy
+
+    let account_id = account.id();
+    let batch = is_batchable.then_some(BatchKey {
+        account_id,
+        privacy_policy,
+        minconf,
+        input_selection: strategy,
+        broadcast,
+    });
+
+    let progress = OperationProgress::default();
+    let send_progress = progress.clone();
+    let send = async move {
+        match batch {
+            // The proposal above only checked that this payment can be sent on its own;
+            // the shared transaction is proposed once the batch is complete.
+            Some(key) => {
+                drop(wallet);
+                batcher
+                    .submit(key, confirmations_policy, usk, payments)
+                    .await
+            }
+            None => {
+                run(
+                    wallet,
+                    keystore,
+                    chain,
+                    account_id,
+                    usk,
+                    proposal,
+                    groups,
+                    broadcast,
+                    SplitPolicy {
+                        confirmations_policy,
+                        privacy_policy,
+                        limits,
+                        fee_limit,
+                    },
+                    send_progress,
+                )
+                .await
+            }
+        }
+    };
+
+    Ok((
+        Some(
+            ContextInfo::new(
+                "z_sendmany",
+                json!({
+                    "fromaddress": fromaddress,
+                    "amounts": amounts,
+                    "minconf": minconf,
+                    "fee": fee,
+                    "privacy_policy": privacy_policy_param,
+                    "allow_split": allow_split,
+                    "input_selection": input_selection,
+                    "batchable": batchable,
+                    "no_broadcast": no_broadcast,
+                }),
+            )
+            .with_progress(progress),
+        ),
+        send,
+    ))
+}
+
+/// The parameters of a `z_sendmany` operation, as recorded in its context.
+#[derive(Deserialize)]
+struct ResumeParams {
+    fromaddress: String,
+    amounts: Vec<AmountParameter>,
+    minconf: Option<u32>,
+    fee: Option<JsonValue>,
+    privacy_policy: Option<String>,
+    allow_split: Option<bool>,
+    input_selection: Option<String>,
+    batchable: Option<bool>,
+    no_broadcast: Option<bool>,
+}
+
+/// Re-issues a `z_sendmany` request from the context parameters of an operation that
+/// had not started executing when Zallet last stopped.
+pub(super) async fn resume(
+    wallet: DbHandle,
+    keystore: KeyStore,
+    chain: FetchServiceSubscriber,
+    batcher: PaymentBatcher,
+    params: &str,
+) -> RpcResult<(
+    Option<ContextInfo>,
+    impl Future<Output = RpcResult<SendResult>>,
+)> {
+    let params = serde_json::from_str::<ResumeParams>(params).map_err(|e| {
+        LegacyCode::Deserialization.with_message(format!("Cannot resume operation: {e}"))
+    })?;
+
+    call(
+        wallet,
+        keystore,
+        chain,
+        batcher,
+        params.fromaddress,
+        params.amounts,
+        params.minconf,
+        params.fee,
+        params.privacy_policy,
+        params.allow_split,
+        params.input_selection,
+        params.batchable,
+        params.no_broadcast,
+    )
+    .await
+}
+
+/// Parses the recipients of a payment.
+pub(super) fn parse_payments(amounts: &[AmountParameter]) -> RpcResult<Vec<Payment>> {
+    let mut recipient_addrs = HashSet::new();
+    let mut payments = vec![];
+    let mut total_out = Zatoshis::ZERO;
+    let dust = DustThresholds::from_config(&APP.config().note_management);
+
+    for amount in amounts {
+        let addr: ZcashAddress = amount.address.parse().map_err(|e| {
+            LegacyCode::InvalidParameter.with_data(
+                format!(
+                    "Invalid parameter, unknown address format: {}",
+                    amount.address,
+                ),
+                json!({ "address": amount.address, "reason": format!("{e}") }),
+            )
+        })?;
+
+        if !recipient_addrs.insert(addr.clone()) {
+            return Err(LegacyCode::InvalidParameter.with_data(
+                format!(
+                    "Invalid parameter, duplicated recipient address: {}",
+                    amount.address,
+                ),
+                json!({ "address": amount.address, "reason": "duplicated recipient" }),
+            ));
+        }
+
+        let memo = amount.memo.as_deref().map(parse_memo).transpose()?;
+        let value = zatoshis_from_value(&amount.amount)?;
+        if dust.is_dust(&addr, value) {
+            return Err(LegacyCode::InvalidParameter.with_data(
+                format!(
+                    "Invalid parameter, amount {} for {} is below the dust threshold of {} ZEC",
+                    value_from_zatoshis(value),
+                    amount.address,
+                    value_from_zatoshis(dust.threshold(&addr)),
+                ),
+                json!({
+                    "address": amount.address,
+                    "amount": value.into_u64(),
+                    "limit": dust.threshold(&addr).into_u64(),
+                }),
+            ));
+        }
+
+        let payment = Payment::new(addr, value, memo, None, None, vec![]).ok_or_else(|| {
+            LegacyCode::InvalidParameter.with_static("Cannot send memo to transparent recipient")
+        })?;
+
+        payments.push(payment);
+        total_out = (total_out + value)
+            .ok_or_else(|| LegacyCode::InvalidParameter.with_static("Value too large"))?;
+    }
+
+    if payments.is_empty() {
+        return Err(LegacyCode::InvalidParameter.with_static("No recipients"));
+    }
+
+    Ok(payments)
+}
+
+/// Parses the `privacy_policy` parameter of methods that send funds.
+pub(super) fn parse_privacy_policy(privacy_policy: Option<&str>) -> RpcResult<PrivacyPolicy> {
+    match privacy_policy {
+        Some("LegacyCompat") => Err(LegacyCode::InvalidParameter
+            .with_static("LegacyCompat privacy policy is unsupported in Zallet")),
+        Some(s) => PrivacyPolicy::from_str(s).ok_or_else(|| {
+            LegacyCode::InvalidParameter.with_message(format!("Unknown privacy policy {s}"))
+        }),
+        None => Ok(PrivacyPolicy::FullPrivacy),
+    }
+}
+
+/// Parses the `minconf` parameter of methods that send funds.
+///
+/// If it is omitted, the confirmations configured in the `builder` section are used.
+pub(super) fn parse_confirmations_policy(minconf: Option<u32>) -> ConfirmationsPolicy {
+    match minconf {
+        Some(minconf) => NonZeroU32::new(minconf).map_or(
+            ConfirmationsPolicy::new_symmetrical(NonZeroU32::MIN, true),
+            |c| ConfirmationsPolicy::new_symmetrical(c, false),
+        ),
+        None => APP.config().builder.confirmations_policy(),
+    }
+}
+
+/// Derives the spending key for the account with the given derivation, decrypting its
+/// seed from the keystore.
+pub(super) async fn spending_key(
+    keystore: &KeyStore,
+    params: &Network,
+    derivation: &Zip32Derivation,
+) -> RpcResult<UnifiedSpendingKey> {
+    let seed = keystore
+        .decrypt_seed(derivation.seed_fingerprint())
+        .await
+        .map_err(ComponentFailure::keystore)?;
+
+    UnifiedSpendingKey::from_seed(params, seed.expose_secret(), derivation.account_index())
+        .map_err(|e| LegacyCode::InvalidAddressOrKey.with_message(e.to_string()))
+}
+
+/// Returns the spending key for the given account, or `None` if the account is
+/// watch-only.
+///
+/// Accounts imported from a unified spending key have no derivation, so their key is
+/// read from the keystore instead.
+pub(super) async fn account_spending_key(
+    keystore: &KeyStore,
+    params: &Network,
+    account: &impl Account,
+) -> RpcResult<Option<UnifiedSpendingKey>> {
+    match (account.source().key_derivation(), account.ufvk()) {
+        (Some(derivation), _) => spending_key(keystore, params, derivation).await.map(Some),
+        (None, Some(ufvk)) if !is_watch_only(account) => Ok(keystore
+            .decrypt_standalone_unified_key(ufvk)
+            .await
+            .map_err(ComponentFailure::keystore)?),
+        _ => Ok(None),
+    }
+}
+
+/// The policies that each transaction in a split payment must satisfy.
+#[derive(Clone, Copy)]
+struct SplitPolicy {
+    confirmations_policy: ConfirmationsPolicy,
+    privacy_policy: PrivacyPolicy,
+    limits: BatchLimits,
+    fee_limit: FeeLimit,
+}
+
+/// The progress of a `z_sendmany` operation.
+#[derive(Serialize)]
+struct SendProgress {
+    /// The number of groups of recipients that have been paid.
+    groups_sent: usize,
+    groups_total: usize,
+    /// The transactions sent so far, including those that only merged notes.
+    txids: Vec<String>,
+    /// The transaction whose confirmation the operation is waiting for, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    waiting_for: Option<String>,
+}
+
+/// The next transaction in the chain that pays a group of recipients.
+#[derive(Debug, PartialEq)]
+enum ChainLink {
+    /// The group's proposal can be sent as is.
+    Payment,
+    /// The group's proposal spends more Orchard notes than the action limit permits.
+    /// These of its notes must first be merged into a single note, which a later
+    /// proposal for the group can spend in their place.
+    Consolidation(Vec<ReservedInput>),
+}
+
+/// How often a chain of transactions checks whether its latest transaction has been
+/// confirmed.
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Proposes a transfer of the given payments from the given account.
+pub(super) fn propose(
+    wallet: &mut DbHandle,
+    account_id: AccountUuid,
+    request: TransactionRequest,
+    confirmations_policy: ConfirmationsPolicy,
+) -> RpcResult<Proposal<StandardFeeRule, ReceivedNoteId>> {
+    let params = *wallet.params();
+
     let change_strategy = MultiOutputChangeStrategy::new(
-        StandardFeeRule::Zip317,
+        fees::fee_rule(),
         None,
         ShieldedProtocol::Orchard,
-        DustOutputPolicy::default(),
+        APP.config().note_management.dust_policy(),
         APP.config().note_management.split_policy(),
     );
 
@@ -271,21 +633,46 @@ pub(crate) async fn call(
     // currently do).
     let input_selector = GreedyInputSelector::new();
 
-    let proposal = propose_transfer::<_, _, _, _, Infallible>(
+    propose_transfer::<_, _, _, _, Infallible>(
         wallet.as_mut(),
         &params,
-        account.id(),
+        account_id,
         &input_selector,
         &change_strategy,
         request,
         confirmations_policy,
     )
-    // TODO: Map errors to `zcashd` shape.
-    .map_err(|e| LegacyCode::Wallet.with_message(format!("Failed to propose transaction: {e}")))?;
+    .map_err(|e| {
+        let mut failure = SendFailure::from_proposal_error(&e);
+        if let SendFailure::InsufficientFunds { pool_breakdown, .. } = &mut failure {
+            *pool_breakdown = spendable_by_pool(wallet, account_id, confirmations_policy);
+        }
+        failure.into()
+    })
+}
 
-    enforce_privacy_policy(&proposal, privacy_policy)?;
+/// Returns the funds that the account can spend from each pool, to report alongside an
+/// insufficient-funds error.
+fn spendable_by_pool(
+    wallet: &DbHandle,
+    account_id: AccountUuid,
+    confirmations_policy: ConfirmationsPolicy,
+) -> Option<PoolBreakdown> {
+    let summary = wallet.get_wallet_summary(confirmations_policy).ok()??;
+    let balance = summary.account_balances().get(&account_id)?;
+    Some(PoolBreakdown {
+        transparent: balance.unshielded_balance().spendable_value(),
+        sapling: balance.sapling_balance().spendable_value(),
+        orchard: balance.orchard_balance().spendable_value(),
+    })
+}
 
-    let orchard_actions_limit = APP.config().builder.limits.orchard_actions().into();
+/// Returns an error if any step of the proposal would exceed the configured limit on
+/// Orchard actions.
+pub(super) fn check_orchard_actions<NoteRef>(
+    proposal: &Proposal<StandardFeeRule, NoteRef>,
+    orchard_actions_limit: usize,
+) -> RpcResult<()> {
     for step in proposal.steps() {
         let orchard_spends = step
             .shielded_inputs()
@@ -317,109 +704,532 @@ pub(crate) async fn call(
                 (orchard_actions, "actions")
             };
 
-            return Err(LegacyCode::Misc.with_message(fl!(
-                "err-excess-orchard-actions",
-                count = count,
-                kind = kind,
-                limit = orchard_actions_limit,
-                config = "-orchardactionlimit=N",
-                bound = format!("N >= %u"),
-            )));
+            return Err(LegacyCode::Misc.with_data(
+                fl!(
+                    "err-excess-orchard-actions",
+                    count = count,
+                    kind = kind,
+                    limit = orchard_actions_limit,
+                    config = "-orchardactionlimit=N",
+                    bound = format!("N >= {count}"),
+                ),
+                json!({ "limit": orchard_actions_limit, "requested": count, "kind": kind }),
+            ));
         }
     }
 
-    let derivation = account.source().key_derivation().ok_or_else(|| {
-        LegacyCode::InvalidAddressOrKey
-            .with_static("Invalid from address, no payment source found for address.")
-    })?;
+    Ok(())
+}
 
-    // Fetch spending key last, to avoid a keystore decryption if unnecessary.
-    let seed = keystore
-        .decrypt_seed(derivation.seed_fingerprint())
-        .await
-        .map_err(|e| match e.kind() {
-            // TODO: Improve internal error types.
-            //       https://github.com/zcash/wallet/issues/256
-            crate::error::ErrorKind::Generic if e.to_string() == "Wallet is locked" => {
-                LegacyCode::WalletUnlockNeeded.with_message(e.to_string())
-            }
-            _ => LegacyCode::Database.with_message(e.to_string()),
-        })?;
-    let usk = UnifiedSpendingKey::from_seed(
-        wallet.params(),
-        seed.expose_secret(),
-        derivation.account_index(),
-    )
-    .map_err(|e| LegacyCode::InvalidAddressOrKey.with_message(e.to_string()))?;
+/// Returns an error if any step of the proposal would pay more in fees than `fee_limit`
+/// permits.
+pub(super) fn check_fees<NoteRef>(
+    proposal: &Proposal<StandardFeeRule, NoteRef>,
+    fee_limit: FeeLimit,
+) -> RpcResult<()> {
+    for step in proposal.steps() {
+        fee_limit.check(&action_counts(step), step.balance().fee_required())?;
+    }
 
-    #[cfg(feature = "transparent-key-import")]
-    let standalone_keys = {
-        let mut keys = std::collections::HashMap::new();
-        for step in proposal.steps() {
-            for input in step.transparent_inputs() {
-                if let Some(address) = script::FromChain::parse(&input.txout().script_pubkey().0)
-                    .ok()
-                    .as_ref()
-                    .and_then(TransparentAddress::from_script_from_chain)
-                {
-                    let secret_key = keystore
-                        .decrypt_standalone_transparent_key(&address)
-                        .await
-                        .map_err(|e| match e.kind() {
-                            // TODO: Improve internal error types.
-                            crate::error::ErrorKind::Generic
-                                if e.to_string() == "Wallet is locked" =>
-                            {
-                                LegacyCode::WalletUnlockNeeded.with_message(e.to_string())
-                            }
-                            _ => LegacyCode::Database.with_message(e.to_string()),
-                        })?;
-                    keys.insert(address, secret_key);
-                }
-            }
+    Ok(())
+}
+
+/// Returns the total value of dust change that the proposal adds to its fees.
+///
+/// The change strategy folds change below `note_management.dust_threshold` into the
+/// fee instead of creating an output for it, so any fee in excess of the conventional
+/// fee for a step's actions is dust.
+pub(super) fn folded_dust(proposal: &Proposal<StandardFeeRule, ReceivedNoteId>) -> Zatoshis {
+    proposal
+        .steps()
+        .iter()
+        .map(|step| {
+            (step.balance().fee_required() - action_counts(step).conventional_fee())
+                .unwrap_or(Zatoshis::ZERO)
+        })
+        .fold(Zatoshis::ZERO, |acc, dust| {
+            (acc + dust).expect("fees are bounded by MAX_MONEY")
+        })
+}
+
+/// Returns the logical actions of the transaction that the given proposal step creates.
+fn action_counts<NoteRef>(step: &Step<NoteRef>) -> ActionCounts {
+    let mut counts = ActionCounts {
+        transparent_inputs: step.transparent_inputs().len() + step.prior_step_inputs().len(),
+        ..ActionCounts::default()
+    };
+
+    for note in step
+        .shielded_inputs()
+        .iter()
+        .flat_map(|inputs| inputs.notes())
+    {
+        match note.note().protocol() {
+            ShieldedProtocol::Sapling => counts.sapling_spends += 1,
+            ShieldedProtocol::Orchard => counts.orchard_spends += 1,
+        }
+    }
+
+    for pool in step.payment_pools().values().copied().chain(
+        step.balance()
+            .proposed_change()
+            .iter()
+            .map(|change| change.output_pool()),
+    ) {
+        match pool {
+            PoolType::Transparent => counts.transparent_outputs += 1,
+            PoolType::Shielded(ShieldedProtocol::Sapling) => counts.sapling_outputs += 1,
+            PoolType::Shielded(ShieldedProtocol::Orchard) => counts.orchard_outputs += 1,
         }
-        keys
+    }
+
+    counts
+}
+
+/// Returns the next transaction in the chain that pays the recipients of `proposal`.
+///
+/// Returns an error if the proposal exceeds the Orchard action limit in a way that
+/// merging its notes cannot fix, such as by paying too many Orchard recipients.
+fn next_link<NoteRef>(
+    proposal: &Proposal<StandardFeeRule, NoteRef>,
+    limits: &BatchLimits,
+) -> RpcResult<ChainLink> {
+    let Err(e) = check_orchard_actions(proposal, limits.orchard_actions) else {
+        return Ok(ChainLink::Payment);
     };
 
-    // TODO: verify that the proposal satisfies the requested privacy policy
+    if proposal
+        .steps()
+        .iter()
+        .any(|step| action_counts(step).orchard_outputs > limits.orchard_actions)
+    {
+        return Err(e);
+    }
 
-    Ok((
-        Some(ContextInfo::new(
-            "z_sendmany",
-            json!({
-                "fromaddress": fromaddress,
-                "amounts": amounts,
-                "minconf": minconf
-            }),
-        )),
-        run(
-            wallet,
-            chain,
-            proposal,
-            SpendingKeys::new(
-                usk,
-                #[cfg(feature = "zcashd-import")]
-                standalone_keys,
-            ),
-        ),
-    ))
+    let notes = proposal_inputs(proposal).into_iter().filter(|input| {
+        matches!(
+            input,
+            ReservedInput::Note {
+                protocol: ShieldedProtocol::Orchard,
+                ..
+            }
+        )
+    });
+    match plan_batches(notes, |_| InputKind::Orchard, limits)
+        .into_iter()
+        .next()
+    {
+        // Merging a single note would not reduce the number of notes to spend.
+        Some(batch) if batch.len() > 1 => Ok(ChainLink::Consolidation(batch)),
+        _ => Err(e),
+    }
+}
+
+/// Returns the total values of the inputs and outputs of each transaction that the
+/// proposal creates, in order.
+///
+/// The outputs of each step are its payments and change, and its inputs cover those
+/// outputs and the step's fee.
+pub(super) fn tx_values<NoteRef>(proposal: &Proposal<StandardFeeRule, NoteRef>) -> Vec<TxValues> {
+    proposal
+        .steps()
+        .iter()
+        .map(|step| {
+            let total_output = step
+                .transaction_request()
+                .payments()
+                .values()
+                .map(|payment| payment.amount())
+                .chain(
+                    step.balance()
+                        .proposed_change()
+                        .iter()
+                        .map(|change| change.value()),
+                )
+                .try_fold(Zatoshis::ZERO, |acc, value| acc + value)
+                .expect("outputs are bounded by MAX_MONEY");
+            TxValues {
+                total_input: (total_output + step.balance().fee_required())
+                    .expect("inputs are bounded by MAX_MONEY"),
+                total_output,
+            }
+        })
+        .collect()
+}
+
+/// Splits the given payments into groups that can each be sent in transactions that
+/// satisfy the Orchard action limit.
+///
+/// Groups are planned with [`plan_recipients`], from the pool in which each recipient
+/// will be paid. A group whose proposal spends too many notes is kept, as its notes can
+/// be merged by earlier transactions in the chain (see [`ChainLink`]).
+///
+/// Each group is proposed against the current wallet state, so the returned proposal is
+/// only usable for the first group; later groups must be proposed again after the
+/// transactions for earlier groups have been created.
+fn plan_split(
+    wallet: &mut DbHandle,
+    account_id: AccountUuid,
+    payments: Vec<Payment>,
+    confirmations_policy: ConfirmationsPolicy,
+    privacy_policy: PrivacyPolicy,
+    limits: &BatchLimits,
+) -> RpcResult<Vec<(Vec<Payment>, Proposal<StandardFeeRule, ReceivedNoteId>)>> {
+    let params = *wallet.params();
+
+    plan_recipients(payments, |payment| recipient_pool(&params, payment), limits)
+        .into_iter()
+        .map(|group| {
+            let proposal = propose(
+                wallet,
+                account_id,
+                transaction_request(group.clone())?,
+                confirmations_policy,
+            )?;
+            enforce_privacy_policy(&proposal, privacy_policy)?;
+            next_link(&proposal, limits)?;
+            Ok((group, proposal))
+        })
+        .collect()
+}
+
+/// Returns the pool in which the given payment's recipient will be paid.
+///
+/// Unified Address recipients are paid in the most recent pool that they have a
+/// receiver for.
+fn recipient_pool(params: &Network, payment: &Payment) -> PoolType {
+    match Address::try_from_zcash_address(params, payment.recipient_address().clone()) {
+        Ok(Address::Unified(ua)) if ua.has_orchard() => PoolType::ORCHARD,
+        Ok(Address::Unified(ua)) if ua.has_sapling() => PoolType::SAPLING,
+        Ok(Address::Sapling(_)) => PoolType::SAPLING,
+        _ => PoolType::Transparent,
+    }
+}
+
+pub(super) fn transaction_request(payments: Vec<Payment>) -> RpcResult<TransactionRequest> {
+    TransactionRequest::new(payments).map_err(|e| {
+        // TODO: Map errors to `zcashd` shape.
+        LegacyCode::InvalidParameter.with_message(format!("Invalid payment request: {e}"))
+    })
+}
+
+#[cfg(feature = "transparent-key-import")]
+pub(super) async fn standalone_keys<NoteRef>(
+    keystore: &KeyStore,
+    proposal: &Proposal<StandardFeeRule, NoteRef>,
+) -> RpcResult<std::collections::HashMap<TransparentAddress, secp256k1::SecretKey>> {
+    let mut keys = std::collections::HashMap::new();
+    for step in proposal.steps() {
+        for input in step.transparent_inputs() {
+            if let Some(address) = script::FromChain::parse(&input.txout().script_pubkey().0)
+                .ok()
+                .as_ref()
+                .and_then(TransparentAddress::from_script_from_chain)
+            {
+                let secret_key = keystore
+                    .decrypt_standalone_transparent_key(&address)
+                    .await
+                    .map_err(ComponentFailure::keystore)?;
+                keys.insert(address, secret_key);
+            }
+        }
+    }
+    Ok(keys)
 }
 
 /// Construct and send the transaction, returning the resulting txid.
 /// Errors in transaction construction will throw.
 ///
+/// `groups` contains the payments made by each transaction, of which `proposal` is the
+/// proposal for the first. If the payment was split to satisfy the Orchard action limit,
+/// the later groups are proposed and sent in order after `proposal`, so that each one
+/// selects from the funds left after its predecessors. A group that would spend too many
+/// notes is preceded by transactions merging some of them (see [`ChainLink`]).
+///
+/// The inputs of each transaction are reserved until the operation finishes. If they
+/// conflict with another operation's inputs, the transaction is proposed again once that
+/// operation finishes.
+///
 /// Notes:
 /// 1. #1159 Currently there is no limit set on the number of elements, which could
 ///    make the tx too large.
 /// 2. #1360 Note selection is not optimal.
-/// 3. #1277 Spendable notes are not locked, so an operation running in parallel
-///    could also try to use them.
+#[allow(clippy::too_many_arguments)]
 async fn run(
     mut wallet: DbHandle,
+    keystore: KeyStore,
     chain: FetchServiceSubscriber,
+    account_id: AccountUuid,
+    usk: UnifiedSpendingKey,
     proposal: Proposal<StandardFeeRule, ReceivedNoteId>,
-    spending_keys: SpendingKeys,
+    groups: Vec<Vec<Payment>>,
+    broadcast: bool,
+    policy: SplitPolicy,
+    progress: OperationProgress,
 ) -> RpcResult<SendResult> {
+    let groups_total = groups.len();
+    let mut results: Vec<(Vec<String>, SendResult)> = vec![];
+    // The reserved inputs are held until the operation finishes.
+    let mut reservations = vec![];
+    let mut first = Some(proposal);
+
+    let report = |results: &[(Vec<String>, SendResult)], waiting_for: Option<TxId>| {
+        progress.set(&SendProgress {
+            groups_sent: results
+                .iter()
+                .filter(|(recipients, _)| !recipients.is_empty())
+                .count(),
+            groups_total,
+            txids: results
+                .iter()
+                .flat_map(|(_, result)| result.txids())
+                .cloned()
+                .collect(),
+            waiting_for: waiting_for.map(|txid| txid.to_string()),
+        })
+    };
+
+    for payments in groups {
+        let propose_group = |wallet: &mut DbHandle| -> RpcResult<_> {
+            let proposal = propose(
+                wallet,
+                account_id,
+                transaction_request(payments.clone())?,
+                policy.confirmations_policy,
+            )?;
+            enforce_privacy_policy(&proposal, policy.privacy_policy)?;
+            Ok(proposal)
+        };
+        let propose_payment = |wallet: &mut DbHandle| -> RpcResult<_> {
+            let proposal = propose_group(wallet)?;
+            check_orchard_actions(&proposal, policy.limits.orchard_actions)?;
+            check_fees(&proposal, policy.fee_limit)?;
+            Ok(proposal)
+        };
+
+        // Merge notes until the group can be paid by a single transaction.
+        let proposal = loop {
+            let proposal = match first.take() {
+                Some(proposal) => Ok(proposal),
+                None => propose_group(&mut wallet),
+            };
+            let inputs = match proposal.and_then(|proposal| {
+                next_link(&proposal, &policy.limits).map(|link| (proposal, link))
+            }) {
+                Ok((proposal, ChainLink::Payment)) => break proposal,
+                Ok((_, ChainLink::Consolidation(inputs))) if broadcast => inputs,
+                Ok(_) => {
+                    return Err(already_sent(
+                        LegacyCode::InvalidParameter.with_static(
+                            "Invalid parameter, a payment that must merge notes before it can be sent cannot be combined with no_broadcast.",
+                        ),
+                        &results,
+                    ));
+                }
+                Err(e) => return Err(already_sent(e, &results)),
+            };
+
+            let (returned_wallet, reservation, txid, result) = consolidate(
+                wallet,
+                &keystore,
+                chain.clone(),
+                account_id,
+                &usk,
+                inputs,
+                &policy,
+            )
+            .await
+            .map_err(|e| already_sent(e, &results))?;
+            wallet = returned_wallet;
+            reservations.push(reservation);
+            let expiry_height = result.expiry_height();
+            results.push((vec![], result));
+
+            report(&results, Some(txid));
+            wait_for_spendable(&wallet, txid, expiry_height, policy.confirmations_policy)
+                .await
+                .map_err(|e| already_sent(e, &results))?;
+
+            // The merged note is larger than any of the notes it merged, so spending the
+            // largest notes first ensures that the group's next proposal spends it.
+            wallet.set_input_selection(InputSelection::LargestFirst);
+        };
+
+        let proposal = match check_fees(&proposal, policy.fee_limit) {
+            Ok(()) => reserve_inputs(&mut wallet, proposal, &propose_payment).await,
+            Err(e) => Err(e),
+        };
+        let (proposal, reservation) = proposal.map_err(|e| already_sent(e, &results))?;
+        reservations.push(reservation);
+
+        let recipients = proposal
+            .steps()
+            .last()
+            .transaction_request()
+            .payments()
+            .values()
+            .map(|payment| payment.recipient_address().encode())
+            .collect::<Vec<_>>();
+
+        let (returned_wallet, _, result) =
+            send_proposal(wallet, &keystore, chain.clone(), &usk, proposal, broadcast)
+                .await
+                .map_err(|e| already_sent(e, &results))?;
+        wallet = returned_wallet;
+        results.push((recipients, result));
+        report(&results, None);
+    }
+
+    Ok(if results.len() > 1 {
+        SendResult::from_split(results)
+    } else {
+        results.pop().expect("created at least one transaction").1
+    })
+}
+
+/// Adds the transactions that an operation has already sent to the error that stopped
+/// it, so that the caller can determine which payments remain outstanding.
+fn already_sent(e: ErrorObjectOwned, results: &[(Vec<String>, SendResult)]) -> ErrorObjectOwned {
+    if results.is_empty() {
+        e
+    } else {
+        LegacyCode::Wallet.with_message(format!(
+            "{} (transactions already sent: {})",
+            e.message(),
+            results
+                .iter()
+                .flat_map(|(_, result)| result.txids())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", "),
+        ))
+    }
+}
+
+/// Merges the given Orchard notes into a single note at the account's internal Orchard
+/// address, so that a later transaction can spend their value in a single action.
+///
+/// Returns the wallet handle along with the reservation of the merged notes, and the ID
+/// of the merging transaction.
+async fn consolidate(
+    mut wallet: DbHandle,
+    keystore: &KeyStore,
+    chain: FetchServiceSubscriber,
+    account_id: AccountUuid,
+    usk: &UnifiedSpendingKey,
+    inputs: Vec<ReservedInput>,
+    policy: &SplitPolicy,
+) -> RpcResult<(DbHandle, Reservation, TxId, SendResult)> {
+    let params = *wallet.params();
+    let recipient = usk
+        .to_unified_full_viewing_key()
+        .orchard()
+        .and_then(|fvk| {
+            UnifiedAddress::from_receivers(
+                Some(fvk.address_at(0u32, orchard::keys::Scope::Internal)),
+                None,
+                None,
+            )
+        })
+        .ok_or_else(|| {
+            LegacyCode::Wallet.with_static("Cannot merge notes for an account without Orchard keys")
+        })?;
+
+    let request = MergeRequest {
+        account_id,
+        pools: vec![ShieldedProtocol::Orchard],
+        recipient: Address::Unified(recipient).to_zcash_address(&params),
+        memo: None,
+        confirmations_policy: policy.confirmations_policy,
+        orchard_actions_limit: policy.limits.orchard_actions,
+        fee_limit: policy.fee_limit,
+    };
+
+    wallet.restrict_inputs(inputs.into_iter().collect());
+    let proposal = request.propose(&mut wallet);
+    let reserved = match proposal {
+        Ok(proposal) => {
+            reserve_inputs(&mut wallet, proposal, |wallet| request.propose(wallet)).await
+        }
+        Err(e) => Err(e),
+    };
+    // Later proposals may spend any of the account's notes.
+    wallet.allow_all_inputs();
+    let (proposal, reservation) = reserved?;
+
+    let (wallet, txids, result) =
+        send_proposal(wallet, keystore, chain, usk, proposal, true).await?;
+    let txid = *txids.last().expect("created at least one transaction");
+    Ok((wallet, reservation, txid, result))
+}
+
+/// Waits until the given transaction has enough confirmations for its outputs to be
+/// spent under `confirmations_policy`.
+///
+/// Returns an error if the transaction expires without being mined, or if Zallet is
+/// shutting down.
+async fn wait_for_spendable(
+    wallet: &DbHandle,
+    txid: TxId,
+    expiry_height: BlockHeight,
+    confirmations_policy: ConfirmationsPolicy,
+) -> RpcResult<()> {
+    loop {
+        asyncop::checkpoint()?;
+
+        let chain_height = wallet.chain_height().map_err(ComponentFailure::database)?;
+        let mined_height = wallet
+            .get_tx_height(txid)
+            .map_err(ComponentFailure::database)?;
+
+        match (chain_height, mined_height) {
+            (Some(chain_height), Some(mined_height))
+                if (u32::from(chain_height) + 1).saturating_sub(u32::from(mined_height))
+                    >= confirmations_policy.trusted().get() =>
+            {
+                return Ok(());
+            }
+            (Some(chain_height), None) if chain_height >= expiry_height => {
+                return Err(LegacyCode::Wallet
+                    .with_message(format!("Transaction {txid} expired before it was mined")));
+            }
+            _ => tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await,
+        }
+    }
+}
+
+/// Creates the transactions for the given proposal, and broadcasts them if `broadcast`
+/// is `true`.
+///
+/// Returns the wallet handle along with the IDs of the created transactions, in the
+/// order of the proposal's steps.
+pub(super) async fn send_proposal(
+    mut wallet: DbHandle,
+    keystore: &KeyStore,
+    chain: FetchServiceSubscriber,
+    usk: &UnifiedSpendingKey,
+    proposal: Proposal<StandardFeeRule, ReceivedNoteId>,
+    broadcast: bool,
+) -> RpcResult<(DbHandle, Vec<TxId>, SendResult)> {
+    #[cfg(feature = "transparent-key-import")]
+    let standalone_keys = standalone_keys(keystore, &proposal).await?;
+
+    #[cfg(not(feature = "transparent-key-import"))]
+    let _ = keystore;
+
+    let spending_keys = SpendingKeys::new(
+        usk.clone(),
+        #[cfg(feature = "zcashd-import")]
+        standalone_keys,
+    );
+
+    let folded_dust = folded_dust(&proposal);
+    let tx_values = tx_values(&proposal);
+
+    // Once the transactions are created they will be broadcast, so this is the last
+    // point at which a shutdown can stop the operation.
+    asyncop::checkpoint()?;
+
     let prover = LocalTxProver::bundled();
     let (wallet, txids) = crate::spawn_blocking!("z_sendmany prover", move || {
         let params = *wallet.params();
@@ -435,9 +1245,751 @@ async fn run(
         .map(|txids| (wallet, txids))
     })
     .await
-    // TODO: Map errors to `zcashd` shape.
-    .map_err(|e| LegacyCode::Wallet.with_message(format!("Failed to propose transaction: {e}")))?
-    .map_err(|e| LegacyCode::Wallet.with_message(format!("Failed to propose transaction: {e}")))?;
+    .map_err(|e| SendFailure::Internal(e.to_string()))?
+    .map_err(|e| SendFailure::from_wallet_error(&e))?;
+
+    let txids = Vec::from(txids);
+    asyncop::record_txids(&txids).await;
+    // The transactions have already been created, so send them even if this fails.
+    if let Err(e) = wallet.record_tx_values(txids.iter().copied().zip(tx_values)) {
+        warn!("Failed to record the input and output values of the created transactions: {e}");
+    }
+    let result = broadcast_transactions(&wallet, chain, txids.clone(), broadcast)
+        .await?
+        .with_folded_dust(folded_dust);
+    Ok((wallet, txids, result))
+}
+
+/// Queues batchable `z_sendmany` payments, and combines compatible payments into shared
+/// transactions.
+///
+/// A batch is sent once `builder.batching.window` has elapsed since its first payment
+/// was queued, or as soon as it contains `builder.batching.max_payments` payments.
+#[derive(Clone)]
+pub(crate) struct PaymentBatcher {
+    wallet: Database,
+    keystore: KeyStore,
+    chain: Chain,
+    queue: Arc<Mutex<BatchQueue>>,
+}
+
+#[derive(Default)]
+struct BatchQueue {
+    next_id: u64,
+    batches: Vec<PendingBatch>,
+}
+
+/// The parameters that payments must share in order to be sent in the same transaction.
+#[derive(Clone, Copy, PartialEq)]
+struct BatchKey {
+    account_id: AccountUuid,
+    privacy_policy: PrivacyPolicy,
+    minconf: Option<u32>,
+    input_selection: Option<InputSelection>,
+    broadcast: bool,
+}
+
+struct PendingBatch {
+    id: u64,
+    key: BatchKey,
+    confirmations_policy: ConfirmationsPolicy,
+    usk: UnifiedSpendingKey,
+    members: Vec<BatchMember>,
+}
+
+/// A queued `z_sendmany` operation.
+struct BatchMember {
+    payments: Vec<Payment>,
+    /// The async operation waiting for this member's result.
+    operations: Vec<AsyncOperation>,
+    result: oneshot::Sender<RpcResult<SendResult>>,
+}
+
+impl BatchQueue {
+    /// Adds a member to the pending batch with the given key, opening a new batch if
+    /// there is none.
+    ///
+    /// Returns the ID of the batch if it was opened by this member, and the batch itself
+    /// if it now contains `max_payments` members and has been removed from the queue.
+    fn push(
+        &mut self,
+        key: BatchKey,
+        confirmations_policy: ConfirmationsPolicy,
+        usk: UnifiedSpendingKey,
+        member: BatchMember,
+        max_payments: u16,
+    ) -> (Option<u64>, Option<PendingBatch>) {
+        let (index, opened) = match self.batches.iter().position(|batch| batch.key == key) {
+            Some(index) => (index, None),
+            None => {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.batches.push(PendingBatch {
+                    id,
+                    key,
+                    confirmations_policy,
+                    usk,
+                    members: vec![],
+                });
+                (self.batches.len() - 1, Some(id))
+            }
+        };
+
+        let batch = &mut self.batches[index];
+        batch.members.push(member);
+        let full = (batch.members.len() >= usize::from(max_payments.max(1)))
+            .then(|| self.batches.remove(index));
+        (opened, full)
+    }
+
+    /// Removes the pending batch with the given ID, if it has not already been sent.
+    fn take(&mut self, id: u64) -> Option<PendingBatch> {
+        let index = self.batches.iter().position(|batch| batch.id == id)?;
+        Some(self.batches.remove(index))
+    }
+}
+
+impl PaymentBatcher {
+    pub(crate) fn new(wallet: Database, keystore: KeyStore, chain: Chain) -> Self {
+        Self {
+            wallet,
+            keystore,
+            chain,
+            queue: Arc::new(Mutex::new(BatchQueue::default())),
+        }
+    }
+
+    /// Queues the given payments to be sent in a transaction shared with other
+    /// compatible payments, and waits for that transaction to be sent.
+    async fn submit(
+        &self,
+        key: BatchKey,
+        confirmations_policy: ConfirmationsPolicy,
+        usk: UnifiedSpendingKey,
+        payments: Vec<Payment>,
+    ) -> RpcResult<SendResult> {
+        let config = APP.config();
+        let config = &config.builder.batching;
+        let (result, receiver) = oneshot::channel();
+
+        let member = BatchMember {
+            payments,
+            operations: asyncop::current_operations(),
+            result,
+        };
+        let (opened, full) = self.queue.lock().expect("not poisoned").push(
+            key,
+            confirmations_policy,
+            usk,
+            member,
+            config.max_payments(),
+        );
+
+        // Send the batch once its window closes, unless it fills up first.
+        if let Some(id) = opened {
+            let batcher = self.clone();
+            let window = config.window();
+            crate::spawn!("z_sendmany batch window", async move {
+                tokio::time::sleep(window).await;
+                if let Some(batch) = batcher.take(id) {
+                    batcher.send(batch).await;
+                }
+            });
+        }
+
+        if let Some(batch) = full {
+            let batcher = self.clone();
+            crate::spawn!("z_sendmany batch", async move { batcher.send(batch).await });
+        }
+
+        receiver.await.unwrap_or_else(|_| {
+            Err(LegacyCode::Misc.with_static("Payment batch was dropped before being sent"))
+        })
+    }
+
+    /// Removes the pending batch with the given ID from the queue.
+    fn take(&self, id: u64) -> Option<PendingBatch> {
+        self.queue.lock().expect("not poisoned").take(id)
+    }
+
+    /// Sends the given batch in a shared transaction, and reports the outcome to each of
+    /// its members.
+    ///
+    /// If the shared transaction cannot be created, every member fails with the same
+    /// error.
+    async fn send(&self, batch: PendingBatch) {
+        // Skip members whose operations are no longer waiting, such as those that timed
+        // out while the batch was open.
+        let mut members = batch
+            .members
+            .into_iter()
+            .filter(|m| !m.result.is_closed())
+            .collect::<Vec<_>>();
+        if members.is_empty() {
+            return;
+        }
+
+        let payments = members
+            .iter()
+            .flat_map(|m| m.payments.iter().cloned())
+            .collect();
+        let operations = members
+            .iter_mut()
+            .flat_map(|m| m.operations.drain(..))
+            .collect();
+
+        // The shared transaction is recorded against every member's operation.
+        let outcome = asyncop::on_behalf_of(
+            operations,
+            self.send_payments(batch.key, batch.confirmations_policy, &batch.usk, payments),
+        )
+        .await;
+        report_batch(members, outcome);
+    }
+
+    /// Sends the given payments in a single transaction, returning the result along with
+    /// the transaction's outputs.
+    async fn send_payments(
+        &self,
+        key: BatchKey,
+        confirmations_policy: ConfirmationsPolicy,
+        usk: &UnifiedSpendingKey,
+        payments: Vec<Payment>,
+    ) -> RpcResult<(SendResult, Vec<SentOutput>)> {
+        let mut wallet = self
+            .wallet
+            .handle()
+            .await
+            .map_err(|e| ComponentFailure::database(e.message()))?;
+        let chain = self
+            .chain
+            .subscribe()
+            .await
+            .map(|s| s.inner())
+            .map_err(|_| ComponentFailure::ChainUnavailable)?;
+
+        if let Some(strategy) = key.input_selection {
+            wallet.set_input_selection(strategy);
+        }
+
+        let propose_batch = |wallet: &mut DbHandle| -> RpcResult<_> {
+            let proposal = propose(
+                wallet,
+                key.account_id,
+                transaction_request(payments.clone())?,
+                confirmations_policy,
+            )?;
+            enforce_privacy_policy(&proposal, key.privacy_policy)?;
+            check_orchard_actions(
+                &proposal,
+                APP.config().builder.limits.orchard_actions().into(),
+            )?;
+            check_fees(&proposal, FeeLimit::from_config(&APP.config().builder))?;
+            Ok(proposal)
+        };
+        let proposal = propose_batch(&mut wallet)?;
+        let (proposal, _reservation) = reserve_inputs(&mut wallet, proposal, propose_batch).await?;
+
+        let (wallet, txids, result) =
+            send_proposal(wallet, &self.keystore, chain, usk, proposal, key.broadcast).await?;
+
+        // The payments are made by the final transaction of the proposal.
+        let txid = txids.last().expect("created at least one transaction");
+        let outputs = wallet
+            .with_raw(|conn, _| sent_outputs(conn, txid))
+            .map_err(ComponentFailure::database)?;
 
-    broadcast_transactions(&wallet, chain, txids.into()).await
+        Ok((result, outputs))
+    }
+}
+
+/// Reports the outcome of sending a shared transaction to each of the batch members
+/// whose payments it contained.
+///
+/// Each member receives the outputs that pay its own payments. If the shared transaction
+/// could not be created, every member fails with the same error.
+fn report_batch(members: Vec<BatchMember>, outcome: RpcResult<(SendResult, Vec<SentOutput>)>) {
+    match outcome {
+        Ok((result, mut outputs)) => {
+            for member in members {
+                let outputs = member
+                    .payments
+                    .iter()
+                    .filter_map(|payment| take_output(&mut outputs, payment))
+                    .collect();
+                // The operation may no longer be waiting for its result.
+                let _ = member.result.send(Ok(result.clone().with_outputs(outputs)));
+            }
+        }
+        Err(e) => {
+            for member in members {
+                let _ = member.result.send(Err(e.clone()));
+            }
+        }
+    }
+}
+
+/// An output of a transaction sent by the wallet.
+struct SentOutput {
+    pool: PoolType,
+    output_index: u32,
+    address: Option<String>,
+    value: u64,
+}
+
+/// Returns the non-change outputs of the given transaction.
+fn sent_outputs(conn: &rusqlite::Connection, txid: &TxId) -> rusqlite::Result<Vec<SentOutput>> {
+    let mut stmt = conn.prepare(
+        "SELECT output_pool, output_index, to_address, value
+         FROM v_tx_outputs
+         WHERE txid = :txid
+         AND NOT is_change",
+    )?;
+
+    let outputs = stmt
+        .query_and_then(named_params! { ":txid": txid.as_ref() }, |row| {
+            let pool = match row.get::<_, i64>("output_pool")? {
+                0 => Some(PoolType::Transparent),
+                2 => Some(PoolType::SAPLING),
+                3 => Some(PoolType::ORCHARD),
+                _ => None,
+            };
+            Ok::<_, rusqlite::Error>(pool.map(|pool| SentOutput {
+                pool,
+                output_index: row.get("output_index")?,
+                address: row.get("to_address")?,
+                value: row.get("value")?,
+            }))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(outputs.into_iter().flatten().collect())
+}
+
+/// Removes the output that pays the given payment from `outputs`, if present.
+///
+/// Outputs paying the same amount to the same recipient are indistinguishable, so any
+/// one of them may be used.
+fn take_output(outputs: &mut Vec<SentOutput>, payment: &Payment) -> Option<BatchedOutput> {
+    let address = payment.recipient_address().encode();
+    let index = outputs.iter().position(|output| {
+        output.address.as_ref() == Some(&address) && output.value == payment.amount().into_u64()
+    })?;
+    let output = outputs.swap_remove(index);
+    Some(BatchedOutput::new(
+        address,
+        output.pool,
+        output.output_index,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use jsonrpsee::core::RpcResult;
+    use serde_json::json;
+    use tokio::sync::oneshot;
+    use uuid::Uuid;
+    use zcash_address::ZcashAddress;
+    use zcash_client_backend::{
+        data_api::{
+            Account as _, InputSource, MaxSpendMode, TargetValue, WalletRead,
+            testing::{
+                AddressType, TestBuilder, TestState, orchard::OrchardPoolTester,
+                pool::ShieldedPoolTester,
+            },
+            wallet::{
+                ConfirmationsPolicy, SpendingKeys, TargetHeight, create_proposed_transactions,
+                input_selection::GreedyInputSelector, propose_transfer,
+            },
+        },
+        fees::{
+            DustOutputPolicy, SplitPolicy, StandardFeeRule, standard::MultiOutputChangeStrategy,
+        },
+        proposal::Proposal,
+        wallet::OvkPolicy,
+        zip321::{Payment, TransactionRequest},
+    };
+    use zcash_client_sqlite::{
+        AccountUuid, ReceivedNoteId,
+        testing::{BlockCache, db::TestDb, db::TestDbFactory},
+    };
+    use zcash_keys::keys::UnifiedSpendingKey;
+    use zcash_primitives::block::BlockHash;
+    use zcash_proofs::prover::LocalTxProver;
+    use zcash_protocol::{
+        PoolType, ShieldedProtocol, TxId,
+        consensus::{BlockHeight, MainNetwork, NetworkType},
+        local_consensus::LocalNetwork,
+        value::Zatoshis,
+    };
+
+    use crate::{
+        components::{
+            database::ReservedInput,
+            json_rpc::{
+                batching::BatchLimits,
+                payments::{PrivacyPolicy, SendResult, proposal_inputs},
+                server::LegacyCode,
+            },
+        },
+        config::BuilderSection,
+    };
+
+    use super::{
+        BatchKey, BatchMember, BatchQueue, ChainLink, SentOutput, check_orchard_actions, next_link,
+        report_batch,
+    };
+
+    type TestWallet = TestState<BlockCache, TestDb, LocalNetwork>;
+
+    const LIMITS: BatchLimits = BatchLimits {
+        transparent_inputs: 50,
+        sapling_spends: 200,
+        orchard_actions: 3,
+    };
+
+    const NOTE_VALUE: u64 = 100_000;
+
+    /// Returns a wallet whose account has four spendable Orchard notes.
+    fn funded_wallet() -> TestWallet {
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let fvk = OrchardPoolTester::test_account_fvk(&st);
+
+        let value = Zatoshis::const_from_u64(NOTE_VALUE);
+        let start = st
+            .generate_next_block(&fvk, AddressType::DefaultExternal, value)
+            .0;
+        for _ in 1..4 {
+            st.generate_next_block(&fvk, AddressType::DefaultExternal, value);
+        }
+        st.scan_cached_blocks(start, 4);
+        st
+    }
+
+    /// Proposes paying `value` zatoshis to the account's own Orchard address.
+    fn propose_to_self(
+        st: &mut TestWallet,
+        value: u64,
+    ) -> Proposal<StandardFeeRule, ReceivedNoteId> {
+        let account_id = st.test_account().unwrap().id();
+        let fvk = OrchardPoolTester::test_account_fvk(st);
+        let network = *st.network();
+
+        let request = TransactionRequest::new(vec![Payment::without_memo(
+            OrchardPoolTester::fvk_default_address(&fvk).to_zcash_address(&network),
+            Zatoshis::const_from_u64(value),
+        )])
+        .unwrap();
+        let change_strategy = MultiOutputChangeStrategy::new(
+            StandardFeeRule::Zip317,
+            None,
+            ShieldedProtocol::Orchard,
+            DustOutputPolicy::default(),
+            SplitPolicy::single_output(),
+        );
+
+        propose_transfer::<_, _, _, _, Infallible>(
+            st.wallet_mut(),
+            &network,
+            account_id,
+            &GreedyInputSelector::new(),
+            &change_strategy,
+            request,
+            ConfirmationsPolicy::new_symmetrical(1.try_into().unwrap(), false),
+        )
+        .unwrap()
+    }
+
+    /// Creates the transaction for the given proposal, and mines it.
+    fn send_and_mine(
+        st: &mut TestWallet,
+        usk: &UnifiedSpendingKey,
+        proposal: &Proposal<StandardFeeRule, ReceivedNoteId>,
+    ) -> TxId {
+        let network = *st.network();
+        let prover = LocalTxProver::bundled();
+        let spending_keys = SpendingKeys::new(
+            usk.clone(),
+            #[cfg(feature = "zcashd-import")]
+            Default::default(),
+        );
+
+        let txid = *create_proposed_transactions::<_, _, Infallible, _, Infallible, _>(
+            st.wallet_mut(),
+            &network,
+            &prover,
+            &prover,
+            &spending_keys,
+            OvkPolicy::Sender,
+            proposal,
+        )
+        .unwrap()
+        .first();
+
+        let height = st.generate_next_block_including(txid).0;
+        st.scan_cached_blocks(height, 1);
+        txid
+    }
+
+    #[test]
+    fn configured_confirmations_select_notes() {
+        let st = funded_wallet();
+        let account_id = st.test_account().unwrap().id();
+        let target_height = TargetHeight::from(st.wallet().chain_height().unwrap().unwrap() + 1);
+
+        // The notes have 4, 3, 2 and 1 confirmations. They were received from another
+        // party, so are untrusted.
+        let selected = |trusted, untrusted| {
+            let builder = BuilderSection {
+                trusted_confirmations: Some(trusted),
+                untrusted_confirmations: Some(untrusted),
+                ..Default::default()
+            };
+            st.wallet()
+                .select_spendable_notes(
+                    account_id,
+                    TargetValue::AllFunds(MaxSpendMode::MaxSpendable),
+                    &[ShieldedProtocol::Orchard],
+                    target_height,
+                    builder.confirmations_policy(),
+                    &[],
+                )
+                .unwrap()
+                .orchard()
+                .len()
+        };
+        assert_eq!(selected(1, 1), 4);
+        assert_eq!(selected(1, 2), 3);
+        assert_eq!(selected(1, 3), 2);
+        assert_eq!(selected(3, 4), 1);
+
+        // The defaults require more confirmations than any of the notes have.
+        assert_eq!(selected(3, 10), 0);
+    }
+
+    #[test]
+    fn orchard_action_overflow() {
+        let mut st = funded_wallet();
+
+        // Once the fee is included, this payment must spend all four notes.
+        let proposal = propose_to_self(&mut st, 3 * NOTE_VALUE);
+        let inputs = proposal_inputs(&proposal);
+        assert_eq!(inputs.len(), 4);
+
+        let e = check_orchard_actions(&proposal, LIMITS.orchard_actions).unwrap_err();
+        let data = serde_json::from_str::<serde_json::Value>(e.data().unwrap().get()).unwrap();
+        assert_eq!(
+            data,
+            json!({ "limit": 3, "requested": 4, "kind": "inputs" }),
+        );
+
+        // One action is left for the merged note, so two of the notes are merged first.
+        let Ok(ChainLink::Consolidation(batch)) = next_link(&proposal, &LIMITS) else {
+            panic!("payment should be preceded by a merge");
+        };
+        assert_eq!(batch.len(), 2);
+        assert!(batch.iter().all(|input| inputs.contains(input)));
+    }
+
+    #[test]
+    fn chained_payment_spends_merged_note() {
+        let mut st = funded_wallet();
+        let usk = st.test_account().unwrap().usk().clone();
+
+        let proposal = propose_to_self(&mut st, 3 * NOTE_VALUE);
+        let Ok(ChainLink::Consolidation(batch)) = next_link(&proposal, &LIMITS) else {
+            panic!("payment should be preceded by a merge");
+        };
+
+        // Merge two notes into one, as `consolidate` does. The notes all have the same
+        // value, so it does not matter which two are merged.
+        let merge = propose_to_self(&mut st, 2 * NOTE_VALUE - 10_000);
+        assert_eq!(proposal_inputs(&merge).len(), batch.len());
+        let merged = send_and_mine(&mut st, &usk, &merge);
+
+        // The payment now spends the merged note in place of the notes it merged, and
+        // fits within the action limit.
+        let proposal = propose_to_self(&mut st, 3 * NOTE_VALUE);
+        assert_eq!(next_link(&proposal, &LIMITS).unwrap(), ChainLink::Payment);
+        assert!(
+            proposal_inputs(&proposal)
+                .iter()
+                .any(|input| matches!(input, ReservedInput::Note { txid, .. } if *txid == merged))
+        );
+    }
+
+    /// Returns the key shared by payments from the given account.
+    fn batch_key(account: u128) -> BatchKey {
+        BatchKey {
+            account_id: AccountUuid::from_uuid(Uuid::from_u128(account)),
+            privacy_policy: PrivacyPolicy::FullPrivacy,
+            minconf: None,
+            input_selection: None,
+            broadcast: true,
+        }
+    }
+
+    /// Returns a payment of `value` zatoshis to the transparent address derived from
+    /// `recipient`.
+    fn payment(recipient: u8, value: u64) -> Payment {
+        Payment::without_memo(
+            ZcashAddress::from_transparent_p2pkh(NetworkType::Main, [recipient; 20]),
+            Zatoshis::const_from_u64(value),
+        )
+    }
+
+    /// Returns a batch member making the given payments, along with the receiver for its
+    /// result.
+    fn member(payments: Vec<Payment>) -> (BatchMember, oneshot::Receiver<RpcResult<SendResult>>) {
+        let (result, receiver) = oneshot::channel();
+        let member = BatchMember {
+            payments,
+            operations: vec![],
+            result,
+        };
+        (member, receiver)
+    }
+
+    /// Queues a member making a single payment, returning the ID of the batch it opened
+    /// and the batch if it is now full.
+    fn push(
+        queue: &mut BatchQueue,
+        key: BatchKey,
+        max_payments: u16,
+    ) -> (Option<u64>, Option<u64>) {
+        let usk =
+            UnifiedSpendingKey::from_seed(&MainNetwork, &[7; 32], zip32::AccountId::ZERO).unwrap();
+        let (member, _) = member(vec![payment(1, 1000)]);
+        let (opened, full) = queue.push(
+            key,
+            ConfirmationsPolicy::new_symmetrical(1.try_into().unwrap(), false),
+            usk,
+            member,
+            max_payments,
+        );
+        (opened, full.map(|batch| batch.id))
+    }
+
+    /// Returns the output of a shared transaction that pays the given payment.
+    fn sent_output(payment: &Payment, output_index: u32) -> SentOutput {
+        SentOutput {
+            pool: PoolType::Transparent,
+            output_index,
+            address: Some(payment.recipient_address().encode()),
+            value: payment.amount().into_u64(),
+        }
+    }
+
+    #[test]
+    fn batch_is_sent_once_full() {
+        let mut queue = BatchQueue::default();
+
+        // Payments with different keys are batched separately.
+        assert_eq!(push(&mut queue, batch_key(1), 3), (Some(0), None));
+        assert_eq!(push(&mut queue, batch_key(1), 3), (None, None));
+        assert_eq!(push(&mut queue, batch_key(2), 3), (Some(1), None));
+        assert_eq!(push(&mut queue, batch_key(1), 3), (None, Some(0)));
+
+        // The full batch was removed, so its window closing does not send it again, and
+        // the next payment opens a new batch.
+        assert!(queue.take(0).is_none());
+        assert_eq!(push(&mut queue, batch_key(1), 3), (Some(2), None));
+        assert_eq!(queue.take(1).unwrap().members.len(), 1);
+
+        // A batch always holds at least one payment.
+        assert_eq!(push(&mut queue, batch_key(3), 0), (Some(3), Some(3)));
+    }
+
+    #[test]
+    fn batch_is_sent_when_window_closes() {
+        let mut queue = BatchQueue::default();
+        assert_eq!(push(&mut queue, batch_key(1), 20), (Some(0), None));
+        assert_eq!(push(&mut queue, batch_key(1), 20), (None, None));
+
+        let batch = queue.take(0).unwrap();
+        assert_eq!(batch.members.len(), 2);
+        assert!(queue.take(0).is_none());
+
+        // Later payments are not added to the batch that was sent.
+        assert_eq!(push(&mut queue, batch_key(1), 20), (Some(1), None));
+    }
+
+    #[test]
+    fn batch_results_are_fanned_out() {
+        // Two members pay the same recipient the same amount.
+        let (first, mut first_rx) = member(vec![payment(1, 1000), payment(2, 2000)]);
+        let (second, mut second_rx) = member(vec![payment(1, 1000)]);
+        let (third, third_rx) = member(vec![payment(3, 3000)]);
+        let outputs = [
+            payment(3, 3000),
+            payment(1, 1000),
+            payment(2, 2000),
+            payment(1, 1000),
+        ]
+        .iter()
+        .zip(0..)
+        .map(|(payment, index)| sent_output(payment, index))
+        .collect();
+
+        // A member that is no longer waiting does not prevent the others being told.
+        drop(third_rx);
+
+        let txid = TxId::from_bytes([9; 32]);
+        let result = SendResult::new(
+            vec![txid],
+            BlockHeight::from_u32(100),
+            Some(Zatoshis::const_from_u64(15_000)),
+        );
+        report_batch(vec![first, second, third], Ok((result, outputs)));
+
+        let first = serde_json::to_value(first_rx.try_recv().unwrap().unwrap()).unwrap();
+        let second = serde_json::to_value(second_rx.try_recv().unwrap().unwrap()).unwrap();
+
+        // Both members share the transaction, but each is told only of its own outputs.
+        for result in [&first, &second] {
+            assert_eq!(result["txid"], json!(txid.to_string()));
+            assert_eq!(result["feeZat"], json!(15_000));
+        }
+        let indices = |result: &serde_json::Value| {
+            result["outputs"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|output| {
+                    assert_eq!(output["pool"], "transparent");
+                    output["output_index"].as_u64().unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+        let (first, second) = (indices(&first), indices(&second));
+        assert_eq!(first.len(), 2);
+        assert!(first[0] == 1 || first[0] == 3);
+        assert_eq!(first[1], 2);
+        assert_eq!(second.len(), 1);
+        assert!(second[0] == 1 || second[0] == 3);
+        assert_ne!(first[0], second[0]);
+    }
+
+    #[test]
+    fn batch_failure_reaches_every_member() {
+        let (first, mut first_rx) = member(vec![payment(1, 1000)]);
+        let (second, mut second_rx) = member(vec![payment(2, 2000), payment(3, 3000)]);
+
+        report_batch(
+            vec![first, second],
+            Err(LegacyCode::Wallet.with_static("Insufficient funds")),
+        );
+
+        for rx in [&mut first_rx, &mut second_rx] {
+            let e = rx.try_recv().unwrap().unwrap_err();
+            assert_eq!(e.code(), LegacyCode::Wallet as i32);
+            assert_eq!(e.message(), "Insufficient funds");
+        }
+    }
 }
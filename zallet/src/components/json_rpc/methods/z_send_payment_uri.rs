@@ -0,0 +1,82 @@
+use jsonrpsee::core::{JsonValue, RpcResult};
+use zaino_state::FetchServiceSubscriber;
+
+use crate::components::{
+    database::DbHandle,
+    json_rpc::{
+        asyncop::{ContextInfo, OperationId},
+        payments::SendResult,
+        utils::parse_account_parameter,
+    },
+    keystore::KeyStore,
+};
+
+use super::{
+    MethodCategory,
+    z_payment_uri_decode::parse_payment_uri,
+    z_send_many::{self, AmountParameter, PaymentBatcher},
+};
+
+/// Response to a `z_sendpaymenturi` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+pub(crate) type ResultType = OperationId;
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_ACCOUNT_DESC: &str =
+    "The UUID, name, or ZIP 32 account index of the account to send funds from.";
+pub(super) const PARAM_URI_DESC: &str = "A ZIP 321 payment URI.";
+pub(super) const PARAM_MINCONF_DESC: &str = "Only use funds confirmed at least this many times.";
+pub(super) const PARAM_FEE_DESC: &str =
+    "The most that the transaction may pay in fees, in ZEC. Defaults to builder.max_fee.";
+pub(super) const PARAM_PRIVACY_POLICY_DESC: &str =
+    "Policy for what information leakage is acceptable.";
+pub(super) const PARAM_NO_BROADCAST_DESC: &str =
+    "If true, create the transaction without broadcasting it, and return it in the result.";
+
+/// Sends the payments requested by a ZIP 321 payment URI.
+///
+/// The payments are sent by a `z_sendmany` operation, so that they are resumed in the
+/// same way if Zallet stops before the operation starts executing.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn call(
+    wallet: DbHandle,
+    keystore: KeyStore,
+    chain: FetchServiceSubscriber,
+    batcher: PaymentBatcher,
+    account: JsonValue,
+    uri: String,
+    minconf: Option<u32>,
+    fee: Option<JsonValue>,
+    privacy_policy: Option<String>,
+    no_broadcast: Option<bool>,
+) -> RpcResult<(
+    Option<ContextInfo>,
+    impl Future<Output = RpcResult<SendResult>>,
+)> {
+    let request = parse_payment_uri(wallet.params(), &uri)?;
+    let account_id = parse_account_parameter(wallet.as_ref(), &keystore, &account).await?;
+
+    let amounts = request
+        .payments()
+        .values()
+        .map(AmountParameter::from_payment)
+        .collect();
+
+    z_send_many::call(
+        wallet,
+        keystore,
+        chain,
+        batcher,
+        account_id.expose_uuid().to_string(),
+        amounts,
+        minconf,
+        fee,
+        privacy_policy,
+        None,
+        None,
+        None,
+        no_broadcast,
+    )
+    .await
+}
@@ -0,0 +1,486 @@
+use std::collections::HashSet;
+use std::convert::Infallible;
+
+use abscissa_core::Application;
+use documented::Documented;
+use jsonrpsee::core::{JsonValue, RpcResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use transparent::address::TransparentAddress;
+use zaino_state::FetchServiceSubscriber;
+use zcash_client_backend::{
+    data_api::{
+        Account as _, InputSource, WalletRead, WalletUtxo,
+        wallet::{
+            ConfirmationsPolicy, SpendingKeys, TargetHeight, create_proposed_transactions,
+            input_selection::GreedyInputSelector, propose_shielding,
+        },
+    },
+    fees::{StandardFeeRule, standard::MultiOutputChangeStrategy},
+    proposal::Proposal,
+    wallet::OvkPolicy,
+};
+use zcash_client_sqlite::{AccountUuid, wallet::Account};
+use zcash_keys::{address::Address, keys::UnifiedSpendingKey};
+use zcash_proofs::prover::LocalTxProver;
+use zcash_protocol::{ShieldedProtocol, value::Zatoshis};
+
+use crate::{
+    components::{
+        database::{DbConnection, DbHandle, ReservedInput},
+        json_rpc::{
+            asyncop::{self, ContextInfo, OperationId},
+            fees::{self, FeeLimit},
+            payments::{
+                SendResult, broadcast_transactions, get_account_for_address, reserve_inputs,
+            },
+            send_error::SendFailure,
+            server::{ComponentFailure, LegacyCode},
+            utils::{JsonZec, parse_account_parameter, value_from_zatoshis},
+        },
+        keystore::KeyStore,
+    },
+    prelude::*,
+};
+
+use super::{
+    MethodCategory,
+    z_send_many::{
+        check_fees, check_orchard_actions, parse_confirmations_policy, spending_key, tx_values,
+    },
+};
+
+#[cfg(feature = "transparent-key-import")]
+use super::z_send_many::standalone_keys;
+
+/// Response to a `z_shieldcoinbase` RPC request.
+pub(crate) type Response = RpcResult<ResultType>;
+
+/// The UTXOs selected for shielding, and the ID of the operation shielding them.
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
+pub(crate) struct ResultType {
+    #[serde(flatten)]
+    selection: Selection,
+
+    /// An operation ID to pass to `z_getoperationstatus` or `z_getoperationresult`.
+    opid: OperationId,
+}
+
+/// The UTXOs selected for shielding.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct Selection {
+    /// The number of spendable UTXOs that were not selected because of `limit`.
+    #[serde(rename = "remainingUTXOs")]
+    remaining_utxos: usize,
+
+    /// The total value of the UTXOs that were not selected, in ZEC.
+    #[serde(rename = "remainingValue")]
+    remaining_value: JsonZec,
+
+    /// The number of UTXOs being shielded.
+    #[serde(rename = "shieldingUTXOs")]
+    shielding_utxos: usize,
+
+    /// The total value of the UTXOs being shielded, in ZEC.
+    #[serde(rename = "shieldingValue")]
+    shielding_value: JsonZec,
+}
+
+impl Selection {
+    /// Returns the result of a `z_shieldcoinbase` call that started the given operation.
+    pub(crate) fn started(self, opid: OperationId) -> ResultType {
+        ResultType {
+            selection: self,
+            opid,
+        }
+    }
+}
+
+pub(super) const CATEGORY: MethodCategory = MethodCategory::Mutating;
+
+pub(super) const PARAM_FROMADDRESS_DESC: &str = "The transparent address to shield funds from, or \"*\" for all of the account's transparent addresses.";
+pub(super) const PARAM_TOADDRESS_DESC: &str =
+    "The UUID or name of the account to shield funds into, or a shielded address of that account.";
+pub(super) const PARAM_FEE_DESC: &str =
+    "The most that the transaction may pay in fees, in ZEC. Defaults to builder.max_fee.";
+pub(super) const PARAM_LIMIT_DESC: &str =
+    "The maximum number of UTXOs to shield, or 0 for builder.limits.transparent_inputs.";
+
+/// The parameters of a shielding transaction, from which it is proposed again if its
+/// inputs were reserved by another operation.
+pub(super) struct ShieldRequest {
+    account_id: AccountUuid,
+    pool: ShieldedProtocol,
+    from_addrs: Vec<TransparentAddress>,
+    confirmations_policy: ConfirmationsPolicy,
+    orchard_actions_limit: usize,
+    fee_limit: FeeLimit,
+}
+
+impl ShieldRequest {
+    /// Returns the parameters of a transaction shielding the given UTXOs into `pool` of
+    /// the given account.
+    ///
+    /// Input selection with `wallet` may only spend those UTXOs for the rest of the
+    /// operation.
+    pub(super) fn new(
+        wallet: &DbHandle,
+        account_id: AccountUuid,
+        pool: ShieldedProtocol,
+        utxos: &[WalletUtxo],
+        confirmations_policy: ConfirmationsPolicy,
+        fee_limit: FeeLimit,
+    ) -> Self {
+        wallet.restrict_inputs(
+            utxos
+                .iter()
+                .map(|utxo| ReservedInput::Transparent(utxo.outpoint().clone()))
+                .collect(),
+        );
+
+        Self {
+            account_id,
+            pool,
+            from_addrs: utxos
+                .iter()
+                .map(|utxo| *utxo.recipient_address())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect(),
+            confirmations_policy,
+            orchard_actions_limit: APP.config().builder.limits.orchard_actions().into(),
+            fee_limit,
+        }
+    }
+
+    /// Proposes a transaction shielding the transparent funds that the wallet connection
+    /// may spend from `from_addrs`.
+    pub(super) fn propose(
+        &self,
+        wallet: &mut DbHandle,
+    ) -> RpcResult<Proposal<StandardFeeRule, Infallible>> {
+        let params = *wallet.params();
+
+        // The shielded funds are sent to the account as change.
+        let change_strategy = MultiOutputChangeStrategy::new(
+            fees::fee_rule(),
+            None,
+            self.pool,
+            APP.config().note_management.dust_policy(),
+            APP.config().note_management.split_policy(),
+        );
+
+        let proposal = propose_shielding::<_, _, _, _, Infallible>(
+            wallet.as_mut(),
+            &params,
+            &GreedyInputSelector::new(),
+            &change_strategy,
+            Zatoshis::ZERO,
+            &self.from_addrs,
+            self.account_id,
+            self.confirmations_policy,
+        )
+        .map_err(|e| SendFailure::from_proposal_error(&e))?;
+
+        check_orchard_actions(&proposal, self.orchard_actions_limit)?;
+        check_fees(&proposal, self.fee_limit)?;
+
+        Ok(proposal)
+    }
+}
+
+pub(crate) async fn call(
+    mut wallet: DbHandle,
+    keystore: KeyStore,
+    chain: FetchServiceSubscriber,
+    fromaddress: String,
+    toaddress: String,
+    fee: Option<JsonValue>,
+    limit: Option<u32>,
+) -> RpcResult<(
+    Selection,
+    (
+        Option<ContextInfo>,
+        impl Future<Output = RpcResult<SendResult>>,
+    ),
+)> {
+    let fee_limit = FeeLimit::from_param(fee.as_ref(), &APP.config().builder)?;
+
+    let broadcast = APP.config().external.broadcast();
+    let params = *wallet.params();
+
+    let (account, pool) = shielding_destination(wallet.as_ref(), &keystore, &toaddress).await?;
+
+    // Each shielding transaction is signed with the keys of a single account, so only
+    // that account's transparent addresses can be shielded.
+    let receivers = wallet
+        .get_transparent_receivers(account.id(), true, true)
+        .map_err(ComponentFailure::database)?;
+    let from_addrs = match fromaddress.as_str() {
+        "*" | "ANY_TADDR" => receivers.into_keys().collect::<Vec<_>>(),
+        _ => match Address::decode(&params, &fromaddress) {
+            Some(Address::Transparent(addr)) if receivers.contains_key(&addr) => vec![addr],
+            Some(Address::Transparent(_)) => {
+                return Err(LegacyCode::InvalidAddressOrKey.with_static(
+                    "Invalid from address, it does not belong to the destination account.",
+                ));
+            }
+            _ => {
+                return Err(LegacyCode::InvalidAddressOrKey
+                    .with_static("Invalid from address, should be a taddr or \"*\"."));
+            }
+        },
+    };
+
+    let confirmations_policy = parse_confirmations_policy(None);
+    let (utxos, remaining) = select_utxos(
+        wallet.as_ref(),
+        &from_addrs,
+        confirmations_policy,
+        max_utxos(limit),
+    )?;
+    if utxos.is_empty() {
+        return Err(
+            LegacyCode::WalletInsufficientFunds.with_static("Could not find any funds to shield.")
+        );
+    }
+
+    let selection = Selection {
+        remaining_utxos: remaining.len(),
+        remaining_value: value_from_zatoshis(total_value(&remaining)?),
+        shielding_utxos: utxos.len(),
+        shielding_value: value_from_zatoshis(total_value(&utxos)?),
+    };
+
+    let request = ShieldRequest::new(
+        &wallet,
+        account.id(),
+        pool,
+        &utxos,
+        confirmations_policy,
+        fee_limit,
+    );
+    let proposal = request.propose(&mut wallet)?;
+
+    let derivation = account.source().key_derivation().ok_or_else(|| {
+        LegacyCode::InvalidAddressOrKey
+            .with_static("Invalid destination, the account has no spending key.")
+    })?;
+
+    // Fetch spending key last, to avoid a keystore decryption if unnecessary.
+    let usk = spending_key(&keystore, wallet.params(), derivation).await?;
+
+    Ok((
+        selection,
+        (
+            Some(ContextInfo::new(
+                "z_shieldcoinbase",
+                json!({
+                    "fromaddress": fromaddress,
+                    "toaddress": toaddress,
+                    "fee": fee,
+                    "limit": limit,
+                }),
+            )),
+            run(wallet, keystore, chain, usk, request, proposal, broadcast),
+        ),
+    ))
+}
+
+/// Returns the account that shielded funds are sent to, and the pool that they are
+/// shielded into.
+///
+/// `propose_shielding` sends the shielded funds to an internal address of the account,
+/// so a destination address only determines the account and the pool.
+pub(super) async fn shielding_destination(
+    wallet: &DbConnection,
+    keystore: &KeyStore,
+    toaddress: &str,
+) -> RpcResult<(Account, ShieldedProtocol)> {
+    match Address::decode(wallet.params(), toaddress) {
+        Some(address) => {
+            let pool = match &address {
+                Address::Sapling(_) => ShieldedProtocol::Sapling,
+                Address::Unified(ua) if ua.has_orchard() => ShieldedProtocol::Orchard,
+                Address::Unified(ua) if ua.has_sapling() => ShieldedProtocol::Sapling,
+                _ => {
+                    return Err(LegacyCode::InvalidParameter.with_static(
+                        "Invalid parameter, destination must be a shielded address or account.",
+                    ));
+                }
+            };
+            let account = get_account_for_address(wallet, &address).map_err(|_| {
+                LegacyCode::InvalidAddressOrKey
+                    .with_static("Destination address does not belong to this wallet.")
+            })?;
+            Ok((account, pool))
+        }
+        None => {
+            let account_id = parse_account_parameter(
+                wallet,
+                keystore,
+                &JsonValue::String(toaddress.into()),
+            )
+            .await
+            .map_err(|_| {
+                LegacyCode::InvalidAddressOrKey.with_static(
+                    "Invalid destination: should be a shielded address, or an account UUID or name.",
+                )
+            })?;
+            let account = wallet
+                .get_account(account_id)
+                .map_err(ComponentFailure::database)?
+                .ok_or_else(|| LegacyCode::InvalidParameter.with_static("Account not found."))?;
+            Ok((account, ShieldedProtocol::Orchard))
+        }
+    }
+}
+
+/// Returns the maximum number of UTXOs to spend in a transaction, given the `limit`
+/// parameter of the request.
+pub(super) fn max_utxos(limit: Option<u32>) -> usize {
+    let max_inputs = usize::from(APP.config().builder.limits.transparent_inputs());
+    match limit.and_then(|limit| usize::try_from(limit).ok()) {
+        Some(limit) if limit > 0 => limit.min(max_inputs),
+        _ => max_inputs,
+    }
+}
+
+/// Returns up to `max_utxos` of the spendable UTXOs received by `from_addrs`, oldest
+/// first, along with the spendable UTXOs that were not selected.
+pub(super) fn select_utxos(
+    wallet: &DbConnection,
+    from_addrs: &[TransparentAddress],
+    confirmations_policy: ConfirmationsPolicy,
+    max_utxos: usize,
+) -> RpcResult<(Vec<WalletUtxo>, Vec<WalletUtxo>)> {
+    let target_height = TargetHeight::from(
+        wallet
+            .chain_height()
+            .map_err(ComponentFailure::database)?
+            .ok_or_else(|| LegacyCode::InWarmup.with_static("Wait for the wallet to start up"))?
+            + 1,
+    );
+
+    let mut utxos = vec![];
+    for addr in from_addrs {
+        utxos.extend(
+            wallet
+                .get_spendable_transparent_outputs(addr, target_height, confirmations_policy)
+                .map_err(ComponentFailure::database)?,
+        );
+    }
+
+    utxos.sort_by_key(|utxo| utxo.mined_height().map_or(u32::MAX, u32::from));
+    let remaining = utxos.split_off(max_utxos.min(utxos.len()));
+    Ok((utxos, remaining))
+}
+
+/// Returns the total value of the given UTXOs.
+pub(super) fn total_value(utxos: &[WalletUtxo]) -> RpcResult<Zatoshis> {
+    utxos
+        .iter()
+        .map(|utxo| utxo.value())
+        .try_fold(Zatoshis::ZERO, |acc, value| acc + value)
+        .ok_or_else(|| LegacyCode::Misc.with_static("Value out of range"))
+}
+
+/// The parameters of a `z_shieldcoinbase` operation, as recorded in its context.
+#[derive(Deserialize)]
+struct ResumeParams {
+    fromaddress: String,
+    toaddress: String,
+    fee: Option<JsonValue>,
+    limit: Option<u32>,
+}
+
+/// Re-issues a `z_shieldcoinbase` request from the context parameters of an operation
+/// that had not started executing when Zallet last stopped.
+pub(super) async fn resume(
+    wallet: DbHandle,
+    keystore: KeyStore,
+    chain: FetchServiceSubscriber,
+    params: &str,
+) -> RpcResult<(
+    Option<ContextInfo>,
+    impl Future<Output = RpcResult<SendResult>>,
+)> {
+    let params = serde_json::from_str::<ResumeParams>(params).map_err(|e| {
+        LegacyCode::Deserialization.with_message(format!("Cannot resume operation: {e}"))
+    })?;
+
+    call(
+        wallet,
+        keystore,
+        chain,
+        params.fromaddress,
+        params.toaddress,
+        params.fee,
+        params.limit,
+    )
+    .await
+    .map(|(_, operation)| operation)
+}
+
+/// Creates the shielding transaction, and broadcasts it if `broadcast` is `true`.
+///
+/// The transaction's inputs are reserved until the operation finishes. If they conflict
+/// with another operation's inputs, the transaction is proposed again once that
+/// operation finishes.
+pub(super) async fn run(
+    mut wallet: DbHandle,
+    keystore: KeyStore,
+    chain: FetchServiceSubscriber,
+    usk: UnifiedSpendingKey,
+    request: ShieldRequest,
+    proposal: Proposal<StandardFeeRule, Infallible>,
+    broadcast: bool,
+) -> RpcResult<SendResult> {
+    let (proposal, _reservation) =
+        reserve_inputs(&mut wallet, proposal, |wallet| request.propose(wallet)).await?;
+
+    #[cfg(feature = "transparent-key-import")]
+    let standalone_keys = standalone_keys(&keystore, &proposal).await?;
+
+    #[cfg(not(feature = "transparent-key-import"))]
+    let _ = keystore;
+
+    let spending_keys = SpendingKeys::new(
+        usk,
+        #[cfg(feature = "zcashd-import")]
+        standalone_keys,
+    );
+
+    let tx_values = tx_values(&proposal);
+
+    // Once the transaction is created it will be broadcast, so this is the last point at
+    // which a shutdown can stop the operation.
+    asyncop::checkpoint()?;
+
+    let prover = LocalTxProver::bundled();
+    let (wallet, txids) = crate::spawn_blocking!("z_shieldcoinbase prover", move || {
+        let params = *wallet.params();
+        create_proposed_transactions::<_, _, Infallible, _, Infallible, _>(
+            wallet.as_mut(),
+            &params,
+            &prover,
+            &prover,
+            &spending_keys,
+            OvkPolicy::Sender,
+            &proposal,
+        )
+        .map(|txids| (wallet, txids))
+    })
+    .await
+    .map_err(|e| SendFailure::Internal(e.to_string()))?
+    .map_err(|e| SendFailure::from_wallet_error(&e))?;
+
+    let txids = Vec::from(txids);
+    asyncop::record_txids(&txids).await;
+    // The transaction has already been created, so send it even if this fails.
+    if let Err(e) = wallet.record_tx_values(txids.iter().copied().zip(tx_values)) {
+        warn!("Failed to record the input and output values of the created transaction: {e}");
+    }
+    broadcast_transactions(&wallet, chain, txids, broadcast).await
+}
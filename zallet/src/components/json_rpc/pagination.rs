@@ -0,0 +1,292 @@
+//! Cursor-based pagination for listing methods.
+//!
+//! Listing methods return every result by default, up to `rpc.max_list_results`. Callers
+//! that pass a `cursor` parameter (the empty string for the first page) instead receive
+//! a page of results, along with a cursor for the next page if more results exist.
+//!
+//! Each cursor encodes the position of the last result in its page, rather than a count
+//! of skipped results, so pages remain consistent when the wallet gains transactions
+//! while they are being fetched.
+
+use jsonrpsee::core::RpcResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{components::json_rpc::server::LegacyCode, prelude::*};
+
+/// The number of results in a page if the caller does not provide a `limit`.
+const DEFAULT_PAGE_SIZE: u32 = 1000;
+
+/// The current version of the cursor encoding.
+const CURSOR_VERSION: u8 = 1;
+
+/// The length of an encoded cursor, in bytes.
+const CURSOR_LEN: usize = 1 + 4 + 4 + 8 + 8;
+
+/// The results of a listing method.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub(crate) enum Listing<T> {
+    /// Every result, when no cursor was given.
+    All(Vec<T>),
+    /// A page of results, when a cursor was given.
+    Page {
+        /// The results in this page.
+        results: Vec<T>,
+        /// The cursor for the next page, or `null` if this is the last page.
+        next_cursor: Option<String>,
+    },
+}
+
+/// The stable position of a result within a listing.
+///
+/// Results are ordered by the height of the transaction they come from, then by the
+/// transaction's index within its block, then by the wallet's row ID for the transaction,
+/// and finally by a method-specific value that distinguishes results from the same
+/// transaction (such as an output index).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Position {
+    height: u32,
+    tx_index: u32,
+    tx_row: i64,
+    entry: u64,
+}
+
+impl Position {
+    /// Constructs a position.
+    ///
+    /// Unmined transactions (with no `height`) are ordered after mined ones, and
+    /// transactions with no known index in their block are ordered after those with one.
+    pub(crate) fn new(height: Option<u32>, tx_index: Option<u32>, tx_row: i64, entry: u64) -> Self {
+        Self {
+            height: height.unwrap_or(u32::MAX),
+            tx_index: tx_index.unwrap_or(u32::MAX),
+            tx_row,
+            entry,
+        }
+    }
+
+    pub(crate) fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub(crate) fn tx_index(&self) -> u32 {
+        self.tx_index
+    }
+
+    pub(crate) fn tx_row(&self) -> i64 {
+        self.tx_row
+    }
+
+    pub(crate) fn entry(&self) -> u64 {
+        self.entry
+    }
+
+    fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(CURSOR_LEN);
+        bytes.push(CURSOR_VERSION);
+        bytes.extend_from_slice(&self.height.to_be_bytes());
+        bytes.extend_from_slice(&self.tx_index.to_be_bytes());
+        bytes.extend_from_slice(&self.tx_row.to_be_bytes());
+        bytes.extend_from_slice(&self.entry.to_be_bytes());
+        hex::encode(bytes)
+    }
+
+    fn decode(cursor: &str) -> Option<Self> {
+        let bytes = hex::decode(cursor).ok()?;
+        if bytes.len() != CURSOR_LEN || bytes[0] != CURSOR_VERSION {
+            return None;
+        }
+        Some(Self {
+            height: u32::from_be_bytes(bytes[1..5].try_into().ok()?),
+            tx_index: u32::from_be_bytes(bytes[5..9].try_into().ok()?),
+            tx_row: i64::from_be_bytes(bytes[9..17].try_into().ok()?),
+            entry: u64::from_be_bytes(bytes[17..25].try_into().ok()?),
+        })
+    }
+}
+
+/// How the caller asked for results to be returned.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Pagination {
+    /// Return every result.
+    All,
+    /// Return up to `limit` results positioned after `after`.
+    Page { after: Option<Position>, limit: u32 },
+}
+
+impl Pagination {
+    /// Parses the `cursor` and `limit` parameters of a listing method.
+    pub(crate) fn parse(cursor: Option<&str>, limit: Option<u32>) -> RpcResult<Self> {
+        let max = max_list_results();
+        match cursor {
+            None => Ok(Self::All),
+            Some(cursor) => {
+                let after = if cursor.is_empty() {
+                    None
+                } else {
+                    Some(Position::decode(cursor).ok_or_else(|| {
+                        LegacyCode::InvalidParameter.with_static("Invalid cursor")
+                    })?)
+                };
+                let limit = match limit {
+                    None => DEFAULT_PAGE_SIZE.min(max),
+                    Some(0) => {
+                        return Err(LegacyCode::InvalidParameter
+                            .with_static("limit must be greater than zero"));
+                    }
+                    Some(limit) if limit > max => {
+                        return Err(LegacyCode::InvalidParameter.with_message(format!(
+                            "limit must be at most {max} (rpc.max_list_results)"
+                        )));
+                    }
+                    Some(limit) => limit,
+                };
+                Ok(Self::Page { after, limit })
+            }
+        }
+    }
+
+    /// Returns the position after which results should be returned, if any.
+    pub(crate) fn after(&self) -> Option<Position> {
+        match self {
+            Self::All => None,
+            Self::Page { after, .. } => *after,
+        }
+    }
+
+    /// Returns the maximum number of results to fetch for this request.
+    ///
+    /// One more result than will be returned is fetched, so that it is possible to tell
+    /// whether the result set was truncated.
+    pub(crate) fn fetch_limit(&self) -> u32 {
+        match self {
+            Self::All => max_list_results().saturating_add(1),
+            Self::Page { limit, .. } => limit.saturating_add(1),
+        }
+    }
+
+    /// Builds the listing from the results positioned after [`Self::after`], in order.
+    ///
+    /// At most [`Self::fetch_limit`] results need to be provided.
+    pub(crate) fn finish<T>(&self, results: Vec<(Position, T)>) -> RpcResult<Listing<T>> {
+        match self {
+            Self::All => {
+                let max = max_list_results();
+                if results.len() > max as usize {
+                    return Err(LegacyCode::InvalidParameter.with_message(format!(
+                        "More than {max} results (rpc.max_list_results); use the cursor parameter to fetch them in pages"
+                    )));
+                }
+                Ok(Listing::All(results.into_iter().map(|(_, r)| r).collect()))
+            }
+            Self::Page { limit, .. } => {
+                let mut results = results;
+                let next_cursor = if results.len() > *limit as usize {
+                    results.truncate(*limit as usize);
+                    results.last().map(|(position, _)| position.encode())
+                } else {
+                    None
+                };
+                Ok(Listing::Page {
+                    results: results.into_iter().map(|(_, r)| r).collect(),
+                    next_cursor,
+                })
+            }
+        }
+    }
+
+    /// Builds the listing from a complete set of results, in any order.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn apply<T>(&self, mut results: Vec<(Position, T)>) -> RpcResult<Listing<T>> {
+        results.sort_by_key(|(position, _)| *position);
+        if let Some(after) = self.after() {
+            results.retain(|(position, _)| *position > after);
+        }
+        results.truncate(self.fetch_limit() as usize);
+        self.finish(results)
+    }
+}
+
+fn max_list_results() -> u32 {
+    APP.config().rpc.max_list_results()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Position;
+
+    #[cfg(zallet_build = "wallet")]
+    use super::{Listing, Pagination};
+
+    #[cfg(zallet_build = "wallet")]
+    fn listing(heights: &[u32]) -> Vec<(Position, u32)> {
+        heights
+            .iter()
+            .enumerate()
+            .map(|(i, h)| (Position::new(Some(*h), Some(0), i as i64, 0), *h))
+            .collect()
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        for position in [
+            Position::new(Some(1), Some(2), 3, 4),
+            Position::new(None, None, i64::MAX, u64::MAX),
+        ] {
+            assert_eq!(Position::decode(&position.encode()), Some(position));
+        }
+        assert_eq!(Position::decode(""), None);
+        assert_eq!(Position::decode("zz"), None);
+        assert_eq!(Position::decode("01"), None);
+    }
+
+    #[test]
+    fn unmined_sorts_last() {
+        assert!(Position::new(Some(u32::MAX - 1), None, 0, 0) < Position::new(None, None, 0, 0));
+        assert!(Position::new(Some(5), Some(9), 0, 0) < Position::new(Some(5), None, 0, 0));
+    }
+
+    #[cfg(zallet_build = "wallet")]
+    #[test]
+    fn pages_are_stable_under_inserts() {
+        let page = |pagination: Pagination, results| match pagination.apply(results).unwrap() {
+            Listing::Page {
+                results,
+                next_cursor,
+            } => (results, next_cursor),
+            Listing::All(_) => unreachable!(),
+        };
+
+        let mut results = listing(&[10, 20, 30, 40, 50]);
+        let (first, cursor) = page(
+            Pagination::Page {
+                after: None,
+                limit: 2,
+            },
+            results.clone(),
+        );
+        assert_eq!(first, [10, 20]);
+
+        // A transaction mined before the cursor does not shift the next page.
+        results.push((Position::new(Some(15), Some(0), 99, 0), 15));
+        let (second, cursor) = page(
+            Pagination::Page {
+                after: Position::decode(&cursor.unwrap()),
+                limit: 2,
+            },
+            results.clone(),
+        );
+        assert_eq!(second, [30, 40]);
+
+        let (third, cursor) = page(
+            Pagination::Page {
+                after: Position::decode(&cursor.unwrap()),
+                limit: 2,
+            },
+            results,
+        );
+        assert_eq!(third, [50]);
+        assert_eq!(cursor, None);
+    }
+}
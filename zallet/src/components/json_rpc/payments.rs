@@ -1,17 +1,29 @@
 use std::{collections::HashSet, fmt};
 
-use abscissa_core::Application;
+use abscissa_core::tracing::debug;
+use documented::Documented;
 use jsonrpsee::{core::RpcResult, types::ErrorObjectOwned};
+use schemars::JsonSchema;
 use serde::Serialize;
 use zaino_state::{FetchServiceSubscriber, ZcashIndexer};
 use zcash_client_backend::{data_api::WalletRead, proposal::Proposal};
 use zcash_client_sqlite::wallet::Account;
 use zcash_keys::address::Address;
-use zcash_protocol::{PoolType, ShieldedProtocol, TxId, memo::MemoBytes};
-
-use crate::{components::database::DbConnection, fl, prelude::APP};
-
-use super::server::LegacyCode;
+use zcash_protocol::{
+    PoolType, ShieldedProtocol, TxId, consensus::BlockHeight, memo::MemoBytes, value::Zatoshis,
+};
+
+use crate::{
+    components::database::{DbConnection, DbHandle, Reservation, ReservedInput},
+    fl,
+};
+
+use super::{
+    asyncop,
+    send_error::SendFailure,
+    server::{ComponentFailure, LegacyCode},
+    utils::{JsonZec, zec_and_zat},
+};
 
 /// A strategy to use for managing privacy when constructing a transaction.
 ///
@@ -432,16 +444,16 @@ pub(super) fn get_account_for_address(
     //       https://github.com/zcash/librustzcash/issues/1944
     for account_id in wallet
         .get_account_ids()
-        .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+        .map_err(ComponentFailure::database)?
     {
         for address_info in wallet
             .list_addresses(account_id)
-            .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+            .map_err(ComponentFailure::database)?
         {
             if address_info.address() == address {
                 return Ok(wallet
                     .get_account(account_id)
-                    .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
+                    .map_err(ComponentFailure::database)?
                     .expect("present"));
             }
         }
@@ -451,44 +463,126 @@ pub(super) fn get_account_for_address(
         .with_static("Invalid from address, no payment source found for address."))
 }
 
-/// Broadcasts the specified transactions to the network, if configured to do so.
+/// Returns the wallet inputs spent by the given proposal.
+pub(super) fn proposal_inputs<FeeRuleT, NoteRef>(
+    proposal: &Proposal<FeeRuleT, NoteRef>,
+) -> Vec<ReservedInput> {
+    let mut inputs = vec![];
+    for step in proposal.steps() {
+        inputs.extend(
+            step.transparent_inputs()
+                .iter()
+                .map(|utxo| ReservedInput::Transparent(utxo.outpoint().clone())),
+        );
+        inputs.extend(
+            step.shielded_inputs()
+                .iter()
+                .flat_map(|inputs| inputs.notes().iter())
+                .map(|note| ReservedInput::Note {
+                    txid: *note.txid(),
+                    protocol: note.note().protocol(),
+                    index: note.output_index().into(),
+                }),
+        );
+    }
+    inputs
+}
+
+/// Reserves the inputs of `proposal` until the returned [`Reservation`] is dropped.
+///
+/// Operations that spend disjoint inputs proceed in parallel. If another operation has
+/// reserved any of the inputs, this waits for it to finish and then obtains a new
+/// proposal from `repropose`, as the inputs may have been spent in the meantime (input
+/// selection skips reserved inputs, so this only happens when both proposals were made
+/// before either was reserved).
+pub(super) async fn reserve_inputs<FeeRuleT, NoteRef>(
+    wallet: &mut DbHandle,
+    mut proposal: Proposal<FeeRuleT, NoteRef>,
+    mut repropose: impl FnMut(&mut DbHandle) -> RpcResult<Proposal<FeeRuleT, NoteRef>>,
+) -> RpcResult<(Proposal<FeeRuleT, NoteRef>, Reservation)> {
+    loop {
+        let reservations = wallet.reservations().clone();
+        let released = reservations.released();
+        if let Some(reservation) = reservations.try_reserve(proposal_inputs(&proposal)) {
+            return Ok((proposal, reservation));
+        }
+
+        debug!("Waiting for an operation spending the same inputs to finish");
+        released.await;
+        proposal = repropose(wallet)?;
+    }
+}
+
+/// A connection through which the wallet's transactions are broadcast to the network.
+pub(super) trait Broadcaster {
+    /// Submits the given hex-encoded transaction to the network.
+    fn broadcast(&self, raw_transaction_hex: String) -> impl Future<Output = RpcResult<()>> + Send;
+}
+
+impl Broadcaster for FetchServiceSubscriber {
+    async fn broadcast(&self, raw_transaction_hex: String) -> RpcResult<()> {
+        self.send_raw_transaction(raw_transaction_hex)
+            .await
+            .map_err(SendFailure::from_broadcast_error)?;
+        Ok(())
+    }
+}
+
+/// Broadcasts the specified transactions to the network if `broadcast` is `true`.
+///
+/// Otherwise, the transactions are recorded in the wallet as un-broadcast, and their raw
+/// bytes are included in the result so that the caller can submit them elsewhere.
 pub(super) async fn broadcast_transactions(
     wallet: &DbConnection,
-    chain: FetchServiceSubscriber,
+    chain: impl Broadcaster,
     txids: Vec<TxId>,
+    broadcast: bool,
 ) -> RpcResult<SendResult> {
-    if APP.config().external.broadcast() {
-        for txid in &txids {
-            let tx = wallet
-                .get_transaction(*txid)
-                .map_err(|e| {
-                    LegacyCode::Database.with_message(format!("Failed to get transaction: {e}"))
-                })?
-                .ok_or_else(|| {
-                    LegacyCode::Wallet
-                        .with_message(format!("Wallet does not contain transaction {txid}"))
-                })?;
-
-            let mut tx_bytes = vec![];
-            tx.write(&mut tx_bytes)
-                .map_err(|e| LegacyCode::OutOfMemory.with_message(e.to_string()))?;
-            let raw_transaction_hex = hex::encode(&tx_bytes);
-
-            chain
-                .send_raw_transaction(raw_transaction_hex)
-                .await
-                .map_err(|e| {
-                    LegacyCode::Wallet
-                        .with_message(format!("SendTransaction: Transaction commit failed:: {e}"))
-                })?;
+    let mut expiry_height = BlockHeight::from_u32(0);
+    let mut fee = Some(Zatoshis::ZERO);
+    let mut raw_transactions = vec![];
+
+    for txid in &txids {
+        let tx = wallet
+            .get_transaction(*txid)
+            .map_err(|e| ComponentFailure::database(format!("Failed to get transaction: {e}")))?
+            .ok_or_else(|| {
+                LegacyCode::Wallet
+                    .with_message(format!("Wallet does not contain transaction {txid}"))
+            })?;
+
+        expiry_height = expiry_height.max(tx.expiry_height());
+
+        // Report the fee that the wallet records for the transaction, so that it matches
+        // the fee reported by `z_listtransactions` and `z_viewtransaction`.
+        let tx_fee = wallet.tx_fee(txid).map_err(ComponentFailure::database)?;
+        fee = fee.zip(tx_fee).and_then(|(acc, tx_fee)| acc + tx_fee);
+
+        let mut tx_bytes = vec![];
+        tx.write(&mut tx_bytes)
+            .map_err(|e| LegacyCode::OutOfMemory.with_message(e.to_string()))?;
+        let raw_transaction_hex = hex::encode(&tx_bytes);
+
+        if broadcast {
+            chain.broadcast(raw_transaction_hex).await?;
+            asyncop::record_broadcast(&[*txid]).await;
+        } else {
+            raw_transactions.push(raw_transaction_hex);
         }
     }
 
-    Ok(SendResult::new(txids))
+    if broadcast {
+        Ok(SendResult::new(txids, expiry_height, fee))
+    } else {
+        wallet
+            .mark_unbroadcast(&txids)
+            .map_err(ComponentFailure::database)?;
+        Ok(SendResult::new(txids, expiry_height, fee).with_hex(raw_transactions))
+    }
 }
 
 /// The result of sending a payment.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Documented, JsonSchema)]
 pub(crate) struct SendResult {
     /// The ID of the resulting transaction, if the payment only produced one.
     ///
@@ -499,18 +593,205 @@ pub(crate) struct SendResult {
 
     /// The IDs of the sent transactions resulting from the payment.
     txids: Vec<String>,
+
+    /// The height after which the sent transactions will expire if they have not been
+    /// mined.
+    expiryheight: u32,
+
+    /// The total fee paid by the sent transactions, in ZEC.
+    ///
+    /// Omitted if the fee of any of the transactions is not known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fee: Option<JsonZec>,
+
+    /// The value of [`SendResult::fee`], in zatoshis.
+    #[serde(default, rename = "feeZat", skip_serializing_if = "Option::is_none")]
+    fee_zat: Option<u64>,
+
+    /// The transactions created for each group of recipients, if the payment was split
+    /// to stay within the Orchard action limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    split: Option<Vec<SplitGroup>>,
+
+    /// The outputs that pay this send's recipients, if the send was batched with others
+    /// into a shared transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    outputs: Option<Vec<BatchedOutput>>,
+
+    /// The raw transactions, hex-encoded and in the same order as [`SendResult::txids`],
+    /// if they were not broadcast.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hex: Option<Vec<String>>,
+
+    /// The value of change that was too small to be worth creating an output for, and
+    /// was instead added to the fee, in ZEC.
+    ///
+    /// Omitted if no change was added to the fee.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    folded_dust: Option<JsonZec>,
+
+    /// The value of [`SendResult::folded_dust`], in zatoshis.
+    #[serde(
+        default,
+        rename = "folded_dustZat",
+        skip_serializing_if = "Option::is_none"
+    )]
+    folded_dust_zat: Option<u64>,
+}
+
+/// An output of a shared transaction that pays a recipient of a batched send.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct BatchedOutput {
+    /// The recipient paid by this output.
+    address: String,
+
+    /// The value pool containing the output.
+    ///
+    /// One of `["transparent", "sapling", "orchard"]`.
+    pool: &'static str,
+
+    /// The index of the output within its pool's outputs in the transaction.
+    output_index: u32,
+}
+
+impl BatchedOutput {
+    pub(super) fn new(address: String, pool: PoolType, output_index: u32) -> Self {
+        Self {
+            address,
+            pool: pool_name(pool),
+            output_index,
+        }
+    }
+}
+
+/// Returns the name used for the given value pool in JSON-RPC responses.
+pub(super) fn pool_name(pool: PoolType) -> &'static str {
+    match pool {
+        PoolType::Transparent => "transparent",
+        PoolType::Shielded(ShieldedProtocol::Sapling) => "sapling",
+        PoolType::Shielded(ShieldedProtocol::Orchard) => "orchard",
+    }
+}
+
+/// A group of recipients that were paid together as part of a split payment.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct SplitGroup {
+    /// The recipients paid by this group's transactions.
+    ///
+    /// Empty for a transaction that only merged the account's notes, so that later
+    /// transactions could spend their value.
+    recipients: Vec<String>,
+
+    /// The IDs of this group's transactions.
+    txids: Vec<String>,
 }
 
 impl SendResult {
-    fn new(txids: Vec<TxId>) -> Self {
+    pub(super) fn new(txids: Vec<TxId>, expiry_height: BlockHeight, fee: Option<Zatoshis>) -> Self {
         let txids = txids
             .into_iter()
             .map(|txid| txid.to_string())
             .collect::<Vec<_>>();
+        let (fee, fee_zat) = fee.map(zec_and_zat).unzip();
+
+        Self {
+            txid: (txids.len() == 1).then(|| txids.first().expect("present").clone()),
+            txids,
+            expiryheight: expiry_height.into(),
+            fee,
+            fee_zat,
+            split: None,
+            outputs: None,
+            hex: None,
+            folded_dust: None,
+            folded_dust_zat: None,
+        }
+    }
+
+    /// Combines the results of sending each group of a split payment, in order.
+    pub(super) fn from_split(groups: Vec<(Vec<String>, SendResult)>) -> Self {
+        let txids = groups
+            .iter()
+            .flat_map(|(_, result)| result.txids.iter().cloned())
+            .collect::<Vec<_>>();
+
+        let folded_dust = groups
+            .iter()
+            .filter_map(|(_, result)| result.folded_dust_zat)
+            .map(Zatoshis::const_from_u64)
+            .try_fold(Zatoshis::ZERO, |acc, value| acc + value)
+            .expect("bounded by the fees");
+
+        let fee = groups
+            .iter()
+            .map(|(_, result)| result.fee_zat.map(Zatoshis::const_from_u64))
+            .try_fold(Zatoshis::ZERO, |acc, fee| acc + fee?);
+        let (fee, fee_zat) = fee.map(zec_and_zat).unzip();
+
+        // Either every group was broadcast, or none were.
+        let hex = groups
+            .iter()
+            .map(|(_, result)| result.hex.clone())
+            .collect::<Option<Vec<_>>>()
+            .map(|hex| hex.concat());
 
         Self {
             txid: (txids.len() == 1).then(|| txids.first().expect("present").clone()),
+            expiryheight: groups
+                .iter()
+                .map(|(_, result)| result.expiryheight)
+                .max()
+                .unwrap_or(0),
+            fee,
+            fee_zat,
+            split: Some(
+                groups
+                    .into_iter()
+                    .map(|(recipients, result)| SplitGroup {
+                        recipients,
+                        txids: result.txids,
+                    })
+                    .collect(),
+            ),
+            outputs: None,
+            hex,
+            folded_dust: None,
+            folded_dust_zat: None,
             txids,
         }
+        .with_folded_dust(folded_dust)
+    }
+
+    /// Records the outputs of a shared transaction that pay this send's recipients.
+    pub(super) fn with_outputs(mut self, outputs: Vec<BatchedOutput>) -> Self {
+        self.outputs = Some(outputs);
+        self
+    }
+
+    /// Records the value of change that was added to the fee.
+    pub(super) fn with_folded_dust(mut self, value: Zatoshis) -> Self {
+        if !value.is_zero() {
+            let (folded_dust, folded_dust_zat) = zec_and_zat(value);
+            self.folded_dust = Some(folded_dust);
+            self.folded_dust_zat = Some(folded_dust_zat);
+        }
+        self
+    }
+
+    /// Records the raw bytes of transactions that were not broadcast.
+    fn with_hex(mut self, hex: Vec<String>) -> Self {
+        self.hex = Some(hex);
+        self
+    }
+
+    /// Returns the IDs of the sent transactions.
+    pub(super) fn txids(&self) -> &[String] {
+        &self.txids
+    }
+
+    /// Returns the height after which the sent transactions will expire if they have
+    /// not been mined.
+    pub(super) fn expiry_height(&self) -> BlockHeight {
+        BlockHeight::from_u32(self.expiryheight)
     }
 }
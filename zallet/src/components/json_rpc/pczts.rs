@@ -0,0 +1,400 @@
+//! Partially created Zcash transactions (PCZTs).
+//!
+//! The PCZT methods let the wallet create transactions that are proven and signed
+//! elsewhere, such as on an air-gapped machine or a hardware wallet. `z_createpczt`
+//! reserves the inputs of each PCZT that it creates, so that concurrent sends do not
+//! select them while the PCZT is being signed. The reservation is released when the PCZT
+//! is finalized with `z_finalizepczt`, or once the wallet's chain tip has passed the
+//! expiry height of its transaction, so that it can no longer be mined.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use base64ct::{Base64, Encoding};
+use jsonrpsee::core::RpcResult;
+use pczt::Pczt;
+use zcash_client_backend::data_api::WalletRead;
+use zcash_protocol::consensus::BlockHeight;
+
+use crate::{
+    components::{
+        database::{Database, Reservation},
+        json_rpc::server::LegacyCode,
+    },
+    prelude::*,
+};
+
+/// How often the wallet's chain tip is checked against the expiry heights of pending
+/// PCZTs.
+const TIP_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The longest that the inputs of a PCZT are reserved, in case the wallet stops learning
+/// about new blocks.
+const MAX_HOLD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The Orchard proving key, which takes several seconds to build.
+pub(super) static ORCHARD_PROVING_KEY: LazyLock<orchard::circuit::ProvingKey> =
+    LazyLock::new(orchard::circuit::ProvingKey::build);
+
+/// The Orchard verifying key, which takes several seconds to build.
+pub(super) static ORCHARD_VERIFYING_KEY: LazyLock<orchard::circuit::VerifyingKey> =
+    LazyLock::new(orchard::circuit::VerifyingKey::build);
+
+/// Parses a base64-encoded PCZT.
+pub(super) fn parse(encoded: &str) -> RpcResult<Pczt> {
+    Base64::decode_vec(encoded)
+        .ok()
+        .and_then(|bytes| Pczt::parse(&bytes).ok())
+        .ok_or_else(|| LegacyCode::Deserialization.with_static("PCZT decode failed"))
+}
+
+/// Encodes a PCZT as base64.
+pub(super) fn encode(pczt: Pczt) -> String {
+    Base64::encode_string(&pczt.serialize())
+}
+
+/// The input reservations held for PCZTs that have not been finalized.
+#[derive(Clone)]
+pub(crate) struct PendingPczts {
+    wallet: Database,
+    inner: Arc<Mutex<PendingInner>>,
+}
+
+#[derive(Default)]
+struct PendingInner {
+    next_id: u64,
+    reservations: HashMap<u64, Reservation>,
+}
+
+impl PendingPczts {
+    pub(crate) fn new(wallet: Database) -> Self {
+        Self {
+            wallet,
+            inner: Default::default(),
+        }
+    }
+
+    /// Holds `reservation` for a PCZT whose transaction expires at `expiry_height`,
+    /// returning the ID with which it can be released.
+    pub(super) fn hold(&self, reservation: Reservation, expiry_height: BlockHeight) -> u64 {
+        self.hold_until(reservation, expiry_height, TIP_POLL_INTERVAL, MAX_HOLD)
+    }
+
+    /// Holds `reservation` until it is released, or the wallet's chain tip passes
+    /// `expiry_height`, checking the tip every `poll_interval`. The reservation is held
+    /// for at most `max_hold`.
+    fn hold_until(
+        &self,
+        reservation: Reservation,
+        expiry_height: BlockHeight,
+        poll_interval: Duration,
+        max_hold: Duration,
+    ) -> u64 {
+        let id = self.insert(reservation);
+
+        let pending = self.clone();
+        crate::spawn!("PCZT reservation expiry", async move {
+            let deadline = tokio::time::Instant::now() + max_hold;
+            let mut interval = tokio::time::interval(poll_interval);
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(deadline) => {
+                        if pending.release(id) {
+                            warn!("Released the inputs of PCZT {id} before its expiry height {expiry_height}");
+                        }
+                        return;
+                    }
+                    _ = interval.tick() => {
+                        if !pending.is_held(id) {
+                            return;
+                        }
+                        if pending.chain_tip().await.is_some_and(|tip| tip > expiry_height) {
+                            if pending.release(id) {
+                                debug!("Released the inputs of PCZT {id}, as its transaction has expired");
+                            }
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+
+        id
+    }
+
+    /// Returns the wallet's view of the chain tip, or `None` if it is not yet known.
+    async fn chain_tip(&self) -> Option<BlockHeight> {
+        match self.wallet.handle().await {
+            Ok(handle) => handle.chain_height().unwrap_or_else(|e| {
+                debug!("Failed to read the wallet's chain tip: {e}");
+                None
+            }),
+            Err(e) => {
+                debug!("Failed to open the wallet: {e}");
+                None
+            }
+        }
+    }
+
+    fn insert(&self, reservation: Reservation) -> u64 {
+        let mut inner = self.inner.lock().expect("not poisoned");
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.reservations.insert(id, reservation);
+        id
+    }
+
+    /// Returns `true` if the inputs of the PCZT with the given ID are still reserved.
+    fn is_held(&self, id: u64) -> bool {
+        self.inner
+            .lock()
+            .expect("not poisoned")
+            .reservations
+            .contains_key(&id)
+    }
+
+    /// Releases the inputs reserved for the PCZT with the given ID, returning `false` if
+    /// they were already released.
+    pub(super) fn release(&self, id: u64) -> bool {
+        self.inner
+            .lock()
+            .expect("not poisoned")
+            .reservations
+            .remove(&id)
+            .is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::time::Duration;
+
+    use pczt::{
+        Pczt,
+        roles::{combiner::Combiner, creator::Creator, signer::Signer},
+    };
+    use zcash_client_backend::{
+        data_api::{
+            Account as _, WalletRead, WalletWrite,
+            testing::{
+                AddressType, TestBuilder, orchard::OrchardPoolTester, pool::ShieldedPoolTester,
+                sapling::SaplingPoolTester,
+            },
+            wallet::{
+                ConfirmationsPolicy, create_pczt_from_proposal,
+                extract_and_store_transaction_from_pczt, input_selection::GreedyInputSelector,
+                propose_transfer,
+            },
+        },
+        fees::{
+            DustOutputPolicy, SplitPolicy, StandardFeeRule, standard::MultiOutputChangeStrategy,
+        },
+        wallet::OvkPolicy,
+        zip321::{Payment, TransactionRequest},
+    };
+    use zcash_client_sqlite::{
+        ReceivedNoteId,
+        testing::{BlockCache, db::TestDbFactory},
+    };
+    use zcash_keys::keys::UnifiedSpendingKey;
+    use zcash_primitives::block::BlockHash;
+    use zcash_proofs::prover::LocalTxProver;
+    use zcash_protocol::{TxId, consensus::BlockHeight, value::Zatoshis};
+
+    use crate::{
+        components::{
+            database::{Database, InputReservations, ReservedInput, testing::create_account},
+            json_rpc::methods::{z_combine_pczts, z_prove_pczt},
+        },
+        config::ZalletConfig,
+    };
+
+    use super::{ORCHARD_VERIFYING_KEY, PendingPczts};
+
+    #[test]
+    fn encoding_round_trip() {
+        let pczt = Creator::new(0xc2d6_d0b4, 1_000_040, 133, [0; 32], [0; 32]).build();
+        let encoded = super::encode(pczt.clone());
+        let decoded = super::parse(&encoded).unwrap();
+        assert_eq!(decoded.serialize(), pczt.serialize());
+
+        // Combining a PCZT with itself leaves it unchanged.
+        let combined = Combiner::new(vec![decoded.clone(), decoded])
+            .combine()
+            .unwrap();
+        assert_eq!(combined.serialize(), pczt.serialize());
+
+        assert!(super::parse("not base64").is_err());
+        assert!(super::parse(&encoded[..20]).is_err());
+    }
+
+    /// Signs every spend in `pczt` that `usk` can authorize, as an offline signer would.
+    fn sign(pczt: Pczt, usk: &UnifiedSpendingKey) -> Pczt {
+        let sapling_spends = pczt.sapling().spends().len();
+        let orchard_actions = pczt.orchard().actions().len();
+        let sapling_ask = &usk.sapling().expsk.ask;
+        let orchard_ask = orchard::keys::SpendAuthorizingKey::from(usk.orchard());
+
+        // Dummy spends are signed when the PCZT is created, and reject other keys.
+        let mut signer = Signer::new(pczt).unwrap();
+        for index in 0..sapling_spends {
+            let _ = signer.sign_sapling(index, sapling_ask);
+        }
+        for index in 0..orchard_actions {
+            let _ = signer.sign_orchard(index, &orchard_ask);
+        }
+        signer.finish()
+    }
+
+    /// Creates a PCZT spending a note in `T`'s pool, proves it with `z_provepczt`, signs
+    /// it with the account's spending key, combines the proven and signed PCZTs with
+    /// `z_combinepczts`, and then finalizes it as `z_finalizepczt` does.
+    async fn round_trip<T: ShieldedPoolTester>() {
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let account = st.test_account().cloned().unwrap();
+        let fvk = T::test_account_fvk(&st);
+
+        st.generate_next_block(
+            &fvk,
+            AddressType::DefaultExternal,
+            Zatoshis::const_from_u64(100_000),
+        );
+        st.scan_cached_blocks(account.birthday().height(), 1);
+
+        let network = *st.network();
+        let request = TransactionRequest::new(vec![Payment::without_memo(
+            T::fvk_default_address(&fvk).to_zcash_address(&network),
+            Zatoshis::const_from_u64(50_000),
+        )])
+        .unwrap();
+        let change_strategy = MultiOutputChangeStrategy::new(
+            StandardFeeRule::Zip317,
+            None,
+            T::SHIELDED_PROTOCOL,
+            DustOutputPolicy::default(),
+            SplitPolicy::single_output(),
+        );
+        let proposal = propose_transfer::<_, _, _, _, Infallible>(
+            st.wallet_mut(),
+            &network,
+            account.id(),
+            &GreedyInputSelector::new(),
+            &change_strategy,
+            request,
+            ConfirmationsPolicy::new_symmetrical(1.try_into().unwrap(), false),
+        )
+        .unwrap();
+        let created = create_pczt_from_proposal::<_, _, Infallible, _, Infallible, _>(
+            st.wallet_mut(),
+            &network,
+            account.id(),
+            OvkPolicy::Sender,
+            &proposal,
+        )
+        .unwrap();
+        let encoded = super::encode(created);
+
+        // The online wallet proves the PCZT, while the offline signer signs it.
+        let proven =
+            serde_json::to_value(z_prove_pczt::call(encoded.clone()).await.unwrap()).unwrap();
+        let signed = super::encode(sign(super::parse(&encoded).unwrap(), account.usk()));
+        let combined = serde_json::to_value(
+            z_combine_pczts::call(vec![proven.as_str().unwrap().into(), signed]).unwrap(),
+        )
+        .unwrap();
+
+        let (spend_vk, output_vk) = LocalTxProver::bundled().verifying_keys();
+        let txid: TxId = extract_and_store_transaction_from_pczt::<_, ReceivedNoteId>(
+            st.wallet_mut(),
+            super::parse(combined.as_str().unwrap()).unwrap(),
+            Some((&spend_vk.prepare(), &output_vk.prepare())),
+            Some(&ORCHARD_VERIFYING_KEY),
+        )
+        .unwrap();
+        assert!(st.wallet().get_transaction(txid).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn sapling_round_trip() {
+        round_trip::<SaplingPoolTester>().await;
+    }
+
+    #[tokio::test]
+    async fn orchard_round_trip() {
+        round_trip::<OrchardPoolTester>().await;
+    }
+
+    /// Sets the chain tip of `wallet` to `height`.
+    async fn set_tip(wallet: &Database, height: u32) {
+        wallet
+            .handle()
+            .await
+            .unwrap()
+            .update_chain_tip(BlockHeight::from_u32(height))
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reservations_are_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = ZalletConfig::default();
+        config.datadir = Some(dir.path().to_path_buf());
+        let wallet = Database::open(&config).await.unwrap();
+        create_account(&mut wallet.handle().await.unwrap());
+        set_tip(&wallet, 1_000_039).await;
+
+        let reservations = InputReservations::default();
+        let input = |index| ReservedInput::Note {
+            txid: TxId::from_bytes([1; 32]),
+            protocol: zcash_protocol::ShieldedProtocol::Orchard,
+            index,
+        };
+        let pending = PendingPczts::new(wallet.clone());
+        let hold = |index, expiry_height, max_hold| {
+            pending.hold_until(
+                reservations.try_reserve([input(index)]).unwrap(),
+                BlockHeight::from_u32(expiry_height),
+                Duration::from_millis(10),
+                max_hold,
+            )
+        };
+
+        // Finalizing a PCZT releases its inputs.
+        let id = hold(0, 1_000_080, Duration::from_secs(600));
+        assert!(reservations.is_reserved(&input(0)));
+        assert!(pending.release(id));
+        assert!(!reservations.is_reserved(&input(0)));
+        assert!(!pending.release(id));
+
+        // Otherwise, they are held while its transaction can still be mined...
+        let id = hold(1, 1_000_040, Duration::from_secs(600));
+        set_tip(&wallet, 1_000_040).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(reservations.is_reserved(&input(1)));
+
+        // ...and released once the chain tip has passed its expiry height.
+        let notified = reservations.released();
+        set_tip(&wallet, 1_000_041).await;
+        tokio::time::timeout(Duration::from_secs(10), notified)
+            .await
+            .unwrap();
+        assert!(!reservations.is_reserved(&input(1)));
+        assert!(!pending.release(id));
+
+        // If the wallet stops learning about new blocks, they are released after the
+        // maximum hold.
+        let notified = reservations.released();
+        let id = hold(2, 1_000_080, Duration::from_millis(50));
+        tokio::time::timeout(Duration::from_secs(10), notified)
+            .await
+            .unwrap();
+        assert!(!reservations.is_reserved(&input(2)));
+        assert!(!pending.release(id));
+    }
+}
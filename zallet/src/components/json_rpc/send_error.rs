@@ -0,0 +1,340 @@
+//! Errors returned by the operations that create and send transactions.
+//!
+//! `zcashd` reported each failure in its send pipeline with a specific legacy error code
+//! and message, which clients match against. The failures that can occur while
+//! proposing, building, and broadcasting a transaction are classified here, so that
+//! every method reports them in the same shape as `zcashd` did.
+//!
+//! Where a failure has details that a client may want to act on, such as the funds
+//! that are missing, they are additionally returned as structured `data` in the error
+//! object. All values in `data` are in zatoshis.
+
+use std::fmt;
+
+use jsonrpsee::types::ErrorObjectOwned;
+use serde_json::json;
+use zcash_client_backend::{
+    data_api::{error::Error, wallet::input_selection::InputSelectorError},
+    fees::ChangeError,
+};
+use zcash_protocol::value::{COIN, Zatoshis};
+
+use super::{batching::MAX_TX_SIZE, server::LegacyCode};
+
+/// A failure to create or send a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum SendFailure {
+    /// The spendable funds do not cover the payments and fee.
+    InsufficientFunds {
+        available: Zatoshis,
+        required: Zatoshis,
+        /// The account's spendable funds in each pool, if they could be determined.
+        pool_breakdown: Option<PoolBreakdown>,
+    },
+    /// The wallet must finish syncing before it can select inputs.
+    SyncRequired,
+    /// The transaction is not permitted by the wallet's policy, for example because it
+    /// sends a memo to a transparent recipient.
+    Policy(String),
+    /// The serialized transaction exceeds the maximum transaction size.
+    TooLarge { size: usize },
+    /// Building the transaction, including creating its proofs, failed.
+    Build(String),
+    /// The wallet does not hold the keys required to sign the transaction.
+    Signing(String),
+    /// The node rejected the transaction.
+    Rejected {
+        /// The node's reject reason, if it could be determined.
+        reason: Option<String>,
+        message: String,
+    },
+    /// An internal error that is not the caller's concern.
+    Internal(String),
+}
+
+/// The funds that an account can spend from each value pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct PoolBreakdown {
+    pub(super) transparent: Zatoshis,
+    pub(super) sapling: Zatoshis,
+    pub(super) orchard: Zatoshis,
+}
+
+impl SendFailure {
+    /// Classifies an error returned while proposing a transaction.
+    pub(super) fn from_proposal_error<DE, CE, SE, FE, CHE, N>(
+        e: &Error<DE, CE, InputSelectorError<DE, SE, CHE, N>, FE, CHE, N>,
+    ) -> Self
+    where
+        Error<DE, CE, InputSelectorError<DE, SE, CHE, N>, FE, CHE, N>: fmt::Display,
+    {
+        match e {
+            Error::NoteSelection(InputSelectorError::InsufficientFunds {
+                available,
+                required,
+            })
+            | Error::NoteSelection(InputSelectorError::Change(ChangeError::InsufficientFunds {
+                available,
+                required,
+            })) => SendFailure::InsufficientFunds {
+                available: *available,
+                required: *required,
+                pool_breakdown: None,
+            },
+            Error::NoteSelection(InputSelectorError::SyncRequired) => SendFailure::SyncRequired,
+            Error::NoteSelection(InputSelectorError::Address(_)) => {
+                SendFailure::Policy(e.to_string())
+            }
+            _ => Self::from_wallet_error(e),
+        }
+    }
+
+    /// Classifies an error returned while creating the transactions for a proposal.
+    pub(super) fn from_wallet_error<DE, CE, SE, FE, CHE, N>(
+        e: &Error<DE, CE, SE, FE, CHE, N>,
+    ) -> Self
+    where
+        Error<DE, CE, SE, FE, CHE, N>: fmt::Display,
+    {
+        match e {
+            Error::InsufficientFunds {
+                available,
+                required,
+            }
+            | Error::Change(ChangeError::InsufficientFunds {
+                available,
+                required,
+            }) => SendFailure::InsufficientFunds {
+                available: *available,
+                required: *required,
+                pool_breakdown: None,
+            },
+            Error::ScanRequired => SendFailure::SyncRequired,
+            Error::MemoForbidden
+            | Error::NoSupportedReceivers(_)
+            | Error::UnsupportedChangeType(_) => SendFailure::Policy(e.to_string()),
+            Error::Builder(_) => SendFailure::Build(e.to_string()),
+            Error::KeyNotRecognized | Error::NoSpendingKey(_) => {
+                SendFailure::Signing(e.to_string())
+            }
+            _ => SendFailure::Internal(e.to_string()),
+        }
+    }
+
+    /// Classifies an error returned by the node when broadcasting a transaction.
+    pub(super) fn from_broadcast_error(e: impl fmt::Display) -> Self {
+        let message = e.to_string();
+        SendFailure::Rejected {
+            reason: reject_reason(&message).map(String::from),
+            message,
+        }
+    }
+}
+
+impl From<SendFailure> for ErrorObjectOwned {
+    fn from(e: SendFailure) -> Self {
+        match e {
+            SendFailure::InsufficientFunds {
+                available,
+                required,
+                pool_breakdown,
+            } => {
+                let mut data = json!({
+                    "required": required.into_u64(),
+                    "available": available.into_u64(),
+                });
+                if let Some(pools) = pool_breakdown {
+                    data["pool_breakdown"] = json!({
+                        "transparent": pools.transparent.into_u64(),
+                        "sapling": pools.sapling.into_u64(),
+                        "orchard": pools.orchard.into_u64(),
+                    });
+                }
+                LegacyCode::InvalidParameter.with_data(
+                    format!(
+                        "Insufficient funds: have {}, need {}",
+                        format_money(available),
+                        format_money(required),
+                    ),
+                    data,
+                )
+            }
+            SendFailure::SyncRequired => LegacyCode::InWarmup.with_static("Wallet sync required"),
+            SendFailure::Policy(e) => LegacyCode::InvalidParameter.with_message(e),
+            SendFailure::TooLarge { size } => LegacyCode::InvalidParameter.with_data(
+                format!(
+                    "Too many outputs, size of raw transaction would be larger than limit of {MAX_TX_SIZE} bytes",
+                ),
+                json!({ "size": size, "limit": MAX_TX_SIZE }),
+            ),
+            SendFailure::Build(e) => {
+                LegacyCode::Wallet.with_message(format!("Failed to build transaction: {e}"))
+            }
+            SendFailure::Signing(e) => {
+                LegacyCode::Wallet.with_message(format!("Failed to sign transaction: {e}"))
+            }
+            SendFailure::Rejected {
+                reason: Some(reason),
+                message,
+            } => LegacyCode::Wallet.with_data(
+                format!("SendTransaction: Transaction commit failed:: {reason}"),
+                json!({ "reject_reason": reason, "node_error": message }),
+            ),
+            SendFailure::Rejected {
+                reason: None,
+                message,
+            } => LegacyCode::Wallet.with_message(format!(
+                "SendTransaction: Transaction commit failed:: {message}"
+            )),
+            SendFailure::Internal(e) => LegacyCode::Wallet.with_message(e),
+        }
+    }
+}
+
+/// Extracts the reject reason (such as `bad-txns-inputs-spent`) from a node's error
+/// message.
+fn reject_reason(message: &str) -> Option<&str> {
+    message
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+        .find(|token| {
+            token.starts_with(|c: char| c.is_ascii_lowercase())
+                && token.contains('-')
+                && !token.ends_with('-')
+                && token
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })
+}
+
+/// Equivalent of `FormatMoney` in `zcashd`.
+fn format_money(value: Zatoshis) -> String {
+    let value = value.into_u64();
+    let formatted = format!("{}.{:08}", value / COIN, value % COIN);
+    // Trim trailing zeroes, keeping at least two decimal places.
+    let trimmed = formatted.trim_end_matches('0');
+    let decimals = trimmed.len() - trimmed.find('.').expect("present") - 1;
+    formatted[..trimmed.len() + 2usize.saturating_sub(decimals)].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpsee::types::ErrorObjectOwned;
+    use zcash_protocol::value::Zatoshis;
+
+    use serde_json::json;
+
+    use super::{PoolBreakdown, SendFailure, reject_reason};
+
+    #[test]
+    fn error_objects() {
+        let cases = [
+            (
+                SendFailure::InsufficientFunds {
+                    available: Zatoshis::const_from_u64(150_000_000),
+                    required: Zatoshis::const_from_u64(200_010_000),
+                    pool_breakdown: None,
+                },
+                -8,
+                "Insufficient funds: have 1.50, need 2.0001",
+            ),
+            (SendFailure::SyncRequired, -28, "Wallet sync required"),
+            (
+                SendFailure::Policy("Memos cannot be sent to transparent addresses.".into()),
+                -8,
+                "Memos cannot be sent to transparent addresses.",
+            ),
+            (
+                SendFailure::TooLarge { size: 150_000 },
+                -8,
+                "Too many outputs, size of raw transaction would be larger than limit of 100000 bytes",
+            ),
+            (
+                SendFailure::Build("Orchard proof creation failed".into()),
+                -4,
+                "Failed to build transaction: Orchard proof creation failed",
+            ),
+            (
+                SendFailure::Signing("No spending key".into()),
+                -4,
+                "Failed to sign transaction: No spending key",
+            ),
+            (
+                SendFailure::from_broadcast_error("RPC error -26: 18: bad-txns-inputs-spent"),
+                -4,
+                "SendTransaction: Transaction commit failed:: bad-txns-inputs-spent",
+            ),
+            (
+                SendFailure::from_broadcast_error("connection refused"),
+                -4,
+                "SendTransaction: Transaction commit failed:: connection refused",
+            ),
+            (
+                SendFailure::Internal("database is locked".into()),
+                -4,
+                "database is locked",
+            ),
+        ];
+
+        for (failure, code, message) in cases {
+            let e = ErrorObjectOwned::from(failure.clone());
+            assert_eq!(e.code(), code, "{failure:?}");
+            assert_eq!(e.message(), message, "{failure:?}");
+        }
+    }
+
+    #[test]
+    fn error_data() {
+        let data = |failure: SendFailure| {
+            serde_json::from_str::<serde_json::Value>(
+                ErrorObjectOwned::from(failure).data().unwrap().get(),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            data(SendFailure::InsufficientFunds {
+                available: Zatoshis::const_from_u64(150_000_000),
+                required: Zatoshis::const_from_u64(200_010_000),
+                pool_breakdown: Some(PoolBreakdown {
+                    transparent: Zatoshis::const_from_u64(0),
+                    sapling: Zatoshis::const_from_u64(50_000_000),
+                    orchard: Zatoshis::const_from_u64(100_000_000),
+                }),
+            }),
+            json!({
+                "required": 200_010_000,
+                "available": 150_000_000,
+                "pool_breakdown": {
+                    "transparent": 0,
+                    "sapling": 50_000_000,
+                    "orchard": 100_000_000,
+                },
+            }),
+        );
+        assert_eq!(
+            data(SendFailure::TooLarge { size: 150_000 }),
+            json!({ "size": 150_000, "limit": 100_000 }),
+        );
+
+        // Failures without details to act on have no data.
+        assert!(
+            ErrorObjectOwned::from(SendFailure::SyncRequired)
+                .data()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn reject_reasons() {
+        let cases = [
+            ("18: absurdly-high-fee", Some("absurdly-high-fee")),
+            ("code: -26, message: 64: tx-size", Some("tx-size")),
+            ("transaction already in block chain", None),
+            ("Transport error: Connection-Refused", None),
+        ];
+
+        for (message, reason) in cases {
+            assert_eq!(reject_reason(message), reason, "{message}");
+        }
+    }
+}
@@ -1,90 +1,300 @@
 //! JSON-RPC server that is compatible with `zcashd`.
 
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use hyper::body::Incoming;
 use jsonrpsee::{
-    server::{RpcServiceBuilder, Server},
-    tracing::info,
+    server::{
+        BatchRequestConfig, HttpBody, HttpRequest, Methods, RpcServiceBuilder, Server,
+        serve_with_graceful_shutdown, stop_channel, ws,
+    },
+    tracing::{debug, info, warn},
+};
+use tokio::{
+    select,
+    task::JoinHandle,
+    time::{self, Instant},
 };
-use tokio::task::JoinHandle;
+use tower::Service;
 
 use crate::{
-    components::{chain::Chain, database::Database},
-    config::RpcSection,
-    error::{Error, ErrorKind},
-    fl,
+    components::wallets::Wallets,
+    config::{RpcAuthMethod, RpcBind, RpcSection},
+    error::{Error, ErrorKind, ExitCode},
+    fl, shutdown,
 };
 
-use super::methods::{RpcImpl, RpcServer as _};
-
 #[cfg(zallet_build = "wallet")]
-use {
-    super::methods::{WalletRpcImpl, WalletRpcServer},
-    crate::components::keystore::KeyStore,
-};
+use super::methods::{WalletManagementRpcImpl, WalletManagementRpcServer as _};
 
 mod error;
-pub(crate) use error::LegacyCode;
+pub(crate) use error::{ComponentFailure, LegacyCode};
 
+mod allow_ip;
 pub(crate) mod authorization;
+pub(crate) mod cookie;
+mod health;
 mod http_request_compatibility;
+mod listener;
+mod metrics;
+mod rate_limit;
+#[cfg(zallet_build = "wallet")]
+mod read_only;
+mod request_limits;
+mod request_log;
 mod rpc_call_compatibility;
+mod tls;
+mod wallet_selection;
+mod websocket;
+
+pub(crate) use tls::TlsPaths;
+
+#[cfg(zallet_build = "wallet")]
+pub(crate) use request_log::RequestId;
 
 type ServerTask = JoinHandle<Result<(), Error>>;
 
 pub(crate) async fn spawn(
     config: RpcSection,
-    wallet: Database,
-    #[cfg(zallet_build = "wallet")] keystore: KeyStore,
-    chain: Chain,
+    listen_addr: RpcBind,
+    cookie_path: PathBuf,
+    tls: Option<TlsPaths>,
+    wallets: Wallets,
 ) -> Result<ServerTask, Error> {
-    // Caller should make sure `bind` only contains a single address (for now).
-    assert_eq!(config.bind.len(), 1);
-    let listen_addr = config.bind[0];
+    let health = health::HealthState::new(wallets.clone(), config.ready_max_scan_lag());
 
-    // Initialize the RPC methods.
+    // Each wallet serves its own methods. When no wallets are loaded, only the methods
+    // that manage wallets are served.
     #[cfg(zallet_build = "wallet")]
-    let wallet_rpc_impl = WalletRpcImpl::new(wallet.clone(), keystore.clone(), chain.clone());
-    let rpc_impl = RpcImpl::new(
-        wallet,
-        #[cfg(zallet_build = "wallet")]
-        keystore,
-        chain,
-    );
+    let unloaded = Methods::from(WalletManagementRpcImpl::new(wallets.downgrade()).into_rpc());
+    #[cfg(not(zallet_build = "wallet"))]
+    let unloaded = Methods::new();
+    let router = Arc::new(wallet_selection::Router::new(wallets.clone(), unloaded));
 
+    let allow_ip = allow_ip::AllowIpLayer::new(config.allow_ip.clone());
     let timeout = config.timeout();
+    let shutdown_timeout = config.shutdown_timeout();
+    let request_logging = config.request_logging();
+    let unix_socket_mode = config.unix_socket_mode();
+    let max_batch_size = config.max_batch_size();
+    let max_request_body_bytes = config.max_request_body_bytes();
+    let max_response_body_bytes = config.max_response_body_bytes();
+    let max_connections = config.max_connections();
+    let rate_limiter = Arc::new(rate_limit::RateLimiter::new(config.rate_limit.clone()));
+    #[cfg(zallet_build = "wallet")]
+    let read_only = config
+        .read_only()
+        .then(|| tower::layer::layer_fn(read_only::ReadOnlyMiddleware::new));
+    #[cfg(not(zallet_build = "wallet"))]
+    let read_only = None::<tower::layer::util::Identity>;
 
+    let (authorization, cookie) = match config.auth_method() {
+        RpcAuthMethod::Cookie => {
+            let cookie = cookie::generate();
+            let authorization = authorization::AuthorizationLayer::new(config.auth, Some(&cookie))
+                .map_err(|()| ErrorKind::Init.context(fl!("err-init-rpc-auth-invalid")))?;
+            (authorization, Some(cookie))
+        }
+        RpcAuthMethod::None if config.auth.is_empty() => {
+            // Client certificates are an alternative to authorizing requests.
+            if !tls
+                .as_ref()
+                .is_some_and(|tls| tls.requires_client_certificate())
+            {
+                warn!("\n{}", fl!("rpc-no-auth-warn"));
+            }
+            (authorization::AuthorizationLayer::disabled(), None)
+        }
+        RpcAuthMethod::None => {
+            return Err(ErrorKind::Init
+                .context(fl!("err-init-rpc-auth-none-with-users"))
+                .into());
+        }
+    };
+
+    let tls = tls.map(tls::Tls::load).transpose()?;
+    #[cfg(unix)]
+    if let Some(tls) = &tls {
+        tls.reload_on_sighup()?;
+    }
+
+    // Clients outside of `rpc.allow_ip` are rejected before anything else.
     let http_middleware = tower::ServiceBuilder::new()
-        .layer(
-            authorization::AuthorizationLayer::new(config.auth)
-                .map_err(|()| ErrorKind::Init.context(fl!("err-init-rpc-auth-invalid")))?,
-        )
+        .layer(allow_ip)
+        .layer(health::HealthLayer::new(health))
+        .layer(authorization)
+        .layer(request_limits::RequestLimitsLayer::new(
+            max_batch_size,
+            max_request_body_bytes,
+        ))
         .layer(http_request_compatibility::HttpRequestMiddlewareLayer::new())
         .timeout(timeout);
 
+    // `jsonrpsee`'s own call logger is not used, because it would log the parameters and
+    // results of every call at the `trace` level, including secrets.
     let rpc_middleware = RpcServiceBuilder::new()
-        .rpc_logger(1024)
+        .layer_fn(move |service| request_log::RequestLogMiddleware::new(service, request_logging))
+        .layer_fn(metrics::MetricsMiddleware::new)
+        .layer_fn(move |service| {
+            rate_limit::RateLimitMiddleware::new(service, rate_limiter.clone())
+        })
+        .option_layer(read_only)
+        .layer_fn(websocket::WebSocketOnlyMiddleware::new)
+        .layer_fn(wallet_selection::WalletSelectionMiddleware::new)
         .layer_fn(rpc_call_compatibility::FixRpcResponseMiddleware::new);
 
-    let server_instance = Server::builder()
-        .http_only()
+    // WebSocket connections are accepted on the same port, for subscriptions.
+    let service_builder = Server::builder()
+        .max_request_body_size(max_request_body_bytes)
+        .max_response_body_size(max_response_body_bytes)
+        .set_batch_request_config(BatchRequestConfig::Limit(max_batch_size))
         .set_http_middleware(http_middleware)
         .set_rpc_middleware(rpc_middleware)
-        .build(listen_addr)
+        .to_service_builder();
+
+    let listener = listener::Listener::bind(&listen_addr, unix_socket_mode)
         .await
-        .map_err(|e| ErrorKind::Init.context(e))?;
-    let addr = server_instance
+        .map_err(|e| Error::from(ErrorKind::Init.context(e)).with_exit_code(ExitCode::RpcBind))?;
+    let addr = listener
         .local_addr()
         .map_err(|e| ErrorKind::Init.context(e))?;
-    info!("Opened RPC endpoint at {}", addr);
+    info!(
+        "Opened RPC endpoint at {}{}",
+        addr,
+        if tls.is_some() { " (TLS)" } else { "" },
+    );
+    // Without authorization we have already warned, and with TLS the credentials are
+    // not sent in plaintext.
+    if let RpcBind::Tcp(socket_addr) = &addr {
+        if cookie.is_some() && tls.is_none() && !socket_addr.ip().is_loopback() {
+            warn!(
+                "\n{}",
+                fl!("rpc-non-loopback-auth-warn", addr = addr.to_string())
+            );
+        }
+    }
 
-    #[allow(unused_mut)]
-    let mut rpc_module = rpc_impl.into_rpc();
-    #[cfg(zallet_build = "wallet")]
-    rpc_module
-        .merge(wallet_rpc_impl.into_rpc())
-        .map_err(|e| ErrorKind::Init.context(e))?;
+    // The cookie file is only written once the server can accept requests with it.
+    let cookie_path = cookie
+        .map(|cookie| {
+            cookie::write(&cookie_path, &cookie)
+                .map(|()| {
+                    info!("Wrote RPC cookie file to {}", cookie_path.display());
+                    cookie_path.clone()
+                })
+                .map_err(|e| {
+                    ErrorKind::Init.context(fl!(
+                        "err-init-rpc-cookie-write",
+                        path = cookie_path.display().to_string(),
+                        error = e.to_string(),
+                    ))
+                })
+        })
+        .transpose()?;
 
+    // We accept connections ourselves instead of using `Server::start`, so that the
+    // caller's address is available to the RPC middleware.
     let server_task = crate::spawn!("JSON-RPC server", async move {
-        server_instance.start(rpc_module).stopped().await;
+        let (stop_handle, server_handle) = stop_channel();
+        let connections = max_connections.map(listener::ConnectionLimit::new);
+
+        loop {
+            let (socket, remote_addr) = select! {
+                conn = listener.accept() => match conn {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        debug!("Failed to accept RPC connection: {e}");
+                        continue;
+                    }
+                },
+                _ = shutdown::requested() => break,
+            };
+
+            // Each connection holds a permit until it is closed.
+            let permit = match &connections {
+                Some(connections) => match connections.acquire(&remote_addr) {
+                    Some(permit) => Some(permit),
+                    None => continue,
+                },
+                None => None,
+            };
+
+            let acceptor = tls.as_ref().map(tls::Tls::acceptor);
+            let service_builder = service_builder.clone();
+            let router = router.clone();
+            let connection_stop_handle = stop_handle.clone();
+            let service = tower::service_fn(move |mut request: HttpRequest<Incoming>| {
+                request.extensions_mut().insert(remote_addr);
+                if !ws::is_upgrade_request(&request) {
+                    request.extensions_mut().insert(websocket::HttpTransport);
+                }
+                let (methods, unavailable) = router.route(request.uri());
+                if let Some(unavailable) = unavailable {
+                    request.extensions_mut().insert(unavailable);
+                }
+                let mut service = service_builder
+                    .clone()
+                    .build(methods, connection_stop_handle.clone());
+                async move { service.call(request.map(HttpBody::new)).await }
+            });
+
+            let connection_stopped = stop_handle.clone().shutdown();
+            match acceptor {
+                None => crate::spawn!("JSON-RPC connection", async move {
+                    let _permit = permit;
+                    serve_with_graceful_shutdown(socket, service, connection_stopped).await
+                }),
+                // The TLS handshake happens in the connection's task, so that a slow
+                // client cannot hold up the accept loop.
+                Some(acceptor) => crate::spawn!("JSON-RPC TLS connection", async move {
+                    let _permit = permit;
+                    match acceptor.accept(socket).await {
+                        Ok(stream) => {
+                            serve_with_graceful_shutdown(stream, service, connection_stopped).await
+                        }
+                        Err(e) => {
+                            debug!("TLS handshake with {remote_addr} failed: {e}");
+                            Ok(())
+                        }
+                    }
+                }),
+            };
+        }
+
+        // Stop accepting connections, and let in-flight requests finish.
+        info!("Stopping RPC server");
+        drop(listener);
+        drop(stop_handle);
+        let deadline = Instant::now() + shutdown_timeout;
+        let _ = server_handle.stop();
+        if time::timeout_at(deadline, server_handle.stopped())
+            .await
+            .is_err()
+        {
+            warn!("In-flight RPC requests did not finish before the shutdown timeout");
+        }
+
+        // Clients can no longer use the cookie.
+        if let Some(cookie_path) = cookie_path {
+            if let Err(e) = cookie::remove(&cookie_path) {
+                warn!(
+                    "Failed to remove RPC cookie file {}: {e}",
+                    cookie_path.display()
+                );
+            }
+        }
+
+        // Async operations outlive the requests that launched them.
+        #[cfg(zallet_build = "wallet")]
+        for wallet in wallets.loaded() {
+            wallet
+                .rpc
+                .drain(deadline.saturating_duration_since(Instant::now()))
+                .await;
+        }
+
+        info!("RPC server stopped");
         Ok(())
     });
 
@@ -0,0 +1,153 @@
+//! Filtering of JSON-RPC clients by IP address, configured with `rpc.allow_ip`.
+
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures::FutureExt;
+use hyper::StatusCode;
+use jsonrpsee::{
+    core::BoxError,
+    server::{HttpBody, HttpRequest, HttpResponse},
+};
+use tower::Service;
+use tracing::debug;
+
+use super::request_log::CallerAddress;
+use crate::config::AllowedIp;
+
+/// Returns whether a client connecting from `addr` may send requests.
+///
+/// Loopback clients are always allowed.
+fn is_allowed(allowed: &[AllowedIp], addr: IpAddr) -> bool {
+    addr.to_canonical().is_loopback() || allowed.iter().any(|allowed| allowed.contains(addr))
+}
+
+async fn forbidden() -> Result<HttpResponse, BoxError> {
+    HttpResponse::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(HttpBody::empty())
+        .map_err(BoxError::from)
+}
+
+/// HTTP middleware that rejects requests from clients outside of `rpc.allow_ip`.
+#[derive(Clone, Debug)]
+pub struct AllowIp<S> {
+    service: S,
+    allowed: Arc<[AllowedIp]>,
+}
+
+/// Implements [`tower::Layer`] for [`AllowIp`].
+#[derive(Clone)]
+pub struct AllowIpLayer {
+    allowed: Arc<[AllowedIp]>,
+}
+
+impl AllowIpLayer {
+    /// Creates a new `AllowIpLayer` that allows loopback clients, and clients within the
+    /// given ranges.
+    pub fn new(allowed: Vec<AllowedIp>) -> Self {
+        Self {
+            allowed: allowed.into(),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for AllowIpLayer {
+    type Service = AllowIp<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        AllowIp {
+            service,
+            allowed: self.allowed.clone(),
+        }
+    }
+}
+
+impl<S> Service<HttpRequest<HttpBody>> for AllowIp<S>
+where
+    S: Service<HttpRequest, Response = HttpResponse> + Clone + Send + 'static,
+    S::Error: Into<BoxError> + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = HttpResponse;
+    type Error = BoxError;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: HttpRequest<HttpBody>) -> Self::Future {
+        // Every connection has a caller address, but fail closed if one is missing.
+        let allowed = match request.extensions().get::<CallerAddress>() {
+            Some(caller) => caller
+                .ip()
+                .is_none_or(|addr| is_allowed(&self.allowed, addr)),
+            None => false,
+        };
+
+        if allowed {
+            let mut service = self.service.clone();
+            async move { service.call(request).await.map_err(Into::into) }.boxed()
+        } else {
+            debug!(
+                "Rejected RPC request from {}, which is not in rpc.allow_ip",
+                request
+                    .extensions()
+                    .get::<CallerAddress>()
+                    .map_or_else(|| "unknown".into(), |caller| caller.to_string()),
+            );
+            forbidden().boxed()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::AllowedIp;
+
+    use super::is_allowed;
+
+    #[test]
+    fn dual_stack_clients() {
+        let allowed = ["192.168.1.0/24", "fd00::/8", "203.0.113.7"]
+            .map(|value| AllowedIp::try_from(value.to_owned()).unwrap());
+        let check = |addr: &str| is_allowed(&allowed, addr.parse().unwrap());
+
+        // Loopback clients are always allowed, including over a dual-stack socket.
+        for addr in ["127.0.0.1", "127.1.2.3", "::1", "::ffff:127.0.0.1"] {
+            assert!(check(addr), "{addr} should be allowed");
+            assert!(is_allowed(&[], addr.parse().unwrap()));
+        }
+
+        // Clients of a dual-stack socket connect from IPv4-mapped IPv6 addresses.
+        for addr in [
+            "192.168.1.5",
+            "::ffff:192.168.1.5",
+            "203.0.113.7",
+            "::ffff:203.0.113.7",
+            "fd12:3456::1",
+        ] {
+            assert!(check(addr), "{addr} should be allowed");
+            assert!(!is_allowed(&[], addr.parse().unwrap()));
+        }
+
+        for addr in [
+            "192.168.2.5",
+            "::ffff:192.168.2.5",
+            "203.0.113.8",
+            "::ffff:203.0.113.8",
+            "fe80::1",
+            // IPv4-compatible addresses are not IPv4-mapped.
+            "::192.168.1.5",
+        ] {
+            assert!(!check(addr), "{addr} should be rejected");
+        }
+    }
+}
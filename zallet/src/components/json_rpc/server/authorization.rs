@@ -24,6 +24,7 @@ use sha2::{
 use tower::Service;
 use tracing::{info, warn};
 
+use super::cookie::COOKIE_USER;
 use crate::{config::RpcAuthSection, fl};
 
 type SaltedPasswordHash = CtOutput<Hmac<Sha256>>;
@@ -103,24 +104,35 @@ impl PasswordHash {
     }
 
     fn check(&self, password: &str) -> bool {
+        // `CtOutput` compares in constant time.
         hash_password(password, &self.salt) == self.hash
     }
 }
 
+/// The username that an HTTP request was authorized as.
+///
+/// This is inserted into the extensions of each authorized request.
+#[derive(Clone, Debug)]
+pub(crate) struct AuthenticatedUser(pub(crate) String);
+
 #[derive(Clone, Debug)]
 pub struct Authorization<S> {
     service: S,
-    users: HashMap<String, PasswordHash>,
+    /// The users that requests may authorize as, or `None` if authorization is disabled.
+    users: Option<HashMap<String, PasswordHash>>,
 }
 
 impl<S> Authorization<S> {
     /// Creates a new `Authorization` with the given service.
-    fn new(service: S, users: HashMap<String, PasswordHash>) -> Self {
+    fn new(service: S, users: Option<HashMap<String, PasswordHash>>) -> Self {
         Self { service, users }
     }
 
-    /// Checks whether the authorization is valid.
-    fn is_authorized(&self, auth_header: &header::HeaderValue) -> bool {
+    /// Checks whether the authorization is valid, returning the authorized user if so.
+    fn authorized_user(
+        users: &HashMap<String, PasswordHash>,
+        auth_header: &header::HeaderValue,
+    ) -> Option<String> {
         let encoded_user_pass = match auth_header
             .to_str()
             .ok()
@@ -129,34 +141,32 @@ impl<S> Authorization<S> {
             .and_then(|b| String::from_utf8(b).ok())
         {
             Some(s) => SecretString::new(s),
-            None => return false,
+            None => return None,
         };
 
-        let (user, pass) = match encoded_user_pass.expose_secret().split_once(':') {
-            Some(res) => res,
-            None => return false,
-        };
+        let (user, pass) = encoded_user_pass.expose_secret().split_once(':')?;
 
-        match self.users.get(user) {
-            None => false,
-            Some(password) => password.check(pass),
-        }
+        users
+            .get(user)
+            .filter(|password| password.check(pass))
+            .map(|_| user.to_string())
     }
 }
 
 /// Implements [`tower::Layer`] for [`Authorization`].
 #[derive(Clone)]
 pub struct AuthorizationLayer {
-    users: HashMap<String, PasswordHash>,
+    users: Option<HashMap<String, PasswordHash>>,
 }
 
 impl AuthorizationLayer {
-    /// Creates a new `AuthorizationLayer`.
-    pub fn new(auth: Vec<RpcAuthSection>) -> Result<Self, ()> {
+    /// Creates a new `AuthorizationLayer` for the given users, and the password from the
+    /// cookie file if cookie authorization is enabled.
+    pub fn new(auth: Vec<RpcAuthSection>, cookie: Option<&SecretString>) -> Result<Self, ()> {
         let mut using_bare_password = false;
         let mut using_pwhash = false;
 
-        let users = auth
+        let mut users: HashMap<_, _> = auth
             .into_iter()
             .map(|a| match (a.password, a.pwhash) {
                 (Some(password), None) => {
@@ -171,6 +181,16 @@ impl AuthorizationLayer {
             })
             .collect::<Result<_, _>>()?;
 
+        if let Some(cookie) = cookie {
+            if users.contains_key(COOKIE_USER) {
+                return Err(());
+            }
+            users.insert(
+                COOKIE_USER.into(),
+                PasswordHash::from_bare(cookie.expose_secret()),
+            );
+        }
+
         if using_bare_password {
             info!("{}", fl!("rpc-bare-password-auth-info"));
             warn!("\n{}", fl!("rpc-bare-password-auth-warn"));
@@ -179,7 +199,12 @@ impl AuthorizationLayer {
             info!("{}", fl!("rpc-pwhash-auth-info"));
         }
 
-        Ok(Self { users })
+        Ok(Self { users: Some(users) })
+    }
+
+    /// Creates an `AuthorizationLayer` that does not authorize requests.
+    pub fn disabled() -> Self {
+        Self { users: None }
     }
 }
 
@@ -209,11 +234,17 @@ where
         self.service.poll_ready(cx).map_err(Into::into)
     }
 
-    fn call(&mut self, request: HttpRequest<HttpBody>) -> Self::Future {
+    fn call(&mut self, mut request: HttpRequest<HttpBody>) -> Self::Future {
+        let Some(users) = &self.users else {
+            let mut service = self.service.clone();
+            return async move { service.call(request).await.map_err(Into::into) }.boxed();
+        };
+
         match request.headers().get(header::AUTHORIZATION) {
             None => unauthorized().boxed(),
             Some(auth_header) => {
-                if self.is_authorized(auth_header) {
+                if let Some(user) = Self::authorized_user(users, auth_header) {
+                    request.extensions_mut().insert(AuthenticatedUser(user));
                     let mut service = self.service.clone();
                     async move { service.call(request).await.map_err(Into::into) }.boxed()
                 } else {
@@ -232,8 +263,17 @@ where
 
 #[cfg(test)]
 mod tests {
-
-    use super::PasswordHash;
+    use base64ct::{Base64, Encoding};
+    use hyper::{StatusCode, header};
+    use jsonrpsee::{
+        core::BoxError,
+        server::{HttpBody, HttpRequest, HttpResponse},
+    };
+    use secrecy::SecretString;
+    use tower::{Layer, Service};
+
+    use super::{AuthorizationLayer, PasswordHash};
+    use crate::{components::json_rpc::server::cookie::COOKIE_USER, config::RpcAuthSection};
 
     #[test]
     fn pwhash_round_trip() {
@@ -245,4 +285,67 @@ mod tests {
         let parsed_pwhash = pwhash_str.parse::<PasswordHash>().unwrap();
         assert!(parsed_pwhash.check(password));
     }
+
+    #[test]
+    fn cookie_authorization() {
+        let cookie = SecretString::new("cookiepassword".into());
+        let user = |user: &str| RpcAuthSection {
+            user: user.into(),
+            password: Some(SecretString::new("userpassword".into())),
+            pwhash: None,
+        };
+
+        // A configured user cannot take the name of the cookie user.
+        assert!(AuthorizationLayer::new(vec![user(COOKIE_USER)], Some(&cookie)).is_err());
+
+        let status = |layer: &AuthorizationLayer, credentials: Option<&str>| {
+            let mut service = layer.layer(tower::service_fn(|_: HttpRequest| async {
+                Ok::<_, BoxError>(HttpResponse::new(HttpBody::empty()))
+            }));
+            let mut request = HttpRequest::builder();
+            if let Some(credentials) = credentials {
+                request = request.header(
+                    header::AUTHORIZATION,
+                    format!("Basic {}", Base64::encode_string(credentials.as_bytes())),
+                );
+            }
+            let request = request.body(HttpBody::empty()).unwrap();
+
+            tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .unwrap()
+                .block_on(service.call(request))
+                .unwrap()
+                .status()
+        };
+
+        let layer = AuthorizationLayer::new(vec![user("alice")], Some(&cookie)).unwrap();
+        assert_eq!(
+            status(&layer, Some("__cookie__:cookiepassword")),
+            StatusCode::OK
+        );
+        assert_eq!(status(&layer, Some("alice:userpassword")), StatusCode::OK);
+        assert_eq!(
+            status(&layer, Some("__cookie__:wrongpassword")),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(&layer, Some("alice:cookiepassword")),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status(&layer, None), StatusCode::UNAUTHORIZED);
+
+        // Without a cookie, only the configured users are authorized.
+        let layer = AuthorizationLayer::new(vec![user("alice")], None).unwrap();
+        assert_eq!(
+            status(&layer, Some("__cookie__:cookiepassword")),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status(&layer, Some("alice:userpassword")), StatusCode::OK);
+
+        // With authorization disabled, every request is passed through.
+        let layer = AuthorizationLayer::disabled();
+        assert_eq!(status(&layer, None), StatusCode::OK);
+    }
 }
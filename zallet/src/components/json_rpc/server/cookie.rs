@@ -0,0 +1,97 @@
+//! Cookie-file authorization for the JSON-RPC server.
+//!
+//! This follows `zcashd` and Bitcoin Core: each time the JSON-RPC server starts, it
+//! writes a random password to a file that only the user running Zallet can read. Local
+//! clients such as `zallet rpc` authorize themselves with the contents of the file.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use rand::{Rng, rngs::OsRng};
+use secrecy::{ExposeSecret, SecretString};
+
+/// The username that requests authorized with the cookie file authenticate as.
+pub(crate) const COOKIE_USER: &str = "__cookie__";
+
+/// Generates a random password for a cookie file.
+pub(super) fn generate() -> SecretString {
+    let password: [u8; 32] = OsRng.r#gen();
+    SecretString::new(hex::encode(password))
+}
+
+/// Writes a cookie file with the given password at `path`.
+///
+/// Any existing file at `path` (such as one left behind by a Zallet process that did not
+/// shut down cleanly) is replaced.
+pub(super) fn write(path: &Path, password: &SecretString) -> io::Result<()> {
+    // The file is created anew so that it has the intended permissions.
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => (),
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    write!(file, "{COOKIE_USER}:{}", password.expose_secret())?;
+    file.sync_all()
+}
+
+/// Removes the cookie file at `path`.
+pub(super) fn remove(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Reads the `user:password` credentials from the cookie file at `path`.
+#[cfg(feature = "rpc-cli")]
+pub(crate) fn read(path: &Path) -> io::Result<SecretString> {
+    let contents = SecretString::new(fs::read_to_string(path)?);
+    Ok(SecretString::new(contents.expose_secret().trim().into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::ExposeSecret;
+
+    use super::{COOKIE_USER, generate, remove, write};
+
+    #[test]
+    fn cookie_file_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".cookie");
+
+        let password = generate();
+        write(&path, &password).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{COOKIE_USER}:{}", password.expose_secret()),
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // A restart replaces the cookie with a new password.
+        let new_password = generate();
+        write(&path, &new_password).unwrap();
+        assert_ne!(new_password.expose_secret(), password.expose_secret());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{COOKIE_USER}:{}", new_password.expose_secret()),
+        );
+
+        remove(&path).unwrap();
+        assert!(!path.exists());
+        // Removing a missing cookie file is not an error.
+        remove(&path).unwrap();
+    }
+}
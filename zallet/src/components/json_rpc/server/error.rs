@@ -1,6 +1,15 @@
 //! RPC error codes & their handling.
 
+use std::fmt;
+
 use jsonrpsee::types::{ErrorCode, ErrorObjectOwned};
+use serde::Serialize;
+
+#[cfg(zallet_build = "wallet")]
+use crate::{
+    components::wallets::WalletsError,
+    error::{Error, ErrorKind},
+};
 
 /// Bitcoin RPC error codes
 ///
@@ -73,8 +82,28 @@ pub enum LegacyCode {
     WalletAlreadyUnlocked = -17,
     /// User must acknowledge backup of the mnemonic seed.
     WalletBackupRequired = -18,
+    /// There are multiple wallets loaded, and the request did not specify which to use
+    WalletNotSpecified = -19,
 }
 
+/// The error code returned when a request selects or names a wallet that is not loaded.
+///
+/// This is `RPC_WALLET_NOT_FOUND` in Bitcoin Core. It is not a [`LegacyCode`] because
+/// `zcashd` reused the code for [`LegacyCode::WalletBackupRequired`].
+pub(crate) const WALLET_NOT_FOUND: i32 = -18;
+
+/// The error code returned when loading a wallet that is already loaded.
+///
+/// This is `RPC_WALLET_ALREADY_LOADED` in Bitcoin Core, which `zcashd` did not have.
+#[cfg(zallet_build = "wallet")]
+const WALLET_ALREADY_LOADED: i32 = -35;
+
+/// The error code returned when a request exceeds the `rpc.rate_limit` budgets.
+///
+/// Neither `zcashd` nor Bitcoin Core limited the rate of requests, so this is taken from
+/// the range that JSON-RPC reserves for implementation-defined server errors.
+pub(crate) const RATE_LIMITED: i32 = -32029;
+
 impl LegacyCode {
     /// Adds a message to this error.
     pub fn with_message(self, message: impl Into<String>) -> ErrorObjectOwned {
@@ -85,6 +114,16 @@ impl LegacyCode {
     pub fn with_static(self, message: &'static str) -> ErrorObjectOwned {
         ErrorObjectOwned::borrowed(self.into(), message, None)
     }
+
+    /// Adds a message to this error, along with structured data describing it.
+    ///
+    /// The data is returned in the `data` field of the JSON-RPC error object, so that
+    /// clients can act on an error without parsing its message. The code and message
+    /// are unchanged by the presence of data, and `zcashd` never set this field, so
+    /// clients that ignore it are unaffected.
+    pub fn with_data(self, message: impl Into<String>, data: impl Serialize) -> ErrorObjectOwned {
+        ErrorObjectOwned::owned(self.into(), message, Some(data))
+    }
 }
 
 impl From<LegacyCode> for ErrorCode {
@@ -98,3 +137,160 @@ impl From<LegacyCode> for i32 {
         code as i32
     }
 }
+
+/// A failure of one of Zallet's components while handling an RPC call.
+///
+/// Methods report failures of the wallet database, the keystore, and the chain indexer
+/// through this type, so that each category of failure is reported with the same legacy
+/// error code by every method. Clients can then rely on the code to decide how to handle
+/// an error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ComponentFailure {
+    /// The wallet database could not be accessed, or a query on it failed.
+    Database(String),
+    /// Key material cannot be decrypted because the wallet is locked.
+    #[cfg(zallet_build = "wallet")]
+    WalletLocked,
+    /// The keystore could not provide the requested key material.
+    #[cfg(zallet_build = "wallet")]
+    Keystore(String),
+    /// The chain indexer is not running yet.
+    ChainUnavailable,
+    /// A request to the chain indexer or the backing full node failed.
+    Chain(String),
+    /// An internal invariant was violated.
+    Internal(String),
+}
+
+impl ComponentFailure {
+    /// Classifies an error returned by the wallet database.
+    pub(crate) fn database(e: impl fmt::Display) -> Self {
+        ComponentFailure::Database(e.to_string())
+    }
+
+    /// Classifies an error returned by the keystore.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn keystore(e: Error) -> Self {
+        match e.kind() {
+            ErrorKind::WalletLocked => ComponentFailure::WalletLocked,
+            _ => ComponentFailure::Keystore(e.message()),
+        }
+    }
+
+    /// Classifies an error returned by the chain indexer.
+    pub(crate) fn chain(e: impl fmt::Display) -> Self {
+        ComponentFailure::Chain(e.to_string())
+    }
+
+    /// Classifies a failure that indicates a bug or a corrupted wallet.
+    pub(crate) fn internal(e: impl fmt::Display) -> Self {
+        ComponentFailure::Internal(e.to_string())
+    }
+}
+
+impl From<ComponentFailure> for ErrorObjectOwned {
+    fn from(e: ComponentFailure) -> Self {
+        match e {
+            ComponentFailure::Database(e) => LegacyCode::Database.with_message(e),
+            #[cfg(zallet_build = "wallet")]
+            ComponentFailure::WalletLocked => LegacyCode::WalletUnlockNeeded.with_static(
+                "Error: Please enter the wallet passphrase with walletpassphrase first.",
+            ),
+            #[cfg(zallet_build = "wallet")]
+            ComponentFailure::Keystore(e) => {
+                LegacyCode::Wallet.with_message(format!("Failed to access key material: {e}"))
+            }
+            ComponentFailure::ChainUnavailable => {
+                LegacyCode::InWarmup.with_static("Waiting for the chain indexer to start")
+            }
+            ComponentFailure::Chain(e) => {
+                LegacyCode::Misc.with_message(format!("Chain indexer request failed: {e}"))
+            }
+            ComponentFailure::Internal(e) => LegacyCode::Misc.with_message(e),
+        }
+    }
+}
+
+#[cfg(zallet_build = "wallet")]
+impl From<WalletsError> for ErrorObjectOwned {
+    fn from(e: WalletsError) -> Self {
+        match e {
+            WalletsError::InvalidName(_) => {
+                LegacyCode::InvalidParameter.with_message(e.to_string())
+            }
+            WalletsError::NotFound(_) => {
+                ErrorObjectOwned::owned(WALLET_NOT_FOUND, e.to_string(), None::<()>)
+            }
+            WalletsError::AlreadyLoaded(_) => {
+                ErrorObjectOwned::owned(WALLET_ALREADY_LOADED, e.to_string(), None::<()>)
+            }
+            WalletsError::AlreadyExists(_) | WalletsError::EncryptionRequired => {
+                LegacyCode::Wallet.with_message(e.to_string())
+            }
+            WalletsError::Failed(e) => LegacyCode::Wallet.with_message(e.message()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpsee::types::ErrorObjectOwned;
+
+    use super::ComponentFailure;
+
+    #[test]
+    fn error_objects() {
+        let cases = [
+            (
+                ComponentFailure::database("database is locked"),
+                -20,
+                "database is locked",
+            ),
+            (
+                ComponentFailure::ChainUnavailable,
+                -28,
+                "Waiting for the chain indexer to start",
+            ),
+            (
+                ComponentFailure::chain("connection refused"),
+                -1,
+                "Chain indexer request failed: connection refused",
+            ),
+            (
+                ComponentFailure::internal("Account was deleted during the request"),
+                -1,
+                "Account was deleted during the request",
+            ),
+        ];
+
+        for (failure, code, message) in cases {
+            let e = ErrorObjectOwned::from(failure.clone());
+            assert_eq!(e.code(), code, "{failure:?}");
+            assert_eq!(e.message(), message, "{failure:?}");
+        }
+    }
+
+    #[cfg(zallet_build = "wallet")]
+    #[test]
+    fn keystore_errors() {
+        use crate::error::ErrorKind;
+
+        let locked = ComponentFailure::keystore(ErrorKind::WalletLocked.into());
+        assert_eq!(locked, ComponentFailure::WalletLocked);
+        assert_eq!(ErrorObjectOwned::from(locked).code(), -13);
+
+        // The guidance on reporting bugs is not included in the RPC error.
+        let failed =
+            ComponentFailure::keystore(ErrorKind::Generic.context("Invalid mnemonic").into());
+        assert_eq!(
+            failed,
+            ComponentFailure::Keystore("Invalid mnemonic".into())
+        );
+        let e = ErrorObjectOwned::from(failed);
+        assert_eq!(e.code(), -4);
+        assert_eq!(
+            e.message(),
+            "Failed to access key material: Invalid mnemonic"
+        );
+    }
+}
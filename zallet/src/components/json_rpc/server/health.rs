@@ -0,0 +1,303 @@
+//! HTTP endpoints that report whether Zallet is alive and ready to serve requests.
+//!
+//! These are intended for process supervisors, so they do not require authorization, and
+//! are answered from cached state without waiting on the wallet database or the chain
+//! indexer. Each endpoint responds to a `GET` request with `200 OK` or
+//! `503 Service Unavailable`, and a JSON body listing any failing conditions.
+//!
+//! - `/health`: the process is running, and the databases of the loaded wallets are
+//!   open.
+//! - `/ready`: additionally, the RPC server is accepting requests, the chain indexer is
+//!   reachable, and every wallet has scanned to within `rpc.ready_max_scan_lag` blocks
+//!   of the chain tip.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures::FutureExt;
+use hyper::{Method, StatusCode, header};
+use jsonrpsee::{
+    core::BoxError,
+    server::{HttpBody, HttpRequest, HttpResponse},
+};
+use serde_json::json;
+use tower::Service;
+
+use crate::{
+    components::{sync::SyncProgress, wallets::Wallets},
+    shutdown,
+};
+
+/// An endpoint served by [`HealthMiddleware`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Endpoint {
+    Health,
+    Ready,
+}
+
+impl Endpoint {
+    fn from_path(path: &str) -> Option<Self> {
+        match path {
+            "/health" => Some(Endpoint::Health),
+            "/ready" => Some(Endpoint::Ready),
+            _ => None,
+        }
+    }
+}
+
+/// The state of Zallet's components, as observed for a single health check.
+#[derive(Clone, Debug)]
+struct Observed {
+    shutting_down: bool,
+    chain_reachable: bool,
+    wallets: Vec<ObservedWallet>,
+}
+
+/// The state of a single wallet, as observed for a health check.
+#[derive(Clone, Debug)]
+struct ObservedWallet {
+    name: Option<String>,
+    database_open: bool,
+    sync: Option<SyncProgress>,
+}
+
+impl Observed {
+    /// Returns the conditions that cause `endpoint` to fail.
+    fn failures(&self, endpoint: Endpoint, max_scan_lag: u32) -> Vec<String> {
+        let mut failures = vec![];
+
+        for wallet in &self.wallets {
+            if !wallet.database_open {
+                failures.push(wallet.describe("Wallet database is closed".into()));
+            }
+        }
+        if endpoint == Endpoint::Health {
+            return failures;
+        }
+
+        if self.shutting_down {
+            failures.push("Zallet is shutting down".into());
+        }
+        if !self.chain_reachable {
+            failures.push("Chain indexer is not reachable".into());
+        }
+        for wallet in &self.wallets {
+            let failure = match wallet.sync.map(|progress| (progress, progress.scan_lag())) {
+                None => "Wallet sync has not started".into(),
+                Some((_, None)) => "Wallet has not scanned any blocks".into(),
+                Some((progress, Some(lag))) if lag > max_scan_lag => format!(
+                    "Wallet is {lag} blocks behind the chain tip at height {} (at most {max_scan_lag} allowed)",
+                    progress.chain_tip,
+                ),
+                Some(_) => continue,
+            };
+            failures.push(wallet.describe(failure));
+        }
+
+        failures
+    }
+}
+
+impl ObservedWallet {
+    /// Attributes a failing condition to this wallet, if it is named.
+    fn describe(&self, failure: String) -> String {
+        match &self.name {
+            Some(name) => format!("{failure} (wallet \"{name}\")"),
+            None => failure,
+        }
+    }
+}
+
+/// The cached state that health checks are answered from.
+#[derive(Clone)]
+pub(crate) struct HealthState {
+    wallets: Wallets,
+    max_scan_lag: u32,
+}
+
+impl HealthState {
+    pub(crate) fn new(wallets: Wallets, max_scan_lag: u32) -> Self {
+        Self {
+            wallets,
+            max_scan_lag,
+        }
+    }
+
+    fn observe(&self) -> Observed {
+        Observed {
+            shutting_down: shutdown::is_requested(),
+            chain_reachable: self.wallets.chain().is_reachable(),
+            wallets: self
+                .wallets
+                .loaded()
+                .iter()
+                .map(|wallet| ObservedWallet {
+                    name: wallet.name.clone(),
+                    database_open: wallet.db.is_open(),
+                    sync: wallet.sync_status.progress(),
+                })
+                .collect(),
+        }
+    }
+
+    fn respond(&self, endpoint: Endpoint) -> Result<HttpResponse, BoxError> {
+        let failures = self.observe().failures(endpoint, self.max_scan_lag);
+        let (status, body) = if failures.is_empty() {
+            (StatusCode::OK, json!({ "status": "ok" }))
+        } else {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                json!({ "status": "unavailable", "failures": failures }),
+            )
+        };
+
+        HttpResponse::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CACHE_CONTROL, "no-store")
+            .body(HttpBody::from(body.to_string()))
+            .map_err(BoxError::from)
+    }
+}
+
+/// HTTP middleware that serves the health endpoints, and passes every other request on
+/// to the JSON-RPC server.
+#[derive(Clone)]
+pub struct HealthMiddleware<S> {
+    service: S,
+    state: Arc<HealthState>,
+}
+
+/// Implements [`tower::Layer`] for [`HealthMiddleware`].
+#[derive(Clone)]
+pub struct HealthLayer {
+    state: Arc<HealthState>,
+}
+
+impl HealthLayer {
+    /// Creates a new `HealthLayer`.
+    pub(crate) fn new(state: HealthState) -> Self {
+        Self {
+            state: Arc::new(state),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for HealthLayer {
+    type Service = HealthMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        HealthMiddleware {
+            service,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<S> Service<HttpRequest<HttpBody>> for HealthMiddleware<S>
+where
+    S: Service<HttpRequest, Response = HttpResponse> + Clone + Send + 'static,
+    S::Error: Into<BoxError> + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = HttpResponse;
+    type Error = BoxError;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: HttpRequest<HttpBody>) -> Self::Future {
+        // JSON-RPC requests are always `POST`s, so this never intercepts them.
+        match Endpoint::from_path(request.uri().path()) {
+            Some(endpoint) if request.method() == Method::GET => {
+                futures::future::ready(self.state.respond(endpoint)).boxed()
+            }
+            _ => {
+                let mut service = self.service.clone();
+                async move { service.call(request).await.map_err(Into::into) }.boxed()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::consensus::BlockHeight;
+
+    use super::{Endpoint, Observed, ObservedWallet};
+    use crate::components::sync::SyncProgress;
+
+    fn wallet(
+        name: Option<&str>,
+        database_open: bool,
+        sync: Option<SyncProgress>,
+    ) -> ObservedWallet {
+        ObservedWallet {
+            name: name.map(String::from),
+            database_open,
+            sync,
+        }
+    }
+
+    #[test]
+    fn failing_conditions() {
+        let progress = SyncProgress {
+            chain_tip: BlockHeight::from_u32(1000),
+            fully_scanned: Some(BlockHeight::from_u32(995)),
+        };
+        let synced = Observed {
+            shutting_down: false,
+            chain_reachable: true,
+            wallets: vec![wallet(None, true, Some(progress))],
+        };
+        assert!(synced.failures(Endpoint::Health, 10).is_empty());
+        assert!(synced.failures(Endpoint::Ready, 10).is_empty());
+        assert_eq!(synced.failures(Endpoint::Ready, 4).len(), 1);
+
+        // Only a closed database makes Zallet unhealthy.
+        let stalled = Observed {
+            shutting_down: true,
+            chain_reachable: false,
+            wallets: vec![wallet(None, true, None)],
+        };
+        assert!(stalled.failures(Endpoint::Health, 10).is_empty());
+        assert_eq!(
+            stalled.failures(Endpoint::Ready, 10),
+            vec![
+                "Zallet is shutting down",
+                "Chain indexer is not reachable",
+                "Wallet sync has not started",
+            ],
+        );
+
+        let closed = Observed {
+            wallets: vec![wallet(None, false, Some(progress))],
+            ..synced.clone()
+        };
+        assert_eq!(closed.failures(Endpoint::Health, 10).len(), 1);
+        assert_eq!(closed.failures(Endpoint::Ready, 10).len(), 1);
+
+        // Each wallet's conditions are reported separately.
+        let several = Observed {
+            wallets: vec![
+                wallet(Some("ops"), true, Some(progress)),
+                wallet(Some("cold"), false, None),
+            ],
+            ..synced
+        };
+        assert_eq!(
+            several.failures(Endpoint::Ready, 10),
+            vec![
+                "Wallet database is closed (wallet \"cold\")",
+                "Wallet sync has not started (wallet \"cold\")",
+            ],
+        );
+    }
+}
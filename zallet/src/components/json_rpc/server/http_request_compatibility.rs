@@ -10,7 +10,7 @@ use http_body_util::BodyExt;
 use hyper::{StatusCode, header};
 use jsonrpsee::{
     core::BoxError,
-    server::{HttpBody, HttpRequest, HttpResponse},
+    server::{HttpBody, HttpRequest, HttpResponse, ws},
     types::{ErrorCode, ErrorObject},
 };
 use serde::{Deserialize, Serialize};
@@ -33,6 +33,8 @@ use tower::Service;
 /// If there is no `content-type` header, we assume the content is JSON, and let the
 /// parser error if we are incorrect.
 ///
+/// WebSocket upgrade requests are passed through unchanged.
+///
 /// ## Security
 ///
 /// Any user-specified data in RPC requests is hex or base58check encoded. We assume the
@@ -211,11 +213,15 @@ where
     }
 
     fn call(&mut self, mut request: HttpRequest<HttpBody>) -> Self::Future {
+        let mut service = self.service.clone();
+
+        if ws::is_upgrade_request(&request) {
+            return async move { service.call(request).await.map_err(Into::into) }.boxed();
+        }
+
         // Fix the request headers.
         Self::insert_or_replace_content_type_header(request.headers_mut());
 
-        let mut service = self.service.clone();
-
         async move {
             let (version, request) = Self::request_to_json_rpc_2(request).await;
             let response = service.call(request).await.map_err(Into::into)?;
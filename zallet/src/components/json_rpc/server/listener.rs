@@ -0,0 +1,301 @@
+//! The sockets that the JSON-RPC server accepts connections on.
+
+use std::io;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+use tracing::warn;
+
+use super::request_log::CallerAddress;
+use crate::config::RpcBind;
+
+/// A connection accepted by a [`Listener`].
+pub(super) trait Connection: AsyncRead + AsyncWrite + Send + Unpin + 'static {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static> Connection for T {}
+
+/// A socket that the JSON-RPC server accepts connections on.
+pub(super) enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(unix::UnixSocket),
+}
+
+impl Listener {
+    /// Listens on `addr`.
+    ///
+    /// Unix domain sockets are created with the permissions in `unix_socket_mode`.
+    pub(super) async fn bind(addr: &RpcBind, unix_socket_mode: u32) -> io::Result<Self> {
+        match addr {
+            RpcBind::Tcp(addr) => TcpListener::bind(addr).await.map(Self::Tcp),
+            #[cfg(unix)]
+            RpcBind::Unix(path) => unix::UnixSocket::bind(path, unix_socket_mode).map(Self::Unix),
+            #[cfg(not(unix))]
+            RpcBind::Unix(_) => {
+                let _ = unix_socket_mode;
+                Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Unix domain sockets are not supported on this platform",
+                ))
+            }
+        }
+    }
+
+    /// Returns the address that this listener is bound to.
+    pub(super) fn local_addr(&self) -> io::Result<RpcBind> {
+        match self {
+            Self::Tcp(listener) => listener.local_addr().map(RpcBind::Tcp),
+            #[cfg(unix)]
+            Self::Unix(socket) => Ok(RpcBind::Unix(socket.path.clone())),
+        }
+    }
+
+    /// Accepts a new connection.
+    pub(super) async fn accept(&self) -> io::Result<(Box<dyn Connection>, CallerAddress)> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                Ok((Box::new(stream), CallerAddress::Tcp(addr)))
+            }
+            #[cfg(unix)]
+            Self::Unix(socket) => {
+                let (stream, _) = socket.listener.accept().await?;
+                Ok((Box::new(stream), CallerAddress::Unix))
+            }
+        }
+    }
+}
+
+/// The limit on the number of open connections set by `rpc.max_connections`.
+pub(super) struct ConnectionLimit {
+    max: NonZeroU32,
+    permits: Arc<Semaphore>,
+}
+
+impl ConnectionLimit {
+    pub(super) fn new(max: NonZeroU32) -> Self {
+        Self {
+            max,
+            permits: Arc::new(Semaphore::new(max.get() as usize)),
+        }
+    }
+
+    /// Returns the permit that the connection from `caller` holds until it is closed, or
+    /// `None` if the limit has been reached, in which case the connection is refused.
+    pub(super) fn acquire(&self, caller: &CallerAddress) -> Option<OwnedSemaphorePermit> {
+        match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                metrics::counter!("zallet.rpc.connections.rejected").increment(1);
+                warn!(
+                    "Refused RPC connection from {caller}: rpc.max_connections ({}) are already open",
+                    self.max,
+                );
+                None
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::ffi::OsString;
+    use std::fs;
+    use std::io;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::path::{Path, PathBuf};
+
+    use tokio::net::UnixListener;
+    use tracing::{info, warn};
+
+    /// A Unix domain socket, which is removed when it is dropped.
+    pub(in super::super) struct UnixSocket {
+        pub(super) listener: UnixListener,
+        pub(super) path: PathBuf,
+    }
+
+    impl UnixSocket {
+        pub(super) fn bind(path: &Path, mode: u32) -> io::Result<Self> {
+            remove_stale_socket(path)?;
+
+            // The socket is created at a temporary path, and only moved into place once
+            // it has the intended permissions, so that clients cannot connect to it
+            // before then.
+            let mut tmp_path = OsString::from(path);
+            tmp_path.push(".tmp");
+            let tmp_path = PathBuf::from(tmp_path);
+            remove_socket(&tmp_path)?;
+
+            let listener = UnixListener::bind(&tmp_path)?;
+            fs::set_permissions(&tmp_path, fs::Permissions::from_mode(mode))
+                .and_then(|()| fs::rename(&tmp_path, path))
+                .inspect_err(|_| {
+                    let _ = fs::remove_file(&tmp_path);
+                })?;
+
+            Ok(Self {
+                listener,
+                path: path.to_owned(),
+            })
+        }
+    }
+
+    impl Drop for UnixSocket {
+        fn drop(&mut self) {
+            if let Err(e) = remove_socket(&self.path) {
+                warn!("Failed to remove RPC socket {}: {e}", self.path.display());
+            }
+        }
+    }
+
+    /// Removes the socket at `path` if it was left behind by a Zallet process that did
+    /// not shut down cleanly.
+    ///
+    /// Fails if another process is listening on the socket.
+    fn remove_stale_socket(path: &Path) -> io::Result<()> {
+        match fs::symlink_metadata(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+            Ok(metadata) if !metadata.file_type().is_socket() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ));
+            }
+            Ok(_) => (),
+        }
+
+        match std::os::unix::net::UnixStream::connect(path) {
+            Ok(_) => Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{} is in use by another process", path.display()),
+            )),
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                info!("Replacing stale RPC socket {}", path.display());
+                remove_socket(path)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Removes the socket at `path`, if it exists.
+    fn remove_socket(path: &Path) -> io::Result<()> {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        use super::UnixSocket;
+
+        #[test]
+        fn unix_socket_lifecycle() {
+            // Binding a Tokio listener requires a runtime.
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .build()
+                .unwrap();
+            let _guard = runtime.enter();
+
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("rpc.sock");
+
+            let socket = UnixSocket::bind(&path, 0o600).unwrap();
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+
+            // A socket that is in use is not replaced.
+            assert!(UnixSocket::bind(&path, 0o600).is_err());
+
+            // The socket is removed when the listener is dropped.
+            drop(socket);
+            assert!(!path.exists());
+
+            // A stale socket left behind by a crashed process is replaced.
+            let stale = std::os::unix::net::UnixListener::bind(&path).unwrap();
+            drop(stale);
+            assert!(path.exists());
+            let socket = UnixSocket::bind(&path, 0o660).unwrap();
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o660);
+            drop(socket);
+
+            // Other files are never removed.
+            fs::write(&path, "not a socket").unwrap();
+            assert!(UnixSocket::bind(&path, 0o600).is_err());
+            assert!(path.exists());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::num::NonZeroU32;
+
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use tokio::net::TcpStream;
+
+    use super::{ConnectionLimit, Listener};
+    use crate::config::RpcBind;
+
+    #[test]
+    fn connections_are_limited() {
+        // The metrics are recorded on this thread, so the listener runs on it too.
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let exporter = recorder.handle();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let listener =
+                    Listener::bind(&RpcBind::Tcp(SocketAddr::from(([127, 0, 0, 1], 0))), 0)
+                        .await
+                        .unwrap();
+                let RpcBind::Tcp(addr) = listener.local_addr().unwrap() else {
+                    unreachable!()
+                };
+                let limit = ConnectionLimit::new(NonZeroU32::new(2).unwrap());
+                let rejected = || {
+                    exporter
+                        .render()
+                        .lines()
+                        .find_map(|line| line.strip_prefix("zallet_rpc_connections_rejected "))
+                        .map_or(0, |count| count.parse::<u64>().unwrap())
+                };
+
+                let connect = async || {
+                    let client = TcpStream::connect(addr).await.unwrap();
+                    let (_, caller) = listener.accept().await.unwrap();
+                    (client, limit.acquire(&caller))
+                };
+
+                // Connections beyond the limit are refused, until one of the open
+                // connections is closed.
+                let (_first, first_permit) = connect().await;
+                let (_second, second_permit) = connect().await;
+                assert!(first_permit.is_some() && second_permit.is_some());
+                let (_third, third_permit) = connect().await;
+                assert!(third_permit.is_none());
+                assert_eq!(rejected(), 1);
+
+                drop(first_permit);
+                let (_fourth, fourth_permit) = connect().await;
+                assert!(fourth_permit.is_some());
+                assert_eq!(rejected(), 1);
+            })
+        });
+    }
+}
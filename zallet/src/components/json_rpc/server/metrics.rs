@@ -0,0 +1,96 @@
+//! Metrics about JSON-RPC calls.
+
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use jsonrpsee::{
+    MethodResponse, server::middleware::rpc::RpcServiceT, types::error::METHOD_NOT_FOUND_CODE,
+};
+
+/// The label given to calls of methods that do not exist.
+///
+/// Clients can call any method name, so these are grouped to bound the number of
+/// time series.
+const UNKNOWN_METHOD: &str = "unknown";
+
+/// Returns the labels that a call to `method` is recorded with, given its response.
+fn labels(method: &str, response: &MethodResponse) -> (String, &'static str) {
+    match response.as_error_code() {
+        Some(METHOD_NOT_FOUND_CODE) => (UNKNOWN_METHOD.into(), "error"),
+        Some(_) => (method.into(), "error"),
+        None => (method.into(), "success"),
+    }
+}
+
+/// JSON-RPC middleware that records the number and duration of calls to each method.
+pub struct MetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S> MetricsMiddleware<S> {
+    /// Create a new `MetricsMiddleware` with the given `service`.
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for MetricsMiddleware<S>
+where
+    S: RpcServiceT<'a>,
+    S::Future: 'a,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: jsonrpsee::types::Request<'a>) -> Self::Future {
+        let method = request.method_name().to_string();
+        let start = Instant::now();
+        let response = self.service.call(request);
+
+        Box::pin(async move {
+            let response = response.await;
+
+            let (method, outcome) = labels(&method, &response);
+            metrics::histogram!("zallet.rpc.request_duration_seconds", "method" => method.clone())
+                .record(start.elapsed().as_secs_f64());
+            metrics::counter!("zallet.rpc.requests", "method" => method, "outcome" => outcome)
+                .increment(1);
+
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpsee::{
+        MethodResponse,
+        types::{ErrorObject, Id, ResponsePayload, error::METHOD_NOT_FOUND_CODE},
+    };
+
+    use super::labels;
+
+    #[test]
+    fn unknown_methods_share_a_label() {
+        let success = MethodResponse::response(
+            Id::Number(1),
+            ResponsePayload::success(serde_json::json!(1)),
+            usize::MAX,
+        );
+        assert_eq!(labels("getinfo", &success), ("getinfo".into(), "success"));
+
+        let error = MethodResponse::error(
+            Id::Number(1),
+            ErrorObject::owned(-8, "Invalid parameter", None::<()>),
+        );
+        assert_eq!(labels("getinfo", &error), ("getinfo".into(), "error"));
+
+        let not_found = MethodResponse::error(
+            Id::Number(1),
+            ErrorObject::owned(METHOD_NOT_FOUND_CODE, "Method not found", None::<()>),
+        );
+        assert_eq!(
+            labels("made_up_method", &not_found),
+            ("unknown".into(), "error")
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! Rate limiting of JSON-RPC requests.
+//!
+//! Calls are charged against a global budget shared by all clients, and a budget for each
+//! client. Each budget is a token bucket that holds up to one second's worth of requests
+//! (or `rpc.rate_limit.client_burst` for the client budgets), so short bursts are
+//! permitted while the sustained rate is bounded.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Either, Ready};
+use jsonrpsee::{
+    MethodResponse, server::middleware::rpc::RpcServiceT, tracing::debug, types::ErrorObjectOwned,
+};
+use serde_json::json;
+
+use super::{authorization::AuthenticatedUser, error::RATE_LIMITED, request_log::CallerAddress};
+use crate::config::{RateLimitClientKey, RpcRateLimitSection};
+
+/// The number of tracked clients above which clients with a full budget are forgotten.
+const MAX_IDLE_CLIENTS: usize = 1024;
+
+/// A client, as identified for rate limiting.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Client {
+    Address(IpAddr),
+    User(String),
+    /// A client that connected from a loopback address or over a Unix domain socket,
+    /// when `rpc.rate_limit.exempt_local` is set.
+    Local,
+    /// A client that cannot be identified. These share a single budget.
+    Unknown,
+}
+
+/// The budget that was exhausted by a rate-limited call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Limit {
+    Global,
+    Client,
+}
+
+impl Limit {
+    fn name(self) -> &'static str {
+        match self {
+            Limit::Global => "global",
+            Limit::Client => "client",
+        }
+    }
+}
+
+/// A budget that is replenished continuously, up to its capacity.
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: NonZeroU32, capacity: NonZeroU32, now: Instant) -> Self {
+        let capacity = f64::from(capacity.get());
+        Self {
+            rate: f64::from(rate.get()),
+            capacity,
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Returns how long to wait until `cost` is available, or `None` if it is available
+    /// now.
+    fn wait_for(&mut self, cost: f64, now: Instant) -> Option<Duration> {
+        self.refill(now);
+        // A cost that exceeds the capacity is charged as a full bucket, so that it can
+        // eventually succeed.
+        let cost = cost.min(self.capacity);
+        (self.tokens < cost).then(|| Duration::from_secs_f64((cost - self.tokens) / self.rate))
+    }
+
+    fn take(&mut self, cost: f64) {
+        self.tokens -= cost.min(self.capacity);
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.capacity
+    }
+}
+
+/// The state of the rate limits applied to JSON-RPC calls.
+pub(super) struct RateLimiter {
+    config: RpcRateLimitSection,
+    global: Mutex<Option<TokenBucket>>,
+    clients: Mutex<HashMap<Client, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub(super) fn new(config: RpcRateLimitSection) -> Self {
+        let global = config
+            .requests_per_second()
+            .map(|rate| TokenBucket::new(rate, rate, Instant::now()));
+        Self {
+            config,
+            global: Mutex::new(global),
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns `true` if any limit is configured.
+    fn is_enabled(&self) -> bool {
+        self.config.requests_per_second().is_some()
+            || self.config.client_requests_per_second().is_some()
+    }
+
+    /// Charges a call to `method` by `client` against the budgets.
+    ///
+    /// [`Client::Local`] is only charged against the global budget.
+    ///
+    /// If a budget is exhausted, nothing is charged, and the time after which the call
+    /// can be retried is returned.
+    fn check(&self, client: Client, method: &str, now: Instant) -> Result<(), (Limit, Duration)> {
+        let cost = f64::from(self.config.method_weight(method));
+
+        let mut global = self.global.lock().expect("not poisoned");
+        let mut clients = self.clients.lock().expect("not poisoned");
+
+        if clients.len() > MAX_IDLE_CLIENTS {
+            // Clients with a full budget have been idle for long enough that forgetting
+            // them has no effect.
+            clients.retain(|_, bucket| {
+                bucket.refill(now);
+                !bucket.is_full()
+            });
+        }
+
+        let mut client_bucket = self
+            .config
+            .client_requests_per_second()
+            .zip(self.config.client_burst())
+            .filter(|_| client != Client::Local)
+            .map(|(rate, burst)| {
+                clients
+                    .entry(client)
+                    .or_insert_with(|| TokenBucket::new(rate, burst, now))
+            });
+
+        let global_wait = global
+            .as_mut()
+            .and_then(|bucket| bucket.wait_for(cost, now));
+        let client_wait = client_bucket
+            .as_mut()
+            .and_then(|bucket| bucket.wait_for(cost, now));
+
+        let res = match (global_wait, client_wait) {
+            (None, None) => {
+                if let Some(bucket) = global.as_mut() {
+                    bucket.take(cost);
+                }
+                if let Some(bucket) = client_bucket {
+                    bucket.take(cost);
+                }
+                Ok(())
+            }
+            (Some(wait), None) => Err((Limit::Global, wait)),
+            (global_wait, Some(wait)) => Err((
+                Limit::Client,
+                global_wait.map_or(wait, |global_wait| global_wait.max(wait)),
+            )),
+        };
+
+        if let Some(bucket) = global.as_ref() {
+            metrics::gauge!("zallet.rpc.rate_limit.global_budget").set(bucket.tokens);
+        }
+        metrics::gauge!("zallet.rpc.rate_limit.tracked_clients").set(clients.len() as f64);
+
+        res
+    }
+}
+
+/// JSON-RPC middleware that enforces the configured rate limits.
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S> RateLimitMiddleware<S> {
+    /// Create a new `RateLimitMiddleware` with the given `service`.
+    pub(super) fn new(service: S, limiter: Arc<RateLimiter>) -> Self {
+        Self { service, limiter }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for RateLimitMiddleware<S>
+where
+    S: RpcServiceT<'a>,
+{
+    type Future = Either<S::Future, Ready<MethodResponse>>;
+
+    fn call(&self, request: jsonrpsee::types::Request<'a>) -> Self::Future {
+        if !self.limiter.is_enabled() {
+            return Either::Left(self.service.call(request));
+        }
+
+        let caller = request.extensions().get::<CallerAddress>();
+        let client =
+            if self.limiter.config.exempt_local() && caller.is_some_and(CallerAddress::is_local) {
+                Client::Local
+            } else {
+                match self.limiter.config.client_key() {
+                    RateLimitClientKey::Address => {
+                        caller.and_then(CallerAddress::ip).map(Client::Address)
+                    }
+                    RateLimitClientKey::User => request
+                        .extensions()
+                        .get::<AuthenticatedUser>()
+                        .map(|user| Client::User(user.0.clone())),
+                }
+                .unwrap_or(Client::Unknown)
+            };
+
+        match self
+            .limiter
+            .check(client.clone(), request.method_name(), Instant::now())
+        {
+            Ok(()) => {
+                metrics::counter!("zallet.rpc.rate_limit.allowed").increment(1);
+                Either::Left(self.service.call(request))
+            }
+            Err((limit, wait)) => {
+                metrics::counter!("zallet.rpc.rate_limit.rejected", "limit" => limit.name())
+                    .increment(1);
+
+                // Round up, so that a client that waits as directed is not limited again.
+                let retry_after = (wait.as_secs() + u64::from(wait.subsec_nanos() > 0)).max(1);
+                debug!(
+                    "Rate limited {} from {client:?} ({} limit); retry after {retry_after}s",
+                    request.method_name(),
+                    limit.name(),
+                );
+
+                Either::Right(future::ready(MethodResponse::error(
+                    request.id(),
+                    ErrorObjectOwned::owned(
+                        RATE_LIMITED,
+                        format!("Rate limit exceeded, retry after {retry_after} seconds"),
+                        Some(json!({ "retry_after": retry_after })),
+                    ),
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::{Duration, Instant};
+
+    use super::{Client, Limit, RateLimiter};
+    use crate::config::RpcRateLimitSection;
+
+    #[test]
+    fn budgets() {
+        let limiter = RateLimiter::new(RpcRateLimitSection {
+            client_requests_per_second: Some(2),
+            method_weights: vec!["rescanblockchain:5".to_string().try_into().unwrap()],
+            requests_per_second: Some(3),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let alice = || Client::Address(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let bob = || Client::Address(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+
+        // Each client can use its own budget, until the global budget runs out.
+        assert_eq!(limiter.check(alice(), "getwalletinfo", now), Ok(()));
+        assert_eq!(limiter.check(alice(), "getwalletinfo", now), Ok(()));
+        assert_eq!(
+            limiter.check(alice(), "getwalletinfo", now),
+            Err((Limit::Client, Duration::from_millis(500))),
+        );
+        assert_eq!(limiter.check(bob(), "getwalletinfo", now), Ok(()));
+        assert_eq!(
+            limiter.check(bob(), "getwalletinfo", now),
+            Err((Limit::Global, Duration::from_secs_f64(1.0 / 3.0))),
+        );
+
+        // Budgets are replenished over time, and heavy methods cost more. A cost above a
+        // budget's capacity is charged as a full budget.
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.check(alice(), "rescanblockchain", later), Ok(()));
+        assert_eq!(
+            limiter.check(bob(), "getwalletinfo", later),
+            Err((Limit::Global, Duration::from_secs_f64(1.0 / 3.0))),
+        );
+    }
+
+    #[test]
+    fn client_burst() {
+        let limiter = RateLimiter::new(RpcRateLimitSection {
+            client_burst: Some(3),
+            client_requests_per_second: Some(1),
+            exempt_local: Some(true),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let alice = || Client::Address(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+
+        // An idle client can burst above its sustained rate.
+        for _ in 0..3 {
+            assert_eq!(limiter.check(alice(), "getwalletinfo", now), Ok(()));
+        }
+        assert_eq!(
+            limiter.check(alice(), "getwalletinfo", now),
+            Err((Limit::Client, Duration::from_secs(1))),
+        );
+
+        // Exempt local clients have no budget of their own.
+        for _ in 0..10 {
+            assert_eq!(limiter.check(Client::Local, "getwalletinfo", now), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Enforcement of `rpc.read_only`.
+
+use futures::future::{self, Either, Ready};
+use jsonrpsee::{MethodResponse, server::middleware::rpc::RpcServiceT};
+
+use super::LegacyCode;
+use crate::components::json_rpc::methods::{self, MethodCategory};
+
+/// Returns whether `method` may be called in read-only mode.
+///
+/// Methods that Zallet does not provide are allowed, so that calls to them fail in the
+/// usual way.
+fn allowed(method: &str) -> bool {
+    methods::category(method) != Some(MethodCategory::Mutating)
+}
+
+/// JSON-RPC middleware that rejects calls to methods that can change the wallet.
+///
+/// This is only installed if `rpc.read_only` is enabled.
+pub struct ReadOnlyMiddleware<S> {
+    service: S,
+}
+
+impl<S> ReadOnlyMiddleware<S> {
+    /// Create a new `ReadOnlyMiddleware` with the given `service`.
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for ReadOnlyMiddleware<S>
+where
+    S: RpcServiceT<'a>,
+{
+    type Future = Either<S::Future, Ready<MethodResponse>>;
+
+    fn call(&self, request: jsonrpsee::types::Request<'a>) -> Self::Future {
+        if !allowed(request.method_name()) {
+            let error = LegacyCode::ForbiddenBySafeMode.with_message(format!(
+                "Zallet is in read-only mode (rpc.read_only), and {} is not allowed",
+                request.method_name(),
+            ));
+            Either::Right(future::ready(MethodResponse::error(request.id(), error)))
+        } else {
+            Either::Left(self.service.call(request))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::allowed;
+
+    #[test]
+    fn read_only_methods() {
+        for method in [
+            "getwalletinfo",
+            "help",
+            "listwallets",
+            "z_combinepczts",
+            "z_gettotalbalance",
+            "z_listtransactions",
+            "z_paymenturi_decode",
+            "z_provepczt",
+            "z_validateaddress",
+            "z_viewtransaction",
+        ] {
+            assert!(allowed(method), "{method} should be allowed");
+        }
+
+        for method in [
+            "backupwallet",
+            "createwallet",
+            "getnewaddress",
+            "loadwallet",
+            "signmessage",
+            "sweepprivkey",
+            "walletlock",
+            "walletpassphrase",
+            "walletpassphrasechange",
+            "z_createpczt",
+            "z_executeproposal",
+            "z_exportkey",
+            "z_exportviewingkey",
+            "z_finalizepczt",
+            "z_getnewaccount",
+            "z_importkey",
+            "z_importviewingkey",
+            "z_importwallet",
+            "z_recoveraccounts",
+            "z_renameaccount",
+            "z_sendmany",
+            "z_sendpaymenturi",
+        ] {
+            assert!(!allowed(method), "{method} should be rejected");
+        }
+    }
+}
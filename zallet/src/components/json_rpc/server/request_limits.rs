@@ -0,0 +1,302 @@
+//! Limits on the size of JSON-RPC requests, configured with `rpc.max_batch_size` and
+//! `rpc.max_request_body_bytes`.
+//!
+//! `jsonrpsee` enforces the same limits, but only after the request has been buffered
+//! by the HTTP middleware that precedes it. Checking them here means that an oversized
+//! request is rejected before anything else reads it.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use futures::FutureExt;
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+use hyper::StatusCode;
+use jsonrpsee::{
+    core::BoxError,
+    server::{HttpBody, HttpRequest, HttpResponse, ws},
+    types::{
+        ErrorObjectOwned,
+        error::{reject_too_big_batch_request, reject_too_big_request},
+    },
+};
+use serde_json::{json, value::RawValue};
+use tower::Service;
+use tracing::warn;
+
+use super::request_log::CallerAddress;
+
+/// Checks that the given request body is not a batch of more than `max_batch_size`
+/// calls.
+///
+/// Bodies that are not valid JSON are left for `jsonrpsee` to reject.
+fn check_batch_size(body: &[u8], max_batch_size: u32) -> Result<(), ErrorObjectOwned> {
+    match serde_json::from_slice::<Vec<&RawValue>>(body) {
+        Ok(batch) if batch.len() > max_batch_size as usize => {
+            Err(reject_too_big_batch_request(max_batch_size as usize))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn rejected(status: StatusCode, error: ErrorObjectOwned) -> Result<HttpResponse, BoxError> {
+    let body = json!({ "jsonrpc": "2.0", "error": error, "id": null });
+    HttpResponse::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(HttpBody::from(body.to_string()))
+        .map_err(BoxError::from)
+}
+
+/// HTTP middleware that rejects requests larger than `rpc.max_request_body_bytes`, and
+/// batches of more than `rpc.max_batch_size` calls.
+#[derive(Clone, Debug)]
+pub struct RequestLimits<S> {
+    service: S,
+    max_batch_size: u32,
+    max_request_body_bytes: u32,
+}
+
+/// Implements [`tower::Layer`] for [`RequestLimits`].
+#[derive(Clone)]
+pub struct RequestLimitsLayer {
+    max_batch_size: u32,
+    max_request_body_bytes: u32,
+}
+
+impl RequestLimitsLayer {
+    /// Creates a new `RequestLimitsLayer` with the given limits.
+    pub fn new(max_batch_size: u32, max_request_body_bytes: u32) -> Self {
+        Self {
+            max_batch_size,
+            max_request_body_bytes,
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for RequestLimitsLayer {
+    type Service = RequestLimits<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        RequestLimits {
+            service,
+            max_batch_size: self.max_batch_size,
+            max_request_body_bytes: self.max_request_body_bytes,
+        }
+    }
+}
+
+impl<S> Service<HttpRequest<HttpBody>> for RequestLimits<S>
+where
+    S: Service<HttpRequest, Response = HttpResponse> + Clone + Send + 'static,
+    S::Error: Into<BoxError> + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = HttpResponse;
+    type Error = BoxError;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: HttpRequest<HttpBody>) -> Self::Future {
+        let mut service = self.service.clone();
+
+        // `jsonrpsee` applies the limits to each WebSocket message itself.
+        if ws::is_upgrade_request(&request) {
+            return async move { service.call(request).await.map_err(Into::into) }.boxed();
+        }
+
+        let max_batch_size = self.max_batch_size;
+        let max_request_body_bytes = self.max_request_body_bytes;
+
+        async move {
+            let caller = request
+                .extensions()
+                .get::<CallerAddress>()
+                .map_or_else(|| "unknown".into(), |caller| caller.to_string());
+
+            let (parts, body) = request.into_parts();
+            let bytes = match Limited::new(body, max_request_body_bytes as usize)
+                .collect()
+                .await
+            {
+                Ok(collected) => collected.to_bytes(),
+                Err(e) if e.is::<LengthLimitError>() => {
+                    metrics::counter!(
+                        "zallet.rpc.requests.rejected",
+                        "limit" => "max_request_body_bytes"
+                    )
+                    .increment(1);
+                    warn!(
+                        "Rejected RPC request from {caller} larger than rpc.max_request_body_bytes ({max_request_body_bytes})"
+                    );
+                    return rejected(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        reject_too_big_request(max_request_body_bytes),
+                    );
+                }
+                Err(e) => return Err(e),
+            };
+
+            if let Err(e) = check_batch_size(&bytes, max_batch_size) {
+                metrics::counter!("zallet.rpc.requests.rejected", "limit" => "max_batch_size")
+                    .increment(1);
+                warn!(
+                    "Rejected RPC batch request from {caller} with more than rpc.max_batch_size ({max_batch_size}) calls"
+                );
+                return rejected(StatusCode::OK, e);
+            }
+
+            service
+                .call(HttpRequest::from_parts(parts, HttpBody::from(bytes.to_vec())))
+                .await
+                .map_err(Into::into)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use http_body_util::BodyExt;
+    use hyper::{StatusCode, header::CONTENT_TYPE};
+    use jsonrpsee::{
+        RpcModule,
+        server::{HttpBody, RpcServiceBuilder, Server, stop_channel},
+        types::error::{TOO_BIG_BATCH_REQUEST_CODE, TOO_BIG_REQUEST_CODE},
+    };
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+
+    use super::{RequestLimitsLayer, check_batch_size};
+    use crate::{
+        components::json_rpc::server::{
+            error::RATE_LIMITED,
+            rate_limit::{RateLimitMiddleware, RateLimiter},
+        },
+        config::RpcRateLimitSection,
+    };
+
+    fn batch(calls: usize) -> Vec<u8> {
+        let calls = (0..calls)
+            .map(|id| format!(r#"{{"jsonrpc":"2.0","method":"getinfo","id":{id}}}"#))
+            .collect::<Vec<_>>();
+        format!("[{}]", calls.join(",")).into_bytes()
+    }
+
+    #[test]
+    fn batch_size() {
+        assert!(check_batch_size(&batch(0), 3).is_ok());
+        assert!(check_batch_size(&batch(3), 3).is_ok());
+        assert_eq!(
+            check_batch_size(&batch(4), 3).unwrap_err().code(),
+            TOO_BIG_BATCH_REQUEST_CODE,
+        );
+
+        // Single calls and invalid JSON are not batches.
+        assert!(check_batch_size(br#"{"method":"getinfo"}"#, 0).is_ok());
+        assert!(check_batch_size(b"[{", 0).is_ok());
+    }
+
+    #[test]
+    fn limits_are_enforced_by_the_server() {
+        // The metrics are recorded on this thread, so the server runs on it too.
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let exporter = recorder.handle();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                // The server accepts bodies of up to 200 bytes, batches of up to 2 calls,
+                // and 3 calls per second.
+                let limiter = Arc::new(RateLimiter::new(RpcRateLimitSection {
+                    requests_per_second: Some(3),
+                    ..Default::default()
+                }));
+                let mut module = RpcModule::new(());
+                module.register_method("getinfo", |_, _, _| "ok").unwrap();
+                let (stop_handle, _server_handle) = stop_channel();
+                let builder = Server::builder()
+                    .set_http_middleware(
+                        tower::ServiceBuilder::new().layer(RequestLimitsLayer::new(2, 200)),
+                    )
+                    .set_rpc_middleware(RpcServiceBuilder::new().layer_fn(move |service| {
+                        RateLimitMiddleware::new(service, limiter.clone())
+                    }))
+                    .to_service_builder();
+                let call = |body: Vec<u8>| {
+                    let service = builder.clone().build(module.clone(), stop_handle.clone());
+                    async move {
+                        let request = hyper::Request::post("/")
+                            .header(CONTENT_TYPE, "application/json")
+                            .body(HttpBody::from(body))
+                            .unwrap();
+                        let response = service.oneshot(request).await.unwrap();
+                        let status = response.status();
+                        let body = response.into_body().collect().await.unwrap().to_bytes();
+                        (status, serde_json::from_slice::<Value>(&body).unwrap())
+                    }
+                };
+                let counter = |name: &str, limit: &str| {
+                    let prefix = format!("{name}{{limit=\"{limit}\"}} ");
+                    exporter
+                        .render()
+                        .lines()
+                        .find_map(|line| line.strip_prefix(&prefix)?.parse::<u64>().ok())
+                        .unwrap_or(0)
+                };
+
+                // Oversized bodies are rejected before they are parsed.
+                let padding = "x".repeat(200);
+                let (status, body) = call(
+                    json!({"jsonrpc": "2.0", "method": "getinfo", "params": [padding], "id": 0})
+                        .to_string()
+                        .into_bytes(),
+                )
+                .await;
+                assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+                assert_eq!(body["error"]["code"], TOO_BIG_REQUEST_CODE);
+                assert_eq!(
+                    counter("zallet_rpc_requests_rejected", "max_request_body_bytes"),
+                    1,
+                );
+
+                // So are batches with too many calls, which are not charged against the
+                // rate limit.
+                let (status, body) = call(batch(3)).await;
+                assert_eq!(status, StatusCode::OK);
+                assert_eq!(body["error"]["code"], TOO_BIG_BATCH_REQUEST_CODE);
+                assert_eq!(counter("zallet_rpc_requests_rejected", "max_batch_size"), 1);
+
+                // Calls within the limits succeed, until the rate limit is reached.
+                let (_, body) = call(batch(2)).await;
+                assert_eq!(
+                    body,
+                    json!([
+                        {"jsonrpc": "2.0", "result": "ok", "id": 0},
+                        {"jsonrpc": "2.0", "result": "ok", "id": 1},
+                    ])
+                );
+                let (_, body) = call(batch(1)).await;
+                assert_eq!(body[0]["result"], "ok");
+                let (status, body) = call(batch(1)).await;
+                assert_eq!(status, StatusCode::OK);
+                assert_eq!(body[0]["error"]["code"], RATE_LIMITED);
+                assert_eq!(body[0]["error"]["data"]["retry_after"], 1);
+                assert_eq!(counter("zallet_rpc_rate_limit_rejected", "global"), 1);
+                assert_eq!(counter("zallet_rpc_requests_rejected", "max_batch_size"), 1);
+            })
+        });
+    }
+}
@@ -0,0 +1,190 @@
+//! Structured logging of JSON-RPC requests.
+//!
+//! Each call is assigned a correlation ID, which is included in every log line about the
+//! call, and is recorded by any async operation that the call launches. This makes it
+//! possible to find the request that created an operation reported by
+//! `z_getoperationstatus`.
+//!
+//! Calls are logged at the `debug` level, so are only output if `logging.filter` enables
+//! it for this module (for example, `"info,zallet::components::json_rpc=debug"`).
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use jsonrpsee::{MethodResponse, server::middleware::rpc::RpcServiceT, tracing::debug};
+use uuid::Uuid;
+
+use crate::config::RequestLogging;
+
+/// Methods whose parameters contain secrets (such as passphrases, private keys, or
+/// seeds), and are never logged.
+///
+/// This includes methods that Zallet does not (yet) implement, so that their parameters
+/// are redacted as soon as they are added. A method must be added here in the same change
+/// that gives it a secret-bearing parameter.
+const REDACTED_METHODS: &[&str] = &[
+    "encryptwallet",
+    "importprivkey",
+    "signrawtransaction",
+    "sweepprivkey",
+    "walletconfirmbackup",
+    "walletpassphrase",
+    "walletpassphrasechange",
+    "z_importkey",
+    "z_importviewingkey",
+];
+
+tokio::task_local! {
+    static REQUEST_ID: RequestId;
+}
+
+/// The correlation ID of a JSON-RPC call.
+#[derive(Clone, Debug)]
+pub(crate) struct RequestId(String);
+
+impl RequestId {
+    fn new() -> Self {
+        Self(format!("req-{}", Uuid::new_v4()))
+    }
+
+    /// Returns the ID of the call that is being handled by the current task, if any.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn current() -> Option<Self> {
+        REQUEST_ID.try_with(|id| id.clone()).ok()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// The address of the client that sent an HTTP request.
+///
+/// This is inserted into the extensions of each request when its connection is accepted.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum CallerAddress {
+    Tcp(SocketAddr),
+    /// A client connected to a Unix domain socket, which has no address.
+    Unix,
+}
+
+impl CallerAddress {
+    /// Returns the IP address of the client, if it connected over TCP.
+    pub(super) fn ip(&self) -> Option<IpAddr> {
+        match self {
+            Self::Tcp(addr) => Some(addr.ip()),
+            Self::Unix => None,
+        }
+    }
+
+    /// Returns `true` if the client connected from a loopback address or over a Unix
+    /// domain socket.
+    pub(super) fn is_local(&self) -> bool {
+        match self {
+            Self::Tcp(addr) => addr.ip().to_canonical().is_loopback(),
+            Self::Unix => true,
+        }
+    }
+}
+
+impl fmt::Display for CallerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix => write!(f, "unix socket"),
+        }
+    }
+}
+
+/// JSON-RPC middleware that logs each call, and assigns it a correlation ID.
+pub struct RequestLogMiddleware<S> {
+    service: S,
+    logging: RequestLogging,
+}
+
+impl<S> RequestLogMiddleware<S> {
+    /// Create a new `RequestLogMiddleware` with the given `service`.
+    pub fn new(service: S, logging: RequestLogging) -> Self {
+        Self { service, logging }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for RequestLogMiddleware<S>
+where
+    S: RpcServiceT<'a>,
+    S::Future: 'a,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: jsonrpsee::types::Request<'a>) -> Self::Future {
+        let id = RequestId::new();
+        let logging = self.logging;
+        let method = request.method_name().to_string();
+        let caller = request
+            .extensions()
+            .get::<CallerAddress>()
+            .map_or_else(|| "unknown".into(), |caller| caller.to_string());
+
+        if logging == RequestLogging::Params {
+            let params = request.params();
+            debug!(
+                "[{id}] {method} params: {}",
+                logged_params(&method, params.as_str())
+            );
+        }
+
+        // Method handlers may run while the call is being dispatched, or while its
+        // response is being awaited, so the ID is made visible to both.
+        let start = Instant::now();
+        let response = REQUEST_ID.sync_scope(id.clone(), || self.service.call(request));
+        let response = REQUEST_ID.scope(id.clone(), response);
+
+        Box::pin(async move {
+            let response = response.await;
+
+            if logging != RequestLogging::None {
+                let outcome = match response.as_error_code() {
+                    Some(code) => format!("error {code}"),
+                    None => "success".into(),
+                };
+                debug!(
+                    "[{id}] {method} from {caller} completed in {}ms: {outcome}",
+                    start.elapsed().as_millis(),
+                );
+            }
+
+            response
+        })
+    }
+}
+
+/// Returns the parameters of a call to `method` as they are logged.
+fn logged_params<'a>(method: &str, params: Option<&'a str>) -> &'a str {
+    if REDACTED_METHODS.contains(&method) {
+        "<redacted>"
+    } else {
+        params.unwrap_or("[]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::logged_params;
+
+    #[test]
+    fn secret_parameters_are_redacted() {
+        let params = Some(r#"["correct horse battery staple", 60]"#);
+        for method in ["walletpassphrase", "z_importkey", "sweepprivkey"] {
+            assert_eq!(logged_params(method, params), "<redacted>");
+        }
+        assert_eq!(logged_params("getwalletinfo", None), "[]");
+        assert_eq!(
+            logged_params("z_getbalanceforaccount", Some(r#"["acct", 1]"#)),
+            r#"["acct", 1]"#
+        );
+    }
+}
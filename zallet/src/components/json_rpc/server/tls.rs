@@ -0,0 +1,225 @@
+//! TLS for the JSON-RPC server.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use rustls::{
+    RootCertStore, ServerConfig,
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::WebPkiClientVerifier,
+};
+use tokio::select;
+use tokio_rustls::TlsAcceptor;
+use tracing::{info, warn};
+
+use crate::{
+    commands::resolve_datadir_path,
+    config::ZalletConfig,
+    error::{Error, ErrorKind},
+    fl, shutdown,
+};
+
+/// The files that the JSON-RPC server's TLS configuration is loaded from.
+#[derive(Clone, Debug)]
+pub(crate) struct TlsPaths {
+    cert: PathBuf,
+    key: PathBuf,
+    client_ca: Option<PathBuf>,
+}
+
+impl TlsPaths {
+    /// Returns the TLS files configured in `config`, or `None` if TLS is not enabled.
+    pub(crate) fn from_config(config: &ZalletConfig) -> Result<Option<Self>, Error> {
+        let resolve = |path: &Option<PathBuf>| {
+            path.as_deref()
+                .map(|path| resolve_datadir_path(config.datadir(), path))
+        };
+
+        match (
+            resolve(&config.rpc.tls_cert_path),
+            resolve(&config.rpc.tls_key_path),
+            resolve(&config.rpc.tls_client_ca_path),
+        ) {
+            (None, None, None) => Ok(None),
+            (Some(cert), Some(key), client_ca) => Ok(Some(Self {
+                cert,
+                key,
+                client_ca,
+            })),
+            _ => Err(ErrorKind::Init
+                .context(fl!("err-init-rpc-tls-incomplete"))
+                .into()),
+        }
+    }
+}
+
+/// The TLS configuration that the JSON-RPC server accepts new connections with.
+///
+/// Connections that were accepted before the configuration is reloaded keep using the
+/// configuration that they were accepted with.
+#[derive(Clone)]
+pub(super) struct Tls {
+    paths: Arc<TlsPaths>,
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+}
+
+impl Tls {
+    /// Loads the TLS configuration, failing if any of its files is unusable.
+    pub(super) fn load(paths: TlsPaths) -> Result<Self, Error> {
+        let config = server_config(&paths).map_err(|error| {
+            ErrorKind::Init.context(fl!("err-init-rpc-tls-invalid", error = error))
+        })?;
+
+        Ok(Self {
+            paths: Arc::new(paths),
+            acceptor: Arc::new(RwLock::new(TlsAcceptor::from(Arc::new(config)))),
+        })
+    }
+
+    /// Returns whether clients must present a certificate.
+    pub(super) fn requires_client_certificate(&self) -> bool {
+        self.paths.client_ca.is_some()
+    }
+
+    /// Returns the acceptor for new connections.
+    pub(super) fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor.read().expect("not poisoned").clone()
+    }
+
+    /// Reloads the TLS configuration from its files.
+    ///
+    /// If the files are no longer usable, the existing configuration is kept.
+    pub(super) fn reload(&self) -> Result<(), String> {
+        let config = server_config(&self.paths)?;
+        *self.acceptor.write().expect("not poisoned") = TlsAcceptor::from(Arc::new(config));
+        Ok(())
+    }
+
+    /// Reloads the TLS configuration whenever Zallet receives `SIGHUP`, until shutdown
+    /// is requested.
+    ///
+    /// This allows certificates to be renewed without restarting Zallet.
+    #[cfg(unix)]
+    pub(super) fn reload_on_sighup(&self) -> Result<(), Error> {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut sighup = signal(SignalKind::hangup()).map_err(|e| ErrorKind::Init.context(e))?;
+        let tls = self.clone();
+        crate::spawn!("JSON-RPC TLS reloader", async move {
+            loop {
+                select! {
+                    _ = sighup.recv() => match tls.reload() {
+                        Ok(()) => info!("Reloaded JSON-RPC TLS configuration"),
+                        Err(e) => warn!(
+                            "Failed to reload JSON-RPC TLS configuration, keeping the previous one: {e}"
+                        ),
+                    },
+                    _ = shutdown::requested() => break,
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Builds the TLS configuration of the JSON-RPC server from its files.
+fn server_config(paths: &TlsPaths) -> Result<ServerConfig, String> {
+    // The provider is chosen explicitly, so that it does not depend on the crate
+    // features that other dependencies enable in `rustls`.
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+    let certs = load_certs(&paths.cert)?;
+    let key = PrivateKeyDer::from_pem_file(&paths.key)
+        .map_err(|e| format!("cannot read private key from {}: {e}", paths.key.display()))?;
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?;
+
+    let builder = match &paths.client_ca {
+        None => builder.with_no_client_auth(),
+        Some(path) => builder.with_client_cert_verifier(client_verifier(path, provider)?),
+    };
+
+    // This checks that the private key matches the certificate.
+    builder.with_single_cert(certs, key).map_err(|e| {
+        format!(
+            "cannot use certificate {} with private key {}: {e}",
+            paths.cert.display(),
+            paths.key.display(),
+        )
+    })
+}
+
+/// Reads a non-empty list of PEM-encoded certificates.
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("cannot read certificates from {}: {e}", path.display()))?;
+
+    if certs.is_empty() {
+        Err(format!(
+            "{} does not contain any certificates",
+            path.display()
+        ))
+    } else {
+        Ok(certs)
+    }
+}
+
+/// Builds a verifier for client certificates issued by the CAs in the given file.
+fn client_verifier(
+    path: &Path,
+    provider: Arc<CryptoProvider>,
+) -> Result<Arc<dyn rustls::server::danger::ClientCertVerifier>, String> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots
+            .add(cert)
+            .map_err(|e| format!("invalid CA certificate in {}: {e}", path.display()))?;
+    }
+
+    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+        .build()
+        .map_err(|e| format!("cannot verify client certificates: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{TlsPaths, server_config};
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("tls")
+            .join(name)
+    }
+
+    fn paths(cert: &str, key: &str, client_ca: Option<&str>) -> TlsPaths {
+        TlsPaths {
+            cert: fixture(cert),
+            key: fixture(key),
+            client_ca: client_ca.map(fixture),
+        }
+    }
+
+    #[test]
+    fn load_server_config() {
+        assert!(server_config(&paths("cert.pem", "key.pem", None)).is_ok());
+        assert!(server_config(&paths("cert.pem", "key.pem", Some("client-ca.pem"))).is_ok());
+
+        // The private key must match the certificate.
+        assert!(server_config(&paths("cert.pem", "other-key.pem", None)).is_err());
+
+        // Every file must be readable, and contain what it is configured as.
+        assert!(server_config(&paths("missing.pem", "key.pem", None)).is_err());
+        assert!(server_config(&paths("cert.pem", "missing.pem", None)).is_err());
+        assert!(server_config(&paths("key.pem", "key.pem", None)).is_err());
+        assert!(server_config(&paths("cert.pem", "cert.pem", None)).is_err());
+        assert!(server_config(&paths("cert.pem", "key.pem", Some("missing.pem"))).is_err());
+        assert!(server_config(&paths("cert.pem", "key.pem", Some("key.pem"))).is_err());
+    }
+}
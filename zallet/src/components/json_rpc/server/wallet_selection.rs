@@ -0,0 +1,169 @@
+//! Selection of the wallet that a JSON-RPC request is made to.
+//!
+//! When Zallet serves several wallets, a client selects one with the `/wallet/<name>`
+//! endpoint path, as in Bitcoin Core, or with a `wallet` query parameter (for example
+//! `/?wallet=<name>`). If both are given, the path takes precedence. The sole wallet
+//! configured by `database.wallet` has the empty name, so it is selected with the
+//! `/wallet/` path.
+//!
+//! Requests that do not select a wallet are dispatched to the sole wallet if there is only
+//! one. Otherwise, calls to methods that access a wallet fail with the "wallet not
+//! specified" error, and calls that select a wallet which is not loaded fail with the
+//! "wallet not found" error.
+
+use futures::future::{self, Either, Ready};
+use hyper::Uri;
+use jsonrpsee::{
+    MethodResponse, server::Methods, server::middleware::rpc::RpcServiceT, types::ErrorObjectOwned,
+};
+
+use super::{LegacyCode, error::WALLET_NOT_FOUND};
+use crate::components::wallets::Wallets;
+
+/// Methods that do not access a wallet, and so can be called without selecting one.
+const WALLET_INDEPENDENT_METHODS: &[&str] = &[
+    "getbackendinfo",
+    "help",
+    "reopenlogs",
+    "rpc.discover",
+    "stop",
+    "verifymessage",
+    "z_converttex",
+];
+
+/// Why a request cannot be dispatched to a wallet.
+///
+/// This is inserted into the extensions of each such request, so that
+/// [`WalletSelectionMiddleware`] can reject its calls to wallet methods.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum Unavailable {
+    /// No wallets are loaded.
+    NoneLoaded,
+    /// Several wallets are loaded, and the request did not select one.
+    NotSpecified,
+    /// The request selected a wallet that is not loaded.
+    NotFound(String),
+}
+
+impl Unavailable {
+    fn error(&self) -> ErrorObjectOwned {
+        match self {
+            Unavailable::NoneLoaded => ErrorObjectOwned::borrowed(
+                WALLET_NOT_FOUND,
+                "No wallet is loaded. Load a wallet using loadwallet or create a new one with createwallet.",
+                None,
+            ),
+            Unavailable::NotSpecified => LegacyCode::WalletNotSpecified.with_static(
+                "Wallet file not specified (must request wallet RPC through /wallet/<name> uri-path).",
+            ),
+            Unavailable::NotFound(name) => ErrorObjectOwned::owned(
+                WALLET_NOT_FOUND,
+                format!("Requested wallet \"{name}\" does not exist or is not loaded"),
+                None::<()>,
+            ),
+        }
+    }
+}
+
+/// Returns the name of the wallet selected by a request to `uri`, if any.
+fn selected_wallet(uri: &Uri) -> Option<&str> {
+    uri.path().strip_prefix("/wallet/").or_else(|| {
+        uri.query()?
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("wallet="))
+    })
+}
+
+/// Dispatches requests to the methods of the wallet they select.
+pub(super) struct Router {
+    wallets: Wallets,
+    /// The methods that are served when no wallets are loaded.
+    unloaded: Methods,
+}
+
+impl Router {
+    pub(super) fn new(wallets: Wallets, unloaded: Methods) -> Self {
+        Self { wallets, unloaded }
+    }
+
+    /// Returns the methods that a request to `uri` should be dispatched to, along with
+    /// the reason that they cannot access a wallet (if any).
+    pub(super) fn route(&self, uri: &Uri) -> (Methods, Option<Unavailable>) {
+        let loaded = self.wallets.loaded();
+
+        // Methods that do not access a wallet are served by the first wallet's methods
+        // when no wallet is available.
+        let fallback = |unavailable| match loaded.first() {
+            Some(wallet) => (wallet.rpc.methods(), Some(unavailable)),
+            None => (
+                self.unloaded.clone(),
+                Some(match unavailable {
+                    Unavailable::NotSpecified => Unavailable::NoneLoaded,
+                    _ => unavailable,
+                }),
+            ),
+        };
+
+        match selected_wallet(uri) {
+            Some(name) => match loaded.iter().find(|wallet| wallet.name() == name) {
+                Some(wallet) => (wallet.rpc.methods(), None),
+                None => fallback(Unavailable::NotFound(name.into())),
+            },
+            None if loaded.len() == 1 => (loaded[0].rpc.methods(), None),
+            None => fallback(Unavailable::NotSpecified),
+        }
+    }
+}
+
+/// JSON-RPC middleware that rejects calls to wallet methods from requests that cannot
+/// access a wallet.
+pub struct WalletSelectionMiddleware<S> {
+    service: S,
+}
+
+impl<S> WalletSelectionMiddleware<S> {
+    /// Create a new `WalletSelectionMiddleware` with the given `service`.
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for WalletSelectionMiddleware<S>
+where
+    S: RpcServiceT<'a>,
+{
+    type Future = Either<S::Future, Ready<MethodResponse>>;
+
+    fn call(&self, request: jsonrpsee::types::Request<'a>) -> Self::Future {
+        let error = request
+            .extensions()
+            .get::<Unavailable>()
+            .filter(|_| !WALLET_INDEPENDENT_METHODS.contains(&request.method_name()))
+            .map(Unavailable::error);
+
+        match error {
+            Some(error) => Either::Right(future::ready(MethodResponse::error(request.id(), error))),
+            None => Either::Left(self.service.call(request)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::Uri;
+
+    use super::selected_wallet;
+
+    #[test]
+    fn wallet_selection() {
+        let select = |uri: &'static str| selected_wallet(&Uri::from_static(uri)).map(String::from);
+
+        assert_eq!(select("/"), None);
+        assert_eq!(select("/health"), None);
+        assert_eq!(select("/wallet/ops").as_deref(), Some("ops"));
+        assert_eq!(select("/?wallet=cold").as_deref(), Some("cold"));
+        assert_eq!(select("/?a=b&wallet=cold").as_deref(), Some("cold"));
+        assert_eq!(select("/wallet/ops?wallet=cold").as_deref(), Some("ops"));
+        assert_eq!(select("/wallet/").as_deref(), Some(""));
+    }
+}
@@ -0,0 +1,50 @@
+//! Restriction of subscriptions to WebSocket connections.
+
+use futures::future::{self, Either, Ready};
+use jsonrpsee::{MethodResponse, server::middleware::rpc::RpcServiceT};
+
+use super::LegacyCode;
+use crate::components::json_rpc::methods::SUBSCRIPTION_METHODS;
+
+/// Marks a request that was made over plain HTTP, rather than over a WebSocket
+/// connection.
+///
+/// This is inserted into the extensions of each such request, so that
+/// [`WebSocketOnlyMiddleware`] can reject its calls to subscription methods.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct HttpTransport;
+
+/// JSON-RPC middleware that rejects calls to subscription methods made over plain HTTP.
+///
+/// Without this, `jsonrpsee` rejects them with a generic internal error.
+pub struct WebSocketOnlyMiddleware<S> {
+    service: S,
+}
+
+impl<S> WebSocketOnlyMiddleware<S> {
+    /// Create a new `WebSocketOnlyMiddleware` with the given `service`.
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for WebSocketOnlyMiddleware<S>
+where
+    S: RpcServiceT<'a>,
+{
+    type Future = Either<S::Future, Ready<MethodResponse>>;
+
+    fn call(&self, request: jsonrpsee::types::Request<'a>) -> Self::Future {
+        if request.extensions().get::<HttpTransport>().is_some()
+            && SUBSCRIPTION_METHODS.contains(&request.method_name())
+        {
+            let error = LegacyCode::Misc.with_message(format!(
+                "{} is only available over WebSocket connections",
+                request.method_name(),
+            ));
+            Either::Right(future::ready(MethodResponse::error(request.id(), error)))
+        } else {
+            Either::Left(self.service.call(request))
+        }
+    }
+}
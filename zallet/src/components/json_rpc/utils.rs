@@ -1,27 +1,36 @@
+use std::collections::HashSet;
 use std::fmt;
+use std::sync::Arc;
 
 use jsonrpsee::{
     core::{JsonValue, RpcResult},
-    types::ErrorCode as RpcErrorCode,
+    types::ErrorObjectOwned,
 };
 use rust_decimal::Decimal;
 use schemars::{JsonSchema, json_schema};
 use serde::Serialize;
+use zaino_state::FetchServiceSubscriber;
+use zcash_client_backend::{
+    data_api::{Account, AccountBirthday, AccountPurpose, AccountSource, WalletRead},
+    proto::service::TreeState,
+};
 use zcash_client_sqlite::AccountUuid;
 use zcash_protocol::{
     TxId,
-    consensus::BlockHeight,
+    consensus::{BlockHeight, NetworkType, Parameters},
     value::{COIN, ZatBalance, Zatoshis},
 };
 use zip32::DiversifierIndex;
 
-use super::server::LegacyCode;
+use super::server::{ComponentFailure, LegacyCode};
+use crate::{
+    components::database::{DbConnection, IndexedAccounts, QueryHeight},
+    network::Network,
+};
 
 #[cfg(zallet_build = "wallet")]
 use {
-    crate::components::{database::DbConnection, keystore::KeyStore},
-    std::collections::HashSet,
-    zcash_client_backend::data_api::{Account, WalletRead},
+    crate::components::keystore::{KeyStore, UnlockGuard},
     zcash_protocol::value::BalanceError,
     zip32::fingerprint::SeedFingerprint,
 };
@@ -29,18 +38,25 @@ use {
 /// The account identifier used for HD derivation of transparent and Sapling addresses via
 /// the legacy `getnewaddress` and `z_getnewaddress` code paths.
 #[cfg(zallet_build = "wallet")]
-const ZCASH_LEGACY_ACCOUNT: u32 = 0x7fff_ffff;
+pub(super) const ZCASH_LEGACY_ACCOUNT: u32 = 0x7fff_ffff;
+
+/// The key source of accounts that `z_importkey` created from a unified spending key.
+pub(super) const IMPORTED_UNIFIED_KEY_SOURCE: &str = "imported_unified_key";
 
+/// The key source of accounts that `z_importkey` created from a Sapling extended
+/// spending key.
+pub(super) const IMPORTED_SAPLING_KEY_SOURCE: &str = "imported_sapling_key";
+
+/// Returns an error if the wallet is locked.
+///
+/// The returned guard defers the relock timeout until it is dropped, so callers that
+/// decrypt key material more than once should hold it until they are done.
 #[cfg(zallet_build = "wallet")]
-pub(super) async fn ensure_wallet_is_unlocked(keystore: &KeyStore) -> RpcResult<()> {
-    // TODO: Consider returning some kind of unlock guard to ensure the caller doesn't
-    // need to race against the relock timeout.
-    if keystore.is_locked().await {
-        Err(LegacyCode::WalletUnlockNeeded
-            .with_static("Error: Please enter the wallet passphrase with walletpassphrase first."))
-    } else {
-        Ok(())
-    }
+pub(super) async fn ensure_wallet_is_unlocked(keystore: &KeyStore) -> RpcResult<UnlockGuard> {
+    keystore
+        .unlock_guard()
+        .await
+        .ok_or_else(|| ComponentFailure::WalletLocked.into())
 }
 
 // TODO: Move this to `zcash_protocol`.
@@ -80,7 +96,7 @@ pub(crate) fn parse_seedfp(
 
 /// Parses the `account` parameter present in many wallet RPCs.
 pub(super) async fn parse_account_parameter(
-    #[cfg(zallet_build = "wallet")] wallet: &DbConnection,
+    wallet: &DbConnection,
     #[cfg(zallet_build = "wallet")] keystore: &KeyStore,
     account: &JsonValue,
 ) -> RpcResult<AccountUuid> {
@@ -91,89 +107,406 @@ pub(super) async fn parse_account_parameter(
         JsonValue::Number(n) => {
             let zip32_account_index = n
                 .as_u64()
-                .filter(|n| n < &u64::from(ZCASH_LEGACY_ACCOUNT))
+                .and_then(|n| u32::try_from(n).ok())
+                .filter(|n| n < &ZCASH_LEGACY_ACCOUNT)
                 .ok_or_else(|| {
                     LegacyCode::InvalidParameter
                         .with_static("Invalid account number, must be 0 <= account <= (2^31)-2.")
                 })?;
 
-            let mut distinct_seeds = HashSet::new();
-            let mut account_id = None;
-
             let legacy_seeds = keystore
                 .list_legacy_seed_fingerprints()
                 .await
-                .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?;
-
-            for candidate_account_id in wallet
-                .get_account_ids()
-                .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
-            {
-                let account = wallet
-                    .get_account(candidate_account_id)
-                    .map_err(|e| LegacyCode::Database.with_message(e.to_string()))?
-                    // This would be a race condition between this and account deletion.
-                    .ok_or(RpcErrorCode::InternalError)?;
-
-                // Ignore accounts from imported keys. `zcashd` did not support importing
-                // UFVKs as "accounts"; the latter always descended from the single seed.
-                if let Some(derivation) = account.source().key_derivation() {
-                    let seed_fp = derivation.seed_fingerprint();
-
-                    // Ignore accounts using a legacy non-mnemonic seed. `zcashd` only
-                    // ever used this to derive a new Sapling spend authority for each
-                    // call to `z_getnewaddress`, and these were never accessible via
-                    // JSON-RPCs that took ZIP 32 account indices.
-                    if !legacy_seeds.contains(seed_fp) {
-                        distinct_seeds.insert(*seed_fp);
-                        if u64::from(u32::from(derivation.account_index())) == zip32_account_index {
-                            account_id = Some(candidate_account_id);
-                        }
+                .map_err(ComponentFailure::keystore)?;
+
+            // Accounts from imported keys are not indexed. `zcashd` did not support
+            // importing UFVKs as "accounts"; the latter always descended from the single
+            // seed.
+            let accounts = wallet
+                .indexed_accounts()
+                .map_err(ComponentFailure::database)?;
+
+            let seed_fp = match keystore.default_seed_fingerprint() {
+                // Always resolve account numbers against the configured seed.
+                Some(seed_fp) => Some(seed_fp),
+                None => {
+                    // Ignore accounts using a legacy non-mnemonic seed. `zcashd` only ever
+                    // used this to derive a new Sapling spend authority for each call to
+                    // `z_getnewaddress`, and these were never accessible via JSON-RPCs that
+                    // took ZIP 32 account indices.
+                    let mut seeds = accounts
+                        .seeds()
+                        .filter(|seed_fp| !legacy_seeds.contains(seed_fp));
+
+                    match (seeds.next(), seeds.next()) {
+                        (Some(seed_fp), None) => Some(seed_fp),
+                        _ => None,
                     }
                 }
-            }
+            };
 
-            if distinct_seeds.len() == 1 {
-                account_id.ok_or_else(|| {
-                    LegacyCode::Wallet.with_message(format!(
-                        "Error: account {zip32_account_index} has not been generated by z_getnewaccount."
-                    ))
-                })
-            } else {
-                Err(LegacyCode::Wallet.with_static("Account numbers are not supported in wallets with multiple seeds. Use the account UUID instead."))
+            match seed_fp {
+                Some(seed_fp) => {
+                    accounts.derived(seed_fp, zip32_account_index).ok_or_else(|| {
+                        LegacyCode::Wallet.with_message(format!(
+                            "Error: account {zip32_account_index} has not been generated by z_getnewaccount."
+                        ))
+                    })
+                }
+                _ => Err(LegacyCode::Wallet.with_static("Account numbers are not supported in wallets with multiple seeds. Use the account UUID instead.")),
             }
         }
-        // This might be an account UUID.
-        JsonValue::String(s) => s
-            .parse()
-            .map(AccountUuid::from_uuid)
-            .map_err(|_| RpcErrorCode::InvalidParams.into()),
-        _ => Err(RpcErrorCode::InvalidParams.into()),
+        // This might be an account UUID or name.
+        JsonValue::String(s) => parse_account_string(s, || {
+            wallet
+                .indexed_accounts()
+                .map_err(|e| ComponentFailure::database(e).into())
+        }),
+        _ => Err(invalid_account()),
     }
 }
 
+/// Parses an `account` parameter given as a string, which is either an account UUID or
+/// the exact name of an account.
+///
+/// `accounts` is only called if the string is not a UUID.
+fn parse_account_string(
+    account: &str,
+    accounts: impl FnOnce() -> RpcResult<Arc<IndexedAccounts>>,
+) -> RpcResult<AccountUuid> {
+    if let Ok(uuid) = account.parse() {
+        return Ok(AccountUuid::from_uuid(uuid));
+    }
+
+    match accounts()?.named(account) {
+        [] => Err(LegacyCode::InvalidParameter.with_message(format!(
+            "Invalid account: not an account UUID, and no account is named \"{account}\"."
+        ))),
+        [account_id] => Ok(*account_id),
+        matching => Err(LegacyCode::InvalidParameter.with_message(format!(
+            "Invalid account: {} accounts are named \"{account}\". Use the account UUID instead.",
+            matching.len(),
+        ))),
+    }
+}
+
+fn invalid_account() -> ErrorObjectOwned {
+    LegacyCode::InvalidParameter
+        .with_static("Invalid account: not an account UUID or account number.")
+}
+
+/// The maximum length of an account name, in characters.
+const MAX_ACCOUNT_NAME_LEN: usize = 100;
+
+/// Checks that `name` can be given to `account`, or to a new account if `account` is
+/// `None`.
+///
+/// Names need not be unique, unless `strict_unique` is set. `accounts` is only called
+/// if `strict_unique` is set.
+pub(super) fn check_account_name(
+    name: &str,
+    account: Option<AccountUuid>,
+    strict_unique: bool,
+    accounts: impl FnOnce() -> RpcResult<Arc<IndexedAccounts>>,
+) -> RpcResult<()> {
+    if name.is_empty() {
+        return Err(LegacyCode::InvalidParameter.with_static("Account name must not be empty"));
+    }
+    if name.chars().count() > MAX_ACCOUNT_NAME_LEN {
+        return Err(LegacyCode::InvalidParameter.with_message(format!(
+            "Account name must be at most {MAX_ACCOUNT_NAME_LEN} characters"
+        )));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(LegacyCode::InvalidParameter
+            .with_static("Account name must not contain control characters"));
+    }
+
+    if strict_unique
+        && accounts()?
+            .named(name)
+            .iter()
+            .any(|named| Some(*named) != account)
+    {
+        return Err(LegacyCode::InvalidParameter
+            .with_message(format!("Another account is already named \"{name}\"")));
+    }
+
+    Ok(())
+}
+
 /// Parses the `diversifier_index` parameter present in many wallet RPCs.
-pub(super) fn parse_diversifier_index(diversifier_index: u128) -> RpcResult<DiversifierIndex> {
-    diversifier_index
-        .try_into()
-        .map_err(|_| LegacyCode::InvalidParameter.with_static("diversifier index is too large."))
+///
+/// A diversifier index can be given in any of the following formats:
+/// - A JSON number, e.g. `258`.
+/// - A decimal string, e.g. `"258"`.
+/// - A `0x`-prefixed hex string encoding the index's bytes in little-endian order, e.g.
+///   `"0x0201"`. The 11-byte encoding used by ZIP 32 may be given in full, or with any
+///   trailing zero bytes omitted.
+pub(super) fn parse_diversifier_index(
+    diversifier_index: &JsonValue,
+) -> RpcResult<DiversifierIndex> {
+    let invalid = |format: &str, reason: String| {
+        LegacyCode::InvalidParameter.with_message(format!(
+            "Invalid diversifier index {diversifier_index} as {format}: {reason}"
+        ))
+    };
+    let from_u128 = |format: &str, index: u128| {
+        DiversifierIndex::try_from(index)
+            .map_err(|_| invalid(format, "must be less than 2^88".into()))
+    };
+
+    match diversifier_index {
+        JsonValue::Number(n) => {
+            const FORMAT: &str = "a JSON number";
+            // Parse the number's text, as JSON numbers can exceed the range of `u64`.
+            let index = n
+                .to_string()
+                .parse::<u128>()
+                .map_err(|_| invalid(FORMAT, "must be a non-negative integer".into()))?;
+            from_u128(FORMAT, index)
+        }
+        JsonValue::String(s) => match s.strip_prefix("0x") {
+            Some(hex_str) => {
+                const FORMAT: &str = "a hex string";
+                let bytes = hex::decode(hex_str).map_err(|e| invalid(FORMAT, e.to_string()))?;
+                match bytes.len() {
+                    0 => Err(invalid(FORMAT, "must contain at least one byte".into())),
+                    1..=11 => {
+                        let mut index = [0; 11];
+                        index[..bytes.len()].copy_from_slice(&bytes);
+                        Ok(DiversifierIndex::from(index))
+                    }
+                    len => Err(invalid(
+                        FORMAT,
+                        format!("{len} bytes is longer than the 11 bytes of an index"),
+                    )),
+                }
+            }
+            None => {
+                const FORMAT: &str = "a decimal string";
+                let index = s.parse::<u128>().map_err(|e| invalid(FORMAT, e.to_string()))?;
+                from_u128(FORMAT, index)
+            }
+        },
+        _ => Err(LegacyCode::InvalidParameter.with_static(
+            "Invalid diversifier index: must be a JSON number, a decimal string, or a 0x-prefixed hex string.",
+        )),
+    }
+}
+
+/// Returns the birthday of an account that is created at the given height.
+pub(super) async fn account_birthday(
+    chain: &FetchServiceSubscriber,
+    params: &Network,
+    birthday_height: BlockHeight,
+) -> RpcResult<AccountBirthday> {
+    let treestate = {
+        let treestate = chain
+            .fetcher
+            .get_treestate(birthday_height.saturating_sub(1).to_string())
+            .await
+            .map_err(ComponentFailure::chain)?;
+
+        TreeState {
+            network: match params.network_type() {
+                NetworkType::Main => "main".into(),
+                NetworkType::Test => "test".into(),
+                NetworkType::Regtest => "regtest".into(),
+            },
+            height: u64::try_from(treestate.height)
+                .map_err(|_| ComponentFailure::chain("Invalid treestate height"))?,
+            hash: treestate.hash,
+            time: treestate.time,
+            sapling_tree: treestate
+                .sapling
+                .commitments()
+                .final_state()
+                .as_ref()
+                .map(hex::encode)
+                .unwrap_or_default(),
+            orchard_tree: treestate
+                .orchard
+                .commitments()
+                .final_state()
+                .as_ref()
+                .map(hex::encode)
+                .unwrap_or_default(),
+        }
+    };
+
+    AccountBirthday::from_treestate(treestate, None)
+        .map_err(|_| ComponentFailure::chain("Invalid treestate").into())
+}
+
+/// Returns whether the wallet can only watch the funds held by the given account.
+///
+/// This is the policy that every RPC applies for its `include_watchonly` parameter and
+/// its watch-only flags: funds are spendable only if they are held by an account whose
+/// spending key the wallet can derive, or whose unified spending key was imported with
+/// `z_importkey`. Accounts imported from a viewing key, including imported UFVKs whose
+/// seed the wallet does not hold, are watch-only.
+///
+/// Accounts imported from a Sapling extended spending key are also watch-only, because
+/// transactions can only be created with a unified spending key.
+pub(super) fn is_watch_only(account: &impl Account) -> bool {
+    match account.source() {
+        AccountSource::Imported {
+            purpose: AccountPurpose::Spending { derivation: None },
+            key_source,
+        } => key_source.as_deref() != Some(IMPORTED_UNIFIED_KEY_SOURCE),
+        source => source.key_derivation().is_none(),
+    }
+}
+
+/// Returns the wallet's watch-only accounts (see [`is_watch_only`]).
+pub(super) fn watch_only_accounts(wallet: &DbConnection) -> RpcResult<HashSet<AccountUuid>> {
+    let mut accounts = HashSet::new();
+    for account_id in wallet
+        .get_account_ids()
+        .map_err(ComponentFailure::database)?
+    {
+        let account = wallet
+            .get_account(account_id)
+            .map_err(ComponentFailure::database)?
+            // This would be a race condition between this and account deletion.
+            .ok_or_else(|| ComponentFailure::internal("Account was deleted during the request"))?;
+
+        if is_watch_only(&account) {
+            accounts.insert(account_id);
+        }
+    }
+    Ok(accounts)
+}
+
+/// The height that a query is evaluated at, as given by the `as_of_height` parameter
+/// present in many wallet RPCs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum AsOfHeight {
+    /// A chain height.
+    Height(BlockHeight),
+    /// The given number of blocks below the wallet's chain tip.
+    BelowTip(u32),
 }
 
 /// Parses the `as_of_height` parameter present in many wallet RPCs.
-pub(super) fn parse_as_of_height(as_of_height: Option<i64>) -> RpcResult<Option<BlockHeight>> {
+///
+/// Follows the conventions of `zcashd`:
+/// - `-1` (or omitting the parameter) means the wallet's chain tip, and returns `None`.
+/// - Other negative values are offsets from the chain tip: `-2` is the block below the
+///   tip, `-3` the block below that, and so on.
+/// - Positive values are chain heights.
+/// - `0` (the genesis block) is rejected.
+///
+/// The parsed height must be resolved against the wallet's chain tip with
+/// [`resolve_as_of_height`].
+pub(super) fn parse_as_of_height(as_of_height: Option<i64>) -> RpcResult<Option<AsOfHeight>> {
+    let out_of_range =
+        || LegacyCode::InvalidParameter.with_static("`as_of_height` parameter out of range");
+
     match as_of_height {
         None | Some(-1) => Ok(None),
-        Some(..0) => Err(LegacyCode::InvalidParameter
-            .with_static("Can not perform the query as of a negative block height")),
         Some(0) => Err(LegacyCode::InvalidParameter
             .with_static("Can not perform the query as of the genesis block")),
-        Some(h) => u32::try_from(h).map_or_else(
-            |_| {
-                Err(LegacyCode::InvalidParameter
-                    .with_static("`as_of_height` parameter out of range"))
-            },
-            |h| Ok(Some(BlockHeight::from(h))),
-        ),
+        Some(h @ ..0) => u32::try_from(-1 - h)
+            .map(|blocks| Some(AsOfHeight::BelowTip(blocks)))
+            .map_err(|_| out_of_range()),
+        Some(h) => u32::try_from(h)
+            .map(|h| Some(AsOfHeight::Height(BlockHeight::from(h))))
+            .map_err(|_| out_of_range()),
+    }
+}
+
+/// Returns the height that a query with the given `as_of_height` parameter should be
+/// evaluated at, given the wallet's current chain tip.
+///
+/// Heights above the chain tip are rejected rather than clamped to it, as the wallet
+/// cannot know the state of the chain at those heights.
+pub(super) fn resolve_as_of_height(
+    as_of_height: Option<AsOfHeight>,
+    chain_tip: BlockHeight,
+) -> RpcResult<BlockHeight> {
+    match as_of_height {
+        None => Ok(chain_tip),
+        Some(AsOfHeight::Height(height)) if height > chain_tip => {
+            Err(LegacyCode::InvalidParameter.with_message(format!(
+                "Can not perform the query as of height {height}, which is above the wallet's chain tip at height {chain_tip}"
+            )))
+        }
+        Some(AsOfHeight::Height(height)) => Ok(height),
+        Some(AsOfHeight::BelowTip(blocks)) => u32::from(chain_tip)
+            .checked_sub(blocks)
+            .filter(|height| *height > 0)
+            .map(BlockHeight::from)
+            .ok_or_else(|| {
+                LegacyCode::InvalidParameter.with_message(format!(
+                    "Can not perform the query as of {blocks} blocks below the wallet's chain tip at height {chain_tip}"
+                ))
+            }),
+    }
+}
+
+/// Returns an error if the wallet cannot answer queries as of `height`, because it is
+/// below the wallet's birthday or the wallet has not yet scanned it.
+fn check_scanned(
+    height: BlockHeight,
+    birthday: Option<BlockHeight>,
+    fully_scanned: Option<BlockHeight>,
+) -> RpcResult<()> {
+    match (birthday, fully_scanned) {
+        (Some(birthday), _) if height < birthday => {
+            Err(LegacyCode::InvalidParameter.with_message(format!(
+                "Can not perform the query as of height {height}, which is below the wallet's birthday at height {birthday}"
+            )))
+        }
+        (_, Some(fully_scanned)) if height <= fully_scanned => Ok(()),
+        (_, Some(fully_scanned)) => Err(LegacyCode::InvalidParameter.with_message(format!(
+            "Can not perform the query as of height {height}, which is above the wallet's fully-scanned height {fully_scanned}"
+        ))),
+        (_, None) => Err(LegacyCode::InvalidParameter.with_message(format!(
+            "Can not perform the query as of height {height}, because the wallet has not fully scanned any blocks"
+        ))),
+    }
+}
+
+/// Returns an error if the wallet cannot answer queries as of `height`.
+///
+/// Queries as of a past height are only accurate for heights that the wallet has fully
+/// scanned, and that are not below the wallet's birthday.
+pub(super) fn ensure_scanned(wallet: &DbConnection, height: BlockHeight) -> RpcResult<()> {
+    check_scanned(
+        height,
+        wallet
+            .get_wallet_birthday()
+            .map_err(ComponentFailure::database)?,
+        wallet
+            .block_fully_scanned()
+            .map_err(ComponentFailure::database)?
+            .map(|block| block.block_height()),
+    )
+}
+
+/// Resolves the `as_of_height` parameter to the point in the wallet's history that a
+/// query should be evaluated at.
+///
+/// If `as_of_height` refers to a past height, the wallet must be able to answer queries
+/// as of that height (see [`ensure_scanned`]).
+pub(super) fn query_height(
+    wallet: &DbConnection,
+    as_of_height: Option<AsOfHeight>,
+) -> RpcResult<QueryHeight> {
+    let chain_tip = wallet
+        .chain_height()
+        .map_err(ComponentFailure::database)?
+        .ok_or(LegacyCode::InWarmup.with_static("Wallet sync required"))?;
+
+    match as_of_height {
+        None => Ok(QueryHeight::Tip(chain_tip)),
+        Some(_) => {
+            let height = resolve_as_of_height(as_of_height, chain_tip)?;
+            ensure_scanned(wallet, height)?;
+            Ok(QueryHeight::AsOf(height))
+        }
     }
 }
 
@@ -181,7 +514,7 @@ pub(super) fn parse_as_of_height(as_of_height: Option<i64>) -> RpcResult<Option<
 pub(super) fn parse_minconf(
     minconf: Option<u32>,
     default: u32,
-    as_of_height: Option<BlockHeight>,
+    as_of_height: Option<AsOfHeight>,
 ) -> RpcResult<u32> {
     match minconf {
         None => Ok(default),
@@ -224,6 +557,23 @@ pub(super) fn value_from_zat_balance(value: ZatBalance) -> JsonZecBalance {
     )
 }
 
+/// Returns a value in both of the forms that RPC responses report monetary fields in:
+/// ZEC (with the 8 decimal places that `zcashd` uses), and zatoshis.
+///
+/// Every monetary field `foo` of a response is paired with a `fooZat` field holding the
+/// value in zatoshis, and both are set from the result of this function.
+pub(super) fn zec_and_zat(value: Zatoshis) -> (JsonZec, u64) {
+    (value_from_zatoshis(value), value.into_u64())
+}
+
+/// Returns a value that may be negative in both of the forms that RPC responses report
+/// monetary fields in.
+///
+/// See [`zec_and_zat`] for details.
+pub(super) fn zec_and_zat_balance(value: ZatBalance) -> (JsonZecBalance, i64) {
+    (value_from_zat_balance(value), value.into())
+}
+
 #[derive(Clone, Debug, Serialize)]
 #[serde(transparent)]
 pub(super) struct JsonZec(Decimal);
@@ -452,15 +802,29 @@ fn parse_fixed_point(mut val: &str, decimals: i64) -> Option<i64> {
 
 #[cfg(test)]
 mod tests {
-    use zcash_protocol::value::{COIN, ZatBalance};
+    use std::sync::Arc;
+
+    use serde_json::json;
+    use uuid::Uuid;
+    use zcash_client_sqlite::AccountUuid;
+    use zcash_protocol::{
+        consensus::BlockHeight,
+        value::{COIN, ZatBalance, Zatoshis},
+    };
 
-    use crate::components::json_rpc::utils::value_from_zat_balance;
+    use crate::components::{
+        database::IndexedAccounts,
+        json_rpc::utils::{
+            AsOfHeight, check_account_name, check_scanned, parse_account_string,
+            parse_as_of_height, parse_diversifier_index, parse_minconf, resolve_as_of_height,
+            value_from_zat_balance, zec_and_zat, zec_and_zat_balance,
+        },
+    };
 
     #[cfg(zallet_build = "wallet")]
     use {
         super::{parse_fixed_point, zatoshis_from_value},
         crate::components::json_rpc::utils::parse_seedfp_parameter,
-        zcash_protocol::value::Zatoshis,
         zip32::fingerprint::SeedFingerprint,
     };
 
@@ -472,6 +836,193 @@ mod tests {
         assert_eq!(parse_seedfp_parameter(&seedfp.to_string()), Ok(seedfp),);
     }
 
+    #[test]
+    fn account_names() {
+        let savings = AccountUuid::from_uuid(Uuid::from_u128(1));
+        let accounts = || {
+            let mut accounts = IndexedAccounts::default();
+            accounts.insert_named("Savings", savings);
+            Ok(Arc::new(accounts))
+        };
+
+        assert!(check_account_name("Cold savings ✨", None, false, || unreachable!()).is_ok());
+        assert!(check_account_name("", None, false, accounts).is_err());
+        assert!(check_account_name("Tab\tname", None, false, accounts).is_err());
+        assert!(check_account_name(&"a".repeat(100), None, false, accounts).is_ok());
+        assert!(check_account_name(&"a".repeat(101), None, false, accounts).is_err());
+
+        // Duplicate names are only rejected if requested, and an account keeps its own
+        // name.
+        assert!(check_account_name("Savings", None, false, accounts).is_ok());
+        assert!(check_account_name("Savings", None, true, accounts).is_err());
+        assert!(check_account_name("Savings", Some(savings), true, accounts).is_ok());
+    }
+
+    #[test]
+    fn account_uuids_and_names() {
+        let uuid = Uuid::parse_str("0190a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5b").unwrap();
+        let other = AccountUuid::from_uuid(Uuid::from_u128(1));
+        let accounts = || {
+            let mut accounts = IndexedAccounts::default();
+            // A name that looks like a truncated UUID.
+            accounts.insert_named("0190a1b2-c3d4-7e5f", other);
+            // A name that is also a valid UUID is shadowed by the account with that UUID.
+            accounts.insert_named(&uuid.to_string(), other);
+            accounts.insert_named("Shared", other);
+            accounts.insert_named("Shared", AccountUuid::from_uuid(Uuid::from_u128(2)));
+            Ok(Arc::new(accounts))
+        };
+
+        // UUIDs are parsed without consulting the wallet's accounts.
+        assert_eq!(
+            parse_account_string(&uuid.to_string(), || unreachable!()),
+            Ok(AccountUuid::from_uuid(uuid)),
+        );
+        assert_eq!(
+            parse_account_string(&uuid.to_string(), accounts),
+            Ok(AccountUuid::from_uuid(uuid)),
+        );
+
+        assert_eq!(
+            parse_account_string("0190a1b2-c3d4-7e5f", accounts),
+            Ok(other)
+        );
+
+        // Unknown and ambiguous names are reported distinctly.
+        let err = |account| parse_account_string(account, accounts).unwrap_err();
+        assert_eq!(
+            err("0190a1b2").message(),
+            "Invalid account: not an account UUID, and no account is named \"0190a1b2\".",
+        );
+        assert_eq!(
+            err("shared").message(),
+            "Invalid account: not an account UUID, and no account is named \"shared\".",
+        );
+        assert_eq!(
+            err("Shared").message(),
+            "Invalid account: 2 accounts are named \"Shared\". Use the account UUID instead.",
+        );
+    }
+
+    #[test]
+    fn diversifier_index_formats() {
+        let parse = |value| parse_diversifier_index(&value).map(u128::from);
+        let err = |value| parse_diversifier_index(&value).unwrap_err();
+
+        // Every format parses to the same index.
+        for value in [
+            json!(258),
+            json!("258"),
+            json!("0x0201"),
+            json!("0x0201000000"),
+        ] {
+            assert_eq!(parse(value), Ok(258));
+        }
+        assert_eq!(parse(json!("0xffffffffffffffffffffff")), Ok((1 << 88) - 1));
+
+        // JSON numbers beyond the range of `u64` are parsed exactly.
+        let max = serde_json::from_str("309485009821345068724781055").unwrap();
+        assert_eq!(parse(max), Ok((1 << 88) - 1));
+
+        // Errors report the format that was attempted.
+        assert_eq!(
+            err(json!(309485009821345068724781056u128)).message(),
+            "Invalid diversifier index 309485009821345068724781056 as a JSON number: must be less than 2^88",
+        );
+        assert_eq!(
+            err(json!(-1)).message(),
+            "Invalid diversifier index -1 as a JSON number: must be a non-negative integer",
+        );
+        assert_eq!(
+            err(json!("twelve")).message(),
+            "Invalid diversifier index \"twelve\" as a decimal string: invalid digit found in string",
+        );
+        assert_eq!(
+            err(json!("0x020")).message(),
+            "Invalid diversifier index \"0x020\" as a hex string: Odd number of digits",
+        );
+        assert_eq!(
+            err(json!("0x000000000000000000000001")).message(),
+            "Invalid diversifier index \"0x000000000000000000000001\" as a hex string: 12 bytes is longer than the 11 bytes of an index",
+        );
+        assert_eq!(
+            err(json!("0x")).message(),
+            "Invalid diversifier index \"0x\" as a hex string: must contain at least one byte"
+        );
+        assert!(parse(json!([1])).is_err());
+    }
+
+    #[test]
+    fn as_of_height_boundaries() {
+        let tip = BlockHeight::from_u32(100);
+        let resolve = |as_of_height| {
+            parse_as_of_height(as_of_height)
+                .and_then(|as_of_height| resolve_as_of_height(as_of_height, tip))
+                .map(u32::from)
+        };
+
+        // The chain tip.
+        assert_eq!(resolve(None), Ok(100));
+        assert_eq!(resolve(Some(-1)), Ok(100));
+        assert_eq!(resolve(Some(100)), Ok(100));
+
+        // Chain heights, which cannot be above the tip.
+        assert_eq!(resolve(Some(1)), Ok(1));
+        assert_eq!(resolve(Some(99)), Ok(99));
+        assert_eq!(
+            resolve(Some(101)).unwrap_err().message(),
+            "Can not perform the query as of height 101, which is above the wallet's chain tip at height 100",
+        );
+        assert!(resolve(Some(i64::from(u32::MAX) + 1)).is_err());
+
+        // Offsets from the tip, which cannot reach the genesis block.
+        assert_eq!(resolve(Some(-2)), Ok(99));
+        assert_eq!(resolve(Some(-100)), Ok(1));
+        assert_eq!(
+            resolve(Some(-101)).unwrap_err().message(),
+            "Can not perform the query as of 100 blocks below the wallet's chain tip at height 100",
+        );
+        assert!(resolve(Some(i64::MIN)).is_err());
+
+        assert_eq!(
+            resolve(Some(0)).unwrap_err().message(),
+            "Can not perform the query as of the genesis block",
+        );
+
+        // `minconf` must be positive when `as_of_height` is given, unless it is the tip.
+        assert_eq!(parse_minconf(Some(0), 1, None), Ok(0));
+        assert!(parse_minconf(Some(0), 1, Some(AsOfHeight::BelowTip(1))).is_err());
+        assert_eq!(parse_minconf(None, 1, Some(AsOfHeight::Height(tip))), Ok(1));
+    }
+
+    #[test]
+    fn as_of_height_must_be_scanned() {
+        let height = BlockHeight::from_u32;
+        let check = |h, birthday, fully_scanned| {
+            check_scanned(height(h), birthday.map(height), fully_scanned.map(height))
+                .map_err(|e| e.message().to_owned())
+        };
+
+        assert_eq!(check(150, Some(100), Some(200)), Ok(()));
+        assert_eq!(check(100, Some(100), Some(200)), Ok(()));
+        assert_eq!(check(200, Some(100), Some(200)), Ok(()));
+        assert_eq!(
+            check(99, Some(100), Some(200)),
+            Err(
+                "Can not perform the query as of height 99, which is below the wallet's birthday at height 100"
+                    .into()
+            ),
+        );
+        assert_eq!(
+            check(201, Some(100), Some(200)),
+            Err(
+                "Can not perform the query as of height 201, which is above the wallet's fully-scanned height 200"
+                    .into()
+            ),
+        );
+        assert!(check(150, Some(100), None).is_err());
+    }
+
     #[test]
     fn rpc_format_monetary_values() {
         let format = |v| value_from_zat_balance(ZatBalance::const_from_i64(v)).to_string();
@@ -511,6 +1062,31 @@ mod tests {
         assert_eq!(format(coin / 100000000), "0.00000001");
     }
 
+    #[test]
+    fn rpc_serialize_monetary_values() {
+        let json = |v| {
+            let (zec, zat) = zec_and_zat_balance(ZatBalance::const_from_i64(v));
+            (serde_json::to_string(&zec).unwrap(), zat)
+        };
+
+        // ZEC values are always JSON numbers with exactly 8 decimal places, and are
+        // never written in scientific notation.
+        assert_eq!(json(0), ("0.00000000".into(), 0));
+        assert_eq!(json(1), ("0.00000001".into(), 1));
+        assert_eq!(json(10), ("0.00000010".into(), 10));
+        assert_eq!(json(50000000), ("0.50000000".into(), 50000000));
+        assert_eq!(json(100000000), ("1.00000000".into(), 100000000));
+        assert_eq!(
+            json(2099999999999999),
+            ("20999999.99999999".into(), 2099999999999999)
+        );
+        assert_eq!(json(-1), ("-0.00000001".into(), -1));
+
+        let (zec, zat) = zec_and_zat(Zatoshis::const_from_u64(12345));
+        assert_eq!(serde_json::to_string(&zec).unwrap(), "0.00012345");
+        assert_eq!(zat, 12345);
+    }
+
     #[cfg(zallet_build = "wallet")]
     #[test]
     fn rpc_parse_monetary_values() {
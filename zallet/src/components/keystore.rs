@@ -112,17 +112,23 @@ use std::collections::HashSet;
 use std::fmt;
 use std::fs::File;
 use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
-use bip0039::{English, Mnemonic};
-use rusqlite::named_params;
+use bip0039::{
+    ChineseSimplified, ChineseTraditional, Count, Czech, English, French, Italian, Japanese,
+    Korean, Language, Mnemonic, Portuguese, Spanish,
+};
+use rand::{RngCore, rngs::OsRng};
+use rusqlite::{OptionalExtension, named_params};
 use secrecy::{ExposeSecret, SecretString, SecretVec, Zeroize};
 use tokio::{
-    sync::{Mutex, RwLock},
+    sync::{Mutex, OwnedRwLockReadGuard, RwLock},
     task::JoinHandle,
     time,
 };
+use zcash_protocol::consensus::BlockHeight;
 use zip32::fingerprint::SeedFingerprint;
 
 use crate::network::Network;
@@ -135,33 +141,111 @@ use super::database::Database;
 
 use crate::fl;
 
+use sapling::zip32::{DiversifiableFullViewingKey, ExtendedSpendingKey};
+use zcash_keys::keys::{Era, UnifiedFullViewingKey, UnifiedSpendingKey};
+
 #[cfg(feature = "zcashd-import")]
-use {
-    sapling::zip32::{DiversifiableFullViewingKey, ExtendedSpendingKey},
-    transparent::address::TransparentAddress,
-    zcash_keys::address::Address,
-};
+use {transparent::address::TransparentAddress, zcash_keys::address::Address};
 
 pub(super) mod db;
 
 mod error;
 pub(crate) use error::KeystoreError;
 
+mod passphrase;
+pub(crate) use passphrase::{forget_env_passphrase, startup_passphrase, take_env_passphrase};
+
 type RelockTask = (SystemTime, JoinHandle<()>);
 
+/// Defers the keystore's relock timeout while it is held.
+///
+/// Obtained from [`KeyStore::unlock_guard`].
+pub(crate) struct UnlockGuard {
+    _guard: OwnedRwLockReadGuard<()>,
+}
+
+/// Generates a new 24-word BIP 39 mnemonic phrase.
+pub(crate) fn generate_mnemonic() -> Mnemonic<English> {
+    // Adapted from `Mnemonic::generate` so we can use `OsRng` directly.
+    const BITS_PER_BYTE: usize = 8;
+    const MAX_ENTROPY_BITS: usize = Count::Words24.entropy_bits();
+    const ENTROPY_BYTES: usize = MAX_ENTROPY_BITS / BITS_PER_BYTE;
+
+    let mut entropy = [0u8; ENTROPY_BYTES];
+    OsRng.fill_bytes(&mut entropy);
+
+    Mnemonic::<English>::from_entropy(entropy)
+        .expect("valid entropy length won't fail to generate the mnemonic")
+}
+
+/// Returns the seed derived from the given BIP 39 mnemonic phrase, which may use any of
+/// the BIP 39 wordlists.
+///
+/// The seed only depends on the words of the phrase; the wordlist is needed to check the
+/// phrase's checksum. Mnemonics generated by Zallet are in English, but other languages
+/// can be imported with `zallet import-mnemonic --language`.
+pub(crate) fn mnemonic_to_seed(phrase: &str) -> Result<SecretVec<u8>, bip0039::Error> {
+    fn to_seed<L: Language>(phrase: &str) -> Result<[u8; 64], bip0039::Error> {
+        Mnemonic::<L>::from_phrase(phrase).map(|mnemonic| mnemonic.to_seed(""))
+    }
+    const OTHER_LANGUAGES: [fn(&str) -> Result<[u8; 64], bip0039::Error>; 9] = [
+        to_seed::<ChineseSimplified>,
+        to_seed::<ChineseTraditional>,
+        to_seed::<Czech>,
+        to_seed::<French>,
+        to_seed::<Italian>,
+        to_seed::<Japanese>,
+        to_seed::<Korean>,
+        to_seed::<Portuguese>,
+        to_seed::<Spanish>,
+    ];
+
+    let mut seed_bytes = to_seed::<English>(phrase).or_else(|e| {
+        OTHER_LANGUAGES
+            .iter()
+            .find_map(|to_seed| to_seed(phrase).ok())
+            .ok_or(e)
+    })?;
+    let seed = SecretVec::new(seed_bytes.to_vec());
+    seed_bytes.zeroize();
+
+    Ok(seed)
+}
+
 #[derive(Clone)]
 pub(crate) struct KeyStore {
     db: Database,
 
     /// A ciphertext ostensibly containing encrypted age identities, or `None` if the
     /// keystore is not using runtime-encrypted identities.
-    encrypted_identities: Option<Vec<u8>>,
+    ///
+    /// The ciphertext is replaced when the passphrase is changed.
+    encrypted_identities: Option<Arc<RwLock<Vec<u8>>>>,
+
+    /// The path of the age identity file.
+    identity_path: PathBuf,
 
     /// The in-memory cache of age identities for decrypting key material.
     identities: Arc<RwLock<Vec<Box<dyn age::Identity + Send + Sync>>>>,
 
     /// Task that will re-lock the keystore if it has been temporarily unlocked.
     relock_task: Arc<Mutex<Option<RelockTask>>>,
+
+    /// Held for reading by each [`UnlockGuard`], and for writing while the relock task
+    /// clears the age identities.
+    unlock_guards: Arc<RwLock<()>>,
+
+    /// The seed that legacy ZIP 32 account numbers are resolved against.
+    default_seed_fingerprint: Option<SeedFingerprint>,
+
+    /// Whether new accounts may only be derived from mnemonics that have been backed up.
+    require_backup: bool,
+
+    /// Whether the keystore may be unlocked via `walletpassphrase`.
+    allow_unlock: bool,
+
+    /// The longest duration for which the keystore may be unlocked.
+    max_unlock_duration: Duration,
 }
 
 impl fmt::Debug for KeyStore {
@@ -209,7 +293,7 @@ impl KeyStore {
                             .into());
                     }
 
-                    (Some(identity_data), vec![])
+                    (Some(Arc::new(RwLock::new(identity_data))), vec![])
                 }
                 _ => {
                     identity_data.zeroize();
@@ -243,11 +327,61 @@ impl KeyStore {
         Ok(Self {
             db,
             encrypted_identities,
+            identity_path: path,
             identities: Arc::new(RwLock::new(identities)),
             relock_task: Arc::new(Mutex::new(None)),
+            unlock_guards: Arc::new(RwLock::new(())),
+            default_seed_fingerprint: config.keystore.default_seed_fingerprint,
+            require_backup: config.keystore.require_backup(),
+            allow_unlock: config.keystore.allow_unlock(),
+            max_unlock_duration: config.keystore.max_unlock_duration(),
         })
     }
 
+    /// Creates a keystore for a watch-only wallet, which has no age identities.
+    ///
+    /// Key material cannot be stored in or decrypted from the keystore.
+    pub(crate) fn watch_only(config: &ZalletConfig, db: Database) -> Self {
+        Self {
+            db,
+            encrypted_identities: None,
+            identity_path: config.encryption_identity(),
+            identities: Arc::new(RwLock::new(vec![])),
+            relock_task: Arc::new(Mutex::new(None)),
+            unlock_guards: Arc::new(RwLock::new(())),
+            default_seed_fingerprint: config.keystore.default_seed_fingerprint,
+            require_backup: config.keystore.require_backup(),
+            allow_unlock: config.keystore.allow_unlock(),
+            max_unlock_duration: config.keystore.max_unlock_duration(),
+        }
+    }
+
+    /// Returns the seed that legacy ZIP 32 account numbers are resolved against, if
+    /// `keystore.default_seed_fingerprint` is set.
+    pub(crate) fn default_seed_fingerprint(&self) -> Option<&SeedFingerprint> {
+        self.default_seed_fingerprint.as_ref()
+    }
+
+    /// Returns `true` if new accounts may only be derived from mnemonics that the user has
+    /// confirmed they backed up, as configured by `keystore.require_backup`.
+    pub(crate) fn require_backup(&self) -> bool {
+        self.require_backup
+    }
+
+    /// Returns `true` if the keystore may be unlocked via `walletpassphrase`, as
+    /// configured by `keystore.allow_unlock`.
+    pub(crate) fn allows_unlock(&self) -> bool {
+        self.allow_unlock
+    }
+
+    /// Returns the duration for which [`Self::unlock`] unlocks the keystore when asked to
+    /// unlock it for `timeout` seconds.
+    ///
+    /// This is `timeout` limited to `keystore.max_unlock_duration`.
+    pub(crate) fn unlock_duration(&self, timeout: u64) -> Duration {
+        clamp_unlock_duration(timeout, self.max_unlock_duration)
+    }
+
     /// Returns `true` if the keystore's age identities are runtime-encrypted.
     ///
     /// When this returns `true`, [`Self::is_locked`] must return `false` in order to have
@@ -256,19 +390,66 @@ impl KeyStore {
         self.encrypted_identities.is_some()
     }
 
+    /// Checks that the keystore satisfies `keystore.require_encryption`, if it is set.
+    ///
+    /// When required, wallet encryption must have been initialized, and the age identity
+    /// file must be passphrase-encrypted. The age identity file must also be
+    /// passphrase-encrypted if `keystore.allow_unlock` is disabled, as a plaintext
+    /// identity leaves the wallet permanently unlocked.
+    pub(crate) async fn enforce_required_encryption(
+        &self,
+        config: &ZalletConfig,
+    ) -> Result<(), Error> {
+        if !config.keystore.allow_unlock() && !self.uses_encrypted_identities() {
+            return Err(ErrorKind::Init
+                .context(fl!(
+                    "err-init-unlock-disabled-plaintext-identity",
+                    path = config.encryption_identity().display().to_string(),
+                ))
+                .into());
+        }
+
+        if !config.keystore.require_encryption() {
+            return Ok(());
+        }
+
+        if self.maybe_recipients().await?.is_empty() {
+            return Err(ErrorKind::Init
+                .context(fl!(
+                    "err-init-encryption-required-uninitialized",
+                    init_cmd = format!(
+                        "zallet -d {} init-wallet-encryption",
+                        config.datadir().display()
+                    ),
+                ))
+                .into());
+        }
+
+        if !self.uses_encrypted_identities() {
+            return Err(ErrorKind::Init
+                .context(fl!(
+                    "err-init-encryption-required-plaintext-identity",
+                    path = config.encryption_identity().display().to_string(),
+                ))
+                .into());
+        }
+
+        Ok(())
+    }
+
     /// Returns `true` if the keystore's age identities are not available for decrypting
     /// key material.
     ///
     /// - If [`Self::uses_encrypted_identities`] returns `false`, this always returns
     ///   `true`.
-    /// - If [`Self::uses_encrypted_identities`] returns `true`, this returns `true` when
-    ///   [`Self::unlocked_until`] returns `None`.
+    /// - If [`Self::uses_encrypted_identities`] returns `true`, this returns `true` until
+    ///   the keystore is unlocked with [`Self::unlock`] or [`Self::unlock_until_locked`].
     pub(crate) async fn is_locked(&self) -> bool {
         self.identities.read().await.is_empty()
     }
 
     /// Returns the [`SystemTime`] at which the keystore will re-lock, if it is currently
-    /// unlocked.
+    /// unlocked with a timeout.
     ///
     /// - To unlock the keystore or extend this time, use [`Self::unlock`].
     /// - To re-lock the keystore before this time, use [`Self::lock`].
@@ -285,7 +466,7 @@ impl KeyStore {
         callbacks: C,
     ) -> Result<Option<age::IdentityFile<age::NoCallbacks>>, Error> {
         let encrypted_identities = match &self.encrypted_identities {
-            Some(data) => data,
+            Some(data) => data.read().await,
             // If the keystore isn't encrypted, we don't need to do anything.
             None => return Ok(None),
         };
@@ -306,13 +487,79 @@ impl KeyStore {
 
     /// Unlocks the keystore using the given passphrase.
     ///
-    /// The keystore will be re-locked after `timeout` seconds. Calling this method again
+    /// The keystore will be re-locked after `timeout` seconds, or after
+    /// `keystore.max_unlock_duration` if that is shorter. Calling this method again
     /// before the existing timeout expires will reset the timeout.
     pub(crate) async fn unlock(
         &self,
         passphrase: age::secrecy::SecretString,
         timeout: u64,
     ) -> bool {
+        let decrypted_identities = match self.decrypt_identities(passphrase).await {
+            Some(identities) => identities,
+            None => return false,
+        };
+
+        // If there is an existing relock task, abort it so we don't race while writing
+        // the decrypted identities.
+        let mut relock_task = self.relock_task.lock().await;
+        if let Some((_, existing_timeout)) = relock_task.take() {
+            existing_timeout.abort();
+            // Wait for the task to either finish or abort, to ensure there's zero
+            // possibility of the `decrypted_identities` write below being cleared.
+            let _ = existing_timeout.await;
+        }
+
+        *self.identities.write().await = decrypted_identities;
+
+        // Start a task to relock the keystore after the given timeout. Operations holding
+        // an `UnlockGuard` may still need to decrypt key material, so relocking waits
+        // for them to finish.
+        let duration = self.unlock_duration(timeout);
+        let identities = self.identities.clone();
+        let unlock_guards = self.unlock_guards.clone();
+        *relock_task = Some((
+            SystemTime::now() + duration,
+            crate::spawn!("Keystore relock", async move {
+                time::sleep(duration).await;
+                let _guards = unlock_guards.write().await;
+                identities.write().await.clear();
+            }),
+        ));
+
+        true
+    }
+
+    /// Unlocks the keystore using the given passphrase, until it is locked with
+    /// [`Self::lock`].
+    ///
+    /// This is used to unlock the keystore with a passphrase supplied when Zallet starts,
+    /// so that unattended restarts leave the wallet usable. A later call to
+    /// [`Self::unlock`] replaces this with a timeout.
+    pub(crate) async fn unlock_until_locked(&self, passphrase: age::secrecy::SecretString) -> bool {
+        let decrypted_identities = match self.decrypt_identities(passphrase).await {
+            Some(identities) => identities,
+            None => return false,
+        };
+
+        let mut relock_task = self.relock_task.lock().await;
+        if let Some((_, existing_timeout)) = relock_task.take() {
+            existing_timeout.abort();
+            let _ = existing_timeout.await;
+        }
+
+        *self.identities.write().await = decrypted_identities;
+
+        true
+    }
+
+    /// Decrypts the keystore's age identities using the given passphrase.
+    ///
+    /// Returns `None` if the passphrase is incorrect.
+    async fn decrypt_identities(
+        &self,
+        passphrase: age::secrecy::SecretString,
+    ) -> Option<Vec<Box<dyn age::Identity + Send + Sync>>> {
         // Prepare a callback that only responds to passphrase requests.
         #[derive(Clone)]
         struct PassphraseCallbacks(age::secrecy::SecretString);
@@ -334,38 +581,131 @@ impl KeyStore {
             .await
         {
             Ok(Some(identity_file)) => identity_file,
-            _ => return false,
+            _ => return None,
         };
 
-        let decrypted_identities = match identity_file.into_identities() {
-            Ok(identities) => identities,
-            Err(_) => return false,
+        identity_file.into_identities().ok()
+    }
+
+    /// Returns a guard that defers the relock timeout while it is held, or `None` if the
+    /// keystore is locked.
+    ///
+    /// This is for operations that decrypt key material at several points, which would
+    /// otherwise fail partway through if the timeout expired. It does not prevent
+    /// [`Self::lock`] from locking the keystore immediately.
+    ///
+    /// A task must not request a guard while it holds one: if the timeout has expired in
+    /// the meantime, the request waits for the relock, which waits for the held guard.
+    pub(crate) async fn unlock_guard(&self) -> Option<UnlockGuard> {
+        let guard = self.unlock_guards.clone().read_owned().await;
+        (!self.is_locked().await).then_some(UnlockGuard { _guard: guard })
+    }
+
+    /// Re-encrypts the keystore's age identity file with a new passphrase.
+    ///
+    /// Key material in the database is encrypted to the age recipients of the identity
+    /// file, which are unaffected by its passphrase, so only the identity file itself is
+    /// re-encrypted. The new identity file is written alongside the old one, and then
+    /// moved into its place.
+    ///
+    /// Returns `false` if `old_passphrase` is incorrect.
+    pub(crate) async fn change_passphrase(
+        &self,
+        old_passphrase: age::secrecy::SecretString,
+        new_passphrase: age::secrecy::SecretString,
+    ) -> Result<bool, Error> {
+        // Hold the write lock throughout, so that concurrent changes are serialized.
+        let mut encrypted_identities = self.encrypted_identities()?.write().await;
+
+        let identity_data = match decrypt_identity_data(&encrypted_identities, old_passphrase)? {
+            Some(identity_data) => identity_data,
+            None => return Ok(false),
         };
 
-        // If there is an existing relock task, abort it so we don't race while writing
-        // the decrypted identities.
-        let mut relock_task = self.relock_task.lock().await;
-        if let Some((_, existing_timeout)) = relock_task.take() {
-            existing_timeout.abort();
-            // Wait for the task to either finish or abort, to ensure there's zero
-            // possibility of the `decrypted_identities` write below being cleared.
-            let _ = existing_timeout.await;
+        let ciphertext = encrypt_identity_file(&identity_data, new_passphrase)
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+
+        write_identity_file(&self.identity_path, &ciphertext)
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+        *encrypted_identities = ciphertext;
+
+        Ok(true)
+    }
+
+    /// Returns whether `passphrase` decrypts the keystore's age identity file.
+    pub(crate) async fn check_passphrase(
+        &self,
+        passphrase: age::secrecy::SecretString,
+    ) -> Result<bool, Error> {
+        let encrypted_identities = self.encrypted_identities()?.read().await;
+        Ok(decrypt_identity_data(&encrypted_identities, passphrase)?.is_some())
+    }
+
+    /// Replaces the keystore's passphrase-encrypted age identity file with its plaintext.
+    ///
+    /// As with [`Self::change_passphrase`], key material in the database is unaffected.
+    /// This is only used by `zallet change-wallet-encryption --remove` while Zallet is not
+    /// running; the keystore must not be used afterwards, because it still treats the
+    /// identity file as encrypted.
+    ///
+    /// Returns `false` if `passphrase` is incorrect.
+    pub(crate) async fn remove_passphrase(
+        &self,
+        passphrase: age::secrecy::SecretString,
+    ) -> Result<bool, Error> {
+        let encrypted_identities = self.encrypted_identities()?.write().await;
+
+        let identity_data = match decrypt_identity_data(&encrypted_identities, passphrase)? {
+            Some(identity_data) => identity_data,
+            None => return Ok(false),
+        };
+
+        write_identity_file(&self.identity_path, identity_data.expose_secret())
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+
+        Ok(true)
+    }
+
+    /// Encrypts the keystore's plaintext age identity file with a passphrase.
+    ///
+    /// This is only used by `zallet change-wallet-encryption` while Zallet is not running;
+    /// the keystore must not be used afterwards, because it still treats the identity
+    /// file as plaintext.
+    pub(crate) async fn add_passphrase(
+        &self,
+        passphrase: age::secrecy::SecretString,
+    ) -> Result<(), Error> {
+        if self.uses_encrypted_identities() {
+            return Err(ErrorKind::Generic
+                .context(fl!(
+                    "err-keystore-already-passphrase-encrypted",
+                    path = self.identity_path.display().to_string(),
+                ))
+                .into());
         }
 
-        *self.identities.write().await = decrypted_identities;
+        let mut buf = vec![];
+        let res = File::open(&self.identity_path).and_then(|mut f| f.read_to_end(&mut buf));
+        let identity_data = SecretVec::new(buf);
+        res.map_err(|e| ErrorKind::Generic.context(e))?;
 
-        // Start a task to relock the keystore after the given timeout.
-        let duration = Duration::from_secs(timeout);
-        let identities = self.identities.clone();
-        *relock_task = Some((
-            SystemTime::now() + duration,
-            crate::spawn!("Keystore relock", async move {
-                time::sleep(duration).await;
-                identities.write().await.clear();
-            }),
-        ));
+        let ciphertext = encrypt_identity_file(&identity_data, passphrase)
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+        write_identity_file(&self.identity_path, &ciphertext)
+            .map_err(|e| ErrorKind::Generic.context(e))?;
 
-        true
+        Ok(())
+    }
+
+    fn encrypted_identities(&self) -> Result<&RwLock<Vec<u8>>, Error> {
+        self.encrypted_identities.as_deref().ok_or_else(|| {
+            ErrorKind::Generic
+                .context(fl!(
+                    "err-keystore-not-passphrase-encrypted",
+                    path = self.identity_path.display().to_string(),
+                ))
+                .into()
+        })
     }
 
     /// Clears the in-memory cache of age identities, locking the keystore.
@@ -472,18 +812,7 @@ impl KeyStore {
                 .collect::<Result<_, _>>()
                 .map_err(|e| ErrorKind::Generic.context(e))?;
 
-            // TODO: Replace with a helper with configurable callbacks.
-            let mut stdin_guard = age::cli_common::StdinGuard::new(false);
-            let recipients = age::cli_common::read_recipients(
-                recipient_strings,
-                vec![],
-                vec![],
-                None,
-                &mut stdin_guard,
-            )
-            .map_err(|e| ErrorKind::Generic.context(e))?;
-
-            Ok(recipients)
+            parse_recipients(recipient_strings)
         })
         .await
     }
@@ -532,9 +861,93 @@ impl KeyStore {
         .await
     }
 
-    pub(crate) async fn encrypt_and_store_mnemonic(
+    /// Records that the user has backed up the mnemonic with the given seed fingerprint.
+    pub(crate) async fn confirm_backup(&self, seed_fp: &SeedFingerprint) -> Result<(), Error> {
+        let now = ::time::OffsetDateTime::now_utc();
+
+        self.with_db_mut(|conn, _| {
+            conn.execute(
+                "INSERT INTO ext_zallet_keystore_mnemonic_backups
+                VALUES (:hd_seed_fingerprint, :confirmed)
+                ON CONFLICT (hd_seed_fingerprint) DO NOTHING",
+                named_params! {
+                    ":hd_seed_fingerprint": seed_fp.to_bytes(),
+                    ":confirmed": now,
+                },
+            )
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns whether the user has confirmed that they backed up the mnemonic with the
+    /// given seed fingerprint.
+    pub(crate) async fn is_backup_confirmed(
+        &self,
+        seed_fp: &SeedFingerprint,
+    ) -> Result<bool, Error> {
+        self.with_db(|conn, _| {
+            conn.query_row(
+                "SELECT EXISTS (
+                    SELECT 1
+                    FROM ext_zallet_keystore_mnemonic_backups
+                    WHERE hd_seed_fingerprint = :hd_seed_fingerprint
+                )",
+                named_params! { ":hd_seed_fingerprint": seed_fp.to_bytes() },
+                |row| row.get(0),
+            )
+            .map_err(|e| ErrorKind::Generic.context(e).into())
+        })
+        .await
+    }
+
+    /// Records the birthday height that was given for the mnemonic with the given seed
+    /// fingerprint when it was imported.
+    pub(crate) async fn set_mnemonic_birthday(
+        &self,
+        seed_fp: &SeedFingerprint,
+        birthday_height: BlockHeight,
+    ) -> Result<(), Error> {
+        self.with_db_mut(|conn, _| {
+            conn.execute(
+                "INSERT INTO ext_zallet_keystore_mnemonic_birthdays
+                VALUES (:hd_seed_fingerprint, :birthday_height)
+                ON CONFLICT (hd_seed_fingerprint) DO NOTHING",
+                named_params! {
+                    ":hd_seed_fingerprint": seed_fp.to_bytes(),
+                    ":birthday_height": u32::from(birthday_height),
+                },
+            )
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns the birthday height that was given for the mnemonic with the given seed
+    /// fingerprint when it was imported, if any.
+    pub(crate) async fn mnemonic_birthday(
+        &self,
+        seed_fp: &SeedFingerprint,
+    ) -> Result<Option<BlockHeight>, Error> {
+        self.with_db(|conn, _| {
+            conn.query_row(
+                "SELECT birthday_height
+                FROM ext_zallet_keystore_mnemonic_birthdays
+                WHERE hd_seed_fingerprint = :hd_seed_fingerprint",
+                named_params! { ":hd_seed_fingerprint": seed_fp.to_bytes() },
+                |row| row.get(0).map(BlockHeight::from_u32),
+            )
+            .optional()
+            .map_err(|e| ErrorKind::Generic.context(e).into())
+        })
+        .await
+    }
+
+    pub(crate) async fn encrypt_and_store_mnemonic<L: Language>(
         &self,
-        mnemonic: Mnemonic,
+        mnemonic: Mnemonic<L>,
     ) -> Result<SeedFingerprint, Error> {
         let recipients = self.recipients().await?;
 
@@ -599,7 +1012,6 @@ impl KeyStore {
         Ok(legacy_seed_fp)
     }
 
-    #[cfg(feature = "zcashd-import")]
     pub(crate) async fn encrypt_and_store_standalone_sapling_key(
         &self,
         sapling_key: &ExtendedSpendingKey,
@@ -628,16 +1040,45 @@ impl KeyStore {
         Ok(dfvk)
     }
 
+    pub(crate) async fn encrypt_and_store_standalone_unified_key(
+        &self,
+        usk: &UnifiedSpendingKey,
+    ) -> Result<UnifiedFullViewingKey, Error> {
+        let recipients = self.recipients().await?;
+
+        let ufvk = usk.to_unified_full_viewing_key();
+        let encrypted_usk = encrypt_standalone_unified_key(&recipients, usk)
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+
+        self.with_db_mut(|conn, network| {
+            conn.execute(
+                "INSERT INTO ext_zallet_keystore_standalone_unified_keys
+                VALUES (:ufvk, :encrypted_usk)
+                ON CONFLICT (ufvk) DO NOTHING ",
+                named_params! {
+                    ":ufvk": ufvk.encode(network),
+                    ":encrypted_usk": encrypted_usk,
+                },
+            )
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+            Ok(())
+        })
+        .await?;
+
+        Ok(ufvk)
+    }
+
     #[cfg(feature = "zcashd-import")]
     pub(crate) async fn encrypt_and_store_standalone_transparent_key(
         &self,
-        key: &zcash_keys::keys::transparent::Key,
-    ) -> Result<(), Error> {
+        key: &secp256k1::SecretKey,
+    ) -> Result<secp256k1::PublicKey, Error> {
         let recipients = self.recipients().await?;
 
-        let encrypted_transparent_key =
-            encrypt_standalone_transparent_privkey(&recipients, key.secret())
-                .map_err(|e| ErrorKind::Generic.context(e))?;
+        let pubkey =
+            secp256k1::PublicKey::from_secret_key(&secp256k1::Secp256k1::signing_only(), key);
+        let encrypted_transparent_key = encrypt_standalone_transparent_privkey(&recipients, key)
+            .map_err(|e| ErrorKind::Generic.context(e))?;
 
         self.with_db_mut(|conn, _| {
             conn.execute(
@@ -645,7 +1086,7 @@ impl KeyStore {
                 VALUES (:pubkey, :encrypted_key_bytes)
                 ON CONFLICT (pubkey) DO NOTHING ",
                 named_params! {
-                    ":pubkey": &key.pubkey().serialize(),
+                    ":pubkey": &pubkey.serialize(),
                     ":encrypted_key_bytes": encrypted_transparent_key,
                 },
             )
@@ -654,7 +1095,7 @@ impl KeyStore {
         })
         .await?;
 
-        Ok(())
+        Ok(pubkey)
     }
 
     /// Decrypts the mnemonic phrase corresponding to the given seed fingerprint.
@@ -662,7 +1103,7 @@ impl KeyStore {
         // Acquire a read lock on the identities for decryption.
         let identities = self.identities.read().await;
         if identities.is_empty() {
-            return Err(ErrorKind::Generic.context(fl!("err-wallet-locked")).into());
+            return Err(ErrorKind::WalletLocked.into());
         }
 
         let encrypted_mnemonic = self
@@ -692,13 +1133,7 @@ impl KeyStore {
     ) -> Result<SecretVec<u8>, Error> {
         let mnemonic = self.decrypt_mnemonic(seed_fp).await?;
 
-        let mut seed_bytes = Mnemonic::<English>::from_phrase(mnemonic.expose_secret())
-            .map_err(|e| ErrorKind::Generic.context(e))?
-            .to_seed("");
-        let seed = SecretVec::new(seed_bytes.to_vec());
-        seed_bytes.zeroize();
-
-        Ok(seed)
+        mnemonic_to_seed(mnemonic.expose_secret()).map_err(|e| ErrorKind::Generic.context(e).into())
     }
 
     /// Exports the mnemonic phrase corresponding to the given seed fingerprint.
@@ -725,6 +1160,197 @@ impl KeyStore {
         Ok(encrypted_mnemonic)
     }
 
+    /// Encrypts a wallet export to the given age recipients, or to the wallet's own
+    /// recipients if `recipients` is `None`.
+    ///
+    /// The export is ASCII-armored, so that it remains a text file.
+    pub(crate) async fn encrypt_export(
+        &self,
+        recipients: Option<Vec<Box<dyn age::Recipient + Send>>>,
+        export: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let recipients = match recipients {
+            Some(recipients) => recipients,
+            None => self.recipients().await?,
+        };
+
+        encrypt_string(&recipients, export, age::armor::Format::AsciiArmor)
+            .map_err(|e| ErrorKind::Generic.context(e).into())
+    }
+
+    /// Decrypts a wallet export that was encrypted to the wallet's age identity.
+    pub(crate) async fn decrypt_export(&self, ciphertext: &[u8]) -> Result<SecretString, Error> {
+        // Acquire a read lock on the identities for decryption.
+        let identities = self.identities.read().await;
+        if identities.is_empty() {
+            return Err(ErrorKind::WalletLocked.into());
+        }
+
+        decrypt_string(&identities, ciphertext).map_err(|e| ErrorKind::Generic.context(e).into())
+    }
+
+    /// Decrypts the mnemonic phrase of every seed in the keystore.
+    ///
+    /// This is only used to export the wallet in plaintext; prefer [`Self::export_mnemonic`]
+    /// for backing up individual seeds.
+    pub(crate) async fn decrypt_mnemonics(
+        &self,
+    ) -> Result<Vec<(SeedFingerprint, SecretString)>, Error> {
+        let mut mnemonics = vec![];
+        for seed_fp in self.list_seed_fingerprints().await? {
+            let mnemonic = self.decrypt_mnemonic(&seed_fp).await?;
+            mnemonics.push((seed_fp, mnemonic));
+        }
+        Ok(mnemonics)
+    }
+
+    /// Decrypts every standalone transparent secret key in the keystore.
+    #[cfg(feature = "transparent-key-import")]
+    pub(crate) async fn decrypt_standalone_transparent_keys(
+        &self,
+    ) -> Result<Vec<secp256k1::SecretKey>, Error> {
+        // Acquire a read lock on the identities for decryption.
+        let identities = self.identities.read().await;
+        if identities.is_empty() {
+            return Err(ErrorKind::WalletLocked.into());
+        }
+
+        let encrypted_keys = self
+            .with_db(|conn, _| {
+                let mut stmt = conn
+                    .prepare(
+                        "SELECT encrypted_transparent_privkey
+                        FROM ext_zallet_keystore_standalone_transparent_keys",
+                    )
+                    .map_err(|e| ErrorKind::Generic.context(e))?;
+
+                let rows = stmt
+                    .query_map([], |row| row.get::<_, Vec<u8>>(0))
+                    .map_err(|e| ErrorKind::Generic.context(e))?;
+
+                Ok(rows
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| ErrorKind::Generic.context(e))?)
+            })
+            .await?;
+
+        encrypted_keys
+            .iter()
+            .map(|ciphertext| decrypt_standalone_transparent_privkey(&identities, ciphertext))
+            .collect()
+    }
+
+    /// Decrypts every standalone Sapling spending key in the keystore.
+    pub(crate) async fn decrypt_standalone_sapling_keys(
+        &self,
+    ) -> Result<Vec<ExtendedSpendingKey>, Error> {
+        // Acquire a read lock on the identities for decryption.
+        let identities = self.identities.read().await;
+        if identities.is_empty() {
+            return Err(ErrorKind::WalletLocked.into());
+        }
+
+        let encrypted_keys = self
+            .with_db(|conn, _| {
+                let mut stmt = conn
+                    .prepare(
+                        "SELECT encrypted_sapling_extsk
+                        FROM ext_zallet_keystore_standalone_sapling_keys",
+                    )
+                    .map_err(|e| ErrorKind::Generic.context(e))?;
+
+                let rows = stmt
+                    .query_map([], |row| row.get::<_, Vec<u8>>(0))
+                    .map_err(|e| ErrorKind::Generic.context(e))?;
+
+                Ok(rows
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| ErrorKind::Generic.context(e))?)
+            })
+            .await?;
+
+        encrypted_keys
+            .iter()
+            .map(|ciphertext| {
+                let key_bytes = decrypt_secret(&identities, ciphertext)?;
+                ExtendedSpendingKey::from_bytes(key_bytes.expose_secret())
+                    // The error type does not implement `Debug`.
+                    .map_err(|_| {
+                        ErrorKind::Generic
+                            .context(String::from("Invalid Sapling spending key"))
+                            .into()
+                    })
+            })
+            .collect()
+    }
+
+    /// Decrypts every standalone unified spending key in the keystore.
+    pub(crate) async fn decrypt_standalone_unified_keys(
+        &self,
+    ) -> Result<Vec<UnifiedSpendingKey>, Error> {
+        // Acquire a read lock on the identities for decryption.
+        let identities = self.identities.read().await;
+        if identities.is_empty() {
+            return Err(ErrorKind::WalletLocked.into());
+        }
+
+        let encrypted_keys = self
+            .with_db(|conn, _| {
+                let mut stmt = conn
+                    .prepare(
+                        "SELECT encrypted_usk
+                        FROM ext_zallet_keystore_standalone_unified_keys",
+                    )
+                    .map_err(|e| ErrorKind::Generic.context(e))?;
+
+                let rows = stmt
+                    .query_map([], |row| row.get::<_, Vec<u8>>(0))
+                    .map_err(|e| ErrorKind::Generic.context(e))?;
+
+                Ok(rows
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| ErrorKind::Generic.context(e))?)
+            })
+            .await?;
+
+        encrypted_keys
+            .iter()
+            .map(|ciphertext| decrypt_standalone_unified_key(&identities, ciphertext))
+            .collect()
+    }
+
+    /// Decrypts the standalone unified spending key with the given UFVK, if the keystore
+    /// contains it.
+    pub(crate) async fn decrypt_standalone_unified_key(
+        &self,
+        ufvk: &UnifiedFullViewingKey,
+    ) -> Result<Option<UnifiedSpendingKey>, Error> {
+        // Acquire a read lock on the identities for decryption.
+        let identities = self.identities.read().await;
+        if identities.is_empty() {
+            return Err(ErrorKind::WalletLocked.into());
+        }
+
+        let encrypted_usk = self
+            .with_db(|conn, network| {
+                Ok(conn
+                    .query_row(
+                        "SELECT encrypted_usk
+                        FROM ext_zallet_keystore_standalone_unified_keys
+                        WHERE ufvk = :ufvk",
+                        named_params! {":ufvk": ufvk.encode(network)},
+                        |row| row.get::<_, Vec<u8>>(0),
+                    )
+                    .optional()
+                    .map_err(|e| ErrorKind::Generic.context(e))?)
+            })
+            .await?;
+
+        encrypted_usk
+            .map(|ciphertext| decrypt_standalone_unified_key(&identities, &ciphertext))
+            .transpose()
+    }
+
     #[cfg(feature = "zcashd-import")]
     pub(crate) async fn decrypt_standalone_transparent_key(
         &self,
@@ -733,7 +1359,7 @@ impl KeyStore {
         // Acquire a read lock on the identities for decryption.
         let identities = self.identities.read().await;
         if identities.is_empty() {
-            return Err(ErrorKind::Generic.context(fl!("err-wallet-locked")).into());
+            return Err(ErrorKind::WalletLocked.into());
         }
 
         let encrypted_key_bytes = self
@@ -762,6 +1388,88 @@ impl KeyStore {
     }
 }
 
+/// Limits a requested unlock timeout of `timeout` seconds to `max`.
+fn clamp_unlock_duration(timeout: u64, max: Duration) -> Duration {
+    Duration::from_secs(timeout).min(max)
+}
+
+fn encrypt_identity_file(
+    identity_data: &SecretVec<u8>,
+    passphrase: age::secrecy::SecretString,
+) -> Result<Vec<u8>, age::EncryptError> {
+    let encryptor = age::Encryptor::with_user_passphrase(passphrase);
+
+    let mut ciphertext = vec![];
+    let mut writer = encryptor.wrap_output(age::armor::ArmoredWriter::wrap_output(
+        &mut ciphertext,
+        age::armor::Format::AsciiArmor,
+    )?)?;
+    writer.write_all(identity_data.expose_secret())?;
+    writer.finish()?.finish()?;
+
+    Ok(ciphertext)
+}
+
+/// Decrypts a passphrase-encrypted age identity file.
+///
+/// Returns `None` if `passphrase` is incorrect.
+fn decrypt_identity_data(
+    encrypted_identities: &[u8],
+    passphrase: age::secrecy::SecretString,
+) -> Result<Option<SecretVec<u8>>, Error> {
+    let decryptor =
+        age::Decryptor::new_buffered(age::armor::ArmoredReader::new(encrypted_identities))
+            .expect("validated on start");
+
+    let identity = age::scrypt::Identity::new(passphrase);
+    let mut reader = match decryptor.decrypt(std::iter::once(&identity as _)) {
+        Ok(reader) => reader,
+        Err(_) => return Ok(None),
+    };
+
+    // As in `decrypt_string`, the buffer is owned by a `SecretVec` before any error is
+    // raised, so that a partial read is zeroized.
+    let mut buf = Vec::with_capacity(encrypted_identities.len());
+    let res = reader.read_to_end(&mut buf);
+    let identity_data = SecretVec::new(buf);
+    res.map_err(|e| ErrorKind::Generic.context(e))?;
+
+    Ok(Some(identity_data))
+}
+
+/// Replaces the identity file at `path` with `contents`.
+///
+/// The file is written to a temporary path first, so that a failure cannot leave the
+/// wallet without a usable identity file.
+fn write_identity_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".new");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)
+}
+
+/// Parses the given age recipients.
+pub(crate) fn parse_recipients(
+    recipient_strings: Vec<String>,
+) -> Result<Vec<Box<dyn age::Recipient + Send>>, Error> {
+    // TODO: Replace with a helper with configurable callbacks.
+    let mut stdin_guard = age::cli_common::StdinGuard::new(false);
+    let recipients =
+        age::cli_common::read_recipients(recipient_strings, vec![], vec![], None, &mut stdin_guard)
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+
+    Ok(recipients)
+}
+
 fn encrypt_string(
     recipients: &[Box<dyn age::Recipient + Send>],
     plaintext: &str,
@@ -784,7 +1492,8 @@ fn decrypt_string(
     identities: &[Box<dyn age::Identity + Send + Sync>],
     ciphertext: &[u8],
 ) -> Result<SecretString, age::DecryptError> {
-    let decryptor = age::Decryptor::new(ciphertext)?;
+    // Wallet exports are ASCII-armored, while secrets in the keystore are not.
+    let decryptor = age::Decryptor::new_buffered(age::armor::ArmoredReader::new(ciphertext))?;
 
     // The plaintext is always shorter than the ciphertext. Over-allocating the initial
     // string ensures that no internal re-allocations occur that might leave plaintext
@@ -805,7 +1514,6 @@ fn decrypt_string(
     Ok(mnemonic)
 }
 
-#[cfg(any(feature = "transparent-key-import", feature = "zcashd-import"))]
 fn encrypt_secret(
     recipients: &[Box<dyn age::Recipient + Send>],
     secret: &SecretVec<u8>,
@@ -828,7 +1536,6 @@ fn encrypt_legacy_seed_bytes(
     encrypt_secret(recipients, seed)
 }
 
-#[cfg(feature = "zcashd-import")]
 fn encrypt_standalone_sapling_key(
     recipients: &[Box<dyn age::Recipient + Send>],
     key: &ExtendedSpendingKey,
@@ -837,6 +1544,14 @@ fn encrypt_standalone_sapling_key(
     encrypt_secret(recipients, &secret)
 }
 
+fn encrypt_standalone_unified_key(
+    recipients: &[Box<dyn age::Recipient + Send>],
+    usk: &UnifiedSpendingKey,
+) -> Result<Vec<u8>, age::EncryptError> {
+    let secret = SecretVec::new(usk.to_bytes(Era::Orchard));
+    encrypt_secret(recipients, &secret)
+}
+
 #[cfg(feature = "transparent-key-import")]
 fn encrypt_standalone_transparent_privkey(
     recipients: &[Box<dyn age::Recipient + Send>],
@@ -846,11 +1561,10 @@ fn encrypt_standalone_transparent_privkey(
     encrypt_secret(recipients, &secret)
 }
 
-#[cfg(feature = "transparent-key-import")]
-fn decrypt_standalone_transparent_privkey(
+fn decrypt_secret(
     identities: &[Box<dyn age::Identity + Send + Sync>],
     ciphertext: &[u8],
-) -> Result<secp256k1::SecretKey, Error> {
+) -> Result<SecretVec<u8>, Error> {
     let decryptor = age::Decryptor::new(ciphertext).map_err(|e| ErrorKind::Generic.context(e))?;
 
     // The plaintext is always shorter than the ciphertext. Over-allocating the initial
@@ -869,8 +1583,212 @@ fn decrypt_standalone_transparent_privkey(
     // zeroed out when we raise the error on the following line.
     let buf_secret = SecretVec::new(buf);
     res.map_err(|e| ErrorKind::Generic.context(e))?;
+
+    Ok(buf_secret)
+}
+
+fn decrypt_standalone_unified_key(
+    identities: &[Box<dyn age::Identity + Send + Sync>],
+    ciphertext: &[u8],
+) -> Result<UnifiedSpendingKey, Error> {
+    let buf_secret = decrypt_secret(identities, ciphertext)?;
+    UnifiedSpendingKey::from_bytes(Era::Orchard, buf_secret.expose_secret())
+        // The error type does not implement `Display`.
+        .map_err(|_| {
+            ErrorKind::Generic
+                .context(String::from("Invalid unified spending key"))
+                .into()
+        })
+}
+
+#[cfg(feature = "transparent-key-import")]
+fn decrypt_standalone_transparent_privkey(
+    identities: &[Box<dyn age::Identity + Send + Sync>],
+    ciphertext: &[u8],
+) -> Result<secp256k1::SecretKey, Error> {
+    let buf_secret = decrypt_secret(identities, ciphertext)?;
     let secret_key = secp256k1::SecretKey::from_slice(buf_secret.expose_secret())
         .map_err(|e| ErrorKind::Generic.context(e))?;
 
     Ok(secret_key)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use age::secrecy::{ExposeSecret as _, SecretString};
+    use bip0039::{English, Japanese, Mnemonic};
+    use secrecy::ExposeSecret;
+
+    use crate::{components::database::Database, config::ZalletConfig};
+
+    use zip32::fingerprint::SeedFingerprint;
+
+    use super::{KeyStore, clamp_unlock_duration, generate_mnemonic, mnemonic_to_seed};
+
+    /// Creates a wallet in `dir` with a plaintext age identity file, returning its config
+    /// and the identity's recipient.
+    fn plaintext_wallet(dir: &Path) -> (ZalletConfig, String) {
+        let mut config = ZalletConfig::default();
+        config.datadir = Some(dir.to_path_buf());
+
+        let identity = age::x25519::Identity::generate();
+        std::fs::write(
+            config.encryption_identity(),
+            format!("{}\n", identity.to_string().expose_secret()),
+        )
+        .unwrap();
+
+        (config, identity.to_public().to_string())
+    }
+
+    async fn open_keystore(config: &ZalletConfig) -> KeyStore {
+        let db = Database::open(config).await.unwrap();
+        KeyStore::new(config, db).unwrap()
+    }
+
+    /// Creates a wallet in `dir` with a passphrase-encrypted age identity file, returning
+    /// its config and keystore, and the fingerprint of a mnemonic stored in it.
+    async fn encrypted_wallet(
+        dir: &Path,
+        passphrase: &str,
+    ) -> (ZalletConfig, KeyStore, SeedFingerprint) {
+        let (config, recipient) = plaintext_wallet(dir);
+        let keystore = open_keystore(&config).await;
+        keystore
+            .initialize_recipients(vec![recipient])
+            .await
+            .unwrap();
+        let seed_fp = keystore
+            .encrypt_and_store_mnemonic(generate_mnemonic())
+            .await
+            .unwrap();
+        keystore
+            .add_passphrase(SecretString::from(passphrase))
+            .await
+            .unwrap();
+
+        // The keystore only uses the encrypted identity file once it is reopened.
+        let keystore = open_keystore(&config).await;
+        assert!(keystore.uses_encrypted_identities());
+        (config, keystore, seed_fp)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn required_encryption_is_enforced() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut config, recipient) = plaintext_wallet(dir.path());
+        let keystore = open_keystore(&config).await;
+
+        // Nothing is required by default.
+        keystore.enforce_required_encryption(&config).await.unwrap();
+
+        // Wallet encryption must have been initialized.
+        config.keystore.require_encryption = Some(true);
+        assert!(keystore.enforce_required_encryption(&config).await.is_err());
+
+        // The identity file must be passphrase-encrypted.
+        keystore
+            .initialize_recipients(vec![recipient])
+            .await
+            .unwrap();
+        assert!(keystore.enforce_required_encryption(&config).await.is_err());
+
+        // A plaintext identity is also rejected if the wallet cannot be unlocked.
+        config.keystore.require_encryption = None;
+        config.keystore.allow_unlock = Some(false);
+        assert!(keystore.enforce_required_encryption(&config).await.is_err());
+
+        keystore
+            .add_passphrase(SecretString::from("passphrase"))
+            .await
+            .unwrap();
+        let keystore = open_keystore(&config).await;
+        assert!(keystore.uses_encrypted_identities());
+        keystore.enforce_required_encryption(&config).await.unwrap();
+        config.keystore.require_encryption = Some(true);
+        keystore.enforce_required_encryption(&config).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn relock_waits_for_unlock_guards() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, keystore, seed_fp) = encrypted_wallet(dir.path(), "passphrase").await;
+        assert!(keystore.is_locked().await);
+        assert!(keystore.unlock_guard().await.is_none());
+
+        assert!(keystore.unlock(SecretString::from("passphrase"), 1).await);
+        let guard = keystore.unlock_guard().await.unwrap();
+
+        // The timeout expires while the guard is held, so the keystore stays unlocked.
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(!keystore.is_locked().await);
+        keystore.decrypt_seed(&seed_fp).await.unwrap();
+
+        // Once the guard is dropped, the keystore is relocked.
+        drop(guard);
+        let (_, relock) = keystore.relock_task.lock().await.take().unwrap();
+        relock.await.unwrap();
+        assert!(keystore.is_locked().await);
+        assert!(keystore.decrypt_seed(&seed_fp).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn passphrase_change_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, keystore, seed_fp) = encrypted_wallet(dir.path(), "old").await;
+        let old = || SecretString::from("old");
+        let new = || SecretString::from("new");
+
+        assert!(!keystore.change_passphrase(new(), old()).await.unwrap());
+        assert!(keystore.change_passphrase(old(), new()).await.unwrap());
+        assert!(keystore.check_passphrase(new()).await.unwrap());
+        assert!(!keystore.check_passphrase(old()).await.unwrap());
+        assert!(!keystore.unlock(old(), 60).await);
+        assert!(keystore.unlock(new(), 60).await);
+        keystore.decrypt_seed(&seed_fp).await.unwrap();
+
+        // The new passphrase is persisted, and changing it back restores the original.
+        let keystore = open_keystore(&config).await;
+        assert!(!keystore.unlock(old(), 60).await);
+        assert!(keystore.change_passphrase(new(), old()).await.unwrap());
+        let keystore = open_keystore(&config).await;
+        assert!(keystore.unlock(old(), 60).await);
+        keystore.decrypt_seed(&seed_fp).await.unwrap();
+    }
+
+    #[test]
+    fn unlock_duration_is_clamped() {
+        let max = Duration::from_secs(3600);
+        assert_eq!(clamp_unlock_duration(0, max), Duration::ZERO);
+        assert_eq!(clamp_unlock_duration(60, max), Duration::from_secs(60));
+        assert_eq!(clamp_unlock_duration(3600, max), max);
+        assert_eq!(clamp_unlock_duration(3601, max), max);
+        assert_eq!(clamp_unlock_duration(u64::MAX, max), max);
+    }
+
+    #[test]
+    fn mnemonic_seeds_in_any_language() {
+        let english = Mnemonic::<English>::from_entropy([7; 32]).unwrap();
+        let japanese = Mnemonic::<Japanese>::from_entropy([7; 32]).unwrap();
+
+        for (phrase, seed) in [
+            (english.phrase(), english.to_seed("")),
+            (japanese.phrase(), japanese.to_seed("")),
+        ] {
+            assert_eq!(
+                mnemonic_to_seed(phrase).unwrap().expose_secret(),
+                &seed.to_vec()
+            );
+        }
+
+        let (last, rest) = english.phrase().rsplit_once(' ').unwrap();
+        let bad_checksum = format!(
+            "{rest} {}",
+            if last == "abandon" { "zoo" } else { "abandon" }
+        );
+        assert!(mnemonic_to_seed(&bad_checksum).is_err());
+    }
+}
@@ -53,6 +53,46 @@ CREATE TABLE ext_zallet_keystore_mnemonics (
 )
 "#;
 
+/// Records which mnemonic seed phrases the user has confirmed that they backed up.
+///
+/// While `keystore.require_backup` is enabled, new accounts can only be derived from
+/// mnemonics that are listed here.
+///
+/// ### Columns
+///
+/// - `hd_seed_fingerprint` is the [ZIP 32 fingerprint] for the seed derived from a
+///   mnemonic in `ext_zallet_keystore_mnemonics`.
+/// - `confirmed`: The time at which the backup was confirmed, as a string in the format
+///   `yyyy-MM-dd HH:mm:ss.fffffffzzz`.
+///
+/// [ZIP 32 fingerprint]: https://zips.z.cash/zip-0032#seed-fingerprints
+pub(crate) const TABLE_MNEMONIC_BACKUPS: &str = r#"
+CREATE TABLE ext_zallet_keystore_mnemonic_backups (
+    hd_seed_fingerprint BLOB NOT NULL UNIQUE,
+    confirmed TEXT NOT NULL
+)
+"#;
+
+/// Records the birthday heights that were given for imported mnemonic seed phrases.
+///
+/// Accounts derived from these mnemonics with `z_getnewaccount` are given this birthday,
+/// so that the wallet scans the chain for their existing transactions.
+///
+/// ### Columns
+///
+/// - `hd_seed_fingerprint` is the [ZIP 32 fingerprint] for the seed derived from a
+///   mnemonic in `ext_zallet_keystore_mnemonics`.
+/// - `birthday_height` is the height of the first block that may contain transactions
+///   involving the seed's accounts.
+///
+/// [ZIP 32 fingerprint]: https://zips.z.cash/zip-0032#seed-fingerprints
+pub(crate) const TABLE_MNEMONIC_BIRTHDAYS: &str = r#"
+CREATE TABLE ext_zallet_keystore_mnemonic_birthdays (
+    hd_seed_fingerprint BLOB NOT NULL UNIQUE,
+    birthday_height INTEGER NOT NULL
+)
+"#;
+
 /// Stores encrypted raw HD seeds. These are likely to only be produced via `zcashd` wallet import.
 ///
 /// ### Columns
@@ -108,3 +148,22 @@ CREATE TABLE ext_zallet_keystore_standalone_transparent_keys (
     encrypted_transparent_privkey BLOB NOT NULL
 )
 "#;
+
+/// Stores encrypted standalone unified spending keys.
+///
+/// ### Columns
+///
+/// - `ufvk` is the encoding of the [`UnifiedFullViewingKey`] derived from the spending
+///   key.
+/// - `encrypted_usk` is the [`UnifiedSpendingKey`] in its binary serialization, in an
+///   [age encrypted file].
+///
+/// [`UnifiedFullViewingKey`]: zcash_keys::keys::UnifiedFullViewingKey
+/// [`UnifiedSpendingKey`]: zcash_keys::keys::UnifiedSpendingKey
+/// [age encrypted file]: https://c2sp.org/age#encrypted-file-format
+pub(crate) const TABLE_STANDALONE_UNIFIED_KEYS: &str = r#"
+CREATE TABLE ext_zallet_keystore_standalone_unified_keys (
+    ufvk TEXT NOT NULL UNIQUE,
+    encrypted_usk BLOB NOT NULL
+)
+"#;
@@ -2,12 +2,21 @@ use schemerz_rusqlite::RusqliteMigration;
 use zcash_client_sqlite::wallet::init::WalletMigrationError;
 
 mod initial_setup;
+mod mnemonic_backups;
+mod mnemonic_birthdays;
+mod standalone_unified_keys;
 
 pub(in crate::components) fn all()
 -> impl Iterator<Item = Box<dyn RusqliteMigration<Error = WalletMigrationError>>> {
     [
         // initial_setup
         Box::new(initial_setup::Migration {}) as _,
+        // mnemonic_backups
+        Box::new(mnemonic_backups::Migration) as _,
+        // standalone_unified_keys
+        Box::new(standalone_unified_keys::Migration) as _,
+        // mnemonic_birthdays
+        Box::new(mnemonic_birthdays::Migration) as _,
     ]
     .into_iter()
 }
@@ -0,0 +1,57 @@
+use std::collections::HashSet;
+
+use rusqlite::named_params;
+use schemerz_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_client_sqlite::wallet::init::WalletMigrationError;
+
+use super::initial_setup;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x4e1b08df_78ca_4b5c_943d_0068b8e33a71);
+
+pub(super) struct Migration;
+
+impl schemerz::Migration<Uuid> for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [initial_setup::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Records which mnemonic seed phrases have had their backups confirmed."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch(
+            "CREATE TABLE ext_zallet_keystore_mnemonic_backups (
+                hd_seed_fingerprint BLOB NOT NULL UNIQUE,
+                confirmed TEXT NOT NULL
+            );",
+        )?;
+
+        // Wallets created before backups were tracked could already be used to generate
+        // new accounts, so we treat their existing mnemonics as backed up rather than
+        // blocking them on upgrade.
+        transaction.execute(
+            "INSERT INTO ext_zallet_keystore_mnemonic_backups
+            SELECT hd_seed_fingerprint, :confirmed
+            FROM ext_zallet_keystore_mnemonics",
+            named_params! {
+                ":confirmed": ::time::OffsetDateTime::now_utc(),
+            },
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+//! Supplying the wallet passphrase when Zallet starts.
+//!
+//! An encrypted wallet is normally unlocked with the `walletpassphrase` JSON-RPC method,
+//! which requires someone to be around whenever Zallet restarts. `zallet start` can
+//! instead obtain the passphrase itself, from (in order of precedence):
+//!
+//! - `keystore.passphrase_file` (or `--passphrase-file`), which may be a regular file, a
+//!   named pipe, or a file descriptor such as `/dev/fd/3`.
+//! - The [`PASSPHRASE_ENV_VAR`] environment variable, if `keystore.passphrase_from_env`
+//!   is enabled.
+//! - An interactive prompt, if stdin is a terminal.
+//!
+//! [`PASSPHRASE_ENV_VAR`] is removed from Zallet's environment as soon as Zallet starts,
+//! so that it is not inherited by the commands that Zallet runs.
+
+use std::fs::File;
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
+use std::sync::Mutex;
+
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::{
+    config::ZalletConfig,
+    error::{Error, ErrorKind, ExitCode},
+    fl,
+};
+
+/// The environment variable that the wallet passphrase is read from, if
+/// `keystore.passphrase_from_env` is enabled.
+pub(crate) const PASSPHRASE_ENV_VAR: &str = "ZALLET_WALLET_PASSPHRASE";
+
+/// The largest passphrase file that is read.
+///
+/// This bounds the buffer that the passphrase is read into, so that it is never
+/// reallocated (which could leave copies of the passphrase strewn around the heap).
+const MAX_PASSPHRASE_LEN: u64 = 4096;
+
+/// The value of [`PASSPHRASE_ENV_VAR`] when Zallet started, or `Err(())` if it was not
+/// valid Unicode, until [`forget_env_passphrase`] is called.
+static ENV_PASSPHRASE: Mutex<Result<Option<SecretString>, ()>> = Mutex::new(Ok(None));
+
+/// Removes [`PASSPHRASE_ENV_VAR`] from the environment, keeping its value for
+/// [`startup_passphrase`].
+///
+/// This must be called before Zallet spawns any threads, as changing the environment
+/// while another thread reads it is undefined behaviour.
+pub(crate) fn take_env_passphrase() {
+    let passphrase = match std::env::var(PASSPHRASE_ENV_VAR) {
+        Ok(passphrase) => Ok(Some(SecretString::new(passphrase))),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(std::env::VarError::NotUnicode(_)) => Err(()),
+    };
+    if !matches!(passphrase, Ok(None)) {
+        // SAFETY: This is called from `application::boot`, before the Tokio runtime (or
+        // anything else that spawns threads) is started.
+        #[allow(unsafe_code)]
+        unsafe {
+            std::env::remove_var(PASSPHRASE_ENV_VAR)
+        };
+    }
+    *ENV_PASSPHRASE.lock().expect("not poisoned") = passphrase;
+}
+
+/// Erases the passphrase taken from [`PASSPHRASE_ENV_VAR`], once the wallets that
+/// `zallet start` unlocks have been unlocked.
+pub(crate) fn forget_env_passphrase() {
+    *ENV_PASSPHRASE.lock().expect("not poisoned") = Ok(None);
+}
+
+/// Obtains the passphrase that `zallet start` unlocks encrypted wallets with.
+///
+/// Returns `None` if no source is configured and stdin is not a terminal, or if the user
+/// enters an empty passphrase at the prompt. The wallets then start locked.
+pub(crate) fn startup_passphrase(config: &ZalletConfig) -> Result<Option<SecretString>, Error> {
+    if let Some(path) = config.passphrase_file() {
+        read_passphrase_file(&path).map(Some)
+    } else if config.keystore.passphrase_from_env() {
+        ENV_PASSPHRASE
+            .lock()
+            .expect("not poisoned")
+            .clone()
+            .map_err(|()| {
+                keystore_error(fl!(
+                    "err-keystore-passphrase-env-unreadable",
+                    var = PASSPHRASE_ENV_VAR,
+                ))
+            })
+    } else if io::stdin().is_terminal() {
+        let passphrase = rpassword::prompt_password(fl!("cmd-start-passphrase-prompt"))
+            .map(SecretString::new)
+            .map_err(|e| ErrorKind::Generic.context(e))?;
+        Ok((!passphrase.expose_secret().is_empty()).then_some(passphrase))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Reads the passphrase from the file at `path`, without any trailing newline.
+///
+/// Files longer than [`MAX_PASSPHRASE_LEN`] are rejected rather than truncated, as a
+/// truncated passphrase would unlock nothing.
+fn read_passphrase_file(path: &Path) -> Result<SecretString, Error> {
+    let unreadable = |e: io::Error| {
+        keystore_error(fl!(
+            "err-keystore-passphrase-file-unreadable",
+            path = path.display().to_string(),
+            error = e.to_string(),
+        ))
+    };
+
+    let file = File::open(path).map_err(unreadable)?;
+
+    // Named pipes and file descriptors are only readable by processes that have been
+    // given them, so only regular files have permissions worth checking.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let metadata = file.metadata().map_err(unreadable)?;
+        if metadata.is_file() && metadata.permissions().mode() & 0o004 != 0 {
+            return Err(keystore_error(fl!(
+                "err-keystore-passphrase-file-world-readable",
+                path = path.display().to_string(),
+            )));
+        }
+    }
+
+    // As in `decrypt_string`, the buffer is owned by a `SecretString` before any error is
+    // raised, so that a partial read is zeroized. One byte more than the limit is read,
+    // so that longer files can be detected.
+    let mut buf = String::with_capacity(MAX_PASSPHRASE_LEN as usize + 1);
+    let res = file.take(MAX_PASSPHRASE_LEN + 1).read_to_string(&mut buf);
+    let too_long = buf.len() as u64 > MAX_PASSPHRASE_LEN;
+    let len = buf.trim_end_matches(['\n', '\r']).len();
+    buf.truncate(len);
+    let passphrase = SecretString::new(buf);
+    res.map_err(unreadable)?;
+
+    if too_long {
+        return Err(keystore_error(fl!(
+            "err-keystore-passphrase-file-too-long",
+            path = path.display().to_string(),
+            limit = MAX_PASSPHRASE_LEN,
+        )));
+    }
+
+    Ok(passphrase)
+}
+
+fn keystore_error(message: String) -> Error {
+    Error::from(ErrorKind::Init.context(message)).with_exit_code(ExitCode::KeyStore)
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::ExposeSecret;
+
+    use super::read_passphrase_file;
+
+    #[test]
+    fn passphrase_file_is_read_without_trailing_newline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passphrase");
+        std::fs::write(&path, "correct horse battery staple\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        assert_eq!(
+            read_passphrase_file(&path).unwrap().expose_secret(),
+            "correct horse battery staple",
+        );
+        assert!(read_passphrase_file(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn long_passphrase_file_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passphrase");
+        let write = |len| {
+            std::fs::write(&path, "a".repeat(len)).unwrap();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+            }
+        };
+
+        write(4096);
+        assert_eq!(
+            read_passphrase_file(&path).unwrap().expose_secret().len(),
+            4096
+        );
+
+        write(4097);
+        assert!(read_passphrase_file(&path).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn world_readable_passphrase_file_is_refused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passphrase");
+        std::fs::write(&path, "correct horse battery staple").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert!(read_passphrase_file(&path).is_err());
+    }
+}
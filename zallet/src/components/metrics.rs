@@ -0,0 +1,202 @@
+//! Prometheus metrics endpoint.
+//!
+//! Zallet records metrics with the [`metrics`] crate as it runs. When `metrics.enable` is
+//! set, they are served in the Prometheus text format at `/metrics` on `metrics.bind`,
+//! which is separate from the JSON-RPC server.
+//!
+//! The state of each loaded wallet is sampled whenever the endpoint is scraped:
+//! - `zallet_wallet_chain_tip_height` and `zallet_wallet_fully_scanned_height`: how far
+//!   the wallet has scanned, compared to the chain tip.
+//! - `zallet_wallet_async_operations`: the number of async operations in each state.
+//! - `zallet_wallet_balance_zatoshis`: the balance of each value pool, if
+//!   `metrics.wallet_balances` is set.
+//!
+//! Wallet metrics are labelled with the name of the wallet, which is empty for the sole
+//! wallet configured by `database.wallet`.
+
+use std::convert::Infallible;
+use std::num::NonZeroU32;
+
+use hyper::{Method, StatusCode, body::Incoming, header};
+use jsonrpsee::{
+    server::{HttpBody, HttpRequest, HttpResponse, serve_with_graceful_shutdown},
+    tracing::{Instrument, debug, info, warn},
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use tokio::{net::TcpListener, select};
+use zcash_client_backend::data_api::{WalletRead, wallet::ConfirmationsPolicy};
+
+use crate::{
+    components::{
+        TaskHandle,
+        wallets::{LoadedWallet, Wallets},
+    },
+    config::ZalletConfig,
+    error::{Error, ErrorKind},
+    fl, shutdown,
+};
+
+/// The buckets of the histograms of durations, in seconds.
+const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+#[derive(Debug)]
+pub(crate) struct Metrics {}
+
+impl Metrics {
+    pub(crate) async fn spawn(
+        config: &ZalletConfig,
+        wallets: Wallets,
+    ) -> Result<TaskHandle, Error> {
+        if !config.metrics.enable() {
+            // Emulate a normally-operating ongoing task to simplify subsequent logic.
+            return Ok(crate::spawn!(
+                "No metrics",
+                async {
+                    shutdown::requested().await;
+                    Ok(())
+                }
+                .in_current_span()
+            ));
+        }
+
+        let handle = PrometheusBuilder::new()
+            .set_buckets_for_metric(Matcher::Suffix("seconds".into()), DURATION_BUCKETS)
+            .and_then(|builder| builder.install_recorder())
+            .map_err(|e| {
+                ErrorKind::Init.context(fl!("err-init-metrics-recorder", error = e.to_string()))
+            })?;
+
+        let addr = config.metrics.bind();
+        let listener = TcpListener::bind(addr).await.map_err(|e| {
+            ErrorKind::Init.context(fl!(
+                "err-init-metrics-bind",
+                addr = addr.to_string(),
+                error = e.to_string(),
+            ))
+        })?;
+        info!("Serving metrics at http://{addr}/metrics");
+
+        let scraper = Scraper {
+            handle,
+            wallets,
+            wallet_balances: config.metrics.wallet_balances(),
+        };
+
+        Ok(crate::spawn!("Metrics endpoint", async move {
+            loop {
+                let (socket, _) = select! {
+                    conn = listener.accept() => match conn {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            debug!("Failed to accept metrics connection: {e}");
+                            continue;
+                        }
+                    },
+                    _ = shutdown::requested() => break,
+                };
+
+                let scraper = scraper.clone();
+                let service = tower::service_fn(move |request: HttpRequest<Incoming>| {
+                    let scraper = scraper.clone();
+                    async move { Ok::<_, Infallible>(scraper.respond(&request).await) }
+                });
+                crate::spawn!(
+                    "Metrics connection",
+                    serve_with_graceful_shutdown(socket, service, shutdown::requested())
+                );
+            }
+
+            info!("Metrics endpoint stopped");
+            Ok(())
+        }))
+    }
+}
+
+/// Answers requests to the metrics endpoint.
+#[derive(Clone)]
+struct Scraper {
+    handle: PrometheusHandle,
+    wallets: Wallets,
+    wallet_balances: bool,
+}
+
+impl Scraper {
+    async fn respond(&self, request: &HttpRequest<Incoming>) -> HttpResponse {
+        let (status, body) = match request.uri().path() {
+            "/metrics" if request.method() == Method::GET => {
+                for wallet in self.wallets.loaded() {
+                    self.sample(&wallet).await;
+                }
+                self.handle.run_upkeep();
+                (StatusCode::OK, self.handle.render())
+            }
+            "/metrics" => (StatusCode::METHOD_NOT_ALLOWED, String::new()),
+            _ => (StatusCode::NOT_FOUND, String::new()),
+        };
+
+        HttpResponse::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(HttpBody::from(body))
+            .expect("valid response")
+    }
+
+    /// Records the current state of the given wallet.
+    async fn sample(&self, wallet: &LoadedWallet) {
+        let name = wallet.name.clone().unwrap_or_default();
+
+        if let Some(progress) = wallet.sync_status.progress() {
+            metrics::gauge!("zallet.wallet.chain_tip_height", "wallet" => name.clone())
+                .set(u32::from(progress.chain_tip));
+            if let Some(fully_scanned) = progress.fully_scanned {
+                metrics::gauge!("zallet.wallet.fully_scanned_height", "wallet" => name.clone())
+                    .set(u32::from(fully_scanned));
+            }
+        }
+
+        #[cfg(zallet_build = "wallet")]
+        for (state, count) in wallet.rpc.operation_counts().await {
+            metrics::gauge!(
+                "zallet.wallet.async_operations",
+                "wallet" => name.clone(),
+                "state" => state,
+            )
+            .set(count as f64);
+        }
+
+        if self.wallet_balances {
+            if let Err(e) = self.sample_balances(wallet, &name).await {
+                warn!("Failed to read the balances of wallet \"{name}\" for metrics: {e}");
+            }
+        }
+    }
+
+    async fn sample_balances(&self, wallet: &LoadedWallet, name: &str) -> Result<(), Error> {
+        let db = wallet.db.handle().await?;
+        let Some(summary) = db
+            .get_wallet_summary(ConfirmationsPolicy::new_symmetrical(NonZeroU32::MIN, false))
+            .map_err(|e| ErrorKind::Generic.context(e))?
+        else {
+            return Ok(());
+        };
+
+        let mut pools = [("transparent", 0), ("sapling", 0), ("orchard", 0)];
+        for balance in summary.account_balances().values() {
+            pools[0].1 += balance.unshielded_balance().total().into_u64();
+            pools[1].1 += balance.sapling_balance().total().into_u64();
+            pools[2].1 += balance.orchard_balance().total().into_u64();
+        }
+        for (pool, balance) in pools {
+            metrics::gauge!(
+                "zallet.wallet.balance_zatoshis",
+                "wallet" => name.to_owned(),
+                "pool" => pool,
+            )
+            .set(balance as f64);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,476 @@
+//! Push notifications of wallet events to webhooks.
+//!
+//! # Payloads
+//!
+//! Each event is sent as an HTTP `POST` request with a JSON body of the form:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "id": "evt-0b9c1f4e-3c1a-4c1e-9d4a-2f0e6b8f7a51",
+//!   "timestamp": 1760000000,
+//!   "event": "transaction.mined",
+//!   "data": { "txid": "…", "height": 3000000 }
+//! }
+//! ```
+//!
+//! When Zallet serves several wallets (configured with `database.wallets`), the payload
+//! also has a `wallet` field with the name of the wallet that the event concerns.
+//!
+//! The `version` field is incremented whenever a change is made to the payload format
+//! that receivers might not tolerate. The same event may be delivered more than once
+//! (for example, if a delivery times out after the receiver processed it, or if a chain
+//! reorg causes a transaction to be mined again), so receivers should use `id` and the
+//! event data to deduplicate.
+//!
+//! If a webhook has a secret configured, the request carries an `X-Zallet-Signature`
+//! header containing `sha256=` followed by the hex-encoded HMAC-SHA256 of the raw request
+//! body, keyed with the secret.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use jsonrpsee::tracing::{debug, warn};
+use reqwest::{StatusCode, Url};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::{sync::mpsc, time};
+use uuid::Uuid;
+use zcash_protocol::{TxId, consensus::BlockHeight};
+
+use crate::{
+    config::ZalletConfig,
+    error::{Error, ErrorKind},
+};
+
+/// The version of the payload format.
+const PAYLOAD_VERSION: u32 = 1;
+
+/// The header containing the payload signature.
+const SIGNATURE_HEADER: &str = "X-Zallet-Signature";
+
+/// The longest time that is waited between attempts to deliver an event.
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// A wallet event that can be pushed to webhooks.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", content = "data")]
+pub(crate) enum Event {
+    /// A wallet transaction was detected in the mempool.
+    #[serde(rename = "transaction.detected")]
+    TransactionDetected {
+        #[serde(serialize_with = "serialize_txid")]
+        txid: TxId,
+    },
+
+    /// A wallet transaction was mined.
+    #[serde(rename = "transaction.mined")]
+    TransactionMined {
+        #[serde(serialize_with = "serialize_txid")]
+        txid: TxId,
+        #[serde(serialize_with = "serialize_height")]
+        height: BlockHeight,
+    },
+
+    /// A wallet transaction reached the configured number of confirmations.
+    #[serde(rename = "transaction.confirmed")]
+    TransactionConfirmed {
+        #[serde(serialize_with = "serialize_txid")]
+        txid: TxId,
+        #[serde(serialize_with = "serialize_height")]
+        height: BlockHeight,
+        confirmations: u32,
+    },
+
+    /// An unmined wallet transaction expired.
+    #[serde(rename = "transaction.expired")]
+    TransactionExpired {
+        #[serde(serialize_with = "serialize_txid")]
+        txid: TxId,
+        #[serde(serialize_with = "serialize_height")]
+        expiry_height: BlockHeight,
+    },
+
+    /// A shielded address that had already been paid from outside the wallet received a
+    /// payment in another transaction.
+    #[serde(rename = "address.reused")]
+    AddressReused {
+        address: String,
+        #[serde(serialize_with = "serialize_txid")]
+        txid: TxId,
+        /// The number of transactions from outside the wallet that have paid the address.
+        payments: u32,
+    },
+
+    /// The wallet caught up with the chain tip after starting.
+    #[serde(rename = "sync.chain_tip_reached")]
+    ChainTipReached {
+        #[serde(serialize_with = "serialize_height")]
+        height: BlockHeight,
+    },
+
+    /// The wallet finished scanning the history of the chain.
+    #[serde(rename = "sync.history_recovered")]
+    HistoryRecovered {
+        #[serde(serialize_with = "serialize_height")]
+        height: BlockHeight,
+    },
+}
+
+fn serialize_txid<S: serde::Serializer>(txid: &TxId, serializer: S) -> Result<S::Ok, S::Error> {
+    txid.to_string().serialize(serializer)
+}
+
+fn serialize_height<S: serde::Serializer>(
+    height: &BlockHeight,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    u32::from(*height).serialize(serializer)
+}
+
+impl Event {
+    /// The name of this event.
+    fn name(&self) -> &'static str {
+        match self {
+            Event::TransactionDetected { .. } => "transaction.detected",
+            Event::TransactionMined { .. } => "transaction.mined",
+            Event::TransactionConfirmed { .. } => "transaction.confirmed",
+            Event::TransactionExpired { .. } => "transaction.expired",
+            Event::AddressReused { .. } => "address.reused",
+            Event::ChainTipReached { .. } => "sync.chain_tip_reached",
+            Event::HistoryRecovered { .. } => "sync.history_recovered",
+        }
+    }
+}
+
+/// The JSON body of a webhook request.
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    version: u32,
+    id: &'a str,
+    timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wallet: Option<&'a str>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// An event ready to be delivered.
+#[derive(Debug)]
+struct Delivery {
+    id: String,
+    event: &'static str,
+    body: Vec<u8>,
+}
+
+/// Pushes wallet events to the configured webhooks.
+///
+/// Each webhook has its own bounded outbox, so a slow or unreachable webhook does not
+/// delay deliveries to the others.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Notifications {
+    outboxes: Arc<Vec<mpsc::Sender<Arc<Delivery>>>>,
+    confirmations: u32,
+    address_reuse: bool,
+    /// The name of the wallet whose events are delivered, if it is named.
+    wallet: Option<Arc<str>>,
+}
+
+impl Notifications {
+    /// Starts the delivery tasks for the configured webhooks.
+    pub(crate) fn spawn(config: &ZalletConfig) -> Result<Self, Error> {
+        let section = &config.notifications;
+
+        let client = reqwest::Client::builder()
+            .timeout(section.timeout())
+            .user_agent(concat!("zallet/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| ErrorKind::Init.context(e))?;
+
+        let mut outboxes = vec![];
+        for (index, webhook) in section.webhooks.iter().enumerate() {
+            let url = Url::parse(&webhook.url)
+                .ok()
+                .filter(|url| matches!(url.scheme(), "http" | "https"))
+                .ok_or_else(|| {
+                    ErrorKind::Init.context(format!(
+                        "notifications.webhooks[{index}].url is not an http or https URL",
+                    ))
+                })?;
+
+            let (outbox, receiver) = mpsc::channel(section.queue_size().max(1));
+            let webhook = Webhook {
+                index,
+                url,
+                secret: webhook.secret.clone(),
+                client: client.clone(),
+                max_attempts: section.max_attempts().get(),
+            };
+            crate::spawn!("Webhook delivery", webhook.run(receiver));
+            outboxes.push(outbox);
+        }
+
+        Ok(Self {
+            outboxes: Arc::new(outboxes),
+            confirmations: section.confirmations().get(),
+            address_reuse: section.address_reuse(),
+            wallet: config.wallet_name.as_deref().map(Arc::from),
+        })
+    }
+
+    /// The number of confirmations at which a `transaction.confirmed` event is sent.
+    pub(crate) fn confirmations(&self) -> u32 {
+        self.confirmations
+    }
+
+    /// Whether to warn about shielded address reuse.
+    pub(crate) fn address_reuse(&self) -> bool {
+        self.address_reuse
+    }
+
+    /// Queues the given event for delivery to every webhook.
+    pub(crate) fn emit(&self, event: Event) {
+        if self.outboxes.is_empty() {
+            return;
+        }
+
+        let id = format!("evt-{}", Uuid::new_v4());
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let body = serde_json::to_vec(&Payload {
+            version: PAYLOAD_VERSION,
+            id: &id,
+            timestamp,
+            wallet: self.wallet.as_deref(),
+            event: &event,
+        })
+        .expect("payload is serializable");
+        let delivery = Arc::new(Delivery {
+            id,
+            event: event.name(),
+            body,
+        });
+
+        for (index, outbox) in self.outboxes.iter().enumerate() {
+            if outbox.try_send(delivery.clone()).is_err() {
+                warn!(
+                    "Outbox for webhook {index} is full, dropping {} event {}",
+                    delivery.event, delivery.id,
+                );
+                metrics::counter!("zallet.notifications.dropped", "webhook" => index.to_string())
+                    .increment(1);
+            }
+        }
+    }
+}
+
+/// A webhook that events are delivered to.
+struct Webhook {
+    /// The webhook's position in the config, used to identify it in logs and metrics
+    /// without revealing any credentials in its URL.
+    index: usize,
+    url: Url,
+    secret: Option<SecretString>,
+    client: reqwest::Client,
+    max_attempts: u32,
+}
+
+impl Webhook {
+    async fn run(self, mut outbox: mpsc::Receiver<Arc<Delivery>>) -> Result<(), Error> {
+        let webhook = self.index.to_string();
+
+        while let Some(delivery) = outbox.recv().await {
+            let mut attempt = 1;
+            loop {
+                match self.deliver(&delivery).await {
+                    Ok(()) => {
+                        debug!(
+                            "Delivered {} event {} to webhook {}",
+                            delivery.event, delivery.id, self.index,
+                        );
+                        metrics::counter!("zallet.notifications.delivered", "webhook" => webhook.clone())
+                            .increment(1);
+                        break;
+                    }
+                    Err(e) if e.retryable && attempt < self.max_attempts => {
+                        let delay = backoff(attempt);
+                        debug!(
+                            "Failed to deliver {} event {} to webhook {} (attempt {attempt}), retrying in {}s: {}",
+                            delivery.event,
+                            delivery.id,
+                            self.index,
+                            delay.as_secs(),
+                            e.message,
+                        );
+                        metrics::counter!("zallet.notifications.retried", "webhook" => webhook.clone())
+                            .increment(1);
+                        time::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to deliver {} event {} to webhook {} after {attempt} attempts: {}",
+                            delivery.event, delivery.id, self.index, e.message,
+                        );
+                        metrics::counter!("zallet.notifications.failed", "webhook" => webhook.clone())
+                            .increment(1);
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Makes a single attempt to deliver the given event.
+    async fn deliver(&self, delivery: &Delivery) -> Result<(), DeliveryError> {
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Zallet-Event", delivery.event)
+            .header("X-Zallet-Delivery", &delivery.id);
+        if let Some(secret) = &self.secret {
+            request = request.header(
+                SIGNATURE_HEADER,
+                sign(secret.expose_secret().as_bytes(), &delivery.body),
+            );
+        }
+
+        let response = request
+            .body(delivery.body.clone())
+            .send()
+            .await
+            .map_err(|e| DeliveryError {
+                // Strip the URL from the error, as it may contain credentials.
+                message: e.without_url().to_string(),
+                retryable: true,
+            })?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(DeliveryError {
+                message: format!("webhook responded with {status}"),
+                // Client errors other than these indicate a problem with the request that
+                // retrying won't fix.
+                retryable: !status.is_client_error()
+                    || matches!(
+                        status,
+                        StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS
+                    ),
+            })
+        }
+    }
+}
+
+struct DeliveryError {
+    message: String,
+    retryable: bool,
+}
+
+/// Returns the time to wait after the given failed attempt before trying again.
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(1u64 << attempt.saturating_sub(1).min(16)).min(MAX_BACKOFF)
+}
+
+/// Returns the value of the signature header for the given request body.
+fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    use zcash_protocol::{TxId, consensus::BlockHeight};
+
+    use super::{Event, PAYLOAD_VERSION, Payload, backoff, sign};
+
+    #[test]
+    fn signature_matches_hmac_sha256() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+        );
+    }
+
+    #[test]
+    fn receivers_can_verify_signature() {
+        let secret = b"webhook secret";
+        let body = serde_json::to_vec(&Payload {
+            version: PAYLOAD_VERSION,
+            id: "evt-00000000-0000-0000-0000-000000000000",
+            timestamp: 1_700_000_000,
+            wallet: None,
+            event: &Event::TransactionMined {
+                txid: TxId::from_bytes([7; 32]),
+                height: BlockHeight::from_u32(3_000_000),
+            },
+        })
+        .unwrap();
+
+        // This is what a receiver does with the raw body and the signature header.
+        let header = sign(secret, &body);
+        let signature = hex::decode(header.strip_prefix("sha256=").unwrap()).unwrap();
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(&body);
+        assert!(mac.clone().verify_slice(&signature).is_ok());
+
+        // A modified body or a different secret doesn't verify.
+        let mut tampered = body.clone();
+        *tampered.last_mut().unwrap() = b' ';
+        assert_ne!(sign(secret, &tampered), header);
+        assert_ne!(sign(b"other secret", &body), header);
+    }
+
+    #[test]
+    fn payload_format() {
+        let body = serde_json::to_value(Payload {
+            version: PAYLOAD_VERSION,
+            id: "evt-00000000-0000-0000-0000-000000000000",
+            timestamp: 1_700_000_000,
+            wallet: None,
+            event: &Event::TransactionConfirmed {
+                txid: TxId::from_bytes([0; 32]),
+                height: BlockHeight::from_u32(100),
+                confirmations: 10,
+            },
+        })
+        .unwrap();
+
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "version": 1,
+                "id": "evt-00000000-0000-0000-0000-000000000000",
+                "timestamp": 1_700_000_000,
+                "event": "transaction.confirmed",
+                "data": {
+                    "txid": TxId::from_bytes([0; 32]).to_string(),
+                    "height": 100,
+                    "confirmations": 10,
+                },
+            }),
+        );
+    }
+
+    #[test]
+    fn backoff_is_exponential_and_capped() {
+        assert_eq!(backoff(1), Duration::from_secs(1));
+        assert_eq!(backoff(2), Duration::from_secs(2));
+        assert_eq!(backoff(5), Duration::from_secs(16));
+        assert_eq!(backoff(20), Duration::from_secs(5 * 60));
+    }
+}
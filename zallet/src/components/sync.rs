@@ -33,16 +33,17 @@
 
 #![allow(deprecated)] // For zaino
 
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
+use std::ops::{Range, RangeInclusive};
 use std::sync::{
-    Arc,
+    Arc, Mutex,
     atomic::{AtomicU32, Ordering},
 };
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use futures::StreamExt as _;
 use jsonrpsee::tracing::{self, debug, info, warn};
-use tokio::{sync::Notify, time};
+use tokio::{select, sync::Notify, time};
 use transparent::{
     address::Script,
     bundle::{OutPoint, TxOut},
@@ -62,11 +63,12 @@ use zcash_client_backend::{
     scanning::ScanError,
     wallet::WalletTransparentOutput,
 };
+use zcash_client_sqlite::error::SqliteClientError;
 use zcash_keys::encoding::AddressCodec;
 use zcash_primitives::transaction::Transaction;
 use zcash_protocol::{
     TxId,
-    consensus::{self, BlockHeight},
+    consensus::{self, BlockHeight, NetworkUpgrade, Parameters},
     value::Zatoshis,
 };
 use zcash_script::script;
@@ -77,9 +79,13 @@ use super::{
     TaskHandle,
     chain::Chain,
     database::{Database, DbConnection},
+    notifications::{Event, Notifications},
 };
 use crate::{
-    components::json_rpc::utils::parse_txid, config::ZalletConfig, error::Error, network::Network,
+    components::json_rpc::utils::parse_txid,
+    config::{SyncSection, ZalletConfig},
+    error::Error,
+    network::Network,
 };
 
 mod cache;
@@ -87,29 +93,248 @@ mod cache;
 mod error;
 pub(crate) use error::SyncError;
 
+mod events;
+pub(crate) use events::{TransactionEvent, TransactionEventKind, TransactionEvents};
+
+mod notify;
+use notify::Notifier;
+
+mod prefetch;
+
+mod rescan;
+pub(crate) use rescan::{Rescan, RescanError};
+
 mod steps;
 use steps::ChainBlock;
+#[cfg(zallet_build = "wallet")]
+pub(crate) use steps::{fetch_blocks, fetch_chain_state};
+
+/// The period over which [`SyncStatus::scan_rate`] is measured.
+const SCAN_RATE_WINDOW: Duration = Duration::from_secs(60);
 
 #[derive(Debug)]
 pub(crate) struct WalletSync {}
 
+/// The progress of wallet sync, as last recorded by the sync tasks.
+///
+/// This is cached so that it can be reported without waiting on the wallet database or
+/// the chain indexer.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SyncStatus {
+    progress: Arc<Mutex<Option<SyncProgress>>>,
+    scanned: Arc<Mutex<ScanHistory>>,
+    /// The boundary between the regions of the chain handled by the [`steady_state`] and
+    /// [`recover_history`] tasks.
+    boundary: Arc<AtomicU32>,
+    /// The rescan that is in progress, if any.
+    rescan: Arc<Mutex<Option<Rescan>>>,
+    /// Wakes the [`recover_history`] task when a rescan is scheduled.
+    rescan_scheduled: Arc<Notify>,
+}
+
+/// The batches of blocks that the sync tasks scanned within the last
+/// [`SCAN_RATE_WINDOW`].
+#[derive(Debug)]
+struct ScanHistory {
+    /// When the history started being recorded.
+    started: Instant,
+    /// When each batch finished scanning, and the number of blocks in it.
+    batches: VecDeque<(Instant, u32)>,
+}
+
+impl Default for ScanHistory {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            batches: VecDeque::new(),
+        }
+    }
+}
+
+impl ScanHistory {
+    fn prune(&mut self, now: Instant) {
+        while self
+            .batches
+            .front()
+            .is_some_and(|(time, _)| now.saturating_duration_since(*time) > SCAN_RATE_WINDOW)
+        {
+            self.batches.pop_front();
+        }
+    }
+
+    /// Returns the number of blocks per second that were scanned within the window
+    /// ending at `now`.
+    fn rate(&mut self, now: Instant) -> f64 {
+        self.prune(now);
+        let blocks = self
+            .batches
+            .iter()
+            .map(|(_, blocks)| u64::from(*blocks))
+            .sum::<u64>();
+        // Until the history covers a whole window, the rate is measured over its length.
+        let window = now
+            .saturating_duration_since(self.started)
+            .min(SCAN_RATE_WINDOW);
+        if window.is_zero() {
+            0.0
+        } else {
+            blocks as f64 / window.as_secs_f64()
+        }
+    }
+}
+
+/// A snapshot of [`SyncStatus`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SyncProgress {
+    /// The wallet's view of the chain tip.
+    pub(crate) chain_tip: BlockHeight,
+    /// The height below which the wallet has scanned every block, if any.
+    pub(crate) fully_scanned: Option<BlockHeight>,
+}
+
+impl SyncProgress {
+    /// Returns the number of blocks that the wallet has yet to scan below the chain tip,
+    /// or `None` if it has not fully scanned any blocks.
+    pub(crate) fn scan_lag(&self) -> Option<u32> {
+        self.fully_scanned
+            .map(|height| u32::from(self.chain_tip).saturating_sub(height.into()))
+    }
+}
+
+impl SyncStatus {
+    /// Returns the most recently recorded progress, or `None` if sync has not finished
+    /// initializing.
+    pub(crate) fn progress(&self) -> Option<SyncProgress> {
+        *self.progress.lock().expect("not poisoned")
+    }
+
+    /// Records the wallet's current sync progress.
+    fn record(&self, db_data: &DbConnection) -> Result<(), SyncError> {
+        let progress = match db_data.chain_height()? {
+            Some(chain_tip) => Some(SyncProgress {
+                chain_tip,
+                fully_scanned: db_data
+                    .block_fully_scanned()?
+                    .map(|block| block.block_height()),
+            }),
+            None => None,
+        };
+        *self.progress.lock().expect("not poisoned") = progress;
+        Ok(())
+    }
+
+    /// Returns the number of blocks per second that the sync tasks have scanned over the
+    /// last minute.
+    pub(crate) fn scan_rate(&self) -> f64 {
+        self.scanned
+            .lock()
+            .expect("not poisoned")
+            .rate(Instant::now())
+    }
+
+    /// Records that the sync tasks scanned the given number of blocks.
+    fn record_scanned(&self, blocks: u32) {
+        let now = Instant::now();
+        let mut scanned = self.scanned.lock().expect("not poisoned");
+        scanned.prune(now);
+        scanned.batches.push_back((now, blocks));
+    }
+
+    /// Returns the rescan that is in progress, if any.
+    pub(crate) fn rescan(&self) -> Option<Rescan> {
+        self.rescan.lock().expect("not poisoned").clone()
+    }
+
+    /// Schedules the wallet to rescan the blocks from `start` to `stop` (inclusive), and
+    /// returns the heights of the blocks that will be rescanned.
+    ///
+    /// `start` defaults to, and is raised to, the wallet's birthday. `stop` defaults to,
+    /// and is lowered to, the highest block that is not handled by [`steady_state`].
+    /// Fails if a rescan is already in progress.
+    pub(crate) fn schedule_rescan(
+        &self,
+        db_data: &DbConnection,
+        start: Option<BlockHeight>,
+        stop: Option<BlockHeight>,
+    ) -> Result<RangeInclusive<BlockHeight>, RescanError> {
+        let mut rescan = self.rescan.lock().expect("not poisoned");
+        if let Some(rescan) = rescan.as_ref() {
+            return Err(RescanError::InProgress(rescan.clone()));
+        }
+
+        let lowest = db_data
+            .get_wallet_birthday()?
+            .ok_or(RescanError::NoAccounts)?
+            .max(
+                db_data
+                    .params()
+                    .activation_height(NetworkUpgrade::Sapling)
+                    .unwrap_or(BlockHeight::from_u32(0)),
+            );
+        let boundary = BlockHeight::from_u32(self.boundary.load(Ordering::Acquire));
+        let start = start.map_or(lowest, |start| start.max(lowest));
+        let stop = match stop {
+            Some(stop) if stop < boundary => stop,
+            _ => boundary.saturating_sub(1),
+        };
+        if start > stop || boundary == BlockHeight::from_u32(0) {
+            return Err(RescanError::EmptyRange { start, stop });
+        }
+
+        db_data.reschedule_scan(start..stop + 1)?;
+        info!("Rescanning blocks {start} to {stop}");
+        // This is set after the rescan is scheduled, so that `recover_history` only
+        // considers it finished once it has seen the rescheduled blocks.
+        *rescan = Some(Rescan {
+            heights: start..=stop,
+            started: Instant::now(),
+        });
+        self.rescan_scheduled.notify_one();
+
+        Ok(start..=stop)
+    }
+
+    /// Records that the rescan in progress (if any) has finished, if it was scheduled
+    /// before the wallet's scan queue was last checked at `checked_at`.
+    fn finish_rescan(&self, checked_at: Instant) {
+        let mut rescan = self.rescan.lock().expect("not poisoned");
+        if rescan
+            .as_ref()
+            .is_some_and(|rescan| rescan.started < checked_at)
+        {
+            let rescan = rescan.take().expect("checked");
+            info!(
+                "Finished rescanning blocks {} to {}",
+                rescan.heights.start(),
+                rescan.heights.end(),
+            );
+        }
+    }
+}
+
 impl WalletSync {
     pub(crate) async fn spawn(
         config: &ZalletConfig,
         db: Database,
         chain: Chain,
+        status: SyncStatus,
+        events: TransactionEvents,
     ) -> Result<(TaskHandle, TaskHandle, TaskHandle, TaskHandle), Error> {
         let params = config.consensus.network();
+        let notify = Notifier::spawn(&config.external, config.wallet_name.as_deref())?;
+        let notifications = Notifications::spawn(config)?;
 
         // Ensure the wallet is in a state that the sync tasks can work with.
         let chain_subscriber = chain.subscribe().await?.inner();
         let mut db_data = db.handle().await?;
         let (starting_tip, starting_boundary) =
             initialize(chain_subscriber, &params, db_data.as_mut()).await?;
+        status.record(db_data.as_mut())?;
 
         // Manage the boundary between the `steady_state` and `recover_history` tasks with
         // an atomic.
-        let current_boundary = Arc::new(AtomicU32::new(starting_boundary.into()));
+        let current_boundary = status.boundary.clone();
+        current_boundary.store(starting_boundary.into(), Ordering::Release);
 
         // TODO: Zaino should provide us an API that allows us to be notified when the chain tip
         // changes; here, we produce our own signal via the "mempool stream closing" side effect
@@ -121,6 +346,8 @@ impl WalletSync {
         // Spawn the ongoing sync tasks.
         let chain_subscriber = chain.subscribe().await?.inner();
         let lower_boundary = current_boundary.clone();
+        let steady_state_status = status.clone();
+        let steady_state_notifications = notifications.clone();
         let steady_state_task = crate::spawn!("Steady state sync", async move {
             steady_state(
                 &chain_subscriber,
@@ -129,6 +356,10 @@ impl WalletSync {
                 starting_tip,
                 lower_boundary,
                 tip_change_signal_source,
+                notify,
+                &steady_state_notifications,
+                &events,
+                &steady_state_status,
             )
             .await?;
             Ok(())
@@ -137,6 +368,7 @@ impl WalletSync {
         let chain_subscriber = chain.subscribe().await?.inner();
         let mut db_data = db.handle().await?;
         let upper_boundary = current_boundary.clone();
+        let max_in_flight_batches = config.sync.max_in_flight_batches().get() as usize;
         let recover_history_task = crate::spawn!("Recover history", async move {
             recover_history(
                 chain_subscriber,
@@ -144,6 +376,9 @@ impl WalletSync {
                 db_data.as_mut(),
                 upper_boundary,
                 1000,
+                max_in_flight_batches,
+                &notifications,
+                &status,
             )
             .await?;
             Ok(())
@@ -164,12 +399,14 @@ impl WalletSync {
 
         let chain_subscriber = chain.subscribe().await?.inner();
         let mut db_data = db.handle().await?;
+        let index_memos = config.database.memo_index();
         let data_requests_task = crate::spawn!("Data requests", async move {
             data_requests(
                 chain_subscriber,
                 &params,
                 db_data.as_mut(),
                 req_tip_change_signal_receiver,
+                index_memos,
             )
             .await?;
             Ok(())
@@ -184,6 +421,23 @@ impl WalletSync {
     }
 }
 
+/// Sizes the pool of worker threads that trial-decrypt outputs while scanning.
+///
+/// `zcash_client_backend` trial-decrypts on Rayon's global thread pool, which can only be
+/// configured before it is first used, so this must be called before any wallet starts
+/// syncing.
+pub(crate) fn configure_scan_workers(config: &SyncSection) {
+    let workers = config.scan_workers();
+    match rayon::ThreadPoolBuilder::new()
+        .num_threads(workers)
+        .thread_name(|i| format!("zallet-scan-{i}"))
+        .build_global()
+    {
+        Ok(()) => info!("Trial-decrypting outputs with {workers} scan workers"),
+        Err(e) => warn!("Could not configure the scan workers: {e}"),
+    }
+}
+
 fn update_boundary(current_boundary: BlockHeight, tip_height: BlockHeight) -> BlockHeight {
     current_boundary.max(tip_height - 100)
 }
@@ -262,6 +516,9 @@ async fn initialize(
             ) {
                 Ok(_) => Ok(()),
                 Err(chain::error::Error::Scan(ScanError::PrevHashMismatch { at_height })) => {
+                    db_data
+                        .record_reorg(at_height - 10)
+                        .map_err(|e| chain::error::Error::Wallet(e.into()))?;
                     db_data
                         .truncate_to_height(at_height - 10)
                         .map_err(chain::error::Error::Wallet)?;
@@ -291,10 +548,21 @@ async fn steady_state(
     mut prev_tip: ChainBlock,
     lower_boundary: Arc<AtomicU32>,
     tip_change_signal: Arc<Notify>,
+    notify: Option<Notifier>,
+    notifications: &Notifications,
+    events: &TransactionEvents,
+    status: &SyncStatus,
 ) -> Result<(), SyncError> {
     info!("Steady-state sync task started");
+    let mut reached_tip = false;
     let mut current_tip = steps::get_chain_tip(chain).await?;
 
+    // The wallet transactions that conflict with a mined transaction. We track these so
+    // that we can notify when a reorg or a newly-mined block changes them.
+    let mut conflicted = db_data
+        .conflicted_transactions()
+        .map_err(SqliteClientError::from)?;
+
     // TODO: Remove this once we've made `zcash_client_sqlite` changes to support scanning
     // regular blocks.
     let db_cache = cache::MemoryCache::new();
@@ -335,12 +603,15 @@ async fn steady_state(
             // Ensured by `find_fork`.
             assert!(fork_point.height < prev_tip.height);
 
-            // Rewind the wallet to the fork point.
-            // TODO: Is there anything else we should do with the blocks in the old fork?
+            // Rewind the wallet to the fork point, recording the blocks in the old fork
+            // so that `listsinceblock` can report the transactions they removed.
             info!(
                 "Chain reorg detected, rewinding to {} {}",
                 fork_point.height, fork_point.hash
             );
+            db_data
+                .record_reorg(fork_point.height)
+                .map_err(SqliteClientError::from)?;
             db_data.truncate_to_height(fork_point.height)?;
         }
 
@@ -370,8 +641,37 @@ async fn steady_state(
             })?;
 
             db_cache.delete(scan_range).await?;
+            status.record_scanned(scan_range.len() as u32);
+
+            report_block_events(
+                db_data,
+                notify.as_ref(),
+                notifications,
+                events,
+                from_height..end_height,
+            )?;
         }
 
+        // The chain diff may have mined a transaction that conflicts with one of ours,
+        // or (in a reorg) rolled one back.
+        // This is only recomputed if the chain diff changed the wallet.
+        let now_conflicted = db_data
+            .conflicted_transactions()
+            .map_err(SqliteClientError::from)?;
+        for txid in now_conflicted.symmetric_difference(&conflicted) {
+            if now_conflicted.contains(txid) {
+                warn!("Wallet transaction {txid} conflicts with a mined transaction");
+                events.emit(db_data, TransactionEventKind::Conflicted, *txid)?;
+            } else {
+                info!("Wallet transaction {txid} no longer conflicts with a mined transaction");
+            }
+            if let Some(notifier) = &notify {
+                notifier.notify(txid);
+            }
+        }
+        conflicted = now_conflicted;
+        status.record(db_data)?;
+
         // Now that we're done applying the chain diff, update our chain pointers.
         prev_tip = current_tip;
         current_tip = steps::get_chain_tip(chain).await?;
@@ -384,6 +684,12 @@ async fn steady_state(
 
         // We have caught up to the chain tip. Stream the mempool state into the wallet.
         info!("Reached chain tip, streaming mempool");
+        if !reached_tip {
+            reached_tip = true;
+            notifications.emit(Event::ChainTipReached {
+                height: current_tip.height,
+            });
+        }
         let mempool_height = current_tip.height + 1;
         let consensus_branch_id = consensus::BranchId::for_height(params, mempool_height);
         let mut mempool_stream = chain.get_mempool_stream().await?;
@@ -395,8 +701,23 @@ async fn steady_state(
                         consensus_branch_id,
                     )
                     .expect("Zaino should only provide valid transactions");
-                    info!("Scanning mempool tx {}", tx.txid());
+                    let txid = tx.txid();
+                    let known = db_data
+                        .contains_transaction(&txid)
+                        .map_err(SqliteClientError::from)?;
+                    info!("Scanning mempool tx {}", txid);
                     decrypt_and_store_transaction(params, db_data, &tx, None)?;
+                    if !known
+                        && db_data
+                            .contains_transaction(&txid)
+                            .map_err(SqliteClientError::from)?
+                    {
+                        if let Some(notifier) = &notify {
+                            notifier.notify(&txid);
+                        }
+                        notifications.emit(Event::TransactionDetected { txid });
+                        events.emit(db_data, TransactionEventKind::Detected, txid)?;
+                    }
                 }
                 Err(e) => {
                     warn!("Error receiving transaction: {}", e);
@@ -410,6 +731,79 @@ async fn steady_state(
     }
 }
 
+/// Reports the changes to wallet transactions caused by connecting the blocks at the
+/// given heights.
+fn report_block_events(
+    db_data: &DbConnection,
+    notify: Option<&Notifier>,
+    notifications: &Notifications,
+    events: &TransactionEvents,
+    heights: Range<BlockHeight>,
+) -> Result<(), SyncError> {
+    for (txid, height) in db_data
+        .transactions_mined_in(heights.clone())
+        .map_err(SqliteClientError::from)?
+    {
+        if let Some(notifier) = notify {
+            notifier.notify(&txid);
+        }
+        notifications.emit(Event::TransactionMined { txid, height });
+        events.emit(db_data, TransactionEventKind::Mined, txid)?;
+
+        if notifications.address_reuse() {
+            for reused in db_data
+                .reused_addresses_paid_by(&txid)
+                .map_err(SqliteClientError::from)?
+            {
+                warn!(
+                    "Address {} has now received {} payments from outside the wallet (most recently in {txid}); reusing an address lets its payers link their payments",
+                    reused.address, reused.payments,
+                );
+                notifications.emit(Event::AddressReused {
+                    address: reused.address,
+                    txid,
+                    payments: reused.payments,
+                });
+            }
+        }
+    }
+
+    // A transaction mined at height `h` has `n` confirmations once block `h + n - 1` is
+    // connected.
+    let confirmations = notifications.confirmations();
+    let depth = confirmations - 1;
+    for (txid, height) in db_data
+        .transactions_mined_in(
+            heights.start.saturating_sub(depth)..heights.end.saturating_sub(depth),
+        )
+        .map_err(SqliteClientError::from)?
+    {
+        notifications.emit(Event::TransactionConfirmed {
+            txid,
+            height,
+            confirmations,
+        });
+    }
+
+    // A transaction with expiry height `h` can no longer be mined once block `h + 1` is
+    // connected.
+    for (txid, expiry_height) in db_data
+        .transactions_expiring_in(heights.start.saturating_sub(1)..heights.end.saturating_sub(1))
+        .map_err(SqliteClientError::from)?
+    {
+        info!("Wallet transaction {txid} expired at height {expiry_height}");
+        if let Some(notifier) = notify {
+            notifier.notify(&txid);
+        }
+        notifications.emit(Event::TransactionExpired {
+            txid,
+            expiry_height,
+        });
+    }
+
+    Ok(())
+}
+
 /// Recovers historic wallet state.
 ///
 /// This function only operates on finalized chain state, and does not handle reorgs.
@@ -420,8 +814,12 @@ async fn recover_history(
     db_data: &mut DbConnection,
     upper_boundary: Arc<AtomicU32>,
     batch_size: u32,
+    max_in_flight_batches: usize,
+    notifications: &Notifications,
+    status: &SyncStatus,
 ) -> Result<(), SyncError> {
     info!("History recovery sync task started");
+    let mut recovering = true;
     // TODO: Remove this once we've made `zcash_client_sqlite` changes to support scanning
     // regular blocks.
     let db_cache = cache::MemoryCache::new();
@@ -436,42 +834,76 @@ async fn recover_history(
         // Get the next suggested scan range. We drop the rest because we re-fetch the
         // entire list regularly.
         let upper_boundary = BlockHeight::from_u32(upper_boundary.load(Ordering::Acquire));
+        let checked_at = Instant::now();
         let scan_range = match db_data
             .suggest_scan_ranges()?
             .into_iter()
             .filter_map(|r| r.truncate_end(upper_boundary))
             .next()
         {
-            Some(r) => r,
+            Some(r) => {
+                recovering = true;
+                r
+            }
             None => {
+                if recovering {
+                    recovering = false;
+                    info!("History recovery complete up to height {upper_boundary}");
+                    notifications.emit(Event::HistoryRecovered {
+                        height: upper_boundary,
+                    });
+                }
+                status.finish_rescan(checked_at);
+
                 // Wait for scan ranges to become available.
                 debug!("No scan ranges, sleeping");
-                interval.tick().await;
+                select! {
+                    _ = interval.tick() => (),
+                    _ = status.rescan_scheduled.notified() => (),
+                }
                 continue;
             }
         };
 
         // Limit the number of blocks we download and scan at any one time.
-        for scan_range in (0..).scan(Some(scan_range), |acc, _| {
-            acc.clone().map(|remaining| {
-                if let Some((cur, next)) =
-                    remaining.split_at(remaining.block_range().start + batch_size)
-                {
-                    *acc = Some(next);
-                    cur
-                } else {
-                    *acc = None;
-                    remaining
-                }
+        let batches = (0..)
+            .scan(Some(scan_range), |acc, _| {
+                acc.clone().map(|remaining| {
+                    if let Some((cur, next)) =
+                        remaining.split_at(remaining.block_range().start + batch_size)
+                    {
+                        *acc = Some(next);
+                        cur
+                    } else {
+                        *acc = None;
+                        remaining
+                    }
+                })
             })
-        }) {
-            db_cache
-                .insert(steps::fetch_blocks(&chain, &scan_range).await?)
-                .await?;
-
-            let from_state =
-                steps::fetch_chain_state(&chain, params, scan_range.block_range().start - 1)
+            .collect::<Vec<_>>();
+
+        // Download the batches ahead of scanning them, so that the two overlap. Batches
+        // are still scanned (and their results committed) in height order.
+        let fetch_chain = chain.clone();
+        let params_for_fetch = *params;
+        let mut prefetched =
+            prefetch::Prefetched::spawn(batches, max_in_flight_batches, move |scan_range| {
+                let chain = fetch_chain.clone();
+                async move {
+                    let blocks = steps::fetch_blocks(&chain, &scan_range).await?;
+                    let from_state = steps::fetch_chain_state(
+                        &chain,
+                        &params_for_fetch,
+                        scan_range.block_range().start - 1,
+                    )
                     .await?;
+                    Ok((scan_range, blocks, from_state))
+                }
+            });
+
+        while let Some(batch) = prefetched.next().await {
+            let (scan_range, blocks, from_state) = batch?;
+            db_cache.insert(blocks).await?;
 
             // Scan the downloaded blocks.
             tokio::task::block_in_place(|| {
@@ -495,6 +927,8 @@ async fn recover_history(
 
             // Delete the now-scanned blocks.
             db_cache.delete(scan_range).await?;
+            status.record_scanned(scan_range.len() as u32);
+            status.record(db_data)?;
 
             if scan_ranges_updated {
                 break;
@@ -565,6 +999,34 @@ async fn poll_transparent(
     }
 }
 
+/// Marks any Sprout notes migrated from `zcashd` that `tx` spends.
+///
+/// Failures are logged rather than interrupting sync, as they only affect the reported
+/// Sprout balance.
+#[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+fn record_sprout_spends(db_data: &DbConnection, tx: &Transaction) {
+    if tx.sprout_bundle().is_none() {
+        return;
+    }
+
+    let txid = tx.txid();
+    let mut raw_tx = vec![];
+    let result = tx
+        .write(&mut raw_tx)
+        .and_then(|()| crate::sprout::nullifiers(&raw_tx))
+        .map_err(|e| e.to_string())
+        .and_then(|nullifiers| {
+            db_data
+                .record_sprout_spends(&txid, &nullifiers)
+                .map_err(|e| e.to_string())
+        });
+    match result {
+        Ok(0) => (),
+        Ok(spent) => info!("{txid} spends {spent} Sprout notes migrated from zcashd"),
+        Err(e) => warn!("Failed to check {txid} for spends of migrated Sprout notes: {e}"),
+    }
+}
+
 /// Fetches information that the wallet requests to complete its view of transaction
 /// history.
 #[tracing::instrument(skip_all)]
@@ -573,6 +1035,7 @@ async fn data_requests(
     params: &Network,
     db_data: &mut DbConnection,
     tip_change_signal: Arc<Notify>,
+    index_memos: bool,
 ) -> Result<(), SyncError> {
     loop {
         // Wait for the chain tip to advance
@@ -680,6 +1143,8 @@ async fn data_requests(
 
                     if let Some((tx, mined_height)) = tx {
                         decrypt_and_store_transaction(params, db_data, &tx, mined_height)?;
+                        #[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+                        record_sprout_spends(db_data, &tx);
                     } else {
                         db_data
                             .set_transaction_status(txid, TransactionStatus::TxidNotRecognized)?;
@@ -807,5 +1272,43 @@ async fn data_requests(
                 }
             }
         }
+
+        // Index the memos of any notes that were received in the fetched transactions.
+        if index_memos {
+            db_data.index_memos().map_err(SqliteClientError::from)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{SCAN_RATE_WINDOW, ScanHistory};
+
+    #[test]
+    fn scan_rate_window() {
+        let started = Instant::now();
+        let mut history = ScanHistory {
+            started,
+            batches: [(started + Duration::from_secs(5), 1000)].into(),
+        };
+
+        // Until a whole window has passed, the rate covers the time since sync started.
+        assert_eq!(history.rate(started), 0.0);
+        assert_eq!(history.rate(started + Duration::from_secs(10)), 100.0);
+
+        history
+            .batches
+            .push_back((started + Duration::from_secs(50), 200));
+        assert_eq!(history.rate(started + SCAN_RATE_WINDOW), 20.0);
+
+        // Batches scanned before the window are forgotten.
+        assert_eq!(
+            history.rate(started + SCAN_RATE_WINDOW + Duration::from_secs(10)),
+            200.0 / 60.0,
+        );
+        assert_eq!(history.batches.len(), 1);
+        assert_eq!(history.rate(started + Duration::from_secs(200)), 0.0);
     }
 }
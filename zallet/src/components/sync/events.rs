@@ -0,0 +1,91 @@
+//! Events that are pushed to JSON-RPC subscribers when wallet transactions change.
+
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+use zcash_client_backend::data_api::WalletRead;
+use zcash_client_sqlite::error::SqliteClientError;
+use zcash_protocol::TxId;
+
+use super::SyncError;
+use crate::components::database::{DbConnection, TxEffects};
+
+/// The number of events that are buffered for each subscriber.
+///
+/// A subscriber that falls further behind than this misses the oldest events, so that
+/// slow subscribers never hold up wallet sync.
+const CAPACITY: usize = 1024;
+
+/// What happened to a wallet transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TransactionEventKind {
+    /// The transaction was first detected, in the mempool.
+    Detected,
+    /// The transaction was mined.
+    Mined,
+    /// The transaction conflicts with a mined transaction, and so can never be mined.
+    Conflicted,
+}
+
+/// A change to a wallet transaction.
+#[derive(Clone, Debug)]
+pub(crate) struct TransactionEvent {
+    pub(crate) kind: TransactionEventKind,
+    pub(crate) txid: TxId,
+    /// The number of confirmations that the transaction had when the event occurred.
+    pub(crate) confirmations: u32,
+    pub(crate) effects: TxEffects,
+}
+
+/// The sender of a wallet's transaction events.
+#[derive(Clone, Debug)]
+pub(crate) struct TransactionEvents {
+    sender: broadcast::Sender<Arc<TransactionEvent>>,
+}
+
+impl TransactionEvents {
+    pub(crate) fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+
+    /// Returns a receiver for the events that occur from now on.
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<Arc<TransactionEvent>> {
+        self.sender.subscribe()
+    }
+
+    /// Sends an event for the given wallet transaction to the current subscribers.
+    pub(super) fn emit(
+        &self,
+        db_data: &DbConnection,
+        kind: TransactionEventKind,
+        txid: TxId,
+    ) -> Result<(), SyncError> {
+        // The transaction's effects are only looked up if someone is listening.
+        if self.sender.receiver_count() == 0 {
+            return Ok(());
+        }
+
+        let Some(effects) = db_data
+            .transaction_effects(&txid)
+            .map_err(SqliteClientError::from)?
+        else {
+            return Ok(());
+        };
+        let confirmations = match (effects.mined_height, db_data.chain_height()?) {
+            (Some(height), Some(chain_tip)) => {
+                u32::from(chain_tip + 1).saturating_sub(height.into())
+            }
+            _ => 0,
+        };
+
+        // This only fails if every subscriber has since gone away.
+        let _ = self.sender.send(Arc::new(TransactionEvent {
+            kind,
+            txid,
+            confirmations,
+            effects,
+        }));
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+//! Executor for the `external.notify` command.
+
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use jsonrpsee::tracing::warn;
+use tokio::{
+    process::Command,
+    sync::{Semaphore, mpsc},
+    time,
+};
+use zcash_protocol::TxId;
+
+use crate::{
+    config::ExternalSection,
+    error::{Error, ErrorKind},
+};
+
+/// The maximum number of notifications that may be waiting for a free command slot.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// The maximum number of bytes of a failed command's stderr that are logged.
+const MAX_LOGGED_STDERR: usize = 1024;
+
+/// Runs the `external.notify` command for changed wallet transactions.
+///
+/// Notifications are queued and handled by a background task, which runs at most
+/// `external.notify_concurrency` commands at once. If the queue is full, notifications
+/// are dropped with a warning.
+#[derive(Clone, Debug)]
+pub(super) struct Notifier {
+    queue: mpsc::Sender<TxId>,
+}
+
+impl Notifier {
+    /// Starts the notifier for the configured command, if any.
+    ///
+    /// `wallet` is the name of the wallet whose transactions are notified, if it is one
+    /// of the wallets listed in `database.wallets`.
+    pub(super) fn spawn(
+        config: &ExternalSection,
+        wallet: Option<&str>,
+    ) -> Result<Option<Self>, Error> {
+        let Some(command) = &config.notify else {
+            return Ok(None);
+        };
+
+        let argv = split_command(command).map_err(|e| {
+            ErrorKind::Init.context(format!("Invalid external.notify command: {e}"))
+        })?;
+        let concurrency = Arc::new(Semaphore::new(config.notify_concurrency().get().into()));
+        let timeout = config.notify_timeout();
+        let wallet = wallet.unwrap_or_default().to_owned();
+
+        let (queue, mut receiver) = mpsc::channel::<TxId>(QUEUE_CAPACITY);
+        crate::spawn!("walletnotify", async move {
+            while let Some(txid) = receiver.recv().await {
+                let permit = concurrency
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("semaphore is never closed");
+                let args = substitute(&argv, &txid.to_string(), &wallet);
+                crate::spawn!("walletnotify command", async move {
+                    run(args, timeout).await;
+                    drop(permit);
+                });
+            }
+        });
+
+        Ok(Some(Self { queue }))
+    }
+
+    /// Queues a notification that the given wallet transaction has changed.
+    pub(super) fn notify(&self, txid: &TxId) {
+        match self.queue.try_send(*txid) {
+            Ok(()) => (),
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                warn!("Notify queue is full, dropping notification for transaction {txid}")
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                warn!("Notify task has stopped, dropping notification for transaction {txid}")
+            }
+        }
+    }
+}
+
+/// Runs a single notify command to completion, logging any failure.
+async fn run(args: Vec<String>, timeout: Duration) {
+    let (program, rest) = args
+        .split_first()
+        .expect("split_command rejects empty commands");
+    let child = Command::new(program)
+        .args(rest)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+
+    let child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Failed to run notify command {args:?}: {e}");
+            return;
+        }
+    };
+
+    match time::timeout(timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) if output.status.success() => (),
+        Ok(Ok(output)) => warn!(
+            "Notify command {args:?} exited with {}: {}",
+            output.status,
+            truncate(&String::from_utf8_lossy(&output.stderr), MAX_LOGGED_STDERR),
+        ),
+        Ok(Err(e)) => warn!("Failed to run notify command {args:?}: {e}"),
+        // Dropping the future drops the child, which kills it.
+        Err(_) => warn!(
+            "Notify command {args:?} did not finish within {} seconds and was killed",
+            timeout.as_secs()
+        ),
+    }
+}
+
+/// Splits a command string into its arguments.
+///
+/// Arguments are separated by whitespace. Single quotes preserve everything up to the
+/// next single quote; double quotes preserve everything up to the next unescaped double
+/// quote; and a backslash outside of single quotes escapes the following character.
+fn split_command(command: &str) -> Result<Vec<String>, &'static str> {
+    let mut args = vec![];
+    let mut current: Option<String> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err("unterminated double quote"),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("unterminated double quote"),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => current.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing backslash"),
+            },
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(arg) = current {
+        args.push(arg);
+    }
+
+    if args.is_empty() {
+        Err("command is empty")
+    } else {
+        Ok(args)
+    }
+}
+
+/// Replaces `%s` in each argument with the given transaction ID, and `%w` with the given
+/// wallet name.
+///
+/// The values are never re-split, so they always end up within a single argument.
+fn substitute(argv: &[String], txid: &str, wallet: &str) -> Vec<String> {
+    argv.iter()
+        .map(|arg| arg.replace("%s", txid).replace("%w", wallet))
+        .collect()
+}
+
+/// Truncates `s` to at most `max` bytes, on a character boundary.
+fn truncate(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s.trim_end();
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_command, substitute, truncate};
+
+    #[test]
+    fn splits_commands() {
+        assert_eq!(
+            split_command("/usr/bin/notify --tx %s").unwrap(),
+            ["/usr/bin/notify", "--tx", "%s"],
+        );
+        assert_eq!(
+            split_command(r#"  sh -c 'echo "$1" >> log' sh %s  "#).unwrap(),
+            ["sh", "-c", r#"echo "$1" >> log"#, "sh", "%s"],
+        );
+        assert_eq!(
+            split_command(r#""C:\Program Files\notify.exe" a\ b "x\"y""#).unwrap(),
+            [r"C:\Program Files\notify.exe", "a b", r#"x"y"#],
+        );
+        assert_eq!(split_command("cmd ''").unwrap(), ["cmd", ""]);
+
+        assert!(split_command("").is_err());
+        assert!(split_command("   ").is_err());
+        assert!(split_command("cmd 'unterminated").is_err());
+        assert!(split_command("cmd \"unterminated").is_err());
+        assert!(split_command("cmd \\").is_err());
+    }
+
+    #[test]
+    fn substitution_stays_within_argument() {
+        let argv = split_command("notify --txid=%s %s").unwrap();
+        assert_eq!(
+            substitute(&argv, "a b; rm -rf /", ""),
+            ["notify", "--txid=a b; rm -rf /", "a b; rm -rf /"],
+        );
+
+        // Every occurrence is replaced, including several within one argument.
+        let argv = split_command("notify %s/%s.json %s").unwrap();
+        assert_eq!(
+            substitute(&argv, "abcd", ""),
+            ["notify", "abcd/abcd.json", "abcd"],
+        );
+
+        let argv = split_command("notify %w %s").unwrap();
+        assert_eq!(substitute(&argv, "abcd", "ops"), ["notify", "ops", "abcd"]);
+        assert_eq!(substitute(&argv, "abcd", ""), ["notify", "", "abcd"]);
+    }
+
+    #[test]
+    fn truncates_on_char_boundary() {
+        assert_eq!(truncate("short\n", 10), "short");
+        assert_eq!(truncate("ééé", 3), "é");
+    }
+}
@@ -0,0 +1,146 @@
+//! Fetching batches of blocks ahead of the scanner.
+
+use std::future::Future;
+
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use super::SyncError;
+
+/// Batches that a background task fetches ahead of the consumer.
+///
+/// Batches are yielded in the order in which they were requested. At most `in_flight`
+/// fetched batches are buffered, in addition to the one being fetched, so memory use is
+/// bounded however far the consumer falls behind. If fetching a batch fails, the error is
+/// yielded in its place and no further batches are fetched.
+///
+/// Dropping this stops the background task, so a consumer that fails (or no longer needs
+/// the remaining batches) does not leave it running.
+pub(super) struct Prefetched<T> {
+    batches: mpsc::Receiver<Result<T, SyncError>>,
+    task: JoinHandle<()>,
+}
+
+impl<T: Send + 'static> Prefetched<T> {
+    /// Spawns a task that calls `fetch` for each of `requests` in turn.
+    pub(super) fn spawn<I, F, Fut>(requests: I, in_flight: usize, mut fetch: F) -> Self
+    where
+        I: IntoIterator + Send + 'static,
+        I::IntoIter: Send,
+        F: FnMut(I::Item) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, SyncError>> + Send,
+    {
+        let (tx, batches) = mpsc::channel(in_flight.max(1));
+
+        let task = tokio::spawn(async move {
+            for request in requests {
+                let batch = fetch(request).await;
+                let failed = batch.is_err();
+
+                // The channel is closed if the consumer has stopped.
+                if tx.send(batch).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Self { batches, task }
+    }
+
+    /// Returns the next batch, or `None` once every requested batch has been returned.
+    pub(super) async fn next(&mut self) -> Option<Result<T, SyncError>> {
+        self.batches.recv().await
+    }
+}
+
+impl<T> Drop for Prefetched<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    };
+    use std::time::Duration;
+
+    use zcash_client_sqlite::error::SqliteClientError;
+
+    use super::{Prefetched, SyncError};
+
+    /// Returns a `fetch` closure that counts its calls, and fails for `fail_at`.
+    fn counting_fetch(
+        fetched: Arc<AtomicU32>,
+        fail_at: Option<u32>,
+    ) -> impl FnMut(u32) -> std::future::Ready<Result<u32, SyncError>> + Send + 'static {
+        move |request| {
+            fetched.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(if Some(request) == fail_at {
+                Err(SqliteClientError::CorruptedData(format!("batch {request}")).into())
+            } else {
+                Ok(request)
+            })
+        }
+    }
+
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    #[tokio::test]
+    async fn batches_are_yielded_in_order() {
+        let fetched = Arc::new(AtomicU32::new(0));
+        let mut batches = Prefetched::spawn(0..10, 3, counting_fetch(fetched.clone(), None));
+
+        for expected in 0..10 {
+            assert_eq!(batches.next().await.unwrap().unwrap(), expected);
+        }
+        assert!(batches.next().await.is_none());
+        assert_eq!(fetched.load(Ordering::SeqCst), 10);
+    }
+
+    #[tokio::test]
+    async fn error_stops_fetching() {
+        let fetched = Arc::new(AtomicU32::new(0));
+        let mut batches = Prefetched::spawn(0..10, 2, counting_fetch(fetched.clone(), Some(3)));
+
+        for expected in 0..3 {
+            assert_eq!(batches.next().await.unwrap().unwrap(), expected);
+        }
+        assert!(matches!(
+            batches.next().await,
+            Some(Err(SyncError::Other(e))) if e.to_string().contains("batch 3"),
+        ));
+        assert!(batches.next().await.is_none());
+
+        // No batches after the failed one were fetched.
+        assert_eq!(fetched.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn fetching_is_bounded_by_in_flight_batches() {
+        let fetched = Arc::new(AtomicU32::new(0));
+        let mut batches = Prefetched::spawn(0..100, 2, counting_fetch(fetched.clone(), None));
+
+        // Two batches are buffered, and the third waits to be buffered.
+        settle().await;
+        assert_eq!(fetched.load(Ordering::SeqCst), 3);
+
+        assert_eq!(batches.next().await.unwrap().unwrap(), 0);
+        settle().await;
+        assert_eq!(fetched.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn dropping_stops_fetching() {
+        let fetched = Arc::new(AtomicU32::new(0));
+        let batches = Prefetched::spawn(0..100, 2, counting_fetch(fetched.clone(), None));
+        settle().await;
+        drop(batches);
+
+        settle().await;
+        assert_eq!(fetched.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,88 @@
+//! Rescans of blocks that the wallet has already scanned.
+//!
+//! A rescan is requested with the `rescanblockchain` JSON-RPC method, or with
+//! `zallet start --rescan-from`. The requested blocks are scheduled to be scanned again
+//! (see [`DbConnection::reschedule_scan`]), and the [`recover_history`] task then scans
+//! them. Only one rescan can be in progress at a time.
+//!
+//! Blocks within 100 blocks of the chain tip are handled by the [`steady_state`] task,
+//! which rescans them whenever a reorg occurs, so they are never rescanned this way.
+//!
+//! [`recover_history`]: super::recover_history
+//! [`steady_state`]: super::steady_state
+
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::time::Instant;
+
+use zcash_client_sqlite::error::SqliteClientError;
+use zcash_protocol::consensus::BlockHeight;
+
+use crate::components::database::DbConnection;
+
+/// A rescan that is in progress.
+#[derive(Clone, Debug)]
+pub(crate) struct Rescan {
+    /// The heights of the blocks that are being rescanned.
+    pub(crate) heights: RangeInclusive<BlockHeight>,
+    /// When the rescan was scheduled.
+    pub(crate) started: Instant,
+}
+
+impl Rescan {
+    /// Returns the fraction of the blocks being rescanned that have been scanned.
+    pub(crate) fn progress(&self, db_data: &DbConnection) -> Result<f64, rusqlite::Error> {
+        let (start, stop) = (*self.heights.start(), *self.heights.end());
+        let total = u32::from(stop) + 1 - u32::from(start);
+        let remaining = db_data.unscanned_blocks_in(start..stop + 1)?;
+        Ok(f64::from(total.saturating_sub(remaining)) / f64::from(total))
+    }
+}
+
+/// Errors that can occur while scheduling a rescan.
+#[derive(Debug)]
+pub(crate) enum RescanError {
+    /// A rescan is already in progress.
+    InProgress(Rescan),
+    /// The wallet has no accounts, so it has nothing to rescan.
+    NoAccounts,
+    /// None of the requested blocks can be rescanned.
+    EmptyRange {
+        start: BlockHeight,
+        stop: BlockHeight,
+    },
+    Database(SqliteClientError),
+}
+
+impl fmt::Display for RescanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RescanError::InProgress(rescan) => write!(
+                f,
+                "Wallet is currently rescanning blocks {} to {}. Wait for the rescan to finish.",
+                rescan.heights.start(),
+                rescan.heights.end(),
+            ),
+            RescanError::NoAccounts => write!(f, "Wallet has no accounts to rescan"),
+            RescanError::EmptyRange { start, stop } => write!(
+                f,
+                "No blocks can be rescanned from height {start} to {stop}. Blocks below the wallet's birthday, and blocks within 100 blocks of the chain tip, are not rescanned.",
+            ),
+            RescanError::Database(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for RescanError {}
+
+impl From<SqliteClientError> for RescanError {
+    fn from(e: SqliteClientError) -> Self {
+        RescanError::Database(e)
+    }
+}
+
+impl From<rusqlite::Error> for RescanError {
+    fn from(e: rusqlite::Error) -> Self {
+        RescanError::Database(e.into())
+    }
+}
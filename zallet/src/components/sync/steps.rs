@@ -190,7 +190,7 @@ pub(super) async fn find_fork(
 ///
 /// This function only fetches blocks within the main chain, and should only be given a
 /// range within the finalized chain state (where heights map 1:1 with blocks).
-pub(super) async fn fetch_blocks(
+pub(crate) async fn fetch_blocks(
     chain: &FetchServiceSubscriber,
     scan_range: &ScanRange,
 ) -> Result<Vec<CompactBlock>, SyncError> {
@@ -297,7 +297,7 @@ async fn fetch_compact_block_inner(
     })
 }
 
-pub(super) async fn fetch_chain_state(
+pub(crate) async fn fetch_chain_state(
     chain: &FetchServiceSubscriber,
     params: &Network,
     height: BlockHeight,
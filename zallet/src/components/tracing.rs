@@ -1,21 +1,56 @@
 use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
 
 use tracing::level_filters::LevelFilter;
 use tracing_log::LogTracer;
-use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt};
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry, fmt::MakeWriter, layer::SubscriberExt, reload,
+};
 
 use abscissa_core::{Component, FrameworkError, FrameworkErrorKind, terminal::ColorChoice};
 
+use crate::{
+    config::{LogFormat, ZalletConfig},
+    error::{Error, ErrorKind},
+    fl,
+};
+
+mod log_file;
+use log_file::LogFile;
+
+/// The layer that writes log events to their outputs.
+type OutputLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Handles for reconfiguring the global tracing subscriber.
+struct Handles {
+    /// Whether ANSI colors are used when logging to stderr.
+    ansi: bool,
+    filter: reload::Handle<EnvFilter, Registry>,
+    output: reload::Handle<OutputLayer, Registry>,
+    /// The log file, once `zallet start` has opened it.
+    log_file: Mutex<Option<Arc<LogFile>>>,
+}
+
+static HANDLES: OnceLock<Handles> = OnceLock::new();
+
 /// Abscissa component for initializing the `tracing` subsystem
+///
+/// Until the config has been loaded, events are logged to stderr according to the
+/// `RUST_LOG` environment variable. [`configure`] then applies the `[logging]` config
+/// section.
 #[derive(Component, Debug)]
 #[component(core)]
 pub(crate) struct Tracing {}
 
 impl Tracing {
     pub(crate) fn new(color_choice: ColorChoice) -> Result<Self, FrameworkError> {
-        let env_filter = EnvFilter::builder()
-            .with_default_directive(LevelFilter::INFO.into())
-            .from_env_lossy();
+        let ansi = match color_choice {
+            ColorChoice::Always => true,
+            ColorChoice::AlwaysAnsi => true,
+            ColorChoice::Auto => true,
+            ColorChoice::Never => false,
+        };
 
         // Configure log/tracing interoperability by setting a `LogTracer` as
         // the global logger for the log crate, which converts all log events
@@ -23,26 +58,170 @@ impl Tracing {
         LogTracer::init().map_err(|e| FrameworkErrorKind::ComponentError.context(e))?;
 
         // Construct a tracing subscriber with the supplied filter and enable reloading.
-        let fmt_layer = tracing_subscriber::fmt::layer()
-            .with_writer(io::stderr)
-            .with_ansi(match color_choice {
-                ColorChoice::Always => true,
-                ColorChoice::AlwaysAnsi => true,
-                ColorChoice::Auto => true,
-                ColorChoice::Never => false,
-            })
-            .with_filter(env_filter);
+        // The filter only applies to the output layer, so that it does not affect the
+        // console layer.
+        let (filter, filter_handle) = reload::Layer::new(env_filter(None));
+        let (output, output_handle) =
+            reload::Layer::new(output_layer(LogFormat::Text, ansi, io::stderr));
 
-        let subscriber = tracing_subscriber::registry().with(fmt_layer);
+        let subscriber = tracing_subscriber::registry().with(output.with_filter(filter));
 
         // Spawn the console server in the background, and apply the console layer.
         #[cfg(all(feature = "tokio-console", tokio_unstable))]
         let subscriber = subscriber.with(console_subscriber::spawn());
 
-        // Now set it as the global tracing subscriber and save the handle.
+        // Now set it as the global tracing subscriber and save the handles.
         tracing::subscriber::set_global_default(subscriber)
             .map_err(|e| FrameworkErrorKind::ComponentError.context(e))?;
+        HANDLES
+            .set(Handles {
+                ansi,
+                filter: filter_handle,
+                output: output_handle,
+                log_file: Mutex::new(None),
+            })
+            .map_err(|_| {
+                FrameworkErrorKind::ComponentError.context("tracing was already initialized")
+            })?;
 
         Ok(Self {})
     }
 }
+
+/// Applies the `[logging]` config section.
+///
+/// Events are still only logged to stderr; [`log_to_file`] starts writing to the log
+/// file.
+pub(crate) fn configure(config: &ZalletConfig) -> Result<(), FrameworkError> {
+    let handles = handles();
+    handles
+        .filter
+        .reload(env_filter(Some(config)))
+        .map_err(|e| FrameworkErrorKind::ComponentError.context(e))?;
+    handles
+        .output
+        .reload(output_layer(
+            config.logging.format(),
+            handles.ansi,
+            io::stderr,
+        ))
+        .map_err(|e| FrameworkErrorKind::ComponentError.context(e))?;
+    Ok(())
+}
+
+/// Starts writing logs to the log file, if `logging.output` includes it.
+///
+/// Only `zallet start` writes to the log file, so that other Zallet commands run
+/// alongside it do not rotate it.
+pub(crate) fn log_to_file(config: &ZalletConfig) -> Result<(), Error> {
+    let output = config.logging.output();
+    if !output.to_file() {
+        return Ok(());
+    }
+
+    let path = config.log_file_path();
+    let log_file = LogFile::open(
+        path.clone(),
+        config.logging.rotation(),
+        config.logging.max_file_size(),
+        config.logging.max_files(),
+    )
+    .map_err(|e| {
+        ErrorKind::Init.context(fl!(
+            "err-init-log-file-open",
+            path = path.display().to_string(),
+            error = e.to_string(),
+        ))
+    })?;
+    let log_file = Arc::new(log_file);
+
+    let handles = handles();
+    let format = config.logging.format();
+    let stderr = output
+        .to_stderr()
+        .then(|| output_layer(format, handles.ansi, io::stderr));
+    let file = output_layer(format, false, log_file.clone());
+    handles
+        .output
+        .reload(Box::new(Layer::and_then(stderr, file)) as OutputLayer)
+        .map_err(|e| ErrorKind::Init.context(e))?;
+    *handles.log_file.lock().expect("not poisoned") = Some(log_file);
+
+    #[cfg(unix)]
+    reopen_on_sighup()?;
+
+    Ok(())
+}
+
+/// Reopens the log file, so that logging continues in a new file after the log file
+/// has been renamed by an external tool such as `logrotate`.
+///
+/// Returns the path of the log file, or `None` if Zallet is not writing to one.
+pub(crate) fn reopen_log_file() -> io::Result<Option<PathBuf>> {
+    let log_file = handles().log_file.lock().expect("not poisoned").clone();
+    match log_file {
+        Some(log_file) => {
+            log_file.reopen()?;
+            Ok(Some(log_file.path().to_path_buf()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Reopens the log file whenever Zallet receives `SIGHUP`, until shutdown is requested.
+#[cfg(unix)]
+fn reopen_on_sighup() -> Result<(), Error> {
+    use tokio::{
+        select,
+        signal::unix::{SignalKind, signal},
+    };
+    use tracing::{info, warn};
+
+    let mut sighup = signal(SignalKind::hangup()).map_err(|e| ErrorKind::Init.context(e))?;
+    crate::spawn!("Log file reopener", async move {
+        loop {
+            select! {
+                _ = sighup.recv() => match reopen_log_file() {
+                    Ok(Some(path)) => info!("Reopened log file {}", path.display()),
+                    Ok(None) => (),
+                    Err(e) => warn!("Failed to reopen log file: {e}"),
+                },
+                _ = crate::shutdown::requested() => break,
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handles() -> &'static Handles {
+    HANDLES
+        .get()
+        .expect("Tracing component is registered before the config is loaded")
+}
+
+/// Returns the filter that selects which events are logged.
+///
+/// The `--verbose` flag takes precedence over the `RUST_LOG` environment variable,
+/// which takes precedence over `logging.filter`.
+fn env_filter(config: Option<&ZalletConfig>) -> EnvFilter {
+    let builder = EnvFilter::builder().with_default_directive(LevelFilter::INFO.into());
+    match config {
+        Some(config) if config.verbose => builder.parse_lossy("debug"),
+        Some(config) if std::env::var_os(EnvFilter::DEFAULT_ENV).is_none() => {
+            builder.parse_lossy(config.logging.filter())
+        }
+        _ => builder.from_env_lossy(),
+    }
+}
+
+/// Returns a layer that writes log events to `writer` in the given format.
+fn output_layer<W>(format: LogFormat, ansi: bool, writer: W) -> OutputLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Text => Box::new(layer.with_ansi(ansi)),
+        LogFormat::Json => Box::new(layer.with_ansi(false).json()),
+    }
+}
@@ -0,0 +1,256 @@
+//! The log file written by `zallet start`.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::LogRotation;
+
+/// A log file that rotates itself according to `logging.rotation`.
+///
+/// Each event is written with a single call to [`Write::write`], so the file is only
+/// ever rotated between events.
+#[derive(Debug)]
+pub(super) struct LogFile {
+    path: PathBuf,
+    rotation: LogRotation,
+    max_file_size: u64,
+    max_files: u32,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// The open file, or `None` if it could not be reopened.
+    file: Option<File>,
+    /// The number of bytes in the file.
+    size: u64,
+    /// The rotation period in which the file was last written to.
+    period: u64,
+}
+
+impl LogFile {
+    /// Opens the log file at `path` for appending, creating it if necessary.
+    pub(super) fn open(
+        path: PathBuf,
+        rotation: LogRotation,
+        max_file_size: u64,
+        max_files: u32,
+    ) -> io::Result<Self> {
+        let (file, size, modified) = open_append(&path)?;
+        let log_file = Self {
+            path,
+            rotation,
+            max_file_size,
+            max_files,
+            state: Mutex::new(State {
+                file: Some(file),
+                size,
+                period: 0,
+            }),
+        };
+        log_file.state.lock().expect("not poisoned").period = log_file.period_of(modified);
+        Ok(log_file)
+    }
+
+    /// Returns the path of the log file.
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Closes the log file, and opens the file that is now at its path.
+    ///
+    /// This is used after the file has been renamed by an external tool such as
+    /// `logrotate`.
+    pub(super) fn reopen(&self) -> io::Result<()> {
+        let mut state = self.state.lock().expect("not poisoned");
+        // Close the file first, so that it is not left open if reopening fails.
+        state.file = None;
+        let (file, size, modified) = open_append(&self.path)?;
+        state.file = Some(file);
+        state.size = size;
+        state.period = self.period_of(modified);
+        Ok(())
+    }
+
+    /// Returns the rotation period that contains `time`.
+    ///
+    /// This is always zero if the file is not rotated by time.
+    fn period_of(&self, time: SystemTime) -> u64 {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        match self.rotation {
+            LogRotation::Hourly => secs / (60 * 60),
+            LogRotation::Daily => secs / (24 * 60 * 60),
+            LogRotation::Never | LogRotation::Size => 0,
+        }
+    }
+
+    /// Returns whether the file must be rotated before `len` more bytes are written to
+    /// it in `period`.
+    fn needs_rotation(&self, state: &State, len: usize, period: u64) -> bool {
+        match self.rotation {
+            LogRotation::Never => false,
+            LogRotation::Hourly | LogRotation::Daily => state.size > 0 && period != state.period,
+            LogRotation::Size => {
+                state.size > 0 && state.size.saturating_add(len as u64) > self.max_file_size
+            }
+        }
+    }
+
+    /// Renames the log file to `<path>.1` (after renaming any older files to make room,
+    /// and deleting those beyond `max_files`), and starts a new log file.
+    fn rotate(&self, state: &mut State) -> io::Result<()> {
+        state.file = None;
+
+        let rotated = |n: u32| {
+            let mut path = self.path.clone().into_os_string();
+            path.push(format!(".{n}"));
+            PathBuf::from(path)
+        };
+        for n in (1..=self.max_files).rev() {
+            rename_if_exists(&rotated(n), &rotated(n + 1))?;
+        }
+        rename_if_exists(&self.path, &rotated(1))?;
+        remove_if_exists(&rotated(self.max_files.saturating_add(1)))?;
+
+        let (file, size, _) = open_append(&self.path)?;
+        state.file = Some(file);
+        state.size = size;
+        Ok(())
+    }
+}
+
+impl Write for &LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().expect("not poisoned");
+
+        let period = self.period_of(SystemTime::now());
+        if self.needs_rotation(&state, buf.len(), period) {
+            // If the file cannot be rotated, logging continues in the current file.
+            if let Err(e) = self.rotate(&mut state) {
+                eprintln!("Failed to rotate log file {}: {e}", self.path.display());
+            }
+        }
+        state.period = period;
+
+        // If the file could not be (re)opened, try again for each event.
+        if state.file.is_none() {
+            let (file, size, _) = open_append(&self.path)?;
+            state.file = Some(file);
+            state.size = size;
+        }
+
+        state.file.as_mut().expect("opened above").write_all(buf)?;
+        state.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.state.lock().expect("not poisoned").file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Opens the file at `path` for appending, returning it along with its size and the time
+/// at which it was last modified.
+fn open_append(path: &Path) -> io::Result<(File, u64, SystemTime)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let metadata = file.metadata()?;
+    let modified = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+    Ok((file, metadata.len(), modified))
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use crate::config::LogRotation;
+
+    use super::LogFile;
+
+    fn write_line(log_file: &LogFile, line: &str) {
+        let mut writer = log_file;
+        writer.write_all(line.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn size_rotation_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zallet.log");
+        let log_file = LogFile::open(path.clone(), LogRotation::Size, 10, 2).unwrap();
+
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            write_line(&log_file, line);
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("zallet.log.1")).unwrap(),
+            "third\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("zallet.log.2")).unwrap(),
+            "second\n"
+        );
+        assert!(!dir.path().join("zallet.log.3").exists());
+    }
+
+    #[test]
+    fn events_are_not_split_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zallet.log");
+        let log_file = LogFile::open(path.clone(), LogRotation::Size, 4, 1).unwrap();
+
+        // An event larger than the maximum size is written whole to a new file.
+        write_line(&log_file, "a\n");
+        write_line(&log_file, "a longer line\n");
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a longer line\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("zallet.log.1")).unwrap(),
+            "a\n"
+        );
+    }
+
+    // Open files cannot be renamed on Windows.
+    #[cfg(unix)]
+    #[test]
+    fn reopen_follows_external_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zallet.log");
+        let log_file = LogFile::open(path.clone(), LogRotation::Never, 0, 0).unwrap();
+
+        write_line(&log_file, "before\n");
+        fs::rename(&path, dir.path().join("zallet.log.old")).unwrap();
+        write_line(&log_file, "renamed\n");
+        log_file.reopen().unwrap();
+        write_line(&log_file, "after\n");
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("zallet.log.old")).unwrap(),
+            "before\nrenamed\n"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "after\n");
+    }
+}
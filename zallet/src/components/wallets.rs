@@ -0,0 +1,872 @@
+//! The wallets that Zallet serves.
+//!
+//! `zallet start` loads the wallets listed in `database.wallets` (or the single wallet
+//! configured by `database.wallet`). Each loaded wallet has its own sync tasks and
+//! JSON-RPC methods.
+//!
+//! In wallet builds, wallets can also be loaded and unloaded while Zallet is running,
+//! with the `createwallet`, `loadwallet`, and `unloadwallet` JSON-RPC methods. Wallets
+//! created this way are stored in the `wallets/<name>` subdirectory of the data
+//! directory. A loaded wallet that is not listed in `database.wallets` is not loaded
+//! again when Zallet restarts.
+
+use std::sync::{Arc, Mutex, RwLock};
+
+use abscissa_core::tracing::info;
+use futures::{FutureExt, future};
+use tokio::{
+    select,
+    sync::{mpsc, oneshot},
+    task::{JoinError, JoinHandle},
+};
+
+use crate::{
+    components::{
+        TaskHandle,
+        chain::{Chain, Connection},
+        database::Database,
+        json_rpc::WalletMethods,
+        sync::{SyncStatus, TransactionEvents, WalletSync},
+    },
+    config::ZalletConfig,
+    error::{Error, ExitCode},
+};
+
+#[cfg(zallet_build = "wallet")]
+use {
+    crate::{components::keystore::KeyStore, error::ErrorKind, fl, prelude::*},
+    abscissa_core::tracing::warn,
+    age::secrecy::ExposeSecret,
+    std::{fmt, fs, io::Write, path::PathBuf, sync::Weak},
+    tokio::sync::MutexGuard,
+};
+
+/// The result of a wallet sync task that exited while its wallet was loaded, along with
+/// a description of the task.
+pub(crate) type TaskExit = (String, Result<Result<(), Error>, JoinError>);
+
+/// The wallets that Zallet serves.
+#[derive(Clone)]
+pub(crate) struct Wallets {
+    inner: Arc<Inner>,
+}
+
+/// A handle to [`Wallets`] that does not keep them alive.
+///
+/// Every loaded wallet serves the JSON-RPC methods that manage wallets, so those methods
+/// hold this instead of [`Wallets`] to avoid a reference cycle.
+#[cfg(zallet_build = "wallet")]
+#[derive(Clone)]
+pub(crate) struct WeakWallets(Weak<Inner>);
+
+struct Inner {
+    chain: Chain,
+    registry: Registry<LoadedWallet>,
+    task_exits: mpsc::UnboundedSender<TaskExit>,
+}
+
+/// The loaded wallets, which are identified by name.
+///
+/// This is generic over the wallet type so that the handling of concurrent changes can be
+/// tested without serving real wallets.
+struct Registry<W> {
+    loaded: RwLock<Vec<Arc<W>>>,
+    /// Serializes the loading, creation, and unloading of wallets.
+    #[cfg(zallet_build = "wallet")]
+    changes: tokio::sync::Mutex<()>,
+}
+
+/// A wallet that can be held in a [`Registry`].
+#[cfg(zallet_build = "wallet")]
+trait Named {
+    fn name(&self) -> &str;
+}
+
+/// A wallet that Zallet is serving.
+pub(crate) struct LoadedWallet {
+    /// The name of the wallet, or `None` if it is the sole wallet configured by
+    /// `database.wallet`.
+    pub(crate) name: Option<String>,
+    pub(crate) db: Database,
+    pub(crate) sync_status: SyncStatus,
+    pub(crate) rpc: WalletMethods,
+    sync_tasks: Mutex<Option<SyncTasks>>,
+}
+
+/// A wallet whose database and keystore are open, but which is not yet being served.
+pub(crate) struct OpenedWallet {
+    pub(crate) config: ZalletConfig,
+    pub(crate) db: Database,
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) keystore: KeyStore,
+}
+
+/// Errors that can occur while changing the set of loaded wallets.
+#[cfg(zallet_build = "wallet")]
+#[derive(Debug)]
+pub(crate) enum WalletsError {
+    InvalidName(String),
+    NotFound(String),
+    AlreadyLoaded(String),
+    AlreadyExists(String),
+    EncryptionRequired,
+    Failed(Error),
+}
+
+#[cfg(zallet_build = "wallet")]
+impl fmt::Display for WalletsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalletsError::InvalidName(name) => write!(
+                f,
+                "Invalid wallet name \"{name}\": names may only contain ASCII letters, digits, '-', '_', and '.', and must not start with '.'",
+            ),
+            WalletsError::NotFound(name) => write!(f, "Wallet \"{name}\" not found"),
+            WalletsError::AlreadyLoaded(name) => {
+                write!(f, "Wallet \"{name}\" is already loaded")
+            }
+            WalletsError::AlreadyExists(name) => {
+                write!(f, "Wallet \"{name}\" already exists")
+            }
+            WalletsError::EncryptionRequired => write!(
+                f,
+                "keystore.require_encryption is enabled, so wallets with spending keys cannot be created over JSON-RPC",
+            ),
+            WalletsError::Failed(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(zallet_build = "wallet")]
+impl std::error::Error for WalletsError {}
+
+#[cfg(zallet_build = "wallet")]
+impl From<Error> for WalletsError {
+    fn from(e: Error) -> Self {
+        WalletsError::Failed(e)
+    }
+}
+
+impl OpenedWallet {
+    /// Opens the database and keystore of the wallet described by `config`.
+    pub(crate) async fn open(config: ZalletConfig) -> Result<Self, Error> {
+        let db = Database::open(&config)
+            .await
+            .map_err(|e| e.with_exit_code(ExitCode::Database))?;
+        #[cfg(zallet_build = "wallet")]
+        let keystore =
+            KeyStore::new(&config, db.clone()).map_err(|e| e.with_exit_code(ExitCode::KeyStore))?;
+        #[cfg(zallet_build = "wallet")]
+        keystore
+            .enforce_required_encryption(&config)
+            .await
+            .map_err(|e| e.with_exit_code(ExitCode::KeyStore))?;
+
+        // Zallet cannot spend Sprout notes, so make sure that the user knows about any
+        // that were migrated from `zcashd`.
+        #[cfg(zallet_build = "wallet")]
+        if let Some(balance) = db
+            .handle()
+            .await?
+            .sprout_balance()
+            .map_err(|e| ErrorKind::Generic.context(e))?
+            .filter(|balance| balance.notes > 0)
+        {
+            warn!(
+                "{}",
+                fl!(
+                    "warn-sprout-funds",
+                    count = balance.notes,
+                    value = balance.value.into_u64(),
+                )
+            );
+        }
+
+        Ok(Self {
+            config,
+            db,
+            #[cfg(zallet_build = "wallet")]
+            keystore,
+        })
+    }
+
+    /// Opens a wallet that was created with [`Wallets::create`].
+    ///
+    /// The wallet is watch-only if it has no age identity file.
+    #[cfg(zallet_build = "wallet")]
+    async fn open_created(config: ZalletConfig) -> Result<Self, Error> {
+        if config.encryption_identity().exists() {
+            Self::open(config).await
+        } else {
+            let db = Database::open(&config).await?;
+            let keystore = KeyStore::watch_only(&config, db.clone());
+            Ok(Self {
+                config,
+                db,
+                keystore,
+            })
+        }
+    }
+}
+
+impl Wallets {
+    /// Creates an empty set of wallets.
+    ///
+    /// Returns the wallets, along with a receiver for the exits of their sync tasks.
+    /// Sync tasks are ongoing, so Zallet should shut down if any of them exit.
+    pub(crate) fn new(chain: Chain) -> (Self, mpsc::UnboundedReceiver<TaskExit>) {
+        let (task_exits, task_exits_rx) = mpsc::unbounded_channel();
+        let wallets = Self {
+            inner: Arc::new(Inner {
+                chain,
+                registry: Registry::new(),
+                task_exits,
+            }),
+        };
+        (wallets, task_exits_rx)
+    }
+
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn downgrade(&self) -> WeakWallets {
+        WeakWallets(Arc::downgrade(&self.inner))
+    }
+
+    pub(crate) fn chain(&self) -> &Chain {
+        &self.inner.chain
+    }
+
+    /// Returns the wallets that are currently loaded, in the order they were loaded.
+    pub(crate) fn loaded(&self) -> Vec<Arc<LoadedWallet>> {
+        self.inner.registry.loaded()
+    }
+
+    #[cfg(zallet_build = "wallet")]
+    /// Returns the loaded wallet with the given name.
+    ///
+    /// The sole wallet configured by `database.wallet` has the empty name.
+    pub(crate) fn get(&self, name: &str) -> Option<Arc<LoadedWallet>> {
+        self.inner.registry.get(name)
+    }
+
+    /// Starts serving an opened wallet.
+    ///
+    /// The wallet's async operations are restored, and its sync tasks are started. If
+    /// this fails, the wallet's database is closed.
+    pub(crate) async fn attach(&self, wallet: OpenedWallet) -> Result<Arc<LoadedWallet>, Error> {
+        let wallet = self.serve(wallet).await?;
+        self.inner.registry.insert(wallet.clone());
+        Ok(wallet)
+    }
+
+    /// Starts serving an opened wallet, without adding it to the loaded wallets.
+    async fn serve(&self, wallet: OpenedWallet) -> Result<Arc<LoadedWallet>, Error> {
+        let OpenedWallet {
+            config,
+            db,
+            #[cfg(zallet_build = "wallet")]
+            keystore,
+        } = wallet;
+        let chain = &self.inner.chain;
+        let sync_status = SyncStatus::default();
+        let events = TransactionEvents::new();
+        let sync = SyncSpawner {
+            config,
+            db: db.clone(),
+            chain: chain.clone(),
+            status: sync_status.clone(),
+            events: events.clone(),
+        };
+
+        let spawned = async {
+            let rpc = WalletMethods::new(
+                &db,
+                #[cfg(zallet_build = "wallet")]
+                keystore,
+                chain,
+                events,
+                sync_status.clone(),
+                #[cfg(zallet_build = "wallet")]
+                self.downgrade(),
+            )
+            .await?;
+            let tasks = sync.spawn().await?;
+            Ok::<_, Error>((rpc, tasks))
+        }
+        .await;
+        let (rpc, tasks) = match spawned {
+            Ok(spawned) => spawned,
+            Err(e) => {
+                db.close();
+                return Err(e);
+            }
+        };
+
+        let name = sync.config.wallet_name.clone();
+        let sync_tasks = SyncTasks::watch(tasks, sync, self.inner.task_exits.clone());
+
+        Ok(Arc::new(LoadedWallet {
+            name,
+            db,
+            sync_status,
+            rpc,
+            sync_tasks: Mutex::new(Some(sync_tasks)),
+        }))
+    }
+
+    /// Loads the named wallet.
+    ///
+    /// The wallet is either listed in `database.wallets`, or was created with
+    /// [`Wallets::create`]. The empty name loads the wallet configured by
+    /// `database.wallet`, if `database.wallets` is empty.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) async fn load(&self, name: &str) -> Result<Arc<LoadedWallet>, WalletsError> {
+        self.inner
+            .registry
+            .load(name, self.open_and_serve(name))
+            .await
+    }
+
+    /// Opens and serves the named wallet, without adding it to the loaded wallets.
+    #[cfg(zallet_build = "wallet")]
+    async fn open_and_serve(&self, name: &str) -> Result<Arc<LoadedWallet>, WalletsError> {
+        info!("Loading wallet \"{name}\"");
+        let config = APP.config();
+        let wallet = if name.is_empty() && config.database.wallets.is_empty() {
+            OpenedWallet::open(ZalletConfig::clone(&config)).await?
+        } else if config.database.wallets.iter().any(|w| w.name == name) {
+            OpenedWallet::open(config.for_wallet(name).expect("present")).await?
+        } else {
+            let wallet_config = config
+                .for_wallet(name)
+                .ok_or_else(|| WalletsError::NotFound(name.into()))?;
+            OpenedWallet::open_created(wallet_config).await?
+        };
+
+        Ok(self.serve(wallet).await?)
+    }
+
+    /// Creates a new wallet with the given name.
+    ///
+    /// The wallet is stored in the `wallets/<name>` subdirectory of the data directory.
+    /// Unless it is watch-only, it has a new plaintext age identity, with which its
+    /// keystore is initialized.
+    ///
+    /// The wallet is served once [`NewWallet::finish`] is called, and deleted if the
+    /// returned [`NewWallet`] is dropped before then. No other wallets can be loaded,
+    /// created, or unloaded in the meantime.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) async fn create(
+        &self,
+        name: &str,
+        watch_only: bool,
+    ) -> Result<NewWallet<'_>, WalletsError> {
+        let changes = self.inner.registry.changes.lock().await;
+
+        let config = APP.config();
+        let wallet_config = config
+            .for_created_wallet(name)
+            .ok_or_else(|| WalletsError::InvalidName(name.into()))?;
+        let dir = wallet_config
+            .wallet_db_path()
+            .parent()
+            .expect("wallet database is in a subdirectory")
+            .to_path_buf();
+        if self.get(name).is_some()
+            || config.database.wallets.iter().any(|w| w.name == name)
+            || dir.exists()
+        {
+            return Err(WalletsError::AlreadyExists(name.into()));
+        }
+        if !watch_only && wallet_config.keystore.require_encryption() {
+            return Err(WalletsError::EncryptionRequired);
+        }
+
+        let mut new_wallet = NewWallet {
+            wallets: self,
+            wallet: None,
+            dir,
+            finished: false,
+            _changes: changes,
+        };
+        fs::create_dir_all(&new_wallet.dir)
+            .map_err(|e| Error::from(ErrorKind::Generic.context(e)))?;
+
+        if !watch_only {
+            let identity = age::x25519::Identity::generate();
+            write_identity_file(&wallet_config, &identity)?;
+            let wallet = OpenedWallet::open(wallet_config).await?;
+            new_wallet.wallet = Some(wallet);
+            new_wallet
+                .wallet()
+                .keystore
+                .initialize_recipients(vec![identity.to_public().to_string()])
+                .await?;
+        } else {
+            new_wallet.wallet = Some(OpenedWallet::open_created(wallet_config).await?);
+        }
+
+        info!("Created wallet \"{name}\"");
+        Ok(new_wallet)
+    }
+
+    /// Stops serving the named wallet.
+    ///
+    /// The wallet is removed first, so that new JSON-RPC requests cannot select it;
+    /// requests that are already being handled by it can still finish, including the
+    /// request that unloads it. Then its executing async operations are given up to
+    /// `rpc.shutdown_timeout` to finish, its sync tasks are stopped, and its database is
+    /// closed.
+    ///
+    /// Returns `false` if any async operation was still executing when the wallet was
+    /// unloaded.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) async fn unload(&self, name: &str) -> Result<bool, WalletsError> {
+        self.inner
+            .registry
+            .unload(name, |wallet| async move {
+                info!("Unloading wallet \"{name}\"");
+                let drained = wallet.rpc.drain(APP.config().rpc.shutdown_timeout()).await;
+                if !drained {
+                    warn!("Unloaded wallet \"{name}\" with async operations still executing");
+                }
+                wallet.stop_sync().await;
+                wallet.db.close();
+                drained
+            })
+            .await
+    }
+
+    /// Stops serving every wallet, and closes their databases.
+    ///
+    /// This should be called after the JSON-RPC server has stopped.
+    pub(crate) async fn close(&self) {
+        for wallet in self.inner.registry.take_all() {
+            wallet.stop_sync().await;
+            wallet.db.close();
+        }
+    }
+}
+
+#[cfg(zallet_build = "wallet")]
+impl WeakWallets {
+    /// Returns the wallets, or `None` if Zallet is shutting down.
+    pub(crate) fn upgrade(&self) -> Option<Wallets> {
+        self.0.upgrade().map(|inner| Wallets { inner })
+    }
+}
+
+impl<W> Registry<W> {
+    fn new() -> Self {
+        Self {
+            loaded: RwLock::new(vec![]),
+            #[cfg(zallet_build = "wallet")]
+            changes: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn loaded(&self) -> Vec<Arc<W>> {
+        self.loaded.read().expect("not poisoned").clone()
+    }
+
+    fn insert(&self, wallet: Arc<W>) {
+        self.loaded.write().expect("not poisoned").push(wallet);
+    }
+
+    fn take_all(&self) -> Vec<Arc<W>> {
+        std::mem::take(&mut *self.loaded.write().expect("not poisoned"))
+    }
+}
+
+#[cfg(zallet_build = "wallet")]
+impl<W: Named> Registry<W> {
+    fn get(&self, name: &str) -> Option<Arc<W>> {
+        self.loaded
+            .read()
+            .expect("not poisoned")
+            .iter()
+            .find(|wallet| wallet.name() == name)
+            .cloned()
+    }
+
+    /// Adds the wallet that `open` resolves to under the given name, unless a wallet with
+    /// that name is already loaded (in which case `open` is not polled).
+    ///
+    /// No other wallets can be loaded, created, or unloaded while `open` runs.
+    async fn load(
+        &self,
+        name: &str,
+        open: impl Future<Output = Result<Arc<W>, WalletsError>>,
+    ) -> Result<Arc<W>, WalletsError> {
+        let _changes = self.changes.lock().await;
+        if self.get(name).is_some() {
+            return Err(WalletsError::AlreadyLoaded(name.into()));
+        }
+
+        let wallet = open.await?;
+        self.insert(wallet.clone());
+        Ok(wallet)
+    }
+
+    /// Removes the named wallet, and then passes it to `close`.
+    ///
+    /// Callers that already hold the wallet can keep using it after it has been removed.
+    /// No other wallets can be loaded, created, or unloaded while `close` runs.
+    async fn unload<T, F, Fut>(&self, name: &str, close: F) -> Result<T, WalletsError>
+    where
+        F: FnOnce(Arc<W>) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let _changes = self.changes.lock().await;
+        let wallet = {
+            let mut loaded = self.loaded.write().expect("not poisoned");
+            let index = loaded
+                .iter()
+                .position(|wallet| wallet.name() == name)
+                .ok_or_else(|| WalletsError::NotFound(name.into()))?;
+            loaded.remove(index)
+        };
+
+        Ok(close(wallet).await)
+    }
+}
+
+#[cfg(zallet_build = "wallet")]
+impl Named for LoadedWallet {
+    fn name(&self) -> &str {
+        self.name()
+    }
+}
+
+impl LoadedWallet {
+    /// Returns the name of the wallet, which is empty for the sole wallet configured by
+    /// `database.wallet`.
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("")
+    }
+
+    /// Stops the wallet's sync tasks, and waits for them to release their database
+    /// connections.
+    async fn stop_sync(&self) {
+        let sync_tasks = self.sync_tasks.lock().expect("not poisoned").take();
+        if let Some(sync_tasks) = sync_tasks {
+            sync_tasks.stop().await;
+        }
+    }
+}
+
+/// A wallet that is being created by [`Wallets::create`].
+#[cfg(zallet_build = "wallet")]
+pub(crate) struct NewWallet<'a> {
+    wallets: &'a Wallets,
+    wallet: Option<OpenedWallet>,
+    dir: PathBuf,
+    finished: bool,
+    /// Held so that no other wallet can be created with the same name.
+    _changes: MutexGuard<'a, ()>,
+}
+
+#[cfg(zallet_build = "wallet")]
+impl NewWallet<'_> {
+    pub(crate) fn wallet(&self) -> &OpenedWallet {
+        self.wallet.as_ref().expect("set by Wallets::create")
+    }
+
+    /// Starts serving the new wallet.
+    pub(crate) async fn finish(mut self) -> Result<Arc<LoadedWallet>, Error> {
+        let wallet = self.wallet.take().expect("set by Wallets::create");
+        let wallet = self.wallets.attach(wallet).await?;
+        self.finished = true;
+        Ok(wallet)
+    }
+}
+
+#[cfg(zallet_build = "wallet")]
+impl Drop for NewWallet<'_> {
+    fn drop(&mut self) {
+        if let Some(wallet) = self.wallet.take() {
+            wallet.db.close();
+        }
+        if !self.finished {
+            if let Err(e) = fs::remove_dir_all(&self.dir) {
+                warn!(
+                    "Failed to remove {} after failing to create a wallet: {e}",
+                    self.dir.display(),
+                );
+            }
+        }
+    }
+}
+
+/// Writes a new plaintext age identity file for a wallet created over JSON-RPC.
+#[cfg(zallet_build = "wallet")]
+fn write_identity_file(
+    config: &ZalletConfig,
+    identity: &age::x25519::Identity,
+) -> Result<(), Error> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options
+        .open(config.encryption_identity())
+        .map_err(|e| ErrorKind::Generic.context(e))?;
+    writeln!(file, "# public key: {}", identity.to_public())
+        .and_then(|()| writeln!(file, "{}", identity.to_string().expose_secret()))
+        .map_err(|e| ErrorKind::Generic.context(e))?;
+    Ok(())
+}
+
+/// What is needed to start the sync tasks of a wallet.
+struct SyncSpawner {
+    config: ZalletConfig,
+    db: Database,
+    chain: Chain,
+    status: SyncStatus,
+    events: TransactionEvents,
+}
+
+impl SyncSpawner {
+    fn label(&self, task: &str) -> String {
+        match &self.config.wallet_name {
+            Some(name) => format!("Wallet \"{name}\" {task}"),
+            None => format!("Wallet {task}"),
+        }
+    }
+
+    async fn spawn(&self) -> Result<Vec<(String, TaskHandle)>, Error> {
+        let (steady_state, recover_history, poll_transparent, data_requests) = WalletSync::spawn(
+            &self.config,
+            self.db.clone(),
+            self.chain.clone(),
+            self.status.clone(),
+            self.events.clone(),
+        )
+        .await?;
+
+        Ok(vec![
+            (self.label("steady-state sync"), steady_state),
+            (self.label("recover-history sync"), recover_history),
+            (self.label("poll-transparent sync"), poll_transparent),
+            (self.label("data-requests sync"), data_requests),
+        ])
+    }
+}
+
+/// The sync tasks of a loaded wallet.
+struct SyncTasks {
+    stop: oneshot::Sender<()>,
+    watcher: JoinHandle<()>,
+}
+
+impl SyncTasks {
+    /// Watches the given tasks, reporting the first of them to exit to `task_exits`.
+    ///
+    /// The tasks are all stopped once any of them exits, or [`SyncTasks::stop`] is
+    /// called. If the chain indexer reconnects to a backend, the tasks are instead
+    /// restarted with `sync`, because they are subscribed to the old connection.
+    fn watch(
+        mut tasks: Vec<(String, TaskHandle)>,
+        sync: SyncSpawner,
+        task_exits: mpsc::UnboundedSender<TaskExit>,
+    ) -> Self {
+        let (stop, mut stopped) = oneshot::channel();
+        let watcher = crate::spawn!("Wallet sync watcher", async move {
+            let mut connection = sync.chain.connection();
+            loop {
+                let connected = *connection.borrow_and_update();
+                let exited = async {
+                    let ((task, result), _, _) =
+                        future::select_all(tasks.iter_mut().map(|(task, handle)| {
+                            handle.map(move |result| (task.clone(), result))
+                        }))
+                        .await;
+
+                    // A task fails if the backend that it is using becomes unusable. If
+                    // Zallet reconnects to a backend, restart the task instead.
+                    let failed = matches!(result, Ok(Err(_)));
+                    if failed
+                        && sync.chain.has_fallbacks()
+                        && sync.chain.wait_for_reconnect(connected).await
+                    {
+                        None
+                    } else {
+                        Some((task, result))
+                    }
+                };
+
+                let restart = select! {
+                    exit = exited => match exit {
+                        Some(exit) => {
+                            let _ = task_exits.send(exit);
+                            false
+                        }
+                        None => true,
+                    },
+                    _ = connection.wait_for(|c| {
+                        matches!(c, Connection::Connected(_)) && *c != connected
+                    }) => true,
+                    // This also fires if the wallet is dropped without being unloaded.
+                    _ = &mut stopped => false,
+                };
+
+                for (_, handle) in &tasks {
+                    handle.abort();
+                }
+                for (_, handle) in tasks.drain(..) {
+                    if !handle.is_finished() {
+                        let _ = handle.await;
+                    }
+                }
+                if !restart {
+                    return;
+                }
+
+                info!(
+                    "Restarting {} after the chain indexer reconnected",
+                    sync.label("sync")
+                );
+                match sync.spawn().await {
+                    Ok(respawned) => tasks = respawned,
+                    Err(e) => {
+                        let _ = task_exits.send((sync.label("sync"), Ok(Err(e))));
+                        return;
+                    }
+                }
+            }
+        });
+
+        Self { stop, watcher }
+    }
+
+    /// Stops the tasks, and waits for them to finish.
+    async fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.watcher.await;
+    }
+}
+
+#[cfg(all(test, zallet_build = "wallet"))]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use futures::future;
+
+    use super::{Named, Registry, WalletsError};
+
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+    struct TestWallet {
+        name: String,
+        id: usize,
+        closed: AtomicBool,
+    }
+
+    impl Named for TestWallet {
+        fn name(&self) -> &str {
+            &self.name
+        }
+    }
+
+    async fn open(name: &str) -> Result<Arc<TestWallet>, WalletsError> {
+        // Give other changes a chance to run while the wallet is being opened.
+        tokio::task::yield_now().await;
+        Ok(Arc::new(TestWallet {
+            name: name.into(),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            closed: AtomicBool::new(false),
+        }))
+    }
+
+    async fn close(wallet: Arc<TestWallet>) {
+        tokio::task::yield_now().await;
+        wallet.closed.store(true, Ordering::Relaxed);
+    }
+
+    #[tokio::test]
+    async fn concurrent_loads_of_the_same_wallet() {
+        let registry = Registry::new();
+
+        let results = future::join_all((0..8).map(|_| registry.load("a", open("a")))).await;
+
+        assert_eq!(results.iter().filter(|res| res.is_ok()).count(), 1);
+        assert!(
+            results
+                .iter()
+                .filter_map(|res| res.as_ref().err())
+                .all(|e| matches!(e, WalletsError::AlreadyLoaded(name) if name == "a"))
+        );
+        assert_eq!(registry.loaded().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn concurrent_load_and_unload() {
+        let registry = Registry::new();
+        let first = registry.load("a", open("a")).await.unwrap();
+        registry.load("b", open("b")).await.unwrap();
+
+        // Changes are applied in the order they were requested.
+        let (unloaded, reloaded, duplicate) = tokio::join!(
+            registry.unload("a", close),
+            registry.load("a", open("a")),
+            registry.load("b", open("b")),
+        );
+        unloaded.unwrap();
+        let reloaded = reloaded.unwrap();
+        assert!(matches!(duplicate, Err(WalletsError::AlreadyLoaded(_))));
+
+        assert!(first.closed.load(Ordering::Relaxed));
+        assert_ne!(reloaded.id, first.id);
+        assert_eq!(registry.get("a").unwrap().id, reloaded.id);
+        let mut names = registry
+            .loaded()
+            .iter()
+            .map(|wallet| wallet.name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["a", "b"]);
+
+        assert!(matches!(
+            registry.unload("c", close).await,
+            Err(WalletsError::NotFound(name)) if name == "c"
+        ));
+    }
+
+    #[tokio::test]
+    async fn unload_while_in_flight() {
+        let registry = &Registry::new();
+        registry.load("a", open("a")).await.unwrap();
+
+        // A request that selected the wallet before it was unloaded keeps it.
+        let in_flight = registry.get("a").unwrap();
+
+        let events = &Mutex::new(vec![]);
+        let (unloaded, reloaded) = tokio::join!(
+            registry.unload("a", |wallet| async move {
+                events.lock().unwrap().push("unloading");
+                // New requests can no longer select the wallet.
+                assert!(registry.get("a").is_none());
+                close(wallet).await;
+                events.lock().unwrap().push("unloaded");
+            }),
+            registry.load("a", async {
+                events.lock().unwrap().push("loading");
+                open("a").await
+            }),
+        );
+        unloaded.unwrap();
+        reloaded.unwrap();
+
+        // The wallet is not loaded again until it has been closed.
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["unloading", "unloaded", "loading"]
+        );
+
+        // The in-flight request still has the wallet it was using.
+        assert_eq!(in_flight.name(), "a");
+        assert!(in_flight.closed.load(Ordering::Relaxed));
+        assert!(!Arc::ptr_eq(&in_flight, &registry.get("a").unwrap()));
+    }
+}
@@ -1,27 +1,38 @@
 //! Zallet Config
 
-use std::collections::{BTreeMap, HashMap};
-use std::fmt::Write;
-use std::net::SocketAddr;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Write};
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
 use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use documented::{Documented, DocumentedFields};
+use ipnet::IpNet;
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use zcash_client_backend::data_api::wallet::ConfirmationsPolicy;
-use zcash_protocol::consensus::NetworkType;
+use zcash_protocol::{
+    consensus::NetworkType,
+    value::{MAX_MONEY, Zatoshis},
+};
 
-use crate::commands::{lock_datadir, resolve_datadir_path};
+use crate::commands::{DatadirLock, datadir_locked, lock_datadir, resolve_datadir_path};
+use crate::fl;
 use crate::network::{Network, RegTestNuParam};
 
 #[cfg(zallet_build = "wallet")]
 use {
-    std::num::NonZeroU16, zcash_client_backend::fees::SplitPolicy, zcash_protocol::value::Zatoshis,
+    std::num::NonZeroU16,
+    zcash_client_backend::fees::{DustAction, DustOutputPolicy, SplitPolicy},
     zip32::fingerprint::SeedFingerprint,
 };
 
+/// The number of blocks within its expiry height at which a transaction is considered to
+/// be expiring soon.
+pub(crate) const TX_EXPIRING_SOON_THRESHOLD: u32 = 3;
+
 /// Zallet Configuration
 ///
 /// Most fields are `Option<T>` to enable distinguishing between a user relying on a
@@ -40,6 +51,32 @@ pub struct ZalletConfig {
     #[serde(skip)]
     pub(crate) datadir: Option<PathBuf>,
 
+    /// Whether to wait for other Zallet processes to release the datadir lock.
+    ///
+    /// This cannot be set in a config file; it is set from the `--wait` flag by
+    /// `EntryPoint::process_config`.
+    #[serde(skip)]
+    pub(crate) wait_for_datadir_lock: bool,
+
+    /// The path that the config file is loaded from, whether or not it exists.
+    ///
+    /// This cannot be set in a config file; it is set by `EntryPoint::process_config`.
+    #[serde(skip)]
+    pub(crate) config_file: Option<PathBuf>,
+
+    /// Whether to log debug events, regardless of `logging.filter`.
+    ///
+    /// This cannot be set in a config file; it is set from the `--verbose` flag by
+    /// `EntryPoint::process_config`.
+    #[serde(skip)]
+    pub(crate) verbose: bool,
+
+    /// The wallet from `database.wallets` that this config has been narrowed to.
+    ///
+    /// This cannot be set in a config file; it is set by [`ZalletConfig::for_wallet`].
+    #[serde(skip)]
+    pub(crate) wallet_name: Option<String>,
+
     /// Settings that affect transactions created by Zallet.
     pub builder: BuilderSection,
 
@@ -66,8 +103,20 @@ pub struct ZalletConfig {
     #[cfg(zallet_build = "wallet")]
     pub note_management: NoteManagementSection,
 
+    /// Settings for Zallet's logs.
+    pub logging: LoggingSection,
+
+    /// Settings for the Prometheus metrics endpoint.
+    pub metrics: MetricsSection,
+
+    /// Settings for push notifications of wallet events.
+    pub notifications: NotificationsSection,
+
     /// Settings for the JSON-RPC interface.
     pub rpc: RpcSection,
+
+    /// Settings for how Zallet scans the chain.
+    pub sync: SyncSection,
 }
 
 impl ZalletConfig {
@@ -84,8 +133,100 @@ impl ZalletConfig {
     /// Ensures only a single Zallet process is using the data directory.
     ///
     /// This should be called inside any command that writes to the Zallet datadir.
-    pub(crate) fn lock_datadir(&self) -> Result<fmutex::Guard<'static>, crate::error::Error> {
-        lock_datadir(self.datadir())
+    ///
+    /// If the `--wait` flag was given, this waits for any other Zallet process using the
+    /// data directory to finish.
+    pub(crate) fn lock_datadir(&self) -> Result<DatadirLock, crate::error::Error> {
+        lock_datadir(self.datadir(), self.wait_for_datadir_lock)
+    }
+
+    /// Returns whether a Zallet process is currently using the data directory.
+    pub(crate) fn datadir_locked(&self) -> Result<bool, crate::error::Error> {
+        datadir_locked(self.datadir())
+    }
+
+    /// Returns the configs for each of the wallets that Zallet loads.
+    ///
+    /// If `database.wallets` is empty, this is the single wallet configured by
+    /// `database.wallet` (and `keystore.encryption_identity`). Otherwise it is each of
+    /// the listed wallets, narrowed with [`ZalletConfig::for_wallet`].
+    pub(crate) fn wallet_configs(&self) -> Vec<ZalletConfig> {
+        if self.database.wallets.is_empty() {
+            vec![self.clone()]
+        } else {
+            self.database
+                .wallets
+                .iter()
+                .map(|wallet| self.narrowed_to(wallet))
+                .collect()
+        }
+    }
+
+    /// Returns this config narrowed to the named wallet, or `None` if there is no such
+    /// wallet.
+    ///
+    /// The wallet is either listed in `database.wallets`, or was created with the
+    /// `createwallet` JSON-RPC method. The wallet's files replace `database.wallet` and
+    /// `keystore.encryption_identity`, and its other settings override the corresponding
+    /// global settings.
+    pub(crate) fn for_wallet(&self, name: &str) -> Option<ZalletConfig> {
+        match self
+            .database
+            .wallets
+            .iter()
+            .find(|wallet| wallet.name == name)
+        {
+            Some(wallet) => Some(self.narrowed_to(wallet)),
+            None => {
+                let config = self.for_created_wallet(name)?;
+                config.wallet_db_path().exists().then_some(config)
+            }
+        }
+    }
+
+    /// Returns this config narrowed to a wallet created with the `createwallet` JSON-RPC
+    /// method, or `None` if `name` is not a valid wallet name.
+    ///
+    /// The wallet's files are stored in the `wallets/<name>` subdirectory of the data
+    /// directory. This does not check whether the wallet exists.
+    pub(crate) fn for_created_wallet(&self, name: &str) -> Option<ZalletConfig> {
+        if !WalletSection::is_valid_name(name) {
+            return None;
+        }
+        let dir = Path::new("wallets").join(name);
+        Some(self.narrowed_to(&WalletSection {
+            name: name.into(),
+            wallet: dir.join("wallet.db"),
+            encryption_identity: Some(dir.join("identity.txt")),
+            #[cfg(zallet_build = "wallet")]
+            allow_unlock: None,
+            #[cfg(zallet_build = "wallet")]
+            passphrase_file: None,
+            notify: None,
+        }))
+    }
+
+    fn narrowed_to(&self, wallet: &WalletSection) -> ZalletConfig {
+        let mut config = self.clone();
+        config.wallet_name = Some(wallet.name.clone());
+        config.database.wallet = Some(wallet.wallet.clone());
+        config.database.wallets = vec![];
+        #[cfg(zallet_build = "wallet")]
+        {
+            if let Some(path) = &wallet.encryption_identity {
+                config.keystore.encryption_identity = Some(path.clone());
+            }
+            if let Some(allow_unlock) = wallet.allow_unlock {
+                config.keystore.allow_unlock = Some(allow_unlock);
+            }
+            if let Some(path) = &wallet.passphrase_file {
+                config.keystore.passphrase_file = Some(path.clone());
+            }
+        }
+        if let Some(command) = &wallet.notify {
+            config.external.notify = Some(command.clone());
+        }
+        config
     }
 
     /// Returns the path to the encryption identity.
@@ -94,6 +235,55 @@ impl ZalletConfig {
         resolve_datadir_path(self.datadir(), self.keystore.encryption_identity())
     }
 
+    /// Returns the path to the file containing the wallet passphrase, if configured.
+    #[cfg(zallet_build = "wallet")]
+    pub(crate) fn passphrase_file(&self) -> Option<PathBuf> {
+        self.keystore
+            .passphrase_file
+            .as_deref()
+            .map(|path| resolve_datadir_path(self.datadir(), path))
+    }
+
+    /// Returns the addresses that the JSON-RPC server listens on.
+    ///
+    /// Relative Unix domain socket paths are resolved against the data directory.
+    pub(crate) fn rpc_bind(&self) -> Vec<RpcBind> {
+        self.rpc
+            .bind
+            .iter()
+            .map(|bind| match bind {
+                RpcBind::Tcp(addr) => RpcBind::Tcp(*addr),
+                RpcBind::Unix(path) => RpcBind::Unix(resolve_datadir_path(self.datadir(), path)),
+            })
+            .collect()
+    }
+
+    /// Returns the path to the JSON-RPC cookie file.
+    pub(crate) fn rpc_cookie_path(&self) -> PathBuf {
+        resolve_datadir_path(self.datadir(), self.rpc.cookie_file())
+    }
+
+    /// Returns the addresses of the full nodes / validators that Zallet can use as its
+    /// chain source, in order of preference.
+    ///
+    /// If `indexer.validator_address` is unset, the first address is the standard
+    /// JSON-RPC port for mainnet or testnet (as appropriate) on localhost.
+    pub(crate) fn validator_addresses(&self) -> Vec<String> {
+        let validator_address = self.indexer.validator_address.clone().unwrap_or_else(|| {
+            // The default JSON-RPC ports of Zebra and zcashd.
+            let default_port = match self.consensus.network() {
+                Network::Consensus(zcash_protocol::consensus::Network::MainNetwork) => 8232,
+                _ => 18232,
+            };
+            format!("127.0.0.1:{default_port}")
+        });
+
+        [validator_address]
+            .into_iter()
+            .chain(self.indexer.fallback_validator_addresses.iter().cloned())
+            .collect()
+    }
+
     /// Returns the path to the indexer's database.
     pub(crate) fn indexer_db_path(&self) -> PathBuf {
         resolve_datadir_path(self.datadir(), self.indexer.db_path())
@@ -103,15 +293,354 @@ impl ZalletConfig {
     pub(crate) fn wallet_db_path(&self) -> PathBuf {
         resolve_datadir_path(self.datadir(), self.database.wallet_path())
     }
+
+    /// Returns the path to the PID file of `zallet start`.
+    pub(crate) fn pid_file_path(&self) -> PathBuf {
+        resolve_datadir_path(self.datadir(), self.external.pid_file())
+    }
+
+    /// Returns the path to the audit log, if it is enabled.
+    pub(crate) fn audit_log_path(&self) -> Option<PathBuf> {
+        self.external
+            .audit_log
+            .as_deref()
+            .map(|path| resolve_datadir_path(self.datadir(), path))
+    }
+
+    /// Returns the path to the log file.
+    pub(crate) fn log_file_path(&self) -> PathBuf {
+        resolve_datadir_path(self.datadir(), self.logging.file())
+    }
+
+    /// Returns the directory to be used when exporting data, if it is configured.
+    pub(crate) fn export_dir(&self) -> Option<PathBuf> {
+        self.external
+            .export_dir
+            .as_deref()
+            .map(|path| resolve_datadir_path(self.datadir(), path))
+    }
+
+    /// Returns the paths that are set in this config, along with the option that each
+    /// is set by.
+    fn configured_paths(&self) -> Vec<(&'static str, &Path)> {
+        let mut paths = vec![
+            ("database.wallet", self.database.wallet.as_deref()),
+            ("external.audit_log", self.external.audit_log.as_deref()),
+            ("external.export_dir", self.external.export_dir.as_deref()),
+            ("external.pid_file", self.external.pid_file.as_deref()),
+            (
+                "indexer.validator_cookie_path",
+                self.indexer.validator_cookie_path.as_deref(),
+            ),
+            ("indexer.db_path", self.indexer.db_path.as_deref()),
+            #[cfg(zallet_build = "wallet")]
+            (
+                "keystore.encryption_identity",
+                self.keystore.encryption_identity.as_deref(),
+            ),
+            #[cfg(zallet_build = "wallet")]
+            (
+                "keystore.passphrase_file",
+                self.keystore.passphrase_file.as_deref(),
+            ),
+            ("logging.file", self.logging.file.as_deref()),
+            ("rpc.cookie_file", self.rpc.cookie_file.as_deref()),
+            ("rpc.tls_cert_path", self.rpc.tls_cert_path.as_deref()),
+            ("rpc.tls_key_path", self.rpc.tls_key_path.as_deref()),
+            (
+                "rpc.tls_client_ca_path",
+                self.rpc.tls_client_ca_path.as_deref(),
+            ),
+        ];
+        for wallet in &self.database.wallets {
+            paths.push(("database.wallets", Some(&wallet.wallet)));
+            paths.push(("database.wallets", wallet.encryption_identity.as_deref()));
+            #[cfg(zallet_build = "wallet")]
+            paths.push(("database.wallets", wallet.passphrase_file.as_deref()));
+        }
+        paths
+            .into_iter()
+            .filter_map(|(option, path)| Some((option, path?)))
+            .collect()
+    }
+
+    /// Checks that the names in `database.wallets` are valid and distinct, returning a
+    /// description of the first problem found.
+    pub(crate) fn check_wallet_names(&self) -> Result<(), String> {
+        let mut names = HashSet::new();
+        for wallet in &self.database.wallets {
+            let name = wallet.name.clone();
+            if !WalletSection::is_valid_name(&name) {
+                return Err(fl!("err-config-wallet-name-invalid", name = name));
+            } else if !names.insert(&wallet.name) {
+                return Err(fl!("err-config-wallet-name-duplicate", name = name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks this config for settings that are invalid, or risky.
+    ///
+    /// `zallet start` refuses to start if any errors are found, and logs any warnings.
+    /// Settings that cannot be parsed are instead rejected when the config is loaded.
+    pub(crate) fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = vec![];
+
+        if let Err((option, e)) = self.builder.check_confirmations() {
+            issues.push(ConfigIssue::error(option, e));
+        }
+
+        if let Err(e) = tracing_subscriber::EnvFilter::builder().parse(self.logging.filter()) {
+            issues.push(ConfigIssue::error(
+                "logging.filter",
+                fl!(
+                    "err-config-logging-filter-invalid",
+                    filter = self.logging.filter(),
+                    error = e.to_string(),
+                ),
+            ));
+        }
+
+        if let Err(e) = self.check_wallet_names() {
+            issues.push(ConfigIssue::error("database.wallets", e));
+        }
+
+        // Zallet doesn't expand `~`, so such paths would silently refer to a directory
+        // named `~` within the data directory.
+        for (option, path) in self.configured_paths() {
+            if starts_with_tilde(path) {
+                issues.push(ConfigIssue::error(
+                    option,
+                    fl!(
+                        "err-config-path-tilde",
+                        option = option,
+                        path = path.display().to_string(),
+                    ),
+                ));
+            }
+        }
+
+        // Paths starting with `~` have already been reported.
+        if let Some(export_dir) = self.export_dir().filter(|_| {
+            !self
+                .external
+                .export_dir
+                .as_deref()
+                .is_some_and(starts_with_tilde)
+        }) {
+            let path = export_dir.display().to_string();
+            if !export_dir.is_dir() {
+                issues.push(ConfigIssue::error(
+                    "external.export_dir",
+                    fl!("err-config-export-dir-missing", path = path),
+                ));
+            } else if let Err(e) = check_writable(&export_dir) {
+                issues.push(ConfigIssue::error(
+                    "external.export_dir",
+                    fl!(
+                        "err-config-export-dir-unwritable",
+                        path = path,
+                        error = e.to_string(),
+                    ),
+                ));
+            }
+        }
+
+        let mut binds = HashSet::new();
+        for bind in &self.rpc.bind {
+            let addr = bind.to_string();
+            if !binds.insert(bind) {
+                issues.push(ConfigIssue::error(
+                    "rpc.bind",
+                    fl!("err-config-rpc-bind-duplicate", addr = addr),
+                ));
+                continue;
+            }
+
+            let RpcBind::Tcp(socket_addr) = bind else {
+                continue;
+            };
+            if socket_addr.ip().is_loopback() {
+                continue;
+            }
+            if self.rpc.auth_method() == RpcAuthMethod::None {
+                issues.push(ConfigIssue::warning(
+                    "rpc.bind",
+                    fl!("warn-config-rpc-public-no-auth", addr = addr.clone()),
+                ));
+            } else if self.rpc.tls_cert_path.is_none() || self.rpc.tls_key_path.is_none() {
+                issues.push(ConfigIssue::warning(
+                    "rpc.bind",
+                    fl!("warn-config-rpc-public-no-tls", addr = addr.clone()),
+                ));
+            }
+            if self.rpc.allow_ip.is_empty() {
+                issues.push(ConfigIssue::warning(
+                    "rpc.allow_ip",
+                    fl!("warn-config-rpc-public-no-allow-ip", addr = addr),
+                ));
+            }
+        }
+
+        if let Some(proxy) = &self.indexer.proxy {
+            if proxy
+                .rsplit_once(':')
+                .is_none_or(|(host, port)| host.is_empty() || port.parse::<u16>().is_err())
+            {
+                issues.push(ConfigIssue::error(
+                    "indexer.proxy",
+                    fl!("err-config-indexer-proxy-invalid", proxy = proxy.clone()),
+                ));
+            }
+        } else if self.indexer.onion_only() {
+            issues.push(ConfigIssue::error(
+                "indexer.onion_only",
+                fl!("err-config-indexer-onion-only-without-proxy"),
+            ));
+        }
+
+        // Onion addresses can only be reached through a proxy, which must not be bypassed
+        // if `indexer.onion_only` is set.
+        for (i, addr) in self.validator_addresses().into_iter().enumerate() {
+            let option = if i == 0 {
+                "indexer.validator_address"
+            } else {
+                "indexer.fallback_validator_addresses"
+            };
+            let onion = IndexerSection::is_onion_address(&addr);
+            if onion && self.indexer.proxy.is_none() {
+                issues.push(ConfigIssue::error(
+                    option,
+                    fl!(
+                        "err-config-indexer-onion-without-proxy",
+                        option = option,
+                        addr = addr,
+                    ),
+                ));
+            } else if !onion && self.indexer.onion_only() {
+                issues.push(ConfigIssue::error(
+                    option,
+                    fl!("err-config-indexer-not-onion", option = option, addr = addr),
+                ));
+            }
+        }
+
+        if self.rpc.timeout == Some(0) {
+            issues.push(ConfigIssue::error(
+                "rpc.timeout",
+                fl!("err-config-rpc-timeout-zero"),
+            ));
+        }
+
+        if self.external.force_shutdown_timeout() <= self.rpc.shutdown_timeout() {
+            issues.push(ConfigIssue::warning(
+                "external.force_shutdown_timeout",
+                fl!("warn-config-force-shutdown-timeout-short"),
+            ));
+        }
+
+        issues
+    }
+}
+
+/// Returns whether `path` starts with `~`, which Zallet does not expand to a home
+/// directory.
+pub(crate) fn starts_with_tilde(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with('~'))
+}
+
+/// Checks that files can be created in `dir`, by creating and removing one.
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(format!(".zallet-validate-{}", std::process::id()));
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)?;
+    fs::remove_file(probe)
+}
+
+/// A problem found by [`ZalletConfig::validate`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct ConfigIssue {
+    pub(crate) severity: Severity,
+    /// The config option with the problem, such as `rpc.bind`.
+    ///
+    /// `None` if the config file could not be parsed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) option: Option<&'static str>,
+    pub(crate) message: String,
+}
+
+/// How serious a [`ConfigIssue`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Severity {
+    /// Zallet will not start with this config.
+    Error,
+    /// The config is usable, but probably not what was intended, or risky.
+    Warning,
+}
+
+impl ConfigIssue {
+    pub(crate) fn error(option: &'static str, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            option: Some(option),
+            message,
+        }
+    }
+
+    pub(crate) fn warning(option: &'static str, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            option: Some(option),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: ")?,
+            Severity::Warning => write!(f, "warning: ")?,
+        }
+        if let Some(option) = self.option {
+            write!(f, "{option}: ")?;
+        }
+        write!(f, "{}", self.message)
+    }
 }
 
 /// Settings that affect transactions created by Zallet.
 #[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
 #[serde(deny_unknown_fields)]
 pub struct BuilderSection {
+    /// The strategy used to select the notes spent by a transaction.
+    ///
+    /// - `fewest_inputs`: spend as few notes as possible, minimizing the fee.
+    /// - `largest_first`: spend the largest notes first.
+    /// - `oldest_first`: spend the oldest notes first, consolidating the wallet over
+    ///   time.
+    /// - `single_pool`: fund each transaction from a single shielded pool where
+    ///   possible, to avoid revealing the amount that crosses between pools.
+    ///
+    /// This can be overridden for individual transactions by methods that send funds.
+    pub input_selection: Option<InputSelection>,
+
+    /// The most, in zatoshis, that a transaction created by Zallet may pay in fees.
+    ///
+    /// Zallet pays the ZIP 317 conventional fee, plus any dust change (see
+    /// `note_management.dust_threshold`); it cannot yet be configured to pay a fixed fee.
+    /// Transactions that would pay more than this are rejected before they are created.
+    /// Methods that send funds can set a lower limit with their `fee` parameter.
+    pub max_fee: Option<u64>,
+
     /// Whether to spend unconfirmed transparent change when sending transactions.
     ///
-    /// Does not affect unconfirmed shielded change, which cannot be spent.
+    /// Only change from transactions created by this wallet is affected; unconfirmed
+    /// transparent outputs received from other parties are never spent. Does not affect
+    /// unconfirmed shielded change, which cannot be spent.
     pub spend_zeroconf_change: Option<bool>,
 
     /// The number of confirmations required for a trusted transaction output (TXO) to
@@ -124,13 +653,11 @@ pub struct BuilderSection {
     /// This setting is a trade-off between latency and reliability: a smaller value makes
     /// trusted TXOs spendable more quickly, but the spending transaction has a higher
     /// risk of failure if a chain reorg occurs that unmines the receiving transaction.
-    pub trusted_confirmations: Option<u32>,
-
-    /// The number of blocks after which a transaction created by Zallet that has not been
-    /// mined will become invalid.
     ///
-    /// - Minimum: `TX_EXPIRING_SOON_THRESHOLD + 1`
-    pub tx_expiry_delta: Option<u16>,
+    /// Methods that send funds use this, along with `untrusted_confirmations`, unless
+    /// their `minconf` parameter is set. Must be at least 1, as a shielded note cannot
+    /// be spent until it is mined.
+    pub trusted_confirmations: Option<u32>,
 
     /// The number of confirmations required for an untrusted transaction output (TXO) to
     /// become spendable.
@@ -143,20 +670,41 @@ pub struct BuilderSection {
     /// risk of failure if the sender of the receiving transaction is malicious and
     /// double-spends the funds.
     ///
-    /// Values smaller than `trusted_confirmations` are ignored.
+    /// Must be at least `trusted_confirmations`.
     pub untrusted_confirmations: Option<u32>,
 
+    /// Settings for combining payments that are sent with `batchable: true` into
+    /// shared transactions.
+    pub batching: BuilderBatchingSection,
+
     /// Configurable limits on transaction builder operation (to prevent e.g. memory
     /// exhaustion).
     pub limits: BuilderLimitsSection,
 }
 
 impl BuilderSection {
+    /// The strategy used to select the notes spent by a transaction.
+    ///
+    /// Default is `fewest_inputs`.
+    pub fn input_selection(&self) -> InputSelection {
+        self.input_selection.unwrap_or_default()
+    }
+
+    /// The most that a transaction created by Zallet may pay in fees.
+    ///
+    /// Default is 10_000_000 (0.1 ZEC, the default of `zcashd`'s `-maxtxfee`).
+    pub fn max_fee(&self) -> Zatoshis {
+        Zatoshis::from_u64(self.max_fee.unwrap_or(10_000_000))
+            .unwrap_or(Zatoshis::const_from_u64(MAX_MONEY))
+    }
+
     /// Whether to spend unconfirmed transparent change when sending transactions.
     ///
     /// Default is `true`.
     ///
-    /// Does not affect unconfirmed shielded change, which cannot be spent.
+    /// Only change from transactions created by this wallet is affected; unconfirmed
+    /// transparent outputs received from other parties are never spent. Does not affect
+    /// unconfirmed shielded change, which cannot be spent.
     pub fn spend_zeroconf_change(&self) -> bool {
         self.spend_zeroconf_change.unwrap_or(true)
     }
@@ -177,13 +725,29 @@ impl BuilderSection {
         self.trusted_confirmations.unwrap_or(3)
     }
 
-    /// The number of blocks after which a transaction created by Zallet that has not been
-    /// mined will become invalid.
+    /// Returns the option at fault and an error message if the configured numbers of
+    /// confirmations cannot be used to spend funds.
     ///
-    /// - Minimum: `TX_EXPIRING_SOON_THRESHOLD + 1`
-    /// - Default: 40
-    pub fn tx_expiry_delta(&self) -> u16 {
-        self.tx_expiry_delta.unwrap_or(40)
+    /// `trusted_confirmations` must be at least 1, and `untrusted_confirmations` must be
+    /// at least `trusted_confirmations`.
+    pub(crate) fn check_confirmations(&self) -> Result<(), (&'static str, String)> {
+        if self.trusted_confirmations() == 0 {
+            Err((
+                "builder.trusted_confirmations",
+                fl!("err-config-trusted-confirmations-zero"),
+            ))
+        } else if self.untrusted_confirmations() < self.trusted_confirmations() {
+            Err((
+                "builder.untrusted_confirmations",
+                fl!(
+                    "err-config-untrusted-confirmations-too-small",
+                    untrusted = self.untrusted_confirmations(),
+                    trusted = self.trusted_confirmations(),
+                ),
+            ))
+        } else {
+            Ok(())
+        }
     }
 
     /// The number of confirmations required for an untrusted transaction output (TXO) to
@@ -197,7 +761,7 @@ impl BuilderSection {
     /// risk of failure if the sender of the receiving transaction is malicious and
     /// double-spends the funds.
     ///
-    /// Values smaller than `trusted_confirmations` are ignored.
+    /// Must be at least `trusted_confirmations`.
     ///
     /// Default is 10.
     pub fn untrusted_confirmations(&self) -> u32 {
@@ -207,16 +771,80 @@ impl BuilderSection {
     /// Returns the confirmations policy used for spending, based on number of trusted and
     /// untrusted confirmations specified by this configuration section.
     ///
-    /// This will return an error if the number of confirmations required for spending untrusted
-    /// TXOs is less than the number of confirmations required for spending trusted TXOs
-    #[allow(clippy::result_unit_err)]
-    pub fn confirmations_policy(&self) -> Result<ConfirmationsPolicy, ()> {
-        let allow_zero_conf_shielding = self.untrusted_confirmations() == 0;
-        ConfirmationsPolicy::new(
-            NonZeroU32::new(self.trusted_confirmations()).unwrap_or(NonZeroU32::MIN),
-            NonZeroU32::new(self.untrusted_confirmations()).unwrap_or(NonZeroU32::MIN),
-            allow_zero_conf_shielding,
-        )
+    /// # Panics
+    ///
+    /// Panics if the section fails [`BuilderSection::check_confirmations`], which is
+    /// checked when the config is loaded.
+    pub fn confirmations_policy(&self) -> ConfirmationsPolicy {
+        NonZeroU32::new(self.trusted_confirmations())
+            .zip(NonZeroU32::new(self.untrusted_confirmations()))
+            .and_then(|(trusted, untrusted)| {
+                ConfirmationsPolicy::new(trusted, untrusted, false).ok()
+            })
+            .expect("checked when the config is loaded")
+    }
+}
+
+/// A strategy for selecting the notes spent by a transaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputSelection {
+    /// Spend as few notes as possible, minimizing the fee.
+    #[default]
+    FewestInputs,
+    /// Spend the largest notes first.
+    LargestFirst,
+    /// Spend the oldest notes first.
+    OldestFirst,
+    /// Fund each transaction from a single shielded pool where possible.
+    SinglePool,
+}
+
+impl InputSelection {
+    /// Parses a strategy from its name in the config file.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fewest_inputs" => Some(Self::FewestInputs),
+            "largest_first" => Some(Self::LargestFirst),
+            "oldest_first" => Some(Self::OldestFirst),
+            "single_pool" => Some(Self::SinglePool),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for combining payments that are sent with `batchable: true` into shared
+/// transactions.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+#[serde(deny_unknown_fields)]
+pub struct BuilderBatchingSection {
+    /// The maximum number of queued sends that are combined into a single transaction.
+    ///
+    /// A batch is sent as soon as it reaches this size, without waiting for the rest of
+    /// the window.
+    pub max_payments: Option<u16>,
+
+    /// The time (in seconds) that a batchable send waits for other sends to join its
+    /// batch.
+    pub window: Option<u64>,
+}
+
+impl BuilderBatchingSection {
+    /// The maximum number of queued sends that are combined into a single transaction.
+    ///
+    /// A batch is sent as soon as it reaches this size, without waiting for the rest of
+    /// the window.
+    ///
+    /// Default is 20.
+    pub fn max_payments(&self) -> u16 {
+        self.max_payments.unwrap_or(20)
+    }
+
+    /// The time that a batchable send waits for other sends to join its batch.
+    ///
+    /// Default is 5 seconds.
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window.unwrap_or(5))
     }
 }
 
@@ -225,17 +853,144 @@ impl BuilderSection {
 #[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
 #[serde(deny_unknown_fields)]
 pub struct BuilderLimitsSection {
+    /// The maximum size (in bytes) of the serialized result of an async operation.
+    ///
+    /// Larger results are replaced with a summary containing the IDs of the
+    /// transactions that the operation created.
+    pub operation_result_size: Option<u32>,
+
+    /// The maximum time (in seconds) that an async operation may spend executing.
+    ///
+    /// An operation that exceeds this is cancelled and marked as failed, unless it has
+    /// already broadcast a transaction.
+    pub operation_timeout: Option<u64>,
+
+    /// Overrides of `operation_timeout` for specific RPC methods, each in the form
+    /// `"method:seconds"`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub operation_timeouts: Vec<OperationTimeout>,
+
     /// The maximum number of Orchard actions permitted in a constructed transaction.
     pub orchard_actions: Option<u16>,
+
+    /// The maximum time (in seconds) for which a finished async operation is retained.
+    ///
+    /// Older operations are dropped whether or not their result has been fetched with
+    /// `z_getoperationresult`.
+    pub retained_operation_age: Option<u64>,
+
+    /// The maximum number of finished async operations to retain.
+    ///
+    /// Beyond this, the operations that finished earliest are dropped whether or not
+    /// their result has been fetched with `z_getoperationresult`.
+    pub retained_operations: Option<u32>,
+
+    /// The maximum number of Sapling spends permitted in each transaction created by
+    /// operations that merge or sweep many inputs.
+    pub sapling_spends: Option<u16>,
+
+    /// The maximum number of transparent inputs permitted in each transaction created by
+    /// operations that merge or sweep many inputs.
+    pub transparent_inputs: Option<u16>,
 }
 
 impl BuilderLimitsSection {
+    /// The maximum size (in bytes) of the serialized result of an async operation.
+    ///
+    /// Default is 1 MiB.
+    pub fn operation_result_size(&self) -> u32 {
+        self.operation_result_size.unwrap_or(1 << 20)
+    }
+
+    /// The maximum time that an async operation may spend executing.
+    ///
+    /// Default is 30 minutes.
+    pub fn operation_timeout(&self) -> Duration {
+        Duration::from_secs(self.operation_timeout.unwrap_or(30 * 60))
+    }
+
+    /// The maximum time that an async operation created by the given RPC method may
+    /// spend executing.
+    pub fn operation_timeout_for(&self, method: &str) -> Duration {
+        self.operation_timeouts
+            .iter()
+            .rev()
+            .find(|timeout| timeout.method == method)
+            .map(|timeout| timeout.timeout)
+            .unwrap_or_else(|| self.operation_timeout())
+    }
+
     /// The maximum number of Orchard actions permitted in a constructed transaction.
     ///
     /// Default is 50.
     pub fn orchard_actions(&self) -> u16 {
         self.orchard_actions.unwrap_or(50)
     }
+
+    /// The maximum time for which a finished async operation is retained.
+    ///
+    /// Default is 1 day.
+    pub fn retained_operation_age(&self) -> Duration {
+        Duration::from_secs(self.retained_operation_age.unwrap_or(24 * 60 * 60))
+    }
+
+    /// The maximum number of finished async operations to retain.
+    ///
+    /// Default is 1000.
+    pub fn retained_operations(&self) -> u32 {
+        self.retained_operations.unwrap_or(1000)
+    }
+
+    /// The maximum number of Sapling spends permitted in each transaction created by
+    /// operations that merge or sweep many inputs.
+    ///
+    /// Default is 200.
+    pub fn sapling_spends(&self) -> u16 {
+        self.sapling_spends.unwrap_or(200)
+    }
+
+    /// The maximum number of transparent inputs permitted in each transaction created by
+    /// operations that merge or sweep many inputs.
+    ///
+    /// Default is 50.
+    pub fn transparent_inputs(&self) -> u16 {
+        self.transparent_inputs.unwrap_or(50)
+    }
+}
+
+/// A timeout for the async operations created by a specific RPC method.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub struct OperationTimeout {
+    method: String,
+    timeout: Duration,
+}
+
+impl TryFrom<String> for OperationTimeout {
+    type Error = &'static str;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (method, secs) = value
+            .split_once(':')
+            .filter(|(method, _)| !method.is_empty())
+            .ok_or("Invalid `operation_timeouts` entry")?;
+
+        let secs = secs
+            .parse()
+            .map_err(|_| "Invalid `operation_timeouts` entry")?;
+
+        Ok(Self {
+            method: method.into(),
+            timeout: Duration::from_secs(secs),
+        })
+    }
+}
+
+impl From<OperationTimeout> for String {
+    fn from(timeout: OperationTimeout) -> Self {
+        format!("{}:{}", timeout.method, timeout.timeout.as_secs())
+    }
 }
 
 /// Zallet's understanding of the consensus rules.
@@ -245,7 +1000,10 @@ impl BuilderLimitsSection {
 #[derive(Clone, Debug, Deserialize, Serialize, Documented, DocumentedFields)]
 #[serde(deny_unknown_fields)]
 pub struct ConsensusSection {
-    /// Network type.
+    /// The Zcash network to use: `"main"`, `"test"`, or `"regtest"`.
+    ///
+    /// This can be overridden with the `--network` flag. A wallet database can only be
+    /// used with the network it was created for.
     #[serde(with = "crate::network::kind")]
     pub network: NetworkType,
 
@@ -276,15 +1034,36 @@ impl ConsensusSection {
 #[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
 #[serde(deny_unknown_fields)]
 pub struct DatabaseSection {
+    /// Whether to index the text memos of received notes, so that they can be searched
+    /// with `z_searchmemos`.
+    ///
+    /// Disabling this discards the index when Zallet next starts.
+    pub memo_index: Option<bool>,
+
     /// Path to the wallet database file.
     ///
     /// This can be either an absolute path, or a path relative to the data directory.
     /// Note that on Windows, you must either use single quotes for this field's value, or
     /// replace all backslashes `\` with forward slashes `/`.
     pub wallet: Option<PathBuf>,
+
+    /// A list of named wallets for Zallet to load.
+    ///
+    /// If any wallets are listed, `wallet` is ignored, and commands other than
+    /// `zallet start` must select a wallet with the `--wallet` flag.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wallets: Vec<WalletSection>,
 }
 
 impl DatabaseSection {
+    /// Whether to index the text memos of received notes, so that they can be searched
+    /// with `z_searchmemos`.
+    ///
+    /// Default is `true`.
+    pub fn memo_index(&self) -> bool {
+        self.memo_index.unwrap_or(true)
+    }
+
     /// Path to the wallet database file.
     ///
     /// This can be either an absolute path, or a path relative to the data directory.
@@ -297,31 +1076,137 @@ impl DatabaseSection {
     }
 }
 
-/// Settings controlling how Zallet interacts with the outside world.
-#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+/// A named wallet, with its own database and key material.
+#[derive(Clone, Debug, Deserialize, Serialize, Documented, DocumentedFields)]
 #[serde(deny_unknown_fields)]
-pub struct ExternalSection {
-    /// Whether the wallet should broadcast transactions.
-    pub broadcast: Option<bool>,
+pub struct WalletSection {
+    /// The name of the wallet.
+    ///
+    /// JSON-RPC clients select the wallet with the `/wallet/<name>` endpoint path, so
+    /// this may only contain ASCII letters, digits, `-`, `_`, and `.`, and must not
+    /// start with `.`.
+    pub name: String,
 
-    /// Directory to be used when exporting data.
+    /// Path to the wallet database file.
     ///
-    /// This must be an absolute path; relative paths are not resolved within the datadir.
-    /// Note that on Windows, you must either use single quotes for this field's value, or
-    /// replace all backslashes `\` with forward slashes `/`.
-    pub export_dir: Option<PathBuf>,
+    /// This can be either an absolute path, or a path relative to the data directory.
+    pub wallet: PathBuf,
 
-    /// Executes the specified command when a wallet transaction changes.
+    /// Path to the age identity file that encrypts the wallet's key material.
     ///
-    /// A wallet transaction "change" can be anything that alters how the transaction
-    /// affects the wallet's balance. Examples include (but are not limited to):
-    /// - A new transaction is created by the wallet.
-    /// - A wallet transaction is added to the mempool.
+    /// This can be either an absolute path, or a path relative to the data directory.
+    /// If unset, `keystore.encryption_identity` is used, so that wallets can share an
+    /// identity.
+    pub encryption_identity: Option<PathBuf>,
+
+    /// Whether the `walletpassphrase` JSON-RPC method may unlock the wallet.
+    ///
+    /// If unset, `keystore.allow_unlock` is used.
+    #[cfg(zallet_build = "wallet")]
+    pub allow_unlock: Option<bool>,
+
+    /// Path to a file containing the wallet's passphrase, which `zallet start` uses to
+    /// unlock the wallet.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    /// If unset, the wallet is unlocked with the passphrase that `zallet start` obtains
+    /// for all wallets (from `keystore.passphrase_file`, for example).
+    #[cfg(zallet_build = "wallet")]
+    pub passphrase_file: Option<PathBuf>,
+
+    /// Executes the specified command when a transaction in this wallet changes.
+    ///
+    /// This takes the same form as `external.notify`, which is used if this is unset.
+    pub notify: Option<String>,
+}
+
+impl WalletSection {
+    /// Returns whether `name` can be used as the name of a wallet.
+    pub(crate) fn is_valid_name(name: &str) -> bool {
+        // Names are also used as directory names for wallets created over JSON-RPC, so
+        // they must not be `.` or `..`.
+        !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+    }
+}
+
+/// Settings controlling how Zallet interacts with the outside world.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+#[serde(deny_unknown_fields)]
+pub struct ExternalSection {
+    /// Path to an append-only log of spending and key operations.
+    ///
+    /// Each entry is chained to the previous one by its hash, so that changes to the log
+    /// can be detected with `zallet audit verify`. The log is disabled if unset.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    /// Note that on Windows, you must either use single quotes for this field's value, or
+    /// replace all backslashes `\` with forward slashes `/`.
+    pub audit_log: Option<PathBuf>,
+
+    /// Whether the wallet should broadcast transactions.
+    pub broadcast: Option<bool>,
+
+    /// Directory to be used when exporting data.
+    ///
+    /// The `backupwallet` and `z_exportwallet` JSON-RPC methods only write files within
+    /// this directory, and fail if it is unset. `z_importwallet` resolves relative paths
+    /// against it.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    /// Note that on Windows, you must either use single quotes for this field's value, or
+    /// replace all backslashes `\` with forward slashes `/`.
+    pub export_dir: Option<PathBuf>,
+
+    /// Time (in seconds) after a shutdown is requested at which Zallet exits, even if
+    /// some of its components have not yet stopped.
+    ///
+    /// This should be longer than `rpc.shutdown_timeout`, so that in-flight JSON-RPC
+    /// requests and async operations can finish first.
+    pub force_shutdown_timeout: Option<u64>,
+
+    /// Executes the specified command when a wallet transaction changes.
+    ///
+    /// A wallet transaction "change" can be anything that alters how the transaction
+    /// affects the wallet's balance. Examples include (but are not limited to):
+    /// - A new transaction is created by the wallet.
+    /// - A wallet transaction is added to the mempool.
     /// - A block containing a wallet transaction is mined or unmined.
     /// - A wallet transaction is removed from the mempool due to conflicts.
     ///
-    /// `%s` in the command is replaced by the hex encoding of the transaction ID.
+    /// The command is split into arguments at whitespace, and run directly rather than
+    /// through a shell. Single or double quotes can be used to group an argument that
+    /// contains whitespace, and a backslash escapes the following character outside of
+    /// single quotes. `%s` in any argument is replaced by the hex encoding of the
+    /// transaction ID, and `%w` by the name of the wallet from `database.wallets` (or
+    /// the empty string if `database.wallets` is not used).
+    ///
+    /// To use shell features such as redirection, run the shell explicitly and pass the
+    /// transaction ID as a positional argument:
+    /// ```toml
+    /// [external]
+    /// notify = "sh -c 'echo \"$1\" >> /var/log/zallet-tx.log' sh %s"
+    /// ```
     pub notify: Option<String>,
+
+    /// The maximum number of `notify` commands that may run at the same time.
+    ///
+    /// Notifications beyond this limit are queued. If too many notifications are queued,
+    /// further notifications are dropped with a warning.
+    pub notify_concurrency: Option<NonZeroU16>,
+
+    /// Time (in seconds) that a `notify` command may run before it is killed.
+    pub notify_timeout: Option<u64>,
+
+    /// Path to the file in which `zallet start` records its process ID.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    /// Note that on Windows, you must either use single quotes for this field's value, or
+    /// replace all backslashes `\` with forward slashes `/`.
+    pub pid_file: Option<PathBuf>,
 }
 
 impl ExternalSection {
@@ -331,6 +1216,40 @@ impl ExternalSection {
     pub fn broadcast(&self) -> bool {
         self.broadcast.unwrap_or(true)
     }
+
+    /// Time after a shutdown is requested at which Zallet exits, even if some of its
+    /// components have not yet stopped.
+    ///
+    /// Default is 45 seconds.
+    pub fn force_shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.force_shutdown_timeout.unwrap_or(45))
+    }
+
+    /// The maximum number of `notify` commands that may run at the same time.
+    ///
+    /// Default is 4.
+    pub fn notify_concurrency(&self) -> NonZeroU16 {
+        self.notify_concurrency
+            .unwrap_or_else(|| NonZeroU16::new(4).expect("valid"))
+    }
+
+    /// Time that a `notify` command may run before it is killed.
+    ///
+    /// Default is 60 seconds.
+    pub fn notify_timeout(&self) -> Duration {
+        Duration::from_secs(self.notify_timeout.unwrap_or(60))
+    }
+
+    /// Path to the file in which `zallet start` records its process ID.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    ///
+    /// Default is `zallet.pid`.
+    fn pid_file(&self) -> &Path {
+        self.pid_file
+            .as_deref()
+            .unwrap_or_else(|| Path::new("zallet.pid"))
+    }
 }
 
 /// Settings for Zallet features.
@@ -454,171 +1373,1125 @@ pub struct ExperimentalFeaturesSection {
     pub other: BTreeMap<String, toml::Value>,
 }
 
-/// Settings for the Zaino chain indexer.
+/// Settings for the Zaino chain indexer.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+#[serde(deny_unknown_fields)]
+pub struct IndexerSection {
+    /// IP address and port of the JSON-RPC interface for the full node / validator being
+    /// used as a data source.
+    ///
+    /// If unset, connects on localhost to the standard JSON-RPC port for mainnet or
+    /// testnet (as appropriate).
+    pub validator_address: Option<String>,
+
+    /// Addresses of further full nodes / validators to fail over to, in order of
+    /// preference.
+    ///
+    /// If the node at `validator_address` stops responding or its chain tip stops
+    /// advancing, Zallet switches to the first of these nodes that is on the same chain.
+    /// They must accept the same credentials as `validator_address`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_validator_addresses: Vec<String>,
+
+    /// Path to the validator cookie file.
+    ///
+    /// If set, cookie file authorization will be used.
+    pub validator_cookie_path: Option<PathBuf>,
+
+    /// Full node / validator Username.
+    pub validator_user: Option<String>,
+
+    /// Full node / validator Password.
+    pub validator_password: Option<String>,
+
+    /// Path to the folder where the indexer maintains its state.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    /// Note that on Windows, you must either use single quotes for this field's value, or
+    /// replace all backslashes `\` with forward slashes `/`.
+    pub db_path: Option<PathBuf>,
+
+    /// Address (`host:port`) of a SOCKS5 proxy, such as Tor, to connect to the full
+    /// nodes / validators through.
+    ///
+    /// If set, every connection to `validator_address` and
+    /// `fallback_validator_addresses` (including the broadcast of transactions) goes
+    /// through the proxy, and their hostnames are resolved by the proxy rather than
+    /// locally. This is required to use `.onion` addresses. Zallet refuses to start if
+    /// the proxy cannot be reached.
+    ///
+    /// Webhook deliveries (`notifications.webhooks`) do not go through the proxy.
+    pub proxy: Option<String>,
+
+    /// Whether to refuse to connect to full nodes / validators that do not have `.onion`
+    /// addresses.
+    ///
+    /// Requires `proxy` to be set.
+    pub onion_only: Option<bool>,
+}
+
+impl IndexerSection {
+    /// Path to the folder where the indexer maintains its state.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    ///
+    /// Default is `zaino`.
+    fn db_path(&self) -> &Path {
+        self.db_path
+            .as_deref()
+            .unwrap_or_else(|| Path::new("zaino"))
+    }
+
+    /// Whether to refuse to connect to full nodes / validators that do not have `.onion`
+    /// addresses.
+    ///
+    /// Default is `false`.
+    pub fn onion_only(&self) -> bool {
+        self.onion_only.unwrap_or(false)
+    }
+
+    /// Returns `true` if `address` (a `host:port` string) has a `.onion` hostname.
+    pub(crate) fn is_onion_address(address: &str) -> bool {
+        address
+            .rsplit_once(':')
+            .is_some_and(|(host, _)| host.to_ascii_lowercase().ends_with(".onion"))
+    }
+}
+
+/// Settings for the key store.
+#[cfg(zallet_build = "wallet")]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+#[serde(deny_unknown_fields)]
+pub struct KeyStoreSection {
+    /// Path to the age identity file that encrypts key material.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    /// Note that on Windows, you must either use single quotes for this field's value, or
+    /// replace all backslashes `\` with forward slashes `/`.
+    pub encryption_identity: Option<PathBuf>,
+
+    /// Whether to require a confirmed backup of a mnemonic seed phrase before deriving
+    /// new accounts from it.
+    ///
+    /// If enabled, `z_getnewaccount` will refuse to derive an account from a mnemonic
+    /// (and `getnewaddress` will refuse to derive an address from it) until its backup
+    /// has been confirmed with the `walletconfirmbackup` JSON-RPC method.
+    /// Mnemonics added with `zallet import-mnemonic` or `zallet migrate-zcashd-wallet` are
+    /// already backed up.
+    pub require_backup: Option<bool>,
+
+    /// Whether to refuse to run without passphrase-encrypted key material.
+    ///
+    /// If enabled, `zallet start` will fail if wallet encryption has not been initialized
+    /// with `zallet init-wallet-encryption`, or if the age identity file is not encrypted
+    /// with a passphrase. Commands that store new seeds in the wallet will also refuse to
+    /// run under the same conditions.
+    pub require_encryption: Option<bool>,
+
+    /// Whether the `walletpassphrase` JSON-RPC method may unlock the wallet.
+    ///
+    /// If disabled, an encrypted wallet stays locked while Zallet is running, so it can
+    /// only be used for view-only operations: methods that need spending keys (such as
+    /// `z_sendmany`) fail even if the JSON-RPC caller knows the wallet passphrase. This
+    /// limits what a compromised JSON-RPC credential can do. Zallet will refuse to start
+    /// with this disabled if the age identity file is not encrypted with a passphrase.
+    pub allow_unlock: Option<bool>,
+
+    /// The maximum number of seconds for which `walletpassphrase` may unlock the wallet.
+    ///
+    /// Longer timeouts requested via `walletpassphrase` are reduced to this value.
+    pub max_unlock_duration: Option<u64>,
+
+    /// Path to a file containing the wallet passphrase, which `zallet start` uses to
+    /// unlock encrypted wallets.
+    ///
+    /// This lets Zallet restart unattended (for example under systemd) without leaving
+    /// the wallet locked. The passphrase is the file's contents, without any trailing
+    /// newline. The file may also be a named pipe, or a file descriptor such as
+    /// `/dev/fd/3`. Zallet refuses to start if a regular file is readable by other users.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    /// Note that on Windows, you must either use single quotes for this field's value, or
+    /// replace all backslashes `\` with forward slashes `/`.
+    pub passphrase_file: Option<PathBuf>,
+
+    /// Whether `zallet start` may read the wallet passphrase from the
+    /// `ZALLET_WALLET_PASSPHRASE` environment variable.
+    ///
+    /// This is only used if `passphrase_file` is not set. Environment variables are
+    /// weaker protection for secrets than files: they are inherited by child processes,
+    /// and can often be read by other processes running as the same user, so this must
+    /// be explicitly enabled.
+    pub passphrase_from_env: Option<bool>,
+
+    /// The seed that legacy ZIP 32 account numbers are resolved against.
+    ///
+    /// JSON-RPC methods inherited from `zcashd` identify accounts by their ZIP 32 account
+    /// number, which is only meaningful relative to a seed. By default, account numbers
+    /// are rejected if the wallet contains more than one seed. If this is set to the
+    /// fingerprint of a seed in the wallet, account numbers are instead always resolved
+    /// against that seed.
+    #[serde(default, with = "seedfp")]
+    pub default_seed_fingerprint: Option<SeedFingerprint>,
+}
+
+#[cfg(zallet_build = "wallet")]
+impl KeyStoreSection {
+    /// Path to the age identity file that encrypts key material.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    ///
+    /// Default is `encryption-identity.txt`.
+    fn encryption_identity(&self) -> &Path {
+        self.encryption_identity
+            .as_deref()
+            .unwrap_or_else(|| Path::new("encryption-identity.txt"))
+    }
+
+    /// Whether to require a confirmed backup of a mnemonic seed phrase before deriving
+    /// new accounts from it.
+    ///
+    /// If enabled, `z_getnewaccount` will refuse to derive an account from a mnemonic
+    /// (and `getnewaddress` will refuse to derive an address from it) until its backup
+    /// has been confirmed with the `walletconfirmbackup` JSON-RPC method.
+    /// Mnemonics added with `zallet import-mnemonic` or `zallet migrate-zcashd-wallet` are
+    /// already backed up.
+    ///
+    /// Default is `true`.
+    pub fn require_backup(&self) -> bool {
+        self.require_backup.unwrap_or(true)
+    }
+
+    /// Whether to refuse to run without passphrase-encrypted key material.
+    ///
+    /// Default is `false`.
+    pub fn require_encryption(&self) -> bool {
+        self.require_encryption.unwrap_or(false)
+    }
+
+    /// Whether the `walletpassphrase` JSON-RPC method may unlock the wallet.
+    ///
+    /// Default is `true`.
+    pub fn allow_unlock(&self) -> bool {
+        self.allow_unlock.unwrap_or(true)
+    }
+
+    /// The maximum duration for which `walletpassphrase` may unlock the wallet.
+    ///
+    /// Default is 86400 seconds (one day).
+    pub fn max_unlock_duration(&self) -> Duration {
+        Duration::from_secs(self.max_unlock_duration.unwrap_or(86400))
+    }
+
+    /// Whether `zallet start` may read the wallet passphrase from the
+    /// `ZALLET_WALLET_PASSPHRASE` environment variable.
+    ///
+    /// Default is `false`.
+    pub fn passphrase_from_env(&self) -> bool {
+        self.passphrase_from_env.unwrap_or(false)
+    }
+}
+
+/// Note management configuration section.
+//
+// TODO: Decide whether this should be part of `[builder]`.
+//       https://github.com/zcash/wallet/issues/251
+#[cfg(zallet_build = "wallet")]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+#[serde(deny_unknown_fields)]
+pub struct NoteManagementSection {
+    /// The value, in zatoshis, below which a shielded output is considered dust.
+    ///
+    /// Payments of less than this value to shielded recipients are rejected. Change
+    /// of less than this value is added to the transaction fee instead of being sent
+    /// to a change output, as it would cost more to spend than it is worth.
+    pub dust_threshold: Option<u32>,
+
+    /// The minimum value that Zallet should target for each shielded note in the wallet.
+    pub min_note_value: Option<u32>,
+
+    /// The target number of shielded notes with value at least `min_note_value` that
+    /// Zallet should aim to maintain within each account in the wallet.
+    ///
+    /// If an account contains fewer such notes, Zallet will split larger notes (in change
+    /// outputs of other transactions) to achieve the target.
+    pub target_note_count: Option<NonZeroU16>,
+
+    /// The value, in zatoshis, below which a transparent output is considered dust.
+    ///
+    /// Payments of less than this value to transparent recipients are rejected.
+    pub transparent_dust_threshold: Option<u32>,
+}
+
+#[cfg(zallet_build = "wallet")]
+impl NoteManagementSection {
+    /// The value below which a shielded output is considered dust.
+    ///
+    /// Payments of less than this value to shielded recipients are rejected. Change
+    /// of less than this value is added to the transaction fee instead of being sent
+    /// to a change output, as it would cost more to spend than it is worth.
+    ///
+    /// Default is 5000 (the ZIP 317 marginal fee).
+    pub fn dust_threshold(&self) -> Zatoshis {
+        Zatoshis::const_from_u64(self.dust_threshold.unwrap_or(5000).into())
+    }
+
+    /// The minimum value that Zallet should target for each shielded note in the wallet.
+    ///
+    /// Default is 100_0000.
+    pub fn min_note_value(&self) -> Zatoshis {
+        Zatoshis::const_from_u64(self.min_note_value.unwrap_or(100_0000).into())
+    }
+
+    /// The target number of shielded notes with value at least `min_note_value` that
+    /// Zallet should aim to maintain within each account in the wallet.
+    ///
+    /// If an account contains fewer such notes, Zallet will split larger notes (in change
+    /// outputs of other transactions) to achieve the target.
+    ///
+    /// Default is 4.
+    pub fn target_note_count(&self) -> NonZeroU16 {
+        self.target_note_count
+            .unwrap_or_else(|| NonZeroU16::new(4).expect("valid"))
+    }
+
+    /// The value below which a transparent output is considered dust.
+    ///
+    /// Payments of less than this value to transparent recipients are rejected.
+    ///
+    /// Default is 54 (the threshold below which `zcashd` considers a P2PKH output to be
+    /// non-standard).
+    pub fn transparent_dust_threshold(&self) -> Zatoshis {
+        Zatoshis::const_from_u64(self.transparent_dust_threshold.unwrap_or(54).into())
+    }
+
+    pub(crate) fn split_policy(&self) -> SplitPolicy {
+        SplitPolicy::with_min_output_value(self.target_note_count().into(), self.min_note_value())
+    }
+
+    pub(crate) fn dust_policy(&self) -> DustOutputPolicy {
+        DustOutputPolicy::new(DustAction::AddDustToFee, Some(self.dust_threshold()))
+    }
+}
+
+/// Settings for Zallet's logs.
+///
+/// Only `zallet start` writes to the log file; other commands always log to stderr. On
+/// Unix systems, sending `SIGHUP` to Zallet (or calling the `reopenlogs` JSON-RPC method)
+/// reopens the log file, so that it can also be rotated by an external tool such as
+/// `logrotate`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingSection {
+    /// Which events to log, as a comma-separated list of `tracing` filter directives.
+    ///
+    /// A directive is either a level (`error`, `warn`, `info`, `debug`, or `trace`) that
+    /// applies to every module, or a module path and the level for that module. For
+    /// example, `"info,zallet::components::json_rpc=debug"` additionally logs each
+    /// JSON-RPC request.
+    ///
+    /// The `RUST_LOG` environment variable takes precedence over this if it is set, and
+    /// the `--verbose` flag takes precedence over both.
+    pub filter: Option<String>,
+
+    /// The format of log lines.
+    ///
+    /// - `text`: human-readable lines.
+    /// - `json`: a JSON object per line, for log collectors.
+    pub format: Option<LogFormat>,
+
+    /// Where `zallet start` writes its logs.
+    ///
+    /// - `stderr`: the standard error stream.
+    /// - `file`: the file at `logging.file`.
+    /// - `both`: both of the above.
+    pub output: Option<LogOutput>,
+
+    /// Path to the log file.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    /// Note that on Windows, you must either use single quotes for this field's value, or
+    /// replace all backslashes `\` with forward slashes `/`.
+    pub file: Option<PathBuf>,
+
+    /// When Zallet rotates the log file.
+    ///
+    /// - `never`: the log file is never rotated by Zallet.
+    /// - `hourly`, `daily`: the log file is rotated when the first event of each hour
+    ///   or day (in UTC) is logged.
+    /// - `size`: the log file is rotated when it would exceed `logging.max_file_size`.
+    ///
+    /// The current log file is renamed with the suffix `.1`, after renaming any older
+    /// log files from `.1` to `.2` and so on.
+    pub rotation: Option<LogRotation>,
+
+    /// The size (in MiB) at which the log file is rotated, if `logging.rotation` is
+    /// `size`.
+    pub max_file_size: Option<NonZeroU32>,
+
+    /// The number of rotated log files to keep.
+    ///
+    /// Older log files are deleted when the log file is rotated.
+    pub max_files: Option<u32>,
+}
+
+impl LoggingSection {
+    /// Which events to log, as a comma-separated list of `tracing` filter directives.
+    ///
+    /// Default is `info`.
+    pub fn filter(&self) -> &str {
+        self.filter.as_deref().unwrap_or("info")
+    }
+
+    /// The format of log lines.
+    ///
+    /// Default is `text`.
+    pub fn format(&self) -> LogFormat {
+        self.format.unwrap_or_default()
+    }
+
+    /// Where `zallet start` writes its logs.
+    ///
+    /// Default is `stderr`.
+    pub fn output(&self) -> LogOutput {
+        self.output.unwrap_or_default()
+    }
+
+    /// Path to the log file.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    ///
+    /// Default is `zallet.log`.
+    fn file(&self) -> &Path {
+        self.file
+            .as_deref()
+            .unwrap_or_else(|| Path::new("zallet.log"))
+    }
+
+    /// When Zallet rotates the log file.
+    ///
+    /// Default is `never`.
+    pub fn rotation(&self) -> LogRotation {
+        self.rotation.unwrap_or_default()
+    }
+
+    /// The size (in bytes) at which the log file is rotated, if `logging.rotation` is
+    /// `size`.
+    ///
+    /// Default is 100 MiB.
+    pub fn max_file_size(&self) -> u64 {
+        u64::from(self.max_file_size.map_or(100, NonZeroU32::get)) * 1024 * 1024
+    }
+
+    /// The number of rotated log files to keep.
+    ///
+    /// Default is 7.
+    pub fn max_files(&self) -> u32 {
+        self.max_files.unwrap_or(7)
+    }
+}
+
+/// The format of log lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// A JSON object per line.
+    Json,
+}
+
+/// Where `zallet start` writes its logs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogOutput {
+    /// The standard error stream.
+    #[default]
+    Stderr,
+    /// The log file.
+    File,
+    /// Both the standard error stream and the log file.
+    Both,
+}
+
+impl LogOutput {
+    /// Returns whether logs are written to the standard error stream.
+    pub fn to_stderr(self) -> bool {
+        matches!(self, Self::Stderr | Self::Both)
+    }
+
+    /// Returns whether logs are written to the log file.
+    pub fn to_file(self) -> bool {
+        matches!(self, Self::File | Self::Both)
+    }
+}
+
+/// When Zallet rotates the log file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    /// Never rotate the log file.
+    #[default]
+    Never,
+    /// Rotate the log file every hour.
+    Hourly,
+    /// Rotate the log file every day.
+    Daily,
+    /// Rotate the log file when it reaches `logging.max_file_size`.
+    Size,
+}
+
+/// Settings for the Prometheus metrics endpoint.
+///
+/// Metrics are served in the Prometheus text format at `/metrics`, on a listener that is
+/// separate from the JSON-RPC server. The endpoint does not require authorization, so
+/// `metrics.bind` should only be reachable by the monitoring system.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsSection {
+    /// The address that the metrics endpoint listens on.
+    pub bind: Option<SocketAddr>,
+
+    /// Whether to serve metrics.
+    pub enable: Option<bool>,
+
+    /// Whether to report the balance of each value pool in each wallet.
+    ///
+    /// Anyone who can reach the metrics endpoint can read these balances, so they are
+    /// only reported if this is enabled.
+    pub wallet_balances: Option<bool>,
+}
+
+impl MetricsSection {
+    /// The address that the metrics endpoint listens on.
+    ///
+    /// Default is `127.0.0.1:9464`.
+    pub fn bind(&self) -> SocketAddr {
+        self.bind
+            .unwrap_or_else(|| SocketAddr::from(([127, 0, 0, 1], 9464)))
+    }
+
+    /// Whether to serve metrics.
+    ///
+    /// Default is `false`.
+    pub fn enable(&self) -> bool {
+        self.enable.unwrap_or(false)
+    }
+
+    /// Whether to report the balance of each value pool in each wallet.
+    ///
+    /// Default is `false`.
+    pub fn wallet_balances(&self) -> bool {
+        self.wallet_balances.unwrap_or(false)
+    }
+}
+
+/// Settings for push notifications of wallet events.
+///
+/// Each event is delivered to every configured webhook as an HTTP `POST` request with a
+/// JSON body. Deliveries that fail are retried with exponential backoff.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationsSection {
+    /// Whether to warn when a shielded address that has already been paid from outside
+    /// the wallet receives a payment in another transaction.
+    ///
+    /// The warning is logged, and sent to webhooks as an `address.reused` event. Disable
+    /// this if you intentionally publish a single address (for example, to receive
+    /// donations).
+    pub address_reuse: Option<bool>,
+
+    /// The number of confirmations at which a `transaction.confirmed` event is sent for
+    /// a wallet transaction.
+    pub confirmations: Option<NonZeroU32>,
+
+    /// The maximum number of attempts made to deliver each event to a webhook.
+    pub max_attempts: Option<NonZeroU32>,
+
+    /// The maximum number of events that may be waiting for delivery to each webhook.
+    ///
+    /// If a webhook falls this far behind, further events for it are dropped with a
+    /// warning.
+    pub queue_size: Option<usize>,
+
+    /// Timeout (in seconds) for each attempt to deliver an event.
+    pub timeout: Option<u64>,
+
+    /// A list of webhooks that wallet events are delivered to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookSection>,
+}
+
+impl NotificationsSection {
+    /// Whether to warn when a shielded address that has already been paid from outside
+    /// the wallet receives a payment in another transaction.
+    ///
+    /// Default is `true`.
+    pub fn address_reuse(&self) -> bool {
+        self.address_reuse.unwrap_or(true)
+    }
+
+    /// The number of confirmations at which a `transaction.confirmed` event is sent for
+    /// a wallet transaction.
+    ///
+    /// Default is 10 confirmations.
+    pub fn confirmations(&self) -> NonZeroU32 {
+        self.confirmations
+            .unwrap_or_else(|| NonZeroU32::new(10).expect("valid"))
+    }
+
+    /// The maximum number of attempts made to deliver each event to a webhook.
+    ///
+    /// Default is 6 attempts.
+    pub fn max_attempts(&self) -> NonZeroU32 {
+        self.max_attempts
+            .unwrap_or_else(|| NonZeroU32::new(6).expect("valid"))
+    }
+
+    /// The maximum number of events that may be waiting for delivery to each webhook.
+    ///
+    /// Default is 1000 events.
+    pub fn queue_size(&self) -> usize {
+        self.queue_size.unwrap_or(1000)
+    }
+
+    /// Timeout for each attempt to deliver an event.
+    ///
+    /// Default is 10 seconds.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(10))
+    }
+}
+
+/// A webhook that wallet events are delivered to.
+#[derive(Clone, Debug, Deserialize, Serialize, Documented, DocumentedFields)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookSection {
+    /// The `http` or `https` URL that events are sent to.
+    pub url: String,
+
+    /// A secret used to sign the events sent to this webhook.
+    ///
+    /// If set, each request carries an `X-Zallet-Signature` header containing
+    /// `sha256=` followed by the hex-encoded HMAC-SHA256 of the raw request body, keyed
+    /// with this secret.
+    #[serde(serialize_with = "serialize_secret")]
+    pub secret: Option<SecretString>,
+}
+
+/// Settings for the JSON-RPC interface.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+#[serde(deny_unknown_fields)]
+pub struct RpcSection {
+    /// Addresses to listen for JSON-RPC connections.
+    ///
+    /// Note: The RPC server is disabled by default. To enable the RPC server, set a
+    /// listen address in the config:
+    /// ```toml
+    /// [rpc]
+    /// bind = ["127.0.0.1:28232"]
+    /// ```
+    ///
+    /// On Unix, an address can instead be the path of a Unix domain socket, such as
+    /// `"/run/zallet/rpc.sock"`. Paths must contain a `/`; relative paths (such as
+    /// `"./rpc.sock"`) are resolved against the data directory.
+    ///
+    /// # Security
+    ///
+    /// If you bind Zallet's RPC port to a public IP address, anyone on the internet can
+    /// view your transactions and spend your funds.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bind: Vec<RpcBind>,
+
+    /// IP addresses and CIDR ranges (such as `"192.168.1.0/24"` or `"fd00::/8"`) of the
+    /// clients that may connect to the JSON-RPC interface, in addition to loopback
+    /// addresses.
+    ///
+    /// Requests from other clients are rejected with `403 Forbidden` before they are
+    /// authorized. If this is empty, only clients on the same machine can connect, even
+    /// if `bind` contains a non-loopback address. Clients connecting to a Unix domain
+    /// socket are not filtered.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_ip: Vec<AllowedIp>,
+
+    /// The permissions (as an octal mode, such as `0o660`) of the Unix domain sockets
+    /// in `bind`.
+    ///
+    /// Only users who can write to a socket can connect to it.
+    pub unix_socket_mode: Option<u32>,
+
+    /// Timeout (in seconds) during HTTP requests.
+    pub timeout: Option<u64>,
+
+    /// Time (in seconds) that Zallet waits during shutdown for in-flight JSON-RPC
+    /// requests and executing async operations to finish.
+    ///
+    /// Async operations that are still executing when this time elapses are stopped
+    /// abruptly, and reported as interrupted when Zallet next starts.
+    pub shutdown_timeout: Option<u64>,
+
+    /// How much detail to log about each JSON-RPC request.
+    ///
+    /// - `none`: don't log requests.
+    /// - `calls`: log the method, caller address, duration, and outcome of each
+    ///   request, along with a correlation ID that async operations also report.
+    /// - `params`: additionally log the parameters of each request. Parameters of
+    ///   methods that handle secrets, such as passphrases or private keys, are
+    ///   always redacted.
+    ///
+    /// Requests are logged at the `debug` level, so `logging.filter` must also enable
+    /// `debug` events for `zallet::components::json_rpc` for them to be output.
+    pub request_logging: Option<RequestLogging>,
+
+    /// The number of blocks that the wallet may be behind the chain tip while the
+    /// `/ready` HTTP endpoint reports Zallet as ready.
+    ///
+    /// The `/health` and `/ready` endpoints are served on the JSON-RPC listen address,
+    /// and do not require authorization.
+    pub ready_max_scan_lag: Option<u32>,
+
+    /// The maximum number of results that a listing method such as `z_listtransactions`
+    /// or `listunspent` returns in a single response.
+    ///
+    /// A request that would return more results than this fails, unless it uses the
+    /// `cursor` parameter to fetch the results in pages of at most this size.
+    pub max_list_results: Option<u32>,
+
+    /// The maximum number of calls in a JSON-RPC batch request.
+    ///
+    /// Larger batches are rejected with an error, without any of their calls being
+    /// processed.
+    pub max_batch_size: Option<u32>,
+
+    /// The maximum size of a JSON-RPC request, in bytes.
+    ///
+    /// Larger requests are rejected with an error before they are parsed.
+    pub max_request_body_bytes: Option<u32>,
+
+    /// The maximum size of a JSON-RPC response, in bytes.
+    ///
+    /// A call whose response would be larger than this fails with an error instead.
+    pub max_response_body_bytes: Option<u32>,
+
+    /// The maximum number of JSON-RPC connections that may be open at once.
+    ///
+    /// Connections beyond this are closed as soon as they are accepted. Unlimited if
+    /// unset.
+    pub max_connections: Option<u32>,
+
+    /// Whether to reject every JSON-RPC method that can change the wallet.
+    ///
+    /// When enabled, methods that send funds, import, export, or sign with keys, lock or
+    /// unlock the wallet, create accounts or addresses, load or unload wallets, or
+    /// otherwise change the state of Zallet fail with a "read-only mode" error. Methods that only query
+    /// the wallet or the chain are unaffected.
+    #[cfg(zallet_build = "wallet")]
+    pub read_only: Option<bool>,
+
+    /// How requests to the JSON-RPC server are authorized.
+    ///
+    /// - `cookie`: when the JSON-RPC server starts, Zallet writes a random password to
+    ///   `cookie_file`. Requests must authorize with it (as the user `__cookie__`), or
+    ///   as one of the users configured in `rpc.auth`. The file is removed when Zallet
+    ///   stops, and a new password is generated each time it starts.
+    /// - `none`: requests are not authorized. Only use this if access to the JSON-RPC
+    ///   port is controlled in some other way, such as by an authenticating proxy.
+    ///   `rpc.auth` must be empty.
+    pub auth_method: Option<RpcAuthMethod>,
+
+    /// Path to the cookie file that Zallet writes if `auth_method` is `cookie`.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    /// Note that on Windows, you must either use single quotes for this field's value, or
+    /// replace all backslashes `\` with forward slashes `/`.
+    pub cookie_file: Option<PathBuf>,
+
+    /// Path to a PEM file containing the TLS certificate chain of the JSON-RPC server.
+    ///
+    /// If this and `tls_key_path` are set, the JSON-RPC server only accepts HTTPS
+    /// connections. On Unix, the certificate chain and private key are reloaded when
+    /// Zallet receives `SIGHUP`; on other platforms, Zallet must be restarted to use a
+    /// new certificate.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    pub tls_cert_path: Option<PathBuf>,
+
+    /// Path to a PEM file containing the private key for `tls_cert_path`.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    pub tls_key_path: Option<PathBuf>,
+
+    /// Path to a PEM file containing the certificates of the CAs that client
+    /// certificates must be issued by.
+    ///
+    /// If set, TLS clients must present a certificate issued by one of these CAs. This
+    /// can be combined with `auth_method = "none"` to authorize requests with client
+    /// certificates instead of passwords.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    pub tls_client_ca_path: Option<PathBuf>,
+
+    /// Limits on the rate at which JSON-RPC requests are served.
+    pub rate_limit: RpcRateLimitSection,
+
+    /// A list of users for which access to the JSON-RPC interface is authorized.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auth: Vec<RpcAuthSection>,
+}
+
+impl RpcSection {
+    /// The permissions of the Unix domain sockets in `bind`.
+    ///
+    /// Default is `0o600` (only the user running Zallet can connect).
+    pub fn unix_socket_mode(&self) -> u32 {
+        self.unix_socket_mode.unwrap_or(0o600)
+    }
+
+    /// Timeout during HTTP requests.
+    ///
+    /// Default is 30 seconds.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(30))
+    }
+
+    /// Time that Zallet waits during shutdown for in-flight JSON-RPC requests and
+    /// executing async operations to finish.
+    ///
+    /// Default is 30 seconds.
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout.unwrap_or(30))
+    }
+
+    /// How much detail to log about each JSON-RPC request.
+    ///
+    /// Default is `calls`.
+    pub fn request_logging(&self) -> RequestLogging {
+        self.request_logging.unwrap_or_default()
+    }
+
+    /// The number of blocks that the wallet may be behind the chain tip while the
+    /// `/ready` HTTP endpoint reports Zallet as ready.
+    ///
+    /// Default is 10 blocks.
+    pub fn ready_max_scan_lag(&self) -> u32 {
+        self.ready_max_scan_lag.unwrap_or(10)
+    }
+
+    /// The maximum number of results that a listing method returns in a single response.
+    ///
+    /// Default is 50000 results.
+    pub fn max_list_results(&self) -> u32 {
+        self.max_list_results.unwrap_or(50_000)
+    }
+
+    /// The maximum number of calls in a JSON-RPC batch request.
+    ///
+    /// Default is 1000 calls.
+    pub fn max_batch_size(&self) -> u32 {
+        self.max_batch_size.unwrap_or(1000)
+    }
+
+    /// The maximum size of a JSON-RPC request, in bytes.
+    ///
+    /// Default is 10 MiB.
+    pub fn max_request_body_bytes(&self) -> u32 {
+        self.max_request_body_bytes.unwrap_or(10 * 1024 * 1024)
+    }
+
+    /// The maximum size of a JSON-RPC response, in bytes.
+    ///
+    /// Default is 10 MiB.
+    pub fn max_response_body_bytes(&self) -> u32 {
+        self.max_response_body_bytes.unwrap_or(10 * 1024 * 1024)
+    }
+
+    /// The maximum number of JSON-RPC connections that may be open at once.
+    ///
+    /// Default is unlimited.
+    pub fn max_connections(&self) -> Option<NonZeroU32> {
+        self.max_connections.and_then(NonZeroU32::new)
+    }
+
+    /// Whether to reject every JSON-RPC method that can change the wallet.
+    ///
+    /// Default is `false`.
+    #[cfg(zallet_build = "wallet")]
+    pub fn read_only(&self) -> bool {
+        self.read_only.unwrap_or(false)
+    }
+
+    /// How requests to the JSON-RPC server are authorized.
+    ///
+    /// Default is `cookie`.
+    pub fn auth_method(&self) -> RpcAuthMethod {
+        self.auth_method.unwrap_or_default()
+    }
+
+    /// Path to the cookie file that Zallet writes if `auth_method` is `cookie`.
+    ///
+    /// This can be either an absolute path, or a path relative to the data directory.
+    ///
+    /// Default is `.cookie`.
+    fn cookie_file(&self) -> &Path {
+        self.cookie_file
+            .as_deref()
+            .unwrap_or_else(|| Path::new(".cookie"))
+    }
+}
+
+/// How requests to the JSON-RPC server are authorized.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RpcAuthMethod {
+    /// Authorize requests with a cookie file, or with the users in `rpc.auth`.
+    #[default]
+    Cookie,
+    /// Don't authorize requests.
+    None,
+}
+
+/// How much detail to log about each JSON-RPC request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestLogging {
+    /// Don't log requests.
+    None,
+    /// Log the method, caller address, duration, and outcome of each request.
+    #[default]
+    Calls,
+    /// Additionally log the (possibly redacted) parameters of each request.
+    Params,
+}
+
+/// Limits on the rate at which JSON-RPC requests are served.
+///
+/// Each request uses up an amount of the budget equal to its method's weight. Budget is
+/// replenished continuously at the configured rate, and a request that exceeds the
+/// remaining budget fails with an error that says when to retry.
 #[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
 #[serde(deny_unknown_fields)]
-pub struct IndexerSection {
-    /// IP address and port of the JSON-RPC interface for the full node / validator being
-    /// used as a data source.
+pub struct RpcRateLimitSection {
+    /// The budget that each client may use in a burst, after being idle.
     ///
-    /// If unset, connects on localhost to the standard JSON-RPC port for mainnet or
-    /// testnet (as appropriate).
-    pub validator_address: Option<String>,
+    /// Defaults to `client_requests_per_second` if unset.
+    pub client_burst: Option<u32>,
 
-    /// Path to the validator cookie file.
+    /// How clients are identified for `client_requests_per_second`.
     ///
-    /// If set, cookie file authorization will be used.
-    pub validator_cookie_path: Option<PathBuf>,
+    /// - `address`: by the IP address that they connect from.
+    /// - `user`: by the username that they authenticate as.
+    pub client_key: Option<RateLimitClientKey>,
 
-    /// Full node / validator Username.
-    pub validator_user: Option<String>,
+    /// The budget that each client may use per second.
+    ///
+    /// Unlimited if unset.
+    pub client_requests_per_second: Option<u32>,
 
-    /// Full node / validator Password.
-    pub validator_password: Option<String>,
+    /// Whether clients that connect from a loopback address or over a Unix domain
+    /// socket are exempt from the rate limits.
+    ///
+    /// Their requests still count towards `requests_per_second`.
+    pub exempt_local: Option<bool>,
 
-    /// Path to the folder where the indexer maintains its state.
+    /// The weights of specific RPC methods, each in the form `"method:weight"`.
     ///
-    /// This can be either an absolute path, or a path relative to the data directory.
-    /// Note that on Windows, you must either use single quotes for this field's value, or
-    /// replace all backslashes `\` with forward slashes `/`.
-    pub db_path: Option<PathBuf>,
-}
+    /// Methods that are not listed have a weight of 1.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub method_weights: Vec<MethodWeight>,
 
-impl IndexerSection {
-    /// Path to the folder where the indexer maintains its state.
+    /// The budget that all clients may use per second in total.
     ///
-    /// This can be either an absolute path, or a path relative to the data directory.
+    /// Unlimited if unset.
+    pub requests_per_second: Option<u32>,
+}
+
+impl RpcRateLimitSection {
+    /// The budget that each client may use in a burst, after being idle.
     ///
-    /// Default is `zaino`.
-    fn db_path(&self) -> &Path {
-        self.db_path
-            .as_deref()
-            .unwrap_or_else(|| Path::new("zaino"))
+    /// Default is `client_requests_per_second`.
+    pub fn client_burst(&self) -> Option<NonZeroU32> {
+        self.client_burst
+            .and_then(NonZeroU32::new)
+            .or_else(|| self.client_requests_per_second())
     }
-}
 
-/// Settings for the key store.
-#[cfg(zallet_build = "wallet")]
-#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
-#[serde(deny_unknown_fields)]
-pub struct KeyStoreSection {
-    /// Path to the age identity file that encrypts key material.
+    /// How clients are identified for `client_requests_per_second`.
     ///
-    /// This can be either an absolute path, or a path relative to the data directory.
-    /// Note that on Windows, you must either use single quotes for this field's value, or
-    /// replace all backslashes `\` with forward slashes `/`.
-    pub encryption_identity: Option<PathBuf>,
+    /// Default is `address`.
+    pub fn client_key(&self) -> RateLimitClientKey {
+        self.client_key.unwrap_or_default()
+    }
 
-    /// By default, the wallet will not allow generation of new spending keys & addresses
-    /// from the mnemonic seed until the backup of that seed has been confirmed with the
-    /// `zcashd-wallet-tool` utility. A user may start zallet with `--walletrequirebackup=false`
-    /// to allow generation of spending keys even if the backup has not yet been confirmed.
-    pub require_backup: Option<bool>,
-}
+    /// The budget that each client may use per second.
+    ///
+    /// Default is unlimited.
+    pub fn client_requests_per_second(&self) -> Option<NonZeroU32> {
+        self.client_requests_per_second.and_then(NonZeroU32::new)
+    }
 
-#[cfg(zallet_build = "wallet")]
-impl KeyStoreSection {
-    /// Path to the age identity file that encrypts key material.
+    /// Whether clients that connect from a loopback address or over a Unix domain
+    /// socket are exempt from the rate limits.
     ///
-    /// This can be either an absolute path, or a path relative to the data directory.
+    /// Default is `false`.
+    pub fn exempt_local(&self) -> bool {
+        self.exempt_local.unwrap_or(false)
+    }
+
+    /// The weight of the given RPC method.
     ///
-    /// Default is `encryption-identity.txt`.
-    fn encryption_identity(&self) -> &Path {
-        self.encryption_identity
-            .as_deref()
-            .unwrap_or_else(|| Path::new("encryption-identity.txt"))
+    /// Default is 1.
+    pub fn method_weight(&self, method: &str) -> u32 {
+        self.method_weights
+            .iter()
+            .rev()
+            .find(|weight| weight.method == method)
+            .map_or(1, |weight| weight.weight)
     }
 
-    /// Whether to require a confirmed wallet backup.
+    /// The budget that all clients may use per second in total.
     ///
-    /// By default, the wallet will not allow generation of new spending keys & addresses
-    /// from the mnemonic seed until the backup of that seed has been confirmed with the
-    /// `zcashd-wallet-tool` utility. A user may start zallet with `--walletrequirebackup=false`
-    /// to allow generation of spending keys even if the backup has not yet been confirmed.
-    pub fn require_backup(&self) -> bool {
-        self.require_backup.unwrap_or(true)
+    /// Default is unlimited.
+    pub fn requests_per_second(&self) -> Option<NonZeroU32> {
+        self.requests_per_second.and_then(NonZeroU32::new)
     }
 }
 
-/// Note management configuration section.
-//
-// TODO: Decide whether this should be part of `[builder]`.
-//       https://github.com/zcash/wallet/issues/251
-#[cfg(zallet_build = "wallet")]
-#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
-#[serde(deny_unknown_fields)]
-pub struct NoteManagementSection {
-    /// The minimum value that Zallet should target for each shielded note in the wallet.
-    pub min_note_value: Option<u32>,
+/// An address that the JSON-RPC server listens on.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub enum RpcBind {
+    /// A TCP socket address.
+    Tcp(SocketAddr),
+    /// The path of a Unix domain socket.
+    Unix(PathBuf),
+}
 
-    /// The target number of shielded notes with value at least `min_note_value` that
-    /// Zallet should aim to maintain within each account in the wallet.
-    ///
-    /// If an account contains fewer such notes, Zallet will split larger notes (in change
-    /// outputs of other transactions) to achieve the target.
-    pub target_note_count: Option<NonZeroU16>,
+impl TryFrom<String> for RpcBind {
+    type Error = &'static str;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if let Ok(addr) = value.parse() {
+            Ok(Self::Tcp(addr))
+        } else if value.contains('/') {
+            Ok(Self::Unix(value.into()))
+        } else {
+            // Don't mistake a malformed socket address (such as one without a port) for
+            // a file name.
+            Err("Invalid `rpc.bind` entry: expected a socket address or a path")
+        }
+    }
 }
 
-#[cfg(zallet_build = "wallet")]
-impl NoteManagementSection {
-    /// The minimum value that Zallet should target for each shielded note in the wallet.
-    ///
-    /// Default is 100_0000.
-    pub fn min_note_value(&self) -> Zatoshis {
-        Zatoshis::const_from_u64(self.min_note_value.unwrap_or(100_0000).into())
+impl From<RpcBind> for String {
+    fn from(bind: RpcBind) -> Self {
+        bind.to_string()
     }
+}
 
-    /// The target number of shielded notes with value at least `min_note_value` that
-    /// Zallet should aim to maintain within each account in the wallet.
-    ///
-    /// If an account contains fewer such notes, Zallet will split larger notes (in change
-    /// outputs of other transactions) to achieve the target.
+impl fmt::Display for RpcBind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// A range of IP addresses that may connect to the JSON-RPC interface.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub struct AllowedIp(IpNet);
+
+impl AllowedIp {
+    /// Returns whether `addr` is within this range.
     ///
-    /// Default is 4.
-    pub fn target_note_count(&self) -> NonZeroU16 {
-        self.target_note_count
-            .unwrap_or_else(|| NonZeroU16::new(4).expect("valid"))
+    /// IPv4-mapped IPv6 addresses (which clients of a dual-stack socket connect from)
+    /// match the IPv4 ranges that contain the IPv4 address.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        self.0.contains(&addr) || self.0.contains(&addr.to_canonical())
     }
+}
 
-    pub(crate) fn split_policy(&self) -> SplitPolicy {
-        SplitPolicy::with_min_output_value(self.target_note_count().into(), self.min_note_value())
+impl From<IpNet> for AllowedIp {
+    fn from(net: IpNet) -> Self {
+        Self(net)
     }
 }
 
-/// Settings for the JSON-RPC interface.
-#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
-#[serde(deny_unknown_fields)]
-pub struct RpcSection {
-    /// Addresses to listen for JSON-RPC connections.
-    ///
-    /// Note: The RPC server is disabled by default. To enable the RPC server, set a
-    /// listen address in the config:
-    /// ```toml
-    /// [rpc]
-    /// bind = ["127.0.0.1:28232"]
-    /// ```
-    ///
-    /// # Security
-    ///
-    /// If you bind Zallet's RPC port to a public IP address, anyone on the internet can
-    /// view your transactions and spend your funds.
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub bind: Vec<SocketAddr>,
+impl TryFrom<String> for AllowedIp {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value
+            .parse()
+            .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+            .map(Self)
+            .map_err(|_| {
+                format!(
+                    "Invalid `rpc.allow_ip` entry '{value}': expected an IP address or CIDR range"
+                )
+            })
+    }
+}
 
-    /// Timeout (in seconds) during HTTP requests.
-    pub timeout: Option<u64>,
+impl From<AllowedIp> for String {
+    fn from(allowed: AllowedIp) -> Self {
+        if allowed.0.prefix_len() == allowed.0.max_prefix_len() {
+            allowed.0.addr().to_string()
+        } else {
+            allowed.0.to_string()
+        }
+    }
+}
 
-    /// A list of users for which access to the JSON-RPC interface is authorized.
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub auth: Vec<RpcAuthSection>,
+/// How clients are identified when limiting the rate of their requests.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitClientKey {
+    /// By the IP address that the client connects from.
+    #[default]
+    Address,
+    /// By the username that the client authenticates as.
+    User,
 }
 
-impl RpcSection {
-    /// Timeout during HTTP requests.
-    ///
-    /// Default is 30 seconds.
-    pub fn timeout(&self) -> Duration {
-        Duration::from_secs(self.timeout.unwrap_or(30))
+/// The weight of a specific RPC method, for rate limiting.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub struct MethodWeight {
+    method: String,
+    weight: u32,
+}
+
+impl TryFrom<String> for MethodWeight {
+    type Error = &'static str;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (method, weight) = value
+            .split_once(':')
+            .filter(|(method, _)| !method.is_empty())
+            .ok_or("Invalid `method_weights` entry")?;
+
+        let weight = weight
+            .parse()
+            .ok()
+            .filter(|weight| *weight > 0)
+            .ok_or("Invalid `method_weights` entry")?;
+
+        Ok(Self {
+            method: method.into(),
+            weight,
+        })
+    }
+}
+
+impl From<MethodWeight> for String {
+    fn from(weight: MethodWeight) -> Self {
+        format!("{}:{}", weight.method, weight.weight)
     }
 }
 
@@ -635,7 +2508,7 @@ pub struct RpcAuthSection {
     /// The password for this user.
     ///
     /// This cannot be set when `pwhash` is set.
-    #[serde(serialize_with = "serialize_rpc_password")]
+    #[serde(serialize_with = "serialize_secret")]
     pub password: Option<SecretString>,
 
     /// A hash of the password for this user.
@@ -644,11 +2517,55 @@ pub struct RpcAuthSection {
     pub pwhash: Option<String>,
 }
 
-fn serialize_rpc_password<S: serde::Serializer>(
-    password: &Option<SecretString>,
+/// Settings for how Zallet scans the chain.
+///
+/// While the wallet is recovering its history, batches of blocks are downloaded ahead
+/// of the batch being scanned, so that downloading and scanning overlap. Within each
+/// batch, the outputs of the blocks are trial-decrypted in parallel on a pool of worker
+/// threads, and the results are committed to the wallet database in height order.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+#[serde(deny_unknown_fields)]
+pub struct SyncSection {
+    /// The number of batches of blocks that may be downloaded ahead of the batch being
+    /// scanned.
+    ///
+    /// Each batch contains up to 1000 compact blocks, which are held in memory until
+    /// they have been scanned.
+    pub max_in_flight_batches: Option<NonZeroU32>,
+
+    /// The number of worker threads used to trial-decrypt outputs.
+    ///
+    /// If unset, this is the number of CPU threads available to Zallet. The same threads
+    /// are also used to create proofs for transactions.
+    pub scan_workers: Option<NonZeroU32>,
+}
+
+impl SyncSection {
+    /// The number of batches of blocks that may be downloaded ahead of the batch being
+    /// scanned.
+    ///
+    /// Default is 2 batches.
+    pub fn max_in_flight_batches(&self) -> NonZeroU32 {
+        self.max_in_flight_batches
+            .unwrap_or_else(|| NonZeroU32::new(2).expect("valid"))
+    }
+
+    /// The number of worker threads used to trial-decrypt outputs.
+    ///
+    /// Default is the number of CPU threads available to Zallet.
+    pub fn scan_workers(&self) -> usize {
+        self.scan_workers.map_or_else(
+            || std::thread::available_parallelism().map_or(1, |n| n.get()),
+            |n| n.get() as usize,
+        )
+    }
+}
+
+fn serialize_secret<S: serde::Serializer>(
+    secret: &Option<SecretString>,
     serializer: S,
 ) -> Result<S::Ok, S::Error> {
-    match password {
+    match secret {
         Some(p) => p.expose_secret().serialize(serializer),
         None => None::<String>.serialize(serializer),
     }
@@ -662,6 +2579,8 @@ impl ZalletConfig {
         // make changes to the config structure.
         let conf = ZalletConfig::default();
         let field_defaults = [
+            builder("input_selection", conf.builder.input_selection()),
+            builder("max_fee", conf.builder.max_fee().into_u64()),
             builder(
                 "spend_zeroconf_change",
                 conf.builder.spend_zeroconf_change(),
@@ -670,33 +2589,94 @@ impl ZalletConfig {
                 "trusted_confirmations",
                 conf.builder.trusted_confirmations(),
             ),
-            builder("tx_expiry_delta", conf.builder.tx_expiry_delta()),
             builder(
                 "untrusted_confirmations",
                 conf.builder.untrusted_confirmations(),
             ),
+            builder_batching("max_payments", conf.builder.batching.max_payments()),
+            builder_batching("window", conf.builder.batching.window().as_secs()),
+            builder_limits(
+                "operation_result_size",
+                conf.builder.limits.operation_result_size(),
+            ),
+            builder_limits(
+                "operation_timeout",
+                conf.builder.limits.operation_timeout().as_secs(),
+            ),
+            builder_limits(
+                "operation_timeouts",
+                &conf.builder.limits.operation_timeouts,
+            ),
             builder_limits("orchard_actions", conf.builder.limits.orchard_actions()),
+            builder_limits(
+                "retained_operation_age",
+                conf.builder.limits.retained_operation_age().as_secs(),
+            ),
+            builder_limits(
+                "retained_operations",
+                conf.builder.limits.retained_operations(),
+            ),
+            builder_limits("sapling_spends", conf.builder.limits.sapling_spends()),
+            builder_limits(
+                "transparent_inputs",
+                conf.builder.limits.transparent_inputs(),
+            ),
             consensus(
                 "network",
                 crate::network::kind::Serializable(conf.consensus.network),
             ),
             consensus("regtest_nuparams", &conf.consensus.regtest_nuparams),
+            database("memo_index", conf.database.memo_index()),
             database("wallet", conf.database.wallet_path()),
+            external("audit_log", &conf.external.audit_log),
             external("broadcast", conf.external.broadcast()),
             external("export_dir", &conf.external.export_dir),
+            external(
+                "force_shutdown_timeout",
+                conf.external.force_shutdown_timeout().as_secs(),
+            ),
             external("notify", &conf.external.notify),
+            external("notify_concurrency", conf.external.notify_concurrency()),
+            external("notify_timeout", conf.external.notify_timeout().as_secs()),
+            external("pid_file", conf.external.pid_file()),
             features("as_of_version", &conf.features.as_of_version),
             features("legacy_pool_seed_fingerprint", None::<String>),
             indexer("validator_address", &conf.indexer.validator_address),
+            indexer(
+                "fallback_validator_addresses",
+                &conf.indexer.fallback_validator_addresses,
+            ),
             indexer("validator_cookie_path", &conf.indexer.validator_cookie_path),
             indexer("validator_user", &conf.indexer.validator_user),
             indexer("validator_password", &conf.indexer.validator_password),
             indexer("db_path", conf.indexer.db_path()),
+            indexer("proxy", &conf.indexer.proxy),
+            indexer("onion_only", conf.indexer.onion_only()),
+            #[cfg(zallet_build = "wallet")]
+            keystore("default_seed_fingerprint", None::<String>),
             #[cfg(zallet_build = "wallet")]
             keystore("encryption_identity", conf.keystore.encryption_identity()),
             #[cfg(zallet_build = "wallet")]
             keystore("require_backup", conf.keystore.require_backup()),
             #[cfg(zallet_build = "wallet")]
+            keystore("require_encryption", conf.keystore.require_encryption()),
+            #[cfg(zallet_build = "wallet")]
+            keystore("allow_unlock", conf.keystore.allow_unlock()),
+            #[cfg(zallet_build = "wallet")]
+            keystore(
+                "max_unlock_duration",
+                conf.keystore.max_unlock_duration().as_secs(),
+            ),
+            #[cfg(zallet_build = "wallet")]
+            keystore("passphrase_file", &conf.keystore.passphrase_file),
+            #[cfg(zallet_build = "wallet")]
+            keystore("passphrase_from_env", conf.keystore.passphrase_from_env()),
+            #[cfg(zallet_build = "wallet")]
+            note_management(
+                "dust_threshold",
+                conf.note_management.dust_threshold().into_u64(),
+            ),
+            #[cfg(zallet_build = "wallet")]
             note_management(
                 "min_note_value",
                 conf.note_management.min_note_value().into_u64(),
@@ -706,17 +2686,77 @@ impl ZalletConfig {
                 "target_note_count",
                 conf.note_management.target_note_count(),
             ),
+            #[cfg(zallet_build = "wallet")]
+            note_management(
+                "transparent_dust_threshold",
+                conf.note_management.transparent_dust_threshold().into_u64(),
+            ),
+            logging("filter", conf.logging.filter()),
+            logging("format", conf.logging.format()),
+            logging("output", conf.logging.output()),
+            logging("file", conf.logging.file()),
+            logging("rotation", conf.logging.rotation()),
+            logging(
+                "max_file_size",
+                conf.logging.max_file_size() / (1024 * 1024),
+            ),
+            logging("max_files", conf.logging.max_files()),
+            metrics("bind", conf.metrics.bind()),
+            metrics("enable", conf.metrics.enable()),
+            metrics("wallet_balances", conf.metrics.wallet_balances()),
+            notifications("address_reuse", conf.notifications.address_reuse()),
+            notifications("confirmations", conf.notifications.confirmations()),
+            notifications("max_attempts", conf.notifications.max_attempts()),
+            notifications("queue_size", conf.notifications.queue_size()),
+            notifications("timeout", conf.notifications.timeout().as_secs()),
             rpc("bind", &conf.rpc.bind),
+            rpc("allow_ip", &conf.rpc.allow_ip),
+            rpc("unix_socket_mode", conf.rpc.unix_socket_mode()),
             rpc("timeout", conf.rpc.timeout().as_secs()),
+            rpc("shutdown_timeout", conf.rpc.shutdown_timeout().as_secs()),
+            rpc("request_logging", conf.rpc.request_logging()),
+            rpc("ready_max_scan_lag", conf.rpc.ready_max_scan_lag()),
+            rpc("max_list_results", conf.rpc.max_list_results()),
+            rpc("max_batch_size", conf.rpc.max_batch_size()),
+            rpc("max_request_body_bytes", conf.rpc.max_request_body_bytes()),
+            rpc(
+                "max_response_body_bytes",
+                conf.rpc.max_response_body_bytes(),
+            ),
+            rpc("max_connections", conf.rpc.max_connections()),
+            #[cfg(zallet_build = "wallet")]
+            rpc("read_only", conf.rpc.read_only()),
+            rpc("auth_method", conf.rpc.auth_method()),
+            rpc("cookie_file", conf.rpc.cookie_file()),
+            rpc("tls_cert_path", &conf.rpc.tls_cert_path),
+            rpc("tls_key_path", &conf.rpc.tls_key_path),
+            rpc("tls_client_ca_path", &conf.rpc.tls_client_ca_path),
+            rpc_rate_limit("client_burst", conf.rpc.rate_limit.client_burst()),
+            rpc_rate_limit("client_key", conf.rpc.rate_limit.client_key()),
+            rpc_rate_limit(
+                "client_requests_per_second",
+                conf.rpc.rate_limit.client_requests_per_second(),
+            ),
+            rpc_rate_limit("exempt_local", conf.rpc.rate_limit.exempt_local()),
+            rpc_rate_limit("method_weights", &conf.rpc.rate_limit.method_weights),
+            rpc_rate_limit(
+                "requests_per_second",
+                conf.rpc.rate_limit.requests_per_second(),
+            ),
+            sync("max_in_flight_batches", conf.sync.max_in_flight_batches()),
+            // The default depends on the machine, so is not included.
+            sync("scan_workers", &conf.sync.scan_workers),
         ]
         .into_iter()
         .collect::<HashMap<_, _>>();
 
         // The glue that makes the above easy to maintain:
         const BUILDER: &str = "builder";
+        const BUILDER_BATCHING: &str = "builder.batching";
         const BUILDER_LIMITS: &str = "builder.limits";
         const CONSENSUS: &str = "consensus";
         const DATABASE: &str = "database";
+        const DATABASE_WALLETS: &str = "database.wallets";
         const EXTERNAL: &str = "external";
         const FEATURES: &str = "features";
         const FEATURES_DEPRECATED: &str = "features.deprecated";
@@ -726,14 +2766,26 @@ impl ZalletConfig {
         const KEYSTORE: &str = "keystore";
         #[cfg(zallet_build = "wallet")]
         const NOTE_MANAGEMENT: &str = "note_management";
+        const LOGGING: &str = "logging";
+        const METRICS: &str = "metrics";
+        const NOTIFICATIONS: &str = "notifications";
+        const NOTIFICATIONS_WEBHOOKS: &str = "notifications.webhooks";
         const RPC: &str = "rpc";
         const RPC_AUTH: &str = "rpc.auth";
+        const RPC_RATE_LIMIT: &str = "rpc.rate_limit";
+        const SYNC: &str = "sync";
         fn builder<T: Serialize>(
             f: &'static str,
             d: T,
         ) -> ((&'static str, &'static str), Option<toml::Value>) {
             field(BUILDER, f, d)
         }
+        fn builder_batching<T: Serialize>(
+            f: &'static str,
+            d: T,
+        ) -> ((&'static str, &'static str), Option<toml::Value>) {
+            field(BUILDER_BATCHING, f, d)
+        }
         fn builder_limits<T: Serialize>(
             f: &'static str,
             d: T,
@@ -784,12 +2836,42 @@ impl ZalletConfig {
         ) -> ((&'static str, &'static str), Option<toml::Value>) {
             field(NOTE_MANAGEMENT, f, d)
         }
+        fn logging<T: Serialize>(
+            f: &'static str,
+            d: T,
+        ) -> ((&'static str, &'static str), Option<toml::Value>) {
+            field(LOGGING, f, d)
+        }
+        fn metrics<T: Serialize>(
+            f: &'static str,
+            d: T,
+        ) -> ((&'static str, &'static str), Option<toml::Value>) {
+            field(METRICS, f, d)
+        }
+        fn notifications<T: Serialize>(
+            f: &'static str,
+            d: T,
+        ) -> ((&'static str, &'static str), Option<toml::Value>) {
+            field(NOTIFICATIONS, f, d)
+        }
         fn rpc<T: Serialize>(
             f: &'static str,
             d: T,
         ) -> ((&'static str, &'static str), Option<toml::Value>) {
             field(RPC, f, d)
         }
+        fn rpc_rate_limit<T: Serialize>(
+            f: &'static str,
+            d: T,
+        ) -> ((&'static str, &'static str), Option<toml::Value>) {
+            field(RPC_RATE_LIMIT, f, d)
+        }
+        fn sync<T: Serialize>(
+            f: &'static str,
+            d: T,
+        ) -> ((&'static str, &'static str), Option<toml::Value>) {
+            field(SYNC, f, d)
+        }
         fn field<T: Serialize>(
             s: &'static str,
             f: &'static str,
@@ -877,6 +2959,9 @@ impl ZalletConfig {
             for field_name in T::FIELD_NAMES {
                 match (section_name, *field_name) {
                     // Render nested sections.
+                    (BUILDER, "batching") => {
+                        write_section::<BuilderBatchingSection>(config, BUILDER_BATCHING, sec_def)
+                    }
                     (BUILDER, "limits") => {
                         write_section::<BuilderLimitsSection>(config, BUILDER_LIMITS, sec_def)
                     }
@@ -890,6 +2975,17 @@ impl ZalletConfig {
                         FEATURES_EXPERIMENTAL,
                         sec_def,
                     ),
+                    (RPC, "rate_limit") => {
+                        write_section::<RpcRateLimitSection>(config, RPC_RATE_LIMIT, sec_def)
+                    }
+                    (DATABASE, "wallets") => {
+                        write_list_section::<WalletSection>(config, DATABASE_WALLETS, sec_def)
+                    }
+                    (NOTIFICATIONS, "webhooks") => write_list_section::<WebhookSection>(
+                        config,
+                        NOTIFICATIONS_WEBHOOKS,
+                        sec_def,
+                    ),
                     (RPC, "auth") => {
                         write_list_section::<RpcAuthSection>(config, RPC_AUTH, sec_def)
                     }
@@ -962,7 +3058,13 @@ impl ZalletConfig {
                 NOTE_MANAGEMENT => {
                     write_section::<NoteManagementSection>(&mut config, field_name, &sec_def)
                 }
+                LOGGING => write_section::<LoggingSection>(&mut config, field_name, &sec_def),
+                METRICS => write_section::<MetricsSection>(&mut config, field_name, &sec_def),
+                NOTIFICATIONS => {
+                    write_section::<NotificationsSection>(&mut config, field_name, &sec_def)
+                }
                 RPC => write_section::<RpcSection>(&mut config, field_name, &sec_def),
+                SYNC => write_section::<SyncSection>(&mut config, field_name, &sec_def),
                 // Top-level fields correspond to CLI settings, and cannot be configured
                 // via a file.
                 _ => (),
@@ -972,3 +3074,246 @@ impl ZalletConfig {
         config
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{AllowedIp, RpcBind, Severity, WalletSection, ZalletConfig};
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Rpc {
+        bind: Vec<RpcBind>,
+    }
+
+    #[test]
+    fn rpc_bind_roundtrip() {
+        let rpc: Rpc = toml::from_str(
+            r#"bind = ["127.0.0.1:28232", "[::1]:28232", "/run/zallet/rpc.sock", "./rpc.sock"]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rpc.bind,
+            vec![
+                RpcBind::Tcp("127.0.0.1:28232".parse().unwrap()),
+                RpcBind::Tcp("[::1]:28232".parse().unwrap()),
+                RpcBind::Unix("/run/zallet/rpc.sock".into()),
+                RpcBind::Unix("./rpc.sock".into()),
+            ],
+        );
+
+        let encoded = toml::to_string(&rpc).unwrap();
+        let decoded: Rpc = toml::from_str(&encoded).unwrap();
+        assert_eq!(decoded.bind, rpc.bind);
+
+        for invalid in ["", "127.0.0.1", "localhost:28232", "rpc.sock"] {
+            assert!(
+                toml::from_str::<Rpc>(&format!("bind = [\"{invalid}\"]")).is_err(),
+                "{invalid:?} should be rejected",
+            );
+        }
+    }
+    #[test]
+    fn allowed_ip_parsing() {
+        let allowed = |value: &str| AllowedIp::try_from(value.to_owned());
+
+        for value in ["192.168.1.0/24", "10.1.2.3", "fd00::/8", "::1"] {
+            assert_eq!(String::from(allowed(value).unwrap()), value);
+        }
+        assert_eq!(
+            allowed("10.1.2.3/32").map(String::from),
+            Ok("10.1.2.3".to_owned()),
+        );
+
+        // The offending entry is included in the error.
+        assert_eq!(
+            allowed("10.0.0.0/33"),
+            Err(
+                "Invalid `rpc.allow_ip` entry '10.0.0.0/33': expected an IP address or CIDR range"
+                    .into()
+            ),
+        );
+        for value in ["", "*", "10.0.0", "localhost", "fd00::/129", "10.0.0.0/"] {
+            assert!(allowed(value).is_err(), "{value:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn wallet_names() {
+        for name in ["ops", "cold-2", "wallet_3", "v1.0", "a..b"] {
+            assert!(
+                WalletSection::is_valid_name(name),
+                "{name:?} should be valid"
+            );
+        }
+
+        // Names of wallets created over JSON-RPC are used as directory names, so they
+        // must not escape the `wallets` directory or be hidden.
+        let config = ZalletConfig::default();
+        for name in [
+            "", ".", "..", ".hidden", "a/b", "a\\b", "../a", "a b", "wället",
+        ] {
+            assert!(
+                !WalletSection::is_valid_name(name),
+                "{name:?} should be invalid"
+            );
+            assert!(config.for_created_wallet(name).is_none());
+        }
+
+        let created = config.for_created_wallet("ops").unwrap();
+        assert_eq!(
+            created.database.wallet,
+            Some(
+                std::path::Path::new("wallets")
+                    .join("ops")
+                    .join("wallet.db")
+            )
+        );
+    }
+
+    #[cfg(zallet_build = "wallet")]
+    #[test]
+    fn wallet_overrides() {
+        let mut config = ZalletConfig::default();
+        config.external.notify = Some("notify-all %s".into());
+        config.database = toml::from_str(
+            r#"
+            [[wallets]]
+            name = "ops"
+            wallet = "ops.db"
+
+            [[wallets]]
+            name = "cold"
+            wallet = "cold.db"
+            allow_unlock = false
+            passphrase_file = "cold-passphrase"
+            notify = "notify-cold %s"
+            "#,
+        )
+        .unwrap();
+
+        let ops = config.for_wallet("ops").unwrap();
+        assert_eq!(ops.wallet_name.as_deref(), Some("ops"));
+        assert_eq!(ops.database.wallet, Some("ops.db".into()));
+        assert_eq!(ops.external.notify.as_deref(), Some("notify-all %s"));
+        assert!(ops.keystore.allow_unlock());
+        assert_eq!(ops.keystore.passphrase_file, None);
+
+        let cold = config.for_wallet("cold").unwrap();
+        assert_eq!(cold.database.wallet, Some("cold.db".into()));
+        assert!(cold.database.wallets.is_empty());
+        assert_eq!(cold.external.notify.as_deref(), Some("notify-cold %s"));
+        assert!(!cold.keystore.allow_unlock());
+        assert_eq!(
+            cold.keystore.passphrase_file,
+            Some("cold-passphrase".into())
+        );
+    }
+
+    #[test]
+    fn validate() {
+        let issues = |config: &ZalletConfig| {
+            config
+                .validate()
+                .into_iter()
+                .map(|issue| (issue.severity, issue.option.unwrap()))
+                .collect::<Vec<_>>()
+        };
+
+        let mut config = ZalletConfig::default();
+        assert_eq!(issues(&config), vec![]);
+
+        config.builder.trusted_confirmations = Some(0);
+        config.rpc.timeout = Some(0);
+        config.rpc.bind = vec![
+            RpcBind::Tcp("127.0.0.1:28232".parse().unwrap()),
+            RpcBind::Tcp("127.0.0.1:28232".parse().unwrap()),
+        ];
+        assert_eq!(
+            issues(&config),
+            vec![
+                (Severity::Error, "builder.trusted_confirmations"),
+                (Severity::Error, "rpc.bind"),
+                (Severity::Error, "rpc.timeout"),
+            ],
+        );
+
+        // Untrusted outputs cannot need fewer confirmations than trusted outputs.
+        let mut config = ZalletConfig::default();
+        config.builder.untrusted_confirmations = Some(0);
+        assert_eq!(
+            issues(&config),
+            vec![(Severity::Error, "builder.untrusted_confirmations")],
+        );
+        config.builder.untrusted_confirmations = Some(2);
+        assert_eq!(
+            issues(&config),
+            vec![(Severity::Error, "builder.untrusted_confirmations")],
+        );
+        config.builder.trusted_confirmations = Some(2);
+        assert_eq!(issues(&config), vec![]);
+
+        // Zallet could exit before in-flight requests finish.
+        let mut config = ZalletConfig::default();
+        config.external.force_shutdown_timeout = Some(30);
+        assert_eq!(
+            issues(&config),
+            vec![(Severity::Warning, "external.force_shutdown_timeout")],
+        );
+
+        // Invalid filter directives would otherwise be ignored.
+        let mut config = ZalletConfig::default();
+        config.logging.filter = Some("info,zallet=loud".into());
+        assert_eq!(issues(&config), vec![(Severity::Error, "logging.filter")]);
+        config.logging.filter = Some("warn,zallet::components::json_rpc=debug".into());
+        assert_eq!(issues(&config), vec![]);
+
+        // `~` is not expanded.
+        let mut config = ZalletConfig::default();
+        config.external.pid_file = Some("~/zallet.pid".into());
+        config.rpc.cookie_file = Some("cookie~".into());
+        assert_eq!(
+            issues(&config),
+            vec![(Severity::Error, "external.pid_file")],
+        );
+
+        // Onion addresses need a proxy, which is then always used.
+        let mut config = ZalletConfig::default();
+        config.indexer.validator_address = Some("example.onion:8232".into());
+        config.indexer.fallback_validator_addresses = vec!["127.0.0.1:8232".into()];
+        assert_eq!(
+            issues(&config),
+            vec![(Severity::Error, "indexer.validator_address")],
+        );
+        config.indexer.proxy = Some("127.0.0.1:9050".into());
+        assert_eq!(issues(&config), vec![]);
+        config.indexer.onion_only = Some(true);
+        assert_eq!(
+            issues(&config),
+            vec![(Severity::Error, "indexer.fallback_validator_addresses")],
+        );
+        config.indexer.fallback_validator_addresses = vec![];
+        assert_eq!(issues(&config), vec![]);
+        config.indexer.proxy = Some("9050".into());
+        assert_eq!(issues(&config), vec![(Severity::Error, "indexer.proxy")]);
+        config.indexer.proxy = None;
+        assert_eq!(
+            issues(&config),
+            vec![
+                (Severity::Error, "indexer.onion_only"),
+                (Severity::Error, "indexer.validator_address"),
+            ],
+        );
+
+        // Binding to a public address is risky, but allowed.
+        let mut config = ZalletConfig::default();
+        config.rpc.bind = vec![RpcBind::Tcp("0.0.0.0:28232".parse().unwrap())];
+        assert_eq!(
+            issues(&config),
+            vec![
+                (Severity::Warning, "rpc.bind"),
+                (Severity::Warning, "rpc.allow_ip"),
+            ],
+        );
+    }
+}
@@ -36,6 +36,8 @@ pub(crate) enum ErrorKind {
     #[cfg(feature = "rpc-cli")]
     RpcCli(RpcCliError),
     Sync,
+    /// The keystore's identities must be unlocked before key material can be decrypted.
+    WalletLocked,
 }
 
 impl fmt::Display for ErrorKind {
@@ -46,6 +48,7 @@ impl fmt::Display for ErrorKind {
             #[cfg(feature = "rpc-cli")]
             ErrorKind::RpcCli(e) => e.fmt(f),
             ErrorKind::Sync => wfl!(f, "err-kind-sync"),
+            ErrorKind::WalletLocked => wfl!(f, "err-wallet-locked"),
         }
     }
 }
@@ -59,21 +62,79 @@ impl ErrorKind {
     }
 }
 
+/// The exit codes of `zallet start` and other commands.
+///
+/// These let init scripts and service managers tell why Zallet failed to start. They are
+/// part of Zallet's stable interface (and are listed in `zallet start --help`, or the
+/// `--help` of the commands that use them), so existing codes must never be changed or
+/// reused.
+///
+/// Exit code 2 is not used, because it is the exit code for invalid command-line usage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ExitCode {
+    /// A failure without a more specific exit code.
+    Failure = 1,
+    /// The config file could not be read or parsed, or contained invalid settings.
+    Config = 3,
+    /// A wallet database could not be opened.
+    Database = 4,
+    /// A wallet's keystore could not be opened, or does not satisfy the config.
+    KeyStore = 5,
+    /// The JSON-RPC server could not listen on `rpc.bind`.
+    RpcBind = 6,
+    /// The chain indexer could not connect to its backend.
+    Backend = 7,
+    /// The wallet passphrase given to a command was incorrect.
+    WrongPassphrase = 8,
+}
+
+impl ExitCode {
+    /// Returns the exit code as used by [`std::process::exit`].
+    pub(crate) fn code(self) -> i32 {
+        self as i32
+    }
+}
+
 /// Error type
 #[derive(Debug)]
-pub(crate) struct Error(Box<Context<ErrorKind>>);
+pub(crate) struct Error {
+    inner: Box<Context<ErrorKind>>,
+    exit_code: ExitCode,
+}
 
 impl Deref for Error {
     type Target = Context<ErrorKind>;
 
     fn deref(&self) -> &Context<ErrorKind> {
-        &self.0
+        &self.inner
+    }
+}
+
+impl Error {
+    /// Sets the exit code that Zallet will use if this error causes it to exit.
+    pub(crate) fn with_exit_code(mut self, exit_code: ExitCode) -> Self {
+        self.exit_code = exit_code;
+        self
+    }
+
+    /// Returns the exit code that Zallet uses if this error causes it to exit.
+    pub(crate) fn exit_code(&self) -> ExitCode {
+        self.exit_code
+    }
+
+    /// Returns a description of this error, without the guidance on reporting bugs that
+    /// is included when it is displayed.
+    pub(crate) fn message(&self) -> String {
+        match std::error::Error::source(&*self.inner) {
+            Some(source) => source.to_string(),
+            None => self.inner.kind().to_string(),
+        }
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{}", self.0)?;
+        writeln!(f, "{}", self.inner)?;
         writeln!(f)?;
         writeln!(f, "[ {} ]", crate::fl!("err-ux-A"))?;
         write!(
@@ -87,7 +148,7 @@ impl fmt::Display for Error {
 
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        self.0.source()
+        self.inner.source()
     }
 }
 
@@ -99,7 +160,10 @@ impl From<ErrorKind> for Error {
 
 impl From<Context<ErrorKind>> for Error {
     fn from(context: Context<ErrorKind>) -> Self {
-        Error(Box::new(context))
+        Error {
+            inner: Box::new(context),
+            exit_code: ExitCode::Failure,
+        }
     }
 }
 
@@ -115,3 +179,46 @@ impl From<RpcCliError> for Error {
         ErrorKind::RpcCli(e).into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ExitCode;
+
+    #[test]
+    fn exit_codes_are_stable() {
+        // These are relied upon by init scripts; do not change them.
+        assert_eq!(ExitCode::Failure.code(), 1);
+        assert_eq!(ExitCode::Config.code(), 3);
+        assert_eq!(ExitCode::Database.code(), 4);
+        assert_eq!(ExitCode::KeyStore.code(), 5);
+        assert_eq!(ExitCode::RpcBind.code(), 6);
+        assert_eq!(ExitCode::Backend.code(), 7);
+        assert_eq!(ExitCode::WrongPassphrase.code(), 8);
+    }
+
+    #[test]
+    fn exit_codes_are_documented() {
+        for code in [
+            ExitCode::Failure,
+            ExitCode::Config,
+            ExitCode::Database,
+            ExitCode::KeyStore,
+            ExitCode::RpcBind,
+            ExitCode::Backend,
+        ] {
+            assert!(
+                crate::cli::START_EXIT_CODES.contains(&format!("\n  {}  ", code.code())),
+                "{code:?} is missing from `zallet start --help`",
+            );
+        }
+
+        #[cfg(zallet_build = "wallet")]
+        for code in [ExitCode::Failure, ExitCode::WrongPassphrase] {
+            assert!(
+                crate::cli::CHANGE_WALLET_ENCRYPTION_EXIT_CODES
+                    .contains(&format!("\n  {}  ", code.code())),
+                "{code:?} is missing from `zallet change-wallet-encryption --help`",
+            );
+        }
+    }
+}
@@ -4,7 +4,7 @@
 //!
 //! [Abscissa]: https://github.com/iqlusioninc/abscissa
 
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 #![deny(rustdoc::broken_intra_doc_links)]
 #![warn(
     missing_docs,
@@ -24,12 +24,20 @@ pub mod config;
 mod error;
 mod i18n;
 pub mod network;
+mod pid_file;
 mod prelude;
+mod shutdown;
 mod task;
 
 #[cfg(feature = "zcashd-import")]
 mod rosetta;
 
+#[cfg(all(zallet_build = "wallet", feature = "zcashd-import"))]
+mod sprout;
+
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+mod systemd;
+
 // Needed for the `Component` derive to work.
 use abscissa_core::{Application, Version, component};
 
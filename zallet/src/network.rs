@@ -150,7 +150,7 @@ pub(crate) mod kind {
     use serde::{Deserializer, Serializer, de::Visitor};
     use zcash_protocol::consensus::NetworkType;
 
-    fn str_to_type(s: &str) -> Option<NetworkType> {
+    pub(crate) fn str_to_type(s: &str) -> Option<NetworkType> {
         match s {
             "main" => Some(NetworkType::Main),
             "test" => Some(NetworkType::Test),
@@ -0,0 +1,97 @@
+//! The PID file of `zallet start`.
+//!
+//! `zallet start` records its process ID in a PID file (`external.pid_file`), so that
+//! init scripts, `zallet stop`, and `zallet status` can find the running process. The
+//! file is removed when Zallet shuts down cleanly.
+//!
+//! Zallet holds the datadir lock for as long as it runs, so a PID file that exists while
+//! the datadir is unlocked is stale: it was left behind by a Zallet process that did not
+//! shut down cleanly.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    config::ZalletConfig,
+    error::{Error, ErrorKind},
+    prelude::*,
+};
+
+/// The PID file of the current process.
+///
+/// The file is removed when this is dropped.
+pub(crate) struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Records the ID of the current process in the configured PID file.
+    ///
+    /// This must only be called while holding the datadir lock, as any existing PID file
+    /// is treated as stale and replaced.
+    pub(crate) fn create(config: &ZalletConfig) -> Result<Self, Error> {
+        let path = config.pid_file_path();
+
+        if let Some(pid) = read(&path)? {
+            warn!(
+                "Replacing stale PID file {} left by process {pid}, which did not shut down cleanly",
+                path.display(),
+            );
+        }
+
+        fs::write(&path, format!("{}\n", std::process::id())).map_err(|e| {
+            ErrorKind::Init.context(format!("Failed to write PID file {}: {e}", path.display()))
+        })?;
+
+        Ok(Self { path })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove PID file {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Returns the process ID recorded in the PID file at `path`, or `None` if there is no
+/// PID file.
+pub(crate) fn read(path: &Path) -> Result<Option<u32>, Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) => contents.trim().parse().map(Some).map_err(|_| {
+            ErrorKind::Generic
+                .context(format!("PID file {} is malformed", path.display()))
+                .into()
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(ErrorKind::Generic
+            .context(format!("Failed to read PID file {}: {e}", path.display()))
+            .into()),
+    }
+}
+
+/// Whether Zallet is running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Status {
+    /// Zallet is running, with the process ID from its PID file (if any).
+    Running(Option<u32>),
+    /// Zallet is not running, but left behind a PID file with the given process ID.
+    Stale(u32),
+    /// Zallet is not running.
+    Stopped,
+}
+
+/// Returns whether Zallet is running with the given config.
+pub(crate) fn status(config: &ZalletConfig) -> Result<Status, Error> {
+    let pid = read(&config.pid_file_path())?;
+    Ok(if config.datadir_locked()? {
+        Status::Running(pid)
+    } else {
+        match pid {
+            Some(pid) => Status::Stale(pid),
+            None => Status::Stopped,
+        }
+    })
+}
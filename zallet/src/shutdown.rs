@@ -0,0 +1,66 @@
+//! Coordination of a graceful shutdown.
+//!
+//! When Zallet is asked to stop, long-running tasks are signalled through this module
+//! instead of being cancelled, so that they can finish (or stop at a safe point) before
+//! the process exits.
+
+use std::sync::LazyLock;
+
+use tokio::sync::watch;
+
+static SHUTDOWN: LazyLock<watch::Sender<bool>> = LazyLock::new(|| watch::Sender::new(false));
+
+/// Requests that Zallet shut down gracefully.
+pub(crate) fn request() {
+    SHUTDOWN.send_replace(true);
+}
+
+/// Returns `true` if a graceful shutdown has been requested.
+pub(crate) fn is_requested() -> bool {
+    *SHUTDOWN.borrow()
+}
+
+/// Waits until a graceful shutdown is requested.
+///
+/// Returns immediately if one has already been requested.
+pub(crate) async fn requested() {
+    let mut rx = SHUTDOWN.subscribe();
+    // The sender is never dropped, so this cannot fail.
+    let _ = rx.wait_for(|requested| *requested).await;
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    //! A shutdown request applies to the whole process, so tests that request one must
+    //! not run alongside tests that it would disrupt.
+
+    use std::sync::LazyLock;
+
+    use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+    static REQUESTS: LazyLock<RwLock<()>> = LazyLock::new(|| RwLock::new(()));
+
+    /// Prevents other tests from requesting a shutdown until the returned guard is
+    /// dropped.
+    pub(crate) async fn without_shutdown() -> RwLockReadGuard<'static, ()> {
+        REQUESTS.read().await
+    }
+
+    /// Allows the caller to request a shutdown, which is withdrawn once the returned
+    /// guard is dropped.
+    pub(crate) async fn allow_shutdown() -> ShutdownGuard {
+        ShutdownGuard {
+            _requests: REQUESTS.write().await,
+        }
+    }
+
+    pub(crate) struct ShutdownGuard {
+        _requests: RwLockWriteGuard<'static, ()>,
+    }
+
+    impl Drop for ShutdownGuard {
+        fn drop(&mut self) {
+            super::SHUTDOWN.send_replace(false);
+        }
+    }
+}
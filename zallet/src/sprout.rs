@@ -0,0 +1,439 @@
+//! Detection of the Sprout notes held by `zcashd` wallets.
+//!
+//! Zallet cannot spend Sprout notes, but a `zcashd` wallet may still hold some. So that
+//! their value does not disappear from view, `zallet migrate-zcashd-wallet` keeps the
+//! wallet's Sprout spending keys for long enough to trial-decrypt the Sprout outputs of
+//! the wallet's transactions, and records the notes it finds (see
+//! `components::database::sprout`).
+//!
+//! `zcash_primitives` does not expose the contents of JoinSplit descriptions
+//! (<https://github.com/zcash/librustzcash/issues/1943>), so they are parsed here from
+//! the raw transaction encoding.
+
+use std::io::{self, Read};
+
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce, aead::Aead};
+use sha2::digest::generic_array::GenericArray;
+use zcash_encoding::CompactSize;
+use zcash_protocol::{TxId, value::Zatoshis};
+
+use crate::components::database::SproutNote;
+
+/// The size of a Sprout note ciphertext.
+const CIPHERTEXT_SIZE: usize = 601;
+
+/// The size of a Sprout note plaintext.
+const NOTE_PLAINTEXT_SIZE: usize = 585;
+
+/// The initial state of SHA-256.
+const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// `SHA256Compress` applied to a single 512-bit block.
+fn sha256_compress(block: &[u8; 64]) -> [u8; 32] {
+    let mut state = SHA256_IV;
+    sha2::compress256(&mut state, &[*GenericArray::from_slice(block)]);
+
+    let mut out = [0; 32];
+    for (chunk, word) in out.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
+
+/// The Sprout PRFs, keyed by the spending key `a_sk`, with the given 4-bit domain
+/// separation prefix.
+fn prf(prefix: u8, a_sk: &[u8; 32], input: &[u8; 32]) -> [u8; 32] {
+    let mut block = [0; 64];
+    block[..32].copy_from_slice(a_sk);
+    block[0] |= prefix << 4;
+    block[32..].copy_from_slice(input);
+    sha256_compress(&block)
+}
+
+/// A Sprout spending key, along with the keys derived from it that are needed to
+/// decrypt notes.
+pub(crate) struct SpendingKey {
+    a_sk: [u8; 32],
+    sk_enc: [u8; 32],
+    pk_enc: [u8; 32],
+}
+
+impl SpendingKey {
+    /// Parses a Sprout spending key, returning `None` if it is not a 252-bit value.
+    pub(crate) fn from_bytes(a_sk: [u8; 32]) -> Option<Self> {
+        (a_sk[0] & 0xf0 == 0).then(|| {
+            // sk_enc = Clamp(PRF^addr_{a_sk}(1)). `x25519` clamps its scalar argument, so
+            // we don't need to do so here.
+            let mut t = [0; 32];
+            t[0] = 1;
+            let sk_enc = prf(0b1100, &a_sk, &t);
+            let pk_enc = x25519_dalek::x25519(sk_enc, x25519_dalek::X25519_BASEPOINT_BYTES);
+            Self {
+                a_sk,
+                sk_enc,
+                pk_enc,
+            }
+        })
+    }
+
+    /// Derives the nullifier of the note with the given `rho` received by this key.
+    fn nullifier(&self, rho: &[u8; 32]) -> [u8; 32] {
+        prf(0b1110, &self.a_sk, rho)
+    }
+
+    /// Trial-decrypts the note ciphertext at `index` within a JoinSplit description.
+    ///
+    /// Returns the value and `rho` of the note if it was sent to this key.
+    fn decrypt(
+        &self,
+        h_sig: &[u8; 32],
+        epk: &[u8; 32],
+        index: u8,
+        ciphertext: &[u8; CIPHERTEXT_SIZE],
+    ) -> Option<(Zatoshis, [u8; 32])> {
+        let shared_secret = x25519_dalek::x25519(self.sk_enc, *epk);
+
+        let mut personal = [0; 16];
+        personal[..8].copy_from_slice(b"ZcashKDF");
+        personal[8] = index;
+        let key = blake2b_simd::Params::new()
+            .hash_length(32)
+            .personal(&personal)
+            .to_state()
+            .update(h_sig)
+            .update(&shared_secret)
+            .update(epk)
+            .update(&self.pk_enc)
+            .finalize();
+
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(key.as_bytes()))
+            .decrypt(&Nonce::default(), &ciphertext[..])
+            .ok()?;
+
+        // The plaintext is the lead byte, the value, rho, rcm, and the memo.
+        if plaintext.len() != NOTE_PLAINTEXT_SIZE || plaintext[0] != 0 {
+            return None;
+        }
+        let value = Zatoshis::from_u64(u64::from_le_bytes(
+            plaintext[1..9].try_into().expect("correct length"),
+        ))
+        .ok()?;
+        let rho = plaintext[9..41].try_into().expect("correct length");
+        Some((value, rho))
+    }
+}
+
+/// The fields of a JoinSplit description that are needed to find and track Sprout notes.
+struct JoinSplit {
+    nullifiers: [[u8; 32]; 2],
+    epk: [u8; 32],
+    random_seed: [u8; 32],
+    ciphertexts: [[u8; CIPHERTEXT_SIZE]; 2],
+}
+
+fn read_array<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn skip(r: &mut impl Read, len: u64) -> io::Result<()> {
+    if io::copy(&mut r.by_ref().take(len), &mut io::sink())? == len {
+        Ok(())
+    } else {
+        Err(io::ErrorKind::UnexpectedEof.into())
+    }
+}
+
+/// Parses the JoinSplit descriptions of a transaction, along with its JoinSplit public
+/// key.
+fn join_splits(mut raw_tx: &[u8]) -> io::Result<(Vec<JoinSplit>, [u8; 32])> {
+    let r = &mut raw_tx;
+
+    let header = u32::from_le_bytes(read_array(r)?);
+    let overwintered = header >> 31 == 1;
+    let version = header & 0x7fff_ffff;
+    // Sprout was introduced in v2 transactions, and removed in v5 transactions.
+    if !(2..5).contains(&version) {
+        return Ok((vec![], [0; 32]));
+    }
+    if overwintered {
+        skip(r, 4)?; // nVersionGroupId
+    }
+
+    for _ in 0..CompactSize::read(&mut *r)? {
+        skip(r, 36)?; // prevout
+        let script_len = CompactSize::read(&mut *r)?;
+        skip(r, script_len.saturating_add(4))?; // scriptSig, nSequence
+    }
+    for _ in 0..CompactSize::read(&mut *r)? {
+        skip(r, 8)?; // value
+        let script_len = CompactSize::read(&mut *r)?;
+        skip(r, script_len)?; // scriptPubKey
+    }
+    skip(r, 4)?; // nLockTime
+    if overwintered {
+        skip(r, 4)?; // nExpiryHeight
+    }
+
+    // v4 transactions have Sapling fields, and use Groth16 proofs for JoinSplits.
+    let sapling = overwintered && version >= 4;
+    if sapling {
+        skip(r, 8)?; // valueBalanceSapling
+        let spends = CompactSize::read(&mut *r)?;
+        skip(r, spends.saturating_mul(384))?;
+        let outputs = CompactSize::read(&mut *r)?;
+        skip(r, outputs.saturating_mul(948))?;
+    }
+
+    let count = CompactSize::read(&mut *r)?;
+    let mut descriptions = vec![];
+    for _ in 0..count {
+        skip(r, 8 + 8 + 32)?; // vpub_old, vpub_new, anchor
+        let nullifiers = [read_array(r)?, read_array(r)?];
+        skip(r, 64)?; // commitments
+        let epk = read_array(r)?;
+        let random_seed = read_array(r)?;
+        skip(r, 64 + if sapling { 192 } else { 296 })?; // vmacs, proof
+        let ciphertexts = [read_array(r)?, read_array(r)?];
+        descriptions.push(JoinSplit {
+            nullifiers,
+            epk,
+            random_seed,
+            ciphertexts,
+        });
+    }
+    let pub_key = if count > 0 { read_array(r)? } else { [0; 32] };
+
+    Ok((descriptions, pub_key))
+}
+
+/// Returns the Sprout nullifiers revealed by a transaction.
+pub(crate) fn nullifiers(raw_tx: &[u8]) -> io::Result<Vec<[u8; 32]>> {
+    let (descriptions, _) = join_splits(raw_tx)?;
+    Ok(descriptions
+        .into_iter()
+        .flat_map(|js| js.nullifiers)
+        .collect())
+}
+
+/// Returns the Sprout notes that a transaction sent to any of the given keys.
+pub(crate) fn received_notes(
+    txid: TxId,
+    raw_tx: &[u8],
+    keys: &[SpendingKey],
+) -> io::Result<Vec<SproutNote>> {
+    let (descriptions, pub_key) = join_splits(raw_tx)?;
+
+    let mut notes = vec![];
+    for (js_index, js) in descriptions.iter().enumerate() {
+        let h_sig = blake2b_simd::Params::new()
+            .hash_length(32)
+            .personal(b"ZcashComputehSig")
+            .to_state()
+            .update(&js.random_seed)
+            .update(&js.nullifiers[0])
+            .update(&js.nullifiers[1])
+            .update(&pub_key)
+            .finalize();
+        let h_sig = h_sig.as_bytes().try_into().expect("correct length");
+
+        for (note_index, ciphertext) in (0..).zip(&js.ciphertexts) {
+            if let Some((key, (value, rho))) = keys.iter().find_map(|key| {
+                key.decrypt(h_sig, &js.epk, note_index, ciphertext)
+                    .map(|note| (key, note))
+            }) {
+                notes.push(SproutNote {
+                    txid,
+                    js_index: u32::try_from(js_index).expect("bounded by transaction size"),
+                    note_index,
+                    value,
+                    nullifier: key.nullifier(&rho),
+                });
+            }
+        }
+    }
+
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::{TxId, value::Zatoshis};
+
+    use super::{CIPHERTEXT_SIZE, SpendingKey, join_splits, nullifiers, received_notes};
+
+    // These vectors were computed with an independent implementation of the Sprout
+    // key derivation and in-band note encryption in the Zcash protocol specification
+    // (§§ 4.2.1, 4.17, 5.4.2).
+
+    const A_SK: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+    const PK_ENC: &str = "96385d344d169d2082a3a73e47be10b9c08c14241513a895e1a4bc2f2d4b0808";
+    /// The nullifier of the note with `rho = [0x11; 32]` received by `A_SK`.
+    const NULLIFIER: &str = "1312d1232c1ac878f8ba373dd2590d9ef4a668b9113d7017cc61cae36708edb4";
+
+    // The fields of a JoinSplit description that sends a note of 123456789 zatoshis
+    // with `rho = [0x11; 32]` to `A_SK` as its second output, using `esk = [0x42; 32]`.
+    const RANDOM_SEED: [u8; 32] = [0x33; 32];
+    const NULLIFIERS: [[u8; 32]; 2] = [[0x44; 32], [0x55; 32]];
+    const JOINSPLIT_PUB_KEY: [u8; 32] = [0x66; 32];
+    const H_SIG: &str = "26b691187722d34949769fcf1da63a48e0ab6b83fbb5e46a1380864d2eb744c4";
+    const EPK: &str = "132c442be010fbd57e72603328aa76e71fccc1503aae219327d14d9c9993f472";
+    const CIPHERTEXT: &str = concat!(
+        "b402436aa09ad7336c9f9cc49ad7c0888abd33f506e78db9f79b3268592b073c9ad47b79ed12e0c5df6341cf",
+        "5fdfb52cdc716ad16e12aa66e6eeb6ffdf704aed647a8b8ab544e2cf7d04ce27b23aad2c6c46a19c942c0bc6",
+        "a51a0fcae938f57918b4eef183465e0160d5c3d5ab1be1080a02053a35d49503382984b4f99650e3c990e42e",
+        "dfc7c6d229ed4cd5ac54ea3ec47525f6141aea8e74bb2cb4049ced062354a4f803f19d4e61697a0dfafce4b7",
+        "ccca5bf2ea2fae6663d7b26b15f0f08e2e5a60416559d80052e2d2b67b9115118c7c74223b7756ec23a47143",
+        "6397171394a51f3ce7d15a210f1d2e16d2a237799690fa60764b43c67c177c0502881b1e2da2363e6fe31042",
+        "097d294384fad215b9d68ff138b6dc54fe6259a981e8427a5ed945e20dfc5f05e750dda644b954a18c7da0ce",
+        "7c2ad976fb31eeff32eb93345a231d3432b738437d421c24a5b80d43f44f25a6c810cedb2933d795d2a60e5d",
+        "7c9da5c3a667d97172568a0334273d72c85a9f0c76e147a6d3592bdf9ee15fd4e6544a40ecbc4ac19570bb39",
+        "acb9cac4da5524b5b0ec23ac6869b106780e17cec9290aa55f727885fa53b0bf9ef06b6ecedfd65fc35bce2d",
+        "a1365685f124086c0649fdc21fbf5802e84b7290a3618b38a050f8e08b9aa979a8b11bc86827b31a57a3756f",
+        "a47b7545dd0201b703b9d8e52c06ec45b01ce12e77ae50d08c3d8d6dcd584c887b7439cb5ed06181ae7233fc",
+        "76be37ebb239cb337bda7b4473e96676e53224f3dbda0916bd55a971b940b45f3de53fe8aa0ec548fbbfb46f",
+        "9a3eb12a77bbf9ef427414cf793646167d772932ce11096e7698236874",
+    );
+
+    fn decode<const N: usize>(s: &str) -> [u8; N] {
+        hex::decode(s).unwrap().try_into().unwrap()
+    }
+
+    fn key() -> SpendingKey {
+        SpendingKey::from_bytes(decode(A_SK)).unwrap()
+    }
+
+    /// Encodes a transaction with a transparent input and output, and the JoinSplit
+    /// description above.
+    fn transaction(header: u32, version_group_id: Option<u32>) -> Vec<u8> {
+        let sapling = version_group_id.is_some() && header & 0x7fff_ffff >= 4;
+
+        let mut tx = header.to_le_bytes().to_vec();
+        if let Some(version_group_id) = version_group_id {
+            tx.extend(version_group_id.to_le_bytes());
+        }
+        // One transparent input, with a 3-byte scriptSig.
+        tx.push(1);
+        tx.extend([0xaa; 36]);
+        tx.push(3);
+        tx.extend([0xbb; 3]);
+        tx.extend(u32::MAX.to_le_bytes());
+        // One transparent output, with a 25-byte scriptPubKey.
+        tx.push(1);
+        tx.extend(5000u64.to_le_bytes());
+        tx.push(25);
+        tx.extend([0xcc; 25]);
+        tx.extend(0u32.to_le_bytes()); // nLockTime
+        if version_group_id.is_some() {
+            tx.extend(0u32.to_le_bytes()); // nExpiryHeight
+        }
+        if sapling {
+            tx.extend(0i64.to_le_bytes()); // valueBalanceSapling
+            tx.push(1);
+            tx.extend([0xdd; 384]);
+            tx.push(1);
+            tx.extend([0xee; 948]);
+        }
+
+        // One JoinSplit description.
+        tx.push(1);
+        tx.extend(0u64.to_le_bytes()); // vpub_old
+        tx.extend(5000u64.to_le_bytes()); // vpub_new
+        tx.extend([0x01; 32]); // anchor
+        tx.extend(NULLIFIERS.concat());
+        tx.extend([0x02; 64]); // commitments
+        tx.extend(decode::<32>(EPK));
+        tx.extend(RANDOM_SEED);
+        tx.extend([0x03; 64]); // vmacs
+        tx.extend(vec![0x04; if sapling { 192 } else { 296 }]); // proof
+        tx.extend([0x05; CIPHERTEXT_SIZE]); // A note sent to some other key.
+        tx.extend(decode::<CIPHERTEXT_SIZE>(CIPHERTEXT));
+        tx.extend(JOINSPLIT_PUB_KEY);
+        tx.extend([0x06; 64]); // joinSplitSig
+        if sapling {
+            tx.extend([0x07; 64]); // bindingSigSapling
+        }
+        tx
+    }
+
+    #[test]
+    fn key_derivation() {
+        assert_eq!(key().pk_enc, decode(PK_ENC));
+        assert_eq!(key().nullifier(&[0x11; 32]), decode(NULLIFIER));
+
+        // Spending keys are 252-bit values.
+        let mut a_sk = decode::<32>(A_SK);
+        a_sk[0] |= 0x10;
+        assert!(SpendingKey::from_bytes(a_sk).is_none());
+    }
+
+    #[test]
+    fn note_decryption() {
+        let h_sig = decode(H_SIG);
+        let epk = decode(EPK);
+        let ciphertext = decode(CIPHERTEXT);
+
+        let (value, rho) = key().decrypt(&h_sig, &epk, 1, &ciphertext).unwrap();
+        assert_eq!(value, Zatoshis::const_from_u64(123456789));
+        assert_eq!(rho, [0x11; 32]);
+
+        // The note index, hSig and the recipient are all bound into the key.
+        assert!(key().decrypt(&h_sig, &epk, 0, &ciphertext).is_none());
+        assert!(key().decrypt(&[0; 32], &epk, 1, &ciphertext).is_none());
+        let other = SpendingKey::from_bytes([0x01; 32]).unwrap();
+        assert!(other.decrypt(&h_sig, &epk, 1, &ciphertext).is_none());
+    }
+
+    #[test]
+    fn join_split_parsing() {
+        let txid = TxId::from_bytes([0x99; 32]);
+
+        for (header, version_group_id) in [
+            (2, None),
+            (0x8000_0003, Some(0x03c4_8270)),
+            (0x8000_0004, Some(0x892f_2085)),
+        ] {
+            let tx = transaction(header, version_group_id);
+
+            let (descriptions, pub_key) = join_splits(&tx).unwrap();
+            assert_eq!(descriptions.len(), 1);
+            assert_eq!(descriptions[0].epk, decode(EPK));
+            assert_eq!(pub_key, JOINSPLIT_PUB_KEY);
+            assert_eq!(nullifiers(&tx).unwrap(), NULLIFIERS);
+
+            let other = SpendingKey::from_bytes([0x01; 32]).unwrap();
+            let notes = received_notes(txid, &tx, &[other, key()]).unwrap();
+            assert_eq!(notes.len(), 1);
+            assert_eq!(notes[0].txid, txid);
+            assert_eq!(notes[0].js_index, 0);
+            assert_eq!(notes[0].note_index, 1);
+            assert_eq!(notes[0].value, Zatoshis::const_from_u64(123456789));
+            assert_eq!(notes[0].nullifier, decode(NULLIFIER));
+
+            // A truncated transaction is an error rather than a missing note.
+            assert!(join_splits(&tx[..tx.len() - 100]).is_err());
+        }
+    }
+
+    #[test]
+    fn transactions_without_join_splits() {
+        // v1 transactions predate Sprout.
+        let mut v1 = transaction(2, None);
+        v1[0] = 1;
+        assert!(nullifiers(&v1).unwrap().is_empty());
+
+        // A v4 transaction with no JoinSplits has no JoinSplit public key.
+        let mut tx = 0x8000_0004u32.to_le_bytes().to_vec();
+        tx.extend(0x892f_2085u32.to_le_bytes());
+        tx.extend([0; 2]); // No transparent inputs or outputs.
+        tx.extend([0; 8]); // nLockTime, nExpiryHeight
+        tx.extend([0; 8]); // valueBalanceSapling
+        tx.extend([0; 3]); // No Sapling spends, outputs or JoinSplits.
+        let (descriptions, pub_key) = join_splits(&tx).unwrap();
+        assert!(descriptions.is_empty());
+        assert_eq!(pub_key, [0; 32]);
+    }
+}
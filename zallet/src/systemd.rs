@@ -0,0 +1,131 @@
+//! Integration with systemd's service notification protocol.
+//!
+//! When `zallet start` runs as a systemd service with `Type=notify`, systemd passes it the
+//! address of a notification socket in `$NOTIFY_SOCKET`. Zallet uses it to report when
+//! it is ready and when it is stopping, to show its sync progress in `systemctl status`,
+//! and (if `WatchdogSec=` is set) to pet the service watchdog.
+//!
+//! Outside of systemd, `$NOTIFY_SOCKET` is unset and none of this is used.
+
+use std::env;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+use crate::{components::wallets::Wallets, prelude::*};
+
+/// How often to update the service status when the watchdog is not enabled.
+const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A connection to systemd's notification socket.
+pub(crate) struct Notifier {
+    socket: UnixDatagram,
+    addr: SocketAddr,
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    /// Connects to the notification socket, or returns `None` if Zallet was not started
+    /// by systemd with notification support.
+    pub(crate) fn from_env() -> Option<Self> {
+        let path = env::var_os("NOTIFY_SOCKET")?;
+        let addr = match path.as_bytes() {
+            [b'@', name @ ..] => SocketAddr::from_abstract_name(name),
+            _ => SocketAddr::from_pathname(&path),
+        };
+        let socket = addr.and_then(|addr| {
+            let socket = UnixDatagram::unbound()?;
+            // Never block Zallet on systemd.
+            socket.set_nonblocking(true)?;
+            Ok((socket, addr))
+        });
+
+        match socket {
+            Ok((socket, addr)) => Some(Self {
+                socket,
+                addr,
+                watchdog: watchdog_timeout(),
+            }),
+            Err(e) => {
+                warn!("Failed to connect to the systemd notification socket: {e}");
+                None
+            }
+        }
+    }
+
+    /// Returns how often [`Notifier::tick`] should be called.
+    ///
+    /// This is half of the watchdog timeout if the watchdog is enabled, as recommended
+    /// by `sd_watchdog_enabled(3)`.
+    pub(crate) fn tick_interval(&self) -> Duration {
+        self.watchdog.map_or(STATUS_INTERVAL, |timeout| {
+            (timeout / 2).min(STATUS_INTERVAL)
+        })
+    }
+
+    /// Reports that Zallet has finished starting up.
+    pub(crate) fn ready(&self, wallets: &Wallets) {
+        self.notify(&format!("READY=1\nSTATUS={}", status(wallets)));
+    }
+
+    /// Pets the watchdog (if it is enabled), and updates the service status.
+    pub(crate) fn tick(&self, wallets: &Wallets) {
+        let status = status(wallets);
+        if self.watchdog.is_some() {
+            self.notify(&format!("WATCHDOG=1\nSTATUS={status}"));
+        } else {
+            self.notify(&format!("STATUS={status}"));
+        }
+    }
+
+    /// Reports that Zallet has begun shutting down.
+    pub(crate) fn stopping(&self) {
+        self.notify("STOPPING=1\nSTATUS=Shutting down");
+    }
+
+    fn notify(&self, state: &str) {
+        if let Err(e) = self.socket.send_to_addr(state.as_bytes(), &self.addr) {
+            warn!("Failed to notify systemd: {e}");
+        }
+    }
+}
+
+/// Returns the watchdog timeout that systemd has configured for this process, if any.
+fn watchdog_timeout() -> Option<Duration> {
+    // The watchdog applies to a specific process, if `$WATCHDOG_PID` is set.
+    let applies_to_us = env::var_os("WATCHDOG_PID").is_none_or(|pid| {
+        pid.to_str().and_then(|pid| pid.parse().ok()) == Some(std::process::id())
+    });
+    if !applies_to_us {
+        return None;
+    }
+
+    env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|usec| usec.parse().ok())
+        .filter(|&usec| usec > 0)
+        .map(Duration::from_micros)
+}
+
+/// Describes the sync progress of the loaded wallets, for `systemctl status`.
+fn status(wallets: &Wallets) -> String {
+    let describe =
+        |wallet: &crate::components::wallets::LoadedWallet| match wallet.sync_status.progress() {
+            Some(progress) => match progress.fully_scanned {
+                Some(height) => format!("scanned to {height} of {}", progress.chain_tip),
+                None => format!("scanning, chain tip {}", progress.chain_tip),
+            },
+            None => "starting sync".into(),
+        };
+
+    match wallets.loaded().as_slice() {
+        [] => "No wallets loaded".into(),
+        [wallet] if wallet.name.is_none() => format!("Wallet {}", describe(wallet)),
+        loaded => loaded
+            .iter()
+            .map(|wallet| format!("{}: {}", wallet.name(), describe(wallet)))
+            .collect::<Vec<_>>()
+            .join("; "),
+    }
+}
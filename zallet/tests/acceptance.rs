@@ -42,7 +42,12 @@ fn start_no_args() {
         .arg(datadir.path())
         .arg("start")
         .run();
-    cmd.wait().unwrap().expect_code(1);
+    // The wallet build fails to find an encryption identity; the other build fails to
+    // connect to a backend.
+    #[cfg(zallet_build = "wallet")]
+    cmd.wait().unwrap().expect_code(5);
+    #[cfg(not(zallet_build = "wallet"))]
+    cmd.wait().unwrap().expect_code(7);
 }
 
 /// Example of a test which matches a regular expression
@@ -127,8 +132,9 @@ fn setup_new_wallet() {
             .arg(datadir.path())
             .arg("start")
             .run();
-        // We added invalid data to the config in order to ensure that `start` fails.
-        cmd.wait().unwrap().expect_code(1);
+        // We added invalid data to the config in order to ensure that `start` fails to
+        // connect to a backend.
+        cmd.wait().unwrap().expect_code(7);
     }
 }
 